@@ -1,10 +1,20 @@
 from . import data_loaders
+from .functions_sdk.checkpoints import load_checkpoint, save_checkpoint
 from .functions_sdk.graph import Graph
+from .functions_sdk.graph_definition import (
+    GpuRequirement,
+    GraphParameter,
+    GraphSLA,
+    OutputLimits,
+    RuntimeDescriptor,
+)
 from .functions_sdk.image import Image
 from .functions_sdk.indexify_functions import (
     indexify_function,
     indexify_router,
 )
+from .functions_sdk.invocation_state import invocation_state
+from .functions_sdk.parameters import get_parameter
 from .functions_sdk.pipeline import Pipeline
 from .http_client import IndexifyClient
 from .remote_graph import RemoteGraph
@@ -14,12 +24,21 @@ from .settings import DEFAULT_SERVICE_URL
 __all__ = [
     "data_loaders",
     "Graph",
+    "GpuRequirement",
+    "GraphParameter",
+    "GraphSLA",
+    "OutputLimits",
+    "RuntimeDescriptor",
     "RemoteGraph",
     "Pipeline",
     "RemotePipeline",
     "Image",
     "indexify_function",
     "indexify_router",
+    "invocation_state",
+    "get_parameter",
+    "load_checkpoint",
+    "save_checkpoint",
     "DEFAULT_SERVICE_URL",
     "IndexifyClient",
 ]
@@ -1,8 +1,9 @@
 import asyncio
 import json
+import os
 import ssl
 from concurrent.futures.process import BrokenProcessPool
-from typing import Dict, List, Optional
+from typing import Dict, List, Optional, Set, Union
 
 import httpx
 import yaml
@@ -19,7 +20,19 @@ from indexify.functions_sdk.data_objects import (
     RouterOutput,
 )
 
-from .api_objects import ExecutorMetadata, Task
+from indexify.functions_sdk.checkpoints import RemoteTaskCheckpoints
+from indexify.functions_sdk.invocation_state import RemoteInvocationState
+from indexify.settings import auth_headers
+
+from .api_objects import (
+    ExecutorControl,
+    ExecutorHeartbeat,
+    ExecutorHeartbeatResponse,
+    ExecutorMetadata,
+    RecoveredTask,
+    Task,
+    WarmPoolHint,
+)
 from .downloader import DownloadedInputs, Downloader
 from .executor_tasks import DownloadGraphTask, DownloadInputTask, ExtractTask
 from .function_worker import FunctionWorker
@@ -38,13 +51,16 @@ custom_theme = Theme(
 
 console = Console(theme=custom_theme)
 
+# How long a poll of the control channel waits for a command
+CONTROL_POLL_SECS = 30
+
 
 class FunctionInput(BaseModel):
     task_id: str
     namespace: str
     compute_graph: str
     function: str
-    input: IndexifyData
+    input: Union[IndexifyData, List[IndexifyData]]
     init_value: Optional[IndexifyData] = None
 
 
@@ -86,19 +102,29 @@ class ExtractorAgent:
             self._protocol = "http"
             self._config = {}
 
-        self._task_store: TaskStore = TaskStore()
+        self._task_store: TaskStore = TaskStore(
+            journal_path=os.path.join(code_path, "task_journal.json")
+        )
         self._executor_id = executor_id
         self._function_worker = function_worker
         self._has_registered = False
         self._server_addr = server_addr
         self._base_url = f"{self._protocol}://{self._server_addr}"
         self._code_path = code_path
-        self._downloader = Downloader(code_path=code_path, base_url=self._base_url)
+        self._downloader = Downloader(
+            code_path=code_path,
+            base_url=self._base_url,
+            region=os.environ.get("INDEXIFY_REGION"),
+        )
         self._max_queued_tasks = 10
         self._task_reporter = TaskReporter(
             base_url=self._base_url, executor_id=self._executor_id
         )
         self._probe = RuntimeProbes()
+        self._heartbeat_interval = 10
+        # Features the server enabled when the executor registered, None if
+        # the server predates feature negotiation
+        self._server_features: Optional[Set[str]] = None
 
     async def task_completion_reporter(self):
         console.print(Text("Starting task completion reporter", style="bold cyan"))
@@ -125,7 +151,11 @@ class ExtractorAgent:
 
                 try:
                     # Send task outcome to the server
-                    self._task_reporter.report_task_outcome(completed_task=task_outcome)
+                    self._task_reporter.report_task_outcome(
+                        completed_task=task_outcome,
+                        inline_outputs=self._server_features is not None
+                        and "inline_outputs" in self._server_features,
+                    )
                 except Exception as e:
                     # The connection was dropped in the middle of the reporting, process, retry
                     console.print(
@@ -141,6 +171,98 @@ class ExtractorAgent:
 
                 self._task_store.mark_reported(task_id=task_outcome.task.id)
 
+    async def heartbeat(self):
+        url = f"{self._base_url}/internal/executors/{self._executor_id}/heartbeat"
+        while True:
+            await asyncio.sleep(self._heartbeat_interval)
+            if (
+                self._server_features is not None
+                and "lease_renewal" not in self._server_features
+            ):
+                continue
+            data = ExecutorHeartbeat(
+                cached_artifacts=self._downloader.cached_artifacts(),
+                queue_depth=self._task_store.num_pending_tasks(),
+                max_outstanding_tasks=self._max_queued_tasks,
+                recovered_tasks=recovered_tasks,
+            ).model_dump()
+            try:
+                async with httpx.AsyncClient(headers=auth_headers()) as client:
+                    response = await client.post(url, json=data)
+                    response.raise_for_status()
+            except Exception as e:
+                # The executor re-registers with its artifacts if it was deregistered
+                console.print(
+                    Text("heartbeat Error: ", style="red bold")
+                    + Text(f"failed to send heartbeat: {e}", style="red")
+                )
+                continue
+            # Servers predating warm pools respond without a body
+            if not response.content:
+                continue
+            heartbeat = ExecutorHeartbeatResponse.model_validate(response.json())
+            for hint in heartbeat.warm_pool:
+                await self._warm(hint)
+
+    async def _warm(self, hint: WarmPoolHint):
+        # Functions run in the executor process, one loaded instance serves
+        # all the instances the server asks for
+        try:
+            code_path = await self._downloader.download_graph(
+                hint.namespace,
+                hint.compute_graph,
+                hint.graph_version,
+                code_sha256=hint.code_sha256,
+            )
+            self._function_worker.warm(
+                hint.namespace,
+                hint.compute_graph,
+                hint.compute_fn,
+                code_path,
+                hint.graph_version,
+            )
+        except Exception as e:
+            console.print(
+                Text("warm pool Error: ", style="red bold")
+                + Text(f"failed to load {hint.compute_fn}: {e}", style="red")
+            )
+
+    async def control_channel(self):
+        url = f"{self._base_url}/internal/executors/{self._executor_id}/control"
+        while True:
+            if (
+                self._server_features is None
+                or "control_channel" not in self._server_features
+            ):
+                await asyncio.sleep(self._heartbeat_interval)
+                continue
+            try:
+                async with httpx.AsyncClient(
+                    headers=auth_headers(), timeout=CONTROL_POLL_SECS + 30
+                ) as client:
+                    response = await client.get(
+                        url, params={"wait_secs": CONTROL_POLL_SECS}
+                    )
+                    response.raise_for_status()
+                    control = ExecutorControl.model_validate(response.json())
+                    if len(control.aborts) == 0:
+                        continue
+                    for command in control.aborts:
+                        self._task_store.abort([command.task_id], command.reason)
+                    # Commands are redelivered until they are acknowledged
+                    response = await client.post(
+                        f"{url}/ack",
+                        json={"task_ids": [c.task_id for c in control.aborts]},
+                    )
+                    response.raise_for_status()
+            except Exception as e:
+                # Not connected until the task stream is open again
+                console.print(
+                    Text("control channel Error: ", style="red bold")
+                    + Text(f"failed to poll commands: {e}", style="red")
+                )
+                await asyncio.sleep(5)
+
     async def task_launcher(self):
         async_tasks: List[asyncio.Task] = []
         fn_queue: List[FunctionInput] = []
@@ -152,6 +274,9 @@ class ExtractorAgent:
         while True:
             fn: FunctionInput
             for fn in fn_queue:
+                # The task was pre-empted while its inputs were downloaded
+                if not self._task_store.has_task(fn.task_id):
+                    continue
                 task: Task = self._task_store.get_task(fn.task_id)
                 async_tasks.append(
                     ExtractTask(
@@ -160,6 +285,22 @@ class ExtractorAgent:
                         input=fn.input,
                         code_path=f"{self._code_path}/{task.namespace}/{task.compute_graph}.{task.graph_version}",
                         init_value=fn.init_value,
+                        parameters=task.parameters,
+                        invocation_state=RemoteInvocationState(
+                            self._base_url,
+                            task.namespace,
+                            task.compute_graph,
+                            task.invocation_id,
+                        ),
+                        checkpoints=RemoteTaskCheckpoints(
+                            self._base_url,
+                            task.namespace,
+                            task.compute_graph,
+                            task.invocation_id,
+                            task.compute_fn,
+                            task.id,
+                            task.checkpoint is not None,
+                        ),
                     )
                 )
 
@@ -268,6 +409,8 @@ class ExtractorAgent:
                             stdout=outputs.stdout,
                             stderr=outputs.stderr,
                             reducer=outputs.reducer,
+                            cpu_seconds=outputs.cpu_seconds,
+                            cold_start=outputs.cold_start,
                         )
                         self._task_store.complete(outcome=completed_task)
                     except BrokenProcessPool:
@@ -297,6 +440,8 @@ class ExtractorAgent:
         )
         asyncio.create_task(self.task_launcher())
         asyncio.create_task(self.task_completion_reporter())
+        asyncio.create_task(self.heartbeat())
+        asyncio.create_task(self.control_channel())
         self._should_run = True
         while self._should_run:
             self._protocol = "http"
@@ -307,11 +452,29 @@ class ExtractorAgent:
                 return words[0].capitalize() + "" + " ".join(words[1:])
 
             runtime_probe: ProbeInfo = self._probe.probe()
+            recovered_tasks = self._task_store.recovered_tasks()
+            if recovered_tasks is not None:
+                # The server reconciles them with the tasks it allocated to
+                # the executor before the crash
+                recovered_tasks = [
+                    RecoveredTask(
+                        namespace=task.namespace,
+                        compute_graph=task.compute_graph,
+                        compute_fn=task.compute_fn,
+                        invocation_id=task.invocation_id,
+                        task_id=task.id,
+                    )
+                    for task in recovered_tasks
+                ]
             data = ExecutorMetadata(
                 id=self._executor_id,
                 addr="",
                 image_name=runtime_probe.image_name,
                 labels=runtime_probe.labels,
+                cached_artifacts=self._downloader.cached_artifacts(),
+                gpus=runtime_probe.gpus,
+                runtimes=runtime_probe.runtimes,
+                max_outstanding_tasks=self._max_queued_tasks,
             ).model_dump()
 
             panel_content = "\n".join(
@@ -326,7 +489,7 @@ class ExtractorAgent:
             )
 
             try:
-                async with httpx.AsyncClient() as client:
+                async with httpx.AsyncClient(headers=auth_headers()) as client:
                     async with aconnect_sse(
                         client,
                         "POST",
@@ -334,11 +497,40 @@ class ExtractorAgent:
                         json=data,
                         headers={"Content-Type": "application/json"},
                     ) as event_source:
+                        if event_source.response.status_code == 426:
+                            # The server requires a newer executor, retrying won't help
+                            await event_source.response.aread()
+                            console.print(
+                                Text("registration Error: ", style="red bold")
+                                + Text(event_source.response.text, style="red")
+                            )
+                            return
+                        features = event_source.response.headers.get(
+                            "x-indexify-features"
+                        )
+                        if features is not None:
+                            self._server_features = set(
+                                feature for feature in features.split(",") if feature
+                            )
                         console.print(
                             Text("executor registered successfully", style="bold green")
                         )
+                        self._task_store.clear_recovered_tasks()
                         async for sse in event_source.aiter_sse():
                             data = json.loads(sse.data)
+                            if sse.event == "abort":
+                                self._task_store.abort(data)
+                                continue
+                            if sse.event == "prefetch":
+                                for task_dict in data:
+                                    asyncio.create_task(
+                                        self._prefetch(
+                                            Task.model_validate(
+                                                task_dict, strict=False
+                                            )
+                                        )
+                                    )
+                                continue
                             tasks = []
                             for task_dict in data:
                                 tasks.append(
@@ -353,6 +545,17 @@ class ExtractorAgent:
                 await asyncio.sleep(5)
                 continue
 
+    async def _prefetch(self, task: Task):
+        try:
+            await self._downloader.prefetch(task)
+        except Exception as e:
+            # Prefetching is best effort, the task downloads its input when
+            # it runs
+            console.print(
+                Text("prefetch error: ", style="yellow")
+                + Text(f"task {task.id}: {e}", style="yellow")
+            )
+
     async def _shutdown(self, loop):
         console.print(Text("shutting down agent...", style="bold yellow"))
         self._should_run = False
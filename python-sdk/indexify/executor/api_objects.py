@@ -1,8 +1,39 @@
+from importlib.metadata import PackageNotFoundError, version
 from typing import Any, Dict, List, Optional
 
 from pydantic import BaseModel, Json
 
 from indexify.functions_sdk.data_objects import IndexifyData
+from indexify.functions_sdk.graph_definition import RuntimeDescriptor
+
+# Version of the executor protocol spoken by this executor
+PROTOCOL_VERSION = 1
+# Optional protocol features this executor supports, the server enables the
+# ones it supports as well
+SUPPORTED_FEATURES = [
+    "lease_renewal",
+    "diagnostics",
+    "prefetch_hints",
+    "inline_outputs",
+    "control_channel",
+]
+
+
+def _sdk_version() -> Optional[str]:
+    try:
+        return version("indexify")
+    except PackageNotFoundError:
+        return None
+
+
+# Software version reported to the server when registering
+SDK_VERSION = _sdk_version()
+
+
+class TaskCheckpoint(BaseModel):
+    size: int
+    sha256_hash: str
+    created_at: int
 
 
 class Task(BaseModel):
@@ -14,6 +45,36 @@ class Task(BaseModel):
     input_key: str
     reducer_output_id: Optional[str] = None
     graph_version: int
+    invocation_labels: Dict[str, Any] = {}
+    code_sha256: Optional[str] = None
+    # Ids of the executor GPUs reserved for the task
+    gpu_ids: List[str] = []
+    # Runtime the sandbox of the task must be started with
+    runtime: Optional[RuntimeDescriptor] = None
+    # Keys of the outputs of a closed window, read together as a list input
+    input_batch: List[str] = []
+    # Resolved parameters of the invocation
+    parameters: Dict[str, Any] = {}
+    # Latest checkpoint of the task, saved by a previous attempt
+    checkpoint: Optional[TaskCheckpoint] = None
+    # Token the function calls the server with while the task runs
+    token: Optional[str] = None
+
+
+class GpuDevice(BaseModel):
+    id: str
+    model: str
+    free_memory_mb: int
+
+
+class RecoveredTask(BaseModel):
+    namespace: str
+    compute_graph: str
+    compute_fn: str
+    invocation_id: str
+    task_id: str
+    # Urls of the blobs uploaded for the outputs of the task
+    partial_outputs: List[str] = []
 
 
 class ExecutorMetadata(BaseModel):
@@ -21,6 +82,50 @@ class ExecutorMetadata(BaseModel):
     addr: str
     image_name: str
     labels: Dict[str, Any]
+    # sha256 hashes of the graph code cached by the executor
+    cached_artifacts: List[str] = []
+    gpus: List[GpuDevice] = []
+    # Runtimes the executor can start sandboxes with
+    runtimes: List[RuntimeDescriptor] = []
+    # Tasks accepted before finishing some, unlimited when not set
+    max_outstanding_tasks: Optional[int] = None
+    protocol_version: int = PROTOCOL_VERSION
+    features: List[str] = SUPPORTED_FEATURES
+    version: Optional[str] = SDK_VERSION
+    # Tasks the executor was running when it went down, sent when it registers
+    # again after a crash
+    recovered_tasks: Optional[List[RecoveredTask]] = None
+
+
+class ExecutorHeartbeat(BaseModel):
+    cached_artifacts: List[str]
+    # Tasks received and not finished yet
+    queue_depth: int = 0
+    max_outstanding_tasks: Optional[int] = None
+
+
+class WarmPoolHint(BaseModel):
+    namespace: str
+    compute_graph: str
+    compute_fn: str
+    graph_version: int
+    code_sha256: str
+    instances: int
+
+
+class ExecutorHeartbeatResponse(BaseModel):
+    warm_pool: List[WarmPoolHint] = []
+
+
+class AbortCommand(BaseModel):
+    task_id: str
+    # preempted, cancelled or relocated
+    reason: str
+    issued_at: int
+
+
+class ExecutorControl(BaseModel):
+    aborts: List[AbortCommand] = []
 
 
 class RouterOutput(BaseModel):
@@ -33,7 +138,8 @@ class FnOutput(BaseModel):
 
 class TaskResult(BaseModel):
     router_output: Optional[RouterOutput] = None
-    outcome: str
+    # Not set for partial results
+    outcome: Optional[str] = None
     namespace: str
     compute_graph: str
     compute_fn: str
@@ -41,3 +147,9 @@ class TaskResult(BaseModel):
     executor_id: str
     task_id: str
     reducer: bool = False
+    partial: bool = False
+    # Resources used by the task, when measured
+    cpu_seconds: Optional[float] = None
+    gpu_seconds: Optional[float] = None
+    # Whether the function was loaded for the task instead of being warm
+    cold_start: Optional[bool] = None
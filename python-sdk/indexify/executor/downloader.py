@@ -1,5 +1,8 @@
 import os
-from typing import Optional
+import shutil
+from collections import OrderedDict
+from typing import List, Optional, Union
+from urllib.parse import quote
 
 import httpx
 from pydantic import BaseModel
@@ -9,6 +12,7 @@ from rich.theme import Theme
 
 from indexify.functions_sdk.data_objects import IndexifyData
 from indexify.functions_sdk.object_serializer import MsgPackSerializer
+from indexify.settings import auth_headers
 
 from .api_objects import Task
 
@@ -24,20 +28,69 @@ console = Console(theme=custom_theme)
 
 
 class DownloadedInputs(BaseModel):
-    input: IndexifyData
+    # A list for the tasks after a window
+    input: Union[IndexifyData, List[IndexifyData]]
     init_value: Optional[IndexifyData] = None
 
 
+# Inputs of hinted tasks kept in memory until the tasks are allocated
+MAX_PREFETCHED_INPUTS = 16
+
+
 class Downloader:
-    def __init__(self, code_path: str, base_url: str):
+    def __init__(self, code_path: str, base_url: str, region: Optional[str] = None):
         self.code_path = code_path
         self.base_url = base_url
-
-    async def download_graph(self, namespace: str, name: str, version: int) -> str:
+        # Inputs are downloaded from their replica in the region when the
+        # server has one
+        self._params = {"region": region} if region else {}
+        self._prefetched: "OrderedDict[str, DownloadedInputs]" = OrderedDict()
+
+    async def prefetch(self, task: Task):
+        """Downloads the code and input of a task the executor may be
+        allocated soon. Inputs of reducer tasks aren't prefetched since the
+        accumulated value changes until the task runs."""
+        await self.download_graph(
+            task.namespace,
+            task.compute_graph,
+            task.graph_version,
+            code_sha256=task.code_sha256,
+        )
+        if task.reducer_output_id or task.id in self._prefetched:
+            return
+        self._prefetched[task.id] = await self._fetch_input(task)
+        while len(self._prefetched) > MAX_PREFETCHED_INPUTS:
+            self._prefetched.popitem(last=False)
+
+    def cached_artifacts(self) -> List[str]:
+        """Returns the sha256 hashes of the graph code cached on disk."""
+        artifacts_path = os.path.join(self.code_path, "artifacts")
+        if not os.path.isdir(artifacts_path):
+            return []
+        return [f for f in os.listdir(artifacts_path) if not f.endswith(".tmp")]
+
+    async def download_graph(
+        self,
+        namespace: str,
+        name: str,
+        version: int,
+        code_sha256: Optional[str] = None,
+    ) -> str:
         path = os.path.join(self.code_path, namespace, f"{name}.{version}")
         if os.path.exists(path):
             return path
 
+        # Graphs sharing the same code are only downloaded once
+        if code_sha256:
+            artifact_path = os.path.join(self.code_path, "artifacts", code_sha256)
+            if not os.path.exists(artifact_path):
+                await self._download_artifact(
+                    code_sha256, artifact_path, namespace, name
+                )
+            os.makedirs(os.path.dirname(path), exist_ok=True)
+            shutil.copyfile(artifact_path, path)
+            return path
+
         console.print(
             Panel(
                 f"Downloading graph: {name}\nPath: {path}",
@@ -47,7 +100,8 @@ class Downloader:
         )
 
         response = httpx.get(
-            f"{self.base_url}/internal/namespaces/{namespace}/compute_graphs/{name}/code"
+            f"{self.base_url}/internal/namespaces/{namespace}/compute_graphs/{name}/code",
+            headers=auth_headers(),
         )
         try:
             response.raise_for_status()
@@ -66,12 +120,81 @@ class Downloader:
             f.write(response.content)
         return path
 
+    async def _download_artifact(
+        self, code_sha256: str, path: str, namespace: str, name: str
+    ):
+        console.print(
+            Panel(
+                f"Downloading artifact: {code_sha256}\nPath: {path}",
+                title="downloader",
+                border_style="cyan",
+            )
+        )
+        response = httpx.get(
+            f"{self.base_url}/internal/artifacts/{code_sha256}",
+            headers=auth_headers(),
+        )
+        if response.status_code == 404:
+            # Servers which don't index artifacts only serve the graph code
+            response = httpx.get(
+                f"{self.base_url}/internal/namespaces/{namespace}/compute_graphs/{name}/code",
+                headers=auth_headers(),
+            )
+        try:
+            response.raise_for_status()
+        except httpx.HTTPStatusError as e:
+            console.print(
+                Panel(
+                    f"Failed to download artifact: {code_sha256}\nError: {response.text}",
+                    title="downloader error",
+                    border_style="error",
+                )
+            )
+            raise
+
+        os.makedirs(os.path.dirname(path), exist_ok=True)
+        # Write to a temporary file so a partial download is never cached
+        tmp_path = f"{path}.tmp"
+        with open(tmp_path, "wb") as f:
+            f.write(response.content)
+        os.replace(tmp_path, path)
+
     async def download_input(self, task: Task) -> IndexifyData:
+        prefetched = self._prefetched.pop(task.id, None)
+        if prefetched is not None:
+            return prefetched
+        return await self._fetch_input(task)
+
+    async def _fetch_batch(self, task: Task) -> DownloadedInputs:
+        batch = []
+        for key in task.input_batch:
+            response = httpx.get(
+                f"{self.base_url}/internal/fn_outputs/{quote(key, safe='')}",
+                params=self._params,
+                headers=auth_headers(),
+            )
+            try:
+                response.raise_for_status()
+            except httpx.HTTPStatusError:
+                console.print(
+                    Panel(
+                        f"failed to download input: {key}\nError: {response.text}",
+                        title="downloader error",
+                        border_style="error",
+                    )
+                )
+                raise
+            batch.append(MsgPackSerializer.deserialize(response.content))
+        return DownloadedInputs(input=batch)
+
+    async def _fetch_input(self, task: Task) -> DownloadedInputs:
+        if task.input_batch:
+            return await self._fetch_batch(task)
         input_id = task.input_key.split("|")[-1]
         if task.invocation_id == input_id:
             url = f"{self.base_url}/namespaces/{task.namespace}/compute_graphs/{task.compute_graph}/invocations/{task.invocation_id}/payload"
         else:
-            url = f"{self.base_url}/internal/fn_outputs/{task.input_key}"
+            url = f"{self.base_url}/internal/fn_outputs/{quote(task.input_key, safe='')}"
 
         reducer_url = None
         if task.reducer_output_id:
@@ -85,7 +208,7 @@ class Downloader:
             )
         )
 
-        response = httpx.get(url)
+        response = httpx.get(url, params=self._params, headers=auth_headers())
         try:
             response.raise_for_status()
         except httpx.HTTPStatusError as e:
@@ -105,7 +228,9 @@ class Downloader:
 
         init_value = None
         if reducer_url:
-            init_value = httpx.get(reducer_url)
+            init_value = httpx.get(
+                reducer_url, params=self._params, headers=auth_headers()
+            )
             try:
                 init_value.raise_for_status()
             except httpx.HTTPStatusError as e:
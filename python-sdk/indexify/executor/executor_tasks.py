@@ -1,7 +1,9 @@
 import asyncio
-from typing import Optional
+from typing import Any, Dict, Optional
 
+from indexify.functions_sdk.checkpoints import RemoteTaskCheckpoints
 from indexify.functions_sdk.data_objects import IndexifyData
+from indexify.functions_sdk.invocation_state import InvocationState
 
 from .api_objects import Task
 from .downloader import Downloader
@@ -20,7 +22,10 @@ class DownloadGraphTask(asyncio.Task):
         kwargs["loop"] = asyncio.get_event_loop()
         super().__init__(
             downloader.download_graph(
-                task.namespace, task.compute_graph, task.graph_version
+                task.namespace,
+                task.compute_graph,
+                task.graph_version,
+                code_sha256=task.code_sha256,
             ),
             **kwargs,
         )
@@ -53,6 +58,9 @@ class ExtractTask(asyncio.Task):
         input: IndexifyData,
         init_value: Optional[IndexifyData] = None,
         code_path: str,
+        parameters: Optional[Dict[str, Any]] = None,
+        invocation_state: Optional[InvocationState] = None,
+        checkpoints: Optional[RemoteTaskCheckpoints] = None,
         **kwargs,
     ):
         kwargs["name"] = "run_function"
@@ -66,6 +74,10 @@ class ExtractTask(asyncio.Task):
                 init_value=init_value,
                 code_path=code_path,
                 version=task.graph_version,
+                parameters=parameters,
+                invocation_state=invocation_state,
+                checkpoints=checkpoints,
+                task_token=task.token,
             ),
             **kwargs,
         )
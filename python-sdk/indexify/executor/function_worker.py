@@ -1,18 +1,29 @@
 import asyncio
+import time
 import traceback
 from concurrent.futures.process import BrokenProcessPool
-from typing import Dict, List, Optional
+from typing import Any, Dict, List, Optional
 
 import cloudpickle
 from pydantic import BaseModel
 from rich import print
 
+from indexify.functions_sdk.checkpoints import (
+    RemoteTaskCheckpoints,
+    checkpoints_scope,
+)
 from indexify.functions_sdk.data_objects import (
     FunctionWorkerOutput,
     IndexifyData,
     RouterOutput,
 )
 from indexify.functions_sdk.indexify_functions import IndexifyFunctionWrapper
+from indexify.functions_sdk.invocation_state import (
+    InvocationState,
+    invocation_state_scope,
+)
+from indexify.functions_sdk.parameters import parameters_scope
+from indexify.settings import task_token_scope
 
 function_wrapper_map: Dict[str, IndexifyFunctionWrapper] = {}
 
@@ -38,6 +49,8 @@ class FunctionOutput(BaseModel):
     exception: Optional[str] = None
     stdout: str = ""
     stderr: str = ""
+    # The function wasn't loaded before the task
+    cold_start: bool = False
 
 
 def _load_function(
@@ -73,9 +86,14 @@ class FunctionWorker:
         code_path: str,
         version: int,
         init_value: Optional[IndexifyData] = None,
+        invocation_state: Optional[InvocationState] = None,
+        parameters: Optional[Dict[str, Any]] = None,
+        checkpoints: Optional[RemoteTaskCheckpoints] = None,
+        task_token: Optional[str] = None,
     ) -> FunctionWorkerOutput:
+        cpu_start = time.thread_time()
         try:
-            result = _run_function(namespace, graph_name, fn_name, input, code_path, version, init_value)
+            result = _run_function(namespace, graph_name, fn_name, input, code_path, version, init_value, invocation_state, parameters, checkpoints, task_token)
             # TODO - bring back running in a separate process
         except Exception as e:
             return FunctionWorkerOutput(
@@ -84,6 +102,7 @@ class FunctionWorker:
                 stderr=e.stderr,
                 reducer=e.is_reducer,
                 success=False,
+                cpu_seconds=time.thread_time() - cpu_start,
             )
 
         return FunctionWorkerOutput(
@@ -94,8 +113,16 @@ class FunctionWorker:
             stderr=result.stderr,
             reducer=result.reducer,
             success=result.success,
+            cpu_seconds=time.thread_time() - cpu_start,
+            cold_start=result.cold_start,
         )
 
+    def warm(
+        self, namespace: str, graph_name: str, fn_name: str, code_path: str, version: int
+    ):
+        """Loads a function ahead of its tasks, which then start warm."""
+        _load_function(namespace, graph_name, fn_name, code_path, version)
+
     def shutdown(self):
         self._executor.shutdown(wait=True, cancel_futures=True)
 
@@ -108,6 +135,10 @@ def _run_function(
     code_path: str,
     version: int,
     init_value: Optional[IndexifyData] = None,
+    invocation_state: Optional[InvocationState] = None,
+    parameters: Optional[Dict[str, Any]] = None,
+    checkpoints: Optional[RemoteTaskCheckpoints] = None,
+    task_token: Optional[str] = None,
 ) -> FunctionOutput:
     import io
     import traceback
@@ -120,25 +151,32 @@ def _run_function(
     fn_output = None
     has_failed = False
     exception_msg = None
+    key = f"{namespace}/{graph_name}/{version}/{fn_name}"
+    cold_start = key not in function_wrapper_map
     print(
         f"[bold] function_worker: [/bold] invoking function {fn_name} in graph {graph_name}"
     )
     with redirect_stdout(stdout_capture), redirect_stderr(stderr_capture):
         try:
-            key = f"{namespace}/{graph_name}/{version}/{fn_name}"
             if key not in function_wrapper_map:
                 _load_function(namespace, graph_name, fn_name, code_path, version)
 
             fn = function_wrapper_map[key]
-            if (
-                str(type(fn.indexify_function))
-                == "<class 'indexify.functions_sdk.indexify_functions.IndexifyRo'>"
-            ):
-                router_output = fn.invoke_router(fn_name, input)
-            else:
-                fn_output = fn.invoke_fn_ser(fn_name, input, init_value)
-
-                is_reducer = fn.indexify_function.accumulate is not None
+            with parameters_scope(parameters or {}), checkpoints_scope(
+                checkpoints
+            ), task_token_scope(task_token):
+                if (
+                    str(type(fn.indexify_function))
+                    == "<class 'indexify.functions_sdk.indexify_functions.IndexifyRo'>"
+                ):
+                    router_output = fn.invoke_router(fn_name, input)
+                elif invocation_state is not None:
+                    with invocation_state_scope(invocation_state):
+                        fn_output = fn.invoke_fn_ser(fn_name, input, init_value)
+                else:
+                    fn_output = fn.invoke_fn_ser(fn_name, input, init_value)
+
+                    is_reducer = fn.indexify_function.accumulate is not None
         except Exception as e:
             import sys
 
@@ -157,6 +195,7 @@ def _run_function(
             stderr=stderr_capture.getvalue(),
             reducer=is_reducer,
             success=False,
+            cold_start=cold_start,
         )
     return FunctionOutput(
         fn_outputs=fn_output,
@@ -165,4 +204,5 @@ def _run_function(
         success=True,
         stdout=stdout_capture.getvalue(),
         stderr=stderr_capture.getvalue(),
+        cold_start=cold_start,
     )
@@ -1,15 +1,20 @@
 import os
 import platform
+import subprocess
 import sys
-from typing import Any, Dict, Tuple
+from typing import Any, Dict, List, Optional, Tuple
 
 from pydantic import BaseModel
 
+from .api_objects import GpuDevice, RuntimeDescriptor
+
 
 class ProbeInfo(BaseModel):
     image_name: str
     python_major_version: int
     labels: Dict[str, Any] = {}
+    gpus: List[GpuDevice] = []
+    runtimes: List[RuntimeDescriptor] = []
 
 
 class RuntimeProbes:
@@ -29,10 +34,41 @@ class RuntimeProbes:
                 return file.read().strip()
         return "tensorlake/indexify-executor-default"
 
+    def _read_pip_hash(self) -> Optional[str]:
+        file_path = os.path.expanduser("~/.indexify/pip_hash")
+        if os.path.exists(file_path):
+            with open(file_path, "r") as file:
+                return file.read().strip()
+        return None
+
     def _get_python_version(self) -> Tuple[int, int]:
         version_info = sys.version_info
         return version_info.major, version_info.minor
 
+    def _probe_gpus(self) -> List[GpuDevice]:
+        try:
+            result = subprocess.run(
+                [
+                    "nvidia-smi",
+                    "--query-gpu=index,name,memory.free",
+                    "--format=csv,noheader,nounits",
+                ],
+                capture_output=True,
+                text=True,
+                check=True,
+            )
+        except (OSError, subprocess.CalledProcessError):
+            return []
+        gpus = []
+        for line in result.stdout.strip().splitlines():
+            index, name, free_memory = [value.strip() for value in line.split(",")]
+            # nvidia-smi reports names like "NVIDIA A10G"
+            model = name.removeprefix("NVIDIA ").strip()
+            gpus.append(
+                GpuDevice(id=index, model=model, free_memory_mb=int(free_memory))
+            )
+        return gpus
+
     def probe(self) -> ProbeInfo:
         labels = {
             "os": self._os_name,
@@ -41,8 +77,21 @@ class RuntimeProbes:
             "python_major_version": self._python_version_major,
             "python_minor_version": self._python_version_minor,
         }
+        # Payloads replicated to the region of the executor are downloaded
+        # from the replica
+        region = os.environ.get("INDEXIFY_REGION")
+        if region:
+            labels["region"] = region
         return ProbeInfo(
             image_name=self._image_name,
             python_major_version=self._python_version_major,
             labels=labels,
+            gpus=self._probe_gpus(),
+            runtimes=[
+                RuntimeDescriptor(
+                    image=self._image_name,
+                    python_version=f"{self._python_version_major}.{self._python_version_minor}",
+                    pip_hash=self._read_pip_hash(),
+                )
+            ],
         )
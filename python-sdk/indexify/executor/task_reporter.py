@@ -10,6 +10,7 @@ from indexify.executor.api_objects import Task, TaskResult
 from indexify.executor.task_store import CompletedTask
 from indexify.functions_sdk.data_objects import IndexifyData, RouterOutput
 from indexify.functions_sdk.object_serializer import MsgPackSerializer
+from indexify.settings import auth_headers
 
 
 # https://github.com/psf/requests/issues/1081#issuecomment-428504128
@@ -20,22 +21,51 @@ class ForceMultipartDict(dict):
 
 FORCE_MULTIPART = ForceMultipartDict()
 
+# Outputs up to this size are sent inline with the task outcome when the
+# server supports it, saving the server a blob store upload
+INLINE_OUTPUT_MAX_BYTES = 64 * 1024
+
 
 class TaskReporter:
     def __init__(self, base_url: str, executor_id: str):
         self._base_url = base_url
         self._executor_id = executor_id
 
-    def report_task_outcome(self, completed_task: CompletedTask):
+    def report_partial_outputs(self, task: Task, outputs: List[IndexifyData]):
+        """Uploads outputs of a running task so the server can start the
+        downstream functions before the task finishes."""
+        fn_outputs = []
+        for output in outputs:
+            output_bytes = MsgPackSerializer.serialize(output)
+            fn_outputs.append(
+                ("node_outputs", (nanoid.generate(), io.BytesIO(output_bytes)))
+            )
+        task_result = TaskResult(
+            namespace=task.namespace,
+            compute_graph=task.compute_graph,
+            compute_fn=task.compute_fn,
+            invocation_id=task.invocation_id,
+            executor_id=self._executor_id,
+            task_id=task.id,
+            partial=True,
+        )
+        self._post_task_result(task_result, fn_outputs)
+
+    def report_task_outcome(
+        self, completed_task: CompletedTask, inline_outputs: bool = False
+    ):
         fn_outputs = []
         print(
             f"[bold]task-reporter[/bold] uploading output of size: {len(completed_task.outputs or [])}"
         )
         for output in completed_task.outputs or []:
             output_bytes = MsgPackSerializer.serialize(output)
-            fn_outputs.append(
-                ("node_outputs", (nanoid.generate(), io.BytesIO(output_bytes)))
+            field = (
+                "inline_node_outputs"
+                if inline_outputs and len(output_bytes) <= INLINE_OUTPUT_MAX_BYTES
+                else "node_outputs"
             )
+            fn_outputs.append((field, (nanoid.generate(), io.BytesIO(output_bytes))))
 
         if completed_task.errors:
             print(
@@ -86,7 +116,12 @@ class TaskReporter:
             executor_id=self._executor_id,
             task_id=completed_task.task.id,
             reducer=completed_task.reducer,
+            cpu_seconds=completed_task.cpu_seconds,
+            cold_start=completed_task.cold_start,
         )
+        self._post_task_result(task_result, fn_outputs)
+
+    def _post_task_result(self, task_result: TaskResult, fn_outputs: List):
         task_result_data = task_result.model_dump_json(exclude_none=True)
 
         kwargs = {"data": {"task_result": task_result_data}}
@@ -97,12 +132,25 @@ class TaskReporter:
         try:
             response = httpx.post(
                 url=f"{self._base_url}/internal/ingest_files",
+                headers=auth_headers(),
                 **kwargs,
             )
         except Exception as e:
             print(f"failed to report task outcome {e}")
             raise e
 
+        if response.status_code == 413 and any(
+            name == "inline_node_outputs" for name, _ in fn_outputs
+        ):
+            # The server accepts smaller inline outputs, upload them instead
+            fn_outputs = [
+                ("node_outputs" if name == "inline_node_outputs" else name, file)
+                for name, file in fn_outputs
+            ]
+            for _, (_, data) in fn_outputs:
+                data.seek(0)
+            return self._post_task_result(task_result, fn_outputs)
+
         try:
             response.raise_for_status()
         except Exception as e:
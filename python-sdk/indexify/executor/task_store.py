@@ -1,5 +1,7 @@
 import asyncio
-from typing import Dict, List, Literal, Optional
+import json
+import os
+from typing import Dict, List, Literal, Optional, Set
 
 from pydantic import BaseModel
 from rich import print
@@ -18,20 +20,57 @@ class CompletedTask(BaseModel):
     stdout: Optional[str] = None
     stderr: Optional[str] = None
     reducer: bool = False
+    cpu_seconds: Optional[float] = None
+    cold_start: Optional[bool] = None
 
 
 class TaskStore:
-    def __init__(self) -> None:
+    def __init__(self, journal_path: Optional[str] = None) -> None:
+        # Tasks received and not reported yet are journaled, so the executor
+        # can report them to the server after a crash
+        self._journal_path = journal_path
+        self._recovered_tasks = self._read_journal()
         self._tasks: Dict[str, Task] = {}
         self._running_tasks: Dict[str, Task] = {}
         self._finished: Dict[str, CompletedTask] = {}
         self._retries: Dict[str, int] = {}
+        # Tasks aborted by the server whose outcomes are dropped
+        self._aborted: Set[str] = set()
         self._new_task_event = asyncio.Event()
         self._finished_task_event = asyncio.Event()
 
+    def _read_journal(self) -> Optional[List[Task]]:
+        if self._journal_path is None or not os.path.exists(self._journal_path):
+            return None
+        try:
+            with open(self._journal_path, "r") as f:
+                return [Task.model_validate(task) for task in json.load(f)]
+        except Exception as e:
+            print(f"[bold] task store: [/bold] ignoring unreadable journal: {e}")
+            return None
+
+    def _write_journal(self):
+        if self._journal_path is None:
+            return
+        tmp_path = f"{self._journal_path}.tmp"
+        with open(tmp_path, "w") as f:
+            json.dump([task.model_dump() for task in self._tasks.values()], f)
+        os.replace(tmp_path, self._journal_path)
+
+    def recovered_tasks(self) -> Optional[List[Task]]:
+        """Tasks the previous run of the executor didn't report, None when
+        it shut down without leaving a journal."""
+        return self._recovered_tasks
+
+    def clear_recovered_tasks(self):
+        self._recovered_tasks = None
+
     def get_task(self, id) -> Task:
         return self._tasks[id]
 
+    def has_task(self, id) -> bool:
+        return id in self._tasks
+
     def add_tasks(self, tasks: List[Task]):
         task: Task
         for task in tasks:
@@ -41,11 +80,14 @@ class TaskStore:
                 or (task.id in self._finished)
             ):
                 continue
+            # A pre-empted task can be allocated to the executor again
+            self._aborted.discard(task.id)
             print(
                 f"[bold] task store: [/bold] added task: {task.id} graph: {task.compute_graph} fn: {task.compute_fn} to queue"
             )
             self._tasks[task.id] = task
             self._new_task_event.set()
+        self._write_journal()
 
     async def get_runnable_tasks(self) -> Dict[str, Task]:
         while True:
@@ -62,7 +104,19 @@ class TaskStore:
             self._running_tasks[task_id] = self._tasks[task_id]
         return out
 
+    def abort(self, task_ids: List[str], reason: str = "preempted"):
+        for task_id in task_ids:
+            self._tasks.pop(task_id, None)
+            self._running_tasks.pop(task_id, None)
+            self._finished.pop(task_id, None)
+            self._retries.pop(task_id, None)
+            self._aborted.add(task_id)
+            print(f"[bold] task store: [/bold] aborted {reason} task: {task_id}")
+        self._write_journal()
+
     def complete(self, outcome: CompletedTask):
+        if outcome.task.id in self._aborted:
+            return
         self._retries.pop(outcome.task.id, None)
         self._finished[outcome.task.id] = outcome
         if outcome.task.id in self._running_tasks:
@@ -85,8 +139,9 @@ class TaskStore:
             self._new_task_event.set()
 
     def mark_reported(self, task_id: str):
-        self._tasks.pop(task_id)
-        self._finished.pop(task_id)
+        self._tasks.pop(task_id, None)
+        self._finished.pop(task_id, None)
+        self._write_journal()
         print(f"[bold] task store: [/bold] removed task: {task_id} from queue")
 
     def report_failed(self, task_id: str):
@@ -99,6 +154,7 @@ class TaskStore:
             # The only possible error at this point is task not present at
             # the coordinator.
             self._tasks.pop(task_id)
+            self._write_journal()
 
     def num_pending_tasks(self) -> int:
         return len(self._tasks) + len(self._running_tasks)
@@ -0,0 +1,83 @@
+from contextlib import contextmanager
+from contextvars import ContextVar
+from typing import Optional
+
+import httpx
+
+from indexify.settings import auth_headers
+
+
+class RemoteTaskCheckpoints:
+    """
+    Checkpoints of a task stored by the server. The latest checkpoint is
+    handed to the attempt resuming the task after it's retried or reassigned.
+    """
+
+    def __init__(
+        self,
+        base_url: str,
+        namespace: str,
+        compute_graph: str,
+        invocation_id: str,
+        compute_fn: str,
+        task_id: str,
+        has_checkpoint: bool,
+    ):
+        self._url = f"{base_url}/internal/namespaces/{namespace}/compute_graphs/{compute_graph}/invocations/{invocation_id}/fn/{compute_fn}/tasks/{task_id}/checkpoint"
+        self._has_checkpoint = has_checkpoint
+
+    def load(self) -> Optional[bytes]:
+        if not self._has_checkpoint:
+            return None
+        response = httpx.get(self._url, headers=auth_headers())
+        if response.status_code == 404:
+            return None
+        response.raise_for_status()
+        return response.content
+
+    def save(self, data: bytes):
+        response = httpx.post(self._url, content=data, headers=auth_headers())
+        response.raise_for_status()
+        self._has_checkpoint = True
+
+
+_current_checkpoints: ContextVar[Optional[RemoteTaskCheckpoints]] = ContextVar(
+    "indexify_task_checkpoints", default=None
+)
+
+
+@contextmanager
+def checkpoints_scope(checkpoints: Optional[RemoteTaskCheckpoints]):
+    token = _current_checkpoints.set(checkpoints)
+    try:
+        yield checkpoints
+    finally:
+        _current_checkpoints.reset(token)
+
+
+def load_checkpoint() -> Optional[bytes]:
+    """
+    Latest checkpoint saved by a previous attempt of the task the calling
+    function runs in, None when the task starts from scratch or runs locally.
+
+    Example:
+    @indexify_function()
+    def crawl(site: str) -> List[str]:
+        state = load_checkpoint()
+        crawler = Crawler(site, resume_from=pickle.loads(state) if state else None)
+        for page in crawler:
+            save_checkpoint(pickle.dumps(crawler.state()))
+        return crawler.pages
+    """
+    checkpoints = _current_checkpoints.get()
+    return checkpoints.load() if checkpoints is not None else None
+
+
+def save_checkpoint(data: bytes):
+    """
+    Persists the progress of a long running function, replacing its previous
+    checkpoint. Does nothing when the function runs locally.
+    """
+    checkpoints = _current_checkpoints.get()
+    if checkpoints is not None:
+        checkpoints.save(data)
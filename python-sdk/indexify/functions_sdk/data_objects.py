@@ -28,6 +28,10 @@ class FunctionWorkerOutput(BaseModel):
     stderr: Optional[str]
     reducer: bool = False
     success: bool = True
+    # CPU time used by the function
+    cpu_seconds: Optional[float] = None
+    # Whether the function was loaded for the task
+    cold_start: Optional[bool] = None
 
 
 class File(BaseModel):
@@ -23,11 +23,18 @@ from .data_objects import IndexifyData, RouterOutput
 from .graph_definition import (
     ComputeGraphMetadata,
     FunctionMetadata,
+    GateMetadata,
+    GraphParameter,
+    GraphSLA,
+    IngestionTransform,
     NodeMetadata,
     RouterMetadata,
     RuntimeInformation,
+    SinkMetadata,
+    WindowMetadata,
 )
 from .graph_validation import validate_node, validate_route
+from .invocation_state import LocalInvocationState, invocation_state_scope
 from .indexify_functions import (
     IndexifyFunction,
     IndexifyFunctionWrapper,
@@ -35,6 +42,7 @@ from .indexify_functions import (
 )
 from .local_cache import CacheAwareFunctionWrapper
 from .object_serializer import get_serializer
+from .parameters import parameters_scope
 
 RouterFn = Annotated[
     Callable[[IndexifyData], Optional[List[IndexifyFunction]]], "RouterFn"
@@ -61,13 +69,32 @@ def is_pydantic_model_from_annotation(type_annotation):
 
 class Graph:
     def __init__(
-        self, name: str, start_node: IndexifyFunction, description: Optional[str] = None
+        self,
+        name: str,
+        start_node: IndexifyFunction,
+        description: Optional[str] = None,
+        sla: Optional[GraphSLA] = None,
+        parameters: Optional[Dict[str, GraphParameter]] = None,
+        ingestion_hooks: Optional[List[IngestionTransform]] = None,
+        default_lane: str = "latency",
+        skip_payload_compression: bool = False,
     ):
         self.name = name
         self.description = description
+        self.sla = sla
+        self.parameters = parameters or {}
+        self.ingestion_hooks = ingestion_hooks or []
+        self.default_lane = default_lane
+        self.skip_payload_compression = skip_payload_compression
         self.nodes: Dict[str, Union[IndexifyFunction, IndexifyRouter]] = {}
         self.routers: Dict[str, List[str]] = defaultdict(list)
+        # Targets of routers used when they select none
+        self.router_defaults: Dict[str, List[str]] = {}
         self.edges: Dict[str, List[str]] = defaultdict(list)
+        # Gates by name with their descriptions
+        self.gates: Dict[str, str] = {}
+        self.windows: Dict[str, WindowMetadata] = {}
+        self.sinks: Dict[str, SinkMetadata] = {}
         self.accumulator_zero_values: Dict[str, Any] = {}
 
         self.add_node(start_node)
@@ -103,10 +130,18 @@ class Graph:
         return self
 
     def route(
-        self, from_node: Type[IndexifyRouter], to_nodes: List[Type[IndexifyFunction]]
+        self,
+        from_node: Type[IndexifyRouter],
+        to_nodes: List[Type[IndexifyFunction]],
+        default_nodes: Optional[List[Type[IndexifyFunction]]] = None,
     ) -> "Graph":
 
         validate_route(from_node=from_node, to_nodes=to_nodes)
+        for node in default_nodes or []:
+            if node not in to_nodes:
+                raise Exception(
+                    f"Default node {node.name} of router {from_node.name} is not in to_nodes"
+                )
 
         print(
             f"Adding router {from_node.name} to nodes {[node.name for node in to_nodes]}"
@@ -115,6 +150,120 @@ class Graph:
         for node in to_nodes:
             self.add_node(node)
             self.routers[from_node.name].append(node.name)
+        if default_nodes:
+            self.router_defaults[from_node.name] = [node.name for node in default_nodes]
+        return self
+
+    def add_gate(
+        self,
+        from_node: Type[IndexifyFunction],
+        name: str,
+        to_nodes: List[Type[IndexifyFunction]],
+        description: Optional[str] = None,
+    ) -> "Graph":
+        """
+        Adds a gate between from_node and to_nodes. The invocation waits at the
+        gate until it is signaled, the payload of the signal is the input of
+        to_nodes.
+        """
+        if name in self.nodes:
+            raise ValueError(f"gate {name} has the name of a function")
+        self.add_node(from_node)
+        self.gates[name] = description or ""
+        self.edges[from_node.name].append(name)
+        for node in to_nodes:
+            self.add_node(node)
+            self.edges[name].append(node.name)
+        return self
+
+    def add_window(
+        self,
+        from_node: Type[IndexifyFunction],
+        name: str,
+        to_nodes: List[Type[IndexifyFunction]],
+        max_items: Optional[int] = None,
+        max_age_secs: Optional[int] = None,
+        description: Optional[str] = None,
+        event_time_secs: Optional[int] = None,
+        allowed_lateness_secs: int = 0,
+        late_output: Optional[Type[IndexifyFunction]] = None,
+    ) -> "Graph":
+        """
+        Adds a window between from_node and to_nodes. The outputs of from_node
+        are accumulated across invocations of the graph until max_items
+        outputs arrived or the oldest waited max_age_secs, to_nodes are then
+        called once with the list of the outputs.
+
+        With event_time_secs, the outputs are grouped in windows of that many
+        seconds by the event time of their invocation, each closing once the
+        watermark, the latest event time minus allowed_lateness_secs, passes
+        its end. Outputs of closed windows are passed one at a time to
+        late_output.
+        """
+        if name in self.nodes or name in self.gates or name in self.sinks:
+            raise ValueError(f"window {name} has the name of another node")
+        if max_items is None and max_age_secs is None and event_time_secs is None:
+            raise ValueError(
+                f"window {name} needs max_items, max_age_secs or event_time_secs"
+            )
+        if event_time_secs is None and (allowed_lateness_secs or late_output):
+            raise ValueError(
+                f"window {name} needs event_time_secs for allowed_lateness_secs and late_output"
+            )
+        for node in to_nodes + ([late_output] if late_output else []):
+            if issubclass(node, IndexifyFunction) and node.accumulate:
+                raise ValueError(
+                    f"window {name} can't be followed by reducer {node.name}"
+                )
+        self.add_node(from_node)
+        self.windows[name] = WindowMetadata(
+            name=name,
+            description=description or "",
+            max_items=max_items,
+            max_age_secs=max_age_secs,
+            event_time_secs=event_time_secs,
+            allowed_lateness_secs=allowed_lateness_secs,
+            late_output=late_output.name if late_output else None,
+        )
+        self.edges[from_node.name].append(name)
+        for node in to_nodes:
+            self.add_node(node)
+            self.edges[name].append(node.name)
+        if late_output:
+            self.add_node(late_output)
+        return self
+
+    def add_sink(
+        self,
+        from_node: Type[IndexifyFunction],
+        name: str,
+        connector: Dict[str, Any],
+        id_field: str = "id",
+        vector_field: str = "embedding",
+        description: Optional[str] = None,
+    ) -> "Graph":
+        """
+        Adds a sink after from_node. The server writes the outputs of from_node,
+        JSON records or lists of records, to the index of the connector, e.g.
+        {"type": "qdrant", "url": "http://qdrant:6333", "collection": "docs"}.
+        from_node must use the json encoder. Records without id_field get an id
+        derived from their output.
+        """
+        if name in self.nodes or name in self.gates or name in self.windows:
+            raise ValueError(f"sink {name} has the name of another node")
+        if from_node.payload_encoder != "json":
+            raise ValueError(
+                f"sink {name} needs {from_node.name} to use the json encoder"
+            )
+        self.add_node(from_node)
+        self.sinks[name] = SinkMetadata(
+            name=name,
+            description=description or "",
+            connector=connector,
+            id_field=id_field,
+            vector_field=vector_field,
+        )
+        self.edges[from_node.name].append(name)
         return self
 
     def serialize(self, additional_modules):
@@ -157,6 +306,11 @@ class Graph:
             description=start_node.description,
             reducer=start_node.accumulate is not None,
             image_name=start_node.image._image_name,
+            gpu=start_node.gpu,
+            concurrency_key=start_node.concurrency_key,
+            runtime=start_node.runtime,
+            output_limits=start_node.output_limits,
+            min_warm_instances=start_node.min_warm_instances,
         )
         metadata_edges = self.edges.copy()
         metadata_nodes = {}
@@ -170,6 +324,8 @@ class Graph:
                         target_fns=self.routers[node_name],
                         payload_encoder=node.payload_encoder,
                         image_name=node.image._image_name,
+                        default_targets=self.router_defaults.get(node_name, []),
+                        on_error=node.on_error,
                     )
                 )
             else:
@@ -180,8 +336,21 @@ class Graph:
                         description=node.description,
                         reducer=node.accumulate is not None,
                         image_name=node.image._image_name,
+                        gpu=node.gpu,
+                        concurrency_key=node.concurrency_key,
+                        runtime=node.runtime,
+                        output_limits=node.output_limits,
+                        min_warm_instances=node.min_warm_instances,
                     )
                 )
+        for gate_name, description in self.gates.items():
+            metadata_nodes[gate_name] = NodeMetadata(
+                gate=GateMetadata(name=gate_name, description=description)
+            )
+        for window_name, window in self.windows.items():
+            metadata_nodes[window_name] = NodeMetadata(window=window)
+        for sink_name, sink in self.sinks.items():
+            metadata_nodes[sink_name] = NodeMetadata(sink=sink)
 
         return ComputeGraphMetadata(
             name=self.name,
@@ -189,6 +358,11 @@ class Graph:
             start_node=NodeMetadata(compute_fn=start_node),
             nodes=metadata_nodes,
             edges=metadata_edges,
+            sla=self.sla,
+            parameters=self.parameters,
+            ingestion_hooks=self.ingestion_hooks,
+            default_lane=self.default_lane,
+            skip_payload_compression=self.skip_payload_compression,
             runtime_information=RuntimeInformation(
                 major_version=sys.version_info.major,
                 minor_version=sys.version_info.minor,
@@ -196,6 +370,21 @@ class Graph:
         )
 
     def run(self, block_until_done: bool = False, **kwargs) -> str:
+        if self.gates:
+            raise ValueError("graphs with gates can only run on a server")
+        if self.windows:
+            raise ValueError("graphs with windows can only run on a server")
+        if self.sinks:
+            raise ValueError("graphs with sinks can only run on a server")
+        # Local runs use the defaults of the parameters
+        missing = [
+            name for name, param in self.parameters.items() if param.default is None
+        ]
+        if missing:
+            raise ValueError(
+                f"parameters {', '.join(missing)} have no default, run the graph on a server"
+            )
+        parameters = {name: param.default for name, param in self.parameters.items()}
         start_node = self.nodes[self._start_node]
         serializer = get_serializer(start_node.payload_encoder)
         input = IndexifyData(id=generate(), payload=serializer.serialize(kwargs))
@@ -210,7 +399,10 @@ class Graph:
             }
         self._results[input.id] = outputs
         enable_cache = kwargs.get('enable_cache', True)
-        self._run(input, outputs,enable_cache)
+        with invocation_state_scope(LocalInvocationState()), parameters_scope(
+            parameters
+        ):
+            self._run(input, outputs,enable_cache)
         return input.id
 
     def _run(
@@ -273,8 +465,11 @@ class Graph:
                 if edge in self.routers:
                     out_edges.remove(edge)
                     for output in function_outputs:
-                        dynamic_edges = self._route(edge, output) or []
-                        for dynamic_edge in dynamic_edges.edges:
+                        router_output = self._route(edge, output)
+                        dynamic_edges = router_output.edges if router_output else []
+                        if not dynamic_edges:
+                            dynamic_edges = self.router_defaults.get(edge, [])
+                        for dynamic_edge in dynamic_edges:
                             if dynamic_edge in self.nodes:
                                 print(
                                     f"[bold]dynamic router returned node: {dynamic_edge}[/bold]"
@@ -1,10 +1,32 @@
-from typing import Dict, List, Optional
+from typing import Any, Dict, List, Optional
 
 from pydantic import BaseModel
 
 from .object_serializer import get_serializer
 
 
+class GpuRequirement(BaseModel):
+    # Any GPU model is accepted when not set, e.g. "A10G"
+    model: Optional[str] = None
+    count: int = 1
+
+
+class RuntimeDescriptor(BaseModel):
+    # Container image, including its tag
+    image: str
+    # Any version is accepted when not set, e.g. "3.11"
+    python_version: Optional[str] = None
+    # Hash of the pip requirements installed in the image
+    pip_hash: Optional[str] = None
+
+
+class OutputLimits(BaseModel):
+    # Bounds the outputs of a single task, the outputs it streamed included.
+    # Tasks exceeding them fail and their outputs are dropped.
+    max_outputs: Optional[int] = None
+    max_output_bytes: Optional[int] = None
+
+
 class FunctionMetadata(BaseModel):
     name: str
     fn_name: str
@@ -12,6 +34,13 @@ class FunctionMetadata(BaseModel):
     reducer: bool = False
     image_name: str
     payload_encoder: str = "cloudpickle"
+    gpu: Optional[GpuRequirement] = None
+    concurrency_key: Optional[str] = None
+    # Tasks are only placed on executors advertising this runtime
+    runtime: Optional[RuntimeDescriptor] = None
+    output_limits: Optional[OutputLimits] = None
+    # Environments of the function executors keep loaded between its tasks
+    min_warm_instances: int = 0
 
 
 class RouterMetadata(BaseModel):
@@ -21,11 +50,78 @@ class RouterMetadata(BaseModel):
     target_fns: List[str]
     image_name: str
     payload_encoder: str = "cloudpickle"
+    # Set by the server for routers uploaded with a WASM module
+    wasm_module_sha256: Optional[str] = None
+    # Targets the input is routed to when the router selects none
+    default_targets: List[str] = []
+    # fail_invocation, route_to_default or skip
+    on_error: str = "fail_invocation"
+
+
+class GateMetadata(BaseModel):
+    name: str
+    description: str = ""
+
+
+class WindowMetadata(BaseModel):
+    name: str
+    description: str = ""
+    max_items: Optional[int] = None
+    max_age_secs: Optional[int] = None
+    # Tumbling windows of this many seconds by the event time of invocations
+    event_time_secs: Optional[int] = None
+    allowed_lateness_secs: int = 0
+    late_output: Optional[str] = None
+
+
+class SinkMetadata(BaseModel):
+    name: str
+    description: str = ""
+    # Index the inputs are written to, e.g.
+    # {"type": "qdrant", "url": "http://qdrant:6333", "collection": "docs"}.
+    # Credentials name secrets of the graph, e.g. dsn_secret or api_key_secret.
+    connector: Dict[str, Any]
+    id_field: str = "id"
+    vector_field: str = "embedding"
 
 
 class NodeMetadata(BaseModel):
     dynamic_router: Optional[RouterMetadata] = None
     compute_fn: Optional[FunctionMetadata] = None
+    # Pauses the invocation until the gate is signaled
+    gate: Optional[GateMetadata] = None
+    # Accumulates inputs across invocations until the window closes
+    window: Optional[WindowMetadata] = None
+    # Writes its inputs to an index from the server
+    sink: Optional[SinkMetadata] = None
+
+
+class GraphSLA(BaseModel):
+    # Invocations must complete successfully within this duration
+    max_duration_secs: int
+    # Fraction of the invocations of the window meeting the SLA, e.g. 0.95
+    target: float
+    window_secs: int = 3600
+
+
+class GraphParameter(BaseModel):
+    description: str = ""
+    # Parameters without a default must be bound when the graph is
+    # instantiated or passed by each invocation
+    default: Optional[Any] = None
+
+
+class IngestionTransform(BaseModel):
+    # decompress, split_archive or strip_json_fields
+    transform: str
+    # Fields removed by strip_json_fields, nested fields are separated by dots
+    fields: Optional[List[str]] = None
+
+
+class GraphPause(BaseModel):
+    paused_at: int
+    # New invocations are rejected while the graph is paused
+    reject_invocations: bool = False
 
 
 # RuntimeInformation is a class that holds data about the environment in which the graph should run.
@@ -42,6 +138,24 @@ class ComputeGraphMetadata(BaseModel):
     edges: Dict[str, List[str]]
     accumulator_zero_values: Dict[str, bytes] = {}
     runtime_information: RuntimeInformation
+    sla: Optional[GraphSLA] = None
+    # Set by the server, incremented with every update of the graph
+    revision: Optional[int] = None
+    # Set by the server while the graph is paused
+    paused: Optional[GraphPause] = None
+    # Read by functions with get_parameter, and referenced by configs as
+    # {params.<name>}
+    parameters: Dict[str, GraphParameter] = {}
+    # Set by the server for graphs instantiated from a template
+    parameter_values: Dict[str, Any] = {}
+    template: Optional[str] = None
+    # Run by the server over invocation payloads before the first task
+    ingestion_hooks: List[IngestionTransform] = []
+    # latency or batch, lane of the invocations which don't choose one
+    default_lane: str = "latency"
+    # Stores the payloads uncompressed, for media which is already
+    # compressed
+    skip_payload_compression: bool = False
 
     def get_input_payload_serializer(self):
         return get_serializer(self.start_node.compute_fn.payload_encoder)
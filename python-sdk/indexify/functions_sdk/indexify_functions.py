@@ -18,6 +18,7 @@ from pydantic import BaseModel
 from typing_extensions import get_type_hints
 
 from .data_objects import IndexifyData, RouterOutput
+from .graph_definition import GpuRequirement, OutputLimits, RuntimeDescriptor
 from .image import DEFAULT_IMAGE_3_10, Image
 from .object_serializer import CloudPickleSerializer, get_serializer
 
@@ -69,6 +70,13 @@ class IndexifyFunction(ABC):
     placement_constraints: List[PlacementConstraints] = []
     accumulate: Optional[Type[Any]] = None
     payload_encoder: Optional[str] = "cloudpickle"
+    gpu: Optional[GpuRequirement] = None
+    concurrency_key: Optional[str] = None
+    runtime: Optional[RuntimeDescriptor] = None
+    output_limits: Optional[OutputLimits] = None
+    # Environments executors keep loaded to start tasks without loading the
+    # function, for functions with spiky traffic
+    min_warm_instances: int = 0
 
     @abstractmethod
     def run(self, *args, **kwargs) -> Union[List[Any], Any]:
@@ -90,6 +98,11 @@ class IndexifyRouter(ABC):
     image: Optional[Image] = DEFAULT_IMAGE_3_10
     placement_constraints: List[PlacementConstraints] = []
     payload_encoder: Optional[str] = "cloudpickle"
+    # Path of a WASM build of the router which the server runs instead of an executor
+    wasm_module: Optional[str] = None
+    # What happens to the branch when the router fails: fail_invocation,
+    # route_to_default or skip
+    on_error: str = "fail_invocation"
 
     @abstractmethod
     def run(self, *args, **kwargs) -> Optional[List[IndexifyFunction]]:
@@ -102,6 +115,8 @@ def indexify_router(
     image: Optional[Image] = DEFAULT_IMAGE_3_10,
     placement_constraints: List[PlacementConstraints] = [],
     payload_encoder: Optional[str] = "cloudpickle",
+    wasm_module: Optional[str] = None,
+    on_error: str = "fail_invocation",
 ):
     def construct(fn):
         args = locals().copy()
@@ -125,6 +140,8 @@ def indexify_router(
 
         IndexifyRo.image = image
         IndexifyRo.payload_encoder = payload_encoder
+        IndexifyRo.wasm_module = wasm_module
+        IndexifyRo.on_error = on_error
         return IndexifyRo
 
     return construct
@@ -137,6 +154,11 @@ def indexify_function(
     accumulate: Optional[Type[BaseModel]] = None,
     payload_encoder: Optional[str] = "cloudpickle",
     placement_constraints: List[PlacementConstraints] = [],
+    gpu: Optional[GpuRequirement] = None,
+    concurrency_key: Optional[str] = None,
+    runtime: Optional[RuntimeDescriptor] = None,
+    output_limits: Optional[OutputLimits] = None,
+    min_warm_instances: int = 0,
 ):
     def construct(fn):
         args = locals().copy()
@@ -161,6 +183,11 @@ def indexify_function(
         IndexifyFn.image = image
         IndexifyFn.accumulate = accumulate
         IndexifyFn.payload_encoder = payload_encoder
+        IndexifyFn.gpu = gpu
+        IndexifyFn.concurrency_key = concurrency_key
+        IndexifyFn.runtime = runtime
+        IndexifyFn.output_limits = output_limits
+        IndexifyFn.min_warm_instances = min_warm_instances
         return IndexifyFn
 
     return construct
@@ -224,9 +251,16 @@ class IndexifyFunctionWrapper:
         return extracted_data if isinstance(extracted_data, list) else [extracted_data]
 
     def invoke_fn_ser(
-        self, name: str, input: IndexifyData, acc: Optional[Any] = None
+        self,
+        name: str,
+        input: Union[IndexifyData, List[IndexifyData]],
+        acc: Optional[Any] = None,
     ) -> List[IndexifyData]:
-        input = self.deserialize_input(name, input)
+        if isinstance(input, list):
+            # The inputs of a closed window are passed together as a list
+            input = [self.deserialize_input(name, item, batch=True) for item in input]
+        else:
+            input = self.deserialize_input(name, input)
         serializer = get_serializer(self.indexify_function.payload_encoder)
         if acc is not None:
             acc = self.indexify_function.accumulate.model_validate(
@@ -245,7 +279,9 @@ class IndexifyFunctionWrapper:
         input = self.deserialize_input(name, input)
         return RouterOutput(edges=self.run_router(input))
 
-    def deserialize_input(self, compute_fn: str, indexify_data: IndexifyData) -> Any:
+    def deserialize_input(
+        self, compute_fn: str, indexify_data: IndexifyData, batch: bool = False
+    ) -> Any:
         if self.indexify_function.payload_encoder == "cloudpickle":
             return CloudPickleSerializer.deserialize(indexify_data.payload)
         payload = msgpack.unpackb(indexify_data.payload)
@@ -266,6 +302,9 @@ class IndexifyFunctionWrapper:
         arg_name, arg_type = next(iter(arg_types.items()))
         if arg_type is None:
             raise ValueError(f"Argument {arg_name} has no type annotation")
+        # Items of a batch are validated against the type of the list
+        if batch and get_origin(arg_type) is list:
+            arg_type = get_args(arg_type)[0]
         if is_pydantic_model_from_annotation(arg_type):
             if len(payload.keys()) == 1 and isinstance(list(payload.values())[0], dict):
                 payload = list(payload.values())[0]
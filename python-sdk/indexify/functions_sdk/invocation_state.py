@@ -0,0 +1,135 @@
+from abc import ABC, abstractmethod
+from contextlib import contextmanager
+from contextvars import ContextVar
+from typing import Any, Dict, Optional
+from urllib.parse import quote
+
+import httpx
+from pydantic import BaseModel
+
+from indexify.settings import auth_headers
+
+
+class InvocationStateEntry(BaseModel):
+    key: str
+    value: Any
+    # Incremented by every write of the key
+    version: int
+
+
+class InvocationState(ABC):
+    """
+    Scratch space shared by the functions of an invocation, for small bits of
+    state which are not passed along as payloads. Values must be JSON
+    serializable and are deleted with the invocation.
+    """
+
+    @abstractmethod
+    def get_entry(self, key: str) -> Optional[InvocationStateEntry]:
+        pass
+
+    @abstractmethod
+    def _set(
+        self, key: str, value: Any, expected_version: Optional[int]
+    ) -> Optional[InvocationStateEntry]:
+        pass
+
+    def get(self, key: str, default: Any = None) -> Any:
+        entry = self.get_entry(key)
+        return default if entry is None else entry.value
+
+    def set(self, key: str, value: Any) -> InvocationStateEntry:
+        return self._set(key, value, None)
+
+    def compare_and_swap(
+        self, key: str, value: Any, expected_version: int
+    ) -> Optional[InvocationStateEntry]:
+        """
+        Writes the value only if the key is at the expected version, 0 if it
+        must not exist yet. Returns None if the version doesn't match.
+        """
+        return self._set(key, value, expected_version)
+
+
+class LocalInvocationState(InvocationState):
+    """Scratch space of invocations run locally by Graph.run."""
+
+    def __init__(self):
+        self._entries: Dict[str, InvocationStateEntry] = {}
+
+    def get_entry(self, key: str) -> Optional[InvocationStateEntry]:
+        return self._entries.get(key)
+
+    def _set(
+        self, key: str, value: Any, expected_version: Optional[int]
+    ) -> Optional[InvocationStateEntry]:
+        current = self._entries.get(key)
+        version = current.version if current is not None else 0
+        if expected_version is not None and expected_version != version:
+            return None
+        entry = InvocationStateEntry(key=key, value=value, version=version + 1)
+        self._entries[key] = entry
+        return entry
+
+
+class RemoteInvocationState(InvocationState):
+    """Scratch space of an invocation stored by the server."""
+
+    def __init__(
+        self, base_url: str, namespace: str, compute_graph: str, invocation_id: str
+    ):
+        self._url = f"{base_url}/internal/namespaces/{namespace}/compute_graphs/{compute_graph}/invocations/{invocation_id}/state"
+
+    def get_entry(self, key: str) -> Optional[InvocationStateEntry]:
+        response = httpx.get(
+            f"{self._url}/{quote(key, safe='')}", headers=auth_headers()
+        )
+        if response.status_code == 404:
+            return None
+        response.raise_for_status()
+        return InvocationStateEntry.model_validate(response.json())
+
+    def _set(
+        self, key: str, value: Any, expected_version: Optional[int]
+    ) -> Optional[InvocationStateEntry]:
+        response = httpx.post(
+            f"{self._url}/{quote(key, safe='')}",
+            json={"value": value, "expected_version": expected_version},
+            headers=auth_headers(),
+        )
+        if response.status_code == 409:
+            return None
+        response.raise_for_status()
+        return InvocationStateEntry.model_validate(response.json())
+
+
+_current_state: ContextVar[Optional[InvocationState]] = ContextVar(
+    "indexify_invocation_state", default=None
+)
+
+
+@contextmanager
+def invocation_state_scope(state: InvocationState):
+    token = _current_state.set(state)
+    try:
+        yield state
+    finally:
+        _current_state.reset(token)
+
+
+def invocation_state() -> InvocationState:
+    """
+    Scratch space of the invocation the calling function runs in.
+
+    Example:
+    @indexify_function()
+    def count(x: int) -> int:
+        state = invocation_state()
+        entry = state.get_entry("seen")
+        state.compare_and_swap("seen", (entry.value if entry else 0) + 1, entry.version if entry else 0)
+        return x
+    """
+    state = _current_state.get()
+    if state is None:
+        raise RuntimeError("invocation state is only available while a function runs")
+    return state
@@ -0,0 +1,40 @@
+from contextlib import contextmanager
+from contextvars import ContextVar
+from typing import Any, Dict, Optional
+
+_current_parameters: ContextVar[Optional[Dict[str, Any]]] = ContextVar(
+    "indexify_graph_parameters", default=None
+)
+
+_MISSING = object()
+
+
+@contextmanager
+def parameters_scope(parameters: Dict[str, Any]):
+    token = _current_parameters.set(parameters)
+    try:
+        yield parameters
+    finally:
+        _current_parameters.reset(token)
+
+
+def get_parameter(name: str, default: Any = _MISSING) -> Any:
+    """
+    Value of a parameter of the graph for the invocation the calling function
+    runs in, resolved by the server from the invocation, the values bound to
+    the graph and the declared defaults.
+
+    Example:
+    @indexify_function()
+    def embed(text: str) -> List[float]:
+        model = load_model(get_parameter("model"))
+        return model.embed(text)
+    """
+    parameters = _current_parameters.get()
+    if parameters is None:
+        raise RuntimeError("parameters are only available while a function runs")
+    if name in parameters:
+        return parameters[name]
+    if default is _MISSING:
+        raise KeyError(f"parameter {name} has no value")
+    return default
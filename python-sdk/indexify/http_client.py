@@ -7,14 +7,22 @@ import httpx
 import msgpack
 import yaml
 from httpx_sse import connect_sse
-from pydantic import BaseModel, Json
+from pydantic import BaseModel, Field, Json
 from rich import print
 
 from indexify.error import ApiException
 from indexify.functions_sdk.data_objects import IndexifyData
 from indexify.functions_sdk.graph import ComputeGraphMetadata, Graph
 from indexify.functions_sdk.indexify_functions import IndexifyFunction
-from indexify.settings import DEFAULT_SERVICE_URL, DEFAULT_SERVICE_URL_HTTPS
+from indexify.functions_sdk.object_serializer import (
+    MsgPackSerializer,
+    get_serializer,
+)
+from indexify.settings import (
+    DEFAULT_SERVICE_URL,
+    DEFAULT_SERVICE_URL_HTTPS,
+    auth_headers,
+)
 
 
 class InvocationEventPayload(BaseModel):
@@ -33,18 +41,128 @@ class InvocationEvent(BaseModel):
 class GraphOutputMetadata(BaseModel):
     id: str
     compute_fn: str
+    # The payload was deleted past the retention of the graph
+    expired: bool = False
+
+
+class PlannedTask(BaseModel):
+    compute_fn: str
+    kind: str
+    parents: List[str]
+    dynamic: bool
+    reducer: bool
+    sub_graph: Optional[str] = None
+
+
+class InvocationPlan(BaseModel):
+    tasks: List[PlannedTask]
+    warnings: List[str]
+
+
+class LintWarning(BaseModel):
+    rule: str
+    node: Optional[str] = None
+    message: str
+
+
+class GraphLint(BaseModel):
+    compute_graph: str
+    warnings: List[LintWarning]
+
+
+class InvocationSummary(BaseModel):
+    id: str
+    payload_size: int
+    payload_sha_256: str
+    labels: Dict[str, Any] = {}
+    attributes: Dict[str, Any] = {}
+
+
+class InvocationSearchResults(BaseModel):
+    invocations: List[InvocationSummary]
+    cursor: Optional[str] = None
 
 
 class GraphOutputs(BaseModel):
     outputs: List[GraphOutputMetadata]
 
 
+class InvocationError(BaseModel):
+    compute_fn: str
+    task_id: str
+
+
+class InvocationResult(BaseModel):
+    id: str
+    status: str
+    outputs: List[GraphOutputMetadata]
+    errors: List[InvocationError]
+    created_at: int
+    finished_at: int
+    duration_ms: int
+
+
+class InlineFnOutput(BaseModel):
+    compute_fn: str
+    id: str
+    # Base64 encoded bytes of the output
+    data: str
+
+
+class SyncInvocationResult(BaseModel):
+    id: str
+    # False when the timeout elapsed before the invocation finished
+    finished: bool
+    result: Optional[InvocationResult] = None
+    inline_outputs: List[InlineFnOutput] = []
+
+
+class TaskAnalytics(BaseModel):
+    pending_tasks: int
+    successful_tasks: int
+    failed_tasks: int
+
+
+class TopologyNode(BaseModel):
+    name: str
+    kind: str
+    reducer: bool
+    sub_graph: Optional[str] = None
+    router_targets: List[str]
+    analytics: TaskAnalytics
+    executed: Optional[bool] = None
+
+
+class TopologyEdge(BaseModel):
+    from_: str = Field(alias="from")
+    to: str
+    dynamic: bool
+    executed: Optional[bool] = None
+
+
+class GraphTopology(BaseModel):
+    compute_graph: str
+    version: int
+    start_node: str
+    nodes: List[TopologyNode]
+    edges: List[TopologyEdge]
+    invocation_id: Optional[str] = None
+
+
+class PayloadUrl(BaseModel):
+    url: str
+    expires_at: int
+    size: int
+    sha256_hash: str
+
+
 class IndexifyClient:
     def __init__(
         self,
         service_url: str = DEFAULT_SERVICE_URL,
         config_path: Optional[str] = None,
         namespace: str = "default",
+        api_token: Optional[str] = None,
         **kwargs,
     ):
         if os.environ.get("INDEXIFY_URL"):
@@ -52,7 +170,11 @@ class IndexifyClient:
             service_url = os.environ["INDEXIFY_URL"]
 
         self.service_url = service_url
-        self._client = httpx.Client()
+        # Falls back to the token of INDEXIFY_API_TOKEN
+        self._headers = (
+            {"Authorization": f"Bearer {api_token}"} if api_token else auth_headers()
+        )
+        self._client = httpx.Client(headers=self._headers)
         if config_path:
             with open(config_path, "r") as file:
                 config = yaml.safe_load(file)
@@ -63,6 +185,7 @@ class IndexifyClient:
                     http2=True,
                     cert=(tls_config["cert_path"], tls_config["key_path"]),
                     verify=tls_config.get("ca_bundle_path", True),
+                    headers=self._headers,
                 )
 
         self.namespace: str = namespace
@@ -73,9 +196,13 @@ class IndexifyClient:
         self._graphs: Dict[str, Graph] = {}
         self._fns: Dict[str, IndexifyFunction] = {}
 
-    def _request(self, method: str, **kwargs) -> httpx.Response:
+    def _request(
+        self, method: str, timeout: Optional[float] = None, **kwargs
+    ) -> httpx.Response:
         try:
-            response = self._client.request(method, timeout=self._timeout, **kwargs)
+            response = self._client.request(
+                method, timeout=timeout or self._timeout, **kwargs
+            )
             status_code = str(response.status_code)
             if status_code.startswith("4"):
                 raise ApiException(
@@ -160,19 +287,200 @@ class IndexifyClient:
     def __exit__(self, exc_type, exc_value, traceback):
         self.close()
 
-    def register_compute_graph(self, graph: Graph, additional_modules):
+    def register_compute_graph(
+        self,
+        graph: Graph,
+        additional_modules,
+        expected_revision: Optional[int] = None,
+        force: bool = False,
+        canary_percent: Optional[int] = None,
+    ):
+        """
+        Registers or updates a graph. Updates are rejected if the graph was
+        changed since expected_revision, which defaults to the revision the
+        graph is at when this method is called. force skips the check.
+
+        With canary_percent, the update is published as a canary receiving
+        that percentage of the new invocations until it is finalized or
+        rolled back.
+        """
         graph_metadata = graph.definition()
         serialized_code = cloudpickle.dumps(graph.serialize(additional_modules))
+        if expected_revision is None and not force:
+            expected_revision = self._graph_revision(graph.name)
+        data = {
+            "compute_graph": graph_metadata.model_dump_json(exclude_none=True),
+            "force": "true" if force else "false",
+        }
+        if expected_revision is not None:
+            data["expected_revision"] = str(expected_revision)
+        if canary_percent is not None:
+            data["canary_percent"] = str(canary_percent)
+        files = {"code": serialized_code}
+        for name, node in graph.nodes.items():
+            wasm_module = getattr(node, "wasm_module", None)
+            if wasm_module:
+                with open(wasm_module, "rb") as f:
+                    files[f"wasm.{name}"] = f.read()
         response = self._post(
             f"namespaces/{self.namespace}/compute_graphs",
-            files={"code": serialized_code},
-            data={"compute_graph": graph_metadata.model_dump_json(exclude_none=True)},
+            files=files,
+            data=data,
         )
         response.raise_for_status()
         self._graphs[graph.name] = graph
         for fn_name, fn in graph.nodes.items():
             self._fns[f"{graph.name}/{fn_name}"] = fn
 
+    def apply_manifest(
+        self,
+        graphs: List[Graph],
+        additional_modules=[],
+        force: bool = False,
+    ):
+        """
+        Registers or updates several graphs at once, none of them is applied
+        if any is invalid or was changed since the revision it is at when this
+        method is called. force skips the revision checks.
+        """
+        manifest_graphs = []
+        files = {}
+        for graph in graphs:
+            manifest_graph = {
+                "compute_graph": json.loads(
+                    graph.definition().model_dump_json(exclude_none=True)
+                ),
+                "force": force,
+            }
+            if not force:
+                manifest_graph["expected_revision"] = self._graph_revision(graph.name)
+            manifest_graphs.append(manifest_graph)
+            files[f"code.{graph.name}"] = cloudpickle.dumps(
+                graph.serialize(additional_modules)
+            )
+            for name, node in graph.nodes.items():
+                wasm_module = getattr(node, "wasm_module", None)
+                if wasm_module:
+                    with open(wasm_module, "rb") as f:
+                        files[f"wasm.{graph.name}.{name}"] = f.read()
+        response = self._post(
+            f"namespaces/{self.namespace}/manifests",
+            files=files,
+            data={"manifest": json.dumps({"graphs": manifest_graphs})},
+        )
+        response.raise_for_status()
+        for graph in graphs:
+            self._graphs[graph.name] = graph
+            for fn_name, fn in graph.nodes.items():
+                self._fns[f"{graph.name}/{fn_name}"] = fn
+
+    def graph_canary(self, name: str) -> dict:
+        return self._get(
+            f"namespaces/{self.namespace}/compute_graphs/{name}/canary"
+        ).json()
+
+    def set_canary_percent(self, name: str, percent: int):
+        self._put(
+            f"namespaces/{self.namespace}/compute_graphs/{name}/canary",
+            json={"percent": percent},
+        )
+
+    def finalize_canary(self, name: str):
+        self._post(f"namespaces/{self.namespace}/compute_graphs/{name}/canary/finalize")
+
+    def rollback_canary(self, name: str):
+        self._delete(f"namespaces/{self.namespace}/compute_graphs/{name}/canary")
+
+    def diff_run(self, graph: str, **kwargs) -> Dict[str, Any]:
+        """
+        Invokes the current and the canary version of the graph with the same
+        input, returns the ids of both invocations to diff once they finish.
+        """
+        response = self._post(
+            f"namespaces/{self.namespace}/compute_graphs/{graph}/diff_runs",
+            headers={"Content-Type": "application/cbor"},
+            data=cloudpickle.dumps(kwargs),
+        )
+        return response.json()
+
+    def diff_invocations(self, graph: str, base: str, head: str) -> Dict[str, Any]:
+        """
+        Compares the terminal outputs of two finished invocations of the graph
+        by hash and size.
+        """
+        response = self._get(
+            f"namespaces/{self.namespace}/compute_graphs/{graph}/invocations/diff",
+            params={"base": base, "head": head},
+        )
+        return response.json()
+
+    def pause_graph(self, name: str, reject_invocations: bool = False):
+        """
+        Stops allocating the tasks of the graph until it is resumed, new
+        invocations are still accepted unless reject_invocations is set.
+        """
+        self._post(
+            f"namespaces/{self.namespace}/compute_graphs/{name}/pause",
+            json={"reject_invocations": reject_invocations},
+        )
+
+    def resume_graph(self, name: str):
+        self._post(f"namespaces/{self.namespace}/compute_graphs/{name}/resume")
+
+    def _legal_hold_endpoint(self, graph: str, invocation_id: Optional[str]) -> str:
+        endpoint = f"namespaces/{self.namespace}/compute_graphs/{graph}"
+        if invocation_id is not None:
+            endpoint += f"/invocations/{invocation_id}"
+        return f"{endpoint}/legal_hold"
+
+    def set_legal_hold(
+        self, graph: str, reason: str, invocation_id: Optional[str] = None
+    ):
+        """
+        Holds an invocation, or every invocation of the graph when
+        invocation_id is unset. Held data doesn't expire and can't be deleted
+        until the hold is cleared.
+        """
+        self._put(
+            self._legal_hold_endpoint(graph, invocation_id), json={"reason": reason}
+        )
+
+    def clear_legal_hold(
+        self, graph: str, reason: str = "", invocation_id: Optional[str] = None
+    ):
+        self._delete(
+            self._legal_hold_endpoint(graph, invocation_id), params={"reason": reason}
+        )
+
+    def legal_holds(self) -> List[Dict[str, Any]]:
+        response = self._get(f"namespaces/{self.namespace}/legal_holds")
+        return response.json()["holds"]
+
+    def delete_ingested_data(
+        self, graph: str, invocation_id: str, mode: str = "soft"
+    ) -> Dict[str, Any]:
+        """
+        Deletes the ingested payload of a finished invocation and every output
+        derived from it. The soft mode keeps tombstones of the payloads, the
+        hard mode removes the invocations too. Returns the report of the
+        deletion, which tracks the removal of its blobs.
+        """
+        return self._delete(
+            f"namespaces/{self.namespace}/compute_graphs/{graph}/invocations/{invocation_id}/data",
+            params={"mode": mode},
+        ).json()
+
+    def data_deletion(self, graph: str, invocation_id: str) -> Dict[str, Any]:
+        return self._get(
+            f"namespaces/{self.namespace}/compute_graphs/{graph}/invocations/{invocation_id}/data_deletion"
+        ).json()
+
+    def _graph_revision(self, name: str) -> Optional[int]:
+        try:
+            return self.graph(name).revision
+        except ApiException:
+            return None
+
     def graphs(self) -> List[str]:
         response = self._get(f"graphs")
         return response.json()["graphs"]
@@ -196,6 +504,17 @@ class IndexifyClient:
             namespaces.append(item["name"])
         return namespaces
 
+    def can_i(self, permission: str, namespace: Optional[str] = None) -> bool:
+        """
+        Whether the client's token has a permission, read, write, admin or
+        execute, in a namespace or in all namespaces when unset.
+        """
+        params = {"permission": permission}
+        if namespace is not None:
+            params["namespace"] = namespace
+        response = self._get("can_i", params=params)
+        return response.json()["allowed"]
+
     @classmethod
     def new_namespace(
         cls, namespace: str, server_addr: Optional[str] = "http://localhost:8900"
@@ -219,6 +538,17 @@ class IndexifyClient:
     def create_namespace(self, namespace: str):
         self._post("namespaces", json={"name": namespace})
 
+    def namespace_deletion(self, namespace: str) -> dict:
+        """Returns the confirmation token to delete the namespace, or the
+        progress of its deletion."""
+        return self._get(f"namespaces/{namespace}/deletion").json()
+
+    def delete_namespace(self, namespace: str, confirmation_token: str) -> dict:
+        return self._delete(
+            f"namespaces/{namespace}",
+            params={"confirmation_token": confirmation_token},
+        ).json()
+
     def logs(
         self, invocation_id: str, cg_name: str, fn_name: str, file: str
     ) -> Optional[str]:
@@ -232,15 +562,204 @@ class IndexifyClient:
             print(f"failed to fetch logs: {e}")
             return None
 
+    def get_task_logs(
+        self,
+        invocation_id: str,
+        cg_name: str,
+        fn_name: str,
+        task_id: str,
+        file: str,
+        byte_range: Optional[str] = None,
+    ) -> Optional[bytes]:
+        headers = {"Range": byte_range} if byte_range else {}
+        try:
+            response = self._get(
+                f"namespaces/{self.namespace}/compute_graphs/{cg_name}/invocations/{invocation_id}/fn/{fn_name}/tasks/{task_id}/logs/{file}",
+                headers=headers,
+            )
+            response.raise_for_status()
+            return response.content
+        except ApiException as e:
+            print(f"failed to fetch task logs: {e}")
+            return None
+
+    def plan_invocation(
+        self,
+        graph: str,
+        content_type: str = "application/cbor",
+        size: Optional[int] = None,
+    ) -> InvocationPlan:
+        response = self._post(
+            f"namespaces/{self.namespace}/compute_graphs/{graph}/plan",
+            json={"content_type": content_type, "size": size},
+        )
+        return InvocationPlan(**response.json())
+
+    def lint_graph(self, graph: Graph) -> GraphLint:
+        """
+        Best practice warnings of a graph, before registering it. Raises if
+        the graph would be rejected when registered.
+        """
+        response = self._post(
+            f"namespaces/{self.namespace}/lint",
+            content=graph.definition().model_dump_json(exclude_none=True),
+            headers={"Content-Type": "application/json"},
+        )
+        return GraphLint(**response.json())
+
+    def search_invocations(
+        self,
+        graph: str,
+        filter: str,
+        limit: Optional[int] = None,
+        cursor: Optional[str] = None,
+    ) -> InvocationSearchResults:
+        """
+        Invocations whose attributes match the filter, e.g. `doc_type=invoice,amount>=100`.
+        """
+        params = {"filter": filter}
+        if limit is not None:
+            params["limit"] = limit
+        if cursor is not None:
+            params["cursor"] = cursor
+        response = self._get(
+            f"namespaces/{self.namespace}/compute_graphs/{graph}/invocations/search",
+            params=params,
+        )
+        return InvocationSearchResults(**response.json())
+
+    def invocation_result(self, graph: str, invocation_id: str) -> InvocationResult:
+        """
+        Result of a finished invocation, raises an ApiException with status 404
+        until it finishes.
+        """
+        response = self._get(
+            f"namespaces/{self.namespace}/compute_graphs/{graph}/invocations/{invocation_id}/result"
+        )
+        return InvocationResult(**response.json())
+
+    def get_invocation_events(
+        self, graph: str, invocation_id: str, limit: Optional[int] = None
+    ) -> List[dict]:
+        """
+        Timeline of an invocation, e.g. the tasks it created, the executors
+        they were allocated to and their outcomes, oldest first.
+        """
+        params = {"limit": limit} if limit is not None else None
+        response = self._get(
+            f"namespaces/{self.namespace}/compute_graphs/{graph}/invocations/{invocation_id}/events",
+            params=params,
+        )
+        return response.json()["events"]
+
+    def replay_invocation(
+        self,
+        graph: str,
+        invocation_id: str,
+        as_of: Optional[int] = None,
+        state_change_id: Optional[int] = None,
+    ) -> dict:
+        """
+        State of an invocation, its task statuses and analytics, as of a time
+        in milliseconds since the epoch or once a state change was processed.
+        """
+        params = {}
+        if as_of is not None:
+            params["as_of"] = as_of
+        if state_change_id is not None:
+            params["state_change_id"] = state_change_id
+        response = self._get(
+            f"namespaces/{self.namespace}/compute_graphs/{graph}/invocations/{invocation_id}/replay",
+            params=params,
+        )
+        return response.json()
+
+    def invocation_eta(self, graph: str, invocation_id: str) -> dict:
+        """
+        Estimated milliseconds left before an invocation finishes, with the
+        queue positions of its pending tasks.
+        """
+        response = self._get(
+            f"namespaces/{self.namespace}/compute_graphs/{graph}/invocations/{invocation_id}/eta"
+        )
+        return response.json()
+
+    def graph_topology(
+        self, graph: str, invocation_id: Optional[str] = None
+    ) -> GraphTopology:
+        """
+        Nodes and edges of a graph along with the task analytics of its nodes.
+        With an invocation id, the analytics are the invocation's and the nodes
+        and edges it ran are marked as executed.
+        """
+        params = {"invocation_id": invocation_id} if invocation_id else None
+        response = self._get(
+            f"namespaces/{self.namespace}/compute_graphs/{graph}/topology",
+            params=params,
+        )
+        return GraphTopology(**response.json())
+
+    def payload_url(
+        self,
+        graph: str,
+        invocation_id: str,
+        fn_name: Optional[str] = None,
+        output_id: Optional[str] = None,
+        expires_in_secs: Optional[int] = None,
+    ) -> PayloadUrl:
+        """
+        Short lived url of the input of an invocation, or of a function output when
+        `fn_name` and `output_id` are set. Payloads which are not stored in S3 are
+        streamed by the server.
+        """
+        response = self._post(
+            f"namespaces/{self.namespace}/payloads/url",
+            json={
+                "compute_graph": graph,
+                "invocation_id": invocation_id,
+                "fn_name": fn_name,
+                "output_id": output_id,
+                "expires_in_secs": expires_in_secs,
+            },
+        )
+        payload_url = PayloadUrl(**response.json())
+        if payload_url.url.startswith("/"):
+            payload_url.url = f"{self.service_url}{payload_url.url}"
+        return payload_url
+
     def rerun_graph(self, graph: str):
         self._post(f"namespaces/{self.namespace}/compute_graphs/{graph}/rerun")
 
     def invoke_graph_with_object(
-        self, graph: str, block_until_done: bool = False, **kwargs
+        self,
+        graph: str,
+        block_until_done: bool = False,
+        labels: Optional[Dict[str, Any]] = None,
+        attributes: Optional[Dict[str, Any]] = None,
+        priority: Optional[int] = None,
+        invocation_group: Optional[str] = None,
+        parameters: Optional[Dict[str, Any]] = None,
+        event_time: Optional[int] = None,
+        lane: Optional[str] = None,
+        **kwargs,
     ) -> str:
         ser_input = cloudpickle.dumps(kwargs)
         params = {"block_until_finish": block_until_done}
-        with httpx.Client() as client:
+        if labels:
+            params["labels"] = json.dumps(labels)
+        if attributes:
+            params["attributes"] = json.dumps(attributes)
+        if priority is not None:
+            params["priority"] = priority
+        if invocation_group is not None:
+            params["invocation_group"] = invocation_group
+        if parameters:
+            params["parameters"] = json.dumps(parameters)
+        if event_time is not None:
+            params["event_time"] = event_time
+        if lane is not None:
+            params["lane"] = lane
+        with httpx.Client(headers=self._headers) as client:
             with connect_sse(
                 client,
                 "POST",
@@ -289,6 +808,122 @@ class IndexifyClient:
                         )
         raise Exception("invocation ID not returned")
 
+    def invoke_graph_sync(
+        self,
+        graph: str,
+        timeout_secs: Optional[int] = None,
+        labels: Optional[Dict[str, Any]] = None,
+        priority: Optional[int] = None,
+        parameters: Optional[Dict[str, Any]] = None,
+        **kwargs,
+    ) -> SyncInvocationResult:
+        """
+        Invokes a graph and waits for it to finish, returning its result and the
+        terminal outputs small enough to be sent inline. `finished` is False
+        when the timeout elapsed first, the invocation keeps running.
+        """
+        params = {}
+        if timeout_secs is not None:
+            params["timeout_secs"] = timeout_secs
+        if labels:
+            params["labels"] = json.dumps(labels)
+        if priority is not None:
+            params["priority"] = priority
+        if parameters:
+            params["parameters"] = json.dumps(parameters)
+        response = self._post(
+            f"namespaces/{self.namespace}/compute_graphs/{graph}/invoke_sync",
+            headers={"Content-Type": "application/cbor"},
+            content=cloudpickle.dumps(kwargs),
+            params=params,
+            # The server bounds the wait, the request only needs to outlive it
+            timeout=None if timeout_secs is None else timeout_secs + 10,
+        )
+        return SyncInvocationResult(**response.json())
+
+    def signal_invocation(
+        self,
+        graph: str,
+        invocation_id: str,
+        gate: str,
+        payload: Any,
+        encoder: str = "cloudpickle",
+    ):
+        """
+        Opens a gate of an invocation, the payload is the input of the nodes
+        after the gate.
+        """
+        data = IndexifyData(
+            payload=get_serializer(encoder).serialize(payload),
+            payload_encoding=encoder,
+        )
+        self._post(
+            f"namespaces/{self.namespace}/compute_graphs/{graph}/invocations/{invocation_id}/gates/{gate}/signal",
+            headers={"Content-Type": "application/octet-stream"},
+            content=MsgPackSerializer.serialize(data),
+        )
+
+    def close_window(self, graph: str, window: str):
+        """
+        Closes a window before it's full, the inputs waiting in it are passed
+        together to the nodes after the window.
+        """
+        self._post(
+            f"namespaces/{self.namespace}/compute_graphs/{graph}/windows/{window}/close"
+        )
+
+    def instantiate_graph(
+        self,
+        template: str,
+        name: str,
+        parameters: Optional[Dict[str, Any]] = None,
+        description: Optional[str] = None,
+        expected_revision: Optional[int] = None,
+    ):
+        """
+        Creates the graph `name` from the graph `template` with the values of
+        its parameters bound, the instance shares the code of the template.
+        Pass the current revision of the instance to update its values.
+        """
+        body = {"name": name, "parameters": parameters or {}}
+        if description is not None:
+            body["description"] = description
+        if expected_revision is not None:
+            body["expected_revision"] = expected_revision
+        self._post(
+            f"namespaces/{self.namespace}/compute_graphs/{template}/instantiate",
+            json=body,
+        )
+
+    def create_invocation_group(
+        self,
+        id: Optional[str] = None,
+        labels: Optional[Dict[str, Any]] = None,
+    ) -> dict:
+        """
+        Creates a group tracking a batch of invocations together, invocations
+        join it with the invocation_group parameter. A random id is generated
+        if none is given.
+        """
+        body = {"labels": labels or {}}
+        if id is not None:
+            body["id"] = id
+        return self._post(
+            f"namespaces/{self.namespace}/invocation_groups", json=body
+        ).json()
+
+    def close_invocation_group(self, id: str) -> dict:
+        """
+        Closes the group to new invocations, it finishes once all its
+        invocations finished.
+        """
+        return self._post(
+            f"namespaces/{self.namespace}/invocation_groups/{id}/close"
+        ).json()
+
+    def invocation_group(self, id: str) -> dict:
+        return self._get(f"namespaces/{self.namespace}/invocation_groups/{id}").json()
+
     def _download_output(
         self,
         namespace: str,
@@ -330,6 +965,10 @@ class IndexifyClient:
         outputs = []
         for output in graph_outputs.outputs:
             if output.compute_fn == fn_name:
+                if output.expired:
+                    raise ValueError(
+                        f"output {output.id} of {fn_name} expired past the retention of graph {graph}"
+                    )
                 indexify_data = self._download_output(
                     self.namespace, graph, invocation_id, fn_name, output.id
                 )
@@ -1,4 +1,4 @@
-from typing import Any, List, Optional
+from typing import Any, Dict, List, Optional
 
 from indexify.functions_sdk.graph import Graph
 
@@ -15,10 +15,28 @@ class RemoteGraph:
         self._name = name
         self._client = IndexifyClient(service_url=server_url)
 
-    def run(self, block_until_done: bool = False, **kwargs) -> str:
+    def run(
+        self,
+        block_until_done: bool = False,
+        labels: Optional[Dict[str, Any]] = None,
+        attributes: Optional[Dict[str, Any]] = None,
+        priority: Optional[int] = None,
+        invocation_group: Optional[str] = None,
+        parameters: Optional[Dict[str, Any]] = None,
+        event_time: Optional[int] = None,
+        lane: Optional[str] = None,
+        **kwargs,
+    ) -> str:
         """
         Run the graph with the given inputs. The input is for the start function of the graph.
         :param block_until_done: If True, the function will block until the graph execution is complete.
+        :param labels: Metadata attached to the invocation and its tasks, e.g. a customer id.
+        :param attributes: Indexed metadata the invocation can be searched by, strings, numbers or booleans.
+        :param priority: Tasks of higher priority invocations are scheduled first and can pre-empt lower priority tasks if the server enables it. Defaults to 0.
+        :param invocation_group: Id of an open invocation group the invocation joins.
+        :param parameters: Overrides of the parameters of the graph for this invocation.
+        :param event_time: When the input was produced, in milliseconds since the epoch. Event-time windows group their inputs by it.
+        :param lane: Scheduling lane of the invocation, "latency" or "batch" for replays and backfills. Defaults to the lane of the graph.
         :param kwargs: The input to the start function of the graph. Pass the input as keyword arguments.
         :return: The invocation ID of the graph execution.
 
@@ -30,7 +48,16 @@ class RemoteGraph:
         invocation_id = remote_graph.run(x=1)
         """
         return self._client.invoke_graph_with_object(
-            self._name, block_until_done, **kwargs
+            self._name,
+            block_until_done,
+            labels,
+            attributes,
+            priority,
+            invocation_group,
+            parameters,
+            event_time,
+            lane,
+            **kwargs,
         )
 
     def rerun(self):
@@ -46,14 +73,27 @@ class RemoteGraph:
         g: Graph,
         additional_modules=[],
         server_url: Optional[str] = "http://localhost:8900",
+        expected_revision: Optional[int] = None,
+        force: bool = False,
+        canary_percent: Optional[int] = None,
     ):
         """
         Create a new RemoteGraph from a local Graph object.
         :param g: The local Graph object.
         :param server_url: The URL of the server where the graph will be registered.
+        :param expected_revision: Revision of the registered graph this update is based on,
+            defaults to its current revision.
+        :param force: Update the graph even if it was changed since expected_revision.
+        :param canary_percent: Publish the update as a canary receiving this percentage of the new invocations.
         """
         client = IndexifyClient(service_url=server_url)
-        client.register_compute_graph(g, additional_modules)
+        client.register_compute_graph(
+            g,
+            additional_modules,
+            expected_revision=expected_revision,
+            force=force,
+            canary_percent=canary_percent,
+        )
         return cls(name=g.name, server_url=server_url)
 
     @classmethod
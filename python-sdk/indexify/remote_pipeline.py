@@ -13,12 +13,19 @@ class RemotePipeline(RemoteGraph):
         p: Pipeline,
         additional_modules=[],
         server_url: Optional[str] = "http://localhost:8900",
+        expected_revision: Optional[int] = None,
+        force: bool = False,
     ):
         """
         Create a new RemoteGraph from a local Graph object.
         :param g: The local Graph object.
         :param server_url: The URL of the server where the graph will be registered.
+        :param expected_revision: Revision of the registered graph this update is based on,
+            defaults to its current revision.
+        :param force: Update the graph even if it was changed since expected_revision.
         """
         client = IndexifyClient(service_url=server_url)
-        client.register_compute_graph(p._graph, additional_modules)
+        client.register_compute_graph(
+            p._graph, additional_modules, expected_revision=expected_revision, force=force
+        )
         return cls(name=p._graph.name, server_url=server_url)
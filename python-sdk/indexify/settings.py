@@ -1,2 +1,37 @@
+import os
+from contextlib import contextmanager
+from contextvars import ContextVar
+from typing import Dict, Optional
+
 DEFAULT_SERVICE_URL = "http://localhost:8900"
 DEFAULT_SERVICE_URL_HTTPS = "https://localhost:8900"
+
+
+_task_token: ContextVar[Optional[str]] = ContextVar(
+    "indexify_task_token", default=None
+)
+
+
+@contextmanager
+def task_token_scope(token: Optional[str]):
+    """
+    Token of the task a function runs, sent by the server with the task and
+    scoped to its namespace and invocation.
+    """
+    reset_token = _task_token.set(token)
+    try:
+        yield
+    finally:
+        _task_token.reset(reset_token)
+
+
+def auth_headers() -> Dict[str, str]:
+    """
+    Authorization header required by servers with auth enabled. Functions
+    call the server with the token of their task, anything else with the
+    token in INDEXIFY_API_TOKEN.
+    """
+    token = _task_token.get() or os.environ.get("INDEXIFY_API_TOKEN")
+    if not token:
+        return {}
+    return {"Authorization": f"Bearer {token}"}
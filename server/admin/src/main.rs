@@ -0,0 +1,327 @@
+//! Command line client for the operational apis of an Indexify server.
+
+use anyhow::{anyhow, Result};
+use clap::{Parser, Subcommand};
+use reqwest::Method;
+use serde_json::{Map, Value};
+
+#[derive(Parser)]
+#[command(version, about = "Operate an Indexify server", long_about = None)]
+struct Cli {
+    /// Url of the server's http api
+    #[arg(long, default_value = "http://localhost:8900")]
+    server: String,
+    /// Bearer token of the requests, required by servers with auth enabled
+    #[arg(long, env = "INDEXIFY_API_TOKEN", hide_env_values = true)]
+    token: Option<String>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List, drain or blacklist executors
+    #[command(subcommand)]
+    Executors(ExecutorsCommand),
+    /// Show the context, tasks and events of an invocation
+    Invocation {
+        namespace: String,
+        compute_graph: String,
+        invocation_id: String,
+    },
+    /// List or redrive the webhook events which ran out of delivery attempts
+    #[command(subcommand)]
+    DeadLetters(DeadLettersCommand),
+    /// Snapshot the state store into the blob storage
+    Snapshot,
+    /// Wake up the garbage collector
+    Gc,
+    /// Show how far behind the scheduler is on state changes
+    Lag,
+    /// List the alerts of the alert rules of the server
+    Alerts {
+        /// Only list the firing alerts
+        #[arg(long)]
+        firing: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ExecutorsCommand {
+    List,
+    /// Move the tasks allocated to the executor to other executors
+    Drain {
+        id: String,
+        /// Number of allocations moved at a time
+        #[arg(long)]
+        batch_size: Option<usize>,
+    },
+    /// List the executors blacklisted for functions they failed repeatedly
+    Blacklist,
+    /// Let a blacklisted executor run the function again
+    Unblacklist {
+        namespace: String,
+        compute_graph: String,
+        compute_fn: String,
+        id: String,
+    },
+    /// List the reports of the tasks reconciled when executors came back
+    /// after a crash, of every executor unless one is given
+    Reconciliations { id: Option<String> },
+}
+
+#[derive(Subcommand)]
+enum DeadLettersCommand {
+    List {
+        namespace: String,
+    },
+    /// Queue the dead letters for delivery again
+    Redrive {
+        namespace: String,
+    },
+}
+
+/// Http request made by a command, named after the field its response is
+/// printed under when the command makes several requests.
+#[derive(Debug, PartialEq)]
+struct ApiRequest {
+    name: &'static str,
+    method: Method,
+    path: String,
+}
+
+impl ApiRequest {
+    fn get(name: &'static str, path: String) -> Self {
+        Self {
+            name,
+            method: Method::GET,
+            path,
+        }
+    }
+
+    fn post(name: &'static str, path: String) -> Self {
+        Self {
+            name,
+            method: Method::POST,
+            path,
+        }
+    }
+
+    fn delete(name: &'static str, path: String) -> Self {
+        Self {
+            name,
+            method: Method::DELETE,
+            path,
+        }
+    }
+}
+
+impl Command {
+    fn requests(&self) -> Vec<ApiRequest> {
+        match self {
+            Command::Executors(ExecutorsCommand::List) => {
+                vec![ApiRequest::get(
+                    "executors",
+                    "/internal/executors".to_string(),
+                )]
+            }
+            Command::Executors(ExecutorsCommand::Drain { id, batch_size }) => {
+                let mut path = format!("/internal/executors/{}/requeue", id);
+                if let Some(batch_size) = batch_size {
+                    path.push_str(&format!("?batch_size={}", batch_size));
+                }
+                vec![ApiRequest::post("requeue", path)]
+            }
+            Command::Executors(ExecutorsCommand::Blacklist) => vec![ApiRequest::get(
+                "blacklist",
+                "/internal/executor_blacklist".to_string(),
+            )],
+            Command::Executors(ExecutorsCommand::Unblacklist {
+                namespace,
+                compute_graph,
+                compute_fn,
+                id,
+            }) => vec![ApiRequest::delete(
+                "unblacklist",
+                format!(
+                    "/internal/executor_blacklist/{}/{}/{}/{}",
+                    namespace, compute_graph, compute_fn, id
+                ),
+            )],
+            Command::Executors(ExecutorsCommand::Reconciliations { id }) => {
+                let path = match id {
+                    Some(id) => format!("/internal/executors/{}/reconciliations", id),
+                    None => "/internal/executor_reconciliations".to_string(),
+                };
+                vec![ApiRequest::get("reconciliations", path)]
+            }
+            Command::Invocation {
+                namespace,
+                compute_graph,
+                invocation_id,
+            } => {
+                let prefix = format!(
+                    "/namespaces/{}/compute_graphs/{}/invocations/{}",
+                    namespace, compute_graph, invocation_id
+                );
+                vec![
+                    ApiRequest::get("context", format!("{}/context", prefix)),
+                    ApiRequest::get("tasks", format!("{}/tasks", prefix)),
+                    ApiRequest::get("events", format!("{}/events", prefix)),
+                ]
+            }
+            Command::DeadLetters(DeadLettersCommand::List { namespace }) => vec![ApiRequest::get(
+                "dead_letters",
+                format!("/namespaces/{}/webhooks/dead_letters", namespace),
+            )],
+            Command::DeadLetters(DeadLettersCommand::Redrive { namespace }) => {
+                vec![ApiRequest::post(
+                    "redrive",
+                    format!("/namespaces/{}/webhooks/dead_letters/redrive", namespace),
+                )]
+            }
+            Command::Snapshot => vec![ApiRequest::post(
+                "snapshot",
+                "/internal/snapshots".to_string(),
+            )],
+            Command::Gc => vec![ApiRequest::post("gc", "/internal/gc".to_string())],
+            Command::Lag => vec![ApiRequest::get(
+                "lag",
+                "/internal/state_changes/lag".to_string(),
+            )],
+            Command::Alerts { firing } => {
+                let mut path = "/internal/alerts".to_string();
+                if *firing {
+                    path.push_str("?state=firing");
+                }
+                vec![ApiRequest::get("alerts", path)]
+            }
+        }
+    }
+}
+
+async fn send(cli: &Cli, client: &reqwest::Client, request: &ApiRequest) -> Result<Value> {
+    let url = format!("{}{}", cli.server.trim_end_matches('/'), request.path);
+    let mut builder = client.request(request.method.clone(), &url);
+    if let Some(token) = &cli.token {
+        builder = builder.bearer_auth(token);
+    }
+    let response = builder.send().await?;
+    let status = response.status();
+    let body = response.text().await?;
+    if !status.is_success() {
+        return Err(anyhow!(
+            "{} {} failed with {}: {}",
+            request.method,
+            url,
+            status,
+            body
+        ));
+    }
+    if body.is_empty() {
+        return Ok(Value::Null);
+    }
+    Ok(serde_json::from_str(&body).unwrap_or(Value::String(body)))
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let client = reqwest::Client::new();
+    let requests = cli.command.requests();
+    let output = if let [request] = requests.as_slice() {
+        send(&cli, &client, request).await?
+    } else {
+        let mut responses = Map::new();
+        for request in &requests {
+            responses.insert(
+                request.name.to_string(),
+                send(&cli, &client, request).await?,
+            );
+        }
+        Value::Object(responses)
+    };
+    if !output.is_null() {
+        println!("{}", serde_json::to_string_pretty(&output)?);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn requests(args: &[&str]) -> Vec<ApiRequest> {
+        let cli = Cli::try_parse_from(["indexify-admin"].iter().chain(args)).unwrap();
+        cli.command.requests()
+    }
+
+    #[test]
+    fn test_command_requests() {
+        assert_eq!(
+            requests(&["executors", "drain", "executor_1", "--batch-size", "5"]),
+            vec![ApiRequest::post(
+                "requeue",
+                "/internal/executors/executor_1/requeue?batch_size=5".to_string()
+            )]
+        );
+        assert_eq!(
+            requests(&[
+                "executors",
+                "unblacklist",
+                "ns",
+                "graph",
+                "fn",
+                "executor_1"
+            ]),
+            vec![ApiRequest::delete(
+                "unblacklist",
+                "/internal/executor_blacklist/ns/graph/fn/executor_1".to_string()
+            )]
+        );
+        assert_eq!(
+            requests(&["executors", "reconciliations", "executor_1"]),
+            vec![ApiRequest::get(
+                "reconciliations",
+                "/internal/executors/executor_1/reconciliations".to_string()
+            )]
+        );
+        assert_eq!(
+            requests(&["invocation", "ns", "graph", "inv"]),
+            vec![
+                ApiRequest::get(
+                    "context",
+                    "/namespaces/ns/compute_graphs/graph/invocations/inv/context".to_string()
+                ),
+                ApiRequest::get(
+                    "tasks",
+                    "/namespaces/ns/compute_graphs/graph/invocations/inv/tasks".to_string()
+                ),
+                ApiRequest::get(
+                    "events",
+                    "/namespaces/ns/compute_graphs/graph/invocations/inv/events".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            requests(&["dead-letters", "redrive", "ns"]),
+            vec![ApiRequest::post(
+                "redrive",
+                "/namespaces/ns/webhooks/dead_letters/redrive".to_string()
+            )]
+        );
+        assert_eq!(
+            requests(&["alerts", "--firing"]),
+            vec![ApiRequest::get(
+                "alerts",
+                "/internal/alerts?state=firing".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_token() {
+        let cli = Cli::try_parse_from(["indexify-admin", "--token", "secret", "gc"]).unwrap();
+        assert_eq!(cli.token.as_deref(), Some("secret"));
+    }
+}
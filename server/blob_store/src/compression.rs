@@ -0,0 +1,50 @@
+//! Zstd compression of blobs. Blobs are compressed while they're streamed to
+//! the storage, and decompressed while they're read back, so neither is held
+//! in memory whole.
+
+use std::io::Write;
+
+use anyhow::Result;
+use bytes::Bytes;
+use futures::{stream::BoxStream, Stream, StreamExt};
+
+/// Compresses a stream of bytes, the compressed bytes are sent as soon as the
+/// encoder flushes them.
+pub fn encode<'a>(
+    mut data: impl Stream<Item = Result<Bytes>> + Send + Unpin + 'a,
+    level: i32,
+) -> impl Stream<Item = Result<Bytes>> + Send + 'a {
+    async_stream::try_stream! {
+        let mut encoder = zstd::stream::write::Encoder::new(Vec::new(), level)?;
+        while let Some(chunk) = data.next().await {
+            encoder.write_all(&chunk?)?;
+            let compressed = std::mem::take(encoder.get_mut());
+            if !compressed.is_empty() {
+                yield Bytes::from(compressed);
+            }
+        }
+        yield Bytes::from(encoder.finish()?);
+    }
+}
+
+/// Decompresses a stream of bytes compressed by `encode`.
+pub fn decode(
+    mut data: BoxStream<'static, Result<Bytes>>,
+) -> BoxStream<'static, Result<Bytes>> {
+    async_stream::try_stream! {
+        let mut decoder = zstd::stream::write::Decoder::new(Vec::new())?;
+        while let Some(chunk) = data.next().await {
+            decoder.write_all(&chunk?)?;
+            let decompressed = std::mem::take(decoder.get_mut());
+            if !decompressed.is_empty() {
+                yield Bytes::from(decompressed);
+            }
+        }
+        decoder.flush()?;
+        let rest = std::mem::take(decoder.get_mut());
+        if !rest.is_empty() {
+            yield Bytes::from(rest);
+        }
+    }
+    .boxed()
+}
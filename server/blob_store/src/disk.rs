@@ -1,3 +1,5 @@
+use std::ops::Range;
+
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
 use bytes::Bytes;
@@ -40,4 +42,13 @@ impl BlobStorageReader for DiskFileReader {
         });
         Ok(Box::pin(UnboundedReceiverStream::new(rx)))
     }
+
+    async fn get_range(&self, range: Range<usize>) -> Result<Bytes> {
+        let file_path = self.file_path.trim_start_matches("file://").to_string();
+        let client = LocalFileSystem::new();
+        client
+            .get_range(&file_path.clone().into(), range)
+            .await
+            .map_err(|e| anyhow!("Failed to read file: {:?}, error: {}", file_path, e))
+    }
 }
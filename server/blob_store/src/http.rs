@@ -1,3 +1,5 @@
+use std::ops::Range;
+
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
 use bytes::Bytes;
@@ -21,7 +23,7 @@ impl HttpReader {
 impl BlobStorageReader for HttpReader {
     async fn get(&self) -> Result<BoxStream<'static, Result<Bytes>>> {
         let client = reqwest::Client::new();
-        let response = client.get(&self.url).send().await?;
+        let response = client.get(&self.url).send().await?.error_for_status()?;
         let stream = async_stream::stream! {
             let mut stream = response.bytes_stream();
             while let Some(chunk) = stream.next().await {
@@ -30,4 +32,21 @@ impl BlobStorageReader for HttpReader {
         };
         Ok(Box::pin(stream))
     }
+
+    async fn get_range(&self, range: Range<usize>) -> Result<Bytes> {
+        if range.is_empty() {
+            return Ok(Bytes::new());
+        }
+        let client = reqwest::Client::new();
+        let response = client
+            .get(&self.url)
+            .header(
+                reqwest::header::RANGE,
+                format!("bytes={}-{}", range.start, range.end - 1),
+            )
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(response.bytes().await?)
+    }
 }
@@ -1,4 +1,4 @@
-use std::{env, fmt::Debug, sync::Arc};
+use std::{collections::HashMap, env, fmt::Debug, ops::Range, sync::Arc, time::Duration};
 
 use anyhow::{anyhow, Context, Result};
 use async_trait::async_trait;
@@ -7,15 +7,19 @@ use futures::{stream::BoxStream, StreamExt};
 use object_store::{
     aws::{AmazonS3, AmazonS3Builder},
     local,
+    signer::Signer,
+    GetOptions,
     ObjectStore,
     WriteMultipart,
 };
+use reqwest::Method;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use tokio::io::AsyncWrite;
 
 use self::{disk::DiskFileReader, s3::S3FileReader};
 
+pub mod compression;
 pub mod disk;
 pub mod http;
 pub mod s3;
@@ -37,6 +41,26 @@ pub struct DiskStorageConfig {
 pub struct BlobStorageConfig {
     pub s3: Option<S3Config>,
     pub disk: Option<DiskStorageConfig>,
+    /// Cheaper backend old payloads are moved to by storage lifecycle
+    /// policies
+    #[serde(default)]
+    pub cold: Option<ColdStorageConfig>,
+    /// Backends of other regions payloads are replicated to by the
+    /// replication policies of namespaces, by region
+    #[serde(default)]
+    pub replicas: HashMap<String, ReplicaStorageConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColdStorageConfig {
+    pub s3: Option<S3Config>,
+    pub disk: Option<DiskStorageConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplicaStorageConfig {
+    pub s3: Option<S3Config>,
+    pub disk: Option<DiskStorageConfig>,
 }
 
 impl BlobStorageConfig {
@@ -46,6 +70,8 @@ impl BlobStorageConfig {
             disk: Some(DiskStorageConfig {
                 path: path.to_string(),
             }),
+            cold: None,
+            replicas: HashMap::new(),
         }
     }
 }
@@ -58,6 +84,8 @@ impl Default for BlobStorageConfig {
             disk: Some(DiskStorageConfig {
                 path: blob_store_path.to_str().unwrap().to_string(),
             }),
+            cold: None,
+            replicas: HashMap::new(),
         }
     }
 }
@@ -67,6 +95,37 @@ pub struct PutResult {
     pub url: String,
     pub size_bytes: u64,
     pub sha256_hash: String,
+    /// Set when the blob was stored compressed, the size and hash of the
+    /// result are the ones of the uncompressed bytes then
+    pub compressed: Option<CompressedBlob>,
+}
+
+/// Size and hash of the bytes of a blob stored compressed.
+#[derive(Debug, Clone)]
+pub struct CompressedBlob {
+    pub size_bytes: u64,
+    pub sha256_hash: String,
+}
+
+/// Object of the blob storage, as described by its metadata.
+#[derive(Debug, Clone)]
+pub struct BlobObject {
+    /// Url of the object, pinned to its version in versioned buckets
+    pub url: String,
+    pub size_bytes: u64,
+    pub e_tag: Option<String>,
+    pub version: Option<String>,
+}
+
+// Query of the urls of S3 objects pinned to a version
+const VERSION_QUERY: &str = "?versionId=";
+
+/// Splits the version an S3 url is pinned to off the url.
+pub fn split_version(url: &str) -> (&str, Option<&str>) {
+    match url.split_once(VERSION_QUERY) {
+        Some((url, version)) => (url, Some(version)),
+        None => (url, None),
+    }
 }
 
 #[async_trait]
@@ -87,12 +146,18 @@ pub trait BlobStoragePartWriter {
 #[async_trait]
 pub trait BlobStorageReader {
     async fn get(&self) -> Result<BoxStream<'static, Result<Bytes>>>;
+    async fn get_range(&self, range: Range<usize>) -> Result<Bytes>;
 }
 
 #[derive(Clone)]
 pub struct BlobStorage {
     object_store: Arc<dyn ObjectStore>,
+    // Same store as `object_store` when blobs are stored in S3, used to sign
+    // urls
+    s3: Option<Arc<AmazonS3>>,
     config: BlobStorageConfig,
+    cold: Option<Arc<BlobStorage>>,
+    replicas: HashMap<String, Arc<BlobStorage>>,
 }
 
 pub struct StoragePartWriter {
@@ -126,9 +191,12 @@ fn file_storage(disk: DiskStorageConfig) -> Result<local::LocalFileSystem> {
 
 impl BlobStorage {
     pub fn new(config: BlobStorageConfig) -> Result<Self> {
-        let object_store: Arc<dyn ObjectStore> = if let Some(s3) = config.s3.as_ref() {
-            let s = s3_storage(s3)?;
-            Arc::new(s)
+        let s3 = match config.s3.as_ref() {
+            Some(s3) => Some(Arc::new(s3_storage(s3)?)),
+            None => None,
+        };
+        let object_store: Arc<dyn ObjectStore> = if let Some(s3) = &s3 {
+            s3.clone()
         } else {
             // If it's not S3, assume it's a file
             let s = file_storage(config.disk.clone().unwrap_or_else(|| DiskStorageConfig {
@@ -136,12 +204,90 @@ impl BlobStorage {
             }))?;
             Arc::new(s)
         };
+        let cold = match &config.cold {
+            Some(cold) => Some(Arc::new(BlobStorage::new(BlobStorageConfig {
+                s3: cold.s3.clone(),
+                disk: cold.disk.clone(),
+                cold: None,
+                replicas: HashMap::new(),
+            })?)),
+            None => None,
+        };
+        let mut replicas = HashMap::new();
+        for (region, replica) in &config.replicas {
+            let storage = BlobStorage::new(BlobStorageConfig {
+                s3: replica.s3.clone(),
+                disk: replica.disk.clone(),
+                cold: None,
+                replicas: HashMap::new(),
+            })?;
+            replicas.insert(region.clone(), Arc::new(storage));
+        }
         Ok(Self {
             object_store,
+            s3,
             config,
+            cold,
+            replicas,
         })
     }
 
+    /// Storage of the cold tier, when one is configured.
+    pub fn cold(&self) -> Option<&BlobStorage> {
+        self.cold.as_deref()
+    }
+
+    /// Storage of the replicas of a region, when one is configured.
+    pub fn replica(&self, region: &str) -> Option<&BlobStorage> {
+        self.replicas.get(region).map(|replica| replica.as_ref())
+    }
+
+    /// Key of a blob url of this storage, `None` for blobs stored elsewhere.
+    fn object_key<'a>(&self, url: &'a str) -> Option<&'a str> {
+        if let Some(s3) = &self.config.s3 {
+            return parse_s3_url(url)
+                .ok()
+                .filter(|(bucket, _)| *bucket == s3.bucket)
+                .map(|(_, key)| key);
+        }
+        let prefix = format!("file://{}/", self.config.disk.as_ref()?.path);
+        url.strip_prefix(prefix.as_str())
+    }
+
+    // Blobs of the cold tier and of the replicas are read, deleted and
+    // signed by their own storage
+    fn storage_for(&self, url: &str) -> Option<&BlobStorage> {
+        if self.object_key(url).is_some() {
+            return None;
+        }
+        self.cold()
+            .into_iter()
+            .chain(self.replicas.values().map(|replica| replica.as_ref()))
+            .find(|storage| storage.object_key(url).is_some())
+    }
+
+    /// Copies a blob of the hot tier to the cold tier under the same key.
+    /// The blob isn't deleted from the hot tier.
+    pub async fn archive(&self, url: &str) -> Result<PutResult> {
+        let cold = self.cold().ok_or(anyhow!("no cold storage configured"))?;
+        copy_blob(self, cold, url).await
+    }
+
+    /// Copies a blob of the cold tier back to the hot tier.
+    pub async fn rehydrate(&self, url: &str) -> Result<PutResult> {
+        let cold = self.cold().ok_or(anyhow!("no cold storage configured"))?;
+        copy_blob(cold, self, url).await
+    }
+
+    /// Copies a blob of the hot tier to the storage of the replicas of a
+    /// region under the same key.
+    pub async fn replicate(&self, url: &str, region: &str) -> Result<PutResult> {
+        let replica = self
+            .replica(region)
+            .ok_or(anyhow!("no replica storage configured for region {}", region))?;
+        copy_blob(self, replica, url).await
+    }
+
     pub async fn put(
         &self,
         key: &str,
@@ -172,9 +318,61 @@ impl BlobStorage {
             url: self.path_url(&path),
             size_bytes,
             sha256_hash: hash,
+            compressed: None,
+        })
+    }
+
+    /// Writes a blob compressed with zstd when it's at least `min_size_bytes`
+    /// long, smaller blobs are written as they are.
+    pub async fn put_compressed(
+        &self,
+        key: &str,
+        mut data: impl futures::Stream<Item = Result<Bytes>> + Send + Unpin,
+        min_size_bytes: u64,
+        level: i32,
+    ) -> Result<PutResult, anyhow::Error> {
+        // Bytes are buffered until the blob is known to reach the threshold
+        let mut head = BytesMut::new();
+        while (head.len() as u64) < min_size_bytes {
+            match data.next().await {
+                Some(chunk) => head.extend_from_slice(&chunk?),
+                None => {
+                    return self
+                        .put(key, futures::stream::iter(vec![Ok(head.freeze())]))
+                        .await
+                }
+            }
+        }
+        let mut hasher = Sha256::new();
+        let mut size_bytes = 0;
+        let uncompressed = futures::stream::iter(vec![Ok(head.freeze())])
+            .chain(data)
+            .map(|item| {
+                item.map(|bytes| {
+                    hasher.update(&bytes);
+                    size_bytes += bytes.len() as u64;
+                    bytes
+                })
+            });
+        let stored = self
+            .put(key, Box::pin(compression::encode(uncompressed, level)))
+            .await?;
+        Ok(PutResult {
+            url: stored.url,
+            size_bytes,
+            sha256_hash: format!("{:x}", hasher.finalize()),
+            compressed: Some(CompressedBlob {
+                size_bytes: stored.size_bytes,
+                sha256_hash: stored.sha256_hash,
+            }),
         })
     }
 
+    /// Url of the blob `put` writes under the key.
+    pub fn key_url(&self, key: &str) -> String {
+        self.path_url(&object_store::path::Path::from(key))
+    }
+
     pub fn path_url(&self, path: &object_store::path::Path) -> String {
         if let Some(s3) = &self.config.s3 {
             format!("s3://{}/{}", s3.bucket, path)
@@ -189,11 +387,15 @@ impl BlobStorage {
     }
 
     pub fn get(&self, key: &str) -> BlobStorageReaderTS {
+        if let Some(storage) = self.storage_for(key) {
+            return storage.get(key);
+        }
         if key.starts_with("s3://") {
-            let (bucket, key) = parse_s3_url(key)
+            let (url, version) = split_version(key);
+            let (bucket, key) = parse_s3_url(url)
                 .map_err(|err| anyhow::anyhow!("unable to parse s3 url: {}", err))
                 .unwrap();
-            return Arc::new(S3FileReader::new(bucket, key, &self.config));
+            return Arc::new(S3FileReader::new(bucket, key, &self.config).with_version(version));
         }
 
         if key.starts_with("http") {
@@ -204,27 +406,100 @@ impl BlobStorage {
         Arc::new(DiskFileReader::new(key))
     }
 
+    /// Deletes a blob, deleting a blob which doesn't exist succeeds.
     pub async fn delete(&self, key: &str) -> Result<()> {
+        if let Some(storage) = self.storage_for(key) {
+            return Box::pin(storage.delete(key)).await;
+        }
         if let Some(s3) = &self.config.s3 {
             let (bucket, key) = parse_s3_url(key)
                 .map_err(|err| anyhow::anyhow!("unable to parse s3 url: {}", err))?;
             if bucket != s3.bucket {
                 return Err(anyhow!("invalid bucket {}", bucket));
             }
-            let path = object_store::path::Path::from(key);
-            self.object_store.delete(&path).await?;
-            return Ok(());
+            return self.delete_path(key).await;
         } else {
             let prefix = format!("file://{}/", self.config.disk.as_ref().unwrap().path);
             if let Some(key) = key.strip_prefix(prefix.as_str()) {
-                let path = object_store::path::Path::from(key);
-                self.object_store.delete(&path).await?;
-                return Ok(());
+                return self.delete_path(key).await;
             }
         }
         Err(anyhow!("invalid key {}", key))
     }
 
+    async fn delete_path(&self, key: &str) -> Result<()> {
+        let path = object_store::path::Path::from(key);
+        match self.object_store.delete(&path).await {
+            Ok(()) | Err(object_store::Error::NotFound { .. }) => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Presigned url to download a blob straight from S3, `None` when the blob
+    /// is not stored in S3.
+    pub async fn signed_url(&self, key: &str, expires_in: Duration) -> Result<Option<String>> {
+        if let Some(storage) = self.storage_for(key) {
+            return Box::pin(storage.signed_url(key, expires_in)).await;
+        }
+        let (Some(s3), Some(s3_config)) = (&self.s3, &self.config.s3) else {
+            return Ok(None);
+        };
+        // Presigned urls can't pin a version, the server streams those blobs
+        if split_version(key).1.is_some() {
+            return Ok(None);
+        }
+        let Ok((bucket, key)) = parse_s3_url(key) else {
+            return Ok(None);
+        };
+        if bucket != s3_config.bucket {
+            return Err(anyhow!("invalid bucket {}", bucket));
+        }
+        let path = object_store::path::Path::from(key);
+        let url = s3.signed_url(Method::GET, &path, expires_in).await?;
+        Ok(Some(url.to_string()))
+    }
+
+    /// Metadata of an object of this storage, used to register objects as
+    /// payloads without copying them. The url of the object is pinned to its
+    /// current version when the bucket is versioned.
+    pub async fn head(&self, url: &str) -> Result<BlobObject> {
+        let (url, version) = split_version(url);
+        let key = self
+            .object_key(url)
+            .ok_or(anyhow!("{} is not in the blob storage", url))?;
+        let options = GetOptions {
+            head: true,
+            version: version.map(|version| version.to_string()),
+            ..Default::default()
+        };
+        let meta = self
+            .object_store
+            .get_opts(&object_store::path::Path::from(key), options)
+            .await
+            .map_err(|e| anyhow!("can't find {}: {}", url, e))?
+            .meta;
+        let url = match (&meta.version, &self.config.s3) {
+            (Some(version), Some(_)) => format!("{}{}{}", url, VERSION_QUERY, version),
+            _ => url.to_string(),
+        };
+        Ok(BlobObject {
+            url,
+            size_bytes: meta.size as u64,
+            e_tag: meta.e_tag,
+            version: meta.version,
+        })
+    }
+
+    /// Reads the blob `put` wrote under the key, `None` when there's none.
+    pub async fn read_key(&self, key: &str) -> Result<Option<Bytes>> {
+        let path = object_store::path::Path::from(key);
+        match self.object_store.get(&path).await {
+            Ok(result) => Ok(Some(result.bytes().await?)),
+            Err(object_store::Error::NotFound { .. }) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
     pub async fn read_bytes(&self, key: &str) -> Result<Bytes> {
         let reader = self.get(key);
         let mut stream = reader.get().await?;
@@ -234,6 +509,21 @@ impl BlobStorage {
         }
         Ok(bytes.into())
     }
+
+    /// Reads a blob written compressed by `put_compressed`, decompressed.
+    pub async fn read_decompressed_bytes(&self, key: &str) -> Result<Bytes> {
+        let compressed = self.read_bytes(key).await?;
+        Ok(zstd::decode_all(compressed.as_ref())?.into())
+    }
+}
+
+async fn copy_blob(from: &BlobStorage, to: &BlobStorage, url: &str) -> Result<PutResult> {
+    let key = from.object_key(url).ok_or(anyhow!(
+        "blob {} is not in the storage it's copied from",
+        url
+    ))?;
+    let data = from.get(url).get().await?;
+    to.put(key, data).await
 }
 
 fn parse_s3_url(s3_url: &str) -> Result<(&str, &str), &str> {
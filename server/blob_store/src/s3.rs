@@ -1,10 +1,10 @@
-use std::{env, sync::Arc};
+use std::{env, ops::Range, sync::Arc};
 
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
 use bytes::Bytes;
 use futures::{stream::BoxStream, StreamExt};
-use object_store::{aws::AmazonS3Builder, ObjectStore};
+use object_store::{aws::AmazonS3Builder, GetOptions, ObjectStore};
 use tokio::sync::mpsc;
 use tokio_stream::wrappers::UnboundedReceiverStream;
 
@@ -13,6 +13,7 @@ use super::{BlobStorageConfig, BlobStorageReader};
 pub struct S3FileReader {
     client: Arc<dyn ObjectStore>,
     key: String,
+    version: Option<String>,
 }
 
 impl S3FileReader {
@@ -33,6 +34,20 @@ impl S3FileReader {
         S3FileReader {
             client: Arc::new(client),
             key: key.to_string(),
+            version: None,
+        }
+    }
+
+    /// Reads the version of the object instead of its latest version.
+    pub fn with_version(mut self, version: Option<&str>) -> Self {
+        self.version = version.map(|version| version.to_string());
+        self
+    }
+
+    fn options(&self) -> GetOptions {
+        GetOptions {
+            version: self.version.clone(),
+            ..Default::default()
         }
     }
 }
@@ -44,7 +59,7 @@ impl BlobStorageReader for S3FileReader {
         let (tx, rx) = mpsc::unbounded_channel();
         let key = self.key.clone();
         let get_result = client_clone
-            .get(&key.clone().into())
+            .get_opts(&key.clone().into(), self.options())
             .await
             .map_err(|e| anyhow!("can't get s3 object {:?}: {:?}", key.clone(), e))?;
         tokio::spawn(async move {
@@ -58,4 +73,18 @@ impl BlobStorageReader for S3FileReader {
         });
         Ok(Box::pin(UnboundedReceiverStream::new(rx)))
     }
+
+    async fn get_range(&self, range: Range<usize>) -> Result<Bytes> {
+        let options = GetOptions {
+            range: Some(range.into()),
+            ..self.options()
+        };
+        self.client
+            .get_opts(&self.key.clone().into(), options)
+            .await
+            .map_err(|e| anyhow!("can't get s3 object {:?}: {:?}", self.key, e))?
+            .bytes()
+            .await
+            .map_err(|e| anyhow!("can't get s3 object {:?}: {:?}", self.key, e))
+    }
 }
@@ -11,5 +11,7 @@ fn main() -> Result<(), Box<dyn Error>> {
         .add_instructions(&si)?
         .emit()?;
 
+    tonic_build::compile_protos("proto/indexify.proto")?;
+
     Ok(())
 }
@@ -0,0 +1,268 @@
+//! Alerting rules over the built-in metrics of the server, e.g. the failed
+//! task rate of a compute graph over 10 minutes above 5%. The leader
+//! evaluates the rules periodically. An alert fires once its rule held for
+//! the pending duration of the rule, and resolves as soon as it stops
+//! holding.
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertMetric {
+    /// Fraction of the tasks of the graph finished in the window which
+    /// failed
+    FailedTaskRate,
+    /// Tasks of the graph which failed in the window
+    FailedTasks,
+    /// Tasks of the graph waiting for an executor
+    UnallocatedTasks,
+    /// State changes the leader hasn't processed yet
+    StateChangeLag,
+    /// Age of the oldest unprocessed state change, in milliseconds
+    StateChangeLagMs,
+    /// Executors registered with the server
+    Executors,
+}
+
+impl AlertMetric {
+    /// Whether the metric is measured on a compute graph, the other metrics
+    /// are measured on the cluster.
+    pub fn of_graph(&self) -> bool {
+        matches!(
+            self,
+            AlertMetric::FailedTaskRate | AlertMetric::FailedTasks | AlertMetric::UnallocatedTasks
+        )
+    }
+
+    /// Whether the metric is measured over a window of time.
+    pub fn windowed(&self) -> bool {
+        matches!(self, AlertMetric::FailedTaskRate | AlertMetric::FailedTasks)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum AlertComparison {
+    #[serde(rename = ">")]
+    Above,
+    #[serde(rename = ">=")]
+    AtLeast,
+    #[serde(rename = "<")]
+    Below,
+    #[serde(rename = "<=")]
+    AtMost,
+}
+
+impl AlertComparison {
+    pub fn holds(&self, value: f64, threshold: f64) -> bool {
+        match self {
+            AlertComparison::Above => value > threshold,
+            AlertComparison::AtLeast => value >= threshold,
+            AlertComparison::Below => value < threshold,
+            AlertComparison::AtMost => value <= threshold,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AlertRule {
+    /// Unique name of the rule, alerts are identified by it
+    pub name: String,
+    pub metric: AlertMetric,
+    /// Graph the metric is measured on, required by the metrics of a graph
+    #[serde(default)]
+    pub namespace: Option<String>,
+    #[serde(default)]
+    pub compute_graph: Option<String>,
+    /// Window of the metrics measured over a window of time
+    #[serde(default)]
+    pub window_secs: Option<u64>,
+    pub comparison: AlertComparison,
+    pub threshold: f64,
+    /// How long the rule must hold before the alert fires
+    #[serde(default)]
+    pub for_secs: u64,
+}
+
+impl AlertRule {
+    pub fn validate(&self) -> Result<()> {
+        if self.name.is_empty() {
+            return Err(anyhow!("alert rules must have a name"));
+        }
+        let scoped = self.namespace.is_some() && self.compute_graph.is_some();
+        let unscoped = self.namespace.is_none() && self.compute_graph.is_none();
+        if self.metric.of_graph() && !scoped {
+            return Err(anyhow!(
+                "alert rule {} must set the namespace and compute_graph of its metric",
+                self.name
+            ));
+        }
+        if !self.metric.of_graph() && !unscoped {
+            return Err(anyhow!(
+                "alert rule {} measures a metric of the cluster, it can't set a namespace or compute_graph",
+                self.name
+            ));
+        }
+        match (self.metric.windowed(), self.window_secs) {
+            (true, None | Some(0)) => {
+                return Err(anyhow!(
+                    "alert rule {} must set a positive window_secs",
+                    self.name
+                ))
+            }
+            (false, Some(_)) => {
+                return Err(anyhow!(
+                    "alert rule {} measures a metric without a window, it can't set window_secs",
+                    self.name
+                ))
+            }
+            _ => {}
+        }
+        if !self.threshold.is_finite() {
+            return Err(anyhow!("alert rule {} must have a finite threshold", self.name));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertState {
+    Ok,
+    /// The rule holds, for less than its pending duration
+    Pending,
+    Firing,
+}
+
+/// State of the alert of a rule at its last evaluation.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AlertStatus {
+    pub rule: AlertRule,
+    pub state: AlertState,
+    /// `None` while the metric has no value, e.g. no task finished in the
+    /// window of a failed task rate
+    pub value: Option<f64>,
+    /// When the alert entered its state, in milliseconds since the epoch
+    pub since: u64,
+    pub evaluated_at: u64,
+}
+
+impl AlertStatus {
+    /// Status of the alert of the rule given the value of its metric and the
+    /// status of the previous evaluation. Rules never hold for metrics
+    /// without a value.
+    pub fn evaluate(
+        rule: &AlertRule,
+        value: Option<f64>,
+        previous: Option<&AlertStatus>,
+        now: u64,
+    ) -> Self {
+        let holds = value.is_some_and(|value| rule.comparison.holds(value, rule.threshold));
+        let previous_state = previous.map(|previous| (previous.state, previous.since));
+        let (state, since) = match (holds, previous_state) {
+            (false, Some((AlertState::Ok, since))) => (AlertState::Ok, since),
+            (false, _) => (AlertState::Ok, now),
+            (true, Some((AlertState::Firing, since))) => (AlertState::Firing, since),
+            (true, previous_state) => {
+                let pending_since = match previous_state {
+                    Some((AlertState::Pending, since)) => since,
+                    _ => now,
+                };
+                if now.saturating_sub(pending_since) >= rule.for_secs * 1000 {
+                    (AlertState::Firing, now)
+                } else {
+                    (AlertState::Pending, pending_since)
+                }
+            }
+        };
+        Self {
+            rule: rule.clone(),
+            state,
+            value,
+            since,
+            evaluated_at: now,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.rule.name
+    }
+
+    pub fn firing(&self) -> bool {
+        self.state == AlertState::Firing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alert_status() {
+        let rule = AlertRule {
+            name: "failures".to_string(),
+            metric: AlertMetric::FailedTaskRate,
+            namespace: Some("ns".to_string()),
+            compute_graph: Some("graph".to_string()),
+            window_secs: Some(600),
+            comparison: AlertComparison::Above,
+            threshold: 0.05,
+            for_secs: 60,
+        };
+        rule.validate().unwrap();
+
+        let status = AlertStatus::evaluate(&rule, None, None, 0);
+        assert_eq!((status.state, status.since), (AlertState::Ok, 0));
+
+        // Fires once the rule held for its pending duration
+        let status = AlertStatus::evaluate(&rule, Some(0.1), Some(&status), 1000);
+        assert_eq!((status.state, status.since), (AlertState::Pending, 1000));
+        let status = AlertStatus::evaluate(&rule, Some(0.1), Some(&status), 30_000);
+        assert_eq!((status.state, status.since), (AlertState::Pending, 1000));
+        let status = AlertStatus::evaluate(&rule, Some(0.2), Some(&status), 61_000);
+        assert_eq!((status.state, status.since), (AlertState::Firing, 61_000));
+        let status = AlertStatus::evaluate(&rule, Some(0.2), Some(&status), 90_000);
+        assert_eq!((status.state, status.since), (AlertState::Firing, 61_000));
+
+        // Resolves as soon as the rule stops holding
+        let status = AlertStatus::evaluate(&rule, Some(0.05), Some(&status), 120_000);
+        assert_eq!((status.state, status.since), (AlertState::Ok, 120_000));
+        let status = AlertStatus::evaluate(&rule, Some(0.1), Some(&status), 150_000);
+        let status = AlertStatus::evaluate(&rule, None, Some(&status), 180_000);
+        assert_eq!(status.state, AlertState::Ok);
+
+        let lag = AlertRule {
+            name: "lag".to_string(),
+            metric: AlertMetric::StateChangeLag,
+            namespace: None,
+            compute_graph: None,
+            window_secs: None,
+            comparison: AlertComparison::AtLeast,
+            threshold: 1000.0,
+            for_secs: 0,
+        };
+        let status = AlertStatus::evaluate(&lag, Some(1000.0), None, 0);
+        assert!(status.firing());
+
+        for invalid in [
+            AlertRule {
+                compute_graph: None,
+                ..rule.clone()
+            },
+            AlertRule {
+                window_secs: None,
+                ..rule.clone()
+            },
+            AlertRule {
+                namespace: Some("ns".to_string()),
+                ..lag.clone()
+            },
+            AlertRule {
+                window_secs: Some(60),
+                ..lag.clone()
+            },
+        ] {
+            assert!(invalid.validate().is_err());
+        }
+    }
+}
@@ -0,0 +1,234 @@
+//! Keys of the index of invocation attributes.
+//!
+//! An attribute of an invocation is indexed under
+//! `<namespace>|<compute_graph>|<name>|<type><value>\0<invocation_id>`, the
+//! values are encoded so that the keys of an attribute sort by value within a
+//! type. Equality and range filters are then scans of a key range.
+
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use crate::{
+    error::DataModelError,
+    filter::{Condition, Expression, Operator},
+};
+
+const BOOL_TAG: u8 = b'b';
+const NUMBER_TAG: u8 = b'n';
+const STRING_TAG: u8 = b's';
+const SEPARATOR: u8 = 0;
+
+fn invalid(reason: String) -> DataModelError {
+    DataModelError::InvalidAttribute(reason)
+}
+
+/// Attributes are scalars, their names can't contain the key delimiter.
+pub fn validate_attributes(attributes: &HashMap<String, Value>) -> Result<(), DataModelError> {
+    for (name, value) in attributes {
+        if name.is_empty() || name.contains('|') {
+            return Err(invalid(format!("invalid attribute name {:?}", name)));
+        }
+        encode_value(value).map_err(|_| {
+            invalid(format!(
+                "attribute {} must be a string, a number or a boolean",
+                name
+            ))
+        })?;
+        if value.as_str().is_some_and(|s| s.contains('\0')) {
+            return Err(invalid(format!("attribute {} contains a nul byte", name)));
+        }
+    }
+    Ok(())
+}
+
+fn encode_value(value: &Value) -> Result<Vec<u8>, DataModelError> {
+    match value {
+        Value::Bool(b) => Ok(vec![BOOL_TAG, *b as u8]),
+        Value::Number(n) => {
+            let n = n
+                .as_f64()
+                .ok_or_else(|| invalid(format!("invalid number {}", n)))?;
+            // Flipping the sign bit of positive numbers and all the bits of
+            // negative ones makes the big endian bytes sort like the numbers
+            let bits = n.to_bits();
+            let bits = if n.is_sign_negative() {
+                !bits
+            } else {
+                bits | (1 << 63)
+            };
+            let mut encoded = vec![NUMBER_TAG];
+            encoded.extend(bits.to_be_bytes());
+            Ok(encoded)
+        }
+        Value::String(s) => {
+            let mut encoded = vec![STRING_TAG];
+            encoded.extend(s.as_bytes());
+            Ok(encoded)
+        }
+        _ => Err(invalid(format!("unsupported attribute value {}", value))),
+    }
+}
+
+fn name_prefix(namespace: &str, compute_graph: &str, name: &str) -> Vec<u8> {
+    format!("{}|{}|{}|", namespace, compute_graph, name).into_bytes()
+}
+
+/// Prefix of the index keys of all the attributes of a compute graph.
+pub fn graph_prefix(namespace: &str, compute_graph: &str) -> Vec<u8> {
+    format!("{}|{}|", namespace, compute_graph).into_bytes()
+}
+
+pub fn index_key(
+    namespace: &str,
+    compute_graph: &str,
+    name: &str,
+    value: &Value,
+    invocation_id: &str,
+) -> Result<Vec<u8>, DataModelError> {
+    let mut key = name_prefix(namespace, compute_graph, name);
+    key.extend(encode_value(value)?);
+    key.push(SEPARATOR);
+    key.extend(invocation_id.as_bytes());
+    Ok(key)
+}
+
+/// Key ranges, start inclusive and end exclusive, holding the invocations
+/// matching the expression. Values are only compared to values of the same
+/// type.
+pub fn key_ranges(
+    namespace: &str,
+    compute_graph: &str,
+    expression: &Expression,
+) -> Result<Vec<(Vec<u8>, Vec<u8>)>, DataModelError> {
+    let prefix = name_prefix(namespace, compute_graph, &expression.key);
+    let with = |bytes: &[u8]| {
+        let mut key = prefix.clone();
+        key.extend(bytes);
+        key
+    };
+    // Keys of the value followed by the separator, or anything after it
+    let value_range = |value: &Value| -> Result<(Vec<u8>, Vec<u8>), DataModelError> {
+        let encoded = encode_value(value)?;
+        Ok((
+            with(&[encoded.as_slice(), &[SEPARATOR]].concat()),
+            with(&[encoded.as_slice(), &[SEPARATOR + 1]].concat()),
+        ))
+    };
+    let type_range = |value: &Value| -> Result<(Vec<u8>, Vec<u8>), DataModelError> {
+        let tag = encode_value(value)?[0];
+        Ok((with(&[tag]), with(&[tag + 1])))
+    };
+    let ranges = match expression.operator {
+        Operator::Eq => vec![value_range(&expression.value)?],
+        Operator::In => expression
+            .value
+            .as_array()
+            .ok_or_else(|| invalid(format!("invalid filter {}", expression)))?
+            .iter()
+            .map(value_range)
+            .collect::<Result<_, _>>()?,
+        Operator::Gt | Operator::GtEq | Operator::Lt | Operator::LtEq => {
+            let (value_start, value_end) = value_range(&expression.value)?;
+            let (type_start, type_end) = type_range(&expression.value)?;
+            match expression.operator {
+                Operator::Gt => vec![(value_end, type_end)],
+                Operator::GtEq => vec![(value_start, type_end)],
+                Operator::Lt => vec![(type_start, value_start)],
+                _ => vec![(type_start, value_end)],
+            }
+        }
+        _ => {
+            return Err(invalid(format!(
+                "unsupported attribute filter {}, only =, in, >, >=, < and <= are supported",
+                expression
+            )))
+        }
+    };
+    Ok(ranges)
+}
+
+/// Expressions of a filter, which must all match, as long as the filter
+/// only uses conjunctions of expressions.
+pub fn expressions(conditions: &[Condition]) -> Result<Vec<&Expression>, DataModelError> {
+    let mut all = vec![];
+    for condition in conditions {
+        match condition {
+            Condition::Expr(expression) => all.push(expression),
+            Condition::And(conditions) => all.extend(expressions(conditions)?),
+            _ => {
+                return Err(invalid(format!(
+                    "unsupported attribute filter {}, only && of expressions is supported",
+                    condition
+                )))
+            }
+        }
+    }
+    Ok(all)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn matching(expression: &str, values: &[Value]) -> Vec<Value> {
+        let expression = Expression::from_str(expression).unwrap();
+        let ranges = key_ranges("ns", "graph", &expression).unwrap();
+        values
+            .iter()
+            .filter(|value| {
+                let key = index_key("ns", "graph", &expression.key, value, "inv").unwrap();
+                ranges
+                    .iter()
+                    .any(|(start, end)| key >= *start && key < *end)
+            })
+            .cloned()
+            .collect()
+    }
+
+    #[test]
+    fn test_key_ranges() {
+        let values = vec![
+            json!(-10.5),
+            json!(-1),
+            json!(0),
+            json!(2),
+            json!(100),
+            json!("a"),
+            json!("ab"),
+            json!("b"),
+            json!(true),
+        ];
+        assert_eq!(matching("x=2", &values), vec![json!(2)]);
+        assert_eq!(matching("x=a", &values), vec![json!("a")]);
+        assert_eq!(matching("x>0", &values), vec![json!(2), json!(100)]);
+        assert_eq!(matching("x<=-1", &values), vec![json!(-10.5), json!(-1)]);
+        assert_eq!(matching("x>=ab", &values), vec![json!("ab"), json!("b")]);
+        assert_eq!(matching("x<b", &values), vec![json!("a"), json!("ab")]);
+        assert_eq!(
+            matching("x in (a, 100)", &values),
+            vec![json!(100), json!("a")]
+        );
+        assert_eq!(matching("x=true", &values), vec![json!(true)]);
+
+        let expression = Expression::from_str("x!=a").unwrap();
+        assert!(key_ranges("ns", "graph", &expression).is_err());
+    }
+
+    #[test]
+    fn test_validate_attributes() {
+        let valid = HashMap::from([
+            ("doc_type".to_string(), json!("invoice")),
+            ("amount".to_string(), json!(12.5)),
+        ]);
+        assert!(validate_attributes(&valid).is_ok());
+
+        let nested = HashMap::from([("doc".to_string(), json!({"type": "invoice"}))]);
+        assert!(validate_attributes(&nested).is_err());
+
+        let invalid_name = HashMap::from([("doc|type".to_string(), json!("invoice"))]);
+        assert!(validate_attributes(&invalid_name).is_err());
+    }
+}
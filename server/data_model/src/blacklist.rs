@@ -0,0 +1,55 @@
+//! Executors excluded from the placement of the tasks of a function.
+//!
+//! An executor which fails the tasks of a function repeatedly while other
+//! executors run them successfully is blacklisted for the function until the
+//! entry expires, the other functions are still placed on it.
+
+use serde::{Deserialize, Serialize};
+
+use crate::ExecutorId;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ExecutorBlacklistEntry {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub compute_fn: String,
+    pub executor_id: ExecutorId,
+    // Consecutive failures of the function on the executor
+    pub failures: u32,
+    // Milliseconds since the epoch
+    pub blacklisted_at: u64,
+    pub expires_at: u64,
+}
+
+impl ExecutorBlacklistEntry {
+    pub fn key(&self) -> String {
+        Self::key_from(
+            &self.namespace,
+            &self.compute_graph,
+            &self.compute_fn,
+            &self.executor_id,
+        )
+    }
+
+    pub fn key_from(
+        namespace: &str,
+        compute_graph: &str,
+        compute_fn: &str,
+        executor_id: &ExecutorId,
+    ) -> String {
+        format!(
+            "{}{}",
+            Self::fn_prefix(namespace, compute_graph, compute_fn),
+            executor_id
+        )
+    }
+
+    /// Prefix of the keys of the executors blacklisted for a function.
+    pub fn fn_prefix(namespace: &str, compute_graph: &str, compute_fn: &str) -> String {
+        format!("{}|{}|{}|", namespace, compute_graph, compute_fn)
+    }
+
+    pub fn is_active(&self, now: u64) -> bool {
+        now < self.expires_at
+    }
+}
@@ -0,0 +1,75 @@
+//! Canary versions of compute graphs. A new version can be published as a
+//! canary receiving a percentage of the new invocations while the others keep
+//! running on the current version, until the canary is finalized or rolled
+//! back.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{error::DataModelError, ComputeGraph};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GraphCanary {
+    /// The canary version of the graph, its version follows the current one
+    pub compute_graph: ComputeGraph,
+    /// Percentage of the new invocations routed to the canary, in [0, 100]
+    pub percent: u8,
+    pub created_at: u64,
+}
+
+impl GraphCanary {
+    pub fn validate_percent(percent: u8) -> Result<(), DataModelError> {
+        if percent > 100 {
+            return Err(DataModelError::InvalidGraph(
+                "invalid canary, percent must be in [0, 100]".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Whether an invocation is routed to the canary. Invocations are
+    /// sampled by hashing their id so the decision doesn't depend on the
+    /// order they are received in.
+    pub fn samples(&self, invocation_id: &str) -> bool {
+        // FNV-1a, stable across releases unlike the std hasher
+        let hash = invocation_id
+            .bytes()
+            .fold(0xcbf29ce484222325u64, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+            });
+        hash % 100 < self.percent as u64
+    }
+
+    pub fn key(&self) -> String {
+        self.compute_graph.key()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_objects::tests::mock_graph_a;
+
+    #[test]
+    fn test_canary_sampling() {
+        let mut canary = GraphCanary {
+            compute_graph: mock_graph_a(),
+            percent: 0,
+            created_at: 0,
+        };
+        let ids: Vec<String> = (0..1000).map(|i| format!("invocation_{}", i)).collect();
+        let sampled = |canary: &GraphCanary| ids.iter().filter(|id| canary.samples(id)).count();
+        assert_eq!(sampled(&canary), 0);
+
+        canary.percent = 100;
+        assert_eq!(sampled(&canary), ids.len());
+
+        canary.percent = 20;
+        let count = sampled(&canary);
+        assert!((150..250).contains(&count), "sampled {}", count);
+        // The same invocations are sampled every time
+        assert_eq!(sampled(&canary), count);
+
+        assert!(GraphCanary::validate_percent(100).is_ok());
+        assert!(GraphCanary::validate_percent(101).is_err());
+    }
+}
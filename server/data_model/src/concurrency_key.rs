@@ -0,0 +1,123 @@
+//! Concurrency keys of compute functions.
+//!
+//! A function's concurrency key is a template such as
+//! `customer-{labels.customer_id}`, evaluated against each task when it is
+//! created. Tasks of the function with the same key are never allocated at
+//! the same time. Placeholders are `labels.<name>`, `params.<name>`,
+//! `namespace`, `compute_graph` and `invocation_id`.
+
+use crate::{error::DataModelError, Task};
+
+enum Segment<'a> {
+    Literal(&'a str),
+    Placeholder(&'a str),
+}
+
+fn parse(template: &str) -> Result<Vec<Segment<'_>>, DataModelError> {
+    let invalid = |reason: &str| {
+        DataModelError::InvalidGraph(format!(
+            "invalid concurrency key {:?}: {}",
+            template, reason
+        ))
+    };
+    let mut segments = vec![];
+    let mut rest = template;
+    while let Some(start) = rest.find(['{', '}']) {
+        if rest[start..].starts_with('}') {
+            return Err(invalid("unmatched }"));
+        }
+        let end = rest[start..]
+            .find('}')
+            .map(|end| start + end)
+            .ok_or_else(|| invalid("unmatched {"))?;
+        if start > 0 {
+            segments.push(Segment::Literal(&rest[..start]));
+        }
+        let name = &rest[start + 1..end];
+        let known = matches!(name, "namespace" | "compute_graph" | "invocation_id") ||
+            name.strip_prefix("labels.")
+                .or_else(|| name.strip_prefix("params."))
+                .is_some_and(|label| !label.is_empty());
+        if !known {
+            return Err(invalid(&format!("unknown placeholder {}", name)));
+        }
+        segments.push(Segment::Placeholder(name));
+        rest = &rest[end + 1..];
+    }
+    if !rest.is_empty() {
+        segments.push(Segment::Literal(rest));
+    }
+    if segments.is_empty() {
+        return Err(invalid("empty key"));
+    }
+    Ok(segments)
+}
+
+pub fn validate_concurrency_key(template: &str) -> Result<(), DataModelError> {
+    parse(template).map(|_| ())
+}
+
+/// Key of the task, labels the invocation doesn't have and parameters
+/// without a value are rendered as `null` so their tasks share a key.
+pub fn evaluate_concurrency_key(template: &str, task: &Task) -> Result<String, DataModelError> {
+    let mut key = String::new();
+    for segment in parse(template)? {
+        match segment {
+            Segment::Literal(literal) => key.push_str(literal),
+            Segment::Placeholder("namespace") => key.push_str(&task.namespace),
+            Segment::Placeholder("compute_graph") => key.push_str(&task.compute_graph_name),
+            Segment::Placeholder("invocation_id") => key.push_str(&task.invocation_id),
+            Segment::Placeholder(name) => {
+                let value = match name.strip_prefix("params.") {
+                    Some(parameter) => task.parameters.get(parameter),
+                    None => task
+                        .invocation_labels
+                        .get(name.trim_start_matches("labels.")),
+                };
+                match value {
+                    Some(serde_json::Value::String(value)) => key.push_str(value),
+                    Some(value) => key.push_str(&value.to_string()),
+                    None => key.push_str("null"),
+                }
+            }
+        }
+    }
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_objects::tests::{create_mock_task, mock_graph_a};
+
+    #[test]
+    fn test_concurrency_key() {
+        let mut task = create_mock_task(&mock_graph_a(), "fn_a", "input", "inv");
+        task.invocation_labels = [
+            ("customer_id".to_string(), serde_json::json!("c1")),
+            ("shard".to_string(), serde_json::json!(3)),
+        ]
+        .into_iter()
+        .collect();
+        task.parameters = [("model".to_string(), serde_json::json!("minilm"))]
+            .into_iter()
+            .collect();
+
+        let key = |template: &str| evaluate_concurrency_key(template, &task).unwrap();
+        assert_eq!(key("customer-{labels.customer_id}"), "customer-c1");
+        assert_eq!(key("{labels.shard}/{labels.region}"), "3/null");
+        assert_eq!(key("{invocation_id}"), "inv");
+        assert_eq!(key("{params.model}-{params.other}"), "minilm-null");
+
+        for template in [
+            "",
+            "{labels.}",
+            "{params.}",
+            "{unknown}",
+            "{namespace",
+            "namespace}",
+        ] {
+            assert!(validate_concurrency_key(template).is_err(), "{}", template);
+        }
+    }
+}
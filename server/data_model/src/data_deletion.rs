@@ -0,0 +1,122 @@
+//! Deletions of the data of an ingested payload, e.g. for right to be
+//! forgotten requests. The input of the invocation and every output derived
+//! from it, including in the invocations of the sub graphs it ran, are
+//! deleted along with their blobs. A report of each deletion is kept to
+//! track the removal of the blobs, which must happen before its deadline.
+
+use serde::{Deserialize, Serialize};
+
+use crate::keys::KeyPrefix;
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DataDeletionMode {
+    /// The payloads are replaced by tombstones, the records of the
+    /// invocations, their tasks and outputs are kept along with the size and
+    /// hash of the payloads
+    #[default]
+    Soft,
+    /// The records of the invocations, their tasks and outputs are removed
+    /// too
+    Hard,
+}
+
+/// Invocation whose data was deleted, the ingested invocation or the
+/// invocation of a sub graph it ran.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DeletedInvocation {
+    pub compute_graph: String,
+    pub invocation_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DataDeletion {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub invocation_id: String,
+    pub mode: DataDeletionMode,
+    /// Identity which requested the deletion
+    pub requested_by: String,
+    /// Milliseconds since the epoch
+    pub requested_at: u64,
+    /// The blobs are removed from the storage by then, in milliseconds
+    /// since the epoch
+    pub deadline: u64,
+    /// The ingested invocation first
+    pub invocations: Vec<DeletedInvocation>,
+    /// Function outputs deleted, the payloads of the invocations excluded
+    pub outputs: u64,
+    /// Size of the deleted payloads and outputs
+    pub bytes: u64,
+    /// Paths of the blobs handed to the garbage collector
+    pub blobs: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DataDeletionStatus {
+    /// Blobs are left to remove before the deadline
+    Pending,
+    /// Every blob was removed
+    Completed,
+    /// Blobs are left to remove past the deadline
+    Overdue,
+}
+
+impl DataDeletion {
+    pub fn key_from(namespace: &str, compute_graph: &str, invocation_id: &str) -> String {
+        format!("{}|{}|{}", namespace, compute_graph, invocation_id)
+    }
+
+    pub fn key(&self) -> String {
+        Self::key_from(&self.namespace, &self.compute_graph, &self.invocation_id)
+    }
+
+    pub fn key_prefix(namespace: &str, compute_graph: Option<&str>) -> KeyPrefix {
+        match compute_graph {
+            Some(compute_graph) => KeyPrefix::compute_graph(namespace, compute_graph),
+            None => KeyPrefix::namespace(namespace),
+        }
+    }
+
+    /// Status of the deletion at `now`, given how many of its blobs the
+    /// garbage collector has yet to remove.
+    pub fn status(&self, pending_blobs: usize, now: u64) -> DataDeletionStatus {
+        if pending_blobs == 0 {
+            DataDeletionStatus::Completed
+        } else if now > self.deadline {
+            DataDeletionStatus::Overdue
+        } else {
+            DataDeletionStatus::Pending
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deletion_status() {
+        let deletion = DataDeletion {
+            namespace: "ns".to_string(),
+            compute_graph: "graph".to_string(),
+            invocation_id: "inv".to_string(),
+            mode: DataDeletionMode::Soft,
+            requested_by: "admin".to_string(),
+            requested_at: 100,
+            deadline: 200,
+            invocations: vec![],
+            outputs: 0,
+            bytes: 0,
+            blobs: vec!["a".to_string()],
+        };
+        assert_eq!(deletion.status(1, 150), DataDeletionStatus::Pending);
+        assert_eq!(deletion.status(1, 201), DataDeletionStatus::Overdue);
+        assert_eq!(deletion.status(0, 201), DataDeletionStatus::Completed);
+        assert!(deletion
+            .key()
+            .as_bytes()
+            .starts_with(DataDeletion::key_prefix("ns", Some("graph")).as_bytes()));
+    }
+}
@@ -0,0 +1,24 @@
+use std::fmt::{self, Display};
+
+/// Errors returned when building or validating data model objects.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DataModelError {
+    // A required field of a builder was not set
+    MissingField(&'static str),
+    InvalidGraph(String),
+    InvalidAttribute(String),
+    InvalidParameter(String),
+}
+
+impl Display for DataModelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DataModelError::MissingField(field) => write!(f, "{} is required", field),
+            DataModelError::InvalidGraph(reason) => write!(f, "invalid compute graph: {}", reason),
+            DataModelError::InvalidAttribute(reason) => write!(f, "invalid attribute: {}", reason),
+            DataModelError::InvalidParameter(reason) => write!(f, "invalid parameter: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for DataModelError {}
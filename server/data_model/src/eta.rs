@@ -0,0 +1,204 @@
+//! Estimates of the time left before an invocation finishes, from the
+//! latencies of its functions and the queues of unallocated tasks.
+//!
+//! Functions take their median latency. A running task is expected to take
+//! the median, or the 90th percentile once it ran longer than the median.
+//! A pending task waits for the tasks queued before it, which the running
+//! tasks of its function drain in parallel. The invocation finishes with its
+//! slowest outstanding task followed by the slowest path of downstream
+//! functions.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{latency::LatencySketch, ComputeGraph, Task, TaskOutcome};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaskEta {
+    pub task: Task,
+    pub running: bool,
+    /// Unallocated tasks of the function scheduled before a pending task
+    pub queue_position: Option<u64>,
+    pub remaining_ms: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvocationEta {
+    pub tasks: Vec<TaskEta>,
+    pub remaining_ms: u64,
+    /// Functions without finished tasks yet, the estimate leaves them out
+    pub fns_without_latency: Vec<String>,
+}
+
+pub struct EtaEstimator<'a> {
+    graph: &'a ComputeGraph,
+    latencies: &'a HashMap<String, LatencySketch>,
+    // Running tasks per function, across invocations
+    running: &'a HashMap<String, u64>,
+    // Slowest path of downstream functions, per function
+    downstream_ms: HashMap<String, u64>,
+    fns_without_latency: HashSet<String>,
+}
+
+impl<'a> EtaEstimator<'a> {
+    pub fn new(
+        graph: &'a ComputeGraph,
+        latencies: &'a HashMap<String, LatencySketch>,
+        running: &'a HashMap<String, u64>,
+    ) -> Self {
+        Self {
+            graph,
+            latencies,
+            running,
+            downstream_ms: HashMap::new(),
+            fns_without_latency: HashSet::new(),
+        }
+    }
+
+    fn latency_ms(&mut self, compute_fn: &str, quantile: f64) -> u64 {
+        match self
+            .latencies
+            .get(compute_fn)
+            .and_then(|sketch| sketch.quantile(quantile))
+        {
+            Some(latency_ms) => latency_ms,
+            None => {
+                self.fns_without_latency.insert(compute_fn.to_string());
+                0
+            }
+        }
+    }
+
+    fn downstream_ms(&mut self, compute_fn: &str, visiting: &mut HashSet<String>) -> u64 {
+        if let Some(downstream_ms) = self.downstream_ms.get(compute_fn) {
+            return *downstream_ms;
+        }
+        // Guards against cycles, graphs are validated to be acyclic
+        if !visiting.insert(compute_fn.to_string()) {
+            return 0;
+        }
+        let mut slowest = 0;
+        for child in self
+            .graph
+            .edges
+            .get(compute_fn)
+            .cloned()
+            .unwrap_or_default()
+        {
+            let path_ms = self.latency_ms(&child, 0.5) + self.downstream_ms(&child, visiting);
+            slowest = slowest.max(path_ms);
+        }
+        visiting.remove(compute_fn);
+        self.downstream_ms.insert(compute_fn.to_string(), slowest);
+        slowest
+    }
+
+    /// Estimates the outstanding tasks of an invocation. `queued` holds the
+    /// unallocated tasks of the graph, of every invocation.
+    pub fn estimate(mut self, tasks: Vec<Task>, queued: &[Task], now_ms: u64) -> InvocationEta {
+        // Unallocated tasks are scheduled by decreasing priority, then in key
+        // order
+        let mut queues: HashMap<&str, Vec<&Task>> = HashMap::new();
+        for task in queued {
+            queues.entry(&task.compute_fn_name).or_default().push(task);
+        }
+        for queue in queues.values_mut() {
+            queue.sort_by_key(|task| (std::cmp::Reverse(task.priority), task.key()));
+        }
+        let mut estimates = vec![];
+        let mut remaining_ms = 0;
+        for task in tasks {
+            if task.outcome != TaskOutcome::Unknown {
+                continue;
+            }
+            let median_ms = self.latency_ms(&task.compute_fn_name, 0.5);
+            let (running, queue_position, task_ms) = match task.allocated_at {
+                Some(allocated_at) => {
+                    let elapsed_ms = now_ms.saturating_sub(allocated_at);
+                    let expected_ms = if elapsed_ms < median_ms {
+                        median_ms
+                    } else {
+                        self.latency_ms(&task.compute_fn_name, 0.9)
+                    };
+                    (true, None, expected_ms.saturating_sub(elapsed_ms))
+                }
+                None => {
+                    let position = queues
+                        .get(task.compute_fn_name.as_str())
+                        .and_then(|queue| queue.iter().position(|queued| queued.id == task.id))
+                        .unwrap_or(0) as u64;
+                    let parallelism = self
+                        .running
+                        .get(&task.compute_fn_name)
+                        .copied()
+                        .unwrap_or(0)
+                        .max(1);
+                    let wait_ms = position / parallelism * median_ms;
+                    (false, Some(position), wait_ms + median_ms)
+                }
+            };
+            let downstream_ms = self.downstream_ms(&task.compute_fn_name, &mut HashSet::new());
+            remaining_ms = remaining_ms.max(task_ms + downstream_ms);
+            estimates.push(TaskEta {
+                task,
+                running,
+                queue_position,
+                remaining_ms: task_ms,
+            });
+        }
+        let mut fns_without_latency: Vec<String> = self.fns_without_latency.into_iter().collect();
+        fns_without_latency.sort();
+        InvocationEta {
+            tasks: estimates,
+            remaining_ms,
+            fns_without_latency,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_objects::tests::{create_mock_task, mock_graph_a};
+
+    #[test]
+    fn test_estimate_invocation() {
+        // fn_a -> fn_b, fn_c
+        let graph = mock_graph_a();
+        let sketch = |latency_ms: u64| {
+            let mut sketch = LatencySketch::default();
+            sketch.add(latency_ms);
+            sketch
+        };
+        let latencies = HashMap::from([
+            ("fn_a".to_string(), sketch(1000)),
+            ("fn_b".to_string(), sketch(3000)),
+        ]);
+        let running = HashMap::from([("fn_a".to_string(), 2)]);
+
+        let mut running_task = create_mock_task(&graph, "fn_a", "input", "inv");
+        running_task.allocated_at = Some(10_000);
+        let pending_task = create_mock_task(&graph, "fn_a", "input", "inv");
+        let mut other_tasks: Vec<Task> = (0..4)
+            .map(|_| create_mock_task(&graph, "fn_a", "input", "other"))
+            .collect();
+        for task in &mut other_tasks {
+            task.priority = 1;
+        }
+        let mut queued = other_tasks;
+        queued.push(pending_task.clone());
+
+        let eta = EtaEstimator::new(&graph, &latencies, &running).estimate(
+            vec![running_task, pending_task],
+            &queued,
+            10_400,
+        );
+        assert_eq!(eta.tasks[0].remaining_ms, 600);
+        assert!(eta.tasks[0].running);
+        // Four higher priority tasks are drained two at a time before it
+        assert_eq!(eta.tasks[1].queue_position, Some(4));
+        assert_eq!(eta.tasks[1].remaining_ms, 3000);
+        // fn_b is the slowest downstream function, fn_c has no history
+        assert_eq!(eta.remaining_ms, 6000);
+        assert_eq!(eta.fns_without_latency, vec!["fn_c".to_string()]);
+    }
+}
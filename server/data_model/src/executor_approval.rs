@@ -0,0 +1,90 @@
+//! Approval of the executors joining the cluster. When approval is required,
+//! an executor registering for the first time is recorded as pending and
+//! turned away until an admin approves it, unless it presents a bootstrap
+//! token. The labels set by the approval are stamped on the executor, over
+//! the labels it reports.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{ExecutorId, ExecutorMetadata};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalStatus {
+    Pending,
+    Approved,
+    Denied,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ExecutorApproval {
+    pub executor_id: ExecutorId,
+    pub status: ApprovalStatus,
+    // What the executor reported when it first registered
+    pub addr: String,
+    pub image_name: String,
+    pub reported_labels: HashMap<String, serde_json::Value>,
+    /// Stamped on the executor once approved
+    pub labels: HashMap<String, serde_json::Value>,
+    /// Milliseconds since the epoch
+    pub requested_at: u64,
+    pub decided_at: Option<u64>,
+    /// Identity which approved or denied the executor
+    pub decided_by: Option<String>,
+}
+
+impl ExecutorApproval {
+    pub fn pending(executor: &ExecutorMetadata, now: u64) -> Self {
+        Self {
+            executor_id: executor.id.clone(),
+            status: ApprovalStatus::Pending,
+            addr: executor.addr.clone(),
+            image_name: executor.image_name.clone(),
+            reported_labels: executor.labels.clone(),
+            labels: HashMap::new(),
+            requested_at: now,
+            decided_at: None,
+            decided_by: None,
+        }
+    }
+
+    pub fn decide(&mut self, status: ApprovalStatus, decided_by: &str, now: u64) {
+        self.status = status;
+        self.decided_at = Some(now);
+        self.decided_by = Some(decided_by.to_string());
+    }
+
+    /// Labels of the approved executor, the stamped labels override the
+    /// reported ones.
+    pub fn stamp_labels(&self, labels: &mut HashMap<String, serde_json::Value>) {
+        labels.extend(self.labels.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stamp_labels() {
+        let executor = ExecutorMetadata {
+            id: ExecutorId::new("e1".to_string()),
+            labels: HashMap::from([
+                ("tier".to_string(), serde_json::json!("gpu")),
+                ("zone".to_string(), serde_json::json!("a")),
+            ]),
+            ..Default::default()
+        };
+        let mut approval = ExecutorApproval::pending(&executor, 1);
+        approval.labels = HashMap::from([("tier".to_string(), serde_json::json!("cpu"))]);
+        approval.decide(ApprovalStatus::Approved, "admin", 2);
+        assert_eq!(approval.decided_at, Some(2));
+
+        let mut labels = executor.labels.clone();
+        approval.stamp_labels(&mut labels);
+        assert_eq!(labels["tier"], "cpu");
+        assert_eq!(labels["zone"], "a");
+    }
+}
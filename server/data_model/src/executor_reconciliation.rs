@@ -0,0 +1,98 @@
+//! Reconciliation of the tasks of an executor restarting after a crash. The
+//! executor reports the tasks it was running and the blobs it uploaded for
+//! them when it registers again, they're checked against the tasks allocated
+//! to it. The outputs the tasks streamed and their checkpoints are kept, the
+//! tasks are allocated again and the uploads which were never committed are
+//! handed to the garbage collector. A report is kept for each restart.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{ExecutorId, Task, TaskId};
+
+/// Task an executor was running when it went down, as reported by it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ReportedTask {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub compute_fn: String,
+    pub invocation_id: String,
+    pub task_id: TaskId,
+    /// Urls of the blobs uploaded for the outputs of the task
+    pub partial_outputs: Vec<String>,
+}
+
+impl ReportedTask {
+    pub fn task_key(&self) -> Vec<u8> {
+        Task::key_from(
+            &self.namespace,
+            &self.compute_graph,
+            &self.invocation_id,
+            &self.compute_fn,
+            self.task_id.get(),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReconciliationOutcome {
+    /// The task was allocated to the executor, it's allocated again
+    Retried,
+    /// The task finished before the executor went down
+    Finished,
+    /// The allocation of the task expired along with the executor, the task
+    /// was scheduled again already
+    LeaseLost,
+    /// The task doesn't exist anymore, e.g. its invocation was deleted
+    NotFound,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ReconciledTask {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub compute_fn: String,
+    pub invocation_id: String,
+    pub task_id: TaskId,
+    /// Tasks allocated to the executor which it didn't report are retried
+    /// too
+    pub reported: bool,
+    pub outcome: ReconciliationOutcome,
+    /// Outputs the task streamed before the crash, kept for its retry
+    pub salvaged_outputs: u64,
+    /// Whether the retry resumes from a checkpoint of the task
+    pub salvaged_checkpoint: bool,
+    /// Partial outputs which were never committed, handed to the garbage
+    /// collector
+    pub discarded_outputs: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ExecutorReconciliation {
+    pub executor_id: ExecutorId,
+    /// When the executor registered again, in milliseconds since the epoch
+    pub restarted_at: u64,
+    pub tasks: Vec<ReconciledTask>,
+}
+
+impl ExecutorReconciliation {
+    pub fn key_prefix(executor_id: &ExecutorId) -> String {
+        format!("{}|", executor_id)
+    }
+
+    /// Reports of an executor are ordered by the time it restarted at.
+    pub fn key(&self) -> String {
+        format!(
+            "{}{:020}",
+            Self::key_prefix(&self.executor_id),
+            self.restarted_at
+        )
+    }
+
+    pub fn num_retried(&self) -> usize {
+        self.tasks
+            .iter()
+            .filter(|task| task.outcome == ReconciliationOutcome::Retried)
+            .count()
+    }
+}
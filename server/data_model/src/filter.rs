@@ -4,7 +4,7 @@ use std::{
     fmt::{self, Display},
 };
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use serde::{de::Deserializer, Deserialize, Serialize, Serializer};
 use serde_json::Value;
 
@@ -16,18 +16,23 @@ pub enum Operator {
     Lt,
     GtEq,
     LtEq,
+    In,
+    NotIn,
+    Exists,
 }
 
 impl Operator {
     pub fn from_str(operator: &str) -> Result<Self> {
         match operator {
-            "=" => Ok(Self::Eq),
+            "=" | "==" => Ok(Self::Eq),
             "!=" => Ok(Self::Neq),
             ">" => Ok(Self::Gt),
             "<" => Ok(Self::Lt),
             ">=" => Ok(Self::GtEq),
             "<=" => Ok(Self::LtEq),
-            _ => Err(anyhow::anyhow!("Invalid filter operator: {}", operator)),
+            "in" => Ok(Self::In),
+            "notin" => Ok(Self::NotIn),
+            _ => Err(anyhow!("Invalid filter operator: {}", operator)),
         }
     }
 }
@@ -44,11 +49,17 @@ impl Display for Operator {
                 Operator::Lt => "<",
                 Operator::GtEq => ">=",
                 Operator::LtEq => "<=",
+                Operator::In => "in",
+                Operator::NotIn => "notin",
+                Operator::Exists => "",
             }
         )
     }
 }
 
+/// A single label comparison, e.g. `gpu=true`, `tier in (a,b)` or `gpu`.
+///
+/// The value of `in` and `notin` is an array, `Exists` has no value.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Expression {
     pub key: String,
@@ -77,30 +88,65 @@ impl<'de> Deserialize<'de> for Expression {
 
 impl Expression {
     pub fn from_str(str: &str) -> Result<Self> {
-        // This parser must start with the longest operators first.
-        let operators = vec!["!=", ">=", "<=", "=", ">", "<"];
-        for operator in operators {
-            let parts: Vec<&str> = str.split(operator).collect();
-            if parts.len() != 2 {
-                continue;
-            }
+        match Condition::from_str(str)? {
+            Condition::Expr(expr) => Ok(expr),
+            _ => Err(anyhow!(
+                "Invalid filter, expected a single expression: {}",
+                str
+            )),
+        }
+    }
 
-            let key = parts[0].to_string();
-            let value = serde_json::from_str(parts[1]).unwrap_or(serde_json::json!(parts[1]));
-            let operator = Operator::from_str(operator)?;
-            return Ok(Self {
-                key,
-                value,
-                operator,
-            });
+    pub fn matches(&self, values: &HashMap<String, Value>) -> bool {
+        if self.operator == Operator::Exists {
+            return values.contains_key(&self.key);
+        }
+        let Some(value) = values.get(&self.key) else {
+            return false;
+        };
+        let is_equal = |other: &Value| partial_cmp(value, other) == Some(Ordering::Equal);
+        match self.operator {
+            Operator::In => self
+                .value
+                .as_array()
+                .is_some_and(|v| v.iter().any(is_equal)),
+            Operator::NotIn => self
+                .value
+                .as_array()
+                .is_some_and(|v| !v.iter().any(is_equal)),
+            _ => match partial_cmp(value, &self.value) {
+                Some(ordering) => match self.operator {
+                    Operator::Eq => ordering == Ordering::Equal,
+                    Operator::Neq => ordering != Ordering::Equal,
+                    Operator::Gt => ordering == Ordering::Greater,
+                    Operator::Lt => ordering == Ordering::Less,
+                    Operator::GtEq => ordering != Ordering::Less,
+                    Operator::LtEq => ordering != Ordering::Greater,
+                    _ => false,
+                },
+                None => false,
+            },
         }
-        Err(anyhow::anyhow!("Invalid filter: {}", str))
     }
 }
 
 impl Display for Expression {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}{}{}", self.key, self.operator, self.value)
+        match self.operator {
+            Operator::Exists => write!(f, "{}", self.key),
+            Operator::In | Operator::NotIn => {
+                let values = match &self.value {
+                    Value::Array(values) => values
+                        .iter()
+                        .map(|v| v.to_string())
+                        .collect::<Vec<_>>()
+                        .join(","),
+                    value => value.to_string(),
+                };
+                write!(f, "{} {} ({})", self.key, self.operator, values)
+            }
+            _ => write!(f, "{}{}{}", self.key, self.operator, self.value),
+        }
     }
 }
 
@@ -113,36 +159,323 @@ fn partial_cmp(lhs: &Value, rhs: &Value) -> Option<Ordering> {
     }
 }
 
+/// Boolean combination of expressions.
+///
+/// Grammar, `&&` binds tighter than `||`:
+///
+/// ```text
+/// or    := and ("||" and)*
+/// and   := unary ("&&" unary)*
+/// unary := "!" unary | "(" or ")" | key op value | key ("in" | "notin") "(" value ("," value)* ")" | key
+/// ```
+///
+/// Values are JSON, bare words which aren't valid JSON are strings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Condition {
+    Expr(Expression),
+    And(Vec<Condition>),
+    Or(Vec<Condition>),
+    Not(Box<Condition>),
+}
+
+impl Condition {
+    pub fn from_str(str: &str) -> Result<Self> {
+        let mut parser = Parser::new(str)?;
+        let condition = parser.parse_or()?;
+        parser.expect_end()?;
+        Ok(condition)
+    }
+
+    pub fn matches(&self, values: &HashMap<String, Value>) -> bool {
+        match self {
+            Condition::Expr(expr) => expr.matches(values),
+            Condition::And(conditions) => conditions.iter().all(|c| c.matches(values)),
+            Condition::Or(conditions) => conditions.iter().any(|c| c.matches(values)),
+            Condition::Not(condition) => !condition.matches(values),
+        }
+    }
+}
+
+impl From<Expression> for Condition {
+    fn from(expr: Expression) -> Self {
+        Condition::Expr(expr)
+    }
+}
+
+impl Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Condition::Expr(expr) => write!(f, "{}", expr),
+            Condition::And(conditions) => {
+                for (i, condition) in conditions.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " && ")?;
+                    }
+                    match condition {
+                        Condition::Or(_) => write!(f, "({})", condition)?,
+                        _ => write!(f, "{}", condition)?,
+                    }
+                }
+                Ok(())
+            }
+            Condition::Or(conditions) => {
+                for (i, condition) in conditions.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " || ")?;
+                    }
+                    write!(f, "{}", condition)?;
+                }
+                Ok(())
+            }
+            Condition::Not(condition) => match condition.as_ref() {
+                Condition::Expr(expr) if expr.operator == Operator::Exists => {
+                    write!(f, "!{}", expr)
+                }
+                condition => write!(f, "!({})", condition),
+            },
+        }
+    }
+}
+
+impl Serialize for Condition {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        format!("{}", self).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Condition {
+    fn deserialize<D>(deserializer: D) -> Result<Condition, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Condition::from_str(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    Comma,
+    Not,
+    And,
+    Or,
+    Op(String),
+    Word(String),
+    // Raw JSON string, including the quotes
+    Str(String),
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '(' => tokens.push(Token::LParen),
+            ')' => tokens.push(Token::RParen),
+            ',' => tokens.push(Token::Comma),
+            '!' | '=' | '<' | '>' => {
+                if chars.next_if(|(_, c)| *c == '=').is_some() {
+                    tokens.push(Token::Op(format!("{}=", c)));
+                } else if c == '!' {
+                    tokens.push(Token::Not);
+                } else {
+                    tokens.push(Token::Op(c.to_string()));
+                }
+            }
+            '&' | '|' => {
+                if chars.next_if(|(_, next)| *next == c).is_none() {
+                    return Err(anyhow!("Invalid filter, expected {}{}: {}", c, c, input));
+                }
+                tokens.push(if c == '&' { Token::And } else { Token::Or });
+            }
+            '"' => {
+                let mut escaped = false;
+                let mut end = None;
+                for (i, c) in chars.by_ref() {
+                    match c {
+                        '\\' if !escaped => escaped = true,
+                        '"' if !escaped => {
+                            end = Some(i);
+                            break;
+                        }
+                        _ => escaped = false,
+                    }
+                }
+                let end = end.ok_or(anyhow!("Invalid filter, unterminated string: {}", input))?;
+                tokens.push(Token::Str(input[start..=end].to_string()));
+            }
+            _ => {
+                let mut end = start + c.len_utf8();
+                while let Some((i, c)) =
+                    chars.next_if(|(_, c)| !c.is_whitespace() && !"()!,&|=<>\"".contains(*c))
+                {
+                    end = i + c.len_utf8();
+                }
+                tokens.push(Token::Word(input[start..end].to_string()));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Result<Self> {
+        Ok(Self {
+            input,
+            tokens: tokenize(input)?,
+            pos: 0,
+        })
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn error(&self, message: &str) -> anyhow::Error {
+        anyhow!("Invalid filter, {}: {}", message, self.input)
+    }
+
+    fn expect(&mut self, token: Token) -> Result<()> {
+        if self.next() != Some(token.clone()) {
+            return Err(self.error(&format!("expected {:?}", token)));
+        }
+        Ok(())
+    }
+
+    fn expect_end(&self) -> Result<()> {
+        if self.peek().is_some() {
+            return Err(self.error("unexpected trailing input"));
+        }
+        Ok(())
+    }
+
+    fn parse_or(&mut self) -> Result<Condition> {
+        let mut conditions = vec![self.parse_and()?];
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            conditions.push(self.parse_and()?);
+        }
+        Ok(match conditions.len() {
+            1 => conditions.remove(0),
+            _ => Condition::Or(conditions),
+        })
+    }
+
+    fn parse_and(&mut self) -> Result<Condition> {
+        let mut conditions = vec![self.parse_unary()?];
+        while self.peek() == Some(&Token::And) {
+            self.pos += 1;
+            conditions.push(self.parse_unary()?);
+        }
+        Ok(match conditions.len() {
+            1 => conditions.remove(0),
+            _ => Condition::And(conditions),
+        })
+    }
+
+    fn parse_unary(&mut self) -> Result<Condition> {
+        match self.next() {
+            Some(Token::Not) => Ok(Condition::Not(Box::new(self.parse_unary()?))),
+            Some(Token::LParen) => {
+                let condition = self.parse_or()?;
+                self.expect(Token::RParen)?;
+                Ok(condition)
+            }
+            Some(Token::Word(key)) => self.parse_expression(key),
+            _ => Err(self.error("expected a label key")),
+        }
+    }
+
+    fn parse_expression(&mut self, key: String) -> Result<Condition> {
+        let operator = match self.peek() {
+            Some(Token::Op(op)) => Operator::from_str(op)?,
+            Some(Token::Word(word)) if word == "in" || word == "notin" => Operator::from_str(word)?,
+            _ => {
+                return Ok(Condition::Expr(Expression {
+                    key,
+                    value: Value::Null,
+                    operator: Operator::Exists,
+                }))
+            }
+        };
+        self.pos += 1;
+        let value = match operator {
+            Operator::In | Operator::NotIn => {
+                self.expect(Token::LParen)?;
+                let mut values = vec![self.parse_value()?];
+                while self.peek() == Some(&Token::Comma) {
+                    self.pos += 1;
+                    values.push(self.parse_value()?);
+                }
+                self.expect(Token::RParen)?;
+                Value::Array(values)
+            }
+            _ => self.parse_value()?,
+        };
+        Ok(Condition::Expr(Expression {
+            key,
+            value,
+            operator,
+        }))
+    }
+
+    fn parse_value(&mut self) -> Result<Value> {
+        match self.next() {
+            Some(Token::Word(word)) => {
+                Ok(serde_json::from_str(&word).unwrap_or(Value::String(word)))
+            }
+            Some(Token::Str(raw)) => Ok(serde_json::from_str(&raw)?),
+            _ => Err(self.error("expected a value")),
+        }
+    }
+}
+
+/// Conditions which must all match, serialized as a list of strings.
 #[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
-pub struct LabelsFilter(pub Vec<Expression>);
+pub struct LabelsFilter(pub Vec<Condition>);
 
 impl LabelsFilter {
+    /// Parses comma separated conditions, e.g. `gpu,tier in (a,b)`.
+    pub fn from_str(str: &str) -> Result<Self> {
+        let mut parser = Parser::new(str)?;
+        let mut conditions = Vec::new();
+        while parser.peek().is_some() {
+            conditions.push(parser.parse_or()?);
+            if parser.peek().is_some() {
+                parser.expect(Token::Comma)?;
+            }
+        }
+        Ok(Self(conditions))
+    }
+
     pub fn is_empty(&self) -> bool {
         self.0.is_empty()
     }
 
-    pub fn expressions(&self) -> &[Expression] {
+    pub fn conditions(&self) -> &[Condition] {
         &self.0
     }
 
     pub fn matches(&self, values: &HashMap<String, Value>) -> bool {
-        self.0.iter().all(|expr| {
-            let value = values.get(&expr.key);
-            match value {
-                Some(value) => match partial_cmp(value, &expr.value) {
-                    Some(ordering) => match expr.operator {
-                        Operator::Eq => ordering == std::cmp::Ordering::Equal,
-                        Operator::Neq => ordering != std::cmp::Ordering::Equal,
-                        Operator::Gt => ordering == std::cmp::Ordering::Greater,
-                        Operator::Lt => ordering == std::cmp::Ordering::Less,
-                        Operator::GtEq => ordering != std::cmp::Ordering::Less,
-                        Operator::LtEq => ordering != std::cmp::Ordering::Greater,
-                    },
-                    None => false,
-                },
-                None => false,
-            }
-        })
+        self.0.iter().all(|condition| condition.matches(values))
     }
 }
 
@@ -184,12 +517,14 @@ mod tests {
                 key: "key1".to_string(),
                 value: serde_json::json!(1),
                 operator: Operator::Eq,
-            },
+            }
+            .into(),
             Expression {
                 key: "key2".to_string(),
                 value: serde_json::json!(2),
                 operator: Operator::Gt,
-            },
+            }
+            .into(),
         ]);
 
         let mut values = HashMap::new();
@@ -202,4 +537,71 @@ mod tests {
         values.insert("key2".to_string(), serde_json::json!(3));
         assert!(filter.matches(&values));
     }
+
+    #[test]
+    fn test_conditions() {
+        let values = HashMap::from([
+            ("tier".to_string(), serde_json::json!("gold")),
+            ("gpus".to_string(), serde_json::json!(4)),
+            ("spot".to_string(), serde_json::json!(false)),
+        ]);
+        let cases = [
+            ("tier in (gold, silver)", true),
+            ("tier notin (gold,silver)", false),
+            ("gpus in (2,4)", true),
+            ("gpus>=4 && gpus<8", true),
+            ("spot", true),
+            ("!spot", false),
+            ("!region", true),
+            ("region || gpus>2", true),
+            ("!(tier=gold || spot=true)", false),
+            ("tier=silver || gpus>2 && spot=false", true),
+            ("(tier=silver || gpus>2) && spot=true", false),
+            ("tier=\"gold\"", true),
+        ];
+        for (filter, expected) in cases {
+            let condition = Condition::from_str(filter).unwrap();
+            assert_eq!(condition.matches(&values), expected, "{}", filter);
+        }
+
+        assert!(Condition::from_str("tier in gold").is_err());
+        assert!(Condition::from_str("tier=gold &&").is_err());
+        assert!(Condition::from_str("(tier=gold").is_err());
+        assert!(Condition::from_str("tier=gold | spot").is_err());
+    }
+
+    #[test]
+    fn test_serde_round_trip() {
+        let filter = LabelsFilter::from_str(
+            "gpu, tier in (gold,\"silver plus\"), !(spot=true || region=us), gpus>2 && zone",
+        )
+        .unwrap();
+        assert_eq!(filter.conditions().len(), 4);
+        assert_eq!(
+            filter
+                .conditions()
+                .iter()
+                .map(|c| c.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "gpu",
+                "tier in (\"gold\",\"silver plus\")",
+                "!(spot=true || region=\"us\")",
+                "gpus>2 && zone",
+            ]
+        );
+        let json = serde_json::to_string(&filter).unwrap();
+        let decoded: LabelsFilter = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, filter);
+
+        // Filters stored before the grammar upgrade still decode
+        let decoded: LabelsFilter = serde_json::from_str("[\"gpu=true\",\"mem>=16\"]").unwrap();
+        assert_eq!(
+            decoded,
+            LabelsFilter(vec![
+                Expression::from_str("gpu=true").unwrap().into(),
+                Expression::from_str("mem>=16").unwrap().into(),
+            ])
+        );
+    }
 }
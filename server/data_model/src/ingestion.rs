@@ -0,0 +1,96 @@
+//! Ingestion hooks of compute graphs. The server runs the payload of an
+//! invocation through the transforms of its graph, in order, before the first
+//! task of the invocation is created.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{error::DataModelError, Node};
+
+/// Function name the parts of a split payload are stored under, the tasks of
+/// the start node read them as their input.
+pub const INGESTION_FN: &str = "ingestion";
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case", tag = "transform")]
+pub enum IngestionTransform {
+    /// Decompresses gzip payloads, other payloads pass through
+    Decompress,
+    /// Splits a zip archive into a part per file, the start node runs once
+    /// per part
+    SplitArchive,
+    /// Removes fields from JSON payloads, e.g. `user.email`. Nested fields
+    /// are separated by dots and fields of arrays of objects are removed from
+    /// every element.
+    StripJsonFields { fields: Vec<String> },
+}
+
+/// Checks that archives are split at most once and into a start node which
+/// can run once per part.
+pub fn validate_hooks(hooks: &[IngestionTransform], start_fn: &Node) -> Result<(), DataModelError> {
+    let splits = hooks
+        .iter()
+        .filter(|hook| **hook == IngestionTransform::SplitArchive)
+        .count();
+    if splits > 1 {
+        return Err(DataModelError::InvalidGraph(
+            "archives can only be split once".to_string(),
+        ));
+    }
+    if splits == 1 && start_fn.reducer() {
+        return Err(DataModelError::InvalidGraph(
+            "archives can't be split into a reducer start node".to_string(),
+        ));
+    }
+    for hook in hooks {
+        if let IngestionTransform::StripJsonFields { fields } = hook {
+            if fields.is_empty() ||
+                fields
+                    .iter()
+                    .any(|field| field.split('.').any(str::is_empty))
+            {
+                return Err(DataModelError::InvalidGraph(
+                    "strip_json_fields needs fields, e.g. user.email".to_string(),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_objects::tests::mock_graph_a;
+
+    #[test]
+    fn test_validate_hooks() {
+        let graph = mock_graph_a();
+        let strip = |field: &str| IngestionTransform::StripJsonFields {
+            fields: vec![field.to_string()],
+        };
+        assert!(validate_hooks(
+            &[
+                IngestionTransform::Decompress,
+                IngestionTransform::SplitArchive,
+                strip("user.email")
+            ],
+            &graph.start_fn
+        )
+        .is_ok());
+        assert!(validate_hooks(
+            &[
+                IngestionTransform::SplitArchive,
+                IngestionTransform::SplitArchive
+            ],
+            &graph.start_fn
+        )
+        .is_err());
+        assert!(validate_hooks(&[strip("user..email")], &graph.start_fn).is_err());
+
+        let mut start_fn = graph.start_fn.clone();
+        if let Node::Compute(compute_fn) = &mut start_fn {
+            compute_fn.reducer = true;
+        }
+        assert!(validate_hooks(&[IngestionTransform::SplitArchive], &start_fn).is_err());
+    }
+}
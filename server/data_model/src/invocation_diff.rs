@@ -0,0 +1,223 @@
+//! Differences between the terminal outputs of two finished invocations of a
+//! graph, e.g. of the same payload run on the current and the canary version
+//! of the graph. Outputs are compared by their hash and size, in any order.
+
+use std::collections::BTreeMap;
+
+use crate::{GraphVersion, InvocationResult, InvocationStatus, OutputPayload};
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct OutputDigest {
+    pub sha256_hash: String,
+    pub size: u64,
+}
+
+/// Terminal outputs of a function in the base and the head invocation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionOutputsDiff {
+    pub compute_fn: String,
+    pub base_outputs: u64,
+    pub base_bytes: u64,
+    pub head_outputs: u64,
+    pub head_bytes: u64,
+    /// Outputs of the head invocation without an identical output in the base
+    pub added: Vec<OutputDigest>,
+    /// Outputs of the base invocation without an identical output in the head
+    pub removed: Vec<OutputDigest>,
+}
+
+impl FunctionOutputsDiff {
+    pub fn identical(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffedInvocation {
+    pub invocation_id: String,
+    pub graph_version: GraphVersion,
+    pub status: InvocationStatus,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvocationsDiff {
+    pub base: DiffedInvocation,
+    pub head: DiffedInvocation,
+    /// Functions with terminal outputs in either invocation, by name
+    pub functions: Vec<FunctionOutputsDiff>,
+}
+
+impl InvocationsDiff {
+    pub fn new(
+        base: &InvocationResult,
+        base_version: GraphVersion,
+        head: &InvocationResult,
+        head_version: GraphVersion,
+    ) -> Self {
+        let base_digests = digests(base);
+        let head_digests = digests(head);
+        let mut compute_fns: Vec<&String> =
+            base_digests.keys().chain(head_digests.keys()).collect();
+        compute_fns.sort();
+        compute_fns.dedup();
+        let functions = compute_fns
+            .into_iter()
+            .map(|compute_fn| {
+                let base = base_digests.get(compute_fn).map_or(&[][..], Vec::as_slice);
+                let head = head_digests.get(compute_fn).map_or(&[][..], Vec::as_slice);
+                FunctionOutputsDiff {
+                    compute_fn: compute_fn.clone(),
+                    base_outputs: base.len() as u64,
+                    base_bytes: base.iter().map(|digest| digest.size).sum(),
+                    head_outputs: head.len() as u64,
+                    head_bytes: head.iter().map(|digest| digest.size).sum(),
+                    added: unmatched(head, base),
+                    removed: unmatched(base, head),
+                }
+            })
+            .collect();
+        Self {
+            base: DiffedInvocation {
+                invocation_id: base.invocation_id.clone(),
+                graph_version: base_version,
+                status: base.status,
+            },
+            head: DiffedInvocation {
+                invocation_id: head.invocation_id.clone(),
+                graph_version: head_version,
+                status: head.status,
+            },
+            functions,
+        }
+    }
+
+    pub fn identical(&self) -> bool {
+        self.base.status == self.head.status &&
+            self.functions.iter().all(FunctionOutputsDiff::identical)
+    }
+}
+
+// Digests of the data outputs of each terminal function, sorted
+fn digests(result: &InvocationResult) -> BTreeMap<String, Vec<OutputDigest>> {
+    let mut digests: BTreeMap<String, Vec<OutputDigest>> = BTreeMap::new();
+    for output in &result.outputs {
+        // Router outputs are edges rather than data
+        let OutputPayload::Fn(payload) = &output.payload else {
+            continue;
+        };
+        digests
+            .entry(output.compute_fn_name.clone())
+            .or_default()
+            .push(OutputDigest {
+                sha256_hash: payload.sha256_hash.clone(),
+                size: payload.size,
+            });
+    }
+    for digests in digests.values_mut() {
+        digests.sort();
+    }
+    digests
+}
+
+// Outputs of `a` left once each output of `b` matched one identical output
+// of `a`, both sorted
+fn unmatched(a: &[OutputDigest], b: &[OutputDigest]) -> Vec<OutputDigest> {
+    let mut unmatched = vec![];
+    let mut b = b.iter().peekable();
+    for digest in a {
+        while b.next_if(|other| *other < digest).is_some() {}
+        if b.next_if(|other| *other == digest).is_none() {
+            unmatched.push(digest.clone());
+        }
+    }
+    unmatched
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DataPayload, NodeOutput};
+
+    fn result(invocation_id: &str, outputs: &[(&str, &str, u64)]) -> InvocationResult {
+        InvocationResult {
+            namespace: "test".to_string(),
+            compute_graph_name: "graph".to_string(),
+            invocation_id: invocation_id.to_string(),
+            status: InvocationStatus::Success,
+            outputs: outputs
+                .iter()
+                .enumerate()
+                .map(|(idx, (compute_fn, hash, size))| NodeOutput {
+                    id: idx.to_string(),
+                    graph_version: GraphVersion::default(),
+                    namespace: "test".to_string(),
+                    compute_graph_name: "graph".to_string(),
+                    compute_fn_name: compute_fn.to_string(),
+                    invocation_id: invocation_id.to_string(),
+                    payload: OutputPayload::Fn(DataPayload {
+                        path: format!("{}/{}", invocation_id, idx),
+                        size: *size,
+                        sha256_hash: hash.to_string(),
+                        tier: Default::default(),
+                        mime_type: None,
+                        content_encoding: None,
+                        metadata: Default::default(),
+                        replicas: Vec::new(),
+                        compression: None,
+                    }),
+                    errors: None,
+                    reduced_state: false,
+                    streamed: false,
+                })
+                .collect(),
+            errors: vec![],
+            created_at: 0,
+            finished_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_invocations_diff() {
+        let base = result(
+            "base",
+            &[("fn_c", "a", 1), ("fn_c", "b", 2), ("fn_c", "b", 2), ("fn_d", "x", 5)],
+        );
+        // Same outputs in another order
+        let head = result(
+            "head",
+            &[("fn_c", "b", 2), ("fn_d", "x", 5), ("fn_c", "a", 1), ("fn_c", "b", 2)],
+        );
+        let diff = InvocationsDiff::new(&base, GraphVersion(1), &head, GraphVersion(2));
+        assert!(diff.identical());
+        assert_eq!(diff.functions.len(), 2);
+        assert_eq!(diff.functions[0].base_bytes, 5);
+
+        let head = result("head", &[("fn_c", "a", 1), ("fn_c", "c", 3), ("fn_e", "y", 7)]);
+        let diff = InvocationsDiff::new(&base, GraphVersion(1), &head, GraphVersion(2));
+        assert!(!diff.identical());
+        let names: Vec<&str> = diff
+            .functions
+            .iter()
+            .map(|function| function.compute_fn.as_str())
+            .collect();
+        assert_eq!(names, vec!["fn_c", "fn_d", "fn_e"]);
+        let fn_c = &diff.functions[0];
+        assert_eq!((fn_c.base_outputs, fn_c.head_outputs), (3, 2));
+        let hashes = |digests: &[OutputDigest]| -> Vec<String> {
+            digests
+                .iter()
+                .map(|digest| digest.sha256_hash.clone())
+                .collect()
+        };
+        assert_eq!(hashes(&fn_c.added), vec!["c"]);
+        assert_eq!(hashes(&fn_c.removed), vec!["b", "b"]);
+        assert_eq!(hashes(&diff.functions[1].removed), vec!["x"]);
+        assert_eq!(hashes(&diff.functions[2].added), vec!["y"]);
+
+        // A failed invocation differs even with the same outputs
+        let mut failed = base.clone();
+        failed.status = InvocationStatus::Failure;
+        let diff = InvocationsDiff::new(&base, GraphVersion(1), &failed, GraphVersion(2));
+        assert!(!diff.identical());
+    }
+}
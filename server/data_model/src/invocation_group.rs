@@ -0,0 +1,145 @@
+//! Groups of related invocations tracked together, e.g. one invocation per
+//! file of an uploaded archive. Invocations join a group when they are
+//! created, and the group finishes once it's closed and all its invocations
+//! finished.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::InvocationStatus;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum InvocationGroupStatus {
+    // No invocation of the group finished yet
+    Pending,
+    Running,
+    // All the invocations of the closed group succeeded
+    Success,
+    // The group finished and some of its invocations failed
+    Failure,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct InvocationGroup {
+    pub namespace: String,
+    pub id: String,
+    #[serde(default)]
+    pub labels: HashMap<String, serde_json::Value>,
+    pub created_at: u64,
+    // Invocations can't join a closed group
+    pub closed: bool,
+    pub invocations: u64,
+    pub succeeded: u64,
+    pub failed: u64,
+    pub finished_at: Option<u64>,
+}
+
+impl InvocationGroup {
+    pub fn new(
+        namespace: &str,
+        id: &str,
+        labels: HashMap<String, serde_json::Value>,
+        created_at: u64,
+    ) -> Self {
+        Self {
+            namespace: namespace.to_string(),
+            id: id.to_string(),
+            labels,
+            created_at,
+            closed: false,
+            invocations: 0,
+            succeeded: 0,
+            failed: 0,
+            finished_at: None,
+        }
+    }
+
+    pub fn key(&self) -> String {
+        Self::key_from(&self.namespace, &self.id)
+    }
+
+    pub fn key_from(namespace: &str, id: &str) -> String {
+        format!("{}|{}", namespace, id)
+    }
+
+    /// Key of an invocation in the members of the group.
+    pub fn member_key(&self, compute_graph: &str, invocation_id: &str) -> String {
+        Self::member_key_from(&self.namespace, &self.id, compute_graph, invocation_id)
+    }
+
+    pub fn member_key_from(
+        namespace: &str,
+        id: &str,
+        compute_graph: &str,
+        invocation_id: &str,
+    ) -> String {
+        format!("{}|{}|{}|{}", namespace, id, compute_graph, invocation_id)
+    }
+
+    pub fn status(&self) -> InvocationGroupStatus {
+        match self.finished_at {
+            Some(_) if self.failed > 0 => InvocationGroupStatus::Failure,
+            Some(_) => InvocationGroupStatus::Success,
+            None if self.succeeded + self.failed == 0 => InvocationGroupStatus::Pending,
+            None => InvocationGroupStatus::Running,
+        }
+    }
+
+    /// Records that an invocation of the group finished. Returns whether the
+    /// group finished with it.
+    pub fn invocation_finished(&mut self, status: InvocationStatus, now: u64) -> bool {
+        match status {
+            InvocationStatus::Success => self.succeeded += 1,
+            InvocationStatus::Failure => self.failed += 1,
+        }
+        self.finish_if_done(now)
+    }
+
+    /// Closes the group to new invocations. Returns whether the group
+    /// finished, which happens when all its invocations already finished.
+    pub fn close(&mut self, now: u64) -> bool {
+        self.closed = true;
+        self.finish_if_done(now)
+    }
+
+    fn finish_if_done(&mut self, now: u64) -> bool {
+        if self.finished_at.is_some() ||
+            !self.closed ||
+            self.succeeded + self.failed < self.invocations
+        {
+            return false;
+        }
+        self.finished_at = Some(now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_group_status() {
+        let mut group = InvocationGroup::new("ns", "batch", HashMap::new(), 1);
+        group.invocations = 2;
+        assert_eq!(group.status(), InvocationGroupStatus::Pending);
+
+        assert!(!group.invocation_finished(InvocationStatus::Success, 2));
+        assert_eq!(group.status(), InvocationGroupStatus::Running);
+        // Open groups don't finish, more invocations can join them
+        assert!(!group.invocation_finished(InvocationStatus::Failure, 3));
+        assert_eq!(group.status(), InvocationGroupStatus::Running);
+
+        assert!(group.close(4));
+        assert_eq!(group.finished_at, Some(4));
+        assert_eq!(group.status(), InvocationGroupStatus::Failure);
+
+        let mut group = InvocationGroup::new("ns", "batch", HashMap::new(), 1);
+        group.invocations = 1;
+        assert!(!group.close(2));
+        assert!(group.invocation_finished(InvocationStatus::Success, 3));
+        assert_eq!(group.status(), InvocationGroupStatus::Success);
+    }
+}
@@ -0,0 +1,215 @@
+//! Prefixes of the composite keys of the state store.
+//!
+//! Keys join the fields identifying an object with `|`, starting with the
+//! namespace, e.g. `<namespace>|<compute_graph>|<invocation_id>|<fn>|<id>`,
+//! so the keys of the objects of a namespace, a graph or an invocation are
+//! contiguous and can be iterated or deleted as a range.
+//!
+//! The keys of tasks, allocations, invocations and of the objects of
+//! invocations, such as function outputs and invocation state, are encoded
+//! instead, every field is its length as a big endian u32 followed by its
+//! bytes. Fields can contain any character, `ns|a` and `ns` + `a` don't share
+//! keys, and the encoded fields of a namespace, a graph or an invocation are
+//! still a prefix of the keys of their objects.
+//!
+//! Keys handed out as identifiers, such as the key of the input of a task
+//! given to executors, are textual keys: the fields are joined with `|`, with
+//! `%` and `|` within fields escaped as `%25` and `%7C`.
+
+use anyhow::{anyhow, Result};
+
+pub const KEY_SEPARATOR: u8 = b'|';
+
+const FIELD_LENGTH_BYTES: usize = 4;
+
+/// Encodes the fields of a key, see the module docs.
+pub fn encode_key(fields: &[&str]) -> Vec<u8> {
+    let mut key = Vec::with_capacity(
+        fields
+            .iter()
+            .map(|field| FIELD_LENGTH_BYTES + field.len())
+            .sum(),
+    );
+    for field in fields {
+        key.extend_from_slice(&(field.len() as u32).to_be_bytes());
+        key.extend_from_slice(field.as_bytes());
+    }
+    key
+}
+
+/// Decodes the first `count` fields of an encoded key, along with the bytes
+/// following them.
+pub fn decode_key_fields(key: &[u8], count: usize) -> Result<(Vec<String>, &[u8])> {
+    let mut fields = Vec::with_capacity(count);
+    let mut rest = key;
+    for _ in 0..count {
+        if rest.len() < FIELD_LENGTH_BYTES {
+            return Err(anyhow!("truncated key {:?}", key));
+        }
+        let (length, tail) = rest.split_at(FIELD_LENGTH_BYTES);
+        let length = u32::from_be_bytes(length.try_into()?) as usize;
+        if tail.len() < length {
+            return Err(anyhow!("truncated key {:?}", key));
+        }
+        let (field, tail) = tail.split_at(length);
+        fields.push(String::from_utf8(field.to_vec())?);
+        rest = tail;
+    }
+    Ok((fields, rest))
+}
+
+/// Decodes every field of an encoded key.
+pub fn decode_key(key: &[u8]) -> Result<Vec<String>> {
+    let mut fields = vec![];
+    let mut rest = key;
+    while !rest.is_empty() {
+        let (mut field, tail) = decode_key_fields(rest, 1)?;
+        fields.append(&mut field);
+        rest = tail;
+    }
+    Ok(fields)
+}
+
+/// Joins the fields of a textual key, see the module docs.
+pub fn join_textual_key(fields: &[&str]) -> String {
+    fields
+        .iter()
+        .map(|field| field.replace('%', "%25").replace('|', "%7C"))
+        .collect::<Vec<_>>()
+        .join("|")
+}
+
+/// Splits a textual key into its fields, see the module docs.
+pub fn split_textual_key(key: &str) -> Result<Vec<String>> {
+    key.split('|')
+        .map(|field| {
+            let mut unescaped = String::with_capacity(field.len());
+            let mut rest = field;
+            while let Some(index) = rest.find('%') {
+                unescaped.push_str(&rest[..index]);
+                match rest.get(index..index + 3) {
+                    Some("%25") => unescaped.push('%'),
+                    Some("%7C") => unescaped.push('|'),
+                    _ => return Err(anyhow!("invalid escape in key {}", key)),
+                }
+                rest = &rest[index + 3..];
+            }
+            unescaped.push_str(rest);
+            Ok(unescaped)
+        })
+        .collect()
+}
+
+/// Prefix shared by the keys whose leading fields are the given fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyPrefix(Vec<u8>);
+
+impl KeyPrefix {
+    pub fn new(fields: &[&str]) -> Self {
+        let mut prefix = Vec::new();
+        for field in fields {
+            prefix.extend_from_slice(field.as_bytes());
+            prefix.push(KEY_SEPARATOR);
+        }
+        Self(prefix)
+    }
+
+    pub fn namespace(namespace: &str) -> Self {
+        Self::new(&[namespace])
+    }
+
+    pub fn compute_graph(namespace: &str, compute_graph: &str) -> Self {
+        Self::new(&[namespace, compute_graph])
+    }
+
+    /// Prefix of the encoded keys whose leading fields are the given fields.
+    pub fn encoded(fields: &[&str]) -> Self {
+        Self(encode_key(fields))
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Exclusive end of the range of the keys with the prefix, None for the
+    /// empty prefix which covers every key.
+    pub fn upper_bound(&self) -> Option<Vec<u8>> {
+        prefix_upper_bound(&self.0)
+    }
+}
+
+/// Smallest key greater than every key starting with the prefix, None when
+/// there is none.
+pub fn prefix_upper_bound(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut upper_bound = prefix.to_vec();
+    while let Some(last) = upper_bound.pop() {
+        if last < u8::MAX {
+            upper_bound.push(last + 1);
+            return Some(upper_bound);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_prefix() {
+        let prefix = KeyPrefix::new(&["ns", "graph", "inv"]);
+        assert_eq!(prefix.as_bytes(), b"ns|graph|inv|");
+        let upper_bound = prefix.upper_bound().unwrap();
+        assert_eq!(upper_bound, b"ns|graph|inv}".to_vec());
+
+        // Keys of the invocation sort within the range, the keys of an
+        // invocation whose id extends this one don't
+        for key in [&b"ns|graph|inv|fn|1"[..], b"ns|graph|inv|~"] {
+            assert!(key.starts_with(prefix.as_bytes()) && key < upper_bound.as_slice());
+        }
+        assert!(!b"ns|graph|inv1|fn|1".starts_with(prefix.as_bytes()));
+        assert!(b"ns|graph|inv1|fn|1".as_slice() < prefix.as_bytes());
+
+        assert_eq!(prefix_upper_bound(&[1, u8::MAX]), Some(vec![2]));
+        assert_eq!(prefix_upper_bound(&[u8::MAX]), None);
+        assert_eq!(KeyPrefix::new(&[]).upper_bound(), None);
+    }
+
+    #[test]
+    fn test_encoded_keys() {
+        let key = encode_key(&["ns|a", "graph_b", "", "inv"]);
+        assert_eq!(
+            decode_key(&key).unwrap(),
+            vec!["ns|a", "graph_b", "", "inv"]
+        );
+        let (fields, rest) = decode_key_fields(&key, 2).unwrap();
+        assert_eq!(fields, vec!["ns|a", "graph_b"]);
+        assert_eq!(decode_key(rest).unwrap(), vec!["", "inv"]);
+        assert!(decode_key(&key[..key.len() - 1]).is_err());
+
+        // Fields containing the separator don't collide
+        assert_ne!(encode_key(&["ns|a", "b"]), encode_key(&["ns", "a|b"]));
+
+        // The keys of an invocation are in the range of its prefix, the keys
+        // of an invocation whose id extends its id aren't
+        let prefix = KeyPrefix::encoded(&["ns", "graph", "inv"]);
+        let upper_bound = prefix.upper_bound().unwrap();
+        let key = encode_key(&["ns", "graph", "inv", "fn", "1"]);
+        assert!(key.starts_with(prefix.as_bytes()) && key < upper_bound);
+        let key = encode_key(&["ns", "graph", "inv1", "fn", "1"]);
+        assert!(!key.starts_with(prefix.as_bytes()));
+    }
+
+    #[test]
+    fn test_textual_keys() {
+        assert_eq!(join_textual_key(&["ns", "graph", "id"]), "ns|graph|id");
+        let key = join_textual_key(&["ns|a", "100%", "", "%7C"]);
+        assert_eq!(key, "ns%7Ca|100%25||%257C");
+        assert_eq!(
+            split_textual_key(&key).unwrap(),
+            vec!["ns|a", "100%", "", "%7C"]
+        );
+        assert!(split_textual_key("ns|100%").is_err());
+        assert!(split_textual_key("ns|%41").is_err());
+    }
+}
@@ -0,0 +1,128 @@
+//! Execution latencies of the tasks of compute functions. Latencies are added
+//! to log-bucketed sketches, which estimate quantiles within a relative error
+//! of 1% in a size bounded by the range of the latencies rather than by their
+//! number.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+// Relative error of the estimated quantiles
+const RELATIVE_ACCURACY: f64 = 0.01;
+
+fn gamma() -> f64 {
+    (1.0 + RELATIVE_ACCURACY) / (1.0 - RELATIVE_ACCURACY)
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct LatencySketch {
+    pub count: u64,
+    pub sum_ms: u64,
+    pub max_ms: u64,
+    // Latencies under 1ms, the buckets only hold positive latencies
+    zero_count: u64,
+    // Bucket index -> number of latencies in (gamma^(index-1), gamma^index]
+    buckets: BTreeMap<i32, u64>,
+}
+
+impl LatencySketch {
+    pub fn add(&mut self, latency_ms: u64) {
+        self.count += 1;
+        self.sum_ms += latency_ms;
+        self.max_ms = self.max_ms.max(latency_ms);
+        if latency_ms == 0 {
+            self.zero_count += 1;
+            return;
+        }
+        let index = ((latency_ms as f64).ln() / gamma().ln()).ceil() as i32;
+        *self.buckets.entry(index).or_default() += 1;
+    }
+
+    pub fn mean_ms(&self) -> Option<u64> {
+        (self.count > 0).then(|| self.sum_ms / self.count)
+    }
+
+    /// Estimated latency of the quantile `q` in [0, 1], None until a latency
+    /// is added.
+    pub fn quantile(&self, q: f64) -> Option<u64> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = ((q.clamp(0.0, 1.0) * self.count as f64).ceil() as u64).max(1);
+        let mut seen = self.zero_count;
+        if seen >= rank {
+            return Some(0);
+        }
+        let gamma = gamma();
+        for (index, count) in &self.buckets {
+            seen += count;
+            // The last bucket holds the maximum, which is known exactly
+            if seen == self.count {
+                return Some(self.max_ms);
+            }
+            if seen >= rank {
+                let estimate = 2.0 * gamma.powi(*index) / (gamma + 1.0);
+                return Some((estimate.round() as u64).min(self.max_ms));
+            }
+        }
+        Some(self.max_ms)
+    }
+}
+
+/// Latencies of the finished tasks of a compute function, measured from their
+/// allocation, and how many of them started in a warm function environment.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FunctionLatency {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub compute_fn: String,
+    pub sketch: LatencySketch,
+    // Tasks whose executor loaded the environment of the function, or reused
+    // a warm one. Tasks of executors not reporting it count in neither.
+    #[serde(default)]
+    pub cold_starts: u64,
+    #[serde(default)]
+    pub warm_starts: u64,
+    pub updated_at: u64,
+}
+
+impl FunctionLatency {
+    pub fn key(&self) -> String {
+        Self::key_from(&self.namespace, &self.compute_graph, &self.compute_fn)
+    }
+
+    pub fn key_from(namespace: &str, compute_graph: &str, compute_fn: &str) -> String {
+        format!("{}|{}|{}", namespace, compute_graph, compute_fn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quantiles() {
+        let mut sketch = LatencySketch::default();
+        assert_eq!(sketch.quantile(0.5), None);
+
+        for latency_ms in 1..=1000 {
+            sketch.add(latency_ms);
+        }
+        let within = |estimate: Option<u64>, expected: u64| {
+            let estimate = estimate.unwrap() as f64;
+            (estimate - expected as f64).abs() <= expected as f64 * RELATIVE_ACCURACY + 1.0
+        };
+        assert!(within(sketch.quantile(0.5), 500));
+        assert!(within(sketch.quantile(0.9), 900));
+        assert!(within(sketch.quantile(0.99), 990));
+        assert_eq!(sketch.quantile(1.0), Some(1000));
+        assert_eq!(sketch.mean_ms(), Some(500));
+
+        let mut sketch = LatencySketch::default();
+        sketch.add(0);
+        sketch.add(0);
+        sketch.add(40);
+        assert_eq!(sketch.quantile(0.5), Some(0));
+        assert_eq!(sketch.quantile(0.99), Some(40));
+    }
+}
@@ -0,0 +1,93 @@
+//! Legal holds. A hold on an invocation, or on every invocation of a compute
+//! graph, exempts them from the expiry of their payloads and from being
+//! deleted or purged, including with their namespace, until it's cleared.
+//! Every hold set or cleared is recorded in an audit log.
+
+use serde::{Deserialize, Serialize};
+
+use crate::keys::KeyPrefix;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LegalHold {
+    pub namespace: String,
+    pub compute_graph: String,
+    /// Every invocation of the graph is held when unset
+    pub invocation_id: Option<String>,
+    pub reason: String,
+    /// Identity which set the hold
+    pub set_by: String,
+    /// Milliseconds since the epoch
+    pub set_at: u64,
+}
+
+impl LegalHold {
+    /// The hold of a graph is keyed by the prefix of the keys of its
+    /// invocations' holds, so a prefix scan finds both.
+    pub fn key_from(namespace: &str, compute_graph: &str, invocation_id: Option<&str>) -> String {
+        format!(
+            "{}|{}|{}",
+            namespace,
+            compute_graph,
+            invocation_id.unwrap_or_default()
+        )
+    }
+
+    pub fn key(&self) -> String {
+        Self::key_from(
+            &self.namespace,
+            &self.compute_graph,
+            self.invocation_id.as_deref(),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LegalHoldAction {
+    Set,
+    Cleared,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LegalHoldAuditEntry {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub invocation_id: Option<String>,
+    pub action: LegalHoldAction,
+    pub identity: String,
+    pub reason: String,
+    /// Milliseconds since the epoch
+    pub at: u64,
+}
+
+impl LegalHoldAuditEntry {
+    pub fn key_prefix(namespace: &str) -> KeyPrefix {
+        KeyPrefix::namespace(namespace)
+    }
+
+    /// Entries of a namespace are ordered by the time they were recorded at
+    /// in nanoseconds, `seq` tells apart the entries recorded at the same
+    /// time.
+    pub fn key(namespace: &str, recorded_at_nanos: u128, seq: u32) -> Vec<u8> {
+        let mut key = Self::key_prefix(namespace).as_bytes().to_vec();
+        key.extend_from_slice(format!("{:020}|{:010}", recorded_at_nanos, seq).as_bytes());
+        key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hold_keys() {
+        let graph_key = LegalHold::key_from("ns", "graph", None);
+        let invocation_key = LegalHold::key_from("ns", "graph", Some("inv"));
+        assert_eq!(graph_key, "ns|graph|");
+        assert!(invocation_key.starts_with(&graph_key));
+        assert!(graph_key
+            .as_bytes()
+            .starts_with(KeyPrefix::compute_graph("ns", "graph").as_bytes()));
+        assert!(!LegalHold::key_from("ns", "graph_b", None).starts_with(&graph_key));
+    }
+}
@@ -1,17 +1,41 @@
+pub mod alerts;
+pub mod attributes;
+pub mod blacklist;
+pub mod canary;
+pub mod concurrency_key;
+pub mod data_deletion;
+pub mod error;
+pub mod eta;
+pub mod executor_approval;
+pub mod executor_reconciliation;
 pub mod filter;
+pub mod ingestion;
+pub mod invocation_diff;
+pub mod invocation_group;
+pub mod keys;
+pub mod latency;
+pub mod legal_hold;
+pub mod lint;
+pub mod parameters;
+pub mod sinks;
+pub mod sla;
 pub mod test_objects;
+pub mod timeline;
+pub mod webhooks;
 
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
     fmt::{self, Display},
     hash::{DefaultHasher, Hash, Hasher},
+    str::FromStr,
     time::{SystemTime, UNIX_EPOCH},
 };
 
 use anyhow::{anyhow, Result};
 use derive_builder::Builder;
+use error::DataModelError;
 use filter::LabelsFilter;
-use indexify_utils::default_creation_time;
+use indexify_utils::{default_creation_time, get_epoch_time_in_ms};
 use serde::{Deserialize, Serialize};
 
 // Invoke graph for all existing payloads
@@ -64,6 +88,10 @@ impl TaskId {
     pub fn new(id: String) -> Self {
         Self(id)
     }
+
+    pub fn get(&self) -> &str {
+        &self.0
+    }
 }
 
 impl Display for TaskId {
@@ -86,6 +114,167 @@ pub struct DynamicEdgeRouter {
     pub target_functions: Vec<String>,
     pub payload_encoder: String,
     pub image_name: String,
+    // Routers with a WASM module are run by the server instead of an executor
+    #[serde(default)]
+    #[builder(default)]
+    pub wasm_module: Option<DataPayload>,
+    // Targets the input is routed to when the router selects none, or when it
+    // fails and on_error is route_to_default
+    #[serde(default)]
+    #[builder(default)]
+    pub default_targets: Vec<String>,
+    #[serde(default)]
+    #[builder(default)]
+    pub on_error: RouterErrorPolicy,
+}
+
+// What the scheduler does with the branch of a router which failed
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RouterErrorPolicy {
+    // The branch ends and the invocation fails
+    #[default]
+    FailInvocation,
+    // The input is routed to the default targets
+    RouteToDefault,
+    // The branch ends without failing the invocation
+    Skip,
+}
+
+impl DynamicEdgeRouter {
+    /// Checks that the default targets are targets of the router, and that
+    /// there are some if failures are routed to them.
+    pub fn validate(&self) -> Result<(), DataModelError> {
+        for target in &self.default_targets {
+            if !self.target_functions.contains(target) {
+                return Err(DataModelError::InvalidGraph(format!(
+                    "default target {} of router {} is not one of its target functions",
+                    target, self.name
+                )));
+            }
+        }
+        if self.on_error == RouterErrorPolicy::RouteToDefault && self.default_targets.is_empty() {
+            return Err(DataModelError::InvalidGraph(format!(
+                "router {} routes failures to its default targets but has none",
+                self.name
+            )));
+        }
+        Ok(())
+    }
+
+    /// Targets of a task of the router which failed, `None` if its failure
+    /// fails the invocation.
+    pub fn targets_on_error(&self) -> Option<&[String]> {
+        match self.on_error {
+            RouterErrorPolicy::FailInvocation => None,
+            RouterErrorPolicy::RouteToDefault => Some(&self.default_targets),
+            RouterErrorPolicy::Skip => Some(&[]),
+        }
+    }
+}
+
+// Allows at most max_tasks tasks of a function to be allocated per window
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RateLimit {
+    pub max_tasks: u32,
+    pub window_secs: u64,
+}
+
+// Bounds the outputs a single task of the function emits, the outputs it
+// streamed included. Tasks exceeding them fail and their outputs are dropped.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OutputLimits {
+    #[serde(default)]
+    pub max_outputs: Option<u64>,
+    #[serde(default)]
+    pub max_output_bytes: Option<u64>,
+}
+
+impl OutputLimits {
+    /// The failure of a task which emitted `outputs` outputs of
+    /// `output_bytes` bytes in total, if they exceed the limits.
+    pub fn check(&self, outputs: u64, output_bytes: u64) -> Option<TaskFailure> {
+        let exceeded = |limit, max: Option<u64>, actual| {
+            max.filter(|max| actual > *max)
+                .map(|max| TaskFailure::OutputLimitExceeded { limit, max, actual })
+        };
+        exceeded(OutputLimit::Outputs, self.max_outputs, outputs).or_else(|| {
+            exceeded(
+                OutputLimit::OutputBytes,
+                self.max_output_bytes,
+                output_bytes,
+            )
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputLimit {
+    Outputs,
+    OutputBytes,
+}
+
+// Why the server failed a task, as opposed to the function raising
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case", tag = "reason")]
+pub enum TaskFailure {
+    OutputLimitExceeded {
+        limit: OutputLimit,
+        max: u64,
+        actual: u64,
+    },
+    // The input of an event-time window arrived after the watermark passed
+    // the end of its window, and the window has no late output
+    LateInput {
+        event_time: u64,
+        watermark: u64,
+    },
+}
+
+// GPUs a task of the function needs, they are reserved on the executor for
+// the duration of the task
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GpuRequirement {
+    // Any GPU model is accepted when not set
+    #[serde(default)]
+    pub model: Option<String>,
+    pub count: u32,
+}
+
+impl GpuRequirement {
+    pub fn matches_model(&self, model: &str) -> bool {
+        match &self.model {
+            Some(required) => required.eq_ignore_ascii_case(model),
+            None => true,
+        }
+    }
+}
+
+/// Sandbox a function runs in. Executors advertise the runtimes they can
+/// start, tasks of a function declaring a runtime are only placed on the
+/// executors advertising a matching one.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub struct RuntimeDescriptor {
+    // Container image, including its tag
+    pub image: String,
+    #[serde(default)]
+    pub python_version: Option<String>,
+    // Hash of the pip requirements installed in the image
+    #[serde(default)]
+    pub pip_hash: Option<String>,
+}
+
+impl RuntimeDescriptor {
+    /// Fields the requirement leaves unset match any advertised value.
+    pub fn satisfied_by(&self, advertised: &RuntimeDescriptor) -> bool {
+        fn matches(required: &Option<String>, advertised: &Option<String>) -> bool {
+            required.is_none() || required == advertised
+        }
+        self.image == advertised.image &&
+            matches(&self.python_version, &advertised.python_version) &&
+            matches(&self.pip_hash, &advertised.pip_hash)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
@@ -97,11 +286,204 @@ pub struct ComputeFn {
     pub reducer: bool,
     pub payload_encoder: String,
     pub image_name: String,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    // Secret name -> sealed secret value, never stored in plaintext
+    #[serde(default)]
+    pub secrets: HashMap<String, String>,
+    #[serde(default)]
+    pub rate_limit: Option<RateLimit>,
+    // Name of the executor pool of the namespace the fn is placed on
+    #[serde(default)]
+    pub executor_pool: Option<String>,
+    #[serde(default)]
+    pub gpu: Option<GpuRequirement>,
+    // Template of the key serializing the tasks of the function, see
+    // concurrency_key
+    #[serde(default)]
+    pub concurrency_key: Option<String>,
+    #[serde(default)]
+    pub runtime: Option<RuntimeDescriptor>,
+    #[serde(default)]
+    pub output_limits: Option<OutputLimits>,
+    // Environments of the function executors keep loaded between its tasks,
+    // spread across the executors it can run on
+    #[serde(default)]
+    pub min_warm_instances: u32,
 }
 
 impl ComputeFn {
     pub fn matches_executor(&self, executor: &ExecutorMetadata) -> bool {
-        self.placement_constraints.matches(&executor.labels)
+        self.placement_constraints.matches(&executor.labels) &&
+            self.runtime
+                .as_ref()
+                .map_or(true, |runtime| executor.supports_runtime(runtime))
+    }
+}
+
+// Invokes another compute graph of the same namespace with the node's input.
+// The final outputs of the child invocation become the outputs of the node.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SubGraph {
+    pub name: String,
+    pub description: String,
+    pub compute_graph: String,
+}
+
+// Pauses a branch of an invocation until the gate is signaled, e.g. by a
+// human approval. The payload of the signal is the output of the node.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Gate {
+    pub name: String,
+    pub description: String,
+}
+
+// Accumulates its inputs across the invocations of the graph and emits them
+// together once the window closes, after max_items inputs or when the oldest
+// input waited max_age_secs. The invocation closing the window runs the
+// downstream nodes with the batch, the other invocations finish at the window.
+//
+// With event_time_secs set, inputs are grouped in tumbling windows of that
+// many seconds by the event time of their invocation instead, each closing
+// once the watermark passes its end. The watermark trails the latest event
+// time seen by allowed_lateness_secs. Inputs of windows already closed are
+// late, they're routed to the late_output node.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Window {
+    pub name: String,
+    pub description: String,
+    pub max_items: Option<u32>,
+    pub max_age_secs: Option<u64>,
+    #[serde(default)]
+    pub event_time_secs: Option<u64>,
+    #[serde(default)]
+    pub allowed_lateness_secs: u64,
+    #[serde(default)]
+    pub late_output: Option<String>,
+}
+
+impl Window {
+    pub fn validate(&self) -> Result<(), DataModelError> {
+        if self.max_items.is_none() &&
+            self.max_age_secs.is_none() &&
+            self.event_time_secs.is_none()
+        {
+            return Err(DataModelError::InvalidGraph(format!(
+                "window {} needs max_items, max_age_secs or event_time_secs",
+                self.name
+            )));
+        }
+        if self.max_items == Some(0) ||
+            self.max_age_secs == Some(0) ||
+            self.event_time_secs == Some(0)
+        {
+            return Err(DataModelError::InvalidGraph(format!(
+                "invalid window {}, max_items, max_age_secs and event_time_secs must be positive",
+                self.name
+            )));
+        }
+        if self.event_time_secs.is_none() &&
+            (self.allowed_lateness_secs > 0 || self.late_output.is_some())
+        {
+            return Err(DataModelError::InvalidGraph(format!(
+                "window {} needs event_time_secs for allowed_lateness_secs and late_output",
+                self.name
+            )));
+        }
+        Ok(())
+    }
+
+    /// End of the event-time window of an input with the event time, `None`
+    /// for windows without event time. Both in milliseconds.
+    pub fn event_window_end(&self, event_time: u64) -> Option<u64> {
+        self.event_time_secs.map(|secs| {
+            let size = secs * 1000;
+            (event_time / size + 1) * size
+        })
+    }
+
+    /// Watermark of the window once it saw inputs up to `max_event_time`,
+    /// event-time windows ending at or before it are closed.
+    pub fn watermark(&self, max_event_time: u64) -> u64 {
+        max_event_time.saturating_sub(self.allowed_lateness_secs * 1000)
+    }
+
+    /// Whether the oldest input of the window, buffered at `oldest_at`, waited
+    /// long enough for the window to close at `now`. Both in milliseconds.
+    pub fn expired(&self, oldest_at: u64, now: u64) -> bool {
+        self.max_age_secs
+            .is_some_and(|max_age| oldest_at.saturating_add(max_age * 1000) <= now)
+    }
+}
+
+/// Task of a window waiting for the window to close.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WindowItem {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub window: String,
+    pub task_key: Vec<u8>,
+    // Milliseconds since the epoch
+    pub buffered_at: u64,
+    // Event time of the invocation of the task, in milliseconds since the
+    // epoch
+    #[serde(default)]
+    pub event_time: Option<u64>,
+}
+
+impl WindowItem {
+    pub fn new(task: &Task, buffered_at: u64, event_time: Option<u64>) -> Self {
+        Self {
+            namespace: task.namespace.clone(),
+            compute_graph: task.compute_graph_name.clone(),
+            window: task.compute_fn_name.clone(),
+            task_key: task.key(),
+            buffered_at,
+            event_time,
+        }
+    }
+
+    /// Event time of the input, the time it was buffered for invocations
+    /// without event time.
+    pub fn event_time(&self) -> u64 {
+        self.event_time.unwrap_or(self.buffered_at)
+    }
+
+    /// Items of a window are ordered by the time they were buffered.
+    pub fn key(&self) -> Vec<u8> {
+        let mut key = format!(
+            "{}{:020}|",
+            Self::key_prefix(&self.namespace, &self.compute_graph, &self.window),
+            self.buffered_at,
+        )
+        .into_bytes();
+        key.extend_from_slice(&self.task_key);
+        key
+    }
+
+    pub fn key_prefix(namespace: &str, compute_graph: &str, window: &str) -> String {
+        format!("{}|{}|{}|", namespace, compute_graph, window)
+    }
+}
+
+/// Latest event time seen by an event-time window, its watermark trails it
+/// by the allowed lateness of the window.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WindowWatermark {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub window: String,
+    // Milliseconds since the epoch
+    pub max_event_time: u64,
+}
+
+impl WindowWatermark {
+    pub fn key(&self) -> String {
+        Self::key_from(&self.namespace, &self.compute_graph, &self.window)
+    }
+
+    pub fn key_from(namespace: &str, compute_graph: &str, window: &str) -> String {
+        format!("{}|{}|{}", namespace, compute_graph, window)
     }
 }
 
@@ -109,6 +491,10 @@ impl ComputeFn {
 pub enum Node {
     Router(DynamicEdgeRouter),
     Compute(ComputeFn),
+    SubGraph(SubGraph),
+    Gate(Gate),
+    Window(Window),
+    Sink(sinks::Sink),
 }
 
 impl Node {
@@ -116,6 +502,10 @@ impl Node {
         match self {
             Node::Router(router) => &router.name,
             Node::Compute(compute) => &compute.name,
+            Node::SubGraph(sub_graph) => &sub_graph.name,
+            Node::Gate(gate) => &gate.name,
+            Node::Window(window) => &window.name,
+            Node::Sink(sink) => &sink.name,
         }
     }
 
@@ -123,22 +513,118 @@ impl Node {
         match self {
             Node::Router(router) => &router.image_name,
             Node::Compute(compute) => &compute.image_name,
+            Node::SubGraph(_) | Node::Gate(_) | Node::Window(_) | Node::Sink(_) => "",
         }
     }
 
     pub fn matches_executor(&self, executor: &ExecutorMetadata) -> bool {
         match self {
-            Node::Router(_) => true,
+            Node::Router(router) => router.wasm_module.is_none(),
             Node::Compute(compute) => compute.matches_executor(executor),
+            // Sub graphs are run by the server, never by an executor, gates
+            // wait for a signal, windows are closed and sinks written by the
+            // server
+            Node::SubGraph(_) | Node::Gate(_) | Node::Window(_) | Node::Sink(_) => false,
         }
     }
 
     pub fn reducer(&self) -> bool {
         match self {
-            Node::Router(_) => false,
+            Node::Router(_) |
+            Node::SubGraph(_) |
+            Node::Gate(_) |
+            Node::Window(_) |
+            Node::Sink(_) => false,
             Node::Compute(compute) => compute.reducer,
         }
     }
+
+    pub fn rate_limit(&self) -> Option<&RateLimit> {
+        match self {
+            Node::Router(_) |
+            Node::SubGraph(_) |
+            Node::Gate(_) |
+            Node::Window(_) |
+            Node::Sink(_) => None,
+            Node::Compute(compute) => compute.rate_limit.as_ref(),
+        }
+    }
+
+    pub fn executor_pool(&self) -> Option<&str> {
+        match self {
+            Node::Router(_) |
+            Node::SubGraph(_) |
+            Node::Gate(_) |
+            Node::Window(_) |
+            Node::Sink(_) => None,
+            Node::Compute(compute) => compute.executor_pool.as_deref(),
+        }
+    }
+
+    pub fn gpu(&self) -> Option<&GpuRequirement> {
+        match self {
+            Node::Router(_) |
+            Node::SubGraph(_) |
+            Node::Gate(_) |
+            Node::Window(_) |
+            Node::Sink(_) => None,
+            Node::Compute(compute) => compute.gpu.as_ref(),
+        }
+    }
+
+    pub fn concurrency_key(&self) -> Option<&str> {
+        match self {
+            Node::Router(_) |
+            Node::SubGraph(_) |
+            Node::Gate(_) |
+            Node::Window(_) |
+            Node::Sink(_) => None,
+            Node::Compute(compute) => compute.concurrency_key.as_deref(),
+        }
+    }
+
+    pub fn runtime(&self) -> Option<&RuntimeDescriptor> {
+        match self {
+            Node::Router(_) |
+            Node::SubGraph(_) |
+            Node::Gate(_) |
+            Node::Window(_) |
+            Node::Sink(_) => None,
+            Node::Compute(compute) => compute.runtime.as_ref(),
+        }
+    }
+
+    pub fn wasm_module(&self) -> Option<&DataPayload> {
+        match self {
+            Node::Router(router) => router.wasm_module.as_ref(),
+            _ => None,
+        }
+    }
+
+    pub fn sub_graph(&self) -> Option<&str> {
+        match self {
+            Node::SubGraph(sub_graph) => Some(&sub_graph.compute_graph),
+            _ => None,
+        }
+    }
+
+    pub fn is_gate(&self) -> bool {
+        matches!(self, Node::Gate(_))
+    }
+
+    pub fn window(&self) -> Option<&Window> {
+        match self {
+            Node::Window(window) => Some(window),
+            _ => None,
+        }
+    }
+
+    pub fn sink(&self) -> Option<&sinks::Sink> {
+        match self {
+            Node::Sink(sink) => Some(sink),
+            _ => None,
+        }
+    }
 }
 
 impl Node {
@@ -151,10 +637,7 @@ impl Node {
         reducer_output_id: Option<String>,
         graph_version: GraphVersion,
     ) -> Result<Task> {
-        let name = match self {
-            Node::Router(router) => router.name.clone(),
-            Node::Compute(compute) => compute.name.clone(),
-        };
+        let name = self.name().to_string();
         let task = TaskBuilder::default()
             .namespace(namespace.to_string())
             .compute_fn_name(name)
@@ -175,10 +658,7 @@ impl Node {
         task_id: &str,
         task_output_key: &str,
     ) -> ReduceTask {
-        let name = match self {
-            Node::Router(router) => router.name.clone(),
-            Node::Compute(compute) => compute.name.clone(),
-        };
+        let name = self.name().to_string();
         ReduceTask {
             namespace: namespace.to_string(),
             compute_graph_name: compute_graph_name.to_string(),
@@ -212,6 +692,15 @@ impl Default for GraphVersion {
     }
 }
 
+/// Pause of a compute graph, new invocations are accepted unless
+/// `reject_invocations` is set.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GraphPause {
+    pub paused_at: u64,
+    #[serde(default)]
+    pub reject_invocations: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct RuntimeInformation {
     pub major_version: u8,
@@ -224,18 +713,390 @@ pub struct ComputeGraph {
     pub name: String,
     pub description: String,
     pub version: GraphVersion, // Version incremented with code update
+    // Incremented with every update, used to detect concurrent updates
+    #[serde(default)]
+    pub revision: u64,
     pub code: ComputeGraphCode,
     pub created_at: u64,
     pub start_fn: Node,
     pub nodes: HashMap<String, Node>,
     pub edges: HashMap<String, Vec<String>>,
     pub runtime_information: RuntimeInformation,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    // Secret name -> sealed secret value, never stored in plaintext
+    #[serde(default)]
+    pub secrets: HashMap<String, String>,
+    #[serde(default)]
+    pub queue_limits: QueueLimits,
+    #[serde(default)]
+    pub sla: Option<sla::GraphSla>,
+    // Time the graph was deleted at, deleted graphs can be restored until
+    // they are purged
+    #[serde(default)]
+    pub deleted_at: Option<u64>,
+    // Set while the graph is paused, no task of the graph is allocated
+    #[serde(default)]
+    pub paused: Option<GraphPause>,
+    // Parameters referenced by the function configs as `{params.<name>}`
+    #[serde(default)]
+    pub parameters: HashMap<String, parameters::GraphParameter>,
+    // Values bound when the graph was instantiated from a template
+    #[serde(default)]
+    pub parameter_values: HashMap<String, serde_json::Value>,
+    // Graph the graph was instantiated from
+    #[serde(default)]
+    pub template: Option<String>,
+    // Transforms of the invocation payloads, in the order they run
+    #[serde(default)]
+    pub ingestion_hooks: Vec<ingestion::IngestionTransform>,
+    // Lane of the invocations which don't choose one
+    #[serde(default)]
+    pub default_lane: SchedulingLane,
+    // Payloads of the graph are stored uncompressed, for media which is
+    // already compressed like images or video
+    #[serde(default)]
+    pub skip_payload_compression: bool,
 }
 
 impl ComputeGraph {
     pub fn key(&self) -> String {
         format!("{}|{}", self.namespace, self.name)
     }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.is_some()
+    }
+
+    /// Checks that edges only reference nodes of the graph, that rate
+    /// limits, GPU counts, sinks, the SLA and the ingestion hooks are valid
+    /// and that configs only reference declared parameters.
+    pub fn validate(&self) -> Result<(), DataModelError> {
+        if let Some(sla) = &self.sla {
+            sla.validate()?;
+        }
+        parameters::validate_parameter_values(&self.parameters, &self.parameter_values)?;
+        ingestion::validate_hooks(&self.ingestion_hooks, &self.start_fn)?;
+        let fn_envs = self.nodes.values().filter_map(|node| match node {
+            Node::Compute(compute_fn) => Some(&compute_fn.env),
+            _ => None,
+        });
+        for value in std::iter::once(&self.env)
+            .chain(fn_envs)
+            .flat_map(|env| env.values())
+        {
+            for name in parameters::referenced_parameters(value)? {
+                if !self.parameters.contains_key(name) {
+                    return Err(DataModelError::InvalidParameter(format!(
+                        "undeclared parameter {} referenced in env",
+                        name
+                    )));
+                }
+            }
+        }
+        for (from, targets) in &self.edges {
+            for name in std::iter::once(from).chain(targets) {
+                if !self.nodes.contains_key(name) {
+                    return Err(DataModelError::InvalidGraph(format!(
+                        "edge references unknown node {}",
+                        name
+                    )));
+                }
+            }
+        }
+        for (name, node) in &self.nodes {
+            if let Some(rate_limit) = node.rate_limit() {
+                if rate_limit.max_tasks == 0 || rate_limit.window_secs == 0 {
+                    return Err(DataModelError::InvalidGraph(format!(
+                        "invalid rate limit for fn {}, max_tasks and window_secs must be positive",
+                        name
+                    )));
+                }
+            }
+            if let Some(gpu) = node.gpu() {
+                if gpu.count == 0 {
+                    return Err(DataModelError::InvalidGraph(format!(
+                        "invalid gpu requirement for fn {}, count must be positive",
+                        name
+                    )));
+                }
+            }
+            if let Some(template) = node.concurrency_key() {
+                concurrency_key::validate_concurrency_key(template)?;
+            }
+            if let Node::Router(router) = node {
+                router.validate()?;
+            }
+            if let Some(window) = node.window() {
+                window.validate()?;
+                // The batch of the window is the single input of the
+                // downstream functions, and late inputs of the late output
+                let late_output = window.late_output.iter();
+                for target in self.edges.get(name).into_iter().flatten().chain(late_output) {
+                    match self.nodes.get(target) {
+                        Some(Node::Compute(compute_fn)) if !compute_fn.reducer => {}
+                        _ => {
+                            return Err(DataModelError::InvalidGraph(format!(
+                                "window {} can only be followed by functions which aren't reducers, not {}",
+                                name, target
+                            )))
+                        }
+                    }
+                }
+            }
+            if let Some(sink) = node.sink() {
+                sink.validate(&self.secrets)?;
+                if self.edges.get(name).is_some_and(|edges| !edges.is_empty()) {
+                    return Err(DataModelError::InvalidGraph(format!(
+                        "sink {} has no outputs and can't be followed by other nodes",
+                        name
+                    )));
+                }
+            }
+        }
+        if self.start_fn.window().is_some() {
+            return Err(DataModelError::InvalidGraph(
+                "the start node can't be a window".to_string(),
+            ));
+        }
+        if self.start_fn.sink().is_some() {
+            return Err(DataModelError::InvalidGraph(
+                "the start node can't be a sink".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Names of the compute graphs invoked by the sub graph nodes.
+    pub fn sub_graphs(&self) -> Vec<&str> {
+        let mut sub_graphs: Vec<&str> = self
+            .nodes
+            .values()
+            .chain(std::iter::once(&self.start_fn))
+            .filter_map(|node| node.sub_graph())
+            .collect();
+        sub_graphs.sort();
+        sub_graphs.dedup();
+        sub_graphs
+    }
+
+    /// Returns the chain of graph names leading back to this graph if
+    /// following sub graph references forms a cycle. `lookup` resolves the
+    /// other graphs of the namespace.
+    pub fn find_sub_graph_cycle<F>(&self, lookup: F) -> Result<Option<Vec<String>>>
+    where
+        F: Fn(&str) -> Result<Option<ComputeGraph>>,
+    {
+        let mut stack: Vec<(String, Vec<String>)> = self
+            .sub_graphs()
+            .into_iter()
+            .map(|name| (name.to_string(), vec![self.name.clone()]))
+            .collect();
+        let mut visited = std::collections::HashSet::new();
+        while let Some((name, mut path)) = stack.pop() {
+            path.push(name.clone());
+            if name == self.name {
+                return Ok(Some(path));
+            }
+            if !visited.insert(name.clone()) {
+                continue;
+            }
+            let graph = match lookup(&name)? {
+                Some(graph) => graph,
+                None => continue,
+            };
+            for child in graph.sub_graphs() {
+                stack.push((child.to_string(), path.clone()));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Nodes whose outputs are the final outputs of an invocation.
+    pub fn terminal_nodes(&self) -> Vec<&Node> {
+        self.nodes
+            .values()
+            .filter(|node| {
+                !matches!(node, Node::Router(_)) &&
+                    self.edges
+                        .get(node.name())
+                        .map_or(true, |edges| edges.is_empty())
+            })
+            .collect()
+    }
+
+    /// Nodes an invocation of the graph would run, in the order their tasks
+    /// would be created. Router targets are only run if the router picks them,
+    /// and late outputs of windows for late inputs, they are marked as
+    /// dynamic unless a static edge also leads to them.
+    pub fn plan(&self) -> InvocationPlan {
+        let start = self.start_fn.name().to_string();
+        let mut static_nodes = HashSet::from([start.clone()]);
+        let mut stack = vec![start.clone()];
+        while let Some(name) = stack.pop() {
+            for child in self.edges.get(&name).into_iter().flatten() {
+                if static_nodes.insert(child.clone()) {
+                    stack.push(child.clone());
+                }
+            }
+        }
+
+        let mut parents: HashMap<String, Vec<String>> = HashMap::new();
+        let mut order = vec![start.clone()];
+        let mut visited = HashSet::from([start]);
+        let mut queue = VecDeque::from(order.clone());
+        while let Some(name) = queue.pop_front() {
+            let mut children: Vec<&String> = self.edges.get(&name).into_iter().flatten().collect();
+            match self.nodes.get(&name) {
+                Some(Node::Router(router)) => children.extend(&router.target_functions),
+                Some(Node::Window(window)) => children.extend(&window.late_output),
+                _ => {}
+            }
+            for child in children {
+                parents.entry(child.clone()).or_default().push(name.clone());
+                if visited.insert(child.clone()) {
+                    order.push(child.clone());
+                    queue.push_back(child.clone());
+                }
+            }
+        }
+
+        let mut unreachable: Vec<String> = self
+            .nodes
+            .keys()
+            .filter(|name| !visited.contains(*name))
+            .cloned()
+            .collect();
+        unreachable.sort();
+        InvocationPlan {
+            nodes: order
+                .into_iter()
+                .map(|name| PlannedNode {
+                    parents: parents.remove(&name).unwrap_or_default(),
+                    dynamic: !static_nodes.contains(&name),
+                    name,
+                })
+                .collect(),
+            unreachable,
+        }
+    }
+
+    /// Edges of the graph sorted by their nodes. The targets of a router are
+    /// dynamic edges, only followed if the router picks the target, as is the
+    /// late output of a window.
+    pub fn topology_edges(&self) -> Vec<GraphEdge> {
+        let mut edges: BTreeMap<(String, String), bool> = BTreeMap::new();
+        for (from, targets) in &self.edges {
+            for to in targets {
+                edges.insert((from.clone(), to.clone()), false);
+            }
+        }
+        for node in self.nodes.values() {
+            let targets = match node {
+                Node::Router(router) => router.target_functions.as_slice(),
+                Node::Window(window) => window.late_output.as_slice(),
+                _ => continue,
+            };
+            for to in targets {
+                edges
+                    .entry((node.name().to_string(), to.clone()))
+                    .or_insert(true);
+            }
+        }
+        edges
+            .into_iter()
+            .map(|((from, to), dynamic)| GraphEdge { from, to, dynamic })
+            .collect()
+    }
+
+    /// Returns the environment a function of this graph should run with.
+    /// Values defined on the function take precedence over the graph level
+    /// ones. Secrets are returned sealed.
+    /// Values of the parameters of an invocation overriding `overrides`.
+    pub fn resolve_parameters(
+        &self,
+        overrides: &HashMap<String, serde_json::Value>,
+    ) -> Result<HashMap<String, serde_json::Value>, DataModelError> {
+        parameters::resolve_parameters(&self.parameters, &self.parameter_values, overrides)
+    }
+
+    /// Environment of the function with the parameter placeholders of its
+    /// values rendered.
+    pub fn fn_environment(
+        &self,
+        fn_name: &str,
+        parameters: &HashMap<String, serde_json::Value>,
+    ) -> Result<Option<FnEnvironment>, DataModelError> {
+        let Some(node) = self.nodes.get(fn_name) else {
+            return Ok(None);
+        };
+        let mut environment = FnEnvironment {
+            env: self.env.clone(),
+            secrets: self.secrets.clone(),
+        };
+        if let Node::Compute(compute_fn) = node {
+            environment.env.extend(compute_fn.env.clone());
+            environment.secrets.extend(compute_fn.secrets.clone());
+        }
+        for value in environment.env.values_mut() {
+            *value = parameters::render_parameters(value, parameters)?;
+        }
+        Ok(Some(environment))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlannedNode {
+    pub name: String,
+    pub parents: Vec<String>,
+    // Only run if a router picks the node
+    pub dynamic: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphEdge {
+    pub from: String,
+    pub to: String,
+    // Router target, only followed if the router picks it
+    pub dynamic: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvocationPlan {
+    pub nodes: Vec<PlannedNode>,
+    // Nodes no invocation can reach from the start fn
+    pub unreachable: Vec<String>,
+}
+
+/// Bounds on the amount of pending work before new invocations are rejected,
+/// and on the size of the payloads accepted.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct QueueLimits {
+    pub max_pending_invocations: Option<u64>,
+    pub max_unallocated_tasks: Option<u64>,
+    #[serde(default)]
+    pub max_payload_bytes: Option<u64>,
+}
+
+impl QueueLimits {
+    /// Limits set on self take precedence over the fallback ones.
+    pub fn or(&self, fallback: &QueueLimits) -> QueueLimits {
+        QueueLimits {
+            max_pending_invocations: self
+                .max_pending_invocations
+                .or(fallback.max_pending_invocations),
+            max_unallocated_tasks: self
+                .max_unallocated_tasks
+                .or(fallback.max_unallocated_tasks),
+            max_payload_bytes: self.max_payload_bytes.or(fallback.max_payload_bytes),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct FnEnvironment {
+    pub env: HashMap<String, String>,
+    pub secrets: HashMap<String, String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -243,11 +1104,122 @@ pub struct RouterOutput {
     pub edges: Vec<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+/// Storage backend a payload is in. Payloads are moved to the cold tier by
+/// storage lifecycle policies, and back when they are replayed.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StorageTier {
+    #[default]
+    Hot,
+    Cold,
+    // The payload was deleted past the retention of its graph, its record is
+    // kept along with its size and hash
+    Expired,
+    // An object of the blob storage registered by reference instead of being
+    // uploaded, it's owned by the client and never moved or deleted
+    Referenced,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct DataPayload {
     pub path: String,
     pub size: u64,
     pub sha256_hash: String,
+    #[serde(default)]
+    pub tier: StorageTier,
+    /// Media type of the bytes, e.g. `application/json`
+    #[serde(default)]
+    pub mime_type: Option<String>,
+    /// Encoding applied to the bytes, e.g. `gzip`
+    #[serde(default)]
+    pub content_encoding: Option<String>,
+    /// Set by the client at ingestion or by the function which produced the
+    /// payload
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+    /// Copies of the payload in the storage of other regions, made by the
+    /// replication policy of its namespace
+    #[serde(default)]
+    pub replicas: Vec<PayloadReplica>,
+    /// Set when the server stored the bytes compressed, the size and hash of
+    /// the payload are the ones of the uncompressed bytes. Payloads are
+    /// decompressed when they're downloaded.
+    #[serde(default)]
+    pub compression: Option<PayloadCompression>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PayloadCodec {
+    Zstd,
+}
+
+/// Compression of the stored bytes of a payload.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PayloadCompression {
+    pub codec: PayloadCodec,
+    /// Size of the bytes in the blob store
+    pub stored_size: u64,
+    /// Hash of the bytes in the blob store, copies of the blob to other
+    /// tiers and regions are checked against it
+    pub stored_sha256_hash: String,
+}
+
+/// Label of executors naming the region they run in, they download the
+/// replicas of that region.
+pub const REGION_LABEL: &str = "region";
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PayloadReplica {
+    pub region: String,
+    pub path: String,
+}
+
+impl DataPayload {
+    /// Prefix of the paths of small payloads stored in the state store
+    /// rather than the blob store
+    pub const INLINE_PATH_PREFIX: &'static str = "inline://";
+
+    pub fn inline_path(key: &str) -> String {
+        format!("{}{}", Self::INLINE_PATH_PREFIX, key)
+    }
+
+    pub fn is_inline(&self) -> bool {
+        is_inline_path(&self.path)
+    }
+
+    /// Paths of the payload and of its replicas.
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.path.as_str())
+            .chain(self.replicas.iter().map(|replica| replica.path.as_str()))
+    }
+
+    pub fn codec(&self) -> Option<PayloadCodec> {
+        self.compression.as_ref().map(|compression| compression.codec)
+    }
+
+    /// Hash of the bytes in the blob store.
+    pub fn stored_sha256_hash(&self) -> &str {
+        self.compression
+            .as_ref()
+            .map_or(&self.sha256_hash, |compression| &compression.stored_sha256_hash)
+    }
+
+    pub fn replica(&self, region: &str) -> Option<&PayloadReplica> {
+        self.replicas.iter().find(|replica| replica.region == region)
+    }
+
+    /// Path of the copy of the payload nearest to the region, the payload
+    /// itself when it has no replica there.
+    pub fn nearest_path(&self, region: Option<&str>) -> &str {
+        region
+            .and_then(|region| self.replica(region))
+            .map_or(&self.path, |replica| &replica.path)
+    }
+}
+
+pub fn is_inline_path(path: &str) -> bool {
+    path.starts_with(DataPayload::INLINE_PATH_PREFIX)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -255,6 +1227,59 @@ pub struct TaskDiagnostics {
     pub exception: Option<DataPayload>,
     pub stdout: Option<DataPayload>,
     pub stderr: Option<DataPayload>,
+    // Newline delimited JSON log records emitted by the function
+    #[serde(default)]
+    pub structured_logs: Option<DataPayload>,
+}
+
+impl TaskDiagnostics {
+    /// Returns the diagnostic blob by the name executors upload it with.
+    pub fn get(&self, file: &str) -> Result<Option<&DataPayload>> {
+        match file {
+            "stdout" => Ok(self.stdout.as_ref()),
+            "stderr" => Ok(self.stderr.as_ref()),
+            "exception_msg" => Ok(self.exception.as_ref()),
+            "structured_logs" => Ok(self.structured_logs.as_ref()),
+            _ => Err(anyhow!("Invalid file type")),
+        }
+    }
+
+    pub fn payloads(&self) -> impl Iterator<Item = &DataPayload> {
+        [
+            &self.exception,
+            &self.stdout,
+            &self.stderr,
+            &self.structured_logs,
+        ]
+        .into_iter()
+        .flatten()
+    }
+}
+
+/// Blob uploaded for a task which the state store doesn't reference yet. The
+/// upload is committed along with the outcome of the task, uploads which are
+/// never committed are deleted by the garbage collector.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PendingUpload {
+    pub url: String,
+    pub namespace: String,
+    pub compute_graph: String,
+    pub compute_fn: String,
+    pub invocation_id: String,
+    pub task_id: TaskId,
+    pub created_at: u64,
+}
+
+impl PendingUpload {
+    pub fn task_key(&self) -> Vec<u8> {
+        Task::key_from(
+            &self.namespace,
+            &self.compute_graph,
+            &self.invocation_id,
+            &self.compute_fn,
+            self.task_id.get(),
+        )
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -275,10 +1300,14 @@ pub struct NodeOutput {
     pub payload: OutputPayload,
     pub errors: Option<DataPayload>,
     pub reduced_state: bool,
+    // Emitted while the task was still running, downstream tasks were
+    // created when it was ingested
+    #[serde(default)]
+    pub streamed: bool,
 }
 
 impl NodeOutput {
-    pub fn key(&self, invocation_id: &str) -> String {
+    pub fn key(&self, invocation_id: &str) -> Vec<u8> {
         NodeOutput::key_from(
             &self.namespace,
             &self.compute_graph_name,
@@ -294,11 +1323,30 @@ impl NodeOutput {
         invocation_id: &str,
         compute_fn: &str,
         id: &str,
-    ) -> String {
-        format!(
-            "{}|{}|{}|{}|{}",
-            namespace, compute_graph, invocation_id, compute_fn, id
-        )
+    ) -> Vec<u8> {
+        keys::encode_key(&[namespace, compute_graph, invocation_id, compute_fn, id])
+    }
+
+    /// Textual key of the output, which tasks hand to executors as the key
+    /// of their input.
+    pub fn input_key(&self, invocation_id: &str) -> String {
+        keys::join_textual_key(&[
+            &self.namespace,
+            &self.compute_graph_name,
+            invocation_id,
+            &self.compute_fn_name,
+            &self.id,
+        ])
+    }
+
+    /// Key of the output whose textual key is `input_key`.
+    pub fn key_from_input_key(input_key: &str) -> Result<Vec<u8>> {
+        let fields = keys::split_textual_key(input_key)?;
+        if fields.len() != 5 {
+            return Err(anyhow!("invalid output key {}", input_key));
+        }
+        let fields: Vec<&str> = fields.iter().map(String::as_str).collect();
+        Ok(keys::encode_key(&fields))
     }
 }
 
@@ -307,22 +1355,26 @@ impl NodeOutputBuilder {
         let ns = self
             .namespace
             .clone()
-            .ok_or(anyhow!("namespace is required"))?;
+            .ok_or(DataModelError::MissingField("namespace"))?;
         let cg_name = self
             .compute_graph_name
             .clone()
-            .ok_or(anyhow!("compute_graph_name is required"))?;
+            .ok_or(DataModelError::MissingField("compute_graph_name"))?;
         let fn_name = self
             .compute_fn_name
             .clone()
-            .ok_or(anyhow!("compute_fn_name is required"))?;
+            .ok_or(DataModelError::MissingField("compute_fn_name"))?;
         let invocation_id = self
             .invocation_id
             .clone()
-            .ok_or(anyhow!("invocation_id is required"))?;
+            .ok_or(DataModelError::MissingField("invocation_id"))?;
         let graph_version = self.graph_version.clone().unwrap_or_default();
-        let payload = self.payload.clone().ok_or(anyhow!("payload is required"))?;
+        let payload = self
+            .payload
+            .clone()
+            .ok_or(DataModelError::MissingField("payload"))?;
         let reduced_state = self.reduced_state.clone().unwrap_or(false);
+        let streamed = self.streamed.unwrap_or(false);
         let mut hasher = DefaultHasher::new();
         ns.hash(&mut hasher);
         cg_name.hash(&mut hasher);
@@ -347,10 +1399,24 @@ impl NodeOutputBuilder {
             payload,
             errors,
             reduced_state,
+            streamed,
         })
     }
 }
 
+/// Scheduling lane of an invocation. Latency lane tasks are allocated ahead
+/// of batch lane tasks of the same priority, and can be given slots of the
+/// executors batch lane tasks never fill.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum SchedulingLane {
+    /// Interactive invocations of fresh data
+    #[default]
+    Latency,
+    /// Replays and backfills
+    Batch,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Builder)]
 #[builder(build_fn(skip))]
 pub struct InvocationPayload {
@@ -358,19 +1424,68 @@ pub struct InvocationPayload {
     pub namespace: String,
     pub compute_graph_name: String,
     pub payload: DataPayload,
+    // Metadata attached by the caller, e.g. a customer id
+    #[serde(default)]
+    pub labels: HashMap<String, serde_json::Value>,
+    // Indexed metadata the invocations of a graph can be searched by
+    #[serde(default)]
+    pub attributes: HashMap<String, serde_json::Value>,
+    // Tasks of higher priority invocations are allocated first and can
+    // pre-empt running tasks of lower priority invocations
+    #[serde(default)]
+    pub priority: i32,
+    // Invocation group the invocation is a member of
+    #[serde(default)]
+    pub group: Option<String>,
+    // Overrides of the parameters of the graph
+    #[serde(default)]
+    pub parameters: HashMap<String, serde_json::Value>,
+    // Parts an ingestion hook split the payload into, the start node runs
+    // once per part instead of once with the payload
+    #[serde(default)]
+    pub parts: Vec<DataPayload>,
+    // When the data of the invocation was produced, in milliseconds since
+    // the epoch. Event-time windows group the inputs by it.
+    #[serde(default)]
+    pub event_time: Option<u64>,
+    // Runs the invocation on this version of the graph, the current or the
+    // canary one, instead of sampling the canary
+    #[serde(default)]
+    pub graph_version: Option<GraphVersion>,
+    // Defaults to the lane of the graph when not set
+    #[serde(default)]
+    pub lane: Option<SchedulingLane>,
 }
 
 impl InvocationPayload {
-    pub fn key(&self) -> String {
-        format!("{}|{}|{}", self.namespace, self.compute_graph_name, self.id)
+    /// Outputs the parts are stored as, the tasks of the start node read
+    /// them by their key.
+    pub fn part_outputs(&self, graph_version: GraphVersion) -> Result<Vec<NodeOutput>> {
+        self.parts
+            .iter()
+            .map(|part| {
+                NodeOutputBuilder::default()
+                    .namespace(self.namespace.clone())
+                    .compute_graph_name(self.compute_graph_name.clone())
+                    .compute_fn_name(ingestion::INGESTION_FN.to_string())
+                    .invocation_id(self.id.clone())
+                    .graph_version(graph_version)
+                    .payload(OutputPayload::Fn(part.clone()))
+                    .build()
+            })
+            .collect()
     }
 
-    pub fn key_from(ns: &str, cg: &str, id: &str) -> String {
-        format!("{}|{}|{}", ns, cg, id)
+    pub fn key(&self) -> Vec<u8> {
+        Self::key_from(&self.namespace, &self.compute_graph_name, &self.id)
     }
 
-    pub fn invocation_context_key(&self) -> String {
-        format!("{}|{}|{}", self.namespace, self.compute_graph_name, self.id)
+    pub fn key_from(ns: &str, cg: &str, id: &str) -> Vec<u8> {
+        keys::encode_key(&[ns, cg, id])
+    }
+
+    pub fn invocation_context_key(&self) -> Vec<u8> {
+        GraphInvocationCtx::key_from(&self.namespace, &self.compute_graph_name, &self.id)
     }
 }
 
@@ -379,50 +1494,192 @@ impl InvocationPayloadBuilder {
         let ns = self
             .namespace
             .clone()
-            .ok_or(anyhow!("namespace is required"))?;
+            .ok_or(DataModelError::MissingField("namespace"))?;
         let cg_name = self
             .compute_graph_name
             .clone()
-            .ok_or(anyhow!("compute_graph_name is required"))?;
-        let payload = self.payload.clone().ok_or(anyhow!("payload is required"))?;
+            .ok_or(DataModelError::MissingField("compute_graph_name"))?;
+        let payload = self
+            .payload
+            .clone()
+            .ok_or(DataModelError::MissingField("payload"))?;
         let mut hasher = DefaultHasher::new();
         ns.hash(&mut hasher);
         cg_name.hash(&mut hasher);
         payload.sha256_hash.hash(&mut hasher);
         payload.path.hash(&mut hasher);
+        // The same payload can be invoked on each version of the graph
+        let graph_version = self.graph_version.flatten();
+        if let Some(version) = graph_version {
+            version.0.hash(&mut hasher);
+        }
         let id = format!("{:x}", hasher.finish());
+        let labels = self.labels.clone().unwrap_or_default();
+        let attributes = self.attributes.clone().unwrap_or_default();
+        attributes::validate_attributes(&attributes)?;
         Ok(InvocationPayload {
             id,
             namespace: ns,
             compute_graph_name: cg_name,
             payload,
+            labels,
+            attributes,
+            priority: self.priority.unwrap_or_default(),
+            group: self.group.clone().flatten(),
+            parameters: self.parameters.clone().unwrap_or_default(),
+            parts: self.parts.clone().unwrap_or_default(),
+            event_time: self.event_time.flatten(),
+            graph_version,
+            lane: self.lane.flatten(),
         })
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Builder)]
-#[builder(build_fn(skip))]
-pub struct GraphInvocationCtx {
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Builder)]
+#[builder(build_fn(skip))]
+pub struct GraphInvocationCtx {
+    pub namespace: String,
+    pub compute_graph_name: String,
+    pub graph_version: GraphVersion,
+    pub invocation_id: String,
+    pub completed: bool,
+    pub outstanding_tasks: u64,
+    pub fn_task_analytics: HashMap<String, TaskAnalytics>,
+    pub is_system_task: bool,
+    // Set when the invocation runs a sub graph node of another invocation
+    #[serde(default)]
+    pub parent: Option<ParentInvocation>,
+    #[serde(default)]
+    pub labels: HashMap<String, serde_json::Value>,
+    // Milliseconds since the epoch, zero for invocations created before it
+    // was recorded
+    #[serde(default)]
+    pub created_at: u64,
+    #[serde(default)]
+    pub priority: i32,
+    // Set when the invocation was routed to the canary version of the graph
+    #[serde(default)]
+    pub canary: bool,
+    // Resources used by the finished tasks of the invocation
+    #[serde(default)]
+    pub usage: TaskUsage,
+    // Invocation group the invocation is a member of
+    #[serde(default)]
+    pub group: Option<String>,
+    // Resolved values of the parameters of the graph
+    #[serde(default)]
+    pub parameters: HashMap<String, serde_json::Value>,
+    // Event time of the invocation, in milliseconds since the epoch
+    #[serde(default)]
+    pub event_time: Option<u64>,
+    #[serde(default)]
+    pub lane: SchedulingLane,
+}
+
+/// The sub graph task of a parent invocation waiting for a child invocation.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum InvocationStatus {
+    Success,
+    Failure,
+}
+
+/// A task of an invocation which failed.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct InvocationError {
+    pub compute_fn: String,
+    pub task_id: TaskId,
+    pub exception: Option<DataPayload>,
+}
+
+/// Outcome of a finished invocation, assembled when its last task finishes.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct InvocationResult {
+    pub namespace: String,
+    pub compute_graph_name: String,
+    pub invocation_id: String,
+    pub status: InvocationStatus,
+    // Outputs of the terminal nodes of the graph
+    pub outputs: Vec<NodeOutput>,
+    pub errors: Vec<InvocationError>,
+    pub created_at: u64,
+    pub finished_at: u64,
+}
+
+impl InvocationResult {
+    pub fn key(&self) -> Vec<u8> {
+        GraphInvocationCtx::key_from(
+            &self.namespace,
+            &self.compute_graph_name,
+            &self.invocation_id,
+        )
+    }
+}
+
+/// A value of the scratch space shared by the tasks of an invocation.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct InvocationStateEntry {
+    pub namespace: String,
+    pub compute_graph_name: String,
+    pub invocation_id: String,
+    pub key: String,
+    pub value: serde_json::Value,
+    // Starts at 1 and is incremented by every write of the key
+    pub version: u64,
+}
+
+impl InvocationStateEntry {
+    pub fn key(&self) -> Vec<u8> {
+        Self::key_from(
+            &self.namespace,
+            &self.compute_graph_name,
+            &self.invocation_id,
+            &self.key,
+        )
+    }
+
+    pub fn key_from(ns: &str, cg: &str, invocation_id: &str, key: &str) -> Vec<u8> {
+        keys::encode_key(&[ns, cg, invocation_id, key])
+    }
+
+    /// Prefix of the keys of all the values of an invocation.
+    pub fn key_prefix(ns: &str, cg: &str, invocation_id: &str) -> keys::KeyPrefix {
+        keys::KeyPrefix::encoded(&[ns, cg, invocation_id])
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ParentInvocation {
     pub namespace: String,
-    pub compute_graph_name: String,
-    pub graph_version: GraphVersion,
+    pub compute_graph: String,
     pub invocation_id: String,
-    pub completed: bool,
-    pub outstanding_tasks: u64,
-    pub fn_task_analytics: HashMap<String, TaskAnalytics>,
-    pub is_system_task: bool,
+    pub compute_fn: String,
+    pub task_id: TaskId,
+}
+
+impl ParentInvocation {
+    pub fn task_key(&self) -> Vec<u8> {
+        Task::key_from(
+            &self.namespace,
+            &self.compute_graph,
+            &self.invocation_id,
+            &self.compute_fn,
+            self.task_id.get(),
+        )
+    }
 }
 
 impl GraphInvocationCtx {
-    pub fn key(&self) -> String {
-        format!(
-            "{}|{}|{}",
-            self.namespace, self.compute_graph_name, self.invocation_id
+    pub fn key(&self) -> Vec<u8> {
+        Self::key_from(
+            &self.namespace,
+            &self.compute_graph_name,
+            &self.invocation_id,
         )
     }
 
-    pub fn key_from(ns: &str, cg: &str, id: &str) -> String {
-        format!("{}|{}|{}", ns, cg, id)
+    pub fn key_from(ns: &str, cg: &str, id: &str) -> Vec<u8> {
+        keys::encode_key(&[ns, cg, id])
     }
 }
 
@@ -431,21 +1688,23 @@ impl GraphInvocationCtxBuilder {
         let namespace = self
             .namespace
             .clone()
-            .ok_or(anyhow!("namespace is required"))?;
+            .ok_or(DataModelError::MissingField("namespace"))?;
         let cg_name = self
             .compute_graph_name
             .clone()
-            .ok_or(anyhow!("compute_graph_name is required"))?;
+            .ok_or(DataModelError::MissingField("compute_graph_name"))?;
         let invocation_id = self
             .invocation_id
             .clone()
-            .ok_or(anyhow!("ingested_data_object_id is required"))?;
+            .ok_or(DataModelError::MissingField("invocation_id"))?;
         let mut fn_task_analytics = HashMap::new();
         for (fn_name, _node) in compute_graph.nodes.iter() {
             fn_task_analytics.insert(fn_name.clone(), TaskAnalytics::default());
         }
         let graph_version = self.graph_version.clone().unwrap_or_default();
         let is_system_task = self.is_system_task.unwrap_or(false);
+        let parent = self.parent.clone().flatten();
+        let labels = self.labels.clone().unwrap_or_default();
         Ok(GraphInvocationCtx {
             namespace,
             graph_version,
@@ -455,6 +1714,16 @@ impl GraphInvocationCtxBuilder {
             fn_task_analytics,
             outstanding_tasks: 1, // Starts with 1 for the initial state change event
             is_system_task,
+            parent,
+            labels,
+            created_at: get_epoch_time_in_ms(),
+            priority: self.priority.unwrap_or_default(),
+            canary: self.canary.unwrap_or_default(),
+            usage: TaskUsage::default(),
+            group: self.group.clone().flatten(),
+            parameters: self.parameters.clone().unwrap_or_default(),
+            event_time: self.event_time.flatten(),
+            lane: self.lane.unwrap_or_default(),
         })
     }
 }
@@ -472,16 +1741,46 @@ pub struct ReduceTask {
 }
 
 impl ReduceTask {
-    pub fn key(&self) -> String {
-        format!(
-            "{}|{}|{}|{}|{}|{}",
-            self.namespace,
-            self.compute_graph_name,
-            self.invocation_id,
-            self.compute_fn_name,
-            self.task_id,
-            self.task_output_key,
-        )
+    pub fn key(&self) -> Vec<u8> {
+        keys::encode_key(&[
+            &self.namespace,
+            &self.compute_graph_name,
+            &self.invocation_id,
+            &self.compute_fn_name,
+            &self.task_id,
+            &self.task_output_key,
+        ])
+    }
+}
+
+/// Progress of creating the downstream tasks of a finished task. Tasks of a
+/// large fan out are created in batches, the cursor is persisted along with
+/// each batch so the fan out resumes after a restart.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FanOutCursor {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub invocation_id: String,
+    pub task_id: TaskId,
+    // Downstream nodes of the task, a task is created for each pair of edge
+    // and output
+    pub edges: Vec<String>,
+    pub next: usize,
+    pub total: usize,
+}
+
+impl FanOutCursor {
+    pub fn key(&self) -> Vec<u8> {
+        keys::encode_key(&[
+            &self.namespace,
+            &self.compute_graph,
+            &self.invocation_id,
+            self.task_id.get(),
+        ])
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.next >= self.total
     }
 }
 
@@ -492,6 +1791,58 @@ pub enum TaskOutcome {
     Failure,
 }
 
+/// Why an executor is told to stop running a task
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AbortReason {
+    /// The task was pre-empted by a higher priority task
+    Preempted,
+    /// The invocation of the task was cancelled, e.g. its namespace was
+    /// deleted
+    Cancelled,
+    /// The executor no longer matches the placement constraints of the task,
+    /// it's allocated to another executor
+    Relocated,
+}
+
+/// Tells an executor to stop running a task and drop its outcome. Commands
+/// are delivered until the executor acknowledges them.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AbortCommand {
+    pub task_id: TaskId,
+    pub reason: AbortReason,
+    /// Milliseconds since the epoch
+    pub issued_at: u64,
+}
+
+impl AbortCommand {
+    pub fn new(task_id: TaskId, reason: AbortReason) -> Self {
+        Self {
+            task_id,
+            reason,
+            issued_at: get_epoch_time_in_ms(),
+        }
+    }
+
+    pub fn key_prefix(executor_id: &ExecutorId) -> String {
+        format!("{}|", executor_id)
+    }
+
+    pub fn key_from(executor_id: &ExecutorId, task_id: &TaskId) -> String {
+        format!("{}{}", Self::key_prefix(executor_id), task_id)
+    }
+}
+
+/// Opaque state persisted by a long running task, e.g. the progress of a
+/// crawl, so a new attempt of the task resumes from it instead of starting
+/// over.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TaskCheckpoint {
+    pub payload: DataPayload,
+    /// Milliseconds since the epoch
+    pub created_at: u64,
+}
+
 #[derive(Serialize, Debug, Deserialize, Clone, PartialEq, Builder)]
 #[builder(build_fn(skip))]
 pub struct Task {
@@ -507,9 +1858,74 @@ pub struct Task {
     pub diagnostics: Option<TaskDiagnostics>,
     pub reducer_output_id: Option<String>,
     pub graph_version: GraphVersion,
+    // Labels of the invocation, set when the task is created
+    #[serde(default)]
+    pub invocation_labels: HashMap<String, serde_json::Value>,
+    // Sha256 of the graph code the task runs, executors fetch it as a content
+    // addressed artifact
+    #[serde(default)]
+    pub code_sha256: Option<String>,
+    // Virtual time in nanoseconds assigned by the fair share scheduler when
+    // the task is allocated, orders the tasks of an executor
+    #[serde(default)]
+    pub dispatch_time: Option<u64>,
+    // Ids of the executor GPUs reserved for the task when it is allocated
+    #[serde(default)]
+    pub gpu_ids: Vec<String>,
+    // Priority of the invocation, set when the task is created
+    #[serde(default)]
+    pub priority: i32,
+    // Evaluated concurrency key of the function, tasks of the function with
+    // the same key are never allocated at the same time
+    #[serde(default)]
+    pub concurrency_key: Option<String>,
+    // Milliseconds since the epoch, set when the task is allocated
+    #[serde(default)]
+    pub allocated_at: Option<u64>,
+    // Set when the task finishes
+    #[serde(default)]
+    pub usage: Option<TaskUsage>,
+    // Runtime of the function in the graph version of the task, set when the
+    // task is created so executors start the matching sandbox
+    #[serde(default)]
+    pub runtime: Option<RuntimeDescriptor>,
+    // Outputs the task streamed while running, they count towards the output
+    // limits of its function
+    #[serde(default)]
+    pub streamed_outputs: u64,
+    #[serde(default)]
+    pub streamed_output_bytes: u64,
+    // Set when the server failed the task, e.g. for exceeding the output
+    // limits of its function
+    #[serde(default)]
+    pub failure: Option<TaskFailure>,
+    // Keys of the outputs of a closed window the task reads together as its
+    // input, input_node_output_key is the first of them
+    #[serde(default)]
+    pub input_batch: Vec<String>,
+    // Resolved parameters of the invocation, set when the task is created
+    #[serde(default)]
+    pub parameters: HashMap<String, serde_json::Value>,
+    // Latest checkpoint persisted by the running task, handed to the next
+    // attempt when the task is retried or reassigned
+    #[serde(default)]
+    pub checkpoint: Option<TaskCheckpoint>,
+    // Lane of the invocation, set when the task is created
+    #[serde(default)]
+    pub lane: SchedulingLane,
 }
 
 impl Task {
+    /// Concurrency key scoped to the function of the task.
+    pub fn scoped_concurrency_key(&self) -> Option<String> {
+        self.concurrency_key.as_ref().map(|key| {
+            format!(
+                "{}|{}|{}|{}",
+                self.namespace, self.compute_graph_name, self.compute_fn_name, key
+            )
+        })
+    }
+
     pub fn terminal_state(&self) -> bool {
         self.outcome != TaskOutcome::Unknown
     }
@@ -519,47 +1935,71 @@ impl Task {
         compute_graph: &str,
         invocation_id: &str,
         compute_fn_name: &str,
-    ) -> String {
-        format!(
-            "{}|{}|{}|{}",
-            namespace, compute_graph, invocation_id, compute_fn_name
-        )
+    ) -> Vec<u8> {
+        keys::encode_key(&[namespace, compute_graph, invocation_id, compute_fn_name])
     }
 
-    pub fn key(&self) -> String {
-        // <namespace>_<compute_graph_name>_<invocation_id>_<fn_name>_<task_id>
-        format!(
-            "{}|{}|{}|{}|{}",
-            self.namespace,
-            self.compute_graph_name,
-            self.invocation_id,
-            self.compute_fn_name,
-            self.id
+    pub fn key(&self) -> Vec<u8> {
+        Self::key_from(
+            &self.namespace,
+            &self.compute_graph_name,
+            &self.invocation_id,
+            &self.compute_fn_name,
+            self.id.get(),
         )
     }
 
-    pub fn key_output(&self, output_id: &str) -> String {
-        format!("{}|{}|{}", self.namespace, self.id, output_id)
+    // <namespace><compute_graph_name><invocation_id><fn_name><task_id>, encoded
+    pub fn key_from(
+        namespace: &str,
+        compute_graph: &str,
+        invocation_id: &str,
+        compute_fn_name: &str,
+        id: &str,
+    ) -> Vec<u8> {
+        keys::encode_key(&[namespace, compute_graph, invocation_id, compute_fn_name, id])
+    }
+
+    pub fn key_output(&self, output_id: &str) -> Vec<u8> {
+        keys::encode_key(&[&self.namespace, self.id.get(), output_id])
+    }
+
+    /// Prefix of the keys of the outputs of the task in the TaskOutputs
+    /// column.
+    pub fn key_outputs_prefix(namespace: &str, task_id: &str) -> keys::KeyPrefix {
+        keys::KeyPrefix::encoded(&[namespace, task_id])
+    }
+
+    /// Allocations of an executor are ordered by the time their tasks were
+    /// dispatched, followed by the key of the task.
+    pub fn make_allocation_key(&self, executor_id: &ExecutorId) -> Vec<u8> {
+        let nsecs = match self.dispatch_time {
+            Some(dispatch_time) => dispatch_time as u128,
+            None => {
+                let duration = self.creation_time.duration_since(UNIX_EPOCH).unwrap();
+                let secs = duration.as_secs() as u128;
+                let nsecs = duration.subsec_nanos() as u128;
+                secs * 1_000_000_000 + nsecs
+            }
+        };
+        let mut key = keys::encode_key(&[executor_id.get(), &nsecs.to_string()]);
+        key.extend_from_slice(&self.key());
+        key
     }
 
-    pub fn make_allocation_key(&self, executor_id: &ExecutorId) -> String {
-        let duration = self.creation_time.duration_since(UNIX_EPOCH).unwrap();
-        let secs = duration.as_secs() as u128;
-        let nsecs = duration.subsec_nanos() as u128;
-        let nsecs = secs * 1_000_000_000 + nsecs;
-        format!("{}|{}|{}", executor_id, nsecs, self.key())
+    /// Prefix of the allocation keys of the executor.
+    pub fn allocation_key_prefix(executor_id: &ExecutorId) -> Vec<u8> {
+        keys::encode_key(&[executor_id.get()])
     }
 
     pub fn key_from_allocation_key(allocation_key: &[u8]) -> Result<Vec<u8>> {
-        let pos_1 = allocation_key
-            .iter()
-            .position(|&x| x == b'|')
-            .ok_or(anyhow!("invalid executor key"))?;
-        let pos_2 = allocation_key[pos_1 + 1..]
-            .iter()
-            .position(|&x| x == b'|')
-            .ok_or(anyhow!("invalid executor key"))?;
-        Ok(allocation_key[pos_1 + 1 + pos_2 + 1..].to_vec())
+        let (_, task_key) = keys::decode_key_fields(allocation_key, 2)?;
+        Ok(task_key.to_vec())
+    }
+
+    pub fn executor_from_allocation_key(allocation_key: &[u8]) -> Result<ExecutorId> {
+        let (fields, _) = keys::decode_key_fields(allocation_key, 1)?;
+        Ok(ExecutorId::new(fields[0].clone()))
     }
 }
 
@@ -578,27 +2018,27 @@ impl TaskBuilder {
         let namespace = self
             .namespace
             .clone()
-            .ok_or(anyhow!("namespace is not present"))?;
+            .ok_or(DataModelError::MissingField("namespace"))?;
         let cg_name = self
             .compute_graph_name
             .clone()
-            .ok_or(anyhow!("compute graph name is not present"))?;
+            .ok_or(DataModelError::MissingField("compute_graph_name"))?;
         let compute_fn_name = self
             .compute_fn_name
             .clone()
-            .ok_or(anyhow!("compute fn name is not present"))?;
+            .ok_or(DataModelError::MissingField("compute_fn_name"))?;
         let input_key = self
             .input_node_output_key
             .clone()
-            .ok_or(anyhow!("input data object id is not present"))?;
+            .ok_or(DataModelError::MissingField("input_node_output_key"))?;
         let invocation_id = self
             .invocation_id
             .clone()
-            .ok_or(anyhow!("ingestion data object id is not present"))?;
+            .ok_or(DataModelError::MissingField("invocation_id"))?;
         let graph_version = self
             .graph_version
             .clone()
-            .ok_or(anyhow!("graph version is not present"))?;
+            .ok_or(DataModelError::MissingField("graph_version"))?;
         let reducer_output_id = self.reducer_output_id.clone().flatten();
         let id = uuid::Uuid::new_v4().to_string();
         let task = Task {
@@ -613,6 +2053,22 @@ impl TaskBuilder {
             diagnostics: None,
             reducer_output_id,
             graph_version,
+            invocation_labels: HashMap::new(),
+            code_sha256: None,
+            dispatch_time: None,
+            gpu_ids: vec![],
+            priority: 0,
+            concurrency_key: None,
+            allocated_at: None,
+            usage: None,
+            runtime: None,
+            streamed_outputs: 0,
+            streamed_output_bytes: 0,
+            failure: None,
+            input_batch: self.input_batch.clone().unwrap_or_default(),
+            parameters: HashMap::new(),
+            checkpoint: None,
+            lane: SchedulingLane::default(),
         };
         Ok(task)
     }
@@ -644,6 +2100,16 @@ impl TaskAnalytics {
             self.pending_tasks -= 1;
         }
     }
+
+    pub fn merge(&mut self, other: &TaskAnalytics) {
+        self.pending_tasks += other.pending_tasks;
+        self.successful_tasks += other.successful_tasks;
+        self.failed_tasks += other.failed_tasks;
+    }
+
+    pub fn total(&self) -> u64 {
+        self.pending_tasks + self.successful_tasks + self.failed_tasks
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -652,12 +2118,206 @@ pub struct ExecutorMetadata {
     pub image_name: String,
     pub addr: String,
     pub labels: HashMap<String, serde_json::Value>,
+    // Sha256 of the code artifacts cached by the executor
+    #[serde(default)]
+    pub cached_artifacts: HashSet<String>,
+    #[serde(default)]
+    pub gpus: Vec<GpuDevice>,
+    // Zero for executors which predate protocol versioning
+    #[serde(default)]
+    pub protocol_version: u32,
+    // Features negotiated when the executor registered
+    #[serde(default)]
+    pub features: BTreeSet<ExecutorFeature>,
+    // Runtimes the executor can start sandboxes of
+    #[serde(default)]
+    pub runtimes: Vec<RuntimeDescriptor>,
+    // Tasks received by the executor and not finished yet, as of its last
+    // heartbeat
+    #[serde(default)]
+    pub queue_depth: u64,
+    // Tasks the executor accepts before finishing some, unlimited when not
+    // set
+    #[serde(default)]
+    pub max_outstanding_tasks: Option<u32>,
+    // Software version of the executor, when it sends one
+    #[serde(default)]
+    pub version: Option<String>,
+    // Last registration or heartbeat of the executor, in ms since epoch
+    #[serde(default)]
+    pub last_heartbeat_at: Option<u64>,
 }
 
 impl ExecutorMetadata {
     pub fn key(&self) -> String {
         format!("{}", self.id)
     }
+
+    /// Whether another task can be allocated to the executor, given the
+    /// number of tasks allocated to it. The queue depth it reported counts
+    /// when it's larger.
+    pub fn has_room(&self, allocated: usize) -> bool {
+        self.free_capacity(allocated).map_or(true, |free| free > 0)
+    }
+
+    /// Number of tasks which can still be allocated to the executor, None
+    /// when it accepts any number of tasks.
+    pub fn free_capacity(&self, allocated: usize) -> Option<u64> {
+        self.max_outstanding_tasks.map(|max_outstanding| {
+            (max_outstanding as u64).saturating_sub((allocated as u64).max(self.queue_depth))
+        })
+    }
+
+    pub fn supports_runtime(&self, runtime: &RuntimeDescriptor) -> bool {
+        self.runtimes
+            .iter()
+            .any(|advertised| runtime.satisfied_by(advertised))
+    }
+
+    /// Picks GPUs satisfying the requirement among the GPUs which are not in
+    /// use. Returns None if the executor doesn't have enough free GPUs.
+    pub fn free_gpus(
+        &self,
+        requirement: &GpuRequirement,
+        in_use: &HashSet<String>,
+    ) -> Option<Vec<String>> {
+        let gpu_ids: Vec<String> = self
+            .gpus
+            .iter()
+            .filter(|gpu| requirement.matches_model(&gpu.model) && !in_use.contains(&gpu.id))
+            .take(requirement.count as usize)
+            .map(|gpu| gpu.id.clone())
+            .collect();
+        if gpu_ids.len() < requirement.count as usize {
+            return None;
+        }
+        Some(gpu_ids)
+    }
+}
+
+/// Version of the protocol the server speaks with executors.
+pub const EXECUTOR_PROTOCOL_VERSION: u32 = 1;
+
+/// Optional parts of the executor protocol, an executor only relies on the
+/// features the server accepted when it registered.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecutorFeature {
+    // Heartbeats renew the lease of the executor on its tasks
+    LeaseRenewal,
+    // Large outputs are uploaded in several requests
+    ChunkedUpload,
+    // Stdout, stderr and structured logs are uploaded with task outcomes
+    Diagnostics,
+    // The task stream hints at upcoming tasks, whose inputs and code the
+    // executor can download before the tasks are allocated
+    PrefetchHints,
+    // Small outputs are sent inline with task outcomes
+    InlineOutputs,
+    // Outcomes of many tasks are reported with a single request
+    BatchCompletions,
+    // Aborts are polled from the control channel with their reason and
+    // acknowledged, instead of being sent over the task stream
+    ControlChannel,
+}
+
+impl ExecutorFeature {
+    pub const ALL: [ExecutorFeature; 7] = [
+        ExecutorFeature::LeaseRenewal,
+        ExecutorFeature::ChunkedUpload,
+        ExecutorFeature::Diagnostics,
+        ExecutorFeature::PrefetchHints,
+        ExecutorFeature::InlineOutputs,
+        ExecutorFeature::BatchCompletions,
+        ExecutorFeature::ControlChannel,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExecutorFeature::LeaseRenewal => "lease_renewal",
+            ExecutorFeature::ChunkedUpload => "chunked_upload",
+            ExecutorFeature::Diagnostics => "diagnostics",
+            ExecutorFeature::PrefetchHints => "prefetch_hints",
+            ExecutorFeature::InlineOutputs => "inline_outputs",
+            ExecutorFeature::BatchCompletions => "batch_completions",
+            ExecutorFeature::ControlChannel => "control_channel",
+        }
+    }
+
+    /// Oldest protocol version the feature can be negotiated with
+    pub fn min_protocol_version(&self) -> u32 {
+        match self {
+            ExecutorFeature::LeaseRenewal |
+            ExecutorFeature::ChunkedUpload |
+            ExecutorFeature::Diagnostics |
+            ExecutorFeature::PrefetchHints |
+            ExecutorFeature::InlineOutputs |
+            ExecutorFeature::BatchCompletions |
+            ExecutorFeature::ControlChannel => 1,
+        }
+    }
+
+    /// Features requested by an executor which the server supports at the
+    /// executor's protocol version. Features the server doesn't know, e.g.
+    /// requested by newer executors, are ignored.
+    pub fn negotiate(protocol_version: u32, requested: &[String]) -> BTreeSet<ExecutorFeature> {
+        requested
+            .iter()
+            .filter_map(|feature| ExecutorFeature::from_str(feature).ok())
+            .filter(|feature| feature.min_protocol_version() <= protocol_version)
+            .collect()
+    }
+}
+
+impl Display for ExecutorFeature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for ExecutorFeature {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        ExecutorFeature::ALL
+            .into_iter()
+            .find(|feature| feature.as_str() == s)
+            .ok_or(anyhow!("unknown executor feature {}", s))
+    }
+}
+
+// A GPU of an executor as advertised when the executor registers
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct GpuDevice {
+    pub id: String,
+    pub model: String,
+    pub free_memory_mb: u64,
+}
+
+/// A named group of executors selected by their labels. Functions target a
+/// pool by name instead of repeating the label filters.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ExecutorPool {
+    pub namespace: String,
+    pub name: String,
+    pub selector: LabelsFilter,
+    pub min_size: Option<u32>,
+    pub max_size: Option<u32>,
+    pub created_at: u64,
+}
+
+impl ExecutorPool {
+    pub fn key(&self) -> String {
+        ExecutorPool::key_from(&self.namespace, &self.name)
+    }
+
+    pub fn key_from(namespace: &str, name: &str) -> String {
+        format!("{}|{}", namespace, name)
+    }
+
+    pub fn matches_executor(&self, executor: &ExecutorMetadata) -> bool {
+        self.selector.matches(&executor.labels)
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
@@ -674,6 +2334,9 @@ pub struct TaskFinishedEvent {
     pub compute_fn: String,
     pub invocation_id: String,
     pub task_id: TaskId,
+    // Unset for tasks finalized by the server
+    #[serde(default)]
+    pub executor_id: Option<ExecutorId>,
 }
 
 impl fmt::Display for TaskFinishedEvent {
@@ -686,15 +2349,57 @@ impl fmt::Display for TaskFinishedEvent {
     }
 }
 
+/// Tasks finalized together by a batch of task results, handled like as many
+/// `TaskFinished` changes.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct TasksFinishedEvent {
+    pub tasks: Vec<TaskFinishedEvent>,
+}
+
+/// Outputs emitted by a task which is still running.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct TaskOutputsStreamedEvent {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub compute_fn: String,
+    pub invocation_id: String,
+    pub task_id: TaskId,
+    pub output_ids: Vec<String>,
+}
+
+impl fmt::Display for TaskOutputsStreamedEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "TaskOutputsStreamedEvent(namespace: {}, compute_graph: {}, compute_fn: {}, task_id: {}, outputs: {})",
+            self.namespace, self.compute_graph, self.compute_fn, self.task_id, self.output_ids.len()
+        )
+    }
+}
+
+/// Tasks of an executor moved back to the unallocated tasks.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct TasksRequeuedEvent {
+    pub executor_id: ExecutorId,
+    pub num_tasks: usize,
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub enum ChangeType {
     InvokeComputeGraph(InvokeComputeGraphEvent),
     TaskFinished(TaskFinishedEvent),
+    TaskOutputsStreamed(TaskOutputsStreamedEvent),
     TombstoneIngestedData,
     TombstoneComputeGraph,
     ExecutorAdded,
     ExecutorRemoved,
+    ExecutorUpdated,
     TaskCreated,
+    ExecutorPoolUpdated,
+    TasksRequeued(TasksRequeuedEvent),
+    GraphResumed,
+    ExecutorBlacklisted,
+    TasksFinished(TasksFinishedEvent),
 }
 
 impl fmt::Display for ChangeType {
@@ -702,11 +2407,18 @@ impl fmt::Display for ChangeType {
         match self {
             ChangeType::InvokeComputeGraph(_) => write!(f, "InvokeComputeGraph"),
             ChangeType::TaskFinished(_) => write!(f, "TaskFinished"),
+            ChangeType::TaskOutputsStreamed(_) => write!(f, "TaskOutputsStreamed"),
             ChangeType::TombstoneIngestedData => write!(f, "TombstoneIngestedData"),
             ChangeType::TombstoneComputeGraph => write!(f, "TombstoneComputeGraph"),
             ChangeType::ExecutorAdded => write!(f, "ExecutorAdded"),
             ChangeType::ExecutorRemoved => write!(f, "ExecutorRemoved"),
+            ChangeType::ExecutorUpdated => write!(f, "ExecutorUpdated"),
             ChangeType::TaskCreated => write!(f, "TaskCreated"),
+            ChangeType::ExecutorPoolUpdated => write!(f, "ExecutorPoolUpdated"),
+            ChangeType::TasksRequeued(_) => write!(f, "TasksRequeued"),
+            ChangeType::GraphResumed => write!(f, "GraphResumed"),
+            ChangeType::ExecutorBlacklisted => write!(f, "ExecutorBlacklisted"),
+            ChangeType::TasksFinished(_) => write!(f, "TasksFinished"),
         }
     }
 }
@@ -754,4 +2466,540 @@ pub struct StateChange {
 pub struct Namespace {
     pub name: String,
     pub created_at: u64,
+    #[serde(default)]
+    pub queue_limits: QueueLimits,
+    /// Share of the executors the tasks of the namespace get relative to
+    /// other namespaces with pending tasks
+    #[serde(default = "default_scheduling_weight")]
+    pub scheduling_weight: u32,
+    /// Executors set aside for the tasks of the namespace
+    #[serde(default)]
+    pub reserved_capacity: Option<ReservedCapacity>,
+    /// Regions the payloads of the namespace are copied to
+    #[serde(default)]
+    pub replication: Option<ReplicationPolicy>,
+    /// Set while the namespace is being deleted, it is removed once its
+    /// compute graphs and invocations are purged
+    #[serde(default)]
+    pub deleted_at: Option<u64>,
+}
+
+/// Payloads of the invocations of a namespace are copied asynchronously to
+/// the replica storage of each region, for executors in those regions to
+/// download them nearby.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ReplicationPolicy {
+    pub regions: Vec<String>,
+}
+
+/// Minimum number of executors matching a selector reserved for the tasks of
+/// a namespace. Other namespaces only get tasks allocated to the reserved
+/// executors while the namespace has no pending tasks.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ReservedCapacity {
+    pub selector: LabelsFilter,
+    pub min_executors: u32,
+}
+
+/// Objects of a namespace being deleted which aren't purged yet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NamespaceDeletionProgress {
+    pub namespace: String,
+    pub deleted_at: u64,
+    pub compute_graphs: u64,
+    pub invocations: u64,
+}
+
+pub fn default_scheduling_weight() -> u32 {
+    1
+}
+
+/// Bytes stored in the blob storage, by kind of data
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct StorageUsage {
+    pub code_bytes: u64,
+    pub ingestion_bytes: u64,
+    pub output_bytes: u64,
+}
+
+impl StorageUsage {
+    pub fn total_bytes(&self) -> u64 {
+        self.code_bytes + self.ingestion_bytes + self.output_bytes
+    }
+
+    pub fn add(&mut self, other: &StorageUsage) {
+        self.code_bytes += other.code_bytes;
+        self.ingestion_bytes += other.ingestion_bytes;
+        self.output_bytes += other.output_bytes;
+    }
+}
+
+/// Storage used by a compute graph, including its invocations and outputs
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct GraphStorageUsage {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub usage: StorageUsage,
+}
+
+impl GraphStorageUsage {
+    pub fn key(&self) -> String {
+        format!("{}|{}", self.namespace, self.compute_graph)
+    }
+}
+
+/// CPU and GPU time used by a task, as reported by its executor
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct ResourceUsage {
+    pub cpu_seconds: f64,
+    pub gpu_seconds: f64,
+    // Whether the executor loaded the environment of the function for the
+    // task rather than reusing a warm one, unset if it didn't report it
+    #[serde(default)]
+    pub cold_start: Option<bool>,
+}
+
+/// Resources used by finished tasks. Durations are measured by the server from
+/// the allocation of the tasks.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct TaskUsage {
+    pub tasks: u64,
+    pub duration_ms: u64,
+    pub input_bytes: u64,
+    pub output_bytes: u64,
+    pub cpu_seconds: f64,
+    pub gpu_seconds: f64,
+    // Tasks started in a newly loaded or an already warm function
+    // environment, counting the tasks of executors reporting it
+    #[serde(default)]
+    pub cold_starts: u64,
+    #[serde(default)]
+    pub warm_starts: u64,
+    #[serde(default)]
+    pub failed_tasks: u64,
+}
+
+impl TaskUsage {
+    pub fn add(&mut self, other: &TaskUsage) {
+        self.tasks += other.tasks;
+        self.duration_ms += other.duration_ms;
+        self.input_bytes += other.input_bytes;
+        self.output_bytes += other.output_bytes;
+        self.cpu_seconds += other.cpu_seconds;
+        self.gpu_seconds += other.gpu_seconds;
+        self.cold_starts += other.cold_starts;
+        self.warm_starts += other.warm_starts;
+        self.failed_tasks += other.failed_tasks;
+    }
+}
+
+/// Resources used by the tasks of a compute graph
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct GraphTaskUsage {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub usage: TaskUsage,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_objects::tests::{mock_graph_a, mock_graph_b};
+
+    fn graph_with_sub_graph(name: &str, sub_graph: &str) -> ComputeGraph {
+        let mut graph = mock_graph_a();
+        graph.name = name.to_string();
+        graph.nodes.insert(
+            "fn_sub".to_string(),
+            Node::SubGraph(SubGraph {
+                name: "fn_sub".to_string(),
+                description: "".to_string(),
+                compute_graph: sub_graph.to_string(),
+            }),
+        );
+        graph
+            .edges
+            .insert("fn_b".to_string(), vec!["fn_sub".to_string()]);
+        graph
+    }
+
+    #[test]
+    fn test_sub_graph_cycle_detection() -> Result<()> {
+        let graphs = HashMap::from([
+            (
+                "graph_B".to_string(),
+                graph_with_sub_graph("graph_B", "graph_C"),
+            ),
+            ("graph_C".to_string(), mock_graph_a()),
+        ]);
+        let lookup = |name: &str| Ok(graphs.get(name).cloned());
+
+        let graph = graph_with_sub_graph("graph_A", "graph_B");
+        assert_eq!(graph.sub_graphs(), vec!["graph_B"]);
+        assert!(graph.find_sub_graph_cycle(lookup)?.is_none());
+
+        let graph = graph_with_sub_graph("graph_C", "graph_B");
+        assert_eq!(
+            graph.find_sub_graph_cycle(lookup)?,
+            Some(vec![
+                "graph_C".to_string(),
+                "graph_B".to_string(),
+                "graph_C".to_string()
+            ])
+        );
+
+        let graph = graph_with_sub_graph("graph_A", "graph_A");
+        assert!(graph.find_sub_graph_cycle(lookup)?.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_plan() {
+        let plan = mock_graph_b().plan();
+        let planned = |name: &str, parents: &[&str], dynamic: bool| PlannedNode {
+            name: name.to_string(),
+            parents: parents.iter().map(|parent| parent.to_string()).collect(),
+            dynamic,
+        };
+        assert_eq!(plan.nodes.len(), 4);
+        assert_eq!(plan.nodes[0], planned("fn_a", &[], false));
+        assert_eq!(plan.nodes[1], planned("router_x", &["fn_a"], false));
+        assert!(plan.nodes[2..].contains(&planned("fn_b", &["router_x"], true)));
+        assert!(plan.nodes[2..].contains(&planned("fn_c", &["router_x"], true)));
+        assert!(plan.unreachable.is_empty());
+
+        let mut graph = mock_graph_a();
+        graph
+            .edges
+            .insert("fn_a".to_string(), vec!["fn_b".to_string()]);
+        let plan = graph.plan();
+        assert_eq!(plan.nodes.len(), 2);
+        assert_eq!(plan.unreachable, vec!["fn_c".to_string()]);
+    }
+
+    #[test]
+    fn test_topology_edges() {
+        let edge = |from: &str, to: &str, dynamic: bool| GraphEdge {
+            from: from.to_string(),
+            to: to.to_string(),
+            dynamic,
+        };
+        assert_eq!(
+            mock_graph_b().topology_edges(),
+            vec![
+                edge("fn_a", "router_x", false),
+                edge("router_x", "fn_b", true),
+                edge("router_x", "fn_c", true),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_terminal_nodes() {
+        let graph = graph_with_sub_graph("graph_A", "graph_B");
+        let mut terminal_nodes: Vec<&str> = graph
+            .terminal_nodes()
+            .iter()
+            .map(|node| node.name())
+            .collect();
+        terminal_nodes.sort();
+        assert_eq!(terminal_nodes, vec!["fn_c", "fn_sub"]);
+    }
+
+    #[test]
+    fn test_validate_graph() {
+        let mut graph = mock_graph_a();
+        assert!(graph.validate().is_ok());
+
+        graph
+            .edges
+            .insert("fn_c".to_string(), vec!["fn_unknown".to_string()]);
+        assert!(matches!(
+            graph.validate(),
+            Err(DataModelError::InvalidGraph(_))
+        ));
+
+        let mut graph = mock_graph_a();
+        if let Some(Node::Compute(compute_fn)) = graph.nodes.get_mut("fn_b") {
+            compute_fn.rate_limit = Some(RateLimit {
+                max_tasks: 0,
+                window_secs: 10,
+            });
+        }
+        assert!(matches!(
+            graph.validate(),
+            Err(DataModelError::InvalidGraph(_))
+        ));
+
+        let mut graph = mock_graph_a();
+        if let Some(Node::Compute(compute_fn)) = graph.nodes.get_mut("fn_b") {
+            compute_fn.gpu = Some(GpuRequirement {
+                model: Some("A10G".to_string()),
+                count: 0,
+            });
+        }
+        assert!(matches!(
+            graph.validate(),
+            Err(DataModelError::InvalidGraph(_))
+        ));
+
+        // Default targets of routers are among their targets
+        let mut graph = mock_graph_b();
+        assert!(graph.validate().is_ok());
+        if let Some(Node::Router(router)) = graph.nodes.get_mut("router_x") {
+            router.on_error = RouterErrorPolicy::RouteToDefault;
+        }
+        assert!(matches!(
+            graph.validate(),
+            Err(DataModelError::InvalidGraph(_))
+        ));
+        if let Some(Node::Router(router)) = graph.nodes.get_mut("router_x") {
+            router.default_targets = vec!["fn_d".to_string()];
+        }
+        assert!(matches!(
+            graph.validate(),
+            Err(DataModelError::InvalidGraph(_))
+        ));
+        if let Some(Node::Router(router)) = graph.nodes.get_mut("router_x") {
+            router.default_targets = vec!["fn_c".to_string()];
+        }
+        assert!(graph.validate().is_ok());
+
+        // Windows need a bound and are followed by functions
+        let mut graph = mock_graph_a();
+        graph
+            .edges
+            .insert("fn_b".to_string(), vec!["fn_c".to_string()]);
+        let mut window = Window {
+            name: "fn_b".to_string(),
+            description: "".to_string(),
+            max_items: None,
+            max_age_secs: None,
+            event_time_secs: None,
+            allowed_lateness_secs: 0,
+            late_output: None,
+        };
+        graph
+            .nodes
+            .insert("fn_b".to_string(), Node::Window(window.clone()));
+        assert!(matches!(
+            graph.validate(),
+            Err(DataModelError::InvalidGraph(_))
+        ));
+        window.max_items = Some(100);
+        graph
+            .nodes
+            .insert("fn_b".to_string(), Node::Window(window.clone()));
+        assert!(graph.validate().is_ok());
+        if let Some(Node::Compute(compute_fn)) = graph.nodes.get_mut("fn_c") {
+            compute_fn.reducer = true;
+        }
+        assert!(matches!(
+            graph.validate(),
+            Err(DataModelError::InvalidGraph(_))
+        ));
+
+        assert!(!window.expired(1_000, 1_000_000));
+        window.max_age_secs = Some(60);
+        assert!(!window.expired(1_000, 60_999));
+        assert!(window.expired(1_000, 61_000));
+
+        // Late outputs need event time and get single inputs
+        window.late_output = Some("fn_c".to_string());
+        graph
+            .nodes
+            .insert("fn_b".to_string(), Node::Window(window.clone()));
+        assert!(matches!(
+            graph.validate(),
+            Err(DataModelError::InvalidGraph(_))
+        ));
+        if let Some(Node::Compute(compute_fn)) = graph.nodes.get_mut("fn_c") {
+            compute_fn.reducer = false;
+        }
+        window.event_time_secs = Some(60);
+        graph
+            .nodes
+            .insert("fn_b".to_string(), Node::Window(window.clone()));
+        assert!(graph.validate().is_ok());
+        let mut late_to_unknown = window.clone();
+        late_to_unknown.late_output = Some("fn_x".to_string());
+        graph
+            .nodes
+            .insert("fn_b".to_string(), Node::Window(late_to_unknown));
+        assert!(matches!(
+            graph.validate(),
+            Err(DataModelError::InvalidGraph(_))
+        ));
+        assert_eq!(window.event_window_end(0), Some(60_000));
+        assert_eq!(window.event_window_end(59_999), Some(60_000));
+        assert_eq!(window.event_window_end(60_000), Some(120_000));
+        window.allowed_lateness_secs = 10;
+        assert_eq!(window.watermark(70_000), 60_000);
+        assert_eq!(window.watermark(5_000), 0);
+
+        // Configs can only reference declared parameters
+        let mut graph = mock_graph_a();
+        graph
+            .env
+            .insert("MODEL".to_string(), "{params.model}".to_string());
+        assert!(matches!(
+            graph.validate(),
+            Err(DataModelError::InvalidParameter(_))
+        ));
+        graph
+            .parameters
+            .insert("model".to_string(), Default::default());
+        assert!(graph.validate().is_ok());
+        let parameters = graph
+            .resolve_parameters(&HashMap::from([(
+                "model".to_string(),
+                serde_json::json!("minilm"),
+            )]))
+            .unwrap();
+        let environment = graph.fn_environment("fn_b", &parameters).unwrap().unwrap();
+        assert_eq!(environment.env["MODEL"], "minilm");
+        graph
+            .parameter_values
+            .insert("other".to_string(), serde_json::json!(1));
+        assert!(graph.validate().is_err());
+    }
+
+    #[test]
+    fn test_free_gpus() {
+        let gpu = |id: &str, model: &str| GpuDevice {
+            id: id.to_string(),
+            model: model.to_string(),
+            free_memory_mb: 24_000,
+        };
+        let executor = ExecutorMetadata {
+            gpus: vec![gpu("0", "A10G"), gpu("1", "A10G"), gpu("2", "H100")],
+            ..Default::default()
+        };
+        let requirement = GpuRequirement {
+            model: Some("a10g".to_string()),
+            count: 1,
+        };
+
+        let in_use = HashSet::new();
+        assert_eq!(
+            executor.free_gpus(&requirement, &in_use),
+            Some(vec!["0".to_string()])
+        );
+
+        let in_use = HashSet::from(["0".to_string()]);
+        assert_eq!(
+            executor.free_gpus(&requirement, &in_use),
+            Some(vec!["1".to_string()])
+        );
+
+        let in_use = HashSet::from(["0".to_string(), "1".to_string()]);
+        assert_eq!(executor.free_gpus(&requirement, &in_use), None);
+
+        let any_model = GpuRequirement {
+            model: None,
+            count: 1,
+        };
+        assert_eq!(
+            executor.free_gpus(&any_model, &in_use),
+            Some(vec!["2".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_supports_runtime() {
+        let executor = ExecutorMetadata {
+            runtimes: vec![RuntimeDescriptor {
+                image: "tensorlake/indexify:1.0".to_string(),
+                python_version: Some("3.11".to_string()),
+                pip_hash: Some("abc".to_string()),
+            }],
+            ..Default::default()
+        };
+        let runtime = |image: &str, python_version: Option<&str>| RuntimeDescriptor {
+            image: image.to_string(),
+            python_version: python_version.map(|v| v.to_string()),
+            pip_hash: None,
+        };
+
+        assert!(executor.supports_runtime(&runtime("tensorlake/indexify:1.0", None)));
+        assert!(executor.supports_runtime(&runtime("tensorlake/indexify:1.0", Some("3.11"))));
+        assert!(!executor.supports_runtime(&runtime("tensorlake/indexify:1.0", Some("3.10"))));
+        assert!(!executor.supports_runtime(&runtime("tensorlake/indexify:2.0", None)));
+        assert!(!ExecutorMetadata::default()
+            .supports_runtime(&runtime("tensorlake/indexify:1.0", None)));
+    }
+
+    #[test]
+    fn test_has_room() {
+        assert!(ExecutorMetadata::default().has_room(1000));
+
+        let executor = ExecutorMetadata {
+            max_outstanding_tasks: Some(2),
+            ..Default::default()
+        };
+        assert!(executor.has_room(1));
+        assert_eq!(executor.free_capacity(1), Some(1));
+        assert!(!executor.has_room(2));
+        assert_eq!(executor.free_capacity(3), Some(0));
+        // A backed up executor gets no tasks until it drains its queue
+        let executor = ExecutorMetadata {
+            queue_depth: 2,
+            ..executor
+        };
+        assert!(!executor.has_room(0));
+        assert_eq!(ExecutorMetadata::default().free_capacity(1000), None);
+    }
+
+    #[test]
+    fn test_negotiate_executor_features() {
+        let requested = vec![
+            "diagnostics".to_string(),
+            "lease_renewal".to_string(),
+            "from_the_future".to_string(),
+        ];
+        assert_eq!(
+            ExecutorFeature::negotiate(1, &requested),
+            BTreeSet::from([ExecutorFeature::LeaseRenewal, ExecutorFeature::Diagnostics])
+        );
+        assert!(ExecutorFeature::negotiate(0, &requested).is_empty());
+    }
+
+    #[test]
+    fn test_output_limits() {
+        let limits = OutputLimits {
+            max_outputs: Some(2),
+            max_output_bytes: Some(100),
+        };
+        assert_eq!(limits.check(2, 100), None);
+        assert_eq!(
+            limits.check(3, 1000),
+            Some(TaskFailure::OutputLimitExceeded {
+                limit: OutputLimit::Outputs,
+                max: 2,
+                actual: 3,
+            })
+        );
+        assert_eq!(
+            limits.check(1, 101),
+            Some(TaskFailure::OutputLimitExceeded {
+                limit: OutputLimit::OutputBytes,
+                max: 100,
+                actual: 101,
+            })
+        );
+        let limits = OutputLimits {
+            max_outputs: None,
+            max_output_bytes: None,
+        };
+        assert_eq!(limits.check(u64::MAX, u64::MAX), None);
+    }
+
+    #[test]
+    fn test_builder_missing_field() {
+        let err = TaskBuilder::default().build().unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<DataModelError>(),
+            Some(&DataModelError::MissingField("namespace"))
+        );
+    }
 }
@@ -0,0 +1,225 @@
+//! Best practice warnings of compute graph definitions. Unlike validation
+//! errors they don't prevent a graph from being deployed, they point at
+//! designs which usually aren't intended.
+
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{ComputeGraph, Node};
+
+/// Chains of nodes longer than this are reported, every hop of a chain is a
+/// task and a round trip to the blob store.
+pub const MAX_CHAIN_DEPTH: usize = 16;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum LintRule {
+    /// The node isn't reachable from the start node, it never runs
+    UnreachableNode,
+    /// A router which always picks the same target is a static edge
+    SingleTargetRouter,
+    /// The function declares no runtime or GPU, its tasks can be placed on any
+    /// executor
+    MissingResourceRequests,
+    /// The function declares no output limits, a task can emit any number of
+    /// outputs of any size
+    UnboundedOutputs,
+    /// The longest chain of nodes of the graph is longer than MAX_CHAIN_DEPTH
+    DeepChain,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LintWarning {
+    pub rule: LintRule,
+    /// Node the warning is about, unset for the warnings about the whole graph
+    pub node: Option<String>,
+    pub message: String,
+}
+
+impl LintWarning {
+    fn new(rule: LintRule, node: Option<&str>, message: String) -> Self {
+        Self {
+            rule,
+            node: node.map(|node| node.to_string()),
+            message,
+        }
+    }
+}
+
+/// Warnings of the graph, sorted by node and rule.
+pub fn lint(graph: &ComputeGraph) -> Vec<LintWarning> {
+    let mut warnings = vec![];
+    for name in graph.plan().unreachable {
+        warnings.push(LintWarning::new(
+            LintRule::UnreachableNode,
+            Some(&name),
+            format!(
+                "node {} is not reachable from the start node {}",
+                name,
+                graph.start_fn.name()
+            ),
+        ));
+    }
+
+    let mut nodes: Vec<&Node> = graph.nodes.values().collect();
+    if !graph.nodes.contains_key(graph.start_fn.name()) {
+        nodes.push(&graph.start_fn);
+    }
+    for node in nodes {
+        match node {
+            Node::Router(router) if router.target_functions.len() == 1 => {
+                warnings.push(LintWarning::new(
+                    LintRule::SingleTargetRouter,
+                    Some(&router.name),
+                    format!(
+                        "router {} has a single target {}, an edge would not need a task",
+                        router.name, router.target_functions[0]
+                    ),
+                ));
+            }
+            Node::Compute(compute_fn) => {
+                if compute_fn.runtime.is_none() && compute_fn.gpu.is_none() {
+                    warnings.push(LintWarning::new(
+                        LintRule::MissingResourceRequests,
+                        Some(&compute_fn.name),
+                        format!(
+                            "fn {} declares no runtime or gpu, its tasks can be placed on any executor",
+                            compute_fn.name
+                        ),
+                    ));
+                }
+                if compute_fn.output_limits.is_none() {
+                    warnings.push(LintWarning::new(
+                        LintRule::UnboundedOutputs,
+                        Some(&compute_fn.name),
+                        format!(
+                            "fn {} declares no output limits, its outputs are unbounded",
+                            compute_fn.name
+                        ),
+                    ));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let depth = chain_depth(graph);
+    if depth > MAX_CHAIN_DEPTH {
+        warnings.push(LintWarning::new(
+            LintRule::DeepChain,
+            None,
+            format!(
+                "the longest chain of nodes is {} nodes long, more than {}",
+                depth, MAX_CHAIN_DEPTH
+            ),
+        ));
+    }
+    warnings.sort_by(|a, b| (&a.node, a.rule).cmp(&(&b.node, b.rule)));
+    warnings
+}
+
+/// Number of nodes of the longest chain starting at the start node,
+/// following the edges and the targets of the routers.
+pub fn chain_depth(graph: &ComputeGraph) -> usize {
+    fn depth<'a>(
+        graph: &'a ComputeGraph,
+        name: &'a str,
+        visiting: &mut HashSet<&'a str>,
+        depths: &mut HashMap<&'a str, usize>,
+    ) -> usize {
+        if let Some(depth) = depths.get(name) {
+            return *depth;
+        }
+        // Cycles are broken where they close
+        if !visiting.insert(name) {
+            return 0;
+        }
+        let mut children: Vec<&String> = graph.edges.get(name).into_iter().flatten().collect();
+        if let Some(Node::Router(router)) = graph.nodes.get(name) {
+            children.extend(&router.target_functions);
+        }
+        let longest = children
+            .into_iter()
+            .map(|child| depth(graph, child, visiting, depths))
+            .max()
+            .unwrap_or(0);
+        visiting.remove(name);
+        depths.insert(name, longest + 1);
+        longest + 1
+    }
+    depth(
+        graph,
+        graph.start_fn.name(),
+        &mut HashSet::new(),
+        &mut HashMap::new(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_objects::tests::{mock_graph_a, mock_graph_b},
+        OutputLimits,
+        RuntimeDescriptor,
+    };
+
+    #[test]
+    fn test_lint() {
+        let mut graph = mock_graph_b();
+        for node in graph.nodes.values_mut() {
+            if let Node::Compute(compute_fn) = node {
+                compute_fn.runtime = Some(RuntimeDescriptor::default());
+                compute_fn.output_limits = Some(OutputLimits {
+                    max_outputs: Some(10),
+                    max_output_bytes: None,
+                });
+            }
+        }
+        assert!(lint(&graph).is_empty());
+        assert_eq!(chain_depth(&graph), 3);
+
+        if let Some(Node::Router(router)) = graph.nodes.get_mut("router_x") {
+            router.target_functions.truncate(1);
+        }
+        if let Some(Node::Compute(fn_b)) = graph.nodes.get_mut("fn_b") {
+            fn_b.output_limits = None;
+        }
+        let warnings = lint(&graph);
+        let rules: Vec<(Option<&str>, LintRule)> = warnings
+            .iter()
+            .map(|warning| (warning.node.as_deref(), warning.rule))
+            .collect();
+        assert_eq!(
+            rules,
+            vec![
+                (Some("fn_b"), LintRule::UnboundedOutputs),
+                (Some("fn_c"), LintRule::UnreachableNode),
+                (Some("router_x"), LintRule::SingleTargetRouter),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_deep_chain() {
+        let mut graph = mock_graph_a();
+        let template = graph.nodes["fn_b"].clone();
+        let mut previous = "fn_a".to_string();
+        graph.edges.clear();
+        for i in 0..MAX_CHAIN_DEPTH {
+            let name = format!("fn_{}", i);
+            let Node::Compute(mut compute_fn) = template.clone() else {
+                unreachable!()
+            };
+            compute_fn.name = name.clone();
+            graph.nodes.insert(name.clone(), Node::Compute(compute_fn));
+            graph.edges.insert(previous, vec![name.clone()]);
+            previous = name;
+        }
+        assert_eq!(chain_depth(&graph), MAX_CHAIN_DEPTH + 1);
+        assert!(lint(&graph)
+            .iter()
+            .any(|warning| warning.rule == LintRule::DeepChain && warning.node.is_none()));
+    }
+}
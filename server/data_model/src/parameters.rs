@@ -0,0 +1,167 @@
+//! Parameters of compute graph templates.
+//!
+//! A graph declares parameters, e.g. a model name, which its function configs
+//! reference as `{params.<name>}`. Values are bound when a graph is
+//! instantiated from a template and can be overridden per invocation, the
+//! resolved values are copied to the tasks of the invocation.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::DataModelError;
+
+const PLACEHOLDER_PREFIX: &str = "{params.";
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct GraphParameter {
+    #[serde(default)]
+    pub description: String,
+    // Parameters without a default must be bound by the graph or the
+    // invocation
+    #[serde(default)]
+    pub default: Option<Value>,
+}
+
+fn invalid(reason: String) -> DataModelError {
+    DataModelError::InvalidParameter(reason)
+}
+
+fn check_declared(
+    declared: &HashMap<String, GraphParameter>,
+    values: &HashMap<String, Value>,
+) -> Result<(), DataModelError> {
+    match values.keys().find(|name| !declared.contains_key(*name)) {
+        Some(name) => Err(invalid(format!("unknown parameter {}", name))),
+        None => Ok(()),
+    }
+}
+
+/// Checks that the bound values only set declared parameters.
+pub fn validate_parameter_values(
+    declared: &HashMap<String, GraphParameter>,
+    bound: &HashMap<String, Value>,
+) -> Result<(), DataModelError> {
+    check_declared(declared, bound)
+}
+
+/// Values of all the declared parameters, the overrides of an invocation
+/// take precedence over the bound values which take precedence over the
+/// defaults.
+pub fn resolve_parameters(
+    declared: &HashMap<String, GraphParameter>,
+    bound: &HashMap<String, Value>,
+    overrides: &HashMap<String, Value>,
+) -> Result<HashMap<String, Value>, DataModelError> {
+    check_declared(declared, bound)?;
+    check_declared(declared, overrides)?;
+    let mut resolved = HashMap::new();
+    for (name, parameter) in declared {
+        let value = overrides
+            .get(name)
+            .or_else(|| bound.get(name))
+            .or(parameter.default.as_ref())
+            .ok_or_else(|| invalid(format!("parameter {} has no value", name)))?;
+        resolved.insert(name.clone(), value.clone());
+    }
+    Ok(resolved)
+}
+
+/// Names of the parameters referenced by a config value.
+pub fn referenced_parameters(value: &str) -> Result<Vec<&str>, DataModelError> {
+    let mut names = vec![];
+    let mut rest = value;
+    while let Some(start) = rest.find(PLACEHOLDER_PREFIX) {
+        rest = &rest[start + PLACEHOLDER_PREFIX.len()..];
+        let end = rest
+            .find('}')
+            .ok_or_else(|| invalid(format!("unterminated placeholder in {:?}", value)))?;
+        if end == 0 {
+            return Err(invalid(format!("empty parameter name in {:?}", value)));
+        }
+        names.push(&rest[..end]);
+        rest = &rest[end + 1..];
+    }
+    Ok(names)
+}
+
+/// Replaces the `{params.<name>}` placeholders of a config value, strings
+/// are inserted as is and other values as JSON.
+pub fn render_parameters(
+    value: &str,
+    parameters: &HashMap<String, Value>,
+) -> Result<String, DataModelError> {
+    let mut rendered = value.to_string();
+    for name in referenced_parameters(value)? {
+        let parameter = parameters
+            .get(name)
+            .ok_or_else(|| invalid(format!("parameter {} has no value", name)))?;
+        let text = match parameter {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        };
+        rendered = rendered.replace(&format!("{}{}}}", PLACEHOLDER_PREFIX, name), &text);
+    }
+    Ok(rendered)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn declared() -> HashMap<String, GraphParameter> {
+        HashMap::from([
+            (
+                "model".to_string(),
+                GraphParameter {
+                    description: "embedding model".to_string(),
+                    default: None,
+                },
+            ),
+            (
+                "chunk_size".to_string(),
+                GraphParameter {
+                    description: "".to_string(),
+                    default: Some(json!(512)),
+                },
+            ),
+        ])
+    }
+
+    #[test]
+    fn test_resolve_parameters() {
+        let bound = HashMap::from([("model".to_string(), json!("minilm"))]);
+        let resolved = resolve_parameters(&declared(), &bound, &HashMap::new()).unwrap();
+        assert_eq!(resolved["model"], json!("minilm"));
+        assert_eq!(resolved["chunk_size"], json!(512));
+
+        let overrides = HashMap::from([("chunk_size".to_string(), json!(1024))]);
+        let resolved = resolve_parameters(&declared(), &bound, &overrides).unwrap();
+        assert_eq!(resolved["chunk_size"], json!(1024));
+
+        // Required parameters must be bound or overridden
+        assert!(resolve_parameters(&declared(), &HashMap::new(), &HashMap::new()).is_err());
+        let unknown = HashMap::from([("other".to_string(), json!(1))]);
+        assert!(resolve_parameters(&declared(), &bound, &unknown).is_err());
+        assert!(validate_parameter_values(&declared(), &unknown).is_err());
+    }
+
+    #[test]
+    fn test_render_parameters() {
+        let parameters = HashMap::from([
+            ("model".to_string(), json!("minilm")),
+            ("chunk_size".to_string(), json!(512)),
+        ]);
+        assert_eq!(
+            render_parameters("{params.model}:{params.chunk_size}", &parameters).unwrap(),
+            "minilm:512"
+        );
+        assert_eq!(render_parameters("plain", &parameters).unwrap(), "plain");
+        assert!(render_parameters("{params.other}", &parameters).is_err());
+        assert!(render_parameters("{params.model", &parameters).is_err());
+        assert!(referenced_parameters("{params.}").is_err());
+    }
+}
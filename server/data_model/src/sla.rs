@@ -0,0 +1,154 @@
+//! Service level agreements of compute graphs, e.g. 95% of invocations
+//! complete within 5 minutes. Attainment is computed over a rolling window
+//! from the timings of the invocations which finished in it.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{error::DataModelError, InvocationResult, InvocationStatus};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GraphSla {
+    /// Invocations must complete successfully within this duration
+    pub max_duration_secs: u64,
+    /// Fraction of the invocations of the window meeting the SLA, in (0, 1]
+    pub target: f64,
+    pub window_secs: u64,
+}
+
+impl GraphSla {
+    pub fn validate(&self) -> Result<(), DataModelError> {
+        if self.max_duration_secs == 0 || self.window_secs == 0 {
+            return Err(DataModelError::InvalidGraph(
+                "invalid sla, max_duration_secs and window_secs must be positive".to_string(),
+            ));
+        }
+        if !(self.target > 0.0 && self.target <= 1.0) {
+            return Err(DataModelError::InvalidGraph(
+                "invalid sla, target must be in (0, 1]".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Whether a finished invocation met the SLA, failed invocations never
+    /// do.
+    pub fn met_by(&self, result: &InvocationResult) -> bool {
+        result.status == InvocationStatus::Success &&
+            result.finished_at.saturating_sub(result.created_at) <= self.max_duration_secs * 1000
+    }
+}
+
+/// SLA attainment of a compute graph at a point in time.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SlaStatus {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub sla: GraphSla,
+    /// Invocations finished in the window, plus the running ones which
+    /// already exceeded the maximum duration
+    pub invocations: u64,
+    pub met: u64,
+    /// `None` while there are no invocations in the window
+    pub attainment: Option<f64>,
+    pub breached: bool,
+    pub evaluated_at: u64,
+}
+
+impl SlaStatus {
+    /// `overdue` counts the running invocations which already exceeded the
+    /// maximum duration, they can't meet the SLA anymore.
+    pub fn evaluate<'a>(
+        namespace: &str,
+        compute_graph: &str,
+        sla: &GraphSla,
+        finished: impl IntoIterator<Item = &'a InvocationResult>,
+        overdue: u64,
+        now: u64,
+    ) -> Self {
+        let mut invocations = overdue;
+        let mut met = 0;
+        for result in finished {
+            invocations += 1;
+            if sla.met_by(result) {
+                met += 1;
+            }
+        }
+        let attainment = (invocations > 0).then(|| met as f64 / invocations as f64);
+        Self {
+            namespace: namespace.to_string(),
+            compute_graph: compute_graph.to_string(),
+            sla: sla.clone(),
+            invocations,
+            met,
+            attainment,
+            breached: attainment.is_some_and(|attainment| attainment < sla.target),
+            evaluated_at: now,
+        }
+    }
+
+    pub fn key(&self) -> String {
+        SlaStatus::key_from(&self.namespace, &self.compute_graph)
+    }
+
+    pub fn key_from(namespace: &str, compute_graph: &str) -> String {
+        format!("{}|{}", namespace, compute_graph)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(status: InvocationStatus, duration_secs: u64) -> InvocationResult {
+        InvocationResult {
+            namespace: "ns".to_string(),
+            compute_graph_name: "graph".to_string(),
+            invocation_id: "inv".to_string(),
+            status,
+            outputs: vec![],
+            errors: vec![],
+            created_at: 1000,
+            finished_at: 1000 + duration_secs * 1000,
+        }
+    }
+
+    #[test]
+    fn test_sla_status() {
+        let sla = GraphSla {
+            max_duration_secs: 300,
+            target: 0.75,
+            window_secs: 3600,
+        };
+        let results = [
+            result(InvocationStatus::Success, 10),
+            result(InvocationStatus::Success, 300),
+            result(InvocationStatus::Success, 301),
+            result(InvocationStatus::Failure, 10),
+        ];
+        let status = SlaStatus::evaluate("ns", "graph", &sla, &results[..2], 0, 0);
+        assert_eq!((status.invocations, status.met), (2, 2));
+        assert!(!status.breached);
+
+        let status = SlaStatus::evaluate("ns", "graph", &sla, &results, 0, 0);
+        assert_eq!(status.attainment, Some(0.5));
+        assert!(status.breached);
+
+        // Overdue invocations count as misses
+        let status = SlaStatus::evaluate("ns", "graph", &sla, &results[..2], 1, 0);
+        assert_eq!((status.invocations, status.met), (3, 2));
+        assert!(status.breached);
+
+        let status = SlaStatus::evaluate("ns", "graph", &sla, [], 0, 0);
+        assert_eq!(status.attainment, None);
+        assert!(!status.breached);
+
+        for target in [0.0, 1.5] {
+            assert!(GraphSla {
+                target,
+                ..sla.clone()
+            }
+            .validate()
+            .is_err());
+        }
+    }
+}
@@ -95,6 +95,12 @@ pub mod tests {
                 sha256_hash: "3433".to_string(),
                 path,
                 size: 12,
+                tier: Default::default(),
+                mime_type: None,
+                content_encoding: None,
+                metadata: Default::default(),
+                replicas: Vec::new(),
+                compression: None,
             }))
             .build()
             .unwrap()
@@ -122,6 +128,12 @@ pub mod tests {
                 path: "test".to_string(),
                 size: 23,
                 sha256_hash: "hash1232".to_string(),
+                tier: Default::default(),
+                mime_type: None,
+                content_encoding: None,
+                metadata: Default::default(),
+                replicas: Vec::new(),
+                compression: None,
             })
             .build()
             .unwrap()
@@ -135,6 +147,12 @@ pub mod tests {
                 path: "test".to_string(),
                 size: 23,
                 sha256_hash: "hash1232".to_string(),
+                tier: Default::default(),
+                mime_type: None,
+                content_encoding: None,
+                metadata: Default::default(),
+                replicas: Vec::new(),
+                compression: None,
             })
             .build()
             .unwrap()
@@ -153,6 +171,7 @@ pub mod tests {
                 ("fn_a".to_string(), Node::Compute(fn_a.clone())),
             ]),
             version: crate::GraphVersion(1),
+            revision: 1,
             edges: HashMap::from([(
                 "fn_a".to_string(),
                 vec!["fn_b".to_string(), "fn_c".to_string()],
@@ -169,6 +188,18 @@ pub mod tests {
                 major_version: 3,
                 minor_version: 10,
             },
+            env: HashMap::new(),
+            secrets: HashMap::new(),
+            queue_limits: Default::default(),
+            sla: None,
+            deleted_at: None,
+            paused: None,
+            parameters: HashMap::new(),
+            parameter_values: HashMap::new(),
+            template: None,
+            ingestion_hooks: vec![],
+            default_lane: Default::default(),
+            skip_payload_compression: false,
         }
     }
 
@@ -181,6 +212,9 @@ pub mod tests {
             target_functions: vec!["fn_b".to_string(), "fn_c".to_string()],
             payload_encoder: "cloudpickle".to_string(),
             image_name: TEST_EXECUTOR_IMAGE_NAME.to_string(),
+            wasm_module: None,
+            default_targets: vec![],
+            on_error: Default::default(),
         };
         let fn_b = test_compute_fn("fn_b");
         let fn_c = test_compute_fn("fn_c");
@@ -194,6 +228,7 @@ pub mod tests {
                 ("fn_a".to_string(), Node::Compute(fn_a.clone())),
             ]),
             version: crate::GraphVersion(1),
+            revision: 1,
             edges: HashMap::from([("fn_a".to_string(), vec!["router_x".to_string()])]),
             description: "description graph_B".to_string(),
             code: ComputeGraphCode {
@@ -207,6 +242,18 @@ pub mod tests {
                 major_version: 3,
                 minor_version: 10,
             },
+            env: HashMap::new(),
+            secrets: HashMap::new(),
+            queue_limits: Default::default(),
+            sla: None,
+            deleted_at: None,
+            paused: None,
+            parameters: HashMap::new(),
+            parameter_values: HashMap::new(),
+            template: None,
+            ingestion_hooks: vec![],
+            default_lane: Default::default(),
+            skip_payload_compression: false,
         }
     }
 
@@ -233,12 +280,25 @@ pub mod tests {
                 sha256_hash: "hash123".to_string(),
             },
             version: crate::GraphVersion(1),
+            revision: 1,
             created_at: 5,
             start_fn: Compute(fn_a),
             runtime_information: RuntimeInformation {
                 major_version: 3,
                 minor_version: 10,
             },
+            env: HashMap::new(),
+            secrets: HashMap::new(),
+            queue_limits: Default::default(),
+            sla: None,
+            deleted_at: None,
+            paused: None,
+            parameters: HashMap::new(),
+            parameter_values: HashMap::new(),
+            template: None,
+            ingestion_hooks: vec![],
+            default_lane: Default::default(),
+            skip_payload_compression: false,
         }
     }
 
@@ -252,6 +312,15 @@ pub mod tests {
             image_name: TEST_EXECUTOR_IMAGE_NAME.to_string(),
             addr: "".to_string(),
             labels: Default::default(),
+            cached_artifacts: Default::default(),
+            gpus: vec![],
+            protocol_version: 0,
+            features: Default::default(),
+            runtimes: Default::default(),
+            queue_depth: 0,
+            max_outstanding_tasks: None,
+            version: None,
+            last_heartbeat_at: None,
         }
     }
 }
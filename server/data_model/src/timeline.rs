@@ -0,0 +1,305 @@
+//! Timeline of an invocation. The state store records an event whenever a
+//! task of the invocation changes state, so users can see what happened to
+//! the invocation and when.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    keys::{self, KeyPrefix},
+    InvocationStatus,
+    TaskAnalytics,
+    TaskOutcome,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct InvocationEvent {
+    /// Milliseconds since the epoch
+    pub at: u64,
+    pub kind: InvocationEventKind,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum InvocationEventKind {
+    Invoked,
+    TaskCreated {
+        task_id: String,
+        compute_fn: String,
+    },
+    TaskAllocated {
+        task_id: String,
+        compute_fn: String,
+        executor_id: String,
+    },
+    /// The allocation was revoked to run a higher priority task
+    TaskPreempted {
+        task_id: String,
+        compute_fn: String,
+        executor_id: String,
+    },
+    /// The task will be allocated again, its executor was lost or its tasks
+    /// were requeued
+    TaskRequeued {
+        task_id: String,
+        compute_fn: String,
+        executor_id: String,
+    },
+    TaskFinished {
+        task_id: String,
+        compute_fn: String,
+        outcome: TaskOutcome,
+    },
+    RouterChose {
+        task_id: String,
+        compute_fn: String,
+        edges: Vec<String>,
+    },
+    InvocationFinished {
+        status: InvocationStatus,
+    },
+}
+
+impl InvocationEvent {
+    pub fn key_prefix(namespace: &str, compute_graph: &str, invocation_id: &str) -> KeyPrefix {
+        KeyPrefix::encoded(&[namespace, compute_graph, invocation_id])
+    }
+
+    /// Events are ordered by the time they were recorded at in nanoseconds,
+    /// `seq` tells apart the events recorded at the same time.
+    pub fn key(
+        namespace: &str,
+        compute_graph: &str,
+        invocation_id: &str,
+        recorded_at_nanos: u128,
+        seq: u32,
+    ) -> Vec<u8> {
+        keys::encode_key(&[
+            namespace,
+            compute_graph,
+            invocation_id,
+            &format!("{:020}", recorded_at_nanos),
+            &format!("{:010}", seq),
+        ])
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplayedTaskStatus {
+    Pending,
+    Running,
+    Completed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ReplayedTask {
+    pub id: String,
+    pub compute_fn: String,
+    pub status: ReplayedTaskStatus,
+    pub executor_id: Option<String>,
+    pub outcome: TaskOutcome,
+}
+
+/// State of an invocation as of a point in time, rebuilt by replaying its
+/// timeline.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ReplayedInvocation {
+    /// Milliseconds since the epoch
+    pub as_of: u64,
+    /// Set once the invocation finished
+    pub status: Option<InvocationStatus>,
+    /// Tasks in the order they were created
+    pub tasks: Vec<ReplayedTask>,
+    /// Keyed by compute fn
+    pub analytics: HashMap<String, TaskAnalytics>,
+}
+
+impl ReplayedInvocation {
+    /// Replays the events recorded until `as_of`, `events` must be ordered
+    /// like the timeline. Returns None when the invocation wasn't invoked yet.
+    pub fn replay<'a>(
+        events: impl IntoIterator<Item = &'a InvocationEvent>,
+        as_of: u64,
+    ) -> Option<Self> {
+        let mut invoked = false;
+        let mut replayed = Self {
+            as_of,
+            status: None,
+            tasks: Vec::new(),
+            analytics: HashMap::new(),
+        };
+        let mut task_index: HashMap<String, usize> = HashMap::new();
+        for event in events.into_iter().take_while(|event| event.at <= as_of) {
+            match &event.kind {
+                InvocationEventKind::Invoked => invoked = true,
+                InvocationEventKind::TaskCreated {
+                    task_id,
+                    compute_fn,
+                } => {
+                    task_index.insert(task_id.clone(), replayed.tasks.len());
+                    replayed.tasks.push(ReplayedTask {
+                        id: task_id.clone(),
+                        compute_fn: compute_fn.clone(),
+                        status: ReplayedTaskStatus::Pending,
+                        executor_id: None,
+                        outcome: TaskOutcome::Unknown,
+                    });
+                }
+                InvocationEventKind::TaskAllocated {
+                    task_id,
+                    executor_id,
+                    ..
+                } => {
+                    if let Some(task) = task_mut(&mut replayed.tasks, &task_index, task_id) {
+                        task.status = ReplayedTaskStatus::Running;
+                        task.executor_id = Some(executor_id.clone());
+                    }
+                }
+                InvocationEventKind::TaskPreempted { task_id, .. } |
+                InvocationEventKind::TaskRequeued { task_id, .. } => {
+                    if let Some(task) = task_mut(&mut replayed.tasks, &task_index, task_id) {
+                        task.status = ReplayedTaskStatus::Pending;
+                        task.executor_id = None;
+                    }
+                }
+                InvocationEventKind::TaskFinished {
+                    task_id, outcome, ..
+                } => {
+                    if let Some(task) = task_mut(&mut replayed.tasks, &task_index, task_id) {
+                        task.status = ReplayedTaskStatus::Completed;
+                        task.outcome = outcome.clone();
+                    }
+                }
+                InvocationEventKind::RouterChose { .. } => {}
+                InvocationEventKind::InvocationFinished { status } => {
+                    replayed.status = Some(*status);
+                }
+            }
+        }
+        if !invoked {
+            return None;
+        }
+        for task in &replayed.tasks {
+            let analytics = replayed
+                .analytics
+                .entry(task.compute_fn.clone())
+                .or_default();
+            match task.outcome {
+                TaskOutcome::Success => analytics.successful_tasks += 1,
+                TaskOutcome::Failure => analytics.failed_tasks += 1,
+                TaskOutcome::Unknown => analytics.pending_tasks += 1,
+            }
+        }
+        Some(replayed)
+    }
+}
+
+fn task_mut<'a>(
+    tasks: &'a mut [ReplayedTask],
+    task_index: &HashMap<String, usize>,
+    task_id: &str,
+) -> Option<&'a mut ReplayedTask> {
+    task_index.get(task_id).map(|index| &mut tasks[*index])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(at: u64, kind: InvocationEventKind) -> InvocationEvent {
+        InvocationEvent { at, kind }
+    }
+
+    #[test]
+    fn test_replay_invocation() {
+        let events = vec![
+            event(1, InvocationEventKind::Invoked),
+            event(
+                1,
+                InvocationEventKind::TaskCreated {
+                    task_id: "t1".to_string(),
+                    compute_fn: "fn_a".to_string(),
+                },
+            ),
+            event(
+                2,
+                InvocationEventKind::TaskAllocated {
+                    task_id: "t1".to_string(),
+                    compute_fn: "fn_a".to_string(),
+                    executor_id: "e1".to_string(),
+                },
+            ),
+            event(
+                3,
+                InvocationEventKind::TaskRequeued {
+                    task_id: "t1".to_string(),
+                    compute_fn: "fn_a".to_string(),
+                    executor_id: "e1".to_string(),
+                },
+            ),
+            event(
+                4,
+                InvocationEventKind::TaskAllocated {
+                    task_id: "t1".to_string(),
+                    compute_fn: "fn_a".to_string(),
+                    executor_id: "e2".to_string(),
+                },
+            ),
+            event(
+                5,
+                InvocationEventKind::TaskFinished {
+                    task_id: "t1".to_string(),
+                    compute_fn: "fn_a".to_string(),
+                    outcome: TaskOutcome::Success,
+                },
+            ),
+            event(
+                5,
+                InvocationEventKind::TaskCreated {
+                    task_id: "t2".to_string(),
+                    compute_fn: "fn_b".to_string(),
+                },
+            ),
+            event(
+                6,
+                InvocationEventKind::TaskFinished {
+                    task_id: "t2".to_string(),
+                    compute_fn: "fn_b".to_string(),
+                    outcome: TaskOutcome::Failure,
+                },
+            ),
+            event(
+                6,
+                InvocationEventKind::InvocationFinished {
+                    status: InvocationStatus::Failure,
+                },
+            ),
+        ];
+
+        assert_eq!(ReplayedInvocation::replay(&events, 0), None);
+
+        let replayed = ReplayedInvocation::replay(&events, 2).unwrap();
+        assert_eq!(replayed.status, None);
+        assert_eq!(replayed.tasks.len(), 1);
+        assert_eq!(replayed.tasks[0].status, ReplayedTaskStatus::Running);
+        assert_eq!(replayed.tasks[0].executor_id, Some("e1".to_string()));
+        assert_eq!(replayed.analytics["fn_a"].pending_tasks, 1);
+
+        let replayed = ReplayedInvocation::replay(&events, 3).unwrap();
+        assert_eq!(replayed.tasks[0].status, ReplayedTaskStatus::Pending);
+        assert_eq!(replayed.tasks[0].executor_id, None);
+
+        let replayed = ReplayedInvocation::replay(&events, 5).unwrap();
+        assert_eq!(replayed.tasks[0].status, ReplayedTaskStatus::Completed);
+        assert_eq!(replayed.tasks[0].executor_id, Some("e2".to_string()));
+        assert_eq!(replayed.tasks[1].status, ReplayedTaskStatus::Pending);
+        assert_eq!(replayed.analytics["fn_a"].successful_tasks, 1);
+        assert_eq!(replayed.analytics["fn_b"].pending_tasks, 1);
+
+        let replayed = ReplayedInvocation::replay(&events, 6).unwrap();
+        assert_eq!(replayed.status, Some(InvocationStatus::Failure));
+        assert_eq!(replayed.analytics["fn_b"].failed_tasks, 1);
+    }
+}
@@ -0,0 +1,388 @@
+//! Webhooks notifying the endpoints registered by a namespace of lifecycle
+//! events. Events are queued as deliveries in the same transaction as the
+//! change they describe, and delivered by the leader with retries.
+
+use std::{
+    collections::BTreeSet,
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+use anyhow::{anyhow, Result};
+use indexify_utils::get_epoch_time_in_ms;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    alerts::AlertStatus,
+    invocation_group::InvocationGroup,
+    sla::SlaStatus,
+    ComputeGraph,
+    ExecutorId,
+    InvocationResult,
+    InvocationStatus,
+};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEventType {
+    InvocationCompleted,
+    InvocationFailed,
+    GraphCreated,
+    SlaBreached,
+    SlaRecovered,
+    // Executors aren't namespaced, the event is sent to the webhooks of
+    // every namespace subscribed to it
+    ExecutorRemoved,
+    InvocationGroupFinished,
+    DataRejected,
+    // Alerts of the rules of the cluster metrics are sent to the webhooks of
+    // every namespace subscribed to them
+    AlertFiring,
+    AlertResolved,
+}
+
+impl WebhookEventType {
+    pub const ALL: [WebhookEventType; 10] = [
+        WebhookEventType::InvocationCompleted,
+        WebhookEventType::InvocationFailed,
+        WebhookEventType::GraphCreated,
+        WebhookEventType::SlaBreached,
+        WebhookEventType::SlaRecovered,
+        WebhookEventType::ExecutorRemoved,
+        WebhookEventType::InvocationGroupFinished,
+        WebhookEventType::DataRejected,
+        WebhookEventType::AlertFiring,
+        WebhookEventType::AlertResolved,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookEventType::InvocationCompleted => "invocation_completed",
+            WebhookEventType::InvocationFailed => "invocation_failed",
+            WebhookEventType::GraphCreated => "graph_created",
+            WebhookEventType::SlaBreached => "sla_breached",
+            WebhookEventType::SlaRecovered => "sla_recovered",
+            WebhookEventType::ExecutorRemoved => "executor_removed",
+            WebhookEventType::InvocationGroupFinished => "invocation_group_finished",
+            WebhookEventType::DataRejected => "data_rejected",
+            WebhookEventType::AlertFiring => "alert_firing",
+            WebhookEventType::AlertResolved => "alert_resolved",
+        }
+    }
+}
+
+impl Display for WebhookEventType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for WebhookEventType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        WebhookEventType::ALL
+            .into_iter()
+            .find(|event_type| event_type.as_str() == s)
+            .ok_or(anyhow!("unknown webhook event {}", s))
+    }
+}
+
+/// An endpoint of a namespace receiving the events it subscribed to.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Webhook {
+    pub namespace: String,
+    pub id: String,
+    pub url: String,
+    pub events: BTreeSet<WebhookEventType>,
+    /// Hex encoded key the payloads delivered to the endpoint are signed with
+    pub secret: String,
+    pub created_at: u64,
+    /// Only receives the events of this graph when set
+    #[serde(default)]
+    pub compute_graph: Option<String>,
+}
+
+impl Webhook {
+    pub fn key(&self) -> String {
+        Webhook::key_from(&self.namespace, &self.id)
+    }
+
+    pub fn key_from(namespace: &str, id: &str) -> String {
+        format!("{}|{}", namespace, id)
+    }
+
+    pub fn subscribes_to(&self, event_type: WebhookEventType) -> bool {
+        self.events.contains(&event_type)
+    }
+
+    /// Whether the event is delivered to the webhook. Webhooks of a graph
+    /// don't receive the events which aren't about a graph.
+    pub fn receives(&self, event: &WebhookEvent) -> bool {
+        if !self.subscribes_to(event.event_type) {
+            return false;
+        }
+        match &self.compute_graph {
+            Some(compute_graph) => {
+                event.data["compute_graph"].as_str() == Some(compute_graph.as_str())
+            }
+            None => true,
+        }
+    }
+}
+
+/// Check which rejected data of a graph.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Guardrail {
+    PayloadSize,
+}
+
+/// Data of a graph rejected by a guardrail, the payload wasn't stored.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DataRejection {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub guardrail: Guardrail,
+    pub error: String,
+    /// Task which produced the payload, None for invocation inputs
+    pub invocation_id: Option<String>,
+    pub compute_fn: Option<String>,
+    pub task_id: Option<String>,
+    /// Url the payload was fetched from or registered with
+    pub url: Option<String>,
+    pub size_bytes: u64,
+}
+
+/// The JSON body delivered to webhooks.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WebhookEvent {
+    pub id: String,
+    pub event_type: WebhookEventType,
+    pub created_at: u64,
+    pub data: serde_json::Value,
+}
+
+impl WebhookEvent {
+    fn new(event_type: WebhookEventType, data: serde_json::Value) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            event_type,
+            created_at: get_epoch_time_in_ms(),
+            data,
+        }
+    }
+
+    pub fn invocation_finished(result: &InvocationResult) -> Self {
+        let event_type = match result.status {
+            InvocationStatus::Success => WebhookEventType::InvocationCompleted,
+            InvocationStatus::Failure => WebhookEventType::InvocationFailed,
+        };
+        let failed_functions: BTreeSet<&str> = result
+            .errors
+            .iter()
+            .map(|error| error.compute_fn.as_str())
+            .collect();
+        Self::new(
+            event_type,
+            serde_json::json!({
+                "namespace": result.namespace,
+                "compute_graph": result.compute_graph_name,
+                "invocation_id": result.invocation_id,
+                "created_at": result.created_at,
+                "finished_at": result.finished_at,
+                "failed_functions": failed_functions,
+            }),
+        )
+    }
+
+    pub fn graph_created(compute_graph: &ComputeGraph) -> Self {
+        Self::new(
+            WebhookEventType::GraphCreated,
+            serde_json::json!({
+                "namespace": compute_graph.namespace,
+                "compute_graph": compute_graph.name,
+                "version": compute_graph.version.0,
+            }),
+        )
+    }
+
+    pub fn sla_changed(status: &SlaStatus) -> Self {
+        let event_type = if status.breached {
+            WebhookEventType::SlaBreached
+        } else {
+            WebhookEventType::SlaRecovered
+        };
+        Self::new(
+            event_type,
+            serde_json::json!({
+                "namespace": status.namespace,
+                "compute_graph": status.compute_graph,
+                "target": status.sla.target,
+                "max_duration_secs": status.sla.max_duration_secs,
+                "window_secs": status.sla.window_secs,
+                "attainment": status.attainment,
+                "invocations": status.invocations,
+            }),
+        )
+    }
+
+    pub fn invocation_group_finished(group: &InvocationGroup) -> Self {
+        Self::new(
+            WebhookEventType::InvocationGroupFinished,
+            serde_json::json!({
+                "namespace": group.namespace,
+                "invocation_group": group.id,
+                "status": group.status(),
+                "invocations": group.invocations,
+                "succeeded": group.succeeded,
+                "failed": group.failed,
+                "created_at": group.created_at,
+                "finished_at": group.finished_at,
+            }),
+        )
+    }
+
+    pub fn data_rejected(rejection: &DataRejection) -> Self {
+        Self::new(
+            WebhookEventType::DataRejected,
+            serde_json::json!({
+                "namespace": rejection.namespace,
+                "compute_graph": rejection.compute_graph,
+                "guardrail": rejection.guardrail,
+                "error": rejection.error,
+                "payload": {
+                    "invocation_id": rejection.invocation_id,
+                    "compute_fn": rejection.compute_fn,
+                    "task_id": rejection.task_id,
+                    "url": rejection.url,
+                    "size_bytes": rejection.size_bytes,
+                },
+            }),
+        )
+    }
+
+    pub fn executor_removed(executor_id: &ExecutorId) -> Self {
+        Self::new(
+            WebhookEventType::ExecutorRemoved,
+            serde_json::json!({ "executor_id": executor_id.get() }),
+        )
+    }
+
+    pub fn alert_changed(status: &AlertStatus) -> Self {
+        let event_type = if status.firing() {
+            WebhookEventType::AlertFiring
+        } else {
+            WebhookEventType::AlertResolved
+        };
+        Self::new(
+            event_type,
+            serde_json::json!({
+                "alert": status.rule.name,
+                "metric": status.rule.metric,
+                "namespace": status.rule.namespace,
+                "compute_graph": status.rule.compute_graph,
+                "comparison": status.rule.comparison,
+                "threshold": status.rule.threshold,
+                "value": status.value,
+                "since": status.since,
+            }),
+        )
+    }
+}
+
+/// An event queued for a webhook. Deliveries which fail are retried with
+/// exponential backoff, and moved to the dead letters of the namespace once
+/// they run out of attempts.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WebhookDelivery {
+    pub namespace: String,
+    pub webhook_id: String,
+    pub event: WebhookEvent,
+    pub attempts: u32,
+    pub next_attempt_at: u64,
+    pub last_error: Option<String>,
+}
+
+impl WebhookDelivery {
+    pub fn new(webhook: &Webhook, event: WebhookEvent) -> Self {
+        Self {
+            namespace: webhook.namespace.clone(),
+            webhook_id: webhook.id.clone(),
+            next_attempt_at: event.created_at,
+            event,
+            attempts: 0,
+            last_error: None,
+        }
+    }
+
+    pub fn key(&self) -> String {
+        format!("{}|{}|{}", self.namespace, self.webhook_id, self.event.id)
+    }
+
+    pub fn webhook_key(&self) -> String {
+        Webhook::key_from(&self.namespace, &self.webhook_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_webhook_event_types() {
+        for event_type in WebhookEventType::ALL {
+            assert_eq!(
+                WebhookEventType::from_str(event_type.as_str()).unwrap(),
+                event_type
+            );
+            assert_eq!(
+                serde_json::to_value(event_type).unwrap(),
+                serde_json::json!(event_type.as_str())
+            );
+        }
+        assert!(WebhookEventType::from_str("task_created").is_err());
+    }
+
+    #[test]
+    fn test_graph_webhooks() {
+        let webhook = Webhook {
+            namespace: "ns".to_string(),
+            id: "quality".to_string(),
+            url: "http://localhost/hook".to_string(),
+            events: [
+                WebhookEventType::DataRejected,
+                WebhookEventType::ExecutorRemoved,
+            ]
+            .into(),
+            secret: "00".to_string(),
+            created_at: 0,
+            compute_graph: Some("graph".to_string()),
+        };
+        let rejection = |compute_graph: &str| {
+            WebhookEvent::data_rejected(&DataRejection {
+                namespace: "ns".to_string(),
+                compute_graph: compute_graph.to_string(),
+                guardrail: Guardrail::PayloadSize,
+                error: "too large".to_string(),
+                invocation_id: Some("inv".to_string()),
+                compute_fn: Some("fn".to_string()),
+                task_id: Some("task".to_string()),
+                url: None,
+                size_bytes: 10,
+            })
+        };
+        let event = rejection("graph");
+        assert!(webhook.receives(&event));
+        assert_eq!(event.data["payload"]["task_id"], "task");
+        assert_eq!(event.data["guardrail"], "payload_size");
+        assert!(!webhook.receives(&rejection("other")));
+        let executor_removed = WebhookEvent::executor_removed(&ExecutorId::new("e".to_string()));
+        assert!(!webhook.receives(&executor_removed));
+        assert!(Webhook {
+            compute_graph: None,
+            ..webhook
+        }
+        .receives(&executor_removed));
+    }
+}
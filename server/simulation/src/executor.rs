@@ -0,0 +1,73 @@
+use std::collections::BTreeMap;
+
+use data_model::{ExecutorMetadata, Task, TaskId};
+
+/// A task started by a simulated executor.
+pub(crate) struct RunningTask {
+    pub(crate) task: Task,
+    pub(crate) finishes_at: u64,
+}
+
+/// Executor of the simulation, tasks allocated to it finish after a number of
+/// ticks instead of running functions.
+pub(crate) struct SimExecutor {
+    pub(crate) metadata: ExecutorMetadata,
+    pub(crate) online: bool,
+    // Multiplies the duration of the tasks started on the executor
+    pub(crate) slowdown: u64,
+    pub(crate) running: BTreeMap<TaskId, RunningTask>,
+    pub(crate) tasks_finished: u64,
+}
+
+impl SimExecutor {
+    pub(crate) fn new(metadata: ExecutorMetadata) -> Self {
+        Self {
+            metadata,
+            online: true,
+            slowdown: 1,
+            running: BTreeMap::new(),
+            tasks_finished: 0,
+        }
+    }
+
+    /// Starts the allocated tasks which are not running yet, the duration of
+    /// each task is drawn by `duration`.
+    pub(crate) fn start_tasks(
+        &mut self,
+        allocated: Vec<Task>,
+        now: u64,
+        mut duration: impl FnMut() -> u64,
+    ) {
+        for task in allocated {
+            if self.running.contains_key(&task.id) {
+                continue;
+            }
+            let finishes_at = now + duration() * self.slowdown;
+            self.running
+                .insert(task.id.clone(), RunningTask { task, finishes_at });
+        }
+    }
+
+    /// Removes and returns the tasks finished at `now`.
+    pub(crate) fn finished_tasks(&mut self, now: u64) -> Vec<Task> {
+        let finished: Vec<TaskId> = self
+            .running
+            .iter()
+            .filter(|(_, running)| running.finishes_at <= now)
+            .map(|(id, _)| id.clone())
+            .collect();
+        finished
+            .iter()
+            .filter_map(|id| self.running.remove(id))
+            .map(|running| running.task)
+            .collect()
+    }
+
+    /// Goes offline, the running tasks are lost.
+    pub(crate) fn crash(&mut self) -> usize {
+        self.online = false;
+        let lost = self.running.len();
+        self.running.clear();
+        lost
+    }
+}
@@ -0,0 +1,689 @@
+//! Deterministic simulation of the scheduler and the state store.
+//!
+//! Synthetic executors run the allocated tasks for a number of ticks of a
+//! simulated clock, and scripted faults take executors away while tasks run
+//! on them. Allocation policies and recovery can be exercised without real
+//! executors. Runs with the same seed and script are identical, as long as
+//! the allocation strategy isn't `random`.
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use anyhow::{anyhow, Result};
+use data_model::{
+    ChangeType,
+    ComputeGraph,
+    DataPayload,
+    ExecutorId,
+    ExecutorMetadata,
+    InvocationPayloadBuilder,
+    Node,
+    NodeOutputBuilder,
+    OutputPayload,
+    RouterOutput,
+    Task,
+    TaskFinishedEvent,
+    TaskOutcome,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use state_store::{
+    requests::{
+        CreateComputeGraphRequest,
+        CreateTasksRequest,
+        DeregisterExecutorRequest,
+        FinalizeTaskRequest,
+        InvokeComputeGraphRequest,
+        ReductionTasks,
+        RegisterExecutorRequest,
+        RequestPayload,
+        SchedulerUpdateRequest,
+        StateMachineUpdateRequest,
+    },
+    IndexifyState,
+};
+use task_scheduler::{
+    allocator::AllocationConfig,
+    task_creator::{
+        handle_invoke_compute_graph,
+        handle_task_finished,
+        handle_task_outputs_streamed,
+        resume_fan_out,
+        sub_graph_invocations,
+    },
+    TaskCreationResult,
+    TaskScheduler,
+};
+use tempfile::TempDir;
+use tracing::info;
+
+mod executor;
+
+use executor::SimExecutor;
+
+// Bounds the scheduler runs of a tick, each run processes the state changes
+// written by the previous one
+const MAX_SCHEDULER_RUNS_PER_TICK: usize = 100;
+const MAX_TASKS_PER_EXECUTOR: usize = 10_000;
+
+#[derive(Debug, Clone)]
+pub struct SimulationConfig {
+    /// Seeds the durations of the tasks
+    pub seed: u64,
+    /// Tasks run for a number of ticks drawn in this range, both inclusive
+    pub min_task_ticks: u64,
+    pub max_task_ticks: u64,
+    pub fan_out_batch_size: usize,
+    pub allocation: AllocationConfig,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            min_task_ticks: 1,
+            max_task_ticks: 3,
+            fan_out_batch_size: 1000,
+            allocation: AllocationConfig::default(),
+        }
+    }
+}
+
+/// Failure injected at a tick of the simulation, executors are referred to by
+/// id.
+#[derive(Debug, Clone)]
+pub enum Fault {
+    /// The executor goes away for good, its tasks are scheduled again
+    ExecutorLost(String),
+    /// Tasks started on the executor from now on take `factor` times longer
+    SlowTasks { executor: String, factor: u64 },
+    /// The executor crashes, losing its running tasks, and registers again
+    /// after `down_ticks`
+    CrashRestart { executor: String, down_ticks: u64 },
+}
+
+#[derive(Debug, Clone)]
+enum Event {
+    Fault(Fault),
+    Restart(String),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SimulationReport {
+    pub ticks: u64,
+    pub invocations: usize,
+    pub invocations_completed: usize,
+    pub tasks_finished: u64,
+    /// Tasks running on executors when they were lost or crashed
+    pub tasks_lost: u64,
+    pub tasks_finished_by_executor: BTreeMap<String, u64>,
+}
+
+impl SimulationReport {
+    pub fn all_completed(&self) -> bool {
+        self.invocations_completed == self.invocations
+    }
+}
+
+pub struct Simulation {
+    indexify_state: Arc<IndexifyState>,
+    task_scheduler: TaskScheduler,
+    config: SimulationConfig,
+    rng: StdRng,
+    now: u64,
+    executors: BTreeMap<String, SimExecutor>,
+    events: BTreeMap<u64, Vec<Event>>,
+    invocations: Vec<(String, String, String)>,
+    tasks_lost: u64,
+    // Removed with the simulation
+    _state_dir: TempDir,
+}
+
+impl Simulation {
+    pub async fn new(config: SimulationConfig) -> Result<Self> {
+        if config.min_task_ticks == 0 || config.min_task_ticks > config.max_task_ticks {
+            return Err(anyhow!(
+                "task ticks must be positive with min_task_ticks <= max_task_ticks"
+            ));
+        }
+        let state_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(state_dir.path().join("state")).await?;
+        let task_scheduler = TaskScheduler::new(indexify_state.clone());
+        task_scheduler.set_allocation_config(&config.allocation);
+        Ok(Self {
+            indexify_state,
+            task_scheduler,
+            rng: StdRng::seed_from_u64(config.seed),
+            config,
+            now: 0,
+            executors: BTreeMap::new(),
+            events: BTreeMap::new(),
+            invocations: vec![],
+            tasks_lost: 0,
+            _state_dir: state_dir,
+        })
+    }
+
+    pub fn indexify_state(&self) -> Arc<IndexifyState> {
+        self.indexify_state.clone()
+    }
+
+    pub fn now(&self) -> u64 {
+        self.now
+    }
+
+    async fn write(&self, payload: RequestPayload) -> Result<()> {
+        self.indexify_state
+            .write(StateMachineUpdateRequest {
+                payload,
+                state_changes_processed: vec![],
+            })
+            .await
+    }
+
+    pub async fn create_compute_graph(&self, compute_graph: ComputeGraph) -> Result<()> {
+        self.write(RequestPayload::CreateComputeGraph(
+            CreateComputeGraphRequest {
+                namespace: compute_graph.namespace.clone(),
+                compute_graph,
+                expected_revision: None,
+                force: false,
+            },
+        ))
+        .await
+    }
+
+    /// Invokes the compute graph and returns the id of the invocation.
+    pub async fn invoke(&mut self, namespace: &str, compute_graph: &str) -> Result<String> {
+        let invocation_payload = InvocationPayloadBuilder::default()
+            .namespace(namespace.to_string())
+            .compute_graph_name(compute_graph.to_string())
+            .payload(DataPayload {
+                path: format!("sim://invocations/{}", self.invocations.len()),
+                size: 0,
+                sha256_hash: "".to_string(),
+                tier: Default::default(),
+                mime_type: None,
+                content_encoding: None,
+                metadata: Default::default(),
+                replicas: Vec::new(),
+                compression: None,
+            })
+            .build()?;
+        let id = invocation_payload.id.clone();
+        self.write(RequestPayload::InvokeComputeGraph(
+            InvokeComputeGraphRequest {
+                namespace: namespace.to_string(),
+                compute_graph_name: compute_graph.to_string(),
+                invocation_payload,
+            },
+        ))
+        .await?;
+        self.invocations
+            .push((namespace.to_string(), compute_graph.to_string(), id.clone()));
+        Ok(id)
+    }
+
+    pub async fn add_executor(&mut self, executor: ExecutorMetadata) -> Result<()> {
+        let id = executor.id.get().to_string();
+        if self.executors.contains_key(&id) {
+            return Err(anyhow!("executor {} already exists", id));
+        }
+        self.write(RequestPayload::RegisterExecutor(RegisterExecutorRequest {
+            executor: executor.clone(),
+        }))
+        .await?;
+        self.executors.insert(id, SimExecutor::new(executor));
+        Ok(())
+    }
+
+    /// Schedules a fault at the given tick.
+    pub fn inject(&mut self, tick: u64, fault: Fault) {
+        self.events
+            .entry(tick)
+            .or_default()
+            .push(Event::Fault(fault));
+    }
+
+    /// Advances the simulation by one tick: applies the faults of the tick,
+    /// finishes the tasks which are done and starts the newly allocated ones.
+    pub async fn step(&mut self) -> Result<()> {
+        for event in self.events.remove(&self.now).unwrap_or_default() {
+            self.apply(event).await?;
+        }
+        self.finish_tasks().await?;
+        self.schedule().await?;
+        self.start_tasks()?;
+        self.now += 1;
+        Ok(())
+    }
+
+    /// Steps until every invocation completed or `max_ticks` passed.
+    pub async fn run(&mut self, max_ticks: u64) -> Result<SimulationReport> {
+        let until = self.now + max_ticks;
+        while self.now < until {
+            self.step().await?;
+            if self.report()?.all_completed() {
+                break;
+            }
+        }
+        self.report()
+    }
+
+    pub fn report(&self) -> Result<SimulationReport> {
+        let reader = self.indexify_state.reader();
+        let mut invocations_completed = 0;
+        for (namespace, compute_graph, id) in &self.invocations {
+            if reader
+                .invocation_ctx(namespace, compute_graph, id)?
+                .completed
+            {
+                invocations_completed += 1;
+            }
+        }
+        let tasks_finished_by_executor: BTreeMap<String, u64> = self
+            .executors
+            .iter()
+            .map(|(id, executor)| (id.clone(), executor.tasks_finished))
+            .collect();
+        Ok(SimulationReport {
+            ticks: self.now,
+            invocations: self.invocations.len(),
+            invocations_completed,
+            tasks_finished: tasks_finished_by_executor.values().sum(),
+            tasks_lost: self.tasks_lost,
+            tasks_finished_by_executor,
+        })
+    }
+
+    fn executor(&mut self, id: &str) -> Result<&mut SimExecutor> {
+        self.executors
+            .get_mut(id)
+            .ok_or(anyhow!("executor {} not found", id))
+    }
+
+    async fn apply(&mut self, event: Event) -> Result<()> {
+        info!(tick = self.now, "simulation event: {:?}", event);
+        match event {
+            Event::Fault(Fault::ExecutorLost(id)) => self.take_down(&id).await,
+            Event::Fault(Fault::SlowTasks { executor, factor }) => {
+                self.executor(&executor)?.slowdown = factor.max(1);
+                Ok(())
+            }
+            Event::Fault(Fault::CrashRestart {
+                executor,
+                down_ticks,
+            }) => {
+                self.take_down(&executor).await?;
+                self.events
+                    .entry(self.now + down_ticks.max(1))
+                    .or_default()
+                    .push(Event::Restart(executor));
+                Ok(())
+            }
+            Event::Restart(id) => {
+                let executor = self.executor(&id)?;
+                executor.online = true;
+                let metadata = executor.metadata.clone();
+                self.write(RequestPayload::RegisterExecutor(RegisterExecutorRequest {
+                    executor: metadata,
+                }))
+                .await
+            }
+        }
+    }
+
+    async fn take_down(&mut self, id: &str) -> Result<()> {
+        let executor = self.executor(id)?;
+        if !executor.online {
+            return Ok(());
+        }
+        let lost = executor.crash() as u64;
+        self.tasks_lost += lost;
+        self.write(RequestPayload::DeregisterExecutor(
+            DeregisterExecutorRequest {
+                executor_id: ExecutorId::new(id.to_string()),
+            },
+        ))
+        .await
+    }
+
+    async fn finish_tasks(&mut self) -> Result<()> {
+        let mut finished = vec![];
+        for (id, executor) in self.executors.iter_mut() {
+            if !executor.online {
+                continue;
+            }
+            for task in executor.finished_tasks(self.now) {
+                executor.tasks_finished += 1;
+                finished.push((id.clone(), task));
+            }
+        }
+        for (executor_id, task) in finished {
+            let request = self.finalize_request(&executor_id, &task)?;
+            self.write(RequestPayload::FinalizeTask(request)).await?;
+        }
+        Ok(())
+    }
+
+    // Tasks succeed with one output, routers route to all their targets
+    fn finalize_request(&self, executor_id: &str, task: &Task) -> Result<FinalizeTaskRequest> {
+        let compute_graph = self
+            .indexify_state
+            .reader()
+            .get_compute_graph_version(
+                &task.namespace,
+                &task.compute_graph_name,
+                &task.graph_version,
+            )?
+            .ok_or(anyhow!("compute graph not found"))?;
+        let payload = match compute_graph.nodes.get(&task.compute_fn_name) {
+            Some(Node::Router(router)) => OutputPayload::Router(RouterOutput {
+                edges: router.target_functions.clone(),
+            }),
+            _ => OutputPayload::Fn(DataPayload {
+                path: format!("sim://outputs/{}", task.id),
+                size: 0,
+                sha256_hash: "".to_string(),
+                tier: Default::default(),
+                mime_type: None,
+                content_encoding: None,
+                metadata: Default::default(),
+                replicas: Vec::new(),
+                compression: None,
+            }),
+        };
+        let node_output = NodeOutputBuilder::default()
+            .namespace(task.namespace.clone())
+            .compute_graph_name(task.compute_graph_name.clone())
+            .compute_fn_name(task.compute_fn_name.clone())
+            .invocation_id(task.invocation_id.clone())
+            .graph_version(task.graph_version.clone())
+            .payload(payload)
+            .build()?;
+        Ok(FinalizeTaskRequest {
+            namespace: task.namespace.clone(),
+            compute_graph: task.compute_graph_name.clone(),
+            compute_fn: task.compute_fn_name.clone(),
+            invocation_id: task.invocation_id.clone(),
+            task_id: task.id.clone(),
+            node_outputs: vec![node_output],
+            task_outcome: TaskOutcome::Success,
+            executor_id: ExecutorId::new(executor_id.to_string()),
+            diagnostics: None,
+            inline_payloads: vec![],
+            resource_usage: None,
+        })
+    }
+
+    fn start_tasks(&mut self) -> Result<()> {
+        let reader = self.indexify_state.reader();
+        let (min, max) = (self.config.min_task_ticks, self.config.max_task_ticks);
+        for executor in self.executors.values_mut() {
+            if !executor.online {
+                continue;
+            }
+            let allocated =
+                reader.get_tasks_by_executor(&executor.metadata.id, MAX_TASKS_PER_EXECUTOR)?;
+            executor.start_tasks(allocated, self.now, || self.rng.gen_range(min..=max));
+        }
+        Ok(())
+    }
+
+    async fn schedule(&self) -> Result<()> {
+        for _ in 0..MAX_SCHEDULER_RUNS_PER_TICK {
+            if self
+                .indexify_state
+                .reader()
+                .get_unprocessed_state_changes()?
+                .is_empty()
+            {
+                return Ok(());
+            }
+            self.run_scheduler().await?;
+        }
+        Err(anyhow!(
+            "state changes still pending after {} scheduler runs",
+            MAX_SCHEDULER_RUNS_PER_TICK
+        ))
+    }
+
+    async fn handle_task_finished(
+        &self,
+        event: &TaskFinishedEvent,
+        fan_out_batch_size: usize,
+    ) -> Result<TaskCreationResult> {
+        let reader = self.indexify_state.reader();
+        let task = reader
+            .get_task_from_finished_event(event)?
+            .ok_or(anyhow!("task not found {}", event.task_id))?;
+        let compute_graph = reader
+            .get_compute_graph_version(
+                &task.namespace,
+                &task.compute_graph_name,
+                &task.graph_version,
+            )?
+            .ok_or(anyhow!("compute graph not found"))?;
+        handle_task_finished(
+            self.indexify_state.clone(),
+            task,
+            compute_graph,
+            fan_out_batch_size,
+        )
+        .await
+    }
+
+    // Same as a run of the server's scheduler, without WASM routers
+    async fn run_scheduler(&self) -> Result<()> {
+        let state_changes = self
+            .indexify_state
+            .reader()
+            .get_unprocessed_state_changes()?;
+        let fan_out_batch_size = self.config.fan_out_batch_size;
+        let mut task_creation_results = vec![];
+        for state_change in &state_changes {
+            let result = match &state_change.change_type {
+                ChangeType::InvokeComputeGraph(event) => Some(
+                    handle_invoke_compute_graph(self.indexify_state.clone(), event.clone()).await?,
+                ),
+                ChangeType::TaskFinished(event) => {
+                    Some(self.handle_task_finished(event, fan_out_batch_size).await?)
+                }
+                ChangeType::TasksFinished(event) => {
+                    for event in &event.tasks {
+                        task_creation_results
+                            .push(self.handle_task_finished(event, fan_out_batch_size).await?);
+                    }
+                    None
+                }
+                ChangeType::TaskOutputsStreamed(event) => {
+                    let compute_graph = self
+                        .indexify_state
+                        .reader()
+                        .get_invocation_compute_graph(
+                            &event.namespace,
+                            &event.compute_graph,
+                            &event.invocation_id,
+                        )?
+                        .ok_or(anyhow!("compute graph not found"))?;
+                    Some(
+                        handle_task_outputs_streamed(
+                            self.indexify_state.clone(),
+                            event.clone(),
+                            compute_graph,
+                        )
+                        .await?,
+                    )
+                }
+                _ => None,
+            };
+            task_creation_results.extend(result);
+        }
+        for cursor in self.indexify_state.reader().fan_out_cursors()? {
+            if self
+                .indexify_state
+                .reader()
+                .is_compute_graph_paused(&cursor.namespace, &cursor.compute_graph)?
+            {
+                continue;
+            }
+            task_creation_results.push(
+                resume_fan_out(self.indexify_state.clone(), cursor, fan_out_batch_size).await?,
+            );
+        }
+        let mut task_requests = vec![];
+        let mut reduction_tasks = ReductionTasks::default();
+        let mut new_sub_graph_invocations = vec![];
+        for result in task_creation_results {
+            new_sub_graph_invocations.extend(sub_graph_invocations(
+                self.indexify_state.clone(),
+                &result.tasks,
+            )?);
+            task_requests.push(CreateTasksRequest {
+                namespace: result.namespace,
+                invocation_id: result.invocation_id,
+                compute_graph: result.compute_graph,
+                tasks: result.tasks,
+                fan_out_cursor: result.fan_out_cursor,
+            });
+            reduction_tasks
+                .new_reduction_tasks
+                .extend(result.new_reduction_tasks);
+            reduction_tasks
+                .processed_reduction_tasks
+                .extend(result.processed_reduction_tasks);
+        }
+        let allocate = state_changes.iter().any(|state_change| {
+            matches!(
+                state_change.change_type,
+                ChangeType::TaskCreated |
+                    ChangeType::TaskFinished(_) |
+                    ChangeType::TasksFinished(_) |
+                    ChangeType::ExecutorAdded |
+                    ChangeType::ExecutorRemoved |
+                    ChangeType::ExecutorUpdated |
+                    ChangeType::ExecutorPoolUpdated |
+                    ChangeType::TasksRequeued(_) |
+                    ChangeType::GraphResumed
+            )
+        });
+        let placement = if allocate {
+            Some(self.task_scheduler.schedule_unplaced_tasks()?)
+        } else {
+            None
+        };
+        let (allocations, preemptions, diagnostic_msgs) = placement
+            .map(|p| (p.task_placements, p.preemptions, p.diagnostic_msgs))
+            .unwrap_or_default();
+        self.indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::SchedulerUpdate(SchedulerUpdateRequest {
+                    task_requests,
+                    allocations,
+                    reduction_tasks,
+                    diagnostic_msgs,
+                    sub_graph_invocations: new_sub_graph_invocations,
+                    preemptions,
+                }),
+                state_changes_processed: state_changes.into_iter().map(|c| c.id).collect(),
+            })
+            .await
+    }
+}
+
+/// Executor able to run the functions of the test graphs.
+pub fn test_executor(id: &str) -> ExecutorMetadata {
+    ExecutorMetadata {
+        id: ExecutorId::new(id.to_string()),
+        addr: format!("sim://{}", id),
+        ..data_model::test_objects::tests::mock_executor()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use data_model::test_objects::tests::{mock_graph_a, TEST_NAMESPACE};
+    use task_scheduler::allocator::AllocationStrategy;
+
+    use super::*;
+
+    async fn simulation(seed: u64, script: Vec<(u64, Fault)>) -> Result<SimulationReport> {
+        let mut simulation = Simulation::new(SimulationConfig {
+            seed,
+            min_task_ticks: 2,
+            max_task_ticks: 4,
+            allocation: AllocationConfig {
+                default: AllocationStrategy::Spread,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .await?;
+        simulation.create_compute_graph(mock_graph_a()).await?;
+        for id in ["executor_1", "executor_2"] {
+            simulation.add_executor(test_executor(id)).await?;
+        }
+        for _ in 0..5 {
+            simulation.invoke(TEST_NAMESPACE, "graph_A").await?;
+        }
+        for (tick, fault) in script {
+            simulation.inject(tick, fault);
+        }
+        simulation.run(200).await
+    }
+
+    #[tokio::test]
+    async fn test_invocations_complete() -> Result<()> {
+        let report = simulation(7, vec![]).await?;
+        assert!(report.all_completed());
+        // Every invocation runs fn_a, fn_b and fn_c
+        assert_eq!(report.tasks_finished, 15);
+        assert_eq!(report.tasks_lost, 0);
+        assert!(report.tasks_finished_by_executor.values().all(|n| *n > 0));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_recovery_from_executor_faults() -> Result<()> {
+        let report = simulation(
+            7,
+            vec![
+                (1, Fault::ExecutorLost("executor_1".to_string())),
+                (
+                    2,
+                    Fault::CrashRestart {
+                        executor: "executor_2".to_string(),
+                        down_ticks: 3,
+                    },
+                ),
+            ],
+        )
+        .await?;
+        // Lost tasks run again once executor_2 is back
+        assert!(report.all_completed());
+        assert!(report.tasks_lost > 0);
+        assert_eq!(report.tasks_finished, 15);
+        assert_eq!(report.tasks_finished_by_executor["executor_1"], 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_same_seed_same_run() -> Result<()> {
+        let script = || {
+            vec![(
+                3,
+                Fault::SlowTasks {
+                    executor: "executor_1".to_string(),
+                    factor: 5,
+                },
+            )]
+        };
+        let first = simulation(11, script()).await?;
+        let second = simulation(11, script()).await?;
+        assert!(first.all_completed());
+        assert_eq!(first.ticks, second.ticks);
+        assert_eq!(
+            first.tasks_finished_by_executor,
+            second.tasks_finished_by_executor
+        );
+        Ok(())
+    }
+}
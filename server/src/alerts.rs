@@ -0,0 +1,366 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::Result;
+use data_model::alerts::{AlertMetric, AlertRule, AlertState, AlertStatus};
+use indexify_utils::get_epoch_time_in_ms;
+use state_store::{
+    requests::{RecordAlertStatusesRequest, RequestPayload, StateMachineUpdateRequest},
+    IndexifyState,
+};
+use tokio::sync::watch;
+
+use crate::config::RuntimeConfig;
+
+// How often the alert rules are evaluated
+const EVALUATION_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Finished and failed tasks of a compute graph when it was sampled, counted
+/// since the graph was created.
+#[derive(Debug, Clone, Copy)]
+struct TaskCounts {
+    at: u64,
+    tasks: u64,
+    failed_tasks: u64,
+}
+
+/// Evaluates the alert rules of the runtime configuration and records the
+/// statuses of their alerts, which notifies the webhooks when an alert fires
+/// or resolves. Runs on the leader.
+pub struct AlertMonitor {
+    state: Arc<IndexifyState>,
+    runtime_config_rx: watch::Receiver<RuntimeConfig>,
+    shutdown_rx: watch::Receiver<()>,
+    // Task counts of the graphs of the windowed rules at each evaluation, the
+    // windowed metrics are the differences over their window. A new leader
+    // starts sampling afresh.
+    samples: HashMap<(String, String), VecDeque<TaskCounts>>,
+}
+
+impl AlertMonitor {
+    pub fn new(
+        state: Arc<IndexifyState>,
+        runtime_config_rx: watch::Receiver<RuntimeConfig>,
+        shutdown_rx: watch::Receiver<()>,
+    ) -> Self {
+        Self {
+            state,
+            runtime_config_rx,
+            shutdown_rx,
+            samples: HashMap::new(),
+        }
+    }
+
+    fn sample_task_counts(&mut self, rules: &[AlertRule], now: u64) -> Result<()> {
+        let mut windows: HashMap<(String, String), u64> = HashMap::new();
+        for rule in rules.iter().filter(|rule| rule.metric.windowed()) {
+            let (Some(namespace), Some(compute_graph)) = (&rule.namespace, &rule.compute_graph)
+            else {
+                continue;
+            };
+            let window = windows
+                .entry((namespace.clone(), compute_graph.clone()))
+                .or_default();
+            *window = (*window).max(rule.window_secs.unwrap_or_default() * 1000);
+        }
+        self.samples.retain(|graph, _| windows.contains_key(graph));
+
+        let reader = self.state.reader();
+        let namespaces: HashSet<&String> = windows.keys().map(|(namespace, _)| namespace).collect();
+        let mut usage_of_graphs = HashMap::new();
+        for namespace in namespaces {
+            for usage in reader.namespace_task_usage(namespace)? {
+                usage_of_graphs.insert((usage.namespace, usage.compute_graph), usage.usage);
+            }
+        }
+        for ((namespace, compute_graph), window_ms) in windows {
+            let usage = usage_of_graphs
+                .remove(&(namespace.clone(), compute_graph.clone()))
+                .unwrap_or_default();
+            let samples = self.samples.entry((namespace, compute_graph)).or_default();
+            samples.push_back(TaskCounts {
+                at: now,
+                tasks: usage.tasks,
+                failed_tasks: usage.failed_tasks,
+            });
+            // The newest sample older than the window is kept as its baseline
+            let window_start = now.saturating_sub(window_ms);
+            while samples.len() > 1 && samples[1].at <= window_start {
+                samples.pop_front();
+            }
+        }
+        Ok(())
+    }
+
+    /// Tasks of the graph of the rule which finished in its window, and
+    /// those which failed. The window is shorter until the leader sampled
+    /// the graph for long enough.
+    fn windowed_task_counts(&self, rule: &AlertRule, now: u64) -> (u64, u64) {
+        let (Some(namespace), Some(compute_graph)) = (&rule.namespace, &rule.compute_graph) else {
+            return (0, 0);
+        };
+        let key = (namespace.clone(), compute_graph.clone());
+        let Some(samples) = self.samples.get(&key) else {
+            return (0, 0);
+        };
+        let window_start = now.saturating_sub(rule.window_secs.unwrap_or_default() * 1000);
+        let (Some(latest), Some(oldest)) = (samples.back(), samples.front()) else {
+            return (0, 0);
+        };
+        let baseline = samples
+            .iter()
+            .rev()
+            .find(|sample| sample.at <= window_start)
+            .unwrap_or(oldest);
+        (
+            latest.tasks.saturating_sub(baseline.tasks),
+            latest.failed_tasks.saturating_sub(baseline.failed_tasks),
+        )
+    }
+
+    fn metric_value(&self, rule: &AlertRule, now: u64) -> Result<Option<f64>> {
+        let reader = self.state.reader();
+        let value = match rule.metric {
+            AlertMetric::FailedTaskRate => {
+                let (tasks, failed_tasks) = self.windowed_task_counts(rule, now);
+                (tasks > 0).then(|| failed_tasks as f64 / tasks as f64)
+            }
+            AlertMetric::FailedTasks => Some(self.windowed_task_counts(rule, now).1 as f64),
+            AlertMetric::UnallocatedTasks => match (&rule.namespace, &rule.compute_graph) {
+                (Some(namespace), Some(compute_graph)) => {
+                    Some(reader.count_unallocated_tasks(namespace, compute_graph, u64::MAX)? as f64)
+                }
+                _ => None,
+            },
+            AlertMetric::StateChangeLag => Some(reader.unprocessed_state_changes_lag()?.0 as f64),
+            AlertMetric::StateChangeLagMs => {
+                let (_, oldest_created_at) = reader.unprocessed_state_changes_lag()?;
+                let lag_ms = oldest_created_at
+                    .map(|created_at| now.saturating_sub(created_at))
+                    .unwrap_or_default();
+                Some(lag_ms as f64)
+            }
+            AlertMetric::Executors => Some(reader.get_all_executors()?.len() as f64),
+        };
+        Ok(value)
+    }
+
+    async fn evaluate(&mut self, now: u64) -> Result<()> {
+        let rules = self.runtime_config_rx.borrow().alert_rules.clone();
+        self.sample_task_counts(&rules, now)?;
+        let recorded: HashMap<String, AlertStatus> = self
+            .state
+            .reader()
+            .alert_statuses()?
+            .into_iter()
+            .map(|status| (status.key().to_string(), status))
+            .collect();
+        if rules.is_empty() && recorded.is_empty() {
+            return Ok(());
+        }
+        let mut statuses = Vec::with_capacity(rules.len());
+        for rule in &rules {
+            let value = self.metric_value(rule, now)?;
+            let status = AlertStatus::evaluate(rule, value, recorded.get(&rule.name), now);
+            let was_firing = recorded
+                .get(&rule.name)
+                .is_some_and(|recorded| recorded.firing());
+            if status.firing() && !was_firing {
+                tracing::warn!("alert {} firing, value {:?}", rule.name, value);
+            }
+            if status.state == AlertState::Ok && was_firing {
+                tracing::info!("alert {} resolved, value {:?}", rule.name, value);
+            }
+            statuses.push(status);
+        }
+        let names: HashSet<&String> = rules.iter().map(|rule| &rule.name).collect();
+        let removed = recorded
+            .into_keys()
+            .filter(|name| !names.contains(name))
+            .collect();
+        self.state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::RecordAlertStatuses(RecordAlertStatusesRequest {
+                    statuses,
+                    removed,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await
+    }
+
+    pub async fn start(&mut self) -> Result<()> {
+        let mut interval = tokio::time::interval(EVALUATION_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    if let Err(err) = self.evaluate(get_epoch_time_in_ms()).await {
+                        tracing::error!("error evaluating alert rules: {:?}", err);
+                    }
+                }
+                _ = self.shutdown_rx.changed() => return Ok(()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use data_model::{
+        alerts::AlertComparison,
+        test_objects::tests::{mock_graph_a, TEST_NAMESPACE},
+        webhooks::{Webhook, WebhookEventType},
+        GraphTaskUsage,
+        TaskUsage,
+    };
+    use state_store::{
+        requests::{CreateComputeGraphRequest, CreateWebhookRequest, NamespaceRequest},
+        serializer::{JsonEncode, JsonEncoder},
+        state_machine::IndexifyObjectsColumns,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_alert_rules() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let graph = mock_graph_a();
+        for payload in [
+            RequestPayload::CreateNameSpace(NamespaceRequest {
+                name: TEST_NAMESPACE.to_string(),
+                queue_limits: Default::default(),
+                scheduling_weight: None,
+                reserved_capacity: None,
+                replication: None,
+            }),
+            RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph: graph.clone(),
+                expected_revision: None,
+                force: false,
+            }),
+            RequestPayload::CreateWebhook(CreateWebhookRequest {
+                webhook: Webhook {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    id: "alerts".to_string(),
+                    url: "http://localhost/hook".to_string(),
+                    events: [
+                        WebhookEventType::AlertFiring,
+                        WebhookEventType::AlertResolved,
+                    ]
+                    .into(),
+                    secret: "00".to_string(),
+                    created_at: 0,
+                    compute_graph: None,
+                },
+            }),
+        ] {
+            state
+                .write(StateMachineUpdateRequest {
+                    payload,
+                    state_changes_processed: vec![],
+                })
+                .await?;
+        }
+        let set_usage = |tasks: u64, failed_tasks: u64| -> Result<()> {
+            let usage = GraphTaskUsage {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph: graph.name.clone(),
+                usage: TaskUsage {
+                    tasks,
+                    failed_tasks,
+                    ..Default::default()
+                },
+            };
+            state.db.put_cf(
+                &IndexifyObjectsColumns::TaskUsage.cf_db(&state.db),
+                format!("{}|{}", TEST_NAMESPACE, graph.name),
+                &JsonEncoder::encode(&usage)?,
+            )?;
+            Ok(())
+        };
+        let rule = AlertRule {
+            name: "failures".to_string(),
+            metric: AlertMetric::FailedTaskRate,
+            namespace: Some(TEST_NAMESPACE.to_string()),
+            compute_graph: Some(graph.name.clone()),
+            window_secs: Some(600),
+            comparison: AlertComparison::Above,
+            threshold: 0.05,
+            for_secs: 0,
+        };
+        let (runtime_config_tx, runtime_config_rx) = watch::channel(RuntimeConfig {
+            alert_rules: vec![rule.clone()],
+            ..Default::default()
+        });
+        let mut monitor = AlertMonitor::new(state.clone(), runtime_config_rx, watch::channel(()).1);
+        let reader = state.reader();
+        let events = || -> Result<Vec<WebhookEventType>> {
+            let mut events: Vec<_> = reader
+                .due_webhook_deliveries(u64::MAX, 10)?
+                .into_iter()
+                .map(|delivery| delivery.event.event_type)
+                .collect();
+            events.sort();
+            Ok(events)
+        };
+
+        // Failures before the window don't count
+        set_usage(100, 50)?;
+        monitor.evaluate(0).await?;
+        set_usage(200, 51)?;
+        monitor.evaluate(60_000).await?;
+        let statuses = reader.alert_statuses()?;
+        assert_eq!(statuses[0].state, AlertState::Ok);
+        assert_eq!(statuses[0].value, Some(0.01));
+
+        // Fires once when the rate of the window exceeds the threshold
+        set_usage(300, 61)?;
+        monitor.evaluate(120_000).await?;
+        monitor.evaluate(180_000).await?;
+        assert_eq!(events()?, vec![WebhookEventType::AlertFiring]);
+        let statuses = reader.alert_statuses()?;
+        assert_eq!(statuses[0].state, AlertState::Firing);
+        assert_eq!(statuses[0].value, Some(0.055));
+
+        // The failures leave the window
+        monitor.evaluate(720_000).await?;
+        assert_eq!(
+            events()?,
+            vec![
+                WebhookEventType::AlertFiring,
+                WebhookEventType::AlertResolved
+            ]
+        );
+        assert_eq!(reader.alert_statuses()?[0].value, None);
+
+        // Alerts of the cluster metrics, the alerts of removed rules are
+        // deleted
+        runtime_config_tx.send_modify(|config| {
+            config.alert_rules = vec![AlertRule {
+                name: "no_executors".to_string(),
+                metric: AlertMetric::Executors,
+                namespace: None,
+                compute_graph: None,
+                window_secs: None,
+                comparison: AlertComparison::Below,
+                threshold: 1.0,
+                for_secs: 60,
+            }]
+        });
+        monitor.evaluate(730_000).await?;
+        let statuses = reader.alert_statuses()?;
+        assert_eq!(statuses.len(), 1);
+        assert_eq!(statuses[0].state, AlertState::Pending);
+        assert_eq!(statuses[0].value, Some(0.0));
+        monitor.evaluate(790_000).await?;
+        assert!(reader.alert_statuses()?[0].firing());
+        assert_eq!(events()?.len(), 3);
+        assert!(monitor.samples.is_empty());
+        Ok(())
+    }
+}
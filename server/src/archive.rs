@@ -0,0 +1,548 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use anyhow::Result;
+use arrow_array::{
+    Array,
+    ArrayRef,
+    BooleanArray,
+    Float64Array,
+    Int32Array,
+    RecordBatch,
+    StringArray,
+    TimestampMillisecondArray,
+    UInt32Array,
+    UInt64Array,
+};
+use blob_store::{BlobStorage, BlobStorageConfig};
+use bytes::Bytes;
+use data_model::{
+    GraphInvocationCtx,
+    InvocationResult,
+    InvocationStatus,
+    Task,
+    TaskOutcome,
+    TaskUsage,
+};
+use futures::stream;
+use indexify_utils::get_epoch_time_in_ms;
+use parquet::{
+    arrow::ArrowWriter,
+    basic::Compression,
+    file::properties::WriterProperties,
+};
+use serde::{Deserialize, Serialize};
+use state_store::IndexifyState;
+
+use crate::config::ArchiveConfig;
+
+// Invocations exported to a single set of files
+const BATCH_SIZE: usize = 1000;
+
+// Results are timestamped before they're committed, invocations which
+// finished in the last minute are left to the next export so that a result
+// committed during an export isn't skipped
+const SETTLE_MS: u64 = 60 * 1000;
+
+/// Progress of the exports, stored next to the files.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Watermark {
+    /// Invocations which finished until then, in ms since epoch, are exported
+    exported_until: u64,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct ArchiveExport {
+    pub invocations: usize,
+    pub tasks: usize,
+    pub files: usize,
+}
+
+fn column(name: &'static str, array: impl Array + 'static) -> (&'static str, ArrayRef) {
+    (name, Arc::new(array))
+}
+
+#[derive(Default)]
+struct UsageColumns {
+    tasks: Vec<Option<u64>>,
+    duration_ms: Vec<Option<u64>>,
+    input_bytes: Vec<Option<u64>>,
+    output_bytes: Vec<Option<u64>>,
+    cpu_seconds: Vec<Option<f64>>,
+    gpu_seconds: Vec<Option<f64>>,
+    cold_starts: Vec<Option<u64>>,
+    warm_starts: Vec<Option<u64>>,
+}
+
+impl UsageColumns {
+    fn push(&mut self, usage: Option<&TaskUsage>) {
+        self.tasks.push(usage.map(|usage| usage.tasks));
+        self.duration_ms.push(usage.map(|usage| usage.duration_ms));
+        self.input_bytes.push(usage.map(|usage| usage.input_bytes));
+        self.output_bytes.push(usage.map(|usage| usage.output_bytes));
+        self.cpu_seconds.push(usage.map(|usage| usage.cpu_seconds));
+        self.gpu_seconds.push(usage.map(|usage| usage.gpu_seconds));
+        self.cold_starts.push(usage.map(|usage| usage.cold_starts));
+        self.warm_starts.push(usage.map(|usage| usage.warm_starts));
+    }
+
+    fn columns(self) -> Vec<(&'static str, ArrayRef)> {
+        vec![
+            column("usage_tasks", UInt64Array::from(self.tasks)),
+            column("duration_ms", UInt64Array::from(self.duration_ms)),
+            column("input_bytes", UInt64Array::from(self.input_bytes)),
+            column("output_bytes", UInt64Array::from(self.output_bytes)),
+            column("cpu_seconds", Float64Array::from(self.cpu_seconds)),
+            column("gpu_seconds", Float64Array::from(self.gpu_seconds)),
+            column("cold_starts", UInt64Array::from(self.cold_starts)),
+            column("warm_starts", UInt64Array::from(self.warm_starts)),
+        ]
+    }
+}
+
+// One row per finished invocation
+#[derive(Default)]
+struct InvocationColumns {
+    namespace: Vec<String>,
+    compute_graph: Vec<String>,
+    invocation_id: Vec<String>,
+    graph_version: Vec<u32>,
+    status: Vec<String>,
+    labels: Vec<String>,
+    priority: Vec<i32>,
+    canary: Vec<bool>,
+    created_at: Vec<i64>,
+    finished_at: Vec<i64>,
+    outputs: Vec<u64>,
+    failed_tasks: Vec<u64>,
+    usage: UsageColumns,
+}
+
+impl InvocationColumns {
+    fn push(&mut self, result: &InvocationResult, ctx: &GraphInvocationCtx) -> Result<()> {
+        self.namespace.push(result.namespace.clone());
+        self.compute_graph.push(result.compute_graph_name.clone());
+        self.invocation_id.push(result.invocation_id.clone());
+        self.graph_version.push(ctx.graph_version.0);
+        let status = match result.status {
+            InvocationStatus::Success => "success",
+            InvocationStatus::Failure => "failure",
+        };
+        self.status.push(status.to_string());
+        self.labels.push(serde_json::to_string(&ctx.labels)?);
+        self.priority.push(ctx.priority);
+        self.canary.push(ctx.canary);
+        self.created_at.push(result.created_at as i64);
+        self.finished_at.push(result.finished_at as i64);
+        self.outputs.push(result.outputs.len() as u64);
+        self.failed_tasks.push(result.errors.len() as u64);
+        self.usage.push(Some(&ctx.usage));
+        Ok(())
+    }
+
+    fn batch(self) -> Result<RecordBatch> {
+        let mut columns = vec![
+            column("namespace", StringArray::from(self.namespace)),
+            column("compute_graph", StringArray::from(self.compute_graph)),
+            column("invocation_id", StringArray::from(self.invocation_id)),
+            column("graph_version", UInt32Array::from(self.graph_version)),
+            column("status", StringArray::from(self.status)),
+            column("labels", StringArray::from(self.labels)),
+            column("priority", Int32Array::from(self.priority)),
+            column("canary", BooleanArray::from(self.canary)),
+            column("created_at", TimestampMillisecondArray::from(self.created_at)),
+            column("finished_at", TimestampMillisecondArray::from(self.finished_at)),
+            column("outputs", UInt64Array::from(self.outputs)),
+            column("failed_tasks", UInt64Array::from(self.failed_tasks)),
+        ];
+        columns.extend(self.usage.columns());
+        Ok(RecordBatch::try_from_iter(columns)?)
+    }
+}
+
+// One row per task of a finished invocation
+#[derive(Default)]
+struct TaskColumns {
+    namespace: Vec<String>,
+    compute_graph: Vec<String>,
+    invocation_id: Vec<String>,
+    compute_fn: Vec<String>,
+    task_id: Vec<String>,
+    graph_version: Vec<u32>,
+    outcome: Vec<String>,
+    created_at: Vec<i64>,
+    allocated_at: Vec<Option<i64>>,
+    failure: Vec<Option<String>>,
+    usage: UsageColumns,
+}
+
+impl TaskColumns {
+    fn push(&mut self, task: &Task) -> Result<()> {
+        let created_at = task
+            .creation_time
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        self.namespace.push(task.namespace.clone());
+        self.compute_graph.push(task.compute_graph_name.clone());
+        self.invocation_id.push(task.invocation_id.clone());
+        self.compute_fn.push(task.compute_fn_name.clone());
+        self.task_id.push(task.id.to_string());
+        self.graph_version.push(task.graph_version.0);
+        let outcome = match task.outcome {
+            TaskOutcome::Unknown => "unknown",
+            TaskOutcome::Success => "success",
+            TaskOutcome::Failure => "failure",
+        };
+        self.outcome.push(outcome.to_string());
+        self.created_at.push(created_at.as_millis() as i64);
+        self.allocated_at
+            .push(task.allocated_at.map(|allocated_at| allocated_at as i64));
+        self.failure.push(
+            task.failure
+                .as_ref()
+                .map(serde_json::to_string)
+                .transpose()?,
+        );
+        self.usage.push(task.usage.as_ref());
+        Ok(())
+    }
+
+    fn batch(self) -> Result<RecordBatch> {
+        let mut columns = vec![
+            column("namespace", StringArray::from(self.namespace)),
+            column("compute_graph", StringArray::from(self.compute_graph)),
+            column("invocation_id", StringArray::from(self.invocation_id)),
+            column("compute_fn", StringArray::from(self.compute_fn)),
+            column("task_id", StringArray::from(self.task_id)),
+            column("graph_version", UInt32Array::from(self.graph_version)),
+            column("outcome", StringArray::from(self.outcome)),
+            column("created_at", TimestampMillisecondArray::from(self.created_at)),
+            column("allocated_at", TimestampMillisecondArray::from(self.allocated_at)),
+            column("failure", StringArray::from(self.failure)),
+        ];
+        columns.extend(self.usage.columns());
+        Ok(RecordBatch::try_from_iter(columns)?)
+    }
+}
+
+// One row per function of a finished invocation
+#[derive(Default)]
+struct AnalyticsColumns {
+    namespace: Vec<String>,
+    compute_graph: Vec<String>,
+    invocation_id: Vec<String>,
+    compute_fn: Vec<String>,
+    pending_tasks: Vec<u64>,
+    successful_tasks: Vec<u64>,
+    failed_tasks: Vec<u64>,
+}
+
+impl AnalyticsColumns {
+    fn push(&mut self, ctx: &GraphInvocationCtx) {
+        let mut compute_fns: Vec<&String> = ctx.fn_task_analytics.keys().collect();
+        compute_fns.sort();
+        for compute_fn in compute_fns {
+            let analytics = &ctx.fn_task_analytics[compute_fn];
+            self.namespace.push(ctx.namespace.clone());
+            self.compute_graph.push(ctx.compute_graph_name.clone());
+            self.invocation_id.push(ctx.invocation_id.clone());
+            self.compute_fn.push(compute_fn.clone());
+            self.pending_tasks.push(analytics.pending_tasks);
+            self.successful_tasks.push(analytics.successful_tasks);
+            self.failed_tasks.push(analytics.failed_tasks);
+        }
+    }
+
+    fn batch(self) -> Result<RecordBatch> {
+        let columns = vec![
+            column("namespace", StringArray::from(self.namespace)),
+            column("compute_graph", StringArray::from(self.compute_graph)),
+            column("invocation_id", StringArray::from(self.invocation_id)),
+            column("compute_fn", StringArray::from(self.compute_fn)),
+            column("pending_tasks", UInt64Array::from(self.pending_tasks)),
+            column("successful_tasks", UInt64Array::from(self.successful_tasks)),
+            column("failed_tasks", UInt64Array::from(self.failed_tasks)),
+        ];
+        Ok(RecordBatch::try_from_iter(columns)?)
+    }
+}
+
+fn to_parquet(batch: &RecordBatch) -> Result<Bytes> {
+    let props = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build();
+    let mut writer = ArrowWriter::try_new(Vec::new(), batch.schema(), Some(props))?;
+    writer.write(batch)?;
+    Ok(Bytes::from(writer.into_inner()?))
+}
+
+/// Exports the records of finished invocations, their tasks and the task
+/// analytics of their functions to Parquet files, runs on the leader. Once
+/// exported, the invocations can be deleted from the live store.
+///
+/// Exports are at least once, an export interrupted before its watermark is
+/// written is exported again, so rows are deduplicated on their ids.
+pub struct InvocationArchiver {
+    state: Arc<IndexifyState>,
+    storage: Arc<BlobStorage>,
+    config: ArchiveConfig,
+    shutdown_rx: tokio::sync::watch::Receiver<()>,
+}
+
+impl InvocationArchiver {
+    pub fn new(
+        state: Arc<IndexifyState>,
+        blob_storage: Arc<BlobStorage>,
+        config: ArchiveConfig,
+        shutdown_rx: tokio::sync::watch::Receiver<()>,
+    ) -> Result<Self> {
+        let storage = if config.s3.is_none() && config.disk.is_none() {
+            blob_storage
+        } else {
+            Arc::new(BlobStorage::new(BlobStorageConfig {
+                s3: config.s3.clone(),
+                disk: config.disk.clone(),
+                cold: None,
+                replicas: HashMap::new(),
+            })?)
+        };
+        Ok(Self {
+            state,
+            storage,
+            config,
+            shutdown_rx,
+        })
+    }
+
+    fn watermark_key(&self) -> String {
+        format!("{}/_watermark.json", self.config.prefix)
+    }
+
+    async fn watermark(&self) -> Result<Watermark> {
+        match self.storage.read_key(&self.watermark_key()).await? {
+            Some(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            None => Ok(Watermark::default()),
+        }
+    }
+
+    async fn put(&self, key: &str, bytes: Bytes) -> Result<()> {
+        let data_stream = Box::pin(stream::once(async { Ok(bytes) }));
+        self.storage.put(key, data_stream).await?;
+        Ok(())
+    }
+
+    /// Writes the files of a batch of finished invocations, returns how many
+    /// tasks were exported.
+    async fn export_batch(&self, results: &[InvocationResult], file: &str) -> Result<usize> {
+        let reader = self.state.reader();
+        let mut invocations = InvocationColumns::default();
+        let mut tasks = TaskColumns::default();
+        let mut analytics = AnalyticsColumns::default();
+        for result in results {
+            let ctx = reader.invocation_ctx(
+                &result.namespace,
+                &result.compute_graph_name,
+                &result.invocation_id,
+            )?;
+            invocations.push(result, &ctx)?;
+            analytics.push(&ctx);
+            let (invocation_tasks, _) = reader.list_tasks_by_compute_graph(
+                &result.namespace,
+                &result.compute_graph_name,
+                &result.invocation_id,
+                None,
+                None,
+            )?;
+            for task in &invocation_tasks {
+                tasks.push(task)?;
+            }
+        }
+        let exported_tasks = tasks.task_id.len();
+        let tables = [
+            ("invocations", invocations.batch()?),
+            ("tasks", tasks.batch()?),
+            ("analytics", analytics.batch()?),
+        ];
+        for (table, batch) in tables {
+            let key = format!("{}/{}/{}.parquet", self.config.prefix, table, file);
+            self.put(&key, to_parquet(&batch)?).await?;
+        }
+        Ok(exported_tasks)
+    }
+
+    /// Exports the invocations which finished since the last export.
+    pub async fn export(&self) -> Result<ArchiveExport> {
+        let watermark = self.watermark().await?;
+        let until = get_epoch_time_in_ms().saturating_sub(SETTLE_MS);
+        let mut export = ArchiveExport::default();
+        if until <= watermark.exported_until {
+            return Ok(export);
+        }
+        let mut restart_key = None;
+        for part in 0.. {
+            let (results, next_key) =
+                self.state.reader().invocation_results_finished_between(
+                    watermark.exported_until,
+                    until,
+                    restart_key.as_deref(),
+                    Some(BATCH_SIZE),
+                )?;
+            if !results.is_empty() {
+                let file = format!("{}-{}-{:05}", watermark.exported_until, until, part);
+                export.tasks += self.export_batch(&results, &file).await?;
+                export.invocations += results.len();
+                export.files += 3;
+            }
+            match next_key {
+                Some(next_key) => restart_key = Some(next_key),
+                None => break,
+            }
+        }
+        let watermark = Watermark {
+            exported_until: until,
+        };
+        self.put(
+            &self.watermark_key(),
+            Bytes::from(serde_json::to_vec(&watermark)?),
+        )
+        .await?;
+        Ok(export)
+    }
+
+    pub async fn start(&mut self) -> Result<()> {
+        let mut interval = tokio::time::interval(Duration::from_secs(self.config.interval_secs));
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    match self.export().await {
+                        Ok(export) if export.invocations > 0 => {
+                            tracing::info!(
+                                "exported {} invocations and {} tasks to the archive",
+                                export.invocations,
+                                export.tasks
+                            );
+                        }
+                        Ok(_) => {}
+                        Err(err) => tracing::error!("error exporting invocations: {:?}", err),
+                    }
+                }
+                _ = self.shutdown_rx.changed() => return Ok(()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::cast::AsArray;
+    use data_model::{
+        test_objects::tests::{create_mock_task, mock_graph_a, TEST_NAMESPACE},
+        GraphInvocationCtxBuilder,
+    };
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use state_store::{
+        serializer::{JsonEncode, JsonEncoder},
+        state_machine::IndexifyObjectsColumns,
+    };
+    use tokio::sync::watch;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_invocation_archiver() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let config = BlobStorageConfig::new_disk(temp_dir.path().join("blobs").to_str().unwrap());
+        let storage = Arc::new(BlobStorage::new(config)?);
+        let graph = mock_graph_a();
+        let add_invocation = |id: &str, finished_at: u64| -> Result<()> {
+            let mut ctx = GraphInvocationCtxBuilder::default()
+                .namespace(TEST_NAMESPACE.to_string())
+                .compute_graph_name(graph.name.clone())
+                .invocation_id(id.to_string())
+                .build(graph.clone())?;
+            ctx.completed = true;
+            let mut task = create_mock_task(&graph, "fn_a", "input", id);
+            task.outcome = TaskOutcome::Success;
+            let result = InvocationResult {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph_name: graph.name.clone(),
+                invocation_id: id.to_string(),
+                status: InvocationStatus::Success,
+                outputs: vec![],
+                errors: vec![],
+                created_at: finished_at - 1000,
+                finished_at,
+            };
+            state.db.put_cf(
+                &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&state.db),
+                ctx.key(),
+                &JsonEncoder::encode(&ctx)?,
+            )?;
+            state.db.put_cf(
+                &IndexifyObjectsColumns::Tasks.cf_db(&state.db),
+                task.key(),
+                &JsonEncoder::encode(&task)?,
+            )?;
+            state.db.put_cf(
+                &IndexifyObjectsColumns::InvocationResults.cf_db(&state.db),
+                result.key(),
+                &JsonEncoder::encode(&result)?,
+            )?;
+            Ok(())
+        };
+        let now = get_epoch_time_in_ms();
+        add_invocation("old", now - 5 * SETTLE_MS)?;
+        // Left to the next export
+        add_invocation("recent", now)?;
+
+        let config = ArchiveConfig {
+            s3: None,
+            disk: None,
+            prefix: "archive".to_string(),
+            interval_secs: 3600,
+        };
+        let archiver =
+            InvocationArchiver::new(state.clone(), storage.clone(), config, watch::channel(()).1)?;
+        let export = archiver.export().await?;
+        assert_eq!(
+            export,
+            ArchiveExport {
+                invocations: 1,
+                tasks: 1,
+                files: 3,
+            }
+        );
+        let until = archiver.watermark().await?.exported_until;
+        assert!(until >= now - SETTLE_MS);
+        let read_table = |table: &str| {
+            let key = format!("archive/{}/0-{}-00000.parquet", table, until);
+            let storage = storage.clone();
+            async move {
+                let bytes = storage.read_key(&key).await?.unwrap();
+                let batches = ParquetRecordBatchReaderBuilder::try_new(bytes)?
+                    .build()?
+                    .collect::<Result<Vec<_>, _>>()?;
+                anyhow::Ok(batches)
+            }
+        };
+        let invocations = read_table("invocations").await?;
+        assert_eq!(invocations[0].num_rows(), 1);
+        let ids = invocations[0]
+            .column_by_name("invocation_id")
+            .unwrap()
+            .as_string::<i32>();
+        assert_eq!(ids.value(0), "old");
+        let tasks = read_table("tasks").await?;
+        let outcomes = tasks[0]
+            .column_by_name("outcome")
+            .unwrap()
+            .as_string::<i32>();
+        assert_eq!(outcomes.value(0), "success");
+        let analytics = read_table("analytics").await?;
+        assert_eq!(analytics[0].num_rows(), graph.nodes.len());
+
+        // Exported invocations aren't exported again
+        assert_eq!(archiver.export().await?.invocations, 0);
+        Ok(())
+    }
+}
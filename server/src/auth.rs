@@ -0,0 +1,293 @@
+//! Role based access control. Requests authenticate with a bearer token
+//! mapped to an identity, either a static token or a JWT of the configured
+//! identity provider, and the role bindings of a namespace grant roles to
+//! identities and their groups. Every decision is logged with the identity,
+//! namespace and permission it concerns.
+
+use std::fmt;
+
+use axum::http::{header, HeaderMap, StatusCode};
+use indexify_utils::get_epoch_time_in_ms;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::{debug, info, warn};
+use utoipa::ToSchema;
+
+use crate::{
+    http_objects::IndexifyAPIError,
+    oidc::{JwksCache, OidcConfig},
+};
+
+/// Bindings to this namespace apply to every namespace and to the routes
+/// which are not scoped to a namespace.
+pub const ALL_NAMESPACES: &str = "*";
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct AuthConfig {
+    /// Identities authenticated by a static bearer token
+    #[serde(default)]
+    pub identities: Vec<StaticIdentity>,
+    /// Identity provider whose tokens are accepted along with the static
+    /// ones
+    #[serde(default)]
+    pub oidc: Option<OidcConfig>,
+    #[serde(default)]
+    pub bindings: Vec<RoleBinding>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StaticIdentity {
+    pub name: String,
+    /// Hex encoded SHA-256 hash of the token, the token itself isn't stored
+    pub token_sha256: String,
+    #[serde(default)]
+    pub groups: Vec<String>,
+}
+
+/// Grants a role in a namespace, or in all of them, to identities and to the
+/// members of groups.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RoleBinding {
+    pub namespace: String,
+    pub role: Role,
+    #[serde(default)]
+    pub subjects: Vec<String>,
+    #[serde(default)]
+    pub groups: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Reads graphs, invocations and their outputs
+    Viewer,
+    /// Viewers which also create graphs and invoke them
+    Editor,
+    /// Every permission but replicating the state, including deleting
+    /// namespaces and operating the cluster when bound to all namespaces
+    Admin,
+    /// Executors pulling tasks and reporting their outcomes
+    Executor,
+    /// Replicas of the server, authenticated by their peer token
+    Peer,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    Read,
+    Write,
+    Admin,
+    Execute,
+    Replicate,
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let permission = match self {
+            Permission::Read => "read",
+            Permission::Write => "write",
+            Permission::Admin => "admin",
+            Permission::Execute => "execute",
+            Permission::Replicate => "replicate",
+        };
+        write!(f, "{}", permission)
+    }
+}
+
+impl Role {
+    pub fn grants(&self, permission: Permission) -> bool {
+        match self {
+            Role::Viewer => permission == Permission::Read,
+            Role::Editor => matches!(permission, Permission::Read | Permission::Write),
+            // The replication log carries sealed secrets and follower acks
+            Role::Admin => permission != Permission::Replicate,
+            Role::Executor => permission == Permission::Execute,
+            Role::Peer => matches!(permission, Permission::Read | Permission::Replicate),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Identity {
+    pub name: String,
+    pub groups: Vec<String>,
+}
+
+impl AuthConfig {
+    /// Identity of the bearer token of the request, if any.
+    pub fn authenticate(&self, headers: &HeaderMap, jwks: &JwksCache) -> Option<Identity> {
+        let token = bearer_token(headers)?;
+        let token_sha256 = hex::encode(Sha256::digest(token.as_bytes()));
+        let static_identity = self
+            .identities
+            .iter()
+            .find(|identity| identity.token_sha256.eq_ignore_ascii_case(&token_sha256));
+        if let Some(identity) = static_identity {
+            return Some(Identity {
+                name: identity.name.clone(),
+                groups: identity.groups.clone(),
+            });
+        }
+        let oidc = self.oidc.as_ref()?;
+        jwks.verify(oidc, token, get_epoch_time_in_ms() / 1000)
+            .inspect_err(|err| debug!("rejected token: {:?}", err))
+            .ok()
+    }
+
+    /// Identity of the request, unauthenticated requests are rejected with
+    /// `401`.
+    pub fn require_identity(
+        &self,
+        headers: &HeaderMap,
+        jwks: &JwksCache,
+    ) -> Result<Identity, IndexifyAPIError> {
+        self.authenticate(headers, jwks).ok_or_else(|| {
+            IndexifyAPIError::new(StatusCode::UNAUTHORIZED, "missing or unknown bearer token")
+        })
+    }
+
+    /// Roles bound to the identity in the namespace, including the ones bound
+    /// in all namespaces.
+    pub fn roles(&self, identity: &Identity, namespace: &str) -> Vec<Role> {
+        let mut roles = Vec::new();
+        for binding in &self.bindings {
+            if binding.namespace != namespace && binding.namespace != ALL_NAMESPACES {
+                continue;
+            }
+            let bound = binding.subjects.contains(&identity.name) ||
+                binding
+                    .groups
+                    .iter()
+                    .any(|group| identity.groups.contains(group));
+            if bound && !roles.contains(&binding.role) {
+                roles.push(binding.role);
+            }
+        }
+        roles
+    }
+
+    pub fn is_allowed(&self, identity: &Identity, namespace: &str, permission: Permission) -> bool {
+        self.roles(identity, namespace)
+            .iter()
+            .any(|role| role.grants(permission))
+    }
+
+    /// Authenticates the request and checks the permission, logging the
+    /// decision. Requests lacking the permission are rejected with `403`.
+    pub fn authorize(
+        &self,
+        headers: &HeaderMap,
+        jwks: &JwksCache,
+        namespace: &str,
+        permission: Permission,
+        route: &str,
+    ) -> Result<Identity, IndexifyAPIError> {
+        let identity = self.require_identity(headers, jwks).inspect_err(|_| {
+            warn!(
+                route,
+                namespace,
+                %permission,
+                allowed = false,
+                "authz decision: unauthenticated"
+            );
+        })?;
+        let allowed = self.is_allowed(&identity, namespace, permission);
+        info!(
+            identity = identity.name,
+            route,
+            namespace,
+            %permission,
+            allowed,
+            "authz decision"
+        );
+        if !allowed {
+            return Err(IndexifyAPIError::new(
+                StatusCode::FORBIDDEN,
+                &format!(
+                    "{} doesn't have the {} permission in namespace {}",
+                    identity.name, permission, namespace
+                ),
+            ));
+        }
+        Ok(identity)
+    }
+}
+
+pub(crate) fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{http::HeaderValue, response::IntoResponse};
+
+    use super::*;
+
+    fn config() -> AuthConfig {
+        AuthConfig {
+            identities: vec![StaticIdentity {
+                name: "alice".to_string(),
+                token_sha256: hex::encode(Sha256::digest(b"secret")),
+                groups: vec!["ml".to_string()],
+            }],
+            bindings: vec![
+                RoleBinding {
+                    namespace: "research".to_string(),
+                    role: Role::Editor,
+                    subjects: vec![],
+                    groups: vec!["ml".to_string()],
+                },
+                RoleBinding {
+                    namespace: ALL_NAMESPACES.to_string(),
+                    role: Role::Viewer,
+                    subjects: vec!["alice".to_string()],
+                    groups: vec![],
+                },
+            ],
+            oidc: None,
+        }
+    }
+
+    fn headers(token: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {}", token)).unwrap(),
+        );
+        headers
+    }
+
+    #[test]
+    fn test_authorize() {
+        let config = config();
+        let jwks = JwksCache::default();
+        let identity = config.authenticate(&headers("secret"), &jwks).unwrap();
+        assert_eq!(identity.name, "alice");
+        assert_eq!(config.authenticate(&headers("other"), &jwks), None);
+        assert_eq!(config.authenticate(&HeaderMap::new(), &jwks), None);
+
+        assert_eq!(
+            config.roles(&identity, "research"),
+            vec![Role::Editor, Role::Viewer]
+        );
+        assert!(config.is_allowed(&identity, "research", Permission::Write));
+        assert!(config.is_allowed(&identity, "default", Permission::Read));
+        assert!(!config.is_allowed(&identity, "default", Permission::Write));
+        assert!(!config.is_allowed(&identity, "research", Permission::Admin));
+
+        let err = config
+            .authorize(&headers("secret"), &jwks, "default", Permission::Write, "/")
+            .unwrap_err();
+        assert_eq!(err.into_response().status(), StatusCode::FORBIDDEN);
+        let err = config
+            .authorize(&HeaderMap::new(), &jwks, "default", Permission::Read, "/")
+            .unwrap_err();
+        assert_eq!(err.into_response().status(), StatusCode::UNAUTHORIZED);
+    }
+}
@@ -0,0 +1,460 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use axum::http::{header, HeaderMap, HeaderValue};
+use futures::future::join_all;
+use rand::Rng;
+use reqwest::StatusCode;
+use sha2::{Digest, Sha256};
+use state_store::{replication::decode_entries, IndexifyState};
+use tokio::sync::watch;
+use tracing::{debug, error, info};
+
+use crate::{
+    auth::bearer_token,
+    config::ClusterConfig,
+    http_objects::{ClusterStatus, VoteRequest, VoteResponse},
+};
+
+// Maximum number of log entries fetched from the leader per request.
+const REPLICATION_BATCH_SIZE: usize = 1000;
+
+// Longest download of a snapshot of the leader's state.
+const SNAPSHOT_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+/// Header of the term of the leader serving a snapshot of its state.
+pub const TERM_HEADER: &str = "x-indexify-term";
+
+/// Elects a leader among the replicas of the server and keeps followers in
+/// sync with the leader's replication log.
+///
+/// A node which hasn't heard from a leader for a randomized election timeout
+/// of one to two `lease_timeout_ms` asks its peers to vote for it in a new
+/// term. Each node votes at most once per term, for candidates whose log is
+/// at least as up to date as its own, and a candidate which receives the
+/// votes of a majority of the cluster becomes the leader of the term. Nodes
+/// which heard from a leader within `lease_timeout_ms` don't vote, and
+/// candidates only start a term once a pre-vote tells them they can win it.
+/// Followers reject the log of a leader of an older term. A leader steps
+/// down when a majority of the cluster doesn't follow it for
+/// `lease_timeout_ms` or it sees a newer term.
+///
+/// A follower whose log diverged from the leader's, or misses entries the
+/// leader truncated, replaces its state with a snapshot of the leader's and
+/// continues from the last entry of the leader's log.
+///
+/// Replication is asynchronous: the leader applies its writes, then
+/// acknowledges them once a majority of the cluster applied them too.
+/// Entries applied by every follower are truncated from the log.
+pub struct Cluster {
+    config: ClusterConfig,
+    indexify_state: Arc<IndexifyState>,
+    peer_addrs: HashMap<u64, String>,
+    leader_tx: watch::Sender<Option<u64>>,
+    // Term this node was elected leader of
+    leader_term: AtomicU64,
+    last_leader_seen: Mutex<Instant>,
+    client: reqwest::Client,
+}
+
+impl Cluster {
+    pub fn new(config: ClusterConfig, indexify_state: Arc<IndexifyState>) -> Result<Self> {
+        // Peers authenticate every request with their peer token
+        let mut authorization = HeaderValue::from_str(&format!("Bearer {}", config.peer_token))?;
+        authorization.set_sensitive(true);
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(config.lease_timeout_ms))
+            .default_headers(HeaderMap::from_iter([(header::AUTHORIZATION, authorization)]))
+            .build()?;
+        let peer_addrs = config
+            .peers
+            .iter()
+            .map(|peer| (peer.node_id, peer.addr.clone()))
+            .collect();
+        let (leader_tx, _) = watch::channel(None);
+        indexify_state.enable_replication_log();
+        indexify_state.set_read_only(true);
+        indexify_state.set_replication_quorum(
+            config.peers.iter().map(|peer| peer.node_id).collect(),
+            Duration::from_millis(config.lease_timeout_ms),
+        );
+        Ok(Self {
+            config,
+            indexify_state,
+            peer_addrs,
+            leader_tx,
+            leader_term: AtomicU64::new(0),
+            last_leader_seen: Mutex::new(Instant::now()),
+            client,
+        })
+    }
+
+    pub fn is_leader(&self) -> bool {
+        *self.leader_tx.borrow() == Some(self.config.node_id)
+    }
+
+    pub fn leader_addr(&self) -> Option<String> {
+        let leader_id = (*self.leader_tx.borrow())?;
+        self.peer_addrs.get(&leader_id).cloned()
+    }
+
+    pub fn status(&self) -> ClusterStatus {
+        ClusterStatus {
+            node_id: self.config.node_id,
+            leader_id: *self.leader_tx.borrow(),
+            last_applied_seq: self.indexify_state.last_replicated_seq(),
+            last_applied_term: self.indexify_state.last_replicated_term(),
+            term: self.indexify_state.replication_term(),
+            log_start_seq: self.indexify_state.replication_log_start(),
+        }
+    }
+
+    /// Id of the peer whose token the request is authenticated with, if
+    /// any.
+    pub fn authenticate_peer(&self, headers: &HeaderMap) -> Option<u64> {
+        let token = bearer_token(headers)?;
+        let token_sha256 = hex::encode(Sha256::digest(token.as_bytes()));
+        self.config
+            .peers
+            .iter()
+            .find(|peer| peer.token_sha256.eq_ignore_ascii_case(&token_sha256))
+            .map(|peer| peer.node_id)
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<Option<u64>> {
+        self.leader_tx.subscribe()
+    }
+
+    pub async fn start(&self, shutdown_rx: watch::Receiver<()>) {
+        tokio::join!(
+            self.run_membership(shutdown_rx.clone()),
+            self.run_replication(shutdown_rx)
+        );
+    }
+
+    async fn run_membership(&self, mut shutdown_rx: watch::Receiver<()>) {
+        let mut interval =
+            tokio::time::interval(Duration::from_millis(self.config.heartbeat_interval_ms));
+        let lease_timeout = Duration::from_millis(self.config.lease_timeout_ms);
+        let majority = self.majority();
+        // Time at which a majority of the cluster last followed this node
+        let mut last_quorum = Instant::now();
+        let mut last_election: Option<Instant> = None;
+        let mut election_timeout = self.election_timeout();
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = shutdown_rx.changed() => {
+                    info!("cluster membership shutting down");
+                    return;
+                }
+            }
+            let peers = self.peer_statuses().await;
+            let term = self.indexify_state.replication_term();
+            if self.is_leader() {
+                let leader_term = self.leader_term.load(Ordering::Relaxed);
+                let followers = peers
+                    .iter()
+                    .filter(|peer| {
+                        peer.leader_id == Some(self.config.node_id) && peer.term == leader_term
+                    })
+                    .count();
+                if followers + 1 >= majority {
+                    last_quorum = Instant::now();
+                }
+                let newer_term =
+                    term > leader_term || peers.iter().any(|peer| peer.term > leader_term);
+                if newer_term || last_quorum.elapsed() > lease_timeout {
+                    info!(
+                        "node {} stepping down as leader of term {}, followers: {}",
+                        self.config.node_id, leader_term, followers
+                    );
+                    self.set_leader(None);
+                }
+                continue;
+            }
+
+            // Leaders of older terms than the ones seen are stale
+            let leader = peers
+                .iter()
+                .filter(|peer| peer.is_leader() && peer.term >= term)
+                .max_by_key(|peer| peer.term);
+            if let Some(leader) = leader {
+                *self.last_leader_seen.lock().unwrap() = Instant::now();
+                self.indexify_state.observe_replication_term(leader.term);
+                self.set_leader(Some(leader.node_id));
+                // Every follower applied the entries the leader truncated
+                if let Err(err) = self
+                    .indexify_state
+                    .truncate_replication_log(leader.log_start_seq)
+                {
+                    error!("failed to truncate the replication log: {:?}", err);
+                }
+                continue;
+            }
+
+            if self.leader_tx.borrow().is_some() {
+                self.set_leader(None);
+            }
+            let timed_out = |at: Instant| at.elapsed() > election_timeout;
+            // The partial state of an interrupted snapshot can't lead
+            if self.indexify_state.is_installing_replication_snapshot() ||
+                peers.len() + 1 < majority ||
+                !timed_out(*self.last_leader_seen.lock().unwrap()) ||
+                !last_election.map_or(true, timed_out)
+            {
+                continue;
+            }
+            last_election = Some(Instant::now());
+            election_timeout = self.election_timeout();
+            match self.run_election().await {
+                Ok(Some(term)) => {
+                    info!(
+                        "node {} elected leader of term {} at seq {}",
+                        self.config.node_id,
+                        term,
+                        self.indexify_state.last_replicated_seq()
+                    );
+                    self.leader_term.store(term, Ordering::Relaxed);
+                    last_quorum = Instant::now();
+                    self.set_leader(Some(self.config.node_id));
+                }
+                Ok(None) => {}
+                Err(err) => error!("failed to run an election: {:?}", err),
+            }
+        }
+    }
+
+    /// Asks the peers to vote for this node in a new term, once a pre-vote
+    /// tells it can win the term. Returns the term once a majority of the
+    /// cluster voted for this node.
+    async fn run_election(&self) -> Result<Option<u64>> {
+        let next_term = self.indexify_state.replication_term() + 1;
+        if self.request_votes(next_term, true).await + 1 < self.majority() {
+            return Ok(None);
+        }
+        let term = self
+            .indexify_state
+            .start_election(self.config.node_id)
+            .await?;
+        let votes = self.request_votes(term, false).await;
+        info!(
+            "node {} received {} votes out of {} in term {}",
+            self.config.node_id,
+            votes + 1,
+            self.config.peers.len() + 1,
+            term
+        );
+        if votes + 1 < self.majority() {
+            return Ok(None);
+        }
+        // Fails when a newer term was seen since the election started
+        self.indexify_state.start_replication_term(term).await?;
+        Ok(Some(term))
+    }
+
+    /// Number of peers granting their vote in `term`.
+    async fn request_votes(&self, term: u64, pre_vote: bool) -> usize {
+        let request = VoteRequest {
+            term,
+            last_log_seq: self.indexify_state.last_replicated_seq(),
+            last_log_term: self.indexify_state.last_replicated_term(),
+            pre_vote,
+        };
+        let requests = self.peer_addrs.values().map(|addr| {
+            self.client
+                .post(format!("http://{}/internal/cluster/vote", addr))
+                .timeout(Duration::from_millis(self.config.heartbeat_interval_ms))
+                .json(&request)
+                .send()
+        });
+        let mut granted = 0;
+        for response in join_all(requests).await {
+            let vote = match response.and_then(|r| r.error_for_status()) {
+                Ok(response) => response.json::<VoteResponse>().await,
+                Err(err) => Err(err),
+            };
+            match vote {
+                Ok(vote) if vote.granted => granted += 1,
+                // The candidate can't win against a peer of a newer term
+                Ok(vote) if !pre_vote => self.indexify_state.observe_replication_term(vote.term),
+                Ok(_) => {}
+                Err(err) => debug!("failed to request a vote: {:?}", err),
+            }
+        }
+        granted
+    }
+
+    /// Answers the vote request of a peer. Nodes which heard from a leader
+    /// within `lease_timeout_ms` don't vote, so that a node which only lost
+    /// its connection to the leader can't depose it.
+    pub async fn handle_vote(&self, candidate: u64, request: VoteRequest) -> Result<VoteResponse> {
+        let lease_timeout = Duration::from_millis(self.config.lease_timeout_ms);
+        if self.is_leader() || self.last_leader_seen.lock().unwrap().elapsed() <= lease_timeout {
+            return Ok(VoteResponse {
+                term: self.indexify_state.replication_term(),
+                granted: false,
+            });
+        }
+        let (term, granted) = self
+            .indexify_state
+            .request_vote(
+                request.term,
+                candidate,
+                (request.last_log_term, request.last_log_seq),
+                request.pre_vote,
+            )
+            .await?;
+        if granted && !request.pre_vote {
+            info!(
+                "node {} voted for node {} in term {}",
+                self.config.node_id, candidate, term
+            );
+        }
+        Ok(VoteResponse { term, granted })
+    }
+
+    fn majority(&self) -> usize {
+        (self.config.peers.len() + 1) / 2 + 1
+    }
+
+    // Randomized so that candidates rarely split the votes of a term
+    fn election_timeout(&self) -> Duration {
+        let jitter = rand::thread_rng().gen_range(0..=self.config.lease_timeout_ms);
+        Duration::from_millis(self.config.lease_timeout_ms + jitter)
+    }
+
+    /// Tails the log of the leader while this node is a follower.
+    async fn run_replication(&self, mut shutdown_rx: watch::Receiver<()>) {
+        let mut leader_rx = self.subscribe();
+        loop {
+            let leader_id = *leader_rx.borrow_and_update();
+            let Some(leader_id) = leader_id.filter(|id| *id != self.config.node_id) else {
+                tokio::select! {
+                    _ = leader_rx.changed() => {}
+                    _ = shutdown_rx.changed() => return,
+                }
+                continue;
+            };
+            tokio::select! {
+                result = self.replicate_from(leader_id) => {
+                    if let Err(err) = result {
+                        error!("failed to replicate from leader {}: {:?}", leader_id, err);
+                        tokio::time::sleep(Duration::from_millis(self.config.heartbeat_interval_ms))
+                            .await;
+                    }
+                }
+                _ = leader_rx.changed() => {}
+                _ = shutdown_rx.changed() => return,
+            }
+        }
+    }
+
+    fn set_leader(&self, leader_id: Option<u64>) {
+        self.indexify_state
+            .set_read_only(leader_id != Some(self.config.node_id));
+        self.leader_tx.send_if_modified(|current| {
+            if *current == leader_id {
+                return false;
+            }
+            *current = leader_id;
+            true
+        });
+    }
+
+    async fn peer_statuses(&self) -> Vec<ClusterStatus> {
+        let requests = self.peer_addrs.values().map(|addr| {
+            self.client
+                .get(format!("http://{}/internal/cluster/status", addr))
+                .timeout(Duration::from_millis(self.config.heartbeat_interval_ms))
+                .send()
+        });
+        let mut statuses = Vec::new();
+        for response in join_all(requests).await {
+            let status = match response.and_then(|r| r.error_for_status()) {
+                Ok(response) => response.json::<ClusterStatus>().await,
+                Err(err) => Err(err),
+            };
+            if let Ok(status) = status {
+                statuses.push(status);
+            }
+        }
+        statuses
+    }
+
+    /// Fetches the entries of the leader after the last applied one, waiting
+    /// for up to a heartbeat interval when there are none. Fetching reports
+    /// the entries applied so far to the leader.
+    async fn replicate_from(&self, leader_id: u64) -> Result<()> {
+        let addr = self
+            .peer_addrs
+            .get(&leader_id)
+            .ok_or(anyhow!("unknown leader {}", leader_id))?;
+        if self.indexify_state.is_installing_replication_snapshot() {
+            return self.install_snapshot_from(leader_id, addr).await;
+        }
+        let response = self
+            .client
+            .get(format!("http://{}/internal/cluster/log", addr))
+            .query(&[
+                ("after", self.indexify_state.last_replicated_seq()),
+                ("after_term", self.indexify_state.last_replicated_term()),
+                ("limit", REPLICATION_BATCH_SIZE as u64),
+                ("term", self.indexify_state.replication_term()),
+                ("wait_ms", self.config.heartbeat_interval_ms),
+            ])
+            .send()
+            .await?;
+        if response.status() == StatusCode::GONE {
+            info!(
+                "node {} can't catch up with the log of leader {} at seq {}",
+                self.config.node_id,
+                leader_id,
+                self.indexify_state.last_replicated_seq()
+            );
+            return self.install_snapshot_from(leader_id, addr).await;
+        }
+        let data = response.error_for_status()?.bytes().await?;
+        let (term, entries) = decode_entries(&data)?;
+        self.indexify_state
+            .apply_replication_log(term, entries)
+            .await
+    }
+
+    /// Replaces the state of this node with a snapshot of the leader's.
+    async fn install_snapshot_from(&self, leader_id: u64, addr: &str) -> Result<()> {
+        let response = self
+            .client
+            .get(format!("http://{}/internal/cluster/snapshot", addr))
+            .timeout(SNAPSHOT_TIMEOUT)
+            .send()
+            .await?
+            .error_for_status()?;
+        let term = response
+            .headers()
+            .get(TERM_HEADER)
+            .and_then(|term| term.to_str().ok()?.parse().ok())
+            .ok_or(anyhow!("snapshot of leader {} has no term", leader_id))?;
+        let data = futures::stream::try_unfold(response, |mut response| async move {
+            Ok::<_, anyhow::Error>(response.chunk().await?.map(|chunk| (chunk, response)))
+        });
+        self.indexify_state
+            .install_replication_snapshot(term, Box::pin(data))
+            .await?;
+        info!(
+            "node {} installed a snapshot of leader {} at seq {}",
+            self.config.node_id,
+            leader_id,
+            self.indexify_state.last_replicated_seq()
+        );
+        Ok(())
+    }
+}
@@ -1,18 +1,593 @@
-use std::{env, fmt::Debug, net::SocketAddr};
+use std::{collections::HashSet, env, fmt::Debug, net::SocketAddr};
 
 use anyhow::Result;
-use blob_store::BlobStorageConfig;
+use blob_store::{BlobStorageConfig, DiskStorageConfig, S3Config};
+use data_model::{alerts::AlertRule, RateLimit, EXECUTOR_PROTOCOL_VERSION};
 use figment::{
-    providers::{Format, Yaml},
+    providers::{Format, Toml, Yaml},
     Figment,
 };
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use task_scheduler::{
+    aging::PriorityAgingConfig,
+    allocator::AllocationConfig,
+    blacklist::BlacklistConfig,
+    lanes::LaneConfig,
+    preemption::PreemptionConfig,
+};
+use tracing_subscriber::EnvFilter;
+
+use crate::auth::AuthConfig;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServerConfig {
     pub state_store_path: String,
     pub listen_addr: String,
+    /// Address of the gRPC api, disabled when unset
+    #[serde(default)]
+    pub grpc_listen_addr: Option<String>,
     pub blob_storage: BlobStorageConfig,
+    /// Hex encoded 32 byte key used to seal compute graph secrets
+    pub secrets_key: Option<String>,
+    /// Snapshot url to restore the state store from when it doesn't exist yet
+    #[serde(default)]
+    pub restore_snapshot: Option<String>,
+    /// Directory the state store is backed up to before destructive
+    /// migrations, `<state_store_path>.backups` when unset
+    #[serde(default)]
+    pub migration_backup_dir: Option<String>,
+    /// Replicas of the server, only the leader runs the scheduler
+    #[serde(default)]
+    pub cluster: Option<ClusterConfig>,
+    /// Settings applied again when the configuration is reloaded
+    #[serde(default)]
+    pub runtime: RuntimeConfig,
+    /// Limits of the routers run by the server's WASM runtime
+    #[serde(default)]
+    pub wasm: WasmConfig,
+    /// Signed payload download urls
+    #[serde(default)]
+    pub downloads: DownloadConfig,
+    /// Tokens sent with tasks for their functions to call the server
+    #[serde(default)]
+    pub task_tokens: TaskTokenConfig,
+    /// Delivery of the events of webhooks
+    #[serde(default)]
+    pub webhooks: WebhookConfig,
+    /// Writes of sink nodes to their index
+    #[serde(default)]
+    pub sinks: SinkConfig,
+    /// Invocation inputs fetched by the server from a url
+    #[serde(default)]
+    pub url_fetch: UrlFetchConfig,
+    /// Export of finished invocations to Parquet files, disabled when unset
+    #[serde(default)]
+    pub archive: Option<ArchiveConfig>,
+    /// Format of the logs written to stdout
+    #[serde(default)]
+    pub log_format: LogFormat,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    /// One JSON object per line, with the fields of the request, scheduler
+    /// and store spans the event was logged in
+    Json,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WebhookConfig {
+    /// Attempts made to deliver an event before it's moved to the dead
+    /// letters of its namespace
+    #[serde(default = "default_webhook_max_attempts")]
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled by every later retry
+    #[serde(default = "default_webhook_initial_backoff_secs")]
+    pub initial_backoff_secs: u64,
+    #[serde(default = "default_webhook_max_backoff_secs")]
+    pub max_backoff_secs: u64,
+    /// Endpoints not responding within the timeout fail the attempt
+    #[serde(default = "default_webhook_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        WebhookConfig {
+            max_attempts: default_webhook_max_attempts(),
+            initial_backoff_secs: default_webhook_initial_backoff_secs(),
+            max_backoff_secs: default_webhook_max_backoff_secs(),
+            timeout_secs: default_webhook_timeout_secs(),
+        }
+    }
+}
+
+fn default_webhook_max_attempts() -> u32 {
+    8
+}
+
+fn default_webhook_initial_backoff_secs() -> u64 {
+    5
+}
+
+fn default_webhook_max_backoff_secs() -> u64 {
+    3600
+}
+
+fn default_webhook_timeout_secs() -> u64 {
+    10
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveConfig {
+    /// Storage the Parquet files are written to, the blob storage when
+    /// neither is set
+    #[serde(default)]
+    pub s3: Option<S3Config>,
+    #[serde(default)]
+    pub disk: Option<DiskStorageConfig>,
+    /// Key prefix of the files in the storage
+    #[serde(default = "default_archive_prefix")]
+    pub prefix: String,
+    /// How often the invocations finished since the last export are exported
+    #[serde(default = "default_archive_interval_secs")]
+    pub interval_secs: u64,
+}
+
+fn default_archive_prefix() -> String {
+    "archive".to_string()
+}
+
+fn default_archive_interval_secs() -> u64 {
+    3600
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SinkConfig {
+    /// Attempts made to write the input of a sink task before the task fails
+    #[serde(default = "default_sink_max_attempts")]
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled by every later retry
+    #[serde(default = "default_sink_initial_backoff_secs")]
+    pub initial_backoff_secs: u64,
+    #[serde(default = "default_sink_max_backoff_secs")]
+    pub max_backoff_secs: u64,
+    /// Indexes not responding within the timeout fail the attempt
+    #[serde(default = "default_sink_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+impl Default for SinkConfig {
+    fn default() -> Self {
+        SinkConfig {
+            max_attempts: default_sink_max_attempts(),
+            initial_backoff_secs: default_sink_initial_backoff_secs(),
+            max_backoff_secs: default_sink_max_backoff_secs(),
+            timeout_secs: default_sink_timeout_secs(),
+        }
+    }
+}
+
+fn default_sink_max_attempts() -> u32 {
+    5
+}
+
+fn default_sink_initial_backoff_secs() -> u64 {
+    1
+}
+
+fn default_sink_max_backoff_secs() -> u64 {
+    60
+}
+
+fn default_sink_timeout_secs() -> u64 {
+    30
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DownloadConfig {
+    /// Hex encoded key signing the download urls served by the server, a
+    /// random key is used when unset. Replicas must share the key.
+    #[serde(default)]
+    pub signing_key: Option<String>,
+    #[serde(default = "default_download_expiry_secs")]
+    pub default_expiry_secs: u64,
+    #[serde(default = "default_download_max_expiry_secs")]
+    pub max_expiry_secs: u64,
+}
+
+impl Default for DownloadConfig {
+    fn default() -> Self {
+        DownloadConfig {
+            signing_key: None,
+            default_expiry_secs: default_download_expiry_secs(),
+            max_expiry_secs: default_download_max_expiry_secs(),
+        }
+    }
+}
+
+fn default_download_expiry_secs() -> u64 {
+    300
+}
+
+fn default_download_max_expiry_secs() -> u64 {
+    3600
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TaskTokenConfig {
+    /// Hex encoded key signing the tokens of tasks, a random key is used when
+    /// unset. Replicas must share the key.
+    #[serde(default)]
+    pub signing_key: Option<String>,
+    /// Lifetime of a token from when its task is sent to an executor, the
+    /// token is also revoked once its task finishes
+    #[serde(default = "default_task_token_ttl_secs")]
+    pub ttl_secs: u64,
+}
+
+impl Default for TaskTokenConfig {
+    fn default() -> Self {
+        TaskTokenConfig {
+            signing_key: None,
+            ttl_secs: default_task_token_ttl_secs(),
+        }
+    }
+}
+
+fn default_task_token_ttl_secs() -> u64 {
+    3600
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UrlFetchConfig {
+    /// Urls the server fetches inputs from must start with one of the
+    /// prefixes, e.g. `s3://ingest-bucket/`. Any http(s) or s3 url is fetched
+    /// when empty.
+    #[serde(default)]
+    pub allowed_url_prefixes: Vec<String>,
+    /// Inputs larger than this are rejected, along with the ones exceeding
+    /// the payload limit of their graph
+    #[serde(default = "default_url_fetch_max_bytes")]
+    pub max_bytes: u64,
+    /// Attempts made to fetch an input before the fetch fails
+    #[serde(default = "default_url_fetch_max_attempts")]
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled by every later retry
+    #[serde(default = "default_url_fetch_initial_backoff_secs")]
+    pub initial_backoff_secs: u64,
+    /// Attempts not done within the timeout fail
+    #[serde(default = "default_url_fetch_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+impl Default for UrlFetchConfig {
+    fn default() -> Self {
+        UrlFetchConfig {
+            allowed_url_prefixes: vec![],
+            max_bytes: default_url_fetch_max_bytes(),
+            max_attempts: default_url_fetch_max_attempts(),
+            initial_backoff_secs: default_url_fetch_initial_backoff_secs(),
+            timeout_secs: default_url_fetch_timeout_secs(),
+        }
+    }
+}
+
+fn default_url_fetch_max_bytes() -> u64 {
+    10 * 1024 * 1024 * 1024
+}
+
+fn default_url_fetch_max_attempts() -> u32 {
+    3
+}
+
+fn default_url_fetch_initial_backoff_secs() -> u64 {
+    2
+}
+
+fn default_url_fetch_timeout_secs() -> u64 {
+    1800
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WasmConfig {
+    /// Instructions a router can execute for one task, roughly
+    #[serde(default = "default_wasm_fuel")]
+    pub fuel: u64,
+    /// Maximum size of the linear memory of a router
+    #[serde(default = "default_wasm_max_memory_bytes")]
+    pub max_memory_bytes: usize,
+}
+
+impl Default for WasmConfig {
+    fn default() -> Self {
+        WasmConfig {
+            fuel: default_wasm_fuel(),
+            max_memory_bytes: default_wasm_max_memory_bytes(),
+        }
+    }
+}
+
+fn default_wasm_fuel() -> u64 {
+    100_000_000
+}
+
+fn default_wasm_max_memory_bytes() -> usize {
+    64 * 1024 * 1024
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RuntimeConfig {
+    /// Log filter directives, e.g. `info,state_store=debug`
+    #[serde(default = "default_log_level")]
+    pub log_level: String,
+    /// Rate limit of compute functions which don't declare one
+    #[serde(default)]
+    pub default_fn_rate_limit: Option<RateLimit>,
+    /// Deleted compute graphs can be restored for this long before their
+    /// invocations are permanently removed
+    #[serde(default = "default_deleted_graph_retention_secs")]
+    pub deleted_graph_retention_secs: u64,
+    /// Blobs registered for upload but not committed by a task outcome
+    /// within this long are deleted
+    #[serde(default = "default_pending_upload_timeout_secs")]
+    pub pending_upload_timeout_secs: u64,
+    /// The blobs of the data deleted for a deletion request are removed from
+    /// the storage within this long, later removals are reported as overdue
+    #[serde(default = "default_data_deletion_deadline_secs")]
+    pub data_deletion_deadline_secs: u64,
+    /// Maximum number of downstream tasks created at once for the outputs of
+    /// a finished task, larger fan outs are created over several batches
+    #[serde(default = "default_fan_out_batch_size")]
+    pub fan_out_batch_size: usize,
+    /// Strategies picking the executor of a task, by namespace and graph
+    #[serde(default)]
+    pub allocation: AllocationConfig,
+    /// Lets high priority tasks pre-empt running lower priority tasks,
+    /// disabled when unset
+    #[serde(default)]
+    pub preemption: Option<PreemptionConfig>,
+    /// Blacklists executors for a function after they failed it repeatedly
+    /// while other executors ran it, disabled when unset
+    #[serde(default)]
+    pub executor_blacklist: Option<BlacklistConfig>,
+    /// Raises the priority of queued tasks with their waiting time, disabled
+    /// when unset
+    #[serde(default)]
+    pub priority_aging: Option<PriorityAgingConfig>,
+    /// Slots of the executors kept for latency lane invocations, which batch
+    /// lane invocations like backfills never fill. None are kept when unset.
+    #[serde(default)]
+    pub scheduling_lanes: Option<LaneConfig>,
+    /// Executors speaking an older protocol version are rejected when they
+    /// register
+    #[serde(default)]
+    pub min_executor_protocol_version: u32,
+    /// Policies moving the payloads of old invocations to cold storage, or
+    /// deleting them
+    #[serde(default)]
+    pub storage_lifecycle_policies: Vec<LifecyclePolicy>,
+    /// Rejects new invocations and graph mutations and pauses the allocation
+    /// of tasks, reads keep being served. Used during backups and migrations.
+    #[serde(default)]
+    pub maintenance_mode: bool,
+    /// Largest task output executors can send inline with the outcome of a
+    /// task, stored in the state store instead of the blob store
+    #[serde(default = "default_inline_output_max_bytes")]
+    pub inline_output_max_bytes: usize,
+    /// Invocation payloads and function outputs reaching a size are stored
+    /// compressed, every payload is stored as it is when unset
+    #[serde(default)]
+    pub payload_compression: Option<PayloadCompressionConfig>,
+    /// Timeouts and inline outputs of the invoke-and-wait endpoint
+    #[serde(default)]
+    pub sync_invoke: SyncInvokeConfig,
+    /// Most task results executors can report with a single request
+    #[serde(default = "default_max_batched_task_results")]
+    pub max_batched_task_results: usize,
+    /// Identities and their role bindings, every request is allowed when
+    /// unset
+    #[serde(default)]
+    pub auth: Option<AuthConfig>,
+    /// New executors join once approved by an admin, executors join when
+    /// they register when unset
+    #[serde(default)]
+    pub executor_approval: Option<ExecutorApprovalConfig>,
+    /// Rules over the built-in metrics evaluated by the leader, their alerts
+    /// notify the webhooks when they fire and resolve
+    #[serde(default)]
+    pub alert_rules: Vec<AlertRule>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct ExecutorApprovalConfig {
+    /// Hex encoded SHA-256 hashes of the bootstrap tokens, executors
+    /// presenting one of them are approved when they register. The tokens
+    /// themselves aren't stored.
+    #[serde(default)]
+    pub bootstrap_token_sha256s: Vec<String>,
+}
+
+impl ExecutorApprovalConfig {
+    pub fn accepts(&self, bootstrap_token: &str) -> bool {
+        let token_sha256 = hex::encode(Sha256::digest(bootstrap_token.as_bytes()));
+        self.bootstrap_token_sha256s
+            .iter()
+            .any(|sha256| sha256.eq_ignore_ascii_case(&token_sha256))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PayloadCompressionConfig {
+    /// Smaller payloads are stored as they are, compressing them saves
+    /// little
+    #[serde(default = "default_compression_min_size_bytes")]
+    pub min_size_bytes: u64,
+    /// Zstd level, from 1 to 22. Higher levels trade CPU for smaller
+    /// payloads.
+    #[serde(default = "default_compression_level")]
+    pub level: i32,
+}
+
+impl Default for PayloadCompressionConfig {
+    fn default() -> Self {
+        PayloadCompressionConfig {
+            min_size_bytes: default_compression_min_size_bytes(),
+            level: default_compression_level(),
+        }
+    }
+}
+
+fn default_compression_min_size_bytes() -> u64 {
+    64 * 1024
+}
+
+fn default_compression_level() -> i32 {
+    3
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SyncInvokeConfig {
+    /// Timeout of requests which don't set one
+    #[serde(default = "default_sync_invoke_default_timeout_secs")]
+    pub default_timeout_secs: u64,
+    /// Longer timeouts requested by clients are lowered to it
+    #[serde(default = "default_sync_invoke_max_timeout_secs")]
+    pub max_timeout_secs: u64,
+    /// Total size of the terminal outputs returned inline, the outputs past
+    /// it are downloaded separately
+    #[serde(default = "default_sync_invoke_inline_outputs_max_bytes")]
+    pub inline_outputs_max_bytes: u64,
+}
+
+impl Default for SyncInvokeConfig {
+    fn default() -> Self {
+        SyncInvokeConfig {
+            default_timeout_secs: default_sync_invoke_default_timeout_secs(),
+            max_timeout_secs: default_sync_invoke_max_timeout_secs(),
+            inline_outputs_max_bytes: default_sync_invoke_inline_outputs_max_bytes(),
+        }
+    }
+}
+
+fn default_sync_invoke_default_timeout_secs() -> u64 {
+    30
+}
+
+fn default_sync_invoke_max_timeout_secs() -> u64 {
+    300
+}
+
+fn default_sync_invoke_inline_outputs_max_bytes() -> u64 {
+    1024 * 1024
+}
+
+/// Moves the input and outputs of the completed invocations of a namespace,
+/// or of one of its compute graphs, to cold storage once they are old enough,
+/// and deletes them past their retention. The records of the invocations and
+/// their tasks are kept.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LifecyclePolicy {
+    pub namespace: String,
+    /// Applies to every compute graph of the namespace when unset
+    #[serde(default)]
+    pub compute_graph: Option<String>,
+    #[serde(default)]
+    pub archive_after_days: Option<u64>,
+    /// Payloads are deleted and marked as expired past the retention
+    #[serde(default)]
+    pub expire_payloads_after_days: Option<u64>,
+}
+
+impl Default for RuntimeConfig {
+    fn default() -> Self {
+        RuntimeConfig {
+            log_level: default_log_level(),
+            default_fn_rate_limit: None,
+            deleted_graph_retention_secs: default_deleted_graph_retention_secs(),
+            pending_upload_timeout_secs: default_pending_upload_timeout_secs(),
+            data_deletion_deadline_secs: default_data_deletion_deadline_secs(),
+            fan_out_batch_size: default_fan_out_batch_size(),
+            allocation: AllocationConfig::default(),
+            preemption: None,
+            executor_blacklist: None,
+            priority_aging: None,
+            scheduling_lanes: None,
+            min_executor_protocol_version: 0,
+            storage_lifecycle_policies: vec![],
+            maintenance_mode: false,
+            inline_output_max_bytes: default_inline_output_max_bytes(),
+            payload_compression: None,
+            sync_invoke: SyncInvokeConfig::default(),
+            max_batched_task_results: default_max_batched_task_results(),
+            auth: None,
+            executor_approval: None,
+            alert_rules: vec![],
+        }
+    }
+}
+
+fn default_log_level() -> String {
+    "info".to_string()
+}
+
+fn default_deleted_graph_retention_secs() -> u64 {
+    24 * 60 * 60
+}
+
+fn default_pending_upload_timeout_secs() -> u64 {
+    60 * 60
+}
+
+fn default_data_deletion_deadline_secs() -> u64 {
+    60 * 60
+}
+
+pub fn default_fan_out_batch_size() -> usize {
+    1000
+}
+
+fn default_inline_output_max_bytes() -> usize {
+    64 * 1024
+}
+
+fn default_max_batched_task_results() -> usize {
+    100
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerConfig {
+    pub node_id: u64,
+    /// Address of the peer's http api, e.g. `indexify-1:8900`
+    pub addr: String,
+    /// Hex encoded SHA-256 hash of the `peer_token` of the peer
+    pub token_sha256: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClusterConfig {
+    pub node_id: u64,
+    /// Token this replica authenticates to its peers with
+    pub peer_token: String,
+    pub peers: Vec<PeerConfig>,
+    #[serde(default = "default_heartbeat_interval_ms")]
+    pub heartbeat_interval_ms: u64,
+    /// A leader which can't reach a majority of the cluster for this long
+    /// steps down, and followers elect a new leader
+    #[serde(default = "default_lease_timeout_ms")]
+    pub lease_timeout_ms: u64,
+}
+
+fn default_heartbeat_interval_ms() -> u64 {
+    500
+}
+
+fn default_lease_timeout_ms() -> u64 {
+    5000
 }
 
 impl Default for ServerConfig {
@@ -21,7 +596,21 @@ impl Default for ServerConfig {
         ServerConfig {
             state_store_path: state_store_path.to_str().unwrap().to_string(),
             listen_addr: "0.0.0.0:8900".to_string(),
+            grpc_listen_addr: None,
             blob_storage: Default::default(),
+            secrets_key: None,
+            restore_snapshot: None,
+            migration_backup_dir: None,
+            cluster: None,
+            runtime: Default::default(),
+            wasm: Default::default(),
+            downloads: Default::default(),
+            task_tokens: Default::default(),
+            webhooks: Default::default(),
+            sinks: Default::default(),
+            url_fetch: Default::default(),
+            archive: None,
+            log_format: Default::default(),
         }
     }
 }
@@ -29,7 +618,11 @@ impl Default for ServerConfig {
 impl ServerConfig {
     pub fn from_path(path: &str) -> Result<ServerConfig> {
         let config_str = std::fs::read_to_string(path)?;
-        let config: ServerConfig = Figment::new().merge(Yaml::string(&config_str)).extract()?;
+        let config: ServerConfig = if path.ends_with(".toml") {
+            Figment::new().merge(Toml::string(&config_str)).extract()?
+        } else {
+            Figment::new().merge(Yaml::string(&config_str)).extract()?
+        };
         config.validate()?;
         Ok(config)
     }
@@ -45,12 +638,330 @@ impl ServerConfig {
                 "must specify one of s3 or disk blob storage"
             ));
         }
+        if let Some(cold) = &self.blob_storage.cold {
+            if cold.s3.is_some() == cold.disk.is_some() {
+                return Err(anyhow::anyhow!(
+                    "must specify one of s3 or disk cold blob storage"
+                ));
+            }
+        }
+        if let Some(secrets_key) = &self.secrets_key {
+            match hex::decode(secrets_key) {
+                Ok(key) if key.len() == 32 => {}
+                _ => {
+                    return Err(anyhow::anyhow!(
+                        "secrets_key must be a hex encoded 32 byte key"
+                    ))
+                }
+            }
+        }
+        if let Some(cluster) = &self.cluster {
+            let mut node_ids = HashSet::from([cluster.node_id]);
+            for peer in &cluster.peers {
+                if !node_ids.insert(peer.node_id) {
+                    return Err(anyhow::anyhow!(
+                        "duplicate node id in cluster config: {}",
+                        peer.node_id
+                    ));
+                }
+            }
+            if cluster.heartbeat_interval_ms >= cluster.lease_timeout_ms {
+                return Err(anyhow::anyhow!(
+                    "cluster heartbeat_interval_ms must be lower than lease_timeout_ms"
+                ));
+            }
+        }
+        if self.wasm.fuel == 0 {
+            return Err(anyhow::anyhow!("wasm fuel must be positive"));
+        }
+        if let Some(signing_key) = &self.downloads.signing_key {
+            match hex::decode(signing_key) {
+                Ok(key) if key.len() >= 32 => {}
+                _ => {
+                    return Err(anyhow::anyhow!(
+                        "downloads signing_key must be a hex encoded key of at least 32 bytes"
+                    ))
+                }
+            }
+        }
+        if self.downloads.default_expiry_secs == 0 ||
+            self.downloads.default_expiry_secs > self.downloads.max_expiry_secs
+        {
+            return Err(anyhow::anyhow!(
+                "downloads default_expiry_secs must be positive and at most max_expiry_secs"
+            ));
+        }
+        if let Some(signing_key) = &self.task_tokens.signing_key {
+            match hex::decode(signing_key) {
+                Ok(key) if key.len() >= 32 => {}
+                _ => {
+                    return Err(anyhow::anyhow!(
+                        "task_tokens signing_key must be a hex encoded key of at least 32 bytes"
+                    ))
+                }
+            }
+        }
+        if self.task_tokens.ttl_secs == 0 {
+            return Err(anyhow::anyhow!("task_tokens ttl_secs must be positive"));
+        }
+        if self.webhooks.max_attempts == 0 || self.webhooks.timeout_secs == 0 {
+            return Err(anyhow::anyhow!(
+                "webhooks max_attempts and timeout_secs must be positive"
+            ));
+        }
+        if self.sinks.max_attempts == 0 || self.sinks.timeout_secs == 0 {
+            return Err(anyhow::anyhow!(
+                "sinks max_attempts and timeout_secs must be positive"
+            ));
+        }
+        if let Some(archive) = &self.archive {
+            if archive.s3.is_some() && archive.disk.is_some() {
+                return Err(anyhow::anyhow!(
+                    "cannot specify both s3 and disk archive storage"
+                ));
+            }
+            if archive.interval_secs == 0 {
+                return Err(anyhow::anyhow!("archive interval_secs must be positive"));
+            }
+        }
+        if EnvFilter::try_new(&self.runtime.log_level).is_err() {
+            return Err(anyhow::anyhow!(
+                "invalid log level: {}",
+                self.runtime.log_level
+            ));
+        }
+        if let Some(rate_limit) = &self.runtime.default_fn_rate_limit {
+            if rate_limit.max_tasks == 0 || rate_limit.window_secs == 0 {
+                return Err(anyhow::anyhow!(
+                    "default_fn_rate_limit max_tasks and window_secs must be positive"
+                ));
+            }
+        }
+        if let Some(auth) = &self.runtime.auth {
+            for identity in &auth.identities {
+                if !matches!(hex::decode(&identity.token_sha256), Ok(hash) if hash.len() == 32) {
+                    return Err(anyhow::anyhow!(
+                        "token_sha256 of identity {} must be a hex encoded SHA-256 hash",
+                        identity.name
+                    ));
+                }
+            }
+            if let Some(oidc) = &auth.oidc {
+                if oidc.issuer.is_empty() || oidc.audience.is_empty() {
+                    return Err(anyhow::anyhow!("oidc issuer and audience must be set"));
+                }
+                if oidc.jwks_refresh_secs == 0 {
+                    return Err(anyhow::anyhow!("oidc jwks_refresh_secs must be positive"));
+                }
+            }
+            if auth
+                .bindings
+                .iter()
+                .any(|binding| binding.namespace.is_empty())
+            {
+                return Err(anyhow::anyhow!(
+                    "role bindings must name a namespace, or * for all namespaces"
+                ));
+            }
+        }
+        if let Some(priority_aging) = &self.runtime.priority_aging {
+            if priority_aging.interval_secs == 0 || priority_aging.step <= 0 {
+                return Err(anyhow::anyhow!(
+                    "priority_aging interval_secs and step must be positive"
+                ));
+            }
+        }
+        if let Some(lanes) = &self.runtime.scheduling_lanes {
+            if lanes.reserved_latency_slots == 0 {
+                return Err(anyhow::anyhow!(
+                    "scheduling_lanes reserved_latency_slots must be positive"
+                ));
+            }
+        }
+        if let Some(compression) = &self.runtime.payload_compression {
+            if !(1..=22).contains(&compression.level) {
+                return Err(anyhow::anyhow!(
+                    "payload_compression level must be between 1 and 22"
+                ));
+            }
+        }
+        if let Some(approval) = &self.runtime.executor_approval {
+            let invalid = approval
+                .bootstrap_token_sha256s
+                .iter()
+                .any(|sha256| sha256.len() != 64 || hex::decode(sha256).is_err());
+            if invalid {
+                return Err(anyhow::anyhow!(
+                    "executor_approval bootstrap_token_sha256s must be hex encoded SHA-256 hashes"
+                ));
+            }
+        }
+        if self.runtime.max_batched_task_results == 0 {
+            return Err(anyhow::anyhow!("max_batched_task_results must be positive"));
+        }
+        let sync_invoke = &self.runtime.sync_invoke;
+        if sync_invoke.default_timeout_secs == 0 ||
+            sync_invoke.default_timeout_secs > sync_invoke.max_timeout_secs
+        {
+            return Err(anyhow::anyhow!(
+                "sync_invoke default_timeout_secs must be positive and at most max_timeout_secs"
+            ));
+        }
+        for graph in self.runtime.allocation.graphs.keys() {
+            if !graph.contains('/') {
+                return Err(anyhow::anyhow!(
+                    "allocation graphs must be keyed by <namespace>/<compute graph>: {}",
+                    graph
+                ));
+            }
+        }
+        if let Some(preemption) = &self.runtime.preemption {
+            if preemption.budget.max_tasks == 0 || preemption.budget.window_secs == 0 {
+                return Err(anyhow::anyhow!(
+                    "preemption budget max_tasks and window_secs must be positive"
+                ));
+            }
+            if preemption.min_priority_gap <= 0 {
+                return Err(anyhow::anyhow!(
+                    "preemption min_priority_gap must be positive"
+                ));
+            }
+        }
+        if let Some(blacklist) = &self.runtime.executor_blacklist {
+            if blacklist.max_failures == 0 || blacklist.duration_secs == 0 {
+                return Err(anyhow::anyhow!(
+                    "executor_blacklist max_failures and duration_secs must be positive"
+                ));
+            }
+        }
+        let archives = self
+            .runtime
+            .storage_lifecycle_policies
+            .iter()
+            .any(|policy| policy.archive_after_days.is_some());
+        if archives && self.blob_storage.cold.is_none() {
+            return Err(anyhow::anyhow!(
+                "storage lifecycle policies archiving payloads require a cold blob storage"
+            ));
+        }
+        for policy in &self.runtime.storage_lifecycle_policies {
+            match (policy.archive_after_days, policy.expire_payloads_after_days) {
+                (None, None) => {
+                    return Err(anyhow::anyhow!(
+                        "storage lifecycle policy of namespace {} must set archive_after_days or expire_payloads_after_days",
+                        policy.namespace
+                    ))
+                }
+                (Some(0), _) | (_, Some(0)) => {
+                    return Err(anyhow::anyhow!(
+                        "storage lifecycle policy of namespace {} must have positive durations",
+                        policy.namespace
+                    ))
+                }
+                _ => {}
+            }
+        }
+        let mut alert_names = HashSet::new();
+        for rule in &self.runtime.alert_rules {
+            rule.validate()?;
+            if !alert_names.insert(&rule.name) {
+                return Err(anyhow::anyhow!("duplicate alert rule {}", rule.name));
+            }
+        }
+        if self.runtime.min_executor_protocol_version > EXECUTOR_PROTOCOL_VERSION {
+            return Err(anyhow::anyhow!(
+                "min_executor_protocol_version can't exceed the server's protocol version {}",
+                EXECUTOR_PROTOCOL_VERSION
+            ));
+        }
         if self.listen_addr.parse::<SocketAddr>().is_err() {
             return Err(anyhow::anyhow!(
                 "invalid listen address: {}",
                 self.listen_addr
             ));
         }
+        if let Some(grpc_listen_addr) = &self.grpc_listen_addr {
+            if grpc_listen_addr.parse::<SocketAddr>().is_err() {
+                return Err(anyhow::anyhow!(
+                    "invalid grpc listen address: {}",
+                    grpc_listen_addr
+                ));
+            }
+            if *grpc_listen_addr == self.listen_addr {
+                return Err(anyhow::anyhow!(
+                    "grpc listen address must differ from the listen address"
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toml_config() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let path = temp_dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            r#"
+state_store_path = "/tmp/indexify/state"
+listen_addr = "0.0.0.0:8900"
+grpc_listen_addr = "0.0.0.0:8901"
+
+[blob_storage.disk]
+path = "/tmp/indexify/blobs"
+
+[runtime]
+log_level = "debug"
+maintenance_mode = true
+
+[[runtime.storage_lifecycle_policies]]
+namespace = "default"
+archive_after_days = 30
+"#,
+        )?;
+        let config = ServerConfig::from_path(path.to_str().unwrap())?;
+        assert_eq!(config.state_store_path, "/tmp/indexify/state");
+        assert_eq!(config.grpc_listen_addr.as_deref(), Some("0.0.0.0:8901"));
+        assert_eq!(
+            config.blob_storage.disk.map(|disk| disk.path).as_deref(),
+            Some("/tmp/indexify/blobs")
+        );
+        assert_eq!(config.runtime.log_level, "debug");
+        assert!(config.runtime.maintenance_mode);
+        let policies = &config.runtime.storage_lifecycle_policies;
+        assert_eq!(policies.len(), 1);
+        assert_eq!(policies[0].namespace, "default");
+        assert_eq!(policies[0].archive_after_days, Some(30));
+        assert_eq!(policies[0].expire_payloads_after_days, None);
+        // Fields missing from the file have their defaults
+        assert_eq!(
+            config.runtime.fan_out_batch_size,
+            default_fan_out_batch_size()
+        );
+        assert_eq!(config.webhooks, WebhookConfig::default());
+
+        // Files are validated once parsed
+        std::fs::write(
+            &path,
+            r#"
+state_store_path = "/tmp/indexify/state"
+listen_addr = "0.0.0.0:8900"
+
+[blob_storage]
+
+[runtime]
+log_level = "debug"
+"#,
+        )?;
+        let err = ServerConfig::from_path(path.to_str().unwrap()).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("must specify one of s3 or disk blob storage"));
         Ok(())
     }
 }
@@ -0,0 +1,137 @@
+use std::{path::PathBuf, sync::Mutex};
+
+use anyhow::{anyhow, Result};
+use tokio::sync::watch;
+use tracing::{info, warn};
+use tracing_subscriber::{reload, EnvFilter, Registry};
+
+use crate::config::{RuntimeConfig, ServerConfig};
+
+pub type LogFilterHandle = reload::Handle<EnvFilter, Registry>;
+
+/// Reloads the configuration file and applies the settings which can change
+/// while the server is running. Other changes take effect after a restart.
+pub struct ConfigReloader {
+    // Unset when the server was started with the default configuration
+    path: Option<PathBuf>,
+    config: Mutex<ServerConfig>,
+    log_filter: LogFilterHandle,
+    runtime_tx: watch::Sender<RuntimeConfig>,
+}
+
+impl ConfigReloader {
+    pub fn new(path: Option<PathBuf>, config: ServerConfig, log_filter: LogFilterHandle) -> Self {
+        let (runtime_tx, _) = watch::channel(config.runtime.clone());
+        Self {
+            path,
+            config: Mutex::new(config),
+            log_filter,
+            runtime_tx,
+        }
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<RuntimeConfig> {
+        self.runtime_tx.subscribe()
+    }
+
+    pub fn runtime(&self) -> RuntimeConfig {
+        self.runtime_tx.borrow().clone()
+    }
+
+    pub fn reload(&self) -> Result<RuntimeConfig> {
+        let path = self
+            .path
+            .as_ref()
+            .ok_or(anyhow!("server was started without a configuration file"))?;
+        let path = path
+            .to_str()
+            .ok_or(anyhow!("invalid config path {:?}", path))?;
+        let new_config = ServerConfig::from_path(path)?;
+        let mut config = self.config.lock().unwrap();
+        let mut static_config = new_config.clone();
+        static_config.runtime = config.runtime.clone();
+        if serde_json::to_value(&static_config)? != serde_json::to_value(&*config)? {
+            warn!("configuration changes outside of `runtime` require a restart");
+        }
+        if new_config.runtime.log_level != config.runtime.log_level {
+            self.log_filter
+                .reload(EnvFilter::try_new(&new_config.runtime.log_level)?)?;
+        }
+        config.runtime = new_config.runtime.clone();
+        self.runtime_tx.send_replace(new_config.runtime.clone());
+        info!("reloaded configuration from {}", path);
+        Ok(new_config.runtime)
+    }
+
+    // Reloads the configuration on SIGHUP
+    #[cfg(unix)]
+    pub async fn watch_signals(&self, mut shutdown_rx: watch::Receiver<()>) -> Result<()> {
+        let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())?;
+        loop {
+            tokio::select! {
+                _ = hangup.recv() => {
+                    if let Err(err) = self.reload() {
+                        warn!("failed to reload configuration: {:?}", err);
+                    }
+                }
+                _ = shutdown_rx.changed() => return Ok(()),
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub async fn watch_signals(&self, _shutdown_rx: watch::Receiver<()>) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATIC_CONFIG: &str = r#"
+state_store_path = "/tmp/indexify/state"
+listen_addr = "0.0.0.0:8900"
+
+[blob_storage.disk]
+path = "/tmp/indexify/blobs"
+"#;
+
+    #[test]
+    fn test_reload() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let path = temp_dir.path().join("config.toml");
+        let write_runtime = |runtime: &str| {
+            std::fs::write(&path, format!("{}\n[runtime]\n{}", STATIC_CONFIG, runtime))
+        };
+        write_runtime("log_level = \"info\"\n")?;
+        let config = ServerConfig::from_path(path.to_str().unwrap())?;
+        // The log filter is reloaded through the layer, which is kept alive
+        let (_layer, log_filter) = reload::Layer::new(EnvFilter::new("info"));
+        let reloader = ConfigReloader::new(Some(path.clone()), config, log_filter);
+        let mut runtime_rx = reloader.subscribe();
+
+        write_runtime("log_level = \"debug\"\nmaintenance_mode = true\nfan_out_batch_size = 10\n")?;
+        let runtime = reloader.reload()?;
+        assert_eq!(runtime.log_level, "debug");
+        assert!(runtime.maintenance_mode);
+        assert_eq!(runtime.fan_out_batch_size, 10);
+        assert_eq!(reloader.runtime(), runtime);
+        assert!(runtime_rx.has_changed()?);
+        assert_eq!(*runtime_rx.borrow_and_update(), runtime);
+
+        // Invalid files are rejected and the current configuration is kept
+        write_runtime("maintenance_mode = false\nmax_batched_task_results = 0\n")?;
+        assert!(reloader.reload().is_err());
+        std::fs::write(&path, "listen_addr = [")?;
+        assert!(reloader.reload().is_err());
+        assert_eq!(reloader.runtime(), runtime);
+        assert!(!runtime_rx.has_changed()?);
+
+        // Servers started with the default configuration can't reload it
+        let (_, log_filter) = reload::Layer::new(EnvFilter::new("info"));
+        let reloader = ConfigReloader::new(None, ServerConfig::default(), log_filter);
+        assert!(reloader.reload().is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,110 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use ring::{hmac, rand::SystemRandom};
+
+use crate::config::DownloadConfig;
+
+/// Signs the urls the server streams payloads from when they are not in S3.
+///
+/// A signature covers the namespace, the blob path and the expiry, so a url
+/// can't be replayed for another namespace or after it expires.
+pub struct DownloadUrlSigner {
+    key: hmac::Key,
+    default_expiry: Duration,
+    max_expiry: Duration,
+}
+
+impl DownloadUrlSigner {
+    pub fn new(config: &DownloadConfig) -> Result<Self> {
+        let key = match &config.signing_key {
+            Some(key) => {
+                let key = hex::decode(key).map_err(|e| anyhow!("invalid signing key: {}", e))?;
+                hmac::Key::new(hmac::HMAC_SHA256, &key)
+            }
+            // Urls signed with a random key are only valid until the server
+            // restarts
+            None => hmac::Key::generate(hmac::HMAC_SHA256, &SystemRandom::new())
+                .map_err(|_| anyhow!("unable to generate signing key"))?,
+        };
+        Ok(Self {
+            key,
+            default_expiry: Duration::from_secs(config.default_expiry_secs),
+            max_expiry: Duration::from_secs(config.max_expiry_secs),
+        })
+    }
+
+    /// Requested expiry, bounded by the configured maximum.
+    pub fn expiry(&self, expires_in_secs: Option<u64>) -> Duration {
+        expires_in_secs
+            .map(Duration::from_secs)
+            .unwrap_or(self.default_expiry)
+            .min(self.max_expiry)
+    }
+
+    fn message(namespace: &str, path: &str, expires_at: u64) -> String {
+        format!("{}|{}|{}", namespace, path, expires_at)
+    }
+
+    pub fn sign(&self, namespace: &str, path: &str, expires_at: u64) -> String {
+        let tag = hmac::sign(
+            &self.key,
+            Self::message(namespace, path, expires_at).as_bytes(),
+        );
+        hex::encode(tag.as_ref())
+    }
+
+    /// `now` and `expires_at` are seconds since the epoch.
+    pub fn verify(
+        &self,
+        namespace: &str,
+        path: &str,
+        expires_at: u64,
+        signature: &str,
+        now: u64,
+    ) -> Result<()> {
+        let signature = hex::decode(signature).map_err(|_| anyhow!("invalid signature"))?;
+        hmac::verify(
+            &self.key,
+            Self::message(namespace, path, expires_at).as_bytes(),
+            &signature,
+        )
+        .map_err(|_| anyhow!("invalid signature"))?;
+        if expires_at <= now {
+            return Err(anyhow!("url expired"));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_and_verify() {
+        let signer = DownloadUrlSigner::new(&DownloadConfig::default()).unwrap();
+        let signature = signer.sign("ns", "file:///blobs/a", 100);
+        assert!(signer
+            .verify("ns", "file:///blobs/a", 100, &signature, 99)
+            .is_ok());
+        // Expired
+        assert!(signer
+            .verify("ns", "file:///blobs/a", 100, &signature, 100)
+            .is_err());
+        // Another namespace, path or expiry
+        assert!(signer
+            .verify("other", "file:///blobs/a", 100, &signature, 99)
+            .is_err());
+        assert!(signer
+            .verify("ns", "file:///blobs/b", 100, &signature, 99)
+            .is_err());
+        assert!(signer
+            .verify("ns", "file:///blobs/a", 200, &signature, 99)
+            .is_err());
+
+        assert_eq!(signer.expiry(None), Duration::from_secs(300));
+        assert_eq!(signer.expiry(Some(10)), Duration::from_secs(10));
+        assert_eq!(signer.expiry(Some(100_000)), Duration::from_secs(3600));
+    }
+}
@@ -1,18 +1,111 @@
-use std::{sync::Arc, time::Duration};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
 
-use anyhow::Result;
-use data_model::{ExecutorId, ExecutorMetadata};
+use anyhow::{anyhow, Result};
+use data_model::{
+    executor_approval::{ApprovalStatus, ExecutorApproval},
+    executor_reconciliation::ReportedTask,
+    ExecutorFeature,
+    ExecutorId,
+    ExecutorMetadata,
+    GraphVersion,
+    Node,
+    EXECUTOR_PROTOCOL_VERSION,
+};
+use indexify_utils::get_epoch_time_in_ms;
 use state_store::{
     requests::{
         DeregisterExecutorRequest,
+        ReconcileExecutorTasksRequest,
+        RecordExecutorApprovalRequest,
         RegisterExecutorRequest,
         RequestPayload,
+        RequeueExecutorTasksRequest,
         StateMachineUpdateRequest,
+        UpdateExecutorArtifactsRequest,
+        UpdateExecutorLabelsRequest,
     },
     IndexifyState,
 };
 
+use crate::config::ExecutorApprovalConfig;
+
 pub const EXECUTOR_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Sent back to registering executors, features are comma separated
+pub const PROTOCOL_VERSION_HEADER: &str = "x-indexify-protocol-version";
+pub const FEATURES_HEADER: &str = "x-indexify-features";
+
+// Sent by registering executors to be approved without an admin
+pub const BOOTSTRAP_TOKEN_HEADER: &str = "x-indexify-bootstrap-token";
+
+// Recorded as the identity approving the executors with a bootstrap token
+const BOOTSTRAP_TOKEN: &str = "bootstrap_token";
+
+/// Rejects executors older than the minimum protocol version and returns the
+/// features enabled for the executor.
+pub fn negotiate_protocol(
+    protocol_version: u32,
+    requested_features: &[String],
+    min_protocol_version: u32,
+) -> Result<BTreeSet<ExecutorFeature>> {
+    if protocol_version < min_protocol_version {
+        return Err(anyhow!(
+            "executor protocol version {} is older than the minimum supported version {}, server version is {}",
+            protocol_version,
+            min_protocol_version,
+            EXECUTOR_PROTOCOL_VERSION
+        ));
+    }
+    Ok(ExecutorFeature::negotiate(
+        protocol_version.min(EXECUTOR_PROTOCOL_VERSION),
+        requested_features,
+    ))
+}
+
+pub fn features_header_value(features: &BTreeSet<ExecutorFeature>) -> String {
+    features
+        .iter()
+        .map(|feature| feature.as_str())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Whether a registering executor joins the cluster.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Admission {
+    Admitted,
+    PendingApproval,
+    Denied,
+}
+
+impl Admission {
+    pub fn rejection(&self, executor_id: &ExecutorId) -> Option<String> {
+        match self {
+            Admission::Admitted => None,
+            Admission::PendingApproval => {
+                Some(format!("executor {} is pending approval", executor_id))
+            }
+            Admission::Denied => Some(format!("executor {} was denied", executor_id)),
+        }
+    }
+}
+
+/// Function environment an executor keeps loaded between the tasks of the
+/// function
+#[derive(Debug, Clone, PartialEq)]
+pub struct WarmPoolHint {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub compute_fn: String,
+    pub graph_version: GraphVersion,
+    pub code_sha256: String,
+    pub instances: u32,
+}
+
 pub struct ExecutorManager {
     indexify_state: Arc<IndexifyState>,
 }
@@ -47,6 +140,99 @@ impl ExecutorManager {
             .await
     }
 
+    /// Decides whether a registering executor joins, when executors need to
+    /// be approved. Executors are recorded as pending approval the first
+    /// time they register, unless they present a bootstrap token. Admitted
+    /// executors get the labels of their approval.
+    pub async fn admit(
+        &self,
+        config: Option<&ExecutorApprovalConfig>,
+        executor: &mut ExecutorMetadata,
+        bootstrap_token: Option<&str>,
+    ) -> Result<Admission> {
+        let Some(config) = config else {
+            return Ok(Admission::Admitted);
+        };
+        let approval = self
+            .indexify_state
+            .reader()
+            .get_executor_approval(&executor.id)?;
+        let bootstrapped = bootstrap_token.is_some_and(|token| config.accepts(token));
+        let approval = match approval {
+            // Decisions of admins stand, a denied executor can't bootstrap
+            Some(approval) if approval.status != ApprovalStatus::Pending => approval,
+            approval if bootstrapped => {
+                let now = get_epoch_time_in_ms();
+                let mut approval =
+                    approval.unwrap_or_else(|| ExecutorApproval::pending(executor, now));
+                approval.decide(ApprovalStatus::Approved, BOOTSTRAP_TOKEN, now);
+                self.record_approval(approval.clone()).await?;
+                approval
+            }
+            Some(approval) => approval,
+            None => {
+                let approval = ExecutorApproval::pending(executor, get_epoch_time_in_ms());
+                self.record_approval(approval.clone()).await?;
+                tracing::info!("executor {} is pending approval", executor.id);
+                approval
+            }
+        };
+        Ok(match approval.status {
+            ApprovalStatus::Approved => {
+                approval.stamp_labels(&mut executor.labels);
+                Admission::Admitted
+            }
+            ApprovalStatus::Pending => Admission::PendingApproval,
+            ApprovalStatus::Denied => Admission::Denied,
+        })
+    }
+
+    /// Approves or denies an executor which registered, on behalf of
+    /// `decided_by`. Labels are only stamped on approved executors, denied
+    /// executors are deregistered. Returns None when the executor never
+    /// registered.
+    pub async fn decide_approval(
+        &self,
+        executor_id: &ExecutorId,
+        status: ApprovalStatus,
+        labels: HashMap<String, serde_json::Value>,
+        decided_by: &str,
+    ) -> Result<Option<ExecutorApproval>> {
+        let reader = self.indexify_state.reader();
+        let Some(mut approval) = reader.get_executor_approval(executor_id)? else {
+            return Ok(None);
+        };
+        approval.decide(status, decided_by, get_epoch_time_in_ms());
+        approval.labels = labels;
+        self.record_approval(approval.clone()).await?;
+        let registered = reader
+            .get_all_executors()?
+            .into_iter()
+            .find(|executor| &executor.id == executor_id);
+        if let Some(mut executor) = registered {
+            match status {
+                ApprovalStatus::Approved => {
+                    approval.stamp_labels(&mut executor.labels);
+                    self.update_labels(executor.id, executor.labels).await?;
+                }
+                ApprovalStatus::Denied => self.deregister_executor(executor.id).await?,
+                ApprovalStatus::Pending => {}
+            }
+        }
+        Ok(Some(approval))
+    }
+
+    async fn record_approval(&self, approval: ExecutorApproval) -> Result<()> {
+        self.indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::RecordExecutorApproval(RecordExecutorApprovalRequest {
+                    approval,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await
+    }
+
     pub async fn deregister_executor(&self, executor_id: ExecutorId) -> Result<()> {
         self.indexify_state
             .write(StateMachineUpdateRequest {
@@ -58,6 +244,154 @@ impl ExecutorManager {
             .await
     }
 
+    /// Moves the tasks allocated to an executor back to the unallocated tasks
+    /// so they are scheduled again, e.g. when the executor is known to have
+    /// failed before its stream timed out
+    pub async fn requeue_tasks(&self, executor_id: ExecutorId, batch_size: usize) -> Result<()> {
+        self.indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::RequeueExecutorTasks(RequeueExecutorTasksRequest {
+                    executor_id,
+                    batch_size,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await
+    }
+
+    /// Reconciles the tasks an executor reports it was running when it went
+    /// down with the tasks allocated to it, before it registers again. The
+    /// tasks are scheduled again and a report of the restart is kept.
+    pub async fn reconcile_tasks(
+        &self,
+        executor_id: ExecutorId,
+        reported_tasks: Vec<ReportedTask>,
+    ) -> Result<()> {
+        self.indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::ReconcileExecutorTasks(ReconcileExecutorTasksRequest {
+                    executor_id,
+                    reported_tasks,
+                    restarted_at: get_epoch_time_in_ms(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await
+    }
+
+    /// Replaces the labels of a registered executor without re-registering
+    /// it, its tasks which can't run on it anymore are scheduled again. The
+    /// labels stamped by its approval are kept.
+    pub async fn update_labels(
+        &self,
+        executor_id: ExecutorId,
+        mut labels: HashMap<String, serde_json::Value>,
+    ) -> Result<()> {
+        let approval = self
+            .indexify_state
+            .reader()
+            .get_executor_approval(&executor_id)?;
+        if let Some(approval) = approval {
+            if approval.status == ApprovalStatus::Approved {
+                approval.stamp_labels(&mut labels);
+            }
+        }
+        self.indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::UpdateExecutorLabels(UpdateExecutorLabelsRequest {
+                    executor_id,
+                    labels,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await
+    }
+
+    /// Records the code artifacts cached by a registered executor and its
+    /// outstanding task window
+    pub async fn heartbeat(
+        &self,
+        executor_id: ExecutorId,
+        cached_artifacts: HashSet<String>,
+        queue_depth: u64,
+        max_outstanding_tasks: Option<u32>,
+    ) -> Result<()> {
+        self.indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::UpdateExecutorArtifacts(UpdateExecutorArtifactsRequest {
+                    executor_id,
+                    cached_artifacts,
+                    queue_depth,
+                    max_outstanding_tasks,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await
+    }
+
+    /// Environments the executor keeps warm for the functions with
+    /// min_warm_instances. The instances of a function are spread over the
+    /// executors it can run on, in the order of their ids.
+    pub fn warm_pool(&self, executor_id: &ExecutorId) -> Result<Vec<WarmPoolHint>> {
+        let reader = self.indexify_state.reader();
+        let mut executors = reader.get_all_executors()?;
+        executors.sort_by(|a, b| a.id.cmp(&b.id));
+        let mut pools = HashMap::new();
+        let mut hints = Vec::new();
+        for graph in reader.all_compute_graphs()? {
+            for node in graph.nodes.values() {
+                let Node::Compute(compute_fn) = node else {
+                    continue;
+                };
+                if compute_fn.min_warm_instances == 0 {
+                    continue;
+                }
+                let pool = match &compute_fn.executor_pool {
+                    Some(pool_name) => {
+                        let key = (graph.namespace.clone(), pool_name.clone());
+                        if !pools.contains_key(&key) {
+                            let pool = reader.get_executor_pool(&graph.namespace, pool_name)?;
+                            pools.insert(key.clone(), pool);
+                        }
+                        match &pools[&key] {
+                            Some(pool) => Some(pool),
+                            // Nothing runs the function until its pool exists
+                            None => continue,
+                        }
+                    }
+                    None => None,
+                };
+                let candidates: Vec<&ExecutorId> = executors
+                    .iter()
+                    .filter(|executor| {
+                        executor.image_name == compute_fn.image_name &&
+                            pool.map_or(true, |pool| pool.matches_executor(executor)) &&
+                            compute_fn.matches_executor(executor)
+                    })
+                    .map(|executor| &executor.id)
+                    .collect();
+                let Some(index) = candidates.iter().position(|id| *id == executor_id) else {
+                    continue;
+                };
+                let count = candidates.len() as u32;
+                let instances = compute_fn.min_warm_instances / count +
+                    u32::from((index as u32) < compute_fn.min_warm_instances % count);
+                if instances == 0 {
+                    continue;
+                }
+                hints.push(WarmPoolHint {
+                    namespace: graph.namespace.clone(),
+                    compute_graph: graph.name.clone(),
+                    compute_fn: compute_fn.name.clone(),
+                    graph_version: graph.version,
+                    code_sha256: graph.code.sha256_hash.clone(),
+                    instances,
+                });
+            }
+        }
+        Ok(hints)
+    }
+
     pub async fn list_executors(&self) -> Result<Vec<ExecutorMetadata>> {
         self.indexify_state.reader().get_all_executors()
     }
@@ -78,8 +412,16 @@ mod tests {
     use std::sync::Arc;
 
     use anyhow::Result;
-    use data_model::{ExecutorId, ExecutorMetadata};
-    use state_store::IndexifyState;
+    use data_model::{
+        test_objects::tests::{mock_graph_a, TEST_EXECUTOR_IMAGE_NAME, TEST_NAMESPACE},
+        ExecutorId,
+        ExecutorMetadata,
+    };
+    use sha2::{Digest, Sha256};
+    use state_store::{
+        requests::{CreateComputeGraphRequest, NamespaceRequest},
+        IndexifyState,
+    };
 
     use super::*;
 
@@ -95,6 +437,15 @@ mod tests {
             image_name: "test".to_string(),
             addr: "".to_string(),
             labels: Default::default(),
+            cached_artifacts: Default::default(),
+            gpus: vec![],
+            protocol_version: 0,
+            features: Default::default(),
+            runtimes: Default::default(),
+            queue_depth: 0,
+            max_outstanding_tasks: None,
+            version: None,
+            last_heartbeat_at: None,
         };
         ex.register_executor(executor).await?;
 
@@ -117,6 +468,15 @@ mod tests {
             image_name: "test".to_string(),
             addr: "".to_string(),
             labels: Default::default(),
+            cached_artifacts: Default::default(),
+            gpus: vec![],
+            protocol_version: 0,
+            features: Default::default(),
+            runtimes: Default::default(),
+            queue_depth: 0,
+            max_outstanding_tasks: None,
+            version: None,
+            last_heartbeat_at: None,
         };
         ex.register_executor(executor.clone()).await?;
 
@@ -151,4 +511,141 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_executor_approval() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let ex = ExecutorManager::new(indexify_state.clone()).await;
+        let executor = |id: &str| ExecutorMetadata {
+            id: ExecutorId::new(id.to_string()),
+            labels: HashMap::from([("tier".to_string(), serde_json::json!("gpu"))]),
+            ..Default::default()
+        };
+        let config = ExecutorApprovalConfig {
+            bootstrap_token_sha256s: vec![hex::encode(Sha256::digest(b"token"))],
+        };
+
+        let mut new = executor("new");
+        assert_eq!(ex.admit(None, &mut new, None).await?, Admission::Admitted);
+        for _ in 0..2 {
+            let admission = ex.admit(Some(&config), &mut new, Some("wrong")).await?;
+            assert_eq!(admission, Admission::PendingApproval);
+        }
+        let approvals = indexify_state.reader().list_executor_approvals()?;
+        assert_eq!(approvals.len(), 1);
+        assert_eq!(approvals[0].status, ApprovalStatus::Pending);
+
+        let labels = HashMap::from([("tier".to_string(), serde_json::json!("cpu"))]);
+        let approval = ex
+            .decide_approval(&new.id, ApprovalStatus::Approved, labels, "admin")
+            .await?
+            .unwrap();
+        assert_eq!(approval.decided_by.as_deref(), Some("admin"));
+        assert_eq!(
+            ex.admit(Some(&config), &mut new, None).await?,
+            Admission::Admitted
+        );
+        assert_eq!(new.labels["tier"], "cpu");
+
+        let mut bootstrapped = executor("bootstrapped");
+        assert_eq!(
+            ex.admit(Some(&config), &mut bootstrapped, Some("token"))
+                .await?,
+            Admission::Admitted
+        );
+        assert_eq!(bootstrapped.labels["tier"], "gpu");
+
+        // A denied executor can't bootstrap itself
+        ex.decide_approval(
+            &bootstrapped.id,
+            ApprovalStatus::Denied,
+            HashMap::new(),
+            "admin",
+        )
+        .await?;
+        assert_eq!(
+            ex.admit(Some(&config), &mut bootstrapped, Some("token"))
+                .await?,
+            Admission::Denied
+        );
+        assert!(ex
+            .decide_approval(
+                &ExecutorId::new("unknown".to_string()),
+                ApprovalStatus::Approved,
+                HashMap::new(),
+                "admin"
+            )
+            .await?
+            .is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_warm_pool() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let ex = ExecutorManager::new(indexify_state.clone()).await;
+        let mut graph = mock_graph_a();
+        if let Some(Node::Compute(compute_fn)) = graph.nodes.get_mut("fn_b") {
+            compute_fn.min_warm_instances = 3;
+        }
+        for payload in [
+            RequestPayload::CreateNameSpace(NamespaceRequest {
+                name: TEST_NAMESPACE.to_string(),
+                queue_limits: Default::default(),
+                scheduling_weight: None,
+                reserved_capacity: None,
+                replication: None,
+            }),
+            RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph: graph.clone(),
+                expected_revision: None,
+                force: false,
+            }),
+        ] {
+            indexify_state
+                .write(StateMachineUpdateRequest {
+                    payload,
+                    state_changes_processed: vec![],
+                })
+                .await?;
+        }
+        for (id, image_name) in [
+            ("a", TEST_EXECUTOR_IMAGE_NAME),
+            ("b", TEST_EXECUTOR_IMAGE_NAME),
+            ("other", "other_image"),
+        ] {
+            ex.register_executor(ExecutorMetadata {
+                id: ExecutorId::new(id.to_string()),
+                image_name: image_name.to_string(),
+                ..Default::default()
+            })
+            .await?;
+        }
+
+        let instances = |id: &str| -> Result<Vec<(String, u32)>> {
+            Ok(ex
+                .warm_pool(&ExecutorId::new(id.to_string()))?
+                .into_iter()
+                .map(|hint| (hint.compute_fn, hint.instances))
+                .collect())
+        };
+        assert_eq!(instances("a")?, vec![("fn_b".to_string(), 2)]);
+        assert_eq!(instances("b")?, vec![("fn_b".to_string(), 1)]);
+        // Executors which can't run the function keep nothing warm
+        assert!(instances("other")?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_negotiate_protocol() {
+        let requested = vec!["lease_renewal".to_string(), "unknown".to_string()];
+        assert!(negotiate_protocol(0, &requested, 1).is_err());
+        assert!(negotiate_protocol(0, &requested, 0).unwrap().is_empty());
+        let features = negotiate_protocol(EXECUTOR_PROTOCOL_VERSION + 1, &requested, 1).unwrap();
+        assert_eq!(features, BTreeSet::from([ExecutorFeature::LeaseRenewal]));
+        assert_eq!(features_header_value(&features), "lease_renewal");
+    }
 }
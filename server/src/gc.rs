@@ -1,13 +1,29 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use anyhow::Result;
 use blob_store::BlobStorage;
-use state_store::IndexifyState;
+use indexify_utils::get_epoch_time_in_ms;
+use state_store::{
+    requests::{
+        DeleteComputeGraphRequest,
+        DeleteNamespaceRequest,
+        RequestPayload,
+        StateMachineUpdateRequest,
+        SweepPendingUploadsRequest,
+    },
+    IndexifyState,
+};
+
+use crate::config::RuntimeConfig;
+
+// How often deleted compute graphs past their retention are purged
+const PURGE_INTERVAL: Duration = Duration::from_secs(60);
 
 pub struct Gc {
     state: Arc<IndexifyState>,
     storage: Arc<BlobStorage>,
     rx: tokio::sync::watch::Receiver<()>,
+    runtime_config_rx: tokio::sync::watch::Receiver<RuntimeConfig>,
     shutdown_rx: tokio::sync::watch::Receiver<()>,
 }
 
@@ -15,6 +31,7 @@ impl Gc {
     pub fn new(
         state: Arc<IndexifyState>,
         storage: Arc<BlobStorage>,
+        runtime_config_rx: tokio::sync::watch::Receiver<RuntimeConfig>,
         shutdown_rx: tokio::sync::watch::Receiver<()>,
     ) -> Self {
         let rx = state.get_gc_watcher();
@@ -22,41 +39,153 @@ impl Gc {
             state,
             storage,
             rx,
+            runtime_config_rx,
             shutdown_rx,
         }
     }
 
+    /// Purges the deleted compute graphs whose retention has expired, the
+    /// graphs of deleted namespaces are purged right away along with the
+    /// namespaces.
+    async fn purge_deleted_graphs(&self) -> Result<()> {
+        let retention_ms = self.runtime_config_rx.borrow().deleted_graph_retention_secs * 1000;
+        let deleted_before = get_epoch_time_in_ms().saturating_sub(retention_ms);
+        let reader = self.state.reader();
+        let deleted_namespaces: Vec<String> = reader
+            .get_all_namespaces()?
+            .into_iter()
+            .filter(|namespace| namespace.deleted_at.is_some())
+            .map(|namespace| namespace.name)
+            .collect();
+        let graphs = reader.expired_deleted_compute_graphs(u64::MAX)?;
+        for graph in graphs {
+            let expired = graph.deleted_at.is_some_and(|at| at < deleted_before);
+            if !expired && !deleted_namespaces.contains(&graph.namespace) {
+                continue;
+            }
+            // Purged once the holds on the graph and its invocations are
+            // cleared
+            if !reader
+                .legal_holds(&graph.namespace, Some(&graph.name))?
+                .is_empty()
+            {
+                tracing::debug!(
+                    "deleted compute graph {}/{} is under a legal hold",
+                    graph.namespace,
+                    graph.name
+                );
+                continue;
+            }
+            tracing::info!(
+                "purging deleted compute graph {}/{}",
+                graph.namespace,
+                graph.name
+            );
+            self.state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::PurgeComputeGraph(DeleteComputeGraphRequest {
+                        namespace: graph.namespace,
+                        name: graph.name,
+                    }),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+        }
+        for namespace in deleted_namespaces {
+            self.state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::PurgeNamespace(DeleteNamespaceRequest { namespace }),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Hands the uploads which were never committed by a task outcome to the
+    /// garbage collector.
+    async fn sweep_pending_uploads(&self) -> Result<()> {
+        let timeout_ms = self.runtime_config_rx.borrow().pending_upload_timeout_secs * 1000;
+        let created_before = get_epoch_time_in_ms().saturating_sub(timeout_ms);
+        loop {
+            let uploads = self
+                .state
+                .reader()
+                .stale_pending_uploads(created_before, 100)?;
+            if uploads.is_empty() {
+                return Ok(());
+            }
+            self.state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::SweepPendingUploads(SweepPendingUploadsRequest {
+                        urls: uploads.into_iter().map(|upload| upload.url).collect(),
+                    }),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+        }
+    }
+
     pub async fn start(&mut self) -> Result<()> {
         let state = self.state.clone();
         let storage = self.storage.clone();
+        let mut purge_interval = tokio::time::interval(PURGE_INTERVAL);
+        // Urls are deleted in key order, the urls which failed to be deleted
+        // are retried by the next pass over the urls
+        let mut after: Option<String> = None;
+        let mut failed = false;
         loop {
             if self.shutdown_rx.has_changed().unwrap_or(false) {
                 println!("Shutdown signal received.");
                 return Ok(());
             }
 
-            let urls = state.reader().get_gc_urls(Some(10))?;
+            let urls = state.reader().gc_urls_after(after.as_deref(), 10)?;
             if urls.is_empty() {
+                // A pass without failures starts over right away, for the urls
+                // added behind it
+                if after.take().is_some() && !std::mem::take(&mut failed) {
+                    continue;
+                }
                 tokio::select! {
                     _ = self.rx.changed() => { self.rx.borrow_and_update(); }
+                    _ = purge_interval.tick() => {
+                        if let Err(e) = self.purge_deleted_graphs().await {
+                            tracing::error!("error purging deleted compute graphs: {:?}", e);
+                        }
+                        if let Err(e) = self.sweep_pending_uploads().await {
+                            tracing::error!("error sweeping pending uploads: {:?}", e);
+                        }
+                    }
                     _ = self.shutdown_rx.changed() => {
                         println!("Shutdown signal received.");
                         return Ok(());
                     }
                 }
             } else {
-                for url in urls.iter() {
-                    tracing::debug!("Deleting url {:?}", url);
-                    if let Err(e) = storage.delete(url).await {
-                        tracing::error!("Error deleting url {:?}: {:?}", url, e);
+                after = urls.last().cloned();
+                let mut deleted = Vec::with_capacity(urls.len());
+                for url in urls {
+                    // Inline payloads are removed from the state store along
+                    // with their urls
+                    if !data_model::is_inline_path(&url) {
+                        tracing::debug!("Deleting url {:?}", url);
+                        if let Err(e) = storage.delete(&url).await {
+                            tracing::error!("Error deleting url {:?}: {:?}", url, e);
+                            failed = true;
+                            continue;
+                        }
                     }
+                    deleted.push(url);
+                }
+                if !deleted.is_empty() {
+                    self.state
+                        .write(StateMachineUpdateRequest {
+                            payload: RequestPayload::RemoveGcUrls(deleted),
+                            state_changes_processed: vec![],
+                        })
+                        .await?;
                 }
-                self.state
-                    .write(state_store::requests::StateMachineUpdateRequest {
-                        payload: state_store::requests::RequestPayload::RemoveGcUrls(urls),
-                        state_changes_processed: vec![],
-                    })
-                    .await?;
             }
         }
     }
@@ -97,7 +226,8 @@ mod tests {
             blob_store::BlobStorageConfig::new_disk(temp_dir.path().join("blob").to_str().unwrap());
         let storage = Arc::new(BlobStorage::new(config)?);
         let (tx, rx) = watch::channel(());
-        let mut gc = Gc::new(state.clone(), storage.clone(), rx);
+        let (_, runtime_config_rx) = watch::channel(RuntimeConfig::default());
+        let mut gc = Gc::new(state.clone(), storage.clone(), runtime_config_rx, rx);
 
         tokio::spawn(async move {
             info!("starting garbage collector");
@@ -111,6 +241,8 @@ mod tests {
                 payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
                     namespace: TEST_NAMESPACE.to_string(),
                     compute_graph: compute_graph.clone(),
+                    expected_revision: None,
+                    force: false,
                 }),
                 state_changes_processed: vec![],
             })
@@ -132,9 +264,16 @@ mod tests {
                 path: res.url.clone(),
                 size: res.size_bytes,
                 sha256_hash: res.sha256_hash,
+                tier: Default::default(),
+                mime_type: None,
+                content_encoding: None,
+                metadata: Default::default(),
+                replicas: Vec::new(),
+                compression: None,
             }),
             errors: None,
             reduced_state: false,
+            streamed: false,
         };
         let key = output.key(&output.invocation_id);
         let serialized_output = JsonEncoder::encode(&output)?;
@@ -146,16 +285,23 @@ mod tests {
 
         storage.read_bytes(&res.url).await?;
 
-        let request = RequestPayload::DeleteComputeGraph(DeleteComputeGraphRequest {
-            namespace: TEST_NAMESPACE.to_string(),
-            name: compute_graph.name.clone(),
-        });
-        state
-            .write(StateMachineUpdateRequest {
-                payload: request,
-                state_changes_processed: vec![],
-            })
-            .await?;
+        for request in [
+            RequestPayload::DeleteComputeGraph(DeleteComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                name: compute_graph.name.clone(),
+            }),
+            RequestPayload::PurgeComputeGraph(DeleteComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                name: compute_graph.name.clone(),
+            }),
+        ] {
+            state
+                .write(StateMachineUpdateRequest {
+                    payload: request,
+                    state_changes_processed: vec![],
+                })
+                .await?;
+        }
 
         let time = std::time::Instant::now();
         loop {
@@ -175,4 +321,59 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_purge_expired_deleted_graphs() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let config =
+            blob_store::BlobStorageConfig::new_disk(temp_dir.path().join("blob").to_str().unwrap());
+        let storage = Arc::new(BlobStorage::new(config)?);
+        let (runtime_config_tx, runtime_config_rx) = watch::channel(RuntimeConfig::default());
+        let gc = Gc::new(
+            state.clone(),
+            storage,
+            runtime_config_rx,
+            watch::channel(()).1,
+        );
+
+        let compute_graph = mock_graph_a();
+        for payload in [
+            RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph: compute_graph.clone(),
+                expected_revision: None,
+                force: false,
+            }),
+            RequestPayload::DeleteComputeGraph(DeleteComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                name: compute_graph.name.clone(),
+            }),
+        ] {
+            state
+                .write(StateMachineUpdateRequest {
+                    payload,
+                    state_changes_processed: vec![],
+                })
+                .await?;
+        }
+        tokio::time::sleep(Duration::from_millis(5)).await;
+
+        // Within the retention window
+        gc.purge_deleted_graphs().await?;
+        let (deleted, _) =
+            state
+                .reader()
+                .list_deleted_compute_graphs(TEST_NAMESPACE, None, None)?;
+        assert_eq!(deleted.len(), 1);
+
+        runtime_config_tx.send_modify(|config| config.deleted_graph_retention_secs = 0);
+        gc.purge_deleted_graphs().await?;
+        let (deleted, _) =
+            state
+                .reader()
+                .list_deleted_compute_graphs(TEST_NAMESPACE, None, None)?;
+        assert!(deleted.is_empty());
+        Ok(())
+    }
 }
@@ -0,0 +1,944 @@
+use std::{collections::HashMap, net::SocketAddr, pin::Pin};
+
+use axum::body::Bytes;
+use blob_store::PutResult;
+use data_model::{
+    attributes::validate_attributes,
+    executor_reconciliation::ReportedTask,
+    DataPayload,
+    ExecutorId,
+    SchedulingLane,
+    TaskDiagnostics,
+};
+use futures::{Stream, StreamExt};
+use indexify_utils::GuardStreamExt;
+use nanoid::nanoid;
+use proto::{
+    indexify_service_server::{IndexifyService, IndexifyServiceServer},
+    invocation_event::Event,
+    CreateComputeGraphRequest,
+    CreateComputeGraphResponse,
+    GetTasksRequest,
+    InvocationEvent,
+    InvokeComputeGraphRequest,
+    InvokeComputeGraphResponse,
+    ReportTaskOutcomeRequest,
+    ReportTaskOutcomeResponse,
+    TaskList,
+    WatchInvocationRequest,
+};
+use state_store::{
+    invocation_events::InvocationStateChangeEvent,
+    requests::InlinePayload,
+    ExecutorEvent,
+};
+use tokio::sync::{broadcast::error::RecvError, watch};
+use tonic::{Request, Response, Status};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{
+    auth::{Permission, ALL_NAMESPACES},
+    executors::{self, EXECUTOR_TIMEOUT},
+    routes::{
+        check_writable,
+        internal_ingest::{
+            diagnostic_file_name,
+            finalize_task_result,
+            output_file_name,
+            prepare_data_payload,
+            register_pending_upload,
+            RouterOutput,
+            TaskOutcome,
+            TaskResult,
+        },
+        invocation_groups::check_invocation_group,
+        invoke::{check_backpressure, invoke_with_payload},
+        parse_compute_graph,
+        payload_compression::{self, compression_of, PayloadCompressor},
+        payload_limits::PayloadLimit,
+        register_compute_graph,
+        RouteState,
+    },
+};
+
+pub mod proto {
+    tonic::include_proto!("indexify");
+}
+
+const TASK_LIMIT: usize = 10;
+
+type GrpcStream<T> = Pin<Box<dyn Stream<Item = Result<T, Status>> + Send>>;
+
+impl From<data_model::Task> for proto::Task {
+    fn from(task: data_model::Task) -> Self {
+        let outcome: proto::TaskOutcome = task.outcome.into();
+        Self {
+            id: task.id.to_string(),
+            namespace: task.namespace,
+            compute_graph: task.compute_graph_name,
+            compute_fn: task.compute_fn_name,
+            invocation_id: task.invocation_id,
+            input_key: task.input_node_output_key,
+            reducer_output_id: task.reducer_output_id,
+            graph_version: task.graph_version.0,
+            outcome: outcome.into(),
+            invocation_labels: task
+                .invocation_labels
+                .into_iter()
+                .map(|(key, value)| (key, value.to_string()))
+                .collect(),
+            code_sha256: task.code_sha256,
+            gpu_ids: task.gpu_ids,
+            runtime: task.runtime.map(Into::into),
+            input_batch: task.input_batch,
+            parameters: task
+                .parameters
+                .into_iter()
+                .map(|(key, value)| (key, value.to_string()))
+                .collect(),
+            checkpoint: task.checkpoint.map(|checkpoint| proto::TaskCheckpoint {
+                size: checkpoint.payload.size,
+                sha256_hash: checkpoint.payload.sha256_hash,
+                created_at: checkpoint.created_at,
+            }),
+            token: None,
+        }
+    }
+}
+
+impl From<data_model::RuntimeDescriptor> for proto::Runtime {
+    fn from(runtime: data_model::RuntimeDescriptor) -> Self {
+        Self {
+            image: runtime.image,
+            python_version: runtime.python_version,
+            pip_hash: runtime.pip_hash,
+        }
+    }
+}
+
+impl From<proto::Runtime> for data_model::RuntimeDescriptor {
+    fn from(runtime: proto::Runtime) -> Self {
+        Self {
+            image: runtime.image,
+            python_version: runtime.python_version,
+            pip_hash: runtime.pip_hash,
+        }
+    }
+}
+
+impl From<proto::RecoveredTask> for ReportedTask {
+    fn from(task: proto::RecoveredTask) -> Self {
+        Self {
+            namespace: task.namespace,
+            compute_graph: task.compute_graph,
+            compute_fn: task.compute_fn,
+            invocation_id: task.invocation_id,
+            task_id: data_model::TaskId::new(task.task_id),
+            partial_outputs: task.partial_outputs,
+        }
+    }
+}
+
+impl From<data_model::TaskOutcome> for proto::TaskOutcome {
+    fn from(outcome: data_model::TaskOutcome) -> Self {
+        match outcome {
+            data_model::TaskOutcome::Unknown => proto::TaskOutcome::Unknown,
+            data_model::TaskOutcome::Success => proto::TaskOutcome::Success,
+            data_model::TaskOutcome::Failure => proto::TaskOutcome::Failure,
+        }
+    }
+}
+
+fn decode_labels(
+    labels: HashMap<String, String>,
+) -> Result<HashMap<String, serde_json::Value>, Status> {
+    decode_json_values("label", labels)
+}
+
+fn decode_attributes(
+    attributes: HashMap<String, String>,
+) -> Result<HashMap<String, serde_json::Value>, Status> {
+    let attributes = decode_json_values("attribute", attributes)?;
+    validate_attributes(&attributes).map_err(|e| Status::invalid_argument(e.to_string()))?;
+    Ok(attributes)
+}
+
+fn decode_json_values(
+    kind: &str,
+    values: HashMap<String, String>,
+) -> Result<HashMap<String, serde_json::Value>, Status> {
+    let mut decoded = HashMap::new();
+    for (key, value) in values {
+        let value = serde_json::from_str(&value).map_err(|e| {
+            Status::invalid_argument(format!("{} {} is not valid json: {}", kind, key, e))
+        })?;
+        decoded.insert(key, value);
+    }
+    Ok(decoded)
+}
+
+fn decode_lane(lane: Option<String>) -> Result<Option<SchedulingLane>, Status> {
+    lane.map(|lane| {
+        serde_json::from_value(serde_json::Value::String(lane.clone()))
+            .map_err(|_| Status::invalid_argument(format!("unknown scheduling lane {}", lane)))
+    })
+    .transpose()
+}
+
+fn event_from(ev: InvocationStateChangeEvent) -> Option<InvocationEvent> {
+    let invocation_id = ev.invocation_id();
+    let event = match ev {
+        InvocationStateChangeEvent::AsyncInvocation(_) |
+        InvocationStateChangeEvent::InvocationGroupFinished(_) => return None,
+        InvocationStateChangeEvent::TaskCreated(ev) => Event::TaskCreated(proto::TaskCreated {
+            fn_name: ev.fn_name,
+            task_id: ev.task_id,
+        }),
+        InvocationStateChangeEvent::TaskAssigned(ev) => Event::TaskAssigned(proto::TaskAssigned {
+            fn_name: ev.fn_name,
+            task_id: ev.task_id,
+            executor_id: ev.executor_id,
+        }),
+        InvocationStateChangeEvent::TaskCompleted(ev) => {
+            let outcome: proto::TaskOutcome = ev.outcome.into();
+            Event::TaskCompleted(proto::TaskCompleted {
+                fn_name: ev.fn_name,
+                task_id: ev.task_id,
+                outcome: outcome.into(),
+            })
+        }
+        InvocationStateChangeEvent::InvocationFinished(_) => {
+            Event::InvocationFinished(proto::InvocationFinished {})
+        }
+        InvocationStateChangeEvent::DiagnosticMessage(ev) => {
+            Event::DiagnosticMessage(proto::DiagnosticMessage {
+                message: ev.message,
+            })
+        }
+    };
+    Some(InvocationEvent {
+        invocation_id,
+        event: Some(event),
+    })
+}
+
+pub struct GrpcService {
+    state: RouteState,
+}
+
+impl GrpcService {
+    pub fn new(state: RouteState) -> Self {
+        Self { state }
+    }
+
+    async fn put(&self, key: &str, data: Vec<u8>) -> Result<PutResult, Status> {
+        self.state
+            .blob_storage
+            .put(key, futures::stream::iter(vec![Ok(Bytes::from(data))]))
+            .await
+            .map_err(|e| Status::internal(format!("failed to write to blob store: {}", e)))
+    }
+
+    /// Writes an invocation payload or function output, compressed with the
+    /// compression of its graph.
+    async fn put_payload(
+        &self,
+        key: &str,
+        data: Vec<u8>,
+        compressor: Option<&PayloadCompressor>,
+    ) -> Result<PutResult, Status> {
+        payload_compression::put_payload(
+            &self.state.blob_storage,
+            key,
+            futures::stream::iter(vec![Ok(Bytes::from(data))]),
+            compressor,
+        )
+        .await
+        .map_err(|e| Status::internal(format!("failed to write to blob store: {}", e)))
+    }
+
+    /// Checks the role bindings like the HTTP api does, with the bearer token
+    /// of the `authorization` metadata.
+    fn authorize<T>(
+        &self,
+        request: &Request<T>,
+        namespace: &str,
+        permission: Permission,
+        method: &str,
+    ) -> Result<(), Status> {
+        if let Some(auth) = self.state.config_reloader.runtime().auth {
+            let headers = request.metadata().clone().into_headers();
+            auth.authorize(&headers, &self.state.jwks, namespace, permission, method)?;
+        }
+        Ok(())
+    }
+}
+
+#[tonic::async_trait]
+impl IndexifyService for GrpcService {
+    type GetTasksStream = GrpcStream<TaskList>;
+    type WatchInvocationStream = GrpcStream<InvocationEvent>;
+
+    async fn create_compute_graph(
+        &self,
+        request: Request<CreateComputeGraphRequest>,
+    ) -> Result<Response<CreateComputeGraphResponse>, Status> {
+        self.authorize(
+            &request,
+            &request.get_ref().namespace,
+            Permission::Write,
+            "CreateComputeGraph",
+        )?;
+        let request = request.into_inner();
+        check_writable(&self.state)?;
+        let canary_percent = request
+            .canary_percent
+            .map(u8::try_from)
+            .transpose()
+            .map_err(|_| Status::invalid_argument("canary_percent must be in [0, 100]"))?;
+        let definition = parse_compute_graph(&request.namespace, &request.compute_graph_json)?;
+        let file_name = format!("{}_{}", request.namespace, nanoid!());
+        let put_result = self.put(&file_name, request.code).await?;
+        let mut wasm_modules = HashMap::new();
+        for (router, module) in request.wasm_modules {
+            let file_name = format!("{}_{}.wasm", request.namespace, nanoid!());
+            wasm_modules.insert(router, self.put(&file_name, module).await?);
+        }
+        let name = register_compute_graph(
+            &self.state,
+            request.namespace,
+            definition,
+            put_result,
+            wasm_modules,
+            request.expected_revision,
+            request.force,
+            canary_percent,
+        )
+        .await?;
+        Ok(Response::new(CreateComputeGraphResponse { name }))
+    }
+
+    async fn invoke_compute_graph(
+        &self,
+        request: Request<InvokeComputeGraphRequest>,
+    ) -> Result<Response<InvokeComputeGraphResponse>, Status> {
+        self.authorize(
+            &request,
+            &request.get_ref().namespace,
+            Permission::Write,
+            "InvokeComputeGraph",
+        )?;
+        let request = request.into_inner();
+        check_backpressure(&self.state, &request.namespace, &request.compute_graph)?;
+        check_invocation_group(
+            &self.state,
+            &request.namespace,
+            request.invocation_group.as_deref(),
+        )?;
+        if let Some(limit) =
+            PayloadLimit::of_graph(&self.state, &request.namespace, &request.compute_graph)?
+        {
+            limit.check(&self.state, request.payload.len() as u64)?;
+        }
+        let labels = decode_labels(request.labels)?;
+        let attributes = decode_attributes(request.attributes)?;
+        let parameters = decode_json_values("parameter", request.parameters)?;
+        let lane = decode_lane(request.lane)?;
+        // Payloads the client encoded are already compressed
+        let compressor = match request.content_encoding {
+            Some(_) => None,
+            None => PayloadCompressor::of_graph(
+                &self.state,
+                &request.namespace,
+                &request.compute_graph,
+            )?,
+        };
+        let put_result = self
+            .put_payload(&Uuid::new_v4().to_string(), request.payload, compressor.as_ref())
+            .await?;
+        let compression = compression_of(&put_result);
+        let data_payload = DataPayload {
+            path: put_result.url,
+            size: put_result.size_bytes,
+            sha256_hash: put_result.sha256_hash,
+            tier: Default::default(),
+            mime_type: request.mime_type,
+            content_encoding: request.content_encoding,
+            metadata: request.payload_metadata,
+            replicas: Vec::new(),
+            compression,
+        };
+        let invocation_id = invoke_with_payload(
+            &self.state,
+            &request.namespace,
+            &request.compute_graph,
+            data_payload,
+            labels,
+            attributes,
+            request.priority,
+            lane,
+            request.invocation_group,
+            parameters,
+            request.event_time,
+            None,
+        )
+        .await?;
+        Ok(Response::new(InvokeComputeGraphResponse { invocation_id }))
+    }
+
+    async fn get_tasks(
+        &self,
+        request: Request<GetTasksRequest>,
+    ) -> Result<Response<Self::GetTasksStream>, Status> {
+        self.authorize(&request, ALL_NAMESPACES, Permission::Execute, "GetTasks")?;
+        let bootstrap_token = request
+            .metadata()
+            .get(executors::BOOTSTRAP_TOKEN_HEADER)
+            .and_then(|token| token.to_str().ok())
+            .map(|token| token.to_string());
+        let executor = request
+            .into_inner()
+            .executor
+            .ok_or(Status::invalid_argument("executor is required"))?;
+        let labels = decode_labels(executor.labels)?;
+        let runtime = self.state.config_reloader.runtime();
+        let features = executors::negotiate_protocol(
+            executor.protocol_version,
+            &executor.features,
+            runtime.min_executor_protocol_version,
+        )
+        .map_err(|e| Status::failed_precondition(e.to_string()))?;
+        let features_value = executors::features_header_value(&features);
+        let recovered_tasks = executor.recovering.then(|| {
+            executor
+                .recovered_tasks
+                .into_iter()
+                .map(ReportedTask::from)
+                .collect::<Vec<_>>()
+        });
+        let executor_id = ExecutorId::new(executor.id);
+        let mut executor = data_model::ExecutorMetadata {
+            id: executor_id.clone(),
+            image_name: executor.image_name,
+            addr: executor.addr,
+            labels,
+            cached_artifacts: executor.cached_artifacts.into_iter().collect(),
+            gpus: executor
+                .gpus
+                .into_iter()
+                .map(|gpu| data_model::GpuDevice {
+                    id: gpu.id,
+                    model: gpu.model,
+                    free_memory_mb: gpu.free_memory_mb,
+                })
+                .collect(),
+            protocol_version: executor.protocol_version,
+            features,
+            runtimes: executor.runtimes.into_iter().map(Into::into).collect(),
+            queue_depth: 0,
+            max_outstanding_tasks: executor.max_outstanding_tasks,
+            version: executor.version,
+            last_heartbeat_at: None,
+        };
+        let admission = self
+            .state
+            .executor_manager
+            .admit(
+                runtime.executor_approval.as_ref(),
+                &mut executor,
+                bootstrap_token.as_deref(),
+            )
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+        if let Some(rejection) = admission.rejection(&executor_id) {
+            return Err(Status::permission_denied(rejection));
+        }
+        if let Some(recovered_tasks) = recovered_tasks {
+            self.state
+                .executor_manager
+                .reconcile_tasks(executor_id.clone(), recovered_tasks)
+                .await
+                .map_err(|e| Status::internal(e.to_string()))?;
+        }
+        self.state
+            .executor_manager
+            .register_executor(executor)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+        let executor_manager = self.state.executor_manager.clone();
+        let task_tokens = self.state.task_tokens.clone();
+        let stream = state_store::task_stream(
+            self.state.indexify_state.clone(),
+            executor_id.clone(),
+            TASK_LIMIT,
+        )
+        .map(move |event| match event {
+            Ok(ExecutorEvent::Tasks(tasks)) => Ok(TaskList {
+                tasks: tasks
+                    .into_iter()
+                    .map(|task| proto::Task {
+                        token: task_tokens.dispatch_token(&task),
+                        ..task.into()
+                    })
+                    .collect(),
+                aborted_task_ids: vec![],
+                prefetch_tasks: vec![],
+            }),
+            Ok(ExecutorEvent::Abort(task_ids)) => Ok(TaskList {
+                tasks: vec![],
+                aborted_task_ids: task_ids.iter().map(|id| id.to_string()).collect(),
+                prefetch_tasks: vec![],
+            }),
+            Ok(ExecutorEvent::Prefetch(tasks)) => Ok(TaskList {
+                tasks: vec![],
+                aborted_task_ids: vec![],
+                prefetch_tasks: tasks.into_iter().map(Into::into).collect(),
+            }),
+            Err(e) => Err(Status::internal(e.to_string())),
+        })
+        .guard(|| executors::schedule_deregister(executor_manager, executor_id, EXECUTOR_TIMEOUT));
+        let mut response = Response::new(Box::pin(stream) as Self::GetTasksStream);
+        let metadata = response.metadata_mut();
+        metadata.insert(
+            executors::PROTOCOL_VERSION_HEADER,
+            data_model::EXECUTOR_PROTOCOL_VERSION.into(),
+        );
+        if let Ok(value) = features_value.parse() {
+            metadata.insert(executors::FEATURES_HEADER, value);
+        }
+        Ok(response)
+    }
+
+    async fn report_task_outcome(
+        &self,
+        request: Request<ReportTaskOutcomeRequest>,
+    ) -> Result<Response<ReportTaskOutcomeResponse>, Status> {
+        self.authorize(
+            &request,
+            &request.get_ref().namespace,
+            Permission::Execute,
+            "ReportTaskOutcome",
+        )?;
+        let request = request.into_inner();
+        let outcome = match request.outcome() {
+            proto::TaskOutcome::Unknown => None,
+            proto::TaskOutcome::Success => Some(TaskOutcome::Success),
+            proto::TaskOutcome::Failure => Some(TaskOutcome::Failure),
+        };
+        let task_result = TaskResult {
+            router_output: request.router_output.map(|router_output| RouterOutput {
+                edges: router_output.edges,
+            }),
+            outcome,
+            namespace: request.namespace,
+            compute_graph: request.compute_graph,
+            compute_fn: request.compute_fn,
+            task_id: request.task_id,
+            invocation_id: request.invocation_id,
+            executor_id: request.executor_id,
+            reducer: request.reducer,
+            partial: request.partial,
+            cpu_seconds: request.cpu_seconds,
+            gpu_seconds: request.gpu_seconds,
+            cold_start: request.cold_start,
+            output_mime_type: request.output_mime_type,
+            output_content_encoding: request.output_content_encoding,
+            output_metadata: request.output_metadata,
+        };
+        let payload_limit = PayloadLimit::of_graph(
+            &self.state,
+            &task_result.namespace,
+            &task_result.compute_graph,
+        )?;
+        if let Some(limit) = &payload_limit {
+            for output in request.fn_outputs.iter().chain(&request.inline_fn_outputs) {
+                limit.check(&self.state, output.len() as u64)?;
+            }
+        }
+        let compressor = match task_result.output_content_encoding {
+            Some(_) => None,
+            None => PayloadCompressor::of_graph(
+                &self.state,
+                &task_result.namespace,
+                &task_result.compute_graph,
+            )?,
+        };
+        let mut output_objects = Vec::new();
+        let blob_outputs = request.fn_outputs.len();
+        for (sequence, output) in request.fn_outputs.into_iter().enumerate() {
+            let file_name = output_file_name(&task_result, sequence);
+            register_pending_upload(&self.state, &task_result, &file_name).await?;
+            output_objects.push(self.put_payload(&file_name, output, compressor.as_ref()).await?);
+        }
+        let max_bytes = self.state.config_reloader.runtime().inline_output_max_bytes;
+        let mut inline_outputs = Vec::new();
+        for (sequence, data) in request.inline_fn_outputs.into_iter().enumerate() {
+            if data.len() > max_bytes {
+                return Err(Status::invalid_argument(format!(
+                    "inline outputs are limited to {} bytes",
+                    max_bytes
+                )));
+            }
+            let file_name = output_file_name(&task_result, blob_outputs + sequence);
+            inline_outputs.push(InlinePayload {
+                path: DataPayload::inline_path(&file_name),
+                data,
+            });
+        }
+        let mut diagnostics = Vec::new();
+        for (name, data) in [
+            ("exception_msg", request.exception_msg),
+            ("stdout", request.stdout),
+            ("stderr", request.stderr),
+            ("structured_logs", request.structured_logs),
+        ] {
+            let put_result = match data {
+                Some(data) => {
+                    let file_name = diagnostic_file_name(&task_result, name);
+                    register_pending_upload(&self.state, &task_result, &file_name).await?;
+                    Some(self.put(&file_name, data).await?)
+                }
+                None => None,
+            };
+            diagnostics.push(prepare_data_payload(put_result));
+        }
+        let [exception, stdout, stderr, structured_logs]: [_; 4] = diagnostics.try_into().unwrap();
+        let task_diagnostic = TaskDiagnostics {
+            exception,
+            stdout,
+            stderr,
+            structured_logs,
+        };
+        finalize_task_result(
+            &self.state,
+            task_result,
+            output_objects,
+            inline_outputs,
+            task_diagnostic,
+        )
+        .await?;
+        Ok(Response::new(ReportTaskOutcomeResponse {}))
+    }
+
+    async fn watch_invocation(
+        &self,
+        request: Request<WatchInvocationRequest>,
+    ) -> Result<Response<Self::WatchInvocationStream>, Status> {
+        self.authorize(
+            &request,
+            &request.get_ref().namespace,
+            Permission::Read,
+            "WatchInvocation",
+        )?;
+        let request = request.into_inner();
+        let mut rx = self.state.indexify_state.task_event_stream();
+        let ctx = self
+            .state
+            .indexify_state
+            .reader()
+            .invocation_ctx(
+                &request.namespace,
+                &request.compute_graph,
+                &request.invocation_id,
+            )
+            .map_err(|e| Status::not_found(e.to_string()))?;
+        let finished = ctx.completed;
+        let id = request.invocation_id;
+        let stream = async_stream::stream! {
+            if finished {
+                yield Ok(InvocationEvent {
+                    invocation_id: id,
+                    event: Some(Event::InvocationFinished(proto::InvocationFinished {})),
+                });
+                return;
+            }
+            loop {
+                match rx.recv().await {
+                    Ok(ev) => {
+                        if ev.invocation_id() != id && !ev.invocation_id().is_empty() {
+                            continue;
+                        }
+                        let finished = matches!(ev, InvocationStateChangeEvent::InvocationFinished(_));
+                        if let Some(event) = event_from(ev) {
+                            yield Ok(event);
+                        }
+                        if finished {
+                            return;
+                        }
+                    }
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => return,
+                }
+            }
+        };
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
+
+pub async fn serve(
+    addr: SocketAddr,
+    state: RouteState,
+    mut shutdown_rx: watch::Receiver<()>,
+) -> anyhow::Result<()> {
+    info!("server grpc api listening on {}", addr);
+    tonic::transport::Server::builder()
+        .add_service(IndexifyServiceServer::new(GrpcService::new(state)))
+        .serve_with_shutdown(addr, async move {
+            let _ = shutdown_rx.changed().await;
+        })
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use data_model::{
+        test_objects::tests::{create_mock_task, mock_graph_a, TEST_NAMESPACE},
+        QueueLimits,
+    };
+    use sha2::{Digest, Sha256};
+    use state_store::requests::{
+        self,
+        NamespaceRequest,
+        ReductionTasks,
+        RequestPayload,
+        SchedulerUpdateRequest,
+        StateMachineUpdateRequest,
+        TaskPlacement,
+    };
+
+    use super::*;
+    use crate::{
+        auth::{AuthConfig, Role, RoleBinding, StaticIdentity},
+        config::RuntimeConfig,
+        routes::test_route_state::TestRouteState,
+    };
+
+    fn identity(name: &str) -> StaticIdentity {
+        StaticIdentity {
+            name: name.to_string(),
+            token_sha256: hex::encode(Sha256::digest(name.as_bytes())),
+            groups: vec![],
+        }
+    }
+
+    fn binding(namespace: &str, role: Role, subject: &str) -> RoleBinding {
+        RoleBinding {
+            namespace: namespace.to_string(),
+            role,
+            subjects: vec![subject.to_string()],
+            groups: vec![],
+        }
+    }
+
+    // The token of the identities is their name
+    fn request<T>(message: T, token: Option<&str>) -> Request<T> {
+        let mut request = Request::new(message);
+        if let Some(token) = token {
+            request.metadata_mut().insert(
+                "authorization",
+                format!("Bearer {}", token).parse().unwrap(),
+            );
+        }
+        request
+    }
+
+    #[tokio::test]
+    async fn test_grpc_service() -> anyhow::Result<()> {
+        let runtime = RuntimeConfig {
+            auth: Some(AuthConfig {
+                identities: vec![identity("client"), identity("executor")],
+                oidc: None,
+                bindings: vec![
+                    binding(TEST_NAMESPACE, Role::Editor, "client"),
+                    binding(ALL_NAMESPACES, Role::Executor, "executor"),
+                ],
+            }),
+            ..Default::default()
+        };
+        let test_state = TestRouteState::new(runtime).await?;
+        let indexify_state = test_state.route_state.indexify_state.clone();
+        let write = |payload| {
+            indexify_state.write(StateMachineUpdateRequest {
+                payload,
+                state_changes_processed: vec![],
+            })
+        };
+        write(RequestPayload::CreateNameSpace(NamespaceRequest {
+            name: TEST_NAMESPACE.to_string(),
+            queue_limits: QueueLimits {
+                max_pending_invocations: Some(1),
+                ..Default::default()
+            },
+            scheduling_weight: None,
+            reserved_capacity: None,
+            replication: None,
+        }))
+        .await?;
+        let cg = mock_graph_a();
+        write(RequestPayload::CreateComputeGraph(
+            requests::CreateComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph: cg.clone(),
+                expected_revision: None,
+                force: false,
+            },
+        ))
+        .await?;
+        let service = GrpcService::new(test_state.route_state.clone());
+
+        // Invocations need the write permission in the namespace
+        let invoke_request = || InvokeComputeGraphRequest {
+            namespace: TEST_NAMESPACE.to_string(),
+            compute_graph: cg.name.clone(),
+            payload: b"input".to_vec(),
+            ..Default::default()
+        };
+        let err = service
+            .invoke_compute_graph(request(invoke_request(), None))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::Unauthenticated);
+        let err = service
+            .invoke_compute_graph(request(invoke_request(), Some("executor")))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::PermissionDenied);
+        let invocation_id = service
+            .invoke_compute_graph(request(invoke_request(), Some("client")))
+            .await?
+            .into_inner()
+            .invocation_id;
+        let ctx = indexify_state.reader().invocation_ctx(
+            TEST_NAMESPACE,
+            &cg.name,
+            &invocation_id,
+        )?;
+        assert!(!ctx.completed);
+
+        // The namespace allows a single pending invocation
+        let err = service
+            .invoke_compute_graph(request(invoke_request(), Some("client")))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::ResourceExhausted);
+
+        let watch_request = |invocation_id: &str| WatchInvocationRequest {
+            namespace: TEST_NAMESPACE.to_string(),
+            compute_graph: cg.name.clone(),
+            invocation_id: invocation_id.to_string(),
+        };
+        let err = service
+            .watch_invocation(request(watch_request("unknown"), Some("client")))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(err.code(), tonic::Code::NotFound);
+        let mut events = service
+            .watch_invocation(request(watch_request(&invocation_id), Some("client")))
+            .await?
+            .into_inner();
+
+        // Tasks are pulled by executors only
+        let executor_id = "executor_1";
+        let get_tasks_request = || GetTasksRequest {
+            executor: Some(proto::ExecutorMetadata {
+                id: executor_id.to_string(),
+                image_name: "test-image".to_string(),
+                protocol_version: data_model::EXECUTOR_PROTOCOL_VERSION,
+                ..Default::default()
+            }),
+        };
+        let err = service
+            .get_tasks(request(get_tasks_request(), Some("client")))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(err.code(), tonic::Code::PermissionDenied);
+        let task = create_mock_task(&cg, "fn_a", "input_key", &invocation_id);
+        write(RequestPayload::SchedulerUpdate(SchedulerUpdateRequest {
+            task_requests: vec![requests::CreateTasksRequest {
+                namespace: cg.namespace.clone(),
+                compute_graph: cg.name.clone(),
+                invocation_id: invocation_id.clone(),
+                tasks: vec![task.clone()],
+                fan_out_cursor: None,
+            }],
+            allocations: vec![TaskPlacement {
+                task: task.clone(),
+                executor: ExecutorId::new(executor_id.to_string()),
+            }],
+            reduction_tasks: ReductionTasks::default(),
+            diagnostic_msgs: vec![],
+            sub_graph_invocations: vec![],
+            preemptions: vec![],
+        }))
+        .await?;
+        let mut tasks = service
+            .get_tasks(request(get_tasks_request(), Some("executor")))
+            .await?
+            .into_inner();
+        let task_list = tasks.next().await.unwrap()?;
+        assert_eq!(task_list.tasks.len(), 1);
+        assert_eq!(task_list.tasks[0].id, task.id.to_string());
+        assert_eq!(task_list.tasks[0].invocation_id, invocation_id);
+        assert!(indexify_state
+            .reader()
+            .get_executor(&ExecutorId::new(executor_id.to_string()))?
+            .is_some());
+
+        // Outcomes are reported by executors only
+        let outcome_request = || ReportTaskOutcomeRequest {
+            namespace: TEST_NAMESPACE.to_string(),
+            compute_graph: cg.name.clone(),
+            compute_fn: "fn_a".to_string(),
+            invocation_id: invocation_id.clone(),
+            task_id: task.id.to_string(),
+            executor_id: executor_id.to_string(),
+            outcome: proto::TaskOutcome::Success.into(),
+            fn_outputs: vec![b"output".to_vec()],
+            ..Default::default()
+        };
+        let err = service
+            .report_task_outcome(request(outcome_request(), Some("client")))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::PermissionDenied);
+        service
+            .report_task_outcome(request(outcome_request(), Some("executor")))
+            .await?;
+        let finished_task = indexify_state
+            .reader()
+            .get_task(
+                TEST_NAMESPACE,
+                &cg.name,
+                &invocation_id,
+                "fn_a",
+                &task.id.to_string(),
+            )?
+            .unwrap();
+        assert_eq!(finished_task.outcome, data_model::TaskOutcome::Success);
+        let outputs = indexify_state
+            .reader()
+            .get_task_outputs(TEST_NAMESPACE, &task.id.to_string())?;
+        assert_eq!(outputs.len(), 1);
+
+        // The watcher saw the task created, assigned and completed
+        let mut watched = Vec::new();
+        while watched.len() < 3 {
+            let event = events.next().await.unwrap()?;
+            assert_eq!(event.invocation_id, invocation_id);
+            watched.push(event.event.unwrap());
+        }
+        assert!(matches!(&watched[0], Event::TaskCreated(ev) if ev.task_id == task.id.to_string()));
+        assert!(
+            matches!(&watched[1], Event::TaskAssigned(ev) if ev.executor_id == executor_id)
+        );
+        let Event::TaskCompleted(completed) = &watched[2] else {
+            panic!("expected the task to complete");
+        };
+        assert_eq!(completed.outcome(), proto::TaskOutcome::Success);
+        Ok(())
+    }
+}
@@ -1,19 +1,32 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 use axum::{
-    http::StatusCode,
+    http::{header, StatusCode},
     response::{IntoResponse, Response},
 };
-use data_model::ComputeGraphCode;
+use data_model::{
+    attributes::validate_attributes,
+    error::DataModelError,
+    filter::{Condition, LabelsFilter},
+    ComputeGraphCode,
+};
 use indexify_utils::get_epoch_time_in_ms;
 use serde::{Deserialize, Serialize};
+use state_store::error::StateStoreError;
 use utoipa::ToSchema;
 
+use crate::auth::{Permission, Role};
+
 #[derive(Debug, ToSchema, Serialize, Deserialize)]
 pub struct IndexifyAPIError {
     #[serde(skip)]
     status_code: StatusCode,
     message: String,
+    #[serde(skip)]
+    retry_after_secs: Option<u64>,
+    // Returned as a JSON body instead of the message
+    #[serde(skip)]
+    details: Option<serde_json::Value>,
 }
 
 impl IndexifyAPIError {
@@ -21,6 +34,36 @@ impl IndexifyAPIError {
         Self {
             status_code,
             message: message.to_string(),
+            retry_after_secs: None,
+            details: None,
+        }
+    }
+
+    /// Retriable error, clients should try again after retry_after_secs.
+    pub fn too_many_requests(message: &str, retry_after_secs: u64) -> Self {
+        Self {
+            status_code: StatusCode::TOO_MANY_REQUESTS,
+            message: message.to_string(),
+            retry_after_secs: Some(retry_after_secs),
+            details: None,
+        }
+    }
+
+    /// The payload is larger than the limit of its graph. `size_bytes` is the
+    /// declared size of the payload, or the bytes received when it was cut.
+    pub fn payload_too_large(size_bytes: u64, max_bytes: u64) -> Self {
+        Self {
+            status_code: StatusCode::PAYLOAD_TOO_LARGE,
+            message: format!(
+                "payload of at least {} bytes exceeds the limit of {} bytes",
+                size_bytes, max_bytes
+            ),
+            retry_after_secs: None,
+            details: Some(serde_json::json!({
+                "error": "too_large",
+                "size_bytes": size_bytes,
+                "max_bytes": max_bytes,
+            })),
         }
     }
 
@@ -28,8 +71,22 @@ impl IndexifyAPIError {
         Self::new(StatusCode::BAD_REQUEST, e)
     }
 
+    /// Maps the typed errors of the data model and the state store to their
+    /// status codes, any other error is an internal error.
     pub fn internal_error(e: anyhow::Error) -> Self {
-        Self::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string().as_str())
+        let status_code = if e.downcast_ref::<DataModelError>().is_some() {
+            StatusCode::BAD_REQUEST
+        } else {
+            match e.downcast_ref::<StateStoreError>() {
+                Some(StateStoreError::NotFound(_)) => StatusCode::NOT_FOUND,
+                Some(StateStoreError::Conflict(_)) => StatusCode::CONFLICT,
+                Some(StateStoreError::ReadOnly) |
+                Some(StateStoreError::Maintenance) |
+                Some(StateStoreError::NotAcknowledged) => StatusCode::SERVICE_UNAVAILABLE,
+                None => StatusCode::INTERNAL_SERVER_ERROR,
+            }
+        };
+        Self::new(status_code, e.to_string().as_str())
     }
 
     pub fn internal_error_str(e: &str) -> Self {
@@ -43,15 +100,48 @@ impl IndexifyAPIError {
     pub fn bad_request(message: &str) -> Self {
         Self::new(StatusCode::BAD_REQUEST, message)
     }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
 }
 
 impl IntoResponse for IndexifyAPIError {
     fn into_response(self) -> Response {
         tracing::error!("API Error: {} - {}", self.status_code, self.message);
+        if let Some(retry_after_secs) = self.retry_after_secs {
+            return (
+                self.status_code,
+                [(header::RETRY_AFTER, retry_after_secs.to_string())],
+                self.message,
+            )
+                .into_response();
+        }
+        if let Some(details) = self.details {
+            return (self.status_code, axum::Json(details)).into_response();
+        }
         (self.status_code, self.message).into_response()
     }
 }
 
+impl From<IndexifyAPIError> for tonic::Status {
+    fn from(err: IndexifyAPIError) -> Self {
+        let code = match err.status_code {
+            StatusCode::BAD_REQUEST => tonic::Code::InvalidArgument,
+            StatusCode::NOT_FOUND => tonic::Code::NotFound,
+            StatusCode::UNAUTHORIZED => tonic::Code::Unauthenticated,
+            StatusCode::FORBIDDEN => tonic::Code::PermissionDenied,
+            StatusCode::CONFLICT => tonic::Code::AlreadyExists,
+            StatusCode::TOO_MANY_REQUESTS | StatusCode::PAYLOAD_TOO_LARGE => {
+                tonic::Code::ResourceExhausted
+            }
+            StatusCode::SERVICE_UNAVAILABLE => tonic::Code::Unavailable,
+            _ => tonic::Code::Internal,
+        };
+        tonic::Status::new(code, err.message)
+    }
+}
+
 impl From<serde_json::Error> for IndexifyAPIError {
     fn from(e: serde_json::Error) -> Self {
         Self::bad_request(&e.to_string())
@@ -64,10 +154,146 @@ pub struct ListParams {
     pub cursor: Option<Vec<u8>>,
 }
 
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone, Default)]
+pub struct QueueLimits {
+    pub max_pending_invocations: Option<u64>,
+    pub max_unallocated_tasks: Option<u64>,
+    /// Maximum size of invocation payloads and function outputs
+    pub max_payload_bytes: Option<u64>,
+}
+
+/// Share of the invocations completing successfully within a duration, over
+/// a rolling window
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct GraphSla {
+    pub max_duration_secs: u64,
+    /// Fraction of the invocations meeting the SLA, e.g. 0.95
+    pub target: f64,
+    #[serde(default = "default_sla_window_secs")]
+    pub window_secs: u64,
+}
+
+fn default_sla_window_secs() -> u64 {
+    60 * 60
+}
+
+impl From<GraphSla> for data_model::sla::GraphSla {
+    fn from(val: GraphSla) -> Self {
+        data_model::sla::GraphSla {
+            max_duration_secs: val.max_duration_secs,
+            target: val.target,
+            window_secs: val.window_secs,
+        }
+    }
+}
+
+impl From<data_model::sla::GraphSla> for GraphSla {
+    fn from(val: data_model::sla::GraphSla) -> Self {
+        Self {
+            max_duration_secs: val.max_duration_secs,
+            target: val.target,
+            window_secs: val.window_secs,
+        }
+    }
+}
+
+/// Parameter of a graph, referenced by function configs as
+/// `{params.<name>}`
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema, Clone)]
+pub struct GraphParameter {
+    #[serde(default)]
+    pub description: String,
+    /// Parameters without a default must be bound when the graph is
+    /// instantiated or passed by each invocation
+    #[serde(default)]
+    pub default: Option<serde_json::Value>,
+}
+
+impl From<GraphParameter> for data_model::parameters::GraphParameter {
+    fn from(val: GraphParameter) -> Self {
+        data_model::parameters::GraphParameter {
+            description: val.description,
+            default: val.default,
+        }
+    }
+}
+
+impl From<data_model::parameters::GraphParameter> for GraphParameter {
+    fn from(val: data_model::parameters::GraphParameter) -> Self {
+        Self {
+            description: val.description,
+            default: val.default,
+        }
+    }
+}
+
+/// Creates a compute graph from a template graph with the values of its
+/// parameters bound
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct InstantiateComputeGraph {
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub parameters: HashMap<String, serde_json::Value>,
+    /// Current revision of the instance when re-instantiating it
+    pub expected_revision: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SlaStatus {
+    pub sla: GraphSla,
+    /// Invocations finished in the window, and running invocations which
+    /// already exceeded the maximum duration
+    pub invocations: u64,
+    pub met: u64,
+    pub attainment: Option<f64>,
+    pub breached: bool,
+    pub evaluated_at: u64,
+}
+
+impl From<data_model::sla::SlaStatus> for SlaStatus {
+    fn from(status: data_model::sla::SlaStatus) -> Self {
+        Self {
+            sla: status.sla.into(),
+            invocations: status.invocations,
+            met: status.met,
+            attainment: status.attainment,
+            breached: status.breached,
+            evaluated_at: status.evaluated_at,
+        }
+    }
+}
+
+impl From<QueueLimits> for data_model::QueueLimits {
+    fn from(val: QueueLimits) -> Self {
+        data_model::QueueLimits {
+            max_pending_invocations: val.max_pending_invocations,
+            max_unallocated_tasks: val.max_unallocated_tasks,
+            max_payload_bytes: val.max_payload_bytes,
+        }
+    }
+}
+
+impl From<data_model::QueueLimits> for QueueLimits {
+    fn from(val: data_model::QueueLimits) -> Self {
+        Self {
+            max_pending_invocations: val.max_pending_invocations,
+            max_unallocated_tasks: val.max_unallocated_tasks,
+            max_payload_bytes: val.max_payload_bytes,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct Namespace {
     name: String,
     created_at: u64,
+    queue_limits: QueueLimits,
+    scheduling_weight: u32,
+    reserved_capacity: Option<ReservedCapacity>,
+    replication: Option<ReplicationPolicy>,
+    /// Set while the namespace is being deleted
+    deleted_at: Option<u64>,
 }
 
 impl From<data_model::Namespace> for Namespace {
@@ -75,15 +301,212 @@ impl From<data_model::Namespace> for Namespace {
         Self {
             name: namespace.name,
             created_at: namespace.created_at,
+            queue_limits: namespace.queue_limits.into(),
+            scheduling_weight: namespace.scheduling_weight,
+            reserved_capacity: namespace.reserved_capacity.map(Into::into),
+            replication: namespace.replication.map(Into::into),
+            deleted_at: namespace.deleted_at,
+        }
+    }
+}
+
+/// Executors reserved for the tasks of a namespace, the tasks of other
+/// namespaces are only allocated to them while the namespace has no pending
+/// tasks.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ReservedCapacity {
+    /// Label conditions of the executors which can be reserved, e.g.
+    /// `tier=dedicated`
+    pub selector: Vec<String>,
+    pub min_executors: u32,
+}
+
+impl ReservedCapacity {
+    pub fn into_data_model(self) -> Result<data_model::ReservedCapacity, IndexifyAPIError> {
+        if self.min_executors == 0 {
+            return Err(IndexifyAPIError::bad_request(
+                "reserved_capacity.min_executors must be positive",
+            ));
+        }
+        let selector = self
+            .selector
+            .iter()
+            .map(|condition| Condition::from_str(condition))
+            .collect::<anyhow::Result<Vec<_>>>()
+            .map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))?;
+        Ok(data_model::ReservedCapacity {
+            selector: LabelsFilter(selector),
+            min_executors: self.min_executors,
+        })
+    }
+}
+
+/// Regions the payloads of a namespace are copied to in the background, each
+/// region must have a replica storage configured. Executors download the
+/// copy of the region in their `region` label.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ReplicationPolicy {
+    pub regions: Vec<String>,
+}
+
+impl From<data_model::ReplicationPolicy> for ReplicationPolicy {
+    fn from(policy: data_model::ReplicationPolicy) -> Self {
+        Self {
+            regions: policy.regions,
+        }
+    }
+}
+
+impl From<ReplicationPolicy> for data_model::ReplicationPolicy {
+    fn from(policy: ReplicationPolicy) -> Self {
+        Self {
+            regions: policy.regions,
+        }
+    }
+}
+
+impl From<data_model::ReservedCapacity> for ReservedCapacity {
+    fn from(reserved: data_model::ReservedCapacity) -> Self {
+        Self {
+            selector: reserved
+                .selector
+                .conditions()
+                .iter()
+                .map(|condition| condition.to_string())
+                .collect(),
+            min_executors: reserved.min_executors,
         }
     }
 }
 
+#[derive(Debug, Deserialize)]
+pub struct DeleteNamespaceParams {
+    pub confirmation_token: Option<String>,
+}
+
+/// Deletion state of a namespace. The confirmation token must be passed to
+/// delete it, its compute graphs and invocations are then purged in the
+/// background.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct NamespaceDeletion {
+    pub namespace: String,
+    /// Unset once the namespace is being deleted
+    pub confirmation_token: Option<String>,
+    pub deleted_at: Option<u64>,
+    /// Compute graphs and invocations left to purge
+    pub remaining_compute_graphs: u64,
+    pub remaining_invocations: u64,
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct NamespaceList {
     pub namespaces: Vec<Namespace>,
 }
 
+/// Bytes stored in the blob storage, by kind of data
+#[derive(Debug, Serialize, Deserialize, ToSchema, Default)]
+pub struct StorageUsage {
+    pub code_bytes: u64,
+    pub ingestion_bytes: u64,
+    pub output_bytes: u64,
+    pub total_bytes: u64,
+}
+
+impl From<data_model::StorageUsage> for StorageUsage {
+    fn from(usage: data_model::StorageUsage) -> Self {
+        Self {
+            total_bytes: usage.total_bytes(),
+            code_bytes: usage.code_bytes,
+            ingestion_bytes: usage.ingestion_bytes,
+            output_bytes: usage.output_bytes,
+        }
+    }
+}
+
+/// Resources used by finished tasks, durations are measured from the
+/// allocation of the tasks and CPU/GPU time is reported by executors
+#[derive(Debug, Serialize, Deserialize, ToSchema, Default, Clone)]
+pub struct TaskUsage {
+    pub tasks: u64,
+    pub duration_ms: u64,
+    pub input_bytes: u64,
+    pub output_bytes: u64,
+    pub cpu_seconds: f64,
+    pub gpu_seconds: f64,
+    /// Tasks started in a newly loaded function environment, and in a warm
+    /// one
+    pub cold_starts: u64,
+    pub warm_starts: u64,
+    pub failed_tasks: u64,
+}
+
+impl From<data_model::TaskUsage> for TaskUsage {
+    fn from(usage: data_model::TaskUsage) -> Self {
+        Self {
+            tasks: usage.tasks,
+            duration_ms: usage.duration_ms,
+            input_bytes: usage.input_bytes,
+            output_bytes: usage.output_bytes,
+            cpu_seconds: usage.cpu_seconds,
+            gpu_seconds: usage.gpu_seconds,
+            cold_starts: usage.cold_starts,
+            warm_starts: usage.warm_starts,
+            failed_tasks: usage.failed_tasks,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct GraphStorageUsage {
+    pub compute_graph: String,
+    pub usage: StorageUsage,
+    #[serde(default)]
+    pub task_usage: TaskUsage,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct NamespaceUsage {
+    pub namespace: String,
+    pub usage: StorageUsage,
+    #[serde(default)]
+    pub task_usage: TaskUsage,
+    pub compute_graphs: Vec<GraphStorageUsage>,
+}
+
+impl NamespaceUsage {
+    pub fn from_data_model(
+        namespace: String,
+        graphs: Vec<data_model::GraphStorageUsage>,
+        task_usage: Vec<data_model::GraphTaskUsage>,
+    ) -> Self {
+        let mut total = data_model::StorageUsage::default();
+        let mut total_task_usage = data_model::TaskUsage::default();
+        let mut by_graph: BTreeMap<String, (data_model::StorageUsage, data_model::TaskUsage)> =
+            BTreeMap::new();
+        for graph in graphs {
+            total.add(&graph.usage);
+            by_graph.entry(graph.compute_graph).or_default().0 = graph.usage;
+        }
+        for graph in task_usage {
+            total_task_usage.add(&graph.usage);
+            by_graph.entry(graph.compute_graph).or_default().1 = graph.usage;
+        }
+        Self {
+            namespace,
+            usage: total.into(),
+            task_usage: total_task_usage.into(),
+            compute_graphs: by_graph
+                .into_iter()
+                .map(|(compute_graph, (usage, task_usage))| GraphStorageUsage {
+                    compute_graph,
+                    usage: usage.into(),
+                    task_usage: task_usage.into(),
+                })
+                .collect(),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
 pub struct ComputeFn {
     pub name: String,
@@ -92,6 +515,32 @@ pub struct ComputeFn {
     pub reducer: bool,
     pub payload_encoder: String,
     pub image_name: String,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    #[serde(default)]
+    pub secrets: HashMap<String, String>,
+    #[serde(default)]
+    pub rate_limit: Option<RateLimit>,
+    #[serde(default)]
+    pub executor_pool: Option<String>,
+    /// GPUs reserved for each task of the function, e.g.
+    /// `{"model": "A10G", "count": 1}`
+    #[serde(default)]
+    pub gpu: Option<GpuRequirement>,
+    /// Tasks of the function with the same key never run at the same time,
+    /// e.g. `customer-{labels.customer_id}`
+    #[serde(default)]
+    pub concurrency_key: Option<String>,
+    /// Tasks of the function only run on executors advertising this runtime
+    #[serde(default)]
+    pub runtime: Option<RuntimeDescriptor>,
+    /// Tasks emitting more outputs fail and their outputs are dropped
+    #[serde(default)]
+    pub output_limits: Option<OutputLimits>,
+    /// Environments of the function executors keep loaded to start its tasks
+    /// without loading it, spread across the executors it can run on
+    #[serde(default)]
+    pub min_warm_instances: u32,
 }
 
 impl From<&ComputeFn> for data_model::ComputeFn {
@@ -104,6 +553,15 @@ impl From<&ComputeFn> for data_model::ComputeFn {
             reducer: val.reducer,
             payload_encoder: val.payload_encoder.clone(),
             image_name: val.image_name.clone(),
+            env: val.env.clone(),
+            secrets: val.secrets.clone(),
+            rate_limit: val.rate_limit.clone().map(Into::into),
+            executor_pool: val.executor_pool.clone(),
+            gpu: val.gpu.clone().map(Into::into),
+            concurrency_key: val.concurrency_key.clone(),
+            runtime: val.runtime.clone().map(Into::into),
+            output_limits: val.output_limits.clone().map(Into::into),
+            min_warm_instances: val.min_warm_instances,
         }
     }
 }
@@ -118,6 +576,15 @@ impl From<ComputeFn> for data_model::ComputeFn {
             reducer: val.reducer,
             payload_encoder: val.payload_encoder.clone(),
             image_name: val.image_name.clone(),
+            env: val.env.clone(),
+            secrets: val.secrets.clone(),
+            rate_limit: val.rate_limit.clone().map(Into::into),
+            executor_pool: val.executor_pool.clone(),
+            gpu: val.gpu.clone().map(Into::into),
+            concurrency_key: val.concurrency_key.clone(),
+            runtime: val.runtime.clone().map(Into::into),
+            output_limits: val.output_limits.clone().map(Into::into),
+            min_warm_instances: val.min_warm_instances,
         }
     }
 }
@@ -131,10 +598,140 @@ impl From<data_model::ComputeFn> for ComputeFn {
             reducer: c.reducer,
             payload_encoder: c.payload_encoder,
             image_name: c.image_name,
+            env: c.env,
+            secrets: redact_secrets(c.secrets),
+            rate_limit: c.rate_limit.map(Into::into),
+            executor_pool: c.executor_pool,
+            gpu: c.gpu.map(Into::into),
+            concurrency_key: c.concurrency_key,
+            runtime: c.runtime.map(Into::into),
+            output_limits: c.output_limits.map(Into::into),
+            min_warm_instances: c.min_warm_instances,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct RateLimit {
+    pub max_tasks: u32,
+    pub window_secs: u64,
+}
+
+impl From<RateLimit> for data_model::RateLimit {
+    fn from(val: RateLimit) -> Self {
+        data_model::RateLimit {
+            max_tasks: val.max_tasks,
+            window_secs: val.window_secs,
+        }
+    }
+}
+
+impl From<data_model::RateLimit> for RateLimit {
+    fn from(val: data_model::RateLimit) -> Self {
+        Self {
+            max_tasks: val.max_tasks,
+            window_secs: val.window_secs,
+        }
+    }
+}
+
+/// Bounds the outputs of a single task of the function, the outputs it
+/// streamed included
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct OutputLimits {
+    #[serde(default)]
+    pub max_outputs: Option<u64>,
+    #[serde(default)]
+    pub max_output_bytes: Option<u64>,
+}
+
+impl From<OutputLimits> for data_model::OutputLimits {
+    fn from(val: OutputLimits) -> Self {
+        data_model::OutputLimits {
+            max_outputs: val.max_outputs,
+            max_output_bytes: val.max_output_bytes,
+        }
+    }
+}
+
+impl From<data_model::OutputLimits> for OutputLimits {
+    fn from(val: data_model::OutputLimits) -> Self {
+        Self {
+            max_outputs: val.max_outputs,
+            max_output_bytes: val.max_output_bytes,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct GpuRequirement {
+    /// Any GPU model is accepted when not set
+    #[serde(default)]
+    pub model: Option<String>,
+    pub count: u32,
+}
+
+impl From<GpuRequirement> for data_model::GpuRequirement {
+    fn from(val: GpuRequirement) -> Self {
+        data_model::GpuRequirement {
+            model: val.model,
+            count: val.count,
         }
     }
 }
 
+impl From<data_model::GpuRequirement> for GpuRequirement {
+    fn from(val: data_model::GpuRequirement) -> Self {
+        Self {
+            model: val.model,
+            count: val.count,
+        }
+    }
+}
+
+/// Sandbox a function runs in, e.g.
+/// `{"image": "tensorlake/pdf:1.2", "python_version": "3.11"}`
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct RuntimeDescriptor {
+    /// Container image, including its tag
+    pub image: String,
+    #[serde(default)]
+    pub python_version: Option<String>,
+    /// Hash of the pip requirements installed in the image
+    #[serde(default)]
+    pub pip_hash: Option<String>,
+}
+
+impl From<RuntimeDescriptor> for data_model::RuntimeDescriptor {
+    fn from(val: RuntimeDescriptor) -> Self {
+        data_model::RuntimeDescriptor {
+            image: val.image,
+            python_version: val.python_version,
+            pip_hash: val.pip_hash,
+        }
+    }
+}
+
+impl From<data_model::RuntimeDescriptor> for RuntimeDescriptor {
+    fn from(val: data_model::RuntimeDescriptor) -> Self {
+        Self {
+            image: val.image,
+            python_version: val.python_version,
+            pip_hash: val.pip_hash,
+        }
+    }
+}
+
+pub const REDACTED_SECRET: &str = "<redacted>";
+
+// Secrets are write only, the API only reveals which secrets are set.
+fn redact_secrets(secrets: HashMap<String, String>) -> HashMap<String, String> {
+    secrets
+        .into_keys()
+        .map(|name| (name, REDACTED_SECRET.to_string()))
+        .collect()
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
 pub struct DynamicRouter {
     pub name: String,
@@ -143,6 +740,48 @@ pub struct DynamicRouter {
     pub target_fns: Vec<String>,
     pub payload_encoder: String,
     pub image_name: String,
+    /// Hash of the WASM module the server runs the router with, modules are
+    /// uploaded with the graph code
+    #[serde(default, skip_deserializing)]
+    pub wasm_module_sha256: Option<String>,
+    /// Targets the input is routed to when the router selects none
+    #[serde(default)]
+    pub default_targets: Vec<String>,
+    #[serde(default)]
+    pub on_error: RouterErrorPolicy,
+}
+
+/// What happens to the branch of a router which failed
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RouterErrorPolicy {
+    /// The invocation fails
+    #[default]
+    FailInvocation,
+    /// The input is routed to the default targets
+    RouteToDefault,
+    /// The branch ends without failing the invocation
+    Skip,
+}
+
+impl From<RouterErrorPolicy> for data_model::RouterErrorPolicy {
+    fn from(policy: RouterErrorPolicy) -> Self {
+        match policy {
+            RouterErrorPolicy::FailInvocation => data_model::RouterErrorPolicy::FailInvocation,
+            RouterErrorPolicy::RouteToDefault => data_model::RouterErrorPolicy::RouteToDefault,
+            RouterErrorPolicy::Skip => data_model::RouterErrorPolicy::Skip,
+        }
+    }
+}
+
+impl From<data_model::RouterErrorPolicy> for RouterErrorPolicy {
+    fn from(policy: data_model::RouterErrorPolicy) -> Self {
+        match policy {
+            data_model::RouterErrorPolicy::FailInvocation => RouterErrorPolicy::FailInvocation,
+            data_model::RouterErrorPolicy::RouteToDefault => RouterErrorPolicy::RouteToDefault,
+            data_model::RouterErrorPolicy::Skip => RouterErrorPolicy::Skip,
+        }
+    }
 }
 
 impl From<DynamicRouter> for data_model::DynamicEdgeRouter {
@@ -154,6 +793,9 @@ impl From<DynamicRouter> for data_model::DynamicEdgeRouter {
             target_functions: val.target_fns.clone(),
             payload_encoder: val.payload_encoder.clone(),
             image_name: val.image_name.clone(),
+            wasm_module: None,
+            default_targets: val.default_targets.clone(),
+            on_error: val.on_error.into(),
         }
     }
 }
@@ -167,86 +809,472 @@ impl From<data_model::DynamicEdgeRouter> for DynamicRouter {
             target_fns: d.target_functions,
             payload_encoder: d.payload_encoder,
             image_name: d.image_name,
+            wasm_module_sha256: d.wasm_module.map(|module| module.sha256_hash),
+            default_targets: d.default_targets,
+            on_error: d.on_error.into(),
         }
     }
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
-pub enum Node {
-    #[serde(rename = "dynamic_router")]
-    DynamicRouter(DynamicRouter),
-    #[serde(rename = "compute_fn")]
-    ComputeFn(ComputeFn),
+pub struct SubGraph {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Name of the compute graph of the namespace to invoke
+    pub compute_graph: String,
 }
 
-impl Node {
-    pub fn name(&self) -> String {
-        match self {
-            Node::DynamicRouter(d) => d.name.clone(),
-            Node::ComputeFn(c) => c.name.clone(),
+impl From<SubGraph> for data_model::SubGraph {
+    fn from(val: SubGraph) -> Self {
+        data_model::SubGraph {
+            name: val.name,
+            description: val.description,
+            compute_graph: val.compute_graph,
         }
     }
 }
 
-impl From<Node> for data_model::Node {
-    fn from(val: Node) -> Self {
-        match val {
-            Node::DynamicRouter(d) => data_model::Node::Router(d.into()),
-            Node::ComputeFn(c) => data_model::Node::Compute(c.into()),
+impl From<data_model::SubGraph> for SubGraph {
+    fn from(s: data_model::SubGraph) -> Self {
+        Self {
+            name: s.name,
+            description: s.description,
+            compute_graph: s.compute_graph,
         }
     }
 }
 
-impl From<data_model::Node> for Node {
-    fn from(node: data_model::Node) -> Self {
-        match node {
-            data_model::Node::Router(d) => Node::DynamicRouter(d.into()),
-            data_model::Node::Compute(c) => Node::ComputeFn(c.into()),
+/// Pauses a branch of an invocation until the gate is signaled, the payload of
+/// the signal becomes the output of the node
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct Gate {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+impl From<Gate> for data_model::Gate {
+    fn from(val: Gate) -> Self {
+        data_model::Gate {
+            name: val.name,
+            description: val.description,
         }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, ToSchema)]
-pub struct RuntimeInformation {
-    pub major_version: u8,
-    pub minor_version: u8,
+impl From<data_model::Gate> for Gate {
+    fn from(g: data_model::Gate) -> Self {
+        Self {
+            name: g.name,
+            description: g.description,
+        }
+    }
 }
 
-impl From<RuntimeInformation> for data_model::RuntimeInformation {
-    fn from(value: RuntimeInformation) -> Self {
-        data_model::RuntimeInformation {
-            major_version: value.major_version,
-            minor_version: value.minor_version,
+/// Accumulates its inputs across invocations of the graph and emits them
+/// together once max_items inputs arrived or the oldest waited max_age_secs
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct Window {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub max_items: Option<u32>,
+    #[serde(default)]
+    pub max_age_secs: Option<u64>,
+    /// Groups the inputs in tumbling windows of this many seconds by the
+    /// event time of their invocation
+    #[serde(default)]
+    pub event_time_secs: Option<u64>,
+    /// How far the watermark trails the latest event time seen
+    #[serde(default)]
+    pub allowed_lateness_secs: u64,
+    /// Function the inputs arriving after their window closed are routed to
+    #[serde(default)]
+    pub late_output: Option<String>,
+}
+
+impl From<Window> for data_model::Window {
+    fn from(val: Window) -> Self {
+        data_model::Window {
+            name: val.name,
+            description: val.description,
+            max_items: val.max_items,
+            max_age_secs: val.max_age_secs,
+            event_time_secs: val.event_time_secs,
+            allowed_lateness_secs: val.allowed_lateness_secs,
+            late_output: val.late_output,
         }
     }
 }
 
-impl From<data_model::RuntimeInformation> for RuntimeInformation {
-    fn from(value: data_model::RuntimeInformation) -> Self {
+impl From<data_model::Window> for Window {
+    fn from(w: data_model::Window) -> Self {
         Self {
-            major_version: value.major_version,
-            minor_version: value.minor_version,
+            name: w.name,
+            description: w.description,
+            max_items: w.max_items,
+            max_age_secs: w.max_age_secs,
+            event_time_secs: w.event_time_secs,
+            allowed_lateness_secs: w.allowed_lateness_secs,
+            late_output: w.late_output,
         }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, ToSchema)]
-pub struct ComputeGraph {
-    pub name: String,
-    pub namespace: String,
-    pub description: String,
-    pub start_node: Node,
-    pub nodes: HashMap<String, Node>,
-    pub edges: HashMap<String, Vec<String>>,
-    #[serde(default = "get_epoch_time_in_ms")]
-    pub created_at: u64,
-    pub runtime_information: RuntimeInformation,
+/// Index a sink writes to, credentials are secrets of the graph
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum SinkConnector {
+    Postgres {
+        table: String,
+        /// Secret holding the connection string
+        dsn_secret: String,
+    },
+    Qdrant {
+        url: String,
+        collection: String,
+        #[serde(default)]
+        api_key_secret: Option<String>,
+    },
+    #[serde(rename = "lancedb")]
+    LanceDb {
+        url: String,
+        database: String,
+        table: String,
+        #[serde(default)]
+        api_key_secret: Option<String>,
+    },
+    Elasticsearch {
+        url: String,
+        index: String,
+        #[serde(default)]
+        api_key_secret: Option<String>,
+    },
 }
 
-impl ComputeGraph {
-    pub fn into_data_model(
-        self,
-        code_path: &str,
+impl From<SinkConnector> for data_model::sinks::SinkConnector {
+    fn from(val: SinkConnector) -> Self {
+        match val {
+            SinkConnector::Postgres { table, dsn_secret } => {
+                data_model::sinks::SinkConnector::Postgres { table, dsn_secret }
+            }
+            SinkConnector::Qdrant {
+                url,
+                collection,
+                api_key_secret,
+            } => data_model::sinks::SinkConnector::Qdrant {
+                url,
+                collection,
+                api_key_secret,
+            },
+            SinkConnector::LanceDb {
+                url,
+                database,
+                table,
+                api_key_secret,
+            } => data_model::sinks::SinkConnector::LanceDb {
+                url,
+                database,
+                table,
+                api_key_secret,
+            },
+            SinkConnector::Elasticsearch {
+                url,
+                index,
+                api_key_secret,
+            } => data_model::sinks::SinkConnector::Elasticsearch {
+                url,
+                index,
+                api_key_secret,
+            },
+        }
+    }
+}
+
+impl From<data_model::sinks::SinkConnector> for SinkConnector {
+    fn from(c: data_model::sinks::SinkConnector) -> Self {
+        match c {
+            data_model::sinks::SinkConnector::Postgres { table, dsn_secret } => {
+                SinkConnector::Postgres { table, dsn_secret }
+            }
+            data_model::sinks::SinkConnector::Qdrant {
+                url,
+                collection,
+                api_key_secret,
+            } => SinkConnector::Qdrant {
+                url,
+                collection,
+                api_key_secret,
+            },
+            data_model::sinks::SinkConnector::LanceDb {
+                url,
+                database,
+                table,
+                api_key_secret,
+            } => SinkConnector::LanceDb {
+                url,
+                database,
+                table,
+                api_key_secret,
+            },
+            data_model::sinks::SinkConnector::Elasticsearch {
+                url,
+                index,
+                api_key_secret,
+            } => SinkConnector::Elasticsearch {
+                url,
+                index,
+                api_key_secret,
+            },
+        }
+    }
+}
+
+/// Writes its inputs, JSON records or arrays of records, to an index from the
+/// server
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct Sink {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub connector: SinkConnector,
+    /// Field of the records holding their id, derived from the output when
+    /// missing
+    #[serde(default = "default_sink_id_field")]
+    pub id_field: String,
+    /// Field of the records holding their embedding
+    #[serde(default = "default_sink_vector_field")]
+    pub vector_field: String,
+}
+
+fn default_sink_id_field() -> String {
+    "id".to_string()
+}
+
+fn default_sink_vector_field() -> String {
+    "embedding".to_string()
+}
+
+impl From<Sink> for data_model::sinks::Sink {
+    fn from(val: Sink) -> Self {
+        data_model::sinks::Sink {
+            name: val.name,
+            description: val.description,
+            connector: val.connector.into(),
+            id_field: val.id_field,
+            vector_field: val.vector_field,
+        }
+    }
+}
+
+impl From<data_model::sinks::Sink> for Sink {
+    fn from(s: data_model::sinks::Sink) -> Self {
+        Self {
+            name: s.name,
+            description: s.description,
+            connector: s.connector.into(),
+            id_field: s.id_field,
+            vector_field: s.vector_field,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub enum Node {
+    #[serde(rename = "dynamic_router")]
+    DynamicRouter(DynamicRouter),
+    #[serde(rename = "compute_fn")]
+    ComputeFn(ComputeFn),
+    #[serde(rename = "sub_graph")]
+    SubGraph(SubGraph),
+    #[serde(rename = "gate")]
+    Gate(Gate),
+    #[serde(rename = "window")]
+    Window(Window),
+    #[serde(rename = "sink")]
+    Sink(Sink),
+}
+
+impl Node {
+    pub fn name(&self) -> String {
+        match self {
+            Node::DynamicRouter(d) => d.name.clone(),
+            Node::ComputeFn(c) => c.name.clone(),
+            Node::SubGraph(s) => s.name.clone(),
+            Node::Gate(g) => g.name.clone(),
+            Node::Window(w) => w.name.clone(),
+            Node::Sink(s) => s.name.clone(),
+        }
+    }
+}
+
+impl From<Node> for data_model::Node {
+    fn from(val: Node) -> Self {
+        match val {
+            Node::DynamicRouter(d) => data_model::Node::Router(d.into()),
+            Node::ComputeFn(c) => data_model::Node::Compute(c.into()),
+            Node::SubGraph(s) => data_model::Node::SubGraph(s.into()),
+            Node::Gate(g) => data_model::Node::Gate(g.into()),
+            Node::Window(w) => data_model::Node::Window(w.into()),
+            Node::Sink(s) => data_model::Node::Sink(s.into()),
+        }
+    }
+}
+
+impl From<data_model::Node> for Node {
+    fn from(node: data_model::Node) -> Self {
+        match node {
+            data_model::Node::Router(d) => Node::DynamicRouter(d.into()),
+            data_model::Node::Compute(c) => Node::ComputeFn(c.into()),
+            data_model::Node::SubGraph(s) => Node::SubGraph(s.into()),
+            data_model::Node::Gate(g) => Node::Gate(g.into()),
+            data_model::Node::Window(w) => Node::Window(w.into()),
+            data_model::Node::Sink(s) => Node::Sink(s.into()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RuntimeInformation {
+    pub major_version: u8,
+    pub minor_version: u8,
+}
+
+impl From<RuntimeInformation> for data_model::RuntimeInformation {
+    fn from(value: RuntimeInformation) -> Self {
+        data_model::RuntimeInformation {
+            major_version: value.major_version,
+            minor_version: value.minor_version,
+        }
+    }
+}
+
+impl From<data_model::RuntimeInformation> for RuntimeInformation {
+    fn from(value: data_model::RuntimeInformation) -> Self {
+        Self {
+            major_version: value.major_version,
+            minor_version: value.minor_version,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ComputeGraph {
+    pub name: String,
+    pub namespace: String,
+    pub description: String,
+    pub start_node: Node,
+    pub nodes: HashMap<String, Node>,
+    pub edges: HashMap<String, Vec<String>>,
+    #[serde(default = "get_epoch_time_in_ms")]
+    pub created_at: u64,
+    pub runtime_information: RuntimeInformation,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    #[serde(default)]
+    pub secrets: HashMap<String, String>,
+    #[serde(default)]
+    pub queue_limits: QueueLimits,
+    #[serde(default)]
+    pub sla: Option<GraphSla>,
+    // Set by the server, sent back as expected_revision when updating
+    #[serde(default)]
+    pub revision: u64,
+    // Set by the server for deleted graphs
+    #[serde(default)]
+    pub deleted_at: Option<u64>,
+    // Set by the server for paused graphs
+    #[serde(default)]
+    pub paused: Option<GraphPause>,
+    #[serde(default)]
+    pub parameters: HashMap<String, GraphParameter>,
+    // Set by the server for graphs instantiated from a template
+    #[serde(default)]
+    pub parameter_values: HashMap<String, serde_json::Value>,
+    #[serde(default)]
+    pub template: Option<String>,
+    /// Transforms the server applies to invocation payloads, in order, before
+    /// the first task of the invocation is created
+    #[serde(default)]
+    pub ingestion_hooks: Vec<IngestionTransform>,
+    /// Lane of the invocations which don't choose one
+    #[serde(default)]
+    pub default_lane: SchedulingLane,
+    /// Stores the payloads of the graph uncompressed, for media which is
+    /// already compressed
+    #[serde(default)]
+    pub skip_payload_compression: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SchedulingLane {
+    /// Interactive invocations of fresh data, allocated ahead of the batch
+    /// lane and able to use the executor slots reserved for them
+    #[default]
+    Latency,
+    /// Replays and backfills
+    Batch,
+}
+
+impl From<SchedulingLane> for data_model::SchedulingLane {
+    fn from(lane: SchedulingLane) -> Self {
+        match lane {
+            SchedulingLane::Latency => data_model::SchedulingLane::Latency,
+            SchedulingLane::Batch => data_model::SchedulingLane::Batch,
+        }
+    }
+}
+
+impl From<data_model::SchedulingLane> for SchedulingLane {
+    fn from(lane: data_model::SchedulingLane) -> Self {
+        match lane {
+            data_model::SchedulingLane::Latency => SchedulingLane::Latency,
+            data_model::SchedulingLane::Batch => SchedulingLane::Batch,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+#[serde(rename_all = "snake_case", tag = "transform")]
+pub enum IngestionTransform {
+    /// Decompresses gzip payloads, other payloads pass through
+    Decompress,
+    /// Splits a zip archive into a part per file, the start node runs once
+    /// per part
+    SplitArchive,
+    /// Removes fields from JSON payloads, nested fields are separated by dots
+    StripJsonFields { fields: Vec<String> },
+}
+
+impl From<IngestionTransform> for data_model::ingestion::IngestionTransform {
+    fn from(val: IngestionTransform) -> Self {
+        match val {
+            IngestionTransform::Decompress => Self::Decompress,
+            IngestionTransform::SplitArchive => Self::SplitArchive,
+            IngestionTransform::StripJsonFields { fields } => Self::StripJsonFields { fields },
+        }
+    }
+}
+
+impl From<data_model::ingestion::IngestionTransform> for IngestionTransform {
+    fn from(val: data_model::ingestion::IngestionTransform) -> Self {
+        match val {
+            data_model::ingestion::IngestionTransform::Decompress => Self::Decompress,
+            data_model::ingestion::IngestionTransform::SplitArchive => Self::SplitArchive,
+            data_model::ingestion::IngestionTransform::StripJsonFields { fields } => {
+                Self::StripJsonFields { fields }
+            }
+        }
+    }
+}
+
+impl ComputeGraph {
+    pub fn into_data_model(
+        self,
+        code_path: &str,
         sha256_hash: &str,
         size: u64,
     ) -> Result<data_model::ComputeGraph, IndexifyAPIError> {
@@ -262,6 +1290,7 @@ impl ComputeGraph {
             description: self.description,
             start_fn,
             version: Default::default(),
+            revision: 0,
             code: ComputeGraphCode {
                 sha256_hash: sha256_hash.to_string(),
                 size,
@@ -271,7 +1300,26 @@ impl ComputeGraph {
             edges: self.edges.clone(),
             created_at: 0,
             runtime_information: self.runtime_information.into(),
+            env: self.env,
+            secrets: self.secrets,
+            queue_limits: self.queue_limits.into(),
+            sla: self.sla.map(Into::into),
+            deleted_at: None,
+            paused: None,
+            parameters: self
+                .parameters
+                .into_iter()
+                .map(|(name, parameter)| (name, parameter.into()))
+                .collect(),
+            parameter_values: HashMap::new(),
+            template: None,
+            ingestion_hooks: self.ingestion_hooks.into_iter().map(Into::into).collect(),
+            default_lane: self.default_lane.into(),
+            skip_payload_compression: self.skip_payload_compression,
         };
+        compute_graph
+            .validate()
+            .map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))?;
         Ok(compute_graph)
     }
 }
@@ -281,6 +1329,10 @@ impl From<data_model::ComputeGraph> for ComputeGraph {
         let start_fn = match compute_graph.start_fn {
             data_model::Node::Router(d) => Node::DynamicRouter(d.into()),
             data_model::Node::Compute(c) => Node::ComputeFn(c.into()),
+            data_model::Node::SubGraph(s) => Node::SubGraph(s.into()),
+            data_model::Node::Gate(g) => Node::Gate(g.into()),
+            data_model::Node::Window(w) => Node::Window(w.into()),
+            data_model::Node::Sink(s) => Node::Sink(s.into()),
         };
         let mut nodes = HashMap::new();
         for (k, v) in compute_graph.nodes.into_iter() {
@@ -295,188 +1347,2468 @@ impl From<data_model::ComputeGraph> for ComputeGraph {
             edges: compute_graph.edges,
             created_at: compute_graph.created_at,
             runtime_information: compute_graph.runtime_information.into(),
+            env: compute_graph.env,
+            secrets: redact_secrets(compute_graph.secrets),
+            queue_limits: compute_graph.queue_limits.into(),
+            sla: compute_graph.sla.map(Into::into),
+            revision: compute_graph.revision,
+            deleted_at: compute_graph.deleted_at,
+            paused: compute_graph.paused.map(Into::into),
+            parameters: compute_graph
+                .parameters
+                .into_iter()
+                .map(|(name, parameter)| (name, parameter.into()))
+                .collect(),
+            parameter_values: compute_graph.parameter_values,
+            template: compute_graph.template,
+            ingestion_hooks: compute_graph
+                .ingestion_hooks
+                .into_iter()
+                .map(Into::into)
+                .collect(),
+            default_lane: compute_graph.default_lane.into(),
+            skip_payload_compression: compute_graph.skip_payload_compression,
         }
     }
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
-pub struct CreateNamespace {
-    pub name: String,
+pub struct GraphPause {
+    pub paused_at: u64,
+    pub reject_invocations: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize, ToSchema)]
-pub struct ComputeGraphsList {
-    pub compute_graphs: Vec<ComputeGraph>,
-    pub cursor: Option<Vec<u8>>,
+impl From<data_model::GraphPause> for GraphPause {
+    fn from(pause: data_model::GraphPause) -> Self {
+        Self {
+            paused_at: pause.paused_at,
+            reject_invocations: pause.reject_invocations,
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize, ToSchema)]
-pub struct DataObject {
-    pub id: String,
-    pub payload_size: u64,
-    pub payload_sha_256: String,
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct PauseComputeGraph {
+    /// Rejects new invocations while the graph is paused
+    #[serde(default)]
+    pub reject_invocations: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct QueryParams {
-    pub input_id: Option<String>,
-    pub on_graph_end: bool,
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SetLegalHold {
+    /// Why the data is held, e.g. the reference of a case
+    pub reason: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct GraphOutputNotification {
-    pub output_id: String,
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct LegalHold {
+    pub namespace: String,
     pub compute_graph: String,
-    pub fn_name: String,
+    /// Every invocation of the graph is held when unset
+    pub invocation_id: Option<String>,
+    pub reason: String,
+    pub set_by: String,
+    pub set_at: u64,
 }
 
-#[derive(Debug, Serialize, Deserialize, ToSchema)]
-pub struct CreateNamespaceResponse {
-    pub name: Namespace,
+impl From<data_model::legal_hold::LegalHold> for LegalHold {
+    fn from(hold: data_model::legal_hold::LegalHold) -> Self {
+        Self {
+            namespace: hold.namespace,
+            compute_graph: hold.compute_graph,
+            invocation_id: hold.invocation_id,
+            reason: hold.reason,
+            set_by: hold.set_by,
+            set_at: hold.set_at,
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
-pub struct GraphInvocations {
-    pub invocations: Vec<DataObject>,
-    pub cursor: Option<Vec<u8>>,
+pub struct LegalHoldsList {
+    pub holds: Vec<LegalHold>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum LegalHoldAction {
+    Set,
+    Cleared,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
-pub struct GraphInputJson {
-    pub payload: serde_json::Value,
+pub struct LegalHoldAuditEntry {
+    pub compute_graph: String,
+    pub invocation_id: Option<String>,
+    pub action: LegalHoldAction,
+    /// Identity which set or cleared the hold
+    pub identity: String,
+    pub reason: String,
+    pub at: u64,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
-pub struct GraphInputFile {
-    // file:///s3://bucket/key
-    // file:///data/path/to/file
-    pub url: String,
-    pub metadata: serde_json::Value,
-    pub sha_256: String,
-    pub size: u64,
+impl From<data_model::legal_hold::LegalHoldAuditEntry> for LegalHoldAuditEntry {
+    fn from(entry: data_model::legal_hold::LegalHoldAuditEntry) -> Self {
+        Self {
+            compute_graph: entry.compute_graph,
+            invocation_id: entry.invocation_id,
+            action: match entry.action {
+                data_model::legal_hold::LegalHoldAction::Set => LegalHoldAction::Set,
+                data_model::legal_hold::LegalHoldAction::Cleared => LegalHoldAction::Cleared,
+            },
+            identity: entry.identity,
+            reason: entry.reason,
+            at: entry.at,
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
-pub struct InvocationResult {
-    pub outputs: HashMap<String, Vec<DataObject>>,
-    pub cursor: Option<String>,
+pub struct LegalHoldAuditLog {
+    pub entries: Vec<LegalHoldAuditEntry>,
+    pub cursor: Option<Vec<u8>>,
 }
 
-#[derive(Debug, Serialize, Deserialize, ToSchema)]
-pub enum TaskOutcome {
-    Unknown,
-    Success,
-    Failure,
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DataDeletionMode {
+    /// The payloads are replaced by tombstones keeping their size and hash,
+    /// the invocations and their outputs can still be listed
+    #[default]
+    Soft,
+    /// The invocations, their tasks and outputs are removed too
+    Hard,
 }
 
-impl From<data_model::TaskOutcome> for TaskOutcome {
-    fn from(outcome: data_model::TaskOutcome) -> Self {
-        match outcome {
-            data_model::TaskOutcome::Unknown => TaskOutcome::Unknown,
-            data_model::TaskOutcome::Success => TaskOutcome::Success,
-            data_model::TaskOutcome::Failure => TaskOutcome::Failure,
+impl From<DataDeletionMode> for data_model::data_deletion::DataDeletionMode {
+    fn from(mode: DataDeletionMode) -> Self {
+        match mode {
+            DataDeletionMode::Soft => data_model::data_deletion::DataDeletionMode::Soft,
+            DataDeletionMode::Hard => data_model::data_deletion::DataDeletionMode::Hard,
         }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, ToSchema)]
-pub struct GraphVersion(pub u32);
-
-impl From<data_model::GraphVersion> for GraphVersion {
-    fn from(version: data_model::GraphVersion) -> Self {
-        Self(version.0)
+impl From<data_model::data_deletion::DataDeletionMode> for DataDeletionMode {
+    fn from(mode: data_model::data_deletion::DataDeletionMode) -> Self {
+        match mode {
+            data_model::data_deletion::DataDeletionMode::Soft => DataDeletionMode::Soft,
+            data_model::data_deletion::DataDeletionMode::Hard => DataDeletionMode::Hard,
+        }
     }
 }
 
-impl From<GraphVersion> for data_model::GraphVersion {
-    fn from(version: GraphVersion) -> Self {
-        Self(version.0)
-    }
+#[derive(Debug, Deserialize)]
+pub struct DeleteIngestedDataParams {
+    #[serde(default)]
+    pub mode: DataDeletionMode,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DataDeletionStatus {
+    /// Blobs are left to remove before the deadline
+    Pending,
+    Completed,
+    /// Blobs are left to remove past the deadline
+    Overdue,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
-pub struct Task {
-    pub id: String,
+pub struct DeletedInvocation {
+    pub compute_graph: String,
+    pub invocation_id: String,
+}
+
+/// Report of the deletion of the data of an ingested invocation
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DataDeletion {
     pub namespace: String,
-    pub compute_fn: String,
     pub compute_graph: String,
     pub invocation_id: String,
-    pub input_key: String,
-    pub outcome: TaskOutcome,
-    pub reducer_output_id: Option<String>,
-    pub graph_version: GraphVersion,
+    pub mode: DataDeletionMode,
+    pub status: DataDeletionStatus,
+    pub requested_by: String,
+    pub requested_at: u64,
+    /// The blobs are removed from the storage by then
+    pub deadline: u64,
+    /// The ingested invocation and the invocations of the sub graphs it ran
+    pub invocations: Vec<DeletedInvocation>,
+    /// Function outputs deleted
+    pub outputs: u64,
+    /// Size of the deleted payloads and outputs
+    pub bytes: u64,
+    pub blobs: u64,
+    /// Blobs the garbage collector has yet to remove
+    pub pending_blobs: u64,
 }
 
-impl From<data_model::Task> for Task {
-    fn from(task: data_model::Task) -> Self {
+impl DataDeletion {
+    pub fn new(
+        deletion: data_model::data_deletion::DataDeletion,
+        pending_blobs: usize,
+        now: u64,
+    ) -> Self {
+        let status = match deletion.status(pending_blobs, now) {
+            data_model::data_deletion::DataDeletionStatus::Pending => DataDeletionStatus::Pending,
+            data_model::data_deletion::DataDeletionStatus::Completed => {
+                DataDeletionStatus::Completed
+            }
+            data_model::data_deletion::DataDeletionStatus::Overdue => DataDeletionStatus::Overdue,
+        };
         Self {
-            id: task.id.to_string(),
-            namespace: task.namespace,
-            compute_fn: task.compute_fn_name,
-            compute_graph: task.compute_graph_name,
-            invocation_id: task.invocation_id,
-            input_key: task.input_node_output_key,
-            outcome: task.outcome.into(),
-            reducer_output_id: task.reducer_output_id,
-            graph_version: task.graph_version.into(),
+            namespace: deletion.namespace,
+            compute_graph: deletion.compute_graph,
+            invocation_id: deletion.invocation_id,
+            mode: deletion.mode.into(),
+            status,
+            requested_by: deletion.requested_by,
+            requested_at: deletion.requested_at,
+            deadline: deletion.deadline,
+            invocations: deletion
+                .invocations
+                .into_iter()
+                .map(|invocation| DeletedInvocation {
+                    compute_graph: invocation.compute_graph,
+                    invocation_id: invocation.invocation_id,
+                })
+                .collect(),
+            outputs: deletion.outputs,
+            bytes: deletion.bytes,
+            blobs: deletion.blobs.len() as u64,
+            pending_blobs: pending_blobs as u64,
         }
     }
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
-pub struct Tasks {
-    pub tasks: Vec<Task>,
+pub struct DataDeletionsList {
+    pub deletions: Vec<DataDeletion>,
     pub cursor: Option<Vec<u8>>,
 }
 
+/// Describes the payload a graph would be invoked with
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
-pub struct FnOutput {
-    pub compute_fn: String,
-    pub id: String,
+pub struct PlanInvocationRequest {
+    pub content_type: String,
+    /// Size of the payload in bytes
+    #[serde(default)]
+    pub size: Option<u64>,
 }
 
-impl From<data_model::NodeOutput> for FnOutput {
-    fn from(output: data_model::NodeOutput) -> Self {
-        Self {
-            compute_fn: output.compute_fn_name,
-            id: output.id.to_string(),
-        }
-    }
+#[derive(Debug, Serialize, Deserialize, ToSchema, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PlannedNodeKind {
+    ComputeFn,
+    DynamicRouter,
+    SubGraph,
+    Gate,
+    Window,
+    Sink,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
-pub struct FnOutputs {
-    pub outputs: Vec<FnOutput>,
-    pub cursor: Option<Vec<u8>>,
+pub struct PlannedTask {
+    pub compute_fn: String,
+    pub kind: PlannedNodeKind,
+    /// Nodes whose outputs are the inputs of the task
+    pub parents: Vec<String>,
+    /// Only created if a router picks the node
+    pub dynamic: bool,
+    pub reducer: bool,
+    /// Compute graph invoked by a sub graph node
+    pub sub_graph: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
-pub struct InvocationId {
-    pub id: String,
+pub struct InvocationPlan {
+    pub tasks: Vec<PlannedTask>,
+    /// Wiring problems found in the graph
+    pub warnings: Vec<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, ToSchema)]
-pub struct ExecutorMetadata {
-    pub id: String,
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LintRule {
+    UnreachableNode,
+    SingleTargetRouter,
+    MissingResourceRequests,
+    UnboundedOutputs,
+    DeepChain,
+}
+
+impl From<data_model::lint::LintRule> for LintRule {
+    fn from(rule: data_model::lint::LintRule) -> Self {
+        match rule {
+            data_model::lint::LintRule::UnreachableNode => LintRule::UnreachableNode,
+            data_model::lint::LintRule::SingleTargetRouter => LintRule::SingleTargetRouter,
+            data_model::lint::LintRule::MissingResourceRequests => {
+                LintRule::MissingResourceRequests
+            }
+            data_model::lint::LintRule::UnboundedOutputs => LintRule::UnboundedOutputs,
+            data_model::lint::LintRule::DeepChain => LintRule::DeepChain,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct LintWarning {
+    pub rule: LintRule,
+    /// Unset for the warnings about the whole graph
+    pub node: Option<String>,
+    pub message: String,
+}
+
+impl From<data_model::lint::LintWarning> for LintWarning {
+    fn from(warning: data_model::lint::LintWarning) -> Self {
+        Self {
+            rule: warning.rule.into(),
+            node: warning.node,
+            message: warning.message,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct GraphLint {
+    pub compute_graph: String,
+    pub warnings: Vec<LintWarning>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Default)]
+pub struct TaskAnalytics {
+    pub pending_tasks: u64,
+    pub successful_tasks: u64,
+    pub failed_tasks: u64,
+}
+
+impl From<data_model::TaskAnalytics> for TaskAnalytics {
+    fn from(analytics: data_model::TaskAnalytics) -> Self {
+        Self {
+            pending_tasks: analytics.pending_tasks,
+            successful_tasks: analytics.successful_tasks,
+            failed_tasks: analytics.failed_tasks,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct TopologyNode {
+    pub name: String,
+    pub kind: PlannedNodeKind,
+    pub reducer: bool,
+    /// Compute graph invoked by a sub graph node
+    pub sub_graph: Option<String>,
+    /// Nodes a router can route to
+    pub router_targets: Vec<String>,
+    /// Tasks of the node, of the invocation if one is given and of all the
+    /// invocations of the graph otherwise
+    pub analytics: TaskAnalytics,
+    /// Whether the invocation ran the node, only set for invocations
+    pub executed: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct TopologyEdge {
+    pub from: String,
+    pub to: String,
+    /// Router target, only followed if the router picks it
+    pub dynamic: bool,
+    /// Whether the invocation followed the edge, only set for invocations
+    pub executed: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct GraphTopology {
+    pub compute_graph: String,
+    pub version: GraphVersion,
+    pub start_node: String,
+    pub nodes: Vec<TopologyNode>,
+    pub edges: Vec<TopologyEdge>,
+    pub invocation_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TopologyParams {
+    /// Overlays the nodes and edges the invocation ran
+    pub invocation_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CreateNamespace {
+    pub name: String,
+    #[serde(default)]
+    pub queue_limits: QueueLimits,
+    /// Share of the executors the namespace gets relative to other
+    /// namespaces with pending tasks, defaults to 1
+    pub scheduling_weight: Option<u32>,
+    /// Executors reserved for the tasks of the namespace
+    pub reserved_capacity: Option<ReservedCapacity>,
+    /// Regions the payloads of the namespace are replicated to
+    pub replication: Option<ReplicationPolicy>,
+}
+
+/// Compute graphs created or updated together, the code of each graph is
+/// uploaded in the `code.<graph>` field of the request.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Manifest {
+    pub graphs: Vec<ManifestGraph>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ManifestGraph {
+    pub compute_graph: ComputeGraph,
+    /// Current revision of the graph, required when updating it
+    pub expected_revision: Option<u64>,
+    /// Skips the revision check
+    #[serde(default)]
+    pub force: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ManifestApplied {
+    pub compute_graphs: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ComputeGraphsList {
+    pub compute_graphs: Vec<ComputeGraph>,
+    pub cursor: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DataObject {
+    pub id: String,
+    pub payload_size: u64,
+    pub payload_sha_256: String,
+    pub labels: HashMap<String, serde_json::Value>,
+    #[serde(default)]
+    pub attributes: HashMap<String, serde_json::Value>,
+}
+
+impl From<data_model::InvocationPayload> for DataObject {
+    fn from(payload: data_model::InvocationPayload) -> Self {
+        Self {
+            id: payload.id,
+            payload_size: payload.payload.size,
+            payload_sha_256: payload.payload.sha256_hash,
+            labels: payload.labels,
+            attributes: payload.attributes,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QueryParams {
+    pub input_id: Option<String>,
+    pub on_graph_end: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GraphOutputNotification {
+    pub output_id: String,
+    pub compute_graph: String,
+    pub fn_name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CreateNamespaceResponse {
+    pub name: Namespace,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct GraphInvocations {
+    pub invocations: Vec<DataObject>,
+    pub cursor: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct GraphInputJson {
+    pub payload: serde_json::Value,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct GraphInputFile {
+    // file:///s3://bucket/key
+    // file:///data/path/to/file
+    pub url: String,
+    pub metadata: serde_json::Value,
+    pub sha_256: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum InvocationStatus {
+    Success,
+    Failure,
+}
+
+impl From<data_model::InvocationStatus> for InvocationStatus {
+    fn from(status: data_model::InvocationStatus) -> Self {
+        match status {
+            data_model::InvocationStatus::Success => InvocationStatus::Success,
+            data_model::InvocationStatus::Failure => InvocationStatus::Failure,
+        }
+    }
+}
+
+/// A failed task, its logs are served by the task logs endpoint
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct InvocationError {
+    pub compute_fn: String,
+    pub task_id: String,
+}
+
+/// Outcome of a finished invocation
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct InvocationResult {
+    pub id: String,
+    pub status: InvocationStatus,
+    /// Outputs of the terminal functions of the graph
+    pub outputs: Vec<FnOutput>,
+    pub errors: Vec<InvocationError>,
+    /// Milliseconds since the epoch
+    pub created_at: u64,
+    pub finished_at: u64,
+    pub duration_ms: u64,
+}
+
+impl From<data_model::InvocationResult> for InvocationResult {
+    fn from(result: data_model::InvocationResult) -> Self {
+        Self {
+            id: result.invocation_id,
+            status: result.status.into(),
+            outputs: result.outputs.into_iter().map(Into::into).collect(),
+            errors: result
+                .errors
+                .into_iter()
+                .map(|error| InvocationError {
+                    compute_fn: error.compute_fn,
+                    task_id: error.task_id.to_string(),
+                })
+                .collect(),
+            created_at: result.created_at,
+            finished_at: result.finished_at,
+            duration_ms: result.finished_at.saturating_sub(result.created_at),
+        }
+    }
+}
+
+/// Terminal output compared by its hash and size
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct OutputDigest {
+    pub sha256_hash: String,
+    pub size: u64,
+}
+
+impl From<data_model::invocation_diff::OutputDigest> for OutputDigest {
+    fn from(digest: data_model::invocation_diff::OutputDigest) -> Self {
+        Self {
+            sha256_hash: digest.sha256_hash,
+            size: digest.size,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct FunctionOutputsDiff {
+    pub compute_fn: String,
+    pub identical: bool,
+    pub base_outputs: u64,
+    pub base_bytes: u64,
+    pub head_outputs: u64,
+    pub head_bytes: u64,
+    /// Outputs of the head invocation without an identical output in the base
+    pub added: Vec<OutputDigest>,
+    /// Outputs of the base invocation without an identical output in the head
+    pub removed: Vec<OutputDigest>,
+}
+
+impl From<data_model::invocation_diff::FunctionOutputsDiff> for FunctionOutputsDiff {
+    fn from(diff: data_model::invocation_diff::FunctionOutputsDiff) -> Self {
+        Self {
+            identical: diff.identical(),
+            compute_fn: diff.compute_fn,
+            base_outputs: diff.base_outputs,
+            base_bytes: diff.base_bytes,
+            head_outputs: diff.head_outputs,
+            head_bytes: diff.head_bytes,
+            added: diff.added.into_iter().map(Into::into).collect(),
+            removed: diff.removed.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DiffedInvocation {
+    pub id: String,
+    pub graph_version: GraphVersion,
+    pub status: InvocationStatus,
+}
+
+impl From<data_model::invocation_diff::DiffedInvocation> for DiffedInvocation {
+    fn from(invocation: data_model::invocation_diff::DiffedInvocation) -> Self {
+        Self {
+            id: invocation.invocation_id,
+            graph_version: invocation.graph_version.into(),
+            status: invocation.status.into(),
+        }
+    }
+}
+
+/// Differences between the terminal outputs of two finished invocations
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct InvocationsDiff {
+    pub base: DiffedInvocation,
+    pub head: DiffedInvocation,
+    /// Same status and the same terminal outputs, in any order
+    pub identical: bool,
+    pub functions: Vec<FunctionOutputsDiff>,
+}
+
+impl From<data_model::invocation_diff::InvocationsDiff> for InvocationsDiff {
+    fn from(diff: data_model::invocation_diff::InvocationsDiff) -> Self {
+        Self {
+            identical: diff.identical(),
+            base: diff.base.into(),
+            head: diff.head.into(),
+            functions: diff.functions.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InvocationsDiffParams {
+    /// Invocation the head invocation is compared against
+    pub base: String,
+    pub head: String,
+}
+
+/// Invocations of the same payload on the current and the canary version of
+/// a graph, diffed once both finished
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DiffRun {
+    pub base: String,
+    pub base_version: GraphVersion,
+    pub head: String,
+    pub head_version: GraphVersion,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SyncInvocationParams {
+    /// Seconds to wait for the invocation to finish, bounded by the server's
+    /// maximum
+    pub timeout_secs: Option<u64>,
+}
+
+/// Terminal output returned inline by the invoke-and-wait endpoint
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct InlineFnOutput {
+    pub compute_fn: String,
+    pub id: String,
+    /// Base64 encoded bytes of the output
+    pub data: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SyncInvocationResult {
+    pub id: String,
+    /// False when the timeout elapsed first, the result is then served by the
+    /// result endpoint once the invocation finishes
+    pub finished: bool,
+    pub result: Option<InvocationResult>,
+    /// Terminal outputs within the server's inline limit, the others are
+    /// downloaded from the outputs of the result
+    pub inline_outputs: Vec<InlineFnOutput>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CreateInvocationGroup {
+    /// Generated when not set
+    pub id: Option<String>,
+    #[serde(default)]
+    pub labels: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum InvocationGroupStatus {
+    Pending,
+    Running,
+    Success,
+    Failure,
+}
+
+impl From<data_model::invocation_group::InvocationGroupStatus> for InvocationGroupStatus {
+    fn from(status: data_model::invocation_group::InvocationGroupStatus) -> Self {
+        use data_model::invocation_group::InvocationGroupStatus as Status;
+        match status {
+            Status::Pending => InvocationGroupStatus::Pending,
+            Status::Running => InvocationGroupStatus::Running,
+            Status::Success => InvocationGroupStatus::Success,
+            Status::Failure => InvocationGroupStatus::Failure,
+        }
+    }
+}
+
+/// Invocations tracked together, the group finishes once it's closed and
+/// all its invocations finished
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct InvocationGroup {
+    pub id: String,
+    pub labels: HashMap<String, serde_json::Value>,
+    pub status: InvocationGroupStatus,
+    /// Invocations can't join a closed group
+    pub closed: bool,
+    pub invocations: u64,
+    pub succeeded: u64,
+    pub failed: u64,
+    /// Milliseconds since the epoch
+    pub created_at: u64,
+    pub finished_at: Option<u64>,
+}
+
+impl From<data_model::invocation_group::InvocationGroup> for InvocationGroup {
+    fn from(group: data_model::invocation_group::InvocationGroup) -> Self {
+        Self {
+            status: group.status().into(),
+            id: group.id,
+            labels: group.labels,
+            closed: group.closed,
+            invocations: group.invocations,
+            succeeded: group.succeeded,
+            failed: group.failed,
+            created_at: group.created_at,
+            finished_at: group.finished_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct InvocationGroupsList {
+    pub invocation_groups: Vec<InvocationGroup>,
+    pub cursor: Option<Vec<u8>>,
+}
+
+/// An invocation of a group, its status is set once it finished
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct InvocationGroupMember {
+    pub compute_graph: String,
+    pub invocation_id: String,
+    pub status: Option<InvocationStatus>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct InvocationGroupMembers {
+    pub invocations: Vec<InvocationGroupMember>,
+    pub cursor: Option<Vec<u8>>,
+}
+
+/// A value of the scratch space shared by the tasks of an invocation
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct InvocationStateEntry {
+    pub key: String,
+    pub value: serde_json::Value,
+    /// Incremented by every write of the key
+    pub version: u64,
+}
+
+impl From<data_model::InvocationStateEntry> for InvocationStateEntry {
+    fn from(entry: data_model::InvocationStateEntry) -> Self {
+        Self {
+            key: entry.key,
+            value: entry.value,
+            version: entry.version,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SetInvocationState {
+    pub value: serde_json::Value,
+    /// Compare-and-swap, the write fails with 409 unless the value is at
+    /// this version, 0 if it must not exist yet
+    pub expected_version: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PendingReason {
+    NotPending,
+    MaintenanceMode,
+    GraphPaused,
+    NotAllocatable,
+    ConcurrencyKey,
+    NoMatchingExecutor,
+    Blacklisted,
+    GpusBusy,
+    ReservedForOtherNamespaces,
+    CapacityExhausted,
+    ReservedForLatencyLane,
+    RateLimited,
+    Allocatable,
+}
+
+/// Why a task isn't allocated, as seen by the scheduler.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct PendingTaskExplanation {
+    pub task_id: String,
+    pub reason: PendingReason,
+    pub message: String,
+    /// Executors the task can be allocated to once the reason clears
+    pub candidate_executors: Vec<String>,
+    /// Why executors were ruled out
+    pub details: Vec<String>,
+}
+
+impl PendingTaskExplanation {
+    pub fn new(
+        task_id: &str,
+        explanation: task_scheduler::explain::PendingTaskExplanation,
+    ) -> Self {
+        use task_scheduler::explain::PendingReason as Reason;
+        let (reason, message) = match explanation.reason {
+            Reason::NotPending => (
+                PendingReason::NotPending,
+                "the task is allocated or finished".to_string(),
+            ),
+            Reason::MaintenanceMode => (
+                PendingReason::MaintenanceMode,
+                "tasks aren't allocated in maintenance mode".to_string(),
+            ),
+            Reason::GraphPaused => (
+                PendingReason::GraphPaused,
+                "the compute graph is paused".to_string(),
+            ),
+            Reason::NotAllocatable => (
+                PendingReason::NotAllocatable,
+                "the task is run by the server once its gate, window, router or sink is ready"
+                    .to_string(),
+            ),
+            Reason::ConcurrencyKey(key) => (
+                PendingReason::ConcurrencyKey,
+                format!("a task with concurrency key {} is running", key),
+            ),
+            Reason::NoMatchingExecutor => (
+                PendingReason::NoMatchingExecutor,
+                "no executor matches the placement constraints of the function".to_string(),
+            ),
+            Reason::Blacklisted => (
+                PendingReason::Blacklisted,
+                "the matching executors are blacklisted for the function".to_string(),
+            ),
+            Reason::GpusBusy => (
+                PendingReason::GpusBusy,
+                "the matching executors don't have enough free gpus".to_string(),
+            ),
+            Reason::ReservedForOtherNamespaces => (
+                PendingReason::ReservedForOtherNamespaces,
+                "the matching executors are reserved for other namespaces".to_string(),
+            ),
+            Reason::CapacityExhausted => (
+                PendingReason::CapacityExhausted,
+                "the matching executors have no room for more outstanding tasks".to_string(),
+            ),
+            Reason::ReservedForLatencyLane => (
+                PendingReason::ReservedForLatencyLane,
+                "the matching executors only have room in the slots reserved for the latency lane"
+                    .to_string(),
+            ),
+            Reason::RateLimited => (
+                PendingReason::RateLimited,
+                "the rate limit of the function is exhausted".to_string(),
+            ),
+            Reason::Allocatable => (
+                PendingReason::Allocatable,
+                "the task is allocated by the next scheduling pass".to_string(),
+            ),
+        };
+        Self {
+            task_id: task_id.to_string(),
+            reason,
+            message,
+            candidate_executors: explanation
+                .candidates
+                .iter()
+                .map(|id| id.to_string())
+                .collect(),
+            details: explanation.details,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub enum TaskOutcome {
+    Unknown,
+    Success,
+    Failure,
+}
+
+impl From<data_model::TaskOutcome> for TaskOutcome {
+    fn from(outcome: data_model::TaskOutcome) -> Self {
+        match outcome {
+            data_model::TaskOutcome::Unknown => TaskOutcome::Unknown,
+            data_model::TaskOutcome::Success => TaskOutcome::Success,
+            data_model::TaskOutcome::Failure => TaskOutcome::Failure,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct GraphVersion(pub u32);
+
+impl From<data_model::GraphVersion> for GraphVersion {
+    fn from(version: data_model::GraphVersion) -> Self {
+        Self(version.0)
+    }
+}
+
+impl From<GraphVersion> for data_model::GraphVersion {
+    fn from(version: GraphVersion) -> Self {
+        Self(version.0)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct GraphCanary {
+    pub compute_graph: String,
+    /// Version of the canary
+    pub version: GraphVersion,
+    /// Version running the invocations which aren't routed to the canary
+    pub current_version: GraphVersion,
+    /// Percentage of the new invocations routed to the canary
+    pub percent: u8,
+    pub created_at: u64,
+}
+
+impl GraphCanary {
+    pub fn from_data_model(
+        canary: data_model::canary::GraphCanary,
+        current: &data_model::ComputeGraph,
+    ) -> Self {
+        Self {
+            compute_graph: canary.compute_graph.name,
+            version: canary.compute_graph.version.into(),
+            current_version: current.version.into(),
+            percent: canary.percent,
+            created_at: canary.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UpdateGraphCanary {
+    pub percent: u8,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Task {
+    pub id: String,
+    pub namespace: String,
+    pub compute_fn: String,
+    pub compute_graph: String,
+    pub invocation_id: String,
+    pub input_key: String,
+    pub outcome: TaskOutcome,
+    pub reducer_output_id: Option<String>,
+    pub graph_version: GraphVersion,
+    pub invocation_labels: HashMap<String, serde_json::Value>,
+    /// sha256 hash of the graph code, used to fetch it from the artifact
+    /// endpoint
+    pub code_sha256: Option<String>,
+    /// Ids of the executor GPUs reserved for the task
+    pub gpu_ids: Vec<String>,
+    /// Resources used by the task once it finished
+    #[serde(default)]
+    pub usage: Option<TaskUsage>,
+    /// Runtime the executor starts the sandbox of the task with
+    #[serde(default)]
+    pub runtime: Option<RuntimeDescriptor>,
+    /// Set when the server failed the task
+    #[serde(default)]
+    pub failure: Option<TaskFailure>,
+    /// Keys of the outputs of a closed window the task reads as a single list
+    /// input, input_key is the first of them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub input_batch: Vec<String>,
+    /// Resolved parameters of the invocation of the task
+    #[serde(default)]
+    pub parameters: HashMap<String, serde_json::Value>,
+    /// Latest checkpoint of the task, downloaded by the attempt resuming it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checkpoint: Option<TaskCheckpoint>,
+    /// Token the function of the task calls the server with, only sent to
+    /// the executor of the task
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct TaskCheckpoint {
+    pub size: u64,
+    pub sha256_hash: String,
+    /// Milliseconds since the epoch
+    pub created_at: u64,
+}
+
+impl From<data_model::TaskCheckpoint> for TaskCheckpoint {
+    fn from(checkpoint: data_model::TaskCheckpoint) -> Self {
+        Self {
+            size: checkpoint.payload.size,
+            sha256_hash: checkpoint.payload.sha256_hash,
+            created_at: checkpoint.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputLimit {
+    Outputs,
+    OutputBytes,
+}
+
+/// Why the server failed a task, e.g.
+/// `{"reason": "output_limit_exceeded", "limit": "outputs", "max": 100,
+/// "actual": 101}`
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case", tag = "reason")]
+pub enum TaskFailure {
+    OutputLimitExceeded {
+        limit: OutputLimit,
+        max: u64,
+        actual: u64,
+    },
+    /// The input of an event-time window arrived after its window closed,
+    /// times in ms since epoch
+    LateInput {
+        event_time: u64,
+        watermark: u64,
+    },
+}
+
+impl From<data_model::TaskFailure> for TaskFailure {
+    fn from(failure: data_model::TaskFailure) -> Self {
+        match failure {
+            data_model::TaskFailure::OutputLimitExceeded { limit, max, actual } => {
+                TaskFailure::OutputLimitExceeded {
+                    limit: match limit {
+                        data_model::OutputLimit::Outputs => OutputLimit::Outputs,
+                        data_model::OutputLimit::OutputBytes => OutputLimit::OutputBytes,
+                    },
+                    max,
+                    actual,
+                }
+            }
+            data_model::TaskFailure::LateInput {
+                event_time,
+                watermark,
+            } => TaskFailure::LateInput {
+                event_time,
+                watermark,
+            },
+        }
+    }
+}
+
+impl From<data_model::Task> for Task {
+    fn from(task: data_model::Task) -> Self {
+        Self {
+            id: task.id.to_string(),
+            namespace: task.namespace,
+            compute_fn: task.compute_fn_name,
+            compute_graph: task.compute_graph_name,
+            invocation_id: task.invocation_id,
+            input_key: task.input_node_output_key,
+            outcome: task.outcome.into(),
+            reducer_output_id: task.reducer_output_id,
+            graph_version: task.graph_version.into(),
+            invocation_labels: task.invocation_labels,
+            code_sha256: task.code_sha256,
+            gpu_ids: task.gpu_ids,
+            usage: task.usage.map(Into::into),
+            runtime: task.runtime.map(Into::into),
+            failure: task.failure.map(Into::into),
+            input_batch: task.input_batch,
+            parameters: task.parameters,
+            checkpoint: task.checkpoint.map(Into::into),
+            token: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Tasks {
+    pub tasks: Vec<Task>,
+    pub cursor: Option<Vec<u8>>,
+}
+
+/// Event of the timeline of an invocation, the fields which don't apply to
+/// the event are omitted.
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct InvocationEvent {
+    /// Milliseconds since the epoch
+    pub at: u64,
+    /// e.g. task_created, task_allocated, router_chose
+    pub event: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compute_fn: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub executor_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<TaskOutcome>,
+    /// Edges a router chose
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edges: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<InvocationStatus>,
+}
+
+impl From<data_model::timeline::InvocationEvent> for InvocationEvent {
+    fn from(event: data_model::timeline::InvocationEvent) -> Self {
+        use data_model::timeline::InvocationEventKind;
+
+        let at = event.at;
+        match event.kind {
+            InvocationEventKind::Invoked => Self {
+                at,
+                event: "invoked".to_string(),
+                ..Default::default()
+            },
+            InvocationEventKind::TaskCreated {
+                task_id,
+                compute_fn,
+            } => Self {
+                at,
+                event: "task_created".to_string(),
+                task_id: Some(task_id),
+                compute_fn: Some(compute_fn),
+                ..Default::default()
+            },
+            InvocationEventKind::TaskAllocated {
+                task_id,
+                compute_fn,
+                executor_id,
+            } => Self {
+                at,
+                event: "task_allocated".to_string(),
+                task_id: Some(task_id),
+                compute_fn: Some(compute_fn),
+                executor_id: Some(executor_id),
+                ..Default::default()
+            },
+            InvocationEventKind::TaskPreempted {
+                task_id,
+                compute_fn,
+                executor_id,
+            } => Self {
+                at,
+                event: "task_preempted".to_string(),
+                task_id: Some(task_id),
+                compute_fn: Some(compute_fn),
+                executor_id: Some(executor_id),
+                ..Default::default()
+            },
+            InvocationEventKind::TaskRequeued {
+                task_id,
+                compute_fn,
+                executor_id,
+            } => Self {
+                at,
+                event: "task_requeued".to_string(),
+                task_id: Some(task_id),
+                compute_fn: Some(compute_fn),
+                executor_id: Some(executor_id),
+                ..Default::default()
+            },
+            InvocationEventKind::TaskFinished {
+                task_id,
+                compute_fn,
+                outcome,
+            } => Self {
+                at,
+                event: "task_finished".to_string(),
+                task_id: Some(task_id),
+                compute_fn: Some(compute_fn),
+                outcome: Some(outcome.into()),
+                ..Default::default()
+            },
+            InvocationEventKind::RouterChose {
+                task_id,
+                compute_fn,
+                edges,
+            } => Self {
+                at,
+                event: "router_chose".to_string(),
+                task_id: Some(task_id),
+                compute_fn: Some(compute_fn),
+                edges: Some(edges),
+                ..Default::default()
+            },
+            InvocationEventKind::InvocationFinished { status } => Self {
+                at,
+                event: "invocation_finished".to_string(),
+                status: Some(status.into()),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct InvocationEvents {
+    pub events: Vec<InvocationEvent>,
+    pub cursor: Option<Vec<u8>>,
+}
+
+/// Point in time to rebuild the state of an invocation at, one of them is
+/// required.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ReplayInvocationParams {
+    /// Milliseconds since the epoch
+    pub as_of: Option<u64>,
+    /// The state once the state change was processed
+    pub state_change_id: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplayedTaskStatus {
+    Pending,
+    Running,
+    Completed,
+}
+
+impl From<data_model::timeline::ReplayedTaskStatus> for ReplayedTaskStatus {
+    fn from(status: data_model::timeline::ReplayedTaskStatus) -> Self {
+        use data_model::timeline::ReplayedTaskStatus as Status;
+
+        match status {
+            Status::Pending => ReplayedTaskStatus::Pending,
+            Status::Running => ReplayedTaskStatus::Running,
+            Status::Completed => ReplayedTaskStatus::Completed,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ReplayedTask {
+    pub id: String,
+    pub compute_fn: String,
+    pub status: ReplayedTaskStatus,
+    pub executor_id: Option<String>,
+    pub outcome: TaskOutcome,
+}
+
+/// State of an invocation at a point in time, rebuilt from its timeline
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ReplayedInvocation {
+    /// Milliseconds since the epoch
+    pub as_of: u64,
+    /// Set once the invocation finished
+    pub status: Option<InvocationStatus>,
+    /// Tasks in the order they were created
+    pub tasks: Vec<ReplayedTask>,
+    /// Keyed by compute fn
+    pub analytics: HashMap<String, TaskAnalytics>,
+}
+
+impl From<data_model::timeline::ReplayedInvocation> for ReplayedInvocation {
+    fn from(replayed: data_model::timeline::ReplayedInvocation) -> Self {
+        Self {
+            as_of: replayed.as_of,
+            status: replayed.status.map(Into::into),
+            tasks: replayed
+                .tasks
+                .into_iter()
+                .map(|task| ReplayedTask {
+                    id: task.id,
+                    compute_fn: task.compute_fn,
+                    status: task.status.into(),
+                    executor_id: task.executor_id,
+                    outcome: task.outcome.into(),
+                })
+                .collect(),
+            analytics: replayed
+                .analytics
+                .into_iter()
+                .map(|(compute_fn, analytics)| (compute_fn, analytics.into()))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct FnOutput {
+    pub compute_fn: String,
+    pub id: String,
+    /// The payload was deleted past the retention of the graph
+    #[serde(default)]
+    pub expired: bool,
+    #[serde(default)]
+    pub mime_type: Option<String>,
+    #[serde(default)]
+    pub content_encoding: Option<String>,
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+    /// Regions the output was replicated to
+    #[serde(default)]
+    pub replica_regions: Vec<String>,
+}
+
+impl From<data_model::NodeOutput> for FnOutput {
+    fn from(output: data_model::NodeOutput) -> Self {
+        let (expired, mime_type, content_encoding, metadata, replicas) = match output.payload {
+            data_model::OutputPayload::Fn(payload) => (
+                payload.tier == data_model::StorageTier::Expired,
+                payload.mime_type,
+                payload.content_encoding,
+                payload.metadata,
+                payload.replicas,
+            ),
+            data_model::OutputPayload::Router(_) => (false, None, None, HashMap::new(), vec![]),
+        };
+        Self {
+            compute_fn: output.compute_fn_name,
+            id: output.id.to_string(),
+            expired,
+            mime_type,
+            content_encoding,
+            metadata,
+            replica_regions: replicas.into_iter().map(|replica| replica.region).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct FnOutputs {
+    pub outputs: Vec<FnOutput>,
+    pub cursor: Option<Vec<u8>>,
+}
+
+/// Payload a download url is requested for, the invocation's input unless
+/// `fn_name` and `output_id` identify one of its function outputs
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct PayloadUrlRequest {
+    pub compute_graph: String,
+    pub invocation_id: String,
+    #[serde(default)]
+    pub fn_name: Option<String>,
+    #[serde(default)]
+    pub output_id: Option<String>,
+    /// Bounded by the server's maximum expiry
+    #[serde(default)]
+    pub expires_in_secs: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct PayloadUrl {
+    /// Presigned S3 url, or a path of the server api for payloads which are
+    /// not stored in S3
+    pub url: String,
+    /// Seconds since the epoch
+    pub expires_at: u64,
+    pub size: u64,
+    pub sha256_hash: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DownloadParams {
+    /// Region of the caller, the replica of the payload in the region is
+    /// downloaded when there is one
+    pub region: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SignedDownloadParams {
+    /// Hex encoded blob path
+    pub path: String,
+    pub expires_at: u64,
+    pub signature: String,
+    /// Codec the blob was stored with, the download is decompressed
+    #[serde(default)]
+    pub codec: Option<data_model::PayloadCodec>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct InvocationId {
+    pub id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ExecutorMetadata {
+    pub id: String,
+    pub addr: String,
+    pub image_name: String,
+    pub labels: HashMap<String, serde_json::Value>,
+    /// sha256 hashes of the graph code cached by the executor
+    #[serde(default)]
+    pub cached_artifacts: Vec<String>,
+    #[serde(default)]
+    pub gpus: Vec<GpuDevice>,
+    /// Version of the executor protocol spoken by the executor, executors
+    /// which don't send one are at version 0
+    #[serde(default)]
+    pub protocol_version: u32,
+    /// Optional protocol features, the executor sends the features it
+    /// supports and the server lists the ones it accepted
+    #[serde(default)]
+    pub features: Vec<String>,
+    /// Runtimes the executor can start sandboxes of
+    #[serde(default)]
+    pub runtimes: Vec<RuntimeDescriptor>,
+    /// Tasks received by the executor and not finished yet, as of its last
+    /// heartbeat
+    #[serde(default)]
+    pub queue_depth: u64,
+    /// Tasks the executor accepts before finishing some, unlimited when not
+    /// set
+    #[serde(default)]
+    pub max_outstanding_tasks: Option<u32>,
+    /// Software version of the executor
+    #[serde(default)]
+    pub version: Option<String>,
+    /// Last registration or heartbeat of the executor, in ms since epoch.
+    /// Set when listing executors.
+    #[serde(default)]
+    pub last_heartbeat_at: Option<u64>,
+    /// Number of tasks allocated to the executor. Set when listing executors.
+    #[serde(default)]
+    pub allocated_tasks: u64,
+    /// Ids of the tasks allocated to the executor. Set when listing executors.
+    #[serde(default)]
+    pub running_task_ids: Vec<String>,
+    /// Tasks which can still be allocated to the executor, unlimited when not
+    /// set. Set when listing executors.
+    #[serde(default)]
+    pub free_capacity: Option<u64>,
+    /// Tasks the executor was running when it went down, sent when it
+    /// registers again after a crash. They're reconciled against the tasks
+    /// allocated to it before it gets new tasks.
+    #[serde(default)]
+    pub recovered_tasks: Option<Vec<RecoveredTask>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct GpuDevice {
+    pub id: String,
+    pub model: String,
+    pub free_memory_mb: u64,
+}
+
+impl From<GpuDevice> for data_model::GpuDevice {
+    fn from(val: GpuDevice) -> Self {
+        data_model::GpuDevice {
+            id: val.id,
+            model: val.model,
+            free_memory_mb: val.free_memory_mb,
+        }
+    }
+}
+
+impl From<data_model::GpuDevice> for GpuDevice {
+    fn from(val: data_model::GpuDevice) -> Self {
+        Self {
+            id: val.id,
+            model: val.model,
+            free_memory_mb: val.free_memory_mb,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ExecutorHeartbeat {
+    pub cached_artifacts: Vec<String>,
+    /// Tasks received by the executor and not finished yet
+    #[serde(default)]
+    pub queue_depth: u64,
+    /// Tasks the executor accepts before finishing some, no more tasks are
+    /// allocated to it while it has this many
+    #[serde(default)]
+    pub max_outstanding_tasks: Option<u32>,
+}
+
+/// Function environment the executor keeps loaded between the tasks of the
+/// function
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct WarmPoolHint {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub compute_fn: String,
+    pub graph_version: GraphVersion,
+    pub code_sha256: String,
+    /// Environments of the function to keep loaded
+    pub instances: u32,
+}
+
+impl From<crate::executors::WarmPoolHint> for WarmPoolHint {
+    fn from(hint: crate::executors::WarmPoolHint) -> Self {
+        Self {
+            namespace: hint.namespace,
+            compute_graph: hint.compute_graph,
+            compute_fn: hint.compute_fn,
+            graph_version: hint.graph_version.into(),
+            code_sha256: hint.code_sha256,
+            instances: hint.instances,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ExecutorHeartbeatResponse {
+    /// Functions with warm instances on the executor, the executor unloads
+    /// environments of other functions when it needs room
+    pub warm_pool: Vec<WarmPoolHint>,
+}
+
+/// Why an executor is told to stop running a task
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AbortReason {
+    /// Pre-empted by a higher priority task
+    Preempted,
+    /// The invocation of the task was cancelled
+    Cancelled,
+    /// The task was moved to another executor
+    Relocated,
+}
+
+impl From<data_model::AbortReason> for AbortReason {
+    fn from(reason: data_model::AbortReason) -> Self {
+        match reason {
+            data_model::AbortReason::Preempted => AbortReason::Preempted,
+            data_model::AbortReason::Cancelled => AbortReason::Cancelled,
+            data_model::AbortReason::Relocated => AbortReason::Relocated,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct AbortCommand {
+    pub task_id: String,
+    pub reason: AbortReason,
+    /// Milliseconds since the epoch
+    pub issued_at: u64,
+}
+
+impl From<data_model::AbortCommand> for AbortCommand {
+    fn from(command: data_model::AbortCommand) -> Self {
+        Self {
+            task_id: command.task_id.to_string(),
+            reason: command.reason.into(),
+            issued_at: command.issued_at,
+        }
+    }
+}
+
+/// Commands an executor hasn't acknowledged yet
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ExecutorControl {
+    pub aborts: Vec<AbortCommand>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AcknowledgeAborts {
+    /// Tasks the executor stopped running
+    pub task_ids: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct CreateExecutorPool {
+    pub name: String,
+    /// Label conditions an executor must match to be a member, e.g.
+    /// `gpu=true` or `tier in (a,b) && !spot`
+    pub selector: Vec<String>,
+    pub min_size: Option<u32>,
+    pub max_size: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct ExecutorPool {
+    pub name: String,
+    pub selector: Vec<String>,
+    pub min_size: Option<u32>,
+    pub max_size: Option<u32>,
+    pub created_at: u64,
+    pub members: Vec<String>,
+}
+
+impl ExecutorPool {
+    pub fn from_data_model(
+        pool: data_model::ExecutorPool,
+        executors: &[data_model::ExecutorMetadata],
+    ) -> Self {
+        let members = executors
+            .iter()
+            .filter(|executor| pool.matches_executor(executor))
+            .map(|executor| executor.id.to_string())
+            .collect();
+        Self {
+            name: pool.name,
+            selector: pool
+                .selector
+                .conditions()
+                .iter()
+                .map(|condition| condition.to_string())
+                .collect(),
+            min_size: pool.min_size,
+            max_size: pool.max_size,
+            created_at: pool.created_at,
+            members,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ExecutorPoolsList {
+    pub pools: Vec<ExecutorPool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct CreateWebhook {
+    /// http or https url the events are POSTed to
+    pub url: String,
+    /// Events delivered to the webhook, of invocation_completed,
+    /// invocation_failed, graph_created, sla_breached, sla_recovered,
+    /// executor_removed, invocation_group_finished, data_rejected,
+    /// alert_firing and alert_resolved
+    pub events: Vec<String>,
+    /// Only delivers the events of this graph when set
+    #[serde(default)]
+    pub compute_graph: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct Webhook {
+    pub id: String,
+    pub url: String,
+    pub events: Vec<String>,
+    /// Hex encoded key the payloads are signed with, only returned when the
+    /// webhook is created
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    pub created_at: u64,
+    pub compute_graph: Option<String>,
+}
+
+impl From<data_model::webhooks::Webhook> for Webhook {
+    fn from(webhook: data_model::webhooks::Webhook) -> Self {
+        Self {
+            id: webhook.id,
+            url: webhook.url,
+            events: webhook
+                .events
+                .iter()
+                .map(|event_type| event_type.to_string())
+                .collect(),
+            secret: None,
+            created_at: webhook.created_at,
+            compute_graph: webhook.compute_graph,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct WebhooksList {
+    pub webhooks: Vec<Webhook>,
+}
+
+/// An event which couldn't be delivered to a webhook.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct WebhookDeadLetter {
+    pub webhook_id: String,
+    pub event_id: String,
+    pub event_type: String,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    pub created_at: u64,
+    pub data: serde_json::Value,
+}
+
+impl From<data_model::webhooks::WebhookDelivery> for WebhookDeadLetter {
+    fn from(delivery: data_model::webhooks::WebhookDelivery) -> Self {
+        Self {
+            webhook_id: delivery.webhook_id,
+            event_id: delivery.event.id,
+            event_type: delivery.event.event_type.to_string(),
+            attempts: delivery.attempts,
+            last_error: delivery.last_error,
+            created_at: delivery.event.created_at,
+            data: delivery.event.data,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct WebhookDeadLetters {
+    pub dead_letters: Vec<WebhookDeadLetter>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RedriveDeadLetters {
+    /// Number of dead letters queued for delivery again
+    pub redriven: usize,
+}
+
+impl From<data_model::ExecutorMetadata> for ExecutorMetadata {
+    fn from(executor: data_model::ExecutorMetadata) -> Self {
+        Self {
+            id: executor.id.to_string(),
+            addr: executor.addr,
+            image_name: executor.image_name,
+            labels: executor.labels,
+            cached_artifacts: executor.cached_artifacts.into_iter().collect(),
+            gpus: executor.gpus.into_iter().map(Into::into).collect(),
+            protocol_version: executor.protocol_version,
+            features: executor
+                .features
+                .iter()
+                .map(|feature| feature.to_string())
+                .collect(),
+            runtimes: executor.runtimes.into_iter().map(Into::into).collect(),
+            queue_depth: executor.queue_depth,
+            max_outstanding_tasks: executor.max_outstanding_tasks,
+            free_capacity: executor.free_capacity(0),
+            version: executor.version,
+            last_heartbeat_at: executor.last_heartbeat_at,
+            allocated_tasks: 0,
+            running_task_ids: vec![],
+            recovered_tasks: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Snapshot {
+    pub id: String,
+    pub created_at: u64,
+    /// Url of the state dump, used to restore a server
+    pub state_url: String,
+    pub manifest_url: String,
+    /// Number of keys per column family
+    pub column_families: BTreeMap<String, u64>,
+    pub num_blob_references: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct InvokeFromUrl {
+    /// http(s) or s3 url of the input
+    pub url: String,
+    /// Hex encoded sha256 of the input, the invocation isn't started when
+    /// the fetched content doesn't match
+    pub sha256: Option<String>,
+    /// Mime type of the input
+    pub content_type: Option<String>,
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct InvokeFromReference {
+    /// Url of an object of the blob storage of the server, e.g.
+    /// `s3://<bucket>/<key>`, optionally pinned to a version with
+    /// `?versionId=<version>`
+    pub url: String,
+    /// Hex encoded sha256 of the object, recorded as the hash of the input
+    pub sha256: String,
+    /// ETag of the object, the invocation isn't started when the object
+    /// changed since
+    pub etag: Option<String>,
+    /// Mime type of the input
+    pub content_type: Option<String>,
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum UrlFetchStatus {
+    Fetching,
+    /// The input was fetched and the invocation started
+    Invoked,
+    Failed,
+}
+
+/// Fetch of an invocation input from a url by the server.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct UrlFetch {
+    pub id: String,
+    pub namespace: String,
+    pub compute_graph: String,
+    pub url: String,
+    pub status: UrlFetchStatus,
+    pub attempts: u32,
+    /// Set once the invocation is started
+    pub invocation_id: Option<String>,
+    /// Why the last attempt failed
+    pub error: Option<String>,
+    pub created_at: u64,
+    pub finished_at: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RejectedPayloads {
+    pub namespace: String,
+    pub compute_graph: String,
+    /// Number of payloads rejected for exceeding the size limit
+    pub rejected: u64,
+    /// Sum of the sizes of the rejected payloads, as declared or received
+    /// until they were cut
+    pub rejected_bytes: u64,
+    pub largest_bytes: u64,
+    pub last_rejected_at: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RejectedPayloadsList {
+    pub rejected_payloads: Vec<RejectedPayloads>,
+}
+
+/// Latencies of the finished tasks of a function, from their allocation to
+/// their completion. Quantiles are estimated within 1%.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct FunctionLatency {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub compute_fn: String,
+    pub count: u64,
+    pub mean_ms: Option<u64>,
+    pub p50_ms: Option<u64>,
+    pub p90_ms: Option<u64>,
+    pub p99_ms: Option<u64>,
+    pub max_ms: u64,
+    /// Tasks started in a newly loaded function environment, and in a warm
+    /// one, as reported by their executors
+    pub cold_starts: u64,
+    pub warm_starts: u64,
+    pub updated_at: u64,
+}
+
+impl From<data_model::latency::FunctionLatency> for FunctionLatency {
+    fn from(latency: data_model::latency::FunctionLatency) -> Self {
+        Self {
+            count: latency.sketch.count,
+            mean_ms: latency.sketch.mean_ms(),
+            p50_ms: latency.sketch.quantile(0.5),
+            p90_ms: latency.sketch.quantile(0.9),
+            p99_ms: latency.sketch.quantile(0.99),
+            max_ms: latency.sketch.max_ms,
+            cold_starts: latency.cold_starts,
+            warm_starts: latency.warm_starts,
+            namespace: latency.namespace,
+            compute_graph: latency.compute_graph,
+            compute_fn: latency.compute_fn,
+            updated_at: latency.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct FunctionLatencies {
+    pub latencies: Vec<FunctionLatency>,
+}
+
+/// Executor excluded from the placement of the tasks of a function.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ExecutorBlacklistEntry {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub compute_fn: String,
+    pub executor_id: String,
+    /// Consecutive failures of the function on the executor which got it
+    /// blacklisted
+    pub failures: u32,
+    pub blacklisted_at: u64,
+    pub expires_at: u64,
+}
+
+impl From<data_model::blacklist::ExecutorBlacklistEntry> for ExecutorBlacklistEntry {
+    fn from(entry: data_model::blacklist::ExecutorBlacklistEntry) -> Self {
+        Self {
+            namespace: entry.namespace,
+            compute_graph: entry.compute_graph,
+            compute_fn: entry.compute_fn,
+            executor_id: entry.executor_id.to_string(),
+            failures: entry.failures,
+            blacklisted_at: entry.blacklisted_at,
+            expires_at: entry.expires_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ExecutorBlacklist {
+    pub entries: Vec<ExecutorBlacklistEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct TaskEta {
+    pub id: String,
+    pub compute_fn: String,
+    pub running: bool,
+    /// Unallocated tasks of the function scheduled before this one, for
+    /// pending tasks
+    pub queue_position: Option<u64>,
+    pub remaining_ms: u64,
+}
+
+impl From<data_model::eta::TaskEta> for TaskEta {
+    fn from(eta: data_model::eta::TaskEta) -> Self {
+        Self {
+            id: eta.task.id.to_string(),
+            compute_fn: eta.task.compute_fn_name,
+            running: eta.running,
+            queue_position: eta.queue_position,
+            remaining_ms: eta.remaining_ms,
+        }
+    }
+}
+
+/// Estimated time left before an invocation finishes, from the latencies of
+/// its functions and the tasks queued before its own.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct InvocationEta {
+    pub invocation_id: String,
+    pub completed: bool,
+    pub remaining_ms: u64,
+    /// Outstanding tasks of the invocation
+    pub tasks: Vec<TaskEta>,
+    /// Functions without finished tasks yet, the estimate counts them as
+    /// instantaneous
+    pub fns_without_latency: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct GcStatus {
+    /// Number of blobs queued for deletion
+    pub pending_urls: usize,
+}
+
+/// Lookups of a read cache of the state store since the server started
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub hit_rate: f64,
+    pub entries: u64,
+    pub capacity: u64,
+}
+
+impl From<state_store::read_cache::CacheStats> for CacheStats {
+    fn from(stats: state_store::read_cache::CacheStats) -> Self {
+        Self {
+            hit_rate: stats.hit_rate(),
+            hits: stats.hits,
+            misses: stats.misses,
+            entries: stats.entries,
+            capacity: stats.capacity,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ReadCacheStats {
+    pub compute_graphs: CacheStats,
+    pub invocation_ctxs: CacheStats,
+}
+
+impl From<state_store::read_cache::ReadCacheStats> for ReadCacheStats {
+    fn from(stats: state_store::read_cache::ReadCacheStats) -> Self {
+        Self {
+            compute_graphs: stats.compute_graphs.into(),
+            invocation_ctxs: stats.invocation_ctxs.into(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct StateChangeLag {
+    /// Number of state changes not processed by the scheduler yet
+    pub unprocessed: usize,
+    pub oldest_created_at: Option<u64>,
+    /// Age of the oldest unprocessed state change
+    pub lag_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ClusterStatus {
+    pub node_id: u64,
+    pub leader_id: Option<u64>,
+    /// Sequence number of the last replication log entry applied
+    pub last_applied_seq: u64,
+    /// Term of the last replication log entry applied
+    #[serde(default)]
+    pub last_applied_term: u64,
+    /// Highest term seen, each elected leader starts a new term
+    #[serde(default)]
+    pub term: u64,
+    /// First entry kept in the replication log, the entries before were
+    /// applied by every replica
+    #[serde(default)]
+    pub log_start_seq: u64,
+}
+
+impl ClusterStatus {
+    pub fn is_leader(&self) -> bool {
+        self.leader_id == Some(self.node_id)
+    }
+}
+
+/// Vote asked by a candidate, identified by its peer token, to become the
+/// leader of a term
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VoteRequest {
+    pub term: u64,
+    /// Seq and term of the last entry of the candidate's log
+    pub last_log_seq: u64,
+    pub last_log_term: u64,
+    /// Asks whether the vote would be granted, without starting the term
+    #[serde(default)]
+    pub pre_vote: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VoteResponse {
+    /// Term of the voter, the candidate stands down when it's newer
+    pub term: u64,
+    pub granted: bool,
+}
+
+/// Answer of `can_i`, with the roles the caller is bound to in the namespace
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CanIResponse {
+    /// Unset when auth is disabled
+    pub identity: Option<String>,
+    pub namespace: String,
+    pub permission: Permission,
+    pub allowed: bool,
+    pub roles: Vec<Role>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UpdateExecutorLabels {
+    pub labels: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecutorApprovalStatus {
+    Pending,
+    Approved,
+    Denied,
+}
+
+impl From<data_model::executor_approval::ApprovalStatus> for ExecutorApprovalStatus {
+    fn from(status: data_model::executor_approval::ApprovalStatus) -> Self {
+        match status {
+            data_model::executor_approval::ApprovalStatus::Pending => Self::Pending,
+            data_model::executor_approval::ApprovalStatus::Approved => Self::Approved,
+            data_model::executor_approval::ApprovalStatus::Denied => Self::Denied,
+        }
+    }
+}
+
+/// An executor which registered while executors need to be approved.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ExecutorApproval {
+    pub executor_id: String,
+    pub status: ExecutorApprovalStatus,
     pub addr: String,
     pub image_name: String,
+    /// Labels the executor reported when it first registered
+    pub reported_labels: HashMap<String, serde_json::Value>,
+    /// Labels stamped on the executor, over the ones it reports
     pub labels: HashMap<String, serde_json::Value>,
+    pub requested_at: u64,
+    pub decided_at: Option<u64>,
+    pub decided_by: Option<String>,
 }
 
-impl From<data_model::ExecutorMetadata> for ExecutorMetadata {
-    fn from(executor: data_model::ExecutorMetadata) -> Self {
+impl From<data_model::executor_approval::ExecutorApproval> for ExecutorApproval {
+    fn from(approval: data_model::executor_approval::ExecutorApproval) -> Self {
         Self {
-            id: executor.id.to_string(),
-            addr: executor.addr,
-            image_name: executor.image_name,
-            labels: executor.labels,
+            executor_id: approval.executor_id.get().to_string(),
+            status: approval.status.into(),
+            addr: approval.addr,
+            image_name: approval.image_name,
+            reported_labels: approval.reported_labels,
+            labels: approval.labels,
+            requested_at: approval.requested_at,
+            decided_at: approval.decided_at,
+            decided_by: approval.decided_by,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ExecutorApprovalsList {
+    pub approvals: Vec<ExecutorApproval>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ApproveExecutor {
+    /// Labels stamped on the executor
+    #[serde(default)]
+    pub labels: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExecutorApprovalsParams {
+    /// Only lists the executors with this status
+    pub status: Option<ExecutorApprovalStatus>,
+}
+
+/// Task an executor was running when it went down.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RecoveredTask {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub compute_fn: String,
+    pub invocation_id: String,
+    pub task_id: String,
+    /// Urls of the blobs the executor uploaded for the outputs of the task
+    #[serde(default)]
+    pub partial_outputs: Vec<String>,
+}
+
+impl From<RecoveredTask> for data_model::executor_reconciliation::ReportedTask {
+    fn from(task: RecoveredTask) -> Self {
+        Self {
+            namespace: task.namespace,
+            compute_graph: task.compute_graph,
+            compute_fn: task.compute_fn,
+            invocation_id: task.invocation_id,
+            task_id: data_model::TaskId::new(task.task_id),
+            partial_outputs: task.partial_outputs,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReconciliationOutcome {
+    /// The task was allocated to the executor, it's scheduled again
+    Retried,
+    /// The task finished before the executor went down
+    Finished,
+    /// The allocation of the task expired before the executor came back
+    LeaseLost,
+    /// The task doesn't exist anymore
+    NotFound,
+}
+
+impl From<data_model::executor_reconciliation::ReconciliationOutcome> for ReconciliationOutcome {
+    fn from(outcome: data_model::executor_reconciliation::ReconciliationOutcome) -> Self {
+        match outcome {
+            data_model::executor_reconciliation::ReconciliationOutcome::Retried => Self::Retried,
+            data_model::executor_reconciliation::ReconciliationOutcome::Finished => Self::Finished,
+            data_model::executor_reconciliation::ReconciliationOutcome::LeaseLost => {
+                Self::LeaseLost
+            }
+            data_model::executor_reconciliation::ReconciliationOutcome::NotFound => Self::NotFound,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ReconciledTask {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub compute_fn: String,
+    pub invocation_id: String,
+    pub task_id: String,
+    /// False for the tasks allocated to the executor which it didn't report
+    pub reported: bool,
+    pub outcome: ReconciliationOutcome,
+    /// Outputs the task streamed before the crash, kept for its retry
+    pub salvaged_outputs: u64,
+    /// Whether the retry resumes from a checkpoint of the task
+    pub salvaged_checkpoint: bool,
+    /// Partial outputs which were never committed, they're deleted
+    pub discarded_outputs: Vec<String>,
+}
+
+impl From<data_model::executor_reconciliation::ReconciledTask> for ReconciledTask {
+    fn from(task: data_model::executor_reconciliation::ReconciledTask) -> Self {
+        Self {
+            namespace: task.namespace,
+            compute_graph: task.compute_graph,
+            compute_fn: task.compute_fn,
+            invocation_id: task.invocation_id,
+            task_id: task.task_id.to_string(),
+            reported: task.reported,
+            outcome: task.outcome.into(),
+            salvaged_outputs: task.salvaged_outputs,
+            salvaged_checkpoint: task.salvaged_checkpoint,
+            discarded_outputs: task.discarded_outputs,
+        }
+    }
+}
+
+/// Report of the reconciliation of the tasks of an executor which restarted.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ExecutorReconciliation {
+    pub executor_id: String,
+    pub restarted_at: u64,
+    /// Number of tasks scheduled again
+    pub retried: usize,
+    pub tasks: Vec<ReconciledTask>,
+}
+
+impl From<data_model::executor_reconciliation::ExecutorReconciliation> for ExecutorReconciliation {
+    fn from(reconciliation: data_model::executor_reconciliation::ExecutorReconciliation) -> Self {
+        Self {
+            executor_id: reconciliation.executor_id.get().to_string(),
+            restarted_at: reconciliation.restarted_at,
+            retried: reconciliation.num_retried(),
+            tasks: reconciliation.tasks.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ExecutorReconciliationsList {
+    pub reconciliations: Vec<ExecutorReconciliation>,
+    pub cursor: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertMetric {
+    FailedTaskRate,
+    FailedTasks,
+    UnallocatedTasks,
+    StateChangeLag,
+    StateChangeLagMs,
+    Executors,
+}
+
+impl From<data_model::alerts::AlertMetric> for AlertMetric {
+    fn from(metric: data_model::alerts::AlertMetric) -> Self {
+        match metric {
+            data_model::alerts::AlertMetric::FailedTaskRate => Self::FailedTaskRate,
+            data_model::alerts::AlertMetric::FailedTasks => Self::FailedTasks,
+            data_model::alerts::AlertMetric::UnallocatedTasks => Self::UnallocatedTasks,
+            data_model::alerts::AlertMetric::StateChangeLag => Self::StateChangeLag,
+            data_model::alerts::AlertMetric::StateChangeLagMs => Self::StateChangeLagMs,
+            data_model::alerts::AlertMetric::Executors => Self::Executors,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema, PartialEq)]
+pub enum AlertComparison {
+    #[serde(rename = ">")]
+    Above,
+    #[serde(rename = ">=")]
+    AtLeast,
+    #[serde(rename = "<")]
+    Below,
+    #[serde(rename = "<=")]
+    AtMost,
+}
+
+impl From<data_model::alerts::AlertComparison> for AlertComparison {
+    fn from(comparison: data_model::alerts::AlertComparison) -> Self {
+        match comparison {
+            data_model::alerts::AlertComparison::Above => Self::Above,
+            data_model::alerts::AlertComparison::AtLeast => Self::AtLeast,
+            data_model::alerts::AlertComparison::Below => Self::Below,
+            data_model::alerts::AlertComparison::AtMost => Self::AtMost,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertState {
+    Ok,
+    /// The rule holds, for less than its pending duration
+    Pending,
+    Firing,
+}
+
+impl From<data_model::alerts::AlertState> for AlertState {
+    fn from(state: data_model::alerts::AlertState) -> Self {
+        match state {
+            data_model::alerts::AlertState::Ok => Self::Ok,
+            data_model::alerts::AlertState::Pending => Self::Pending,
+            data_model::alerts::AlertState::Firing => Self::Firing,
         }
     }
 }
 
+/// Alert of a rule of the server configuration, as of its last evaluation
+/// by the leader.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Alert {
+    pub name: String,
+    pub metric: AlertMetric,
+    pub namespace: Option<String>,
+    pub compute_graph: Option<String>,
+    pub window_secs: Option<u64>,
+    pub comparison: AlertComparison,
+    pub threshold: f64,
+    pub for_secs: u64,
+    pub state: AlertState,
+    /// Value of the metric, unset while it has none
+    pub value: Option<f64>,
+    /// When the alert entered its state
+    pub since: u64,
+    pub evaluated_at: u64,
+}
+
+impl From<data_model::alerts::AlertStatus> for Alert {
+    fn from(status: data_model::alerts::AlertStatus) -> Self {
+        Self {
+            name: status.rule.name,
+            metric: status.rule.metric.into(),
+            namespace: status.rule.namespace,
+            compute_graph: status.rule.compute_graph,
+            window_secs: status.rule.window_secs,
+            comparison: status.rule.comparison.into(),
+            threshold: status.rule.threshold,
+            for_secs: status.rule.for_secs,
+            state: status.state.into(),
+            value: status.value,
+            since: status.since,
+            evaluated_at: status.evaluated_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AlertsList {
+    pub alerts: Vec<Alert>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AlertsParams {
+    /// Only lists the alerts in this state
+    pub state: Option<AlertState>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RequeueTasksParams {
+    /// Number of allocations moved at a time
+    pub batch_size: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReplicationLogParams {
+    pub after: u64,
+    /// Term of the entry at `after` on the follower
+    pub after_term: Option<u64>,
+    pub limit: Option<usize>,
+    /// Current term of the follower
+    pub term: Option<u64>,
+    /// Waits up to this long for new entries when there are none
+    pub wait_ms: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct FnEnvironmentParams {
+    /// Renders the parameters of the invocation instead of the values bound
+    /// to the graph
+    pub invocation_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct FnEnvironment {
+    pub env: HashMap<String, String>,
+    pub secrets: HashMap<String, String>,
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct InvocationQueryParams {
     pub block_until_finish: Option<bool>,
+    /// JSON object of labels attached to the invocation
+    pub labels: Option<String>,
+    /// JSON object of indexed attributes, strings, numbers or booleans
+    pub attributes: Option<String>,
+    /// Tasks of higher priority invocations are allocated first, defaults
+    /// to 0
+    pub priority: Option<i32>,
+    /// Open invocation group the invocation joins
+    pub invocation_group: Option<String>,
+    /// JSON object overriding the parameters of the graph
+    pub parameters: Option<String>,
+    /// When the data of the invocation was produced, in ms since epoch.
+    /// Event-time windows group their inputs by it.
+    pub event_time: Option<u64>,
+    /// Lane the tasks of the invocation are scheduled in, defaults to the
+    /// lane of the graph
+    pub lane: Option<SchedulingLane>,
+}
+
+impl InvocationQueryParams {
+    pub fn labels(&self) -> Result<HashMap<String, serde_json::Value>, IndexifyAPIError> {
+        match &self.labels {
+            Some(labels) => Ok(serde_json::from_str(labels)?),
+            None => Ok(HashMap::new()),
+        }
+    }
+
+    pub fn parameters(&self) -> Result<HashMap<String, serde_json::Value>, IndexifyAPIError> {
+        match &self.parameters {
+            Some(parameters) => Ok(serde_json::from_str(parameters)?),
+            None => Ok(HashMap::new()),
+        }
+    }
+
+    pub fn attributes(&self) -> Result<HashMap<String, serde_json::Value>, IndexifyAPIError> {
+        let Some(attributes) = &self.attributes else {
+            return Ok(HashMap::new());
+        };
+        let attributes = serde_json::from_str(attributes)?;
+        validate_attributes(&attributes)
+            .map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))?;
+        Ok(attributes)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SearchInvocationsParams {
+    /// Comma separated attribute conditions, e.g.
+    /// `doc_type=invoice,amount>=100`
+    pub filter: String,
+    pub limit: Option<usize>,
+    /// Id of the last invocation of the previous page
+    pub cursor: Option<String>,
+}
+
+impl SearchInvocationsParams {
+    pub fn filter(&self) -> Result<LabelsFilter, IndexifyAPIError> {
+        LabelsFilter::from_str(&self.filter)
+            .map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct InvocationSearchResults {
+    pub invocations: Vec<DataObject>,
+    pub cursor: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ListInvocationsParams {
+    pub limit: Option<usize>,
+    pub cursor: Option<Vec<u8>>,
+    /// Comma separated label conditions, e.g. `customer_id=c1,priority>2`
+    pub labels_filter: Option<String>,
+}
+
+impl ListInvocationsParams {
+    pub fn labels_filter(&self) -> Result<LabelsFilter, IndexifyAPIError> {
+        let Some(labels_filter) = &self.labels_filter else {
+            return Ok(LabelsFilter::default());
+        };
+        LabelsFilter::from_str(labels_filter)
+            .map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))
+    }
 }
 
 #[cfg(test)]
@@ -499,4 +3831,43 @@ mod tests {
         json_value["namespace"] = serde_json::Value::String("test".to_string());
         let _: super::ComputeGraph = serde_json::from_value(json_value).unwrap();
     }
+
+    #[test]
+    fn test_internal_error_status_codes() {
+        use axum::http::StatusCode;
+        use data_model::error::DataModelError;
+        use state_store::error::StateStoreError;
+
+        use super::IndexifyAPIError;
+
+        let status_code = |e: anyhow::Error| IndexifyAPIError::internal_error(e).status_code;
+        assert_eq!(
+            status_code(DataModelError::MissingField("namespace").into()),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            status_code(StateStoreError::not_found("compute graph").into()),
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            status_code(StateStoreError::Conflict("exists".to_string()).into()),
+            StatusCode::CONFLICT
+        );
+        assert_eq!(
+            status_code(StateStoreError::ReadOnly.into()),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(
+            status_code(StateStoreError::Maintenance.into()),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(
+            status_code(StateStoreError::NotAcknowledged.into()),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(
+            status_code(anyhow::anyhow!("io error")),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+    }
 }
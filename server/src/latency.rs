@@ -0,0 +1,206 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::Result;
+use data_model::{
+    latency::{FunctionLatency, LatencySketch},
+    Task,
+};
+use indexify_utils::get_epoch_time_in_ms;
+use state_store::{
+    requests::{RecordFunctionLatenciesRequest, RequestPayload, StateMachineUpdateRequest},
+    IndexifyState,
+};
+use tokio::sync::watch;
+
+// How often the sketches updated since they were last persisted are written
+const PERSIST_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Adds the latencies of the finished tasks to the sketches of their
+/// functions and persists the sketches periodically. Runs on the leader,
+/// starting from the sketches persisted by the previous one.
+pub struct LatencyTracker {
+    state: Arc<IndexifyState>,
+    // Key -> sketch of the function, and whether it changed since it was
+    // persisted
+    latencies: Mutex<HashMap<String, (FunctionLatency, bool)>>,
+}
+
+impl LatencyTracker {
+    pub fn new(state: Arc<IndexifyState>) -> Result<Self> {
+        let latencies = state
+            .reader()
+            .all_function_latencies()?
+            .into_iter()
+            .map(|latency| (latency.key(), (latency, false)))
+            .collect();
+        Ok(Self {
+            state,
+            latencies: Mutex::new(latencies),
+        })
+    }
+
+    /// Adds the latency of a finished task, measured from its allocation, and
+    /// whether it started cold.
+    pub fn record(&self, task: &Task) {
+        let (Some(_), Some(usage)) = (task.allocated_at, &task.usage) else {
+            return;
+        };
+        let key = FunctionLatency::key_from(
+            &task.namespace,
+            &task.compute_graph_name,
+            &task.compute_fn_name,
+        );
+        let mut latencies = self.latencies.lock().unwrap();
+        let (latency, dirty) = latencies.entry(key).or_insert_with(|| {
+            (
+                FunctionLatency {
+                    namespace: task.namespace.clone(),
+                    compute_graph: task.compute_graph_name.clone(),
+                    compute_fn: task.compute_fn_name.clone(),
+                    sketch: LatencySketch::default(),
+                    cold_starts: 0,
+                    warm_starts: 0,
+                    updated_at: 0,
+                },
+                false,
+            )
+        });
+        latency.sketch.add(usage.duration_ms);
+        latency.cold_starts += usage.cold_starts;
+        latency.warm_starts += usage.warm_starts;
+        latency.updated_at = get_epoch_time_in_ms();
+        *dirty = true;
+    }
+
+    pub async fn persist(&self) -> Result<()> {
+        let changed: Vec<FunctionLatency> = {
+            let mut latencies = self.latencies.lock().unwrap();
+            latencies
+                .values_mut()
+                .filter(|(_, dirty)| *dirty)
+                .map(|(latency, dirty)| {
+                    *dirty = false;
+                    latency.clone()
+                })
+                .collect()
+        };
+        if changed.is_empty() {
+            return Ok(());
+        }
+        let result = self
+            .state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::RecordFunctionLatencies(RecordFunctionLatenciesRequest {
+                    latencies: changed.clone(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await;
+        if result.is_err() {
+            // Persisted with the next interval
+            let mut latencies = self.latencies.lock().unwrap();
+            for latency in changed {
+                if let Some((_, dirty)) = latencies.get_mut(&latency.key()) {
+                    *dirty = true;
+                }
+            }
+        }
+        result
+    }
+
+    pub async fn start(&self, mut shutdown_rx: watch::Receiver<()>) -> Result<()> {
+        let mut interval = tokio::time::interval(PERSIST_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    if let Err(err) = self.persist().await {
+                        tracing::error!("error persisting function latencies: {:?}", err);
+                    }
+                }
+                _ = shutdown_rx.changed() => return self.persist().await,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use data_model::{
+        test_objects::tests::{create_mock_task, mock_graph_a, TEST_NAMESPACE},
+        TaskUsage,
+    };
+    use state_store::requests::{CreateComputeGraphRequest, NamespaceRequest};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_latency_tracker() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let graph = mock_graph_a();
+        for payload in [
+            RequestPayload::CreateNameSpace(NamespaceRequest {
+                name: TEST_NAMESPACE.to_string(),
+                queue_limits: Default::default(),
+                scheduling_weight: None,
+                reserved_capacity: None,
+                replication: None,
+            }),
+            RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph: graph.clone(),
+                expected_revision: None,
+                force: false,
+            }),
+        ] {
+            state
+                .write(StateMachineUpdateRequest {
+                    payload,
+                    state_changes_processed: vec![],
+                })
+                .await?;
+        }
+
+        let tracker = LatencyTracker::new(state.clone())?;
+        for duration_ms in [10, 20, 30, 1000] {
+            let mut task = create_mock_task(&graph, "fn_b", "id", "inv");
+            task.allocated_at = Some(1);
+            task.usage = Some(TaskUsage {
+                tasks: 1,
+                duration_ms,
+                cold_starts: (duration_ms == 1000) as u64,
+                warm_starts: (duration_ms != 1000) as u64,
+                ..Default::default()
+            });
+            tracker.record(&task);
+        }
+        // Unallocated tasks have no latency
+        tracker.record(&create_mock_task(&graph, "fn_c", "id", "inv"));
+        tracker.persist().await?;
+
+        let latencies = state
+            .reader()
+            .graph_function_latencies(TEST_NAMESPACE, &graph.name)?;
+        assert_eq!(latencies.len(), 1);
+        assert_eq!(latencies[0].compute_fn, "fn_b");
+        assert_eq!(latencies[0].sketch.count, 4);
+        assert_eq!(latencies[0].sketch.quantile(0.99), Some(1000));
+        assert_eq!(latencies[0].cold_starts, 1);
+        assert_eq!(latencies[0].warm_starts, 3);
+
+        // A new leader continues from the persisted sketches
+        let tracker = LatencyTracker::new(state.clone())?;
+        let mut task = create_mock_task(&graph, "fn_b", "id", "inv");
+        task.allocated_at = Some(1);
+        task.usage = Some(TaskUsage::default());
+        tracker.record(&task);
+        tracker.persist().await?;
+        let latencies = state.reader().all_function_latencies()?;
+        assert_eq!(latencies[0].sketch.count, 5);
+        Ok(())
+    }
+}
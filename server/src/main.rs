@@ -3,39 +3,90 @@ use std::path::PathBuf;
 use clap::Parser;
 use service::Service;
 use tracing::error;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, Layer};
+use tracing_subscriber::{layer::SubscriberExt, reload, util::SubscriberInitExt, Layer};
 
+mod alerts;
+mod archive;
+mod auth;
+mod cluster;
 mod config;
+mod config_reload;
+mod download_urls;
 mod executors;
 mod gc;
+mod grpc;
 mod http_objects;
+mod latency;
+mod oidc;
+mod replicas;
 mod routes;
 mod scheduler;
+mod secrets;
 mod server;
 mod service;
+mod sinks;
+mod sla;
 mod system_tasks;
+mod task_tokens;
+mod tiering;
+mod wasm;
+mod webhooks;
+mod windows;
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
 struct Cli {
     #[arg(short, long, value_name = "config file")]
     config: Option<PathBuf>,
+    /// Print the pending state store migrations and exit without applying
+    /// them
+    #[arg(long)]
+    dry_run_migrations: bool,
 }
 
 #[tokio::main]
 async fn main() {
-    let env_filter = tracing_subscriber::EnvFilter::try_from_default_env()
-        .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info"));
-    tracing_subscriber::registry()
-        .with(tracing_subscriber::fmt::layer().with_filter(env_filter))
-        .init();
-
     let cli = Cli::parse();
-    let config = match cli.config {
+    let config = match &cli.config {
         Some(path) => config::ServerConfig::from_path(path.to_str().unwrap()).unwrap(),
         None => config::ServerConfig::default(),
     };
-    let service = Service::new(config);
+
+    let env_filter = tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new(&config.runtime.log_level));
+    let (env_filter, log_filter) = reload::Layer::new(env_filter);
+    let fmt_layer = match config.log_format {
+        config::LogFormat::Text => tracing_subscriber::fmt::layer().boxed(),
+        // Fields of the enclosing spans, e.g. the request id, namespace and
+        // invocation id of a request, are logged with every event
+        config::LogFormat::Json => tracing_subscriber::fmt::layer()
+            .json()
+            .flatten_event(true)
+            .with_current_span(false)
+            .with_span_list(true)
+            .boxed(),
+    };
+    tracing_subscriber::registry()
+        .with(fmt_layer.with_filter(env_filter))
+        .init();
+
+    if cli.dry_run_migrations {
+        match state_store::plan_migrations(config.state_store_path.clone().into()) {
+            Ok(report) => {
+                println!(
+                    "state store schema version {}, latest version {}",
+                    report.from_version, report.to_version
+                );
+                for migration in report.migrations {
+                    println!("pending migration: {}", migration);
+                }
+            }
+            Err(err) => error!("Error planning migrations: {}", err),
+        }
+        return;
+    }
+
+    let service = Service::new(config, cli.config, log_filter);
     if let Err(err) = service.start().await {
         error!("Error starting service: {}", err);
     }
@@ -0,0 +1,408 @@
+//! Authentication with the JWTs of an external OpenID Connect identity
+//! provider. Tokens are verified with the signing keys of the provider's
+//! JWKS, which are refreshed periodically and when a token is signed by an
+//! unknown key, and their claims are mapped to the identity role bindings
+//! apply to.
+
+use std::{
+    collections::HashMap,
+    future::pending,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ring::signature::{
+    RsaPublicKeyComponents,
+    UnparsedPublicKey,
+    ECDSA_P256_SHA256_FIXED,
+    RSA_PKCS1_2048_8192_SHA256,
+};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::{watch, Notify},
+    time::Instant,
+};
+use tracing::{error, info};
+
+use crate::{auth::Identity, config::RuntimeConfig};
+
+// Unknown signing keys trigger a refresh at most this often
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OidcConfig {
+    /// Must match the `iss` claim of tokens
+    pub issuer: String,
+    /// Must be one of the `aud` claims of tokens
+    pub audience: String,
+    /// Discovered from the openid configuration of the issuer when unset
+    #[serde(default)]
+    pub jwks_url: Option<String>,
+    #[serde(default = "default_jwks_refresh_secs")]
+    pub jwks_refresh_secs: u64,
+    /// Claim holding the name of the identity
+    #[serde(default = "default_subject_claim")]
+    pub subject_claim: String,
+    /// Claim holding the groups of the identity, a list of strings
+    #[serde(default = "default_groups_claim")]
+    pub groups_claim: String,
+    /// Clock skew tolerated when checking the expiry of tokens
+    #[serde(default = "default_leeway_secs")]
+    pub leeway_secs: u64,
+}
+
+fn default_jwks_refresh_secs() -> u64 {
+    3600
+}
+
+fn default_subject_claim() -> String {
+    "sub".to_string()
+}
+
+fn default_groups_claim() -> String {
+    "groups".to_string()
+}
+
+fn default_leeway_secs() -> u64 {
+    60
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct JwkSet {
+    keys: Vec<Jwk>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Jwk {
+    kty: String,
+    #[serde(default)]
+    kid: Option<String>,
+    #[serde(default)]
+    alg: Option<String>,
+    // RSA keys
+    #[serde(default)]
+    n: Option<String>,
+    #[serde(default)]
+    e: Option<String>,
+    // EC keys
+    #[serde(default)]
+    crv: Option<String>,
+    #[serde(default)]
+    x: Option<String>,
+    #[serde(default)]
+    y: Option<String>,
+}
+
+impl Jwk {
+    fn verify(&self, alg: &str, message: &[u8], signature: &[u8]) -> Result<()> {
+        if self.alg.as_deref().is_some_and(|key_alg| key_alg != alg) {
+            bail!("token algorithm {} doesn't match its key", alg);
+        }
+        match (alg, self.kty.as_str()) {
+            ("RS256", "RSA") => {
+                let n = decode_param(&self.n, "n")?;
+                let e = decode_param(&self.e, "e")?;
+                RsaPublicKeyComponents { n: &n, e: &e }
+                    .verify(&RSA_PKCS1_2048_8192_SHA256, message, signature)
+                    .map_err(|_| anyhow!("invalid token signature"))
+            }
+            ("ES256", "EC") if self.crv.as_deref() == Some("P-256") => {
+                let mut point = vec![0x04];
+                point.extend(decode_param(&self.x, "x")?);
+                point.extend(decode_param(&self.y, "y")?);
+                UnparsedPublicKey::new(&ECDSA_P256_SHA256_FIXED, point)
+                    .verify(message, signature)
+                    .map_err(|_| anyhow!("invalid token signature"))
+            }
+            _ => bail!("unsupported token algorithm {}", alg),
+        }
+    }
+}
+
+fn decode_param(param: &Option<String>, name: &str) -> Result<Vec<u8>> {
+    let param = param
+        .as_ref()
+        .ok_or_else(|| anyhow!("signing key is missing {}", name))?;
+    Ok(URL_SAFE_NO_PAD.decode(param)?)
+}
+
+#[derive(Debug, Deserialize)]
+struct JwtHeader {
+    alg: String,
+    #[serde(default)]
+    kid: Option<String>,
+}
+
+/// Signing keys of the identity provider, by key id.
+#[derive(Default)]
+pub struct JwksCache {
+    keys: RwLock<HashMap<String, Jwk>>,
+    refresh_requested: Notify,
+}
+
+impl JwksCache {
+    /// Identity of a token signed by one of the cached keys, issued for the
+    /// configured audience and not expired at `now_secs`.
+    pub fn verify(&self, config: &OidcConfig, token: &str, now_secs: u64) -> Result<Identity> {
+        let mut parts = token.splitn(3, '.');
+        let (Some(header), Some(claims), Some(signature)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            bail!("malformed token");
+        };
+        let message = &token[..header.len() + 1 + claims.len()];
+        let header: JwtHeader = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(header)?)?;
+        let key = self
+            .keys
+            .read()
+            .unwrap()
+            .get(header.kid.as_deref().unwrap_or_default())
+            .cloned();
+        let Some(key) = key else {
+            // The provider may have rotated its keys
+            self.refresh_requested.notify_one();
+            bail!("unknown signing key {:?}", header.kid);
+        };
+        key.verify(
+            &header.alg,
+            message.as_bytes(),
+            &URL_SAFE_NO_PAD.decode(signature)?,
+        )?;
+
+        let claims: serde_json::Value = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(claims)?)?;
+        if claims["iss"].as_str() != Some(config.issuer.as_str()) {
+            bail!("token wasn't issued by {}", config.issuer);
+        }
+        let audience_matches = match &claims["aud"] {
+            serde_json::Value::String(audience) => *audience == config.audience,
+            serde_json::Value::Array(audiences) => audiences
+                .iter()
+                .any(|audience| audience.as_str() == Some(config.audience.as_str())),
+            _ => false,
+        };
+        if !audience_matches {
+            bail!("token wasn't issued for {}", config.audience);
+        }
+        let expires_at = claims["exp"]
+            .as_u64()
+            .ok_or_else(|| anyhow!("token has no expiry"))?;
+        if expires_at + config.leeway_secs < now_secs {
+            bail!("token expired");
+        }
+        if claims["nbf"]
+            .as_u64()
+            .is_some_and(|not_before| not_before > now_secs + config.leeway_secs)
+        {
+            bail!("token isn't valid yet");
+        }
+        let name = claims[&config.subject_claim]
+            .as_str()
+            .ok_or_else(|| anyhow!("token has no {} claim", config.subject_claim))?;
+        let groups = claims[&config.groups_claim]
+            .as_array()
+            .map(|groups| {
+                groups
+                    .iter()
+                    .filter_map(|group| group.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok(Identity {
+            name: name.to_string(),
+            groups,
+        })
+    }
+
+    fn set_keys(&self, jwks: JwkSet) -> usize {
+        let keys: HashMap<_, _> = jwks
+            .keys
+            .into_iter()
+            .map(|key| (key.kid.clone().unwrap_or_default(), key))
+            .collect();
+        let num_keys = keys.len();
+        *self.keys.write().unwrap() = keys;
+        num_keys
+    }
+
+    async fn refresh(&self, client: &reqwest::Client, config: &OidcConfig) -> Result<usize> {
+        let jwks_url = match &config.jwks_url {
+            Some(jwks_url) => jwks_url.clone(),
+            None => {
+                let discovery_url = format!(
+                    "{}/.well-known/openid-configuration",
+                    config.issuer.trim_end_matches('/')
+                );
+                let discovery: serde_json::Value = client
+                    .get(&discovery_url)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+                discovery["jwks_uri"]
+                    .as_str()
+                    .ok_or_else(|| anyhow!("{} has no jwks_uri", discovery_url))?
+                    .to_string()
+            }
+        };
+        let jwks: JwkSet = client
+            .get(&jwks_url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(self.set_keys(jwks))
+    }
+}
+
+/// Keeps the signing keys of the configured identity provider up to date,
+/// until shutdown.
+pub async fn refresh_jwks(
+    cache: Arc<JwksCache>,
+    mut config_rx: watch::Receiver<RuntimeConfig>,
+    mut shutdown_rx: watch::Receiver<()>,
+) {
+    let client = reqwest::Client::new();
+    let mut last_refresh: Option<Instant> = None;
+    loop {
+        let oidc = config_rx
+            .borrow()
+            .auth
+            .as_ref()
+            .and_then(|auth| auth.oidc.clone());
+        if let Some(oidc) = &oidc {
+            match cache.refresh(&client, oidc).await {
+                Ok(num_keys) => info!("refreshed {} signing keys of {}", num_keys, oidc.issuer),
+                Err(err) => error!(
+                    "failed to refresh the signing keys of {}: {:?}",
+                    oidc.issuer, err
+                ),
+            }
+            last_refresh = Some(Instant::now());
+        }
+        let next_refresh = async {
+            match &oidc {
+                Some(oidc) => tokio::time::sleep(Duration::from_secs(oidc.jwks_refresh_secs)).await,
+                None => pending().await,
+            }
+        };
+        let refresh_requested = async {
+            cache.refresh_requested.notified().await;
+            if let Some(last_refresh) = last_refresh {
+                tokio::time::sleep_until(last_refresh + MIN_REFRESH_INTERVAL).await;
+            }
+        };
+        tokio::select! {
+            _ = next_refresh => {}
+            _ = refresh_requested => {}
+            changed = config_rx.changed() => {
+                if changed.is_err() {
+                    return;
+                }
+            }
+            _ = shutdown_rx.changed() => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ring::{
+        rand::SystemRandom,
+        signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_FIXED_SIGNING},
+    };
+
+    use super::*;
+
+    fn config() -> OidcConfig {
+        OidcConfig {
+            issuer: "https://idp.example.com".to_string(),
+            audience: "indexify".to_string(),
+            jwks_url: None,
+            jwks_refresh_secs: default_jwks_refresh_secs(),
+            subject_claim: default_subject_claim(),
+            groups_claim: default_groups_claim(),
+            leeway_secs: 0,
+        }
+    }
+
+    fn sign(key_pair: &EcdsaKeyPair, kid: &str, claims: serde_json::Value) -> String {
+        let header = serde_json::json!({"alg": "ES256", "kid": kid});
+        let message = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(header.to_string()),
+            URL_SAFE_NO_PAD.encode(claims.to_string())
+        );
+        let signature = key_pair
+            .sign(&SystemRandom::new(), message.as_bytes())
+            .unwrap();
+        format!("{}.{}", message, URL_SAFE_NO_PAD.encode(signature.as_ref()))
+    }
+
+    #[test]
+    fn test_verify() {
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng).unwrap();
+        let key_pair =
+            EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8.as_ref(), &rng)
+                .unwrap();
+        let point = key_pair.public_key().as_ref();
+        let cache = JwksCache::default();
+        cache.set_keys(JwkSet {
+            keys: vec![Jwk {
+                kty: "EC".to_string(),
+                kid: Some("key-1".to_string()),
+                alg: Some("ES256".to_string()),
+                n: None,
+                e: None,
+                crv: Some("P-256".to_string()),
+                x: Some(URL_SAFE_NO_PAD.encode(&point[1..33])),
+                y: Some(URL_SAFE_NO_PAD.encode(&point[33..])),
+            }],
+        });
+        let config = config();
+        let claims = serde_json::json!({
+            "iss": "https://idp.example.com",
+            "aud": ["indexify", "other"],
+            "exp": 1000,
+            "sub": "alice",
+            "groups": ["ml"],
+        });
+
+        let identity = cache
+            .verify(&config, &sign(&key_pair, "key-1", claims.clone()), 900)
+            .unwrap();
+        assert_eq!(identity.name, "alice");
+        assert_eq!(identity.groups, vec!["ml".to_string()]);
+
+        // Expired
+        assert!(cache
+            .verify(&config, &sign(&key_pair, "key-1", claims.clone()), 1001)
+            .is_err());
+        // Unknown key
+        assert!(cache
+            .verify(&config, &sign(&key_pair, "key-2", claims.clone()), 900)
+            .is_err());
+        // Another audience
+        let mut other_audience = claims.clone();
+        other_audience["aud"] = "other".into();
+        assert!(cache
+            .verify(&config, &sign(&key_pair, "key-1", other_audience), 900)
+            .is_err());
+        // Tampered claims
+        let token = sign(&key_pair, "key-1", claims);
+        let parts: Vec<_> = token.split('.').collect();
+        let tampered = format!(
+            "{}.{}.{}",
+            parts[0],
+            URL_SAFE_NO_PAD.encode(
+                r#"{"iss":"https://idp.example.com","aud":"indexify","exp":1000,"sub":"admin"}"#
+            ),
+            parts[2]
+        );
+        assert!(cache.verify(&config, &tampered, 900).is_err());
+    }
+}
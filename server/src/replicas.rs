@@ -0,0 +1,343 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{anyhow, Result};
+use blob_store::BlobStorage;
+use data_model::{DataPayload, OutputPayload, PayloadReplica, ReplicationPolicy, StorageTier};
+use indexify_utils::get_epoch_time_in_ms;
+use state_store::{
+    requests::{
+        PayloadOwner,
+        PayloadReplication,
+        RecordPayloadReplicasRequest,
+        RequestPayload,
+        StateMachineUpdateRequest,
+    },
+    IndexifyState,
+};
+
+// How often the payloads of new invocations are replicated
+const REPLICATION_INTERVAL: Duration = Duration::from_secs(60);
+
+// Payloads of invocations created before are no longer replicated
+const REPLICATION_WINDOW_MS: u64 = 24 * 60 * 60 * 1000;
+
+// Invocations whose payloads are replicated in a single write
+const BATCH_SIZE: usize = 100;
+
+/// Copies a payload to the replica storage of a region, checking the copy
+/// against the hash recorded when the payload was written.
+async fn copy_payload(
+    storage: &BlobStorage,
+    payload: &DataPayload,
+    region: &str,
+) -> Result<PayloadReplica> {
+    let result = storage.replicate(&payload.path, region).await?;
+    let sha256_hash = payload.stored_sha256_hash();
+    if !sha256_hash.is_empty() && result.sha256_hash != sha256_hash {
+        return Err(anyhow!(
+            "replica of payload {} in region {} doesn't match its hash",
+            payload.path,
+            region
+        ));
+    }
+    Ok(PayloadReplica {
+        region: region.to_string(),
+        path: result.url,
+    })
+}
+
+/// Applies the replication policies of the namespaces, runs on the leader.
+pub struct PayloadReplicator {
+    state: Arc<IndexifyState>,
+    storage: Arc<BlobStorage>,
+    shutdown_rx: tokio::sync::watch::Receiver<()>,
+}
+
+impl PayloadReplicator {
+    pub fn new(
+        state: Arc<IndexifyState>,
+        storage: Arc<BlobStorage>,
+        shutdown_rx: tokio::sync::watch::Receiver<()>,
+    ) -> Self {
+        Self {
+            state,
+            storage,
+            shutdown_rx,
+        }
+    }
+
+    async fn replicate(
+        &self,
+        owner: PayloadOwner,
+        payload: &DataPayload,
+        policy: &ReplicationPolicy,
+        replications: &mut Vec<PayloadReplication>,
+    ) {
+        // Only payloads of the hot tier are in the storage they're copied
+        // from, inline payloads are stored in the state store
+        if payload.tier != StorageTier::Hot || payload.is_inline() {
+            return;
+        }
+        for region in &policy.regions {
+            if payload.replica(region).is_some() {
+                continue;
+            }
+            match copy_payload(&self.storage, payload, region).await {
+                Ok(replica) => replications.push(PayloadReplication {
+                    owner: owner.clone(),
+                    payload: payload.clone(),
+                    replica,
+                }),
+                Err(err) => {
+                    tracing::error!(
+                        "error replicating payload {} to region {}: {:?}",
+                        payload.path,
+                        region,
+                        err
+                    );
+                }
+            }
+        }
+    }
+
+    /// Replicates the payloads of the invocations of the namespace created
+    /// since `created_after`, returns how many replicas were made.
+    async fn replicate_namespace(
+        &self,
+        namespace: &str,
+        policy: &ReplicationPolicy,
+        created_after: u64,
+    ) -> Result<usize> {
+        let mut replicated = 0;
+        let mut restart_key = None;
+        loop {
+            let (invocations, next_key) = self.state.reader().invocations_created_after(
+                namespace,
+                created_after,
+                restart_key.as_deref(),
+                Some(BATCH_SIZE),
+            )?;
+            let mut replications = vec![];
+            for ctx in invocations {
+                let reader = self.state.reader();
+                let invocation = reader.invocation_payload(
+                    &ctx.namespace,
+                    &ctx.compute_graph_name,
+                    &ctx.invocation_id,
+                )?;
+                let owner = PayloadOwner::Invocation {
+                    namespace: ctx.namespace.clone(),
+                    compute_graph: ctx.compute_graph_name.clone(),
+                    invocation_id: ctx.invocation_id.clone(),
+                };
+                self.replicate(owner, &invocation.payload, policy, &mut replications)
+                    .await;
+                let (outputs, _) = reader.list_outputs_by_compute_graph(
+                    &ctx.namespace,
+                    &ctx.compute_graph_name,
+                    &ctx.invocation_id,
+                    None,
+                    None,
+                )?;
+                for output in outputs {
+                    let OutputPayload::Fn(payload) = &output.payload else {
+                        continue;
+                    };
+                    let owner = PayloadOwner::FnOutput {
+                        key: output.key(&output.invocation_id),
+                    };
+                    self.replicate(owner, payload, policy, &mut replications)
+                        .await;
+                }
+            }
+            if !replications.is_empty() {
+                replicated += replications.len();
+                self.state
+                    .write(StateMachineUpdateRequest {
+                        payload: RequestPayload::RecordPayloadReplicas(
+                            RecordPayloadReplicasRequest { replications },
+                        ),
+                        state_changes_processed: vec![],
+                    })
+                    .await?;
+            }
+            match next_key {
+                Some(next_key) => restart_key = Some(next_key),
+                None => return Ok(replicated),
+            }
+        }
+    }
+
+    async fn apply_policies(&self) -> Result<()> {
+        let created_after = get_epoch_time_in_ms().saturating_sub(REPLICATION_WINDOW_MS);
+        for namespace in self.state.reader().get_all_namespaces()? {
+            let Some(policy) = &namespace.replication else {
+                continue;
+            };
+            if namespace.deleted_at.is_some() {
+                continue;
+            }
+            let replicated = self
+                .replicate_namespace(&namespace.name, policy, created_after)
+                .await?;
+            if replicated > 0 {
+                tracing::info!(
+                    "replicated {} payloads of namespace {}",
+                    replicated,
+                    namespace.name
+                );
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn start(&mut self) -> Result<()> {
+        let mut interval = tokio::time::interval(REPLICATION_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    if let Err(err) = self.apply_policies().await {
+                        tracing::error!("error applying replication policies: {:?}", err);
+                    }
+                }
+                _ = self.shutdown_rx.changed() => return Ok(()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use blob_store::{BlobStorageConfig, DiskStorageConfig, ReplicaStorageConfig};
+    use bytes::Bytes;
+    use data_model::{
+        test_objects::tests::{mock_graph_a, mock_invocation_payload, TEST_NAMESPACE},
+        GraphInvocationCtxBuilder,
+    };
+    use futures::stream;
+    use state_store::{
+        requests::NamespaceRequest,
+        serializer::{JsonEncode, JsonEncoder},
+        state_machine::IndexifyObjectsColumns,
+    };
+    use tokio::sync::watch;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_payload_replication() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let mut config = BlobStorageConfig::new_disk(temp_dir.path().join("hot").to_str().unwrap());
+        config.replicas = HashMap::from([(
+            "eu".to_string(),
+            ReplicaStorageConfig {
+                s3: None,
+                disk: Some(DiskStorageConfig {
+                    path: temp_dir.path().join("eu").to_str().unwrap().to_string(),
+                }),
+            },
+        )]);
+        let storage = Arc::new(BlobStorage::new(config)?);
+
+        state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateNameSpace(NamespaceRequest {
+                    name: TEST_NAMESPACE.to_string(),
+                    queue_limits: Default::default(),
+                    scheduling_weight: None,
+                    reserved_capacity: None,
+                    replication: Some(ReplicationPolicy {
+                        regions: vec!["eu".to_string()],
+                    }),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let data_stream = Box::pin(stream::once(async { Ok(Bytes::from("input")) }));
+        let res = storage.put("input", data_stream).await?;
+        let mut invocation = mock_invocation_payload();
+        invocation.payload = DataPayload {
+            path: res.url.clone(),
+            size: res.size_bytes,
+            sha256_hash: res.sha256_hash,
+            tier: StorageTier::Hot,
+            mime_type: None,
+            content_encoding: None,
+            metadata: Default::default(),
+            replicas: Vec::new(),
+            compression: None,
+        };
+        let mut ctx = GraphInvocationCtxBuilder::default()
+            .namespace(TEST_NAMESPACE.to_string())
+            .compute_graph_name(invocation.compute_graph_name.clone())
+            .invocation_id(invocation.id.clone())
+            .build(mock_graph_a())?;
+        ctx.created_at = get_epoch_time_in_ms();
+        state.db.put_cf(
+            &IndexifyObjectsColumns::GraphInvocations.cf_db(&state.db),
+            invocation.key(),
+            &JsonEncoder::encode(&invocation)?,
+        )?;
+        state.db.put_cf(
+            &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&state.db),
+            ctx.key(),
+            &JsonEncoder::encode(&ctx)?,
+        )?;
+
+        let replicator = PayloadReplicator::new(state.clone(), storage.clone(), watch::channel(()).1);
+        replicator.apply_policies().await?;
+        let reader = state.reader();
+        let replicated =
+            reader.invocation_payload(TEST_NAMESPACE, &ctx.compute_graph_name, &invocation.id)?;
+        let replica = replicated.payload.replica("eu").unwrap().clone();
+        assert_ne!(replica.path, res.url);
+        assert_eq!(storage.read_bytes(&replica.path).await?, "input");
+        assert_eq!(replicated.payload.nearest_path(Some("eu")), replica.path);
+        assert_eq!(replicated.payload.nearest_path(Some("us")), res.url);
+        assert_eq!(replicated.payload.nearest_path(None), res.url);
+        let policy = ReplicationPolicy {
+            regions: vec!["eu".to_string()],
+        };
+        assert_eq!(
+            replicator
+                .replicate_namespace(TEST_NAMESPACE, &policy, 0)
+                .await?,
+            0
+        );
+
+        // Replicas of payloads which changed since they were copied are
+        // garbage collected
+        let owner = PayloadOwner::Invocation {
+            namespace: TEST_NAMESPACE.to_string(),
+            compute_graph: ctx.compute_graph_name.clone(),
+            invocation_id: invocation.id.clone(),
+        };
+        let stale = PayloadReplica {
+            region: "us".to_string(),
+            path: storage.key_url("stale"),
+        };
+        state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::RecordPayloadReplicas(RecordPayloadReplicasRequest {
+                    replications: vec![PayloadReplication {
+                        owner,
+                        payload: DataPayload {
+                            path: storage.key_url("other"),
+                            ..invocation.payload.clone()
+                        },
+                        replica: stale.clone(),
+                    }],
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        assert_eq!(reader.get_gc_urls(None)?, vec![stale.path]);
+        let stored =
+            reader.invocation_payload(TEST_NAMESPACE, &ctx.compute_graph_name, &invocation.id)?;
+        assert_eq!(stored.payload.replicas, vec![replica]);
+        Ok(())
+    }
+}
@@ -1,32 +1,47 @@
-use std::{sync::Arc, time::Duration};
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
 use anyhow::Result;
 use axum::{
-    body::Body,
-    extract::{DefaultBodyLimit, MatchedPath, Multipart, Path, Query, Request, State},
-    http::{Method, Response},
-    response::{sse::Event, IntoResponse},
-    routing::{delete, get, post},
+    body::{Body, Bytes},
+    extract::{DefaultBodyLimit, Multipart, Path, Query, Request, State},
+    http::{HeaderMap, Method, Response, StatusCode},
+    middleware::{self, Next},
+    response::{sse::Event, IntoResponse, Redirect},
+    routing::{delete, get, post, put},
+    Extension,
     Json,
     Router,
 };
 use blob_store::PutResult;
-use data_model::ExecutorId;
+use data_model::{
+    filter::{Condition, LabelsFilter},
+    DataPayload,
+    ExecutorId,
+    StateChangeId,
+};
 use futures::StreamExt;
 use indexify_ui::Assets as UiAssets;
-use indexify_utils::GuardStreamExt;
+use indexify_utils::{get_epoch_time_in_ms, GuardStreamExt};
 use nanoid::nanoid;
 use state_store::{
+    error::StateStoreError,
+    replication::encode_entries,
     requests::{
         CreateComputeGraphRequest,
+        CreateExecutorPoolRequest,
+        CreateGraphCanaryRequest,
         DeleteComputeGraphRequest,
+        DeleteExecutorPoolRequest,
         DeleteInvocationRequest,
         NamespaceRequest,
         RequestPayload,
         StateMachineUpdateRequest,
+        DEFAULT_REQUEUE_BATCH_SIZE,
     },
+    ExecutorEvent,
     IndexifyState,
 };
+use task_scheduler::TaskScheduler;
 use tower_http::{
     cors::{Any, CorsLayer},
     trace::TraceLayer,
@@ -35,44 +50,274 @@ use tracing::info;
 use utoipa::{OpenApi, ToSchema};
 use utoipa_swagger_ui::SwaggerUi;
 
-use crate::executors::{self, EXECUTOR_TIMEOUT};
+use crate::{
+    auth::{Permission, Role},
+    cluster::{Cluster, TERM_HEADER},
+    config::RuntimeConfig,
+    config_reload::ConfigReloader,
+    download_urls::DownloadUrlSigner,
+    executors::{self, EXECUTOR_TIMEOUT},
+    oidc::JwksCache,
+    secrets::{self, SecretsCipher},
+    task_tokens::TaskTokenSigner,
+};
 
+mod alerts;
+mod authz;
+mod canary;
+mod checkpoints;
+mod data_deletions;
 mod download;
-mod internal_ingest;
-mod invoke;
+mod eta;
+mod executor_approvals;
+mod executor_blacklist;
+mod executor_control;
+mod executor_reconciliations;
+mod explain;
+mod gates;
+mod graph_pause;
+mod ingestion_hooks;
+pub(crate) mod internal_ingest;
+mod invocation_diff;
+pub(crate) mod invocation_groups;
+mod invocation_state;
+pub(crate) mod invoke;
+mod invoke_ref;
+mod invoke_sync;
+pub(crate) mod invoke_url;
+mod latencies;
+mod legal_holds;
+mod lint;
 mod logs;
+mod manifests;
+mod namespace_deletion;
+pub(crate) mod payload_compression;
+pub(crate) mod payload_limits;
+mod request_span;
+mod templates;
+#[cfg(test)]
+pub(crate) mod test_route_state;
+mod topology;
+mod webhooks;
+mod windows;
+use authz::{can_i, enforce_authz, PeerId};
+use alerts::list_alerts;
+use canary::{finalize_graph_canary, get_graph_canary, rollback_graph_canary, update_graph_canary};
+use checkpoints::{get_task_checkpoint, put_task_checkpoint};
+use data_deletions::{delete_ingested_data, get_data_deletion, list_data_deletions};
 use download::{
+    create_payload_url,
     download_fn_output_by_key,
     download_fn_output_payload,
     download_invocation_payload,
+    download_signed_payload,
+};
+use eta::get_invocation_eta;
+use executor_approvals::{approve_executor, deny_executor, list_executor_approvals};
+use executor_blacklist::{list_executor_blacklist, remove_executor_blacklist_entry};
+use executor_control::{acknowledge_executor_aborts, poll_executor_control};
+use executor_reconciliations::{list_executor_reconciliations, list_reconciliations_of_executor};
+use explain::explain_pending_task;
+use gates::signal_invocation;
+use graph_pause::{pause_compute_graph, resume_compute_graph};
+use internal_ingest::{ingest_files_from_executor, ingest_task_results};
+use invocation_diff::{create_diff_run, diff_invocations};
+use invocation_groups::{
+    close_invocation_group,
+    create_invocation_group,
+    get_invocation_group,
+    invocation_group_events,
+    list_invocation_group_members,
+    list_invocation_groups,
 };
-use internal_ingest::ingest_files_from_executor;
+use invocation_state::{get_invocation_state, set_invocation_state};
 use invoke::{invoke_with_file, invoke_with_object, rerun_compute_graph};
-use logs::download_logs;
+use invoke_ref::invoke_with_reference;
+use invoke_sync::invoke_and_wait;
+use invoke_url::{get_url_fetch, invoke_from_url, UrlFetches};
+use latencies::{get_function_latencies, list_function_latencies};
+use legal_holds::{
+    clear_graph_legal_hold,
+    clear_invocation_legal_hold,
+    get_legal_hold_audit,
+    list_legal_holds,
+    set_graph_legal_hold,
+    set_invocation_legal_hold,
+};
+use lint::{lint_compute_graph, lint_graph_definition};
+use logs::{download_logs, get_task_logs};
+use manifests::apply_manifest;
+use namespace_deletion::{delete_namespace, get_namespace_deletion};
+use payload_limits::{list_rejected_payloads, RejectedPayloadsMetrics};
+use request_span::{assign_request_id, make_request_span};
+use templates::instantiate_compute_graph;
+use topology::get_topology;
+use webhooks::{
+    create_webhook,
+    delete_webhook,
+    list_webhook_dead_letters,
+    list_webhooks,
+    redrive_webhook_dead_letters,
+};
+use windows::close_window;
 
 use crate::{
     executors::ExecutorManager,
     http_objects::{
+        Alert,
+        AlertComparison,
+        AlertMetric,
+        AlertState,
+        AlertsList,
+        ApproveExecutor,
+        CacheStats,
+        CanIResponse,
+        ClusterStatus,
         ComputeFn,
         ComputeGraph,
         ComputeGraphsList,
+        CreateExecutorPool,
+        CreateInvocationGroup,
         CreateNamespace,
+        CreateWebhook,
+        DataDeletion,
+        DataDeletionMode,
+        DataDeletionStatus,
+        DataDeletionsList,
         DataObject,
+        DeletedInvocation,
+        DiffRun,
+        DiffedInvocation,
         DynamicRouter,
+        ExecutorApproval,
+        ExecutorApprovalStatus,
+        ExecutorApprovalsList,
+        ExecutorBlacklist,
+        ExecutorBlacklistEntry,
+        ExecutorHeartbeat,
+        ExecutorHeartbeatResponse,
         ExecutorMetadata,
+        ExecutorPool,
+        ExecutorPoolsList,
+        ExecutorReconciliation,
+        ExecutorReconciliationsList,
+        FnEnvironment,
+        FnEnvironmentParams,
         FnOutputs,
+        FunctionLatencies,
+        FunctionLatency,
+        FunctionOutputsDiff,
+        GcStatus,
+        GpuDevice,
+        GpuRequirement,
+        GraphCanary,
         GraphInvocations,
+        GraphLint,
+        GraphParameter,
+        GraphPause,
+        GraphSla,
+        GraphStorageUsage,
+        GraphTopology,
         GraphVersion,
         IndexifyAPIError,
+        IngestionTransform,
+        InlineFnOutput,
+        InstantiateComputeGraph,
+        InvocationError,
+        InvocationEta,
+        InvocationEvent,
+        InvocationEvents,
+        InvocationGroup,
+        InvocationGroupMember,
+        InvocationGroupMembers,
+        InvocationGroupStatus,
+        InvocationGroupsList,
+        InvocationPlan,
         InvocationResult,
+        InvocationSearchResults,
+        InvocationStatus,
+        InvocationsDiff,
+        InvokeFromReference,
+        InvokeFromUrl,
+        LegalHold,
+        LegalHoldAction,
+        LegalHoldAuditEntry,
+        LegalHoldAuditLog,
+        LegalHoldsList,
+        LintRule,
+        LintWarning,
+        ListInvocationsParams,
         ListParams,
+        Manifest,
+        ManifestApplied,
+        ManifestGraph,
         Namespace,
+        NamespaceDeletion,
         NamespaceList,
+        NamespaceUsage,
         Node,
+        OutputDigest,
+        OutputLimit,
+        OutputLimits,
+        PauseComputeGraph,
+        PayloadUrl,
+        PayloadUrlRequest,
+        PendingReason,
+        PendingTaskExplanation,
+        PlanInvocationRequest,
+        PlannedNodeKind,
+        PlannedTask,
+        QueueLimits,
+        RateLimit,
+        ReadCacheStats,
+        ReconciledTask,
+        ReconciliationOutcome,
+        RecoveredTask,
+        RedriveDeadLetters,
+        RejectedPayloads,
+        RejectedPayloadsList,
+        ReplayInvocationParams,
+        ReplayedInvocation,
+        ReplayedTask,
+        ReplayedTaskStatus,
+        ReplicationLogParams,
+        ReplicationPolicy,
+        RequeueTasksParams,
+        ReservedCapacity,
+        RouterErrorPolicy,
+        RuntimeDescriptor,
         RuntimeInformation,
+        SchedulingLane,
+        SearchInvocationsParams,
+        SetLegalHold,
+        Sink,
+        SinkConnector,
+        SlaStatus,
+        Snapshot,
+        StateChangeLag,
+        StorageUsage,
+        SubGraph,
+        SyncInvocationResult,
         Task,
+        TaskAnalytics,
+        TaskEta,
+        TaskFailure,
         TaskOutcome,
+        TaskUsage,
         Tasks,
+        TopologyEdge,
+        TopologyNode,
+        UpdateExecutorLabels,
+        UpdateGraphCanary,
+        UrlFetch,
+        UrlFetchStatus,
+        VoteRequest,
+        VoteResponse,
+        Webhook,
+        WebhookDeadLetter,
+        WebhookDeadLetters,
+        WebhooksList,
+        Window,
     },
 };
 
@@ -81,40 +326,241 @@ use crate::{
         paths(
             create_namespace,
             namespaces,
+            namespace_deletion::delete_namespace,
+            namespace_deletion::get_namespace_deletion,
+            canary::get_graph_canary,
+            canary::update_graph_canary,
+            canary::finalize_graph_canary,
+            canary::rollback_graph_canary,
+            gates::signal_invocation,
+            windows::close_window,
+            templates::instantiate_compute_graph,
+            graph_pause::pause_compute_graph,
+            graph_pause::resume_compute_graph,
+            legal_holds::set_graph_legal_hold,
+            legal_holds::clear_graph_legal_hold,
+            legal_holds::set_invocation_legal_hold,
+            legal_holds::clear_invocation_legal_hold,
+            legal_holds::list_legal_holds,
+            legal_holds::get_legal_hold_audit,
+            data_deletions::delete_ingested_data,
+            data_deletions::get_data_deletion,
+            data_deletions::list_data_deletions,
             invoke::invoke_with_object,
+            invoke_sync::invoke_and_wait,
+            invoke_ref::invoke_with_reference,
+            invoke_url::invoke_from_url,
+            invoke_url::get_url_fetch,
             graph_invocations,
+            search_invocations,
             create_compute_graph,
+            manifests::apply_manifest,
             list_compute_graphs,
             get_compute_graph,
+            plan_invocation,
+            topology::get_topology,
+            lint::lint_compute_graph,
+            lint::lint_graph_definition,
+            get_sla_status,
+            latencies::get_function_latencies,
+            invocation_diff::diff_invocations,
+            invocation_diff::create_diff_run,
             delete_compute_graph,
+            undelete_compute_graph,
+            list_deleted_compute_graphs,
+            get_namespace_usage,
             list_tasks,
+            explain::explain_pending_task,
+            get_invocation_events,
+            replay_invocation,
+            eta::get_invocation_eta,
             list_outputs,
+            get_invocation_result,
             delete_invocation,
             logs::download_logs,
+            logs::get_task_logs,
             list_executors,
+            requeue_executor_tasks,
+            update_executor_labels,
+            executor_blacklist::list_executor_blacklist,
+            executor_blacklist::remove_executor_blacklist_entry,
+            executor_approvals::list_executor_approvals,
+            executor_approvals::approve_executor,
+            executor_approvals::deny_executor,
+            executor_reconciliations::list_executor_reconciliations,
+            executor_reconciliations::list_reconciliations_of_executor,
+            create_executor_pool,
+            list_executor_pools,
+            delete_executor_pool,
+            webhooks::create_webhook,
+            webhooks::list_webhooks,
+            webhooks::delete_webhook,
+            webhooks::list_webhook_dead_letters,
+            webhooks::redrive_webhook_dead_letters,
+            invocation_groups::create_invocation_group,
+            invocation_groups::list_invocation_groups,
+            invocation_groups::get_invocation_group,
+            invocation_groups::close_invocation_group,
+            invocation_groups::list_invocation_group_members,
+            invocation_groups::invocation_group_events,
+            create_snapshot,
+            run_gc,
+            state_change_lag,
+            read_cache_stats,
+            payload_limits::list_rejected_payloads,
+            latencies::list_function_latencies,
+            alerts::list_alerts,
+            cluster_status,
+            authz::can_i,
             download::download_fn_output_payload,
+            download::create_payload_url,
         ),
         components(
             schemas(
                 CreateNamespace,
                 NamespaceList,
+                NamespaceDeletion,
+                NamespaceUsage,
+                GraphStorageUsage,
+                StorageUsage,
+                TaskUsage,
+                ClusterStatus,
+                CanIResponse,
+                Permission,
+                Role,
+                UpdateExecutorLabels,
+                GraphCanary,
+                UpdateGraphCanary,
+                GraphPause,
+                PauseComputeGraph,
+                SetLegalHold,
+                LegalHold,
+                LegalHoldsList,
+                LegalHoldAction,
+                LegalHoldAuditEntry,
+                LegalHoldAuditLog,
+                DataDeletionMode,
+                DataDeletionStatus,
+                DeletedInvocation,
+                DataDeletion,
+                DataDeletionsList,
+                CreateExecutorPool,
+                ExecutorPool,
+                ExecutorPoolsList,
+                ExecutorApprovalStatus,
+                ExecutorApproval,
+                ExecutorApprovalsList,
+                ApproveExecutor,
+                RecoveredTask,
+                ReconciliationOutcome,
+                ReconciledTask,
+                ExecutorReconciliation,
+                ExecutorReconciliationsList,
+                AlertMetric,
+                AlertComparison,
+                AlertState,
+                Alert,
+                AlertsList,
+                CreateWebhook,
+                Webhook,
+                WebhooksList,
+                WebhookDeadLetter,
+                WebhookDeadLetters,
+                RedriveDeadLetters,
+                CreateInvocationGroup,
+                InvocationGroup,
+                InvocationGroupStatus,
+                InvocationGroupsList,
+                InvocationGroupMember,
+                InvocationGroupMembers,
                 IndexifyAPIError,
                 Namespace,
                 ComputeGraph,
                 Node,
+                SubGraph,
+                Window,
+                Sink,
+                SinkConnector,
                 DynamicRouter,
+                RouterErrorPolicy,
+                IngestionTransform,
+                SchedulingLane,
                 ComputeFn,
+                RateLimit,
+                OutputLimits,
+                GpuRequirement,
+                GpuDevice,
+                QueueLimits,
+                ReservedCapacity,
+                ReplicationPolicy,
+                GraphSla,
+                GraphParameter,
+                InstantiateComputeGraph,
+                SlaStatus,
                 ComputeGraphCreateType,
                 ComputeGraphsList,
+                Manifest,
+                ManifestGraph,
+                ManifestApplied,
+                PlanInvocationRequest,
+                PlannedNodeKind,
+                PlannedTask,
+                InvocationPlan,
+                TaskAnalytics,
+                TopologyNode,
+                TopologyEdge,
+                GraphTopology,
+                LintRule,
+                LintWarning,
+                GraphLint,
                 InvocationResult,
+                InvocationStatus,
+                SyncInvocationResult,
+                InlineFnOutput,
+                PayloadUrlRequest,
+                PayloadUrl,
+                PendingReason,
+                PendingTaskExplanation,
+                InvocationError,
+                InvocationEvent,
+                InvocationEvents,
+                ReplayedInvocation,
+                ReplayedTask,
+                ReplayedTaskStatus,
+                InvocationEta,
+                TaskEta,
                 ExecutorMetadata,
+                ExecutorBlacklist,
+                ExecutorBlacklistEntry,
                 RuntimeInformation,
+                RuntimeDescriptor,
+                Snapshot,
+                GcStatus,
+                StateChangeLag,
+                ReadCacheStats,
+                CacheStats,
+                RejectedPayloads,
+                RejectedPayloadsList,
+                FunctionLatency,
+                FunctionLatencies,
                 Task,
                 TaskOutcome,
+                TaskFailure,
+                OutputLimit,
                 Tasks,
                 GraphInvocations,
+                InvocationSearchResults,
+                InvocationsDiff,
+                FunctionOutputsDiff,
+                DiffedInvocation,
+                OutputDigest,
+                DiffRun,
                 GraphVersion,
                 DataObject,
+                InvokeFromReference,
+                InvokeFromUrl,
+                UrlFetch,
+                UrlFetchStatus,
             )
         ),
         tags(
@@ -124,11 +570,26 @@ use crate::{
 
 struct ApiDoc;
 
+const DEFAULT_SEARCH_LIMIT: usize = 100;
+
+// Multipart fields of a compute graph upload holding the WASM module of a
+// router, e.g. `wasm.route_by_type`
+const WASM_MODULE_FIELD_PREFIX: &str = "wasm.";
+
 #[derive(Clone)]
 pub struct RouteState {
     pub indexify_state: Arc<IndexifyState>,
     pub blob_storage: Arc<blob_store::BlobStorage>,
     pub executor_manager: Arc<ExecutorManager>,
+    pub secrets_cipher: Option<Arc<SecretsCipher>>,
+    pub cluster: Option<Arc<Cluster>>,
+    pub config_reloader: Arc<ConfigReloader>,
+    pub download_url_signer: Arc<DownloadUrlSigner>,
+    pub task_tokens: Arc<TaskTokenSigner>,
+    pub rejected_payloads: Arc<RejectedPayloadsMetrics>,
+    pub url_fetches: Arc<UrlFetches>,
+    pub task_scheduler: Arc<TaskScheduler>,
+    pub jwks: Arc<JwksCache>,
 }
 
 pub fn create_routes(route_state: RouteState) -> Router {
@@ -140,6 +601,7 @@ pub fn create_routes(route_state: RouteState) -> Router {
     Router::new()
         .merge(SwaggerUi::new("/docs/swagger").url("/docs/openapi.json", ApiDoc::openapi()))
         .route("/", get(index))
+        .route("/can_i", get(can_i).with_state(route_state.clone()))
         .route(
             "/namespaces",
             get(namespaces).with_state(route_state.clone()),
@@ -148,6 +610,14 @@ pub fn create_routes(route_state: RouteState) -> Router {
             "/namespaces",
             post(create_namespace).with_state(route_state.clone()),
         )
+        .route(
+            "/namespaces/:namespace",
+            delete(delete_namespace).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/deletion",
+            get(get_namespace_deletion).with_state(route_state.clone()),
+        )
         .route(
             "/namespaces/:namespace/compute_graphs",
             post(create_compute_graph).with_state(route_state.clone()),
@@ -156,6 +626,10 @@ pub fn create_routes(route_state: RouteState) -> Router {
             "/namespaces/:namespace/compute_graphs",
             get(list_compute_graphs).with_state(route_state.clone()),
         )
+        .route(
+            "/namespaces/:namespace/manifests",
+            post(apply_manifest).with_state(route_state.clone()),
+        )
         .route(
             "/namespaces/:namespace/compute_graphs/:compute_graph",
             delete(delete_compute_graph).with_state(route_state.clone()),
@@ -164,22 +638,149 @@ pub fn create_routes(route_state: RouteState) -> Router {
             "/namespaces/:namespace/compute_graphs/:compute_graph",
             get(get_compute_graph).with_state(route_state.clone()),
         )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/plan",
+            post(plan_invocation).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/topology",
+            get(get_topology).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/lint",
+            get(lint_compute_graph).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/lint",
+            post(lint_graph_definition).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/sla",
+            get(get_sla_status).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/latencies",
+            get(get_function_latencies).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/canary",
+            get(get_graph_canary)
+                .put(update_graph_canary)
+                .delete(rollback_graph_canary)
+                .with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/canary/finalize",
+            post(finalize_graph_canary).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/pause",
+            post(pause_compute_graph).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/resume",
+            post(resume_compute_graph).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/legal_hold",
+            put(set_graph_legal_hold)
+                .delete(clear_graph_legal_hold)
+                .with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/legal_hold",
+            put(set_invocation_legal_hold)
+                .delete(clear_invocation_legal_hold)
+                .with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/legal_holds",
+            get(list_legal_holds).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/legal_holds/audit",
+            get(get_legal_hold_audit).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/data",
+            delete(delete_ingested_data).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/data_deletion",
+            get(get_data_deletion).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/data_deletions",
+            get(list_data_deletions).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/windows/:window/close",
+            post(close_window).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/instantiate",
+            post(instantiate_compute_graph).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/undelete",
+            post(undelete_compute_graph).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/usage",
+            get(get_namespace_usage).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/deleted_compute_graphs",
+            get(list_deleted_compute_graphs).with_state(route_state.clone()),
+        )
         .route(
             "/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/tasks",
             get(list_tasks).with_state(route_state.clone()),
         )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/events",
+            get(get_invocation_events).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/replay",
+            get(replay_invocation).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/eta",
+            get(get_invocation_eta).with_state(route_state.clone()),
+        )
         .route(
             "/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/outputs",
             get(list_outputs).with_state(route_state.clone()),
         )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/result",
+            get(get_invocation_result).with_state(route_state.clone()),
+        )
         .route(
             "/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/context",
             get(get_context).with_state(route_state.clone()),
         )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/gates/:gate/signal",
+            post(signal_invocation).with_state(route_state.clone()),
+        )
         .route(
             "/namespaces/:namespace/compute_graphs/:compute_graph/invocations",
             get(graph_invocations).with_state(route_state.clone()),
         )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/invocations/diff",
+            get(diff_invocations).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/diff_runs",
+            post(create_diff_run).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/invocations/search",
+            get(search_invocations).with_state(route_state.clone()),
+        )
         .route(
             "/namespaces/:namespace/compute_graphs/:compute_graph/invoke_file",
             post(invoke_with_file).with_state(route_state.clone()),
@@ -188,6 +789,22 @@ pub fn create_routes(route_state: RouteState) -> Router {
             "/namespaces/:namespace/compute_graphs/:compute_graph/invoke_object",
             post(invoke_with_object).with_state(route_state.clone()),
         )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/invoke_sync",
+            post(invoke_and_wait).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/invoke_reference",
+            post(invoke_with_reference).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/invoke_url",
+            post(invoke_from_url).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/url_fetches/:fetch_id",
+            get(get_url_fetch).with_state(route_state.clone()),
+        )
         .route(
             "/namespaces/:namespace/compute_graphs/:compute_graph/rerun",
             post(rerun_compute_graph).with_state(route_state.clone()),
@@ -216,36 +833,222 @@ pub fn create_routes(route_state: RouteState) -> Router {
             "/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/fn/:fn_name/logs/:file",
             get(download_logs).with_state(route_state.clone()),
         )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/fn/:fn_name/tasks/:task_id/logs/:file",
+            get(get_task_logs).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/fn/:fn_name/tasks/:task_id/explain",
+            get(explain_pending_task).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/payloads/url",
+            post(create_payload_url).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/payloads/download",
+            get(download_signed_payload).with_state(route_state.clone()),
+        )
         .route(
             "/internal/ingest_files",
             post(ingest_files_from_executor).with_state(route_state.clone()),
         )
+        .route(
+            "/internal/task_results",
+            post(ingest_task_results).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/executor_pools",
+            post(create_executor_pool).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/executor_pools",
+            get(list_executor_pools).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/executor_pools/:name",
+            delete(delete_executor_pool).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/webhooks",
+            post(create_webhook)
+                .get(list_webhooks)
+                .with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/webhooks/dead_letters",
+            get(list_webhook_dead_letters).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/webhooks/dead_letters/redrive",
+            post(redrive_webhook_dead_letters).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/webhooks/:id",
+            delete(delete_webhook).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/invocation_groups",
+            post(create_invocation_group)
+                .get(list_invocation_groups)
+                .with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/invocation_groups/:id",
+            get(get_invocation_group).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/invocation_groups/:id/close",
+            post(close_invocation_group).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/invocation_groups/:id/invocations",
+            get(list_invocation_group_members).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/invocation_groups/:id/events",
+            get(invocation_group_events).with_state(route_state.clone()),
+        )
+        .route(
+            "/internal/snapshots",
+            post(create_snapshot).with_state(route_state.clone()),
+        )
+        .route("/internal/gc", post(run_gc).with_state(route_state.clone()))
+        .route(
+            "/internal/state_changes/lag",
+            get(state_change_lag).with_state(route_state.clone()),
+        )
+        .route(
+            "/internal/metrics/rejected_payloads",
+            get(list_rejected_payloads).with_state(route_state.clone()),
+        )
+        .route(
+            "/internal/metrics/read_cache",
+            get(read_cache_stats).with_state(route_state.clone()),
+        )
+        .route(
+            "/internal/metrics/latencies",
+            get(list_function_latencies).with_state(route_state.clone()),
+        )
+        .route(
+            "/internal/alerts",
+            get(list_alerts).with_state(route_state.clone()),
+        )
+        .route(
+            "/internal/config/reload",
+            post(reload_config).with_state(route_state.clone()),
+        )
+        .route(
+            "/internal/cluster/status",
+            get(cluster_status).with_state(route_state.clone()),
+        )
+        .route(
+            "/internal/cluster/log",
+            get(replication_log).with_state(route_state.clone()),
+        )
+        .route(
+            "/internal/cluster/vote",
+            post(cluster_vote).with_state(route_state.clone()),
+        )
+        .route(
+            "/internal/cluster/snapshot",
+            get(replication_snapshot).with_state(route_state.clone()),
+        )
         .route("/internal/executors", get(list_executors).with_state(route_state.clone()))
         .route(
             "/internal/executors/:id/tasks",
             post(executor_tasks).with_state(route_state.clone()),
         )
+        .route(
+            "/internal/executors/:id/requeue",
+            post(requeue_executor_tasks).with_state(route_state.clone()),
+        )
+        .route(
+            "/internal/executors/:id/labels",
+            put(update_executor_labels).with_state(route_state.clone()),
+        )
+        .route(
+            "/internal/executors/:id/heartbeat",
+            post(executor_heartbeat).with_state(route_state.clone()),
+        )
+        .route(
+            "/internal/executors/:id/control",
+            get(poll_executor_control).with_state(route_state.clone()),
+        )
+        .route(
+            "/internal/executors/:id/control/ack",
+            post(acknowledge_executor_aborts).with_state(route_state.clone()),
+        )
+        .route(
+            "/internal/executor_blacklist",
+            get(list_executor_blacklist).with_state(route_state.clone()),
+        )
+        .route(
+            "/internal/executor_blacklist/:namespace/:compute_graph/:compute_fn/:executor_id",
+            delete(remove_executor_blacklist_entry).with_state(route_state.clone()),
+        )
+        .route(
+            "/internal/executor_reconciliations",
+            get(list_executor_reconciliations).with_state(route_state.clone()),
+        )
+        .route(
+            "/internal/executors/:id/reconciliations",
+            get(list_reconciliations_of_executor).with_state(route_state.clone()),
+        )
+        .route(
+            "/internal/executor_approvals",
+            get(list_executor_approvals).with_state(route_state.clone()),
+        )
+        .route(
+            "/internal/executor_approvals/:executor_id/approve",
+            post(approve_executor).with_state(route_state.clone()),
+        )
+        .route(
+            "/internal/executor_approvals/:executor_id/deny",
+            post(deny_executor).with_state(route_state.clone()),
+        )
+        .route(
+            "/internal/artifacts/:sha256",
+            get(get_artifact)
+                .head(head_artifact)
+                .with_state(route_state.clone()),
+        )
+        .route(
+            "/internal/namespaces/:namespace/compute_graphs/:compute_graph/fns/:fn_name/environment",
+            get(get_fn_environment).with_state(route_state.clone()),
+        )
         .route(
             "/internal/fn_outputs/:input_key",
             get(download_fn_output_by_key).with_state(route_state.clone()),
         )
+        .route(
+            "/internal/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/state/:key",
+            get(get_invocation_state)
+                .post(set_invocation_state)
+                .with_state(route_state.clone()),
+        )
+        .route(
+            "/internal/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/fn/:fn_name/tasks/:task_id/checkpoint",
+            get(get_task_checkpoint)
+                .post(put_task_checkpoint)
+                .with_state(route_state.clone()),
+        )
         .route("/ui", get(ui_index_handler))
         .route("/ui/*rest", get(ui_handler))
+        .layer(middleware::from_fn_with_state(
+            route_state.clone(),
+            forward_writes_to_leader,
+        ))
+        .layer(middleware::from_fn_with_state(
+            route_state.clone(),
+            enforce_authz,
+        ))
         .layer(
             TraceLayer::new_for_http()
-                .make_span_with(|req: &Request| {
-                    let method = req.method();
-                    let uri = req.uri();
-
-                    let matched_path = req
-                        .extensions()
-                        .get::<MatchedPath>()
-                        .map(|matched_path| matched_path.as_str());
-
-                    tracing::debug_span!("request", %method, %uri, matched_path)
-                })
+                .make_span_with(make_request_span)
                 .on_failure(()),
         )
+        .layer(middleware::from_fn(assign_request_id))
         .layer(cors)
         .layer(DefaultBodyLimit::max(usize::MAX))
 }
@@ -254,6 +1057,36 @@ async fn index() -> &'static str {
     "Indexify Server"
 }
 
+// Followers only serve reads, writes are redirected to the leader.
+async fn forward_writes_to_leader(
+    State(state): State<RouteState>,
+    request: Request,
+    next: Next,
+) -> Result<axum::response::Response, IndexifyAPIError> {
+    let Some(cluster) = &state.cluster else {
+        return Ok(next.run(request).await);
+    };
+    // Replicas vote on whichever node they reach
+    if cluster.is_leader() ||
+        request.method() == Method::GET ||
+        request.uri().path().starts_with("/internal/cluster/")
+    {
+        return Ok(next.run(request).await);
+    }
+    let Some(leader_addr) = cluster.leader_addr() else {
+        return Err(IndexifyAPIError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "no leader elected",
+        ));
+    };
+    let path = request
+        .uri()
+        .path_and_query()
+        .map(|path| path.as_str())
+        .unwrap_or("/");
+    Ok(Redirect::temporary(&format!("http://{}{}", leader_addr, path)).into_response())
+}
+
 #[axum::debug_handler]
 #[tracing::instrument(skip_all)]
 async fn ui_index_handler() -> impl IntoResponse {
@@ -285,6 +1118,7 @@ async fn ui_handler(Path(url): Path<String>) -> impl IntoResponse {
     tag = "operations",
     responses(
         (status = 200, description = "Namespace created successfully"),
+        (status = BAD_REQUEST, description = "Invalid scheduling weight, reserved capacity or replication policy"),
         (status = INTERNAL_SERVER_ERROR, description = "Unable to create namespace")
     ),
 )]
@@ -292,11 +1126,36 @@ async fn create_namespace(
     State(state): State<RouteState>,
     Json(namespace): Json<CreateNamespace>,
 ) -> Result<(), IndexifyAPIError> {
+    if namespace.scheduling_weight == Some(0) {
+        return Err(IndexifyAPIError::bad_request(
+            "scheduling_weight must be positive",
+        ));
+    }
+    let reserved_capacity = namespace
+        .reserved_capacity
+        .map(|reserved| reserved.into_data_model())
+        .transpose()?;
+    if let Some(replication) = &namespace.replication {
+        if let Some(region) = replication
+            .regions
+            .iter()
+            .find(|region| state.blob_storage.replica(region).is_none())
+        {
+            return Err(IndexifyAPIError::bad_request(&format!(
+                "no replica storage is configured for region {}",
+                region
+            )));
+        }
+    }
     state
         .indexify_state
         .write(StateMachineUpdateRequest {
             payload: RequestPayload::CreateNameSpace(NamespaceRequest {
                 name: namespace.name,
+                queue_limits: namespace.queue_limits.into(),
+                scheduling_weight: namespace.scheduling_weight,
+                reserved_capacity,
+                replication: namespace.replication.map(Into::into),
             }),
             state_changes_processed: vec![],
         })
@@ -332,6 +1191,10 @@ struct ComputeGraphCreateType {
     compute_graph: ComputeGraph,
     #[schema(format = "binary")]
     code: String,
+    /// Current revision of the graph, required when updating it
+    expected_revision: Option<u64>,
+    /// Skips the revision check
+    force: Option<bool>,
 }
 
 /// Create compute graph
@@ -342,6 +1205,7 @@ struct ComputeGraphCreateType {
     request_body(content_type = "multipart/form-data", content = inline(ComputeGraphCreateType)),
     responses(
         (status = 200, description = "Create a Compute Graph"),
+        (status = CONFLICT, description = "The graph was updated since expected_revision"),
         (status = INTERNAL_SERVER_ERROR, description = "Unable to create compute graphs")
     ),
 )]
@@ -350,12 +1214,27 @@ async fn create_compute_graph(
     State(state): State<RouteState>,
     mut compute_graph_code: Multipart,
 ) -> Result<(), IndexifyAPIError> {
+    check_writable(&state)?;
     let mut compute_graph_definition: Option<ComputeGraph> = Option::None;
     let mut put_result: Option<PutResult> = None;
+    let mut expected_revision: Option<u64> = None;
+    let mut force = false;
+    let mut canary_percent: Option<u8> = None;
+    let mut wasm_modules = HashMap::new();
     while let Some(field) = compute_graph_code.next_field().await.unwrap() {
         let name = field.name();
         if let Some(name) = name {
-            if name == "code" {
+            if let Some(router) = name.strip_prefix(WASM_MODULE_FIELD_PREFIX) {
+                let router = router.to_string();
+                let stream = field.map(|res| res.map_err(|err| anyhow::anyhow!(err)));
+                let file_name = format!("{}_{}.wasm", namespace, nanoid!());
+                let result = state
+                    .blob_storage
+                    .put(&file_name, stream)
+                    .await
+                    .map_err(IndexifyAPIError::internal_error)?;
+                wasm_modules.insert(router, result);
+            } else if name == "code" {
                 let stream = field.map(|res| res.map_err(|err| anyhow::anyhow!(err)));
                 let file_name = format!("{}_{}", namespace, nanoid!());
                 let result = state
@@ -369,9 +1248,29 @@ async fn create_compute_graph(
                     .text()
                     .await
                     .map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))?;
-                let mut json_value: serde_json::Value = serde_json::from_str(&text)?;
-                json_value["namespace"] = serde_json::Value::String(namespace.clone());
-                compute_graph_definition = Some(serde_json::from_value(json_value)?);
+                compute_graph_definition = Some(parse_compute_graph(&namespace, &text)?);
+            } else if name == "expected_revision" {
+                let text = field
+                    .text()
+                    .await
+                    .map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))?;
+                expected_revision = Some(text.trim().parse().map_err(|_| {
+                    IndexifyAPIError::bad_request("expected_revision must be an integer")
+                })?);
+            } else if name == "force" {
+                let text = field
+                    .text()
+                    .await
+                    .map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))?;
+                force = text.trim() == "true";
+            } else if name == "canary_percent" {
+                let text = field
+                    .text()
+                    .await
+                    .map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))?;
+                canary_percent = Some(text.trim().parse().map_err(|_| {
+                    IndexifyAPIError::bad_request("canary_percent must be in [0, 100]")
+                })?);
             }
         }
     }
@@ -385,18 +1284,71 @@ async fn create_compute_graph(
     if put_result.is_none() {
         return Err(IndexifyAPIError::bad_request("Code is required"));
     }
-    let put_result = put_result.unwrap();
-    let compute_graph_definition = compute_graph_definition.unwrap();
-    let compute_graph = compute_graph_definition.into_data_model(
-        &put_result.url,
-        &put_result.sha256_hash,
-        put_result.size_bytes,
-    )?;
-    let name = compute_graph.name.clone();
-    let request = RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+    register_compute_graph(
+        &state,
         namespace,
-        compute_graph,
-    });
+        compute_graph_definition.unwrap(),
+        put_result.unwrap(),
+        wasm_modules,
+        expected_revision,
+        force,
+        canary_percent,
+    )
+    .await?;
+    Ok(())
+}
+
+/// Rejects user writes in maintenance mode before their payloads are
+/// uploaded, the state store rejects them regardless.
+pub(crate) fn check_writable(state: &RouteState) -> Result<(), IndexifyAPIError> {
+    if state.indexify_state.in_maintenance_mode() {
+        return Err(IndexifyAPIError::internal_error(
+            StateStoreError::Maintenance.into(),
+        ));
+    }
+    Ok(())
+}
+
+pub(crate) fn parse_compute_graph(
+    namespace: &str,
+    text: &str,
+) -> Result<ComputeGraph, IndexifyAPIError> {
+    let mut json_value: serde_json::Value = serde_json::from_str(text)?;
+    json_value["namespace"] = serde_json::Value::String(namespace.to_string());
+    Ok(serde_json::from_value(json_value)?)
+}
+
+/// Stores the compute graph whose code was uploaded to put_result, with the
+/// uploaded WASM modules of its routers. Updates of an existing graph must
+/// pass its current revision unless forced.
+pub(crate) async fn register_compute_graph(
+    state: &RouteState,
+    namespace: String,
+    compute_graph_definition: ComputeGraph,
+    put_result: PutResult,
+    wasm_modules: HashMap<String, PutResult>,
+    expected_revision: Option<u64>,
+    force: bool,
+    canary_percent: Option<u8>,
+) -> Result<String, IndexifyAPIError> {
+    let compute_graph =
+        prepare_compute_graph(state, compute_graph_definition, put_result, wasm_modules)?;
+    validate_sub_graphs(state, &compute_graph, &HashMap::new())?;
+    let name = compute_graph.name.clone();
+    let request = match canary_percent {
+        Some(percent) => RequestPayload::CreateGraphCanary(CreateGraphCanaryRequest {
+            namespace,
+            compute_graph,
+            expected_revision,
+            percent,
+        }),
+        None => RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+            namespace,
+            compute_graph,
+            expected_revision,
+            force,
+        }),
+    };
     state
         .indexify_state
         .write(StateMachineUpdateRequest {
@@ -406,16 +1358,122 @@ async fn create_compute_graph(
         .await
         .map_err(IndexifyAPIError::internal_error)?;
     info!("compute graph created: {}", name);
+    Ok(name)
+}
+
+/// Converts the definition of a compute graph whose code was uploaded to
+/// put_result, attaching the uploaded WASM modules of its routers and sealing
+/// its secrets.
+pub(crate) fn prepare_compute_graph(
+    state: &RouteState,
+    compute_graph_definition: ComputeGraph,
+    put_result: PutResult,
+    wasm_modules: HashMap<String, PutResult>,
+) -> Result<data_model::ComputeGraph, IndexifyAPIError> {
+    let mut compute_graph = compute_graph_definition.into_data_model(
+        &put_result.url,
+        &put_result.sha256_hash,
+        put_result.size_bytes,
+    )?;
+    attach_wasm_modules(&mut compute_graph, wasm_modules)?;
+    if secrets::has_secrets(&compute_graph) {
+        let cipher = state
+            .secrets_cipher
+            .as_ref()
+            .ok_or(IndexifyAPIError::bad_request(
+                "server is not configured with a secrets_key, unable to store secrets",
+            ))?;
+        // Secrets uploaded back redacted keep their stored value
+        let existing = state
+            .indexify_state
+            .reader()
+            .get_compute_graph(&compute_graph.namespace, &compute_graph.name)
+            .map_err(IndexifyAPIError::internal_error)?;
+        cipher
+            .seal_compute_graph(&mut compute_graph, existing.as_ref())
+            .map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))?;
+    }
+    Ok(compute_graph)
+}
+
+// WASM routers read their input as JSON, their module is run by the server.
+fn attach_wasm_modules(
+    compute_graph: &mut data_model::ComputeGraph,
+    wasm_modules: HashMap<String, PutResult>,
+) -> Result<(), IndexifyAPIError> {
+    for (name, put_result) in wasm_modules {
+        let Some(data_model::Node::Router(router)) = compute_graph.nodes.get_mut(&name) else {
+            return Err(IndexifyAPIError::bad_request(&format!(
+                "wasm module uploaded for {} which is not a router of the graph",
+                name
+            )));
+        };
+        if router.payload_encoder != "json" {
+            return Err(IndexifyAPIError::bad_request(&format!(
+                "wasm router {} must use the json payload encoder",
+                name
+            )));
+        }
+        router.wasm_module = Some(DataPayload {
+            path: put_result.url,
+            size: put_result.size_bytes,
+            sha256_hash: put_result.sha256_hash,
+            tier: Default::default(),
+            mime_type: None,
+            content_encoding: None,
+            metadata: Default::default(),
+            replicas: Vec::new(),
+            compression: None,
+        });
+    }
+    Ok(())
+}
+
+// Sub graphs must exist in the namespace, or be applied along with the graph,
+// and must not invoke the graph again.
+pub(crate) fn validate_sub_graphs(
+    state: &RouteState,
+    compute_graph: &data_model::ComputeGraph,
+    applied_with: &HashMap<String, data_model::ComputeGraph>,
+) -> Result<(), IndexifyAPIError> {
+    let reader = state.indexify_state.reader();
+    for sub_graph in compute_graph.sub_graphs() {
+        if sub_graph != compute_graph.name &&
+            !applied_with.contains_key(sub_graph) &&
+            reader
+                .get_compute_graph(&compute_graph.namespace, sub_graph)
+                .map_err(IndexifyAPIError::internal_error)?
+                .is_none()
+        {
+            return Err(IndexifyAPIError::bad_request(&format!(
+                "sub graph {} not found in namespace {}",
+                sub_graph, compute_graph.namespace
+            )));
+        }
+    }
+    let cycle = compute_graph
+        .find_sub_graph_cycle(|name| match applied_with.get(name) {
+            Some(graph) => Ok(Some(graph.clone())),
+            None => reader.get_compute_graph(&compute_graph.namespace, name),
+        })
+        .map_err(IndexifyAPIError::internal_error)?;
+    if let Some(cycle) = cycle {
+        return Err(IndexifyAPIError::bad_request(&format!(
+            "sub graphs form a cycle: {}",
+            cycle.join(" -> ")
+        )));
+    }
     Ok(())
 }
 
-/// Delete compute graph
+/// Delete compute graph, it can be restored until its retention expires
 #[utoipa::path(
     delete,
     path = "/namespaces/{namespace}/compute_graphs/{compute_graph}",
     tag = "operations",
     responses(
         (status = 200, description = "Extraction graph deleted successfully"),
+        (status = NOT_FOUND, description = "Compute graph not found"),
         (status = BAD_REQUEST, description = "Unable to delete extraction graph")
     ),
 )]
@@ -438,23 +1496,134 @@ async fn delete_compute_graph(
     Ok(())
 }
 
-/// List compute graphs
+/// Restore a deleted compute graph and its invocations
 #[utoipa::path(
-    get,
-    path = "/namespaces/{namespace}/compute_graphs",
+    post,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/undelete",
     tag = "operations",
     responses(
-        (status = 200, description = "Lists Compute Graph", body = ComputeGraphsList),
+        (status = 200, description = "Compute graph restored"),
+        (status = NOT_FOUND, description = "Deleted compute graph not found"),
         (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
     ),
 )]
-async fn list_compute_graphs(
-    Path(namespace): Path<String>,
-    Query(params): Query<ListParams>,
+async fn undelete_compute_graph(
+    Path((namespace, compute_graph)): Path<(String, String)>,
     State(state): State<RouteState>,
-) -> Result<Json<ComputeGraphsList>, IndexifyAPIError> {
-    let (compute_graphs, cursor) = state
-        .indexify_state
+) -> Result<(), IndexifyAPIError> {
+    let request = RequestPayload::UndeleteComputeGraph(DeleteComputeGraphRequest {
+        namespace,
+        name: compute_graph,
+    });
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: request,
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(())
+}
+
+/// Get the bytes stored and the resources used by the tasks of a namespace,
+/// with a breakdown per compute graph
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/usage",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Storage and task usage of the namespace", body = NamespaceUsage),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+async fn get_namespace_usage(
+    Path(namespace): Path<String>,
+    State(state): State<RouteState>,
+) -> Result<Json<NamespaceUsage>, IndexifyAPIError> {
+    let reader = state.indexify_state.reader();
+    let graphs = reader
+        .namespace_storage_usage(&namespace)
+        .map_err(IndexifyAPIError::internal_error)?;
+    let task_usage = reader
+        .namespace_task_usage(&namespace)
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(NamespaceUsage::from_data_model(
+        namespace, graphs, task_usage,
+    )))
+}
+
+/// Get the SLA attainment of a compute graph over its rolling window
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/sla",
+    tag = "operations",
+    responses(
+        (status = 200, description = "SLA attainment of the compute graph", body = SlaStatus),
+        (status = 404, description = "Compute Graph not found or has no SLA"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+async fn get_sla_status(
+    Path((namespace, name)): Path<(String, String)>,
+    State(state): State<RouteState>,
+) -> Result<Json<SlaStatus>, IndexifyAPIError> {
+    let reader = state.indexify_state.reader();
+    let sla = reader
+        .get_compute_graph(&namespace, &name)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::not_found("Compute Graph not found"))?
+        .sla
+        .ok_or(IndexifyAPIError::not_found("Compute Graph has no SLA"))?;
+    let status = reader
+        .evaluate_sla(&namespace, &name, &sla, get_epoch_time_in_ms())
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(status.into()))
+}
+
+/// List deleted compute graphs which can still be restored
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/deleted_compute_graphs",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Lists deleted Compute Graphs", body = ComputeGraphsList),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+async fn list_deleted_compute_graphs(
+    Path(namespace): Path<String>,
+    Query(params): Query<ListParams>,
+    State(state): State<RouteState>,
+) -> Result<Json<ComputeGraphsList>, IndexifyAPIError> {
+    let (compute_graphs, cursor) = state
+        .indexify_state
+        .reader()
+        .list_deleted_compute_graphs(&namespace, params.cursor.as_deref(), params.limit)
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(ComputeGraphsList {
+        compute_graphs: compute_graphs.into_iter().map(|c| c.into()).collect(),
+        cursor,
+    }))
+}
+
+/// List compute graphs
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/compute_graphs",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Lists Compute Graph", body = ComputeGraphsList),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+async fn list_compute_graphs(
+    Path(namespace): Path<String>,
+    Query(params): Query<ListParams>,
+    State(state): State<RouteState>,
+) -> Result<Json<ComputeGraphsList>, IndexifyAPIError> {
+    let (compute_graphs, cursor) = state
+        .indexify_state
         .reader()
         .list_compute_graphs(&namespace, params.cursor.as_deref(), params.limit)
         .map_err(IndexifyAPIError::internal_error)?;
@@ -489,6 +1658,96 @@ async fn get_compute_graph(
     Err(IndexifyAPIError::not_found("Compute Graph not found"))
 }
 
+/// Plan the tasks an invocation would create, without invoking the graph
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/plan",
+    request_body = PlanInvocationRequest,
+    tag = "operations",
+    responses(
+        (status = 200, description = "Tasks an invocation would create", body = InvocationPlan),
+        (status = 404, description = "Compute Graph not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+async fn plan_invocation(
+    Path((namespace, name)): Path<(String, String)>,
+    State(state): State<RouteState>,
+    Json(request): Json<PlanInvocationRequest>,
+) -> Result<Json<InvocationPlan>, IndexifyAPIError> {
+    let reader = state.indexify_state.reader();
+    let compute_graph = reader
+        .get_compute_graph(&namespace, &name)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::not_found("Compute Graph not found"))?;
+    let plan = compute_graph.plan();
+
+    let mut warnings = vec![];
+    if request.size == Some(0) {
+        warnings.push("payload is empty".to_string());
+    }
+    if let data_model::Node::Compute(start_fn) = &compute_graph.start_fn {
+        if start_fn.payload_encoder == "json" && !request.content_type.contains("json") {
+            warnings.push(format!(
+                "start fn {} decodes json payloads, content type is {}",
+                start_fn.name, request.content_type
+            ));
+        }
+    }
+    for name in &plan.unreachable {
+        warnings.push(format!(
+            "node {} is not reachable from the start fn {}",
+            name,
+            compute_graph.start_fn.name()
+        ));
+    }
+
+    let mut tasks = vec![];
+    for planned in plan.nodes {
+        let node = match compute_graph.nodes.get(&planned.name) {
+            Some(node) => node,
+            None if planned.name == compute_graph.start_fn.name() => &compute_graph.start_fn,
+            None => {
+                warnings.push(format!(
+                    "{} routes to unknown node {}",
+                    planned.parents.join(", "),
+                    planned.name
+                ));
+                continue;
+            }
+        };
+        let kind = match node {
+            data_model::Node::Compute(_) => PlannedNodeKind::ComputeFn,
+            data_model::Node::Router(_) => PlannedNodeKind::DynamicRouter,
+            data_model::Node::SubGraph(_) => PlannedNodeKind::SubGraph,
+            data_model::Node::Gate(_) => PlannedNodeKind::Gate,
+            data_model::Node::Window(_) => PlannedNodeKind::Window,
+            data_model::Node::Sink(_) => PlannedNodeKind::Sink,
+        };
+        if let Some(sub_graph) = node.sub_graph() {
+            let exists = reader
+                .get_compute_graph(&namespace, sub_graph)
+                .map_err(IndexifyAPIError::internal_error)?
+                .is_some();
+            if !exists {
+                warnings.push(format!(
+                    "sub graph {} invokes compute graph {} which does not exist",
+                    planned.name, sub_graph
+                ));
+            }
+        }
+        tasks.push(PlannedTask {
+            kind,
+            parents: planned.parents,
+            dynamic: planned.dynamic,
+            reducer: node.reducer(),
+            sub_graph: node.sub_graph().map(|sub_graph| sub_graph.to_string()),
+            compute_fn: planned.name,
+        });
+    }
+    Ok(Json(InvocationPlan { tasks, warnings }))
+}
+
 /// List Graph invocations
 #[utoipa::path(
     get,
@@ -496,14 +1755,16 @@ async fn get_compute_graph(
     tag = "ingestion",
     responses(
         (status = 200, description = "Compute Graph Definition", body = GraphInvocations),
+        (status = 400, description = "invalid labels filter"),
         (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
     ),
 )]
 async fn graph_invocations(
     Path((namespace, compute_graph)): Path<(String, String)>,
-    Query(params): Query<ListParams>,
+    Query(params): Query<ListInvocationsParams>,
     State(state): State<RouteState>,
 ) -> Result<Json<GraphInvocations>, IndexifyAPIError> {
+    let labels_filter = params.labels_filter()?;
     let (data_objects, cursor) = state
         .indexify_state
         .reader()
@@ -512,22 +1773,54 @@ async fn graph_invocations(
             &compute_graph,
             params.cursor.as_deref(),
             params.limit,
+            &labels_filter,
         )
         .map_err(IndexifyAPIError::internal_error)?;
-    let mut invocations = vec![];
-    for data_object in data_objects {
-        invocations.push(DataObject {
-            id: data_object.id,
-            payload_size: data_object.payload.size,
-            payload_sha_256: data_object.payload.sha256_hash,
-        });
-    }
+    let invocations = data_objects.into_iter().map(DataObject::from).collect();
     Ok(Json(GraphInvocations {
         invocations,
         cursor,
     }))
 }
 
+/// Search Graph invocations by their attributes
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/invocations/search",
+    tag = "ingestion",
+    responses(
+        (status = 200, description = "Invocations matching the filter", body = InvocationSearchResults),
+        (status = 400, description = "invalid attributes filter"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+async fn search_invocations(
+    Path((namespace, compute_graph)): Path<(String, String)>,
+    Query(params): Query<SearchInvocationsParams>,
+    State(state): State<RouteState>,
+) -> Result<Json<InvocationSearchResults>, IndexifyAPIError> {
+    let filter = params.filter()?;
+    if filter.is_empty() {
+        return Err(IndexifyAPIError::bad_request("filter is required"));
+    }
+    let (data_objects, cursor) = state
+        .indexify_state
+        .reader()
+        .search_invocations(
+            &namespace,
+            &compute_graph,
+            &filter,
+            params.cursor.as_deref(),
+            params.limit.unwrap_or(DEFAULT_SEARCH_LIMIT),
+        )
+        .map_err(IndexifyAPIError::internal_error)?;
+    let invocations = data_objects.into_iter().map(DataObject::from).collect();
+    Ok(Json(InvocationSearchResults {
+        invocations,
+        cursor,
+    }))
+}
+
 async fn notify_on_change(
     Path((_namespace, _compute_graph)): Path<(String, String)>,
     State(state): State<RouteState>,
@@ -551,13 +1844,227 @@ async fn notify_on_change(
     )
 }
 
+/// Snapshot the state store into the blob storage
+#[utoipa::path(
+    post,
+    path = "/internal/snapshots",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Snapshot created", body = Snapshot),
+        (status = INTERNAL_SERVER_ERROR, description = "Unable to create snapshot")
+    ),
+)]
+async fn create_snapshot(
+    State(state): State<RouteState>,
+) -> Result<Json<Snapshot>, IndexifyAPIError> {
+    let id = format!("{}_{}", get_epoch_time_in_ms(), nanoid!());
+    let snapshot = state.indexify_state.create_snapshot(&id);
+    let state_put = state
+        .blob_storage
+        .put(
+            &format!("snapshots/{}/state", id),
+            snapshot.data.map(|chunk| chunk.map(Bytes::from)),
+        )
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    let manifest = snapshot
+        .manifest
+        .await
+        .map_err(|e| IndexifyAPIError::internal_error(e.into()))?
+        .map_err(IndexifyAPIError::internal_error)?;
+    let manifest_put = state
+        .blob_storage
+        .put(
+            &format!("snapshots/{}/manifest.json", id),
+            futures::stream::iter(vec![Ok(Bytes::from(serde_json::to_vec(&manifest)?))]),
+        )
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    info!("created snapshot {} at {}", id, state_put.url);
+    Ok(Json(Snapshot {
+        id,
+        created_at: manifest.created_at,
+        state_url: state_put.url,
+        manifest_url: manifest_put.url,
+        column_families: manifest.column_families,
+        num_blob_references: manifest.blob_references.len(),
+    }))
+}
+
+/// Wake up the garbage collector to delete the blobs queued for deletion
+#[utoipa::path(
+    post,
+    path = "/internal/gc",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Garbage collector woken up", body = GcStatus),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+async fn run_gc(State(state): State<RouteState>) -> Result<Json<GcStatus>, IndexifyAPIError> {
+    let pending_urls = state
+        .indexify_state
+        .reader()
+        .get_gc_urls(None)
+        .map_err(IndexifyAPIError::internal_error)?
+        .len();
+    let _ = state.indexify_state.gc_tx.send(());
+    Ok(Json(GcStatus { pending_urls }))
+}
+
+/// Get the number of state changes the scheduler is behind by
+#[utoipa::path(
+    get,
+    path = "/internal/state_changes/lag",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Unprocessed state changes", body = StateChangeLag),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+async fn state_change_lag(
+    State(state): State<RouteState>,
+) -> Result<Json<StateChangeLag>, IndexifyAPIError> {
+    let (unprocessed, oldest_created_at) = state
+        .indexify_state
+        .reader()
+        .unprocessed_state_changes_lag()
+        .map_err(IndexifyAPIError::internal_error)?;
+    let lag_ms = oldest_created_at
+        .map(|created_at| get_epoch_time_in_ms().saturating_sub(created_at))
+        .unwrap_or(0);
+    Ok(Json(StateChangeLag {
+        unprocessed,
+        oldest_created_at,
+        lag_ms,
+    }))
+}
+
+/// Get the hit rates of the caches of compute graphs and invocation contexts
+#[utoipa::path(
+    get,
+    path = "/internal/metrics/read_cache",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Lookups of the read caches of the state store", body = ReadCacheStats),
+    ),
+)]
+async fn read_cache_stats(State(state): State<RouteState>) -> Json<ReadCacheStats> {
+    Json(state.indexify_state.read_cache.stats().into())
+}
+
+async fn reload_config(
+    State(state): State<RouteState>,
+) -> Result<Json<RuntimeConfig>, IndexifyAPIError> {
+    let runtime_config = state
+        .config_reloader
+        .reload()
+        .map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))?;
+    Ok(Json(runtime_config))
+}
+
+/// Get the cluster membership of this server
+#[utoipa::path(
+    get,
+    path = "/internal/cluster/status",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Cluster status", body = ClusterStatus),
+    ),
+)]
+async fn cluster_status(State(state): State<RouteState>) -> Json<ClusterStatus> {
+    match &state.cluster {
+        Some(cluster) => Json(cluster.status()),
+        // A standalone server is always the leader
+        None => Json(ClusterStatus {
+            node_id: 0,
+            leader_id: Some(0),
+            last_applied_seq: state.indexify_state.last_replicated_seq(),
+            last_applied_term: state.indexify_state.last_replicated_term(),
+            term: state.indexify_state.replication_term(),
+            log_start_seq: state.indexify_state.replication_log_start(),
+        }),
+    }
+}
+
+// Longest wait of a follower for new log entries
+const MAX_REPLICATION_LOG_WAIT: Duration = Duration::from_secs(30);
+
+async fn replication_log(
+    Query(params): Query<ReplicationLogParams>,
+    Extension(PeerId(node_id)): Extension<PeerId>,
+    State(state): State<RouteState>,
+) -> Result<Response<Body>, IndexifyAPIError> {
+    let indexify_state = &state.indexify_state;
+    let term = indexify_state.replication_term();
+    // The follower saw a newer leader, this one is stale
+    if params.term.is_some_and(|follower_term| follower_term > term) {
+        return Err(IndexifyAPIError::new(
+            StatusCode::CONFLICT,
+            "the term of the follower is newer than the term of this server",
+        ));
+    }
+    // The follower applied the entries up to `after`
+    indexify_state
+        .record_follower_seq(node_id, params.after)
+        .map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))?;
+    if let Some(wait_ms) = params.wait_ms {
+        let wait = Duration::from_millis(wait_ms).min(MAX_REPLICATION_LOG_WAIT);
+        indexify_state
+            .wait_for_replication_log(params.after, wait)
+            .await;
+    }
+    let Some(entries) = indexify_state
+        .replication_log(params.after, params.after_term, params.limit.unwrap_or(1000))
+        .map_err(IndexifyAPIError::internal_error)?
+    else {
+        return Err(IndexifyAPIError::new(
+            StatusCode::GONE,
+            "the log of the follower diverged or misses truncated entries, install a snapshot",
+        ));
+    };
+    Response::builder()
+        .header("Content-Type", "application/octet-stream")
+        .body(Body::from(encode_entries(term, &entries)))
+        .map_err(|e| IndexifyAPIError::internal_error_str(&e.to_string()))
+}
+
+// Followers which can't catch up with the log install a snapshot of the
+// leader's state
+async fn replication_snapshot(
+    State(state): State<RouteState>,
+) -> Result<Response<Body>, IndexifyAPIError> {
+    let term = state.indexify_state.replication_term();
+    let snapshot = state.indexify_state.create_replication_snapshot();
+    Response::builder()
+        .header("Content-Type", "application/octet-stream")
+        .header(TERM_HEADER, term)
+        .body(Body::from_stream(snapshot.data))
+        .map_err(|e| IndexifyAPIError::internal_error_str(&e.to_string()))
+}
+
+async fn cluster_vote(
+    Extension(PeerId(node_id)): Extension<PeerId>,
+    State(state): State<RouteState>,
+    Json(request): Json<VoteRequest>,
+) -> Result<Json<VoteResponse>, IndexifyAPIError> {
+    let Some(cluster) = &state.cluster else {
+        return Err(IndexifyAPIError::bad_request("this server isn't a replica of a cluster"));
+    };
+    let vote = cluster
+        .handle_vote(node_id, request)
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(vote))
+}
+
 /// List executors
 #[utoipa::path(
     get,
     path = "/internal/executors",
     tag = "operations",
     responses(
-        (status = 200, description = "List all executors", body = Vec<ExecutorMetadata>),
+        (status = 200, description = "List all executors with their allocated tasks and free capacity", body = Vec<ExecutorMetadata>),
         (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
     ),
 )]
@@ -569,36 +2076,286 @@ async fn list_executors(
         .list_executors()
         .await
         .map_err(IndexifyAPIError::internal_error)?;
-    let http_executors = executors.into_iter().map(|e| e.into()).collect();
+    let mut allocated_task_ids = state
+        .indexify_state
+        .reader()
+        .allocated_task_ids()
+        .map_err(IndexifyAPIError::internal_error)?;
+    let http_executors = executors
+        .into_iter()
+        .map(|executor| {
+            let task_ids = allocated_task_ids.remove(&executor.id).unwrap_or_default();
+            let free_capacity = executor.free_capacity(task_ids.len());
+            ExecutorMetadata {
+                allocated_tasks: task_ids.len() as u64,
+                running_task_ids: task_ids.iter().map(|id| id.to_string()).collect(),
+                free_capacity,
+                ..ExecutorMetadata::from(executor)
+            }
+        })
+        .collect();
     Ok(Json(http_executors))
 }
 
+/// Move the tasks allocated to an executor back to the unallocated tasks
+#[utoipa::path(
+    post,
+    path = "/internal/executors/{id}/requeue",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Tasks of the executor requeued"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+async fn requeue_executor_tasks(
+    Path(executor_id): Path<ExecutorId>,
+    Query(params): Query<RequeueTasksParams>,
+    State(state): State<RouteState>,
+) -> Result<(), IndexifyAPIError> {
+    state
+        .executor_manager
+        .requeue_tasks(
+            executor_id,
+            params.batch_size.unwrap_or(DEFAULT_REQUEUE_BATCH_SIZE),
+        )
+        .await
+        .map_err(IndexifyAPIError::internal_error)
+}
+
+/// Replace the labels of an executor, its allocated tasks which can't run on
+/// it anymore are scheduled again
+#[utoipa::path(
+    put,
+    path = "/internal/executors/{id}/labels",
+    request_body = UpdateExecutorLabels,
+    tag = "operations",
+    responses(
+        (status = 200, description = "Labels of the executor updated"),
+        (status = NOT_FOUND, description = "Executor not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+async fn update_executor_labels(
+    Path(executor_id): Path<ExecutorId>,
+    State(state): State<RouteState>,
+    Json(request): Json<UpdateExecutorLabels>,
+) -> Result<(), IndexifyAPIError> {
+    state
+        .executor_manager
+        .update_labels(executor_id, request.labels)
+        .await
+        .map_err(IndexifyAPIError::internal_error)
+}
+
+/// Create an executor pool
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/executor_pools",
+    request_body = CreateExecutorPool,
+    tag = "operations",
+    responses(
+        (status = 200, description = "Executor pool created successfully"),
+        (status = BAD_REQUEST, description = "Invalid executor pool selector"),
+        (status = INTERNAL_SERVER_ERROR, description = "Unable to create executor pool")
+    ),
+)]
+async fn create_executor_pool(
+    Path(namespace): Path<String>,
+    State(state): State<RouteState>,
+    Json(pool): Json<CreateExecutorPool>,
+) -> Result<(), IndexifyAPIError> {
+    if pool.name.is_empty() {
+        return Err(IndexifyAPIError::bad_request(
+            "executor pool name is required",
+        ));
+    }
+    if let (Some(min_size), Some(max_size)) = (pool.min_size, pool.max_size) {
+        if min_size > max_size {
+            return Err(IndexifyAPIError::bad_request(
+                "executor pool min_size must not exceed max_size",
+            ));
+        }
+    }
+    let selector = pool
+        .selector
+        .iter()
+        .map(|condition| Condition::from_str(condition))
+        .collect::<anyhow::Result<Vec<_>>>()
+        .map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))?;
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: RequestPayload::CreateExecutorPool(CreateExecutorPoolRequest {
+                pool: data_model::ExecutorPool {
+                    namespace,
+                    name: pool.name,
+                    selector: LabelsFilter(selector),
+                    min_size: pool.min_size,
+                    max_size: pool.max_size,
+                    created_at: get_epoch_time_in_ms(),
+                },
+            }),
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(())
+}
+
+/// List executor pools with their current members
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/executor_pools",
+    tag = "operations",
+    responses(
+        (status = 200, description = "List executor pools", body = ExecutorPoolsList),
+        (status = INTERNAL_SERVER_ERROR, description = "Unable to list executor pools")
+    ),
+)]
+async fn list_executor_pools(
+    Path(namespace): Path<String>,
+    State(state): State<RouteState>,
+) -> Result<Json<ExecutorPoolsList>, IndexifyAPIError> {
+    let reader = state.indexify_state.reader();
+    let pools = reader
+        .list_executor_pools(&namespace)
+        .map_err(IndexifyAPIError::internal_error)?;
+    let executors = reader
+        .get_all_executors()
+        .map_err(IndexifyAPIError::internal_error)?;
+    let pools = pools
+        .into_iter()
+        .map(|pool| ExecutorPool::from_data_model(pool, &executors))
+        .collect();
+    Ok(Json(ExecutorPoolsList { pools }))
+}
+
+/// Delete an executor pool
+#[utoipa::path(
+    delete,
+    path = "/namespaces/{namespace}/executor_pools/{name}",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Executor pool deleted successfully"),
+        (status = INTERNAL_SERVER_ERROR, description = "Unable to delete executor pool")
+    ),
+)]
+async fn delete_executor_pool(
+    Path((namespace, name)): Path<(String, String)>,
+    State(state): State<RouteState>,
+) -> Result<(), IndexifyAPIError> {
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: RequestPayload::DeleteExecutorPool(DeleteExecutorPoolRequest {
+                namespace,
+                name,
+            }),
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(())
+}
+
 async fn executor_tasks(
     Path(executor_id): Path<ExecutorId>,
     State(state): State<RouteState>,
+    headers: HeaderMap,
     Json(payload): Json<ExecutorMetadata>,
 ) -> Result<impl IntoResponse, IndexifyAPIError> {
     const TASK_LIMIT: usize = 10;
-    let err = state
+    let runtime = state.config_reloader.runtime();
+    let features = executors::negotiate_protocol(
+        payload.protocol_version,
+        &payload.features,
+        runtime.min_executor_protocol_version,
+    )
+    .map_err(|e| IndexifyAPIError::new(StatusCode::UPGRADE_REQUIRED, &e.to_string()))?;
+    let headers = [
+        (
+            executors::PROTOCOL_VERSION_HEADER,
+            data_model::EXECUTOR_PROTOCOL_VERSION.to_string(),
+        ),
+        (
+            executors::FEATURES_HEADER,
+            executors::features_header_value(&features),
+        ),
+    ];
+    let mut executor = data_model::ExecutorMetadata {
+        id: executor_id.clone(),
+        image_name: payload.image_name.clone(),
+        addr: payload.addr.clone(),
+        labels: payload.labels.clone(),
+        cached_artifacts: payload.cached_artifacts.iter().cloned().collect(),
+        gpus: payload.gpus.iter().cloned().map(Into::into).collect(),
+        protocol_version: payload.protocol_version,
+        features,
+        runtimes: payload.runtimes.iter().cloned().map(Into::into).collect(),
+        queue_depth: 0,
+        max_outstanding_tasks: payload.max_outstanding_tasks,
+        version: payload.version.clone(),
+        last_heartbeat_at: None,
+    };
+    let bootstrap_token = headers
+        .get(executors::BOOTSTRAP_TOKEN_HEADER)
+        .and_then(|token| token.to_str().ok());
+    let admission = state
         .executor_manager
-        .register_executor(data_model::ExecutorMetadata {
-            id: executor_id.clone(),
-            image_name: payload.image_name.clone(),
-            addr: payload.addr.clone(),
-            labels: payload.labels.clone(),
-        })
-        .await;
+        .admit(
+            runtime.executor_approval.as_ref(),
+            &mut executor,
+            bootstrap_token,
+        )
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    if let Some(rejection) = admission.rejection(&executor_id) {
+        return Err(IndexifyAPIError::new(StatusCode::FORBIDDEN, &rejection));
+    }
+    if let Some(recovered_tasks) = payload.recovered_tasks {
+        state
+            .executor_manager
+            .reconcile_tasks(
+                executor_id.clone(),
+                recovered_tasks.into_iter().map(Into::into).collect(),
+            )
+            .await
+            .map_err(IndexifyAPIError::internal_error)?;
+    }
+    let err = state.executor_manager.register_executor(executor).await;
     if let Err(e) = err {
         tracing::error!("failed to register executor {}: {:?}", executor_id, e);
         return Err(IndexifyAPIError::internal_error_str(&e.to_string()));
     }
     let stream = state_store::task_stream(state.indexify_state, executor_id.clone(), TASK_LIMIT);
     let executor_manager = state.executor_manager.clone();
+    let task_tokens = state.task_tokens.clone();
     let stream = stream
-        .map(|item| match item {
-            Ok(item) => {
-                let item: Vec<Task> = item.into_iter().map(Into::into).collect();
-                axum::response::sse::Event::default().json_data(item)
+        .map(move |item| match item {
+            Ok(ExecutorEvent::Tasks(tasks)) => {
+                let tasks: Vec<Task> = tasks
+                    .into_iter()
+                    .map(|task| {
+                        let token = task_tokens.dispatch_token(&task);
+                        Task {
+                            token,
+                            ..task.into()
+                        }
+                    })
+                    .collect();
+                axum::response::sse::Event::default().json_data(tasks)
+            }
+            Ok(ExecutorEvent::Abort(task_ids)) => {
+                let task_ids: Vec<String> = task_ids.iter().map(|id| id.to_string()).collect();
+                axum::response::sse::Event::default()
+                    .event("abort")
+                    .json_data(task_ids)
+            }
+            Ok(ExecutorEvent::Prefetch(tasks)) => {
+                let tasks: Vec<Task> = tasks.into_iter().map(Into::into).collect();
+                axum::response::sse::Event::default()
+                    .event("prefetch")
+                    .json_data(tasks)
             }
             Err(e) => {
                 tracing::error!("error in task stream: {}", e);
@@ -606,13 +2363,40 @@ async fn executor_tasks(
             }
         })
         .guard(|| executors::schedule_deregister(executor_manager, executor_id, EXECUTOR_TIMEOUT));
-    Ok(axum::response::Sse::new(stream).keep_alive(
-        axum::response::sse::KeepAlive::new()
-            .interval(Duration::from_secs(1))
-            .text("keep-alive-text"),
+    Ok((
+        headers,
+        axum::response::Sse::new(stream).keep_alive(
+            axum::response::sse::KeepAlive::new()
+                .interval(Duration::from_secs(1))
+                .text("keep-alive-text"),
+        ),
     ))
 }
 
+async fn executor_heartbeat(
+    Path(executor_id): Path<ExecutorId>,
+    State(state): State<RouteState>,
+    Json(payload): Json<ExecutorHeartbeat>,
+) -> Result<Json<ExecutorHeartbeatResponse>, IndexifyAPIError> {
+    state
+        .executor_manager
+        .heartbeat(
+            executor_id.clone(),
+            payload.cached_artifacts.into_iter().collect(),
+            payload.queue_depth,
+            payload.max_outstanding_tasks,
+        )
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    let warm_pool = state
+        .executor_manager
+        .warm_pool(&executor_id)
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(ExecutorHeartbeatResponse {
+        warm_pool: warm_pool.into_iter().map(Into::into).collect(),
+    }))
+}
+
 /// List tasks for an invocation
 #[utoipa::path(
     get,
@@ -644,6 +2428,83 @@ async fn list_tasks(
     Ok(Json(Tasks { tasks, cursor }))
 }
 
+/// Get the timeline of an invocation
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/invocations/{invocation_id}/events",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Events of the invocation in the order they happened", body = InvocationEvents),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+async fn get_invocation_events(
+    Path((namespace, compute_graph, invocation_id)): Path<(String, String, String)>,
+    Query(params): Query<ListParams>,
+    State(state): State<RouteState>,
+) -> Result<Json<InvocationEvents>, IndexifyAPIError> {
+    let (events, cursor) = state
+        .indexify_state
+        .reader()
+        .list_invocation_events(
+            &namespace,
+            &compute_graph,
+            &invocation_id,
+            params.cursor.as_deref(),
+            params.limit,
+        )
+        .map_err(IndexifyAPIError::internal_error)?;
+    let events = events.into_iter().map(Into::into).collect();
+    Ok(Json(InvocationEvents { events, cursor }))
+}
+
+/// Rebuild the state of an invocation as of a time or a state change, from
+/// its timeline
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/invocations/{invocation_id}/replay",
+    tag = "operations",
+    params(
+        ("as_of" = Option<u64>, Query, description = "Milliseconds since the epoch"),
+        ("state_change_id" = Option<u64>, Query, description = "The state once the state change was processed"),
+    ),
+    responses(
+        (status = 200, description = "State of the invocation", body = ReplayedInvocation),
+        (status = 400, description = "Neither or both of as_of and state_change_id are set"),
+        (status = 404, description = "The invocation or state change didn't exist yet"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+async fn replay_invocation(
+    Path((namespace, compute_graph, invocation_id)): Path<(String, String, String)>,
+    Query(params): Query<ReplayInvocationParams>,
+    State(state): State<RouteState>,
+) -> Result<Json<ReplayedInvocation>, IndexifyAPIError> {
+    let reader = state.indexify_state.reader();
+    let as_of = match (params.as_of, params.state_change_id) {
+        (Some(as_of), None) => as_of,
+        (None, Some(id)) => {
+            let state_change = reader
+                .get_state_change(StateChangeId::new(id))
+                .map_err(IndexifyAPIError::internal_error)?
+                .ok_or(IndexifyAPIError::not_found("State change not found"))?;
+            state_change.processed_at.unwrap_or(state_change.created_at)
+        }
+        _ => {
+            return Err(IndexifyAPIError::bad_request(
+                "exactly one of as_of and state_change_id is required",
+            ))
+        }
+    };
+    let replayed = reader
+        .replay_invocation(&namespace, &compute_graph, &invocation_id, as_of)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::not_found(
+            "Invocation not found at that time",
+        ))?;
+    Ok(Json(replayed.into()))
+}
+
 /// Get accounting information for a compute graph invocation
 #[utoipa::path(
     get,
@@ -697,6 +2558,30 @@ async fn list_outputs(
     Ok(Json(FnOutputs { outputs, cursor }))
 }
 
+/// Get the result of a finished invocation
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/invocations/{invocation_id}/result",
+    tag = "retrieve",
+    responses(
+        (status = 200, description = "Result of the invocation", body = InvocationResult),
+        (status = 404, description = "Invocation not found or not finished"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+async fn get_invocation_result(
+    Path((namespace, compute_graph, invocation_id)): Path<(String, String, String)>,
+    State(state): State<RouteState>,
+) -> Result<Json<InvocationResult>, IndexifyAPIError> {
+    let result = state
+        .indexify_state
+        .reader()
+        .invocation_result(&namespace, &compute_graph, &invocation_id)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or_else(|| IndexifyAPIError::not_found("invocation not found or not finished"))?;
+    Ok(Json(result.into()))
+}
+
 /// Delete a specific invocation  
 #[utoipa::path(
     delete,
@@ -756,3 +2641,220 @@ async fn get_code(
         .body(Body::from_stream(code_stream))
         .map_err(|e| IndexifyAPIError::internal_error_str(&e.to_string()))
 }
+
+fn get_code_artifact(
+    state: &RouteState,
+    sha256: &str,
+) -> Result<data_model::ComputeGraphCode, IndexifyAPIError> {
+    state
+        .indexify_state
+        .reader()
+        .get_code_artifact(sha256)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::not_found("Artifact not found"))
+}
+
+/// Returns the size of the graph code with the given sha256 hash, executors
+/// use it to check if an artifact exists before downloading it
+async fn head_artifact(
+    Path(sha256): Path<String>,
+    State(state): State<RouteState>,
+) -> Result<impl IntoResponse, IndexifyAPIError> {
+    let code = get_code_artifact(&state, &sha256)?;
+    Response::builder()
+        .header("Content-Type", "application/octet-stream")
+        .header("Content-Length", code.size.to_string())
+        .body(Body::empty())
+        .map_err(|e| IndexifyAPIError::internal_error_str(&e.to_string()))
+}
+
+async fn get_artifact(
+    Path(sha256): Path<String>,
+    State(state): State<RouteState>,
+) -> Result<impl IntoResponse, IndexifyAPIError> {
+    let code = get_code_artifact(&state, &sha256)?;
+    let code_stream = state
+        .blob_storage
+        .get(&code.path)
+        .get()
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    Response::builder()
+        .header("Content-Type", "application/octet-stream")
+        .header("Content-Length", code.size.to_string())
+        .body(Body::from_stream(code_stream))
+        .map_err(|e| IndexifyAPIError::internal_error_str(&e.to_string()))
+}
+
+async fn get_fn_environment(
+    Path((namespace, compute_graph, fn_name)): Path<(String, String, String)>,
+    Query(params): Query<FnEnvironmentParams>,
+    State(state): State<RouteState>,
+) -> Result<Json<FnEnvironment>, IndexifyAPIError> {
+    let reader = state.indexify_state.reader();
+    let compute_graph = reader
+        .get_compute_graph(&namespace, &compute_graph)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::not_found("Compute Graph not found"))?;
+    let parameters = match &params.invocation_id {
+        Some(invocation_id) => {
+            reader
+                .invocation_ctx(&namespace, &compute_graph.name, invocation_id)
+                .map_err(IndexifyAPIError::internal_error)?
+                .parameters
+        }
+        None => compute_graph
+            .resolve_parameters(&Default::default())
+            .map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))?,
+    };
+    let environment = compute_graph
+        .fn_environment(&fn_name, &parameters)
+        .map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))?
+        .ok_or(IndexifyAPIError::not_found("Compute Fn not found"))?;
+    // Without auth anyone reaching the server could read the secrets
+    if !environment.secrets.is_empty() && state.config_reloader.runtime().auth.is_none() {
+        return Err(IndexifyAPIError::new(
+            StatusCode::FORBIDDEN,
+            "secrets are only served to authenticated executors, configure auth to resolve them",
+        ));
+    }
+    let secrets = match &state.secrets_cipher {
+        Some(cipher) => cipher
+            .open_all(&environment.secrets)
+            .map_err(IndexifyAPIError::internal_error)?,
+        None if environment.secrets.is_empty() => Default::default(),
+        None => {
+            return Err(IndexifyAPIError::internal_error_str(
+                "server is not configured with a secrets_key, unable to resolve secrets",
+            ))
+        }
+    };
+    Ok(Json(FnEnvironment {
+        env: environment.env,
+        secrets,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use data_model::{
+        test_objects::tests::{
+            create_mock_task,
+            mock_executor,
+            mock_graph_a,
+            mock_invocation_payload,
+            TEST_NAMESPACE,
+        },
+        QueueLimits,
+    };
+    use state_store::requests::{
+        CreateComputeGraphRequest,
+        CreateTasksRequest,
+        InvokeComputeGraphRequest,
+        NamespaceRequest,
+        ReductionTasks,
+        RequestPayload,
+        SchedulerUpdateRequest,
+        StateMachineUpdateRequest,
+        TaskPlacement,
+    };
+
+    use super::*;
+    use crate::{config::RuntimeConfig, routes::test_route_state::TestRouteState};
+
+    #[tokio::test]
+    async fn test_list_executors() -> anyhow::Result<()> {
+        let test_state = TestRouteState::new(RuntimeConfig::default()).await?;
+        let state = test_state.route_state.clone();
+        let write = |payload| {
+            state.indexify_state.write(StateMachineUpdateRequest {
+                payload,
+                state_changes_processed: vec![],
+            })
+        };
+        write(RequestPayload::CreateNameSpace(NamespaceRequest {
+            name: TEST_NAMESPACE.to_string(),
+            queue_limits: QueueLimits::default(),
+            scheduling_weight: None,
+            reserved_capacity: None,
+            replication: None,
+        }))
+        .await?;
+        let cg = mock_graph_a();
+        write(RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+            namespace: TEST_NAMESPACE.to_string(),
+            compute_graph: cg.clone(),
+            expected_revision: None,
+            force: false,
+        }))
+        .await?;
+        let invocation_payload = mock_invocation_payload();
+        write(RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+            namespace: TEST_NAMESPACE.to_string(),
+            compute_graph_name: cg.name.clone(),
+            invocation_payload: invocation_payload.clone(),
+        }))
+        .await?;
+
+        // An executor taking up to 3 tasks, and one without a limit
+        let executor = data_model::ExecutorMetadata {
+            max_outstanding_tasks: Some(3),
+            version: Some("0.2.18".to_string()),
+            ..mock_executor()
+        };
+        state
+            .executor_manager
+            .register_executor(executor.clone())
+            .await?;
+        let unlimited_executor = data_model::ExecutorMetadata {
+            id: ExecutorId::new("executor_2".to_string()),
+            ..mock_executor()
+        };
+        state
+            .executor_manager
+            .register_executor(unlimited_executor.clone())
+            .await?;
+
+        let task = create_mock_task(&cg, "fn_a", "input_key", &invocation_payload.id);
+        write(RequestPayload::SchedulerUpdate(SchedulerUpdateRequest {
+            task_requests: vec![CreateTasksRequest {
+                namespace: cg.namespace.clone(),
+                compute_graph: cg.name.clone(),
+                invocation_id: invocation_payload.id.clone(),
+                tasks: vec![task.clone()],
+                fan_out_cursor: None,
+            }],
+            allocations: vec![TaskPlacement {
+                task: task.clone(),
+                executor: executor.id.clone(),
+            }],
+            reduction_tasks: ReductionTasks::default(),
+            diagnostic_msgs: vec![],
+            sub_graph_invocations: vec![],
+            preemptions: vec![],
+        }))
+        .await?;
+
+        let Json(executors) = list_executors(State(state.clone()))
+            .await
+            .map_err(|e| anyhow::anyhow!("failed to list executors: {:?}", e))?;
+        assert_eq!(executors.len(), 2);
+        let listed = executors
+            .iter()
+            .find(|listed| listed.id == executor.id.get())
+            .unwrap();
+        assert_eq!(listed.version.as_deref(), Some("0.2.18"));
+        assert!(listed.last_heartbeat_at.is_some());
+        assert_eq!(listed.allocated_tasks, 1);
+        assert_eq!(listed.running_task_ids, vec![task.id.to_string()]);
+        assert_eq!(listed.free_capacity, Some(2));
+        let listed = executors
+            .iter()
+            .find(|listed| listed.id == unlimited_executor.id.get())
+            .unwrap();
+        assert_eq!(listed.allocated_tasks, 0);
+        assert!(listed.running_task_ids.is_empty());
+        assert_eq!(listed.free_capacity, None);
+        Ok(())
+    }
+}
@@ -0,0 +1,36 @@
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+
+use super::RouteState;
+use crate::http_objects::{Alert, AlertsList, AlertsParams, IndexifyAPIError};
+
+/// List the alerts of the alert rules of the server
+#[utoipa::path(
+    get,
+    path = "/internal/alerts",
+    params(
+        ("state" = Option<String>, Query, description = "ok, pending or firing"),
+    ),
+    tag = "operations",
+    responses(
+        (status = 200, description = "Alerts as of their last evaluation", body = AlertsList),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn list_alerts(
+    Query(params): Query<AlertsParams>,
+    State(state): State<RouteState>,
+) -> Result<Json<AlertsList>, IndexifyAPIError> {
+    let alerts = state
+        .indexify_state
+        .reader()
+        .alert_statuses()
+        .map_err(IndexifyAPIError::internal_error)?
+        .into_iter()
+        .map(Alert::from)
+        .filter(|alert| params.state.map_or(true, |state| alert.state == state))
+        .collect();
+    Ok(Json(AlertsList { alerts }))
+}
@@ -0,0 +1,540 @@
+//! Enforces the role bindings of the auth configuration on every route, and
+//! lets clients check their permissions before making a request. Requests
+//! made with the token of a task are limited to the scope of the task instead.
+
+use axum::{
+    extract::{MatchedPath, Query, Request, State},
+    http::{HeaderMap, Method, StatusCode},
+    middleware::Next,
+    response::Response,
+    Json,
+};
+use data_model::TaskOutcome;
+use indexify_utils::get_epoch_time_in_ms;
+use serde::Deserialize;
+use tracing::{debug, info};
+
+use super::{request_span::path_params, RouteState};
+use crate::{
+    auth::{bearer_token, Identity, Permission, Role, ALL_NAMESPACES},
+    http_objects::{CanIResponse, IndexifyAPIError},
+    task_tokens::{TaskScope, TASK_TOKEN_PREFIX},
+};
+
+// Internal routes called by executors and the functions they run, any other
+// internal route operates the cluster. Matched exactly, the other routes under
+// the same prefixes aren't granted to executors.
+const EXECUTOR_ROUTES: [&str; 13] = [
+    "/internal/ingest_files",
+    "/internal/task_results",
+    "/internal/executors/:id/tasks",
+    "/internal/executors/:id/heartbeat",
+    "/internal/executors/:id/control",
+    "/internal/executors/:id/control/ack",
+    "/internal/executors/:id/labels",
+    "/internal/artifacts/:sha256",
+    "/internal/fn_outputs/:input_key",
+    "/internal/namespaces/:namespace/compute_graphs/:compute_graph/code",
+    "/internal/namespaces/:namespace/compute_graphs/:compute_graph/fns/:fn_name/environment",
+    "/internal/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/state/:key",
+    "/internal/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/fn/:fn_name/tasks/:task_id/checkpoint",
+];
+
+// Routes replicas of the server call on each other, with their peer token
+const CLUSTER_ROUTES: &str = "/internal/cluster/";
+const CLUSTER_STATUS_ROUTE: &str = "/internal/cluster/status";
+
+// Routes a task token invokes the graphs of the namespace of its task with
+const TASK_INVOKE_ROUTES: [&str; 5] = [
+    "/namespaces/:namespace/compute_graphs/:compute_graph/invoke_file",
+    "/namespaces/:namespace/compute_graphs/:compute_graph/invoke_object",
+    "/namespaces/:namespace/compute_graphs/:compute_graph/invoke_sync",
+    "/namespaces/:namespace/compute_graphs/:compute_graph/invoke_reference",
+    "/namespaces/:namespace/compute_graphs/:compute_graph/invoke_url",
+];
+
+// Internal routes of an invocation, e.g. its state and the checkpoints of its
+// tasks
+const INVOCATION_ROUTES: &str =
+    "/internal/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/";
+
+#[derive(Debug, PartialEq)]
+enum Access {
+    Public,
+    // Any identity, e.g. to ask what it's allowed to do
+    Authenticated,
+    Scoped {
+        namespace: String,
+        permission: Permission,
+    },
+}
+
+/// Permission a request needs, and the namespace it needs it in. Routes
+/// which are not scoped to a namespace need it in all namespaces.
+fn required_access(method: &Method, matched_path: &str, path: &str) -> Access {
+    if matched_path == "/" ||
+        matched_path.starts_with("/ui") ||
+        matched_path.starts_with("/docs") ||
+        // Signed urls are their own credential
+        matched_path == "/namespaces/:namespace/payloads/download"
+    {
+        return Access::Public;
+    }
+    if matched_path == "/can_i" {
+        return Access::Authenticated;
+    }
+    let namespace = path_params(matched_path, path)
+        .into_iter()
+        .find(|(name, _)| *name == "namespace")
+        .map(|(_, namespace)| namespace.to_string());
+    let permission = if matched_path == CLUSTER_STATUS_ROUTE {
+        Permission::Read
+    } else if matched_path.starts_with(CLUSTER_ROUTES) {
+        Permission::Replicate
+    } else if matched_path.starts_with("/internal/") {
+        if EXECUTOR_ROUTES.contains(&matched_path) {
+            Permission::Execute
+        } else {
+            Permission::Admin
+        }
+    } else if matched_path == "/namespaces" && method == Method::POST ||
+        matched_path == "/namespaces/:namespace" && method == Method::DELETE ||
+        // Legal holds keep data past its retention
+        matched_path.ends_with("/legal_hold") && method != Method::GET
+    {
+        Permission::Admin
+    } else if method == Method::GET || method == Method::HEAD {
+        Permission::Read
+    } else {
+        Permission::Write
+    };
+    Access::Scoped {
+        namespace: namespace.unwrap_or_else(|| ALL_NAMESPACES.to_string()),
+        permission,
+    }
+}
+
+/// Whether the token of a task can make a request. Task tokens read the
+/// namespace of their task and invoke its graphs, and call the internal routes
+/// of their own invocation, but nothing else.
+fn task_scope_allows(scope: &TaskScope, method: &Method, matched_path: &str, path: &str) -> bool {
+    let params = path_params(matched_path, path);
+    let param = |name: &str| {
+        params
+            .iter()
+            .find(|(param, _)| *param == name)
+            .map(|(_, value)| *value)
+    };
+    if param("namespace") != Some(scope.namespace.as_str()) {
+        return false;
+    }
+    if matched_path.starts_with(INVOCATION_ROUTES) {
+        return param("compute_graph") == Some(scope.compute_graph.as_str()) &&
+            param("invocation_id") == Some(scope.invocation_id.as_str()) &&
+            param("task_id").map_or(true, |task_id| task_id == scope.task_id);
+    }
+    if matched_path.starts_with("/internal/") {
+        return false;
+    }
+    match *method {
+        Method::GET | Method::HEAD => true,
+        Method::POST => TASK_INVOKE_ROUTES.contains(&matched_path),
+        _ => false,
+    }
+}
+
+/// Authorizes a request made with the token of a task, which is only valid
+/// while the task runs.
+fn authorize_task(
+    state: &RouteState,
+    token: &str,
+    method: &Method,
+    matched_path: &str,
+    path: &str,
+) -> Result<Identity, IndexifyAPIError> {
+    let scope = state
+        .task_tokens
+        .verify(token, get_epoch_time_in_ms() / 1000)
+        .map_err(|err| {
+            debug!("rejected task token: {:?}", err);
+            IndexifyAPIError::new(StatusCode::UNAUTHORIZED, "invalid or expired task token")
+        })?;
+    let task = state
+        .indexify_state
+        .reader()
+        .get_task(
+            &scope.namespace,
+            &scope.compute_graph,
+            &scope.invocation_id,
+            &scope.compute_fn,
+            &scope.task_id,
+        )
+        .map_err(IndexifyAPIError::internal_error)?;
+    if !task.is_some_and(|task| task.outcome == TaskOutcome::Unknown) {
+        return Err(IndexifyAPIError::new(
+            StatusCode::UNAUTHORIZED,
+            "the task of the token is finished",
+        ));
+    }
+    let identity = format!("task:{}", scope.task_id);
+    let allowed = task_scope_allows(&scope, method, matched_path, path);
+    info!(
+        identity,
+        route = matched_path,
+        namespace = scope.namespace,
+        allowed,
+        "authz decision"
+    );
+    if !allowed {
+        return Err(IndexifyAPIError::new(
+            StatusCode::FORBIDDEN,
+            &format!(
+                "the token of task {} can't call {} {}",
+                scope.task_id, method, matched_path
+            ),
+        ));
+    }
+    Ok(Identity {
+        name: identity,
+        groups: vec![],
+    })
+}
+
+/// Replica of the server a request is authenticated as, available to the
+/// handlers of the cluster routes as an extension.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PeerId(pub u64);
+
+/// Authorizes a request of another replica of the server, whose peer token
+/// is bound to the peer role.
+fn authorize_peer(
+    node_id: u64,
+    permission: Permission,
+    matched_path: &str,
+) -> Result<Identity, IndexifyAPIError> {
+    let identity = format!("peer:{}", node_id);
+    let allowed = Role::Peer.grants(permission);
+    info!(
+        identity,
+        route = matched_path,
+        %permission,
+        allowed,
+        "authz decision"
+    );
+    if !allowed {
+        return Err(IndexifyAPIError::new(
+            StatusCode::FORBIDDEN,
+            &format!("{} doesn't have the {} permission", identity, permission),
+        ));
+    }
+    Ok(Identity {
+        name: identity,
+        groups: vec![],
+    })
+}
+
+/// Checks the permission of the request, whose identity is then available
+/// to the handlers as an extension.
+pub async fn enforce_authz(
+    State(state): State<RouteState>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, IndexifyAPIError> {
+    // Unmatched requests are answered by the fallback
+    let Some(matched_path) = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|matched_path| matched_path.as_str().to_string())
+    else {
+        return Ok(next.run(request).await);
+    };
+    let access = required_access(request.method(), &matched_path, request.uri().path());
+    // Replicas authenticate with their peer token whether auth is configured
+    // or not, the replication log carries the sealed secrets
+    if matched_path.starts_with(CLUSTER_ROUTES) {
+        let peer_id = state
+            .cluster
+            .as_ref()
+            .and_then(|cluster| cluster.authenticate_peer(request.headers()));
+        match (peer_id, &access) {
+            (Some(node_id), Access::Scoped { permission, .. }) => {
+                let identity = authorize_peer(node_id, *permission, &matched_path)?;
+                request.extensions_mut().insert(identity);
+                request.extensions_mut().insert(PeerId(node_id));
+                return Ok(next.run(request).await);
+            }
+            _ if matched_path != CLUSTER_STATUS_ROUTE => {
+                return Err(IndexifyAPIError::new(
+                    StatusCode::UNAUTHORIZED,
+                    "missing or unknown peer token",
+                ));
+            }
+            // Load balancers check the status of the replicas
+            _ => {}
+        }
+    }
+    let Some(auth) = state.config_reloader.runtime().auth else {
+        return Ok(next.run(request).await);
+    };
+    let task_token = bearer_token(request.headers())
+        .filter(|token| token.starts_with(TASK_TOKEN_PREFIX))
+        .map(str::to_string);
+    if access != Access::Public {
+        if let Some(token) = task_token {
+            let identity = authorize_task(
+                &state,
+                &token,
+                request.method(),
+                &matched_path,
+                request.uri().path(),
+            )?;
+            request.extensions_mut().insert(identity);
+            return Ok(next.run(request).await);
+        }
+    }
+    match access {
+        Access::Public => {}
+        Access::Authenticated => {
+            let identity = auth.require_identity(request.headers(), &state.jwks)?;
+            request.extensions_mut().insert(identity);
+        }
+        Access::Scoped {
+            namespace,
+            permission,
+        } => {
+            let identity = auth.authorize(
+                request.headers(),
+                &state.jwks,
+                &namespace,
+                permission,
+                &matched_path,
+            )?;
+            request.extensions_mut().insert(identity);
+        }
+    }
+    Ok(next.run(request).await)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CanIParams {
+    /// All namespaces when unset
+    pub namespace: Option<String>,
+    pub permission: Permission,
+}
+
+/// Check whether the caller has a permission in a namespace
+#[utoipa::path(
+    get,
+    path = "/can_i",
+    params(
+        ("namespace" = Option<String>, Query, description = "Namespace, all namespaces when unset"),
+        ("permission" = Permission, Query, description = "read, write, admin, execute or replicate"),
+    ),
+    tag = "operations",
+    responses(
+        (status = 200, description = "Whether the permission is granted", body = CanIResponse),
+        (status = 401, description = "missing or unknown bearer token"),
+    ),
+)]
+pub async fn can_i(
+    State(state): State<RouteState>,
+    Query(params): Query<CanIParams>,
+    headers: HeaderMap,
+) -> Result<Json<CanIResponse>, IndexifyAPIError> {
+    let namespace = params
+        .namespace
+        .unwrap_or_else(|| ALL_NAMESPACES.to_string());
+    // Everything is allowed when auth is disabled
+    let Some(auth) = state.config_reloader.runtime().auth else {
+        return Ok(Json(CanIResponse {
+            identity: None,
+            namespace,
+            permission: params.permission,
+            allowed: true,
+            roles: vec![],
+        }));
+    };
+    let identity = auth.require_identity(&headers, &state.jwks)?;
+    let roles = auth.roles(&identity, &namespace);
+    Ok(Json(CanIResponse {
+        allowed: roles.iter().any(|role| role.grants(params.permission)),
+        identity: Some(identity.name),
+        namespace,
+        permission: params.permission,
+        roles,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scoped(namespace: &str, permission: Permission) -> Access {
+        Access::Scoped {
+            namespace: namespace.to_string(),
+            permission,
+        }
+    }
+
+    #[test]
+    fn test_required_access() {
+        assert_eq!(
+            required_access(
+                &Method::GET,
+                "/namespaces/:namespace/compute_graphs",
+                "/namespaces/research/compute_graphs"
+            ),
+            scoped("research", Permission::Read)
+        );
+        assert_eq!(
+            required_access(
+                &Method::POST,
+                "/namespaces/:namespace/compute_graphs/:compute_graph/invoke_object",
+                "/namespaces/research/compute_graphs/graph_a/invoke_object"
+            ),
+            scoped("research", Permission::Write)
+        );
+        assert_eq!(
+            required_access(
+                &Method::DELETE,
+                "/namespaces/:namespace",
+                "/namespaces/research"
+            ),
+            scoped("research", Permission::Admin)
+        );
+        assert_eq!(
+            required_access(&Method::POST, "/namespaces", "/namespaces"),
+            scoped(ALL_NAMESPACES, Permission::Admin)
+        );
+        assert_eq!(
+            required_access(
+                &Method::GET,
+                "/internal/namespaces/:namespace/compute_graphs/:compute_graph/code",
+                "/internal/namespaces/research/compute_graphs/graph_a/code"
+            ),
+            scoped("research", Permission::Execute)
+        );
+        assert_eq!(
+            required_access(&Method::POST, "/internal/gc", "/internal/gc"),
+            scoped(ALL_NAMESPACES, Permission::Admin)
+        );
+        assert_eq!(
+            required_access(
+                &Method::POST,
+                "/internal/executors/:id/control/ack",
+                "/internal/executors/executor_1/control/ack"
+            ),
+            scoped(ALL_NAMESPACES, Permission::Execute)
+        );
+        // Routes under the prefix of an executor route aren't executor routes
+        assert_eq!(
+            required_access(
+                &Method::POST,
+                "/internal/executors/:id/requeue",
+                "/internal/executors/executor_1/requeue"
+            ),
+            scoped(ALL_NAMESPACES, Permission::Admin)
+        );
+        assert_eq!(
+            required_access(
+                &Method::PUT,
+                "/namespaces/:namespace/compute_graphs/:compute_graph/legal_hold",
+                "/namespaces/research/compute_graphs/graph_a/legal_hold"
+            ),
+            scoped("research", Permission::Admin)
+        );
+        assert_eq!(
+            required_access(
+                &Method::GET,
+                "/namespaces/:namespace/payloads/download",
+                "/namespaces/research/payloads/download"
+            ),
+            Access::Public
+        );
+        assert_eq!(
+            required_access(&Method::GET, "/can_i", "/can_i"),
+            Access::Authenticated
+        );
+
+        // Only the peer role replicates the state
+        assert_eq!(
+            required_access(&Method::GET, "/internal/cluster/log", "/internal/cluster/log"),
+            scoped(ALL_NAMESPACES, Permission::Replicate)
+        );
+        assert!(Role::Peer.grants(Permission::Replicate));
+        assert!(!Role::Admin.grants(Permission::Replicate));
+        assert_eq!(
+            required_access(
+                &Method::GET,
+                "/internal/cluster/status",
+                "/internal/cluster/status"
+            ),
+            scoped(ALL_NAMESPACES, Permission::Read)
+        );
+    }
+
+    #[test]
+    fn test_task_scope_allows() {
+        let scope = TaskScope {
+            namespace: "research".to_string(),
+            compute_graph: "graph_a".to_string(),
+            invocation_id: "inv_1".to_string(),
+            compute_fn: "fn_a".to_string(),
+            task_id: "task_1".to_string(),
+            expires_at: 0,
+        };
+        let allows = |method: Method, matched_path: &str, path: &str| {
+            task_scope_allows(&scope, &method, matched_path, path)
+        };
+        // Invokes and reads the graphs of its namespace
+        assert!(allows(
+            Method::POST,
+            "/namespaces/:namespace/compute_graphs/:compute_graph/invoke_object",
+            "/namespaces/research/compute_graphs/graph_b/invoke_object"
+        ));
+        assert!(allows(
+            Method::GET,
+            "/namespaces/:namespace/compute_graphs/:compute_graph/invocations",
+            "/namespaces/research/compute_graphs/graph_b/invocations"
+        ));
+        assert!(!allows(
+            Method::POST,
+            "/namespaces/:namespace/compute_graphs/:compute_graph/invoke_object",
+            "/namespaces/default/compute_graphs/graph_b/invoke_object"
+        ));
+        assert!(!allows(
+            Method::POST,
+            "/namespaces/:namespace/compute_graphs",
+            "/namespaces/research/compute_graphs"
+        ));
+        assert!(!allows(
+            Method::DELETE,
+            "/namespaces/:namespace/compute_graphs/:compute_graph",
+            "/namespaces/research/compute_graphs/graph_a"
+        ));
+
+        // Only the state of its own invocation
+        let state_route = "/internal/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/state/:key";
+        assert!(allows(
+            Method::POST,
+            state_route,
+            "/internal/namespaces/research/compute_graphs/graph_a/invocations/inv_1/state/k"
+        ));
+        assert!(!allows(
+            Method::GET,
+            state_route,
+            "/internal/namespaces/research/compute_graphs/graph_a/invocations/inv_2/state/k"
+        ));
+        let checkpoint_route = "/internal/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/fn/:fn_name/tasks/:task_id/checkpoint";
+        assert!(!allows(
+            Method::POST,
+            checkpoint_route,
+            "/internal/namespaces/research/compute_graphs/graph_a/invocations/inv_1/fn/fn_a/tasks/task_2/checkpoint"
+        ));
+        assert!(!allows(
+            Method::GET,
+            "/internal/namespaces/:namespace/compute_graphs/:compute_graph/code",
+            "/internal/namespaces/research/compute_graphs/graph_a/code"
+        ));
+    }
+}
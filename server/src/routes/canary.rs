@@ -0,0 +1,130 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use state_store::requests::{
+    GraphCanaryRequest,
+    RequestPayload,
+    StateMachineUpdateRequest,
+    UpdateGraphCanaryRequest,
+};
+
+use super::RouteState;
+use crate::http_objects::{GraphCanary, IndexifyAPIError, UpdateGraphCanary};
+
+async fn write(state: &RouteState, payload: RequestPayload) -> Result<(), IndexifyAPIError> {
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload,
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(IndexifyAPIError::internal_error)
+}
+
+/// Get the canary version of a compute graph
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/canary",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Canary of the compute graph", body = GraphCanary),
+        (status = NOT_FOUND, description = "The compute graph has no canary"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn get_graph_canary(
+    Path((namespace, compute_graph)): Path<(String, String)>,
+    State(state): State<RouteState>,
+) -> Result<Json<GraphCanary>, IndexifyAPIError> {
+    let reader = state.indexify_state.reader();
+    let canary = reader
+        .get_graph_canary(&namespace, &compute_graph)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::not_found("canary not found"))?;
+    let current = reader
+        .get_compute_graph(&namespace, &compute_graph)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::not_found("Compute Graph not found"))?;
+    Ok(Json(GraphCanary::from_data_model(canary, &current)))
+}
+
+/// Change the percentage of the new invocations routed to the canary
+#[utoipa::path(
+    put,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/canary",
+    request_body = UpdateGraphCanary,
+    tag = "operations",
+    responses(
+        (status = 200, description = "Traffic split of the canary updated"),
+        (status = BAD_REQUEST, description = "Invalid percentage"),
+        (status = NOT_FOUND, description = "The compute graph has no canary"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn update_graph_canary(
+    Path((namespace, compute_graph)): Path<(String, String)>,
+    State(state): State<RouteState>,
+    Json(request): Json<UpdateGraphCanary>,
+) -> Result<(), IndexifyAPIError> {
+    write(
+        &state,
+        RequestPayload::UpdateGraphCanary(UpdateGraphCanaryRequest {
+            namespace,
+            compute_graph,
+            percent: request.percent,
+        }),
+    )
+    .await
+}
+
+/// Make the canary the current version of the compute graph
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/canary/finalize",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Canary promoted"),
+        (status = NOT_FOUND, description = "The compute graph has no canary"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn finalize_graph_canary(
+    Path((namespace, compute_graph)): Path<(String, String)>,
+    State(state): State<RouteState>,
+) -> Result<(), IndexifyAPIError> {
+    write(
+        &state,
+        RequestPayload::FinalizeGraphCanary(GraphCanaryRequest {
+            namespace,
+            compute_graph,
+        }),
+    )
+    .await
+}
+
+/// Roll back the canary, new invocations all run on the current version
+#[utoipa::path(
+    delete,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/canary",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Canary rolled back"),
+        (status = NOT_FOUND, description = "The compute graph has no canary"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn rollback_graph_canary(
+    Path((namespace, compute_graph)): Path<(String, String)>,
+    State(state): State<RouteState>,
+) -> Result<(), IndexifyAPIError> {
+    write(
+        &state,
+        RequestPayload::RollbackGraphCanary(GraphCanaryRequest {
+            namespace,
+            compute_graph,
+        }),
+    )
+    .await
+}
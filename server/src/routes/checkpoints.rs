@@ -0,0 +1,181 @@
+//! Checkpoints of long running tasks. A running task persists an opaque blob,
+//! e.g. the progress of a crawl, and the attempt resuming the task after it's
+//! retried or reassigned downloads the latest one instead of starting over.
+
+use anyhow::anyhow;
+use axum::{
+    body::Body,
+    extract::{Path, State},
+    http::StatusCode,
+    response::Response,
+    Json,
+};
+use data_model::{DataPayload, PendingUpload, Task, TaskId};
+use futures::StreamExt;
+use indexify_utils::get_epoch_time_in_ms;
+use nanoid::nanoid;
+use state_store::requests::{
+    CheckpointTaskRequest,
+    RegisterPendingUploadsRequest,
+    RequestPayload,
+    StateMachineUpdateRequest,
+};
+
+use super::{
+    download::read_payload,
+    payload_limits::{upload_error, PayloadLimit},
+    RouteState,
+};
+use crate::http_objects::{IndexifyAPIError, TaskCheckpoint};
+
+pub async fn put_task_checkpoint(
+    Path((namespace, compute_graph, invocation_id, fn_name, task_id)): Path<(
+        String,
+        String,
+        String,
+        String,
+        String,
+    )>,
+    State(state): State<RouteState>,
+    body: Body,
+) -> Result<Json<TaskCheckpoint>, IndexifyAPIError> {
+    let task = get_task(
+        &state,
+        &namespace,
+        &compute_graph,
+        &invocation_id,
+        &fn_name,
+        &task_id,
+    )?;
+    if task.terminal_state() {
+        return Err(IndexifyAPIError::new(
+            StatusCode::CONFLICT,
+            "task already finished",
+        ));
+    }
+    // Every checkpoint is a new blob, the previous one is deleted once it's
+    // replaced
+    let file_name = format!(
+        "{}.{}.{}.{}.{}.checkpoint.{}",
+        namespace,
+        compute_graph,
+        fn_name,
+        invocation_id,
+        task_id,
+        nanoid!()
+    );
+    let url = state.blob_storage.key_url(&file_name);
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: RequestPayload::RegisterPendingUploads(RegisterPendingUploadsRequest {
+                uploads: vec![PendingUpload {
+                    url,
+                    namespace: namespace.clone(),
+                    compute_graph: compute_graph.clone(),
+                    compute_fn: fn_name.clone(),
+                    invocation_id: invocation_id.clone(),
+                    task_id: task.id.clone(),
+                    created_at: get_epoch_time_in_ms(),
+                }],
+            }),
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+
+    let payload_limit = PayloadLimit::of_graph(&state, &namespace, &compute_graph)?
+        .map(|limit| limit.for_task(&invocation_id, &fn_name, &task_id));
+    let stream = body
+        .into_data_stream()
+        .map(|res| res.map_err(|err| anyhow!(err)));
+    let put_result = match &payload_limit {
+        Some(limit) => {
+            state
+                .blob_storage
+                .put(&file_name, limit.limit_stream(stream))
+                .await
+        }
+        None => state.blob_storage.put(&file_name, Box::pin(stream)).await,
+    }
+    .map_err(|e| upload_error(&state, payload_limit.as_ref(), e))?;
+    let checkpoint = data_model::TaskCheckpoint {
+        payload: DataPayload {
+            path: put_result.url,
+            size: put_result.size_bytes,
+            sha256_hash: put_result.sha256_hash,
+            tier: Default::default(),
+            mime_type: None,
+            content_encoding: None,
+            metadata: Default::default(),
+            replicas: Vec::new(),
+            compression: None,
+        },
+        created_at: get_epoch_time_in_ms(),
+    };
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: RequestPayload::CheckpointTask(CheckpointTaskRequest {
+                namespace,
+                compute_graph,
+                compute_fn: fn_name,
+                invocation_id,
+                task_id: TaskId::new(task_id),
+                checkpoint: checkpoint.clone(),
+            }),
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(checkpoint.into()))
+}
+
+pub async fn get_task_checkpoint(
+    Path((namespace, compute_graph, invocation_id, fn_name, task_id)): Path<(
+        String,
+        String,
+        String,
+        String,
+        String,
+    )>,
+    State(state): State<RouteState>,
+) -> Result<Response<Body>, IndexifyAPIError> {
+    let checkpoint = get_task(
+        &state,
+        &namespace,
+        &compute_graph,
+        &invocation_id,
+        &fn_name,
+        &task_id,
+    )?
+    .checkpoint
+    .ok_or(IndexifyAPIError::not_found("task has no checkpoint"))?;
+    let payload_stream =
+        read_payload(&state, &checkpoint.payload.path, checkpoint.payload.codec()).await?;
+
+    Response::builder()
+        .header("Content-Type", "application/octet-stream")
+        .header("Content-Length", checkpoint.payload.size.to_string())
+        .body(Body::from_stream(payload_stream))
+        .map_err(|e| IndexifyAPIError::internal_error_str(&e.to_string()))
+}
+
+fn get_task(
+    state: &RouteState,
+    namespace: &str,
+    compute_graph: &str,
+    invocation_id: &str,
+    fn_name: &str,
+    task_id: &str,
+) -> Result<Task, IndexifyAPIError> {
+    state
+        .indexify_state
+        .reader()
+        .get_task(namespace, compute_graph, invocation_id, fn_name, task_id)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::not_found(&format!(
+            "task {} not found",
+            task_id
+        )))
+}
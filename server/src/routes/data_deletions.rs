@@ -0,0 +1,137 @@
+//! Deletions of the data of ingested invocations, for right to be forgotten
+//! requests. The input of the invocation and the outputs derived from it are
+//! deleted, and their blobs removed from the storage before the deadline of
+//! the deletion. The report of a deletion tracks the removal of its blobs.
+
+use axum::{
+    extract::{Path, Query, State},
+    Extension,
+    Json,
+};
+use indexify_utils::get_epoch_time_in_ms;
+use state_store::requests::{DeleteIngestedDataRequest, RequestPayload, StateMachineUpdateRequest};
+
+use super::{check_writable, RouteState};
+use crate::{
+    auth::Identity,
+    http_objects::{
+        DataDeletion,
+        DataDeletionMode,
+        DataDeletionsList,
+        DeleteIngestedDataParams,
+        IndexifyAPIError,
+        ListParams,
+    },
+};
+
+// Recorded as the identity of the deletions requested while auth is disabled
+const ANONYMOUS: &str = "anonymous";
+
+fn report(
+    state: &RouteState,
+    deletion: data_model::data_deletion::DataDeletion,
+) -> Result<DataDeletion, IndexifyAPIError> {
+    let pending_blobs = state
+        .indexify_state
+        .reader()
+        .pending_gc_urls(&deletion.blobs)
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(DataDeletion::new(deletion, pending_blobs, get_epoch_time_in_ms()))
+}
+
+/// Delete the ingested data of a finished invocation along with the outputs
+/// derived from it, including by the sub graphs it ran
+#[utoipa::path(
+    delete,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/invocations/{invocation_id}/data",
+    params(
+        ("mode" = Option<DataDeletionMode>, Query, description = "soft keeps tombstones of the payloads, hard removes the invocations too"),
+    ),
+    tag = "operations",
+    responses(
+        (status = 200, description = "Report of the deletion", body = DataDeletion),
+        (status = NOT_FOUND, description = "Invocation not found"),
+        (status = CONFLICT, description = "The invocation is running or under a legal hold"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn delete_ingested_data(
+    Path((namespace, compute_graph, invocation_id)): Path<(String, String, String)>,
+    Query(params): Query<DeleteIngestedDataParams>,
+    State(state): State<RouteState>,
+    identity: Option<Extension<Identity>>,
+) -> Result<Json<DataDeletion>, IndexifyAPIError> {
+    check_writable(&state)?;
+    let deadline_ms = state.config_reloader.runtime().data_deletion_deadline_secs * 1000;
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: RequestPayload::DeleteIngestedData(DeleteIngestedDataRequest {
+                namespace: namespace.clone(),
+                compute_graph: compute_graph.clone(),
+                invocation_id: invocation_id.clone(),
+                mode: params.mode.into(),
+                identity: identity
+                    .map(|Extension(identity)| identity.name)
+                    .unwrap_or_else(|| ANONYMOUS.to_string()),
+                deadline: get_epoch_time_in_ms() + deadline_ms,
+            }),
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    get_data_deletion(Path((namespace, compute_graph, invocation_id)), State(state)).await
+}
+
+/// Get the report of the deletion of the data of an invocation
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/invocations/{invocation_id}/data_deletion",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Report of the deletion", body = DataDeletion),
+        (status = NOT_FOUND, description = "The data of the invocation wasn't deleted"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn get_data_deletion(
+    Path((namespace, compute_graph, invocation_id)): Path<(String, String, String)>,
+    State(state): State<RouteState>,
+) -> Result<Json<DataDeletion>, IndexifyAPIError> {
+    let deletion = state
+        .indexify_state
+        .reader()
+        .data_deletion(&namespace, &compute_graph, &invocation_id)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::not_found("data deletion not found"))?;
+    Ok(Json(report(&state, deletion)?))
+}
+
+/// List the reports of the data deletions of a namespace
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/data_deletions",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Reports of the data deletions", body = DataDeletionsList),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn list_data_deletions(
+    Path(namespace): Path<String>,
+    Query(params): Query<ListParams>,
+    State(state): State<RouteState>,
+) -> Result<Json<DataDeletionsList>, IndexifyAPIError> {
+    let (deletions, cursor) = state
+        .indexify_state
+        .reader()
+        .data_deletions(&namespace, None, params.cursor.as_deref(), params.limit)
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(DataDeletionsList {
+        deletions: deletions
+            .into_iter()
+            .map(|deletion| report(&state, deletion))
+            .collect::<Result<_, _>>()?,
+        cursor,
+    }))
+}
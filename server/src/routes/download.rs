@@ -1,15 +1,28 @@
 use anyhow::anyhow;
 use axum::{
     body::Body,
-    extract::{Path, State},
+    extract::{Path, Query, State},
+    http::StatusCode,
     response::Response,
+    Json,
 };
+use bytes::Bytes;
+use data_model::{is_inline_path, DataPayload, PayloadCodec, StorageTier};
+use futures::{stream::BoxStream, StreamExt};
+use indexify_utils::get_epoch_time_in_ms;
 
-use super::RouteState;
-use crate::http_objects::IndexifyAPIError;
+use super::{invoke::PAYLOAD_METADATA_HEADER_PREFIX, payload_compression::decompress, RouteState};
+use crate::http_objects::{
+    DownloadParams,
+    IndexifyAPIError,
+    PayloadUrl,
+    PayloadUrlRequest,
+    SignedDownloadParams,
+};
 
 pub async fn download_invocation_payload(
     Path((namespace, compute_graph, invocation_id)): Path<(String, String, String)>,
+    Query(params): Query<DownloadParams>,
     State(state): State<RouteState>,
 ) -> Result<Response<Body>, IndexifyAPIError> {
     let output = state
@@ -22,26 +35,27 @@ pub async fn download_invocation_payload(
                 e
             ))
         })?;
-    let storage_reader = state.blob_storage.get(&output.payload.path);
-    let payload_stream = storage_reader
-        .get()
-        .await
-        .map_err(|e| IndexifyAPIError::internal_error(e))?;
-
-    Response::builder()
-        .header("Content-Type", "application/octet-stream")
-        .header("Content-Length", output.payload.size.to_string())
-        .body(Body::from_stream(payload_stream))
-        .map_err(|e| IndexifyAPIError::internal_error_str(&e.to_string()))
+    check_not_expired(&output.payload)?;
+    let payload_stream = read_payload(
+        &state,
+        output.payload.nearest_path(params.region.as_deref()),
+        output.payload.codec(),
+    )
+    .await?;
+    payload_response(&output.payload, payload_stream)
 }
 
 /// Get function output
 #[utoipa::path(
     get,
     path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/invocations/{invocation_id}/fn/{fn_name}/output/{id}",
+    params(
+        ("region" = Option<String>, Query, description = "Downloads the replica of the output in the region when there is one"),
+    ),
     tag = "retrieve",
     responses(
         (status = 200, description = "Function output"),
+        (status = GONE, description = "The output expired past the retention of the graph"),
         (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
     ),
 )]
@@ -53,6 +67,7 @@ pub async fn download_fn_output_payload(
         String,
         String,
     )>,
+    Query(params): Query<DownloadParams>,
     State(state): State<RouteState>,
 ) -> Result<Response<Body>, IndexifyAPIError> {
     let output = state
@@ -81,21 +96,80 @@ pub async fn download_fn_output_payload(
             )))
         }
     };
-    let storage_reader = state.blob_storage.get(&payload.path);
-    let payload_stream = storage_reader
-        .get()
-        .await
-        .map_err(|e| IndexifyAPIError::internal_error(e))?;
+    check_not_expired(&payload)?;
+    let payload_stream = read_payload(
+        &state,
+        payload.nearest_path(params.region.as_deref()),
+        payload.codec(),
+    )
+    .await?;
+    payload_response(&payload, payload_stream)
+}
 
-    Response::builder()
-        .header("Content-Type", "application/octet-stream")
-        .header("Content-Length", payload.size.to_string())
+fn check_not_expired(payload: &DataPayload) -> Result<(), IndexifyAPIError> {
+    if payload.tier == StorageTier::Expired {
+        return Err(IndexifyAPIError::new(
+            StatusCode::GONE,
+            "payload expired past the retention of its graph",
+        ));
+    }
+    Ok(())
+}
+
+/// Response streaming a payload, with the media type, encoding and metadata
+/// it was uploaded with.
+fn payload_response(
+    payload: &DataPayload,
+    payload_stream: BoxStream<'static, anyhow::Result<Bytes>>,
+) -> Result<Response<Body>, IndexifyAPIError> {
+    let mut response = Response::builder()
+        .header(
+            "Content-Type",
+            payload
+                .mime_type
+                .as_deref()
+                .unwrap_or("application/octet-stream"),
+        )
+        .header("Content-Length", payload.size.to_string());
+    if let Some(content_encoding) = &payload.content_encoding {
+        response = response.header("Content-Encoding", content_encoding);
+    }
+    for (key, value) in &payload.metadata {
+        response = response.header(format!("{}{}", PAYLOAD_METADATA_HEADER_PREFIX, key), value);
+    }
+    response
         .body(Body::from_stream(payload_stream))
         .map_err(|e| IndexifyAPIError::internal_error_str(&e.to_string()))
 }
 
+/// Streams a payload from the blob store, decompressed, or from the state
+/// store when the payload was stored inline.
+pub(crate) async fn read_payload(
+    state: &RouteState,
+    path: &str,
+    codec: Option<PayloadCodec>,
+) -> Result<BoxStream<'static, anyhow::Result<Bytes>>, IndexifyAPIError> {
+    if is_inline_path(path) {
+        let data = state
+            .indexify_state
+            .reader()
+            .get_inline_payload(path)
+            .map_err(IndexifyAPIError::internal_error)?
+            .ok_or(IndexifyAPIError::not_found("payload not found"))?;
+        return Ok(futures::stream::once(async move { Ok(Bytes::from(data)) }).boxed());
+    }
+    let stream = state
+        .blob_storage
+        .get(path)
+        .get()
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(decompress(stream, codec))
+}
+
 pub async fn download_fn_output_by_key(
     Path(output_key): Path<String>,
+    Query(params): Query<DownloadParams>,
     State(state): State<RouteState>,
 ) -> Result<Response<Body>, IndexifyAPIError> {
     let output = state
@@ -117,15 +191,248 @@ pub async fn download_fn_output_by_key(
             )))
         }
     };
-    let storage_reader = state.blob_storage.get(&payload.path);
-    let payload_stream = storage_reader
-        .get()
-        .await
-        .map_err(|e| IndexifyAPIError::internal_error(e))?;
+    check_not_expired(&payload)?;
+    let payload_stream = read_payload(
+        &state,
+        payload.nearest_path(params.region.as_deref()),
+        payload.codec(),
+    )
+    .await?;
+    payload_response(&payload, payload_stream)
+}
+
+/// Create a short lived url to download a payload of the namespace
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/payloads/url",
+    request_body = PayloadUrlRequest,
+    tag = "retrieve",
+    responses(
+        (status = 200, description = "Download url of the payload", body = PayloadUrl),
+        (status = 404, description = "Payload not found in the namespace"),
+        (status = GONE, description = "The payload expired past the retention of the graph"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn create_payload_url(
+    Path(namespace): Path<String>,
+    State(state): State<RouteState>,
+    Json(request): Json<PayloadUrlRequest>,
+) -> Result<Json<PayloadUrl>, IndexifyAPIError> {
+    // Payloads are looked up within the namespace, which scopes the urls
+    let payload = namespace_payload(&state, &namespace, &request)?;
+    check_not_expired(&payload)?;
+    let expiry = state.download_url_signer.expiry(request.expires_in_secs);
+    let expires_at = get_epoch_time_in_ms() / 1000 + expiry.as_secs();
+    // Compressed payloads are streamed by the server, which decompresses them
+    let signed_url = match payload.codec() {
+        Some(_) => None,
+        None => state
+            .blob_storage
+            .signed_url(&payload.path, expiry)
+            .await
+            .map_err(IndexifyAPIError::internal_error)?,
+    };
+    let url = match signed_url {
+        Some(url) => url,
+        None => {
+            let mut url = format!(
+                "/namespaces/{}/payloads/download?path={}&expires_at={}&signature={}",
+                namespace,
+                hex::encode(&payload.path),
+                expires_at,
+                state
+                    .download_url_signer
+                    .sign(&namespace, &payload.path, expires_at),
+            );
+            if let Some(PayloadCodec::Zstd) = payload.codec() {
+                url.push_str("&codec=zstd");
+            }
+            url
+        }
+    };
+    Ok(Json(PayloadUrl {
+        url,
+        expires_at,
+        size: payload.size,
+        sha256_hash: payload.sha256_hash,
+    }))
+}
+
+fn namespace_payload(
+    state: &RouteState,
+    namespace: &str,
+    request: &PayloadUrlRequest,
+) -> Result<DataPayload, IndexifyAPIError> {
+    let reader = state.indexify_state.reader();
+    match (&request.fn_name, &request.output_id) {
+        (None, None) => reader
+            .invocation_payload(namespace, &request.compute_graph, &request.invocation_id)
+            .map(|invocation| invocation.payload)
+            .map_err(IndexifyAPIError::internal_error),
+        (Some(fn_name), Some(output_id)) => {
+            let output = reader
+                .fn_output_payload(
+                    namespace,
+                    &request.compute_graph,
+                    &request.invocation_id,
+                    fn_name,
+                    output_id,
+                )
+                .map_err(IndexifyAPIError::internal_error)?
+                .ok_or(IndexifyAPIError::not_found("fn output not found"))?;
+            match output.payload {
+                data_model::OutputPayload::Fn(payload) => Ok(payload),
+                data_model::OutputPayload::Router(_) => Err(IndexifyAPIError::bad_request(
+                    "router outputs have no payload",
+                )),
+            }
+        }
+        _ => Err(IndexifyAPIError::bad_request(
+            "fn_name and output_id must be set together",
+        )),
+    }
+}
+
+/// Streams a payload from a url created by `create_payload_url`.
+pub async fn download_signed_payload(
+    Path(namespace): Path<String>,
+    Query(params): Query<SignedDownloadParams>,
+    State(state): State<RouteState>,
+) -> Result<Response<Body>, IndexifyAPIError> {
+    let path = hex::decode(&params.path)
+        .ok()
+        .and_then(|path| String::from_utf8(path).ok())
+        .ok_or(IndexifyAPIError::bad_request("invalid path"))?;
+    state
+        .download_url_signer
+        .verify(
+            &namespace,
+            &path,
+            params.expires_at,
+            &params.signature,
+            get_epoch_time_in_ms() / 1000,
+        )
+        .map_err(|e| IndexifyAPIError::new(StatusCode::FORBIDDEN, &e.to_string()))?;
+    let payload_stream = read_payload(&state, &path, params.codec).await?;
 
     Response::builder()
         .header("Content-Type", "application/octet-stream")
-        .header("Content-Length", payload.size.to_string())
         .body(Body::from_stream(payload_stream))
         .map_err(|e| IndexifyAPIError::internal_error_str(&e.to_string()))
 }
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{header, HeaderMap};
+    use data_model::{
+        filter::LabelsFilter,
+        test_objects::tests::{mock_graph_a, TEST_NAMESPACE},
+        QueueLimits,
+    };
+    use state_store::requests::{
+        CreateComputeGraphRequest,
+        NamespaceRequest,
+        RequestPayload,
+        StateMachineUpdateRequest,
+    };
+
+    use super::*;
+    use crate::{
+        config::RuntimeConfig,
+        http_objects::InvocationQueryParams,
+        routes::{invoke::invoke_with_object, test_route_state::TestRouteState},
+    };
+
+    #[tokio::test]
+    async fn test_payload_headers() -> anyhow::Result<()> {
+        let test_state = TestRouteState::new(RuntimeConfig::default()).await?;
+        let state = test_state.route_state.clone();
+        let write = |payload| {
+            state.indexify_state.write(StateMachineUpdateRequest {
+                payload,
+                state_changes_processed: vec![],
+            })
+        };
+        write(RequestPayload::CreateNameSpace(NamespaceRequest {
+            name: TEST_NAMESPACE.to_string(),
+            queue_limits: QueueLimits::default(),
+            scheduling_weight: None,
+            reserved_capacity: None,
+            replication: None,
+        }))
+        .await?;
+        let cg = mock_graph_a();
+        write(RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+            namespace: TEST_NAMESPACE.to_string(),
+            compute_graph: cg.clone(),
+            expected_revision: None,
+            force: false,
+        }))
+        .await?;
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_TYPE, "application/json".parse()?);
+        headers.insert(header::CONTENT_ENCODING, "gzip".parse()?);
+        headers.insert("x-indexify-meta-source", "crawler".parse()?);
+        headers.insert("x-indexify-meta-page", "7".parse()?);
+        let params = InvocationQueryParams {
+            block_until_finish: None,
+            labels: None,
+            attributes: None,
+            priority: None,
+            invocation_group: None,
+            parameters: None,
+            event_time: None,
+            lane: None,
+        };
+        invoke_with_object(
+            Path((TEST_NAMESPACE.to_string(), cg.name.clone())),
+            Query(params),
+            State(state.clone()),
+            headers,
+            Body::from("input"),
+        )
+        .await
+        .map_err(|e| anyhow!("failed to invoke: {:?}", e))?;
+
+        // The payload is stored as the client encoded it, with its headers
+        let (invocations, _) = state.indexify_state.reader().list_invocations(
+            TEST_NAMESPACE,
+            &cg.name,
+            None,
+            None,
+            &LabelsFilter::default(),
+        )?;
+        assert_eq!(invocations.len(), 1);
+        let payload = &invocations[0].payload;
+        assert_eq!(payload.mime_type.as_deref(), Some("application/json"));
+        assert_eq!(payload.content_encoding.as_deref(), Some("gzip"));
+        assert_eq!(payload.compression, None);
+        assert_eq!(payload.metadata.len(), 2);
+        assert_eq!(payload.metadata["source"], "crawler");
+        assert_eq!(payload.metadata["page"], "7");
+
+        // And returned with the same headers on download
+        let response = download_invocation_payload(
+            Path((
+                TEST_NAMESPACE.to_string(),
+                cg.name.clone(),
+                invocations[0].id.clone(),
+            )),
+            Query(DownloadParams { region: None }),
+            State(state.clone()),
+        )
+        .await
+        .map_err(|e| anyhow!("failed to download: {:?}", e))?;
+        let headers = response.headers();
+        assert_eq!(headers[header::CONTENT_TYPE], "application/json");
+        assert_eq!(headers[header::CONTENT_ENCODING], "gzip");
+        assert_eq!(headers[header::CONTENT_LENGTH], "5");
+        assert_eq!(headers["x-indexify-meta-source"], "crawler");
+        assert_eq!(headers["x-indexify-meta-page"], "7");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+        assert_eq!(body, Bytes::from("input"));
+        Ok(())
+    }
+}
@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use data_model::eta::EtaEstimator;
+use indexify_utils::get_epoch_time_in_ms;
+
+use super::RouteState;
+use crate::http_objects::{IndexifyAPIError, InvocationEta};
+
+/// Estimate the time left before an invocation finishes and the queue
+/// positions of its pending tasks
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/invocations/{invocation_id}/eta",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Estimated time left", body = InvocationEta),
+        (status = 404, description = "Invocation not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn get_invocation_eta(
+    Path((namespace, compute_graph, invocation_id)): Path<(String, String, String)>,
+    State(state): State<RouteState>,
+) -> Result<Json<InvocationEta>, IndexifyAPIError> {
+    let reader = state.indexify_state.reader();
+    let ctx = reader
+        .invocation_ctx(&namespace, &compute_graph, &invocation_id)
+        .map_err(IndexifyAPIError::internal_error)?;
+    if ctx.completed {
+        return Ok(Json(InvocationEta {
+            invocation_id,
+            completed: true,
+            remaining_ms: 0,
+            tasks: vec![],
+            fns_without_latency: vec![],
+        }));
+    }
+    let graph = reader
+        .get_invocation_compute_graph(&namespace, &compute_graph, &invocation_id)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::not_found("Compute Graph not found"))?;
+    let latencies: HashMap<_, _> = reader
+        .graph_function_latencies(&namespace, &compute_graph)
+        .map_err(IndexifyAPIError::internal_error)?
+        .into_iter()
+        .map(|latency| (latency.compute_fn, latency.sketch))
+        .collect();
+    let running = reader
+        .running_task_counts(&namespace, &compute_graph)
+        .map_err(IndexifyAPIError::internal_error)?;
+    let queued = reader
+        .unallocated_tasks_of_graph(&namespace, &compute_graph)
+        .map_err(IndexifyAPIError::internal_error)?;
+    let (tasks, _) = reader
+        .list_tasks_by_compute_graph(&namespace, &compute_graph, &invocation_id, None, None)
+        .map_err(IndexifyAPIError::internal_error)?;
+
+    let eta = EtaEstimator::new(&graph, &latencies, &running).estimate(
+        tasks,
+        &queued,
+        get_epoch_time_in_ms(),
+    );
+    Ok(Json(InvocationEta {
+        invocation_id,
+        completed: false,
+        remaining_ms: eta.remaining_ms,
+        tasks: eta.tasks.into_iter().map(Into::into).collect(),
+        fns_without_latency: eta.fns_without_latency,
+    }))
+}
@@ -0,0 +1,136 @@
+//! Approval of the executors joining the cluster, when the executor_approval
+//! setting requires it. Executors are approved or denied on behalf of the
+//! identity of the request.
+
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Path, Query, State},
+    Extension,
+    Json,
+};
+use data_model::{executor_approval::ApprovalStatus, ExecutorId};
+
+use super::RouteState;
+use crate::{
+    auth::Identity,
+    http_objects::{
+        ApproveExecutor,
+        ExecutorApproval,
+        ExecutorApprovalsList,
+        ExecutorApprovalsParams,
+        IndexifyAPIError,
+    },
+};
+
+// Recorded as the identity of the decisions made while auth is disabled
+const ANONYMOUS: &str = "anonymous";
+
+/// List the executors pending approval, and the ones approved or denied
+#[utoipa::path(
+    get,
+    path = "/internal/executor_approvals",
+    params(
+        ("status" = Option<String>, Query, description = "pending, approved or denied"),
+    ),
+    tag = "operations",
+    responses(
+        (status = 200, description = "Approvals of the executors", body = ExecutorApprovalsList),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn list_executor_approvals(
+    Query(params): Query<ExecutorApprovalsParams>,
+    State(state): State<RouteState>,
+) -> Result<Json<ExecutorApprovalsList>, IndexifyAPIError> {
+    let approvals = state
+        .indexify_state
+        .reader()
+        .list_executor_approvals()
+        .map_err(IndexifyAPIError::internal_error)?
+        .into_iter()
+        .map(ExecutorApproval::from)
+        .filter(|approval| {
+            params
+                .status
+                .map_or(true, |status| approval.status == status)
+        })
+        .collect();
+    Ok(Json(ExecutorApprovalsList { approvals }))
+}
+
+async fn decide(
+    state: &RouteState,
+    identity: Option<Extension<Identity>>,
+    executor_id: ExecutorId,
+    status: ApprovalStatus,
+    labels: HashMap<String, serde_json::Value>,
+) -> Result<Json<ExecutorApproval>, IndexifyAPIError> {
+    let decided_by = identity
+        .map(|Extension(identity)| identity.name)
+        .unwrap_or_else(|| ANONYMOUS.to_string());
+    let approval = state
+        .executor_manager
+        .decide_approval(&executor_id, status, labels, &decided_by)
+        .await
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::not_found(&format!(
+            "executor {} never registered",
+            executor_id
+        )))?;
+    Ok(Json(approval.into()))
+}
+
+/// Approve an executor, stamping labels on it
+#[utoipa::path(
+    post,
+    path = "/internal/executor_approvals/{executor_id}/approve",
+    request_body = ApproveExecutor,
+    tag = "operations",
+    responses(
+        (status = 200, description = "Executor approved, it joins the next time it registers", body = ExecutorApproval),
+        (status = NOT_FOUND, description = "The executor never registered"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn approve_executor(
+    Path(executor_id): Path<ExecutorId>,
+    State(state): State<RouteState>,
+    identity: Option<Extension<Identity>>,
+    Json(request): Json<ApproveExecutor>,
+) -> Result<Json<ExecutorApproval>, IndexifyAPIError> {
+    decide(
+        &state,
+        identity,
+        executor_id,
+        ApprovalStatus::Approved,
+        request.labels,
+    )
+    .await
+}
+
+/// Deny an executor, it's deregistered if it was approved
+#[utoipa::path(
+    post,
+    path = "/internal/executor_approvals/{executor_id}/deny",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Executor denied", body = ExecutorApproval),
+        (status = NOT_FOUND, description = "The executor never registered"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn deny_executor(
+    Path(executor_id): Path<ExecutorId>,
+    State(state): State<RouteState>,
+    identity: Option<Extension<Identity>>,
+) -> Result<Json<ExecutorApproval>, IndexifyAPIError> {
+    decide(
+        &state,
+        identity,
+        executor_id,
+        ApprovalStatus::Denied,
+        HashMap::new(),
+    )
+    .await
+}
@@ -0,0 +1,75 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use data_model::ExecutorId;
+use indexify_utils::get_epoch_time_in_ms;
+use state_store::requests::{
+    RemoveExecutorBlacklistEntryRequest,
+    RequestPayload,
+    StateMachineUpdateRequest,
+};
+
+use super::{check_writable, RouteState};
+use crate::http_objects::{ExecutorBlacklist, IndexifyAPIError};
+
+/// List the executors blacklisted for functions they failed repeatedly
+#[utoipa::path(
+    get,
+    path = "/internal/executor_blacklist",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Active blacklist entries", body = ExecutorBlacklist),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn list_executor_blacklist(
+    State(state): State<RouteState>,
+) -> Result<Json<ExecutorBlacklist>, IndexifyAPIError> {
+    let entries = state
+        .indexify_state
+        .reader()
+        .executor_blacklist(get_epoch_time_in_ms())
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(ExecutorBlacklist {
+        entries: entries.into_iter().map(Into::into).collect(),
+    }))
+}
+
+/// Lift the blacklisting of an executor for a function before it expires
+#[utoipa::path(
+    delete,
+    path = "/internal/executor_blacklist/{namespace}/{compute_graph}/{compute_fn}/{executor_id}",
+    tag = "operations",
+    responses(
+        (status = 200, description = "the executor can run the function again"),
+        (status = NOT_FOUND, description = "the executor isn't blacklisted for the function"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn remove_executor_blacklist_entry(
+    Path((namespace, compute_graph, compute_fn, executor_id)): Path<(
+        String,
+        String,
+        String,
+        ExecutorId,
+    )>,
+    State(state): State<RouteState>,
+) -> Result<(), IndexifyAPIError> {
+    check_writable(&state)?;
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: RequestPayload::RemoveExecutorBlacklistEntry(
+                RemoveExecutorBlacklistEntryRequest {
+                    namespace,
+                    compute_graph,
+                    compute_fn,
+                    executor_id,
+                },
+            ),
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(IndexifyAPIError::internal_error)
+}
@@ -0,0 +1,75 @@
+//! Control channel of executors. An executor long-polls it for commands
+//! about the tasks it's running, e.g. to abort a pre-empted or cancelled
+//! task, and acknowledges them once carried out. Commands are persisted and
+//! delivered on every poll until they are acknowledged, so none is lost when
+//! a poll fails, the executor reconnects or the server restarts.
+
+use std::time::Duration;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use data_model::{ExecutorId, TaskId};
+use serde::Deserialize;
+
+use super::RouteState;
+use crate::http_objects::{AcknowledgeAborts, ExecutorControl, IndexifyAPIError};
+
+const DEFAULT_WAIT_SECS: u64 = 30;
+const MAX_WAIT_SECS: u64 = 120;
+
+#[derive(Debug, Deserialize)]
+pub struct ControlParams {
+    /// How long to wait for a command when none is pending
+    wait_secs: Option<u64>,
+}
+
+fn not_connected(executor_id: &ExecutorId) -> IndexifyAPIError {
+    IndexifyAPIError::not_found(&format!(
+        "executor {} isn't connected, it opens its task stream first",
+        executor_id
+    ))
+}
+
+pub async fn poll_executor_control(
+    Path(executor_id): Path<ExecutorId>,
+    Query(params): Query<ControlParams>,
+    State(state): State<RouteState>,
+) -> Result<Json<ExecutorControl>, IndexifyAPIError> {
+    let wait = Duration::from_secs(
+        params
+            .wait_secs
+            .unwrap_or(DEFAULT_WAIT_SECS)
+            .min(MAX_WAIT_SECS),
+    );
+    let aborts = state
+        .indexify_state
+        .abort_commands(&executor_id, wait)
+        .await
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or_else(|| not_connected(&executor_id))?;
+    Ok(Json(ExecutorControl {
+        aborts: aborts.into_iter().map(Into::into).collect(),
+    }))
+}
+
+pub async fn acknowledge_executor_aborts(
+    Path(executor_id): Path<ExecutorId>,
+    State(state): State<RouteState>,
+    Json(payload): Json<AcknowledgeAborts>,
+) -> Result<StatusCode, IndexifyAPIError> {
+    let task_ids: Vec<TaskId> = payload.task_ids.into_iter().map(TaskId::new).collect();
+    let acknowledged = state
+        .indexify_state
+        .acknowledge_aborts(&executor_id, task_ids)
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    tracing::debug!(
+        "executor {} acknowledged {} aborts",
+        executor_id,
+        acknowledged
+    );
+    Ok(StatusCode::NO_CONTENT)
+}
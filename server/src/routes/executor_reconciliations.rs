@@ -0,0 +1,72 @@
+//! Reports of the executors restarting after a crash. The tasks an executor
+//! was running are reconciled with the tasks allocated to it when it
+//! registers again, each restart leaves a report of what was salvaged and
+//! what was retried.
+
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use data_model::ExecutorId;
+
+use super::RouteState;
+use crate::http_objects::{
+    ExecutorReconciliation,
+    ExecutorReconciliationsList,
+    IndexifyAPIError,
+    ListParams,
+};
+
+fn list(
+    state: &RouteState,
+    executor_id: Option<&ExecutorId>,
+    params: ListParams,
+) -> Result<Json<ExecutorReconciliationsList>, IndexifyAPIError> {
+    let (reconciliations, cursor) = state
+        .indexify_state
+        .reader()
+        .executor_reconciliations(executor_id, params.cursor.as_deref(), params.limit)
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(ExecutorReconciliationsList {
+        reconciliations: reconciliations
+            .into_iter()
+            .map(ExecutorReconciliation::from)
+            .collect(),
+        cursor,
+    }))
+}
+
+/// List the reports of the restarts of every executor
+#[utoipa::path(
+    get,
+    path = "/internal/executor_reconciliations",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Reports of the reconciliations", body = ExecutorReconciliationsList),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn list_executor_reconciliations(
+    Query(params): Query<ListParams>,
+    State(state): State<RouteState>,
+) -> Result<Json<ExecutorReconciliationsList>, IndexifyAPIError> {
+    list(&state, None, params)
+}
+
+/// List the reports of the restarts of an executor, the oldest first
+#[utoipa::path(
+    get,
+    path = "/internal/executors/{id}/reconciliations",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Reports of the reconciliations", body = ExecutorReconciliationsList),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn list_reconciliations_of_executor(
+    Path(executor_id): Path<ExecutorId>,
+    Query(params): Query<ListParams>,
+    State(state): State<RouteState>,
+) -> Result<Json<ExecutorReconciliationsList>, IndexifyAPIError> {
+    list(&state, Some(&executor_id), params)
+}
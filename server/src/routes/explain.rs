@@ -0,0 +1,44 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use data_model::Task;
+
+use super::RouteState;
+use crate::http_objects::{IndexifyAPIError, PendingTaskExplanation};
+
+/// Explain why a task isn't allocated yet
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/invocations/{invocation_id}/fn/{fn_name}/tasks/{task_id}/explain",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Why the task isn't allocated", body = PendingTaskExplanation),
+        (status = NOT_FOUND, description = "Task not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn explain_pending_task(
+    Path((namespace, compute_graph, invocation_id, fn_name, task_id)): Path<(
+        String,
+        String,
+        String,
+        String,
+        String,
+    )>,
+    State(state): State<RouteState>,
+) -> Result<Json<PendingTaskExplanation>, IndexifyAPIError> {
+    let task_key = Task::key_from(
+        &namespace,
+        &compute_graph,
+        &invocation_id,
+        &fn_name,
+        &task_id,
+    );
+    let explanation = state
+        .task_scheduler
+        .explain_pending_task(&task_key)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::not_found("task not found"))?;
+    Ok(Json(PendingTaskExplanation::new(&task_id, explanation)))
+}
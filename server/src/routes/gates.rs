@@ -0,0 +1,100 @@
+use anyhow::anyhow;
+use axum::{
+    body::Body,
+    extract::{Path, State},
+};
+use futures::StreamExt;
+use state_store::requests::{RequestPayload, SignalInvocationRequest, StateMachineUpdateRequest};
+use tracing::error;
+
+use super::{check_writable, RouteState};
+use crate::http_objects::IndexifyAPIError;
+
+/// Signal a gate of an invocation
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/invocations/{invocation_id}/gates/{gate}/signal",
+    request_body(content_type = "application/octet-stream", content = inline(Vec<u8>)),
+    tag = "operations",
+    responses(
+        (status = 200, description = "the gate was opened"),
+        (status = 400, description = "the node is not a gate"),
+        (status = 404, description = "no task of the invocation waits on the gate"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn signal_invocation(
+    Path((namespace, compute_graph, invocation_id, gate)): Path<(String, String, String, String)>,
+    State(state): State<RouteState>,
+    body: Body,
+) -> Result<(), IndexifyAPIError> {
+    check_writable(&state)?;
+    let graph = state
+        .indexify_state
+        .reader()
+        .get_compute_graph(&namespace, &compute_graph)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::not_found("Compute Graph not found"))?;
+    match graph.nodes.get(&gate) {
+        Some(node) if node.is_gate() => {}
+        Some(_) => {
+            return Err(IndexifyAPIError::bad_request(&format!(
+                "node {} is not a gate",
+                gate
+            )))
+        }
+        None => {
+            return Err(IndexifyAPIError::not_found(&format!(
+                "gate {} not found",
+                gate
+            )))
+        }
+    }
+
+    // The payload is passed as is to the next nodes, it is encoded by the
+    // client like the outputs of functions
+    let payload_key = format!(
+        "{}.{}.{}.{}.signal.{}",
+        namespace,
+        compute_graph,
+        invocation_id,
+        gate,
+        nanoid::nanoid!()
+    );
+    let payload_stream = body
+        .into_data_stream()
+        .map(|res| res.map_err(|err| anyhow!(err)));
+    let put_result = state
+        .blob_storage
+        .put(&payload_key, Box::pin(payload_stream))
+        .await
+        .map_err(|e| {
+            error!("failed to write to blob store: {}", e);
+            IndexifyAPIError::internal_error(anyhow!("failed to upload content: {}", e))
+        })?;
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: RequestPayload::SignalInvocation(SignalInvocationRequest {
+                namespace,
+                compute_graph,
+                invocation_id,
+                gate,
+                payload: data_model::DataPayload {
+                    path: put_result.url,
+                    size: put_result.size_bytes,
+                    sha256_hash: put_result.sha256_hash,
+                    tier: Default::default(),
+                    mime_type: None,
+                    content_encoding: None,
+                    metadata: Default::default(),
+                    replicas: Vec::new(),
+                    compression: None,
+                },
+            }),
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(())
+}
@@ -0,0 +1,78 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use state_store::requests::{
+    PauseComputeGraphRequest,
+    RequestPayload,
+    ResumeComputeGraphRequest,
+    StateMachineUpdateRequest,
+};
+
+use super::{check_writable, RouteState};
+use crate::http_objects::{IndexifyAPIError, PauseComputeGraph};
+
+async fn write(state: &RouteState, payload: RequestPayload) -> Result<(), IndexifyAPIError> {
+    check_writable(state)?;
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload,
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(IndexifyAPIError::internal_error)
+}
+
+/// Pause a compute graph, its tasks are not allocated until it is resumed
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/pause",
+    request_body = PauseComputeGraph,
+    tag = "operations",
+    responses(
+        (status = 200, description = "the compute graph was paused"),
+        (status = NOT_FOUND, description = "Compute Graph not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn pause_compute_graph(
+    Path((namespace, compute_graph)): Path<(String, String)>,
+    State(state): State<RouteState>,
+    Json(request): Json<PauseComputeGraph>,
+) -> Result<(), IndexifyAPIError> {
+    write(
+        &state,
+        RequestPayload::PauseComputeGraph(PauseComputeGraphRequest {
+            namespace,
+            compute_graph,
+            reject_invocations: request.reject_invocations,
+        }),
+    )
+    .await
+}
+
+/// Resume a paused compute graph
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/resume",
+    tag = "operations",
+    responses(
+        (status = 200, description = "the compute graph was resumed"),
+        (status = NOT_FOUND, description = "Compute Graph not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn resume_compute_graph(
+    Path((namespace, compute_graph)): Path<(String, String)>,
+    State(state): State<RouteState>,
+) -> Result<(), IndexifyAPIError> {
+    write(
+        &state,
+        RequestPayload::ResumeComputeGraph(ResumeComputeGraphRequest {
+            namespace,
+            compute_graph,
+        }),
+    )
+    .await
+}
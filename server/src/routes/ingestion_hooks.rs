@@ -0,0 +1,325 @@
+//! Runs the ingestion hooks of a compute graph over the payload of a new
+//! invocation before it's stored, so the first task of the invocation
+//! already reads the transformed payload. Transformed payloads are written as
+//! new blobs, the uploaded one is kept as is.
+
+use std::{
+    collections::HashMap,
+    io::{Cursor, Read},
+};
+
+use anyhow::anyhow;
+use bytes::Bytes;
+use data_model::{ingestion::IngestionTransform, ComputeGraph, DataPayload};
+use flate2::read::MultiGzDecoder;
+use futures::stream;
+use uuid::Uuid;
+
+use super::{
+    payload_compression::{compression_of, put_payload, read_payload_bytes, PayloadCompressor},
+    payload_limits::PayloadLimit,
+    RouteState,
+};
+use crate::http_objects::IndexifyAPIError;
+
+// Bounds the transformed payloads of graphs without a payload limit, e.g.
+// against archives which decompress to much more than their size
+const DEFAULT_MAX_TRANSFORMED_BYTES: u64 = 1 << 30;
+
+const MAX_ARCHIVE_PARTS: usize = 10_000;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZIP_MAGIC: [u8; 4] = *b"PK\x03\x04";
+
+/// Metadata key of the parts of a split archive, the path of their file in
+/// the archive
+pub const ARCHIVE_ENTRY_METADATA: &str = "archive_entry";
+
+#[derive(Debug, Clone, PartialEq)]
+struct Payload {
+    bytes: Bytes,
+    mime_type: Option<String>,
+    content_encoding: Option<String>,
+    metadata: HashMap<String, String>,
+}
+
+/// Runs the hooks of the graph over the payload. Returns the payload of the
+/// invocation and the parts it was split into, if a hook split it.
+pub async fn apply_ingestion_hooks(
+    state: &RouteState,
+    graph: &ComputeGraph,
+    payload: DataPayload,
+) -> Result<(DataPayload, Vec<DataPayload>), IndexifyAPIError> {
+    if graph.ingestion_hooks.is_empty() {
+        return Ok((payload, vec![]));
+    }
+    let max_bytes = PayloadLimit::of_graph(state, &graph.namespace, &graph.name)?
+        .map(|limit| limit.max_bytes())
+        .unwrap_or(DEFAULT_MAX_TRANSFORMED_BYTES);
+    let bytes = read_payload_bytes(&state.blob_storage, &payload)
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    let input = Payload {
+        bytes,
+        mime_type: payload.mime_type.clone(),
+        content_encoding: payload.content_encoding.clone(),
+        metadata: payload.metadata.clone(),
+    };
+    let hooks = graph.ingestion_hooks.clone();
+    let outputs = tokio::task::spawn_blocking(move || transform(&hooks, input, max_bytes))
+        .await
+        .map_err(|e| IndexifyAPIError::internal_error(anyhow!(e)))??;
+
+    let compressor = PayloadCompressor::of_graph(state, &graph.namespace, &graph.name)?;
+    let mut transformed = Vec::with_capacity(outputs.len());
+    for output in outputs {
+        transformed.push(put_transformed(state, output, compressor.as_ref()).await?);
+    }
+    if graph
+        .ingestion_hooks
+        .contains(&IngestionTransform::SplitArchive)
+    {
+        return Ok((payload, transformed));
+    }
+    let payload = transformed
+        .pop()
+        .ok_or(IndexifyAPIError::internal_error_str(
+            "no transformed payload",
+        ))?;
+    Ok((payload, vec![]))
+}
+
+async fn put_transformed(
+    state: &RouteState,
+    payload: Payload,
+    compressor: Option<&PayloadCompressor>,
+) -> Result<DataPayload, IndexifyAPIError> {
+    let bytes = payload.bytes;
+    // Payloads still encoded, e.g. gzip payloads left as they are, are
+    // already compressed
+    let compressor = compressor.filter(|_| payload.content_encoding.is_none());
+    let put_result = put_payload(
+        &state.blob_storage,
+        &Uuid::new_v4().to_string(),
+        Box::pin(stream::once(async move { Ok(bytes) })),
+        compressor,
+    )
+    .await
+    .map_err(IndexifyAPIError::internal_error)?;
+    let compression = compression_of(&put_result);
+    Ok(DataPayload {
+        path: put_result.url,
+        size: put_result.size_bytes,
+        sha256_hash: put_result.sha256_hash,
+        tier: Default::default(),
+        mime_type: payload.mime_type,
+        content_encoding: payload.content_encoding,
+        metadata: payload.metadata,
+        replicas: Vec::new(),
+        compression,
+    })
+}
+
+/// Runs the transforms in order, each of them over every payload produced by
+/// the previous one. The payloads produced may not exceed `max_bytes` in
+/// total.
+fn transform(
+    hooks: &[IngestionTransform],
+    payload: Payload,
+    max_bytes: u64,
+) -> Result<Vec<Payload>, IndexifyAPIError> {
+    let mut payloads = vec![payload];
+    for hook in hooks {
+        let mut budget = max_bytes;
+        let mut transformed = Vec::with_capacity(payloads.len());
+        for payload in payloads {
+            match hook {
+                IngestionTransform::Decompress => {
+                    transformed.push(decompress(payload, &mut budget)?);
+                }
+                IngestionTransform::SplitArchive => {
+                    transformed.extend(split_archive(payload, &mut budget)?);
+                }
+                IngestionTransform::StripJsonFields { fields } => {
+                    transformed.push(strip_json_fields(payload, fields)?);
+                }
+            }
+        }
+        payloads = transformed;
+    }
+    Ok(payloads)
+}
+
+/// Reads at most the remaining budget, larger contents are rejected.
+fn read_within(reader: impl Read, budget: &mut u64) -> Result<Vec<u8>, IndexifyAPIError> {
+    let mut bytes = Vec::new();
+    reader
+        .take(*budget + 1)
+        .read_to_end(&mut bytes)
+        .map_err(|e| IndexifyAPIError::bad_request(&format!("invalid payload: {}", e)))?;
+    let size = bytes.len() as u64;
+    if size > *budget {
+        return Err(IndexifyAPIError::payload_too_large(size, *budget));
+    }
+    *budget -= size;
+    Ok(bytes)
+}
+
+fn decompress(payload: Payload, budget: &mut u64) -> Result<Payload, IndexifyAPIError> {
+    let gzip = payload
+        .content_encoding
+        .as_deref()
+        .is_some_and(|encoding| encoding.eq_ignore_ascii_case("gzip")) ||
+        payload.bytes.starts_with(&GZIP_MAGIC);
+    if !gzip {
+        return Ok(payload);
+    }
+    let bytes = read_within(MultiGzDecoder::new(&payload.bytes[..]), budget)?;
+    Ok(Payload {
+        bytes: bytes.into(),
+        content_encoding: None,
+        ..payload
+    })
+}
+
+/// Splits zip archives into a payload per file, other payloads are a single
+/// part.
+fn split_archive(payload: Payload, budget: &mut u64) -> Result<Vec<Payload>, IndexifyAPIError> {
+    if !payload.bytes.starts_with(&ZIP_MAGIC) {
+        return Ok(vec![payload]);
+    }
+    let invalid = |e: zip::result::ZipError| {
+        IndexifyAPIError::bad_request(&format!("invalid zip archive: {}", e))
+    };
+    let mut archive = zip::ZipArchive::new(Cursor::new(&payload.bytes[..])).map_err(invalid)?;
+    let mut parts = vec![];
+    for index in 0..archive.len() {
+        let entry = archive.by_index(index).map_err(invalid)?;
+        if entry.is_dir() {
+            continue;
+        }
+        if parts.len() == MAX_ARCHIVE_PARTS {
+            return Err(IndexifyAPIError::bad_request(&format!(
+                "archive has more than {} files",
+                MAX_ARCHIVE_PARTS
+            )));
+        }
+        let name = entry.name().to_string();
+        let bytes = read_within(entry, budget)?;
+        let mut metadata = payload.metadata.clone();
+        metadata.insert(ARCHIVE_ENTRY_METADATA.to_string(), name);
+        parts.push(Payload {
+            bytes: bytes.into(),
+            mime_type: None,
+            content_encoding: None,
+            metadata,
+        });
+    }
+    if parts.is_empty() {
+        return Err(IndexifyAPIError::bad_request("archive has no files"));
+    }
+    Ok(parts)
+}
+
+fn strip_json_fields(payload: Payload, fields: &[String]) -> Result<Payload, IndexifyAPIError> {
+    let mut value: serde_json::Value = serde_json::from_slice(&payload.bytes).map_err(|e| {
+        IndexifyAPIError::bad_request(&format!("strip_json_fields needs a JSON payload: {}", e))
+    })?;
+    for field in fields {
+        strip_field(&mut value, &field.split('.').collect::<Vec<_>>());
+    }
+    let bytes =
+        serde_json::to_vec(&value).map_err(|e| IndexifyAPIError::internal_error(anyhow!(e)))?;
+    Ok(Payload {
+        bytes: bytes.into(),
+        ..payload
+    })
+}
+
+fn strip_field(value: &mut serde_json::Value, path: &[&str]) {
+    match value {
+        serde_json::Value::Array(items) => {
+            for item in items {
+                strip_field(item, path);
+            }
+        }
+        serde_json::Value::Object(object) => match path {
+            [field] => {
+                object.remove(*field);
+            }
+            [field, rest @ ..] => {
+                if let Some(nested) = object.get_mut(*field) {
+                    strip_field(nested, rest);
+                }
+            }
+            [] => {}
+        },
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use axum::{http::StatusCode, response::IntoResponse};
+    use flate2::{write::GzEncoder, Compression};
+    use serde_json::json;
+
+    use super::*;
+
+    fn payload(bytes: Vec<u8>) -> Payload {
+        Payload {
+            bytes: bytes.into(),
+            mime_type: None,
+            content_encoding: None,
+            metadata: HashMap::new(),
+        }
+    }
+
+    fn gzip(bytes: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(bytes).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, bytes) in files {
+            writer
+                .start_file(*name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(bytes).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_transform() {
+        let record = json!({"user": {"name": "alice", "email": "alice@example.com"}, "items": [{"ssn": "1"}]});
+        let archive = zip(&[("a.json", record.to_string().as_bytes()), ("b.json", b"{}")]);
+        let hooks = [
+            IngestionTransform::Decompress,
+            IngestionTransform::SplitArchive,
+            IngestionTransform::StripJsonFields {
+                fields: vec!["user.email".to_string(), "items.ssn".to_string()],
+            },
+        ];
+        let parts = transform(&hooks, payload(gzip(&archive)), 1 << 20).unwrap();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].metadata[ARCHIVE_ENTRY_METADATA], "a.json");
+        let stripped: serde_json::Value = serde_json::from_slice(&parts[0].bytes).unwrap();
+        assert_eq!(stripped, json!({"user": {"name": "alice"}, "items": [{}]}));
+
+        // Payloads which aren't compressed or archived pass through
+        let parts = transform(&hooks[..2], payload(b"plain".to_vec()), 1 << 20).unwrap();
+        assert_eq!(parts, vec![payload(b"plain".to_vec())]);
+
+        // Decompressed payloads are bound by the limit
+        let err = transform(&hooks[..1], payload(gzip(&[0; 4096])), 1024).unwrap_err();
+        assert_eq!(err.into_response().status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let err = transform(&hooks[2..], payload(b"not json".to_vec()), 1 << 20).unwrap_err();
+        assert_eq!(err.into_response().status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -1,24 +1,46 @@
-use std::{collections::HashMap, sync::Arc, vec};
+use std::{collections::HashMap, vec};
 
 use anyhow::{anyhow, Result};
-use axum::extract::{multipart::Field, Multipart, State};
-use blob_store::{BlobStorage, PutResult};
+use axum::{
+    extract::{multipart::Field, Multipart, State},
+    http::StatusCode,
+    Json,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use blob_store::PutResult;
 use data_model::{
     DataPayload,
     ExecutorId,
     NodeOutput,
     NodeOutputBuilder,
     OutputPayload,
+    PendingUpload,
+    ResourceUsage,
     TaskDiagnostics,
     TaskId,
 };
 use futures::StreamExt;
+use indexify_utils::get_epoch_time_in_ms;
+use nanoid::nanoid;
 use serde::{Deserialize, Serialize};
-use state_store::requests::{FinalizeTaskRequest, RequestPayload, StateMachineUpdateRequest};
+use sha2::{Digest, Sha256};
+use state_store::requests::{
+    FinalizeTaskRequest,
+    FinalizeTasksRequest,
+    InlinePayload,
+    RegisterPendingUploadsRequest,
+    RequestPayload,
+    StateMachineUpdateRequest,
+    StreamTaskOutputsRequest,
+};
 use tracing::{error, info};
 use utoipa::ToSchema;
 
-use super::RouteState;
+use super::{
+    payload_compression::{compression_of, put_payload, PayloadCompressor},
+    payload_limits::{upload_error, PayloadLimit},
+    RouteState,
+};
 use crate::http_objects::IndexifyAPIError;
 
 #[derive(Serialize, Deserialize)]
@@ -48,15 +70,36 @@ impl From<TaskOutcome> for data_model::TaskOutcome {
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct TaskResult {
-    router_output: Option<RouterOutput>,
-    outcome: TaskOutcome,
-    namespace: String,
-    compute_graph: String,
-    compute_fn: String,
-    task_id: String,
-    invocation_id: String,
-    executor_id: String,
-    reducer: bool,
+    pub router_output: Option<RouterOutput>,
+    // Not set for partial results
+    pub outcome: Option<TaskOutcome>,
+    pub namespace: String,
+    pub compute_graph: String,
+    pub compute_fn: String,
+    pub task_id: String,
+    pub invocation_id: String,
+    pub executor_id: String,
+    pub reducer: bool,
+    // Outputs emitted while the task is still running, the task is finalized
+    // by a later upload
+    #[serde(default)]
+    pub partial: bool,
+    // Resources used by the task, set by executors which measure them
+    #[serde(default)]
+    pub cpu_seconds: Option<f64>,
+    #[serde(default)]
+    pub gpu_seconds: Option<f64>,
+    // Whether the executor loaded the environment of the function to run the
+    // task instead of reusing a warm one
+    #[serde(default)]
+    pub cold_start: Option<bool>,
+    // Media type, encoding and metadata of the outputs of the upload
+    #[serde(default)]
+    pub output_mime_type: Option<String>,
+    #[serde(default)]
+    pub output_content_encoding: Option<String>,
+    #[serde(default)]
+    pub output_metadata: HashMap<String, String>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -64,6 +107,21 @@ pub struct FnOutput {
     pub payload: serde_json::Value,
 }
 
+/// Result of a task reported along with others, its outputs are sent inline
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BatchedTaskResult {
+    #[serde(flatten)]
+    pub task_result: TaskResult,
+    /// Base64 encoded outputs
+    #[serde(default)]
+    pub inline_outputs: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TaskResults {
+    pub results: Vec<BatchedTaskResult>,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct RouterOutput {
     pub edges: Vec<String>,
@@ -95,14 +153,18 @@ pub async fn ingest_files_from_executor(
     mut files: Multipart,
 ) -> Result<(), IndexifyAPIError> {
     let mut output_objects: Vec<PutResult> = vec![];
+    let mut inline_outputs: Vec<InlinePayload> = vec![];
     let mut exception_msg: Option<PutResult> = None;
     let mut stdout_msg: Option<PutResult> = None;
     let mut stderr_msg: Option<PutResult> = None;
+    let mut structured_logs: Option<PutResult> = None;
     let mut task_result: Option<TaskResult> = None;
+    let mut payload_limit: Option<PayloadLimit> = None;
+    let mut compressor: Option<PayloadCompressor> = None;
 
     // Write data object to blob store.
     let mut node_output_sequence: usize = 0;
-    let diagnostics_keys = vec!["exception_msg", "stdout", "stderr"];
+    let diagnostics_keys = vec!["exception_msg", "stdout", "stderr", "structured_logs"];
     while let Some(mut field) = files.next_field().await.unwrap() {
         if let Some(name) = field.name() {
             let name_ref = name.to_string();
@@ -110,41 +172,45 @@ pub async fn ingest_files_from_executor(
                 let task_result = task_result.as_ref().ok_or_else(|| {
                     IndexifyAPIError::bad_request("task_result is required before node_outputs")
                 })?;
-                let mut file_name = format!(
-                    "{}.{}.{}.{}",
-                    task_result.namespace,
-                    task_result.compute_graph,
-                    task_result.compute_fn,
-                    task_result.invocation_id,
-                );
-                if task_result.reducer {
-                    file_name.push_str(&format!(".{}", node_output_sequence));
-                } else {
-                    file_name.push_str(&format!(
-                        ".{}.{}",
-                        task_result.task_id, node_output_sequence
-                    ));
-                };
-                let res = write_to_disk(state.clone().blob_storage, &mut field, &file_name).await?;
+                let file_name = output_file_name(task_result, node_output_sequence);
+                register_pending_upload(&state, task_result, &file_name).await?;
+                let res = write_to_disk(
+                    &state,
+                    &mut field,
+                    &file_name,
+                    payload_limit.as_ref(),
+                    compressor.as_ref(),
+                )
+                .await?;
                 node_output_sequence += 1;
                 output_objects.push(res.clone());
+            } else if name_ref == "inline_node_outputs" {
+                let task_result = task_result.as_ref().ok_or_else(|| {
+                    IndexifyAPIError::bad_request("task_result is required before node_outputs")
+                })?;
+                let max_bytes = state.config_reloader.runtime().inline_output_max_bytes;
+                let data = read_inline_output(&mut field, max_bytes).await?;
+                if let Some(limit) = &payload_limit {
+                    limit.check(&state, data.len() as u64)?;
+                }
+                let file_name = output_file_name(task_result, node_output_sequence);
+                node_output_sequence += 1;
+                inline_outputs.push(InlinePayload {
+                    path: DataPayload::inline_path(&file_name),
+                    data,
+                });
             } else if diagnostics_keys.iter().any(|e| name_ref.contains(e)) {
                 let task_result = task_result.as_ref().ok_or_else(|| {
                     IndexifyAPIError::bad_request("task_result is required before node_outputs")
                 })?;
-                let file_name = format!(
-                    "{}.{}.{}.{}.{}",
-                    task_result.namespace,
-                    task_result.compute_graph,
-                    task_result.compute_fn,
-                    task_result.invocation_id,
-                    name,
-                );
-                let res = write_to_disk(state.clone().blob_storage, &mut field, &file_name).await?;
+                let file_name = diagnostic_file_name(task_result, name);
+                register_pending_upload(&state, task_result, &file_name).await?;
+                let res = write_to_disk(&state, &mut field, &file_name, None, None).await?;
                 match name_ref.as_str() {
                     "exception_msg" => exception_msg = Some(res),
                     "stdout" => stdout_msg = Some(res),
                     "stderr" => stderr_msg = Some(res),
+                    "structured_logs" => structured_logs = Some(res),
                     _ => {
                         error!("unknown field name {}", name_ref);
                     }
@@ -154,22 +220,185 @@ pub async fn ingest_files_from_executor(
                     .text()
                     .await
                     .map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))?;
-                task_result.replace(serde_json::from_str::<TaskResult>(&text)?);
+                let result = serde_json::from_str::<TaskResult>(&text)?;
+                payload_limit = PayloadLimit::of_graph(
+                    &state,
+                    &result.namespace,
+                    &result.compute_graph,
+                )?
+                .map(|limit| {
+                    limit.for_task(&result.invocation_id, &result.compute_fn, &result.task_id)
+                });
+                // Outputs the function encoded are already compressed
+                compressor = match result.output_content_encoding {
+                    Some(_) => None,
+                    None => PayloadCompressor::of_graph(
+                        &state,
+                        &result.namespace,
+                        &result.compute_graph,
+                    )?,
+                };
+                task_result.replace(result);
             }
         }
     }
 
-    // Save metadata in rocksdb for the objects in the blob store.
     let task_result =
         task_result.ok_or(IndexifyAPIError::bad_request("task_result is required"))?;
-    let mut node_outputs: Vec<NodeOutput> = vec![];
+    let task_diagnostic = TaskDiagnostics {
+        exception: prepare_data_payload(exception_msg),
+        stdout: prepare_data_payload(stdout_msg),
+        stderr: prepare_data_payload(stderr_msg),
+        structured_logs: prepare_data_payload(structured_logs),
+    };
+    finalize_task_result(
+        &state,
+        task_result,
+        output_objects,
+        inline_outputs,
+        task_diagnostic,
+    )
+    .await
+}
 
-    for put_result in output_objects {
-        let data_payload = data_model::DataPayload {
-            path: put_result.url,
-            size: put_result.size_bytes,
-            sha256_hash: put_result.sha256_hash,
+/// Records the results of many short tasks with a single request, the tasks
+/// are finalized together. Tasks with outputs larger than the inline output
+/// limit report their result with `ingest_files_from_executor`.
+pub async fn ingest_task_results(
+    State(state): State<RouteState>,
+    Json(request): Json<TaskResults>,
+) -> Result<(), IndexifyAPIError> {
+    let runtime = state.config_reloader.runtime();
+    if request.results.len() > runtime.max_batched_task_results {
+        return Err(IndexifyAPIError::bad_request(&format!(
+            "at most {} task results can be reported at once",
+            runtime.max_batched_task_results
+        )));
+    }
+    let mut tasks = Vec::with_capacity(request.results.len());
+    for result in request.results {
+        let task_result = result.task_result;
+        let payload_limit =
+            PayloadLimit::of_graph(&state, &task_result.namespace, &task_result.compute_graph)?
+                .map(|limit| {
+                    limit.for_task(
+                        &task_result.invocation_id,
+                        &task_result.compute_fn,
+                        &task_result.task_id,
+                    )
+                });
+        let mut inline_outputs = Vec::with_capacity(result.inline_outputs.len());
+        for (sequence, output) in result.inline_outputs.iter().enumerate() {
+            let data = STANDARD
+                .decode(output)
+                .map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))?;
+            if data.len() > runtime.inline_output_max_bytes {
+                return Err(IndexifyAPIError::new(
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    &format!(
+                        "inline outputs are limited to {} bytes",
+                        runtime.inline_output_max_bytes
+                    ),
+                ));
+            }
+            if let Some(limit) = &payload_limit {
+                limit.check(&state, data.len() as u64)?;
+            }
+            inline_outputs.push(InlinePayload {
+                path: DataPayload::inline_path(&output_file_name(&task_result, sequence)),
+                data,
+            });
+        }
+        let RequestPayload::FinalizeTask(finalize_task) = task_result_request(
+            task_result,
+            vec![],
+            inline_outputs,
+            TaskDiagnostics {
+                exception: None,
+                stdout: None,
+                stderr: None,
+                structured_logs: None,
+            },
+        )?
+        else {
+            return Err(IndexifyAPIError::bad_request(
+                "partial results can't be reported in a batch",
+            ));
         };
+        tasks.push(finalize_task);
+    }
+    if tasks.is_empty() {
+        return Ok(());
+    }
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: RequestPayload::FinalizeTasks(FinalizeTasksRequest { tasks }),
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(IndexifyAPIError::internal_error)
+}
+
+/// Records the outputs uploaded by an executor for a task. Partial results
+/// are streamed to downstream functions, otherwise the task is finalized.
+/// Inline outputs are stored in the state store along with the outcome.
+pub(crate) async fn finalize_task_result(
+    state: &RouteState,
+    task_result: TaskResult,
+    output_objects: Vec<PutResult>,
+    inline_outputs: Vec<InlinePayload>,
+    task_diagnostic: TaskDiagnostics,
+) -> Result<(), IndexifyAPIError> {
+    let request =
+        task_result_request(task_result, output_objects, inline_outputs, task_diagnostic)?;
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: request,
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(|e| {
+            IndexifyAPIError::internal_error(anyhow!("failed to upload content: {}", e))
+        })?;
+    Ok(())
+}
+
+/// Request recording the result of a task, `StreamTaskOutputs` for partial
+/// results and `FinalizeTask` otherwise.
+fn task_result_request(
+    task_result: TaskResult,
+    output_objects: Vec<PutResult>,
+    inline_outputs: Vec<InlinePayload>,
+    task_diagnostic: TaskDiagnostics,
+) -> Result<RequestPayload, IndexifyAPIError> {
+    // Save metadata in rocksdb for the objects in the blob store.
+    let mut node_outputs: Vec<NodeOutput> = vec![];
+
+    let blob_payloads = output_objects.into_iter().map(|put_result| DataPayload {
+        compression: compression_of(&put_result),
+        path: put_result.url,
+        size: put_result.size_bytes,
+        sha256_hash: put_result.sha256_hash,
+        tier: Default::default(),
+        mime_type: task_result.output_mime_type.clone(),
+        content_encoding: task_result.output_content_encoding.clone(),
+        metadata: task_result.output_metadata.clone(),
+        replicas: Vec::new(),
+    });
+    let inline_payloads = inline_outputs.iter().map(|output| DataPayload {
+        path: output.path.clone(),
+        size: output.data.len() as u64,
+        sha256_hash: format!("{:x}", Sha256::digest(&output.data)),
+        tier: Default::default(),
+        mime_type: task_result.output_mime_type.clone(),
+        content_encoding: task_result.output_content_encoding.clone(),
+        metadata: task_result.output_metadata.clone(),
+        replicas: Vec::new(),
+        compression: None,
+    });
+    for data_payload in blob_payloads.chain(inline_payloads) {
         let node_output = NodeOutputBuilder::default()
             .namespace(task_result.namespace.to_string())
             .graph_version(Default::default())
@@ -184,15 +413,32 @@ pub async fn ingest_files_from_executor(
         node_outputs.push(node_output);
     }
 
-    let exception_payload = prepare_data_payload(exception_msg);
-    let stdout_payload = prepare_data_payload(stdout_msg);
-    let stderr_payload = prepare_data_payload(stderr_msg);
-
-    let task_diagnostic = TaskDiagnostics {
-        exception: exception_payload,
-        stdout: stdout_payload,
-        stderr: stderr_payload,
-    };
+    if task_result.partial {
+        if task_result.reducer || task_result.router_output.is_some() {
+            return Err(IndexifyAPIError::bad_request(
+                "partial results are only supported for compute functions which are not reducers",
+            ));
+        }
+        if !inline_outputs.is_empty() {
+            return Err(IndexifyAPIError::bad_request(
+                "partial results can't have inline outputs",
+            ));
+        }
+        return Ok(RequestPayload::StreamTaskOutputs(
+            StreamTaskOutputsRequest {
+                namespace: task_result.namespace.to_string(),
+                compute_graph: task_result.compute_graph.to_string(),
+                compute_fn: task_result.compute_fn.to_string(),
+                invocation_id: task_result.invocation_id.to_string(),
+                task_id: TaskId::new(task_result.task_id.to_string()),
+                node_outputs,
+            },
+        ));
+    }
+    let outcome = task_result
+        .outcome
+        .clone()
+        .ok_or(IndexifyAPIError::bad_request("task outcome is required"))?;
 
     if let Some(router_output) = task_result.router_output {
         let node_output = NodeOutputBuilder::default()
@@ -211,35 +457,133 @@ pub async fn ingest_files_from_executor(
         node_outputs.push(node_output);
     }
 
-    let request = RequestPayload::FinalizeTask(FinalizeTaskRequest {
+    Ok(RequestPayload::FinalizeTask(FinalizeTaskRequest {
         namespace: task_result.namespace.to_string(),
         compute_graph: task_result.compute_graph.to_string(),
         compute_fn: task_result.compute_fn.to_string(),
         invocation_id: task_result.invocation_id.to_string(),
         task_id: TaskId::new(task_result.task_id.to_string()),
         node_outputs,
-        task_outcome: task_result.outcome.clone().into(),
+        task_outcome: outcome.into(),
         executor_id: ExecutorId::new(task_result.executor_id.clone()),
         diagnostics: Some(task_diagnostic),
-    });
+        inline_payloads: inline_outputs,
+        resource_usage: task_result.resource_usage(),
+    }))
+}
+
+impl TaskResult {
+    fn resource_usage(&self) -> Option<ResourceUsage> {
+        if self.cpu_seconds.is_none() && self.gpu_seconds.is_none() && self.cold_start.is_none() {
+            return None;
+        }
+        Some(ResourceUsage {
+            cpu_seconds: self.cpu_seconds.unwrap_or_default(),
+            gpu_seconds: self.gpu_seconds.unwrap_or_default(),
+            cold_start: self.cold_start,
+        })
+    }
+}
 
+pub(crate) fn output_file_name(task_result: &TaskResult, sequence: usize) -> String {
+    let mut file_name = format!(
+        "{}.{}.{}.{}",
+        task_result.namespace,
+        task_result.compute_graph,
+        task_result.compute_fn,
+        task_result.invocation_id,
+    );
+    if task_result.reducer {
+        file_name.push_str(&format!(".{}", sequence));
+    } else if task_result.partial {
+        // Sequences restart with every partial upload of the task
+        file_name.push_str(&format!(
+            ".{}.{}.{}",
+            task_result.task_id,
+            nanoid!(),
+            sequence
+        ));
+    } else {
+        file_name.push_str(&format!(".{}.{}", task_result.task_id, sequence));
+    };
+    file_name
+}
+
+pub(crate) fn diagnostic_file_name(task_result: &TaskResult, name: &str) -> String {
+    format!(
+        "{}.{}.{}.{}.{}.{}",
+        task_result.namespace,
+        task_result.compute_graph,
+        task_result.compute_fn,
+        task_result.invocation_id,
+        task_result.task_id,
+        name,
+    )
+}
+
+/// Registers a blob before it's uploaded so the garbage collector deletes it
+/// if the outcome referencing it is never recorded. Reducer outputs are
+/// overwritten by every reducer task and are not registered.
+pub(crate) async fn register_pending_upload(
+    state: &RouteState,
+    task_result: &TaskResult,
+    file_name: &str,
+) -> Result<(), IndexifyAPIError> {
+    if task_result.reducer {
+        return Ok(());
+    }
+    let upload = PendingUpload {
+        url: state.blob_storage.key_url(file_name),
+        namespace: task_result.namespace.clone(),
+        compute_graph: task_result.compute_graph.clone(),
+        compute_fn: task_result.compute_fn.clone(),
+        invocation_id: task_result.invocation_id.clone(),
+        task_id: TaskId::from(task_result.task_id.as_str()),
+        created_at: get_epoch_time_in_ms(),
+    };
     state
         .indexify_state
         .write(StateMachineUpdateRequest {
-            payload: request,
+            payload: RequestPayload::RegisterPendingUploads(RegisterPendingUploadsRequest {
+                uploads: vec![upload],
+            }),
             state_changes_processed: vec![],
         })
         .await
-        .map_err(|e| {
-            IndexifyAPIError::internal_error(anyhow!("failed to upload content: {}", e))
-        })?;
-    Ok(())
+        .map_err(IndexifyAPIError::internal_error)
+}
+
+/// Reads an output sent inline, rejecting outputs larger than `max_bytes`
+async fn read_inline_output(
+    field: &mut Field<'_>,
+    max_bytes: usize,
+) -> Result<Vec<u8>, IndexifyAPIError> {
+    let mut data = Vec::new();
+    while let Some(chunk) = field
+        .chunk()
+        .await
+        .map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))?
+    {
+        if data.len() + chunk.len() > max_bytes {
+            return Err(IndexifyAPIError::new(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                &format!(
+                    "inline outputs are limited to {} bytes, larger outputs are uploaded as node_outputs",
+                    max_bytes
+                ),
+            ));
+        }
+        data.extend_from_slice(&chunk);
+    }
+    Ok(data)
 }
 
 async fn write_to_disk<'a>(
-    blob_storage: Arc<BlobStorage>,
+    state: &RouteState,
     field: &'a mut Field<'a>,
     file_name: &str,
+    payload_limit: Option<&PayloadLimit>,
+    compressor: Option<&PayloadCompressor>,
 ) -> Result<PutResult, IndexifyAPIError> {
     let _ = field
         .file_name()
@@ -248,16 +592,31 @@ async fn write_to_disk<'a>(
         .to_string();
     info!("writing to blob store, file name = {:?}", file_name);
     let stream = field.map(|res| res.map_err(|err| anyhow::anyhow!(err)));
-    blob_storage.put(&file_name, stream).await.map_err(|e| {
-        error!("failed to write to blob store: {}", e);
-        IndexifyAPIError::internal_error(anyhow!("failed to write to blob store: {}", e))
-    })
+    match payload_limit {
+        Some(limit) => {
+            put_payload(
+                &state.blob_storage,
+                file_name,
+                limit.limit_stream(stream),
+                compressor,
+            )
+            .await
+        }
+        None => put_payload(&state.blob_storage, file_name, stream, compressor).await,
+    }
+    .map_err(|e| upload_error(state, payload_limit, e))
 }
 
-fn prepare_data_payload(msg: Option<PutResult>) -> Option<DataPayload> {
+pub(crate) fn prepare_data_payload(msg: Option<PutResult>) -> Option<DataPayload> {
     msg.map(|msg| DataPayload {
         path: msg.url,
         size: msg.size_bytes,
         sha256_hash: msg.sha256_hash,
+        tier: Default::default(),
+        mime_type: None,
+        content_encoding: None,
+        metadata: Default::default(),
+        replicas: Vec::new(),
+        compression: None,
     })
 }
@@ -0,0 +1,139 @@
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::HeaderMap,
+    Json,
+};
+use data_model::{invocation_diff, GraphVersion, InvocationResult};
+
+use super::{
+    invocation_groups::check_invocation_group,
+    invoke::{check_backpressure, invoke_with_payload, put_invocation_payload},
+    RouteState,
+};
+use crate::http_objects::{
+    DiffRun,
+    IndexifyAPIError,
+    InvocationQueryParams,
+    InvocationsDiff,
+    InvocationsDiffParams,
+};
+
+/// Compare the terminal outputs of two finished invocations of a compute graph
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/invocations/diff",
+    tag = "operations",
+    params(
+        ("base" = String, Query, description = "Invocation the head invocation is compared against"),
+        ("head" = String, Query, description = "Invocation compared to the base invocation"),
+    ),
+    responses(
+        (status = 200, description = "Differences between the outputs of the invocations", body = InvocationsDiff),
+        (status = 404, description = "An invocation is not found or not finished"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn diff_invocations(
+    Path((namespace, compute_graph)): Path<(String, String)>,
+    Query(params): Query<InvocationsDiffParams>,
+    State(state): State<RouteState>,
+) -> Result<Json<InvocationsDiff>, IndexifyAPIError> {
+    let (base, base_version) = finished(&state, &namespace, &compute_graph, &params.base)?;
+    let (head, head_version) = finished(&state, &namespace, &compute_graph, &params.head)?;
+    let diff = invocation_diff::InvocationsDiff::new(&base, base_version, &head, head_version);
+    Ok(Json(diff.into()))
+}
+
+// Result of a finished invocation and the version of the graph it ran on
+fn finished(
+    state: &RouteState,
+    namespace: &str,
+    compute_graph: &str,
+    invocation_id: &str,
+) -> Result<(InvocationResult, GraphVersion), IndexifyAPIError> {
+    let reader = state.indexify_state.reader();
+    let result = reader
+        .invocation_result(namespace, compute_graph, invocation_id)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or_else(|| {
+            IndexifyAPIError::not_found(&format!(
+                "invocation {} not found or not finished",
+                invocation_id
+            ))
+        })?;
+    let ctx = reader
+        .invocation_ctx(namespace, compute_graph, invocation_id)
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok((result, ctx.graph_version))
+}
+
+/// Invoke the current and the canary version of a compute graph with the same
+/// payload, to diff their outputs once both invocations finished
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/diff_runs",
+    request_body(content_type = "application/cbor", content = inline(serde_json::Value)),
+    tag = "ingestion",
+    responses(
+        (status = 200, description = "Invocations of both versions of the graph", body = DiffRun),
+        (status = 400, description = "The compute graph has no canary"),
+        (status = 429, description = "too much pending work, retry after the Retry-After header"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn create_diff_run(
+    Path((namespace, compute_graph)): Path<(String, String)>,
+    Query(params): Query<InvocationQueryParams>,
+    State(state): State<RouteState>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<Json<DiffRun>, IndexifyAPIError> {
+    check_backpressure(&state, &namespace, &compute_graph)?;
+    check_invocation_group(&state, &namespace, params.invocation_group.as_deref())?;
+    let reader = state.indexify_state.reader();
+    let base_version = reader
+        .get_compute_graph(&namespace, &compute_graph)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::not_found("Compute Graph not found"))?
+        .version;
+    let head_version = reader
+        .get_graph_canary(&namespace, &compute_graph)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::bad_request(
+            "the compute graph has no canary version to diff against",
+        ))?
+        .compute_graph
+        .version;
+    let labels = params.labels()?;
+    let attributes = params.attributes()?;
+    let parameters = params.parameters()?;
+    let data_payload =
+        put_invocation_payload(&state, &namespace, &compute_graph, &headers, body).await?;
+    // Pinned invocations get distinct ids from the same payload
+    let mut ids = vec![];
+    for version in [base_version, head_version] {
+        let id = invoke_with_payload(
+            &state,
+            &namespace,
+            &compute_graph,
+            data_payload.clone(),
+            labels.clone(),
+            attributes.clone(),
+            params.priority.unwrap_or_default(),
+            params.lane.map(Into::into),
+            params.invocation_group.clone(),
+            parameters.clone(),
+            params.event_time,
+            Some(version),
+        )
+        .await?;
+        ids.push(id);
+    }
+    Ok(Json(DiffRun {
+        base: ids[0].clone(),
+        base_version: base_version.into(),
+        head: ids[1].clone(),
+        head_version: head_version.into(),
+    }))
+}
@@ -0,0 +1,303 @@
+use std::time::Duration;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{sse::Event, IntoResponse},
+    Json,
+};
+use indexify_utils::get_epoch_time_in_ms;
+use nanoid::nanoid;
+use state_store::{
+    invocation_events::{
+        InvocationFinishedEvent,
+        InvocationGroupFinishedEvent,
+        InvocationStateChangeEvent,
+    },
+    requests::{
+        CloseInvocationGroupRequest,
+        CreateInvocationGroupRequest,
+        RequestPayload,
+        StateMachineUpdateRequest,
+    },
+};
+use tokio::sync::broadcast::error::RecvError;
+
+use super::{check_writable, RouteState};
+use crate::http_objects::{
+    CreateInvocationGroup,
+    IndexifyAPIError,
+    InvocationGroup,
+    InvocationGroupMember,
+    InvocationGroupMembers,
+    InvocationGroupsList,
+    InvocationResult,
+    ListParams,
+};
+
+fn get_group(
+    state: &RouteState,
+    namespace: &str,
+    id: &str,
+) -> Result<data_model::invocation_group::InvocationGroup, IndexifyAPIError> {
+    state
+        .indexify_state
+        .reader()
+        .get_invocation_group(namespace, id)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::not_found("Invocation group not found"))
+}
+
+/// Rejects invocations joining a group which doesn't exist or is closed,
+/// before their payload is uploaded.
+pub(crate) fn check_invocation_group(
+    state: &RouteState,
+    namespace: &str,
+    group: Option<&str>,
+) -> Result<(), IndexifyAPIError> {
+    let Some(group) = group else {
+        return Ok(());
+    };
+    if get_group(state, namespace, group)?.closed {
+        return Err(IndexifyAPIError::new(
+            StatusCode::CONFLICT,
+            &format!("invocation group {} is closed", group),
+        ));
+    }
+    Ok(())
+}
+
+/// Create an invocation group, invocations join it with the
+/// invocation_group parameter
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/invocation_groups",
+    request_body = CreateInvocationGroup,
+    tag = "operations",
+    responses(
+        (status = 200, description = "Invocation group created", body = InvocationGroup),
+        (status = BAD_REQUEST, description = "Invalid id"),
+        (status = CONFLICT, description = "Invocation group already exists"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn create_invocation_group(
+    Path(namespace): Path<String>,
+    State(state): State<RouteState>,
+    Json(request): Json<CreateInvocationGroup>,
+) -> Result<Json<InvocationGroup>, IndexifyAPIError> {
+    check_writable(&state)?;
+    let id = request.id.unwrap_or_else(|| nanoid!());
+    if id.is_empty() || id.contains('|') {
+        return Err(IndexifyAPIError::bad_request(
+            "invocation group id must be non empty and can't contain |",
+        ));
+    }
+    let group = data_model::invocation_group::InvocationGroup::new(
+        &namespace,
+        &id,
+        request.labels,
+        get_epoch_time_in_ms(),
+    );
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: RequestPayload::CreateInvocationGroup(CreateInvocationGroupRequest {
+                group: group.clone(),
+            }),
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(group.into()))
+}
+
+/// List the invocation groups of the namespace
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/invocation_groups",
+    tag = "operations",
+    responses(
+        (status = 200, description = "List invocation groups", body = InvocationGroupsList),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn list_invocation_groups(
+    Path(namespace): Path<String>,
+    Query(params): Query<ListParams>,
+    State(state): State<RouteState>,
+) -> Result<Json<InvocationGroupsList>, IndexifyAPIError> {
+    let (groups, cursor) = state
+        .indexify_state
+        .reader()
+        .list_invocation_groups(&namespace, params.cursor.as_deref(), params.limit)
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(InvocationGroupsList {
+        invocation_groups: groups.into_iter().map(Into::into).collect(),
+        cursor,
+    }))
+}
+
+/// Get an invocation group, with the statuses of its invocations rolled up
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/invocation_groups/{id}",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Invocation group", body = InvocationGroup),
+        (status = 404, description = "Invocation group not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn get_invocation_group(
+    Path((namespace, id)): Path<(String, String)>,
+    State(state): State<RouteState>,
+) -> Result<Json<InvocationGroup>, IndexifyAPIError> {
+    Ok(Json(get_group(&state, &namespace, &id)?.into()))
+}
+
+/// Close an invocation group to new invocations, it finishes once all its
+/// invocations finished
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/invocation_groups/{id}/close",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Invocation group closed", body = InvocationGroup),
+        (status = 404, description = "Invocation group not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn close_invocation_group(
+    Path((namespace, id)): Path<(String, String)>,
+    State(state): State<RouteState>,
+) -> Result<Json<InvocationGroup>, IndexifyAPIError> {
+    check_writable(&state)?;
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: RequestPayload::CloseInvocationGroup(CloseInvocationGroupRequest {
+                namespace: namespace.clone(),
+                id: id.clone(),
+            }),
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(get_group(&state, &namespace, &id)?.into()))
+}
+
+/// List the invocations of an invocation group
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/invocation_groups/{id}/invocations",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Invocations of the group", body = InvocationGroupMembers),
+        (status = 404, description = "Invocation group not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn list_invocation_group_members(
+    Path((namespace, id)): Path<(String, String)>,
+    Query(params): Query<ListParams>,
+    State(state): State<RouteState>,
+) -> Result<Json<InvocationGroupMembers>, IndexifyAPIError> {
+    get_group(&state, &namespace, &id)?;
+    let reader = state.indexify_state.reader();
+    let (members, cursor) = reader
+        .invocation_group_members(&namespace, &id, params.cursor.as_deref(), params.limit)
+        .map_err(IndexifyAPIError::internal_error)?;
+    let mut invocations = Vec::with_capacity(members.len());
+    for ctx in members {
+        let status = if ctx.completed {
+            reader
+                .invocation_result(&namespace, &ctx.compute_graph_name, &ctx.invocation_id)
+                .map_err(IndexifyAPIError::internal_error)?
+                .map(|result| result.status.into())
+        } else {
+            None
+        };
+        invocations.push(InvocationGroupMember {
+            compute_graph: ctx.compute_graph_name,
+            invocation_id: ctx.invocation_id,
+            status,
+        });
+    }
+    Ok(Json(InvocationGroupMembers {
+        invocations,
+        cursor,
+    }))
+}
+
+/// Stream the results of the invocations of a group as they finish, the
+/// stream ends with the group once it finishes
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/invocation_groups/{id}/events",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Server sent events of the group"),
+        (status = 404, description = "Invocation group not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn invocation_group_events(
+    Path((namespace, id)): Path<(String, String)>,
+    State(state): State<RouteState>,
+) -> Result<impl IntoResponse, IndexifyAPIError> {
+    // Subscribed before the group is read so its finish can't be missed
+    let mut rx = state.indexify_state.task_event_stream();
+    let group = get_group(&state, &namespace, &id)?;
+    let event_stream = async_stream::stream! {
+        if group.finished_at.is_some() {
+            yield Event::default().json_data(serde_json::json!({
+                "InvocationGroupFinished": InvocationGroup::from(group),
+            }));
+            return;
+        }
+        loop {
+            match rx.recv().await {
+                Ok(InvocationStateChangeEvent::InvocationFinished(InvocationFinishedEvent {
+                    id: invocation_id,
+                    result,
+                })) => {
+                    let is_member = result.namespace == namespace &&
+                        state
+                            .indexify_state
+                            .reader()
+                            .is_invocation_group_member(
+                                &namespace,
+                                &id,
+                                &result.compute_graph_name,
+                                &invocation_id,
+                            )
+                            .unwrap_or(false);
+                    if is_member {
+                        yield Event::default().json_data(serde_json::json!({
+                            "InvocationFinished": {
+                                "id": invocation_id,
+                                "result": InvocationResult::from(result),
+                            }
+                        }));
+                    }
+                }
+                Ok(InvocationStateChangeEvent::InvocationGroupFinished(
+                    InvocationGroupFinishedEvent { group, .. },
+                )) if group.namespace == namespace && group.id == id => {
+                    yield Event::default().json_data(serde_json::json!({
+                        "InvocationGroupFinished": InvocationGroup::from(group),
+                    }));
+                    return;
+                }
+                Ok(_) | Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => return,
+            }
+        }
+    };
+    Ok(axum::response::Sse::new(event_stream).keep_alive(
+        axum::response::sse::KeepAlive::new()
+            .interval(Duration::from_secs(1))
+            .text("keep-alive-text"),
+    ))
+}
@@ -0,0 +1,75 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use state_store::requests::{RequestPayload, SetInvocationStateRequest, StateMachineUpdateRequest};
+
+use super::RouteState;
+use crate::http_objects::{IndexifyAPIError, InvocationStateEntry, SetInvocationState};
+
+// The scratch space is meant for small bits of state, larger values belong
+// in function outputs
+const MAX_KEY_LEN: usize = 256;
+const MAX_VALUE_BYTES: usize = 64 * 1024;
+
+pub async fn get_invocation_state(
+    Path((namespace, compute_graph, invocation_id, key)): Path<(String, String, String, String)>,
+    State(state): State<RouteState>,
+) -> Result<Json<InvocationStateEntry>, IndexifyAPIError> {
+    let entry = state
+        .indexify_state
+        .reader()
+        .invocation_state(&namespace, &compute_graph, &invocation_id, &key)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::not_found(&format!(
+            "invocation state {} not found",
+            key
+        )))?;
+    Ok(Json(entry.into()))
+}
+
+pub async fn set_invocation_state(
+    Path((namespace, compute_graph, invocation_id, key)): Path<(String, String, String, String)>,
+    State(state): State<RouteState>,
+    Json(request): Json<SetInvocationState>,
+) -> Result<Json<InvocationStateEntry>, IndexifyAPIError> {
+    if key.is_empty() || key.len() > MAX_KEY_LEN {
+        return Err(IndexifyAPIError::bad_request(&format!(
+            "invocation state keys must have 1 to {} bytes",
+            MAX_KEY_LEN
+        )));
+    }
+    if serde_json::to_vec(&request.value)?.len() > MAX_VALUE_BYTES {
+        return Err(IndexifyAPIError::bad_request(&format!(
+            "invocation state values are limited to {} bytes",
+            MAX_VALUE_BYTES
+        )));
+    }
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: RequestPayload::SetInvocationState(SetInvocationStateRequest {
+                namespace: namespace.clone(),
+                compute_graph: compute_graph.clone(),
+                invocation_id: invocation_id.clone(),
+                key: key.clone(),
+                value: request.value,
+                expected_version: request.expected_version,
+            }),
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    // Read back for the new version, a concurrent write may have updated it
+    // since
+    let entry = state
+        .indexify_state
+        .reader()
+        .invocation_state(&namespace, &compute_graph, &invocation_id, &key)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::not_found(&format!(
+            "invocation state {} not found",
+            key
+        )))?;
+    Ok(Json(entry.into()))
+}
@@ -1,14 +1,15 @@
-use std::time::Duration;
+use std::{collections::HashMap, time::Duration};
 
 use anyhow::anyhow;
 use axum::{
     body::Body,
     extract::{Multipart, Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
     response::{sse::Event, IntoResponse},
     Json,
 };
 use blob_store::PutResult;
-use data_model::InvocationPayloadBuilder;
+use data_model::{DataPayload, GraphVersion, InvocationPayloadBuilder, SchedulingLane};
 use futures::{stream, StreamExt};
 use state_store::{
     invocation_events::{InvocationFinishedEvent, InvocationStateChangeEvent},
@@ -23,8 +24,88 @@ use tokio::sync::broadcast::Receiver;
 use tracing::{error, info};
 use uuid::Uuid;
 
-use super::RouteState;
-use crate::http_objects::{GraphInputFile, IndexifyAPIError, InvocationId, InvocationQueryParams};
+use super::{
+    check_writable,
+    ingestion_hooks::apply_ingestion_hooks,
+    invocation_groups::check_invocation_group,
+    payload_compression::{compression_of, put_payload, PayloadCompressor},
+    payload_limits::{upload_error, PayloadLimit},
+    RouteState,
+};
+use crate::http_objects::{
+    GraphInputFile,
+    IndexifyAPIError,
+    InvocationId,
+    InvocationQueryParams,
+    InvocationResult,
+};
+
+const BACKPRESSURE_RETRY_AFTER_SECS: u64 = 5;
+
+/// Prefix of the headers setting the metadata of a payload, e.g.
+/// `x-indexify-meta-source: crawler`
+pub(crate) const PAYLOAD_METADATA_HEADER_PREFIX: &str = "x-indexify-meta-";
+
+// Rejects new invocations while the graph has more pending work than its
+// queue limits allow. Limits of the graph take precedence over the ones of
+// the namespace. Paused graphs can be set to reject new invocations.
+pub(crate) fn check_backpressure(
+    state: &RouteState,
+    namespace: &str,
+    compute_graph: &str,
+) -> Result<(), IndexifyAPIError> {
+    check_writable(state)?;
+    let reader = state.indexify_state.reader();
+    let graph = reader
+        .get_compute_graph(namespace, compute_graph)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::not_found("Compute Graph not found"))?;
+    if graph
+        .paused
+        .as_ref()
+        .is_some_and(|pause| pause.reject_invocations)
+    {
+        return Err(IndexifyAPIError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            &format!("compute graph {} is paused", compute_graph),
+        ));
+    }
+    let namespace_limits = reader
+        .get_namespace(namespace)
+        .map_err(IndexifyAPIError::internal_error)?
+        .map(|ns| ns.queue_limits)
+        .unwrap_or_default();
+    let limits = graph.queue_limits.or(&namespace_limits);
+    if let Some(max_pending_invocations) = limits.max_pending_invocations {
+        let pending_invocations = reader
+            .get_pending_invocations(namespace, compute_graph)
+            .map_err(IndexifyAPIError::internal_error)?;
+        if pending_invocations >= max_pending_invocations {
+            return Err(IndexifyAPIError::too_many_requests(
+                &format!(
+                    "compute graph {} has {} pending invocations, limit is {}",
+                    compute_graph, pending_invocations, max_pending_invocations
+                ),
+                BACKPRESSURE_RETRY_AFTER_SECS,
+            ));
+        }
+    }
+    if let Some(max_unallocated_tasks) = limits.max_unallocated_tasks {
+        let unallocated_tasks = reader
+            .count_unallocated_tasks(namespace, compute_graph, max_unallocated_tasks)
+            .map_err(IndexifyAPIError::internal_error)?;
+        if unallocated_tasks >= max_unallocated_tasks {
+            return Err(IndexifyAPIError::too_many_requests(
+                &format!(
+                    "compute graph {} has at least {} unallocated tasks",
+                    compute_graph, max_unallocated_tasks
+                ),
+                BACKPRESSURE_RETRY_AFTER_SECS,
+            ));
+        }
+    }
+    Ok(())
+}
 
 // #[allow(dead_code)]
 // #[derive(ToSchema)]
@@ -53,9 +134,15 @@ use crate::http_objects::{GraphInputFile, IndexifyAPIError, InvocationId, Invoca
 pub async fn invoke_with_file(
     Path((namespace, compute_graph)): Path<(String, String)>,
     State(state): State<RouteState>,
-    Query(_params): Query<InvocationQueryParams>,
+    Query(params): Query<InvocationQueryParams>,
     mut files: Multipart,
 ) -> Result<Json<InvocationId>, IndexifyAPIError> {
+    check_backpressure(&state, &namespace, &compute_graph)?;
+    check_invocation_group(&state, &namespace, params.invocation_group.as_deref())?;
+    let payload_limit = PayloadLimit::of_graph(&state, &namespace, &compute_graph)?;
+    let labels = params.labels()?;
+    let attributes = params.attributes()?;
+    let parameters = params.parameters()?;
     let mut metadata: Option<serde_json::Value> = None;
     let mut put_result: Option<PutResult> = None;
 
@@ -65,13 +152,16 @@ pub async fn invoke_with_file(
                 let name = Uuid::new_v4().to_string();
                 info!("writing to blob store, file name = {:?}", name);
                 let stream = field.map(|res| res.map_err(|err| anyhow::anyhow!(err)));
-                let res = state.blob_storage.put(&name, stream).await.map_err(|e| {
-                    error!("failed to write to blob store: {}", e);
-                    IndexifyAPIError::internal_error(anyhow!(
-                        "failed to write to blob store: {}",
-                        e
-                    ))
-                })?;
+                let res = match &payload_limit {
+                    Some(limit) => {
+                        state
+                            .blob_storage
+                            .put(&name, limit.limit_stream(stream))
+                            .await
+                    }
+                    None => state.blob_storage.put(&name, stream).await,
+                }
+                .map_err(|e| upload_error(&state, payload_limit.as_ref(), e))?;
                 put_result = Some(res);
             } else if name == "metadata" {
                 let text = field
@@ -106,15 +196,88 @@ pub async fn invoke_with_file(
             error!("failed to write to blob store: {}", e);
             IndexifyAPIError::internal_error(anyhow!("failed to upload content: {}", e))
         })?;
-    let data_payload = data_model::DataPayload {
+    let data_payload = DataPayload {
         path: put_result.url,
         size: put_result.size_bytes,
         sha256_hash: put_result.sha256_hash,
+        tier: Default::default(),
+        mime_type: Some("application/json".to_string()),
+        content_encoding: None,
+        metadata: Default::default(),
+        replicas: Vec::new(),
+        compression: None,
     };
+    let id = invoke_with_payload(
+        &state,
+        &namespace,
+        &compute_graph,
+        data_payload,
+        labels,
+        attributes,
+        params.priority.unwrap_or_default(),
+        params.lane.map(Into::into),
+        params.invocation_group.clone(),
+        parameters,
+        params.event_time,
+        None,
+    )
+    .await?;
+    Ok(Json(InvocationId { id }))
+}
+
+fn content_length(headers: &HeaderMap) -> Option<u64> {
+    headers
+        .get(header::CONTENT_LENGTH)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
+}
+
+/// Creates an invocation of the compute graph with the uploaded payload, after
+/// running it through the ingestion hooks of the graph, and returns its id.
+/// The invocation runs on `graph_version` when set, the current or the canary
+/// version of the graph.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn invoke_with_payload(
+    state: &RouteState,
+    namespace: &str,
+    compute_graph: &str,
+    data_payload: DataPayload,
+    labels: HashMap<String, serde_json::Value>,
+    attributes: HashMap<String, serde_json::Value>,
+    priority: i32,
+    lane: Option<SchedulingLane>,
+    group: Option<String>,
+    parameters: HashMap<String, serde_json::Value>,
+    event_time: Option<u64>,
+    graph_version: Option<GraphVersion>,
+) -> Result<String, IndexifyAPIError> {
+    let graph = state
+        .indexify_state
+        .reader()
+        .get_compute_graph(namespace, compute_graph)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::not_found("Compute Graph not found"))?;
+    // Parameters are resolved again when the invocation is stored, checking
+    // them first rejects bad values with a client error
+    graph
+        .resolve_parameters(&parameters)
+        .map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))?;
+    let (data_payload, parts) = apply_ingestion_hooks(state, &graph, data_payload).await?;
     let invocation_payload = InvocationPayloadBuilder::default()
-        .namespace(namespace.clone())
-        .compute_graph_name(compute_graph.clone())
+        .namespace(namespace.to_string())
+        .compute_graph_name(compute_graph.to_string())
         .payload(data_payload)
+        .parts(parts)
+        .labels(labels)
+        .attributes(attributes)
+        .priority(priority)
+        .lane(lane)
+        .group(group)
+        .parameters(parameters)
+        .event_time(event_time)
+        .graph_version(graph_version)
         .build()
         .map_err(|e| {
             IndexifyAPIError::internal_error(anyhow!("failed to upload content: {}", e))
@@ -122,8 +285,8 @@ pub async fn invoke_with_file(
 
     let id = invocation_payload.id.clone();
     let request = RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
-        namespace: namespace.clone(),
-        compute_graph_name: compute_graph.clone(),
+        namespace: namespace.to_string(),
+        compute_graph_name: compute_graph.to_string(),
         invocation_payload,
     });
     state
@@ -136,7 +299,82 @@ pub async fn invoke_with_file(
         .map_err(|e| {
             IndexifyAPIError::internal_error(anyhow!("failed to upload content: {}", e))
         })?;
-    Ok(Json(InvocationId { id }))
+    Ok(id)
+}
+
+/// Writes the body of an invocation request to the blob store, within the
+/// payload limit of the graph. The payload is described by the headers of the
+/// request.
+pub(crate) async fn put_invocation_payload(
+    state: &RouteState,
+    namespace: &str,
+    compute_graph: &str,
+    headers: &HeaderMap,
+    body: Body,
+) -> Result<DataPayload, IndexifyAPIError> {
+    // Payloads declaring their size are rejected before they are read
+    let payload_limit = PayloadLimit::of_graph(state, namespace, compute_graph)?;
+    if let (Some(limit), Some(content_length)) = (&payload_limit, content_length(headers)) {
+        limit.check(state, content_length)?;
+    }
+    let content_encoding = header_value(headers, header::CONTENT_ENCODING);
+    // Payloads the client encoded are already compressed
+    let compressor = match content_encoding {
+        Some(_) => None,
+        None => PayloadCompressor::of_graph(state, namespace, compute_graph)?,
+    };
+    let payload_key = Uuid::new_v4().to_string();
+    let payload_stream = body
+        .into_data_stream()
+        .map(|res| res.map_err(|err| anyhow::anyhow!(err)));
+    let put_result = match &payload_limit {
+        Some(limit) => {
+            put_payload(
+                &state.blob_storage,
+                &payload_key,
+                limit.limit_stream(payload_stream),
+                compressor.as_ref(),
+            )
+            .await
+        }
+        None => {
+            put_payload(
+                &state.blob_storage,
+                &payload_key,
+                Box::pin(payload_stream),
+                compressor.as_ref(),
+            )
+            .await
+        }
+    }
+    .map_err(|e| upload_error(state, payload_limit.as_ref(), e))?;
+    let compression = compression_of(&put_result);
+    Ok(DataPayload {
+        path: put_result.url,
+        size: put_result.size_bytes,
+        sha256_hash: put_result.sha256_hash,
+        tier: Default::default(),
+        mime_type: header_value(headers, header::CONTENT_TYPE),
+        content_encoding,
+        metadata: payload_metadata(headers),
+        replicas: Vec::new(),
+        compression,
+    })
+}
+
+fn header_value(headers: &HeaderMap, name: header::HeaderName) -> Option<String> {
+    headers.get(name)?.to_str().ok().map(str::to_string)
+}
+
+/// Metadata of a payload set with `x-indexify-meta-<key>` headers.
+pub(crate) fn payload_metadata(headers: &HeaderMap) -> HashMap<String, String> {
+    headers
+        .iter()
+        .filter_map(|(name, value)| {
+            let key = name.as_str().strip_prefix(PAYLOAD_METADATA_HEADER_PREFIX)?;
+            Some((key.to_string(), value.to_str().ok()?.to_string()))
+        })
+        .collect()
 }
 
 /// Invoke Compute Graph
@@ -148,6 +386,7 @@ pub async fn invoke_with_file(
     responses(
         (status = 200, description = "invocation successful"),
         (status = 400, description = "bad request"),
+        (status = 429, description = "too much pending work, retry after the Retry-After header"),
         (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
     ),
 )]
@@ -155,54 +394,36 @@ pub async fn invoke_with_object(
     Path((namespace, compute_graph)): Path<(String, String)>,
     Query(params): Query<InvocationQueryParams>,
     State(state): State<RouteState>,
+    headers: HeaderMap,
     body: Body,
 ) -> Result<impl IntoResponse, IndexifyAPIError> {
+    check_backpressure(&state, &namespace, &compute_graph)?;
+    check_invocation_group(&state, &namespace, params.invocation_group.as_deref())?;
+    let labels = params.labels()?;
+    let attributes = params.attributes()?;
+    let parameters = params.parameters()?;
     let should_block = params.block_until_finish.unwrap_or(false);
-    let payload_key = Uuid::new_v4().to_string();
-    let payload_stream = body
-        .into_data_stream()
-        .map(|res| res.map_err(|err| anyhow::anyhow!(err)));
-    let put_result = state
-        .blob_storage
-        .put(&payload_key, Box::pin(payload_stream))
-        .await
-        .map_err(|e| {
-            error!("failed to write to blob store: {}", e);
-            IndexifyAPIError::internal_error(anyhow!("failed to upload content: {}", e))
-        })?;
-    let data_payload = data_model::DataPayload {
-        path: put_result.url,
-        size: put_result.size_bytes,
-        sha256_hash: put_result.sha256_hash,
-    };
-    let invocation_payload = InvocationPayloadBuilder::default()
-        .namespace(namespace.clone())
-        .compute_graph_name(compute_graph.clone())
-        .payload(data_payload)
-        .build()
-        .map_err(|e| {
-            IndexifyAPIError::internal_error(anyhow!("failed to upload content: {}", e))
-        })?;
-    let id = invocation_payload.id.clone();
+    let data_payload =
+        put_invocation_payload(&state, &namespace, &compute_graph, &headers, body).await?;
     let mut rx: Option<Receiver<InvocationStateChangeEvent>> = None;
     if should_block {
         rx.replace(state.indexify_state.task_event_stream());
     }
-    let request = RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
-        namespace: namespace.clone(),
-        compute_graph_name: compute_graph.clone(),
-        invocation_payload,
-    });
-    state
-        .indexify_state
-        .write(StateMachineUpdateRequest {
-            payload: request,
-            state_changes_processed: vec![],
-        })
-        .await
-        .map_err(|e| {
-            IndexifyAPIError::internal_error(anyhow!("failed to upload content: {}", e))
-        })?;
+    let id = invoke_with_payload(
+        &state,
+        &namespace,
+        &compute_graph,
+        data_payload,
+        labels,
+        attributes,
+        params.priority.unwrap_or_default(),
+        params.lane.map(Into::into),
+        params.invocation_group.clone(),
+        parameters,
+        params.event_time,
+        None,
+    )
+    .await?;
 
     let invocation_event_stream = async_stream::stream! {
         if !should_block {
@@ -213,12 +434,17 @@ pub async fn invoke_with_object(
             loop {
                 if let Ok(ev)  =  rx.recv().await {
                     if ev.invocation_id() == id  || ev.invocation_id() == "" {
-                        yield Event::default().json_data(ev.clone());
-
-                        if let InvocationStateChangeEvent::InvocationFinished(InvocationFinishedEvent{ id }) = ev {
+                        if let InvocationStateChangeEvent::InvocationFinished(InvocationFinishedEvent{ id, result }) = ev {
+                            yield Event::default().json_data(serde_json::json!({
+                                "InvocationFinished": {
+                                    "id": id,
+                                    "result": InvocationResult::from(result),
+                                }
+                            }));
                             yield Event::default().json_data(InvocationId { id: id.clone() });
                             return;
                         }
+                        yield Event::default().json_data(ev.clone());
                     }
                 }
             }
@@ -0,0 +1,104 @@
+//! Invocation inputs registered by reference to an object of the blob storage
+//! of the server, so multi-GB inputs already in the bucket aren't copied. The
+//! object stays owned by the client, it's never moved or deleted.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use data_model::{DataPayload, StorageTier};
+
+use super::{
+    invocation_groups::check_invocation_group,
+    invoke::{check_backpressure, invoke_with_payload},
+    invoke_url::check_sha256,
+    payload_limits::PayloadLimit,
+    RouteState,
+};
+use crate::http_objects::{
+    IndexifyAPIError,
+    InvocationId,
+    InvocationQueryParams,
+    InvokeFromReference,
+};
+
+/// Invoke a compute graph with an object of the blob storage as input,
+/// without copying it
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/invoke_reference",
+    request_body = InvokeFromReference,
+    tag = "ingestion",
+    responses(
+        (status = 200, description = "invocation successful", body = InvocationId),
+        (status = 400, description = "the object isn't in the blob storage or doesn't exist"),
+        (status = 412, description = "the object changed since its etag was read"),
+        (status = 429, description = "too much pending work, retry after the Retry-After header"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn invoke_with_reference(
+    Path((namespace, compute_graph)): Path<(String, String)>,
+    Query(params): Query<InvocationQueryParams>,
+    State(state): State<RouteState>,
+    Json(input): Json<InvokeFromReference>,
+) -> Result<Json<InvocationId>, IndexifyAPIError> {
+    check_backpressure(&state, &namespace, &compute_graph)?;
+    check_invocation_group(&state, &namespace, params.invocation_group.as_deref())?;
+    check_sha256(&input.sha256)?;
+    let object = state
+        .blob_storage
+        .head(&input.url)
+        .await
+        .map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))?;
+    // Without a version to pin, the etag makes sure the object is still the
+    // one the client hashed
+    if let Some(etag) = &input.etag {
+        if object.e_tag.as_deref() != Some(etag.as_str()) {
+            return Err(IndexifyAPIError::new(
+                StatusCode::PRECONDITION_FAILED,
+                &format!(
+                    "etag of {} is {}, expected {}",
+                    input.url,
+                    object.e_tag.as_deref().unwrap_or("unset"),
+                    etag
+                ),
+            ));
+        }
+    } else if object.version.is_none() {
+        return Err(IndexifyAPIError::bad_request(
+            "etag is required for objects of unversioned storage",
+        ));
+    }
+    if let Some(limit) = PayloadLimit::of_graph(&state, &namespace, &compute_graph)? {
+        limit.for_url(&input.url).check(&state, object.size_bytes)?;
+    }
+    let data_payload = DataPayload {
+        path: object.url,
+        size: object.size_bytes,
+        sha256_hash: input.sha256.to_lowercase(),
+        tier: StorageTier::Referenced,
+        mime_type: input.content_type,
+        content_encoding: None,
+        metadata: input.metadata,
+        replicas: Vec::new(),
+        compression: None,
+    };
+    let id = invoke_with_payload(
+        &state,
+        &namespace,
+        &compute_graph,
+        data_payload,
+        params.labels()?,
+        params.attributes()?,
+        params.priority.unwrap_or_default(),
+        params.lane.map(Into::into),
+        params.invocation_group.clone(),
+        params.parameters()?,
+        params.event_time,
+        None,
+    )
+    .await?;
+    Ok(Json(InvocationId { id }))
+}
@@ -0,0 +1,217 @@
+use std::time::Duration;
+
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use data_model::{DataPayload, NodeOutput, OutputPayload, StorageTier};
+use futures::StreamExt;
+use state_store::invocation_events::{InvocationFinishedEvent, InvocationStateChangeEvent};
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+
+use super::{
+    download::read_payload,
+    invocation_groups::check_invocation_group,
+    invoke::{check_backpressure, invoke_with_payload, put_invocation_payload},
+    RouteState,
+};
+use crate::http_objects::{
+    IndexifyAPIError,
+    InlineFnOutput,
+    InvocationQueryParams,
+    SyncInvocationParams,
+    SyncInvocationResult,
+};
+
+/// Invoke a compute graph and wait for it to finish
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/invoke_sync",
+    request_body(content_type = "application/cbor", content = inline(serde_json::Value)),
+    tag = "ingestion",
+    responses(
+        (status = 200, description = "The invocation finished", body = SyncInvocationResult),
+        (status = 202, description = "The timeout elapsed before the invocation finished", body = SyncInvocationResult),
+        (status = 400, description = "bad request"),
+        (status = 429, description = "too much pending work, retry after the Retry-After header"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn invoke_and_wait(
+    Path((namespace, compute_graph)): Path<(String, String)>,
+    Query(params): Query<InvocationQueryParams>,
+    Query(sync_params): Query<SyncInvocationParams>,
+    State(state): State<RouteState>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<(StatusCode, Json<SyncInvocationResult>), IndexifyAPIError> {
+    check_backpressure(&state, &namespace, &compute_graph)?;
+    check_invocation_group(&state, &namespace, params.invocation_group.as_deref())?;
+    let labels = params.labels()?;
+    let attributes = params.attributes()?;
+    let parameters = params.parameters()?;
+    let config = state.config_reloader.runtime().sync_invoke;
+    let timeout = Duration::from_secs(
+        sync_params
+            .timeout_secs
+            .unwrap_or(config.default_timeout_secs)
+            .min(config.max_timeout_secs),
+    );
+    let data_payload =
+        put_invocation_payload(&state, &namespace, &compute_graph, &headers, body).await?;
+    // Subscribed before the invocation is created so its finish can't be missed
+    let rx = state.indexify_state.task_event_stream();
+    let id = invoke_with_payload(
+        &state,
+        &namespace,
+        &compute_graph,
+        data_payload,
+        labels,
+        attributes,
+        params.priority.unwrap_or_default(),
+        params.lane.map(Into::into),
+        params.invocation_group.clone(),
+        parameters,
+        params.event_time,
+        None,
+    )
+    .await?;
+
+    let result = tokio::time::timeout(
+        timeout,
+        wait_for_result(&state, rx, &namespace, &compute_graph, &id),
+    )
+    .await
+    .unwrap_or(Ok(None))?;
+    let Some(result) = result else {
+        return Ok((
+            StatusCode::ACCEPTED,
+            Json(SyncInvocationResult {
+                id,
+                finished: false,
+                result: None,
+                inline_outputs: vec![],
+            }),
+        ));
+    };
+    let mut inline_outputs = Vec::new();
+    for (output, payload) in outputs_to_inline(&result.outputs, config.inline_outputs_max_bytes) {
+        let mut stream = read_payload(&state, &payload.path, payload.codec()).await?;
+        let mut data = Vec::with_capacity(payload.size as usize);
+        while let Some(chunk) = stream.next().await {
+            data.extend_from_slice(&chunk.map_err(IndexifyAPIError::internal_error)?);
+        }
+        inline_outputs.push(InlineFnOutput {
+            compute_fn: output.compute_fn_name.clone(),
+            id: output.id.clone(),
+            data: STANDARD.encode(data),
+        });
+    }
+    Ok((
+        StatusCode::OK,
+        Json(SyncInvocationResult {
+            id,
+            finished: true,
+            result: Some(result.into()),
+            inline_outputs,
+        }),
+    ))
+}
+
+/// Waits for the result of the invocation. Events missed by a lagging
+/// receiver are recovered from the stored result.
+async fn wait_for_result(
+    state: &RouteState,
+    mut rx: Receiver<InvocationStateChangeEvent>,
+    namespace: &str,
+    compute_graph: &str,
+    id: &str,
+) -> Result<Option<data_model::InvocationResult>, IndexifyAPIError> {
+    loop {
+        match rx.recv().await {
+            Ok(InvocationStateChangeEvent::InvocationFinished(InvocationFinishedEvent {
+                id: invocation_id,
+                result,
+            })) if invocation_id == id &&
+                result.namespace == namespace &&
+                result.compute_graph_name == compute_graph =>
+            {
+                return Ok(Some(result));
+            }
+            Ok(_) => {}
+            Err(RecvError::Lagged(_)) => {
+                let result = state
+                    .indexify_state
+                    .reader()
+                    .invocation_result(namespace, compute_graph, id)
+                    .map_err(IndexifyAPIError::internal_error)?;
+                if result.is_some() {
+                    return Ok(result);
+                }
+            }
+            Err(RecvError::Closed) => return Ok(None),
+        }
+    }
+}
+
+/// Function outputs whose total size fits in `max_bytes`, in the order of the
+/// result. Outputs which would exceed it are skipped so smaller ones after
+/// them can still be inlined.
+fn outputs_to_inline(outputs: &[NodeOutput], max_bytes: u64) -> Vec<(&NodeOutput, &DataPayload)> {
+    let mut remaining = max_bytes;
+    outputs
+        .iter()
+        .filter_map(|output| match &output.payload {
+            OutputPayload::Fn(payload) if payload.tier != StorageTier::Expired => {
+                Some((output, payload))
+            }
+            _ => None,
+        })
+        .filter(|(_, payload)| {
+            if payload.size > remaining {
+                return false;
+            }
+            remaining -= payload.size;
+            true
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use data_model::test_objects::tests::mock_node_fn_output;
+
+    use super::*;
+
+    fn output_of_size(compute_fn: &str, size: u64) -> NodeOutput {
+        let mut output = mock_node_fn_output("invocation", "graph", compute_fn, None);
+        if let OutputPayload::Fn(payload) = &mut output.payload {
+            payload.size = size;
+        }
+        output
+    }
+
+    #[test]
+    fn test_outputs_to_inline() {
+        let mut expired = output_of_size("fn_d", 1);
+        if let OutputPayload::Fn(payload) = &mut expired.payload {
+            payload.tier = StorageTier::Expired;
+        }
+        let outputs = vec![
+            output_of_size("fn_a", 6),
+            output_of_size("fn_b", 5),
+            output_of_size("fn_c", 4),
+            expired,
+        ];
+        let inlined: Vec<_> = outputs_to_inline(&outputs, 10)
+            .into_iter()
+            .map(|(output, _)| output.compute_fn_name.as_str())
+            .collect();
+        assert_eq!(inlined, vec!["fn_a", "fn_c"]);
+
+        assert!(outputs_to_inline(&outputs, 0).is_empty());
+    }
+}
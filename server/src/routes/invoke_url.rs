@@ -0,0 +1,353 @@
+//! Invocation inputs fetched by the server from a url instead of being
+//! uploaded, for large files which are already in object storage. The input
+//! is fetched in the background, with retries, and the invocation is started
+//! once it's stored. Fetches are tracked in memory, the status of a fetch is
+//! lost when the server restarts.
+
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+use anyhow::anyhow;
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use blob_store::PutResult;
+use data_model::{DataPayload, SchedulingLane};
+use indexify_utils::get_epoch_time_in_ms;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::{
+    invocation_groups::check_invocation_group,
+    invoke::{check_backpressure, invoke_with_payload},
+    payload_compression::{compression_of, put_payload, PayloadCompressor},
+    payload_limits::{upload_error, PayloadLimit, PayloadTooLarge},
+    RouteState,
+};
+use crate::{
+    config::UrlFetchConfig,
+    http_objects::{
+        IndexifyAPIError,
+        InvocationQueryParams,
+        InvokeFromUrl,
+        UrlFetch,
+        UrlFetchStatus,
+    },
+};
+
+const FETCHABLE_SCHEMES: [&str; 3] = ["http://", "https://", "s3://"];
+
+// Finished fetches are kept this long for their status to be queried
+const FINISHED_FETCH_RETENTION_MS: u64 = 3600 * 1000;
+
+/// Fetches of invocation inputs started on this server.
+pub struct UrlFetches {
+    config: UrlFetchConfig,
+    fetches: Mutex<HashMap<String, UrlFetch>>,
+}
+
+impl UrlFetches {
+    pub fn new(config: UrlFetchConfig) -> Self {
+        Self {
+            config,
+            fetches: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn insert(&self, fetch: UrlFetch) {
+        let mut fetches = self.fetches.lock().unwrap();
+        let expired_before = fetch.created_at.saturating_sub(FINISHED_FETCH_RETENTION_MS);
+        fetches.retain(|_, fetch| fetch.finished_at.map_or(true, |at| at >= expired_before));
+        fetches.insert(fetch.id.clone(), fetch);
+    }
+
+    fn update(&self, id: &str, update: impl FnOnce(&mut UrlFetch)) {
+        if let Some(fetch) = self.fetches.lock().unwrap().get_mut(id) {
+            update(fetch);
+        }
+    }
+
+    fn finish(&self, id: &str, result: Result<String, String>) {
+        self.update(id, |fetch| {
+            fetch.finished_at = Some(get_epoch_time_in_ms());
+            match result {
+                Ok(invocation_id) => {
+                    fetch.status = UrlFetchStatus::Invoked;
+                    fetch.invocation_id = Some(invocation_id);
+                }
+                Err(error) => {
+                    fetch.status = UrlFetchStatus::Failed;
+                    fetch.error = Some(error);
+                }
+            }
+        });
+    }
+
+    fn get(&self, id: &str) -> Option<UrlFetch> {
+        self.fetches.lock().unwrap().get(id).cloned()
+    }
+
+    fn check_url(&self, url: &str) -> Result<(), IndexifyAPIError> {
+        if !FETCHABLE_SCHEMES
+            .iter()
+            .any(|scheme| url.starts_with(scheme))
+        {
+            return Err(IndexifyAPIError::bad_request(
+                "only http(s) and s3 urls can be fetched",
+            ));
+        }
+        // The blob store reader expects a bucket and a key
+        if let Some(path) = url.strip_prefix("s3://") {
+            if !path.contains('/') {
+                return Err(IndexifyAPIError::bad_request(
+                    "s3 urls must be s3://<bucket>/<key>",
+                ));
+            }
+        }
+        let allowed = &self.config.allowed_url_prefixes;
+        if !allowed.is_empty() && !allowed.iter().any(|prefix| url.starts_with(prefix)) {
+            return Err(IndexifyAPIError::bad_request(&format!(
+                "url {} is not allowed",
+                url
+            )));
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn check_sha256(sha256: &str) -> Result<(), IndexifyAPIError> {
+    if sha256.len() != 64 || !sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(IndexifyAPIError::bad_request(
+            "sha256 must be 64 hex characters",
+        ));
+    }
+    Ok(())
+}
+
+struct FetchRequest {
+    id: String,
+    namespace: String,
+    compute_graph: String,
+    input: InvokeFromUrl,
+    limit: PayloadLimit,
+    compressor: Option<PayloadCompressor>,
+    labels: HashMap<String, serde_json::Value>,
+    attributes: HashMap<String, serde_json::Value>,
+    priority: i32,
+    lane: Option<SchedulingLane>,
+    group: Option<String>,
+    parameters: HashMap<String, serde_json::Value>,
+    event_time: Option<u64>,
+}
+
+/// Invoke a compute graph with an input fetched by the server from a url
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/invoke_url",
+    request_body = InvokeFromUrl,
+    tag = "ingestion",
+    responses(
+        (status = 202, description = "the input is being fetched", body = UrlFetch),
+        (status = 400, description = "the url can't be fetched"),
+        (status = 429, description = "too much pending work, retry after the Retry-After header"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn invoke_from_url(
+    Path((namespace, compute_graph)): Path<(String, String)>,
+    Query(params): Query<InvocationQueryParams>,
+    State(state): State<RouteState>,
+    Json(input): Json<InvokeFromUrl>,
+) -> Result<(StatusCode, Json<UrlFetch>), IndexifyAPIError> {
+    check_backpressure(&state, &namespace, &compute_graph)?;
+    check_invocation_group(&state, &namespace, params.invocation_group.as_deref())?;
+    state.url_fetches.check_url(&input.url)?;
+    if let Some(sha256) = &input.sha256 {
+        check_sha256(sha256)?;
+    }
+    let max_bytes = match PayloadLimit::of_graph(&state, &namespace, &compute_graph)? {
+        Some(limit) => limit.max_bytes().min(state.url_fetches.config.max_bytes),
+        None => state.url_fetches.config.max_bytes,
+    };
+    let fetch = UrlFetch {
+        id: Uuid::new_v4().to_string(),
+        namespace: namespace.clone(),
+        compute_graph: compute_graph.clone(),
+        url: input.url.clone(),
+        status: UrlFetchStatus::Fetching,
+        attempts: 0,
+        invocation_id: None,
+        error: None,
+        created_at: get_epoch_time_in_ms(),
+        finished_at: None,
+    };
+    let request = FetchRequest {
+        id: fetch.id.clone(),
+        limit: PayloadLimit::new(&namespace, &compute_graph, max_bytes).for_url(&input.url),
+        compressor: PayloadCompressor::of_graph(&state, &namespace, &compute_graph)?,
+        namespace,
+        compute_graph,
+        input,
+        labels: params.labels()?,
+        attributes: params.attributes()?,
+        priority: params.priority.unwrap_or_default(),
+        lane: params.lane.map(Into::into),
+        group: params.invocation_group.clone(),
+        parameters: params.parameters()?,
+        event_time: params.event_time,
+    };
+    state.url_fetches.insert(fetch.clone());
+    tokio::spawn(async move {
+        let result = fetch_and_invoke(&state, &request)
+            .await
+            .map_err(|e| e.message().to_string());
+        if let Err(error) = &result {
+            warn!(
+                "fetch {} of {} failed: {}",
+                request.id, request.input.url, error
+            );
+        }
+        state.url_fetches.finish(&request.id, result);
+    });
+    Ok((StatusCode::ACCEPTED, Json(fetch)))
+}
+
+async fn fetch_and_invoke(
+    state: &RouteState,
+    request: &FetchRequest,
+) -> Result<String, IndexifyAPIError> {
+    let config = &state.url_fetches.config;
+    let key = Uuid::new_v4().to_string();
+    let mut attempt = 0;
+    let put_result = loop {
+        attempt += 1;
+        state
+            .url_fetches
+            .update(&request.id, |fetch| fetch.attempts = attempt);
+        let fetched = tokio::time::timeout(
+            Duration::from_secs(config.timeout_secs),
+            fetch(state, &key, request),
+        )
+        .await
+        .unwrap_or_else(|_| Err(anyhow!("fetch timed out")));
+        let err = match fetched {
+            Ok(put_result) => break put_result,
+            Err(err) => err,
+        };
+        // Inputs over the limit would be cut again
+        let too_large = err.downcast_ref::<PayloadTooLarge>().is_some();
+        let err = upload_error(state, Some(&request.limit), err);
+        if too_large || attempt >= config.max_attempts {
+            return Err(err);
+        }
+        let backoff = config.initial_backoff_secs << (attempt - 1).min(16);
+        info!(
+            "retrying fetch {} of {} in {}s: {}",
+            request.id,
+            request.input.url,
+            backoff,
+            err.message()
+        );
+        state.url_fetches.update(&request.id, |fetch| {
+            fetch.error = Some(err.message().to_string())
+        });
+        tokio::time::sleep(Duration::from_secs(backoff)).await;
+    };
+    if let Some(sha256) = &request.input.sha256 {
+        if !put_result.sha256_hash.eq_ignore_ascii_case(sha256) {
+            if let Err(err) = state.blob_storage.delete(&put_result.url).await {
+                warn!("failed to delete fetched input {}: {}", put_result.url, err);
+            }
+            return Err(IndexifyAPIError::bad_request(&format!(
+                "sha256 of the fetched input is {}, expected {}",
+                put_result.sha256_hash, sha256
+            )));
+        }
+    }
+    let compression = compression_of(&put_result);
+    let data_payload = DataPayload {
+        path: put_result.url,
+        size: put_result.size_bytes,
+        sha256_hash: put_result.sha256_hash,
+        tier: Default::default(),
+        mime_type: request.input.content_type.clone(),
+        content_encoding: None,
+        metadata: request.input.metadata.clone(),
+        replicas: Vec::new(),
+        compression,
+    };
+    invoke_with_payload(
+        state,
+        &request.namespace,
+        &request.compute_graph,
+        data_payload,
+        request.labels.clone(),
+        request.attributes.clone(),
+        request.priority,
+        request.lane,
+        request.group.clone(),
+        request.parameters.clone(),
+        request.event_time,
+        None,
+    )
+    .await
+}
+
+async fn fetch(state: &RouteState, key: &str, request: &FetchRequest) -> anyhow::Result<PutResult> {
+    let stream = state.blob_storage.get(&request.input.url).get().await?;
+    put_payload(
+        &state.blob_storage,
+        key,
+        request.limit.limit_stream(stream),
+        request.compressor.as_ref(),
+    )
+    .await
+}
+
+/// Status of the fetch of an invocation input
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/url_fetches/{fetch_id}",
+    tag = "ingestion",
+    responses(
+        (status = 200, description = "Status of the fetch", body = UrlFetch),
+        (status = NOT_FOUND, description = "Fetch not found"),
+    ),
+)]
+pub async fn get_url_fetch(
+    Path((namespace, compute_graph, fetch_id)): Path<(String, String, String)>,
+    State(state): State<RouteState>,
+) -> Result<Json<UrlFetch>, IndexifyAPIError> {
+    state
+        .url_fetches
+        .get(&fetch_id)
+        .filter(|fetch| fetch.namespace == namespace && fetch.compute_graph == compute_graph)
+        .map(Json)
+        .ok_or(IndexifyAPIError::not_found("fetch not found"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_url() {
+        let fetches = UrlFetches::new(UrlFetchConfig::default());
+        assert!(fetches.check_url("https://example.com/input.pdf").is_ok());
+        assert!(fetches.check_url("s3://bucket/input.pdf").is_ok());
+        assert!(fetches.check_url("s3://bucket").is_err());
+        assert!(fetches.check_url("file:///etc/passwd").is_err());
+
+        let fetches = UrlFetches::new(UrlFetchConfig {
+            allowed_url_prefixes: vec!["s3://ingest/".to_string()],
+            ..Default::default()
+        });
+        assert!(fetches.check_url("s3://ingest/input.pdf").is_ok());
+        assert!(fetches.check_url("s3://other/input.pdf").is_err());
+        assert!(fetches.check_url("https://example.com/input.pdf").is_err());
+
+        assert!(check_sha256(&"a".repeat(64)).is_ok());
+        assert!(check_sha256("abc").is_err());
+    }
+}
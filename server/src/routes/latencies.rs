@@ -0,0 +1,58 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+
+use super::RouteState;
+use crate::http_objects::{FunctionLatencies, IndexifyAPIError};
+
+/// Get the latency quantiles of the functions of a compute graph
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/latencies",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Latencies of the functions of the compute graph", body = FunctionLatencies),
+        (status = 404, description = "Compute Graph not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn get_function_latencies(
+    Path((namespace, compute_graph)): Path<(String, String)>,
+    State(state): State<RouteState>,
+) -> Result<Json<FunctionLatencies>, IndexifyAPIError> {
+    let reader = state.indexify_state.reader();
+    reader
+        .get_compute_graph(&namespace, &compute_graph)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::not_found("Compute Graph not found"))?;
+    let latencies = reader
+        .graph_function_latencies(&namespace, &compute_graph)
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(FunctionLatencies {
+        latencies: latencies.into_iter().map(Into::into).collect(),
+    }))
+}
+
+/// List the latency quantiles of the functions of all compute graphs
+#[utoipa::path(
+    get,
+    path = "/internal/metrics/latencies",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Latencies of the functions of all compute graphs", body = FunctionLatencies),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn list_function_latencies(
+    State(state): State<RouteState>,
+) -> Result<Json<FunctionLatencies>, IndexifyAPIError> {
+    let latencies = state
+        .indexify_state
+        .reader()
+        .all_function_latencies()
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(FunctionLatencies {
+        latencies: latencies.into_iter().map(Into::into).collect(),
+    }))
+}
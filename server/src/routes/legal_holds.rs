@@ -0,0 +1,247 @@
+//! Legal holds of compute graphs and invocations. Held data is exempt from
+//! the expiry of payloads and can't be deleted or purged, including with its
+//! namespace, until the hold is cleared. Holds are set and cleared on behalf
+//! of the identity of the request and every change is kept in an audit log.
+
+use axum::{
+    extract::{Path, Query, State},
+    Extension,
+    Json,
+};
+use serde::Deserialize;
+use state_store::requests::{LegalHoldRequest, RequestPayload, StateMachineUpdateRequest};
+
+use super::{check_writable, RouteState};
+use crate::{
+    auth::Identity,
+    http_objects::{IndexifyAPIError, LegalHoldAuditLog, LegalHoldsList, ListParams, SetLegalHold},
+};
+
+// Recorded as the identity of the holds changed while auth is disabled
+const ANONYMOUS: &str = "anonymous";
+
+#[derive(Debug, Deserialize)]
+pub struct ClearLegalHoldParams {
+    /// Why the hold is cleared, recorded in the audit log
+    #[serde(default)]
+    reason: String,
+}
+
+async fn write(
+    state: &RouteState,
+    identity: Option<Extension<Identity>>,
+    namespace: String,
+    compute_graph: String,
+    invocation_id: Option<String>,
+    reason: String,
+    set: bool,
+) -> Result<(), IndexifyAPIError> {
+    check_writable(state)?;
+    let request = LegalHoldRequest {
+        namespace,
+        compute_graph,
+        invocation_id,
+        reason,
+        identity: identity
+            .map(|Extension(identity)| identity.name)
+            .unwrap_or_else(|| ANONYMOUS.to_string()),
+    };
+    let payload = if set {
+        RequestPayload::SetLegalHold(request)
+    } else {
+        RequestPayload::ClearLegalHold(request)
+    };
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload,
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(IndexifyAPIError::internal_error)
+}
+
+fn check_reason(reason: &str) -> Result<(), IndexifyAPIError> {
+    if reason.trim().is_empty() {
+        return Err(IndexifyAPIError::bad_request("a legal hold needs a reason"));
+    }
+    Ok(())
+}
+
+/// Hold every invocation of a compute graph, they don't expire and can't be
+/// deleted until the hold is cleared
+#[utoipa::path(
+    put,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/legal_hold",
+    request_body = SetLegalHold,
+    tag = "operations",
+    responses(
+        (status = 200, description = "the compute graph is held"),
+        (status = NOT_FOUND, description = "Compute Graph not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn set_graph_legal_hold(
+    Path((namespace, compute_graph)): Path<(String, String)>,
+    State(state): State<RouteState>,
+    identity: Option<Extension<Identity>>,
+    Json(request): Json<SetLegalHold>,
+) -> Result<(), IndexifyAPIError> {
+    check_reason(&request.reason)?;
+    write(
+        &state,
+        identity,
+        namespace,
+        compute_graph,
+        None,
+        request.reason,
+        true,
+    )
+    .await
+}
+
+/// Clear the legal hold of a compute graph
+#[utoipa::path(
+    delete,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/legal_hold",
+    params(
+        ("reason" = Option<String>, Query, description = "Why the hold is cleared"),
+    ),
+    tag = "operations",
+    responses(
+        (status = 200, description = "the hold was cleared"),
+        (status = NOT_FOUND, description = "the compute graph isn't held"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn clear_graph_legal_hold(
+    Path((namespace, compute_graph)): Path<(String, String)>,
+    Query(params): Query<ClearLegalHoldParams>,
+    State(state): State<RouteState>,
+    identity: Option<Extension<Identity>>,
+) -> Result<(), IndexifyAPIError> {
+    write(
+        &state,
+        identity,
+        namespace,
+        compute_graph,
+        None,
+        params.reason,
+        false,
+    )
+    .await
+}
+
+/// Hold an invocation, its input and outputs don't expire and it can't be
+/// deleted until the hold is cleared
+#[utoipa::path(
+    put,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/invocations/{invocation_id}/legal_hold",
+    request_body = SetLegalHold,
+    tag = "operations",
+    responses(
+        (status = 200, description = "the invocation is held"),
+        (status = NOT_FOUND, description = "Invocation not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn set_invocation_legal_hold(
+    Path((namespace, compute_graph, invocation_id)): Path<(String, String, String)>,
+    State(state): State<RouteState>,
+    identity: Option<Extension<Identity>>,
+    Json(request): Json<SetLegalHold>,
+) -> Result<(), IndexifyAPIError> {
+    check_reason(&request.reason)?;
+    write(
+        &state,
+        identity,
+        namespace,
+        compute_graph,
+        Some(invocation_id),
+        request.reason,
+        true,
+    )
+    .await
+}
+
+/// Clear the legal hold of an invocation
+#[utoipa::path(
+    delete,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/invocations/{invocation_id}/legal_hold",
+    params(
+        ("reason" = Option<String>, Query, description = "Why the hold is cleared"),
+    ),
+    tag = "operations",
+    responses(
+        (status = 200, description = "the hold was cleared"),
+        (status = NOT_FOUND, description = "the invocation isn't held"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn clear_invocation_legal_hold(
+    Path((namespace, compute_graph, invocation_id)): Path<(String, String, String)>,
+    Query(params): Query<ClearLegalHoldParams>,
+    State(state): State<RouteState>,
+    identity: Option<Extension<Identity>>,
+) -> Result<(), IndexifyAPIError> {
+    write(
+        &state,
+        identity,
+        namespace,
+        compute_graph,
+        Some(invocation_id),
+        params.reason,
+        false,
+    )
+    .await
+}
+
+/// List the legal holds of a namespace
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/legal_holds",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Legal holds of the namespace", body = LegalHoldsList),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn list_legal_holds(
+    Path(namespace): Path<String>,
+    State(state): State<RouteState>,
+) -> Result<Json<LegalHoldsList>, IndexifyAPIError> {
+    let holds = state
+        .indexify_state
+        .reader()
+        .legal_holds(&namespace, None)
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(LegalHoldsList {
+        holds: holds.into_iter().map(Into::into).collect(),
+    }))
+}
+
+/// Audit log of the legal holds set and cleared in a namespace, oldest first
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/legal_holds/audit",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Audit log of the legal holds", body = LegalHoldAuditLog),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn get_legal_hold_audit(
+    Path(namespace): Path<String>,
+    Query(params): Query<ListParams>,
+    State(state): State<RouteState>,
+) -> Result<Json<LegalHoldAuditLog>, IndexifyAPIError> {
+    let (entries, cursor) = state
+        .indexify_state
+        .reader()
+        .legal_hold_audit(&namespace, params.cursor.as_deref(), params.limit)
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(LegalHoldAuditLog {
+        entries: entries.into_iter().map(Into::into).collect(),
+        cursor,
+    }))
+}
@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+
+use super::{parse_compute_graph, validate_sub_graphs, RouteState};
+use crate::http_objects::{ComputeGraph, GraphLint, IndexifyAPIError};
+
+fn lint(compute_graph: &data_model::ComputeGraph) -> GraphLint {
+    GraphLint {
+        compute_graph: compute_graph.name.clone(),
+        warnings: data_model::lint::lint(compute_graph)
+            .into_iter()
+            .map(Into::into)
+            .collect(),
+    }
+}
+
+/// Lint the definition of a compute graph before deploying it. Invalid
+/// definitions are rejected as they would be when deployed, valid ones get
+/// the best practice warnings of the graph.
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/lint",
+    request_body = ComputeGraph,
+    tag = "operations",
+    responses(
+        (status = 200, description = "Warnings of the compute graph", body = GraphLint),
+        (status = BAD_REQUEST, description = "The compute graph is invalid"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn lint_graph_definition(
+    Path(namespace): Path<String>,
+    State(state): State<RouteState>,
+    body: String,
+) -> Result<Json<GraphLint>, IndexifyAPIError> {
+    let compute_graph = parse_compute_graph(&namespace, &body)?.into_data_model("", "", 0)?;
+    validate_sub_graphs(&state, &compute_graph, &HashMap::new())?;
+    Ok(Json(lint(&compute_graph)))
+}
+
+/// Lint a deployed compute graph
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/lint",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Warnings of the compute graph", body = GraphLint),
+        (status = NOT_FOUND, description = "Compute Graph not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn lint_compute_graph(
+    Path((namespace, name)): Path<(String, String)>,
+    State(state): State<RouteState>,
+) -> Result<Json<GraphLint>, IndexifyAPIError> {
+    let compute_graph = state
+        .indexify_state
+        .reader()
+        .get_compute_graph(&namespace, &name)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::not_found("Compute Graph not found"))?;
+    Ok(Json(lint(&compute_graph)))
+}
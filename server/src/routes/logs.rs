@@ -1,8 +1,10 @@
+use std::ops::Range;
+
 use anyhow::anyhow;
 use axum::{
     body::Body,
     extract::{Path, State},
-    http::Response,
+    http::{header, HeaderMap, Response, StatusCode},
 };
 
 use super::RouteState;
@@ -53,3 +55,131 @@ pub async fn download_logs(
         .body(Body::from_stream(payload_stream))
         .map_err(|e| IndexifyAPIError::internal_error_str(&e.to_string()))
 }
+
+/// Get the stdout, stderr, exception_msg or structured_logs of a task.
+/// Supports single range requests, e.g. `Range: bytes=-4096` for the tail of
+/// the logs.
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/invocations/{invocation_id}/fn/{fn_name}/tasks/{task_id}/logs/{file}",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Log file"),
+        (status = 206, description = "Requested range of the log file"),
+        (status = NOT_FOUND, description = "Task has no such log file"),
+        (status = RANGE_NOT_SATISFIABLE, description = "Invalid range"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn get_task_logs(
+    Path((namespace, compute_graph, invocation_id, fn_name, task_id, file)): Path<(
+        String,
+        String,
+        String,
+        String,
+        String,
+        String,
+    )>,
+    State(state): State<RouteState>,
+    headers: HeaderMap,
+) -> Result<Response<Body>, IndexifyAPIError> {
+    let payload = state
+        .indexify_state
+        .reader()
+        .get_task_diagnostic_payload(
+            &namespace,
+            &compute_graph,
+            &invocation_id,
+            &fn_name,
+            &task_id,
+            &file,
+        )
+        .map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))?
+        .ok_or(IndexifyAPIError::not_found(&format!(
+            "{} not found for task {}",
+            file, task_id
+        )))?;
+    let storage_reader = state.blob_storage.get(&payload.path);
+
+    let range = match headers.get(header::RANGE) {
+        Some(range) => {
+            let range = range
+                .to_str()
+                .map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))?;
+            Some(
+                parse_range(range, payload.size).ok_or(IndexifyAPIError::new(
+                    StatusCode::RANGE_NOT_SATISFIABLE,
+                    &format!("invalid range {} for size {}", range, payload.size),
+                ))?,
+            )
+        }
+        None => None,
+    };
+    let Some(range) = range else {
+        let payload_stream = storage_reader
+            .get()
+            .await
+            .map_err(IndexifyAPIError::internal_error)?;
+        return Response::builder()
+            .header(header::CONTENT_TYPE, "application/octet-stream")
+            .header(header::CONTENT_LENGTH, payload.size.to_string())
+            .header(header::ACCEPT_RANGES, "bytes")
+            .body(Body::from_stream(payload_stream))
+            .map_err(|e| IndexifyAPIError::internal_error_str(&e.to_string()));
+    };
+
+    let bytes = storage_reader
+        .get_range(range.start as usize..range.end as usize)
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    Response::builder()
+        .status(StatusCode::PARTIAL_CONTENT)
+        .header(header::CONTENT_TYPE, "application/octet-stream")
+        .header(header::CONTENT_LENGTH, bytes.len().to_string())
+        .header(
+            header::CONTENT_RANGE,
+            format!("bytes {}-{}/{}", range.start, range.end - 1, payload.size),
+        )
+        .body(Body::from(bytes))
+        .map_err(|e| IndexifyAPIError::internal_error_str(&e.to_string()))
+}
+
+// Parses a single `bytes=` range into a half open range. Returns None if the
+// range can't be satisfied for a blob of the given size.
+fn parse_range(range: &str, size: u64) -> Option<Range<u64>> {
+    let spec = range.trim().strip_prefix("bytes=")?;
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", suffix) => {
+            let suffix: u64 = suffix.parse().ok()?;
+            (size.saturating_sub(suffix), size)
+        }
+        (start, "") => (start.parse().ok()?, size),
+        (start, end) => {
+            let end: u64 = end.parse().ok()?;
+            (start.parse().ok()?, end.saturating_add(1).min(size))
+        }
+    };
+    if start >= end {
+        return None;
+    }
+    Some(start..end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("bytes=0-99", 1000), Some(0..100));
+        assert_eq!(parse_range("bytes=900-", 1000), Some(900..1000));
+        assert_eq!(parse_range("bytes=-100", 1000), Some(900..1000));
+        assert_eq!(parse_range("bytes=-2000", 1000), Some(0..1000));
+        assert_eq!(parse_range("bytes=500-5000", 1000), Some(500..1000));
+        assert_eq!(parse_range("bytes=1000-", 1000), None);
+        assert_eq!(parse_range("bytes=10-5", 1000), None);
+        assert_eq!(parse_range("bytes=0-1,5-10", 1000), None);
+        assert_eq!(parse_range("items=0-10", 1000), None);
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Multipart, Path, State},
+    Json,
+};
+use blob_store::PutResult;
+use futures::StreamExt;
+use nanoid::nanoid;
+use state_store::requests::{
+    ApplyManifestRequest,
+    CreateComputeGraphRequest,
+    RequestPayload,
+    StateMachineUpdateRequest,
+};
+use tracing::info;
+
+use super::{
+    check_writable,
+    prepare_compute_graph,
+    validate_sub_graphs,
+    RouteState,
+    WASM_MODULE_FIELD_PREFIX,
+};
+use crate::http_objects::{IndexifyAPIError, Manifest, ManifestApplied};
+
+const CODE_FIELD_PREFIX: &str = "code.";
+
+#[allow(dead_code)]
+#[derive(utoipa::ToSchema)]
+struct ApplyManifestType {
+    manifest: Manifest,
+    /// Code of a graph of the manifest, in a `code.<graph>` field
+    #[schema(format = "binary")]
+    code: String,
+}
+
+fn parse_manifest(namespace: &str, text: &str) -> Result<Manifest, IndexifyAPIError> {
+    let mut json_value: serde_json::Value = serde_json::from_str(text)?;
+    if let Some(graphs) = json_value["graphs"].as_array_mut() {
+        for graph in graphs {
+            graph["compute_graph"]["namespace"] = serde_json::Value::String(namespace.to_string());
+        }
+    }
+    Ok(serde_json::from_value(json_value)?)
+}
+
+/// Create or update several compute graphs at once, none of them is applied
+/// if any fails validation or conflicts
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/manifests",
+    tag = "operations",
+    request_body(content_type = "multipart/form-data", content = inline(ApplyManifestType)),
+    responses(
+        (status = 200, description = "Compute graphs of the manifest applied", body = ManifestApplied),
+        (status = BAD_REQUEST, description = "A compute graph of the manifest is invalid"),
+        (status = CONFLICT, description = "A compute graph was updated since its expected_revision"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn apply_manifest(
+    Path(namespace): Path<String>,
+    State(state): State<RouteState>,
+    mut body: Multipart,
+) -> Result<Json<ManifestApplied>, IndexifyAPIError> {
+    check_writable(&state)?;
+    let mut manifest: Option<Manifest> = None;
+    let mut code: HashMap<String, PutResult> = HashMap::new();
+    // Graph -> router -> module
+    let mut wasm_modules: HashMap<String, HashMap<String, PutResult>> = HashMap::new();
+    while let Some(field) = body
+        .next_field()
+        .await
+        .map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))?
+    {
+        let Some(name) = field.name().map(|name| name.to_string()) else {
+            continue;
+        };
+        if name == "manifest" {
+            let text = field
+                .text()
+                .await
+                .map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))?;
+            manifest = Some(parse_manifest(&namespace, &text)?);
+        } else if let Some(graph) = name.strip_prefix(CODE_FIELD_PREFIX) {
+            let stream = field.map(|res| res.map_err(|err| anyhow::anyhow!(err)));
+            let file_name = format!("{}_{}", namespace, nanoid!());
+            let result = state
+                .blob_storage
+                .put(&file_name, stream)
+                .await
+                .map_err(IndexifyAPIError::internal_error)?;
+            code.insert(graph.to_string(), result);
+        } else if let Some(module) = name.strip_prefix(WASM_MODULE_FIELD_PREFIX) {
+            let (graph, router) = module.split_once('.').ok_or(IndexifyAPIError::bad_request(
+                "wasm modules of a manifest are uploaded in wasm.<graph>.<router> fields",
+            ))?;
+            let (graph, router) = (graph.to_string(), router.to_string());
+            let stream = field.map(|res| res.map_err(|err| anyhow::anyhow!(err)));
+            let file_name = format!("{}_{}.wasm", namespace, nanoid!());
+            let result = state
+                .blob_storage
+                .put(&file_name, stream)
+                .await
+                .map_err(IndexifyAPIError::internal_error)?;
+            wasm_modules
+                .entry(graph)
+                .or_default()
+                .insert(router, result);
+        }
+    }
+    let manifest = manifest.ok_or(IndexifyAPIError::bad_request("manifest is required"))?;
+    if manifest.graphs.is_empty() {
+        return Err(IndexifyAPIError::bad_request(
+            "manifest must contain at least one compute graph",
+        ));
+    }
+
+    let mut graphs = Vec::new();
+    let mut applied_with = HashMap::new();
+    for graph in manifest.graphs {
+        let name = graph.compute_graph.name.clone();
+        if applied_with.contains_key(&name) {
+            return Err(IndexifyAPIError::bad_request(&format!(
+                "compute graph {} appears more than once in the manifest",
+                name
+            )));
+        }
+        let put_result = code
+            .remove(&name)
+            .ok_or(IndexifyAPIError::bad_request(&format!(
+                "code of compute graph {} is required",
+                name
+            )))?;
+        let compute_graph = prepare_compute_graph(
+            &state,
+            graph.compute_graph,
+            put_result,
+            wasm_modules.remove(&name).unwrap_or_default(),
+        )?;
+        applied_with.insert(name, compute_graph.clone());
+        graphs.push(CreateComputeGraphRequest {
+            namespace: namespace.clone(),
+            compute_graph,
+            expected_revision: graph.expected_revision,
+            force: graph.force,
+        });
+    }
+    if let Some(graph) = code.keys().chain(wasm_modules.keys()).next() {
+        return Err(IndexifyAPIError::bad_request(&format!(
+            "files uploaded for {} which is not in the manifest",
+            graph
+        )));
+    }
+    for graph in &graphs {
+        validate_sub_graphs(&state, &graph.compute_graph, &applied_with)?;
+    }
+
+    let compute_graphs: Vec<String> = graphs
+        .iter()
+        .map(|graph| graph.compute_graph.name.clone())
+        .collect();
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: RequestPayload::ApplyManifest(ApplyManifestRequest { namespace, graphs }),
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    info!("manifest applied: {}", compute_graphs.join(", "));
+    Ok(Json(ManifestApplied { compute_graphs }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_manifest() {
+        let graph = |name: &str| {
+            serde_json::json!({
+                "name": name,
+                "description": "",
+                "start_node": {"compute_fn": {
+                    "name": "fn_a",
+                    "fn_name": "fn_a",
+                    "description": "",
+                    "reducer": false,
+                    "payload_encoder": "cloudpickle",
+                    "image_name": "image",
+                }},
+                "nodes": {},
+                "edges": {},
+                "runtime_information": {"major_version": 3, "minor_version": 10},
+            })
+        };
+        let text = serde_json::json!({
+            "graphs": [
+                {"compute_graph": graph("graph_a")},
+                {"compute_graph": graph("graph_b"), "expected_revision": 2},
+            ]
+        })
+        .to_string();
+        let manifest = parse_manifest("ns", &text).unwrap();
+        assert_eq!(manifest.graphs.len(), 2);
+        assert!(manifest
+            .graphs
+            .iter()
+            .all(|graph| graph.compute_graph.namespace == "ns" && !graph.force));
+        assert_eq!(manifest.graphs[1].expected_revision, Some(2));
+    }
+}
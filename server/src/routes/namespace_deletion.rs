@@ -0,0 +1,110 @@
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use ring::digest;
+use state_store::requests::{DeleteNamespaceRequest, RequestPayload, StateMachineUpdateRequest};
+
+use super::RouteState;
+use crate::http_objects::{DeleteNamespaceParams, IndexifyAPIError, NamespaceDeletion};
+
+/// Token confirming the deletion of a namespace. It is derived from the
+/// creation time, so it doesn't delete a namespace re-created with the same
+/// name.
+fn confirmation_token(namespace: &data_model::Namespace) -> String {
+    let digest = digest::digest(
+        &digest::SHA256,
+        format!("{}|{}", namespace.name, namespace.created_at).as_bytes(),
+    );
+    hex::encode(&digest.as_ref()[..8])
+}
+
+/// Get the confirmation token to delete a namespace, or the progress of its
+/// deletion
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/deletion",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Deletion state of the namespace", body = NamespaceDeletion),
+        (status = NOT_FOUND, description = "Namespace not found or already purged"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn get_namespace_deletion(
+    Path(namespace): Path<String>,
+    State(state): State<RouteState>,
+) -> Result<Json<NamespaceDeletion>, IndexifyAPIError> {
+    let reader = state.indexify_state.reader();
+    let ns = reader
+        .get_namespace(&namespace)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::not_found("namespace not found"))?;
+    let progress = reader
+        .namespace_deletion_progress(&namespace)
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(match progress {
+        Some(progress) => NamespaceDeletion {
+            namespace,
+            confirmation_token: None,
+            deleted_at: Some(progress.deleted_at),
+            remaining_compute_graphs: progress.compute_graphs,
+            remaining_invocations: progress.invocations,
+        },
+        None => NamespaceDeletion {
+            confirmation_token: Some(confirmation_token(&ns)),
+            namespace,
+            deleted_at: None,
+            remaining_compute_graphs: 0,
+            remaining_invocations: 0,
+        },
+    }))
+}
+
+/// Delete a namespace. Its compute graphs are deleted, its running
+/// invocations cancelled and new writes to it rejected, everything it holds is
+/// purged in the background.
+#[utoipa::path(
+    delete,
+    path = "/namespaces/{namespace}",
+    params(
+        ("confirmation_token" = String, Query, description = "Token returned by the deletion endpoint of the namespace"),
+    ),
+    tag = "operations",
+    responses(
+        (status = 200, description = "Namespace being deleted", body = NamespaceDeletion),
+        (status = BAD_REQUEST, description = "Missing or invalid confirmation token"),
+        (status = NOT_FOUND, description = "Namespace not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn delete_namespace(
+    Path(namespace): Path<String>,
+    Query(params): Query<DeleteNamespaceParams>,
+    State(state): State<RouteState>,
+) -> Result<Json<NamespaceDeletion>, IndexifyAPIError> {
+    let ns = state
+        .indexify_state
+        .reader()
+        .get_namespace(&namespace)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::not_found("namespace not found"))?;
+    if ns.deleted_at.is_none() &&
+        params.confirmation_token.as_deref() != Some(confirmation_token(&ns).as_str())
+    {
+        return Err(IndexifyAPIError::bad_request(
+            "invalid confirmation token, get it from the deletion endpoint of the namespace",
+        ));
+    }
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: RequestPayload::DeleteNamespace(DeleteNamespaceRequest {
+                namespace: namespace.clone(),
+            }),
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    get_namespace_deletion(Path(namespace), State(state)).await
+}
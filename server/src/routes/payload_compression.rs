@@ -0,0 +1,155 @@
+//! Compression of the payloads written to the blob store. Invocation payloads
+//! and function outputs reaching the size threshold of the server are stored
+//! compressed with zstd, and decompressed when they're read. Graphs whose
+//! payloads are already compressed media opt out of it.
+
+use anyhow::Result;
+use blob_store::{compression, BlobStorage, PutResult};
+use bytes::Bytes;
+use data_model::{DataPayload, PayloadCodec, PayloadCompression};
+use futures::{stream::BoxStream, Stream};
+
+use super::RouteState;
+use crate::{config::PayloadCompressionConfig, http_objects::IndexifyAPIError};
+
+/// Compression of the payloads of a compute graph.
+#[derive(Debug, Clone)]
+pub struct PayloadCompressor {
+    config: PayloadCompressionConfig,
+}
+
+impl PayloadCompressor {
+    /// Compression of the payloads of the graph. None when the server doesn't
+    /// compress payloads or the graph opted out.
+    pub fn of_graph(
+        state: &RouteState,
+        namespace: &str,
+        compute_graph: &str,
+    ) -> Result<Option<Self>, IndexifyAPIError> {
+        let Some(config) = state.config_reloader.runtime().payload_compression else {
+            return Ok(None);
+        };
+        let skipped = state
+            .indexify_state
+            .reader()
+            .get_compute_graph(namespace, compute_graph)
+            .map_err(IndexifyAPIError::internal_error)?
+            .is_some_and(|graph| graph.skip_payload_compression);
+        Ok((!skipped).then_some(Self { config }))
+    }
+}
+
+/// Writes a payload to the blob store, compressed when a compressor is given
+/// and the payload reaches its threshold.
+pub async fn put_payload(
+    storage: &BlobStorage,
+    key: &str,
+    data: impl Stream<Item = Result<Bytes>> + Send + Unpin,
+    compressor: Option<&PayloadCompressor>,
+) -> Result<PutResult> {
+    match compressor {
+        Some(compressor) => {
+            storage
+                .put_compressed(
+                    key,
+                    data,
+                    compressor.config.min_size_bytes,
+                    compressor.config.level,
+                )
+                .await
+        }
+        None => storage.put(key, data).await,
+    }
+}
+
+/// Compression of a payload written by `put_payload`.
+pub fn compression_of(put_result: &PutResult) -> Option<PayloadCompression> {
+    put_result
+        .compressed
+        .as_ref()
+        .map(|compressed| PayloadCompression {
+            codec: PayloadCodec::Zstd,
+            stored_size: compressed.size_bytes,
+            stored_sha256_hash: compressed.sha256_hash.clone(),
+        })
+}
+
+/// Decompresses the stored bytes of a payload.
+pub fn decompress(
+    stream: BoxStream<'static, Result<Bytes>>,
+    codec: Option<PayloadCodec>,
+) -> BoxStream<'static, Result<Bytes>> {
+    match codec {
+        Some(PayloadCodec::Zstd) => compression::decode(stream),
+        None => stream,
+    }
+}
+
+/// Reads the bytes of a payload of the blob store, decompressed.
+pub async fn read_payload_bytes(storage: &BlobStorage, payload: &DataPayload) -> Result<Bytes> {
+    match payload.codec() {
+        Some(PayloadCodec::Zstd) => storage.read_decompressed_bytes(&payload.path).await,
+        None => storage.read_bytes(&payload.path).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use blob_store::BlobStorageConfig;
+    use futures::{stream, StreamExt};
+    use sha2::{Digest, Sha256};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_compressed_payloads() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let storage = BlobStorage::new(BlobStorageConfig::new_disk(
+            temp_dir.path().to_str().unwrap(),
+        ))?;
+        let compressor = PayloadCompressor {
+            config: PayloadCompressionConfig {
+                min_size_bytes: 1024,
+                level: 3,
+            },
+        };
+        let data = "payload ".repeat(1000);
+        let chunks = data
+            .as_bytes()
+            .chunks(100)
+            .map(|chunk| Ok(Bytes::copy_from_slice(chunk)))
+            .collect::<Vec<_>>();
+        let res = put_payload(&storage, "large", stream::iter(chunks), Some(&compressor)).await?;
+        assert_eq!(res.size_bytes, data.len() as u64);
+        assert_eq!(res.sha256_hash, format!("{:x}", Sha256::digest(&data)));
+        let compression = compression_of(&res).unwrap();
+        assert!(compression.stored_size < res.size_bytes / 10);
+        assert_ne!(compression.stored_sha256_hash, res.sha256_hash);
+
+        let payload = DataPayload {
+            path: res.url.clone(),
+            size: res.size_bytes,
+            sha256_hash: res.sha256_hash.clone(),
+            tier: Default::default(),
+            mime_type: None,
+            content_encoding: None,
+            metadata: Default::default(),
+            replicas: Vec::new(),
+            compression: Some(compression),
+        };
+        assert_eq!(read_payload_bytes(&storage, &payload).await?, data);
+        let mut decompressed = Vec::new();
+        let mut stored = decompress(storage.get(&res.url).get().await?, payload.codec());
+        while let Some(chunk) = stored.next().await {
+            decompressed.extend_from_slice(&chunk?);
+        }
+        assert_eq!(decompressed, data.as_bytes());
+
+        // Payloads under the threshold are stored as they are
+        let small = stream::iter(vec![Ok(Bytes::from("small"))]);
+        let res = put_payload(&storage, "small", small, Some(&compressor)).await?;
+        assert!(compression_of(&res).is_none());
+        assert_eq!(storage.read_bytes(&res.url).await?, "small");
+        Ok(())
+    }
+}
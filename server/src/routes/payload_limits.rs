@@ -0,0 +1,292 @@
+//! Size limits of invocation payloads and function outputs. Limits are set in
+//! the queue limits of a graph or of its namespace, payloads exceeding them
+//! are rejected before they are written to the blob storage. Webhooks
+//! subscribed to data_rejected are notified of rejections.
+
+use std::{collections::HashMap, fmt, sync::Mutex};
+
+use anyhow::anyhow;
+use axum::{extract::State, Json};
+use bytes::Bytes;
+use data_model::webhooks::{DataRejection, Guardrail, WebhookEvent};
+use futures::{Stream, StreamExt};
+use indexify_utils::get_epoch_time_in_ms;
+use state_store::requests::{
+    RecordDataRejectionRequest,
+    RequestPayload,
+    StateMachineUpdateRequest,
+};
+
+use super::RouteState;
+use crate::http_objects::{IndexifyAPIError, RejectedPayloads, RejectedPayloadsList};
+
+#[derive(Debug)]
+pub struct PayloadTooLarge {
+    pub size_bytes: u64,
+    pub max_bytes: u64,
+}
+
+impl fmt::Display for PayloadTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "payload of at least {} bytes exceeds the limit of {} bytes",
+            self.size_bytes, self.max_bytes
+        )
+    }
+}
+
+impl std::error::Error for PayloadTooLarge {}
+
+/// Maximum payload size of a compute graph.
+#[derive(Debug, Clone)]
+pub struct PayloadLimit {
+    namespace: String,
+    compute_graph: String,
+    max_bytes: u64,
+    // Identify the rejected payloads in the events sent to webhooks
+    task: Option<(String, String, String)>,
+    url: Option<String>,
+}
+
+impl PayloadLimit {
+    pub fn new(namespace: &str, compute_graph: &str, max_bytes: u64) -> Self {
+        Self {
+            namespace: namespace.to_string(),
+            compute_graph: compute_graph.to_string(),
+            max_bytes,
+            task: None,
+            url: None,
+        }
+    }
+
+    /// Limit of the outputs of a task.
+    pub fn for_task(mut self, invocation_id: &str, compute_fn: &str, task_id: &str) -> Self {
+        self.task = Some((
+            invocation_id.to_string(),
+            compute_fn.to_string(),
+            task_id.to_string(),
+        ));
+        self
+    }
+
+    /// Limit of an input fetched from or registered with a url.
+    pub fn for_url(mut self, url: &str) -> Self {
+        self.url = Some(url.to_string());
+        self
+    }
+
+    /// Limit of the graph, falling back to the one of its namespace. None
+    /// when neither sets one.
+    pub fn of_graph(
+        state: &RouteState,
+        namespace: &str,
+        compute_graph: &str,
+    ) -> Result<Option<Self>, IndexifyAPIError> {
+        let reader = state.indexify_state.reader();
+        let graph_limits = reader
+            .get_compute_graph(namespace, compute_graph)
+            .map_err(IndexifyAPIError::internal_error)?
+            .map(|graph| graph.queue_limits)
+            .unwrap_or_default();
+        let namespace_limits = reader
+            .get_namespace(namespace)
+            .map_err(IndexifyAPIError::internal_error)?
+            .map(|ns| ns.queue_limits)
+            .unwrap_or_default();
+        Ok(graph_limits
+            .or(&namespace_limits)
+            .max_payload_bytes
+            .map(|max_bytes| Self::new(namespace, compute_graph, max_bytes)))
+    }
+
+    pub fn max_bytes(&self) -> u64 {
+        self.max_bytes
+    }
+
+    /// Rejects payloads whose size is known upfront.
+    pub fn check(&self, state: &RouteState, size_bytes: u64) -> Result<(), IndexifyAPIError> {
+        if size_bytes <= self.max_bytes {
+            return Ok(());
+        }
+        Err(self.reject(
+            state,
+            PayloadTooLarge {
+                size_bytes,
+                max_bytes: self.max_bytes,
+            },
+        ))
+    }
+
+    /// Fails the stream as soon as more than the limit went through it, so
+    /// the upload of the payload is aborted.
+    pub fn limit_stream(
+        &self,
+        stream: impl Stream<Item = anyhow::Result<Bytes>> + Send + Unpin,
+    ) -> impl Stream<Item = anyhow::Result<Bytes>> + Send + Unpin {
+        let max_bytes = self.max_bytes;
+        let mut received: u64 = 0;
+        stream.map(move |chunk| {
+            let chunk = chunk?;
+            received += chunk.len() as u64;
+            if received > max_bytes {
+                return Err(anyhow::Error::new(PayloadTooLarge {
+                    size_bytes: received,
+                    max_bytes,
+                }));
+            }
+            Ok(chunk)
+        })
+    }
+
+    fn reject(&self, state: &RouteState, err: PayloadTooLarge) -> IndexifyAPIError {
+        state
+            .rejected_payloads
+            .record(&self.namespace, &self.compute_graph, err.size_bytes);
+        let (invocation_id, compute_fn, task_id) = match self.task.clone() {
+            Some((invocation_id, compute_fn, task_id)) => {
+                (Some(invocation_id), Some(compute_fn), Some(task_id))
+            }
+            None => (None, None, None),
+        };
+        notify_rejection(
+            state,
+            DataRejection {
+                namespace: self.namespace.clone(),
+                compute_graph: self.compute_graph.clone(),
+                guardrail: Guardrail::PayloadSize,
+                error: err.to_string(),
+                invocation_id,
+                compute_fn,
+                task_id,
+                url: self.url.clone(),
+                size_bytes: err.size_bytes,
+            },
+        );
+        IndexifyAPIError::payload_too_large(err.size_bytes, err.max_bytes)
+    }
+}
+
+/// Queues the rejection for the webhooks receiving it, in the background so
+/// the client gets its error right away. Nothing is written when no webhook
+/// receives it.
+fn notify_rejection(state: &RouteState, rejection: DataRejection) {
+    let indexify_state = state.indexify_state.clone();
+    tokio::spawn(async move {
+        let event = WebhookEvent::data_rejected(&rejection);
+        let webhooks = match indexify_state.reader().list_webhooks(&rejection.namespace) {
+            Ok(webhooks) => webhooks,
+            Err(err) => {
+                tracing::error!("failed to list webhooks: {:?}", err);
+                return;
+            }
+        };
+        if !webhooks.iter().any(|webhook| webhook.receives(&event)) {
+            return;
+        }
+        let result = indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::RecordDataRejection(RecordDataRejectionRequest {
+                    rejection,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await;
+        if let Err(err) = result {
+            tracing::error!("failed to record data rejection: {:?}", err);
+        }
+    });
+}
+
+/// Maps a failed upload to a TooLarge error when the payload exceeded its
+/// limit, to an internal error otherwise.
+pub fn upload_error(
+    state: &RouteState,
+    limit: Option<&PayloadLimit>,
+    err: anyhow::Error,
+) -> IndexifyAPIError {
+    match err.downcast::<PayloadTooLarge>() {
+        Ok(too_large) => match limit {
+            Some(limit) => limit.reject(state, too_large),
+            None => IndexifyAPIError::payload_too_large(too_large.size_bytes, too_large.max_bytes),
+        },
+        Err(err) => {
+            tracing::error!("failed to write to blob store: {}", err);
+            IndexifyAPIError::internal_error(anyhow!("failed to write to blob store: {}", err))
+        }
+    }
+}
+
+/// Payloads rejected per graph since the server started.
+#[derive(Default)]
+pub struct RejectedPayloadsMetrics {
+    by_graph: Mutex<HashMap<(String, String), RejectedPayloads>>,
+}
+
+impl RejectedPayloadsMetrics {
+    fn record(&self, namespace: &str, compute_graph: &str, size_bytes: u64) {
+        let mut by_graph = self.by_graph.lock().unwrap();
+        let rejected = by_graph
+            .entry((namespace.to_string(), compute_graph.to_string()))
+            .or_insert_with(|| RejectedPayloads {
+                namespace: namespace.to_string(),
+                compute_graph: compute_graph.to_string(),
+                rejected: 0,
+                rejected_bytes: 0,
+                largest_bytes: 0,
+                last_rejected_at: 0,
+            });
+        rejected.rejected += 1;
+        rejected.rejected_bytes += size_bytes;
+        rejected.largest_bytes = rejected.largest_bytes.max(size_bytes);
+        rejected.last_rejected_at = get_epoch_time_in_ms();
+    }
+
+    fn list(&self) -> Vec<RejectedPayloads> {
+        let mut rejected: Vec<_> = self.by_graph.lock().unwrap().values().cloned().collect();
+        rejected.sort_by(|a, b| {
+            (&a.namespace, &a.compute_graph).cmp(&(&b.namespace, &b.compute_graph))
+        });
+        rejected
+    }
+}
+
+/// List the payloads rejected for exceeding their size limit, per graph
+#[utoipa::path(
+    get,
+    path = "/internal/metrics/rejected_payloads",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Rejected payloads per graph", body = RejectedPayloadsList),
+    ),
+)]
+pub async fn list_rejected_payloads(State(state): State<RouteState>) -> Json<RejectedPayloadsList> {
+    Json(RejectedPayloadsList {
+        rejected_payloads: state.rejected_payloads.list(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::stream;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_limit_stream() {
+        let limit = PayloadLimit::new("ns", "graph", 5);
+        let chunks = vec![Ok(Bytes::from("abc")), Ok(Bytes::from("de"))];
+        let received: Vec<_> = limit.limit_stream(stream::iter(chunks)).collect().await;
+        assert!(received.iter().all(|chunk| chunk.is_ok()));
+
+        let chunks = vec![Ok(Bytes::from("abc")), Ok(Bytes::from("def"))];
+        let received: Vec<_> = limit.limit_stream(stream::iter(chunks)).collect().await;
+        let too_large = received[1]
+            .as_ref()
+            .unwrap_err()
+            .downcast_ref::<PayloadTooLarge>()
+            .unwrap();
+        assert_eq!(too_large.size_bytes, 6);
+        assert_eq!(too_large.max_bytes, 5);
+    }
+}
@@ -0,0 +1,101 @@
+//! Request ids and the span API handlers log in. The span carries the request
+//! id along with the namespace, compute graph, invocation and task of the
+//! request, so the logs of a request can be correlated with the ones of the
+//! scheduler and store.
+
+use axum::{
+    extract::{MatchedPath, Request},
+    http::HeaderValue,
+    middleware::Next,
+    response::Response,
+};
+use nanoid::nanoid;
+use tracing::{field::Empty, Span};
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+// Path parameters recorded as fields of the request span
+const SPAN_PATH_PARAMS: [&str; 4] = ["namespace", "compute_graph", "invocation_id", "task_id"];
+
+/// Uses the request id sent by the client, or generates one, and returns it
+/// in the response.
+pub async fn assign_request_id(mut request: Request, next: Next) -> Response {
+    let request_id = match request.headers().get(REQUEST_ID_HEADER) {
+        Some(request_id) => request_id.clone(),
+        None => {
+            let request_id = HeaderValue::from_str(&nanoid!()).unwrap();
+            request
+                .headers_mut()
+                .insert(REQUEST_ID_HEADER, request_id.clone());
+            request_id
+        }
+    };
+    let mut response = next.run(request).await;
+    response.headers_mut().insert(REQUEST_ID_HEADER, request_id);
+    response
+}
+
+pub fn make_request_span(req: &Request) -> Span {
+    let method = req.method();
+    let uri = req.uri();
+    let request_id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|request_id| request_id.to_str().ok());
+    let matched_path = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|matched_path| matched_path.as_str());
+
+    let span = tracing::info_span!(
+        "request",
+        %method,
+        %uri,
+        matched_path,
+        request_id,
+        namespace = Empty,
+        compute_graph = Empty,
+        invocation_id = Empty,
+        task_id = Empty,
+    );
+    if let Some(matched_path) = matched_path {
+        for (name, value) in path_params(matched_path, uri.path()) {
+            if SPAN_PATH_PARAMS.contains(&name) {
+                span.record(name, value);
+            }
+        }
+    }
+    span
+}
+
+/// Values of the parameters of a matched route, e.g. `namespace` of
+/// `/namespaces/:namespace`.
+pub(super) fn path_params<'a>(matched_path: &'a str, path: &'a str) -> Vec<(&'a str, &'a str)> {
+    matched_path
+        .split('/')
+        .zip(path.split('/'))
+        .filter_map(|(template, value)| Some((template.strip_prefix(':')?, value)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_params() {
+        let params = path_params(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id",
+            "/namespaces/default/compute_graphs/graph_a/invocations/123",
+        );
+        assert_eq!(
+            params,
+            vec![
+                ("namespace", "default"),
+                ("compute_graph", "graph_a"),
+                ("invocation_id", "123"),
+            ]
+        );
+        assert!(path_params("/namespaces", "/namespaces").is_empty());
+    }
+}
@@ -0,0 +1,74 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use state_store::requests::{CreateComputeGraphRequest, RequestPayload, StateMachineUpdateRequest};
+use tracing::info;
+
+use super::{check_writable, RouteState};
+use crate::http_objects::{IndexifyAPIError, InstantiateComputeGraph};
+
+/// Create a compute graph from a template graph, binding the values of its
+/// parameters. The instance shares the code of the template.
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/instantiate",
+    tag = "operations",
+    request_body = InstantiateComputeGraph,
+    responses(
+        (status = 200, description = "the graph was instantiated"),
+        (status = 400, description = "unknown parameters"),
+        (status = 404, description = "the template graph doesn't exist"),
+        (status = CONFLICT, description = "the instance was updated since expected_revision"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn instantiate_compute_graph(
+    Path((namespace, compute_graph)): Path<(String, String)>,
+    State(state): State<RouteState>,
+    Json(request): Json<InstantiateComputeGraph>,
+) -> Result<(), IndexifyAPIError> {
+    check_writable(&state)?;
+    if request.name == compute_graph {
+        return Err(IndexifyAPIError::bad_request(
+            "a graph can't be instantiated under its own name",
+        ));
+    }
+    let template = state
+        .indexify_state
+        .reader()
+        .get_compute_graph(&namespace, &compute_graph)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::not_found("Compute Graph not found"))?;
+    let instance = data_model::ComputeGraph {
+        name: request.name,
+        description: request.description.unwrap_or(template.description.clone()),
+        version: Default::default(),
+        revision: 0,
+        created_at: 0,
+        deleted_at: None,
+        paused: None,
+        parameter_values: request.parameters,
+        template: Some(compute_graph),
+        ..template
+    };
+    instance
+        .validate()
+        .map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))?;
+    let name = instance.name.clone();
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                namespace,
+                compute_graph: instance,
+                expected_revision: request.expected_revision,
+                force: false,
+            }),
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    info!("compute graph instantiated: {}", name);
+    Ok(())
+}
@@ -0,0 +1,62 @@
+//! Route state of a server backed by a temporary state store and blob
+//! store, for the tests of the apis.
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use blob_store::{BlobStorage, BlobStorageConfig};
+use state_store::IndexifyState;
+use task_scheduler::TaskScheduler;
+use tempfile::TempDir;
+use tracing_subscriber::{reload, EnvFilter};
+
+use super::{invoke_url::UrlFetches, RouteState};
+use crate::{
+    config::{RuntimeConfig, ServerConfig},
+    config_reload::ConfigReloader,
+    download_urls::DownloadUrlSigner,
+    executors::ExecutorManager,
+    oidc::JwksCache,
+    task_tokens::TaskTokenSigner,
+};
+
+pub struct TestRouteState {
+    pub route_state: RouteState,
+    // Removed with the state store and the blobs when the test ends
+    _temp_dir: TempDir,
+}
+
+impl TestRouteState {
+    pub async fn new(runtime: RuntimeConfig) -> Result<Self> {
+        let temp_dir = TempDir::new()?;
+        let state_path = temp_dir.path().join("state");
+        let config = ServerConfig {
+            state_store_path: state_path.to_str().unwrap().to_string(),
+            blob_storage: BlobStorageConfig::new_disk(
+                temp_dir.path().join("blobs").to_str().unwrap(),
+            ),
+            runtime,
+            ..Default::default()
+        };
+        let indexify_state = IndexifyState::new(state_path).await?;
+        let (_, log_filter) = reload::Layer::new(EnvFilter::new(&config.runtime.log_level));
+        let route_state = RouteState {
+            indexify_state: indexify_state.clone(),
+            blob_storage: Arc::new(BlobStorage::new(config.blob_storage.clone())?),
+            executor_manager: Arc::new(ExecutorManager::new(indexify_state.clone()).await),
+            secrets_cipher: None,
+            cluster: None,
+            config_reloader: Arc::new(ConfigReloader::new(None, config.clone(), log_filter)),
+            download_url_signer: Arc::new(DownloadUrlSigner::new(&config.downloads)?),
+            task_tokens: Arc::new(TaskTokenSigner::new(&config.task_tokens)?),
+            rejected_payloads: Default::default(),
+            url_fetches: Arc::new(UrlFetches::new(config.url_fetch.clone())),
+            task_scheduler: Arc::new(TaskScheduler::new(indexify_state)),
+            jwks: Arc::new(JwksCache::default()),
+        };
+        Ok(Self {
+            route_state,
+            _temp_dir: temp_dir,
+        })
+    }
+}
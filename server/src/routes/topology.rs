@@ -0,0 +1,142 @@
+use std::collections::HashSet;
+
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use data_model::OutputPayload;
+
+use super::RouteState;
+use crate::http_objects::{
+    GraphTopology,
+    IndexifyAPIError,
+    PlannedNodeKind,
+    TopologyEdge,
+    TopologyNode,
+    TopologyParams,
+};
+
+/// Get the topology of a compute graph, to render it
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/topology",
+    params(
+        ("invocation_id" = Option<String>, Query, description = "Overlays the nodes and edges the invocation ran"),
+    ),
+    tag = "retrieve",
+    responses(
+        (status = 200, description = "Nodes and edges of the compute graph", body = GraphTopology),
+        (status = 404, description = "Compute Graph or invocation not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn get_topology(
+    Path((namespace, name)): Path<(String, String)>,
+    Query(params): Query<TopologyParams>,
+    State(state): State<RouteState>,
+) -> Result<Json<GraphTopology>, IndexifyAPIError> {
+    let reader = state.indexify_state.reader();
+    let compute_graph = reader
+        .get_compute_graph(&namespace, &name)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::not_found("Compute Graph not found"))?;
+
+    let (analytics, routed) = match &params.invocation_id {
+        Some(invocation_id) => {
+            let ctx = reader
+                .invocation_ctx(&namespace, &name, invocation_id)
+                .map_err(IndexifyAPIError::internal_error)?;
+            let (outputs, _) = reader
+                .list_outputs_by_compute_graph(&namespace, &name, invocation_id, None, None)
+                .map_err(IndexifyAPIError::internal_error)?;
+            // Router edges the invocation followed, from the outputs of its routers
+            let mut routed = HashSet::new();
+            for output in outputs {
+                if let OutputPayload::Router(router) = output.payload {
+                    for to in router.edges {
+                        routed.insert((output.compute_fn_name.clone(), to));
+                    }
+                }
+            }
+            (ctx.fn_task_analytics, Some(routed))
+        }
+        None => (
+            reader
+                .graph_task_analytics(&namespace, &name)
+                .map_err(IndexifyAPIError::internal_error)?,
+            None,
+        ),
+    };
+    let ran = |node: &str| {
+        analytics
+            .get(node)
+            .map_or(false, |analytics| analytics.total() > 0)
+    };
+
+    let edges = compute_graph
+        .topology_edges()
+        .into_iter()
+        .map(|edge| {
+            let executed = routed.as_ref().map(|routed| {
+                if edge.dynamic {
+                    routed.contains(&(edge.from.clone(), edge.to.clone()))
+                } else {
+                    ran(&edge.from) && ran(&edge.to)
+                }
+            });
+            TopologyEdge {
+                from: edge.from,
+                to: edge.to,
+                dynamic: edge.dynamic,
+                executed,
+            }
+        })
+        .collect();
+
+    let mut graph_nodes: Vec<&data_model::Node> = compute_graph.nodes.values().collect();
+    if !compute_graph
+        .nodes
+        .contains_key(compute_graph.start_fn.name())
+    {
+        graph_nodes.push(&compute_graph.start_fn);
+    }
+    graph_nodes.sort_by_key(|node| node.name());
+    let nodes = graph_nodes
+        .into_iter()
+        .map(|node| {
+            let (kind, router_targets) = match node {
+                data_model::Node::Compute(_) => (PlannedNodeKind::ComputeFn, vec![]),
+                data_model::Node::Router(router) => (
+                    PlannedNodeKind::DynamicRouter,
+                    router.target_functions.clone(),
+                ),
+                data_model::Node::SubGraph(_) => (PlannedNodeKind::SubGraph, vec![]),
+                data_model::Node::Gate(_) => (PlannedNodeKind::Gate, vec![]),
+                data_model::Node::Window(_) => (PlannedNodeKind::Window, vec![]),
+                data_model::Node::Sink(_) => (PlannedNodeKind::Sink, vec![]),
+            };
+            TopologyNode {
+                name: node.name().to_string(),
+                kind,
+                reducer: node.reducer(),
+                sub_graph: node.sub_graph().map(|sub_graph| sub_graph.to_string()),
+                router_targets,
+                executed: routed.as_ref().map(|_| ran(node.name())),
+                analytics: analytics
+                    .get(node.name())
+                    .cloned()
+                    .map(Into::into)
+                    .unwrap_or_default(),
+            }
+        })
+        .collect();
+
+    Ok(Json(GraphTopology {
+        compute_graph: compute_graph.name.clone(),
+        version: compute_graph.version.into(),
+        start_node: compute_graph.start_fn.name().to_string(),
+        nodes,
+        edges,
+        invocation_id: params.invocation_id,
+    }))
+}
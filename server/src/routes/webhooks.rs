@@ -0,0 +1,211 @@
+use std::{collections::BTreeSet, str::FromStr};
+
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use data_model::webhooks::WebhookEventType;
+use indexify_utils::get_epoch_time_in_ms;
+use nanoid::nanoid;
+use state_store::requests::{
+    CreateWebhookRequest,
+    DeleteWebhookRequest,
+    RedriveWebhookDeadLettersRequest,
+    RequestPayload,
+    StateMachineUpdateRequest,
+};
+
+use super::RouteState;
+use crate::{
+    http_objects::{
+        CreateWebhook,
+        IndexifyAPIError,
+        RedriveDeadLetters,
+        Webhook,
+        WebhookDeadLetters,
+        WebhooksList,
+    },
+    webhooks::generate_secret,
+};
+
+/// Register a webhook receiving events of the namespace or of one graph
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/webhooks",
+    request_body = CreateWebhook,
+    tag = "operations",
+    responses(
+        (status = 200, description = "Webhook created, with the secret its payloads are signed with", body = Webhook),
+        (status = BAD_REQUEST, description = "Invalid url or events"),
+        (status = NOT_FOUND, description = "Compute graph not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Unable to create webhook")
+    ),
+)]
+pub async fn create_webhook(
+    Path(namespace): Path<String>,
+    State(state): State<RouteState>,
+    Json(request): Json<CreateWebhook>,
+) -> Result<Json<Webhook>, IndexifyAPIError> {
+    if !request.url.starts_with("http://") && !request.url.starts_with("https://") {
+        return Err(IndexifyAPIError::bad_request(
+            "webhook url must be an http or https url",
+        ));
+    }
+    let events = request
+        .events
+        .iter()
+        .map(|event_type| WebhookEventType::from_str(event_type))
+        .collect::<anyhow::Result<BTreeSet<_>>>()
+        .map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))?;
+    if events.is_empty() {
+        return Err(IndexifyAPIError::bad_request(
+            "webhook must subscribe to at least one event",
+        ));
+    }
+    if let Some(compute_graph) = &request.compute_graph {
+        state
+            .indexify_state
+            .reader()
+            .get_compute_graph(&namespace, compute_graph)
+            .map_err(IndexifyAPIError::internal_error)?
+            .ok_or(IndexifyAPIError::not_found("compute graph not found"))?;
+    }
+    let webhook = data_model::webhooks::Webhook {
+        namespace,
+        id: nanoid!(),
+        url: request.url,
+        events,
+        secret: generate_secret().map_err(IndexifyAPIError::internal_error)?,
+        created_at: get_epoch_time_in_ms(),
+        compute_graph: request.compute_graph,
+    };
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: RequestPayload::CreateWebhook(CreateWebhookRequest {
+                webhook: webhook.clone(),
+            }),
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    let secret = webhook.secret.clone();
+    Ok(Json(Webhook {
+        secret: Some(secret),
+        ..webhook.into()
+    }))
+}
+
+/// List the webhooks of the namespace
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/webhooks",
+    tag = "operations",
+    responses(
+        (status = 200, description = "List webhooks", body = WebhooksList),
+        (status = INTERNAL_SERVER_ERROR, description = "Unable to list webhooks")
+    ),
+)]
+pub async fn list_webhooks(
+    Path(namespace): Path<String>,
+    State(state): State<RouteState>,
+) -> Result<Json<WebhooksList>, IndexifyAPIError> {
+    let webhooks = state
+        .indexify_state
+        .reader()
+        .list_webhooks(&namespace)
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(WebhooksList {
+        webhooks: webhooks.into_iter().map(Into::into).collect(),
+    }))
+}
+
+/// Delete a webhook, along with its undelivered events
+#[utoipa::path(
+    delete,
+    path = "/namespaces/{namespace}/webhooks/{id}",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Webhook deleted successfully"),
+        (status = 404, description = "Webhook not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Unable to delete webhook")
+    ),
+)]
+pub async fn delete_webhook(
+    Path((namespace, id)): Path<(String, String)>,
+    State(state): State<RouteState>,
+) -> Result<(), IndexifyAPIError> {
+    state
+        .indexify_state
+        .reader()
+        .get_webhook(&namespace, &id)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::not_found("Webhook not found"))?;
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: RequestPayload::DeleteWebhook(DeleteWebhookRequest { namespace, id }),
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(())
+}
+
+/// List the events of the namespace's webhooks which ran out of delivery
+/// attempts
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/webhooks/dead_letters",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Undeliverable events", body = WebhookDeadLetters),
+        (status = INTERNAL_SERVER_ERROR, description = "Unable to list dead letters")
+    ),
+)]
+pub async fn list_webhook_dead_letters(
+    Path(namespace): Path<String>,
+    State(state): State<RouteState>,
+) -> Result<Json<WebhookDeadLetters>, IndexifyAPIError> {
+    let dead_letters = state
+        .indexify_state
+        .reader()
+        .list_webhook_dead_letters(&namespace)
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(WebhookDeadLetters {
+        dead_letters: dead_letters.into_iter().map(Into::into).collect(),
+    }))
+}
+
+/// Queue the dead letters of the namespace's webhooks for delivery again
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/webhooks/dead_letters/redrive",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Dead letters queued for delivery", body = RedriveDeadLetters),
+        (status = INTERNAL_SERVER_ERROR, description = "Unable to redrive dead letters")
+    ),
+)]
+pub async fn redrive_webhook_dead_letters(
+    Path(namespace): Path<String>,
+    State(state): State<RouteState>,
+) -> Result<Json<RedriveDeadLetters>, IndexifyAPIError> {
+    let redriven = state
+        .indexify_state
+        .reader()
+        .list_webhook_dead_letters(&namespace)
+        .map_err(IndexifyAPIError::internal_error)?
+        .len();
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: RequestPayload::RedriveWebhookDeadLetters(RedriveWebhookDeadLettersRequest {
+                namespace,
+            }),
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(RedriveDeadLetters { redriven }))
+}
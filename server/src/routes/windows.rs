@@ -0,0 +1,36 @@
+use axum::extract::{Path, State};
+use state_store::requests::{CloseWindowRequest, RequestPayload, StateMachineUpdateRequest};
+
+use super::{check_writable, RouteState};
+use crate::http_objects::IndexifyAPIError;
+
+/// Close a window of a compute graph before it's full, the inputs waiting in
+/// the window are emitted together
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/windows/{window}/close",
+    tag = "operations",
+    responses(
+        (status = 200, description = "the window was closed"),
+        (status = 404, description = "the compute graph has no such window"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn close_window(
+    Path((namespace, compute_graph, window)): Path<(String, String, String)>,
+    State(state): State<RouteState>,
+) -> Result<(), IndexifyAPIError> {
+    check_writable(&state)?;
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: RequestPayload::CloseWindow(CloseWindowRequest {
+                namespace,
+                compute_graph,
+                window,
+            }),
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(IndexifyAPIError::internal_error)
+}
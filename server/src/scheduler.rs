@@ -1,9 +1,22 @@
-use std::{sync::Arc, vec};
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    vec,
+};
 
 use anyhow::{anyhow, Result};
-use data_model::{ChangeType, StateChangeId};
+use data_model::{
+    blacklist::ExecutorBlacklistEntry,
+    ChangeType,
+    StateChange,
+    StateChangeId,
+    TaskFinishedEvent,
+};
 use state_store::{
     requests::{
+        BlacklistExecutorsRequest,
         CreateTasksRequest,
         ReductionTasks,
         RequestPayload,
@@ -13,15 +26,38 @@ use state_store::{
     IndexifyState,
 };
 use task_scheduler::{
-    task_creator::{handle_invoke_compute_graph, handle_task_finished},
+    task_creator::{
+        handle_invoke_compute_graph,
+        handle_task_finished,
+        handle_task_outputs_streamed,
+        resume_fan_out,
+        sub_graph_invocations,
+    },
+    TaskCreationResult,
     TaskScheduler,
 };
 use tokio::{self, sync::watch::Receiver};
-use tracing::{error, info};
+use tracing::{error, info, info_span, Instrument, Span};
+
+use crate::{
+    config::{default_fan_out_batch_size, RuntimeConfig},
+    latency::LatencyTracker,
+    sinks::SinkWriters,
+    wasm::WasmRouters,
+};
 
 pub struct Scheduler {
     indexify_state: Arc<IndexifyState>,
     task_allocator: Arc<TaskScheduler>,
+    fan_out_batch_size: AtomicUsize,
+    // Set in maintenance mode, tasks are created but not allocated
+    allocations_paused: AtomicBool,
+    // Runs the tasks of WASM routers instead of allocating them
+    wasm_routers: Option<Arc<WasmRouters>>,
+    // Writes the inputs of sinks to their index instead of allocating them
+    sink_writers: Option<Arc<SinkWriters>>,
+    // Records the latencies of the finished tasks
+    latencies: Option<Arc<LatencyTracker>>,
 }
 
 impl Scheduler {
@@ -30,71 +66,190 @@ impl Scheduler {
         Self {
             indexify_state,
             task_allocator,
+            fan_out_batch_size: AtomicUsize::new(default_fan_out_batch_size()),
+            allocations_paused: AtomicBool::new(false),
+            wasm_routers: None,
+            sink_writers: None,
+            latencies: None,
         }
     }
 
+    /// Shares the task allocator, and its bookkeeping, with the API which
+    /// explains why tasks are pending.
+    pub fn with_task_allocator(mut self, task_allocator: Arc<TaskScheduler>) -> Self {
+        self.task_allocator = task_allocator;
+        self
+    }
+
+    pub fn with_wasm_routers(mut self, wasm_routers: Arc<WasmRouters>) -> Self {
+        self.wasm_routers = Some(wasm_routers);
+        self
+    }
+
+    pub fn with_sink_writers(mut self, sink_writers: Arc<SinkWriters>) -> Self {
+        self.sink_writers = Some(sink_writers);
+        self
+    }
+
+    pub fn with_latency_tracker(mut self, latencies: Arc<LatencyTracker>) -> Self {
+        self.latencies = Some(latencies);
+        self
+    }
+
+    pub fn set_fan_out_batch_size(&self, fan_out_batch_size: usize) {
+        self.fan_out_batch_size
+            .store(fan_out_batch_size, Ordering::Relaxed);
+    }
+
+    pub fn set_allocations_paused(&self, paused: bool) {
+        self.allocations_paused.store(paused, Ordering::Relaxed);
+    }
+
     pub async fn run_scheduler(&self) -> Result<()> {
         let state_changes = self
             .indexify_state
             .reader()
             .get_unprocessed_state_changes()?;
+        let fan_out_batch_size = self.fan_out_batch_size.load(Ordering::Relaxed);
+        let mut task_creation_results = vec![];
         let mut create_task_requests = vec![];
         let mut processed_state_changes = vec![];
         let mut new_reduction_tasks = vec![];
         let mut processed_reduction_tasks = vec![];
         let mut diagnostic_msgs = vec![];
+        let mut new_sub_graph_invocations = vec![];
+        let mut new_tasks = vec![];
+        let mut blacklist_entries = vec![];
         for state_change in &state_changes {
             processed_state_changes.push(state_change.id.clone());
+            let span = state_change_span(state_change);
             let result = match &state_change.change_type {
-                ChangeType::InvokeComputeGraph(invoke_compute_graph_event) => Some(
+                ChangeType::InvokeComputeGraph(invoke_compute_graph_event) => vec![
                     handle_invoke_compute_graph(
                         self.indexify_state.clone(),
                         invoke_compute_graph_event.clone(),
                     )
+                    .instrument(span)
                     .await?,
-                ),
-                ChangeType::TaskFinished(task_finished_event) => {
-                    let task = self
-                        .indexify_state
-                        .reader()
-                        .get_task_from_finished_event(&task_finished_event)?
-                        .ok_or(anyhow!("task not found {}", task_finished_event.task_id))?;
+                ],
+                ChangeType::TaskFinished(task_finished_event) => vec![
+                    self.handle_task_finished(
+                        task_finished_event,
+                        fan_out_batch_size,
+                        &mut blacklist_entries,
+                    )
+                    .instrument(span)
+                    .await?,
+                ],
+                ChangeType::TasksFinished(event) => {
+                    let mut results = Vec::with_capacity(event.tasks.len());
+                    for task_finished_event in &event.tasks {
+                        results.push(
+                            self.handle_task_finished(
+                                task_finished_event,
+                                fan_out_batch_size,
+                                &mut blacklist_entries,
+                            )
+                            .instrument(task_finished_span(&state_change.id, task_finished_event))
+                            .await?,
+                        );
+                    }
+                    results
+                }
+                ChangeType::TaskOutputsStreamed(event) => {
                     let compute_graph = self
                         .indexify_state
                         .reader()
-                        .get_compute_graph(&task.namespace, &task.compute_graph_name)?
+                        .get_invocation_compute_graph(
+                            &event.namespace,
+                            &event.compute_graph,
+                            &event.invocation_id,
+                        )?
                         .ok_or(anyhow!("compute graph not found"))?;
-                    Some(
-                        handle_task_finished(self.indexify_state.clone(), task, compute_graph)
-                            .await?,
-                    )
+                    vec![
+                        handle_task_outputs_streamed(
+                            self.indexify_state.clone(),
+                            event.clone(),
+                            compute_graph,
+                        )
+                        .instrument(span)
+                        .await?,
+                    ]
                 }
-                _ => None,
+                _ => vec![],
             };
-            if let Some(result) = result {
-                let request = CreateTasksRequest {
-                    namespace: result.namespace.clone(),
-                    invocation_id: result.invocation_id.clone(),
-                    compute_graph: result.compute_graph.clone(),
-                    tasks: result.tasks,
-                };
-                create_task_requests.push(request);
-                new_reduction_tasks.extend(result.new_reduction_tasks);
-                processed_reduction_tasks.extend(result.processed_reduction_tasks);
+            task_creation_results.extend(result);
+        }
+        // Continue the fan outs whose previous batch was written by an earlier
+        // run, new fan outs are persisted by this run. The fan outs of paused
+        // graphs continue once they are resumed.
+        for cursor in self.indexify_state.reader().fan_out_cursors()? {
+            if self
+                .indexify_state
+                .reader()
+                .is_compute_graph_paused(&cursor.namespace, &cursor.compute_graph)?
+            {
+                continue;
             }
+            task_creation_results.push(
+                resume_fan_out(self.indexify_state.clone(), cursor, fan_out_batch_size).await?,
+            );
+        }
+        for result in task_creation_results {
+            new_sub_graph_invocations.extend(sub_graph_invocations(
+                self.indexify_state.clone(),
+                &result.tasks,
+            )?);
+            if self.wasm_routers.is_some() || self.sink_writers.is_some() {
+                new_tasks.extend(result.tasks.iter().cloned());
+            }
+            let request = CreateTasksRequest {
+                namespace: result.namespace.clone(),
+                invocation_id: result.invocation_id.clone(),
+                compute_graph: result.compute_graph.clone(),
+                tasks: result.tasks,
+                fan_out_cursor: result.fan_out_cursor,
+            };
+            create_task_requests.push(request);
+            new_reduction_tasks.extend(result.new_reduction_tasks);
+            processed_reduction_tasks.extend(result.processed_reduction_tasks);
+        }
+        // Written before the tasks are allocated so that this run already
+        // skips the blacklisted executors
+        if !blacklist_entries.is_empty() {
+            self.indexify_state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::BlacklistExecutors(BlacklistExecutorsRequest {
+                        entries: blacklist_entries,
+                    }),
+                    state_changes_processed: vec![],
+                })
+                .await?;
         }
         let mut new_allocations = vec![];
-        for state_change in &state_changes {
-            let task_placement_result = match state_change.change_type {
+        let mut new_preemptions = vec![];
+        // Finished tasks release the GPUs they reserved. Unplaced tasks are
+        // scheduled once per run since the allocations of this run are only
+        // written at the end of it.
+        let allocate = state_changes.iter().any(|state_change| {
+            matches!(
+                state_change.change_type,
                 ChangeType::TaskCreated |
-                ChangeType::ExecutorAdded |
-                ChangeType::ExecutorRemoved => Some(self.task_allocator.schedule_unplaced_tasks()?),
-                _ => None,
-            };
-            if let Some(task_placement_result) = task_placement_result {
-                new_allocations.extend(task_placement_result.task_placements);
-                diagnostic_msgs.extend(task_placement_result.diagnostic_msgs);
-            }
+                    ChangeType::TaskFinished(_) |
+                    ChangeType::TasksFinished(_) |
+                    ChangeType::ExecutorAdded |
+                    ChangeType::ExecutorRemoved |
+                    ChangeType::ExecutorUpdated |
+                    ChangeType::ExecutorPoolUpdated |
+                    ChangeType::TasksRequeued(_) |
+                    ChangeType::GraphResumed
+            )
+        });
+        if allocate && !self.allocations_paused.load(Ordering::Relaxed) {
+            let task_placement_result = self.task_allocator.schedule_unplaced_tasks()?;
+            new_allocations.extend(task_placement_result.task_placements);
+            new_preemptions.extend(task_placement_result.preemptions);
+            diagnostic_msgs.extend(task_placement_result.diagnostic_msgs);
         }
 
         let scheduler_update_request = StateMachineUpdateRequest {
@@ -106,18 +261,129 @@ impl Scheduler {
                     processed_reduction_tasks,
                 },
                 diagnostic_msgs,
+                sub_graph_invocations: new_sub_graph_invocations,
+                preemptions: new_preemptions,
             }),
             state_changes_processed: processed_state_changes,
         };
-        self.indexify_state.write(scheduler_update_request).await
+        self.indexify_state.write(scheduler_update_request).await?;
+        if let Some(wasm_routers) = &self.wasm_routers {
+            wasm_routers.spawn_tasks(new_tasks.clone())?;
+        }
+        if let Some(sink_writers) = &self.sink_writers {
+            sink_writers.spawn_tasks(new_tasks)?;
+        }
+        Ok(())
+    }
+
+    /// Creates the downstream tasks of a finished task, and records its
+    /// outcome on its executor for the blacklist.
+    async fn handle_task_finished(
+        &self,
+        task_finished_event: &TaskFinishedEvent,
+        fan_out_batch_size: usize,
+        blacklist_entries: &mut Vec<ExecutorBlacklistEntry>,
+    ) -> Result<TaskCreationResult> {
+        let task = self
+            .indexify_state
+            .reader()
+            .get_task_from_finished_event(task_finished_event)?
+            .ok_or(anyhow!("task not found {}", task_finished_event.task_id))?;
+        if let Some(latencies) = &self.latencies {
+            latencies.record(&task);
+        }
+        if let Some(executor_id) = &task_finished_event.executor_id {
+            blacklist_entries.extend(
+                self.task_allocator
+                    .record_task_outcome(&task, executor_id)?,
+            );
+        }
+        let compute_graph = self
+            .indexify_state
+            .reader()
+            .get_compute_graph_version(
+                &task.namespace,
+                &task.compute_graph_name,
+                &task.graph_version,
+            )?
+            .ok_or(anyhow!("compute graph not found"))?;
+        handle_task_finished(
+            self.indexify_state.clone(),
+            task,
+            compute_graph,
+            fan_out_batch_size,
+        )
+        .await
+    }
+
+    // Allocates tasks which were held back without waiting for a state change
+    pub async fn allocate_unplaced_tasks(&self) -> Result<()> {
+        if self.allocations_paused.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        let task_placement_result = self.task_allocator.schedule_unplaced_tasks()?;
+        if task_placement_result.task_placements.is_empty() &&
+            task_placement_result.preemptions.is_empty()
+        {
+            return Ok(());
+        }
+        self.indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::SchedulerUpdate(SchedulerUpdateRequest {
+                    task_requests: vec![],
+                    allocations: task_placement_result.task_placements,
+                    reduction_tasks: ReductionTasks::default(),
+                    diagnostic_msgs: task_placement_result.diagnostic_msgs,
+                    sub_graph_invocations: vec![],
+                    preemptions: task_placement_result.preemptions,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await
     }
 
     pub async fn start(
         &self,
         mut shutdown_rx: Receiver<()>,
         mut state_watcher_rx: Receiver<StateChangeId>,
+        mut runtime_config_rx: Receiver<RuntimeConfig>,
     ) -> Result<()> {
+        let runtime_config = runtime_config_rx.borrow_and_update().clone();
+        self.task_allocator
+            .set_default_rate_limit(runtime_config.default_fn_rate_limit);
+        self.task_allocator
+            .set_allocation_config(&runtime_config.allocation);
+        self.task_allocator
+            .set_preemption_config(runtime_config.preemption.as_ref());
+        self.task_allocator
+            .set_blacklist_config(runtime_config.executor_blacklist.as_ref());
+        self.task_allocator
+            .set_priority_aging_config(runtime_config.priority_aging.as_ref());
+        self.task_allocator.set_lane_config(runtime_config.scheduling_lanes.as_ref());
+        self.set_fan_out_batch_size(runtime_config.fan_out_batch_size);
+        self.set_allocations_paused(runtime_config.maintenance_mode);
+        if let Some(wasm_routers) = &self.wasm_routers {
+            if let Err(err) = wasm_routers.resume() {
+                error!("error resuming wasm router tasks: {:?}", err);
+            }
+        }
+        if let Some(sink_writers) = &self.sink_writers {
+            if let Err(err) = sink_writers.resume() {
+                error!("error resuming sink tasks: {:?}", err);
+            }
+        }
+        // Resumes the fan outs interrupted by a restart
+        if let Err(err) = self.run_scheduler().await {
+            error!("error processing and distributing work: {:?}", err);
+        }
         loop {
+            let retry_after = self.task_allocator.rate_limit_retry_after();
+            let rate_limit_retry = async {
+                match retry_after {
+                    Some(retry_after) => tokio::time::sleep(retry_after).await,
+                    None => std::future::pending().await,
+                }
+            };
             tokio::select! {
                 _ = state_watcher_rx.changed() => {
                        let _state_change = *state_watcher_rx.borrow_and_update();
@@ -125,6 +391,30 @@ impl Scheduler {
                               error!("error processing and distributing work: {:?}", err);
                        }
                 },
+                Ok(_) = runtime_config_rx.changed() => {
+                    let runtime_config = runtime_config_rx.borrow_and_update().clone();
+                    self.task_allocator
+                        .set_default_rate_limit(runtime_config.default_fn_rate_limit);
+                    self.task_allocator
+                        .set_allocation_config(&runtime_config.allocation);
+                    self.task_allocator
+                        .set_preemption_config(runtime_config.preemption.as_ref());
+                    self.task_allocator
+                        .set_blacklist_config(runtime_config.executor_blacklist.as_ref());
+                    self.task_allocator
+                        .set_priority_aging_config(runtime_config.priority_aging.as_ref());
+                    self.task_allocator.set_lane_config(runtime_config.scheduling_lanes.as_ref());
+                    self.set_fan_out_batch_size(runtime_config.fan_out_batch_size);
+                    self.set_allocations_paused(runtime_config.maintenance_mode);
+                    if let Err(err) = self.allocate_unplaced_tasks().await {
+                        error!("error allocating tasks after config reload: {:?}", err);
+                    }
+                },
+                _ = rate_limit_retry => {
+                    if let Err(err) = self.allocate_unplaced_tasks().await {
+                        error!("error allocating rate limited tasks: {:?}", err);
+                    }
+                },
                 _ = shutdown_rx.changed() => {
                     info!("scheduler shutting down");
                     break;
@@ -135,21 +425,86 @@ impl Scheduler {
     }
 }
 
+/// Span the handling of a state change is logged in, with the invocation and
+/// task it concerns.
+fn state_change_span(state_change: &StateChange) -> Span {
+    match &state_change.change_type {
+        ChangeType::InvokeComputeGraph(event) => info_span!(
+            "state_change",
+            id = %state_change.id,
+            namespace = event.namespace,
+            compute_graph = event.compute_graph,
+            invocation_id = event.invocation_id,
+        ),
+        ChangeType::TaskFinished(event) => task_finished_span(&state_change.id, event),
+        ChangeType::TaskOutputsStreamed(event) => info_span!(
+            "state_change",
+            id = %state_change.id,
+            namespace = event.namespace,
+            compute_graph = event.compute_graph,
+            invocation_id = event.invocation_id,
+            task_id = %event.task_id,
+        ),
+        _ => info_span!("state_change", id = %state_change.id),
+    }
+}
+
+fn task_finished_span(id: &StateChangeId, event: &TaskFinishedEvent) -> Span {
+    info_span!(
+        "state_change",
+        %id,
+        namespace = event.namespace,
+        compute_graph = event.compute_graph,
+        invocation_id = event.invocation_id,
+        task_id = %event.task_id,
+    )
+}
+
 #[cfg(test)]
 mod tests {
-    use std::time::Duration;
+    use std::{collections::HashMap, time::Duration};
 
     use data_model::{
         test_objects::tests::{
             mock_executor,
             mock_executor_id,
+            mock_graph_a,
+            mock_graph_b,
+            mock_invocation_payload,
             mock_invocation_payload_graph_b,
+            mock_node_fn_output_fn_a,
             TEST_NAMESPACE,
         },
+        ComputeGraph,
+        DataPayload,
         ExecutorId,
+        ExecutorMetadata,
+        GpuDevice,
+        GpuRequirement,
+        InvocationPayloadBuilder,
+        InvocationStatus,
+        Node,
+        RateLimit,
+        RouterErrorPolicy,
+        SchedulingLane,
+        Task,
         TaskOutcome,
     };
-    use state_store::test_state_store::tests::TestStateStore;
+    use state_store::{
+        requests::{
+            CreateComputeGraphRequest,
+            InvokeComputeGraphRequest,
+            PauseComputeGraphRequest,
+            ResumeComputeGraphRequest,
+            StreamTaskOutputsRequest,
+        },
+        test_state_store::tests::TestStateStore,
+    };
+    use task_scheduler::{
+        explain::PendingReason,
+        lanes::LaneConfig,
+        preemption::PreemptionConfig,
+    };
 
     use super::*;
     use crate::executors::{self, ExecutorManager};
@@ -212,6 +567,103 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn create_fan_out_tasks_in_batches() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let scheduler = Scheduler::new(indexify_state.clone());
+        scheduler.set_fan_out_batch_size(3);
+        let invocation_id = state_store.with_simple_graph().await;
+        scheduler.run_scheduler().await?;
+        let list_tasks = || {
+            indexify_state
+                .reader()
+                .list_tasks_by_compute_graph(TEST_NAMESPACE, "graph_A", &invocation_id, None, None)
+                .unwrap()
+                .0
+        };
+        let tasks = list_tasks();
+        assert_eq!(tasks.len(), 1);
+
+        // 5 outputs fan out to fn_b and fn_c
+        state_store
+            .finalize_task(&tasks[0], 5, TaskOutcome::Success, false)
+            .await?;
+        scheduler.run_scheduler().await?;
+        assert_eq!(list_tasks().len(), 1 + 3);
+        assert_eq!(indexify_state.reader().fan_out_cursors()?.len(), 1);
+
+        // Every run creates the next batch until the fan out is done
+        for expected_tasks in [7, 10, 11] {
+            scheduler.run_scheduler().await?;
+            assert_eq!(list_tasks().len(), expected_tasks);
+        }
+        assert!(indexify_state.reader().fan_out_cursors()?.is_empty());
+        let invocation_ctx =
+            indexify_state
+                .reader()
+                .invocation_ctx(TEST_NAMESPACE, "graph_A", &invocation_id)?;
+        assert!(!invocation_ctx.completed);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn create_tasks_for_streamed_outputs() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let scheduler = Scheduler::new(indexify_state.clone());
+        let invocation_id = state_store.with_simple_graph().await;
+        scheduler.run_scheduler().await?;
+        let tasks = indexify_state
+            .reader()
+            .list_tasks_by_compute_graph(TEST_NAMESPACE, "graph_A", &invocation_id, None, None)
+            .unwrap()
+            .0;
+        assert_eq!(tasks.len(), 1);
+        let task = tasks[0].clone();
+
+        // Downstream tasks are created while fn_a is still running
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::StreamTaskOutputs(StreamTaskOutputsRequest {
+                    namespace: task.namespace.clone(),
+                    compute_graph: task.compute_graph_name.clone(),
+                    compute_fn: task.compute_fn_name.clone(),
+                    invocation_id: task.invocation_id.clone(),
+                    task_id: task.id.clone(),
+                    node_outputs: vec![mock_node_fn_output_fn_a(&invocation_id, "graph_A", None)],
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        scheduler.run_scheduler().await?;
+        let tasks = indexify_state
+            .reader()
+            .list_tasks_by_compute_graph(TEST_NAMESPACE, "graph_A", &invocation_id, None, None)
+            .unwrap()
+            .0;
+        assert_eq!(tasks.len(), 3);
+
+        // Finishing the task does not create tasks for the streamed output again
+        state_store
+            .finalize_task(&task, 0, TaskOutcome::Success, false)
+            .await?;
+        scheduler.run_scheduler().await?;
+        let tasks = indexify_state
+            .reader()
+            .list_tasks_by_compute_graph(TEST_NAMESPACE, "graph_A", &invocation_id, None, None)
+            .unwrap()
+            .0;
+        assert_eq!(tasks.len(), 3);
+        let invocation_ctx =
+            indexify_state
+                .reader()
+                .invocation_ctx(TEST_NAMESPACE, "graph_A", &invocation_id)?;
+        assert!(!invocation_ctx.completed);
+        assert_eq!(invocation_ctx.outstanding_tasks, 2);
+        Ok(())
+    }
+
     #[tokio::test]
     async fn handle_failed_tasks() -> Result<()> {
         let state_store = TestStateStore::new().await?;
@@ -253,6 +705,16 @@ mod tests {
 
         assert!(invocation_ctx.completed);
 
+        let result = indexify_state
+            .reader()
+            .invocation_result(TEST_NAMESPACE, "graph_A", &invocation_id)?
+            .unwrap();
+        assert_eq!(result.status, InvocationStatus::Failure);
+        assert!(result.outputs.is_empty());
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(result.errors[0].compute_fn, "fn_a");
+        assert_eq!(result.errors[0].task_id, task.id);
+
         Ok(())
     }
 
@@ -313,6 +775,544 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_paused_graph_tasks_are_not_allocated() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let scheduler = Scheduler::new(indexify_state.clone());
+        let ex = Arc::new(ExecutorManager::new(indexify_state.clone()).await);
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::PauseComputeGraph(PauseComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: "graph_A".to_string(),
+                    reject_invocations: false,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await
+            .expect_err("the graph doesn't exist yet");
+        state_store.with_simple_graph().await;
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::PauseComputeGraph(PauseComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: "graph_A".to_string(),
+                    reject_invocations: false,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        ex.register_executor(mock_executor()).await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+
+        // The task is created but held back while the graph is paused
+        assert_eq!(indexify_state.reader().unallocated_tasks()?.len(), 1);
+        assert!(indexify_state
+            .reader()
+            .get_tasks_by_executor(&mock_executor_id(), 10)?
+            .is_empty());
+
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::ResumeComputeGraph(ResumeComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: "graph_A".to_string(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+
+        assert!(indexify_state.reader().unallocated_tasks()?.is_empty());
+        assert_eq!(
+            indexify_state
+                .reader()
+                .get_tasks_by_executor(&mock_executor_id(), 10)?
+                .len(),
+            1
+        );
+        assert!(!indexify_state
+            .reader()
+            .is_compute_graph_paused(TEST_NAMESPACE, "graph_A")?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_gpu_tasks_are_not_co_scheduled() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let scheduler = Scheduler::new(indexify_state.clone());
+        let ex = Arc::new(ExecutorManager::new(indexify_state.clone()).await);
+
+        // fn_b and fn_c both need the single GPU of the executor
+        let mut graph = mock_graph_a();
+        for name in ["fn_b", "fn_c"] {
+            if let Some(Node::Compute(compute_fn)) = graph.nodes.get_mut(name) {
+                compute_fn.gpu = Some(GpuRequirement {
+                    model: Some("A10G".to_string()),
+                    count: 1,
+                });
+            }
+        }
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: graph,
+                    expected_revision: None,
+                    force: false,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph_name: "graph_A".to_string(),
+                    invocation_payload: mock_invocation_payload(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        ex.register_executor(ExecutorMetadata {
+            gpus: vec![GpuDevice {
+                id: "0".to_string(),
+                model: "A10G".to_string(),
+                free_memory_mb: 24_000,
+            }],
+            ..mock_executor()
+        })
+        .await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+
+        let executor_tasks = indexify_state
+            .reader()
+            .get_tasks_by_executor(&mock_executor_id(), 10)?;
+        assert_eq!(executor_tasks.len(), 1);
+        assert!(executor_tasks[0].gpu_ids.is_empty());
+        assert_eq!(
+            indexify_state.reader().allocated_task_ids()?[&mock_executor_id()],
+            vec![executor_tasks[0].id.clone()]
+        );
+        state_store
+            .finalize_task(&executor_tasks[0], 1, TaskOutcome::Success, false)
+            .await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+
+        // Only one of the GPU tasks is allocated until the GPU is released
+        let executor_tasks = indexify_state
+            .reader()
+            .get_tasks_by_executor(&mock_executor_id(), 10)?;
+        assert_eq!(executor_tasks.len(), 1);
+        assert_eq!(executor_tasks[0].gpu_ids, vec!["0".to_string()]);
+        assert_eq!(indexify_state.reader().unallocated_tasks()?.len(), 1);
+
+        let first_fn = executor_tasks[0].compute_fn_name.clone();
+        state_store
+            .finalize_task(&executor_tasks[0], 0, TaskOutcome::Success, false)
+            .await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+
+        let executor_tasks = indexify_state
+            .reader()
+            .get_tasks_by_executor(&mock_executor_id(), 10)?;
+        assert_eq!(executor_tasks.len(), 1);
+        assert_ne!(executor_tasks[0].compute_fn_name, first_fn);
+        assert_eq!(executor_tasks[0].gpu_ids, vec!["0".to_string()]);
+        assert!(indexify_state.reader().unallocated_tasks()?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_executor_outstanding_task_window() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let scheduler = Scheduler::new(indexify_state.clone());
+        let ex = Arc::new(ExecutorManager::new(indexify_state.clone()).await);
+        state_store.with_simple_graph().await;
+        ex.register_executor(ExecutorMetadata {
+            max_outstanding_tasks: Some(1),
+            ..mock_executor()
+        })
+        .await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+
+        let executor_tasks = indexify_state
+            .reader()
+            .get_tasks_by_executor(&mock_executor_id(), 10)?;
+        assert_eq!(executor_tasks.len(), 1);
+        state_store
+            .finalize_task(&executor_tasks[0], 1, TaskOutcome::Success, false)
+            .await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+
+        // fn_b and fn_c are allocated one at a time
+        let executor_tasks = indexify_state
+            .reader()
+            .get_tasks_by_executor(&mock_executor_id(), 10)?;
+        assert_eq!(executor_tasks.len(), 1);
+        assert_eq!(indexify_state.reader().unallocated_tasks()?.len(), 1);
+
+        let first_fn = executor_tasks[0].compute_fn_name.clone();
+        state_store
+            .finalize_task(&executor_tasks[0], 0, TaskOutcome::Success, false)
+            .await?;
+        // The executor still reports the finished task as queued
+        ex.heartbeat(mock_executor_id(), Default::default(), 1, Some(1))
+            .await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+        assert!(indexify_state
+            .reader()
+            .get_tasks_by_executor(&mock_executor_id(), 10)?
+            .is_empty());
+
+        ex.heartbeat(mock_executor_id(), Default::default(), 0, Some(1))
+            .await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+        let executor_tasks = indexify_state
+            .reader()
+            .get_tasks_by_executor(&mock_executor_id(), 10)?;
+        assert_eq!(executor_tasks.len(), 1);
+        assert_ne!(executor_tasks[0].compute_fn_name, first_fn);
+        assert!(indexify_state.reader().unallocated_tasks()?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_explain_pending_task() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let task_allocator = Arc::new(TaskScheduler::new(indexify_state.clone()));
+        let scheduler =
+            Scheduler::new(indexify_state.clone()).with_task_allocator(task_allocator.clone());
+        let ex = Arc::new(ExecutorManager::new(indexify_state.clone()).await);
+        state_store.with_simple_graph().await;
+        schedule_all(&indexify_state, &scheduler).await?;
+
+        let tasks = indexify_state.reader().unallocated_tasks()?;
+        assert_eq!(tasks.len(), 1);
+        let explanation = task_allocator
+            .explain_pending_task(&tasks[0].key())?
+            .unwrap();
+        assert_eq!(explanation.reason, PendingReason::NoMatchingExecutor);
+
+        ex.register_executor(ExecutorMetadata {
+            max_outstanding_tasks: Some(1),
+            ..mock_executor()
+        })
+        .await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+        let explanation = task_allocator
+            .explain_pending_task(&tasks[0].key())?
+            .unwrap();
+        assert_eq!(explanation.reason, PendingReason::NotPending);
+
+        // fn_b and fn_c are allocated one at a time
+        let executor_tasks = indexify_state
+            .reader()
+            .get_tasks_by_executor(&mock_executor_id(), 10)?;
+        state_store
+            .finalize_task(&executor_tasks[0], 1, TaskOutcome::Success, false)
+            .await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+        let tasks = indexify_state.reader().unallocated_tasks()?;
+        assert_eq!(tasks.len(), 1);
+        let explanation = task_allocator
+            .explain_pending_task(&tasks[0].key())?
+            .unwrap();
+        assert_eq!(explanation.reason, PendingReason::CapacityExhausted);
+        assert_eq!(explanation.candidates, vec![mock_executor_id()]);
+
+        assert!(task_allocator.explain_pending_task(b"missing")?.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_batch_lane_leaves_reserved_slots_to_latency_lane() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let task_allocator = Arc::new(TaskScheduler::new(indexify_state.clone()));
+        task_allocator.set_lane_config(Some(&LaneConfig {
+            reserved_latency_slots: 1,
+        }));
+        let scheduler =
+            Scheduler::new(indexify_state.clone()).with_task_allocator(task_allocator.clone());
+        let ex = Arc::new(ExecutorManager::new(indexify_state.clone()).await);
+        ex.register_executor(ExecutorMetadata {
+            max_outstanding_tasks: Some(2),
+            ..mock_executor()
+        })
+        .await?;
+
+        // Invocations of the graph run in the batch lane by default
+        let graph = ComputeGraph {
+            default_lane: SchedulingLane::Batch,
+            ..mock_graph_a()
+        };
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: graph,
+                    expected_revision: None,
+                    force: false,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let invoke = |path: &str, lane: Option<SchedulingLane>| {
+            let invocation_payload = InvocationPayloadBuilder::default()
+                .namespace(TEST_NAMESPACE.to_string())
+                .compute_graph_name("graph_A".to_string())
+                .payload(DataPayload {
+                    path: path.to_string(),
+                    size: 23,
+                    sha256_hash: "hash".to_string(),
+                    tier: Default::default(),
+                    mime_type: None,
+                    content_encoding: None,
+                    metadata: Default::default(),
+                    replicas: Vec::new(),
+                    compression: None,
+                })
+                .lane(lane)
+                .build()
+                .unwrap();
+            StateMachineUpdateRequest {
+                payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph_name: "graph_A".to_string(),
+                    invocation_payload,
+                }),
+                state_changes_processed: vec![],
+            }
+        };
+        indexify_state.write(invoke("backfill_1", None)).await?;
+        indexify_state.write(invoke("backfill_2", None)).await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+
+        // The last slot of the executor is reserved for the latency lane
+        let executor_tasks = indexify_state
+            .reader()
+            .get_tasks_by_executor(&mock_executor_id(), 10)?;
+        assert_eq!(executor_tasks.len(), 1);
+        assert_eq!(executor_tasks[0].lane, SchedulingLane::Batch);
+        let pending = indexify_state.reader().unallocated_tasks()?;
+        assert_eq!(pending.len(), 1);
+        let explanation = task_allocator
+            .explain_pending_task(&pending[0].key())?
+            .unwrap();
+        assert_eq!(explanation.reason, PendingReason::ReservedForLatencyLane);
+
+        indexify_state
+            .write(invoke("fresh", Some(SchedulingLane::Latency)))
+            .await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+        let executor_tasks = indexify_state
+            .reader()
+            .get_tasks_by_executor(&mock_executor_id(), 10)?;
+        assert_eq!(executor_tasks.len(), 2);
+        assert!(executor_tasks
+            .iter()
+            .any(|task| task.lane == SchedulingLane::Latency));
+        assert_eq!(indexify_state.reader().unallocated_tasks()?.len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_high_priority_task_preempts_running_task() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let scheduler = Scheduler::new(indexify_state.clone());
+        scheduler
+            .task_allocator
+            .set_preemption_config(Some(&PreemptionConfig {
+                budget: RateLimit {
+                    max_tasks: 1,
+                    window_secs: 60,
+                },
+                min_priority_gap: 1,
+            }));
+        let ex = Arc::new(ExecutorManager::new(indexify_state.clone()).await);
+
+        let mut graph = mock_graph_a();
+        if let Some(Node::Compute(compute_fn)) = graph.nodes.get_mut("fn_a") {
+            compute_fn.gpu = Some(GpuRequirement {
+                model: None,
+                count: 1,
+            });
+        }
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: graph,
+                    expected_revision: None,
+                    force: false,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        ex.register_executor(ExecutorMetadata {
+            gpus: vec![GpuDevice {
+                id: "0".to_string(),
+                model: "A10G".to_string(),
+                free_memory_mb: 24_000,
+            }],
+            ..mock_executor()
+        })
+        .await?;
+        let invoke = |priority: i32| {
+            let invocation_payload = InvocationPayloadBuilder::default()
+                .namespace(TEST_NAMESPACE.to_string())
+                .compute_graph_name("graph_A".to_string())
+                .payload(DataPayload {
+                    path: format!("payload_{}", priority),
+                    size: 23,
+                    sha256_hash: "hash".to_string(),
+                    tier: Default::default(),
+                    mime_type: None,
+                    content_encoding: None,
+                    metadata: Default::default(),
+                    replicas: Vec::new(),
+                    compression: None,
+                })
+                .priority(priority)
+                .build()
+                .unwrap();
+            StateMachineUpdateRequest {
+                payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph_name: "graph_A".to_string(),
+                    invocation_payload,
+                }),
+                state_changes_processed: vec![],
+            }
+        };
+
+        indexify_state.write(invoke(0)).await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+        let executor_tasks = indexify_state
+            .reader()
+            .get_tasks_by_executor(&mock_executor_id(), 10)?;
+        assert_eq!(executor_tasks.len(), 1);
+        let low_priority_task = executor_tasks[0].clone();
+
+        indexify_state.write(invoke(10)).await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+
+        // The high priority task takes the GPU of the low priority task
+        let executor_tasks = indexify_state
+            .reader()
+            .get_tasks_by_executor(&mock_executor_id(), 10)?;
+        assert_eq!(executor_tasks.len(), 1);
+        assert_eq!(executor_tasks[0].priority, 10);
+        assert_eq!(executor_tasks[0].gpu_ids, vec!["0".to_string()]);
+        let unallocated_tasks = indexify_state.reader().unallocated_tasks()?;
+        assert_eq!(unallocated_tasks.len(), 1);
+        assert_eq!(unallocated_tasks[0].id, low_priority_task.id);
+        assert!(unallocated_tasks[0].gpu_ids.is_empty());
+
+        // The executor is told to abort the pre-empted task
+        let executor_states = indexify_state.executor_states.read().await;
+        let executor_state = executor_states.get(&mock_executor_id()).unwrap();
+        assert_eq!(
+            executor_state.aborted_task_ids,
+            vec![low_priority_task.id.clone()]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tasks_sharing_concurrency_key_are_serialized() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let scheduler = Scheduler::new(indexify_state.clone());
+        let ex = Arc::new(ExecutorManager::new(indexify_state.clone()).await);
+
+        let mut graph = mock_graph_a();
+        if let Some(Node::Compute(compute_fn)) = graph.nodes.get_mut("fn_a") {
+            compute_fn.concurrency_key = Some("customer-{labels.customer_id}".to_string());
+        }
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: graph,
+                    expected_revision: None,
+                    force: false,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        ex.register_executor(mock_executor()).await?;
+        for (i, customer_id) in ["c1", "c1", "c2"].iter().enumerate() {
+            let invocation_payload = InvocationPayloadBuilder::default()
+                .namespace(TEST_NAMESPACE.to_string())
+                .compute_graph_name("graph_A".to_string())
+                .payload(DataPayload {
+                    path: format!("payload_{}", i),
+                    size: 23,
+                    sha256_hash: "hash".to_string(),
+                    tier: Default::default(),
+                    mime_type: None,
+                    content_encoding: None,
+                    metadata: Default::default(),
+                    replicas: Vec::new(),
+                    compression: None,
+                })
+                .labels(HashMap::from([(
+                    "customer_id".to_string(),
+                    serde_json::json!(customer_id),
+                )]))
+                .build()
+                .unwrap();
+            indexify_state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                        namespace: TEST_NAMESPACE.to_string(),
+                        compute_graph_name: "graph_A".to_string(),
+                        invocation_payload,
+                    }),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+        }
+        schedule_all(&indexify_state, &scheduler).await?;
+
+        // One task per customer runs at a time
+        let executor_tasks = indexify_state
+            .reader()
+            .get_tasks_by_executor(&mock_executor_id(), 10)?;
+        let mut keys: Vec<String> = executor_tasks
+            .iter()
+            .filter_map(|task| task.concurrency_key.clone())
+            .collect();
+        keys.sort();
+        assert_eq!(keys, vec!["customer-c1", "customer-c2"]);
+        let unallocated_tasks = indexify_state.reader().unallocated_tasks()?;
+        assert_eq!(unallocated_tasks.len(), 1);
+        let waiting_task = unallocated_tasks[0].clone();
+
+        let running_task = executor_tasks
+            .iter()
+            .find(|task| task.concurrency_key == waiting_task.concurrency_key)
+            .unwrap();
+        state_store
+            .finalize_task(running_task, 1, TaskOutcome::Success, false)
+            .await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+        assert!(indexify_state.reader().unallocated_tasks()?.is_empty());
+        let executor_tasks = indexify_state
+            .reader()
+            .get_tasks_by_executor(&mock_executor_id(), 10)?;
+        assert!(executor_tasks.iter().any(|task| task.id == waiting_task.id));
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_task_unassign() -> Result<()> {
         let state_store = TestStateStore::new().await?;
@@ -460,4 +1460,81 @@ mod tests {
             .0;
         assert_eq!(tasks.len(), 3);
     }
+
+    #[tokio::test]
+    async fn test_failed_router_routes_to_default_targets() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let scheduler = Scheduler::new(indexify_state.clone());
+        let mut graph = mock_graph_b();
+        if let Some(Node::Router(router)) = graph.nodes.get_mut("router_x") {
+            router.default_targets = vec!["fn_c".to_string()];
+            router.on_error = RouterErrorPolicy::RouteToDefault;
+        }
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: graph,
+                    expected_revision: None,
+                    force: false,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let invocation_payload = mock_invocation_payload_graph_b();
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph_name: "graph_B".to_string(),
+                    invocation_payload: invocation_payload.clone(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let invocation_id = invocation_payload.id;
+        let list_tasks = || -> Result<Vec<Task>> {
+            Ok(indexify_state
+                .reader()
+                .list_tasks_by_compute_graph(TEST_NAMESPACE, "graph_B", &invocation_id, None, None)?
+                .0)
+        };
+        schedule_all(&indexify_state, &scheduler).await?;
+        let tasks = list_tasks()?;
+        state_store
+            .finalize_task(&tasks[0], 1, TaskOutcome::Success, false)
+            .await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+
+        // The input of the failed router is routed to its default target
+        let router_task = list_tasks()?
+            .into_iter()
+            .find(|task| task.compute_fn_name == "router_x")
+            .unwrap();
+        state_store
+            .finalize_task(&router_task, 0, TaskOutcome::Failure, false)
+            .await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+        let default_task = list_tasks()?
+            .into_iter()
+            .find(|task| task.compute_fn_name == "fn_c")
+            .unwrap();
+        assert_eq!(
+            default_task.input_node_output_key,
+            router_task.input_node_output_key
+        );
+
+        // The handled failure doesn't fail the invocation
+        state_store
+            .finalize_task(&default_task, 1, TaskOutcome::Success, false)
+            .await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+        let result = indexify_state
+            .reader()
+            .invocation_result(TEST_NAMESPACE, "graph_B", &invocation_id)?
+            .unwrap();
+        assert_eq!(result.status, InvocationStatus::Success);
+        Ok(())
+    }
 }
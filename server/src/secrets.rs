@@ -0,0 +1,213 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use data_model::{ComputeGraph, Node};
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN},
+    hkdf,
+    hmac,
+};
+
+use crate::http_objects::REDACTED_SECRET;
+
+const HKDF_SALT: &[u8] = b"indexify secrets";
+
+/// Seals and opens graph secrets with AES-256-GCM.
+///
+/// Nonces are derived from an HMAC of the plaintext so that sealing the same
+/// secret twice yields the same value. This keeps re-uploads of an unchanged
+/// graph from looking like a new graph version. The encryption key and the
+/// nonce key are derived from the configured key with HKDF, so neither is
+/// used for both.
+pub struct SecretsCipher {
+    key: LessSafeKey,
+    nonce_key: hmac::Key,
+}
+
+impl SecretsCipher {
+    pub fn from_hex(key: &str) -> Result<Self> {
+        let key_bytes = hex::decode(key).map_err(|e| anyhow!("invalid secrets key: {}", e))?;
+        if key_bytes.len() != AES_256_GCM.key_len() {
+            return Err(anyhow!("secrets key must be 32 bytes"));
+        }
+        let prk = hkdf::Salt::new(hkdf::HKDF_SHA256, HKDF_SALT).extract(&key_bytes);
+        let derive_error = |_| anyhow!("unable to derive the secrets keys");
+        let key: UnboundKey = prk
+            .expand(&[b"encryption".as_slice()], &AES_256_GCM)
+            .map_err(derive_error)?
+            .into();
+        let nonce_key: hmac::Key = prk
+            .expand(&[b"nonce".as_slice()], hmac::HMAC_SHA256)
+            .map_err(derive_error)?
+            .into();
+        Ok(Self {
+            key: LessSafeKey::new(key),
+            nonce_key,
+        })
+    }
+
+    pub fn seal(&self, plaintext: &str) -> Result<String> {
+        let tag = hmac::sign(&self.nonce_key, plaintext.as_bytes());
+        let mut nonce_bytes = [0u8; NONCE_LEN];
+        nonce_bytes.copy_from_slice(&tag.as_ref()[..NONCE_LEN]);
+        let mut in_out = plaintext.as_bytes().to_vec();
+        self.key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce_bytes),
+                Aad::empty(),
+                &mut in_out,
+            )
+            .map_err(|_| anyhow!("unable to seal secret"))?;
+        let mut sealed = nonce_bytes.to_vec();
+        sealed.extend(in_out);
+        Ok(hex::encode(sealed))
+    }
+
+    pub fn open(&self, sealed: &str) -> Result<String> {
+        let sealed = hex::decode(sealed).map_err(|e| anyhow!("invalid sealed secret: {}", e))?;
+        if sealed.len() < NONCE_LEN {
+            return Err(anyhow!("invalid sealed secret"));
+        }
+        let (nonce_bytes, ciphertext) = sealed.split_at(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce_bytes)
+            .map_err(|_| anyhow!("invalid sealed secret nonce"))?;
+        let mut in_out = ciphertext.to_vec();
+        let plaintext = self
+            .key
+            .open_in_place(nonce, Aad::empty(), &mut in_out)
+            .map_err(|_| anyhow!("unable to open secret"))?;
+        Ok(String::from_utf8(plaintext.to_vec())?)
+    }
+
+    pub fn seal_all(&self, secrets: &HashMap<String, String>) -> Result<HashMap<String, String>> {
+        secrets
+            .iter()
+            .map(|(name, value)| Ok((name.clone(), self.seal(value)?)))
+            .collect()
+    }
+
+    pub fn open_all(&self, secrets: &HashMap<String, String>) -> Result<HashMap<String, String>> {
+        secrets
+            .iter()
+            .map(|(name, value)| Ok((name.clone(), self.open(value)?)))
+            .collect()
+    }
+
+    /// Replaces every plaintext secret of the graph and its functions with
+    /// its sealed value. Secrets left redacted, e.g. by a client uploading
+    /// back a graph it read, keep their sealed value in the `existing` graph.
+    pub fn seal_compute_graph(
+        &self,
+        compute_graph: &mut ComputeGraph,
+        existing: Option<&ComputeGraph>,
+    ) -> Result<()> {
+        compute_graph.secrets = self.seal_secrets(
+            &compute_graph.secrets,
+            existing.map(|existing| &existing.secrets),
+        )?;
+        for (name, node) in compute_graph.nodes.iter_mut() {
+            if let Node::Compute(compute_fn) = node {
+                compute_fn.secrets =
+                    self.seal_secrets(&compute_fn.secrets, fn_secrets(existing, name))?;
+            }
+        }
+        if let Node::Compute(compute_fn) = &mut compute_graph.start_fn {
+            compute_fn.secrets =
+                self.seal_secrets(&compute_fn.secrets, fn_secrets(existing, &compute_fn.name))?;
+        }
+        Ok(())
+    }
+
+    fn seal_secrets(
+        &self,
+        secrets: &HashMap<String, String>,
+        existing: Option<&HashMap<String, String>>,
+    ) -> Result<HashMap<String, String>> {
+        secrets
+            .iter()
+            .map(|(name, value)| {
+                if value != REDACTED_SECRET {
+                    return Ok((name.clone(), self.seal(value)?));
+                }
+                let sealed = existing.and_then(|existing| existing.get(name)).ok_or(anyhow!(
+                    "secret {} is redacted but isn't set on the existing graph",
+                    name
+                ))?;
+                Ok((name.clone(), sealed.clone()))
+            })
+            .collect()
+    }
+}
+
+fn fn_secrets<'a>(
+    compute_graph: Option<&'a ComputeGraph>,
+    name: &str,
+) -> Option<&'a HashMap<String, String>> {
+    match compute_graph?.nodes.get(name)? {
+        Node::Compute(compute_fn) => Some(&compute_fn.secrets),
+        _ => None,
+    }
+}
+
+pub fn has_secrets(compute_graph: &ComputeGraph) -> bool {
+    !compute_graph.secrets.is_empty() ||
+        compute_graph.nodes.values().any(|node| match node {
+            Node::Compute(compute_fn) => !compute_fn.secrets.is_empty(),
+            Node::Router(_) |
+            Node::SubGraph(_) |
+            Node::Gate(_) |
+            Node::Window(_) |
+            Node::Sink(_) => false,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_KEY: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+
+    #[test]
+    fn test_seal_and_open() {
+        let cipher = SecretsCipher::from_hex(TEST_KEY).unwrap();
+        let sealed = cipher.seal("api-token").unwrap();
+        assert_ne!(sealed, "api-token");
+        assert_eq!(cipher.seal("api-token").unwrap(), sealed);
+        assert_eq!(cipher.open(&sealed).unwrap(), "api-token");
+
+        let other = SecretsCipher::from_hex(&"ff".repeat(32)).unwrap();
+        assert!(other.open(&sealed).is_err());
+    }
+
+    #[test]
+    fn test_derived_keys() {
+        let cipher = SecretsCipher::from_hex(TEST_KEY).unwrap();
+        let key_bytes = hex::decode(TEST_KEY).unwrap();
+        let sealed = hex::decode(cipher.seal("api-token").unwrap()).unwrap();
+
+        // The nonce isn't the HMAC of the secret under the encryption key
+        let tag = hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, &key_bytes), b"api-token");
+        assert_ne!(&sealed[..NONCE_LEN], &tag.as_ref()[..NONCE_LEN]);
+    }
+
+    #[test]
+    fn test_redacted_secrets() {
+        let cipher = SecretsCipher::from_hex(TEST_KEY).unwrap();
+        let existing = cipher
+            .seal_all(&HashMap::from([("token".to_string(), "api-token".to_string())]))
+            .unwrap();
+        let redacted = HashMap::from([("token".to_string(), REDACTED_SECRET.to_string())]);
+
+        // Redacted secrets keep their stored value instead of being sealed
+        let sealed = cipher.seal_secrets(&redacted, Some(&existing)).unwrap();
+        assert_eq!(sealed, existing);
+        assert_eq!(cipher.open(&sealed["token"]).unwrap(), "api-token");
+        assert!(cipher.seal_secrets(&redacted, None).is_err());
+    }
+
+    #[test]
+    fn test_invalid_key() {
+        assert!(SecretsCipher::from_hex("0011").is_err());
+        assert!(SecretsCipher::from_hex("not hex").is_err());
+    }
+}
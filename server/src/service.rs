@@ -1,65 +1,185 @@
-use std::{net::SocketAddr, sync::Arc};
+use std::{net::SocketAddr, path::PathBuf, sync::Arc};
 
 use anyhow::Result;
 use axum_server::Handle;
 use blob_store::BlobStorage;
-use state_store::IndexifyState;
+use state_store::{migrations::MigrationOptions, snapshot::restore_snapshot, IndexifyState};
+use task_scheduler::TaskScheduler;
 use tokio::{self, signal, sync::watch};
-use tracing::info;
+use tracing::{error, info};
 
 use super::{routes::RouteState, scheduler::Scheduler};
 use crate::{
-    config::ServerConfig,
+    alerts::AlertMonitor,
+    archive::InvocationArchiver,
+    cluster::Cluster,
+    config::{ArchiveConfig, RuntimeConfig, ServerConfig, SinkConfig, WasmConfig, WebhookConfig},
+    config_reload::{ConfigReloader, LogFilterHandle},
+    download_urls::DownloadUrlSigner,
     executors::ExecutorManager,
     gc::Gc,
-    routes::create_routes,
+    grpc,
+    latency::LatencyTracker,
+    oidc::{refresh_jwks, JwksCache},
+    routes::{create_routes, invoke_url::UrlFetches},
+    replicas::PayloadReplicator,
+    secrets::SecretsCipher,
+    sinks::SinkWriters,
+    sla::SlaMonitor,
     system_tasks::SystemTasksExecutor,
+    task_tokens::TaskTokenSigner,
+    tiering::StorageTiering,
+    wasm::WasmRouters,
+    webhooks::WebhookDispatcher,
+    windows::WindowCloser,
 };
 
 pub struct Service {
     pub config: ServerConfig,
+    pub config_reloader: Arc<ConfigReloader>,
 }
 
 impl Service {
-    pub fn new(config: ServerConfig) -> Self {
-        Self { config }
+    pub fn new(
+        config: ServerConfig,
+        config_path: Option<PathBuf>,
+        log_filter: LogFilterHandle,
+    ) -> Self {
+        let config_reloader =
+            Arc::new(ConfigReloader::new(config_path, config.clone(), log_filter));
+        Self {
+            config,
+            config_reloader,
+        }
     }
 
     pub async fn start(&self) -> Result<()> {
         let (shutdown_tx, shutdown_rx) = watch::channel(());
-        let indexify_state = IndexifyState::new(self.config.state_store_path.parse()?).await?;
         let blob_storage = Arc::new(BlobStorage::new(self.config.blob_storage.clone())?);
+        if let Some(snapshot_url) = &self.config.restore_snapshot {
+            let state_store_path: PathBuf = self.config.state_store_path.parse()?;
+            if state_store_path.exists() {
+                info!(
+                    "state store {} already exists, skipping restore of snapshot {}",
+                    self.config.state_store_path, snapshot_url
+                );
+            } else {
+                info!("restoring state store from snapshot {}", snapshot_url);
+                let data = blob_storage.get(snapshot_url).get().await?;
+                restore_snapshot(state_store_path, data).await?;
+            }
+        }
+        let migration_options = MigrationOptions {
+            dry_run: false,
+            backup_dir: self.config.migration_backup_dir.as_ref().map(PathBuf::from),
+        };
+        let indexify_state =
+            IndexifyState::open(self.config.state_store_path.parse()?, &migration_options).await?;
+        let cluster = match &self.config.cluster {
+            Some(cluster_config) => Some(Arc::new(Cluster::new(
+                cluster_config.clone(),
+                indexify_state.clone(),
+            )?)),
+            None => None,
+        };
         let executor_manager = Arc::new(ExecutorManager::new(indexify_state.clone()).await);
+        let secrets_cipher = match &self.config.secrets_key {
+            Some(key) => Some(Arc::new(SecretsCipher::from_hex(key)?)),
+            None => None,
+        };
+        let jwks = Arc::new(JwksCache::default());
+        let task_scheduler = Arc::new(TaskScheduler::new(indexify_state.clone()));
+        tokio::spawn(refresh_jwks(
+            jwks.clone(),
+            self.config_reloader.subscribe(),
+            shutdown_rx.clone(),
+        ));
         let route_state = RouteState {
             indexify_state: indexify_state.clone(),
             blob_storage: blob_storage.clone(),
             executor_manager,
+            secrets_cipher: secrets_cipher.clone(),
+            cluster: cluster.clone(),
+            config_reloader: self.config_reloader.clone(),
+            download_url_signer: Arc::new(DownloadUrlSigner::new(&self.config.downloads)?),
+            task_tokens: Arc::new(TaskTokenSigner::new(&self.config.task_tokens)?),
+            rejected_payloads: Default::default(),
+            url_fetches: Arc::new(UrlFetches::new(self.config.url_fetch.clone())),
+            task_scheduler: task_scheduler.clone(),
+            jwks: jwks.clone(),
         };
+        if let Some(grpc_addr) = &self.config.grpc_listen_addr {
+            let grpc_addr: SocketAddr = grpc_addr.parse()?;
+            let grpc_state = route_state.clone();
+            let grpc_shutdown_rx = shutdown_rx.clone();
+            tokio::spawn(async move {
+                if let Err(err) = grpc::serve(grpc_addr, grpc_state, grpc_shutdown_rx).await {
+                    error!("grpc server failed: {:?}", err);
+                }
+            });
+        }
         let app = create_routes(route_state);
         let handle = Handle::new();
         let handle_sh = handle.clone();
-        let scheduler = Scheduler::new(indexify_state.clone());
 
-        let mut gc = Gc::new(indexify_state.clone(), blob_storage, shutdown_rx.clone());
-        let mut system_tasks_executor =
-            SystemTasksExecutor::new(indexify_state.clone(), shutdown_rx.clone());
-
-        let state_watcher_rx = indexify_state.get_state_change_watcher();
+        let config_reloader = self.config_reloader.clone();
+        let reload_shutdown_rx = shutdown_rx.clone();
         tokio::spawn(async move {
-            info!("starting scheduler");
-            let _ = scheduler.start(shutdown_rx, state_watcher_rx).await;
-            info!("scheduler shutdown");
+            if let Err(err) = config_reloader.watch_signals(reload_shutdown_rx).await {
+                error!(
+                    "failed to watch for configuration reload signals: {:?}",
+                    err
+                );
+            }
         });
+        indexify_state.set_maintenance_mode(self.config_reloader.runtime().maintenance_mode);
+        let maintenance_state = indexify_state.clone();
+        let mut maintenance_config_rx = self.config_reloader.subscribe();
         tokio::spawn(async move {
-            info!("starting garbage collector");
-            let _ = gc.start().await;
-            info!("garbage collector shutdown");
-        });
-        tokio::spawn(async move {
-            info!("starting system tasks executor");
-            let _ = system_tasks_executor.start().await;
-            info!("system tasks executor shutdown");
+            while maintenance_config_rx.changed().await.is_ok() {
+                let maintenance_mode = maintenance_config_rx.borrow().maintenance_mode;
+                if maintenance_mode != maintenance_state.in_maintenance_mode() {
+                    info!("maintenance mode set to {}", maintenance_mode);
+                    maintenance_state.set_maintenance_mode(maintenance_mode);
+                }
+            }
         });
+        let runtime_config_rx = self.config_reloader.subscribe();
+        match cluster {
+            Some(cluster) => {
+                let membership = cluster.clone();
+                let membership_shutdown_rx = shutdown_rx.clone();
+                tokio::spawn(async move {
+                    info!("starting cluster membership");
+                    membership.start(membership_shutdown_rx).await;
+                });
+                tokio::spawn(run_while_leader(
+                    cluster,
+                    indexify_state.clone(),
+                    task_scheduler,
+                    blob_storage,
+                    secrets_cipher,
+                    self.config.wasm.clone(),
+                    self.config.webhooks.clone(),
+                    self.config.sinks.clone(),
+                    self.config.archive.clone(),
+                    runtime_config_rx,
+                    shutdown_rx,
+                ));
+            }
+            None => start_leader_tasks(
+                indexify_state.clone(),
+                task_scheduler,
+                blob_storage,
+                secrets_cipher,
+                self.config.wasm.clone(),
+                self.config.webhooks.clone(),
+                self.config.sinks.clone(),
+                self.config.archive.clone(),
+                runtime_config_rx,
+                shutdown_rx,
+            )?,
+        }
 
         tokio::spawn(async move {
             shutdown_signal(handle_sh, shutdown_tx).await;
@@ -76,6 +196,194 @@ impl Service {
     }
 }
 
+// The scheduler, garbage collector, system tasks, storage tiering, payload
+// replicator, SLA and alert monitors, latency tracker, webhook dispatcher,
+// sink writers and invocation archiver mutate the state or export it, only
+// the leader runs them.
+#[allow(clippy::too_many_arguments)]
+fn start_leader_tasks(
+    indexify_state: Arc<IndexifyState>,
+    task_scheduler: Arc<TaskScheduler>,
+    blob_storage: Arc<BlobStorage>,
+    secrets_cipher: Option<Arc<SecretsCipher>>,
+    wasm_config: WasmConfig,
+    webhook_config: WebhookConfig,
+    sink_config: SinkConfig,
+    archive_config: Option<ArchiveConfig>,
+    runtime_config_rx: watch::Receiver<RuntimeConfig>,
+    shutdown_rx: watch::Receiver<()>,
+) -> Result<()> {
+    let wasm_routers = Arc::new(WasmRouters::new(
+        indexify_state.clone(),
+        blob_storage.clone(),
+        wasm_config,
+    ));
+    let sink_writers = Arc::new(SinkWriters::new(
+        indexify_state.clone(),
+        blob_storage.clone(),
+        secrets_cipher,
+        sink_config,
+    )?);
+    let latency_tracker = Arc::new(LatencyTracker::new(indexify_state.clone())?);
+    let scheduler = Scheduler::new(indexify_state.clone())
+        .with_task_allocator(task_scheduler)
+        .with_wasm_routers(wasm_routers)
+        .with_sink_writers(sink_writers)
+        .with_latency_tracker(latency_tracker.clone());
+    let mut gc = Gc::new(
+        indexify_state.clone(),
+        blob_storage.clone(),
+        runtime_config_rx.clone(),
+        shutdown_rx.clone(),
+    );
+    let mut system_tasks_executor = SystemTasksExecutor::new(
+        indexify_state.clone(),
+        blob_storage.clone(),
+        shutdown_rx.clone(),
+    );
+    let mut storage_tiering = StorageTiering::new(
+        indexify_state.clone(),
+        blob_storage.clone(),
+        runtime_config_rx.clone(),
+        shutdown_rx.clone(),
+    );
+    let invocation_archiver = match archive_config {
+        Some(archive_config) => Some(InvocationArchiver::new(
+            indexify_state.clone(),
+            blob_storage.clone(),
+            archive_config,
+            shutdown_rx.clone(),
+        )?),
+        None => None,
+    };
+    let mut payload_replicator =
+        PayloadReplicator::new(indexify_state.clone(), blob_storage, shutdown_rx.clone());
+    let mut sla_monitor = SlaMonitor::new(indexify_state.clone(), shutdown_rx.clone());
+    let mut alert_monitor = AlertMonitor::new(
+        indexify_state.clone(),
+        runtime_config_rx.clone(),
+        shutdown_rx.clone(),
+    );
+    let mut window_closer = WindowCloser::new(indexify_state.clone(), shutdown_rx.clone());
+    let mut webhook_dispatcher =
+        WebhookDispatcher::new(indexify_state.clone(), webhook_config, shutdown_rx.clone())?;
+
+    let state_watcher_rx = indexify_state.get_state_change_watcher();
+    let latency_shutdown_rx = shutdown_rx.clone();
+    tokio::spawn(async move {
+        info!("starting latency tracker");
+        let _ = latency_tracker.start(latency_shutdown_rx).await;
+        info!("latency tracker shutdown");
+    });
+    tokio::spawn(async move {
+        info!("starting scheduler");
+        let _ = scheduler
+            .start(shutdown_rx, state_watcher_rx, runtime_config_rx)
+            .await;
+        info!("scheduler shutdown");
+    });
+    tokio::spawn(async move {
+        info!("starting garbage collector");
+        let _ = gc.start().await;
+        info!("garbage collector shutdown");
+    });
+    tokio::spawn(async move {
+        info!("starting system tasks executor");
+        let _ = system_tasks_executor.start().await;
+        info!("system tasks executor shutdown");
+    });
+    tokio::spawn(async move {
+        info!("starting storage tiering");
+        let _ = storage_tiering.start().await;
+        info!("storage tiering shutdown");
+    });
+    tokio::spawn(async move {
+        info!("starting payload replicator");
+        let _ = payload_replicator.start().await;
+        info!("payload replicator shutdown");
+    });
+    tokio::spawn(async move {
+        info!("starting sla monitor");
+        let _ = sla_monitor.start().await;
+        info!("sla monitor shutdown");
+    });
+    tokio::spawn(async move {
+        info!("starting alert monitor");
+        let _ = alert_monitor.start().await;
+        info!("alert monitor shutdown");
+    });
+    tokio::spawn(async move {
+        info!("starting window closer");
+        let _ = window_closer.start().await;
+        info!("window closer shutdown");
+    });
+    tokio::spawn(async move {
+        info!("starting webhook dispatcher");
+        let _ = webhook_dispatcher.start().await;
+        info!("webhook dispatcher shutdown");
+    });
+    if let Some(mut invocation_archiver) = invocation_archiver {
+        tokio::spawn(async move {
+            info!("starting invocation archiver");
+            let _ = invocation_archiver.start().await;
+            info!("invocation archiver shutdown");
+        });
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn run_while_leader(
+    cluster: Arc<Cluster>,
+    indexify_state: Arc<IndexifyState>,
+    task_scheduler: Arc<TaskScheduler>,
+    blob_storage: Arc<BlobStorage>,
+    secrets_cipher: Option<Arc<SecretsCipher>>,
+    wasm_config: WasmConfig,
+    webhook_config: WebhookConfig,
+    sink_config: SinkConfig,
+    archive_config: Option<ArchiveConfig>,
+    runtime_config_rx: watch::Receiver<RuntimeConfig>,
+    mut shutdown_rx: watch::Receiver<()>,
+) {
+    let mut leader_rx = cluster.subscribe();
+    loop {
+        while !cluster.is_leader() {
+            tokio::select! {
+                res = leader_rx.changed() => if res.is_err() { return },
+                _ = shutdown_rx.changed() => return,
+            }
+        }
+        info!("became leader, starting leader tasks");
+        let (term_tx, term_rx) = watch::channel(());
+        if let Err(err) = start_leader_tasks(
+            indexify_state.clone(),
+            task_scheduler.clone(),
+            blob_storage.clone(),
+            secrets_cipher.clone(),
+            wasm_config.clone(),
+            webhook_config.clone(),
+            sink_config.clone(),
+            archive_config.clone(),
+            runtime_config_rx.clone(),
+            term_rx,
+        ) {
+            error!("failed to start leader tasks: {:?}", err);
+        }
+        while cluster.is_leader() {
+            tokio::select! {
+                res = leader_rx.changed() => if res.is_err() { break },
+                _ = shutdown_rx.changed() => {
+                    let _ = term_tx.send(());
+                    return;
+                }
+            }
+        }
+        info!("lost leadership, stopping leader tasks");
+        let _ = term_tx.send(());
+    }
+}
+
 async fn shutdown_signal(handle: Handle, shutdown_tx: watch::Sender<()>) {
     let ctrl_c = async {
         signal::ctrl_c()
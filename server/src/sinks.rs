@@ -0,0 +1,547 @@
+//! Writes the inputs of sink nodes to their index from the server. Tasks of
+//! sinks aren't allocated to executors, the leader writes their input once
+//! they're created, with retries, and finalizes them.
+//!
+//! Inputs are JSON records, or JSON arrays of records. Records without an id
+//! get one derived from the input and their position in it, so writing an
+//! input again, after a failed attempt or a restart, replaces the records it
+//! already wrote.
+
+use std::{collections::BTreeSet, sync::Arc, time::Duration};
+
+use anyhow::{anyhow, Result};
+use arrow_array::{Array, FixedSizeListArray, Float32Array, RecordBatch};
+use arrow_ipc::writer::StreamWriter;
+use arrow_json::reader::{infer_json_schema_from_iterator, ReaderBuilder};
+use arrow_schema::{ArrowError, DataType, Field, Schema};
+use blob_store::BlobStorage;
+use data_model::{
+    sinks::{Sink, SinkConnector},
+    ComputeGraph,
+    ExecutorId,
+    Task,
+    TaskDiagnostics,
+    TaskOutcome,
+};
+use rustls::{ClientConfig, RootCertStore};
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use state_store::{
+    requests::{FinalizeTaskRequest, RequestPayload, StateMachineUpdateRequest},
+    IndexifyState,
+};
+use tokio_postgres_rustls::MakeRustlsConnect;
+use tracing::{error, info, warn};
+
+use crate::{
+    config::SinkConfig,
+    secrets::SecretsCipher,
+    wasm::{put_exception, read_input},
+};
+
+type Record = Map<String, Value>;
+
+/// Delay before attempting again a write which failed `attempts` times.
+pub fn retry_delay(config: &SinkConfig, attempts: u32) -> Duration {
+    let exponent = attempts.saturating_sub(1).min(32);
+    let backoff = config.initial_backoff_secs.saturating_mul(1 << exponent);
+    Duration::from_secs(backoff.min(config.max_backoff_secs))
+}
+
+pub struct SinkWriters {
+    indexify_state: Arc<IndexifyState>,
+    blob_storage: Arc<BlobStorage>,
+    secrets_cipher: Option<Arc<SecretsCipher>>,
+    config: SinkConfig,
+    client: reqwest::Client,
+}
+
+impl SinkWriters {
+    pub fn new(
+        indexify_state: Arc<IndexifyState>,
+        blob_storage: Arc<BlobStorage>,
+        secrets_cipher: Option<Arc<SecretsCipher>>,
+        config: SinkConfig,
+    ) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .build()?;
+        Ok(Self {
+            indexify_state,
+            blob_storage,
+            secrets_cipher,
+            config,
+            client,
+        })
+    }
+
+    /// Writes the inputs of the tasks of sinks in the background, other tasks
+    /// are ignored.
+    pub fn spawn_tasks(self: &Arc<Self>, tasks: Vec<Task>) -> Result<()> {
+        for task in tasks {
+            let Some((graph, sink)) = self.sink(&task)? else {
+                continue;
+            };
+            let writers = self.clone();
+            tokio::spawn(async move {
+                if let Err(err) = writers.run_task(&task, &graph, &sink).await {
+                    error!("failed to finalize sink task {}: {:?}", task.id, err);
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// Writes the inputs of the tasks of sinks which were created but not
+    /// finished before the scheduler stopped.
+    pub fn resume(self: &Arc<Self>) -> Result<()> {
+        let tasks = self.indexify_state.reader().unallocated_tasks()?;
+        self.spawn_tasks(tasks)
+    }
+
+    fn sink(&self, task: &Task) -> Result<Option<(ComputeGraph, Sink)>> {
+        let graph = self.indexify_state.reader().get_compute_graph_version(
+            &task.namespace,
+            &task.compute_graph_name,
+            &task.graph_version,
+        )?;
+        Ok(graph.and_then(|graph| {
+            let sink = graph.nodes.get(&task.compute_fn_name)?.sink()?.clone();
+            Some((graph, sink))
+        }))
+    }
+
+    async fn run_task(&self, task: &Task, graph: &ComputeGraph, sink: &Sink) -> Result<()> {
+        let (outcome, diagnostics) = match self.write_input(task, graph, sink).await {
+            Ok(written) => {
+                info!(
+                    "sink task {} wrote {} records to {}",
+                    task.id,
+                    written,
+                    sink.connector.as_str()
+                );
+                (TaskOutcome::Success, None)
+            }
+            Err(err) => {
+                info!("sink task {} failed: {:?}", task.id, err);
+                let exception = put_exception(&self.blob_storage, task, &err).await?;
+                let diagnostics = TaskDiagnostics {
+                    exception: Some(exception),
+                    stdout: None,
+                    stderr: None,
+                    structured_logs: None,
+                };
+                (TaskOutcome::Failure, Some(diagnostics))
+            }
+        };
+        self.indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::FinalizeTask(FinalizeTaskRequest {
+                    namespace: task.namespace.clone(),
+                    compute_graph: task.compute_graph_name.clone(),
+                    compute_fn: task.compute_fn_name.clone(),
+                    invocation_id: task.invocation_id.clone(),
+                    task_id: task.id.clone(),
+                    node_outputs: vec![],
+                    task_outcome: outcome,
+                    // Sink tasks are not allocated to an executor
+                    executor_id: ExecutorId::default(),
+                    diagnostics,
+                    inline_payloads: vec![],
+                    resource_usage: None,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await
+    }
+
+    /// Writes the records of the input of the task, returns how many were
+    /// written.
+    async fn write_input(&self, task: &Task, graph: &ComputeGraph, sink: &Sink) -> Result<usize> {
+        let input = read_input(&self.indexify_state, &self.blob_storage, task).await?;
+        let records = records(sink, &task.input_node_output_key, &input)?;
+        if records.is_empty() {
+            return Ok(0);
+        }
+        let secret = self.secret(graph, sink)?;
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            match self.write(sink, secret.as_deref(), &records).await {
+                Ok(()) => return Ok(records.len()),
+                Err(err) if attempts < self.config.max_attempts => {
+                    warn!(
+                        "sink task {} write failed, attempt {}: {:?}",
+                        task.id, attempts, err
+                    );
+                    tokio::time::sleep(retry_delay(&self.config, attempts)).await;
+                }
+                Err(err) => {
+                    return Err(err.context(format!(
+                        "sink {} failed to write to {} after {} attempts",
+                        sink.name,
+                        sink.connector.as_str(),
+                        attempts
+                    )))
+                }
+            }
+        }
+    }
+
+    fn secret(&self, graph: &ComputeGraph, sink: &Sink) -> Result<Option<String>> {
+        let Some(name) = sink.connector.secret() else {
+            return Ok(None);
+        };
+        let sealed = graph
+            .secrets
+            .get(name)
+            .ok_or(anyhow!("secret {} of sink {} not found", name, sink.name))?;
+        let cipher = self.secrets_cipher.as_ref().ok_or(anyhow!(
+            "server is not configured with a secrets_key, unable to open the secret of sink {}",
+            sink.name
+        ))?;
+        cipher.open(sealed).map(Some)
+    }
+
+    async fn write(&self, sink: &Sink, secret: Option<&str>, records: &[Record]) -> Result<()> {
+        match &sink.connector {
+            SinkConnector::Postgres { table, .. } => {
+                let dsn = secret.ok_or(anyhow!("sink {} has no connection string", sink.name))?;
+                write_postgres(dsn, table, &sink.id_field, records).await
+            }
+            SinkConnector::Qdrant {
+                url, collection, ..
+            } => {
+                let points = records
+                    .iter()
+                    .map(|record| {
+                        let mut payload = record.clone();
+                        let id = payload.remove(&sink.id_field).unwrap_or(Value::Null);
+                        let vector = payload
+                            .remove(&sink.vector_field)
+                            .ok_or(anyhow!("record {} has no {}", id, sink.vector_field))?;
+                        Ok(json!({"id": id, "vector": vector, "payload": payload}))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                let mut request = self
+                    .client
+                    .put(format!(
+                        "{}/collections/{}/points",
+                        url.trim_end_matches('/'),
+                        collection
+                    ))
+                    .query(&[("wait", "true")])
+                    .json(&json!({ "points": points }));
+                if let Some(api_key) = secret {
+                    request = request.header("api-key", api_key);
+                }
+                check_response(request.send().await?).await?;
+                Ok(())
+            }
+            SinkConnector::LanceDb {
+                url,
+                database,
+                table,
+                ..
+            } => {
+                let body = arrow_stream(records, &sink.vector_field)?;
+                let mut request = self
+                    .client
+                    .post(format!(
+                        "{}/v1/table/{}/merge_insert/",
+                        url.trim_end_matches('/'),
+                        table
+                    ))
+                    .query(&[
+                        ("on", sink.id_field.as_str()),
+                        ("when_matched_update_all", "true"),
+                        ("when_not_matched_insert_all", "true"),
+                    ])
+                    .header("x-lancedb-database", database)
+                    .header(
+                        reqwest::header::CONTENT_TYPE,
+                        "application/vnd.apache.arrow.stream",
+                    )
+                    .body(body);
+                if let Some(api_key) = secret {
+                    request = request.header("x-api-key", api_key);
+                }
+                check_response(request.send().await?).await?;
+                Ok(())
+            }
+            SinkConnector::Elasticsearch { url, index, .. } => {
+                let mut body = String::new();
+                for record in records {
+                    let id = match record.get(&sink.id_field) {
+                        Some(Value::String(id)) => id.clone(),
+                        Some(id) => id.to_string(),
+                        None => return Err(anyhow!("record has no {}", sink.id_field)),
+                    };
+                    body.push_str(&json!({"index": {"_index": index, "_id": id}}).to_string());
+                    body.push('\n');
+                    body.push_str(&serde_json::to_string(record)?);
+                    body.push('\n');
+                }
+                let mut request = self
+                    .client
+                    .post(format!("{}/_bulk", url.trim_end_matches('/')))
+                    .header(reqwest::header::CONTENT_TYPE, "application/x-ndjson")
+                    .body(body);
+                if let Some(api_key) = secret {
+                    request = request.header(
+                        reqwest::header::AUTHORIZATION,
+                        format!("ApiKey {}", api_key),
+                    );
+                }
+                let response: Value = check_response(request.send().await?).await?.json().await?;
+                // The bulk API responds with 200 when some documents failed
+                if response["errors"].as_bool() == Some(true) {
+                    let error = response["items"]
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .find_map(|item| item["index"].get("error"))
+                        .cloned()
+                        .unwrap_or(Value::Null);
+                    return Err(anyhow!("elasticsearch rejected documents: {}", error));
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+async fn check_response(response: reqwest::Response) -> Result<reqwest::Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let body = response.text().await.unwrap_or_default();
+    Err(anyhow!("index responded with {}: {}", status, body))
+}
+
+/// Records of the input of a sink, with their id set.
+fn records(sink: &Sink, input_key: &str, input: &[u8]) -> Result<Vec<Record>> {
+    let value: Value = serde_json::from_slice(input).map_err(|e| {
+        anyhow!(
+            "input of sink {} is not JSON, the function before it must use the json encoder: {}",
+            sink.name,
+            e
+        )
+    })?;
+    let values = match value {
+        Value::Array(values) => values,
+        value => vec![value],
+    };
+    values
+        .into_iter()
+        .enumerate()
+        .map(|(idx, value)| {
+            let Value::Object(mut record) = value else {
+                return Err(anyhow!("records of sink {} must be JSON objects", sink.name));
+            };
+            record
+                .entry(sink.id_field.clone())
+                .or_insert_with(|| Value::String(record_id(input_key, idx)));
+            Ok(record)
+        })
+        .collect()
+}
+
+/// UUID derived from the output a record is part of and its position in it,
+/// Qdrant only accepts UUIDs and integers as ids.
+fn record_id(input_key: &str, idx: usize) -> String {
+    let digest = Sha256::digest(format!("{}|{}", input_key, idx));
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&digest[..16]);
+    uuid::Builder::from_random_bytes(bytes)
+        .into_uuid()
+        .to_string()
+}
+
+/// Encodes the records as an Arrow IPC stream. The vector field becomes a
+/// fixed size list of floats, the type of LanceDB vector columns, the type of
+/// the other fields is inferred from their values.
+fn arrow_stream(records: &[Record], vector_field: &str) -> Result<Vec<u8>> {
+    let mut vectors = vec![];
+    let mut fields = vec![];
+    for record in records {
+        let mut record = record.clone();
+        let vector = record
+            .remove(vector_field)
+            .ok_or(anyhow!("record has no {}", vector_field))?;
+        vectors.push(serde_json::from_value::<Vec<f32>>(vector)?);
+        fields.push(Value::Object(record));
+    }
+    let dimensions = vectors.first().map_or(0, Vec::len);
+    if vectors.iter().any(|vector| vector.len() != dimensions) {
+        return Err(anyhow!("vectors of the records have different dimensions"));
+    }
+    let schema = infer_json_schema_from_iterator(fields.iter().map(Ok::<_, ArrowError>))?;
+    let mut decoder = ReaderBuilder::new(Arc::new(schema)).build_decoder()?;
+    decoder.serialize(&fields)?;
+    let batch = decoder.flush()?.ok_or(anyhow!("no records to encode"))?;
+
+    let item = Arc::new(Field::new("item", DataType::Float32, true));
+    let values = Float32Array::from(vectors.into_iter().flatten().collect::<Vec<_>>());
+    let vectors = FixedSizeListArray::try_new(
+        item.clone(),
+        i32::try_from(dimensions)?,
+        Arc::new(values),
+        None,
+    )?;
+    let mut schema_fields: Vec<_> = batch.schema().fields().iter().cloned().collect();
+    schema_fields.push(Arc::new(Field::new(
+        vector_field,
+        vectors.data_type().clone(),
+        false,
+    )));
+    let mut columns = batch.columns().to_vec();
+    columns.push(Arc::new(vectors));
+    let batch = RecordBatch::try_new(Arc::new(Schema::new(schema_fields)), columns)?;
+
+    let mut writer = StreamWriter::try_new(Vec::new(), &batch.schema())?;
+    writer.write(&batch)?;
+    writer.finish()?;
+    Ok(writer.into_inner()?)
+}
+
+/// Upserts the records in the table on the id column, the columns of the
+/// records are the union of their fields. The connection is encrypted as the
+/// `sslmode` of the DSN asks, `prefer` when it's not set.
+async fn write_postgres(dsn: &str, table: &str, id_field: &str, records: &[Record]) -> Result<()> {
+    let (client, connection) = tokio_postgres::connect(dsn, postgres_tls()?).await?;
+    tokio::spawn(async move {
+        if let Err(err) = connection.await {
+            warn!("postgres sink connection failed: {:?}", err);
+        }
+    });
+    let table = table
+        .split('.')
+        .map(quote_ident)
+        .collect::<Vec<_>>()
+        .join(".");
+    let id = quote_ident(id_field);
+    let columns: BTreeSet<String> = records
+        .iter()
+        .flat_map(|record| record.keys())
+        .map(|column| quote_ident(column))
+        .collect();
+    let updates: Vec<String> = columns
+        .iter()
+        .filter(|column| **column != id)
+        .map(|column| format!("{} = EXCLUDED.{}", column, column))
+        .collect();
+    let on_conflict = if updates.is_empty() {
+        "DO NOTHING".to_string()
+    } else {
+        format!("DO UPDATE SET {}", updates.join(", "))
+    };
+    let columns = columns.into_iter().collect::<Vec<_>>().join(", ");
+    // The records are passed as a single JSON parameter, their fields are
+    // converted to the types of the columns by Postgres
+    let statement = format!(
+        "INSERT INTO {table} ({columns}) SELECT {columns} FROM \
+         jsonb_populate_recordset(NULL::{table}, $1::text::jsonb) ON CONFLICT ({id}) {on_conflict}",
+    );
+    client
+        .execute(&statement, &[&serde_json::to_string(records)?])
+        .await?;
+    Ok(())
+}
+
+// Server certificates are verified against the webpki roots
+fn postgres_tls() -> Result<MakeRustlsConnect> {
+    let roots = RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    };
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let config = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    Ok(MakeRustlsConnect::new(config))
+}
+
+fn quote_ident(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::cast::AsArray;
+    use arrow_ipc::reader::StreamReader;
+
+    use super::*;
+
+    fn sink() -> Sink {
+        Sink {
+            name: "index".to_string(),
+            description: "".to_string(),
+            connector: SinkConnector::Qdrant {
+                url: "http://localhost:6333".to_string(),
+                collection: "docs".to_string(),
+                api_key_secret: None,
+            },
+            id_field: "id".to_string(),
+            vector_field: "embedding".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_records() -> Result<()> {
+        let input = br#"[{"id": "doc-1", "text": "a"}, {"text": "b"}]"#;
+        let parsed = records(&sink(), "output-1", input)?;
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0]["id"], "doc-1");
+        // Writing the same output again replaces its records
+        assert_eq!(parsed[1]["id"], record_id("output-1", 1));
+        assert_ne!(record_id("output-1", 1), record_id("output-1", 2));
+        assert_ne!(record_id("output-1", 1), record_id("output-2", 1));
+        assert!(uuid::Uuid::parse_str(parsed[1]["id"].as_str().unwrap()).is_ok());
+
+        assert_eq!(records(&sink(), "output-1", br#"{"text": "c"}"#)?.len(), 1);
+        assert!(records(&sink(), "output-1", b"\x80\x04pickle").is_err());
+        assert!(records(&sink(), "output-1", b"[1, 2]").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_arrow_stream() -> Result<()> {
+        let records = records(
+            &sink(),
+            "output-1",
+            br#"[{"id": "a", "embedding": [0.5, 1.0], "n": 1}, {"id": "b", "embedding": [2.0, 3.0], "n": 2}]"#,
+        )?;
+        let stream = arrow_stream(&records, "embedding")?;
+        let batches = StreamReader::try_new(&stream[..], None)?.collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(batches.len(), 1);
+        let batch = &batches[0];
+        assert_eq!(batch.num_rows(), 2);
+        let vectors = batch
+            .column_by_name("embedding")
+            .unwrap()
+            .as_fixed_size_list();
+        assert_eq!(vectors.value_length(), 2);
+        let values = vectors.value(1);
+        assert_eq!(
+            values
+                .as_primitive::<arrow_array::types::Float32Type>()
+                .values()
+                .to_vec(),
+            vec![2.0, 3.0]
+        );
+        assert!(batch.column_by_name("id").is_some());
+
+        let mut mismatched = records.clone();
+        mismatched[1].insert("embedding".to_string(), json!([1.0]));
+        assert!(arrow_stream(&mismatched, "embedding").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_quote_ident() {
+        assert_eq!(quote_ident("docs"), "\"docs\"");
+        assert_eq!(quote_ident("we\"ird"), "\"we\"\"ird\"");
+    }
+}
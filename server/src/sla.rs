@@ -0,0 +1,196 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Result;
+use indexify_utils::get_epoch_time_in_ms;
+use state_store::{
+    requests::{RecordSlaStatusRequest, RequestPayload, StateMachineUpdateRequest},
+    IndexifyState,
+};
+
+// How often the SLAs of the compute graphs are evaluated
+const EVALUATION_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Evaluates the SLAs of the compute graphs and records when they become
+/// breached or recover, which notifies the webhooks. Runs on the leader.
+pub struct SlaMonitor {
+    state: Arc<IndexifyState>,
+    shutdown_rx: tokio::sync::watch::Receiver<()>,
+}
+
+impl SlaMonitor {
+    pub fn new(state: Arc<IndexifyState>, shutdown_rx: tokio::sync::watch::Receiver<()>) -> Self {
+        Self { state, shutdown_rx }
+    }
+
+    async fn evaluate_slas(&self) -> Result<()> {
+        let now = get_epoch_time_in_ms();
+        let reader = self.state.reader();
+        for namespace in reader.get_all_namespaces()? {
+            let (graphs, _) = reader.list_compute_graphs(&namespace.name, None, None)?;
+            for graph in graphs {
+                let Some(sla) = &graph.sla else {
+                    continue;
+                };
+                let status = reader.evaluate_sla(&graph.namespace, &graph.name, sla, now)?;
+                let recorded = reader.get_sla_status(&graph.namespace, &graph.name)?;
+                let unchanged = recorded.is_some_and(|recorded| {
+                    recorded.breached == status.breached && recorded.sla == status.sla
+                });
+                if unchanged {
+                    continue;
+                }
+                if status.breached {
+                    tracing::warn!(
+                        "sla of compute graph {}/{} breached, attainment {:?}",
+                        graph.namespace,
+                        graph.name,
+                        status.attainment
+                    );
+                }
+                self.state
+                    .write(StateMachineUpdateRequest {
+                        payload: RequestPayload::RecordSlaStatus(RecordSlaStatusRequest { status }),
+                        state_changes_processed: vec![],
+                    })
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn start(&mut self) -> Result<()> {
+        let mut interval = tokio::time::interval(EVALUATION_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    if let Err(err) = self.evaluate_slas().await {
+                        tracing::error!("error evaluating slas: {:?}", err);
+                    }
+                }
+                _ = self.shutdown_rx.changed() => return Ok(()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use data_model::{
+        sla::GraphSla,
+        test_objects::tests::{mock_graph_a, TEST_NAMESPACE},
+        webhooks::{Webhook, WebhookEventType},
+        InvocationResult,
+        InvocationStatus,
+    };
+    use state_store::{
+        requests::{CreateComputeGraphRequest, CreateWebhookRequest, NamespaceRequest},
+        serializer::{JsonEncode, JsonEncoder},
+        state_machine::IndexifyObjectsColumns,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_sla_alerts() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let mut graph = mock_graph_a();
+        graph.sla = Some(GraphSla {
+            max_duration_secs: 60,
+            target: 0.5,
+            window_secs: 3600,
+        });
+        for payload in [
+            RequestPayload::CreateNameSpace(NamespaceRequest {
+                name: TEST_NAMESPACE.to_string(),
+                queue_limits: Default::default(),
+                scheduling_weight: None,
+                reserved_capacity: None,
+                replication: None,
+            }),
+            RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph: graph.clone(),
+                expected_revision: None,
+                force: false,
+            }),
+            RequestPayload::CreateWebhook(CreateWebhookRequest {
+                webhook: Webhook {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    id: "sla".to_string(),
+                    url: "http://localhost/hook".to_string(),
+                    events: [
+                        WebhookEventType::SlaBreached,
+                        WebhookEventType::SlaRecovered,
+                    ]
+                    .into(),
+                    secret: "00".to_string(),
+                    created_at: 0,
+                    compute_graph: None,
+                },
+            }),
+        ] {
+            state
+                .write(StateMachineUpdateRequest {
+                    payload,
+                    state_changes_processed: vec![],
+                })
+                .await?;
+        }
+        let add_result = |id: &str, duration_secs: u64| -> Result<()> {
+            let finished_at = get_epoch_time_in_ms();
+            let result = InvocationResult {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph_name: graph.name.clone(),
+                invocation_id: id.to_string(),
+                status: InvocationStatus::Success,
+                outputs: vec![],
+                errors: vec![],
+                created_at: finished_at - duration_secs * 1000,
+                finished_at,
+            };
+            state.db.put_cf(
+                &IndexifyObjectsColumns::InvocationResults.cf_db(&state.db),
+                result.key(),
+                &JsonEncoder::encode(&result)?,
+            )?;
+            Ok(())
+        };
+        let monitor = SlaMonitor::new(state.clone(), tokio::sync::watch::channel(()).1);
+        let reader = state.reader();
+        let events = || -> Result<Vec<WebhookEventType>> {
+            Ok(reader
+                .due_webhook_deliveries(u64::MAX, 10)?
+                .into_iter()
+                .map(|delivery| delivery.event.event_type)
+                .collect())
+        };
+
+        add_result("fast", 10)?;
+        monitor.evaluate_slas().await?;
+        assert!(events()?.is_empty());
+
+        // Alerts once when the attainment drops below the target
+        add_result("slow_1", 120)?;
+        add_result("slow_2", 120)?;
+        monitor.evaluate_slas().await?;
+        monitor.evaluate_slas().await?;
+        assert_eq!(events()?, vec![WebhookEventType::SlaBreached]);
+        let status = reader.get_sla_status(TEST_NAMESPACE, &graph.name)?.unwrap();
+        assert!(status.breached);
+        assert_eq!((status.invocations, status.met), (3, 1));
+
+        add_result("fast_2", 10)?;
+        monitor.evaluate_slas().await?;
+        let mut events = events()?;
+        events.sort();
+        assert_eq!(
+            events,
+            vec![
+                WebhookEventType::SlaBreached,
+                WebhookEventType::SlaRecovered
+            ]
+        );
+        Ok(())
+    }
+}
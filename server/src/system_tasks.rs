@@ -1,10 +1,15 @@
 use std::sync::{atomic::AtomicBool, Arc};
 
 use anyhow::Result;
+use blob_store::BlobStorage;
+use data_model::filter::LabelsFilter;
 use state_store::IndexifyState;
 
+use crate::tiering::rehydrate_invocation;
+
 pub struct SystemTasksExecutor {
     state: Arc<IndexifyState>,
+    storage: Arc<BlobStorage>,
     rx: tokio::sync::watch::Receiver<()>,
     shutdown_rx: tokio::sync::watch::Receiver<()>,
     should_shutdown: AtomicBool,
@@ -13,10 +18,15 @@ pub struct SystemTasksExecutor {
 const MAX_PENDING_TASKS: usize = 10;
 
 impl SystemTasksExecutor {
-    pub fn new(state: Arc<IndexifyState>, shutdown_rx: tokio::sync::watch::Receiver<()>) -> Self {
+    pub fn new(
+        state: Arc<IndexifyState>,
+        storage: Arc<BlobStorage>,
+        shutdown_rx: tokio::sync::watch::Receiver<()>,
+    ) -> Self {
         let rx = state.get_system_tasks_watcher();
         Self {
             state,
+            storage,
             rx,
             shutdown_rx,
             should_shutdown: AtomicBool::new(false),
@@ -59,9 +69,12 @@ impl SystemTasksExecutor {
                 &task.compute_graph_name,
                 task.restart_key.as_deref(),
                 Some(MAX_PENDING_TASKS - pending_tasks),
+                &LabelsFilter::default(),
             )?;
             for invocation in invocations {
                 tracing::info!("Executing invocation {:?}", invocation);
+                // Replays read the input from the hot tier
+                rehydrate_invocation(&self.state, &self.storage, &invocation).await?;
                 self.state
                     .write(state_store::requests::StateMachineUpdateRequest {
                         payload: state_store::requests::RequestPayload::RerunInvocation(
@@ -123,6 +136,7 @@ impl SystemTasksExecutor {
 #[cfg(test)]
 mod tests {
 
+    use blob_store::BlobStorageConfig;
     use data_model::{
         test_objects::tests::{
             mock_graph_a,
@@ -172,6 +186,12 @@ mod tests {
                 sha256_hash: generate_random_hash(),
                 path: Uuid::new_v4().to_string(),
                 size: 12,
+                tier: Default::default(),
+                mime_type: None,
+                content_encoding: None,
+                metadata: Default::default(),
+                replicas: Vec::new(),
+                compression: None,
             }))
             .build()
             .unwrap()
@@ -198,6 +218,8 @@ mod tests {
             task_outcome: TaskOutcome::Success,
             executor_id: ExecutorId::new(TEST_EXECUTOR_ID.to_string()),
             diagnostics: None,
+            inline_payloads: vec![],
+            resource_usage: None,
         }
     }
 
@@ -216,12 +238,17 @@ mod tests {
             .unwrap();
         let shutdown_rx = tokio::sync::watch::channel(()).1;
         let scheduler = Scheduler::new(state.clone());
-        let mut executor = SystemTasksExecutor::new(state.clone(), shutdown_rx);
+        let storage = Arc::new(BlobStorage::new(BlobStorageConfig::new_disk(
+            temp_dir.path().join("blob").to_str().unwrap(),
+        ))?);
+        let mut executor = SystemTasksExecutor::new(state.clone(), storage, shutdown_rx);
 
         let graph = mock_graph_a();
         let cg_request = CreateComputeGraphRequest {
             namespace: graph.namespace.clone(),
             compute_graph: graph.clone(),
+            expected_revision: None,
+            force: false,
         };
         state
             .write(StateMachineUpdateRequest {
@@ -365,6 +392,8 @@ mod tests {
         let cg_request = CreateComputeGraphRequest {
             namespace: graph.namespace.clone(),
             compute_graph: graph.clone(),
+            expected_revision: Some(1),
+            force: false,
         };
         state
             .write(StateMachineUpdateRequest {
@@ -512,6 +541,12 @@ mod tests {
                 path: "test".to_string(),
                 size: 23,
                 sha256_hash: generate_random_hash(),
+                tier: Default::default(),
+                mime_type: None,
+                content_encoding: None,
+                metadata: Default::default(),
+                replicas: Vec::new(),
+                compression: None,
             })
             .build()
             .unwrap()
@@ -564,12 +599,17 @@ mod tests {
             .unwrap();
         let shutdown_rx = tokio::sync::watch::channel(()).1;
         let scheduler = Scheduler::new(state.clone());
-        let mut executor = SystemTasksExecutor::new(state.clone(), shutdown_rx);
+        let storage = Arc::new(BlobStorage::new(BlobStorageConfig::new_disk(
+            temp_dir.path().join("blob").to_str().unwrap(),
+        ))?);
+        let mut executor = SystemTasksExecutor::new(state.clone(), storage, shutdown_rx);
 
         let graph = mock_graph_a();
         let cg_request = CreateComputeGraphRequest {
             namespace: graph.namespace.clone(),
             compute_graph: graph.clone(),
+            expected_revision: None,
+            force: false,
         };
         state
             .write(StateMachineUpdateRequest {
@@ -620,6 +660,8 @@ mod tests {
         let cg_request = CreateComputeGraphRequest {
             namespace: graph.namespace.clone(),
             compute_graph: graph.clone(),
+            expected_revision: Some(1),
+            force: false,
         };
         state
             .write(StateMachineUpdateRequest {
@@ -686,7 +728,13 @@ mod tests {
         // Verify that all outputs are initialized with correct graph version
         let invocations = state
             .reader()
-            .list_invocations(&graph.namespace, &graph.name, None, None)?
+            .list_invocations(
+                &graph.namespace,
+                &graph.name,
+                None,
+                None,
+                &LabelsFilter::default(),
+            )?
             .0;
         for invocation in invocations {
             let outputs = state
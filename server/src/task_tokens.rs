@@ -0,0 +1,140 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use data_model::Task;
+use indexify_utils::get_epoch_time_in_ms;
+use ring::{hmac, rand::SystemRandom};
+use serde::{Deserialize, Serialize};
+
+use crate::config::TaskTokenConfig;
+
+/// Task tokens are told apart from the other bearer tokens by their prefix.
+pub const TASK_TOKEN_PREFIX: &str = "itt.";
+
+/// Task a token was minted for, the function of the task acts on behalf of
+/// its graph with it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaskScope {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub invocation_id: String,
+    pub compute_fn: String,
+    pub task_id: String,
+    /// Seconds since the epoch
+    pub expires_at: u64,
+}
+
+/// Mints the short lived tokens sent to executors with their tasks, for the
+/// functions to call back into the server without the credentials of the
+/// executor.
+///
+/// A token carries the scope of its task and a signature of the scope, so
+/// it's verified without a lookup, and can't be widened to another graph or
+/// invocation.
+pub struct TaskTokenSigner {
+    key: hmac::Key,
+    ttl: Duration,
+}
+
+impl TaskTokenSigner {
+    pub fn new(config: &TaskTokenConfig) -> Result<Self> {
+        let key = match &config.signing_key {
+            Some(key) => {
+                let key = hex::decode(key).map_err(|e| anyhow!("invalid signing key: {}", e))?;
+                hmac::Key::new(hmac::HMAC_SHA256, &key)
+            }
+            // Tokens signed with a random key are only valid until the server
+            // restarts
+            None => hmac::Key::generate(hmac::HMAC_SHA256, &SystemRandom::new())
+                .map_err(|_| anyhow!("unable to generate signing key"))?,
+        };
+        Ok(Self {
+            key,
+            ttl: Duration::from_secs(config.ttl_secs),
+        })
+    }
+
+    /// Token of a task sent to an executor at `now`, in seconds since the
+    /// epoch.
+    pub fn mint(&self, task: &Task, now: u64) -> Result<String> {
+        let scope = TaskScope {
+            namespace: task.namespace.clone(),
+            compute_graph: task.compute_graph_name.clone(),
+            invocation_id: task.invocation_id.clone(),
+            compute_fn: task.compute_fn_name.clone(),
+            task_id: task.id.to_string(),
+            expires_at: now + self.ttl.as_secs(),
+        };
+        let claims = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&scope)?);
+        let tag = hmac::sign(&self.key, claims.as_bytes());
+        Ok(format!(
+            "{}{}.{}",
+            TASK_TOKEN_PREFIX,
+            claims,
+            hex::encode(tag.as_ref())
+        ))
+    }
+
+    /// Token of a task sent to its executor now, the task is sent without one
+    /// when it can't be minted.
+    pub fn dispatch_token(&self, task: &Task) -> Option<String> {
+        self.mint(task, get_epoch_time_in_ms() / 1000)
+            .inspect_err(|err| {
+                tracing::error!("failed to mint token of task {}: {:?}", task.id, err)
+            })
+            .ok()
+    }
+
+    /// Scope of a token, `now` is seconds since the epoch.
+    pub fn verify(&self, token: &str, now: u64) -> Result<TaskScope> {
+        let (claims, signature) = token
+            .strip_prefix(TASK_TOKEN_PREFIX)
+            .and_then(|token| token.split_once('.'))
+            .ok_or(anyhow!("malformed task token"))?;
+        let signature = hex::decode(signature).map_err(|_| anyhow!("invalid signature"))?;
+        hmac::verify(&self.key, claims.as_bytes(), &signature)
+            .map_err(|_| anyhow!("invalid signature"))?;
+        let scope: TaskScope = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(claims)?)?;
+        if scope.expires_at <= now {
+            return Err(anyhow!("task token expired"));
+        }
+        Ok(scope)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use data_model::test_objects::tests::{create_mock_task, mock_graph_a};
+
+    use super::*;
+
+    #[test]
+    fn test_mint_and_verify() {
+        let signer = TaskTokenSigner::new(&TaskTokenConfig::default()).unwrap();
+        let task = create_mock_task(&mock_graph_a(), "fn_a", "input", "inv");
+        let token = signer.mint(&task, 100).unwrap();
+        assert!(token.starts_with(TASK_TOKEN_PREFIX));
+        let scope = signer.verify(&token, 100).unwrap();
+        assert_eq!(scope.namespace, task.namespace);
+        assert_eq!(scope.invocation_id, "inv");
+        assert_eq!(scope.task_id, task.id.to_string());
+        assert_eq!(scope.expires_at, 3700);
+
+        // Expired
+        assert!(signer.verify(&token, 3700).is_err());
+        // Claims widened to another invocation
+        let (_, signature) = token.rsplit_once('.').unwrap();
+        let widened = TaskScope {
+            invocation_id: "other".to_string(),
+            ..scope
+        };
+        let claims = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&widened).unwrap());
+        let forged = format!("{}{}.{}", TASK_TOKEN_PREFIX, claims, signature);
+        assert!(signer.verify(&forged, 100).is_err());
+        // Signed by another server
+        let other = TaskTokenSigner::new(&TaskTokenConfig::default()).unwrap();
+        assert!(other.verify(&token, 100).is_err());
+        assert!(signer.verify("itt.garbage", 100).is_err());
+    }
+}
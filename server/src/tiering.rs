@@ -0,0 +1,529 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{anyhow, Result};
+use blob_store::BlobStorage;
+use data_model::{DataPayload, InvocationPayload, OutputPayload, StorageTier};
+use indexify_utils::get_epoch_time_in_ms;
+use state_store::{
+    requests::{
+        MovePayloadsRequest,
+        PayloadMove,
+        PayloadOwner,
+        RequestPayload,
+        StateMachineUpdateRequest,
+    },
+    IndexifyState,
+};
+
+use crate::config::{LifecyclePolicy, RuntimeConfig};
+
+// How often the lifecycle policies are applied
+const TIERING_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+// Invocations whose payloads are moved in a single write
+const BATCH_SIZE: usize = 100;
+
+const DAY_MS: u64 = 24 * 60 * 60 * 1000;
+
+/// Copies a payload to the other tier, checking the copy against the hash
+/// recorded when the payload was written.
+async fn copy_payload(
+    storage: &BlobStorage,
+    payload: &DataPayload,
+    tier: StorageTier,
+) -> Result<DataPayload> {
+    let result = match tier {
+        StorageTier::Cold => storage.archive(&payload.path).await?,
+        StorageTier::Hot => storage.rehydrate(&payload.path).await?,
+        StorageTier::Expired | StorageTier::Referenced => {
+            return Err(anyhow!("payloads can't be copied to {:?}", tier))
+        }
+    };
+    let sha256_hash = payload.stored_sha256_hash();
+    if !sha256_hash.is_empty() && result.sha256_hash != sha256_hash {
+        return Err(anyhow!(
+            "copy of payload {} doesn't match its hash",
+            payload.path
+        ));
+    }
+    // The copy is as compressed as the payload, its size and hash stay the
+    // ones of the uncompressed bytes
+    let (size, sha256_hash) = match &payload.compression {
+        Some(_) => (payload.size, payload.sha256_hash.clone()),
+        None => (result.size_bytes, result.sha256_hash),
+    };
+    Ok(DataPayload {
+        path: result.url,
+        size,
+        sha256_hash,
+        tier,
+        mime_type: payload.mime_type.clone(),
+        content_encoding: payload.content_encoding.clone(),
+        metadata: payload.metadata.clone(),
+        // Replicas are copies of the hot payload, they're kept while it's
+        // archived
+        replicas: payload.replicas.clone(),
+        compression: payload.compression.clone(),
+    })
+}
+
+/// Moves the input of an invocation in the cold tier back to the hot tier,
+/// before the invocation is replayed.
+pub async fn rehydrate_invocation(
+    state: &IndexifyState,
+    storage: &BlobStorage,
+    invocation: &InvocationPayload,
+) -> Result<()> {
+    if invocation.payload.tier == StorageTier::Expired {
+        return Err(anyhow!(
+            "input of invocation {} expired, it can't be replayed",
+            invocation.id
+        ));
+    }
+    if invocation.payload.tier != StorageTier::Cold {
+        return Ok(());
+    }
+    let to = copy_payload(storage, &invocation.payload, StorageTier::Hot).await?;
+    state
+        .write(StateMachineUpdateRequest {
+            payload: RequestPayload::MovePayloads(MovePayloadsRequest {
+                moves: vec![PayloadMove {
+                    owner: PayloadOwner::Invocation {
+                        namespace: invocation.namespace.clone(),
+                        compute_graph: invocation.compute_graph_name.clone(),
+                        invocation_id: invocation.id.clone(),
+                    },
+                    from: invocation.payload.clone(),
+                    to,
+                }],
+            }),
+            state_changes_processed: vec![],
+        })
+        .await
+}
+
+/// Applies the storage lifecycle policies, runs on the leader.
+pub struct StorageTiering {
+    state: Arc<IndexifyState>,
+    storage: Arc<BlobStorage>,
+    runtime_config_rx: tokio::sync::watch::Receiver<RuntimeConfig>,
+    shutdown_rx: tokio::sync::watch::Receiver<()>,
+}
+
+impl StorageTiering {
+    pub fn new(
+        state: Arc<IndexifyState>,
+        storage: Arc<BlobStorage>,
+        runtime_config_rx: tokio::sync::watch::Receiver<RuntimeConfig>,
+        shutdown_rx: tokio::sync::watch::Receiver<()>,
+    ) -> Self {
+        Self {
+            state,
+            storage,
+            runtime_config_rx,
+            shutdown_rx,
+        }
+    }
+
+    async fn archive(&self, payload: &DataPayload) -> Option<DataPayload> {
+        // Inline payloads are stored in the state store
+        if payload.tier != StorageTier::Hot || payload.is_inline() {
+            return None;
+        }
+        match copy_payload(&self.storage, payload, StorageTier::Cold).await {
+            Ok(archived) => Some(archived),
+            Err(err) => {
+                tracing::error!("error archiving payload {}: {:?}", payload.path, err);
+                None
+            }
+        }
+    }
+
+    /// Payload replacing a payload past the retention of its graph, the
+    /// original is garbage collected once replaced.
+    fn expire(&self, payload: &DataPayload) -> Option<DataPayload> {
+        // Referenced payloads are owned by the client
+        if matches!(payload.tier, StorageTier::Expired | StorageTier::Referenced) {
+            return None;
+        }
+        Some(DataPayload {
+            tier: StorageTier::Expired,
+            mime_type: None,
+            content_encoding: None,
+            metadata: Default::default(),
+            replicas: Vec::new(),
+            compression: None,
+            ..payload.clone()
+        })
+    }
+
+    async fn transition(&self, payload: &DataPayload, tier: StorageTier) -> Option<DataPayload> {
+        match tier {
+            StorageTier::Expired => self.expire(payload),
+            _ => self.archive(payload).await,
+        }
+    }
+
+    /// Moves the payloads of the invocations matched by the policy to the
+    /// tier, returns how many payloads were moved.
+    async fn apply_policy(
+        &self,
+        policy: &LifecyclePolicy,
+        after_days: u64,
+        tier: StorageTier,
+    ) -> Result<usize> {
+        let created_before = get_epoch_time_in_ms().saturating_sub(after_days * DAY_MS);
+        let mut moved = 0;
+        let mut restart_key = None;
+        loop {
+            let (invocations, next_key) =
+                self.state.reader().completed_invocations_created_before(
+                    &policy.namespace,
+                    policy.compute_graph.as_deref(),
+                    created_before,
+                    restart_key.as_deref(),
+                    Some(BATCH_SIZE),
+                )?;
+            let mut moves = vec![];
+            for ctx in invocations {
+                let reader = self.state.reader();
+                // Held invocations are archived but never expire
+                if tier == StorageTier::Expired &&
+                    reader.is_under_legal_hold(
+                        &ctx.namespace,
+                        &ctx.compute_graph_name,
+                        &ctx.invocation_id,
+                    )?
+                {
+                    continue;
+                }
+                let invocation = reader.invocation_payload(
+                    &ctx.namespace,
+                    &ctx.compute_graph_name,
+                    &ctx.invocation_id,
+                )?;
+                if let Some(to) = self.transition(&invocation.payload, tier).await {
+                    moves.push(PayloadMove {
+                        owner: PayloadOwner::Invocation {
+                            namespace: ctx.namespace.clone(),
+                            compute_graph: ctx.compute_graph_name.clone(),
+                            invocation_id: ctx.invocation_id.clone(),
+                        },
+                        from: invocation.payload,
+                        to,
+                    });
+                }
+                let (outputs, _) = reader.list_outputs_by_compute_graph(
+                    &ctx.namespace,
+                    &ctx.compute_graph_name,
+                    &ctx.invocation_id,
+                    None,
+                    None,
+                )?;
+                for output in outputs {
+                    let OutputPayload::Fn(payload) = &output.payload else {
+                        continue;
+                    };
+                    if let Some(to) = self.transition(payload, tier).await {
+                        moves.push(PayloadMove {
+                            owner: PayloadOwner::FnOutput {
+                                key: output.key(&output.invocation_id),
+                            },
+                            from: payload.clone(),
+                            to,
+                        });
+                    }
+                }
+            }
+            if !moves.is_empty() {
+                moved += moves.len();
+                self.state
+                    .write(StateMachineUpdateRequest {
+                        payload: RequestPayload::MovePayloads(MovePayloadsRequest { moves }),
+                        state_changes_processed: vec![],
+                    })
+                    .await?;
+            }
+            match next_key {
+                Some(next_key) => restart_key = Some(next_key),
+                None => return Ok(moved),
+            }
+        }
+    }
+
+    async fn apply_policies(&self) -> Result<()> {
+        let policies = self
+            .runtime_config_rx
+            .borrow()
+            .storage_lifecycle_policies
+            .clone();
+        for policy in policies {
+            // Payloads past their retention aren't archived first
+            let transitions = [
+                (policy.expire_payloads_after_days, StorageTier::Expired),
+                (policy.archive_after_days, StorageTier::Cold),
+            ];
+            for (after_days, tier) in transitions {
+                let Some(after_days) = after_days else {
+                    continue;
+                };
+                let moved = self.apply_policy(&policy, after_days, tier).await?;
+                if moved > 0 {
+                    tracing::info!(
+                        "moved {} payloads of namespace {} to tier {:?}",
+                        moved,
+                        policy.namespace,
+                        tier
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn start(&mut self) -> Result<()> {
+        let mut interval = tokio::time::interval(TIERING_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    if let Err(err) = self.apply_policies().await {
+                        tracing::error!("error applying storage lifecycle policies: {:?}", err);
+                    }
+                }
+                _ = self.shutdown_rx.changed() => return Ok(()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use blob_store::{BlobStorageConfig, ColdStorageConfig, DiskStorageConfig};
+    use bytes::Bytes;
+    use data_model::{
+        legal_hold::LegalHold,
+        test_objects::tests::{mock_graph_a, mock_invocation_payload, TEST_NAMESPACE},
+        GraphInvocationCtxBuilder,
+    };
+    use futures::stream;
+    use state_store::{
+        serializer::{JsonEncode, JsonEncoder},
+        state_machine::IndexifyObjectsColumns,
+    };
+    use tokio::sync::watch;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_storage_tiering() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let mut config = BlobStorageConfig::new_disk(temp_dir.path().join("hot").to_str().unwrap());
+        config.cold = Some(ColdStorageConfig {
+            s3: None,
+            disk: Some(DiskStorageConfig {
+                path: temp_dir.path().join("cold").to_str().unwrap().to_string(),
+            }),
+        });
+        let storage = Arc::new(BlobStorage::new(config)?);
+
+        let data_stream = Box::pin(stream::once(async { Ok(Bytes::from("input")) }));
+        let res = storage.put("input", data_stream).await?;
+        let mut invocation = mock_invocation_payload();
+        invocation.payload = DataPayload {
+            path: res.url.clone(),
+            size: res.size_bytes,
+            sha256_hash: res.sha256_hash,
+            tier: StorageTier::Hot,
+            mime_type: None,
+            content_encoding: None,
+            metadata: Default::default(),
+            replicas: Vec::new(),
+            compression: None,
+        };
+        let mut ctx = GraphInvocationCtxBuilder::default()
+            .namespace(TEST_NAMESPACE.to_string())
+            .compute_graph_name(invocation.compute_graph_name.clone())
+            .invocation_id(invocation.id.clone())
+            .build(mock_graph_a())?;
+        ctx.completed = true;
+        ctx.created_at = get_epoch_time_in_ms() - 2 * DAY_MS;
+        state.db.put_cf(
+            &IndexifyObjectsColumns::GraphInvocations.cf_db(&state.db),
+            invocation.key(),
+            &JsonEncoder::encode(&invocation)?,
+        )?;
+        state.db.put_cf(
+            &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&state.db),
+            ctx.key(),
+            &JsonEncoder::encode(&ctx)?,
+        )?;
+
+        let (runtime_config_tx, runtime_config_rx) = watch::channel(RuntimeConfig::default());
+        let tiering = StorageTiering::new(
+            state.clone(),
+            storage.clone(),
+            runtime_config_rx,
+            watch::channel(()).1,
+        );
+        let mut policy = LifecyclePolicy {
+            namespace: TEST_NAMESPACE.to_string(),
+            compute_graph: None,
+            archive_after_days: Some(3),
+            expire_payloads_after_days: None,
+        };
+        runtime_config_tx.send_modify(|config| {
+            config.storage_lifecycle_policies = vec![policy.clone()];
+        });
+        tiering.apply_policies().await?;
+        let reader = state.reader();
+        let stored =
+            reader.invocation_payload(TEST_NAMESPACE, &ctx.compute_graph_name, &invocation.id)?;
+        assert_eq!(stored.payload.tier, StorageTier::Hot);
+
+        // Archived once old enough, the hot copy is garbage collected
+        assert_eq!(
+            tiering.apply_policy(&policy, 1, StorageTier::Cold).await?,
+            1
+        );
+        let archived =
+            reader.invocation_payload(TEST_NAMESPACE, &ctx.compute_graph_name, &invocation.id)?;
+        assert_eq!(archived.payload.tier, StorageTier::Cold);
+        assert_ne!(archived.payload.path, res.url);
+        assert_eq!(storage.read_bytes(&archived.payload.path).await?, "input");
+        assert_eq!(reader.get_gc_urls(None)?, vec![res.url.clone()]);
+        assert_eq!(
+            tiering.apply_policy(&policy, 1, StorageTier::Cold).await?,
+            0
+        );
+
+        rehydrate_invocation(&state, &storage, &archived).await?;
+        let rehydrated =
+            reader.invocation_payload(TEST_NAMESPACE, &ctx.compute_graph_name, &invocation.id)?;
+        assert_eq!(rehydrated.payload.tier, StorageTier::Hot);
+        assert_eq!(rehydrated.payload.path, res.url);
+        assert_eq!(storage.read_bytes(&rehydrated.payload.path).await?, "input");
+        assert_eq!(
+            reader.get_gc_urls(None)?,
+            vec![archived.payload.path.clone()]
+        );
+
+        // Held invocations don't expire
+        let hold = LegalHold {
+            namespace: TEST_NAMESPACE.to_string(),
+            compute_graph: ctx.compute_graph_name.clone(),
+            invocation_id: Some(invocation.id.clone()),
+            reason: "litigation".to_string(),
+            set_by: "alice".to_string(),
+            set_at: get_epoch_time_in_ms(),
+        };
+        let holds_cf = IndexifyObjectsColumns::LegalHolds.cf_db(&state.db);
+        state
+            .db
+            .put_cf(&holds_cf, hold.key(), &JsonEncoder::encode(&hold)?)?;
+        assert_eq!(
+            tiering
+                .apply_policy(&policy, 1, StorageTier::Expired)
+                .await?,
+            0
+        );
+        state.db.delete_cf(&holds_cf, hold.key())?;
+
+        // Past the retention the payload is deleted, its record is kept
+        policy.expire_payloads_after_days = Some(1);
+        runtime_config_tx.send_modify(|config| {
+            config.storage_lifecycle_policies = vec![policy.clone()];
+        });
+        tiering.apply_policies().await?;
+        let expired =
+            reader.invocation_payload(TEST_NAMESPACE, &ctx.compute_graph_name, &invocation.id)?;
+        assert_eq!(expired.payload.tier, StorageTier::Expired);
+        assert_eq!(expired.payload.path, res.url);
+        assert_eq!(expired.payload.size, res.size_bytes);
+        let gc_urls = reader.get_gc_urls(None)?;
+        assert!(gc_urls.contains(&res.url));
+        assert_eq!(
+            tiering
+                .apply_policy(&policy, 1, StorageTier::Expired)
+                .await?,
+            0
+        );
+        assert!(rehydrate_invocation(&state, &storage, &expired)
+            .await
+            .is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_referenced_payloads_stay_in_place() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let mut config = BlobStorageConfig::new_disk(temp_dir.path().join("hot").to_str().unwrap());
+        config.cold = Some(ColdStorageConfig {
+            s3: None,
+            disk: Some(DiskStorageConfig {
+                path: temp_dir.path().join("cold").to_str().unwrap().to_string(),
+            }),
+        });
+        let storage = Arc::new(BlobStorage::new(config)?);
+
+        let data_stream = Box::pin(stream::once(async { Ok(Bytes::from("input")) }));
+        let res = storage.put("client/input", data_stream).await?;
+        let object = storage.head(&res.url).await?;
+        assert_eq!(object.url, res.url);
+        assert_eq!(object.size_bytes, res.size_bytes);
+        assert!(storage.head(&storage.key_url("missing")).await.is_err());
+
+        let mut invocation = mock_invocation_payload();
+        invocation.payload = DataPayload {
+            path: object.url,
+            size: object.size_bytes,
+            sha256_hash: res.sha256_hash,
+            tier: StorageTier::Referenced,
+            mime_type: None,
+            content_encoding: None,
+            metadata: Default::default(),
+            replicas: Vec::new(),
+            compression: None,
+        };
+        let mut ctx = GraphInvocationCtxBuilder::default()
+            .namespace(TEST_NAMESPACE.to_string())
+            .compute_graph_name(invocation.compute_graph_name.clone())
+            .invocation_id(invocation.id.clone())
+            .build(mock_graph_a())?;
+        ctx.completed = true;
+        ctx.created_at = get_epoch_time_in_ms() - 2 * DAY_MS;
+        state.db.put_cf(
+            &IndexifyObjectsColumns::GraphInvocations.cf_db(&state.db),
+            invocation.key(),
+            &JsonEncoder::encode(&invocation)?,
+        )?;
+        state.db.put_cf(
+            &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&state.db),
+            ctx.key(),
+            &JsonEncoder::encode(&ctx)?,
+        )?;
+
+        let tiering = StorageTiering::new(
+            state.clone(),
+            storage.clone(),
+            watch::channel(RuntimeConfig::default()).1,
+            watch::channel(()).1,
+        );
+        let policy = LifecyclePolicy {
+            namespace: TEST_NAMESPACE.to_string(),
+            compute_graph: None,
+            archive_after_days: Some(1),
+            expire_payloads_after_days: Some(1),
+        };
+        for tier in [StorageTier::Cold, StorageTier::Expired] {
+            assert_eq!(tiering.apply_policy(&policy, 1, tier).await?, 0);
+        }
+        let reader = state.reader();
+        let stored =
+            reader.invocation_payload(TEST_NAMESPACE, &ctx.compute_graph_name, &invocation.id)?;
+        assert_eq!(stored.payload, invocation.payload);
+        assert!(reader.get_gc_urls(None)?.is_empty());
+        Ok(())
+    }
+}
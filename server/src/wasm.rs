@@ -0,0 +1,349 @@
+//! Runs routers compiled to WASM inside the server, saving the round trip to
+//! an executor for routers which only inspect a small JSON payload.
+//!
+//! A router module exports its `memory` and two functions:
+//! - `alloc(len: i32) -> i32` returns a buffer the input is written to
+//! - `route(ptr: i32, len: i32) -> i64` reads the JSON input of the router from
+//!   the buffer and returns the offset of its output in the high 32 bits and
+//!   its length in the low 32 bits. The output is a JSON array of the names of
+//!   the functions to route to.
+//!
+//! Modules can't import anything, their instructions and memory are bounded
+//! by the `wasm` settings of the server.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{anyhow, Result};
+use blob_store::BlobStorage;
+use bytes::Bytes;
+use data_model::{
+    DataPayload,
+    ExecutorId,
+    Node,
+    NodeOutputBuilder,
+    OutputPayload,
+    RouterOutput,
+    Task,
+    TaskDiagnostics,
+    TaskOutcome,
+};
+use state_store::{
+    requests::{FinalizeTaskRequest, RequestPayload, StateMachineUpdateRequest},
+    IndexifyState,
+};
+use tracing::{error, info};
+use wasmi::{Config, Engine, Linker, Module, Store, StoreLimits, StoreLimitsBuilder};
+
+use crate::{config::WasmConfig, routes::payload_compression::read_payload_bytes};
+
+pub struct WasmRouters {
+    indexify_state: Arc<IndexifyState>,
+    blob_storage: Arc<BlobStorage>,
+    config: WasmConfig,
+    engine: Engine,
+    // Compiled modules by the hash of their code
+    modules: Mutex<HashMap<String, Module>>,
+}
+
+impl WasmRouters {
+    pub fn new(
+        indexify_state: Arc<IndexifyState>,
+        blob_storage: Arc<BlobStorage>,
+        config: WasmConfig,
+    ) -> Self {
+        let mut engine_config = Config::default();
+        engine_config.consume_fuel(true);
+        Self {
+            indexify_state,
+            blob_storage,
+            config,
+            engine: Engine::new(&engine_config),
+            modules: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Runs the tasks of WASM routers in the background, other tasks are
+    /// ignored.
+    pub fn spawn_tasks(self: &Arc<Self>, tasks: Vec<Task>) -> Result<()> {
+        for task in tasks {
+            let graph = self.indexify_state.reader().get_compute_graph_version(
+                &task.namespace,
+                &task.compute_graph_name,
+                &task.graph_version,
+            )?;
+            let is_wasm_router = graph
+                .as_ref()
+                .and_then(|graph| graph.nodes.get(&task.compute_fn_name))
+                .is_some_and(|node| node.wasm_module().is_some());
+            if !is_wasm_router {
+                continue;
+            }
+            let routers = self.clone();
+            tokio::spawn(async move {
+                if let Err(err) = routers.run_task(&task).await {
+                    error!("failed to finalize wasm router task {}: {:?}", task.id, err);
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// Runs the tasks of WASM routers which were created but not finished
+    /// before the scheduler stopped.
+    pub fn resume(self: &Arc<Self>) -> Result<()> {
+        let tasks = self.indexify_state.reader().unallocated_tasks()?;
+        self.spawn_tasks(tasks)
+    }
+
+    async fn run_task(&self, task: &Task) -> Result<()> {
+        let (node_outputs, outcome, diagnostics) = match self.route(task).await {
+            Ok(edges) => {
+                info!("wasm router task {} routed to {:?}", task.id, edges);
+                let node_output = NodeOutputBuilder::default()
+                    .namespace(task.namespace.clone())
+                    .graph_version(Default::default())
+                    .compute_graph_name(task.compute_graph_name.clone())
+                    .invocation_id(task.invocation_id.clone())
+                    .compute_fn_name(task.compute_fn_name.clone())
+                    .payload(OutputPayload::Router(RouterOutput { edges }))
+                    .build()?;
+                (vec![node_output], TaskOutcome::Success, None)
+            }
+            Err(err) => {
+                info!("wasm router task {} failed: {:?}", task.id, err);
+                let exception = put_exception(&self.blob_storage, task, &err).await?;
+                let diagnostics = TaskDiagnostics {
+                    exception: Some(exception),
+                    stdout: None,
+                    stderr: None,
+                    structured_logs: None,
+                };
+                (vec![], TaskOutcome::Failure, Some(diagnostics))
+            }
+        };
+        self.indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::FinalizeTask(FinalizeTaskRequest {
+                    namespace: task.namespace.clone(),
+                    compute_graph: task.compute_graph_name.clone(),
+                    compute_fn: task.compute_fn_name.clone(),
+                    invocation_id: task.invocation_id.clone(),
+                    task_id: task.id.clone(),
+                    node_outputs,
+                    task_outcome: outcome,
+                    // WASM router tasks are not allocated to an executor
+                    executor_id: ExecutorId::default(),
+                    diagnostics,
+                    inline_payloads: vec![],
+                    resource_usage: None,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await
+    }
+
+    async fn route(&self, task: &Task) -> Result<Vec<String>> {
+        let graph = self
+            .indexify_state
+            .reader()
+            .get_compute_graph_version(
+                &task.namespace,
+                &task.compute_graph_name,
+                &task.graph_version,
+            )?
+            .ok_or(anyhow!("compute graph not found"))?;
+        let Some(Node::Router(router)) = graph.nodes.get(&task.compute_fn_name) else {
+            return Err(anyhow!("router not found: {}", task.compute_fn_name));
+        };
+        let module_payload = router
+            .wasm_module
+            .as_ref()
+            .ok_or(anyhow!("router {} has no wasm module", router.name))?;
+        let module = self.module(module_payload).await?;
+        let input = read_input(&self.indexify_state, &self.blob_storage, task).await?;
+        let engine = self.engine.clone();
+        let config = self.config.clone();
+        let edges =
+            tokio::task::spawn_blocking(move || run_router(&engine, &module, &config, &input))
+                .await??;
+        for edge in &edges {
+            if !router.target_functions.contains(edge) {
+                return Err(anyhow!(
+                    "router {} returned {} which is not one of its target functions",
+                    router.name,
+                    edge
+                ));
+            }
+        }
+        Ok(edges)
+    }
+
+    async fn module(&self, payload: &DataPayload) -> Result<Module> {
+        if let Some(module) = self.modules.lock().unwrap().get(&payload.sha256_hash) {
+            return Ok(module.clone());
+        }
+        let code = self.blob_storage.read_bytes(&payload.path).await?;
+        let module = Module::new(&self.engine, &code[..])
+            .map_err(|e| anyhow!("invalid wasm module: {}", e))?;
+        self.modules
+            .lock()
+            .unwrap()
+            .insert(payload.sha256_hash.clone(), module.clone());
+        Ok(module)
+    }
+}
+
+/// Reads the input of a task run by the server. Tasks of the start node read
+/// the invocation payload.
+pub(crate) async fn read_input(
+    indexify_state: &IndexifyState,
+    blob_storage: &BlobStorage,
+    task: &Task,
+) -> Result<Bytes> {
+    let reader = indexify_state.reader();
+    let payload = if task.input_node_output_key == task.invocation_id {
+        reader
+            .invocation_payload(
+                &task.namespace,
+                &task.compute_graph_name,
+                &task.invocation_id,
+            )?
+            .payload
+    } else {
+        match reader
+            .fn_output_payload_by_key(&task.input_node_output_key)?
+            .payload
+        {
+            OutputPayload::Fn(payload) => payload,
+            OutputPayload::Router(_) => {
+                return Err(anyhow!("task {} has a router output as input", task.id))
+            }
+        }
+    };
+    if payload.is_inline() {
+        return reader
+            .get_inline_payload(&payload.path)?
+            .map(Bytes::from)
+            .ok_or(anyhow!("inline payload {} not found", payload.path));
+    }
+    read_payload_bytes(blob_storage, &payload).await
+}
+
+/// Stores the error of a task run by the server as its exception.
+pub(crate) async fn put_exception(
+    blob_storage: &BlobStorage,
+    task: &Task,
+    err: &anyhow::Error,
+) -> Result<DataPayload> {
+    let key = format!(
+        "{}.{}.{}.{}.{}.exception_msg",
+        task.namespace,
+        task.compute_graph_name,
+        task.compute_fn_name,
+        task.invocation_id,
+        task.id
+    );
+    let message = Bytes::from(format!("{:?}", err));
+    let put_result = blob_storage
+        .put(&key, futures::stream::iter(vec![Ok(message)]))
+        .await?;
+    Ok(DataPayload {
+        path: put_result.url,
+        size: put_result.size_bytes,
+        sha256_hash: put_result.sha256_hash,
+        tier: Default::default(),
+        mime_type: None,
+        content_encoding: None,
+        metadata: Default::default(),
+        replicas: Vec::new(),
+        compression: None,
+    })
+}
+
+fn run_router(
+    engine: &Engine,
+    module: &Module,
+    config: &WasmConfig,
+    input: &[u8],
+) -> Result<Vec<String>> {
+    let limits = StoreLimitsBuilder::new()
+        .memory_size(config.max_memory_bytes)
+        .build();
+    let mut store = Store::new(engine, limits);
+    store.limiter(|limits| limits);
+    store.set_fuel(config.fuel)?;
+    let linker = Linker::<StoreLimits>::new(engine);
+    let instance = linker.instantiate(&mut store, module)?.start(&mut store)?;
+    let memory = instance
+        .get_memory(&store, "memory")
+        .ok_or(anyhow!("wasm module does not export its memory"))?;
+    let alloc = instance.get_typed_func::<i32, i32>(&store, "alloc")?;
+    let route = instance.get_typed_func::<(i32, i32), i64>(&store, "route")?;
+
+    let len = i32::try_from(input.len())?;
+    let ptr = alloc.call(&mut store, len)?;
+    memory.write(&mut store, ptr as u32 as usize, input)?;
+    let output = route.call(&mut store, (ptr, len))? as u64;
+    let (output_ptr, output_len) = ((output >> 32) as usize, (output & 0xffff_ffff) as usize);
+    let mut output = vec![0; output_len];
+    memory.read(&store, output_ptr, &mut output)?;
+    serde_json::from_slice(&output).map_err(|e| anyhow!("invalid router output: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Bump allocator from offset 1024, `route` returns its constant output or
+    // echoes its input
+    fn module(engine: &Engine, route: &str) -> Module {
+        let wat = format!(
+            r#"(module
+                (memory (export "memory") 1)
+                (data (i32.const 0) "[\"fn_b\"]")
+                (global $next (mut i32) (i32.const 1024))
+                (func (export "alloc") (param $len i32) (result i32)
+                    (local $ptr i32)
+                    (local.set $ptr (global.get $next))
+                    (global.set $next (i32.add (global.get $next) (local.get $len)))
+                    (local.get $ptr))
+                (func (export "route") (param $ptr i32) (param $len i32) (result i64)
+                    {}))"#,
+            route
+        );
+        Module::new(engine, &wat::parse_str(wat).unwrap()[..]).unwrap()
+    }
+
+    fn engine() -> Engine {
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        Engine::new(&config)
+    }
+
+    #[test]
+    fn test_run_router() -> Result<()> {
+        let engine = engine();
+        let config = WasmConfig::default();
+
+        let constant = module(&engine, "(i64.const 8)");
+        let edges = run_router(&engine, &constant, &config, br#"{"doc_type": "invoice"}"#)?;
+        assert_eq!(edges, vec!["fn_b".to_string()]);
+
+        let echo = module(
+            &engine,
+            "(i64.or (i64.shl (i64.extend_i32_u (local.get $ptr)) (i64.const 32)) \
+             (i64.extend_i32_u (local.get $len)))",
+        );
+        let edges = run_router(&engine, &echo, &config, br#"["fn_b", "fn_c"]"#)?;
+        assert_eq!(edges, vec!["fn_b".to_string(), "fn_c".to_string()]);
+        assert!(run_router(&engine, &echo, &config, b"not json").is_err());
+
+        // Runaway routers are stopped once they are out of fuel
+        let spin = module(&engine, "(loop $spin (br $spin)) (unreachable)");
+        assert!(run_router(&engine, &spin, &config, b"{}").is_err());
+        Ok(())
+    }
+}
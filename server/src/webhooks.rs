@@ -0,0 +1,217 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{anyhow, Result};
+use data_model::webhooks::{Webhook, WebhookDelivery};
+use futures::future::join_all;
+use indexify_utils::get_epoch_time_in_ms;
+use ring::{
+    hmac,
+    rand::{SecureRandom, SystemRandom},
+};
+use state_store::{
+    requests::{RequestPayload, StateMachineUpdateRequest, UpdateWebhookDeliveriesRequest},
+    IndexifyState,
+};
+
+use crate::config::WebhookConfig;
+
+pub const EVENT_HEADER: &str = "x-indexify-event";
+pub const EVENT_ID_HEADER: &str = "x-indexify-event-id";
+pub const TIMESTAMP_HEADER: &str = "x-indexify-timestamp";
+pub const SIGNATURE_HEADER: &str = "x-indexify-signature";
+
+// Deliveries attempted concurrently
+const BATCH_SIZE: usize = 64;
+
+// How often deliveries waiting to be retried are checked for when no event is
+// queued
+const RETRY_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Hex encoded random key signing the payloads of a new webhook.
+pub fn generate_secret() -> Result<String> {
+    let mut secret = [0u8; 32];
+    SystemRandom::new()
+        .fill(&mut secret)
+        .map_err(|_| anyhow!("unable to generate webhook secret"))?;
+    Ok(hex::encode(secret))
+}
+
+/// Signature sent in the signature header, `sha256=<hex hmac>` of
+/// `<timestamp>.<body>`. Receivers should also reject old timestamps, so
+/// captured deliveries can't be replayed.
+pub fn sign_payload(secret: &str, timestamp: u64, body: &[u8]) -> Result<String> {
+    let key = hex::decode(secret).map_err(|_| anyhow!("invalid webhook secret"))?;
+    let mut context = hmac::Context::with_key(&hmac::Key::new(hmac::HMAC_SHA256, &key));
+    context.update(format!("{}.", timestamp).as_bytes());
+    context.update(body);
+    Ok(format!("sha256={}", hex::encode(context.sign().as_ref())))
+}
+
+/// Delay before attempting again a delivery which failed `attempts` times.
+pub fn retry_delay(config: &WebhookConfig, attempts: u32) -> Duration {
+    let exponent = attempts.saturating_sub(1).min(32);
+    let backoff = config.initial_backoff_secs.saturating_mul(1 << exponent);
+    Duration::from_secs(backoff.min(config.max_backoff_secs))
+}
+
+/// Delivers the events queued for webhooks, runs on the leader.
+pub struct WebhookDispatcher {
+    state: Arc<IndexifyState>,
+    config: WebhookConfig,
+    client: reqwest::Client,
+    rx: tokio::sync::watch::Receiver<()>,
+    shutdown_rx: tokio::sync::watch::Receiver<()>,
+}
+
+impl WebhookDispatcher {
+    pub fn new(
+        state: Arc<IndexifyState>,
+        config: WebhookConfig,
+        shutdown_rx: tokio::sync::watch::Receiver<()>,
+    ) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .build()?;
+        let rx = state.get_webhooks_watcher();
+        Ok(Self {
+            state,
+            config,
+            client,
+            rx,
+            shutdown_rx,
+        })
+    }
+
+    async fn deliver(&self, webhook: &Webhook, delivery: &WebhookDelivery) -> Result<()> {
+        let body = serde_json::to_vec(&delivery.event)?;
+        let timestamp = get_epoch_time_in_ms() / 1000;
+        let signature = sign_payload(&webhook.secret, timestamp, &body)?;
+        let response = self
+            .client
+            .post(&webhook.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, delivery.event.event_type.as_str())
+            .header(EVENT_ID_HEADER, &delivery.event.id)
+            .header(TIMESTAMP_HEADER, timestamp.to_string())
+            .header(SIGNATURE_HEADER, signature)
+            .body(body)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(anyhow!("endpoint responded with {}", response.status()));
+        }
+        Ok(())
+    }
+
+    /// Attempts the deliveries which are due, returns how many were
+    /// attempted.
+    async fn dispatch(&self) -> Result<usize> {
+        let now = get_epoch_time_in_ms();
+        let deliveries = {
+            let reader = self.state.reader();
+            reader
+                .due_webhook_deliveries(now, BATCH_SIZE)?
+                .into_iter()
+                .map(|delivery| {
+                    let webhook = reader.get_webhook(&delivery.namespace, &delivery.webhook_id)?;
+                    Ok((delivery, webhook))
+                })
+                .collect::<Result<Vec<_>>>()?
+        };
+        if deliveries.is_empty() {
+            return Ok(0);
+        }
+        let attempts = deliveries.iter().map(|(delivery, webhook)| async move {
+            match webhook {
+                Some(webhook) => self.deliver(webhook, delivery).await,
+                // Deleted along with its deliveries since they were read
+                None => Ok(()),
+            }
+        });
+        let results = join_all(attempts).await;
+
+        let mut request = UpdateWebhookDeliveriesRequest::default();
+        for ((mut delivery, _), result) in deliveries.into_iter().zip(results) {
+            let Err(err) = result else {
+                request.delivered.push(delivery);
+                continue;
+            };
+            delivery.attempts += 1;
+            delivery.last_error = Some(err.to_string());
+            tracing::warn!(
+                "webhook {} delivery of event {} failed, attempt {}: {:?}",
+                delivery.webhook_key(),
+                delivery.event.id,
+                delivery.attempts,
+                err
+            );
+            if delivery.attempts >= self.config.max_attempts {
+                request.dead_lettered.push(delivery);
+            } else {
+                delivery.next_attempt_at =
+                    now + retry_delay(&self.config, delivery.attempts).as_millis() as u64;
+                request.retried.push(delivery);
+            }
+        }
+        let attempted =
+            request.delivered.len() + request.retried.len() + request.dead_lettered.len();
+        self.state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::UpdateWebhookDeliveries(request),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        Ok(attempted)
+    }
+
+    pub async fn start(&mut self) -> Result<()> {
+        loop {
+            if self.shutdown_rx.has_changed().unwrap_or(false) {
+                return Ok(());
+            }
+            match self.dispatch().await {
+                Ok(0) => {}
+                Ok(_) => continue,
+                Err(err) => tracing::error!("error delivering webhook events: {:?}", err),
+            }
+            tokio::select! {
+                _ = self.rx.changed() => { self.rx.borrow_and_update(); }
+                _ = tokio::time::sleep(RETRY_POLL_INTERVAL) => {}
+                _ = self.shutdown_rx.changed() => return Ok(()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_delay() {
+        let config = WebhookConfig {
+            initial_backoff_secs: 5,
+            max_backoff_secs: 60,
+            ..Default::default()
+        };
+        let delays: Vec<u64> = (1..=6)
+            .map(|attempts| retry_delay(&config, attempts).as_secs())
+            .collect();
+        assert_eq!(delays, vec![5, 10, 20, 40, 60, 60]);
+        assert_eq!(retry_delay(&config, u32::MAX).as_secs(), 60);
+    }
+
+    #[test]
+    fn test_sign_payload() -> Result<()> {
+        let secret = generate_secret()?;
+        let signature = sign_payload(&secret, 1700000000, b"{}")?;
+        assert!(signature.starts_with("sha256="));
+        assert_eq!(signature, sign_payload(&secret, 1700000000, b"{}")?);
+        assert_ne!(signature, sign_payload(&secret, 1700000001, b"{}")?);
+        assert_ne!(
+            signature,
+            sign_payload(&generate_secret()?, 1700000000, b"{}")?
+        );
+        Ok(())
+    }
+}
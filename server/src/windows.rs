@@ -0,0 +1,59 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Result;
+use indexify_utils::get_epoch_time_in_ms;
+use state_store::{
+    requests::{CloseWindowRequest, RequestPayload, StateMachineUpdateRequest},
+    IndexifyState,
+};
+
+// How often the windows are checked for inputs older than their max age
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Closes the windows whose oldest input waited longer than the max age of
+/// the window. Windows reaching max_items are closed by the state store when
+/// the tasks are created. Runs on the leader.
+pub struct WindowCloser {
+    state: Arc<IndexifyState>,
+    shutdown_rx: tokio::sync::watch::Receiver<()>,
+}
+
+impl WindowCloser {
+    pub fn new(state: Arc<IndexifyState>, shutdown_rx: tokio::sync::watch::Receiver<()>) -> Self {
+        Self { state, shutdown_rx }
+    }
+
+    async fn close_expired_windows(&self) -> Result<()> {
+        let expired = self
+            .state
+            .reader()
+            .expired_windows(get_epoch_time_in_ms())?;
+        for item in expired {
+            self.state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::CloseWindow(CloseWindowRequest {
+                        namespace: item.namespace,
+                        compute_graph: item.compute_graph,
+                        window: item.window,
+                    }),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+        }
+        Ok(())
+    }
+
+    pub async fn start(&mut self) -> Result<()> {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    if let Err(err) = self.close_expired_windows().await {
+                        tracing::error!("error closing expired windows: {:?}", err);
+                    }
+                }
+                _ = self.shutdown_rx.changed() => return Ok(()),
+            }
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use std::fmt::{self, Display};
+
+/// Errors returned by state store operations. They are wrapped in
+/// `anyhow::Error`, callers can recover them with `downcast_ref`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StateStoreError {
+    NotFound(String),
+    Conflict(String),
+    // Writes are rejected on followers of a cluster
+    ReadOnly,
+    // User writes are rejected while the server is in maintenance mode
+    Maintenance,
+    // A write applied by the leader wasn't acknowledged by a majority of the
+    // cluster in time. It isn't rolled back, followers may still apply it,
+    // and it may be lost if the leader fails
+    NotAcknowledged,
+}
+
+impl StateStoreError {
+    pub fn not_found(what: impl Display) -> Self {
+        StateStoreError::NotFound(what.to_string())
+    }
+}
+
+impl Display for StateStoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateStoreError::NotFound(what) => write!(f, "{} not found", what),
+            StateStoreError::Conflict(reason) => write!(f, "{}", reason),
+            StateStoreError::ReadOnly => {
+                write!(f, "state store is read only, this server is not the leader")
+            }
+            StateStoreError::Maintenance => {
+                write!(f, "server is in maintenance mode, only reads are accepted")
+            }
+            StateStoreError::NotAcknowledged => {
+                write!(
+                    f,
+                    "write was applied by the leader but not acknowledged by a majority in time"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for StateStoreError {}
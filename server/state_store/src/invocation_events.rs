@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use data_model::{TaskAnalytics, TaskOutcome};
+use data_model::{invocation_group::InvocationGroup, InvocationResult, TaskAnalytics, TaskOutcome};
 use serde::{Deserialize, Serialize};
 
 use crate::requests;
@@ -12,6 +12,7 @@ pub enum InvocationStateChangeEvent {
     TaskAssigned(TaskAssigned),
     TaskCompleted(TaskCompleted),
     InvocationFinished(InvocationFinishedEvent),
+    InvocationGroupFinished(InvocationGroupFinishedEvent),
     DiagnosticMessage(DiagnosticMessage),
 }
 
@@ -28,9 +29,9 @@ impl InvocationStateChangeEvent {
     pub fn invocation_id(&self) -> String {
         match self {
             InvocationStateChangeEvent::AsyncInvocation(InvocationStarted { id }) => id.clone(),
-            InvocationStateChangeEvent::InvocationFinished(InvocationFinishedEvent { id }) => {
-                id.clone()
-            }
+            InvocationStateChangeEvent::InvocationFinished(InvocationFinishedEvent {
+                id, ..
+            }) => id.clone(),
             InvocationStateChangeEvent::TaskCreated(TaskCreated { invocation_id, .. }) => {
                 invocation_id.clone()
             }
@@ -40,6 +41,11 @@ impl InvocationStateChangeEvent {
             InvocationStateChangeEvent::TaskCompleted(TaskCompleted { invocation_id, .. }) => {
                 invocation_id.clone()
             }
+            // Group events are keyed by the id of the group
+            InvocationStateChangeEvent::InvocationGroupFinished(InvocationGroupFinishedEvent {
+                id,
+                ..
+            }) => id.clone(),
             InvocationStateChangeEvent::DiagnosticMessage(_) => "".to_string(),
         }
     }
@@ -48,6 +54,13 @@ impl InvocationStateChangeEvent {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct InvocationFinishedEvent {
     pub id: String,
+    pub result: InvocationResult,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InvocationGroupFinishedEvent {
+    pub id: String,
+    pub group: InvocationGroup,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
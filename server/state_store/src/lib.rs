@@ -4,16 +4,26 @@ use std::{
     path::PathBuf,
     pin::Pin,
     sync::{
-        atomic::{self, AtomicU64},
+        atomic::{self, AtomicBool, AtomicU64},
         Arc,
     },
+    time::Duration,
     vec,
 };
 
 use anyhow::{anyhow, Result};
 use data_model::{
+    invocation_group::InvocationGroup,
+    webhooks::WebhookEvent,
+    AbortCommand,
+    AbortReason,
     ChangeType,
+    ExecutorFeature,
     ExecutorId,
+    ExecutorMetadata,
+    ExecutorPool,
+    GraphPause,
+    InvocationResult,
     InvokeComputeGraphEvent,
     StateChange,
     StateChangeBuilder,
@@ -21,10 +31,22 @@ use data_model::{
     Task,
     TaskFinishedEvent,
     TaskId,
+    TaskOutputsStreamedEvent,
+    TasksFinishedEvent,
+    TasksRequeuedEvent,
 };
+use error::StateStoreError;
 use futures::Stream;
 use indexify_utils::get_epoch_time_in_ms;
-use invocation_events::{InvocationFinishedEvent, InvocationStateChangeEvent};
+use invocation_events::{
+    InvocationFinishedEvent,
+    InvocationGroupFinishedEvent,
+    InvocationStateChangeEvent,
+};
+use migrations::{MigrationOptions, MigrationReport};
+use prefix::RangeDeletes;
+use read_cache::{Invalidation, ReadCache};
+use replication::Replication;
 use requests::StateMachineUpdateRequest;
 use rocksdb::{ColumnFamilyDescriptor, Options, TransactionDB, TransactionDBOptions};
 use state_machine::{IndexifyObjectsColumns, InvocationCompletion};
@@ -35,10 +57,16 @@ use tokio::sync::{
     RwLock,
 };
 
+pub mod error;
 pub mod invocation_events;
+pub mod migrations;
+pub mod prefix;
+pub mod read_cache;
+pub mod replication;
 pub mod requests;
 pub mod scanner;
 pub mod serializer;
+pub mod snapshot;
 pub mod state_machine;
 pub mod test_state_store;
 
@@ -47,15 +75,27 @@ pub struct ExecutorState {
     pub new_task_channel: broadcast::Sender<()>,
     pub num_registered: u64,
     pub task_ids_sent: HashSet<TaskId>,
+    // Aborted tasks the executor hasn't been told to abort yet over its task
+    // stream
+    pub aborted_task_ids: Vec<TaskId>,
+    // Notified when abort commands are persisted for the executor to poll
+    // from its control channel
+    pub control_channel: broadcast::Sender<()>,
+    // Upcoming tasks last hinted to the executor
+    pub hinted_task_ids: HashSet<TaskId>,
 }
 
 impl ExecutorState {
     pub fn new() -> Self {
         let (new_task_channel, _) = broadcast::channel(1);
+        let (control_channel, _) = broadcast::channel(1);
         Self {
             new_task_channel,
             num_registered: 0,
             task_ids_sent: HashSet::new(),
+            aborted_task_ids: Vec::new(),
+            control_channel,
+            hinted_task_ids: HashSet::new(),
         }
     }
 
@@ -74,8 +114,24 @@ impl ExecutorState {
         let _ = self.new_task_channel.send(());
     }
 
+    // The tasks are sent again if they are allocated to the executor later.
+    // Executors using the control channel poll the persisted commands of the
+    // tasks there instead of getting them over their task stream.
+    pub fn aborted(&mut self, task_ids: Vec<TaskId>, control_channel: bool) {
+        for task_id in &task_ids {
+            self.task_ids_sent.remove(task_id);
+        }
+        if control_channel {
+            let _ = self.control_channel.send(());
+        } else {
+            self.aborted_task_ids.extend(task_ids);
+        }
+        let _ = self.new_task_channel.send(());
+    }
+
     pub fn subscribe(&mut self) -> broadcast::Receiver<()> {
         self.task_ids_sent.clear();
+        self.hinted_task_ids.clear();
         self.new_task_channel.subscribe()
     }
 }
@@ -86,7 +142,27 @@ impl Default for ExecutorState {
     }
 }
 
-pub type TaskStream = Pin<Box<dyn Stream<Item = Result<Vec<Task>>> + Send + Sync>>;
+/// Sent to an executor over its task stream.
+#[derive(Debug)]
+pub enum ExecutorEvent {
+    Tasks(Vec<Task>),
+    /// Tasks the executor stops running and drops the outcomes of, e.g.
+    /// tasks pre-empted by higher priority tasks. Executors using the control
+    /// channel get them there instead, with their reason.
+    Abort(Vec<TaskId>),
+    /// Unallocated tasks the executor may be allocated, it can download their
+    /// inputs and code ahead of time
+    Prefetch(Vec<Task>),
+}
+
+// Hints are sent to executors which negotiated them, at most this many at
+// once, picked among the first PREFETCH_SCAN_LIMIT unallocated tasks
+const PREFETCH_HINT_LIMIT: usize = 5;
+const PREFETCH_SCAN_LIMIT: usize = 100;
+// How often upcoming tasks are checked for when the executor isn't notified
+const PREFETCH_HINT_INTERVAL: Duration = Duration::from_secs(2);
+
+pub type TaskStream = Pin<Box<dyn Stream<Item = Result<ExecutorEvent>> + Send + Sync>>;
 pub type StateChangeStream =
     Pin<Box<dyn Stream<Item = Result<InvocationStateChangeEvent>> + Send + Sync>>;
 
@@ -103,27 +179,36 @@ pub struct IndexifyState {
     pub gc_rx: tokio::sync::watch::Receiver<()>,
     pub system_tasks_tx: tokio::sync::watch::Sender<()>,
     pub system_tasks_rx: tokio::sync::watch::Receiver<()>,
+    pub webhooks_tx: tokio::sync::watch::Sender<()>,
+    pub webhooks_rx: tokio::sync::watch::Receiver<()>,
+    pub replication: Replication,
+    pub read_cache: Arc<ReadCache>,
+    maintenance_mode: AtomicBool,
 }
 
 impl IndexifyState {
     pub async fn new(path: PathBuf) -> Result<Arc<Self>> {
+        Self::open(path, &MigrationOptions::default()).await
+    }
+
+    /// Opens the state store after applying its pending migrations.
+    pub async fn open(path: PathBuf, migration_options: &MigrationOptions) -> Result<Arc<Self>> {
         let (tx, rx) = tokio::sync::watch::channel(StateChangeId::new(std::u64::MAX));
-        fs::create_dir_all(path.clone())?;
-        let sm_column_families = IndexifyObjectsColumns::iter()
-            .map(|cf| ColumnFamilyDescriptor::new(cf.to_string(), Options::default()));
-        let mut db_opts = Options::default();
-        db_opts.create_missing_column_families(true);
-        db_opts.create_if_missing(true);
-        let db: TransactionDB = TransactionDB::open_cf_descriptors(
-            &db_opts,
-            &TransactionDBOptions::default(),
-            path,
-            sm_column_families,
-        )
-        .map_err(|e| anyhow!("failed to open db: {}", e))?;
+        let db = open_db(path.clone())?;
+        let report =
+            migrations::run_migrations(&db, &path, &migrations::migrations(), migration_options)?;
+        if !report.migrations.is_empty() {
+            tracing::info!(
+                "migrated state store from schema version {} to {}",
+                report.from_version,
+                report.to_version
+            );
+        }
         let (gc_tx, gc_rx) = tokio::sync::watch::channel(());
         let (task_event_tx, _) = tokio::sync::broadcast::channel(100);
         let (system_tasks_tx, system_tasks_rx) = tokio::sync::watch::channel(());
+        let (webhooks_tx, webhooks_rx) = tokio::sync::watch::channel(());
+        let replication = Replication::new(&db)?;
         let s = Arc::new(Self {
             db: Arc::new(db),
             state_change_tx: tx,
@@ -135,6 +220,11 @@ impl IndexifyState {
             gc_rx,
             system_tasks_tx,
             system_tasks_rx,
+            webhooks_tx,
+            webhooks_rx,
+            replication,
+            read_cache: Arc::new(ReadCache::default()),
+            maintenance_mode: AtomicBool::new(false),
         });
 
         let executors = s.reader().get_all_executors()?;
@@ -161,10 +251,43 @@ impl IndexifyState {
         self.system_tasks_rx.clone()
     }
 
+    pub fn get_webhooks_watcher(&self) -> Receiver<()> {
+        self.webhooks_rx.clone()
+    }
+
+    /// In maintenance mode user writes are rejected, the writes of the
+    /// executors and of the background tasks are still accepted.
+    pub fn set_maintenance_mode(&self, maintenance_mode: bool) {
+        self.maintenance_mode
+            .store(maintenance_mode, atomic::Ordering::Relaxed);
+    }
+
+    pub fn in_maintenance_mode(&self) -> bool {
+        self.maintenance_mode.load(atomic::Ordering::Relaxed)
+    }
+
+    #[tracing::instrument(
+        skip_all,
+        fields(namespace = request.payload.written_namespace())
+    )]
     pub async fn write(&self, request: StateMachineUpdateRequest) -> Result<()> {
+        if self.is_read_only() {
+            return Err(StateStoreError::ReadOnly.into());
+        }
+        if self.in_maintenance_mode() && request.payload.is_user_write() {
+            return Err(StateStoreError::Maintenance.into());
+        }
         let mut allocated_tasks_by_executor = Vec::new();
         let mut tasks_finalized: HashMap<ExecutorId, Vec<TaskId>> = HashMap::new();
+        let mut requeued_executor = None;
+        let mut aborted_tasks: HashMap<ExecutorId, Vec<AbortCommand>> = HashMap::new();
+        let mut webhook_events_queued = false;
+        let mut finished_tasks = Vec::new();
         let txn = self.db.transaction();
+        let mut range_deletes = RangeDeletes::default();
+        if let Some(namespace) = request.payload.written_namespace() {
+            state_machine::check_namespace_writable(self.db.clone(), &txn, namespace)?;
+        }
         let new_state_changes = match &request.payload {
             requests::RequestPayload::InvokeComputeGraph(invoke_compute_graph_request) => {
                 let state_changes = self
@@ -221,29 +344,197 @@ impl IndexifyState {
                 state_changes
             }
             requests::RequestPayload::FinalizeTask(finalize_task) => {
-                let state_changes = if state_machine::mark_task_completed(
+                let finalized_outcome = state_machine::mark_task_completed(
                     self.db.clone(),
                     &txn,
                     finalize_task.clone(),
-                )? {
+                )?;
+                let mut finished_task = finalize_task.clone();
+                let state_changes = if let Some(outcome) = finalized_outcome {
+                    finished_task.task_outcome = outcome;
                     self.finalize_task(&finalize_task).await?
                 } else {
                     Vec::new()
                 };
+                finished_tasks.push(finished_task);
                 tasks_finalized
                     .entry(finalize_task.executor_id.clone())
                     .or_default()
                     .push(finalize_task.task_id.clone());
                 state_changes
             }
+            requests::RequestPayload::FinalizeTasks(request) => {
+                let mut finished_events = Vec::new();
+                for finalize_task in &request.tasks {
+                    if let Some(outcome) = state_machine::mark_task_completed(
+                        self.db.clone(),
+                        &txn,
+                        finalize_task.clone(),
+                    )? {
+                        let mut finished_task = finalize_task.clone();
+                        finished_task.task_outcome = outcome;
+                        finished_tasks.push(finished_task);
+                        finished_events.push(task_finished_event(finalize_task));
+                    }
+                    tasks_finalized
+                        .entry(finalize_task.executor_id.clone())
+                        .or_default()
+                        .push(finalize_task.task_id.clone());
+                }
+                // A single state change for the whole batch
+                self.tasks_finished(finished_events)?
+            }
+            requests::RequestPayload::SignalInvocation(request) => {
+                let mut state_changes = vec![];
+                for finalize_task in
+                    state_machine::signal_invocation(self.db.clone(), &txn, &request)?
+                {
+                    state_changes.extend(self.finalize_task(&finalize_task).await?);
+                }
+                state_changes
+            }
+            requests::RequestPayload::CloseWindow(request) => {
+                let mut state_changes = vec![];
+                for finalize_task in state_machine::close_window(self.db.clone(), &txn, &request)? {
+                    state_changes.extend(self.finalize_task(&finalize_task).await?);
+                }
+                state_changes
+            }
+            requests::RequestPayload::StreamTaskOutputs(stream_request) => {
+                match state_machine::stream_task_outputs(self.db.clone(), &txn, &stream_request)? {
+                    Some(output_ids) => self.task_outputs_streamed(&stream_request, output_ids),
+                    None => vec![],
+                }
+            }
+            requests::RequestPayload::CheckpointTask(request) => {
+                if state_machine::checkpoint_task(self.db.clone(), &txn, &request)? {
+                    self.gc_tx.send(()).unwrap();
+                }
+                vec![]
+            }
             requests::RequestPayload::CreateNameSpace(namespace_request) => {
-                state_machine::create_namespace(self.db.clone(), &namespace_request)?;
+                state_machine::create_namespace(self.db.clone(), &txn, &namespace_request)?;
+                vec![]
+            }
+            requests::RequestPayload::DeleteNamespace(request) => {
+                let cancelled =
+                    state_machine::delete_namespace(self.db.clone(), &txn, &request.namespace)?;
+                for (executor_id, task_ids) in cancelled {
+                    aborted_tasks.entry(executor_id).or_default().extend(
+                        task_ids
+                            .into_iter()
+                            .map(|task_id| AbortCommand::new(task_id, AbortReason::Cancelled)),
+                    );
+                }
+                vec![]
+            }
+            requests::RequestPayload::PurgeNamespace(request) => {
+                if state_machine::purge_namespace(
+                    self.db.clone(),
+                    &txn,
+                    &mut range_deletes,
+                    &request.namespace,
+                )? {
+                    tracing::info!("purged deleted namespace {}", request.namespace);
+                }
                 vec![]
             }
             requests::RequestPayload::CreateComputeGraph(req) => {
-                state_machine::create_compute_graph(self.db.clone(), req.compute_graph.clone())?;
+                if state_machine::create_compute_graph(
+                    self.db.clone(),
+                    &txn,
+                    &mut range_deletes,
+                    req,
+                )? {
+                    webhook_events_queued |= state_machine::enqueue_webhook_event(
+                        self.db.clone(),
+                        &txn,
+                        Some(&req.compute_graph.namespace),
+                        WebhookEvent::graph_created(&req.compute_graph),
+                    )?;
+                }
+                vec![]
+            }
+            requests::RequestPayload::ApplyManifest(request) => {
+                for req in &request.graphs {
+                    if state_machine::create_compute_graph(
+                        self.db.clone(),
+                        &txn,
+                        &mut range_deletes,
+                        req,
+                    )? {
+                        webhook_events_queued |= state_machine::enqueue_webhook_event(
+                            self.db.clone(),
+                            &txn,
+                            Some(&req.compute_graph.namespace),
+                            WebhookEvent::graph_created(&req.compute_graph),
+                        )?;
+                    }
+                }
                 vec![]
             }
+            requests::RequestPayload::CreateGraphCanary(request) => {
+                state_machine::create_graph_canary(self.db.clone(), &txn, request)?;
+                vec![]
+            }
+            requests::RequestPayload::UpdateGraphCanary(request) => {
+                state_machine::update_graph_canary(self.db.clone(), &txn, request)?;
+                vec![]
+            }
+            requests::RequestPayload::FinalizeGraphCanary(request) => {
+                let compute_graph =
+                    state_machine::finalize_graph_canary(self.db.clone(), &txn, request)?;
+                tracing::info!(
+                    "promoted canary of compute graph {} to version {}",
+                    compute_graph.name,
+                    compute_graph.version.0
+                );
+                vec![]
+            }
+            requests::RequestPayload::RollbackGraphCanary(request) => {
+                state_machine::rollback_graph_canary(self.db.clone(), &txn, request)?;
+                vec![]
+            }
+            requests::RequestPayload::RegisterPendingUploads(request) => {
+                state_machine::register_pending_uploads(self.db.clone(), &txn, request)?;
+                vec![]
+            }
+            requests::RequestPayload::SweepPendingUploads(request) => {
+                let swept = state_machine::sweep_pending_uploads(self.db.clone(), &txn, request)?;
+                if swept > 0 {
+                    tracing::info!("swept {} uncommitted uploads", swept);
+                    self.gc_tx.send(()).unwrap();
+                }
+                vec![]
+            }
+            requests::RequestPayload::PauseComputeGraph(request) => {
+                state_machine::set_compute_graph_paused(
+                    self.db.clone(),
+                    &txn,
+                    &request.namespace,
+                    &request.compute_graph,
+                    Some(GraphPause {
+                        paused_at: get_epoch_time_in_ms(),
+                        reject_invocations: request.reject_invocations,
+                    }),
+                )?;
+                vec![]
+            }
+            requests::RequestPayload::ResumeComputeGraph(request) => {
+                let was_paused = state_machine::set_compute_graph_paused(
+                    self.db.clone(),
+                    &txn,
+                    &request.namespace,
+                    &request.compute_graph,
+                    None,
+                )?;
+                // Lets the scheduler allocate the tasks held back by the pause
+                if was_paused {
+                    self.graph_resumed(&format!("{}|{}", request.namespace, request.compute_graph))
+                } else {
+                    vec![]
+                }
+            }
             requests::RequestPayload::DeleteComputeGraph(request) => {
                 state_machine::delete_compute_graph(
                     self.db.clone(),
@@ -251,44 +542,120 @@ impl IndexifyState {
                     &request.namespace,
                     &request.name,
                 )?;
+                vec![]
+            }
+            requests::RequestPayload::UndeleteComputeGraph(request) => {
+                state_machine::undelete_compute_graph(
+                    self.db.clone(),
+                    &txn,
+                    &request.namespace,
+                    &request.name,
+                )?;
+                vec![]
+            }
+            requests::RequestPayload::PurgeComputeGraph(request) => {
+                state_machine::purge_compute_graph(
+                    self.db.clone(),
+                    &txn,
+                    &mut range_deletes,
+                    &request.namespace,
+                    &request.name,
+                )?;
                 self.gc_tx.send(()).unwrap();
                 vec![]
             }
             requests::RequestPayload::DeleteInvocation(request) => {
-                state_machine::delete_input_data_object(self.db.clone(), &request)?;
+                state_machine::delete_input_data_object(self.db.clone(), &txn, &request)?;
+                vec![]
+            }
+            requests::RequestPayload::DeleteIngestedData(request) => {
+                state_machine::delete_ingested_data(self.db.clone(), &txn, request)?;
+                self.gc_tx.send(()).unwrap();
                 vec![]
             }
             requests::RequestPayload::SchedulerUpdate(request) => {
-                let new_state_changes = self.change_events_for_scheduler_update(&request);
+                let mut new_state_changes = self.change_events_for_scheduler_update(&request);
                 for req in &request.task_requests {
                     match state_machine::create_tasks(self.db.clone(), &txn, req)? {
-                        Some(completion) => {
-                            if let Err(err) = self.task_event_tx.send(
-                                InvocationStateChangeEvent::InvocationFinished(
-                                    InvocationFinishedEvent {
-                                        id: req.invocation_id.clone(),
-                                    },
-                                ),
-                            ) {
-                                tracing::error!(
-                                    "failed to send invocation state change: {:?}",
-                                    err
-                                );
-                            }
-                            if completion == InvocationCompletion::System {
-                                // Notify the system task handler that it can start new tasks since
-                                // a task was completed
-                                let _ = self.system_tasks_tx.send(());
+                        Some(InvocationCompletion::SubGraph(finalize_task)) => {
+                            new_state_changes.extend(self.finalize_task(&finalize_task).await?);
+                        }
+                        Some(InvocationCompletion::User(result, finished_group)) => {
+                            webhook_events_queued |= state_machine::enqueue_webhook_event(
+                                self.db.clone(),
+                                &txn,
+                                Some(&result.namespace),
+                                WebhookEvent::invocation_finished(&result),
+                            )?;
+                            self.invocation_finished(result);
+                            if let Some(group) = finished_group {
+                                webhook_events_queued |= state_machine::enqueue_webhook_event(
+                                    self.db.clone(),
+                                    &txn,
+                                    Some(&group.namespace),
+                                    WebhookEvent::invocation_group_finished(&group),
+                                )?;
+                                self.invocation_group_finished(group);
                             }
                         }
+                        Some(InvocationCompletion::System(result)) => {
+                            self.invocation_finished(result);
+                            // Notify the system task handler that it can start new tasks since
+                            // a task was completed
+                            let _ = self.system_tasks_tx.send(());
+                        }
                         None => {}
                     };
                 }
+                for finalize_task in state_machine::close_full_windows(
+                    self.db.clone(),
+                    &txn,
+                    &request.task_requests,
+                )? {
+                    new_state_changes.extend(self.finalize_task(&finalize_task).await?);
+                }
+                for sub_graph_invocation in &request.sub_graph_invocations {
+                    match state_machine::create_sub_graph_invocation(
+                        self.db.clone(),
+                        &txn,
+                        sub_graph_invocation,
+                    )? {
+                        Some(finalize_task) => {
+                            new_state_changes.extend(self.finalize_task(&finalize_task).await?);
+                        }
+                        None => {
+                            let invoke_req =
+                                state_machine::sub_graph_invoke_request(sub_graph_invocation);
+                            new_state_changes.extend(self.invoke_compute_graph(&invoke_req).await?);
+                        }
+                    }
+                }
                 state_machine::processed_reduction_tasks(
                     self.db.clone(),
                     &txn,
                     &request.reduction_tasks,
                 )?;
+                // Frees the GPUs of the pre-empted tasks before they are
+                // allocated to other tasks
+                for preemption in &request.preemptions {
+                    if state_machine::preempt_task(
+                        self.db.clone(),
+                        &txn,
+                        &preemption.task,
+                        &preemption.executor,
+                    )? {
+                        aborted_tasks
+                            .entry(preemption.executor.clone())
+                            .or_default()
+                            .push(AbortCommand::new(
+                                preemption.task.id.clone(),
+                                AbortReason::Preempted,
+                            ));
+                    }
+                }
+                for (executor_id, commands) in &aborted_tasks {
+                    new_state_changes.extend(self.tasks_requeued(executor_id, commands.len()));
+                }
                 for allocation in &request.allocations {
                     state_machine::allocate_tasks(
                         self.db.clone(),
@@ -309,6 +676,35 @@ impl IndexifyState {
                 state_machine::register_executor(self.db.clone(), &txn, &request)?;
                 self.register_executor(&request)
             }
+            requests::RequestPayload::UpdateExecutorArtifacts(request) => {
+                if state_machine::update_executor_artifacts(self.db.clone(), &txn, &request)? {
+                    self.executor_updated(&request.executor_id)
+                } else {
+                    vec![]
+                }
+            }
+            requests::RequestPayload::UpdateExecutorLabels(request) => {
+                let task_ids =
+                    state_machine::update_executor_labels(self.db.clone(), &txn, &request)?;
+                let mut state_changes = self.executor_updated(&request.executor_id);
+                if !task_ids.is_empty() {
+                    tracing::info!(
+                        "moved {} tasks of executor {} not matching its new labels",
+                        task_ids.len(),
+                        request.executor_id
+                    );
+                    state_changes.extend(self.tasks_requeued(&request.executor_id, task_ids.len()));
+                    aborted_tasks
+                        .entry(request.executor_id.clone())
+                        .or_default()
+                        .extend(
+                            task_ids
+                                .into_iter()
+                                .map(|task_id| AbortCommand::new(task_id, AbortReason::Relocated)),
+                        );
+                }
+                state_changes
+            }
             requests::RequestPayload::DeregisterExecutor(request) => {
                 let state_changes = self.deregister_executor_events(&request);
                 let removed = {
@@ -328,14 +724,169 @@ impl IndexifyState {
                 if removed {
                     tracing::info!("de-registering executor: {}", request.executor_id);
                     state_machine::deregister_executor(self.db.clone(), &txn, &request)?;
+                    webhook_events_queued |= state_machine::enqueue_webhook_event(
+                        self.db.clone(),
+                        &txn,
+                        None,
+                        WebhookEvent::executor_removed(&request.executor_id),
+                    )?;
                 }
                 state_changes
             }
+            requests::RequestPayload::RequeueExecutorTasks(request) => {
+                let num_tasks = state_machine::requeue_executor_tasks(
+                    self.db.clone(),
+                    &txn,
+                    &request.executor_id,
+                    request.batch_size,
+                )?;
+                tracing::info!(
+                    "requeued {} tasks of executor {}",
+                    num_tasks,
+                    request.executor_id
+                );
+                if num_tasks > 0 {
+                    requeued_executor = Some(request.executor_id.clone());
+                }
+                self.tasks_requeued(&request.executor_id, num_tasks)
+            }
+            requests::RequestPayload::ReconcileExecutorTasks(request) => {
+                let reconciliation =
+                    state_machine::reconcile_executor_tasks(self.db.clone(), &txn, &request)?;
+                let num_tasks = reconciliation.num_retried();
+                tracing::info!(
+                    "reconciled {} tasks reported by executor {}, {} tasks retried",
+                    request.reported_tasks.len(),
+                    request.executor_id,
+                    num_tasks
+                );
+                if num_tasks > 0 {
+                    requeued_executor = Some(request.executor_id.clone());
+                }
+                self.tasks_requeued(&request.executor_id, num_tasks)
+            }
+            requests::RequestPayload::CreateExecutorPool(request) => {
+                state_machine::create_executor_pool(self.db.clone(), &txn, &request)?;
+                self.executor_pool_updated(&request.pool.key())
+            }
+            requests::RequestPayload::BlacklistExecutors(request) => {
+                state_machine::blacklist_executors(self.db.clone(), &txn, request)?;
+                request
+                    .entries
+                    .iter()
+                    .flat_map(|entry| self.executor_blacklisted(&entry.key()))
+                    .collect()
+            }
+            requests::RequestPayload::RemoveExecutorBlacklistEntry(request) => {
+                state_machine::remove_executor_blacklist_entry(self.db.clone(), &txn, request)?;
+                // Lets the scheduler place the tasks of the function on the
+                // executor again
+                self.executor_updated(&request.executor_id)
+            }
+            requests::RequestPayload::RecordExecutorApproval(request) => {
+                state_machine::record_executor_approval(self.db.clone(), &txn, request)?;
+                vec![]
+            }
+            requests::RequestPayload::AcknowledgeAborts(request) => {
+                state_machine::acknowledge_aborts(self.db.clone(), &txn, request)?;
+                vec![]
+            }
+            requests::RequestPayload::SetLegalHold(request) => {
+                state_machine::set_legal_hold(self.db.clone(), &txn, request)?;
+                vec![]
+            }
+            requests::RequestPayload::ClearLegalHold(request) => {
+                state_machine::clear_legal_hold(self.db.clone(), &txn, request)?;
+                vec![]
+            }
+            requests::RequestPayload::DeleteExecutorPool(request) => {
+                state_machine::delete_executor_pool(self.db.clone(), &txn, &request)?;
+                self.executor_pool_updated(&ExecutorPool::key_from(
+                    &request.namespace,
+                    &request.name,
+                ))
+            }
+            requests::RequestPayload::SetInvocationState(request) => {
+                state_machine::set_invocation_state(self.db.clone(), &txn, request)?;
+                vec![]
+            }
+            requests::RequestPayload::CreateInvocationGroup(request) => {
+                state_machine::create_invocation_group(self.db.clone(), &txn, request)?;
+                vec![]
+            }
+            requests::RequestPayload::CloseInvocationGroup(request) => {
+                if let Some(group) =
+                    state_machine::close_invocation_group(self.db.clone(), &txn, request)?
+                {
+                    webhook_events_queued |= state_machine::enqueue_webhook_event(
+                        self.db.clone(),
+                        &txn,
+                        Some(&group.namespace),
+                        WebhookEvent::invocation_group_finished(&group),
+                    )?;
+                    self.invocation_group_finished(group);
+                }
+                vec![]
+            }
+            requests::RequestPayload::CreateWebhook(request) => {
+                state_machine::create_webhook(self.db.clone(), &txn, request)?;
+                vec![]
+            }
+            requests::RequestPayload::DeleteWebhook(request) => {
+                state_machine::delete_webhook(self.db.clone(), &txn, request)?;
+                vec![]
+            }
+            requests::RequestPayload::UpdateWebhookDeliveries(request) => {
+                state_machine::update_webhook_deliveries(self.db.clone(), &txn, request)?;
+                vec![]
+            }
+            requests::RequestPayload::RedriveWebhookDeadLetters(request) => {
+                let redriven =
+                    state_machine::redrive_webhook_dead_letters(self.db.clone(), &txn, request)?;
+                if redriven > 0 {
+                    let _ = self.webhooks_tx.send(());
+                }
+                vec![]
+            }
+            requests::RequestPayload::MovePayloads(request) => {
+                state_machine::move_payloads(self.db.clone(), &txn, request)?;
+                self.gc_tx.send(()).unwrap();
+                vec![]
+            }
+            requests::RequestPayload::RecordPayloadReplicas(request) => {
+                state_machine::record_payload_replicas(self.db.clone(), &txn, request)?;
+                self.gc_tx.send(()).unwrap();
+                vec![]
+            }
+            requests::RequestPayload::RecordSlaStatus(request) => {
+                webhook_events_queued |=
+                    state_machine::record_sla_status(self.db.clone(), &txn, request)?;
+                vec![]
+            }
+            requests::RequestPayload::RecordAlertStatuses(request) => {
+                webhook_events_queued |=
+                    state_machine::record_alert_statuses(self.db.clone(), &txn, request)?;
+                vec![]
+            }
+            requests::RequestPayload::RecordDataRejection(request) => {
+                webhook_events_queued |=
+                    state_machine::record_data_rejection(self.db.clone(), &txn, request)?;
+                vec![]
+            }
+            requests::RequestPayload::RecordFunctionLatencies(request) => {
+                state_machine::record_function_latencies(self.db.clone(), &txn, request)?;
+                vec![]
+            }
             requests::RequestPayload::RemoveGcUrls(urls) => {
                 state_machine::remove_gc_urls(self.db.clone(), &txn, urls.clone())?;
                 vec![]
             }
         };
+        let mut invalidation = Invalidation::for_request(&request.payload);
+        invalidation.add_state_changes(&new_state_changes);
+        if !range_deletes.is_empty() {
+            invalidation.all();
+        }
         if !new_state_changes.is_empty() {
             state_machine::save_state_changes(self.db.clone(), &txn, &new_state_changes)?;
         }
@@ -344,7 +895,23 @@ impl IndexifyState {
             &txn,
             &request.state_changes_processed.clone(),
         )?;
-        txn.commit()?;
+        let mut control_channel_executors = HashSet::new();
+        for (executor_id, commands) in &aborted_tasks {
+            let persisted = state_machine::persist_abort_commands(
+                self.db.clone(),
+                &txn,
+                executor_id,
+                commands,
+            )?;
+            if persisted {
+                control_channel_executors.insert(executor_id.clone());
+            }
+        }
+        // Applied before the transaction commits, the prefixes only hold keys
+        // of deleted objects which are deleted again if the commit fails
+        self.apply_range_deletes(&range_deletes, &txn).await?;
+        let seq = self.commit(txn).await?;
+        self.read_cache.invalidate(&invalidation);
         for executor_id in allocated_tasks_by_executor {
             self.executor_states
                 .write()
@@ -354,6 +921,19 @@ impl IndexifyState {
                     executor_state.notify();
                 });
         }
+        // None of the tasks sent to the executor are allocated to it anymore
+        if let Some(executor_id) = requeued_executor {
+            if let Some(executor_state) = self.executor_states.write().await.get_mut(&executor_id) {
+                executor_state.task_ids_sent.clear();
+                executor_state.notify();
+            }
+        }
+        for (executor_id, commands) in aborted_tasks {
+            if let Some(executor_state) = self.executor_states.write().await.get_mut(&executor_id) {
+                let task_ids = commands.into_iter().map(|command| command.task_id).collect();
+                executor_state.aborted(task_ids, control_channel_executors.contains(&executor_id));
+            }
+        }
         for (executor_id, tasks) in tasks_finalized {
             self.executor_states
                 .write()
@@ -365,23 +945,40 @@ impl IndexifyState {
                     }
                 });
         }
-        self.handle_invocation_state_changes(&request).await;
+        if webhook_events_queued {
+            let _ = self.webhooks_tx.send(());
+        }
+        self.handle_invocation_state_changes(&request, finished_tasks)
+            .await;
         for state_change in new_state_changes {
             self.state_change_tx.send(state_change.id).unwrap();
         }
+        // Acknowledged once a majority of the cluster has the write, which is
+        // applied locally either way
+        if let Some(seq) = seq {
+            self.wait_for_replication(seq).await?;
+        }
         Ok(())
     }
 
-    async fn handle_invocation_state_changes(&self, update_request: &StateMachineUpdateRequest) {
+    /// `finished_tasks` have the outcome the tasks finished with, it differs
+    /// from the requested one when a task exceeded its output limits.
+    async fn handle_invocation_state_changes(
+        &self,
+        update_request: &StateMachineUpdateRequest,
+        finished_tasks: Vec<requests::FinalizeTaskRequest>,
+    ) {
         if self.task_event_tx.receiver_count() == 0 {
             return;
         }
         match &update_request.payload {
-            requests::RequestPayload::FinalizeTask(task_finished_event) => {
-                let ev =
-                    InvocationStateChangeEvent::from_task_finished(task_finished_event.clone());
-                if let Err(err) = self.task_event_tx.send(ev) {
-                    tracing::error!("failed to send invocation state change: {:?}", err);
+            requests::RequestPayload::FinalizeTask(_) |
+            requests::RequestPayload::FinalizeTasks(_) => {
+                for finished_task in finished_tasks {
+                    let ev = InvocationStateChangeEvent::from_task_finished(finished_task);
+                    if let Err(err) = self.task_event_tx.send(ev) {
+                        tracing::error!("failed to send invocation state change: {:?}", err);
+                    }
                 }
             }
             requests::RequestPayload::SchedulerUpdate(sched_update) => {
@@ -441,19 +1038,57 @@ impl IndexifyState {
             .last_state_change_id
             .fetch_add(1, atomic::Ordering::Relaxed);
         let state_change = StateChangeBuilder::default()
-            .change_type(ChangeType::TaskFinished(TaskFinishedEvent {
+            .change_type(ChangeType::TaskFinished(task_finished_event(request)))
+            .created_at(get_epoch_time_in_ms())
+            .object_id(request.task_id.clone().to_string())
+            .id(StateChangeId::new(last_change_id))
+            .processed_at(None)
+            .build()?;
+        Ok(vec![state_change])
+    }
+
+    fn tasks_finished(&self, tasks: Vec<TaskFinishedEvent>) -> Result<Vec<StateChange>> {
+        let Some(first_task) = tasks.first() else {
+            return Ok(vec![]);
+        };
+        let object_id = first_task.task_id.to_string();
+        let last_change_id = self
+            .last_state_change_id
+            .fetch_add(1, atomic::Ordering::Relaxed);
+        let state_change = StateChangeBuilder::default()
+            .change_type(ChangeType::TasksFinished(TasksFinishedEvent { tasks }))
+            .created_at(get_epoch_time_in_ms())
+            .object_id(object_id)
+            .id(StateChangeId::new(last_change_id))
+            .processed_at(None)
+            .build()?;
+        Ok(vec![state_change])
+    }
+
+    fn task_outputs_streamed(
+        &self,
+        request: &requests::StreamTaskOutputsRequest,
+        output_ids: Vec<String>,
+    ) -> Vec<StateChange> {
+        let last_change_id = self
+            .last_state_change_id
+            .fetch_add(1, atomic::Ordering::Relaxed);
+        let state_change = StateChangeBuilder::default()
+            .change_type(ChangeType::TaskOutputsStreamed(TaskOutputsStreamedEvent {
                 namespace: request.namespace.clone(),
                 compute_graph: request.compute_graph.clone(),
                 compute_fn: request.compute_fn.clone(),
                 invocation_id: request.invocation_id.clone(),
                 task_id: request.task_id.clone(),
+                output_ids,
             }))
             .created_at(get_epoch_time_in_ms())
-            .object_id(request.task_id.clone().to_string())
+            .object_id(request.task_id.to_string())
             .id(StateChangeId::new(last_change_id))
             .processed_at(None)
-            .build()?;
-        Ok(vec![state_change])
+            .build()
+            .unwrap();
+        vec![state_change]
     }
 
     async fn invoke_compute_graph(
@@ -477,6 +1112,27 @@ impl IndexifyState {
         Ok(vec![state_change])
     }
 
+    fn invocation_finished(&self, result: InvocationResult) {
+        let event = InvocationStateChangeEvent::InvocationFinished(InvocationFinishedEvent {
+            id: result.invocation_id.clone(),
+            result,
+        });
+        if let Err(err) = self.task_event_tx.send(event) {
+            tracing::error!("failed to send invocation state change: {:?}", err);
+        }
+    }
+
+    fn invocation_group_finished(&self, group: InvocationGroup) {
+        let event =
+            InvocationStateChangeEvent::InvocationGroupFinished(InvocationGroupFinishedEvent {
+                id: group.id.clone(),
+                group,
+            });
+        if let Err(err) = self.task_event_tx.send(event) {
+            tracing::error!("failed to send invocation group state change: {:?}", err);
+        }
+    }
+
     fn change_events_for_scheduler_update(
         &self,
         req: &requests::SchedulerUpdateRequest,
@@ -534,16 +1190,219 @@ impl IndexifyState {
         vec![state_change]
     }
 
-    pub fn reader(&self) -> scanner::StateReader {
-        scanner::StateReader::new(self.db.clone())
-    }
-
-    pub fn task_event_stream(&self) -> broadcast::Receiver<InvocationStateChangeEvent> {
-        self.task_event_tx.subscribe()
-    }
-}
-
-pub fn task_stream(state: Arc<IndexifyState>, executor: ExecutorId, limit: usize) -> TaskStream {
+    fn executor_updated(&self, executor_id: &ExecutorId) -> Vec<StateChange> {
+        let last_change_id = self
+            .last_state_change_id
+            .fetch_add(1, atomic::Ordering::Relaxed);
+        let state_change = StateChangeBuilder::default()
+            .change_type(ChangeType::ExecutorUpdated)
+            .created_at(get_epoch_time_in_ms())
+            .object_id(executor_id.to_string())
+            .id(StateChangeId::new(last_change_id))
+            .processed_at(None)
+            .build()
+            .unwrap();
+        vec![state_change]
+    }
+
+    fn tasks_requeued(&self, executor_id: &ExecutorId, num_tasks: usize) -> Vec<StateChange> {
+        if num_tasks == 0 {
+            return vec![];
+        }
+        let last_change_id = self
+            .last_state_change_id
+            .fetch_add(1, atomic::Ordering::Relaxed);
+        let state_change = StateChangeBuilder::default()
+            .change_type(ChangeType::TasksRequeued(TasksRequeuedEvent {
+                executor_id: executor_id.clone(),
+                num_tasks,
+            }))
+            .created_at(get_epoch_time_in_ms())
+            .object_id(executor_id.to_string())
+            .id(StateChangeId::new(last_change_id))
+            .processed_at(None)
+            .build()
+            .unwrap();
+        vec![state_change]
+    }
+
+    fn executor_pool_updated(&self, pool_key: &str) -> Vec<StateChange> {
+        let last_change_id = self
+            .last_state_change_id
+            .fetch_add(1, atomic::Ordering::Relaxed);
+        let state_change = StateChangeBuilder::default()
+            .change_type(ChangeType::ExecutorPoolUpdated)
+            .created_at(get_epoch_time_in_ms())
+            .object_id(pool_key.to_string())
+            .id(StateChangeId::new(last_change_id))
+            .processed_at(None)
+            .build()
+            .unwrap();
+        vec![state_change]
+    }
+
+    fn executor_blacklisted(&self, entry_key: &str) -> Vec<StateChange> {
+        let last_change_id = self
+            .last_state_change_id
+            .fetch_add(1, atomic::Ordering::Relaxed);
+        let state_change = StateChangeBuilder::default()
+            .change_type(ChangeType::ExecutorBlacklisted)
+            .created_at(get_epoch_time_in_ms())
+            .object_id(entry_key.to_string())
+            .id(StateChangeId::new(last_change_id))
+            .processed_at(None)
+            .build()
+            .unwrap();
+        vec![state_change]
+    }
+
+    fn graph_resumed(&self, graph_key: &str) -> Vec<StateChange> {
+        let last_change_id = self
+            .last_state_change_id
+            .fetch_add(1, atomic::Ordering::Relaxed);
+        let state_change = StateChangeBuilder::default()
+            .change_type(ChangeType::GraphResumed)
+            .created_at(get_epoch_time_in_ms())
+            .object_id(graph_key.to_string())
+            .id(StateChangeId::new(last_change_id))
+            .processed_at(None)
+            .build()
+            .unwrap();
+        vec![state_change]
+    }
+
+    pub fn reader(&self) -> scanner::StateReader {
+        scanner::StateReader::new(self.db.clone(), self.read_cache.clone())
+    }
+
+    pub fn task_event_stream(&self) -> broadcast::Receiver<InvocationStateChangeEvent> {
+        self.task_event_tx.subscribe()
+    }
+
+    /// Abort commands the executor hasn't acknowledged, waiting up to `wait`
+    /// for one to be issued if there are none. None if the executor isn't
+    /// connected.
+    pub async fn abort_commands(
+        &self,
+        executor_id: &ExecutorId,
+        wait: Duration,
+    ) -> Result<Option<Vec<AbortCommand>>> {
+        let Some(mut rx) = self
+            .executor_states
+            .read()
+            .await
+            .get(executor_id)
+            .map(|executor_state| executor_state.control_channel.subscribe())
+        else {
+            return Ok(None);
+        };
+        let commands = self.reader().pending_aborts(executor_id)?;
+        if !commands.is_empty() {
+            return Ok(Some(commands));
+        }
+        let _ = tokio::time::timeout(wait, rx.recv()).await;
+        Ok(Some(self.reader().pending_aborts(executor_id)?))
+    }
+
+    /// Stops delivering the abort commands of the tasks, returns how many
+    /// were pending.
+    pub async fn acknowledge_aborts(
+        &self,
+        executor_id: &ExecutorId,
+        task_ids: Vec<TaskId>,
+    ) -> Result<usize> {
+        let acknowledged = self
+            .reader()
+            .pending_aborts(executor_id)?
+            .iter()
+            .filter(|command| task_ids.contains(&command.task_id))
+            .count();
+        if acknowledged > 0 {
+            self.write(StateMachineUpdateRequest {
+                payload: requests::RequestPayload::AcknowledgeAborts(
+                    requests::AcknowledgeAbortsRequest {
+                        executor_id: executor_id.clone(),
+                        task_ids,
+                    },
+                ),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        }
+        Ok(acknowledged)
+    }
+}
+
+fn task_finished_event(request: &requests::FinalizeTaskRequest) -> TaskFinishedEvent {
+    TaskFinishedEvent {
+        namespace: request.namespace.clone(),
+        compute_graph: request.compute_graph.clone(),
+        compute_fn: request.compute_fn.clone(),
+        invocation_id: request.invocation_id.clone(),
+        task_id: request.task_id.clone(),
+        executor_id: Some(request.executor_id.clone())
+            .filter(|executor_id| *executor_id != ExecutorId::default()),
+    }
+}
+
+/// Migrations the state store at `path` is pending, without applying them.
+pub fn plan_migrations(path: PathBuf) -> Result<MigrationReport> {
+    if !path.exists() {
+        return Err(anyhow!("state store {} does not exist", path.display()));
+    }
+    let db = open_db(path.clone())?;
+    migrations::run_migrations(
+        &db,
+        &path,
+        &migrations::migrations(),
+        &MigrationOptions {
+            dry_run: true,
+            backup_dir: None,
+        },
+    )
+}
+
+pub(crate) fn open_db(path: PathBuf) -> Result<TransactionDB> {
+    fs::create_dir_all(path.clone())?;
+    let sm_column_families = IndexifyObjectsColumns::iter()
+        .map(|cf| ColumnFamilyDescriptor::new(cf.to_string(), Options::default()));
+    let mut db_opts = Options::default();
+    db_opts.create_missing_column_families(true);
+    db_opts.create_if_missing(true);
+    TransactionDB::open_cf_descriptors(
+        &db_opts,
+        &TransactionDBOptions::default(),
+        path,
+        sm_column_families,
+    )
+    .map_err(|e| anyhow!("failed to open db: {}", e))
+}
+
+/// Upcoming tasks of the executor which weren't hinted yet. Only the latest
+/// hints are remembered, so a task is hinted again if it drops out of the
+/// upcoming tasks and comes back.
+async fn new_prefetch_hints(
+    state: &IndexifyState,
+    executor: &ExecutorMetadata,
+) -> Result<Vec<Task>> {
+    let upcoming =
+        state
+            .reader()
+            .upcoming_tasks(executor, PREFETCH_SCAN_LIMIT, PREFETCH_HINT_LIMIT)?;
+    let mut executor_states = state.executor_states.write().await;
+    let Some(executor_state) = executor_states.get_mut(&executor.id) else {
+        return Ok(vec![]);
+    };
+    let hints = upcoming
+        .iter()
+        .filter(|task| !executor_state.hinted_task_ids.contains(&task.id))
+        .cloned()
+        .collect();
+    executor_state.hinted_task_ids = upcoming.into_iter().map(|task| task.id).collect();
+    Ok(hints)
+}
+
+pub fn task_stream(state: Arc<IndexifyState>, executor: ExecutorId, limit: usize) -> TaskStream {
     let stream = async_stream::stream! {
         let mut rx = state
         .executor_states
@@ -552,10 +1411,46 @@ pub fn task_stream(state: Arc<IndexifyState>, executor: ExecutorId, limit: usize
         .entry(executor.clone())
         .or_default()
         .subscribe();
+        let prefetch_executor = match state.reader().get_executor(&executor) {
+            Ok(metadata) => metadata
+                .filter(|metadata| metadata.features.contains(&ExecutorFeature::PrefetchHints)),
+            Err(e) => {
+                yield Err(e);
+                return;
+            }
+        };
+        let mut notified = true;
         loop {
+            if !notified {
+                if let Some(metadata) = &prefetch_executor {
+                    match new_prefetch_hints(&state, metadata).await {
+                        Ok(hints) if !hints.is_empty() => yield Ok(ExecutorEvent::Prefetch(hints)),
+                        Ok(_) => {}
+                        // Hints are best effort, the tasks are sent regardless
+                        Err(e) => tracing::warn!("error computing prefetch hints for executor {}: {:?}", executor, e),
+                    }
+                }
+                notified = match prefetch_executor {
+                    Some(_) => tokio::select! {
+                        _ = rx.recv() => true,
+                        _ = tokio::time::sleep(PREFETCH_HINT_INTERVAL) => false,
+                    },
+                    None => {
+                        let _ = rx.recv().await;
+                        true
+                    }
+                };
+                continue;
+            }
             // Copy the task_ids_sent before reading the tasks.
             // The update thread modifies tasks first and then updates task_ids_sent,
             // this thread does the opposite. This avoids sending the same task multiple times.
+            let aborted_task_ids = std::mem::take(
+                &mut state.executor_states.write().await.get_mut(&executor).unwrap().aborted_task_ids,
+            );
+            if !aborted_task_ids.is_empty() {
+                yield Ok(ExecutorEvent::Abort(aborted_task_ids));
+            }
             let task_ids_sent = state.executor_states.read().await.get(&executor).unwrap().task_ids_sent.clone();
             match state
                 .reader()
@@ -575,14 +1470,14 @@ pub fn task_stream(state: Arc<IndexifyState>, executor: ExecutorId, limit: usize
                             }
                             filtered_tasks
                         }.await;
-                        yield Ok(filtered_tasks)
+                        yield Ok(ExecutorEvent::Tasks(filtered_tasks))
                     },
                     Err(e) => {
                         yield Err(e);
                         return;
                     }
                 }
-            let _ = rx.recv().await;
+            notified = false;
         }
     };
 
@@ -594,18 +1489,91 @@ mod tests {
     use std::collections::HashMap;
 
     use data_model::{
-        test_objects::tests::{create_mock_task, mock_graph_a, TEST_NAMESPACE},
+        blacklist::ExecutorBlacklistEntry,
+        data_deletion::DataDeletionMode,
+        executor_reconciliation::{ReconciliationOutcome, ReportedTask},
+        filter::LabelsFilter,
+        invocation_group::InvocationGroupStatus,
+        legal_hold::LegalHoldAction,
+        test_objects::tests::{
+            create_mock_task,
+            mock_executor,
+            mock_graph_a,
+            mock_graph_b,
+            mock_invocation_payload,
+            mock_node_fn_output,
+            mock_node_fn_output_fn_a,
+            TEST_NAMESPACE,
+        },
+        timeline::InvocationEventKind,
+        webhooks::{DataRejection, Guardrail, Webhook, WebhookEventType},
         ComputeGraph,
+        DataPayload,
         GraphInvocationCtxBuilder,
+        InvocationPayload,
+        InvocationPayloadBuilder,
+        InvocationStatus,
         Namespace,
+        Node,
+        NodeOutput,
+        OutputLimit,
+        OutputLimits,
+        OutputPayload,
+        PayloadReplica,
+        PendingUpload,
+        ResourceUsage,
+        RouterOutput,
+        StorageTier,
+        TaskCheckpoint,
+        TaskDiagnostics,
+        TaskFailure,
+        TaskOutcome,
+        Window,
     };
     use futures::StreamExt;
     use requests::{
+        ApplyManifestRequest,
+        BlacklistExecutorsRequest,
+        CheckpointTaskRequest,
+        CloseInvocationGroupRequest,
         CreateComputeGraphRequest,
+        CreateExecutorPoolRequest,
+        CreateGraphCanaryRequest,
+        CreateInvocationGroupRequest,
+        CreateWebhookRequest,
         DeleteComputeGraphRequest,
+        DeleteExecutorPoolRequest,
+        DeleteIngestedDataRequest,
+        DeleteInvocationRequest,
+        DeleteNamespaceRequest,
+        DeleteWebhookRequest,
+        FinalizeTaskRequest,
+        FinalizeTasksRequest,
+        GraphCanaryRequest,
+        InlinePayload,
+        InvokeComputeGraphRequest,
+        LegalHoldRequest,
+        MovePayloadsRequest,
+        PayloadMove,
+        PayloadOwner,
+        PayloadReplication,
+        RecordDataRejectionRequest,
+        RecordPayloadReplicasRequest,
         ReductionTasks,
+        RegisterExecutorRequest,
+        ReconcileExecutorTasksRequest,
+        RegisterPendingUploadsRequest,
+        RemoveExecutorBlacklistEntryRequest,
+        RequeueExecutorTasksRequest,
         SchedulerUpdateRequest,
+        SetInvocationStateRequest,
+        StreamTaskOutputsRequest,
+        SweepPendingUploadsRequest,
         TaskPlacement,
+        UpdateExecutorArtifactsRequest,
+        UpdateExecutorLabelsRequest,
+        UpdateGraphCanaryRequest,
+        UpdateWebhookDeliveriesRequest,
     };
     use tempfile::TempDir;
     use tokio;
@@ -626,6 +1594,10 @@ mod tests {
             .write(StateMachineUpdateRequest {
                 payload: RequestPayload::CreateNameSpace(NamespaceRequest {
                     name: "namespace1".to_string(),
+                    queue_limits: Default::default(),
+                    scheduling_weight: None,
+                    reserved_capacity: None,
+                    replication: None,
                 }),
                 state_changes_processed: vec![],
             })
@@ -634,6 +1606,10 @@ mod tests {
             .write(StateMachineUpdateRequest {
                 payload: RequestPayload::CreateNameSpace(NamespaceRequest {
                     name: "namespace2".to_string(),
+                    queue_limits: Default::default(),
+                    scheduling_weight: None,
+                    reserved_capacity: None,
+                    replication: None,
                 }),
                 state_changes_processed: vec![],
             })
@@ -668,6 +1644,8 @@ mod tests {
                 payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
                     namespace: TEST_NAMESPACE.to_string(),
                     compute_graph: compute_graph.clone(),
+                    expected_revision: None,
+                    force: false,
                 }),
                 state_changes_processed: vec![],
             })
@@ -742,6 +1720,7 @@ mod tests {
             compute_graph: task.compute_graph_name.clone(),
             invocation_id: task.invocation_id.clone(),
             tasks: vec![task.clone()],
+            fan_out_cursor: None,
         };
 
         indexify_state
@@ -754,6 +1733,8 @@ mod tests {
                     }],
                     reduction_tasks: ReductionTasks::default(),
                     diagnostic_msgs: vec![],
+                    sub_graph_invocations: vec![],
+                    preemptions: vec![],
                 }),
                 state_changes_processed: vec![],
             })
@@ -766,7 +1747,9 @@ mod tests {
         assert_eq!(res[0].id, task.id);
 
         let mut stream = task_stream(indexify_state.clone(), executor_id.clone(), 10);
-        let res = stream.next().await.unwrap()?;
+        let ExecutorEvent::Tasks(res) = stream.next().await.unwrap()? else {
+            panic!("expected tasks");
+        };
 
         assert_eq!(res.len(), 1);
         assert_eq!(res[0].id, task.id);
@@ -796,6 +1779,7 @@ mod tests {
                 namespace: task_1.namespace.clone(),
                 compute_graph: task_1.compute_graph_name.clone(),
                 invocation_id: task_1.invocation_id.clone(),
+                fan_out_cursor: None,
             }],
             allocations: vec![TaskPlacement {
                 task: task_1.clone(),
@@ -803,6 +1787,8 @@ mod tests {
             }],
             reduction_tasks: ReductionTasks::default(),
             diagnostic_msgs: vec![],
+            sub_graph_invocations: vec![],
+            preemptions: vec![],
         };
 
         indexify_state
@@ -818,11 +1804,3343 @@ mod tests {
         assert_eq!(res.len(), 2);
         assert_eq!(res[1].id, task_1.id);
 
-        let res = stream.next().await.unwrap()?;
+        let ExecutorEvent::Tasks(res) = stream.next().await.unwrap()? else {
+            panic!("expected tasks");
+        };
 
         assert_eq!(res.len(), 1);
         assert_eq!(res[0].id, task_1.id);
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_prefetch_hints() -> Result<()> {
+        let state_store = test_state_store::tests::TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let invocation_id = state_store.with_simple_graph().await;
+        let cg = mock_graph_a();
+        let create_tasks = |ids: &[&str]| {
+            let tasks: Vec<Task> = ids
+                .iter()
+                .map(|id| {
+                    create_mock_task(
+                        &cg,
+                        "fn_a",
+                        &format!("namespace|graph|{}|fn_a|{}", invocation_id, id),
+                        &invocation_id,
+                    )
+                })
+                .collect();
+            indexify_state.write(StateMachineUpdateRequest {
+                payload: RequestPayload::SchedulerUpdate(SchedulerUpdateRequest {
+                    task_requests: vec![requests::CreateTasksRequest {
+                        namespace: cg.namespace.clone(),
+                        compute_graph: cg.name.clone(),
+                        invocation_id: invocation_id.clone(),
+                        tasks,
+                        fan_out_cursor: None,
+                    }],
+                    allocations: vec![],
+                    reduction_tasks: ReductionTasks::default(),
+                    diagnostic_msgs: vec![],
+                    sub_graph_invocations: vec![],
+                    preemptions: vec![],
+                }),
+                state_changes_processed: vec![],
+            })
+        };
+        create_tasks(&["id_1", "id_2"]).await?;
+
+        let mut executor = mock_executor();
+        executor.features = [ExecutorFeature::PrefetchHints].into();
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::RegisterExecutor(RegisterExecutorRequest {
+                    executor: executor.clone(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+
+        let mut stream = task_stream(indexify_state.clone(), executor.id.clone(), 10);
+        let ExecutorEvent::Tasks(tasks) = stream.next().await.unwrap()? else {
+            panic!("expected tasks");
+        };
+        assert!(tasks.is_empty());
+        let ExecutorEvent::Prefetch(hints) = stream.next().await.unwrap()? else {
+            panic!("expected prefetch hints");
+        };
+        assert_eq!(hints.len(), 2);
+
+        // Only tasks which weren't hinted yet are sent
+        create_tasks(&["id_3"]).await?;
+        let ExecutorEvent::Prefetch(hints) = stream.next().await.unwrap()? else {
+            panic!("expected prefetch hints");
+        };
+        assert_eq!(hints.len(), 1);
+        assert!(hints[0].input_node_output_key.ends_with("id_3"));
+
+        // Only tasks the executor can run are upcoming
+        let reader = indexify_state.reader();
+        assert_eq!(reader.upcoming_tasks(&executor, 100, 10)?.len(), 3);
+        executor.image_name = "other_image".to_string();
+        assert!(reader.upcoming_tasks(&executor, 100, 10)?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_requeue_executor_tasks() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+
+        let cg = mock_graph_a();
+        let graph_invocation_ctx = GraphInvocationCtxBuilder::default()
+            .namespace(cg.namespace.clone())
+            .compute_graph_name(cg.name.clone())
+            .invocation_id("ingested_id".to_string())
+            .fn_task_analytics(HashMap::new())
+            .build(cg.clone())?;
+        indexify_state.db.put_cf(
+            &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&indexify_state.db),
+            graph_invocation_ctx.key(),
+            &JsonEncoder::encode(&graph_invocation_ctx)?,
+        )?;
+
+        // Three tasks on the failed executor and one on another executor
+        let failed_executor = ExecutorId::new("executor_a".to_string());
+        let other_executor = ExecutorId::new("executor_b".to_string());
+        let tasks: Vec<Task> = (0..4)
+            .map(|i| {
+                create_mock_task(
+                    &cg,
+                    "fn_a",
+                    &format!("namespace|graph|ingested_id|fn|id_{}", i),
+                    "ingested_id",
+                )
+            })
+            .collect();
+        let allocations = tasks
+            .iter()
+            .enumerate()
+            .map(|(i, task)| TaskPlacement {
+                task: task.clone(),
+                executor: if i < 3 {
+                    failed_executor.clone()
+                } else {
+                    other_executor.clone()
+                },
+            })
+            .collect();
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::SchedulerUpdate(SchedulerUpdateRequest {
+                    task_requests: vec![requests::CreateTasksRequest {
+                        namespace: cg.namespace.clone(),
+                        compute_graph: cg.name.clone(),
+                        invocation_id: "ingested_id".to_string(),
+                        tasks: tasks.clone(),
+                        fan_out_cursor: None,
+                    }],
+                    allocations,
+                    reduction_tasks: ReductionTasks::default(),
+                    diagnostic_msgs: vec![],
+                    sub_graph_invocations: vec![],
+                    preemptions: vec![],
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        assert!(indexify_state.reader().unallocated_tasks()?.is_empty());
+        let state_changes_before = indexify_state
+            .reader()
+            .get_unprocessed_state_changes()?
+            .len();
+
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::RequeueExecutorTasks(RequeueExecutorTasksRequest {
+                    executor_id: failed_executor.clone(),
+                    batch_size: 2,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+
+        let reader = indexify_state.reader();
+        assert!(reader
+            .get_tasks_by_executor(&failed_executor, 10)?
+            .is_empty());
+        assert_eq!(reader.get_tasks_by_executor(&other_executor, 10)?.len(), 1);
+        assert_eq!(reader.unallocated_tasks()?.len(), 3);
+
+        // A single state change summarizes the requeued tasks
+        let state_changes = reader.get_unprocessed_state_changes()?;
+        assert_eq!(state_changes.len(), state_changes_before + 1);
+        assert_eq!(
+            state_changes.last().unwrap().change_type,
+            ChangeType::TasksRequeued(TasksRequeuedEvent {
+                executor_id: failed_executor,
+                num_tasks: 3,
+            })
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_update_executor_labels() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let write = |payload| {
+            indexify_state.write(StateMachineUpdateRequest {
+                payload,
+                state_changes_processed: vec![],
+            })
+        };
+        let mut cg = mock_graph_a();
+        if let Some(Node::Compute(compute_fn)) = cg.nodes.get_mut("fn_a") {
+            compute_fn.placement_constraints = LabelsFilter::from_str("zone=a")?;
+        }
+        write(RequestPayload::CreateComputeGraph(
+            CreateComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph: cg.clone(),
+                expected_revision: None,
+                force: false,
+            },
+        ))
+        .await?;
+        let mut executor = mock_executor();
+        executor.labels = HashMap::from([("zone".to_string(), serde_json::json!("a"))]);
+        write(RequestPayload::RegisterExecutor(RegisterExecutorRequest {
+            executor: executor.clone(),
+        }))
+        .await?;
+        let tasks = vec![
+            create_mock_task(&cg, "fn_a", "task_a", "ingested_id"),
+            create_mock_task(&cg, "fn_b", "task_b", "ingested_id"),
+        ];
+        write(RequestPayload::SchedulerUpdate(SchedulerUpdateRequest {
+            task_requests: vec![requests::CreateTasksRequest {
+                namespace: cg.namespace.clone(),
+                compute_graph: cg.name.clone(),
+                invocation_id: "ingested_id".to_string(),
+                tasks: tasks.clone(),
+                fan_out_cursor: None,
+            }],
+            allocations: tasks
+                .iter()
+                .map(|task| TaskPlacement {
+                    task: task.clone(),
+                    executor: executor.id.clone(),
+                })
+                .collect(),
+            reduction_tasks: ReductionTasks::default(),
+            diagnostic_msgs: vec![],
+            sub_graph_invocations: vec![],
+            preemptions: vec![],
+        }))
+        .await?;
+
+        // Only the task constrained to the old zone is moved
+        write(RequestPayload::UpdateExecutorLabels(
+            UpdateExecutorLabelsRequest {
+                executor_id: executor.id.clone(),
+                labels: HashMap::from([("zone".to_string(), serde_json::json!("b"))]),
+            },
+        ))
+        .await?;
+        let reader = indexify_state.reader();
+        assert_eq!(
+            reader.get_all_executors()?[0].labels["zone"],
+            serde_json::json!("b")
+        );
+        let allocated = reader.get_tasks_by_executor(&executor.id, 10)?;
+        assert_eq!(allocated.len(), 1);
+        assert_eq!(allocated[0].compute_fn_name, "fn_b");
+        assert_eq!(reader.unallocated_tasks()?.len(), 1);
+        let state_changes = reader.get_unprocessed_state_changes()?;
+        assert!(state_changes
+            .iter()
+            .any(|state_change| state_change.change_type == ChangeType::ExecutorUpdated));
+
+        let unknown = write(RequestPayload::UpdateExecutorLabels(
+            UpdateExecutorLabelsRequest {
+                executor_id: ExecutorId::new("unknown".to_string()),
+                labels: HashMap::new(),
+            },
+        ))
+        .await;
+        assert!(unknown.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pending_invocations_counter() -> Result<()> {
+        let state_store = test_state_store::tests::TestStateStore::new().await?;
+        let reader = state_store.indexify_state.reader();
+        assert_eq!(
+            reader.get_pending_invocations(TEST_NAMESPACE, "graph_A")?,
+            0
+        );
+
+        state_store.with_simple_graph().await;
+        assert_eq!(
+            reader.get_pending_invocations(TEST_NAMESPACE, "graph_A")?,
+            1
+        );
+
+        state_store
+            .indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::DeleteComputeGraph(DeleteComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    name: "graph_A".to_string(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        assert_eq!(
+            reader.get_pending_invocations(TEST_NAMESPACE, "graph_A")?,
+            0
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pending_invocations_of_deleted_invocations() -> Result<()> {
+        let state_store = test_state_store::tests::TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let write = |payload| {
+            indexify_state.write(StateMachineUpdateRequest {
+                payload,
+                state_changes_processed: vec![],
+            })
+        };
+        let reader = indexify_state.reader();
+        let pending = || reader.get_pending_invocations(TEST_NAMESPACE, "graph_A");
+        let invocation_id = state_store.with_simple_graph().await;
+        assert_eq!(pending()?, 1);
+
+        // Deleting the in-flight invocation frees its slot
+        let delete = || DeleteInvocationRequest {
+            namespace: TEST_NAMESPACE.to_string(),
+            compute_graph: "graph_A".to_string(),
+            invocation_id: invocation_id.clone(),
+        };
+        write(RequestPayload::DeleteInvocation(delete())).await?;
+        assert_eq!(pending()?, 0);
+        let ctx = reader.invocation_ctx(TEST_NAMESPACE, "graph_A", &invocation_id)?;
+        assert!(ctx.completed);
+
+        // Invoking again takes a slot, deleting the finished invocation again
+        // doesn't free another one
+        let mut invocation_payload = mock_invocation_payload();
+        invocation_payload.id = "invocation_2".to_string();
+        write(RequestPayload::InvokeComputeGraph(
+            InvokeComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph_name: "graph_A".to_string(),
+                invocation_payload: invocation_payload.clone(),
+            },
+        ))
+        .await?;
+        assert_eq!(pending()?, 1);
+        write(RequestPayload::DeleteInvocation(delete())).await?;
+        assert_eq!(pending()?, 1);
+
+        // Deleting the namespace frees the slots of its in-flight invocations
+        write(RequestPayload::DeleteNamespace(DeleteNamespaceRequest {
+            namespace: TEST_NAMESPACE.to_string(),
+        }))
+        .await?;
+        assert_eq!(pending()?, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invocation_results() -> Result<()> {
+        let state_store = test_state_store::tests::TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let write = |payload| {
+            indexify_state.write(StateMachineUpdateRequest {
+                payload,
+                state_changes_processed: vec![],
+            })
+        };
+        state_store.with_simple_graph().await;
+        let cg = mock_graph_a();
+        let create_tasks = |invocation_id: &str, tasks: Vec<Task>| {
+            RequestPayload::SchedulerUpdate(SchedulerUpdateRequest {
+                task_requests: vec![requests::CreateTasksRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: cg.name.clone(),
+                    invocation_id: invocation_id.to_string(),
+                    tasks,
+                    fan_out_cursor: None,
+                }],
+                allocations: vec![],
+                reduction_tasks: ReductionTasks::default(),
+                diagnostic_msgs: vec![],
+                sub_graph_invocations: vec![],
+                preemptions: vec![],
+            })
+        };
+        // Runs the terminal functions of the graph, fn_c fails in the second
+        // invocation. The scheduler update following the last task finishes
+        // the invocation.
+        let mut failed_tasks = vec![];
+        for (invocation_id, failed_fn) in [("invocation_1", None), ("invocation_2", Some("fn_c"))] {
+            write(RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph_name: cg.name.clone(),
+                invocation_payload: InvocationPayload {
+                    id: invocation_id.to_string(),
+                    ..mock_invocation_payload()
+                },
+            }))
+            .await?;
+            let tasks: Vec<Task> = ["fn_b", "fn_c"]
+                .iter()
+                .map(|compute_fn| create_mock_task(&cg, compute_fn, "fn_a_output", invocation_id))
+                .collect();
+            write(create_tasks(invocation_id, tasks.clone())).await?;
+            for task in tasks {
+                let failed = failed_fn == Some(task.compute_fn_name.as_str());
+                let diagnostics = TaskDiagnostics {
+                    exception: Some(DataPayload {
+                        path: format!("file:///{}/exception", task.id),
+                        size: 5,
+                        sha256_hash: "hash".to_string(),
+                        tier: Default::default(),
+                        mime_type: None,
+                        content_encoding: None,
+                        metadata: Default::default(),
+                        replicas: Vec::new(),
+                        compression: None,
+                    }),
+                    stdout: None,
+                    stderr: None,
+                    structured_logs: None,
+                };
+                let (node_outputs, task_outcome) = if failed {
+                    failed_tasks.push(task.clone());
+                    (vec![], TaskOutcome::Failure)
+                } else {
+                    let output =
+                        mock_node_fn_output(invocation_id, &cg.name, &task.compute_fn_name, None);
+                    (vec![output], TaskOutcome::Success)
+                };
+                write(RequestPayload::FinalizeTask(FinalizeTaskRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: cg.name.clone(),
+                    compute_fn: task.compute_fn_name.clone(),
+                    invocation_id: invocation_id.to_string(),
+                    task_id: task.id.clone(),
+                    node_outputs,
+                    task_outcome,
+                    executor_id: ExecutorId::default(),
+                    diagnostics: failed.then_some(diagnostics),
+                    inline_payloads: vec![],
+                    resource_usage: None,
+                }))
+                .await?;
+                write(create_tasks(invocation_id, vec![])).await?;
+            }
+        }
+
+        let reader = indexify_state.reader();
+        let result = reader
+            .invocation_result(TEST_NAMESPACE, &cg.name, "invocation_1")?
+            .unwrap();
+        assert_eq!(result.status, InvocationStatus::Success);
+        assert!(result.errors.is_empty());
+        let mut output_fns: Vec<_> = result
+            .outputs
+            .iter()
+            .map(|output| output.compute_fn_name.as_str())
+            .collect();
+        output_fns.sort();
+        assert_eq!(output_fns, vec!["fn_b", "fn_c"]);
+
+        // The result of a failed invocation has the outputs of the functions
+        // which succeeded and the exception of the one which failed
+        assert!(
+            reader
+                .invocation_ctx(TEST_NAMESPACE, &cg.name, "invocation_2")?
+                .completed
+        );
+        let result = reader
+            .invocation_result(TEST_NAMESPACE, &cg.name, "invocation_2")?
+            .unwrap();
+        assert_eq!(result.status, InvocationStatus::Failure);
+        assert_eq!(result.outputs.len(), 1);
+        assert_eq!(result.outputs[0].compute_fn_name, "fn_b");
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(result.errors[0].compute_fn, "fn_c");
+        assert_eq!(result.errors[0].task_id, failed_tasks[0].id);
+        assert_eq!(
+            result.errors[0].exception.as_ref().unwrap().path,
+            format!("file:///{}/exception", failed_tasks[0].id)
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_inline_payloads() -> Result<()> {
+        let state_store = test_state_store::tests::TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let write = |payload| {
+            indexify_state.write(StateMachineUpdateRequest {
+                payload,
+                state_changes_processed: vec![],
+            })
+        };
+        let invocation_id = state_store.with_simple_graph().await;
+        let cg = mock_graph_a();
+        let task = create_mock_task(&cg, "fn_a", "task_1", &invocation_id);
+        write(RequestPayload::SchedulerUpdate(SchedulerUpdateRequest {
+            task_requests: vec![requests::CreateTasksRequest {
+                namespace: cg.namespace.clone(),
+                compute_graph: cg.name.clone(),
+                invocation_id: invocation_id.clone(),
+                tasks: vec![task.clone()],
+                fan_out_cursor: None,
+            }],
+            allocations: vec![],
+            reduction_tasks: ReductionTasks::default(),
+            diagnostic_msgs: vec![],
+            sub_graph_invocations: vec![],
+            preemptions: vec![],
+        }))
+        .await?;
+
+        let path = DataPayload::inline_path("output_0");
+        let mut node_output = mock_node_fn_output_fn_a(&invocation_id, &cg.name, None);
+        node_output.payload = OutputPayload::Fn(DataPayload {
+            path: path.clone(),
+            size: 2,
+            sha256_hash: "hash".to_string(),
+            tier: Default::default(),
+            mime_type: None,
+            content_encoding: None,
+            metadata: Default::default(),
+            replicas: Vec::new(),
+            compression: None,
+        });
+        write(RequestPayload::FinalizeTask(FinalizeTaskRequest {
+            namespace: task.namespace.clone(),
+            compute_graph: task.compute_graph_name.clone(),
+            compute_fn: task.compute_fn_name.clone(),
+            invocation_id: invocation_id.clone(),
+            task_id: task.id.clone(),
+            node_outputs: vec![node_output],
+            task_outcome: TaskOutcome::Success,
+            executor_id: ExecutorId::default(),
+            diagnostics: None,
+            inline_payloads: vec![InlinePayload {
+                path: path.clone(),
+                data: b"ok".to_vec(),
+            }],
+            resource_usage: None,
+        }))
+        .await?;
+        let reader = indexify_state.reader();
+        assert_eq!(reader.get_inline_payload(&path)?, Some(b"ok".to_vec()));
+
+        // Inline payloads are removed with the urls of purged outputs
+        let delete_request = || DeleteComputeGraphRequest {
+            namespace: TEST_NAMESPACE.to_string(),
+            name: cg.name.clone(),
+        };
+        write(RequestPayload::DeleteComputeGraph(delete_request())).await?;
+        write(RequestPayload::PurgeComputeGraph(delete_request())).await?;
+        let urls = reader.get_gc_urls(None)?;
+        assert!(urls.contains(&path));
+        write(RequestPayload::RemoveGcUrls(urls)).await?;
+        assert_eq!(reader.get_inline_payload(&path)?, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_expire_payloads() -> Result<()> {
+        let state_store = test_state_store::tests::TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let write = |payload| {
+            indexify_state.write(StateMachineUpdateRequest {
+                payload,
+                state_changes_processed: vec![],
+            })
+        };
+        let invocation_id = state_store.with_simple_graph().await;
+        let cg = mock_graph_a();
+        let reader = indexify_state.reader();
+        let owner = PayloadOwner::Invocation {
+            namespace: TEST_NAMESPACE.to_string(),
+            compute_graph: cg.name.clone(),
+            invocation_id: invocation_id.clone(),
+        };
+        let payload = reader
+            .invocation_payload(TEST_NAMESPACE, &cg.name, &invocation_id)?
+            .payload;
+        let replica = PayloadReplica {
+            region: "eu".to_string(),
+            path: "file:///eu/input".to_string(),
+        };
+        write(RequestPayload::RecordPayloadReplicas(
+            RecordPayloadReplicasRequest {
+                replications: vec![PayloadReplication {
+                    owner: owner.clone(),
+                    payload: payload.clone(),
+                    replica: replica.clone(),
+                }],
+            },
+        ))
+        .await?;
+        let payload = reader
+            .invocation_payload(TEST_NAMESPACE, &cg.name, &invocation_id)?
+            .payload;
+        assert_eq!(payload.replicas, vec![replica.clone()]);
+
+        // The expired payload keeps the path and hash of the payload, its blob
+        // and the blobs of its replicas are garbage collected
+        let expired = DataPayload {
+            tier: StorageTier::Expired,
+            mime_type: None,
+            content_encoding: None,
+            metadata: Default::default(),
+            compression: None,
+            ..payload.clone()
+        };
+        write(RequestPayload::MovePayloads(MovePayloadsRequest {
+            moves: vec![PayloadMove {
+                owner,
+                from: payload.clone(),
+                to: expired,
+            }],
+        }))
+        .await?;
+        let expired = reader
+            .invocation_payload(TEST_NAMESPACE, &cg.name, &invocation_id)?
+            .payload;
+        assert_eq!(expired.tier, StorageTier::Expired);
+        assert_eq!(expired.path, payload.path);
+        assert_eq!(expired.sha256_hash, payload.sha256_hash);
+        assert!(expired.replicas.is_empty());
+        let mut urls = reader.get_gc_urls(None)?;
+        urls.sort();
+        let mut expected = vec![payload.path.clone(), replica.path.clone()];
+        expected.sort();
+        assert_eq!(urls, expected);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_signal_invocation() -> Result<()> {
+        let state_store = test_state_store::tests::TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let write = |payload| {
+            indexify_state.write(StateMachineUpdateRequest {
+                payload,
+                state_changes_processed: vec![],
+            })
+        };
+        let invocation_id = state_store.with_simple_graph().await;
+        let cg = mock_graph_a();
+        let task = create_mock_task(&cg, "fn_b", "task_1", &invocation_id);
+        write(RequestPayload::SchedulerUpdate(SchedulerUpdateRequest {
+            task_requests: vec![requests::CreateTasksRequest {
+                namespace: cg.namespace.clone(),
+                compute_graph: cg.name.clone(),
+                invocation_id: invocation_id.clone(),
+                tasks: vec![task.clone()],
+                fan_out_cursor: None,
+            }],
+            allocations: vec![],
+            reduction_tasks: ReductionTasks::default(),
+            diagnostic_msgs: vec![],
+            sub_graph_invocations: vec![],
+            preemptions: vec![],
+        }))
+        .await?;
+
+        let signal = |gate: &str| {
+            RequestPayload::SignalInvocation(requests::SignalInvocationRequest {
+                namespace: cg.namespace.clone(),
+                compute_graph: cg.name.clone(),
+                invocation_id: invocation_id.clone(),
+                gate: gate.to_string(),
+                payload: DataPayload {
+                    path: "signal".to_string(),
+                    size: 2,
+                    sha256_hash: "hash".to_string(),
+                    tier: Default::default(),
+                    mime_type: None,
+                    content_encoding: None,
+                    metadata: Default::default(),
+                    replicas: Vec::new(),
+                    compression: None,
+                },
+            })
+        };
+        write(signal("fn_b")).await?;
+        let reader = indexify_state.reader();
+        let task = reader
+            .get_task(
+                &cg.namespace,
+                &cg.name,
+                &invocation_id,
+                "fn_b",
+                &task.id.to_string(),
+            )?
+            .unwrap();
+        assert_eq!(task.outcome, TaskOutcome::Success);
+        let outputs = reader.get_task_outputs(&cg.namespace, &task.id.to_string())?;
+        assert_eq!(outputs.len(), 1);
+
+        // Gates are opened once, unknown gates have no waiting task
+        for gate in ["fn_b", "fn_c"] {
+            let err = write(signal(gate)).await.unwrap_err();
+            assert!(matches!(
+                err.downcast_ref::<StateStoreError>(),
+                Some(StateStoreError::NotFound(_))
+            ));
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_legal_hold() -> Result<()> {
+        let state_store = test_state_store::tests::TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let write = |payload| {
+            indexify_state.write(StateMachineUpdateRequest {
+                payload,
+                state_changes_processed: vec![],
+            })
+        };
+        write(RequestPayload::CreateNameSpace(NamespaceRequest {
+            name: TEST_NAMESPACE.to_string(),
+            queue_limits: Default::default(),
+            scheduling_weight: None,
+            reserved_capacity: None,
+            replication: None,
+        }))
+        .await?;
+        let invocation_id = state_store.with_simple_graph().await;
+        let cg = mock_graph_a();
+        let hold_request = || LegalHoldRequest {
+            namespace: TEST_NAMESPACE.to_string(),
+            compute_graph: cg.name.clone(),
+            invocation_id: Some(invocation_id.clone()),
+            reason: "litigation".to_string(),
+            identity: "alice".to_string(),
+        };
+        write(RequestPayload::SetLegalHold(hold_request())).await?;
+        let reader = indexify_state.reader();
+        assert!(reader.is_under_legal_hold(TEST_NAMESPACE, &cg.name, &invocation_id)?);
+        assert!(!reader.is_under_legal_hold(TEST_NAMESPACE, &cg.name, "other")?);
+
+        // Held invocations can't be deleted, nor their namespace
+        let is_conflict = |err: anyhow::Error| {
+            matches!(
+                err.downcast_ref::<StateStoreError>(),
+                Some(StateStoreError::Conflict(_))
+            )
+        };
+        let err = write(RequestPayload::DeleteInvocation(DeleteInvocationRequest {
+            namespace: TEST_NAMESPACE.to_string(),
+            compute_graph: cg.name.clone(),
+            invocation_id: invocation_id.clone(),
+        }))
+        .await
+        .unwrap_err();
+        assert!(is_conflict(err));
+        let err = write(RequestPayload::DeleteNamespace(DeleteNamespaceRequest {
+            namespace: TEST_NAMESPACE.to_string(),
+        }))
+        .await
+        .unwrap_err();
+        assert!(is_conflict(err));
+
+        write(RequestPayload::ClearLegalHold(LegalHoldRequest {
+            reason: "case closed".to_string(),
+            identity: "bob".to_string(),
+            ..hold_request()
+        }))
+        .await?;
+        assert!(reader.legal_holds(TEST_NAMESPACE, None)?.is_empty());
+        let (audit, _) = reader.legal_hold_audit(TEST_NAMESPACE, None, None)?;
+        assert_eq!(
+            audit
+                .iter()
+                .map(|entry| (entry.action, entry.identity.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (LegalHoldAction::Set, "alice"),
+                (LegalHoldAction::Cleared, "bob")
+            ]
+        );
+        assert!(write(RequestPayload::ClearLegalHold(hold_request()))
+            .await
+            .is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_delete_namespace() -> Result<()> {
+        let state_store = test_state_store::tests::TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let write = |payload| {
+            indexify_state.write(StateMachineUpdateRequest {
+                payload,
+                state_changes_processed: vec![],
+            })
+        };
+        let namespace_request = || NamespaceRequest {
+            name: TEST_NAMESPACE.to_string(),
+            queue_limits: Default::default(),
+            scheduling_weight: None,
+            reserved_capacity: None,
+            replication: None,
+        };
+        write(RequestPayload::CreateNameSpace(namespace_request())).await?;
+        let invocation_id = state_store.with_simple_graph().await;
+        let cg = mock_graph_a();
+        let tasks: Vec<Task> = ["id_1", "id_2"]
+            .iter()
+            .map(|id| {
+                create_mock_task(
+                    &cg,
+                    "fn_a",
+                    &format!("namespace|graph|{}|fn_a|{}", invocation_id, id),
+                    &invocation_id,
+                )
+            })
+            .collect();
+        let executor_id = ExecutorId::new("executor_a".to_string());
+        indexify_state
+            .executor_states
+            .write()
+            .await
+            .entry(executor_id.clone())
+            .or_default();
+        write(RequestPayload::SchedulerUpdate(SchedulerUpdateRequest {
+            task_requests: vec![requests::CreateTasksRequest {
+                namespace: cg.namespace.clone(),
+                compute_graph: cg.name.clone(),
+                invocation_id: invocation_id.clone(),
+                tasks: tasks.clone(),
+                fan_out_cursor: None,
+            }],
+            allocations: vec![TaskPlacement {
+                task: tasks[0].clone(),
+                executor: executor_id.clone(),
+            }],
+            reduction_tasks: ReductionTasks::default(),
+            diagnostic_msgs: vec![],
+            sub_graph_invocations: vec![],
+            preemptions: vec![],
+        }))
+        .await?;
+
+        let delete_request = || DeleteNamespaceRequest {
+            namespace: TEST_NAMESPACE.to_string(),
+        };
+        write(RequestPayload::DeleteNamespace(delete_request())).await?;
+        let reader = indexify_state.reader();
+        assert!(reader
+            .get_compute_graph(TEST_NAMESPACE, &cg.name)?
+            .is_none());
+        assert!(
+            reader
+                .invocation_ctx(TEST_NAMESPACE, &cg.name, &invocation_id)?
+                .completed
+        );
+        assert!(reader.unallocated_tasks()?.is_empty());
+        assert!(reader.get_tasks_by_executor(&executor_id, 10)?.is_empty());
+        assert_eq!(
+            indexify_state.executor_states.read().await[&executor_id].aborted_task_ids,
+            vec![tasks[0].id.clone()]
+        );
+        let progress = reader.namespace_deletion_progress(TEST_NAMESPACE)?.unwrap();
+        assert_eq!((progress.compute_graphs, progress.invocations), (1, 1));
+
+        // Writes to the namespace are rejected until it is purged
+        let err = write(RequestPayload::CreateNameSpace(namespace_request()))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<StateStoreError>(),
+            Some(StateStoreError::Conflict(_))
+        ));
+        write(RequestPayload::PurgeNamespace(delete_request())).await?;
+        assert!(reader.get_namespace(TEST_NAMESPACE)?.is_some());
+
+        write(RequestPayload::PurgeComputeGraph(
+            DeleteComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                name: cg.name.clone(),
+            },
+        ))
+        .await?;
+        write(RequestPayload::PurgeNamespace(delete_request())).await?;
+        assert!(reader.get_namespace(TEST_NAMESPACE)?.is_none());
+        assert!(reader
+            .namespace_deletion_progress(TEST_NAMESPACE)?
+            .is_none());
+        write(RequestPayload::CreateNameSpace(namespace_request())).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_executor_control_channel() -> Result<()> {
+        let state_store = test_state_store::tests::TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let write = |payload| {
+            indexify_state.write(StateMachineUpdateRequest {
+                payload,
+                state_changes_processed: vec![],
+            })
+        };
+        write(RequestPayload::CreateNameSpace(NamespaceRequest {
+            name: TEST_NAMESPACE.to_string(),
+            queue_limits: Default::default(),
+            scheduling_weight: None,
+            reserved_capacity: None,
+            replication: None,
+        }))
+        .await?;
+        let invocation_id = state_store.with_simple_graph().await;
+        let cg = mock_graph_a();
+        let task = create_mock_task(
+            &cg,
+            "fn_a",
+            &format!("namespace|graph|{}|fn_a|id_1", invocation_id),
+            &invocation_id,
+        );
+        let mut executor = mock_executor();
+        executor.features = [ExecutorFeature::ControlChannel].into();
+        write(RequestPayload::RegisterExecutor(RegisterExecutorRequest {
+            executor: executor.clone(),
+        }))
+        .await?;
+        let executor_id = executor.id.clone();
+        indexify_state
+            .executor_states
+            .write()
+            .await
+            .entry(executor_id.clone())
+            .or_default();
+        write(RequestPayload::SchedulerUpdate(SchedulerUpdateRequest {
+            task_requests: vec![requests::CreateTasksRequest {
+                namespace: cg.namespace.clone(),
+                compute_graph: cg.name.clone(),
+                invocation_id: invocation_id.clone(),
+                tasks: vec![task.clone()],
+                fan_out_cursor: None,
+            }],
+            allocations: vec![TaskPlacement {
+                task: task.clone(),
+                executor: executor_id.clone(),
+            }],
+            reduction_tasks: ReductionTasks::default(),
+            diagnostic_msgs: vec![],
+            sub_graph_invocations: vec![],
+            preemptions: vec![],
+        }))
+        .await?;
+        write(RequestPayload::DeleteNamespace(DeleteNamespaceRequest {
+            namespace: TEST_NAMESPACE.to_string(),
+        }))
+        .await?;
+
+        // The abort is delivered over the control channel, not the task stream
+        let commands = indexify_state
+            .abort_commands(&executor_id, Duration::ZERO)
+            .await?
+            .unwrap();
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].task_id, task.id);
+        assert_eq!(commands[0].reason, AbortReason::Cancelled);
+        assert!(indexify_state.executor_states.read().await[&executor_id]
+            .aborted_task_ids
+            .is_empty());
+
+        // Unacknowledged aborts are persisted and redelivered on reconnect
+        indexify_state.executor_states.write().await.remove(&executor_id);
+        assert!(indexify_state
+            .abort_commands(&executor_id, Duration::ZERO)
+            .await?
+            .is_none());
+        assert_eq!(indexify_state.reader().pending_aborts(&executor_id)?, commands);
+        indexify_state
+            .executor_states
+            .write()
+            .await
+            .entry(executor_id.clone())
+            .or_default();
+        assert_eq!(
+            indexify_state
+                .abort_commands(&executor_id, Duration::ZERO)
+                .await?
+                .unwrap(),
+            commands
+        );
+
+        // Acknowledged aborts aren't delivered again
+        assert_eq!(
+            indexify_state
+                .acknowledge_aborts(&executor_id, vec![task.id.clone()])
+                .await?,
+            1
+        );
+        assert!(indexify_state
+            .abort_commands(&executor_id, Duration::ZERO)
+            .await?
+            .unwrap()
+            .is_empty());
+        assert!(indexify_state.reader().pending_aborts(&executor_id)?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_maintenance_mode() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let write = |payload| {
+            indexify_state.write(StateMachineUpdateRequest {
+                payload,
+                state_changes_processed: vec![],
+            })
+        };
+        let create_namespace = || {
+            RequestPayload::CreateNameSpace(NamespaceRequest {
+                name: TEST_NAMESPACE.to_string(),
+                queue_limits: Default::default(),
+                scheduling_weight: None,
+                reserved_capacity: None,
+                replication: None,
+            })
+        };
+
+        indexify_state.set_maintenance_mode(true);
+        let err = write(create_namespace()).await.unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<StateStoreError>(),
+            Some(&StateStoreError::Maintenance)
+        );
+        // Executors keep working
+        write(RequestPayload::RegisterExecutor(RegisterExecutorRequest {
+            executor: mock_executor(),
+        }))
+        .await?;
+
+        indexify_state.set_maintenance_mode(false);
+        write(create_namespace()).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_apply_manifest() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let write = |payload| {
+            indexify_state.write(StateMachineUpdateRequest {
+                payload,
+                state_changes_processed: vec![],
+            })
+        };
+        let graph_request =
+            |compute_graph: ComputeGraph, expected_revision| CreateComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph,
+                expected_revision,
+                force: false,
+            };
+        let (graph_a, graph_b) = (mock_graph_a(), mock_graph_b());
+        write(RequestPayload::CreateComputeGraph(graph_request(
+            graph_a.clone(),
+            None,
+        )))
+        .await?;
+
+        // The update of graph_a conflicts, graph_b isn't created either
+        let err = write(RequestPayload::ApplyManifest(ApplyManifestRequest {
+            namespace: TEST_NAMESPACE.to_string(),
+            graphs: vec![
+                graph_request(graph_b.clone(), None),
+                graph_request(graph_a.clone(), Some(2)),
+            ],
+        }))
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<StateStoreError>(),
+            Some(StateStoreError::Conflict(_))
+        ));
+        let reader = indexify_state.reader();
+        assert!(reader
+            .get_compute_graph(TEST_NAMESPACE, &graph_b.name)?
+            .is_none());
+
+        write(RequestPayload::ApplyManifest(ApplyManifestRequest {
+            namespace: TEST_NAMESPACE.to_string(),
+            graphs: vec![
+                graph_request(graph_b.clone(), None),
+                graph_request(graph_a.clone(), Some(1)),
+            ],
+        }))
+        .await?;
+        let graph = reader.get_compute_graph(TEST_NAMESPACE, &graph_a.name)?;
+        assert_eq!(graph.unwrap().revision, 2);
+        let graph = reader.get_compute_graph(TEST_NAMESPACE, &graph_b.name)?;
+        assert_eq!(graph.unwrap().revision, 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_graph_canary() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let write = |payload| {
+            indexify_state.write(StateMachineUpdateRequest {
+                payload,
+                state_changes_processed: vec![],
+            })
+        };
+        let cg = mock_graph_a();
+        write(RequestPayload::CreateComputeGraph(
+            CreateComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph: cg.clone(),
+                expected_revision: None,
+                force: false,
+            },
+        ))
+        .await?;
+        let mut canary_graph = cg.clone();
+        canary_graph.code.sha256_hash = "canary_hash".to_string();
+        write(RequestPayload::CreateGraphCanary(
+            CreateGraphCanaryRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph: canary_graph.clone(),
+                expected_revision: Some(1),
+                percent: 100,
+            },
+        ))
+        .await?;
+        let reader = indexify_state.reader();
+        let canary = reader.get_graph_canary(TEST_NAMESPACE, &cg.name)?.unwrap();
+        assert_eq!(canary.compute_graph.version, cg.version.next());
+
+        let invoke = |percent: u8| {
+            let indexify_state = indexify_state.clone();
+            let name = cg.name.clone();
+            async move {
+                indexify_state
+                    .write(StateMachineUpdateRequest {
+                        payload: RequestPayload::UpdateGraphCanary(UpdateGraphCanaryRequest {
+                            namespace: TEST_NAMESPACE.to_string(),
+                            compute_graph: name.clone(),
+                            percent,
+                        }),
+                        state_changes_processed: vec![],
+                    })
+                    .await?;
+                let mut invocation_payload = mock_invocation_payload();
+                invocation_payload.id = format!("invocation_{}", percent);
+                indexify_state
+                    .write(StateMachineUpdateRequest {
+                        payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                            namespace: TEST_NAMESPACE.to_string(),
+                            compute_graph_name: name.clone(),
+                            invocation_payload: invocation_payload.clone(),
+                        }),
+                        state_changes_processed: vec![],
+                    })
+                    .await?;
+                indexify_state.reader().invocation_ctx(
+                    TEST_NAMESPACE,
+                    &name,
+                    &invocation_payload.id,
+                )
+            }
+        };
+        let ctx = invoke(100).await?;
+        assert!(ctx.canary);
+        assert_eq!(ctx.graph_version, canary.compute_graph.version);
+        let graph =
+            reader.get_invocation_compute_graph(TEST_NAMESPACE, &cg.name, &ctx.invocation_id)?;
+        assert_eq!(graph.unwrap().code.sha256_hash, "canary_hash");
+        let ctx = invoke(0).await?;
+        assert!(!ctx.canary);
+        assert_eq!(ctx.graph_version, cg.version);
+
+        // Updates of the graph would conflict with the canary
+        let update = write(RequestPayload::CreateComputeGraph(
+            CreateComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph: cg.clone(),
+                expected_revision: Some(1),
+                force: false,
+            },
+        ))
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            update.downcast_ref::<StateStoreError>(),
+            Some(StateStoreError::Conflict(_))
+        ));
+
+        let canary_request = || GraphCanaryRequest {
+            namespace: TEST_NAMESPACE.to_string(),
+            compute_graph: cg.name.clone(),
+        };
+        write(RequestPayload::FinalizeGraphCanary(canary_request())).await?;
+        let graph = reader.get_compute_graph(TEST_NAMESPACE, &cg.name)?.unwrap();
+        assert_eq!(graph.version, canary.compute_graph.version);
+        assert_eq!(graph.code.sha256_hash, "canary_hash");
+        assert_eq!(graph.revision, 2);
+        assert!(reader.get_graph_canary(TEST_NAMESPACE, &cg.name)?.is_none());
+
+        // Rolled back canaries no longer receive invocations
+        write(RequestPayload::CreateGraphCanary(
+            CreateGraphCanaryRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph: cg.clone(),
+                expected_revision: None,
+                percent: 100,
+            },
+        ))
+        .await?;
+        write(RequestPayload::RollbackGraphCanary(canary_request())).await?;
+        assert!(reader.get_graph_canary(TEST_NAMESPACE, &cg.name)?.is_none());
+        let rollback = write(RequestPayload::RollbackGraphCanary(canary_request())).await;
+        assert!(rollback.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_create_list_and_delete_executor_pools() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+
+        let pool = ExecutorPool {
+            namespace: TEST_NAMESPACE.to_string(),
+            name: "gpu".to_string(),
+            selector: LabelsFilter::from_str("gpu=true")?,
+            min_size: Some(1),
+            max_size: Some(4),
+            created_at: 0,
+        };
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateExecutorPool(CreateExecutorPoolRequest {
+                    pool: pool.clone(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+
+        let reader = indexify_state.reader();
+        assert_eq!(
+            reader.list_executor_pools(TEST_NAMESPACE)?,
+            vec![pool.clone()]
+        );
+        assert!(reader.list_executor_pools("other")?.is_empty());
+        assert_eq!(reader.get_executor_pool(TEST_NAMESPACE, "gpu")?, Some(pool));
+
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::DeleteExecutorPool(DeleteExecutorPoolRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    name: "gpu".to_string(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        assert!(reader.get_executor_pool(TEST_NAMESPACE, "gpu")?.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_executor_blacklist() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+
+        let entry = |executor_id: &str, expires_at: u64| ExecutorBlacklistEntry {
+            namespace: TEST_NAMESPACE.to_string(),
+            compute_graph: "graph_A".to_string(),
+            compute_fn: "fn_a".to_string(),
+            executor_id: ExecutorId::new(executor_id.to_string()),
+            failures: 3,
+            blacklisted_at: 100,
+            expires_at,
+        };
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::BlacklistExecutors(BlacklistExecutorsRequest {
+                    entries: vec![entry("executor_1", 200), entry("executor_2", 1000)],
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+
+        let reader = indexify_state.reader();
+        let state_changes = reader.get_unprocessed_state_changes()?;
+        assert_eq!(
+            state_changes
+                .iter()
+                .filter(|change| change.change_type == ChangeType::ExecutorBlacklisted)
+                .count(),
+            2
+        );
+        // Expired entries are ignored
+        assert_eq!(
+            reader.executor_blacklist(500)?,
+            vec![entry("executor_2", 1000)]
+        );
+        assert_eq!(
+            reader.blacklisted_executors(TEST_NAMESPACE, "graph_A", "fn_a", 150)?,
+            HashSet::from([
+                ExecutorId::new("executor_1".to_string()),
+                ExecutorId::new("executor_2".to_string())
+            ])
+        );
+        assert!(reader
+            .blacklisted_executors(TEST_NAMESPACE, "graph_A", "fn_b", 150)?
+            .is_empty());
+
+        let remove = |executor_id: &str| StateMachineUpdateRequest {
+            payload: RequestPayload::RemoveExecutorBlacklistEntry(
+                RemoveExecutorBlacklistEntryRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: "graph_A".to_string(),
+                    compute_fn: "fn_a".to_string(),
+                    executor_id: ExecutorId::new(executor_id.to_string()),
+                },
+            ),
+            state_changes_processed: vec![],
+        };
+        indexify_state.write(remove("executor_2")).await?;
+        assert!(reader.executor_blacklist(500)?.is_empty());
+        assert!(indexify_state.write(remove("executor_2")).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_compute_graph_revision_conflict() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let cg = mock_graph_a();
+        let update = |expected_revision: Option<u64>, force: bool| StateMachineUpdateRequest {
+            payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph: cg.clone(),
+                expected_revision,
+                force,
+            }),
+            state_changes_processed: vec![],
+        };
+        let revision = || -> Result<u64> {
+            Ok(indexify_state
+                .reader()
+                .get_compute_graph(TEST_NAMESPACE, &cg.name)?
+                .unwrap()
+                .revision)
+        };
+
+        indexify_state.write(update(None, false)).await?;
+        assert_eq!(revision()?, 1);
+
+        // Updates must be based on the current revision
+        for expected_revision in [None, Some(0), Some(2)] {
+            let err = indexify_state
+                .write(update(expected_revision, false))
+                .await
+                .unwrap_err();
+            assert!(matches!(
+                err.downcast_ref::<StateStoreError>(),
+                Some(StateStoreError::Conflict(_))
+            ));
+        }
+        indexify_state.write(update(Some(1), false)).await?;
+        assert_eq!(revision()?, 2);
+
+        // Forced updates skip the check
+        indexify_state.write(update(None, true)).await?;
+        assert_eq!(revision()?, 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_delete_and_undelete_compute_graph() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let cg = mock_graph_a();
+        let write = |payload| {
+            indexify_state.write(StateMachineUpdateRequest {
+                payload,
+                state_changes_processed: vec![],
+            })
+        };
+        let create = |force| {
+            RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph: cg.clone(),
+                expected_revision: None,
+                force,
+            })
+        };
+        let graph_request = || DeleteComputeGraphRequest {
+            namespace: TEST_NAMESPACE.to_string(),
+            name: cg.name.clone(),
+        };
+        write(create(false)).await?;
+        write(RequestPayload::DeleteComputeGraph(graph_request())).await?;
+
+        let reader = indexify_state.reader();
+        assert!(reader
+            .get_compute_graph(TEST_NAMESPACE, &cg.name)?
+            .is_none());
+        let (deleted, _) = reader.list_deleted_compute_graphs(TEST_NAMESPACE, None, None)?;
+        assert_eq!(deleted.len(), 1);
+        assert!(deleted[0].deleted_at.is_some());
+
+        // A deleted graph can't be silently replaced
+        assert!(write(create(false)).await.is_err());
+
+        write(RequestPayload::UndeleteComputeGraph(graph_request())).await?;
+        let graph = reader.get_compute_graph(TEST_NAMESPACE, &cg.name)?.unwrap();
+        assert!(graph.deleted_at.is_none());
+        let (deleted, _) = reader.list_deleted_compute_graphs(TEST_NAMESPACE, None, None)?;
+        assert!(deleted.is_empty());
+
+        // Forcing the creation purges the deleted graph
+        write(RequestPayload::DeleteComputeGraph(graph_request())).await?;
+        write(create(true)).await?;
+        let (deleted, _) = reader.list_deleted_compute_graphs(TEST_NAMESPACE, None, None)?;
+        assert!(deleted.is_empty());
+        assert!(reader
+            .get_compute_graph(TEST_NAMESPACE, &cg.name)?
+            .is_some());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_code_artifacts() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let cg = mock_graph_a();
+        let executor = mock_executor();
+        let write = |payload| {
+            indexify_state.write(StateMachineUpdateRequest {
+                payload,
+                state_changes_processed: vec![],
+            })
+        };
+        let update_artifacts = || {
+            RequestPayload::UpdateExecutorArtifacts(UpdateExecutorArtifactsRequest {
+                executor_id: executor.id.clone(),
+                cached_artifacts: [cg.code.sha256_hash.clone()].into(),
+                queue_depth: 0,
+                max_outstanding_tasks: None,
+            })
+        };
+        write(RequestPayload::CreateComputeGraph(
+            CreateComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph: cg.clone(),
+                expected_revision: None,
+                force: false,
+            },
+        ))
+        .await?;
+
+        let reader = indexify_state.reader();
+        let code = reader.get_code_artifact(&cg.code.sha256_hash)?.unwrap();
+        assert_eq!(code.path, cg.code.path);
+        assert!(reader.get_code_artifact("unknown")?.is_none());
+
+        // Only registered executors can report their artifacts
+        assert!(write(update_artifacts()).await.is_err());
+
+        write(RequestPayload::RegisterExecutor(RegisterExecutorRequest {
+            executor: executor.clone(),
+        }))
+        .await?;
+        write(update_artifacts()).await?;
+        let executors = reader.get_all_executors()?;
+        assert!(executors[0].cached_artifacts.contains(&cg.code.sha256_hash));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_storage_usage() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let cg = mock_graph_a();
+        let write = |payload| {
+            indexify_state.write(StateMachineUpdateRequest {
+                payload,
+                state_changes_processed: vec![],
+            })
+        };
+        let usage = || -> Result<data_model::StorageUsage> {
+            let graphs = indexify_state
+                .reader()
+                .namespace_storage_usage(TEST_NAMESPACE)?;
+            Ok(graphs
+                .into_iter()
+                .find(|graph| graph.compute_graph == cg.name)
+                .map(|graph| graph.usage)
+                .unwrap_or_default())
+        };
+        write(RequestPayload::CreateComputeGraph(
+            CreateComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph: cg.clone(),
+                expected_revision: None,
+                force: false,
+            },
+        ))
+        .await?;
+        assert_eq!(usage()?.code_bytes, cg.code.size);
+
+        let invocation_payload = mock_invocation_payload();
+        write(RequestPayload::InvokeComputeGraph(
+            InvokeComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph_name: cg.name.clone(),
+                invocation_payload: invocation_payload.clone(),
+            },
+        ))
+        .await?;
+        assert_eq!(usage()?.ingestion_bytes, invocation_payload.payload.size);
+
+        write(RequestPayload::DeleteInvocation(DeleteInvocationRequest {
+            namespace: TEST_NAMESPACE.to_string(),
+            compute_graph: cg.name.clone(),
+            invocation_id: invocation_payload.id.clone(),
+        }))
+        .await?;
+        assert_eq!(usage()?.ingestion_bytes, 0);
+        assert_eq!(usage()?.total_bytes(), cg.code.size);
+
+        // Purging the graph removes its usage
+        let graph_request = || DeleteComputeGraphRequest {
+            namespace: TEST_NAMESPACE.to_string(),
+            name: cg.name.clone(),
+        };
+        write(RequestPayload::DeleteComputeGraph(graph_request())).await?;
+        write(RequestPayload::PurgeComputeGraph(graph_request())).await?;
+        assert!(indexify_state
+            .reader()
+            .namespace_storage_usage(TEST_NAMESPACE)?
+            .is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_delete_ingested_data() -> Result<()> {
+        let state_store = test_state_store::tests::TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let write = |payload| {
+            indexify_state.write(StateMachineUpdateRequest {
+                payload,
+                state_changes_processed: vec![],
+            })
+        };
+        let invocation_id = state_store.with_simple_graph().await;
+        let cg = mock_graph_a();
+        let task = create_mock_task(&cg, "fn_a", &invocation_id, &invocation_id);
+        write(RequestPayload::SchedulerUpdate(SchedulerUpdateRequest {
+            task_requests: vec![requests::CreateTasksRequest {
+                namespace: cg.namespace.clone(),
+                compute_graph: cg.name.clone(),
+                invocation_id: invocation_id.clone(),
+                tasks: vec![task.clone()],
+                fan_out_cursor: None,
+            }],
+            allocations: vec![],
+            reduction_tasks: ReductionTasks::default(),
+            diagnostic_msgs: vec![],
+            sub_graph_invocations: vec![],
+            preemptions: vec![],
+        }))
+        .await?;
+        state_store
+            .finalize_task(&task, 1, TaskOutcome::Success, false)
+            .await?;
+        let delete_request = |mode| DeleteIngestedDataRequest {
+            namespace: TEST_NAMESPACE.to_string(),
+            compute_graph: cg.name.clone(),
+            invocation_id: invocation_id.clone(),
+            mode,
+            identity: "dpo".to_string(),
+            deadline: 1000,
+        };
+
+        // Running invocations can't have their data deleted
+        let err = write(RequestPayload::DeleteIngestedData(delete_request(
+            DataDeletionMode::Soft,
+        )))
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<StateStoreError>(),
+            Some(StateStoreError::Conflict(_))
+        ));
+
+        let reader = indexify_state.reader();
+        let mut ctx = reader.invocation_ctx(TEST_NAMESPACE, &cg.name, &invocation_id)?;
+        ctx.completed = true;
+        indexify_state.db.put_cf(
+            &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&indexify_state.db),
+            ctx.key(),
+            &JsonEncoder::encode(&ctx)?,
+        )?;
+        write(RequestPayload::DeleteIngestedData(delete_request(
+            DataDeletionMode::Soft,
+        )))
+        .await?;
+
+        // The payloads are tombstoned and their blobs handed to the gc
+        let payload = reader.invocation_payload(TEST_NAMESPACE, &cg.name, &invocation_id)?;
+        assert_eq!(payload.payload.tier, StorageTier::Expired);
+        let (outputs, _) = reader.list_outputs_by_compute_graph(
+            TEST_NAMESPACE,
+            &cg.name,
+            &invocation_id,
+            None,
+            None,
+        )?;
+        assert_eq!(outputs.len(), 1);
+        let OutputPayload::Fn(output) = &outputs[0].payload else {
+            panic!("unexpected output payload");
+        };
+        assert_eq!(output.tier, StorageTier::Expired);
+        let deletion = reader
+            .data_deletion(TEST_NAMESPACE, &cg.name, &invocation_id)?
+            .unwrap();
+        assert_eq!(deletion.requested_by, "dpo");
+        assert_eq!(deletion.invocations.len(), 1);
+        assert_eq!(deletion.outputs, 1);
+        assert_eq!(deletion.blobs.len(), 2);
+        assert_eq!(reader.pending_gc_urls(&deletion.blobs)?, 2);
+        assert_eq!(
+            reader.data_deletions(TEST_NAMESPACE, None, None, None)?.0,
+            vec![deletion]
+        );
+
+        // Hard deletions remove the records of the invocation too
+        write(RequestPayload::DeleteIngestedData(delete_request(
+            DataDeletionMode::Hard,
+        )))
+        .await?;
+        assert!(reader
+            .invocation_payload(TEST_NAMESPACE, &cg.name, &invocation_id)
+            .is_err());
+        assert!(reader
+            .invocation_ctx(TEST_NAMESPACE, &cg.name, &invocation_id)
+            .is_err());
+        assert!(reader
+            .list_outputs_by_compute_graph(TEST_NAMESPACE, &cg.name, &invocation_id, None, None)?
+            .0
+            .is_empty());
+        assert!(reader
+            .get_task_outputs(TEST_NAMESPACE, &task.id.to_string())?
+            .is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_task_usage() -> Result<()> {
+        let state_store = test_state_store::tests::TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let write = |payload| {
+            indexify_state.write(StateMachineUpdateRequest {
+                payload,
+                state_changes_processed: vec![],
+            })
+        };
+        let invocation_id = state_store.with_simple_graph().await;
+        let cg = mock_graph_a();
+        // Tasks of the start node read the invocation payload
+        let task = create_mock_task(&cg, "fn_a", &invocation_id, &invocation_id);
+        write(RequestPayload::SchedulerUpdate(SchedulerUpdateRequest {
+            task_requests: vec![requests::CreateTasksRequest {
+                namespace: cg.namespace.clone(),
+                compute_graph: cg.name.clone(),
+                invocation_id: invocation_id.clone(),
+                tasks: vec![task.clone()],
+                fan_out_cursor: None,
+            }],
+            allocations: vec![TaskPlacement {
+                task: task.clone(),
+                executor: ExecutorId::default(),
+            }],
+            reduction_tasks: ReductionTasks::default(),
+            diagnostic_msgs: vec![],
+            sub_graph_invocations: vec![],
+            preemptions: vec![],
+        }))
+        .await?;
+        write(RequestPayload::FinalizeTask(FinalizeTaskRequest {
+            namespace: task.namespace.clone(),
+            compute_graph: task.compute_graph_name.clone(),
+            compute_fn: task.compute_fn_name.clone(),
+            invocation_id: invocation_id.clone(),
+            task_id: task.id.clone(),
+            node_outputs: vec![mock_node_fn_output_fn_a(&invocation_id, &cg.name, None)],
+            task_outcome: TaskOutcome::Success,
+            executor_id: ExecutorId::default(),
+            diagnostics: None,
+            inline_payloads: vec![],
+            resource_usage: Some(ResourceUsage {
+                cpu_seconds: 1.5,
+                gpu_seconds: 0.0,
+                cold_start: Some(true),
+            }),
+        }))
+        .await?;
+
+        let reader = indexify_state.reader();
+        let usage = reader
+            .get_task(
+                &cg.namespace,
+                &cg.name,
+                &invocation_id,
+                "fn_a",
+                &task.id.to_string(),
+            )?
+            .unwrap()
+            .usage
+            .unwrap();
+        assert_eq!(usage.tasks, 1);
+        assert_eq!(usage.input_bytes, mock_invocation_payload().payload.size);
+        assert_eq!(usage.output_bytes, 12);
+        assert_eq!(usage.cpu_seconds, 1.5);
+        assert_eq!((usage.cold_starts, usage.warm_starts), (1, 0));
+        assert_eq!(
+            reader
+                .invocation_ctx(&cg.namespace, &cg.name, &invocation_id)?
+                .usage,
+            usage
+        );
+        let graphs = reader.namespace_task_usage(TEST_NAMESPACE)?;
+        assert_eq!(graphs.len(), 1);
+        assert_eq!(graphs[0].usage, usage);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_finalize_tasks() -> Result<()> {
+        let state_store = test_state_store::tests::TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let write = |payload| {
+            indexify_state.write(StateMachineUpdateRequest {
+                payload,
+                state_changes_processed: vec![],
+            })
+        };
+        let invocation_id = state_store.with_simple_graph().await;
+        let cg = mock_graph_a();
+        let tasks = vec![
+            create_mock_task(&cg, "fn_a", &invocation_id, &invocation_id),
+            create_mock_task(&cg, "fn_a", &invocation_id, &invocation_id),
+        ];
+        write(RequestPayload::SchedulerUpdate(SchedulerUpdateRequest {
+            task_requests: vec![requests::CreateTasksRequest {
+                namespace: cg.namespace.clone(),
+                compute_graph: cg.name.clone(),
+                invocation_id: invocation_id.clone(),
+                tasks: tasks.clone(),
+                fan_out_cursor: None,
+            }],
+            allocations: tasks
+                .iter()
+                .map(|task| TaskPlacement {
+                    task: task.clone(),
+                    executor: ExecutorId::default(),
+                })
+                .collect(),
+            reduction_tasks: ReductionTasks::default(),
+            diagnostic_msgs: vec![],
+            sub_graph_invocations: vec![],
+            preemptions: vec![],
+        }))
+        .await?;
+        let state_changes_before = indexify_state
+            .reader()
+            .get_unprocessed_state_changes()?
+            .len();
+
+        write(RequestPayload::FinalizeTasks(FinalizeTasksRequest {
+            tasks: tasks
+                .iter()
+                .map(|task| FinalizeTaskRequest {
+                    namespace: task.namespace.clone(),
+                    compute_graph: task.compute_graph_name.clone(),
+                    compute_fn: task.compute_fn_name.clone(),
+                    invocation_id: invocation_id.clone(),
+                    task_id: task.id.clone(),
+                    node_outputs: vec![],
+                    task_outcome: TaskOutcome::Success,
+                    executor_id: ExecutorId::default(),
+                    diagnostics: None,
+                    inline_payloads: vec![],
+                    resource_usage: None,
+                })
+                .collect(),
+        }))
+        .await?;
+
+        let reader = indexify_state.reader();
+        for task in &tasks {
+            let stored_task = reader
+                .get_task(
+                    &cg.namespace,
+                    &cg.name,
+                    &invocation_id,
+                    "fn_a",
+                    &task.id.to_string(),
+                )?
+                .unwrap();
+            assert_eq!(stored_task.outcome, TaskOutcome::Success);
+        }
+        assert!(reader
+            .get_tasks_by_executor(&ExecutorId::default(), 10)?
+            .is_empty());
+
+        // A single state change carries the finished tasks
+        let state_changes = reader.get_unprocessed_state_changes()?;
+        assert_eq!(state_changes.len(), state_changes_before + 1);
+        let ChangeType::TasksFinished(event) = &state_changes.last().unwrap().change_type else {
+            panic!("expected a tasks finished state change");
+        };
+        let finished: Vec<_> = event
+            .tasks
+            .iter()
+            .map(|task| task.task_id.clone())
+            .collect();
+        assert_eq!(
+            finished,
+            tasks.iter().map(|task| task.id.clone()).collect::<Vec<_>>()
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_output_limits() -> Result<()> {
+        let state_store = test_state_store::tests::TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let write = |payload| {
+            indexify_state.write(StateMachineUpdateRequest {
+                payload,
+                state_changes_processed: vec![],
+            })
+        };
+        let mut cg = mock_graph_a();
+        if let Some(Node::Compute(compute_fn)) = cg.nodes.get_mut("fn_a") {
+            compute_fn.output_limits = Some(OutputLimits {
+                max_outputs: Some(2),
+                max_output_bytes: None,
+            });
+        }
+        write(RequestPayload::CreateComputeGraph(
+            CreateComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph: cg.clone(),
+                expected_revision: None,
+                force: false,
+            },
+        ))
+        .await?;
+        let invocation_payload = mock_invocation_payload();
+        let invocation_id = invocation_payload.id.clone();
+        write(RequestPayload::InvokeComputeGraph(
+            InvokeComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph_name: cg.name.clone(),
+                invocation_payload,
+            },
+        ))
+        .await?;
+        let task = create_mock_task(&cg, "fn_a", &invocation_id, &invocation_id);
+        write(RequestPayload::SchedulerUpdate(SchedulerUpdateRequest {
+            task_requests: vec![requests::CreateTasksRequest {
+                namespace: cg.namespace.clone(),
+                compute_graph: cg.name.clone(),
+                invocation_id: invocation_id.clone(),
+                tasks: vec![task.clone()],
+                fan_out_cursor: None,
+            }],
+            allocations: vec![TaskPlacement {
+                task: task.clone(),
+                executor: ExecutorId::default(),
+            }],
+            reduction_tasks: ReductionTasks::default(),
+            diagnostic_msgs: vec![],
+            sub_graph_invocations: vec![],
+            preemptions: vec![],
+        }))
+        .await?;
+
+        // The streamed output is within the limits
+        write(RequestPayload::StreamTaskOutputs(
+            StreamTaskOutputsRequest {
+                namespace: task.namespace.clone(),
+                compute_graph: task.compute_graph_name.clone(),
+                compute_fn: task.compute_fn_name.clone(),
+                invocation_id: invocation_id.clone(),
+                task_id: task.id.clone(),
+                node_outputs: vec![mock_node_fn_output_fn_a(&invocation_id, &cg.name, None)],
+            },
+        ))
+        .await?;
+        // Along with the final outputs the task exceeds them
+        write(RequestPayload::FinalizeTask(FinalizeTaskRequest {
+            namespace: task.namespace.clone(),
+            compute_graph: task.compute_graph_name.clone(),
+            compute_fn: task.compute_fn_name.clone(),
+            invocation_id: invocation_id.clone(),
+            task_id: task.id.clone(),
+            node_outputs: vec![
+                mock_node_fn_output_fn_a(&invocation_id, &cg.name, None),
+                mock_node_fn_output_fn_a(&invocation_id, &cg.name, None),
+            ],
+            task_outcome: TaskOutcome::Success,
+            executor_id: ExecutorId::default(),
+            diagnostics: None,
+            inline_payloads: vec![],
+            resource_usage: None,
+        }))
+        .await?;
+
+        let reader = indexify_state.reader();
+        let task = reader
+            .get_task(
+                &cg.namespace,
+                &cg.name,
+                &invocation_id,
+                "fn_a",
+                &task.id.to_string(),
+            )?
+            .unwrap();
+        assert_eq!(task.outcome, TaskOutcome::Failure);
+        assert_eq!(
+            task.failure,
+            Some(TaskFailure::OutputLimitExceeded {
+                limit: OutputLimit::Outputs,
+                max: 2,
+                actual: 3,
+            })
+        );
+        // Only the streamed output was saved
+        assert_eq!(
+            reader
+                .get_task_outputs(&cg.namespace, &task.id.to_string())?
+                .len(),
+            1
+        );
+        let analytics = reader
+            .invocation_ctx(&cg.namespace, &cg.name, &invocation_id)?
+            .fn_task_analytics;
+        assert_eq!(analytics["fn_a"].failed_tasks, 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_task_checkpoints() -> Result<()> {
+        let state_store = test_state_store::tests::TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let write = |payload| {
+            indexify_state.write(StateMachineUpdateRequest {
+                payload,
+                state_changes_processed: vec![],
+            })
+        };
+        let cg = mock_graph_a();
+        write(RequestPayload::CreateComputeGraph(
+            CreateComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph: cg.clone(),
+                expected_revision: None,
+                force: false,
+            },
+        ))
+        .await?;
+        let invocation_payload = mock_invocation_payload();
+        let invocation_id = invocation_payload.id.clone();
+        write(RequestPayload::InvokeComputeGraph(
+            InvokeComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph_name: cg.name.clone(),
+                invocation_payload,
+            },
+        ))
+        .await?;
+        let task = create_mock_task(&cg, "fn_a", &invocation_id, &invocation_id);
+        write(RequestPayload::SchedulerUpdate(SchedulerUpdateRequest {
+            task_requests: vec![requests::CreateTasksRequest {
+                namespace: cg.namespace.clone(),
+                compute_graph: cg.name.clone(),
+                invocation_id: invocation_id.clone(),
+                tasks: vec![task.clone()],
+                fan_out_cursor: None,
+            }],
+            allocations: vec![TaskPlacement {
+                task: task.clone(),
+                executor: ExecutorId::default(),
+            }],
+            reduction_tasks: ReductionTasks::default(),
+            diagnostic_msgs: vec![],
+            sub_graph_invocations: vec![],
+            preemptions: vec![],
+        }))
+        .await?;
+
+        let checkpoint = |path: &str| {
+            RequestPayload::CheckpointTask(CheckpointTaskRequest {
+                namespace: task.namespace.clone(),
+                compute_graph: task.compute_graph_name.clone(),
+                compute_fn: task.compute_fn_name.clone(),
+                invocation_id: invocation_id.clone(),
+                task_id: task.id.clone(),
+                checkpoint: TaskCheckpoint {
+                    payload: DataPayload {
+                        path: path.to_string(),
+                        size: 1,
+                        sha256_hash: "hash".to_string(),
+                        tier: Default::default(),
+                        mime_type: None,
+                        content_encoding: None,
+                        metadata: Default::default(),
+                        replicas: Vec::new(),
+                        compression: None,
+                    },
+                    created_at: 0,
+                },
+            })
+        };
+        let stored_task = || {
+            indexify_state.reader().get_task(
+                &cg.namespace,
+                &cg.name,
+                &invocation_id,
+                "fn_a",
+                &task.id.to_string(),
+            )
+        };
+        write(checkpoint("file:///checkpoint_1")).await?;
+        write(checkpoint("file:///checkpoint_2")).await?;
+        // The latest checkpoint replaces the previous one
+        let checkpoint_path = stored_task()?
+            .unwrap()
+            .checkpoint
+            .map(|checkpoint| checkpoint.payload.path);
+        assert_eq!(checkpoint_path.as_deref(), Some("file:///checkpoint_2"));
+        assert_eq!(
+            indexify_state.reader().get_gc_urls(None)?,
+            vec!["file:///checkpoint_1".to_string()]
+        );
+
+        // The checkpoint is kept when the task is requeued
+        write(RequestPayload::RequeueExecutorTasks(
+            RequeueExecutorTasksRequest {
+                executor_id: ExecutorId::default(),
+                batch_size: 1,
+            },
+        ))
+        .await?;
+        assert!(stored_task()?.unwrap().checkpoint.is_some());
+
+        // and dropped once the task finishes
+        write(RequestPayload::FinalizeTask(FinalizeTaskRequest {
+            namespace: task.namespace.clone(),
+            compute_graph: task.compute_graph_name.clone(),
+            compute_fn: task.compute_fn_name.clone(),
+            invocation_id: invocation_id.clone(),
+            task_id: task.id.clone(),
+            node_outputs: vec![],
+            task_outcome: TaskOutcome::Success,
+            executor_id: ExecutorId::default(),
+            diagnostics: None,
+            inline_payloads: vec![],
+            resource_usage: None,
+        }))
+        .await?;
+        assert!(stored_task()?.unwrap().checkpoint.is_none());
+        assert_eq!(indexify_state.reader().get_gc_urls(None)?.len(), 2);
+        assert!(write(checkpoint("file:///checkpoint_3")).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_executor_tasks() -> Result<()> {
+        let state_store = test_state_store::tests::TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let write = |payload| {
+            indexify_state.write(StateMachineUpdateRequest {
+                payload,
+                state_changes_processed: vec![],
+            })
+        };
+        let cg = mock_graph_a();
+        write(RequestPayload::CreateComputeGraph(
+            CreateComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph: cg.clone(),
+                expected_revision: None,
+                force: false,
+            },
+        ))
+        .await?;
+        let invocation_payload = mock_invocation_payload();
+        let invocation_id = invocation_payload.id.clone();
+        write(RequestPayload::InvokeComputeGraph(
+            InvokeComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph_name: cg.name.clone(),
+                invocation_payload,
+            },
+        ))
+        .await?;
+        let executor_id = ExecutorId::new("executor_1".to_string());
+        let running = create_mock_task(&cg, "fn_a", &invocation_id, &invocation_id);
+        let forgotten = create_mock_task(&cg, "fn_a", &invocation_id, &invocation_id);
+        write(RequestPayload::SchedulerUpdate(SchedulerUpdateRequest {
+            task_requests: vec![requests::CreateTasksRequest {
+                namespace: cg.namespace.clone(),
+                compute_graph: cg.name.clone(),
+                invocation_id: invocation_id.clone(),
+                tasks: vec![running.clone(), forgotten.clone()],
+                fan_out_cursor: None,
+            }],
+            allocations: [&running, &forgotten]
+                .into_iter()
+                .map(|task| TaskPlacement {
+                    task: task.clone(),
+                    executor: executor_id.clone(),
+                })
+                .collect(),
+            reduction_tasks: ReductionTasks::default(),
+            diagnostic_msgs: vec![],
+            sub_graph_invocations: vec![],
+            preemptions: vec![],
+        }))
+        .await?;
+        write(RequestPayload::RegisterPendingUploads(
+            RegisterPendingUploadsRequest {
+                uploads: vec![PendingUpload {
+                    url: "file:///partial".to_string(),
+                    namespace: running.namespace.clone(),
+                    compute_graph: running.compute_graph_name.clone(),
+                    compute_fn: running.compute_fn_name.clone(),
+                    invocation_id: invocation_id.clone(),
+                    task_id: running.id.clone(),
+                    created_at: 1,
+                }],
+            },
+        ))
+        .await?;
+
+        let reported = |task_id: TaskId, partial_outputs: Vec<String>| ReportedTask {
+            namespace: cg.namespace.clone(),
+            compute_graph: cg.name.clone(),
+            compute_fn: "fn_a".to_string(),
+            invocation_id: invocation_id.clone(),
+            task_id,
+            partial_outputs,
+        };
+        write(RequestPayload::ReconcileExecutorTasks(
+            ReconcileExecutorTasksRequest {
+                executor_id: executor_id.clone(),
+                reported_tasks: vec![
+                    reported(running.id.clone(), vec!["file:///partial".to_string()]),
+                    reported(TaskId::new("unknown".to_string()), vec![]),
+                ],
+                restarted_at: 10,
+            },
+        ))
+        .await?;
+
+        let reader = indexify_state.reader();
+        let (reconciliations, _) =
+            reader.executor_reconciliations(Some(&executor_id), None, None)?;
+        assert_eq!(reconciliations.len(), 1);
+        let outcomes: Vec<_> = reconciliations[0]
+            .tasks
+            .iter()
+            .map(|task| (task.task_id.clone(), task.reported, task.outcome))
+            .collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                (running.id.clone(), true, ReconciliationOutcome::Retried),
+                (TaskId::new("unknown".to_string()), true, ReconciliationOutcome::NotFound),
+                (forgotten.id.clone(), false, ReconciliationOutcome::Retried),
+            ]
+        );
+        assert_eq!(
+            reconciliations[0].tasks[0].discarded_outputs,
+            vec!["file:///partial".to_string()]
+        );
+        assert_eq!(reader.get_gc_urls(None)?, vec!["file:///partial".to_string()]);
+        // Both tasks are scheduled again
+        assert!(reader.allocated_task_counts()?.get(&executor_id).is_none());
+        assert!(reader.is_task_unallocated(&running.key())?);
+        assert!(reader.is_task_unallocated(&forgotten.key())?);
+
+        // The lease on the task is gone once it was retried
+        write(RequestPayload::ReconcileExecutorTasks(
+            ReconcileExecutorTasksRequest {
+                executor_id: executor_id.clone(),
+                reported_tasks: vec![reported(running.id.clone(), vec![])],
+                restarted_at: 20,
+            },
+        ))
+        .await?;
+        let (reconciliations, _) =
+            reader.executor_reconciliations(Some(&executor_id), None, None)?;
+        assert_eq!(reconciliations.len(), 2);
+        assert_eq!(reconciliations[1].tasks[0].outcome, ReconciliationOutcome::LeaseLost);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_windows() -> Result<()> {
+        let state_store = test_state_store::tests::TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let write = |payload| {
+            indexify_state.write(StateMachineUpdateRequest {
+                payload,
+                state_changes_processed: vec![],
+            })
+        };
+        let mut cg = mock_graph_a();
+        cg.nodes.insert(
+            "fn_b".to_string(),
+            Node::Window(Window {
+                name: "fn_b".to_string(),
+                description: "".to_string(),
+                max_items: Some(2),
+                max_age_secs: None,
+                event_time_secs: None,
+                allowed_lateness_secs: 0,
+                late_output: None,
+            }),
+        );
+        write(RequestPayload::CreateComputeGraph(
+            CreateComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph: cg.clone(),
+                expected_revision: None,
+                force: false,
+            },
+        ))
+        .await?;
+
+        let create_task = |task: &Task| {
+            write(RequestPayload::SchedulerUpdate(SchedulerUpdateRequest {
+                task_requests: vec![requests::CreateTasksRequest {
+                    namespace: task.namespace.clone(),
+                    compute_graph: task.compute_graph_name.clone(),
+                    invocation_id: task.invocation_id.clone(),
+                    tasks: vec![task.clone()],
+                    fan_out_cursor: None,
+                }],
+                allocations: vec![],
+                reduction_tasks: ReductionTasks::default(),
+                diagnostic_msgs: vec![],
+                sub_graph_invocations: vec![],
+                preemptions: vec![],
+            }))
+        };
+
+        // Each invocation runs fn_a, whose output waits in the window fn_b
+        let mut window_tasks = vec![];
+        for _ in 0..3 {
+            let invocation_payload = mock_invocation_payload();
+            let invocation_id = invocation_payload.id.clone();
+            write(RequestPayload::InvokeComputeGraph(
+                InvokeComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph_name: cg.name.clone(),
+                    invocation_payload,
+                },
+            ))
+            .await?;
+            let task = create_mock_task(&cg, "fn_a", &invocation_id, &invocation_id);
+            create_task(&task).await?;
+            let output = mock_node_fn_output_fn_a(&invocation_id, &cg.name, None);
+            write(RequestPayload::FinalizeTask(FinalizeTaskRequest {
+                namespace: task.namespace.clone(),
+                compute_graph: task.compute_graph_name.clone(),
+                compute_fn: task.compute_fn_name.clone(),
+                invocation_id: invocation_id.clone(),
+                task_id: task.id.clone(),
+                node_outputs: vec![output.clone()],
+                task_outcome: TaskOutcome::Success,
+                executor_id: ExecutorId::default(),
+                diagnostics: None,
+                inline_payloads: vec![],
+                resource_usage: None,
+            }))
+            .await?;
+            let window_task =
+                create_mock_task(&cg, "fn_b", &output.input_key(&invocation_id), &invocation_id);
+            create_task(&window_task).await?;
+            window_tasks.push(window_task);
+        }
+
+        let reader = indexify_state.reader();
+        let window_task = |idx: usize| -> Result<(TaskOutcome, usize)> {
+            let task: &Task = &window_tasks[idx];
+            let outcome = reader
+                .get_task(
+                    &task.namespace,
+                    &task.compute_graph_name,
+                    &task.invocation_id,
+                    "fn_b",
+                    &task.id.to_string(),
+                )?
+                .unwrap()
+                .outcome;
+            let outputs = reader.get_task_outputs(&task.namespace, &task.id.to_string())?;
+            Ok((outcome, outputs.len()))
+        };
+        // The second input filled the window, its invocation got the batch
+        assert_eq!(window_task(0)?, (TaskOutcome::Success, 0));
+        assert_eq!(window_task(1)?, (TaskOutcome::Success, 2));
+        assert_eq!(window_task(2)?, (TaskOutcome::Unknown, 0));
+        assert_eq!(
+            reader.window_items(TEST_NAMESPACE, &cg.name, "fn_b")?.len(),
+            1
+        );
+
+        write(RequestPayload::CloseWindow(requests::CloseWindowRequest {
+            namespace: TEST_NAMESPACE.to_string(),
+            compute_graph: cg.name.clone(),
+            window: "fn_b".to_string(),
+        }))
+        .await?;
+        assert_eq!(window_task(2)?, (TaskOutcome::Success, 1));
+        assert!(reader
+            .window_items(TEST_NAMESPACE, &cg.name, "fn_b")?
+            .is_empty());
+
+        // Only windows can be closed
+        let err = write(RequestPayload::CloseWindow(requests::CloseWindowRequest {
+            namespace: TEST_NAMESPACE.to_string(),
+            compute_graph: cg.name.clone(),
+            window: "fn_c".to_string(),
+        }))
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<StateStoreError>(),
+            Some(StateStoreError::NotFound(_))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_event_time_windows() -> Result<()> {
+        let state_store = test_state_store::tests::TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let write = |payload| {
+            indexify_state.write(StateMachineUpdateRequest {
+                payload,
+                state_changes_processed: vec![],
+            })
+        };
+        let mut cg = mock_graph_a();
+        cg.nodes.insert(
+            "fn_b".to_string(),
+            Node::Window(Window {
+                name: "fn_b".to_string(),
+                description: "".to_string(),
+                max_items: None,
+                max_age_secs: None,
+                event_time_secs: Some(60),
+                allowed_lateness_secs: 5,
+                late_output: Some("fn_c".to_string()),
+            }),
+        );
+        write(RequestPayload::CreateComputeGraph(
+            CreateComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph: cg.clone(),
+                expected_revision: None,
+                force: false,
+            },
+        ))
+        .await?;
+
+        let create_task = |task: &Task| {
+            write(RequestPayload::SchedulerUpdate(SchedulerUpdateRequest {
+                task_requests: vec![requests::CreateTasksRequest {
+                    namespace: task.namespace.clone(),
+                    compute_graph: task.compute_graph_name.clone(),
+                    invocation_id: task.invocation_id.clone(),
+                    tasks: vec![task.clone()],
+                    fan_out_cursor: None,
+                }],
+                allocations: vec![],
+                reduction_tasks: ReductionTasks::default(),
+                diagnostic_msgs: vec![],
+                sub_graph_invocations: vec![],
+                preemptions: vec![],
+            }))
+        };
+        // Runs fn_a of an invocation with the event time, its output waits in
+        // the window fn_b
+        let buffer = |idx: usize, event_time: u64| {
+            let cg = cg.clone();
+            let write = &write;
+            let create_task = &create_task;
+            async move {
+                let invocation_payload = InvocationPayload {
+                    id: format!("invocation_{}", idx),
+                    event_time: Some(event_time),
+                    ..mock_invocation_payload()
+                };
+                let invocation_id = invocation_payload.id.clone();
+                write(RequestPayload::InvokeComputeGraph(
+                    InvokeComputeGraphRequest {
+                        namespace: TEST_NAMESPACE.to_string(),
+                        compute_graph_name: cg.name.clone(),
+                        invocation_payload,
+                    },
+                ))
+                .await?;
+                let task = create_mock_task(&cg, "fn_a", &invocation_id, &invocation_id);
+                create_task(&task).await?;
+                let output = mock_node_fn_output_fn_a(&invocation_id, &cg.name, None);
+                write(RequestPayload::FinalizeTask(FinalizeTaskRequest {
+                    namespace: task.namespace.clone(),
+                    compute_graph: task.compute_graph_name.clone(),
+                    compute_fn: task.compute_fn_name.clone(),
+                    invocation_id: invocation_id.clone(),
+                    task_id: task.id.clone(),
+                    node_outputs: vec![output.clone()],
+                    task_outcome: TaskOutcome::Success,
+                    executor_id: ExecutorId::default(),
+                    diagnostics: None,
+                    inline_payloads: vec![],
+                    resource_usage: None,
+                }))
+                .await?;
+                let window_task = create_mock_task(
+                    &cg,
+                    "fn_b",
+                    &output.input_key(&invocation_id),
+                    &invocation_id,
+                );
+                create_task(&window_task).await?;
+                anyhow::Ok(window_task)
+            }
+        };
+        let reader = indexify_state.reader();
+        let window_task = |task: &Task| -> Result<(TaskOutcome, Vec<NodeOutput>)> {
+            let outcome = reader
+                .get_task(
+                    &task.namespace,
+                    &task.compute_graph_name,
+                    &task.invocation_id,
+                    "fn_b",
+                    &task.id.to_string(),
+                )?
+                .unwrap()
+                .outcome;
+            let outputs = reader.get_task_outputs(&task.namespace, &task.id.to_string())?;
+            Ok((outcome, outputs))
+        };
+
+        let first = buffer(0, 10_000).await?;
+        let second = buffer(1, 30_000).await?;
+        assert_eq!(window_task(&first)?.0, TaskOutcome::Unknown);
+        // The watermark trails the event time by the allowed lateness, it
+        // passes the end of the first window once it saw 65s
+        let third = buffer(2, 64_000).await?;
+        assert_eq!(window_task(&second)?.0, TaskOutcome::Unknown);
+        let fourth = buffer(3, 65_000).await?;
+        // The newest input of the window gets the batch
+        let batches = |tasks: [&Task; 2]| -> Result<Vec<(TaskOutcome, usize)>> {
+            let mut batches = vec![];
+            for task in tasks {
+                let (outcome, outputs) = window_task(task)?;
+                batches.push((outcome, outputs.len()));
+            }
+            batches.sort_by_key(|(_, outputs)| *outputs);
+            Ok(batches)
+        };
+        assert_eq!(
+            batches([&first, &second])?,
+            vec![(TaskOutcome::Success, 0), (TaskOutcome::Success, 2)]
+        );
+        assert_eq!(window_task(&third)?.0, TaskOutcome::Unknown);
+
+        // Inputs of the closed window are routed to the late output
+        let late = buffer(4, 20_000).await?;
+        let (outcome, outputs) = window_task(&late)?;
+        assert_eq!(outcome, TaskOutcome::Success);
+        assert_eq!(
+            outputs[0].payload,
+            OutputPayload::Router(RouterOutput {
+                edges: vec!["fn_c".to_string()],
+            })
+        );
+
+        // Closing the window flushes the open event-time windows
+        write(RequestPayload::CloseWindow(requests::CloseWindowRequest {
+            namespace: TEST_NAMESPACE.to_string(),
+            compute_graph: cg.name.clone(),
+            window: "fn_b".to_string(),
+        }))
+        .await?;
+        assert_eq!(
+            batches([&third, &fourth])?,
+            vec![(TaskOutcome::Success, 0), (TaskOutcome::Success, 2)]
+        );
+        assert!(reader
+            .window_items(TEST_NAMESPACE, &cg.name, "fn_b")?
+            .is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invocation_events() -> Result<()> {
+        let state_store = test_state_store::tests::TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let invocation_id = state_store.with_simple_graph().await;
+        let cg = mock_graph_a();
+        let task = create_mock_task(&cg, "fn_a", &invocation_id, &invocation_id);
+        let executor_id = ExecutorId::new("executor_1".to_string());
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::SchedulerUpdate(SchedulerUpdateRequest {
+                    task_requests: vec![requests::CreateTasksRequest {
+                        namespace: cg.namespace.clone(),
+                        compute_graph: cg.name.clone(),
+                        invocation_id: invocation_id.clone(),
+                        tasks: vec![task.clone()],
+                        fan_out_cursor: None,
+                    }],
+                    allocations: vec![TaskPlacement {
+                        task: task.clone(),
+                        executor: executor_id.clone(),
+                    }],
+                    reduction_tasks: ReductionTasks::default(),
+                    diagnostic_msgs: vec![],
+                    sub_graph_invocations: vec![],
+                    preemptions: vec![],
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::FinalizeTask(FinalizeTaskRequest {
+                    namespace: task.namespace.clone(),
+                    compute_graph: task.compute_graph_name.clone(),
+                    compute_fn: task.compute_fn_name.clone(),
+                    invocation_id: invocation_id.clone(),
+                    task_id: task.id.clone(),
+                    node_outputs: vec![],
+                    task_outcome: TaskOutcome::Success,
+                    executor_id: executor_id.clone(),
+                    diagnostics: None,
+                    inline_payloads: vec![],
+                    resource_usage: None,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+
+        let (events, _) = indexify_state.reader().list_invocation_events(
+            &cg.namespace,
+            &cg.name,
+            &invocation_id,
+            None,
+            None,
+        )?;
+        let kinds: Vec<InvocationEventKind> = events.into_iter().map(|e| e.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                InvocationEventKind::Invoked,
+                InvocationEventKind::TaskCreated {
+                    task_id: task.id.to_string(),
+                    compute_fn: "fn_a".to_string(),
+                },
+                InvocationEventKind::TaskAllocated {
+                    task_id: task.id.to_string(),
+                    compute_fn: "fn_a".to_string(),
+                    executor_id: "executor_1".to_string(),
+                },
+                InvocationEventKind::TaskFinished {
+                    task_id: task.id.to_string(),
+                    compute_fn: "fn_a".to_string(),
+                    outcome: TaskOutcome::Success,
+                },
+            ]
+        );
+
+        // Deleting the invocation deletes its timeline
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::DeleteInvocation(DeleteInvocationRequest {
+                    namespace: cg.namespace.clone(),
+                    compute_graph: cg.name.clone(),
+                    invocation_id: invocation_id.clone(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let (events, _) = indexify_state.reader().list_invocation_events(
+            &cg.namespace,
+            &cg.name,
+            &invocation_id,
+            None,
+            None,
+        )?;
+        assert!(events.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pending_uploads() -> Result<()> {
+        let state_store = test_state_store::tests::TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let invocation_id = state_store.with_simple_graph().await;
+        let cg = mock_graph_a();
+        let task = create_mock_task(&cg, "fn_a", &invocation_id, &invocation_id);
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::SchedulerUpdate(SchedulerUpdateRequest {
+                    task_requests: vec![requests::CreateTasksRequest {
+                        namespace: cg.namespace.clone(),
+                        compute_graph: cg.name.clone(),
+                        invocation_id: invocation_id.clone(),
+                        tasks: vec![task.clone()],
+                        fan_out_cursor: None,
+                    }],
+                    allocations: vec![TaskPlacement {
+                        task: task.clone(),
+                        executor: ExecutorId::new("executor_1".to_string()),
+                    }],
+                    reduction_tasks: ReductionTasks::default(),
+                    diagnostic_msgs: vec![],
+                    sub_graph_invocations: vec![],
+                    preemptions: vec![],
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let pending_upload = |url: &str| PendingUpload {
+            url: url.to_string(),
+            namespace: task.namespace.clone(),
+            compute_graph: task.compute_graph_name.clone(),
+            compute_fn: task.compute_fn_name.clone(),
+            invocation_id: invocation_id.clone(),
+            task_id: task.id.clone(),
+            created_at: 1,
+        };
+        let register = |url: &str| StateMachineUpdateRequest {
+            payload: RequestPayload::RegisterPendingUploads(RegisterPendingUploadsRequest {
+                uploads: vec![pending_upload(url)],
+            }),
+            state_changes_processed: vec![],
+        };
+        indexify_state.write(register("file:///stdout")).await?;
+        indexify_state.write(register("file:///orphan")).await?;
+        assert_eq!(
+            indexify_state.reader().stale_pending_uploads(2, 10)?,
+            vec![
+                pending_upload("file:///orphan"),
+                pending_upload("file:///stdout")
+            ]
+        );
+
+        // The outcome commits the uploads it references
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::FinalizeTask(FinalizeTaskRequest {
+                    namespace: task.namespace.clone(),
+                    compute_graph: task.compute_graph_name.clone(),
+                    compute_fn: task.compute_fn_name.clone(),
+                    invocation_id: invocation_id.clone(),
+                    task_id: task.id.clone(),
+                    node_outputs: vec![],
+                    task_outcome: TaskOutcome::Success,
+                    executor_id: ExecutorId::new("executor_1".to_string()),
+                    diagnostics: Some(TaskDiagnostics {
+                        stdout: Some(DataPayload {
+                            path: "file:///stdout".to_string(),
+                            size: 1,
+                            sha256_hash: "".to_string(),
+                            tier: Default::default(),
+                            mime_type: None,
+                            content_encoding: None,
+                            metadata: Default::default(),
+                            replicas: Vec::new(),
+                            compression: None,
+                        }),
+                        exception: None,
+                        stderr: None,
+                        structured_logs: None,
+                    }),
+                    inline_payloads: vec![],
+                    resource_usage: None,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        assert_eq!(
+            indexify_state.reader().stale_pending_uploads(2, 10)?,
+            vec![pending_upload("file:///orphan")]
+        );
+
+        // Uploads can't be registered for finished tasks
+        let err = indexify_state
+            .write(register("file:///late"))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<StateStoreError>(),
+            Some(StateStoreError::Conflict(_))
+        ));
+
+        // Uncommitted uploads are handed to the garbage collector
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::SweepPendingUploads(SweepPendingUploadsRequest {
+                    urls: vec!["file:///orphan".to_string()],
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let reader = indexify_state.reader();
+        assert!(reader.stale_pending_uploads(u64::MAX, 10)?.is_empty());
+        assert_eq!(
+            reader.get_gc_urls(None)?,
+            vec!["file:///orphan".to_string()]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invocation_labels() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let cg = mock_graph_a();
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: cg.clone(),
+                    expected_revision: None,
+                    force: false,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let mut invocation_payload = mock_invocation_payload();
+        invocation_payload.labels =
+            HashMap::from([("customer_id".to_string(), serde_json::json!("c1"))]);
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph_name: "graph_A".to_string(),
+                    invocation_payload: invocation_payload.clone(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+
+        let reader = indexify_state.reader();
+        let ctx = reader.invocation_ctx(TEST_NAMESPACE, "graph_A", &invocation_payload.id)?;
+        assert_eq!(ctx.labels, invocation_payload.labels);
+
+        let filter = LabelsFilter::from_str("customer_id=c1")?;
+        let (invocations, _) =
+            reader.list_invocations(TEST_NAMESPACE, "graph_A", None, None, &filter)?;
+        assert_eq!(invocations.len(), 1);
+        let filter = LabelsFilter::from_str("customer_id=c2")?;
+        let (invocations, _) =
+            reader.list_invocations(TEST_NAMESPACE, "graph_A", None, None, &filter)?;
+        assert!(invocations.is_empty());
+
+        // Tasks carry the labels of their invocation
+        let task = create_mock_task(&cg, "fn_a", &invocation_payload.id, &invocation_payload.id);
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::SchedulerUpdate(SchedulerUpdateRequest {
+                    task_requests: vec![requests::CreateTasksRequest {
+                        namespace: TEST_NAMESPACE.to_string(),
+                        compute_graph: "graph_A".to_string(),
+                        invocation_id: invocation_payload.id.clone(),
+                        tasks: vec![task.clone()],
+                        fan_out_cursor: None,
+                    }],
+                    allocations: vec![],
+                    reduction_tasks: ReductionTasks::default(),
+                    diagnostic_msgs: vec![],
+                    sub_graph_invocations: vec![],
+                    preemptions: vec![],
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let task = reader
+            .get_task(
+                TEST_NAMESPACE,
+                "graph_A",
+                &invocation_payload.id,
+                "fn_a",
+                &task.id.to_string(),
+            )?
+            .unwrap();
+        assert_eq!(task.invocation_labels, invocation_payload.labels);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_graph_parameters() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let mut cg = mock_graph_a();
+        cg.parameters = HashMap::from([
+            ("model".to_string(), Default::default()),
+            (
+                "chunk_size".to_string(),
+                data_model::parameters::GraphParameter {
+                    description: "".to_string(),
+                    default: Some(serde_json::json!(512)),
+                },
+            ),
+        ]);
+        cg.parameter_values = HashMap::from([("model".to_string(), serde_json::json!("minilm"))]);
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: cg.clone(),
+                    expected_revision: None,
+                    force: false,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let invoke = |invocation_payload: InvocationPayload| StateMachineUpdateRequest {
+            payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph_name: "graph_A".to_string(),
+                invocation_payload,
+            }),
+            state_changes_processed: vec![],
+        };
+
+        // Unknown parameters are rejected
+        let mut invocation_payload = mock_invocation_payload();
+        invocation_payload.parameters =
+            HashMap::from([("other".to_string(), serde_json::json!(1))]);
+        assert!(indexify_state
+            .write(invoke(invocation_payload))
+            .await
+            .is_err());
+
+        let mut invocation_payload = mock_invocation_payload();
+        invocation_payload.parameters =
+            HashMap::from([("chunk_size".to_string(), serde_json::json!(1024))]);
+        indexify_state
+            .write(invoke(invocation_payload.clone()))
+            .await?;
+        let reader = indexify_state.reader();
+        let ctx = reader.invocation_ctx(TEST_NAMESPACE, "graph_A", &invocation_payload.id)?;
+        let expected = HashMap::from([
+            ("model".to_string(), serde_json::json!("minilm")),
+            ("chunk_size".to_string(), serde_json::json!(1024)),
+        ]);
+        assert_eq!(ctx.parameters, expected);
+
+        // Tasks carry the resolved parameters of their invocation
+        let task = create_mock_task(&cg, "fn_a", &invocation_payload.id, &invocation_payload.id);
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::SchedulerUpdate(SchedulerUpdateRequest {
+                    task_requests: vec![requests::CreateTasksRequest {
+                        namespace: TEST_NAMESPACE.to_string(),
+                        compute_graph: "graph_A".to_string(),
+                        invocation_id: invocation_payload.id.clone(),
+                        tasks: vec![task.clone()],
+                        fan_out_cursor: None,
+                    }],
+                    allocations: vec![],
+                    reduction_tasks: ReductionTasks::default(),
+                    diagnostic_msgs: vec![],
+                    sub_graph_invocations: vec![],
+                    preemptions: vec![],
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let task = reader
+            .get_task(
+                TEST_NAMESPACE,
+                "graph_A",
+                &invocation_payload.id,
+                "fn_a",
+                &task.id.to_string(),
+            )?
+            .unwrap();
+        assert_eq!(task.parameters, expected);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_search_invocations_by_attributes() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let write = |payload| {
+            indexify_state.write(StateMachineUpdateRequest {
+                payload,
+                state_changes_processed: vec![],
+            })
+        };
+        write(RequestPayload::CreateComputeGraph(
+            CreateComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph: mock_graph_a(),
+                expected_revision: None,
+                force: false,
+            },
+        ))
+        .await?;
+        let mut ids = vec![];
+        for (path, doc_type, amount) in [
+            ("a", "invoice", 50),
+            ("b", "invoice", 150),
+            ("c", "receipt", 200),
+        ] {
+            let invocation_payload = InvocationPayloadBuilder::default()
+                .namespace(TEST_NAMESPACE.to_string())
+                .compute_graph_name("graph_A".to_string())
+                .payload(DataPayload {
+                    path: path.to_string(),
+                    size: 10,
+                    sha256_hash: path.to_string(),
+                    tier: Default::default(),
+                    mime_type: None,
+                    content_encoding: None,
+                    metadata: Default::default(),
+                    replicas: Vec::new(),
+                    compression: None,
+                })
+                .attributes(HashMap::from([
+                    ("doc_type".to_string(), serde_json::json!(doc_type)),
+                    ("amount".to_string(), serde_json::json!(amount)),
+                ]))
+                .build()?;
+            ids.push(invocation_payload.id.clone());
+            write(RequestPayload::InvokeComputeGraph(
+                InvokeComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph_name: "graph_A".to_string(),
+                    invocation_payload,
+                },
+            ))
+            .await?;
+        }
+
+        let search = |filter: &str| -> Result<Vec<String>> {
+            let filter = LabelsFilter::from_str(filter)?;
+            let (invocations, _) = indexify_state.reader().search_invocations(
+                TEST_NAMESPACE,
+                "graph_A",
+                &filter,
+                None,
+                10,
+            )?;
+            let mut ids: Vec<String> = invocations.into_iter().map(|i| i.id).collect();
+            ids.sort();
+            Ok(ids)
+        };
+        let sorted = |mut ids: Vec<String>| {
+            ids.sort();
+            ids
+        };
+        assert_eq!(
+            search("doc_type=invoice")?,
+            sorted(vec![ids[0].clone(), ids[1].clone()])
+        );
+        assert_eq!(
+            search("doc_type=invoice,amount>=100")?,
+            vec![ids[1].clone()]
+        );
+        assert_eq!(
+            search("amount>100")?,
+            sorted(vec![ids[1].clone(), ids[2].clone()])
+        );
+        assert!(search("doc_type=contract")?.is_empty());
+        assert!(search("doc_type!=invoice").is_err());
+
+        // Pages follow the order of the ids
+        let filter = LabelsFilter::from_str("amount>0")?;
+        let reader = indexify_state.reader();
+        let (page, cursor) =
+            reader.search_invocations(TEST_NAMESPACE, "graph_A", &filter, None, 2)?;
+        assert_eq!(page.len(), 2);
+        let (rest, cursor) =
+            reader.search_invocations(TEST_NAMESPACE, "graph_A", &filter, cursor.as_deref(), 2)?;
+        assert_eq!(rest.len(), 1);
+        assert!(cursor.is_none());
+
+        // Deleted invocations are removed from the index
+        write(RequestPayload::DeleteInvocation(DeleteInvocationRequest {
+            namespace: TEST_NAMESPACE.to_string(),
+            compute_graph: "graph_A".to_string(),
+            invocation_id: ids[0].clone(),
+        }))
+        .await?;
+        assert_eq!(search("doc_type=invoice")?, vec![ids[1].clone()]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invocation_state() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let write = |payload| {
+            indexify_state.write(StateMachineUpdateRequest {
+                payload,
+                state_changes_processed: vec![],
+            })
+        };
+        write(RequestPayload::CreateComputeGraph(
+            CreateComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph: mock_graph_a(),
+                expected_revision: None,
+                force: false,
+            },
+        ))
+        .await?;
+        let invocation_payload = mock_invocation_payload();
+        let set = |invocation_id: &str, value: serde_json::Value, expected_version| {
+            RequestPayload::SetInvocationState(SetInvocationStateRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph: "graph_A".to_string(),
+                invocation_id: invocation_id.to_string(),
+                key: "counter".to_string(),
+                value,
+                expected_version,
+            })
+        };
+        let get = || {
+            indexify_state.reader().invocation_state(
+                TEST_NAMESPACE,
+                "graph_A",
+                &invocation_payload.id,
+                "counter",
+            )
+        };
+
+        // The invocation must exist
+        let err = write(set(&invocation_payload.id, serde_json::json!(1), None))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<StateStoreError>(),
+            Some(StateStoreError::NotFound(_))
+        ));
+
+        write(RequestPayload::InvokeComputeGraph(
+            InvokeComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph_name: "graph_A".to_string(),
+                invocation_payload: invocation_payload.clone(),
+            },
+        ))
+        .await?;
+        assert!(get()?.is_none());
+        write(set(&invocation_payload.id, serde_json::json!(1), Some(0))).await?;
+        let entry = get()?.unwrap();
+        assert_eq!(entry.value, serde_json::json!(1));
+        assert_eq!(entry.version, 1);
+
+        // Compare-and-swap writes fail unless the version matches
+        for expected_version in [0, 2] {
+            let err = write(set(
+                &invocation_payload.id,
+                serde_json::json!(2),
+                Some(expected_version),
+            ))
+            .await
+            .unwrap_err();
+            assert!(matches!(
+                err.downcast_ref::<StateStoreError>(),
+                Some(StateStoreError::Conflict(_))
+            ));
+        }
+        write(set(&invocation_payload.id, serde_json::json!(2), Some(1))).await?;
+        write(set(&invocation_payload.id, serde_json::json!(3), None)).await?;
+        let entry = get()?.unwrap();
+        assert_eq!(entry.value, serde_json::json!(3));
+        assert_eq!(entry.version, 3);
+
+        // The values are deleted with the invocation
+        write(RequestPayload::DeleteInvocation(DeleteInvocationRequest {
+            namespace: TEST_NAMESPACE.to_string(),
+            compute_graph: "graph_A".to_string(),
+            invocation_id: invocation_payload.id.clone(),
+        }))
+        .await?;
+        assert!(get()?.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invocation_groups() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let write = |payload| {
+            indexify_state.write(StateMachineUpdateRequest {
+                payload,
+                state_changes_processed: vec![],
+            })
+        };
+        let cg = mock_graph_a();
+        write(RequestPayload::CreateComputeGraph(
+            CreateComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph: cg.clone(),
+                expected_revision: None,
+                force: false,
+            },
+        ))
+        .await?;
+        write(RequestPayload::CreateWebhook(CreateWebhookRequest {
+            webhook: Webhook {
+                namespace: TEST_NAMESPACE.to_string(),
+                id: "groups".to_string(),
+                url: "http://localhost/hook".to_string(),
+                events: [WebhookEventType::InvocationGroupFinished].into(),
+                secret: "00".to_string(),
+                created_at: 0,
+                compute_graph: None,
+            },
+        }))
+        .await?;
+        write(RequestPayload::CreateInvocationGroup(
+            CreateInvocationGroupRequest {
+                group: InvocationGroup::new(TEST_NAMESPACE, "batch", HashMap::new(), 0),
+            },
+        ))
+        .await?;
+        let invoke = |id: &str, group: &str| {
+            RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph_name: cg.name.clone(),
+                invocation_payload: InvocationPayload {
+                    id: id.to_string(),
+                    group: Some(group.to_string()),
+                    ..mock_invocation_payload()
+                },
+            })
+        };
+        // Finishes the invocation, which has no task
+        let finish = |id: &str| {
+            RequestPayload::SchedulerUpdate(SchedulerUpdateRequest {
+                task_requests: vec![requests::CreateTasksRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: cg.name.clone(),
+                    invocation_id: id.to_string(),
+                    tasks: vec![],
+                    fan_out_cursor: None,
+                }],
+                allocations: vec![],
+                reduction_tasks: ReductionTasks::default(),
+                diagnostic_msgs: vec![],
+                sub_graph_invocations: vec![],
+                preemptions: vec![],
+            })
+        };
+        let reader = indexify_state.reader();
+        let group = || {
+            reader
+                .get_invocation_group(TEST_NAMESPACE, "batch")
+                .unwrap()
+                .unwrap()
+        };
+
+        let err = write(invoke("inv_0", "missing")).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<StateStoreError>(),
+            Some(StateStoreError::NotFound(_))
+        ));
+        write(invoke("inv_1", "batch")).await?;
+        write(invoke("inv_2", "batch")).await?;
+        write(finish("inv_1")).await?;
+        assert_eq!(group().invocations, 2);
+        assert_eq!(group().status(), InvocationGroupStatus::Running);
+
+        write(RequestPayload::CloseInvocationGroup(
+            CloseInvocationGroupRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                id: "batch".to_string(),
+            },
+        ))
+        .await?;
+        let err = write(invoke("inv_3", "batch")).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<StateStoreError>(),
+            Some(StateStoreError::Conflict(_))
+        ));
+        assert!(reader.due_webhook_deliveries(u64::MAX, 10)?.is_empty());
+
+        write(finish("inv_2")).await?;
+        assert_eq!(group().status(), InvocationGroupStatus::Success);
+        assert!(group().finished_at.is_some());
+        let deliveries = reader.due_webhook_deliveries(u64::MAX, 10)?;
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(
+            deliveries[0].event.event_type,
+            WebhookEventType::InvocationGroupFinished
+        );
+        let (members, _) = reader.invocation_group_members(TEST_NAMESPACE, "batch", None, None)?;
+        assert_eq!(
+            members
+                .iter()
+                .map(|ctx| ctx.invocation_id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["inv_1", "inv_2"]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_webhook_deliveries() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let write = |payload| {
+            indexify_state.write(StateMachineUpdateRequest {
+                payload,
+                state_changes_processed: vec![],
+            })
+        };
+        let webhook = |id: &str, event_type| Webhook {
+            namespace: TEST_NAMESPACE.to_string(),
+            id: id.to_string(),
+            url: "http://localhost/hook".to_string(),
+            events: [event_type].into(),
+            secret: "00".to_string(),
+            created_at: 0,
+            compute_graph: None,
+        };
+        for webhook in [
+            webhook("graphs", WebhookEventType::GraphCreated),
+            webhook("executors", WebhookEventType::ExecutorRemoved),
+        ] {
+            write(RequestPayload::CreateWebhook(CreateWebhookRequest {
+                webhook,
+            }))
+            .await?;
+        }
+        let create_graph = || {
+            RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph: mock_graph_a(),
+                expected_revision: None,
+                force: true,
+            })
+        };
+
+        // Only the creation of the graph is delivered, to its subscriber
+        write(create_graph()).await?;
+        write(create_graph()).await?;
+        let reader = indexify_state.reader();
+        let deliveries = reader.due_webhook_deliveries(u64::MAX, 10)?;
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].webhook_id, "graphs");
+        assert_eq!(
+            deliveries[0].event.event_type,
+            WebhookEventType::GraphCreated
+        );
+
+        let mut retried = deliveries[0].clone();
+        retried.attempts = 1;
+        retried.next_attempt_at = u64::MAX;
+        write(RequestPayload::UpdateWebhookDeliveries(
+            UpdateWebhookDeliveriesRequest {
+                retried: vec![retried.clone()],
+                ..Default::default()
+            },
+        ))
+        .await?;
+        assert!(reader
+            .due_webhook_deliveries(get_epoch_time_in_ms(), 10)?
+            .is_empty());
+
+        write(RequestPayload::UpdateWebhookDeliveries(
+            UpdateWebhookDeliveriesRequest {
+                dead_lettered: vec![retried.clone()],
+                ..Default::default()
+            },
+        ))
+        .await?;
+        assert!(reader.due_webhook_deliveries(u64::MAX, 10)?.is_empty());
+        assert_eq!(
+            reader.list_webhook_dead_letters(TEST_NAMESPACE)?,
+            vec![retried]
+        );
+
+        write(RequestPayload::DeleteWebhook(DeleteWebhookRequest {
+            namespace: TEST_NAMESPACE.to_string(),
+            id: "graphs".to_string(),
+        }))
+        .await?;
+        assert!(reader.get_webhook(TEST_NAMESPACE, "graphs")?.is_none());
+        assert!(reader.list_webhook_dead_letters(TEST_NAMESPACE)?.is_empty());
+        assert_eq!(reader.list_webhooks(TEST_NAMESPACE)?.len(), 1);
+
+        // Webhooks of a graph only receive the rejections of the graph
+        write(RequestPayload::CreateWebhook(CreateWebhookRequest {
+            webhook: Webhook {
+                compute_graph: Some(mock_graph_a().name),
+                ..webhook("quality", WebhookEventType::DataRejected)
+            },
+        }))
+        .await?;
+        let rejection = |compute_graph: String| {
+            RequestPayload::RecordDataRejection(RecordDataRejectionRequest {
+                rejection: DataRejection {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph,
+                    guardrail: Guardrail::PayloadSize,
+                    error: "payload too large".to_string(),
+                    invocation_id: None,
+                    compute_fn: None,
+                    task_id: None,
+                    url: Some("s3://bucket/input".to_string()),
+                    size_bytes: 10,
+                },
+            })
+        };
+        write(rejection(mock_graph_b().name)).await?;
+        write(rejection(mock_graph_a().name)).await?;
+        let deliveries = reader.due_webhook_deliveries(u64::MAX, 10)?;
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].webhook_id, "quality");
+        assert_eq!(
+            deliveries[0].event.data["compute_graph"],
+            mock_graph_a().name
+        );
+        Ok(())
+    }
 }
@@ -0,0 +1,387 @@
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+use anyhow::{anyhow, Result};
+use data_model::keys;
+use indexify_utils::get_epoch_time_in_ms;
+use rocksdb::{IteratorMode, Transaction, TransactionDB};
+use tracing::info;
+
+use crate::{
+    serializer::{JsonEncode, JsonEncoder},
+    snapshot::dump_db,
+    state_machine::IndexifyObjectsColumns,
+};
+
+// Key of the Stats column holding the version of the key/value formats,
+// state stores created before versioning are at version 0
+const SCHEMA_VERSION_KEY: &[u8] = b"schema_version";
+
+/// A change of the key/value formats of the state store. Migrations run in
+/// order of their version when the state store is opened, each in a
+/// transaction which also records the new version.
+pub struct Migration {
+    pub version: u64,
+    pub name: &'static str,
+    /// Rewrites or deletes existing keys, the state store is backed up
+    /// before destructive migrations are applied
+    pub destructive: bool,
+    pub apply: fn(&TransactionDB, &Transaction<TransactionDB>) -> Result<()>,
+}
+
+/// Migrations of the state store, ordered by version.
+pub fn migrations() -> Vec<Migration> {
+    vec![
+        Migration {
+            version: 1,
+            name: "record_schema_version",
+            destructive: false,
+            apply: |_, _| Ok(()),
+        },
+        Migration {
+            version: 2,
+            name: "encode_task_invocation_allocation_keys",
+            destructive: true,
+            apply: encode_task_invocation_allocation_keys,
+        },
+    ]
+}
+
+/// Rewrites the `|` separated keys of tasks, allocations, invocations and
+/// of the outputs of functions with the encoding of data_model::keys, along
+/// with the keys embedded in the values of tasks, task outputs and system
+/// tasks.
+fn encode_task_invocation_allocation_keys(
+    db: &TransactionDB,
+    txn: &Transaction<TransactionDB>,
+) -> Result<()> {
+    let columns = [
+        (IndexifyObjectsColumns::Tasks, 5),
+        (IndexifyObjectsColumns::UnallocatedTasks, 5),
+        // <executor_id>|<nsecs>|<task key>
+        (IndexifyObjectsColumns::TaskAllocations, 7),
+        (IndexifyObjectsColumns::GraphInvocations, 3),
+        (IndexifyObjectsColumns::GraphInvocationCtx, 3),
+        (IndexifyObjectsColumns::FnOutputs, 5),
+        // <namespace>|<task_id>|<output_id>
+        (IndexifyObjectsColumns::TaskOutputs, 3),
+        // <task key>|<output key>
+        (IndexifyObjectsColumns::ReductionTasks, 6),
+    ];
+    for (column, fields) in columns {
+        for (key, value) in column_rows(db, &column)? {
+            let value = match column {
+                IndexifyObjectsColumns::Tasks => encode_task_input_key(&value)?,
+                IndexifyObjectsColumns::TaskOutputs => {
+                    let output_key: String = JsonEncoder::decode(&value)?;
+                    JsonEncoder::encode(&encode_textual_key(output_key.as_bytes(), 5)?)?
+                }
+                _ => value.to_vec(),
+            };
+            let cf = column.cf_db(db);
+            txn.delete_cf(&cf, &key)?;
+            txn.put_cf(&cf, encode_textual_key(&key, fields)?, value)?;
+        }
+    }
+
+    // System tasks resume from the key of the next invocation to process
+    let column = IndexifyObjectsColumns::SystemTasks;
+    for (key, value) in column_rows(db, &column)? {
+        let mut task: serde_json::Value = JsonEncoder::decode(&value)?;
+        let Some(restart_key) = task.get("restart_key").filter(|key| !key.is_null()) else {
+            continue;
+        };
+        let restart_key: Vec<u8> = serde_json::from_value(restart_key.clone())?;
+        task["restart_key"] = serde_json::to_value(encode_textual_key(&restart_key, 3)?)?;
+        txn.put_cf(&column.cf_db(db), &key, JsonEncoder::encode(&task)?)?;
+    }
+    Ok(())
+}
+
+// The key of the input of a task is handed to executors, it stays textual
+// with the fields escaped, see data_model::keys
+fn encode_task_input_key(value: &[u8]) -> Result<Vec<u8>> {
+    let mut task: serde_json::Value = JsonEncoder::decode(value)?;
+    let invocation_id = task["invocation_id"].as_str().unwrap_or_default();
+    let input_key = task["input_node_output_key"]
+        .as_str()
+        .ok_or(anyhow!("invalid task {:?}", task))?;
+    // Tasks of the start node read the invocation payload
+    if input_key != invocation_id {
+        let fields: Vec<&str> = input_key.splitn(5, '|').collect();
+        task["input_node_output_key"] = keys::join_textual_key(&fields).into();
+    }
+    JsonEncoder::encode(&task)
+}
+
+fn column_rows(
+    db: &TransactionDB,
+    column: &IndexifyObjectsColumns,
+) -> Result<Vec<(Box<[u8]>, Box<[u8]>)>> {
+    db.iterator_cf(&column.cf_db(db), IteratorMode::Start)
+        .map(|kv| Ok(kv?))
+        .collect()
+}
+
+// The last field keeps any `|` following the first `count - 1` fields
+fn encode_textual_key(key: &[u8], count: usize) -> Result<Vec<u8>> {
+    let key = std::str::from_utf8(key)?;
+    let fields: Vec<&str> = key.splitn(count, '|').collect();
+    if fields.len() != count {
+        return Err(anyhow!("invalid key {}", key));
+    }
+    Ok(keys::encode_key(&fields))
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MigrationOptions {
+    /// Reports the pending migrations without applying them
+    pub dry_run: bool,
+    /// Directory backups are written to, next to the state store when unset
+    pub backup_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigrationReport {
+    pub from_version: u64,
+    pub to_version: u64,
+    /// Names of the migrations applied, or which would be applied by a dry
+    /// run
+    pub migrations: Vec<String>,
+    pub backup: Option<PathBuf>,
+}
+
+pub fn schema_version(db: &TransactionDB) -> Result<u64> {
+    let cf = IndexifyObjectsColumns::Stats.cf_db(db);
+    match db.get_cf(&cf, SCHEMA_VERSION_KEY)? {
+        Some(value) => {
+            let bytes: [u8; 8] = value
+                .as_slice()
+                .try_into()
+                .map_err(|_| anyhow!("Invalid length for u64 conversion"))?;
+            Ok(u64::from_be_bytes(bytes))
+        }
+        None => Ok(0),
+    }
+}
+
+/// Brings the state store at `path` to the version of the last migration.
+pub fn run_migrations(
+    db: &TransactionDB,
+    path: &PathBuf,
+    migrations: &[Migration],
+    options: &MigrationOptions,
+) -> Result<MigrationReport> {
+    let from_version = schema_version(db)?;
+    let latest = migrations.last().map_or(0, |migration| migration.version);
+    if from_version > latest {
+        return Err(anyhow!(
+            "state store is at schema version {}, newer than the latest version {} known to this server",
+            from_version,
+            latest
+        ));
+    }
+    let pending: Vec<&Migration> = migrations
+        .iter()
+        .filter(|migration| migration.version > from_version)
+        .collect();
+    let mut report = MigrationReport {
+        from_version,
+        to_version: latest,
+        migrations: pending.iter().map(|m| m.name.to_string()).collect(),
+        backup: None,
+    };
+    if options.dry_run || pending.is_empty() {
+        return Ok(report);
+    }
+
+    if pending.iter().any(|migration| migration.destructive) {
+        let backup_dir = options
+            .backup_dir
+            .clone()
+            .unwrap_or_else(|| path.with_extension("backups"));
+        report.backup = Some(backup(db, &backup_dir, from_version)?);
+    }
+    for migration in pending {
+        info!(
+            "applying state store migration {} {}",
+            migration.version, migration.name
+        );
+        let txn = db.transaction();
+        (migration.apply)(db, &txn)?;
+        let cf = IndexifyObjectsColumns::Stats.cf_db(db);
+        txn.put_cf(&cf, SCHEMA_VERSION_KEY, migration.version.to_be_bytes())?;
+        txn.commit()?;
+    }
+    Ok(report)
+}
+
+/// Writes a dump of the state store which can be restored with the
+/// `restore_snapshot` setting, along with its manifest.
+fn backup(db: &TransactionDB, backup_dir: &PathBuf, version: u64) -> Result<PathBuf> {
+    fs::create_dir_all(backup_dir)?;
+    let id = format!("schema-v{}-{}", version, get_epoch_time_in_ms());
+    let path = backup_dir.join(format!("{}.state", id));
+    let mut out = BufWriter::new(File::create(&path)?);
+    let manifest = dump_db(db, &db.snapshot(), &id, |_| true, &mut out)?;
+    out.flush()?;
+    fs::write(
+        backup_dir.join(format!("{}.manifest.json", id)),
+        serde_json::to_vec(&manifest)?,
+    )?;
+    info!("backed up state store to {}", path.display());
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{open_db, snapshot::restore_snapshot};
+
+    #[tokio::test]
+    async fn test_run_migrations() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let path = temp_dir.path().join("state");
+        let db = open_db(path.clone())?;
+        let migrations = vec![
+            Migration {
+                version: 1,
+                name: "first",
+                destructive: false,
+                apply: |db, txn| {
+                    let cf = IndexifyObjectsColumns::Stats.cf_db(db);
+                    txn.put_cf(&cf, b"migrated", b"old")?;
+                    Ok(())
+                },
+            },
+            Migration {
+                version: 2,
+                name: "second",
+                destructive: true,
+                apply: |db, txn| {
+                    let cf = IndexifyObjectsColumns::Stats.cf_db(db);
+                    txn.put_cf(&cf, b"migrated", b"new")?;
+                    Ok(())
+                },
+            },
+        ];
+
+        let options = MigrationOptions {
+            dry_run: true,
+            backup_dir: Some(temp_dir.path().join("backups")),
+        };
+        let report = run_migrations(&db, &path, &migrations[..1], &options)?;
+        assert_eq!(report.migrations, vec!["first".to_string()]);
+        assert_eq!(schema_version(&db)?, 0);
+
+        let options = MigrationOptions {
+            dry_run: false,
+            ..options
+        };
+        let report = run_migrations(&db, &path, &migrations[..1], &options)?;
+        assert_eq!((report.from_version, report.to_version), (0, 1));
+        assert!(report.backup.is_none());
+        assert_eq!(schema_version(&db)?, 1);
+
+        // Only the new migration runs, after a backup of the state at version 1
+        let report = run_migrations(&db, &path, &migrations, &options)?;
+        assert_eq!(report.migrations, vec!["second".to_string()]);
+        assert_eq!(schema_version(&db)?, 2);
+        let cf = IndexifyObjectsColumns::Stats.cf_db(&db);
+        assert_eq!(db.get_cf(&cf, b"migrated")?, Some(b"new".to_vec()));
+
+        let restored_path = temp_dir.path().join("restored");
+        let backup = fs::read(report.backup.unwrap())?;
+        restore_snapshot(restored_path.clone(), futures::stream::iter([Ok(backup)])).await?;
+        let restored = open_db(restored_path)?;
+        assert_eq!(schema_version(&restored)?, 1);
+        let cf = IndexifyObjectsColumns::Stats.cf_db(&restored);
+        assert_eq!(restored.get_cf(&cf, b"migrated")?, Some(b"old".to_vec()));
+
+        // A state store written by a newer server isn't opened
+        assert!(run_migrations(&db, &path, &migrations[..1], &options).is_err());
+        Ok(())
+    }
+    #[test]
+    fn test_encode_task_invocation_allocation_keys() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let db = open_db(temp_dir.path().join("state"))?;
+        let task_key = "ns|graph|inv|fn|task";
+        let output_key = "ns|graph|inv|fn_a|out";
+        let task = serde_json::json!({
+            "invocation_id": "inv",
+            "input_node_output_key": output_key,
+        });
+        let rows = [
+            (IndexifyObjectsColumns::Tasks, task_key.to_string()),
+            (
+                IndexifyObjectsColumns::TaskAllocations,
+                format!("executor|42|{}", task_key),
+            ),
+            (
+                IndexifyObjectsColumns::GraphInvocationCtx,
+                "ns|graph|inv".to_string(),
+            ),
+            (IndexifyObjectsColumns::FnOutputs, output_key.to_string()),
+            (
+                IndexifyObjectsColumns::TaskOutputs,
+                "ns|task|out".to_string(),
+            ),
+        ];
+        for (column, key) in &rows {
+            let value = match column {
+                IndexifyObjectsColumns::Tasks => serde_json::to_vec(&task)?,
+                IndexifyObjectsColumns::TaskOutputs => serde_json::to_vec(output_key)?,
+                _ => b"value".to_vec(),
+            };
+            db.put_cf(&column.cf_db(&db), key, value)?;
+        }
+        let system_task = serde_json::json!({
+            "namespace": "ns",
+            "compute_graph_name": "graph",
+            "restart_key": b"ns|graph|inv",
+        });
+        db.put_cf(
+            &IndexifyObjectsColumns::SystemTasks.cf_db(&db),
+            "ns|graph",
+            serde_json::to_vec(&system_task)?,
+        )?;
+
+        let txn = db.transaction();
+        encode_task_invocation_allocation_keys(&db, &txn)?;
+        txn.commit()?;
+
+        let encoded_task_key = keys::encode_key(&["ns", "graph", "inv", "fn", "task"]);
+        let encoded_output_key = keys::encode_key(&["ns", "graph", "inv", "fn_a", "out"]);
+        let mut allocation_key = keys::encode_key(&["executor", "42"]);
+        allocation_key.extend_from_slice(&encoded_task_key);
+        let expected = [
+            encoded_task_key.clone(),
+            allocation_key,
+            keys::encode_key(&["ns", "graph", "inv"]),
+            encoded_output_key.clone(),
+            keys::encode_key(&["ns", "task", "out"]),
+        ];
+        for ((column, _), key) in rows.iter().zip(expected) {
+            let rows = column_rows(&db, column)?;
+            assert_eq!(rows.len(), 1);
+            assert_eq!(&*rows[0].0, key.as_slice());
+        }
+        let tasks = column_rows(&db, &IndexifyObjectsColumns::Tasks)?;
+        let task: serde_json::Value = serde_json::from_slice(&tasks[0].1)?;
+        assert_eq!(task["input_node_output_key"], output_key);
+        let outputs = column_rows(&db, &IndexifyObjectsColumns::TaskOutputs)?;
+        let key: Vec<u8> = serde_json::from_slice(&outputs[0].1)?;
+        assert_eq!(key, encoded_output_key);
+
+        let system_tasks = column_rows(&db, &IndexifyObjectsColumns::SystemTasks)?;
+        assert_eq!(&*system_tasks[0].0, b"ns|graph");
+        let system_task: serde_json::Value = serde_json::from_slice(&system_tasks[0].1)?;
+        let restart_key: Vec<u8> = serde_json::from_value(system_task["restart_key"].clone())?;
+        assert_eq!(restart_key, keys::encode_key(&["ns", "graph", "inv"]));
+        Ok(())
+    }
+}
@@ -0,0 +1,174 @@
+//! Iteration and deletion of the keys of a column sharing a prefix, see
+//! data_model::keys for the key layout.
+
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use data_model::keys::{prefix_upper_bound, KeyPrefix};
+use rocksdb::{
+    AsColumnFamilyRef,
+    Direction,
+    IteratorMode,
+    ReadOptions,
+    Transaction,
+    TransactionDB,
+    WriteBatchWithTransaction,
+};
+
+use crate::{state_machine::IndexifyObjectsColumns, IndexifyState};
+
+/// Read options bounding iterators to the keys with the prefix, so they stop
+/// at the end of the prefix instead of reading the keys past it.
+pub fn prefix_read_options(prefix: &[u8]) -> ReadOptions {
+    let mut read_options = ReadOptions::default();
+    read_options.set_readahead_size(4_194_304);
+    if let Some(upper_bound) = prefix_upper_bound(prefix) {
+        read_options.set_iterate_upper_bound(upper_bound);
+    }
+    read_options
+}
+
+/// Iterates over the keys with the prefix, from restart_key when set.
+pub fn make_prefix_iterator<'a>(
+    txn: &'a Transaction<TransactionDB>,
+    cf_handle: &impl AsColumnFamilyRef,
+    prefix: &'a [u8],
+    restart_key: &'a Option<Vec<u8>>,
+) -> impl Iterator<Item = Result<(Box<[u8]>, Box<[u8]>)>> + 'a {
+    let iter = txn.iterator_cf_opt(
+        cf_handle,
+        prefix_read_options(prefix),
+        match restart_key {
+            Some(restart_key) => IteratorMode::From(restart_key, Direction::Forward),
+            None => IteratorMode::From(prefix, Direction::Forward),
+        },
+    );
+    iter.map(|item| item.map_err(|e| anyhow!(e.to_string())))
+        .take_while(move |item| match item {
+            Ok((key, _)) => key.starts_with(prefix),
+            Err(_) => true,
+        })
+}
+
+/// Deletes the keys with the prefix one by one in the transaction, for
+/// prefixes the transaction writes again or reads after deleting them.
+pub fn delete_cf_prefix(
+    txn: &Transaction<TransactionDB>,
+    cf: &impl AsColumnFamilyRef,
+    prefix: &[u8],
+) -> Result<()> {
+    for kv in make_prefix_iterator(txn, cf, prefix, &None) {
+        let (key, _) = kv?;
+        txn.delete_cf(cf, &key)?;
+    }
+    Ok(())
+}
+
+/// Prefixes deleted by a write with range deletions, which cost the same
+/// whatever the number of keys. They are applied right before the
+/// transaction of the write commits, so the transaction must not read or
+/// write the keys of the prefixes.
+#[derive(Debug, Default)]
+pub struct RangeDeletes {
+    prefixes: Vec<(IndexifyObjectsColumns, KeyPrefix)>,
+}
+
+impl RangeDeletes {
+    /// Deletes every key of the column with the prefix, the prefix must not
+    /// be empty.
+    pub fn delete_prefix(&mut self, column: IndexifyObjectsColumns, prefix: KeyPrefix) {
+        self.prefixes.push((column, prefix));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.prefixes.is_empty()
+    }
+
+    fn write_batch(&self, db: &TransactionDB) -> WriteBatchWithTransaction<true> {
+        let mut batch = WriteBatchWithTransaction::<true>::default();
+        for (column, prefix) in &self.prefixes {
+            if let Some(upper_bound) = prefix.upper_bound() {
+                batch.delete_range_cf(&column.cf_db(db), prefix.as_bytes(), upper_bound.as_slice());
+            }
+        }
+        batch
+    }
+
+    /// Deletes the keys one by one in the transaction instead.
+    fn delete_in(&self, db: Arc<TransactionDB>, txn: &Transaction<TransactionDB>) -> Result<()> {
+        for (column, prefix) in &self.prefixes {
+            delete_cf_prefix(txn, &column.cf_db(&db), prefix.as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+impl IndexifyState {
+    /// Applies the range deletions of a write, the keys are deleted by the
+    /// transaction instead when the db rejects the range deletions.
+    pub(crate) async fn apply_range_deletes(
+        &self,
+        range_deletes: &RangeDeletes,
+        txn: &Transaction<'_, TransactionDB>,
+    ) -> Result<()> {
+        if range_deletes.is_empty() {
+            return Ok(());
+        }
+        if let Err(err) = self.write_batch(range_deletes.write_batch(&self.db)).await {
+            tracing::warn!(
+                "range deletion failed, deleting the keys one by one: {:?}",
+                err
+            );
+            range_deletes.delete_in(self.db.clone(), txn)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use data_model::keys;
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::IndexifyState;
+
+    #[tokio::test]
+    async fn test_range_deletes() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let db = indexify_state.db.clone();
+        let column = IndexifyObjectsColumns::InvocationState;
+        let entries = [
+            ["ns", "graph", "inv", "a"],
+            ["ns", "graph", "inv", "b"],
+            ["ns", "graph", "inv1", "a"],
+            ["ns", "other", "inv", "a"],
+        ];
+        let txn = db.transaction();
+        for key in entries {
+            txn.put_cf(&column.cf_db(&db), keys::encode_key(&key), [])?;
+        }
+        txn.commit()?;
+
+        let prefix = KeyPrefix::encoded(&["ns", "graph", "inv"]);
+        let list = |prefix: &KeyPrefix| -> Result<Vec<Vec<String>>> {
+            let txn = db.transaction();
+            make_prefix_iterator(&txn, &column.cf_db(&db), prefix.as_bytes(), &None)
+                .map(|kv| keys::decode_key(&kv?.0))
+                .collect()
+        };
+        assert_eq!(list(&prefix)?, vec![entries[0], entries[1]]);
+
+        let mut range_deletes = RangeDeletes::default();
+        range_deletes.delete_prefix(column, prefix.clone());
+        let txn = db.transaction();
+        indexify_state
+            .apply_range_deletes(&range_deletes, &txn)
+            .await?;
+        txn.commit()?;
+        assert!(list(&prefix)?.is_empty());
+        assert_eq!(list(&KeyPrefix::encoded(&["ns"]))?, vec![entries[2], entries[3]]);
+        Ok(())
+    }
+}
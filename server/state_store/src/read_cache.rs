@@ -0,0 +1,436 @@
+//! In-memory LRU caches of the compute graphs and invocation contexts, which
+//! are read for every task the scheduler creates, allocates or finalizes.
+//! Writes invalidate the entries they may have changed once they committed.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use anyhow::Result;
+use data_model::{ChangeType, ComputeGraph, GraphInvocationCtx, StateChange};
+
+use crate::requests::RequestPayload;
+
+const COMPUTE_GRAPHS_CAPACITY: usize = 1024;
+const INVOCATION_CTXS_CAPACITY: usize = 16384;
+
+// Invalidations bump the generation of the slot of their key. A value read
+// from the db is only cached if the generation of its slot didn't change
+// since, so a read racing with a write can't cache the old value.
+const GENERATION_SLOTS: usize = 64;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: u64,
+    pub capacity: u64,
+}
+
+impl CacheStats {
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            return 0.0;
+        }
+        self.hits as f64 / lookups as f64
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReadCacheStats {
+    pub compute_graphs: CacheStats,
+    pub invocation_ctxs: CacheStats,
+}
+
+struct Entries<V> {
+    capacity: usize,
+    values: HashMap<Vec<u8>, (V, u64)>,
+    // Keys by the tick they were last used at, least recently used first
+    recency: BTreeMap<u64, Vec<u8>>,
+    tick: u64,
+    generations: [u64; GENERATION_SLOTS],
+}
+
+impl<V: Clone> Entries<V> {
+    fn get(&mut self, key: &[u8]) -> Option<V> {
+        let (value, last_used) = self.values.get_mut(key)?;
+        self.tick += 1;
+        if let Some(key) = self.recency.remove(last_used) {
+            self.recency.insert(self.tick, key);
+        }
+        *last_used = self.tick;
+        Some(value.clone())
+    }
+
+    fn insert(&mut self, key: Vec<u8>, value: V) {
+        self.remove(&key);
+        if self.values.len() >= self.capacity {
+            if let Some((_, oldest)) = self.recency.pop_first() {
+                self.values.remove(&oldest);
+            }
+        }
+        self.tick += 1;
+        self.recency.insert(self.tick, key.clone());
+        self.values.insert(key, (value, self.tick));
+    }
+
+    fn remove(&mut self, key: &[u8]) {
+        if let Some((_, last_used)) = self.values.remove(key) {
+            self.recency.remove(&last_used);
+        }
+    }
+}
+
+fn generation_slot(key: &[u8]) -> usize {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish() as usize % GENERATION_SLOTS
+}
+
+pub(crate) struct Cache<V> {
+    entries: Mutex<Entries<V>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl<V: Clone> Cache<V> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            entries: Mutex::new(Entries {
+                capacity,
+                values: HashMap::new(),
+                recency: BTreeMap::new(),
+                tick: 0,
+                generations: [0; GENERATION_SLOTS],
+            }),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the cached value of the key, or the value `load` reads from
+    /// the db, which is cached unless it is missing.
+    pub(crate) fn get_or_load(
+        &self,
+        key: Vec<u8>,
+        load: impl FnOnce() -> Result<Option<V>>,
+    ) -> Result<Option<V>> {
+        let slot = generation_slot(&key);
+        let generation = {
+            let mut entries = self.entries.lock().unwrap();
+            if let Some(value) = entries.get(&key) {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return Ok(Some(value));
+            }
+            entries.generations[slot]
+        };
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let value = load()?;
+        if let Some(value) = &value {
+            let mut entries = self.entries.lock().unwrap();
+            if entries.generations[slot] == generation {
+                entries.insert(key, value.clone());
+            }
+        }
+        Ok(value)
+    }
+
+    fn invalidate<'a>(&self, keys: impl IntoIterator<Item = &'a Vec<u8>>) {
+        let mut entries = self.entries.lock().unwrap();
+        for key in keys {
+            entries.generations[generation_slot(key)] += 1;
+            entries.remove(key);
+        }
+    }
+
+    fn clear(&self) {
+        let mut entries = self.entries.lock().unwrap();
+        for generation in entries.generations.iter_mut() {
+            *generation += 1;
+        }
+        entries.values.clear();
+        entries.recency.clear();
+    }
+
+    fn stats(&self) -> CacheStats {
+        let entries = self.entries.lock().unwrap();
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: entries.values.len() as u64,
+            capacity: entries.capacity as u64,
+        }
+    }
+}
+
+pub struct ReadCache {
+    pub(crate) compute_graphs: Cache<ComputeGraph>,
+    pub(crate) invocation_ctxs: Cache<GraphInvocationCtx>,
+}
+
+impl Default for ReadCache {
+    fn default() -> Self {
+        Self::new(COMPUTE_GRAPHS_CAPACITY, INVOCATION_CTXS_CAPACITY)
+    }
+}
+
+impl ReadCache {
+    pub fn new(compute_graphs: usize, invocation_ctxs: usize) -> Self {
+        Self {
+            compute_graphs: Cache::new(compute_graphs),
+            invocation_ctxs: Cache::new(invocation_ctxs),
+        }
+    }
+
+    pub fn stats(&self) -> ReadCacheStats {
+        ReadCacheStats {
+            compute_graphs: self.compute_graphs.stats(),
+            invocation_ctxs: self.invocation_ctxs.stats(),
+        }
+    }
+
+    pub(crate) fn invalidate(&self, invalidation: &Invalidation) {
+        if invalidation.compute_graphs {
+            self.compute_graphs.clear();
+        }
+        if invalidation.all_invocation_ctxs {
+            self.invocation_ctxs.clear();
+        } else {
+            self.invocation_ctxs.invalidate(&invalidation.invocation_ctxs);
+        }
+    }
+
+    /// Drops every entry, for writes bypassing the state machine such as the
+    /// replicated writes of the leader.
+    pub(crate) fn clear(&self) {
+        self.compute_graphs.clear();
+        self.invocation_ctxs.clear();
+    }
+}
+
+/// Entries a write may change.
+#[derive(Debug, Default)]
+pub(crate) struct Invalidation {
+    compute_graphs: bool,
+    all_invocation_ctxs: bool,
+    invocation_ctxs: HashSet<Vec<u8>>,
+}
+
+impl Invalidation {
+    /// The requests rewriting many invocations or graphs invalidate whole
+    /// caches, the others the contexts of the invocations they name. Every
+    /// request is listed so that new ones have to be classified.
+    pub(crate) fn for_request(payload: &RequestPayload) -> Self {
+        let mut invalidation = Self::default();
+        match payload {
+            RequestPayload::InvokeComputeGraph(req) => invalidation.invocation_ctx(
+                &req.namespace,
+                &req.compute_graph_name,
+                &req.invocation_payload.id,
+            ),
+            RequestPayload::RerunInvocation(req) => invalidation.invocation_ctx(
+                &req.namespace,
+                &req.compute_graph_name,
+                &req.invocation_id,
+            ),
+            RequestPayload::FinalizeTask(req) => {
+                invalidation.invocation_ctx(&req.namespace, &req.compute_graph, &req.invocation_id)
+            }
+            RequestPayload::FinalizeTasks(req) => {
+                for req in &req.tasks {
+                    invalidation.invocation_ctx(
+                        &req.namespace,
+                        &req.compute_graph,
+                        &req.invocation_id,
+                    );
+                }
+            }
+            RequestPayload::StreamTaskOutputs(req) => {
+                invalidation.invocation_ctx(&req.namespace, &req.compute_graph, &req.invocation_id)
+            }
+            RequestPayload::CheckpointTask(req) => {
+                invalidation.invocation_ctx(&req.namespace, &req.compute_graph, &req.invocation_id)
+            }
+            RequestPayload::DeleteInvocation(req) => {
+                invalidation.invocation_ctx(&req.namespace, &req.compute_graph, &req.invocation_id)
+            }
+            RequestPayload::SetInvocationState(req) => {
+                invalidation.invocation_ctx(&req.namespace, &req.compute_graph, &req.invocation_id)
+            }
+            RequestPayload::SignalInvocation(req) => {
+                invalidation.invocation_ctx(&req.namespace, &req.compute_graph, &req.invocation_id)
+            }
+            RequestPayload::SchedulerUpdate(req) => {
+                for req in &req.task_requests {
+                    invalidation.invocation_ctx(
+                        &req.namespace,
+                        &req.compute_graph,
+                        &req.invocation_id,
+                    );
+                }
+                for req in &req.sub_graph_invocations {
+                    invalidation.invocation_ctx(
+                        &req.parent.namespace,
+                        &req.parent.compute_graph,
+                        &req.parent.invocation_id,
+                    );
+                }
+                for placement in req.allocations.iter().chain(&req.preemptions) {
+                    invalidation.invocation_ctx(
+                        &placement.task.namespace,
+                        &placement.task.compute_graph_name,
+                        &placement.task.invocation_id,
+                    );
+                }
+            }
+            RequestPayload::CreateComputeGraph(_) |
+            RequestPayload::ApplyManifest(_) |
+            RequestPayload::FinalizeGraphCanary(_) |
+            RequestPayload::RollbackGraphCanary(_) |
+            RequestPayload::DeleteComputeGraph(_) |
+            RequestPayload::UndeleteComputeGraph(_) |
+            RequestPayload::PurgeComputeGraph(_) |
+            RequestPayload::DeleteNamespace(_) |
+            RequestPayload::PurgeNamespace(_) => {
+                invalidation.compute_graphs = true;
+                invalidation.all_invocation_ctxs = true;
+            }
+            RequestPayload::PauseComputeGraph(_) | RequestPayload::ResumeComputeGraph(_) => {
+                invalidation.compute_graphs = true;
+            }
+            // The invocations of the sub graphs are deleted too
+            RequestPayload::DeleteIngestedData(_) |
+            RequestPayload::RerunComputeGraph(_) |
+            RequestPayload::CloseInvocationGroup(_) |
+            RequestPayload::CloseWindow(_) |
+            RequestPayload::DeregisterExecutor(_) |
+            RequestPayload::RequeueExecutorTasks(_) |
+            RequestPayload::ReconcileExecutorTasks(_) |
+            RequestPayload::UpdateExecutorLabels(_) |
+            RequestPayload::BlacklistExecutors(_) => {
+                invalidation.all_invocation_ctxs = true;
+            }
+            RequestPayload::CreateNameSpace(_) |
+            RequestPayload::CreateGraphCanary(_) |
+            RequestPayload::UpdateGraphCanary(_) |
+            RequestPayload::CreateInvocationGroup(_) |
+            RequestPayload::RegisterExecutor(_) |
+            RequestPayload::UpdateExecutorArtifacts(_) |
+            RequestPayload::CreateExecutorPool(_) |
+            RequestPayload::DeleteExecutorPool(_) |
+            RequestPayload::RemoveGcUrls(_) |
+            RequestPayload::UpdateSystemTask(_) |
+            RequestPayload::RemoveSystemTask(_) |
+            RequestPayload::CreateWebhook(_) |
+            RequestPayload::DeleteWebhook(_) |
+            RequestPayload::UpdateWebhookDeliveries(_) |
+            RequestPayload::RedriveWebhookDeadLetters(_) |
+            RequestPayload::MovePayloads(_) |
+            RequestPayload::RecordPayloadReplicas(_) |
+            RequestPayload::RecordSlaStatus(_) |
+            RequestPayload::RecordAlertStatuses(_) |
+            RequestPayload::RecordDataRejection(_) |
+            RequestPayload::RecordFunctionLatencies(_) |
+            RequestPayload::RegisterPendingUploads(_) |
+            RequestPayload::SweepPendingUploads(_) |
+            RequestPayload::RemoveExecutorBlacklistEntry(_) |
+            RequestPayload::RecordExecutorApproval(_) |
+            RequestPayload::AcknowledgeAborts(_) |
+            RequestPayload::SetLegalHold(_) |
+            RequestPayload::ClearLegalHold(_) => {}
+        }
+        invalidation
+    }
+
+    /// Adds the invocations of the state changes of the write, such as the
+    /// parents of finished sub graph invocations.
+    pub(crate) fn add_state_changes(&mut self, state_changes: &[StateChange]) {
+        for state_change in state_changes {
+            match &state_change.change_type {
+                ChangeType::InvokeComputeGraph(event) => self.invocation_ctx(
+                    &event.namespace,
+                    &event.compute_graph,
+                    &event.invocation_id,
+                ),
+                ChangeType::TaskFinished(event) => self.invocation_ctx(
+                    &event.namespace,
+                    &event.compute_graph,
+                    &event.invocation_id,
+                ),
+                ChangeType::TasksFinished(event) => {
+                    for event in &event.tasks {
+                        self.invocation_ctx(
+                            &event.namespace,
+                            &event.compute_graph,
+                            &event.invocation_id,
+                        );
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Writes deleting key ranges invalidate everything.
+    pub(crate) fn all(&mut self) {
+        self.compute_graphs = true;
+        self.all_invocation_ctxs = true;
+    }
+
+    fn invocation_ctx(&mut self, namespace: &str, compute_graph: &str, invocation_id: &str) {
+        self.invocation_ctxs.insert(GraphInvocationCtx::key_from(
+            namespace,
+            compute_graph,
+            invocation_id,
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_eviction_and_invalidation() {
+        let cache: Cache<u32> = Cache::new(2);
+        let load = |value: u32| move || Ok(Some(value));
+        assert_eq!(cache.get_or_load(b"a".to_vec(), load(1)).unwrap(), Some(1));
+        assert_eq!(cache.get_or_load(b"b".to_vec(), load(2)).unwrap(), Some(2));
+        // Cached values are returned without loading them
+        assert_eq!(cache.get_or_load(b"a".to_vec(), load(10)).unwrap(), Some(1));
+        // The least recently used entry is evicted
+        assert_eq!(cache.get_or_load(b"c".to_vec(), load(3)).unwrap(), Some(3));
+        assert_eq!(cache.get_or_load(b"b".to_vec(), load(20)).unwrap(), Some(20));
+        assert_eq!(cache.get_or_load(b"a".to_vec(), load(10)).unwrap(), Some(10));
+
+        cache.invalidate([&b"a".to_vec()]);
+        assert_eq!(cache.get_or_load(b"a".to_vec(), load(100)).unwrap(), Some(100));
+        // Missing values aren't cached
+        assert_eq!(cache.get_or_load(b"d".to_vec(), || Ok(None)).unwrap(), None);
+        assert_eq!(cache.get_or_load(b"d".to_vec(), load(4)).unwrap(), Some(4));
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses), (1, 8));
+        assert_eq!(stats.entries, 2);
+    }
+
+    #[test]
+    fn test_read_racing_with_write_is_not_cached() {
+        let cache: Cache<u32> = Cache::new(2);
+        // The value is invalidated while it is read from the db
+        let value = cache
+            .get_or_load(b"a".to_vec(), || {
+                cache.invalidate([&b"a".to_vec()]);
+                Ok(Some(1))
+            })
+            .unwrap();
+        assert_eq!(value, Some(1));
+        assert_eq!(cache.get_or_load(b"a".to_vec(), || Ok(Some(2))).unwrap(), Some(2));
+    }
+}
@@ -0,0 +1,865 @@
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use futures::{Stream, StreamExt};
+use rocksdb::{Direction, IteratorMode, Transaction, TransactionDB, WriteBatchWithTransaction};
+use strum::IntoEnumIterator;
+use tokio::sync::{watch, Mutex};
+
+use crate::{
+    error::StateStoreError,
+    snapshot::{
+        dump_db,
+        read_field,
+        stream_dump,
+        write_field,
+        write_record,
+        RecordReader,
+        StateSnapshot,
+        RESTORE_BATCH_SIZE,
+    },
+    state_machine::IndexifyObjectsColumns,
+    IndexifyState,
+};
+
+/// A committed write batch of the leader, in commit order. The term is the
+/// one of the leader which wrote it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplicationEntry {
+    pub seq: u64,
+    pub term: u64,
+    pub data: Vec<u8>,
+}
+
+/// Followers of the leader which must apply its writes before they are
+/// acknowledged.
+struct Quorum {
+    followers: Vec<u64>,
+    // Followers applying a write, in addition to the leader
+    acks: usize,
+    timeout: Duration,
+    // Last seq applied by each follower in the current term
+    applied: HashMap<u64, u64>,
+}
+
+pub struct Replication {
+    // Writes are recorded in the replication log
+    log_enabled: AtomicBool,
+    // Followers only apply the leader's log and reject writes
+    read_only: AtomicBool,
+    // Highest term seen, entries of leaders of older terms are stale
+    term: AtomicU64,
+    // Seq and term of the last entry of the log
+    last_entry: watch::Sender<(u64, u64)>,
+    // First entry kept in the log, the ones before were applied by every
+    // follower
+    first_seq: AtomicU64,
+    // Makes the log order match the commit order
+    commit_lock: Mutex<()>,
+    quorum: std::sync::Mutex<Option<Quorum>>,
+    // Last seq applied by a majority of the cluster
+    acked_seq: watch::Sender<u64>,
+    // Term of the last vote of this node and the node voted for
+    vote: std::sync::Mutex<Option<(u64, u64)>>,
+    // A snapshot of the leader is partially installed
+    installing_snapshot: AtomicBool,
+}
+
+// Keys of the ReplicationState column family
+const VOTE_KEY: &[u8] = b"vote";
+const INSTALLING_SNAPSHOT_KEY: &[u8] = b"installing_snapshot";
+
+impl Replication {
+    pub(crate) fn new(db: &TransactionDB) -> Result<Self> {
+        let cf = IndexifyObjectsColumns::ReplicationLog.cf_db(db);
+        let (last_seq, last_term) = match db.iterator_cf(&cf, IteratorMode::End).next() {
+            Some(kv) => {
+                let (key, value) = kv?;
+                (decode_seq(&key)?, decode_log_value(&value)?.0)
+            }
+            None => (0, 0),
+        };
+        let first_seq = match db.iterator_cf(&cf, IteratorMode::Start).next() {
+            Some(kv) => decode_seq(&kv?.0)?,
+            None => 0,
+        };
+        let state_cf = IndexifyObjectsColumns::ReplicationState.cf_db(db);
+        let vote = db
+            .get_cf(&state_cf, VOTE_KEY)?
+            .map(|value| decode_vote(&value))
+            .transpose()?;
+        let installing_snapshot = db.get_cf(&state_cf, INSTALLING_SNAPSHOT_KEY)?.is_some();
+        Ok(Self {
+            log_enabled: AtomicBool::new(false),
+            read_only: AtomicBool::new(false),
+            // A node never votes again in a term it voted in
+            term: AtomicU64::new(vote.map_or(last_term, |(term, _)| term.max(last_term))),
+            last_entry: watch::Sender::new((last_seq, last_term)),
+            first_seq: AtomicU64::new(first_seq),
+            commit_lock: Mutex::new(()),
+            quorum: std::sync::Mutex::new(None),
+            acked_seq: watch::Sender::new(last_seq),
+            vote: std::sync::Mutex::new(vote),
+            installing_snapshot: AtomicBool::new(installing_snapshot),
+        })
+    }
+}
+
+impl IndexifyState {
+    pub fn enable_replication_log(&self) {
+        self.replication.log_enabled.store(true, Ordering::Relaxed);
+    }
+
+    /// Writes of the leader are acknowledged once a majority of the cluster
+    /// applied them, and fail with `NotAcknowledged` after `timeout`.
+    /// Replication is asynchronous, the leader applies a write before its
+    /// followers do, and doesn't roll it back when they don't in time.
+    pub fn set_replication_quorum(&self, followers: Vec<u64>, timeout: Duration) {
+        let acks = (followers.len() + 1) / 2;
+        *self.replication.quorum.lock().unwrap() = Some(Quorum {
+            followers,
+            acks,
+            timeout,
+            applied: HashMap::new(),
+        });
+    }
+
+    pub fn set_read_only(&self, read_only: bool) {
+        self.replication
+            .read_only
+            .store(read_only, Ordering::Relaxed);
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.replication.read_only.load(Ordering::Relaxed)
+    }
+
+    pub fn last_replicated_seq(&self) -> u64 {
+        self.replication.last_entry.borrow().0
+    }
+
+    pub fn last_replicated_term(&self) -> u64 {
+        self.replication.last_entry.borrow().1
+    }
+
+    pub fn replication_term(&self) -> u64 {
+        self.replication.term.load(Ordering::Relaxed)
+    }
+
+    /// Records a term seen in the cluster, the entries of leaders of older
+    /// terms are rejected from then on.
+    pub fn observe_replication_term(&self, term: u64) {
+        self.replication.term.fetch_max(term, Ordering::Relaxed);
+    }
+
+    /// First entry kept in the replication log.
+    pub fn replication_log_start(&self) -> u64 {
+        self.replication.first_seq.load(Ordering::Relaxed)
+    }
+
+    /// The installation of a snapshot of the leader was interrupted, the
+    /// state is partial until a snapshot is installed again.
+    pub fn is_installing_replication_snapshot(&self) -> bool {
+        self.replication.installing_snapshot.load(Ordering::Relaxed)
+    }
+
+    /// Starts an election of `node_id` in the next term, in which it votes
+    /// for itself. Returns the term of the election.
+    pub async fn start_election(&self, node_id: u64) -> Result<u64> {
+        let _guard = self.replication.commit_lock.lock().await;
+        let term = self.replication_term() + 1;
+        self.persist_vote(term, node_id)?;
+        self.observe_replication_term(term);
+        Ok(term)
+    }
+
+    /// Votes for `candidate` in `term`, unless this node voted for another
+    /// candidate in the term or its log is more up to date than the
+    /// candidate's, whose last entry is `last_log`, as `(term, seq)`. Returns
+    /// the term of this node and whether the vote is granted.
+    ///
+    /// A pre-vote only tells whether the vote would be granted in `term`,
+    /// without recording the term or the vote, so that a candidate which
+    /// can't win doesn't make the cluster move to a new term.
+    pub async fn request_vote(
+        &self,
+        term: u64,
+        candidate: u64,
+        last_log: (u64, u64),
+        pre_vote: bool,
+    ) -> Result<(u64, bool)> {
+        let _guard = self.replication.commit_lock.lock().await;
+        let current_term = self.replication_term();
+        // The log of a partial state can't be compared with the candidate's
+        if self.is_installing_replication_snapshot() {
+            return Ok((current_term, false));
+        }
+        let local_log = (self.last_replicated_term(), self.last_replicated_seq());
+        if pre_vote {
+            return Ok((current_term, term > current_term && last_log >= local_log));
+        }
+        if term < current_term {
+            return Ok((current_term, false));
+        }
+        self.observe_replication_term(term);
+        let vote = *self.replication.vote.lock().unwrap();
+        let voted_for_other = vote
+            .is_some_and(|(vote_term, voted_for)| vote_term == term && voted_for != candidate);
+        if voted_for_other || last_log < local_log {
+            return Ok((term, false));
+        }
+        self.persist_vote(term, candidate)?;
+        Ok((term, true))
+    }
+
+    // The commit lock must be held
+    fn persist_vote(&self, term: u64, node_id: u64) -> Result<()> {
+        let mut value = Vec::with_capacity(16);
+        value.extend_from_slice(&term.to_be_bytes());
+        value.extend_from_slice(&node_id.to_be_bytes());
+        self.db.put_cf(
+            &IndexifyObjectsColumns::ReplicationState.cf_db(&self.db),
+            VOTE_KEY,
+            value,
+        )?;
+        *self.replication.vote.lock().unwrap() = Some((term, node_id));
+        Ok(())
+    }
+
+    /// Starts the term a leader was elected in with an empty entry. The
+    /// entries of the previous terms count as applied by the cluster.
+    pub async fn start_replication_term(&self, term: u64) -> Result<()> {
+        let _guard = self.replication.commit_lock.lock().await;
+        let current_term = self.replication_term();
+        let last_term = self.last_replicated_term();
+        // A newer term was seen since the election
+        if term < current_term || term <= last_term {
+            return Err(anyhow!(
+                "term {} isn't the current term {} or isn't newer than the term {} of the log",
+                term,
+                current_term,
+                last_term
+            ));
+        }
+        self.replication.term.store(term, Ordering::Relaxed);
+        if let Some(quorum) = self.replication.quorum.lock().unwrap().as_mut() {
+            quorum.applied.clear();
+        }
+        self.replication
+            .acked_seq
+            .send_replace(self.last_replicated_seq());
+        self.append_to_log(WriteBatchWithTransaction::<true>::default())
+    }
+
+    pub(crate) async fn commit(&self, txn: Transaction<'_, TransactionDB>) -> Result<Option<u64>> {
+        if !self.replication.log_enabled.load(Ordering::Relaxed) {
+            txn.commit()?;
+            return Ok(None);
+        }
+        let _guard = self.replication.commit_lock.lock().await;
+        let batch = txn.get_writebatch();
+        if batch.is_empty() {
+            txn.commit()?;
+            return Ok(None);
+        }
+        let seq = self.last_replicated_seq() + 1;
+        let term = self.replication_term();
+        txn.put_cf(
+            &IndexifyObjectsColumns::ReplicationLog.cf_db(&self.db),
+            seq.to_be_bytes(),
+            encode_log_value(term, batch.data()),
+        )?;
+        txn.commit()?;
+        self.replication.last_entry.send_replace((seq, term));
+        Ok(Some(seq))
+    }
+
+    /// Writes a batch outside of a transaction, it's recorded in the
+    /// replication log like the committed transactions.
+    pub(crate) async fn write_batch(&self, batch: WriteBatchWithTransaction<true>) -> Result<()> {
+        if !self.replication.log_enabled.load(Ordering::Relaxed) {
+            self.db.write(batch)?;
+            return Ok(());
+        }
+        let _guard = self.replication.commit_lock.lock().await;
+        self.append_to_log(batch)
+    }
+
+    // The commit lock must be held
+    fn append_to_log(&self, mut batch: WriteBatchWithTransaction<true>) -> Result<()> {
+        let seq = self.last_replicated_seq() + 1;
+        let term = self.replication_term();
+        let value = encode_log_value(term, batch.data());
+        batch.put_cf(
+            &IndexifyObjectsColumns::ReplicationLog.cf_db(&self.db),
+            seq.to_be_bytes(),
+            value,
+        );
+        self.db.write(batch)?;
+        self.replication.last_entry.send_replace((seq, term));
+        Ok(())
+    }
+
+    /// Waits for a majority of the cluster to apply the entry, which the
+    /// leader already applied. Writes are acknowledged right away when the
+    /// server isn't replicated.
+    pub(crate) async fn wait_for_replication(&self, seq: u64) -> Result<()> {
+        let timeout = match self.replication.quorum.lock().unwrap().as_ref() {
+            Some(quorum) if quorum.acks > 0 => quorum.timeout,
+            _ => return Ok(()),
+        };
+        let mut acked_rx = self.replication.acked_seq.subscribe();
+        match tokio::time::timeout(timeout, acked_rx.wait_for(|acked| *acked >= seq)).await {
+            Ok(Ok(_)) => Ok(()),
+            _ => Err(StateStoreError::NotAcknowledged.into()),
+        }
+    }
+
+    /// Records the last entry applied by a follower. Entries applied by
+    /// every follower are truncated from the log.
+    pub fn record_follower_seq(&self, node_id: u64, seq: u64) -> Result<()> {
+        let (acked, applied_by_all) = {
+            let mut quorum = self.replication.quorum.lock().unwrap();
+            let Some(quorum) = quorum.as_mut() else {
+                return Ok(());
+            };
+            if !quorum.followers.contains(&node_id) {
+                return Err(anyhow!("node {} isn't a follower of the cluster", node_id));
+            }
+            let applied = quorum.applied.entry(node_id).or_default();
+            *applied = (*applied).max(seq);
+            let mut applied: Vec<u64> = quorum.applied.values().copied().collect();
+            applied.sort_unstable_by(|a, b| b.cmp(a));
+            let acked = quorum
+                .acks
+                .checked_sub(1)
+                .and_then(|i| applied.get(i).copied());
+            let applied_by_all = applied
+                .last()
+                .copied()
+                .filter(|_| applied.len() == quorum.followers.len());
+            (acked, applied_by_all)
+        };
+        if let Some(acked) = acked {
+            self.replication.acked_seq.send_if_modified(|current| {
+                if acked <= *current {
+                    return false;
+                }
+                *current = acked;
+                true
+            });
+        }
+        if let Some(seq) = applied_by_all {
+            self.truncate_replication_log(seq)?;
+        }
+        Ok(())
+    }
+
+    /// Drops the entries of the log before `seq`. The last entry is always
+    /// kept, its term is compared with the leader's log.
+    pub fn truncate_replication_log(&self, seq: u64) -> Result<()> {
+        let seq = seq.min(self.last_replicated_seq());
+        let first_seq = self.replication_log_start();
+        if seq <= first_seq {
+            return Ok(());
+        }
+        let mut batch = WriteBatchWithTransaction::<true>::default();
+        batch.delete_range_cf(
+            &IndexifyObjectsColumns::ReplicationLog.cf_db(&self.db),
+            first_seq.to_be_bytes(),
+            seq.to_be_bytes(),
+        );
+        self.db.write(batch)?;
+        self.replication.first_seq.store(seq, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Waits up to `timeout` for entries to be committed after `after`.
+    pub async fn wait_for_replication_log(&self, after: u64, timeout: Duration) {
+        let mut last_entry_rx = self.replication.last_entry.subscribe();
+        let committed = last_entry_rx.wait_for(|(seq, _)| *seq > after);
+        let _ = tokio::time::timeout(timeout, committed).await;
+    }
+
+    /// Returns up to `limit` log entries committed after `after`. The entry
+    /// at `after` must have `after_term`, the log of the follower diverged
+    /// from the leader's otherwise. Returns None when the follower's log
+    /// diverged or the entries it misses were truncated, it must install a
+    /// snapshot of the leader's state to catch up.
+    pub fn replication_log(
+        &self,
+        after: u64,
+        after_term: Option<u64>,
+        limit: usize,
+    ) -> Result<Option<Vec<ReplicationEntry>>> {
+        let cf = IndexifyObjectsColumns::ReplicationLog.cf_db(&self.db);
+        if after == 0 && self.replication_log_start() > 1 {
+            return Ok(None);
+        }
+        if let Some(after_term) = after_term.filter(|_| after > 0) {
+            let Some(value) = self.db.get_cf(&cf, after.to_be_bytes())? else {
+                return Ok(None);
+            };
+            if decode_log_value(&value)?.0 != after_term {
+                return Ok(None);
+            }
+        }
+        let start = (after + 1).to_be_bytes();
+        let iter = self
+            .db
+            .iterator_cf(&cf, IteratorMode::From(&start, Direction::Forward));
+        let mut entries = Vec::new();
+        for kv in iter.take(limit) {
+            let (key, value) = kv?;
+            let (term, data) = decode_log_value(&value)?;
+            entries.push(ReplicationEntry {
+                seq: decode_seq(&key)?,
+                term,
+                data: data.to_vec(),
+            });
+        }
+        Ok(Some(entries))
+    }
+
+    /// Dump of the state for followers which can't catch up with the log, in
+    /// the format of `create_snapshot`. The replication log is reduced to its
+    /// last entry, which the log of the follower continues from.
+    pub fn create_replication_snapshot(&self) -> StateSnapshot {
+        let db = self.db.clone();
+        stream_dump(move |out| {
+            let snapshot = db.snapshot();
+            let include = |column: &IndexifyObjectsColumns| {
+                !matches!(
+                    column,
+                    IndexifyObjectsColumns::ReplicationLog |
+                        IndexifyObjectsColumns::ReplicationState
+                )
+            };
+            let manifest = dump_db(&db, &snapshot, "replication", include, out)?;
+            let log = IndexifyObjectsColumns::ReplicationLog;
+            if let Some(kv) = snapshot.iterator_cf(&log.cf_db(&db), IteratorMode::End).next() {
+                let (key, value) = kv?;
+                write_record(out, log.as_ref().as_bytes(), &key, &value)?;
+            }
+            Ok(manifest)
+        })
+    }
+
+    /// Replaces the state with a snapshot of the leader of `term`, created by
+    /// `create_replication_snapshot`. An interrupted installation leaves a
+    /// marker, the node then installs a snapshot again before replicating or
+    /// voting.
+    pub async fn install_replication_snapshot<B: AsRef<[u8]>>(
+        &self,
+        term: u64,
+        mut data: impl Stream<Item = Result<B>> + Unpin,
+    ) -> Result<()> {
+        let _guard = self.replication.commit_lock.lock().await;
+        let current_term = self.replication_term();
+        if term < current_term {
+            return Err(anyhow!(
+                "stale snapshot of term {}, the current term is {}",
+                term,
+                current_term
+            ));
+        }
+        self.observe_replication_term(term);
+        self.db.put_cf(
+            &IndexifyObjectsColumns::ReplicationState.cf_db(&self.db),
+            INSTALLING_SNAPSHOT_KEY,
+            [],
+        )?;
+        self.replication
+            .installing_snapshot
+            .store(true, Ordering::Relaxed);
+        self.read_cache.clear();
+        for column in IndexifyObjectsColumns::iter() {
+            if matches!(column, IndexifyObjectsColumns::ReplicationState) {
+                continue;
+            }
+            let cf = column.cf_db(&self.db);
+            let mut batch = WriteBatchWithTransaction::<true>::default();
+            for kv in self.db.iterator_cf(&cf, IteratorMode::Start) {
+                batch.delete_cf(&cf, kv?.0);
+                if batch.len() >= RESTORE_BATCH_SIZE {
+                    self.db.write(std::mem::take(&mut batch))?;
+                }
+            }
+            self.db.write(batch)?;
+        }
+
+        let log = IndexifyObjectsColumns::ReplicationLog;
+        let mut reader = RecordReader::default();
+        let mut batch = WriteBatchWithTransaction::<true>::default();
+        let mut last_entry = None;
+        while let Some(chunk) = data.next().await {
+            reader.push(chunk?.as_ref());
+            while let Some((column, key, value)) = reader.next_record()? {
+                if column == IndexifyObjectsColumns::ReplicationState.as_ref() {
+                    return Err(anyhow!("snapshot of the leader has a replication state"));
+                }
+                if column == log.as_ref() {
+                    last_entry = Some((decode_seq(&key)?, decode_log_value(&value)?.0));
+                }
+                let cf = self
+                    .db
+                    .cf_handle(&column)
+                    .ok_or(anyhow!("unknown column family in snapshot: {}", column))?;
+                batch.put_cf(&cf, key, value);
+                if batch.len() >= RESTORE_BATCH_SIZE {
+                    self.db.write(std::mem::take(&mut batch))?;
+                }
+            }
+        }
+        reader.finish()?;
+        let (seq, last_term) =
+            last_entry.ok_or(anyhow!("snapshot of the leader has no replication log entry"))?;
+        batch.delete_cf(
+            &IndexifyObjectsColumns::ReplicationState.cf_db(&self.db),
+            INSTALLING_SNAPSHOT_KEY,
+        );
+        self.db.write(batch)?;
+        self.replication.first_seq.store(seq, Ordering::Relaxed);
+        self.replication.last_entry.send_replace((seq, last_term));
+        self.replication.acked_seq.send_replace(seq);
+        self.replication
+            .installing_snapshot
+            .store(false, Ordering::Relaxed);
+        self.read_cache.clear();
+        Ok(())
+    }
+
+    /// Applies log entries received from the leader of `term`, entries must
+    /// be contiguous with the local log. Entries of a leader of an older term
+    /// than the ones seen are rejected.
+    pub async fn apply_replication_log(
+        &self,
+        term: u64,
+        entries: Vec<ReplicationEntry>,
+    ) -> Result<()> {
+        let _guard = self.replication.commit_lock.lock().await;
+        let current_term = self.replication_term();
+        if term < current_term {
+            return Err(anyhow!(
+                "stale replication log of term {}, the current term is {}",
+                term,
+                current_term
+            ));
+        }
+        self.observe_replication_term(term);
+        let cf = IndexifyObjectsColumns::ReplicationLog.cf_db(&self.db);
+        for entry in entries {
+            let (last_seq, last_term) = *self.replication.last_entry.borrow();
+            let expected = last_seq + 1;
+            if entry.seq < expected {
+                continue;
+            }
+            if entry.seq > expected {
+                return Err(anyhow!(
+                    "replication log gap, expected seq {} got {}",
+                    expected,
+                    entry.seq
+                ));
+            }
+            if entry.term < last_term || entry.term > term {
+                return Err(anyhow!(
+                    "replication log entry {} of term {} doesn't follow term {} of the log",
+                    entry.seq,
+                    entry.term,
+                    last_term
+                ));
+            }
+            let mut batch = WriteBatchWithTransaction::<true>::from_data(&entry.data);
+            batch.put_cf(
+                &cf,
+                entry.seq.to_be_bytes(),
+                encode_log_value(entry.term, &entry.data),
+            );
+            self.db.write(batch)?;
+            self.read_cache.clear();
+            self.replication
+                .last_entry
+                .send_replace((entry.seq, entry.term));
+        }
+        Ok(())
+    }
+}
+
+/// Encodes the entries served by the leader of `term`.
+pub fn encode_entries(term: u64, entries: &[ReplicationEntry]) -> Vec<u8> {
+    let mut data = Vec::new();
+    write_field(&mut data, &term.to_be_bytes());
+    for entry in entries {
+        write_field(&mut data, &entry.seq.to_be_bytes());
+        write_field(&mut data, &entry.term.to_be_bytes());
+        write_field(&mut data, &entry.data);
+    }
+    data
+}
+
+/// Decodes the entries and the term of the leader which served them.
+pub fn decode_entries(data: &[u8]) -> Result<(u64, Vec<ReplicationEntry>)> {
+    let mut entries = Vec::new();
+    let mut cursor = data;
+    let term = decode_seq(read_field(&mut cursor)?)?;
+    while !cursor.is_empty() {
+        let seq = decode_seq(read_field(&mut cursor)?)?;
+        let term = decode_seq(read_field(&mut cursor)?)?;
+        let data = read_field(&mut cursor)?.to_vec();
+        entries.push(ReplicationEntry { seq, term, data });
+    }
+    Ok((term, entries))
+}
+
+// Log values are the term followed by the write batch
+fn encode_log_value(term: u64, data: &[u8]) -> Vec<u8> {
+    let mut value = Vec::with_capacity(8 + data.len());
+    value.extend_from_slice(&term.to_be_bytes());
+    value.extend_from_slice(data);
+    value
+}
+
+fn decode_log_value(value: &[u8]) -> Result<(u64, &[u8])> {
+    if value.len() < 8 {
+        return Err(anyhow!("invalid replication log entry"));
+    }
+    let (term, data) = value.split_at(8);
+    Ok((decode_seq(term)?, data))
+}
+
+fn decode_vote(value: &[u8]) -> Result<(u64, u64)> {
+    if value.len() != 16 {
+        return Err(anyhow!("invalid replication vote"));
+    }
+    let (term, node_id) = value.split_at(8);
+    Ok((decode_seq(term)?, decode_seq(node_id)?))
+}
+
+fn decode_seq(key: &[u8]) -> Result<u64> {
+    Ok(u64::from_be_bytes(
+        key.try_into()
+            .map_err(|_| anyhow!("invalid replication log key"))?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use data_model::test_objects::tests::{mock_graph_a, TEST_NAMESPACE};
+
+    use super::*;
+    use crate::requests::{
+        CreateComputeGraphRequest,
+        NamespaceRequest,
+        RequestPayload,
+        StateMachineUpdateRequest,
+    };
+
+    fn create_namespace(name: &str) -> StateMachineUpdateRequest {
+        StateMachineUpdateRequest {
+            payload: RequestPayload::CreateNameSpace(NamespaceRequest {
+                name: name.to_string(),
+                queue_limits: Default::default(),
+                scheduling_weight: None,
+                reserved_capacity: None,
+                replication: None,
+            }),
+            state_changes_processed: vec![],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replicate_writes_to_follower() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let leader = IndexifyState::new(temp_dir.path().join("leader")).await?;
+        let follower = IndexifyState::new(temp_dir.path().join("follower")).await?;
+        leader.enable_replication_log();
+        follower.enable_replication_log();
+        follower.set_read_only(true);
+        leader.start_replication_term(1).await?;
+
+        leader.write(create_namespace(TEST_NAMESPACE)).await?;
+        leader
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: mock_graph_a(),
+                    expected_revision: None,
+                    force: false,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        assert_eq!(leader.last_replicated_seq(), 3);
+
+        // Ship the log over the wire format
+        let data = encode_entries(1, &leader.replication_log(0, None, 10)?.unwrap());
+        let (term, entries) = decode_entries(&data)?;
+        assert_eq!(entries.len(), 3);
+        assert!(entries.iter().all(|entry| entry.term == 1));
+        follower.apply_replication_log(term, entries).await?;
+        assert_eq!(follower.last_replicated_seq(), 3);
+        assert_eq!(follower.last_replicated_term(), 1);
+        let (graphs, _) = follower
+            .reader()
+            .list_compute_graphs(TEST_NAMESPACE, None, None)?;
+        assert_eq!(graphs.len(), 1);
+
+        // Entries are applied only once
+        follower
+            .apply_replication_log(1, leader.replication_log(1, Some(1), 10)?.unwrap())
+            .await?;
+        assert_eq!(follower.last_replicated_seq(), 3);
+
+        // Followers reject local writes
+        assert!(follower.write(create_namespace("other")).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reject_stale_entries() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let old_leader = IndexifyState::new(temp_dir.path().join("old_leader")).await?;
+        let follower = IndexifyState::new(temp_dir.path().join("follower")).await?;
+        old_leader.enable_replication_log();
+        follower.enable_replication_log();
+        old_leader.start_replication_term(1).await?;
+        old_leader.write(create_namespace(TEST_NAMESPACE)).await?;
+        follower
+            .apply_replication_log(1, old_leader.replication_log(0, None, 10)?.unwrap())
+            .await?;
+
+        // The follower saw the election of a newer leader, the old leader's
+        // writes are stale
+        follower.observe_replication_term(2);
+        old_leader.write(create_namespace("other")).await?;
+        let entries = old_leader.replication_log(2, Some(1), 10)?.unwrap();
+        assert!(follower.apply_replication_log(1, entries).await.is_err());
+        assert_eq!(follower.last_replicated_seq(), 2);
+
+        // A log which diverged from the leader's is detected on fetch
+        follower.start_replication_term(3).await?;
+        assert_eq!(old_leader.replication_log(3, Some(3), 10)?, None);
+        assert!(follower.start_replication_term(3).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_vote_once_per_term() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        state.enable_replication_log();
+        state.start_replication_term(1).await?;
+        state.write(create_namespace(TEST_NAMESPACE)).await?;
+
+        // Candidates whose log is behind aren't elected
+        assert_eq!(state.request_vote(2, 1, (1, 1), false).await?, (2, false));
+        assert_eq!(state.request_vote(2, 1, (1, 2), false).await?, (2, true));
+        assert_eq!(state.request_vote(2, 1, (1, 2), false).await?, (2, true));
+        assert_eq!(state.request_vote(2, 2, (1, 2), false).await?, (2, false));
+        assert_eq!(state.request_vote(1, 2, (1, 2), false).await?, (2, false));
+
+        // Pre-votes record nothing
+        assert_eq!(state.request_vote(3, 2, (1, 2), true).await?, (2, true));
+        assert_eq!(state.replication_term(), 2);
+
+        // A node votes for itself when it starts an election, the vote is
+        // loaded back on restart
+        assert_eq!(state.start_election(0).await?, 3);
+        assert_eq!(state.request_vote(3, 2, (1, 2), false).await?, (3, false));
+        let cf = IndexifyObjectsColumns::ReplicationState.cf_db(&state.db);
+        let vote = state.db.get_cf(&cf, VOTE_KEY)?.unwrap();
+        assert_eq!(decode_vote(&vote)?, (3, 0));
+        state.start_replication_term(3).await?;
+        assert_eq!(state.last_replicated_term(), 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_quorum_and_truncation() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let leader = IndexifyState::new(temp_dir.path().join("leader")).await?;
+        leader.enable_replication_log();
+        leader.set_replication_quorum(vec![1, 2], Duration::from_millis(100));
+        leader.start_replication_term(1).await?;
+
+        // Writes fail when no follower applies them
+        let err = leader
+            .write(create_namespace(TEST_NAMESPACE))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<StateStoreError>(),
+            Some(StateStoreError::NotAcknowledged)
+        ));
+
+        // One follower out of two makes a majority of the cluster
+        leader.set_replication_quorum(vec![1, 2], Duration::from_secs(10));
+        let write = tokio::spawn({
+            let leader = leader.clone();
+            async move { leader.write(create_namespace("other")).await }
+        });
+        leader.wait_for_replication_log(2, Duration::from_secs(10)).await;
+        leader.record_follower_seq(1, 3)?;
+        write.await??;
+        assert!(leader.record_follower_seq(3, 3).is_err());
+
+        // Entries are truncated once every follower applied them
+        assert_eq!(leader.replication_log_start(), 0);
+        leader.record_follower_seq(2, 2)?;
+        assert_eq!(leader.replication_log_start(), 2);
+        assert_eq!(leader.replication_log(2, Some(1), 10)?.unwrap()[0].seq, 3);
+        // Followers missing truncated entries install a snapshot
+        assert_eq!(leader.replication_log(0, None, 10)?, None);
+        leader.record_follower_seq(2, 3)?;
+        assert_eq!(leader.replication_log(3, Some(1), 10)?.unwrap().len(), 0);
+        assert_eq!(leader.last_replicated_seq(), 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_install_replication_snapshot() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let leader = IndexifyState::new(temp_dir.path().join("leader")).await?;
+        let follower = IndexifyState::new(temp_dir.path().join("follower")).await?;
+        leader.enable_replication_log();
+        follower.enable_replication_log();
+        leader.start_replication_term(2).await?;
+        leader.write(create_namespace(TEST_NAMESPACE)).await?;
+
+        // The follower has writes of a former leader which were never
+        // replicated
+        follower.start_replication_term(1).await?;
+        follower.write(create_namespace("diverged")).await?;
+        assert_eq!(leader.replication_log(2, Some(1), 10)?, None);
+
+        // Snapshots of leaders of older terms are stale
+        let stale = futures::stream::iter(Vec::<Result<Vec<u8>>>::new());
+        assert!(follower
+            .install_replication_snapshot(0, stale)
+            .await
+            .is_err());
+
+        let snapshot = leader.create_replication_snapshot();
+        let chunks: Vec<Result<Vec<u8>>> = snapshot.data.collect().await;
+        snapshot.manifest.await??;
+        follower
+            .install_replication_snapshot(2, futures::stream::iter(chunks))
+            .await?;
+        assert!(!follower.is_installing_replication_snapshot());
+        assert_eq!(follower.last_replicated_seq(), 2);
+        assert_eq!(follower.last_replicated_term(), 2);
+        assert!(follower.reader().get_namespace(TEST_NAMESPACE)?.is_some());
+        assert!(follower.reader().get_namespace("diverged")?.is_none());
+
+        // The follower continues from the log of the leader
+        leader.write(create_namespace("other")).await?;
+        let entries = leader.replication_log(2, Some(2), 10)?.unwrap();
+        follower.apply_replication_log(2, entries).await?;
+        assert!(follower.reader().get_namespace("other")?.is_some());
+        Ok(())
+    }
+}
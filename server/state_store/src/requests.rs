@@ -1,13 +1,35 @@
+use std::collections::{HashMap, HashSet};
+
 use data_model::{
+    alerts::AlertStatus,
+    blacklist::ExecutorBlacklistEntry,
+    data_deletion::DataDeletionMode,
+    executor_approval::ExecutorApproval,
+    executor_reconciliation::ReportedTask,
+    invocation_group::InvocationGroup,
+    latency::FunctionLatency,
+    sla::SlaStatus,
+    webhooks::{DataRejection, Webhook, WebhookDelivery},
     ComputeGraph,
+    DataPayload,
     ExecutorId,
     ExecutorMetadata,
+    ExecutorPool,
+    FanOutCursor,
     GraphVersion,
     InvocationPayload,
     NodeOutput,
+    ParentInvocation,
+    PayloadReplica,
+    PendingUpload,
+    QueueLimits,
     ReduceTask,
+    ReplicationPolicy,
+    ReservedCapacity,
+    ResourceUsage,
     StateChangeId,
     Task,
+    TaskCheckpoint,
     TaskDiagnostics,
     TaskId,
 };
@@ -22,16 +44,308 @@ pub enum RequestPayload {
     RerunComputeGraph(RerunComputeGraphRequest),
     RerunInvocation(RerunInvocationRequest),
     FinalizeTask(FinalizeTaskRequest),
+    FinalizeTasks(FinalizeTasksRequest),
+    StreamTaskOutputs(StreamTaskOutputsRequest),
+    CheckpointTask(CheckpointTaskRequest),
     CreateNameSpace(NamespaceRequest),
+    DeleteNamespace(DeleteNamespaceRequest),
+    PurgeNamespace(DeleteNamespaceRequest),
     CreateComputeGraph(CreateComputeGraphRequest),
+    ApplyManifest(ApplyManifestRequest),
+    CreateGraphCanary(CreateGraphCanaryRequest),
+    UpdateGraphCanary(UpdateGraphCanaryRequest),
+    FinalizeGraphCanary(GraphCanaryRequest),
+    RollbackGraphCanary(GraphCanaryRequest),
     DeleteComputeGraph(DeleteComputeGraphRequest),
+    UndeleteComputeGraph(DeleteComputeGraphRequest),
+    PurgeComputeGraph(DeleteComputeGraphRequest),
     DeleteInvocation(DeleteInvocationRequest),
+    DeleteIngestedData(DeleteIngestedDataRequest),
+    CreateInvocationGroup(CreateInvocationGroupRequest),
+    CloseInvocationGroup(CloseInvocationGroupRequest),
     SchedulerUpdate(SchedulerUpdateRequest),
     RegisterExecutor(RegisterExecutorRequest),
     DeregisterExecutor(DeregisterExecutorRequest),
+    RequeueExecutorTasks(RequeueExecutorTasksRequest),
+    ReconcileExecutorTasks(ReconcileExecutorTasksRequest),
+    UpdateExecutorArtifacts(UpdateExecutorArtifactsRequest),
+    UpdateExecutorLabels(UpdateExecutorLabelsRequest),
+    CreateExecutorPool(CreateExecutorPoolRequest),
+    DeleteExecutorPool(DeleteExecutorPoolRequest),
     RemoveGcUrls(Vec<String>),
     UpdateSystemTask(UpdateSystemTaskRequest),
     RemoveSystemTask(RemoveSystemTaskRequest),
+    SetInvocationState(SetInvocationStateRequest),
+    CreateWebhook(CreateWebhookRequest),
+    DeleteWebhook(DeleteWebhookRequest),
+    UpdateWebhookDeliveries(UpdateWebhookDeliveriesRequest),
+    RedriveWebhookDeadLetters(RedriveWebhookDeadLettersRequest),
+    MovePayloads(MovePayloadsRequest),
+    RecordPayloadReplicas(RecordPayloadReplicasRequest),
+    RecordSlaStatus(RecordSlaStatusRequest),
+    RecordAlertStatuses(RecordAlertStatusesRequest),
+    RecordDataRejection(RecordDataRejectionRequest),
+    RecordFunctionLatencies(RecordFunctionLatenciesRequest),
+    SignalInvocation(SignalInvocationRequest),
+    CloseWindow(CloseWindowRequest),
+    RegisterPendingUploads(RegisterPendingUploadsRequest),
+    SweepPendingUploads(SweepPendingUploadsRequest),
+    PauseComputeGraph(PauseComputeGraphRequest),
+    ResumeComputeGraph(ResumeComputeGraphRequest),
+    BlacklistExecutors(BlacklistExecutorsRequest),
+    RemoveExecutorBlacklistEntry(RemoveExecutorBlacklistEntryRequest),
+    RecordExecutorApproval(RecordExecutorApprovalRequest),
+    AcknowledgeAborts(AcknowledgeAbortsRequest),
+    SetLegalHold(LegalHoldRequest),
+    ClearLegalHold(LegalHoldRequest),
+}
+
+impl RequestPayload {
+    /// Namespace the request adds user data to, such requests are rejected
+    /// while the namespace is being deleted.
+    pub fn written_namespace(&self) -> Option<&str> {
+        match self {
+            RequestPayload::InvokeComputeGraph(req) => Some(&req.namespace),
+            RequestPayload::RerunComputeGraph(req) => Some(&req.namespace),
+            RequestPayload::RerunInvocation(req) => Some(&req.namespace),
+            RequestPayload::CreateNameSpace(req) => Some(&req.name),
+            RequestPayload::CreateComputeGraph(req) => Some(&req.namespace),
+            RequestPayload::ApplyManifest(req) => Some(&req.namespace),
+            RequestPayload::CreateGraphCanary(req) => Some(&req.namespace),
+            RequestPayload::UndeleteComputeGraph(req) => Some(&req.namespace),
+            RequestPayload::CreateExecutorPool(req) => Some(&req.pool.namespace),
+            RequestPayload::SetInvocationState(req) => Some(&req.namespace),
+            RequestPayload::CreateInvocationGroup(req) => Some(&req.group.namespace),
+            RequestPayload::CloseInvocationGroup(req) => Some(&req.namespace),
+            RequestPayload::CreateWebhook(req) => Some(&req.webhook.namespace),
+            RequestPayload::RedriveWebhookDeadLetters(req) => Some(&req.namespace),
+            RequestPayload::SignalInvocation(req) => Some(&req.namespace),
+            RequestPayload::SetLegalHold(req) => Some(&req.namespace),
+            _ => None,
+        }
+    }
+
+    /// Requests made on behalf of users, as opposed to the requests of the
+    /// executors and of the background tasks of the server.
+    pub fn is_user_write(&self) -> bool {
+        self.written_namespace().is_some() ||
+            matches!(
+                self,
+                RequestPayload::DeleteNamespace(_) |
+                    RequestPayload::UpdateGraphCanary(_) |
+                    RequestPayload::FinalizeGraphCanary(_) |
+                    RequestPayload::RollbackGraphCanary(_) |
+                    RequestPayload::DeleteComputeGraph(_) |
+                    RequestPayload::PauseComputeGraph(_) |
+                    RequestPayload::ResumeComputeGraph(_) |
+                    RequestPayload::DeleteInvocation(_) |
+                    RequestPayload::DeleteIngestedData(_) |
+                    RequestPayload::DeleteExecutorPool(_) |
+                    RequestPayload::DeleteWebhook(_) |
+                    RequestPayload::RemoveExecutorBlacklistEntry(_) |
+                    RequestPayload::ClearLegalHold(_)
+            )
+    }
+}
+
+/// Blobs about to be uploaded for a running task, registered before they are
+/// written so they can be deleted if the task never reports its outcome.
+#[derive(Debug, Clone)]
+pub struct RegisterPendingUploadsRequest {
+    pub uploads: Vec<PendingUpload>,
+}
+
+/// Uploads which were never committed, handed to the garbage collector
+/// unless they were committed since they were listed.
+#[derive(Debug, Clone)]
+pub struct SweepPendingUploadsRequest {
+    pub urls: Vec<String>,
+}
+
+/// Sets or clears the legal hold of an invocation, or of a whole compute
+/// graph when `invocation_id` is unset, on behalf of `identity`.
+#[derive(Debug, Clone)]
+pub struct LegalHoldRequest {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub invocation_id: Option<String>,
+    pub reason: String,
+    pub identity: String,
+}
+
+/// Stops allocating the tasks of a compute graph until it is resumed.
+#[derive(Debug, Clone)]
+pub struct PauseComputeGraphRequest {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub reject_invocations: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ResumeComputeGraphRequest {
+    pub namespace: String,
+    pub compute_graph: String,
+}
+
+/// Opens a gate of an invocation, the tasks of the gate waiting for the signal
+/// finish with the payload as their output.
+#[derive(Debug, Clone)]
+pub struct SignalInvocationRequest {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub invocation_id: String,
+    pub gate: String,
+    pub payload: DataPayload,
+}
+
+/// Closes a window of a compute graph before it's full, the inputs waiting in
+/// the window are emitted together.
+#[derive(Debug, Clone)]
+pub struct CloseWindowRequest {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub window: String,
+}
+
+/// Executors which failed the tasks of a function repeatedly, excluded from
+/// the placement of its tasks until their entries expire.
+#[derive(Debug, Clone)]
+pub struct BlacklistExecutorsRequest {
+    pub entries: Vec<ExecutorBlacklistEntry>,
+}
+
+/// Lets the tasks of the function be placed on the executor again before
+/// the entry expires.
+#[derive(Debug, Clone)]
+pub struct RemoveExecutorBlacklistEntryRequest {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub compute_fn: String,
+    pub executor_id: ExecutorId,
+}
+
+/// Latest SLA attainment of a compute graph, webhooks are notified when it
+/// becomes breached or recovers.
+#[derive(Debug, Clone)]
+pub struct RecordSlaStatusRequest {
+    pub status: SlaStatus,
+}
+
+/// Alerts evaluated by the leader, webhooks are notified when one starts or
+/// stops firing.
+#[derive(Debug, Clone)]
+pub struct RecordAlertStatusesRequest {
+    pub statuses: Vec<AlertStatus>,
+    /// Names of the rules removed from the configuration
+    pub removed: Vec<String>,
+}
+
+/// Data of a graph rejected by a guardrail, its webhooks are notified.
+#[derive(Debug, Clone)]
+pub struct RecordDataRejectionRequest {
+    pub rejection: DataRejection,
+}
+
+/// Latency sketches of compute functions, replacing the recorded ones.
+/// Sketches of graphs deleted since they were read are dropped.
+#[derive(Debug, Clone)]
+pub struct RecordFunctionLatenciesRequest {
+    pub latencies: Vec<FunctionLatency>,
+}
+
+/// Payloads copied to another storage tier, the copies replace the payloads
+/// unless they changed since they were copied.
+#[derive(Debug, Clone)]
+pub struct MovePayloadsRequest {
+    pub moves: Vec<PayloadMove>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PayloadMove {
+    pub owner: PayloadOwner,
+    pub from: DataPayload,
+    pub to: DataPayload,
+}
+
+/// Payloads copied to the replica storage of other regions, the copies are
+/// recorded on the payloads unless those changed since they were copied.
+#[derive(Debug, Clone)]
+pub struct RecordPayloadReplicasRequest {
+    pub replications: Vec<PayloadReplication>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PayloadReplication {
+    pub owner: PayloadOwner,
+    pub payload: DataPayload,
+    pub replica: PayloadReplica,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PayloadOwner {
+    // The input of an invocation
+    Invocation {
+        namespace: String,
+        compute_graph: String,
+        invocation_id: String,
+    },
+    // The output of a function, by the key of the NodeOutput
+    FnOutput {
+        key: Vec<u8>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct CreateInvocationGroupRequest {
+    pub group: InvocationGroup,
+}
+
+/// Closes an invocation group to new invocations, it finishes once all its
+/// invocations finished.
+#[derive(Debug, Clone)]
+pub struct CloseInvocationGroupRequest {
+    pub namespace: String,
+    pub id: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct CreateWebhookRequest {
+    pub webhook: Webhook,
+}
+
+/// Deletes a webhook along with its pending deliveries and dead letters.
+#[derive(Debug, Clone)]
+pub struct DeleteWebhookRequest {
+    pub namespace: String,
+    pub id: String,
+}
+
+/// Outcome of delivery attempts made by the webhook dispatcher.
+#[derive(Debug, Clone, Default)]
+pub struct UpdateWebhookDeliveriesRequest {
+    pub delivered: Vec<WebhookDelivery>,
+    // Failed deliveries to retry, with their attempts and next attempt updated
+    pub retried: Vec<WebhookDelivery>,
+    pub dead_lettered: Vec<WebhookDelivery>,
+}
+
+/// Queues the dead letters of the namespace's webhooks for delivery again.
+#[derive(Debug, Clone)]
+pub struct RedriveWebhookDeadLettersRequest {
+    pub namespace: String,
+}
+
+/// Writes a value of the scratch space of an invocation.
+#[derive(Debug, Clone)]
+pub struct SetInvocationStateRequest {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub invocation_id: String,
+    pub key: String,
+    pub value: serde_json::Value,
+    // Compare-and-swap, the write fails unless the value is at this version,
+    // 0 if it must not exist yet
+    pub expected_version: Option<u64>,
 }
 
 #[derive(Debug, Clone)]
@@ -55,7 +369,7 @@ pub struct RerunInvocationRequest {
     pub invocation_id: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct FinalizeTaskRequest {
     pub namespace: String,
     pub compute_graph: String,
@@ -66,6 +380,47 @@ pub struct FinalizeTaskRequest {
     pub task_outcome: data_model::TaskOutcome,
     pub executor_id: ExecutorId,
     pub diagnostics: Option<TaskDiagnostics>,
+    /// Contents of the outputs stored in the state store, by path
+    pub inline_payloads: Vec<InlinePayload>,
+    /// Reported by executors which measure it
+    pub resource_usage: Option<ResourceUsage>,
+}
+
+/// Outcomes of many tasks reported together, finalized in a single
+/// transaction.
+#[derive(Debug, Clone)]
+pub struct FinalizeTasksRequest {
+    pub tasks: Vec<FinalizeTaskRequest>,
+}
+
+/// Small output of a task stored in the state store instead of the blob
+/// store, referenced by the inline path of its `DataPayload`.
+#[derive(Debug, Clone)]
+pub struct InlinePayload {
+    pub path: String,
+    pub data: Vec<u8>,
+}
+
+/// Outputs a running task emitted before finishing.
+#[derive(Debug, Clone)]
+pub struct StreamTaskOutputsRequest {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub compute_fn: String,
+    pub invocation_id: String,
+    pub task_id: TaskId,
+    pub node_outputs: Vec<NodeOutput>,
+}
+
+/// Checkpoint uploaded by a running task, replacing its previous one.
+#[derive(Debug, Clone)]
+pub struct CheckpointTaskRequest {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub compute_fn: String,
+    pub invocation_id: String,
+    pub task_id: TaskId,
+    pub checkpoint: TaskCheckpoint,
 }
 
 pub struct InvokeComputeGraphRequest {
@@ -82,11 +437,53 @@ pub struct RerunComputeGraphRequest {
 
 pub struct NamespaceRequest {
     pub name: String,
+    pub queue_limits: QueueLimits,
+    pub scheduling_weight: Option<u32>,
+    pub reserved_capacity: Option<ReservedCapacity>,
+    pub replication: Option<ReplicationPolicy>,
 }
 
 pub struct CreateComputeGraphRequest {
     pub namespace: String,
     pub compute_graph: ComputeGraph,
+    // Revision of the graph the update is based on, None when creating a
+    // new graph
+    pub expected_revision: Option<u64>,
+    // Skips the revision check
+    pub force: bool,
+}
+
+/// Creates or updates several compute graphs of a namespace in one
+/// transaction, none of them is applied if any fails.
+pub struct ApplyManifestRequest {
+    pub namespace: String,
+    pub graphs: Vec<CreateComputeGraphRequest>,
+}
+
+/// Publishes a new version of a graph as a canary receiving `percent` of the
+/// new invocations.
+pub struct CreateGraphCanaryRequest {
+    pub namespace: String,
+    pub compute_graph: ComputeGraph,
+    pub expected_revision: Option<u64>,
+    pub percent: u8,
+}
+
+pub struct UpdateGraphCanaryRequest {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub percent: u8,
+}
+
+pub struct GraphCanaryRequest {
+    pub namespace: String,
+    pub compute_graph: String,
+}
+
+/// Deletes a namespace, its compute graphs are deleted and its invocations
+/// cancelled right away, the namespace is purged once their cleanup is done.
+pub struct DeleteNamespaceRequest {
+    pub namespace: String,
 }
 
 pub struct DeleteComputeGraphRequest {
@@ -105,6 +502,9 @@ pub struct CreateTasksRequest {
     pub compute_graph: String,
     pub invocation_id: String,
     pub tasks: Vec<Task>,
+    // Set when the tasks are a batch of a fan out, the cursor is removed once
+    // the fan out is done
+    pub fan_out_cursor: Option<FanOutCursor>,
 }
 
 #[derive(Debug)]
@@ -116,7 +516,7 @@ pub struct TaskPlacement {
 #[derive(Default, Debug)]
 pub struct ReductionTasks {
     pub new_reduction_tasks: Vec<ReduceTask>,
-    pub processed_reduction_tasks: Vec<String>,
+    pub processed_reduction_tasks: Vec<Vec<u8>>,
 }
 #[derive(Debug)]
 pub struct SchedulerUpdateRequest {
@@ -124,6 +524,17 @@ pub struct SchedulerUpdateRequest {
     pub allocations: Vec<TaskPlacement>,
     pub reduction_tasks: ReductionTasks,
     pub diagnostic_msgs: Vec<String>,
+    pub sub_graph_invocations: Vec<SubGraphInvocationRequest>,
+    // Running tasks whose allocations are revoked for higher priority tasks
+    pub preemptions: Vec<TaskPlacement>,
+}
+
+/// Starts the child invocation for a task of a sub graph node.
+#[derive(Debug)]
+pub struct SubGraphInvocationRequest {
+    pub parent: ParentInvocation,
+    pub compute_graph: String,
+    pub payload: DataPayload,
 }
 
 pub struct DeleteInvocationRequest {
@@ -132,10 +543,73 @@ pub struct DeleteInvocationRequest {
     pub invocation_id: String,
 }
 
+/// Deletes the data of an ingested invocation and the outputs derived from
+/// it on behalf of `identity`, its blobs are removed by `deadline`, in
+/// milliseconds since the epoch.
+#[derive(Debug, Clone)]
+pub struct DeleteIngestedDataRequest {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub invocation_id: String,
+    pub mode: DataDeletionMode,
+    pub identity: String,
+    pub deadline: u64,
+}
+
 pub struct RegisterExecutorRequest {
     pub executor: ExecutorMetadata,
 }
 
+pub struct UpdateExecutorArtifactsRequest {
+    pub executor_id: ExecutorId,
+    pub cached_artifacts: HashSet<String>,
+    pub queue_depth: u64,
+    pub max_outstanding_tasks: Option<u32>,
+}
+
+/// Replaces the labels of a registered executor, its allocated tasks which
+/// can't run on it anymore are moved back to the unallocated tasks.
+pub struct UpdateExecutorLabelsRequest {
+    pub executor_id: ExecutorId,
+    pub labels: HashMap<String, serde_json::Value>,
+}
+
 pub struct DeregisterExecutorRequest {
     pub executor_id: ExecutorId,
 }
+
+// Number of allocations read at a time when moving the tasks of an executor
+pub const DEFAULT_REQUEUE_BATCH_SIZE: usize = 1000;
+
+pub struct RequeueExecutorTasksRequest {
+    pub executor_id: ExecutorId,
+    pub batch_size: usize,
+}
+
+/// Tasks an executor reports it was running when it went down, checked
+/// against the tasks allocated to it when it registers again.
+pub struct ReconcileExecutorTasksRequest {
+    pub executor_id: ExecutorId,
+    pub reported_tasks: Vec<ReportedTask>,
+    pub restarted_at: u64,
+}
+
+pub struct CreateExecutorPoolRequest {
+    pub pool: ExecutorPool,
+}
+
+/// Records an executor awaiting approval, or the decision about it.
+pub struct RecordExecutorApprovalRequest {
+    pub approval: ExecutorApproval,
+}
+
+/// Stops delivering the abort commands of the tasks to the executor.
+pub struct AcknowledgeAbortsRequest {
+    pub executor_id: ExecutorId,
+    pub task_ids: Vec<TaskId>,
+}
+
+pub struct DeleteExecutorPoolRequest {
+    pub namespace: String,
+    pub name: String,
+}
@@ -1,27 +1,65 @@
-use std::{mem, sync::Arc};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    mem,
+    sync::Arc,
+};
 
 use anyhow::{anyhow, Result};
 use data_model::{
+    alerts::AlertStatus,
+    attributes,
+    blacklist::ExecutorBlacklistEntry,
+    canary::GraphCanary,
+    data_deletion::DataDeletion,
+    executor_approval::ExecutorApproval,
+    executor_reconciliation::ExecutorReconciliation,
+    filter::LabelsFilter,
+    invocation_group::InvocationGroup,
+    keys::{self, KeyPrefix},
+    latency::FunctionLatency,
+    legal_hold::{LegalHold, LegalHoldAuditEntry},
+    sla::{GraphSla, SlaStatus},
+    timeline::{InvocationEvent, ReplayedInvocation},
+    webhooks::{Webhook, WebhookDelivery},
+    AbortCommand,
     ComputeGraph,
+    ComputeGraphCode,
     DataPayload,
     ExecutorId,
     ExecutorMetadata,
+    ExecutorPool,
+    FanOutCursor,
     GraphInvocationCtx,
+    GraphStorageUsage,
+    GraphTaskUsage,
+    GraphVersion,
     InvocationPayload,
+    InvocationResult,
+    InvocationStateEntry,
     Namespace,
+    NamespaceDeletionProgress,
     NodeOutput,
+    PendingUpload,
     ReduceTask,
     StateChange,
+    StateChangeId,
     SystemTask,
     Task,
     TaskAnalytics,
     TaskFinishedEvent,
+    TaskId,
+    WindowItem,
 };
 use rocksdb::{Direction, IteratorMode, ReadOptions, TransactionDB};
 use serde::de::DeserializeOwned;
 
-use super::state_machine::IndexifyObjectsColumns;
-use crate::serializer::{JsonEncode, JsonEncoder};
+use super::state_machine::{pending_invocations_key, IndexifyObjectsColumns};
+use crate::{
+    error::StateStoreError,
+    prefix::prefix_read_options,
+    read_cache::ReadCache,
+    serializer::{JsonEncode, JsonEncoder},
+};
 #[derive(Debug)]
 pub struct FilterResponse<T> {
     pub items: Vec<T>,
@@ -31,11 +69,12 @@ pub struct FilterResponse<T> {
 
 pub struct StateReader {
     db: Arc<TransactionDB>,
+    cache: Arc<ReadCache>,
 }
 
 impl StateReader {
-    pub fn new(db: Arc<TransactionDB>) -> Self {
-        Self { db }
+    pub fn new(db: Arc<TransactionDB>, cache: Arc<ReadCache>) -> Self {
+        Self { db, cache }
     }
 
     pub fn get_rows_from_cf_multi_key<V>(
@@ -74,15 +113,13 @@ impl StateReader {
             .cf_handle(column.as_ref())
             .ok_or(anyhow::anyhow!("Failed to get column family {}", column))?;
 
-        let mut read_options = ReadOptions::default();
-        read_options.set_readahead_size(4_194_304);
         let iterator_mode = match restart_key {
             Some(restart_key) => IteratorMode::From(restart_key, Direction::Forward),
             None => IteratorMode::From(&key_prefix, Direction::Forward),
         };
-        let iter = self
-            .db
-            .iterator_cf_opt(&cf_handle, read_options, iterator_mode);
+        let iter =
+            self.db
+                .iterator_cf_opt(&cf_handle, prefix_read_options(key_prefix), iterator_mode);
 
         let mut items = Vec::new();
         let limit = limit.unwrap_or(usize::MAX);
@@ -111,21 +148,36 @@ impl StateReader {
     ) -> Result<(Vec<V>, Option<Vec<u8>>)>
     where
         V: DeserializeOwned,
+    {
+        self.filter_rows_from_cf_with_limits(key_prefix, restart_key, column, |_| true, limit)
+    }
+
+    /// Like get_rows_from_cf_with_limits, rows not matching the filter don't
+    /// count towards the limit.
+    pub fn filter_rows_from_cf_with_limits<V, F>(
+        &self,
+        key_prefix: &[u8],
+        restart_key: Option<&[u8]>,
+        column: IndexifyObjectsColumns,
+        filter: F,
+        limit: Option<usize>,
+    ) -> Result<(Vec<V>, Option<Vec<u8>>)>
+    where
+        V: DeserializeOwned,
+        F: Fn(&V) -> bool,
     {
         let cf_handle = self
             .db
             .cf_handle(column.as_ref())
             .ok_or(anyhow::anyhow!("Failed to get column family {}", column))?;
 
-        let mut read_options = ReadOptions::default();
-        read_options.set_readahead_size(4_194_304);
         let iterator_mode = match restart_key {
             Some(restart_key) => IteratorMode::From(restart_key, Direction::Forward),
             None => IteratorMode::From(&key_prefix, Direction::Forward),
         };
-        let iter = self
-            .db
-            .iterator_cf_opt(&cf_handle, read_options, iterator_mode);
+        let iter =
+            self.db
+                .iterator_cf_opt(&cf_handle, prefix_read_options(key_prefix), iterator_mode);
 
         let mut items = Vec::new();
         let limit = limit.unwrap_or(usize::MAX);
@@ -136,6 +188,9 @@ impl StateReader {
                 break;
             }
             let value = JsonEncoder::decode(&value).map_err(|e| anyhow::anyhow!(e.to_string()))?;
+            if !filter(&value) {
+                continue;
+            }
             if items.len() < limit {
                 items.push(value);
             } else {
@@ -312,6 +367,48 @@ impl StateReader {
         }
     }
 
+    pub fn get_pending_invocations(&self, namespace: &str, compute_graph: &str) -> Result<u64> {
+        let cf = IndexifyObjectsColumns::Stats.cf_db(&self.db);
+        let key = pending_invocations_key(namespace, compute_graph);
+        let value = self.db.get_cf(&cf, key)?;
+        match value {
+            Some(value) => {
+                let bytes: [u8; 8] = value
+                    .as_slice()
+                    .try_into()
+                    .map_err(|_| anyhow::anyhow!("Invalid length for u64 conversion"))?;
+                Ok(u64::from_be_bytes(bytes))
+            }
+            None => Ok(0),
+        }
+    }
+
+    /// Counts the unallocated tasks of a compute graph, stops counting once
+    /// the limit is reached.
+    pub fn count_unallocated_tasks(
+        &self,
+        namespace: &str,
+        compute_graph: &str,
+        limit: u64,
+    ) -> Result<u64> {
+        let prefix = KeyPrefix::encoded(&[namespace, compute_graph]);
+        let cf = IndexifyObjectsColumns::UnallocatedTasks.cf_db(&self.db);
+        let iter = self.db.iterator_cf_opt(
+            &cf,
+            prefix_read_options(prefix.as_bytes()),
+            IteratorMode::From(prefix.as_bytes(), Direction::Forward),
+        );
+        let mut count = 0;
+        for kv in iter {
+            kv?;
+            if count >= limit {
+                break;
+            }
+            count += 1;
+        }
+        Ok(count)
+    }
+
     pub fn get_diagnostic_payload(
         &self,
         ns: &str,
@@ -321,40 +418,38 @@ impl StateReader {
         file: &str,
     ) -> Result<Option<DataPayload>> {
         let key = Task::key_prefix_for_fn(ns, cg, inv_id, cg_fn);
-        println!("{}", key);
         let diagnostic = self.get_rows_from_cf_with_limits::<Task>(
-            &key.as_bytes(),
+            &key,
             None,
             IndexifyObjectsColumns::Tasks,
             None,
         )?;
         for task in diagnostic.0 {
             if let Some(diagnostics) = task.diagnostics {
-                match file {
-                    "stdout" => {
-                        if let Some(stdout) = diagnostics.stdout {
-                            return Ok(Some(stdout));
-                        }
-                    }
-                    "stderr" => {
-                        if let Some(stderr) = diagnostics.stderr {
-                            return Ok(Some(stderr));
-                        }
-                    }
-                    "exception_msg" => {
-                        if let Some(exception_msg) = diagnostics.exception {
-                            return Ok(Some(exception_msg));
-                        }
-                    }
-                    _ => {
-                        return Err(anyhow::anyhow!("Invalid file type"));
-                    }
+                if let Some(payload) = diagnostics.get(file)? {
+                    return Ok(Some(payload.clone()));
                 }
             }
         }
         Ok(None)
     }
 
+    pub fn get_task_diagnostic_payload(
+        &self,
+        ns: &str,
+        cg: &str,
+        inv_id: &str,
+        cg_fn: &str,
+        task_id: &str,
+        file: &str,
+    ) -> Result<Option<DataPayload>> {
+        let task = self.get_task(ns, cg, inv_id, cg_fn, task_id)?;
+        match task.and_then(|task| task.diagnostics) {
+            Some(diagnostics) => Ok(diagnostics.get(file)?.cloned()),
+            None => Ok(None),
+        }
+    }
+
     pub fn get_system_tasks(
         &self,
         limit: Option<usize>,
@@ -385,6 +480,37 @@ impl StateReader {
         Ok(urls)
     }
 
+    /// Urls pending deletion which sort after `after`, in key order.
+    pub fn gc_urls_after(&self, after: Option<&str>, limit: usize) -> Result<Vec<String>> {
+        let cf = IndexifyObjectsColumns::GcUrls.cf_db(&self.db);
+        let mode = match after {
+            Some(after) => IteratorMode::From(after.as_bytes(), Direction::Forward),
+            None => IteratorMode::Start,
+        };
+        let mut urls = Vec::new();
+        for kv in self.db.iterator_cf(&cf, mode) {
+            let (key, _) = kv?;
+            if after.is_some_and(|after| &*key == after.as_bytes()) {
+                continue;
+            }
+            urls.push(String::from_utf8(key.into_vec())?);
+            if urls.len() >= limit {
+                break;
+            }
+        }
+        Ok(urls)
+    }
+
+    /// Contents of a payload stored in the state store, by its inline path
+    pub fn get_inline_payload(&self, path: &str) -> Result<Option<Vec<u8>>> {
+        let cf = IndexifyObjectsColumns::InlinePayloads.cf_db(&self.db);
+        Ok(self.db.get_cf(&cf, path)?)
+    }
+
+    pub fn get_state_change(&self, id: StateChangeId) -> Result<Option<StateChange>> {
+        self.get_from_cf(&IndexifyObjectsColumns::StateChanges, id.to_key())
+    }
+
     pub fn get_unprocessed_state_changes(&self) -> Result<Vec<StateChange>> {
         let cf = IndexifyObjectsColumns::UnprocessedStateChanges.cf_db(&self.db);
         let iter = self.db.iterator_cf(&cf, IteratorMode::Start);
@@ -403,6 +529,44 @@ impl StateReader {
         Ok(state_changes)
     }
 
+    /// Number of state changes the scheduler hasn't processed yet and the
+    /// creation time of the oldest one.
+    pub fn unprocessed_state_changes_lag(&self) -> Result<(usize, Option<u64>)> {
+        let cf = IndexifyObjectsColumns::UnprocessedStateChanges.cf_db(&self.db);
+        let mut count = 0;
+        let mut oldest_created_at: Option<u64> = None;
+        for kv in self.db.iterator_cf(&cf, IteratorMode::Start) {
+            let (_, value) = kv?;
+            let state_change = JsonEncoder::decode::<StateChange>(&value)?;
+            count += 1;
+            oldest_created_at = Some(oldest_created_at.map_or(state_change.created_at, |oldest| {
+                oldest.min(state_change.created_at)
+            }));
+        }
+        Ok((count, oldest_created_at))
+    }
+
+    /// Uploads registered before `created_before` which were never committed
+    pub fn stale_pending_uploads(
+        &self,
+        created_before: u64,
+        limit: usize,
+    ) -> Result<Vec<PendingUpload>> {
+        let cf = IndexifyObjectsColumns::PendingUploads.cf_db(&self.db);
+        let mut uploads = Vec::new();
+        for kv in self.db.iterator_cf(&cf, IteratorMode::Start) {
+            let (_, value) = kv?;
+            let upload = JsonEncoder::decode::<PendingUpload>(&value)?;
+            if upload.created_at < created_before {
+                uploads.push(upload);
+                if uploads.len() >= limit {
+                    break;
+                }
+            }
+        }
+        Ok(uploads)
+    }
+
     pub fn get_all_rows_from_cf<V>(
         &self,
         column: IndexifyObjectsColumns,
@@ -439,22 +603,119 @@ impl StateReader {
         Ok(namespaces)
     }
 
+    pub fn get_namespace(&self, namespace: &str) -> Result<Option<Namespace>> {
+        self.get_from_cf(&IndexifyObjectsColumns::Namespaces, namespace)
+    }
+
+    fn count_keys_with_prefix(&self, column: IndexifyObjectsColumns, prefix: &[u8]) -> Result<u64> {
+        let cf = column.cf_db(&self.db);
+        let iter = self.db.iterator_cf_opt(
+            &cf,
+            prefix_read_options(prefix),
+            IteratorMode::From(prefix, Direction::Forward),
+        );
+        let mut count = 0;
+        for kv in iter {
+            kv?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Progress of the deletion of a namespace, `None` unless it is being
+    /// deleted.
+    pub fn namespace_deletion_progress(
+        &self,
+        namespace: &str,
+    ) -> Result<Option<NamespaceDeletionProgress>> {
+        let Some(deleted_at) = self
+            .get_namespace(namespace)?
+            .and_then(|namespace| namespace.deleted_at)
+        else {
+            return Ok(None);
+        };
+        let prefix = format!("{}|", namespace);
+        let compute_graphs = self.count_keys_with_prefix(
+            IndexifyObjectsColumns::DeletedComputeGraphs,
+            prefix.as_bytes(),
+        )?;
+        let invocations = self.count_keys_with_prefix(
+            IndexifyObjectsColumns::GraphInvocationCtx,
+            KeyPrefix::encoded(&[namespace]).as_bytes(),
+        )?;
+        Ok(Some(NamespaceDeletionProgress {
+            namespace: namespace.to_string(),
+            deleted_at,
+            compute_graphs,
+            invocations,
+        }))
+    }
+
     pub fn list_invocations(
         &self,
         namespace: &str,
         compute_graph: &str,
         cursor: Option<&[u8]>,
         limit: Option<usize>,
+        labels_filter: &LabelsFilter,
     ) -> Result<(Vec<InvocationPayload>, Option<Vec<u8>>)> {
-        let key = format!("{}|{}|", namespace, compute_graph);
-        self.get_rows_from_cf_with_limits::<InvocationPayload>(
+        let key = KeyPrefix::encoded(&[namespace, compute_graph]);
+        self.filter_rows_from_cf_with_limits::<InvocationPayload, _>(
             key.as_bytes(),
             cursor,
             IndexifyObjectsColumns::GraphInvocations,
+            |invocation| labels_filter.matches(&invocation.labels),
             limit,
         )
     }
 
+    /// Invocations whose attributes match all the conditions of the filter,
+    /// in the order of their ids, starting after the cursor.
+    pub fn search_invocations(
+        &self,
+        namespace: &str,
+        compute_graph: &str,
+        filter: &LabelsFilter,
+        cursor: Option<&str>,
+        limit: usize,
+    ) -> Result<(Vec<InvocationPayload>, Option<String>)> {
+        let cf = IndexifyObjectsColumns::InvocationAttributes.cf_db(&self.db);
+        let mut matching: Option<BTreeSet<String>> = None;
+        for expression in attributes::expressions(filter.conditions())? {
+            let mut ids = HashSet::new();
+            for (start, end) in attributes::key_ranges(namespace, compute_graph, expression)? {
+                let mut read_options = ReadOptions::default();
+                read_options.set_iterate_upper_bound(end);
+                let iter = self.db.iterator_cf_opt(
+                    &cf,
+                    read_options,
+                    IteratorMode::From(&start, Direction::Forward),
+                );
+                for kv in iter {
+                    let (_, id) = kv?;
+                    ids.insert(String::from_utf8(id.to_vec())?);
+                }
+            }
+            matching = Some(match matching {
+                Some(matching) => matching.into_iter().filter(|id| ids.contains(id)).collect(),
+                None => ids.into_iter().collect(),
+            });
+        }
+        let mut ids = matching
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|id| cursor.map_or(true, |cursor| id.as_str() > cursor));
+        let mut invocations = Vec::new();
+        for id in ids.by_ref().take(limit) {
+            invocations.push(self.invocation_payload(namespace, compute_graph, &id)?);
+        }
+        let cursor = match ids.next() {
+            Some(_) => invocations.last().map(|invocation| invocation.id.clone()),
+            None => None,
+        };
+        Ok((invocations, cursor))
+    }
+
     pub fn list_compute_graphs(
         &self,
         namespace: &str,
@@ -470,10 +731,98 @@ impl StateReader {
         Ok((compute_graphs, cursor))
     }
 
+    pub fn list_deleted_compute_graphs(
+        &self,
+        namespace: &str,
+        cursor: Option<&[u8]>,
+        limit: Option<usize>,
+    ) -> Result<(Vec<ComputeGraph>, Option<Vec<u8>>)> {
+        self.get_rows_from_cf_with_limits::<ComputeGraph>(
+            namespace.as_bytes(),
+            cursor,
+            IndexifyObjectsColumns::DeletedComputeGraphs,
+            limit,
+        )
+    }
+
+    /// Compute graphs of all namespaces
+    pub fn all_compute_graphs(&self) -> Result<Vec<ComputeGraph>> {
+        let graphs = self
+            .get_all_rows_from_cf::<ComputeGraph>(IndexifyObjectsColumns::ComputeGraphs)?
+            .into_iter()
+            .map(|(_, graph)| graph)
+            .collect();
+        Ok(graphs)
+    }
+
+    /// Deleted compute graphs of all namespaces deleted before
+    /// `deleted_before`, in ms since epoch.
+    pub fn expired_deleted_compute_graphs(&self, deleted_before: u64) -> Result<Vec<ComputeGraph>> {
+        let graphs = self
+            .get_all_rows_from_cf::<ComputeGraph>(IndexifyObjectsColumns::DeletedComputeGraphs)?
+            .into_iter()
+            .map(|(_, graph)| graph)
+            .filter(|graph| graph.deleted_at.map_or(false, |at| at < deleted_before))
+            .collect();
+        Ok(graphs)
+    }
+
     pub fn get_compute_graph(&self, namespace: &str, name: &str) -> Result<Option<ComputeGraph>> {
         let key = format!("{}|{}", namespace, name);
-        let compute_graph = self.get_from_cf(&IndexifyObjectsColumns::ComputeGraphs, key)?;
-        Ok(compute_graph)
+        self.cache
+            .compute_graphs
+            .get_or_load(key.clone().into_bytes(), || {
+                self.get_from_cf(&IndexifyObjectsColumns::ComputeGraphs, key)
+            })
+    }
+
+    pub fn is_compute_graph_paused(&self, namespace: &str, name: &str) -> Result<bool> {
+        Ok(self
+            .get_compute_graph(namespace, name)?
+            .is_some_and(|graph| graph.is_paused()))
+    }
+
+    pub fn get_graph_canary(
+        &self,
+        namespace: &str,
+        compute_graph: &str,
+    ) -> Result<Option<GraphCanary>> {
+        self.get_from_cf(
+            &IndexifyObjectsColumns::GraphCanaries,
+            format!("{}|{}", namespace, compute_graph),
+        )
+    }
+
+    /// The graph at the given version, the canary's if it is at that version
+    /// and the current graph otherwise.
+    pub fn get_compute_graph_version(
+        &self,
+        namespace: &str,
+        name: &str,
+        version: &GraphVersion,
+    ) -> Result<Option<ComputeGraph>> {
+        if let Some(canary) = self.get_graph_canary(namespace, name)? {
+            if canary.compute_graph.version == *version {
+                return Ok(Some(canary.compute_graph));
+            }
+        }
+        self.get_compute_graph(namespace, name)
+    }
+
+    /// The version of the graph an invocation runs on.
+    pub fn get_invocation_compute_graph(
+        &self,
+        namespace: &str,
+        name: &str,
+        invocation_id: &str,
+    ) -> Result<Option<ComputeGraph>> {
+        let key = GraphInvocationCtx::key_from(namespace, name, invocation_id);
+        let ctx: Option<GraphInvocationCtx> =
+            self.get_from_cf(&IndexifyObjectsColumns::GraphInvocationCtx, key)?;
+        match ctx {
+            Some(ctx) => self.get_compute_graph_version(namespace, name, &ctx.graph_version),
+            None => self.get_compute_graph(namespace, name),
+        }
     }
 
     pub fn list_outputs_by_compute_graph(
@@ -484,7 +833,7 @@ impl StateReader {
         restart_key: Option<&[u8]>,
         limit: Option<usize>,
     ) -> Result<(Vec<NodeOutput>, Option<Vec<u8>>)> {
-        let key = format!("{}|{}|{}|", namespace, compute_graph, invocation_id);
+        let key = KeyPrefix::encoded(&[namespace, compute_graph, invocation_id]);
         self.get_rows_from_cf_with_limits::<NodeOutput>(
             key.as_bytes(),
             restart_key,
@@ -511,10 +860,7 @@ impl StateReader {
         compute_fn: &str,
         task_id: &str,
     ) -> Result<Option<Task>> {
-        let key = format!(
-            "{}|{}|{}|{}|{}",
-            namespace, compute_graph, invocation_id, compute_fn, task_id
-        );
+        let key = Task::key_from(namespace, compute_graph, invocation_id, compute_fn, task_id);
         let task = self.get_from_cf(&IndexifyObjectsColumns::Tasks, key)?;
         Ok(task)
     }
@@ -525,7 +871,7 @@ impl StateReader {
         restart_key: Option<&[u8]>,
         limit: Option<usize>,
     ) -> Result<(Vec<Task>, Option<Vec<u8>>)> {
-        let key = format!("{}|", namespace);
+        let key = KeyPrefix::encoded(&[namespace]);
         self.get_rows_from_cf_with_limits::<Task>(
             key.as_bytes(),
             restart_key,
@@ -534,6 +880,37 @@ impl StateReader {
         )
     }
 
+    /// Timeline of an invocation, in the order the events were recorded in.
+    pub fn list_invocation_events(
+        &self,
+        namespace: &str,
+        compute_graph: &str,
+        invocation_id: &str,
+        restart_key: Option<&[u8]>,
+        limit: Option<usize>,
+    ) -> Result<(Vec<InvocationEvent>, Option<Vec<u8>>)> {
+        let key = InvocationEvent::key_prefix(namespace, compute_graph, invocation_id);
+        self.get_rows_from_cf_with_limits::<InvocationEvent>(
+            key.as_bytes(),
+            restart_key,
+            IndexifyObjectsColumns::InvocationEvents,
+            limit,
+        )
+    }
+
+    /// State of the invocation as of `as_of`, None if it wasn't invoked yet.
+    pub fn replay_invocation(
+        &self,
+        namespace: &str,
+        compute_graph: &str,
+        invocation_id: &str,
+        as_of: u64,
+    ) -> Result<Option<ReplayedInvocation>> {
+        let (events, _) =
+            self.list_invocation_events(namespace, compute_graph, invocation_id, None, None)?;
+        Ok(ReplayedInvocation::replay(&events, as_of))
+    }
+
     pub fn list_tasks_by_compute_graph(
         &self,
         namespace: &str,
@@ -542,7 +919,7 @@ impl StateReader {
         restart_key: Option<&[u8]>,
         limit: Option<usize>,
     ) -> Result<(Vec<Task>, Option<Vec<u8>>)> {
-        let key = format!("{}|{}|{}|", namespace, compute_graph, invocation_id);
+        let key = KeyPrefix::encoded(&[namespace, compute_graph, invocation_id]);
         self.get_rows_from_cf_with_limits::<Task>(
             key.as_bytes(),
             restart_key,
@@ -552,26 +929,26 @@ impl StateReader {
     }
 
     pub fn get_task_outputs(&self, namespace: &str, task_id: &str) -> Result<Vec<NodeOutput>> {
-        let key = format!("{}|{}", namespace, task_id);
-        let (node_output_keys, _) = self.get_rows_from_cf_with_limits::<String>(
+        let key = Task::key_outputs_prefix(namespace, task_id);
+        let (node_output_keys, _) = self.get_rows_from_cf_with_limits::<Vec<u8>>(
             key.as_bytes(),
             None,
             IndexifyObjectsColumns::TaskOutputs,
             None,
         )?;
-        let keys = node_output_keys.iter().map(|key| key.as_bytes()).collect();
+        let keys = node_output_keys.iter().map(|key| key.as_slice()).collect();
         let data_objects =
             self.get_rows_from_cf_multi_key::<NodeOutput>(keys, IndexifyObjectsColumns::FnOutputs)?;
         Ok(data_objects)
     }
 
     pub fn get_tasks_by_executor(&self, executor: &ExecutorId, limit: usize) -> Result<Vec<Task>> {
-        let prefix = format!("{}|", executor);
+        let prefix = Task::allocation_key_prefix(executor);
         let res = self.filter_join_cf(
             IndexifyObjectsColumns::TaskAllocations,
             IndexifyObjectsColumns::Tasks,
             |_| true,
-            prefix.as_bytes(),
+            &prefix,
             Task::key_from_allocation_key,
             None,
             Some(limit),
@@ -579,6 +956,155 @@ impl StateReader {
         Ok(res.items)
     }
 
+    /// Number of tasks allocated to each executor with allocated tasks.
+    pub fn allocated_task_counts(&self) -> Result<HashMap<ExecutorId, usize>> {
+        let mut counts = HashMap::new();
+        let iter = self.db.iterator_cf(
+            &IndexifyObjectsColumns::TaskAllocations.cf_db(&self.db),
+            IteratorMode::Start,
+        );
+        for kv in iter {
+            let (key, _) = kv?;
+            let executor_id = Task::executor_from_allocation_key(&key)?;
+            *counts.entry(executor_id).or_default() += 1;
+        }
+        Ok(counts)
+    }
+
+    /// Ids of the tasks allocated to each executor with allocated tasks.
+    pub fn allocated_task_ids(&self) -> Result<HashMap<ExecutorId, Vec<TaskId>>> {
+        let mut task_ids: HashMap<ExecutorId, Vec<TaskId>> = HashMap::new();
+        let iter = self.db.iterator_cf(
+            &IndexifyObjectsColumns::TaskAllocations.cf_db(&self.db),
+            IteratorMode::Start,
+        );
+        for kv in iter {
+            let (key, _) = kv?;
+            // <executor_id><nsecs><ns><cg><invocation_id><fn><task_id>
+            let mut fields = keys::decode_key(&key)?;
+            let (Some(task_id), Some(executor_id)) = (fields.pop(), fields.into_iter().next())
+            else {
+                return Err(anyhow!("invalid allocation key"));
+            };
+            task_ids
+                .entry(ExecutorId::new(executor_id))
+                .or_default()
+                .push(TaskId::new(task_id));
+        }
+        Ok(task_ids)
+    }
+
+    /// Number of allocated tasks of each function of a compute graph.
+    pub fn running_task_counts(
+        &self,
+        namespace: &str,
+        compute_graph: &str,
+    ) -> Result<HashMap<String, u64>> {
+        let prefix = KeyPrefix::encoded(&[namespace, compute_graph]);
+        let mut counts = HashMap::new();
+        let iter = self.db.iterator_cf(
+            &IndexifyObjectsColumns::TaskAllocations.cf_db(&self.db),
+            IteratorMode::Start,
+        );
+        for kv in iter {
+            let (key, _) = kv?;
+            let task_key = Task::key_from_allocation_key(&key)?;
+            let Some(rest) = task_key.strip_prefix(prefix.as_bytes()) else {
+                continue;
+            };
+            // <invocation_id><compute_fn><task_id>
+            let (mut fields, _) = keys::decode_key_fields(rest, 2)?;
+            *counts.entry(fields.swap_remove(1)).or_default() += 1;
+        }
+        Ok(counts)
+    }
+
+    pub fn get_code_artifact(&self, sha256: &str) -> Result<Option<ComputeGraphCode>> {
+        self.get_from_cf(&IndexifyObjectsColumns::CodeArtifacts, sha256)
+    }
+
+    /// Storage used by the compute graphs of a namespace, including deleted
+    /// graphs which weren't purged yet
+    pub fn namespace_storage_usage(&self, namespace: &str) -> Result<Vec<GraphStorageUsage>> {
+        let key_prefix = format!("{}|", namespace);
+        let (usage, _) = self.get_rows_from_cf_with_limits::<GraphStorageUsage>(
+            key_prefix.as_bytes(),
+            None,
+            IndexifyObjectsColumns::StorageUsage,
+            None,
+        )?;
+        Ok(usage)
+    }
+
+    /// Resources used by the tasks of the compute graphs of a namespace
+    pub fn namespace_task_usage(&self, namespace: &str) -> Result<Vec<GraphTaskUsage>> {
+        let key_prefix = format!("{}|", namespace);
+        let (usage, _) = self.get_rows_from_cf_with_limits::<GraphTaskUsage>(
+            key_prefix.as_bytes(),
+            None,
+            IndexifyObjectsColumns::TaskUsage,
+            None,
+        )?;
+        Ok(usage)
+    }
+
+    /// Fan outs whose tasks are still being created
+    pub fn fan_out_cursors(&self) -> Result<Vec<FanOutCursor>> {
+        let (cursors, _) = self.get_rows_from_cf_with_limits::<FanOutCursor>(
+            &[],
+            None,
+            IndexifyObjectsColumns::FanOutCursors,
+            None,
+        )?;
+        Ok(cursors)
+    }
+
+    /// Inputs waiting in a window, oldest first
+    pub fn window_items(
+        &self,
+        namespace: &str,
+        compute_graph: &str,
+        window: &str,
+    ) -> Result<Vec<WindowItem>> {
+        let prefix = WindowItem::key_prefix(namespace, compute_graph, window);
+        let (items, _) = self.get_rows_from_cf_with_limits::<WindowItem>(
+            prefix.as_bytes(),
+            None,
+            IndexifyObjectsColumns::WindowItems,
+            None,
+        )?;
+        Ok(items)
+    }
+
+    /// Oldest input of each window which waited longer than the max age of
+    /// its window at `now`
+    pub fn expired_windows(&self, now: u64) -> Result<Vec<WindowItem>> {
+        let cf = IndexifyObjectsColumns::WindowItems.cf_db(&self.db);
+        let mut expired: Vec<WindowItem> = Vec::new();
+        let mut last_prefix = None;
+        for kv in self.db.iterator_cf(&cf, IteratorMode::Start) {
+            let (_, value) = kv?;
+            let item = JsonEncoder::decode::<WindowItem>(&value)?;
+            let prefix = WindowItem::key_prefix(&item.namespace, &item.compute_graph, &item.window);
+            // Only the oldest item of a window decides when it closes
+            if last_prefix.as_ref() == Some(&prefix) {
+                continue;
+            }
+            last_prefix = Some(prefix);
+            let window_expired = self
+                .get_compute_graph(&item.namespace, &item.compute_graph)?
+                .and_then(|graph| graph.nodes.get(&item.window).cloned())
+                .is_some_and(|node| {
+                    node.window()
+                        .is_some_and(|window| window.expired(item.buffered_at, now))
+                });
+            if window_expired {
+                expired.push(item);
+            }
+        }
+        Ok(expired)
+    }
+
     pub fn get_all_executors(&self) -> Result<Vec<ExecutorMetadata>> {
         let (executors, _) = self.get_rows_from_cf_with_limits::<ExecutorMetadata>(
             &[],
@@ -589,6 +1115,395 @@ impl StateReader {
         Ok(executors)
     }
 
+    pub fn get_executor(&self, executor_id: &ExecutorId) -> Result<Option<ExecutorMetadata>> {
+        self.get_from_cf(&IndexifyObjectsColumns::Executors, executor_id.get())
+    }
+
+    /// Unallocated tasks the executor can run, among the first `scan_limit`
+    /// unallocated tasks. Tasks of functions which need GPUs or a pool are
+    /// included when the executor has the GPUs or is a member of the pool.
+    pub fn upcoming_tasks(
+        &self,
+        executor: &ExecutorMetadata,
+        scan_limit: usize,
+        limit: usize,
+    ) -> Result<Vec<Task>> {
+        let (rows, _) = self.get_raw_rows_from_cf_with_limits(
+            &[],
+            None,
+            IndexifyObjectsColumns::UnallocatedTasks,
+            Some(scan_limit),
+        )?;
+        let keys: Vec<&[u8]> = rows.iter().map(|(key, _)| key.as_ref()).collect();
+        let tasks: Vec<Task> =
+            self.get_rows_from_cf_multi_key(keys, IndexifyObjectsColumns::Tasks)?;
+        let mut graphs: HashMap<String, Option<ComputeGraph>> = HashMap::new();
+        let mut pools: HashMap<String, Option<ExecutorPool>> = HashMap::new();
+        let mut upcoming = vec![];
+        for task in tasks {
+            let graph_key = format!(
+                "{}|{}|{}",
+                task.namespace, task.compute_graph_name, task.graph_version.0
+            );
+            if !graphs.contains_key(&graph_key) {
+                let graph = self.get_compute_graph_version(
+                    &task.namespace,
+                    &task.compute_graph_name,
+                    &task.graph_version,
+                )?;
+                graphs.insert(graph_key.clone(), graph);
+            }
+            let Some(node) = graphs[&graph_key]
+                .as_ref()
+                .and_then(|graph| graph.nodes.get(&task.compute_fn_name))
+            else {
+                continue;
+            };
+            if node.image_name() != executor.image_name || !node.matches_executor(executor) {
+                continue;
+            }
+            if let Some(gpu) = node.gpu() {
+                if executor.free_gpus(gpu, &HashSet::new()).is_none() {
+                    continue;
+                }
+            }
+            if let Some(pool_name) = node.executor_pool() {
+                let pool_key = ExecutorPool::key_from(&task.namespace, pool_name);
+                if !pools.contains_key(&pool_key) {
+                    let pool = self.get_executor_pool(&task.namespace, pool_name)?;
+                    pools.insert(pool_key.clone(), pool);
+                }
+                let member = pools[&pool_key]
+                    .as_ref()
+                    .is_some_and(|pool| pool.matches_executor(executor));
+                if !member {
+                    continue;
+                }
+            }
+            upcoming.push(task);
+            if upcoming.len() >= limit {
+                break;
+            }
+        }
+        Ok(upcoming)
+    }
+
+    pub fn list_executor_pools(&self, namespace: &str) -> Result<Vec<ExecutorPool>> {
+        let prefix = format!("{}|", namespace);
+        let (pools, _) = self.get_rows_from_cf_with_limits::<ExecutorPool>(
+            prefix.as_bytes(),
+            None,
+            IndexifyObjectsColumns::ExecutorPools,
+            None,
+        )?;
+        Ok(pools)
+    }
+
+    pub fn get_executor_approval(
+        &self,
+        executor_id: &ExecutorId,
+    ) -> Result<Option<ExecutorApproval>> {
+        self.get_from_cf(
+            &IndexifyObjectsColumns::ExecutorApprovals,
+            executor_id.get(),
+        )
+    }
+
+    pub fn list_executor_approvals(&self) -> Result<Vec<ExecutorApproval>> {
+        let (approvals, _) = self.get_rows_from_cf_with_limits::<ExecutorApproval>(
+            &[],
+            None,
+            IndexifyObjectsColumns::ExecutorApprovals,
+            None,
+        )?;
+        Ok(approvals)
+    }
+
+    /// Reports of the reconciliations of the tasks of an executor, or of
+    /// every executor, the oldest first.
+    pub fn executor_reconciliations(
+        &self,
+        executor_id: Option<&ExecutorId>,
+        restart_key: Option<&[u8]>,
+        limit: Option<usize>,
+    ) -> Result<(Vec<ExecutorReconciliation>, Option<Vec<u8>>)> {
+        let prefix = executor_id.map(ExecutorReconciliation::key_prefix);
+        self.get_rows_from_cf_with_limits(
+            prefix.as_deref().unwrap_or_default().as_bytes(),
+            restart_key,
+            IndexifyObjectsColumns::ExecutorReconciliations,
+            limit,
+        )
+    }
+
+    /// Abort commands the executor hasn't acknowledged, oldest first.
+    pub fn pending_aborts(&self, executor_id: &ExecutorId) -> Result<Vec<AbortCommand>> {
+        let (mut commands, _) = self.get_rows_from_cf_with_limits::<AbortCommand>(
+            AbortCommand::key_prefix(executor_id).as_bytes(),
+            None,
+            IndexifyObjectsColumns::PendingAborts,
+            None,
+        )?;
+        commands.sort_by_key(|command| command.issued_at);
+        Ok(commands)
+    }
+
+    /// Blacklist entries which haven't expired at `now`.
+    pub fn executor_blacklist(&self, now: u64) -> Result<Vec<ExecutorBlacklistEntry>> {
+        let (entries, _) = self.get_rows_from_cf_with_limits::<ExecutorBlacklistEntry>(
+            &[],
+            None,
+            IndexifyObjectsColumns::ExecutorBlacklist,
+            None,
+        )?;
+        Ok(entries
+            .into_iter()
+            .filter(|entry| entry.is_active(now))
+            .collect())
+    }
+
+    /// Executors blacklisted for the function at `now`.
+    pub fn blacklisted_executors(
+        &self,
+        namespace: &str,
+        compute_graph: &str,
+        compute_fn: &str,
+        now: u64,
+    ) -> Result<HashSet<ExecutorId>> {
+        let prefix = ExecutorBlacklistEntry::fn_prefix(namespace, compute_graph, compute_fn);
+        let (entries, _) = self.get_rows_from_cf_with_limits::<ExecutorBlacklistEntry>(
+            prefix.as_bytes(),
+            None,
+            IndexifyObjectsColumns::ExecutorBlacklist,
+            None,
+        )?;
+        Ok(entries
+            .into_iter()
+            .filter(|entry| entry.is_active(now))
+            .map(|entry| entry.executor_id)
+            .collect())
+    }
+
+    /// Legal holds of the namespace, or of one of its compute graphs and its
+    /// invocations.
+    pub fn legal_holds(
+        &self,
+        namespace: &str,
+        compute_graph: Option<&str>,
+    ) -> Result<Vec<LegalHold>> {
+        let prefix = match compute_graph {
+            Some(compute_graph) => KeyPrefix::compute_graph(namespace, compute_graph),
+            None => KeyPrefix::namespace(namespace),
+        };
+        let (holds, _) = self.get_rows_from_cf_with_limits::<LegalHold>(
+            prefix.as_bytes(),
+            None,
+            IndexifyObjectsColumns::LegalHolds,
+            None,
+        )?;
+        Ok(holds)
+    }
+
+    /// Whether the invocation is held, by its own hold or by its graph's.
+    pub fn is_under_legal_hold(
+        &self,
+        namespace: &str,
+        compute_graph: &str,
+        invocation_id: &str,
+    ) -> Result<bool> {
+        for invocation_id in [None, Some(invocation_id)] {
+            let hold: Option<LegalHold> = self.get_from_cf(
+                &IndexifyObjectsColumns::LegalHolds,
+                LegalHold::key_from(namespace, compute_graph, invocation_id),
+            )?;
+            if hold.is_some() {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Holds set and cleared in the namespace, oldest first.
+    pub fn legal_hold_audit(
+        &self,
+        namespace: &str,
+        restart_key: Option<&[u8]>,
+        limit: Option<usize>,
+    ) -> Result<(Vec<LegalHoldAuditEntry>, Option<Vec<u8>>)> {
+        self.get_rows_from_cf_with_limits(
+            LegalHoldAuditEntry::key_prefix(namespace).as_bytes(),
+            restart_key,
+            IndexifyObjectsColumns::LegalHoldAudit,
+            limit,
+        )
+    }
+
+    pub fn data_deletion(
+        &self,
+        namespace: &str,
+        compute_graph: &str,
+        invocation_id: &str,
+    ) -> Result<Option<DataDeletion>> {
+        self.get_from_cf(
+            &IndexifyObjectsColumns::DataDeletions,
+            DataDeletion::key_from(namespace, compute_graph, invocation_id),
+        )
+    }
+
+    /// Data deletions of the namespace, or of one of its compute graphs.
+    pub fn data_deletions(
+        &self,
+        namespace: &str,
+        compute_graph: Option<&str>,
+        restart_key: Option<&[u8]>,
+        limit: Option<usize>,
+    ) -> Result<(Vec<DataDeletion>, Option<Vec<u8>>)> {
+        self.get_rows_from_cf_with_limits(
+            DataDeletion::key_prefix(namespace, compute_graph).as_bytes(),
+            restart_key,
+            IndexifyObjectsColumns::DataDeletions,
+            limit,
+        )
+    }
+
+    /// How many of the urls the garbage collector has yet to delete.
+    pub fn pending_gc_urls(&self, urls: &[String]) -> Result<usize> {
+        let cf = IndexifyObjectsColumns::GcUrls.cf_db(&self.db);
+        let mut pending = 0;
+        for url in urls {
+            if self.db.get_cf(&cf, url.as_bytes())?.is_some() {
+                pending += 1;
+            }
+        }
+        Ok(pending)
+    }
+
+    pub fn get_executor_pool(&self, namespace: &str, name: &str) -> Result<Option<ExecutorPool>> {
+        let key = ExecutorPool::key_from(namespace, name);
+        self.get_from_cf(&IndexifyObjectsColumns::ExecutorPools, key)
+    }
+
+    pub fn get_invocation_group(
+        &self,
+        namespace: &str,
+        id: &str,
+    ) -> Result<Option<InvocationGroup>> {
+        self.get_from_cf(
+            &IndexifyObjectsColumns::InvocationGroups,
+            InvocationGroup::key_from(namespace, id),
+        )
+    }
+
+    pub fn list_invocation_groups(
+        &self,
+        namespace: &str,
+        cursor: Option<&[u8]>,
+        limit: Option<usize>,
+    ) -> Result<(Vec<InvocationGroup>, Option<Vec<u8>>)> {
+        let prefix = format!("{}|", namespace);
+        self.get_rows_from_cf_with_limits(
+            prefix.as_bytes(),
+            cursor,
+            IndexifyObjectsColumns::InvocationGroups,
+            limit,
+        )
+    }
+
+    /// Contexts of the invocations of a group, ordered by compute graph and
+    /// invocation id.
+    pub fn invocation_group_members(
+        &self,
+        namespace: &str,
+        id: &str,
+        cursor: Option<&[u8]>,
+        limit: Option<usize>,
+    ) -> Result<(Vec<GraphInvocationCtx>, Option<Vec<u8>>)> {
+        let prefix = format!("{}|", InvocationGroup::key_from(namespace, id));
+        // Ns_GroupId_Graph_Id -> Ns_Graph_Id (encoded)
+        let ctx_key = |member_key: &[u8]| -> Result<Vec<u8>> {
+            let member = std::str::from_utf8(&member_key[prefix.len()..])?;
+            let (compute_graph, invocation_id) = member
+                .split_once('|')
+                .ok_or(anyhow!("invalid invocation group member key {}", member))?;
+            Ok(GraphInvocationCtx::key_from(
+                namespace,
+                compute_graph,
+                invocation_id,
+            ))
+        };
+        let res = self.filter_join_cf(
+            IndexifyObjectsColumns::InvocationGroupMembers,
+            IndexifyObjectsColumns::GraphInvocationCtx,
+            |_| true,
+            prefix.as_bytes(),
+            ctx_key,
+            cursor,
+            limit,
+        )?;
+        let cursor = (!res.cursor.is_empty()).then_some(res.cursor);
+        Ok((res.items, cursor))
+    }
+
+    /// Whether an invocation is a member of the group.
+    pub fn is_invocation_group_member(
+        &self,
+        namespace: &str,
+        id: &str,
+        compute_graph: &str,
+        invocation_id: &str,
+    ) -> Result<bool> {
+        let key = InvocationGroup::member_key_from(namespace, id, compute_graph, invocation_id);
+        Ok(self
+            .db
+            .get_cf(
+                &IndexifyObjectsColumns::InvocationGroupMembers.cf_db(&self.db),
+                key,
+            )?
+            .is_some())
+    }
+
+    pub fn list_webhooks(&self, namespace: &str) -> Result<Vec<Webhook>> {
+        let prefix = format!("{}|", namespace);
+        let (webhooks, _) = self.get_rows_from_cf_with_limits::<Webhook>(
+            prefix.as_bytes(),
+            None,
+            IndexifyObjectsColumns::Webhooks,
+            None,
+        )?;
+        Ok(webhooks)
+    }
+
+    pub fn get_webhook(&self, namespace: &str, id: &str) -> Result<Option<Webhook>> {
+        self.get_from_cf(
+            &IndexifyObjectsColumns::Webhooks,
+            Webhook::key_from(namespace, id),
+        )
+    }
+
+    /// Queued deliveries of every namespace due to be attempted at `now`.
+    pub fn due_webhook_deliveries(&self, now: u64, limit: usize) -> Result<Vec<WebhookDelivery>> {
+        let (deliveries, _) = self.filter_rows_from_cf_with_limits::<WebhookDelivery, _>(
+            &[],
+            None,
+            IndexifyObjectsColumns::WebhookDeliveries,
+            |delivery| delivery.next_attempt_at <= now,
+            Some(limit),
+        )?;
+        Ok(deliveries)
+    }
+
+    /// Deliveries of the namespace which ran out of attempts.
+    pub fn list_webhook_dead_letters(&self, namespace: &str) -> Result<Vec<WebhookDelivery>> {
+        let prefix = format!("{}|", namespace);
+        let (dead_letters, _) = self.get_rows_from_cf_with_limits::<WebhookDelivery>(
+            prefix.as_bytes(),
+            None,
+            IndexifyObjectsColumns::WebhookDeadLetters,
+            None,
+        )?;
+        Ok(dead_letters)
+    }
+
     pub fn invocation_ctx(
         &self,
         namespace: &str,
@@ -596,14 +1511,212 @@ impl StateReader {
         invocation_id: &str,
     ) -> Result<GraphInvocationCtx> {
         let key = GraphInvocationCtx::key_from(namespace, compute_graph, invocation_id);
-        let value = self.db.get_cf(
-            &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&self.db),
-            &key,
+        let ctx = self.cache.invocation_ctxs.get_or_load(key.clone(), || {
+            let value = self.db.get_cf(
+                &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&self.db),
+                &key,
+            )?;
+            value.map(|value| JsonEncoder::decode(&value)).transpose()
+        })?;
+        ctx.ok_or_else(|| StateStoreError::not_found("invocation ctx").into())
+    }
+
+    /// Completed invocations created before `created_before`, in ms since
+    /// epoch, of a namespace or of one of its compute graphs.
+    pub fn completed_invocations_created_before(
+        &self,
+        namespace: &str,
+        compute_graph: Option<&str>,
+        created_before: u64,
+        restart_key: Option<&[u8]>,
+        limit: Option<usize>,
+    ) -> Result<(Vec<GraphInvocationCtx>, Option<Vec<u8>>)> {
+        let prefix = match compute_graph {
+            Some(compute_graph) => KeyPrefix::encoded(&[namespace, compute_graph]),
+            None => KeyPrefix::encoded(&[namespace]),
+        };
+        self.filter_rows_from_cf_with_limits(
+            prefix.as_bytes(),
+            restart_key,
+            IndexifyObjectsColumns::GraphInvocationCtx,
+            |ctx: &GraphInvocationCtx| ctx.completed && ctx.created_at < created_before,
+            limit,
+        )
+    }
+
+    /// Invocations of a namespace created since `created_after`, in ms since
+    /// epoch, whether they completed or not.
+    pub fn invocations_created_after(
+        &self,
+        namespace: &str,
+        created_after: u64,
+        restart_key: Option<&[u8]>,
+        limit: Option<usize>,
+    ) -> Result<(Vec<GraphInvocationCtx>, Option<Vec<u8>>)> {
+        self.filter_rows_from_cf_with_limits(
+            KeyPrefix::encoded(&[namespace]).as_bytes(),
+            restart_key,
+            IndexifyObjectsColumns::GraphInvocationCtx,
+            |ctx: &GraphInvocationCtx| ctx.created_at >= created_after,
+            limit,
+        )
+    }
+
+    /// SLA attainment of a compute graph over the window ending at `now`, in
+    /// ms since epoch.
+    pub fn evaluate_sla(
+        &self,
+        namespace: &str,
+        compute_graph: &str,
+        sla: &GraphSla,
+        now: u64,
+    ) -> Result<SlaStatus> {
+        let prefix = KeyPrefix::encoded(&[namespace, compute_graph]);
+        let window_start = now.saturating_sub(sla.window_secs * 1000);
+        let (finished, _) = self.filter_rows_from_cf_with_limits(
+            prefix.as_bytes(),
+            None,
+            IndexifyObjectsColumns::InvocationResults,
+            |result: &InvocationResult| result.finished_at >= window_start,
+            None,
         )?;
-        match value {
-            Some(value) => Ok(JsonEncoder::decode(&value)?),
-            None => Err(anyhow!("invocation ctx not found")),
+        let overdue_before = now.saturating_sub(sla.max_duration_secs * 1000);
+        let (overdue, _) = self.filter_rows_from_cf_with_limits(
+            prefix.as_bytes(),
+            None,
+            IndexifyObjectsColumns::GraphInvocationCtx,
+            |ctx: &GraphInvocationCtx| {
+                !ctx.completed && ctx.created_at > 0 && ctx.created_at < overdue_before
+            },
+            None,
+        )?;
+        Ok(SlaStatus::evaluate(
+            namespace,
+            compute_graph,
+            sla,
+            &finished,
+            overdue.len() as u64,
+            now,
+        ))
+    }
+
+    /// SLA status last recorded for the compute graph by the leader.
+    pub fn get_sla_status(
+        &self,
+        namespace: &str,
+        compute_graph: &str,
+    ) -> Result<Option<SlaStatus>> {
+        self.get_from_cf(
+            &IndexifyObjectsColumns::SlaStatus,
+            SlaStatus::key_from(namespace, compute_graph),
+        )
+    }
+
+    /// Statuses of the alerts last evaluated by the leader, ordered by the
+    /// names of their rules.
+    pub fn alert_statuses(&self) -> Result<Vec<AlertStatus>> {
+        let (statuses, _) = self.get_rows_from_cf_with_limits::<AlertStatus>(
+            &[],
+            None,
+            IndexifyObjectsColumns::AlertStatuses,
+            None,
+        )?;
+        Ok(statuses)
+    }
+
+    /// Latency sketches of the functions of a compute graph, persisted
+    /// periodically by the leader.
+    pub fn graph_function_latencies(
+        &self,
+        namespace: &str,
+        compute_graph: &str,
+    ) -> Result<Vec<FunctionLatency>> {
+        let key_prefix = format!("{}|{}|", namespace, compute_graph);
+        let (latencies, _) = self.get_rows_from_cf_with_limits::<FunctionLatency>(
+            key_prefix.as_bytes(),
+            None,
+            IndexifyObjectsColumns::FunctionLatencies,
+            None,
+        )?;
+        Ok(latencies)
+    }
+
+    pub fn all_function_latencies(&self) -> Result<Vec<FunctionLatency>> {
+        let (latencies, _) = self.get_rows_from_cf_with_limits::<FunctionLatency>(
+            &[],
+            None,
+            IndexifyObjectsColumns::FunctionLatencies,
+            None,
+        )?;
+        Ok(latencies)
+    }
+
+    /// Result of an invocation, `None` until it finishes.
+    pub fn invocation_result(
+        &self,
+        namespace: &str,
+        compute_graph: &str,
+        invocation_id: &str,
+    ) -> Result<Option<InvocationResult>> {
+        let key = GraphInvocationCtx::key_from(namespace, compute_graph, invocation_id);
+        self.get_from_cf(&IndexifyObjectsColumns::InvocationResults, key)
+    }
+
+    /// Results of the invocations of all namespaces which finished after
+    /// `finished_after` and until `finished_until`, in ms since epoch.
+    pub fn invocation_results_finished_between(
+        &self,
+        finished_after: u64,
+        finished_until: u64,
+        restart_key: Option<&[u8]>,
+        limit: Option<usize>,
+    ) -> Result<(Vec<InvocationResult>, Option<Vec<u8>>)> {
+        self.filter_rows_from_cf_with_limits(
+            &[],
+            restart_key,
+            IndexifyObjectsColumns::InvocationResults,
+            |result: &InvocationResult| {
+                result.finished_at > finished_after && result.finished_at <= finished_until
+            },
+            limit,
+        )
+    }
+
+    /// Value of the scratch space of an invocation.
+    pub fn invocation_state(
+        &self,
+        namespace: &str,
+        compute_graph: &str,
+        invocation_id: &str,
+        key: &str,
+    ) -> Result<Option<InvocationStateEntry>> {
+        let key = InvocationStateEntry::key_from(namespace, compute_graph, invocation_id, key);
+        self.get_from_cf(&IndexifyObjectsColumns::InvocationState, key)
+    }
+
+    /// Task analytics of the functions of a graph summed over its invocations
+    pub fn graph_task_analytics(
+        &self,
+        namespace: &str,
+        compute_graph: &str,
+    ) -> Result<HashMap<String, TaskAnalytics>> {
+        let prefix = KeyPrefix::encoded(&[namespace, compute_graph]);
+        let (ctxs, _) = self.get_rows_from_cf_with_limits::<GraphInvocationCtx>(
+            prefix.as_bytes(),
+            None,
+            IndexifyObjectsColumns::GraphInvocationCtx,
+            None,
+        )?;
+        let mut analytics: HashMap<String, TaskAnalytics> = HashMap::new();
+        for ctx in ctxs {
+            for (compute_fn, fn_analytics) in &ctx.fn_task_analytics {
+                analytics
+                    .entry(compute_fn.clone())
+                    .or_default()
+                    .merge(fn_analytics);
+            }
         }
+        Ok(analytics)
     }
 
     pub fn task_analytics(
@@ -642,7 +1755,7 @@ impl StateReader {
         )?;
         match value {
             Some(value) => Ok(JsonEncoder::decode(&value)?),
-            None => Err(anyhow!("invocation payload not found")),
+            None => Err(StateStoreError::not_found("invocation payload").into()),
         }
     }
 
@@ -664,6 +1777,35 @@ impl StateReader {
         Ok(tasks)
     }
 
+    /// Unallocated tasks of the invocations of a compute graph.
+    pub fn unallocated_tasks_of_graph(
+        &self,
+        namespace: &str,
+        compute_graph: &str,
+    ) -> Result<Vec<Task>> {
+        let prefix = KeyPrefix::encoded(&[namespace, compute_graph]);
+        let iter = self.db.iterator_cf_opt(
+            &IndexifyObjectsColumns::UnallocatedTasks.cf_db(&self.db),
+            prefix_read_options(prefix.as_bytes()),
+            IteratorMode::From(prefix.as_bytes(), Direction::Forward),
+        );
+        let mut keys = vec![];
+        for kv in iter {
+            let (key, _) = kv?;
+            keys.push(key);
+        }
+        self.get_rows_from_cf_multi_key(
+            keys.iter().map(|key| key.as_ref()).collect(),
+            IndexifyObjectsColumns::Tasks,
+        )
+    }
+
+    /// Whether the task is waiting to be allocated.
+    pub fn is_task_unallocated(&self, task_key: &[u8]) -> Result<bool> {
+        let cf = IndexifyObjectsColumns::UnallocatedTasks.cf_db(&self.db);
+        Ok(self.db.get_cf(&cf, task_key)?.is_some())
+    }
+
     pub fn fn_output_payload(
         &self,
         namespace: &str,
@@ -683,18 +1825,20 @@ impl StateReader {
         }
     }
 
+    /// Output by its textual key, see `NodeOutput::input_key`.
     pub fn fn_output_payload_by_key(&self, key: &str) -> Result<NodeOutput> {
+        let key = NodeOutput::key_from_input_key(key)?;
         let value = self
             .db
             .get_cf(&IndexifyObjectsColumns::FnOutputs.cf_db(&self.db), &key)?;
         match value {
             Some(value) => Ok(JsonEncoder::decode(&value)?),
-            None => Err(anyhow!("fn output not found")),
+            None => Err(StateStoreError::not_found("fn output").into()),
         }
     }
 
     pub fn all_reduction_tasks(&self, ns: &str, cg: &str, inv_id: &str) -> Result<Vec<ReduceTask>> {
-        let key = format!("{}|{}|{}|", ns, cg, inv_id);
+        let key = KeyPrefix::encoded(&[ns, cg, inv_id]);
         let (tasks, _) = self.get_rows_from_cf_with_limits::<ReduceTask>(
             key.as_bytes(),
             None,
@@ -711,7 +1855,7 @@ impl StateReader {
         inv_id: &str,
         c_fn: &str,
     ) -> Result<Option<ReduceTask>> {
-        let key = format!("{}|{}|{}|{}", ns, cg, inv_id, c_fn);
+        let key = KeyPrefix::encoded(&[ns, cg, inv_id, c_fn]);
         let (tasks, _) = self.get_rows_from_cf_with_limits::<ReduceTask>(
             key.as_bytes(),
             None,
@@ -749,6 +1893,10 @@ mod tests {
                 .write(StateMachineUpdateRequest {
                     payload: RequestPayload::CreateNameSpace(NamespaceRequest {
                         name: name.clone(),
+                        queue_limits: Default::default(),
+                        scheduling_weight: None,
+                        reserved_capacity: None,
+                        replication: None,
                     }),
                     state_changes_processed: vec![],
                 })
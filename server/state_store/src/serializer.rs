@@ -3,8 +3,19 @@ use std::{any::type_name, fmt::Debug};
 use anyhow::Result;
 use serde::de::DeserializeOwned;
 
+// Values written by the BinaryEncoder start with this byte followed by the
+// version of the encoding, a byte JSON values never start with
+const BINARY_ENVELOPE_MAGIC: u8 = 0xff;
+const BINARY_ENVELOPE_VERSION: u8 = 1;
+
 pub struct JsonEncoder;
 
+/// Encodes values as CBOR in a versioned envelope, used for the values
+/// written on the hot path such as tasks and state changes. Both encoders
+/// decode values written by either of them, so existing JSON values stay
+/// readable until they are rewritten.
+pub struct BinaryEncoder;
+
 pub trait JsonEncode {
     fn encode<T: serde::Serialize + Debug>(value: &T) -> Result<Vec<u8>>;
     fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T>;
@@ -23,12 +34,85 @@ impl JsonEncode for JsonEncoder {
     }
 
     fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
-        serde_json::from_slice(bytes).map_err(|e| {
+        decode(bytes)
+    }
+}
+
+impl JsonEncode for BinaryEncoder {
+    fn encode<T: serde::Serialize + Debug>(value: &T) -> Result<Vec<u8>> {
+        let mut bytes = vec![BINARY_ENVELOPE_MAGIC, BINARY_ENVELOPE_VERSION];
+        ciborium::ser::into_writer(value, &mut bytes).map_err(|e| {
+            anyhow::anyhow!(
+                "error serializing into cbor: {}, type: {}, value: {:?}",
+                e,
+                type_name::<T>(),
+                value
+            )
+        })?;
+        Ok(bytes)
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+        decode(bytes)
+    }
+}
+
+fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    match bytes {
+        [BINARY_ENVELOPE_MAGIC, BINARY_ENVELOPE_VERSION, payload @ ..] => {
+            ciborium::de::from_reader(payload).map_err(|e| {
+                anyhow::anyhow!(
+                    "error deserializing from cbor bytes, {}, value: {:?}",
+                    e,
+                    type_name::<T>()
+                )
+            })
+        }
+        [BINARY_ENVELOPE_MAGIC, version, ..] => Err(anyhow::anyhow!(
+            "unsupported binary encoding version {}, value: {:?}",
+            version,
+            type_name::<T>()
+        )),
+        _ => serde_json::from_slice(bytes).map_err(|e| {
             anyhow::anyhow!(
                 "error deserializing from json bytes, {}, value: {:?}",
                 e,
                 type_name::<T>()
             )
-        })
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use data_model::{test_objects::tests::mock_invocation_payload, InvocationPayload};
+
+    use super::*;
+
+    #[test]
+    fn test_binary_encoding_reads_json() -> Result<()> {
+        let invocation = mock_invocation_payload();
+
+        let binary = BinaryEncoder::encode(&invocation)?;
+        assert_eq!(
+            binary[..2],
+            [BINARY_ENVELOPE_MAGIC, BINARY_ENVELOPE_VERSION]
+        );
+        let json = JsonEncoder::encode(&invocation)?;
+        assert!(binary.len() < json.len());
+
+        // Values written in either encoding are read by both encoders
+        for bytes in [&binary, &json] {
+            assert_eq!(JsonEncoder::decode::<InvocationPayload>(bytes)?, invocation);
+            assert_eq!(
+                BinaryEncoder::decode::<InvocationPayload>(bytes)?,
+                invocation
+            );
+        }
+
+        let mut newer = binary.clone();
+        newer[1] = BINARY_ENVELOPE_VERSION + 1;
+        assert!(BinaryEncoder::decode::<InvocationPayload>(&newer).is_err());
+        Ok(())
     }
 }
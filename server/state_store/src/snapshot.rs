@@ -0,0 +1,399 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use data_model::{ComputeGraph, InvocationPayload, NodeOutput, OutputPayload, Task};
+use futures::{Stream, StreamExt};
+use indexify_utils::get_epoch_time_in_ms;
+use rocksdb::{IteratorMode, SnapshotWithThreadMode, TransactionDB, WriteBatchWithTransaction};
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+use tokio::{sync::mpsc, task::JoinHandle};
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::{
+    open_db,
+    serializer::{JsonEncode, JsonEncoder},
+    state_machine::IndexifyObjectsColumns,
+    IndexifyState,
+};
+
+// Number of keys restored per write batch.
+pub(crate) const RESTORE_BATCH_SIZE: usize = 1000;
+
+// Size of the chunks a dump is streamed in, and number of chunks buffered
+// ahead of the reader.
+const SNAPSHOT_CHUNK_SIZE: usize = 1 << 20;
+const SNAPSHOT_CHUNKS_BUFFERED: usize = 4;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SnapshotManifest {
+    pub id: String,
+    pub created_at: u64,
+    // Column family -> number of keys in the snapshot
+    pub column_families: BTreeMap<String, u64>,
+    // Blob urls referenced by the state, they are not copied by the snapshot
+    pub blob_references: Vec<String>,
+}
+
+/// A dump streamed in chunks as it's read from the state store.
+pub struct StateSnapshot {
+    pub data: ReceiverStream<Result<Vec<u8>>>,
+    /// Resolves once the whole dump was read
+    pub manifest: JoinHandle<Result<SnapshotManifest>>,
+}
+
+impl IndexifyState {
+    /// Takes a consistent point in time dump of every column family but the
+    /// vote of this replica.
+    ///
+    /// The dump is a sequence of `(column family, key, value)` records, each
+    /// field prefixed by its length as a big endian u32.
+    pub fn create_snapshot(&self, id: &str) -> StateSnapshot {
+        let db = self.db.clone();
+        let id = id.to_string();
+        stream_dump(move |out| {
+            let include = |column: &IndexifyObjectsColumns| {
+                !matches!(column, IndexifyObjectsColumns::ReplicationState)
+            };
+            dump_db(&db, &db.snapshot(), &id, include, out)
+        })
+    }
+}
+
+/// Runs a dump on a blocking thread, streaming what it writes.
+pub(crate) fn stream_dump(
+    dump: impl FnOnce(&mut ChunkWriter) -> Result<SnapshotManifest> + Send + 'static,
+) -> StateSnapshot {
+    let (tx, rx) = mpsc::channel(SNAPSHOT_CHUNKS_BUFFERED);
+    let manifest = tokio::task::spawn_blocking(move || {
+        let mut out = ChunkWriter {
+            buffer: Vec::with_capacity(SNAPSHOT_CHUNK_SIZE),
+            tx: tx.clone(),
+        };
+        let result = dump(&mut out).and_then(|manifest| {
+            out.flush()?;
+            Ok(manifest)
+        });
+        // The reader sees a failed dump as an error instead of a short one
+        if let Err(err) = &result {
+            let _ = tx.blocking_send(Err(anyhow!("failed to dump the state store: {}", err)));
+        }
+        result
+    });
+    StateSnapshot {
+        data: ReceiverStream::new(rx),
+        manifest,
+    }
+}
+
+/// Sends what is written to it in chunks, blocking while the reader is
+/// behind.
+pub(crate) struct ChunkWriter {
+    buffer: Vec<u8>,
+    tx: mpsc::Sender<Result<Vec<u8>>>,
+}
+
+impl Write for ChunkWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        if self.buffer.len() >= SNAPSHOT_CHUNK_SIZE {
+            self.flush()?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let chunk = std::mem::replace(&mut self.buffer, Vec::with_capacity(SNAPSHOT_CHUNK_SIZE));
+        self.tx
+            .blocking_send(Ok(chunk))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "snapshot reader dropped"))
+    }
+}
+
+/// Writes the columns of a RocksDB snapshot selected by `include` to `out`,
+/// in the format of `IndexifyState::create_snapshot`.
+pub(crate) fn dump_db(
+    db: &TransactionDB,
+    snapshot: &SnapshotWithThreadMode<'_, TransactionDB>,
+    id: &str,
+    include: impl Fn(&IndexifyObjectsColumns) -> bool,
+    out: &mut impl Write,
+) -> Result<SnapshotManifest> {
+    let mut column_families = BTreeMap::new();
+    let mut blob_references = BTreeSet::new();
+    for column in IndexifyObjectsColumns::iter().filter(|column| include(column)) {
+        let cf = column.cf_db(db);
+        let mut count = 0;
+        for kv in snapshot.iterator_cf(&cf, IteratorMode::Start) {
+            let (key, value) = kv?;
+            collect_blob_references(&column, &value, &mut blob_references)?;
+            write_record(out, column.as_ref().as_bytes(), &key, &value)?;
+            count += 1;
+        }
+        column_families.insert(column.to_string(), count);
+    }
+    Ok(SnapshotManifest {
+        id: id.to_string(),
+        created_at: get_epoch_time_in_ms(),
+        column_families,
+        blob_references: blob_references.into_iter().collect(),
+    })
+}
+
+/// Restores a dump created by `IndexifyState::create_snapshot` into a new
+/// state store at `path`. Refuses to overwrite an existing state store.
+///
+/// The dump is restored into a sibling directory which is moved to `path`
+/// once every record is written, a failed restore leaves no state store
+/// behind.
+pub async fn restore_snapshot<B: AsRef<[u8]>>(
+    path: PathBuf,
+    data: impl Stream<Item = Result<B>> + Unpin,
+) -> Result<()> {
+    if path.exists() && path.read_dir()?.next().is_some() {
+        return Err(anyhow!(
+            "state store {} is not empty, refusing to restore snapshot",
+            path.display()
+        ));
+    }
+    let file_name = path
+        .file_name()
+        .ok_or(anyhow!("invalid state store path {}", path.display()))?;
+    let restoring_path = path.with_file_name(format!("{}.restoring", file_name.to_string_lossy()));
+    // Left behind by a restore which didn't complete
+    if restoring_path.exists() {
+        fs::remove_dir_all(&restoring_path)?;
+    }
+    if let Err(err) = restore_records(&restoring_path, data).await {
+        let _ = fs::remove_dir_all(&restoring_path);
+        return Err(err);
+    }
+    if path.exists() {
+        fs::remove_dir(&path)?;
+    }
+    fs::rename(&restoring_path, &path)?;
+    Ok(())
+}
+
+async fn restore_records<B: AsRef<[u8]>>(
+    path: &Path,
+    mut data: impl Stream<Item = Result<B>> + Unpin,
+) -> Result<()> {
+    let db = open_db(path.to_path_buf())?;
+    let mut reader = RecordReader::default();
+    let mut batch = WriteBatchWithTransaction::<true>::default();
+    while let Some(chunk) = data.next().await {
+        reader.push(chunk?.as_ref());
+        while let Some((column, key, value)) = reader.next_record()? {
+            let cf = db
+                .cf_handle(&column)
+                .ok_or(anyhow!("unknown column family in snapshot: {}", column))?;
+            batch.put_cf(&cf, key, value);
+            if batch.len() >= RESTORE_BATCH_SIZE {
+                db.write(std::mem::take(&mut batch))?;
+            }
+        }
+    }
+    reader.finish()?;
+    db.write(batch)?;
+    Ok(())
+}
+
+/// Parses the records of a dump received in chunks.
+#[derive(Default)]
+pub(crate) struct RecordReader {
+    buffer: Vec<u8>,
+    // Start of the first record not read yet
+    pos: usize,
+}
+
+impl RecordReader {
+    pub(crate) fn push(&mut self, chunk: &[u8]) {
+        // Drops the records already read before growing the buffer
+        if self.pos > 0 && self.pos >= self.buffer.len() / 2 {
+            self.buffer.drain(..self.pos);
+            self.pos = 0;
+        }
+        self.buffer.extend_from_slice(chunk);
+    }
+
+    /// Next `(column family, key, value)` record, None until the chunks
+    /// holding all of it were pushed.
+    pub(crate) fn next_record(&mut self) -> Result<Option<(String, Vec<u8>, Vec<u8>)>> {
+        let mut cursor = &self.buffer[self.pos..];
+        let mut fields = Vec::with_capacity(3);
+        for _ in 0..3 {
+            match read_field(&mut cursor) {
+                Ok(field) => fields.push(field),
+                Err(_) => return Ok(None),
+            }
+        }
+        let record = (
+            String::from_utf8(fields[0].to_vec())?,
+            fields[1].to_vec(),
+            fields[2].to_vec(),
+        );
+        self.pos = self.buffer.len() - cursor.len();
+        Ok(Some(record))
+    }
+
+    /// Fails when the dump ended in the middle of a record.
+    pub(crate) fn finish(&self) -> Result<()> {
+        if self.pos < self.buffer.len() {
+            return Err(anyhow!("snapshot is truncated"));
+        }
+        Ok(())
+    }
+}
+
+fn collect_blob_references(
+    column: &IndexifyObjectsColumns,
+    value: &[u8],
+    urls: &mut BTreeSet<String>,
+) -> Result<()> {
+    match column {
+        IndexifyObjectsColumns::ComputeGraphs => {
+            let graph = JsonEncoder::decode::<ComputeGraph>(value)?;
+            urls.insert(graph.code.path);
+        }
+        IndexifyObjectsColumns::GraphInvocations => {
+            let invocation = JsonEncoder::decode::<InvocationPayload>(value)?;
+            urls.insert(invocation.payload.path);
+        }
+        IndexifyObjectsColumns::FnOutputs => {
+            let output = JsonEncoder::decode::<NodeOutput>(value)?;
+            match output.payload {
+                // Inline payloads are part of the dump
+                OutputPayload::Fn(payload) if !payload.is_inline() => {
+                    urls.insert(payload.path);
+                }
+                _ => {}
+            }
+            if let Some(errors) = output.errors {
+                urls.insert(errors.path);
+            }
+        }
+        IndexifyObjectsColumns::Tasks => {
+            let task = JsonEncoder::decode::<Task>(value)?;
+            if let Some(diagnostics) = task.diagnostics {
+                for payload in [
+                    diagnostics.exception,
+                    diagnostics.stdout,
+                    diagnostics.stderr,
+                    diagnostics.structured_logs,
+                ]
+                .into_iter()
+                .flatten()
+                {
+                    urls.insert(payload.path);
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+pub(crate) fn write_record(out: &mut impl Write, column: &[u8], key: &[u8], value: &[u8]) -> io::Result<()> {
+    for field in [column, key, value] {
+        out.write_all(&(field.len() as u32).to_be_bytes())?;
+        out.write_all(field)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn write_field(data: &mut Vec<u8>, field: &[u8]) {
+    data.extend_from_slice(&(field.len() as u32).to_be_bytes());
+    data.extend_from_slice(field);
+}
+
+pub(crate) fn read_field<'a>(cursor: &mut &'a [u8]) -> Result<&'a [u8]> {
+    if cursor.len() < 4 {
+        return Err(anyhow!("snapshot is truncated"));
+    }
+    let (len, rest) = cursor.split_at(4);
+    let len = u32::from_be_bytes(len.try_into()?) as usize;
+    if rest.len() < len {
+        return Err(anyhow!("snapshot is truncated"));
+    }
+    let (field, rest) = rest.split_at(len);
+    *cursor = rest;
+    Ok(field)
+}
+
+#[cfg(test)]
+mod tests {
+    use data_model::test_objects::tests::{mock_graph_a, TEST_NAMESPACE};
+
+    use super::*;
+    use crate::requests::{CreateComputeGraphRequest, RequestPayload, StateMachineUpdateRequest};
+
+    #[tokio::test]
+    async fn test_snapshot_and_restore() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: mock_graph_a(),
+                    expected_revision: None,
+                    force: false,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+
+        let snapshot = indexify_state.create_snapshot("snap");
+        let chunks: Vec<Vec<u8>> = snapshot
+            .data
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<Result<_>>()?;
+        let manifest = snapshot.manifest.await??;
+        assert_eq!(manifest.column_families.get("ComputeGraphs"), Some(&1));
+        let graph = mock_graph_a();
+        assert!(manifest.blob_references.contains(&graph.code.path));
+
+        // Records are split across the chunks they are read from
+        let data = chunks.concat();
+        let stream = |data: &[u8]| {
+            futures::stream::iter(
+                data.chunks(7)
+                    .map(|chunk| Ok(chunk.to_vec()))
+                    .collect::<Vec<Result<Vec<u8>>>>(),
+            )
+        };
+        let restore_path = temp_dir.path().join("restored");
+        restore_snapshot(restore_path.clone(), stream(&data)).await?;
+        assert!(!temp_dir.path().join("restored.restoring").exists());
+        let restored = IndexifyState::new(restore_path.clone()).await?;
+        let (graphs, _) = restored
+            .reader()
+            .list_compute_graphs(TEST_NAMESPACE, None, None)?;
+        assert_eq!(graphs.len(), 1);
+        assert_eq!(graphs[0].name, graph.name);
+
+        // Restoring over an existing state store is rejected
+        assert!(restore_snapshot(restore_path, stream(&data)).await.is_err());
+
+        // A truncated dump leaves no state store behind
+        let truncated_path = temp_dir.path().join("truncated");
+        let err = restore_snapshot(truncated_path.clone(), stream(&data[..data.len() - 1]))
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "snapshot is truncated");
+        assert!(!truncated_path.exists());
+        assert!(!temp_dir.path().join("truncated.restoring").exists());
+        Ok(())
+    }
+}
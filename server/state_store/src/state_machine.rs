@@ -1,28 +1,82 @@
-use std::{collections::HashMap, sync::Arc, vec};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+    vec,
+};
 
-use anyhow::{anyhow, Result};
+use anyhow::Result;
 use data_model::{
+    alerts::{AlertState, AlertStatus},
+    attributes,
+    blacklist::ExecutorBlacklistEntry,
+    canary::GraphCanary,
+    concurrency_key::evaluate_concurrency_key,
+    data_deletion::{DataDeletion, DataDeletionMode, DeletedInvocation},
+    default_scheduling_weight,
+    error::DataModelError,
+    executor_approval::ExecutorApproval,
+    executor_reconciliation::{ExecutorReconciliation, ReconciledTask, ReconciliationOutcome},
+    invocation_group::InvocationGroup,
+    is_inline_path,
+    keys::{self, KeyPrefix},
+    legal_hold::{LegalHold, LegalHoldAction, LegalHoldAuditEntry},
+    sla::SlaStatus,
+    timeline::{InvocationEvent, InvocationEventKind},
+    webhooks::{Webhook, WebhookDelivery, WebhookEvent},
+    AbortCommand,
     ChangeType,
     ComputeGraph,
+    DataPayload,
+    ExecutorFeature,
     ExecutorId,
+    ExecutorMetadata,
+    ExecutorPool,
+    FanOutCursor,
     GraphInvocationCtx,
     GraphInvocationCtxBuilder,
+    GraphPause,
+    GraphStorageUsage,
+    GraphTaskUsage,
+    GraphVersion,
+    InvocationError,
+    InvocationPayload,
+    InvocationResult,
+    InvocationStateEntry,
+    InvocationStatus,
     InvokeComputeGraphEvent,
     Namespace,
+    Node,
     NodeOutput,
+    NodeOutputBuilder,
+    OutputLimits,
     OutputPayload,
+    ParentInvocation,
+    PendingUpload,
+    RouterOutput,
+    SchedulingLane,
     StateChange,
     StateChangeBuilder,
     StateChangeId,
+    StorageTier,
+    StorageUsage,
     SystemTask,
     Task,
     TaskAnalytics,
+    TaskFailure,
+    TaskId,
+    TaskOutcome,
+    TaskUsage,
+    Window,
+    WindowItem,
+    WindowWatermark,
 };
 use indexify_utils::{get_epoch_time_in_ms, OptionInspectNone};
 use rocksdb::{
-    AsColumnFamilyRef,
     BoundColumnFamily,
-    Direction,
     IteratorMode,
     OptimisticTransactionDB,
     ReadOptions,
@@ -32,20 +86,61 @@ use rocksdb::{
 use strum::AsRefStr;
 use tracing::error;
 
-use super::serializer::{JsonEncode, JsonEncoder};
-use crate::requests::{
-    CreateTasksRequest,
-    DeleteInvocationRequest,
-    DeregisterExecutorRequest,
-    FinalizeTaskRequest,
-    InvokeComputeGraphRequest,
-    NamespaceRequest,
-    ReductionTasks,
-    RegisterExecutorRequest,
-    RemoveSystemTaskRequest,
-    RerunComputeGraphRequest,
-    RerunInvocationRequest,
-    UpdateSystemTaskRequest,
+use super::serializer::{BinaryEncoder, JsonEncode, JsonEncoder};
+use crate::{
+    error::StateStoreError,
+    prefix::{delete_cf_prefix, make_prefix_iterator, RangeDeletes},
+    requests::{
+        AcknowledgeAbortsRequest,
+        BlacklistExecutorsRequest,
+        CheckpointTaskRequest,
+        CloseInvocationGroupRequest,
+        CloseWindowRequest,
+        CreateComputeGraphRequest,
+        CreateExecutorPoolRequest,
+        CreateGraphCanaryRequest,
+        CreateInvocationGroupRequest,
+        CreateTasksRequest,
+        CreateWebhookRequest,
+        DeleteExecutorPoolRequest,
+        DeleteIngestedDataRequest,
+        DeleteInvocationRequest,
+        DeleteWebhookRequest,
+        DeregisterExecutorRequest,
+        FinalizeTaskRequest,
+        GraphCanaryRequest,
+        InvokeComputeGraphRequest,
+        LegalHoldRequest,
+        MovePayloadsRequest,
+        NamespaceRequest,
+        PayloadOwner,
+        ReconcileExecutorTasksRequest,
+        RecordAlertStatusesRequest,
+        RecordDataRejectionRequest,
+        RecordExecutorApprovalRequest,
+        RecordFunctionLatenciesRequest,
+        RecordPayloadReplicasRequest,
+        RecordSlaStatusRequest,
+        RedriveWebhookDeadLettersRequest,
+        ReductionTasks,
+        RegisterExecutorRequest,
+        RegisterPendingUploadsRequest,
+        RemoveExecutorBlacklistEntryRequest,
+        RemoveSystemTaskRequest,
+        RerunComputeGraphRequest,
+        RerunInvocationRequest,
+        SetInvocationStateRequest,
+        SignalInvocationRequest,
+        StreamTaskOutputsRequest,
+        SubGraphInvocationRequest,
+        SweepPendingUploadsRequest,
+        UpdateExecutorArtifactsRequest,
+        UpdateExecutorLabelsRequest,
+        UpdateGraphCanaryRequest,
+        UpdateSystemTaskRequest,
+        UpdateWebhookDeliveriesRequest,
+        DEFAULT_REQUEUE_BATCH_SIZE,
+    },
 };
 
 pub type ContentId = String;
@@ -59,30 +154,64 @@ pub type SchemaId = String;
 
 #[derive(AsRefStr, strum::Display, strum::EnumIter)]
 pub enum IndexifyObjectsColumns {
-    StateMachineMetadata, //  StateMachineMetadata
-    Executors,            //  ExecutorId -> Executor Metadata
-    Namespaces,           //  Namespaces
-    ComputeGraphs,        //  Ns_ComputeGraphName -> ComputeGraph
+    StateMachineMetadata,    //  StateMachineMetadata
+    Executors,               //  ExecutorId -> Executor Metadata
+    ExecutorPools,           //  Ns_PoolName -> ExecutorPool
+    ExecutorApprovals,       //  ExecutorId -> ExecutorApproval
+    ExecutorReconciliations, //  ExecutorId_RestartedAt -> ExecutorReconciliation
+    PendingAborts,           //  ExecutorId_TaskId -> AbortCommand
+    Namespaces,              //  Namespaces
+    ComputeGraphs,           //  Ns_ComputeGraphName -> ComputeGraph
+    DeletedComputeGraphs,    //  Ns_ComputeGraphName -> ComputeGraph
+    CodeArtifacts,           //  Sha256 -> ComputeGraphCode
+    StorageUsage,            //  Ns_ComputeGraphName -> GraphStorageUsage
+    TaskUsage,               //  Ns_ComputeGraphName -> GraphTaskUsage
+    SlaStatus,               //  Ns_ComputeGraphName -> SlaStatus
+    AlertStatuses,           //  RuleName -> AlertStatus
+    FunctionLatencies,       //  Ns_ComputeGraphName_Fn -> FunctionLatency
+    GraphCanaries,           //  Ns_ComputeGraphName -> GraphCanary
 
-    Tasks,              //  Ns_CG_<Invocation_Id>_Fn_TaskId -> Task
-    GraphInvocationCtx, //  Ns_CG_IngestedId -> GraphInvocationCtx
-    ReductionTasks,     //  Ns_CG_Fn_TaskId -> ReduceTask
+    Tasks,              //  Ns_CG_<Invocation_Id>_Fn_TaskId (encoded) -> Task
+    GraphInvocationCtx, //  Ns_CG_IngestedId (encoded) -> GraphInvocationCtx
+    InvocationResults,  //  Ns_CG_IngestedId (encoded) -> InvocationResult
+    InvocationState,    //  Ns_CG_IngestedId_Key (encoded) -> InvocationStateEntry
+    InvocationEvents,   //  Ns_CG_IngestedId_Time_Seq (encoded) -> InvocationEvent
+    ReductionTasks,     //  Ns_CG_<Invocation_Id>_Fn_TaskId_OutputKey (encoded) -> ReduceTask
+    FanOutCursors,      //  Ns_CG_<Invocation_Id>_TaskId (encoded) -> FanOutCursor
+    WindowItems,        //  Ns_CG_Window_BufferedAt_TaskKey -> WindowItem
+    WindowWatermarks,   //  Ns_CG_Window -> WindowWatermark
+    ExecutorBlacklist,  //  Ns_CG_Fn_ExecutorId -> ExecutorBlacklistEntry
+    LegalHolds,         //  Ns_CG_<Invocation_Id> -> LegalHold
+    LegalHoldAudit,     //  Ns_Time_Seq -> LegalHoldAuditEntry
+    DataDeletions,      //  Ns_CG_<Invocation_Id> -> DataDeletion
 
-    GraphInvocations, //  Ns_Graph_Id -> InvocationPayload
-    FnOutputs,        //  Ns_Graph_<Ingested_Id>_Fn_Id -> NodeOutput
-    TaskOutputs,      //  NS_TaskID -> NodeOutputID
+    GraphInvocations,       //  Ns_Graph_Id (encoded) -> InvocationPayload
+    InvocationAttributes,   //  Ns_Graph_Attribute_Value_Id -> InvocationId
+    InvocationGroups,       //  Ns_GroupId -> InvocationGroup
+    InvocationGroupMembers, //  Ns_GroupId_Graph_Id -> Empty
+    FnOutputs,              //  Ns_Graph_<Ingested_Id>_Fn_Id (encoded) -> NodeOutput
+    TaskOutputs,            //  NS_TaskID_OutputID (encoded) -> NodeOutput key
 
     StateChanges, //  StateChangeId -> StateChange
 
     UnprocessedStateChanges, //  StateChangeId -> Empty
-    TaskAllocations,         //  ExecutorId -> Task_Key
+    TaskAllocations,         //  ExecutorId_Nsecs (encoded)_Task_Key -> Empty
     UnallocatedTasks,        //  Task_Key -> Empty
 
-    GcUrls, // List of URLs pending deletion
+    GcUrls,         // List of URLs pending deletion
+    PendingUploads, // Url -> PendingUpload of a blob not committed yet
+    InlinePayloads, // Inline path -> Payload of a small task output
 
     SystemTasks, // Long running tasks involving multiple invocations
 
+    Webhooks,           //  Ns_WebhookId -> Webhook
+    WebhookDeliveries,  //  Ns_WebhookId_EventId -> WebhookDelivery
+    WebhookDeadLetters, //  Ns_WebhookId_EventId -> WebhookDelivery
+
     Stats, // Stats
+
+    ReplicationLog,   // Seq -> Committed write batch
+    ReplicationState, // Vote -> Term and node voted for in the term
 }
 
 impl IndexifyObjectsColumns {
@@ -103,13 +232,24 @@ impl IndexifyObjectsColumns {
     }
 }
 
-pub(crate) fn create_namespace(db: Arc<TransactionDB>, req: &NamespaceRequest) -> Result<()> {
+pub(crate) fn create_namespace(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &NamespaceRequest,
+) -> Result<()> {
     let ns = Namespace {
         name: req.name.clone(),
         created_at: get_epoch_time_in_ms(),
+        queue_limits: req.queue_limits.clone(),
+        scheduling_weight: req
+            .scheduling_weight
+            .unwrap_or_else(default_scheduling_weight),
+        reserved_capacity: req.reserved_capacity.clone(),
+        replication: req.replication.clone(),
+        deleted_at: None,
     };
     let serialized_namespace = JsonEncoder::encode(&ns)?;
-    db.put_cf(
+    txn.put_cf(
         &IndexifyObjectsColumns::Namespaces.cf_db(&db),
         &ns.name,
         serialized_namespace,
@@ -117,6 +257,316 @@ pub(crate) fn create_namespace(db: Arc<TransactionDB>, req: &NamespaceRequest) -
     Ok(())
 }
 
+/// Fails with a conflict while the namespace is being deleted.
+pub(crate) fn check_namespace_writable(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    namespace: &str,
+) -> Result<()> {
+    let deleting = txn
+        .get_for_update_cf(
+            &IndexifyObjectsColumns::Namespaces.cf_db(&db),
+            namespace,
+            false,
+        )?
+        .map(|ns| JsonEncoder::decode::<Namespace>(&ns))
+        .transpose()?
+        .is_some_and(|ns| ns.deleted_at.is_some());
+    if deleting {
+        return Err(
+            StateStoreError::Conflict(format!("namespace {} is being deleted", namespace)).into(),
+        );
+    }
+    Ok(())
+}
+
+/// Marks the namespace as being deleted, deletes its compute graphs and
+/// cancels its running invocations. Returns the allocated tasks of the
+/// cancelled invocations, by executor.
+pub(crate) fn delete_namespace(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    namespace: &str,
+) -> Result<HashMap<ExecutorId, Vec<TaskId>>> {
+    let namespaces_cf = IndexifyObjectsColumns::Namespaces.cf_db(&db);
+    let ns = txn
+        .get_for_update_cf(&namespaces_cf, namespace, true)?
+        .ok_or(StateStoreError::not_found(format!(
+            "namespace {}",
+            namespace
+        )))?;
+    let mut ns: Namespace = JsonEncoder::decode(&ns)?;
+    if ns.deleted_at.is_some() {
+        return Ok(HashMap::new());
+    }
+    check_not_held(
+        db.clone(),
+        txn,
+        KeyPrefix::namespace(namespace).as_bytes(),
+        &format!("namespace {}", namespace),
+    )?;
+    ns.deleted_at = Some(get_epoch_time_in_ms());
+    txn.put_cf(&namespaces_cf, namespace, JsonEncoder::encode(&ns)?)?;
+
+    let prefix = format!("{}|", namespace);
+    let mut graphs = vec![];
+    for kv in make_prefix_iterator(
+        txn,
+        &IndexifyObjectsColumns::ComputeGraphs.cf_db(&db),
+        prefix.as_bytes(),
+        &None,
+    ) {
+        let (_, value) = kv?;
+        graphs.push(JsonEncoder::decode::<ComputeGraph>(&value)?.name);
+    }
+    for graph in graphs {
+        delete_compute_graph(db.clone(), txn, namespace, &graph)?;
+    }
+
+    let encoded_prefix = KeyPrefix::encoded(&[namespace]);
+    let mut running = vec![];
+    for kv in make_prefix_iterator(
+        txn,
+        &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
+        encoded_prefix.as_bytes(),
+        &None,
+    ) {
+        let (key, value) = kv?;
+        let ctx: GraphInvocationCtx = JsonEncoder::decode(&value)?;
+        if !ctx.completed {
+            running.push((key, ctx));
+        }
+    }
+    for (key, mut ctx) in running {
+        release_pending_invocation(db.clone(), txn, &ctx)?;
+        ctx.completed = true;
+        txn.put_cf(
+            &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
+            key,
+            JsonEncoder::encode(&ctx)?,
+        )?;
+    }
+
+    // Pending tasks are never scheduled, allocated ones are aborted
+    let mut cancelled: HashMap<Vec<u8>, TaskId> = HashMap::new();
+    for kv in make_prefix_iterator(
+        txn,
+        &IndexifyObjectsColumns::Tasks.cf_db(&db),
+        encoded_prefix.as_bytes(),
+        &None,
+    ) {
+        let (key, value) = kv?;
+        let task: Task = JsonEncoder::decode(&value)?;
+        if !task.terminal_state() {
+            cancelled.insert(key.to_vec(), task.id);
+        }
+    }
+    for key in cancelled.keys() {
+        txn.delete_cf(&IndexifyObjectsColumns::UnallocatedTasks.cf_db(&db), key)?;
+    }
+    let allocations_cf = IndexifyObjectsColumns::TaskAllocations.cf_db(&db);
+    let mut allocations = vec![];
+    for kv in txn.iterator_cf_opt(&allocations_cf, ReadOptions::default(), IteratorMode::Start) {
+        let (key, _) = kv?;
+        let task_key = Task::key_from_allocation_key(&key)?;
+        if let Some(task_id) = cancelled.get(&task_key) {
+            allocations.push((key, task_id.clone()));
+        }
+    }
+    let mut aborted: HashMap<ExecutorId, Vec<TaskId>> = HashMap::new();
+    for (key, task_id) in allocations {
+        txn.delete_cf(&allocations_cf, &key)?;
+        aborted
+            .entry(Task::executor_from_allocation_key(&key)?)
+            .or_default()
+            .push(task_id);
+    }
+    Ok(aborted)
+}
+
+/// Removes a namespace being deleted once all its compute graphs are purged,
+/// along with its remaining objects. Returns whether it was removed.
+pub(crate) fn purge_namespace(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    range_deletes: &mut RangeDeletes,
+    namespace: &str,
+) -> Result<bool> {
+    let namespaces_cf = IndexifyObjectsColumns::Namespaces.cf_db(&db);
+    let Some(ns) = txn.get_for_update_cf(&namespaces_cf, namespace, true)? else {
+        return Ok(false);
+    };
+    if JsonEncoder::decode::<Namespace>(&ns)?.deleted_at.is_none() {
+        return Ok(false);
+    }
+    let prefix = KeyPrefix::namespace(namespace);
+    for column in [
+        IndexifyObjectsColumns::ComputeGraphs,
+        IndexifyObjectsColumns::DeletedComputeGraphs,
+    ] {
+        if let Some(kv) =
+            make_prefix_iterator(txn, &column.cf_db(&db), prefix.as_bytes(), &None).next()
+        {
+            kv?;
+            return Ok(false);
+        }
+    }
+    for column in [
+        IndexifyObjectsColumns::Tasks,
+        IndexifyObjectsColumns::TaskOutputs,
+    ] {
+        range_deletes.delete_prefix(column, KeyPrefix::encoded(&[namespace]));
+    }
+    for column in [
+        IndexifyObjectsColumns::ExecutorPools,
+        IndexifyObjectsColumns::ExecutorBlacklist,
+        IndexifyObjectsColumns::InvocationGroups,
+        IndexifyObjectsColumns::InvocationGroupMembers,
+        IndexifyObjectsColumns::Webhooks,
+        IndexifyObjectsColumns::WebhookDeliveries,
+        IndexifyObjectsColumns::WebhookDeadLetters,
+    ] {
+        range_deletes.delete_prefix(column, prefix.clone());
+    }
+    txn.delete_cf(&namespaces_cf, namespace)?;
+    Ok(true)
+}
+
+pub fn pending_invocations_key(namespace: &str, compute_graph: &str) -> String {
+    format!("pending_invocations|{}|{}", namespace, compute_graph)
+}
+
+/// Frees the slot of an unfinished user invocation in the pending invocations
+/// of its graph, before it's marked completed or removed.
+fn release_pending_invocation(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    ctx: &GraphInvocationCtx,
+) -> Result<()> {
+    if ctx.completed || ctx.is_system_task {
+        return Ok(());
+    }
+    update_stats_counter(
+        db,
+        txn,
+        pending_invocations_key(&ctx.namespace, &ctx.compute_graph_name).as_bytes(),
+        -1,
+    )
+}
+
+fn update_stats_counter(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    key: &[u8],
+    delta: i64,
+) -> Result<()> {
+    let cf = IndexifyObjectsColumns::Stats.cf_db(&db);
+    let value = txn.get_for_update_cf(&cf, key, true)?;
+    let counter = match value {
+        Some(value) => {
+            let bytes: [u8; 8] = value
+                .as_slice()
+                .try_into()
+                .map_err(|_| anyhow::anyhow!("Invalid length for u64 conversion"))?;
+            u64::from_be_bytes(bytes)
+        }
+        None => 0,
+    };
+    let counter = counter.saturating_add_signed(delta);
+    txn.put_cf(&cf, key, &counter.to_be_bytes())?;
+    Ok(())
+}
+
+/// Applies `update` to the storage usage of a compute graph, in the same
+/// transaction as the write or deletion of the data.
+fn update_storage_usage(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    namespace: &str,
+    compute_graph: &str,
+    update: impl FnOnce(&mut StorageUsage),
+) -> Result<()> {
+    let cf = IndexifyObjectsColumns::StorageUsage.cf_db(&db);
+    let key = format!("{}|{}", namespace, compute_graph);
+    let mut usage = match txn.get_for_update_cf(&cf, &key, true)? {
+        Some(value) => JsonEncoder::decode::<GraphStorageUsage>(&value)?,
+        None => GraphStorageUsage {
+            namespace: namespace.to_string(),
+            compute_graph: compute_graph.to_string(),
+            usage: StorageUsage::default(),
+        },
+    };
+    update(&mut usage.usage);
+    txn.put_cf(&cf, &key, JsonEncoder::encode(&usage)?)?;
+    Ok(())
+}
+
+/// Adds the usage of a finished task to the usage of its compute graph.
+fn add_task_usage(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    namespace: &str,
+    compute_graph: &str,
+    task_usage: &TaskUsage,
+) -> Result<()> {
+    let cf = IndexifyObjectsColumns::TaskUsage.cf_db(&db);
+    let key = format!("{}|{}", namespace, compute_graph);
+    let mut usage = match txn.get_for_update_cf(&cf, &key, true)? {
+        Some(value) => JsonEncoder::decode::<GraphTaskUsage>(&value)?,
+        None => GraphTaskUsage {
+            namespace: namespace.to_string(),
+            compute_graph: compute_graph.to_string(),
+            usage: TaskUsage::default(),
+        },
+    };
+    usage.usage.add(task_usage);
+    txn.put_cf(&cf, &key, JsonEncoder::encode(&usage)?)?;
+    Ok(())
+}
+
+/// Size of the input of a task, the invocation payload for tasks of the
+/// start node.
+fn task_input_size(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    task: &Task,
+) -> Result<u64> {
+    if task.input_node_output_key == task.invocation_id {
+        let key = InvocationPayload::key_from(
+            &task.namespace,
+            &task.compute_graph_name,
+            &task.invocation_id,
+        );
+        return Ok(
+            match txn.get_cf(&IndexifyObjectsColumns::GraphInvocations.cf_db(&db), &key)? {
+                Some(value) => {
+                    JsonEncoder::decode::<InvocationPayload>(&value)?
+                        .payload
+                        .size
+                }
+                None => 0,
+            },
+        );
+    }
+    // Usage isn't accounted for inputs whose key doesn't name an output
+    let Ok(key) = NodeOutput::key_from_input_key(&task.input_node_output_key) else {
+        return Ok(0);
+    };
+    Ok(
+        match txn.get_cf(&IndexifyObjectsColumns::FnOutputs.cf_db(&db), key)? {
+            Some(value) => output_size(&JsonEncoder::decode::<NodeOutput>(&value)?),
+            None => 0,
+        },
+    )
+}
+
+fn output_size(output: &NodeOutput) -> u64 {
+    match &output.payload {
+        OutputPayload::Fn(payload) => payload.size,
+        OutputPayload::Router(_) => 0,
+    }
+}
+
 pub fn remove_system_task(
     db: Arc<TransactionDB>,
     txn: &Transaction<TransactionDB>,
@@ -135,10 +585,10 @@ pub fn update_system_task(
     let key = SystemTask::key_from(&req.namespace, &req.compute_graph_name);
     let task = txn
         .get_for_update_cf(&IndexifyObjectsColumns::SystemTasks.cf_db(&db), &key, true)?
-        .ok_or(anyhow::anyhow!("Task not found"))?;
+        .ok_or(StateStoreError::not_found(format!("system task {}", key)))?;
     let mut task = JsonEncoder::decode::<SystemTask>(&task)?;
     task.restart_key = Some(req.restart_key);
-    let serialized_task = JsonEncoder::encode(&task)?;
+    let serialized_task = BinaryEncoder::encode(&task)?;
     txn.put_cf(
         &IndexifyObjectsColumns::SystemTasks.cf_db(&db),
         &key,
@@ -159,7 +609,10 @@ pub fn rerun_compute_graph(
             &key,
             false,
         )?
-        .ok_or(anyhow::anyhow!("Compute graph not found"))?;
+        .ok_or(StateStoreError::not_found(format!(
+            "compute graph {}",
+            req.compute_graph_name
+        )))?;
     let graph: ComputeGraph = JsonEncoder::decode(&graph).unwrap();
     let task_key = SystemTask::key_from(&req.namespace, &req.compute_graph_name);
     let existing_task = txn.get_for_update_cf(
@@ -170,7 +623,7 @@ pub fn rerun_compute_graph(
     if let Some(existing_task) = existing_task {
         let existing_task: SystemTask = JsonEncoder::decode(&existing_task)?;
         if existing_task.graph_version >= graph.version {
-            return Err(anyhow::anyhow!("Task already exists"));
+            return Err(StateStoreError::Conflict("system task already exists".to_string()).into());
         }
     }
     let task = SystemTask::new(
@@ -178,7 +631,7 @@ pub fn rerun_compute_graph(
         req.compute_graph_name.clone(),
         graph.version,
     );
-    let serialized_task = JsonEncoder::encode(&task)?;
+    let serialized_task = BinaryEncoder::encode(&task)?;
     txn.put_cf(
         &IndexifyObjectsColumns::SystemTasks.cf_db(&db),
         &task_key,
@@ -201,7 +654,10 @@ pub fn rerun_invocation(
             &graph_ctx_key,
             true,
         )?
-        .ok_or(anyhow::anyhow!("Graph context not found"))?;
+        .ok_or(StateStoreError::not_found(format!(
+            "graph context {}",
+            req.invocation_id
+        )))?;
     let graph_ctx: GraphInvocationCtx = JsonEncoder::decode(&graph_ctx)?;
     if graph_ctx.graph_version >= req.graph_version {
         tracing::info!(
@@ -210,10 +666,8 @@ pub fn rerun_invocation(
         );
         return Ok(Vec::new());
     }
-    let output_key = format!(
-        "{}|{}|{}|",
-        req.namespace, req.compute_graph_name, req.invocation_id
-    );
+    let output_key =
+        KeyPrefix::encoded(&[&req.namespace, &req.compute_graph_name, &req.invocation_id]);
     let outputs = make_prefix_iterator(
         txn,
         &IndexifyObjectsColumns::FnOutputs.cf_db(&db),
@@ -239,7 +693,10 @@ pub fn rerun_invocation(
             &compute_graph_key,
             false,
         )?
-        .ok_or(anyhow::anyhow!("Compute graph not found"))?;
+        .ok_or(StateStoreError::not_found(format!(
+            "compute graph {}",
+            req.compute_graph_name
+        )))?;
     let graph = JsonEncoder::decode::<ComputeGraph>(&graph)?;
     if graph.version > req.graph_version {
         // Graph was updated after rerun task was created
@@ -254,14 +711,38 @@ pub fn rerun_invocation(
         output_key.as_bytes(),
         &None,
     );
+    let mut deleted_bytes = 0;
     for output in outputs {
-        let (key, _) = output?;
+        let (key, value) = output?;
+        deleted_bytes += output_size(&JsonEncoder::decode::<NodeOutput>(&value)?);
         txn.delete_cf(&IndexifyObjectsColumns::FnOutputs.cf_db(&db), key)?;
     }
+    update_storage_usage(
+        db.clone(),
+        txn,
+        &req.namespace,
+        &req.compute_graph_name,
+        |usage| usage.output_bytes = usage.output_bytes.saturating_sub(deleted_bytes),
+    )?;
+    release_pending_invocation(db.clone(), txn, &graph_ctx)?;
     txn.delete_cf(
         &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
+        &graph_ctx_key,
+    )?;
+    txn.delete_cf(
+        &IndexifyObjectsColumns::InvocationResults.cf_db(&db),
         graph_ctx_key,
     )?;
+    delete_cf_prefix(
+        txn,
+        &IndexifyObjectsColumns::InvocationState.cf_db(&db),
+        InvocationStateEntry::key_prefix(
+            &req.namespace,
+            &req.compute_graph_name,
+            &req.invocation_id,
+        )
+        .as_bytes(),
+    )?;
 
     // Create a new invocation context after all checks passed
     let graph_invocation_ctx = GraphInvocationCtxBuilder::default()
@@ -271,6 +752,8 @@ pub fn rerun_invocation(
         .invocation_id(req.invocation_id.clone())
         .fn_task_analytics(HashMap::new())
         .is_system_task(true)
+        // Reruns backfill the outputs of the new version of the graph
+        .lane(SchedulingLane::Batch)
         .build(graph)?;
     txn.put_cf(
         &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
@@ -310,10 +793,168 @@ pub fn rerun_invocation(
     Ok(vec![state_change])
 }
 
+// Tells apart the invocation events recorded at the same time
+static INVOCATION_EVENT_SEQ: AtomicU32 = AtomicU32::new(0);
+
+/// Appends events to the timeline of an invocation.
+fn put_invocation_events(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    namespace: &str,
+    compute_graph: &str,
+    invocation_id: &str,
+    kinds: impl IntoIterator<Item = InvocationEventKind>,
+) -> Result<()> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?;
+    for kind in kinds {
+        let key = InvocationEvent::key(
+            namespace,
+            compute_graph,
+            invocation_id,
+            now.as_nanos(),
+            INVOCATION_EVENT_SEQ.fetch_add(1, Ordering::Relaxed),
+        );
+        let event = InvocationEvent {
+            at: now.as_millis() as u64,
+            kind,
+        };
+        txn.put_cf(
+            &IndexifyObjectsColumns::InvocationEvents.cf_db(&db),
+            key,
+            BinaryEncoder::encode(&event)?,
+        )?;
+    }
+    Ok(())
+}
+
 pub fn create_graph_input(
     db: Arc<TransactionDB>,
     txn: &Transaction<TransactionDB>,
     req: &InvokeComputeGraphRequest,
+) -> Result<()> {
+    put_graph_input(db, txn, req, None)
+}
+
+fn put_invocation_attributes(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    payload: &InvocationPayload,
+) -> Result<()> {
+    for (name, value) in &payload.attributes {
+        txn.put_cf(
+            &IndexifyObjectsColumns::InvocationAttributes.cf_db(&db),
+            attributes::index_key(
+                &payload.namespace,
+                &payload.compute_graph_name,
+                name,
+                value,
+                &payload.id,
+            )?,
+            payload.id.as_bytes(),
+        )?;
+    }
+    Ok(())
+}
+
+fn get_invocation_group_for_update(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    namespace: &str,
+    id: &str,
+) -> Result<Option<InvocationGroup>> {
+    txn.get_for_update_cf(
+        &IndexifyObjectsColumns::InvocationGroups.cf_db(&db),
+        InvocationGroup::key_from(namespace, id),
+        true,
+    )?
+    .map(|group| JsonEncoder::decode(&group))
+    .transpose()
+}
+
+fn put_invocation_group(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    group: &InvocationGroup,
+) -> Result<()> {
+    txn.put_cf(
+        &IndexifyObjectsColumns::InvocationGroups.cf_db(&db),
+        group.key(),
+        JsonEncoder::encode(group)?,
+    )?;
+    Ok(())
+}
+
+fn add_invocation_group_member(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    payload: &InvocationPayload,
+    group_id: &str,
+) -> Result<()> {
+    let mut group =
+        get_invocation_group_for_update(db.clone(), txn, &payload.namespace, group_id)?.ok_or(
+            StateStoreError::not_found(format!("invocation group {}", group_id)),
+        )?;
+    if group.closed {
+        return Err(
+            StateStoreError::Conflict(format!("invocation group {} is closed", group_id)).into(),
+        );
+    }
+    group.invocations += 1;
+    put_invocation_group(db.clone(), txn, &group)?;
+    txn.put_cf(
+        &IndexifyObjectsColumns::InvocationGroupMembers.cf_db(&db),
+        group.member_key(&payload.compute_graph_name, &payload.id),
+        [],
+    )?;
+    Ok(())
+}
+
+pub(crate) fn create_invocation_group(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &CreateInvocationGroupRequest,
+) -> Result<()> {
+    if txn
+        .get_for_update_cf(
+            &IndexifyObjectsColumns::InvocationGroups.cf_db(&db),
+            req.group.key(),
+            true,
+        )?
+        .is_some()
+    {
+        return Err(StateStoreError::Conflict(format!(
+            "invocation group {} already exists",
+            req.group.id
+        ))
+        .into());
+    }
+    put_invocation_group(db, txn, &req.group)
+}
+
+/// Closes an invocation group. Returns the group if it finished, which
+/// happens when all its invocations already finished.
+pub(crate) fn close_invocation_group(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &CloseInvocationGroupRequest,
+) -> Result<Option<InvocationGroup>> {
+    let mut group =
+        get_invocation_group_for_update(db.clone(), txn, &req.namespace, &req.id)?.ok_or(
+            StateStoreError::not_found(format!("invocation group {}", req.id)),
+        )?;
+    if group.closed {
+        return Ok(None);
+    }
+    let finished = group.close(get_epoch_time_in_ms());
+    put_invocation_group(db, txn, &group)?;
+    Ok(finished.then_some(group))
+}
+
+fn put_graph_input(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &InvokeComputeGraphRequest,
+    parent: Option<ParentInvocation>,
 ) -> Result<()> {
     let compute_graph_key = format!("{}|{}", req.namespace, req.compute_graph_name);
     let cg = txn
@@ -322,14 +963,60 @@ pub fn create_graph_input(
             &compute_graph_key,
             false,
         )?
-        .ok_or(anyhow::anyhow!("Compute graph not found"))?;
-    let cg: ComputeGraph = JsonEncoder::decode(&cg)?;
+        .ok_or(StateStoreError::not_found(format!(
+            "compute graph {}",
+            req.compute_graph_name
+        )))?;
+    let mut cg: ComputeGraph = JsonEncoder::decode(&cg)?;
+    let pinned_version = req.invocation_payload.graph_version;
+    let canary = get_graph_canary(db.clone(), txn, &req.namespace, &req.compute_graph_name)?
+        .filter(|canary| match pinned_version {
+            Some(version) => canary.compute_graph.version == version,
+            None => canary.samples(&req.invocation_payload.id),
+        });
+    let is_canary = canary.is_some();
+    if let Some(canary) = canary {
+        cg = canary.compute_graph;
+    }
+    if let Some(version) = pinned_version {
+        if version != cg.version {
+            return Err(StateStoreError::not_found(format!(
+                "version {} of compute graph {}",
+                version.0, req.compute_graph_name
+            ))
+            .into());
+        }
+    }
+    let parameters = cg.resolve_parameters(&req.invocation_payload.parameters)?;
     let serialized_data_object = JsonEncoder::encode(&req.invocation_payload)?;
     txn.put_cf(
         &IndexifyObjectsColumns::GraphInvocations.cf_db(&db),
         req.invocation_payload.key(),
         &serialized_data_object,
     )?;
+    put_invocation_attributes(db.clone(), txn, &req.invocation_payload)?;
+    if let Some(group) = &req.invocation_payload.group {
+        add_invocation_group_member(db.clone(), txn, &req.invocation_payload, group)?;
+    }
+    // The payload of a sub graph invocation is an output of its parent
+    if parent.is_none() {
+        let size = req.invocation_payload.payload.size;
+        update_storage_usage(
+            db.clone(),
+            txn,
+            &req.namespace,
+            &req.compute_graph_name,
+            |usage| usage.ingestion_bytes += size,
+        )?;
+    }
+
+    for output in req.invocation_payload.part_outputs(cg.version)? {
+        txn.put_cf(
+            &IndexifyObjectsColumns::FnOutputs.cf_db(&db),
+            output.key(&output.invocation_id),
+            JsonEncoder::encode(&output)?,
+        )?;
+    }
 
     let graph_invocation_ctx = GraphInvocationCtxBuilder::default()
         .namespace(req.namespace.to_string())
@@ -337,462 +1024,3122 @@ pub fn create_graph_input(
         .graph_version(cg.version)
         .invocation_id(req.invocation_payload.id.clone())
         .fn_task_analytics(HashMap::new())
+        .parent(parent)
+        .labels(req.invocation_payload.labels.clone())
+        .priority(req.invocation_payload.priority)
+        .canary(is_canary)
+        .group(req.invocation_payload.group.clone())
+        .parameters(parameters)
+        .event_time(req.invocation_payload.event_time)
+        .lane(req.invocation_payload.lane.unwrap_or(cg.default_lane))
         .build(cg)?;
     txn.put_cf(
         &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
         graph_invocation_ctx.key(),
         &JsonEncoder::encode(&graph_invocation_ctx)?,
     )?;
+    put_invocation_events(
+        db.clone(),
+        txn,
+        &req.namespace,
+        &req.compute_graph_name,
+        &req.invocation_payload.id,
+        [InvocationEventKind::Invoked],
+    )?;
+    update_stats_counter(
+        db,
+        txn,
+        pending_invocations_key(&req.namespace, &req.compute_graph_name).as_bytes(),
+        1,
+    )?;
     Ok(())
 }
 
-pub(crate) fn delete_input_data_object(
+/// Starts the child invocation of a sub graph task. The task is never
+/// allocated to an executor, it finishes when the child invocation finishes.
+///
+/// Returns the request finalizing the task if the child invocation could not
+/// be started.
+pub(crate) fn create_sub_graph_invocation(
     db: Arc<TransactionDB>,
-    req: &DeleteInvocationRequest,
-) -> Result<()> {
-    let mut read_options = ReadOptions::default();
-    read_options.set_readahead_size(4_194_304);
-    let prefix = format!(
-        "{}|{}|{}",
-        req.namespace, req.compute_graph, req.invocation_id
-    );
-    let iterator_mode = IteratorMode::From(prefix.as_bytes(), Direction::Forward);
-    let iter = db.iterator_cf_opt(
-        &IndexifyObjectsColumns::GraphInvocations.cf_db(&db),
-        read_options,
-        iterator_mode,
-    );
-    for key in iter {
-        let key = key?;
-        db.delete_cf(&IndexifyObjectsColumns::GraphInvocations.cf_db(&db), &key.0)?;
+    txn: &Transaction<TransactionDB>,
+    req: &SubGraphInvocationRequest,
+) -> Result<Option<FinalizeTaskRequest>> {
+    let parent = &req.parent;
+    txn.delete_cf(
+        &IndexifyObjectsColumns::UnallocatedTasks.cf_db(&db),
+        parent.task_key(),
+    )?;
+    let child_graph = txn.get_cf(
+        &IndexifyObjectsColumns::ComputeGraphs.cf_db(&db),
+        format!("{}|{}", parent.namespace, req.compute_graph),
+    )?;
+    let Some(child_graph) = child_graph else {
+        error!(
+            "sub graph {} of task {} not found",
+            req.compute_graph, parent.task_id
+        );
+        let finalize_req = sub_graph_task_finished(parent, vec![], TaskOutcome::Failure);
+        mark_task_completed(db, txn, finalize_req.clone())?;
+        return Ok(Some(finalize_req));
+    };
+    let child_graph: ComputeGraph = JsonEncoder::decode(&child_graph)?;
+    let mut invoke_req = sub_graph_invoke_request(req);
+    // Child invocations inherit the labels, priority and lane of the parent
+    // invocation, and the values of the parameters the child graph declares
+    let parent_ctx = txn.get_cf(
+        &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
+        GraphInvocationCtx::key_from(
+            &parent.namespace,
+            &parent.compute_graph,
+            &parent.invocation_id,
+        ),
+    )?;
+    if let Some(parent_ctx) = parent_ctx {
+        let parent_ctx: GraphInvocationCtx = JsonEncoder::decode(&parent_ctx)?;
+        invoke_req.invocation_payload.labels = parent_ctx.labels;
+        invoke_req.invocation_payload.priority = parent_ctx.priority;
+        invoke_req.invocation_payload.lane = Some(parent_ctx.lane);
+        invoke_req.invocation_payload.parameters = parent_ctx
+            .parameters
+            .into_iter()
+            .filter(|(name, _)| child_graph.parameters.contains_key(name))
+            .collect();
+    }
+    if let Err(err) = child_graph.resolve_parameters(&invoke_req.invocation_payload.parameters) {
+        error!(
+            "unable to invoke sub graph {} of task {}: {}",
+            req.compute_graph, parent.task_id, err
+        );
+        let finalize_req = sub_graph_task_finished(parent, vec![], TaskOutcome::Failure);
+        mark_task_completed(db, txn, finalize_req.clone())?;
+        return Ok(Some(finalize_req));
     }
+    put_graph_input(db, txn, &invoke_req, Some(parent.clone()))?;
+    Ok(None)
+}
 
-    // FIXME - Delete the data objects which are outputs of the compute functions of
-    // the invocation
-    Ok(())
+pub(crate) fn sub_graph_invoke_request(
+    req: &SubGraphInvocationRequest,
+) -> InvokeComputeGraphRequest {
+    // The task id is unique, unlike the payload derived ids of user invocations
+    InvokeComputeGraphRequest {
+        namespace: req.parent.namespace.clone(),
+        compute_graph_name: req.compute_graph.clone(),
+        invocation_payload: InvocationPayload {
+            id: req.parent.task_id.to_string(),
+            namespace: req.parent.namespace.clone(),
+            compute_graph_name: req.compute_graph.clone(),
+            payload: req.payload.clone(),
+            labels: HashMap::new(),
+            attributes: HashMap::new(),
+            priority: 0,
+            group: None,
+            parameters: HashMap::new(),
+            parts: vec![],
+            event_time: None,
+            graph_version: None,
+            lane: None,
+        },
+    }
 }
 
-pub(crate) fn create_compute_graph(
-    db: Arc<TransactionDB>,
-    mut compute_graph: ComputeGraph,
-) -> Result<()> {
-    let existing_compute_graph = db.get_cf(
+fn sub_graph_task_finished(
+    parent: &ParentInvocation,
+    node_outputs: Vec<NodeOutput>,
+    task_outcome: TaskOutcome,
+) -> FinalizeTaskRequest {
+    FinalizeTaskRequest {
+        namespace: parent.namespace.clone(),
+        compute_graph: parent.compute_graph.clone(),
+        compute_fn: parent.compute_fn.clone(),
+        invocation_id: parent.invocation_id.clone(),
+        task_id: parent.task_id.clone(),
+        node_outputs,
+        task_outcome,
+        // Sub graph tasks are not allocated to an executor
+        executor_id: ExecutorId::default(),
+        diagnostics: None,
+        inline_payloads: vec![],
+        resource_usage: None,
+    }
+}
+
+// Finishes the sub graph task of the parent invocation with the outputs of the
+// terminal nodes of the child invocation.
+fn complete_sub_graph_task(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    child_ctx: &GraphInvocationCtx,
+    parent: &ParentInvocation,
+) -> Result<FinalizeTaskRequest> {
+    let child_graph = txn.get_cf(
         &IndexifyObjectsColumns::ComputeGraphs.cf_db(&db),
-        compute_graph.key(),
+        format!("{}|{}", child_ctx.namespace, child_ctx.compute_graph_name),
     )?;
-
-    if let Some(existing_compute_graph) = existing_compute_graph {
-        let existing_compute_graph: ComputeGraph = JsonEncoder::decode(&existing_compute_graph)?;
-        if compute_graph.code.sha256_hash != existing_compute_graph.code.sha256_hash ||
-            compute_graph.edges != existing_compute_graph.edges ||
-            compute_graph.nodes != existing_compute_graph.nodes ||
-            compute_graph.start_fn != existing_compute_graph.start_fn
-        {
-            compute_graph.version = existing_compute_graph.version.next();
+    let failed = child_ctx
+        .fn_task_analytics
+        .values()
+        .any(|analytics| analytics.failed_tasks > 0);
+    let child_graph: ComputeGraph = match child_graph {
+        Some(child_graph) if !failed => JsonEncoder::decode(&child_graph)?,
+        _ => {
+            let req = sub_graph_task_finished(parent, vec![], TaskOutcome::Failure);
+            mark_task_completed(db, txn, req.clone())?;
+            return Ok(req);
         }
     };
+    let mut node_outputs = vec![];
+    let cf = IndexifyObjectsColumns::FnOutputs.cf_db(&db);
+    for node in child_graph.terminal_nodes() {
+        let prefix = KeyPrefix::encoded(&[
+            &child_ctx.namespace,
+            &child_ctx.compute_graph_name,
+            &child_ctx.invocation_id,
+            node.name(),
+        ]);
+        for kv in make_prefix_iterator(txn, &cf, prefix.as_bytes(), &None) {
+            let (_, value) = kv?;
+            let output: NodeOutput = JsonEncoder::decode(&value)?;
+            if let OutputPayload::Fn(payload) = output.payload {
+                node_outputs.push(
+                    NodeOutputBuilder::default()
+                        .namespace(parent.namespace.clone())
+                        .compute_graph_name(parent.compute_graph.clone())
+                        .compute_fn_name(parent.compute_fn.clone())
+                        .invocation_id(parent.invocation_id.clone())
+                        .payload(OutputPayload::Fn(payload))
+                        .build()?,
+                );
+            }
+        }
+    }
+    let req = sub_graph_task_finished(parent, node_outputs, TaskOutcome::Success);
+    mark_task_completed(db, txn, req.clone())?;
+    Ok(req)
+}
 
-    let serialized_compute_graph = JsonEncoder::encode(&compute_graph)?;
-    db.put_cf(
-        &IndexifyObjectsColumns::ComputeGraphs.cf_db(&db),
-        compute_graph.key(),
-        &serialized_compute_graph,
-    )?;
-    Ok(())
+/// Finishes the tasks of a gate of an invocation waiting for a signal, the
+/// payload of the signal is their output.
+pub(crate) fn signal_invocation(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &SignalInvocationRequest,
+) -> Result<Vec<FinalizeTaskRequest>> {
+    let prefix = Task::key_prefix_for_fn(
+        &req.namespace,
+        &req.compute_graph,
+        &req.invocation_id,
+        &req.gate,
+    );
+    let mut waiting = vec![];
+    let cf = IndexifyObjectsColumns::Tasks.cf_db(&db);
+    for kv in make_prefix_iterator(txn, &cf, &prefix, &None) {
+        let (_, value) = kv?;
+        let task: Task = JsonEncoder::decode(&value)?;
+        if !task.terminal_state() {
+            waiting.push(task);
+        }
+    }
+    if waiting.is_empty() {
+        return Err(StateStoreError::not_found(format!(
+            "task of invocation {} waiting on gate {}",
+            req.invocation_id, req.gate
+        ))
+        .into());
+    }
+    let mut finalized = vec![];
+    for task in waiting {
+        let node_output = NodeOutputBuilder::default()
+            .namespace(req.namespace.clone())
+            .compute_graph_name(req.compute_graph.clone())
+            .compute_fn_name(req.gate.clone())
+            .invocation_id(req.invocation_id.clone())
+            .payload(OutputPayload::Fn(req.payload.clone()))
+            .build()?;
+        let finalize_req = FinalizeTaskRequest {
+            namespace: req.namespace.clone(),
+            compute_graph: req.compute_graph.clone(),
+            compute_fn: req.gate.clone(),
+            invocation_id: req.invocation_id.clone(),
+            task_id: task.id.clone(),
+            node_outputs: vec![node_output],
+            task_outcome: TaskOutcome::Success,
+            // Gate tasks are not allocated to an executor
+            executor_id: ExecutorId::default(),
+            diagnostics: None,
+            inline_payloads: vec![],
+            resource_usage: None,
+        };
+        if mark_task_completed(db.clone(), txn, finalize_req.clone())?.is_some() {
+            finalized.push(finalize_req);
+        }
+    }
+    Ok(finalized)
 }
 
-fn delete_cf_prefix(
+fn window_items(
+    db: Arc<TransactionDB>,
     txn: &Transaction<TransactionDB>,
-    cf: &impl AsColumnFamilyRef,
-    prefix: &[u8],
-) -> Result<()> {
-    let mut read_options = ReadOptions::default();
-    read_options.set_readahead_size(4_194_304);
-    let iterator_mode = IteratorMode::From(prefix, Direction::Forward);
-    let iter = txn.iterator_cf_opt(cf, read_options, iterator_mode);
-    for key in iter {
-        let (key, _) = key?;
-        if !key.starts_with(prefix) {
-            break;
+    namespace: &str,
+    compute_graph: &str,
+    window: &str,
+) -> Result<Vec<WindowItem>> {
+    let prefix = WindowItem::key_prefix(namespace, compute_graph, window);
+    let cf = IndexifyObjectsColumns::WindowItems.cf_db(&db);
+    let mut items = vec![];
+    for kv in make_prefix_iterator(txn, &cf, prefix.as_bytes(), &None) {
+        let (_, value) = kv?;
+        items.push(JsonEncoder::decode::<WindowItem>(&value)?);
+    }
+    Ok(items)
+}
+
+/// Finishes the tasks of the items of a window. The newest task gets the
+/// inputs of all the items as its outputs, the other tasks finish without
+/// outputs.
+fn close_window_items(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    items: &[WindowItem],
+) -> Result<Vec<FinalizeTaskRequest>> {
+    let mut tasks = vec![];
+    for item in items {
+        txn.delete_cf(&IndexifyObjectsColumns::WindowItems.cf_db(&db), item.key())?;
+        // The invocation of the task may have been deleted while it waited
+        let Some(task) = txn.get_for_update_cf(
+            &IndexifyObjectsColumns::Tasks.cf_db(&db),
+            &item.task_key,
+            true,
+        )?
+        else {
+            continue;
+        };
+        let task: Task = JsonEncoder::decode(&task)?;
+        if !task.terminal_state() {
+            tasks.push(task);
         }
-        txn.delete_cf(cf, &key)?;
     }
-    Ok(())
+    let Some(closing_task) = tasks.last().cloned() else {
+        return Ok(vec![]);
+    };
+    let mut batch = vec![];
+    for task in &tasks {
+        let input = txn
+            .get_cf(
+                &IndexifyObjectsColumns::FnOutputs.cf_db(&db),
+                NodeOutput::key_from_input_key(&task.input_node_output_key)?,
+            )?
+            .map(|output| JsonEncoder::decode::<NodeOutput>(&output))
+            .transpose()?;
+        let Some(OutputPayload::Fn(payload)) = input.map(|input| input.payload) else {
+            error!(
+                "input {} of window task {} not found",
+                task.input_node_output_key, task.id
+            );
+            continue;
+        };
+        batch.push(
+            NodeOutputBuilder::default()
+                .namespace(closing_task.namespace.clone())
+                .compute_graph_name(closing_task.compute_graph_name.clone())
+                .compute_fn_name(closing_task.compute_fn_name.clone())
+                .invocation_id(closing_task.invocation_id.clone())
+                .payload(OutputPayload::Fn(payload))
+                .build()?,
+        );
+    }
+    let mut finalized = vec![];
+    for task in tasks {
+        let node_outputs = if task.id == closing_task.id {
+            std::mem::take(&mut batch)
+        } else {
+            vec![]
+        };
+        let finalize_req = FinalizeTaskRequest {
+            namespace: task.namespace.clone(),
+            compute_graph: task.compute_graph_name.clone(),
+            compute_fn: task.compute_fn_name.clone(),
+            invocation_id: task.invocation_id.clone(),
+            task_id: task.id.clone(),
+            node_outputs,
+            task_outcome: TaskOutcome::Success,
+            // Window tasks are not allocated to an executor
+            executor_id: ExecutorId::default(),
+            diagnostics: None,
+            inline_payloads: vec![],
+            resource_usage: None,
+        };
+        if mark_task_completed(db.clone(), txn, finalize_req.clone())?.is_some() {
+            finalized.push(finalize_req);
+        }
+    }
+    Ok(finalized)
 }
 
-pub fn delete_compute_graph(
+/// Closes the windows which got max_items inputs from the created tasks,
+/// max_items inputs at a time.
+pub(crate) fn close_full_windows(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    task_requests: &[CreateTasksRequest],
+) -> Result<Vec<FinalizeTaskRequest>> {
+    let windows: BTreeSet<(&str, &str, &str, GraphVersion)> = task_requests
+        .iter()
+        .flat_map(|req| req.tasks.iter())
+        .map(|task| {
+            (
+                task.namespace.as_str(),
+                task.compute_graph_name.as_str(),
+                task.compute_fn_name.as_str(),
+                task.graph_version,
+            )
+        })
+        .collect();
+    let mut finalized = vec![];
+    for (namespace, compute_graph, name, version) in windows {
+        let graph = get_compute_graph_version(db.clone(), txn, namespace, compute_graph, &version)?;
+        let Some(window) = graph
+            .as_ref()
+            .and_then(|graph| graph.nodes.get(name))
+            .and_then(|node| node.window())
+        else {
+            continue;
+        };
+        let items = window_items(db.clone(), txn, namespace, compute_graph, name)?;
+        if window.event_time_secs.is_some() {
+            finalized.extend(advance_watermark(
+                db.clone(),
+                txn,
+                namespace,
+                compute_graph,
+                window,
+                items,
+            )?);
+            continue;
+        }
+        let Some(max_items) = window.max_items else {
+            continue;
+        };
+        for chunk in items.chunks(max_items as usize) {
+            if chunk.len() == max_items as usize {
+                finalized.extend(close_window_items(db.clone(), txn, chunk)?);
+            }
+        }
+    }
+    Ok(finalized)
+}
+
+/// Advances the watermark of an event-time window to the event times of its
+/// inputs and closes the windows it passed. Inputs of windows the previous
+/// watermark already passed are late.
+fn advance_watermark(
     db: Arc<TransactionDB>,
     txn: &Transaction<TransactionDB>,
     namespace: &str,
-    name: &str,
+    compute_graph: &str,
+    window: &Window,
+    items: Vec<WindowItem>,
+) -> Result<Vec<FinalizeTaskRequest>> {
+    let cf = IndexifyObjectsColumns::WindowWatermarks.cf_db(&db);
+    let key = WindowWatermark::key_from(namespace, compute_graph, &window.name);
+    let previous = txn
+        .get_for_update_cf(&cf, &key, true)?
+        .map(|value| JsonEncoder::decode::<WindowWatermark>(&value))
+        .transpose()?
+        .map(|watermark| watermark.max_event_time);
+    // Inputs of the windows the watermark passed were closed with them, the
+    // remaining ones are new
+    let previous_watermark = previous.map(|max_event_time| window.watermark(max_event_time));
+    let mut finalized = vec![];
+    let mut on_time = vec![];
+    for item in items {
+        let end = window.event_window_end(item.event_time());
+        match previous_watermark {
+            Some(watermark) if end.is_some_and(|end| end <= watermark) => {
+                finalized.extend(close_late_item(db.clone(), txn, window, &item, watermark)?);
+            }
+            _ => on_time.push(item),
+        }
+    }
+    let Some(max_event_time) = on_time
+        .iter()
+        .map(|item| item.event_time())
+        .chain(previous)
+        .max()
+    else {
+        return Ok(finalized);
+    };
+    let watermark = WindowWatermark {
+        namespace: namespace.to_string(),
+        compute_graph: compute_graph.to_string(),
+        window: window.name.clone(),
+        max_event_time,
+    };
+    txn.put_cf(&cf, &key, JsonEncoder::encode(&watermark)?)?;
+    let watermark = window.watermark(max_event_time);
+    finalized.extend(close_event_windows(db, txn, window, &on_time, |end| end <= watermark)?);
+    Ok(finalized)
+}
+
+/// Closes the event-time windows of the items for which `passed` returns true
+/// given their end, and the other ones max_items inputs at a time.
+fn close_event_windows(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    window: &Window,
+    items: &[WindowItem],
+    passed: impl Fn(u64) -> bool,
+) -> Result<Vec<FinalizeTaskRequest>> {
+    let mut by_end: BTreeMap<u64, Vec<WindowItem>> = BTreeMap::new();
+    for item in items {
+        let end = window
+            .event_window_end(item.event_time())
+            .unwrap_or_default();
+        by_end.entry(end).or_default().push(item.clone());
+    }
+    let mut finalized = vec![];
+    for (end, items) in by_end {
+        let max_items = window.max_items.map(|max_items| max_items as usize);
+        for chunk in items.chunks(max_items.unwrap_or(items.len())) {
+            if passed(end) || max_items == Some(chunk.len()) {
+                finalized.extend(close_window_items(db.clone(), txn, chunk)?);
+            }
+        }
+    }
+    Ok(finalized)
+}
+
+/// Finishes the task of a late input of an event-time window, routing the
+/// input to the late output of the window. The task fails when the window has
+/// no late output.
+fn close_late_item(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    window: &Window,
+    item: &WindowItem,
+    watermark: u64,
+) -> Result<Option<FinalizeTaskRequest>> {
+    txn.delete_cf(&IndexifyObjectsColumns::WindowItems.cf_db(&db), item.key())?;
+    let cf = IndexifyObjectsColumns::Tasks.cf_db(&db);
+    let Some(task) = txn.get_for_update_cf(&cf, &item.task_key, true)? else {
+        return Ok(None);
+    };
+    let mut task: Task = JsonEncoder::decode(&task)?;
+    if task.terminal_state() {
+        return Ok(None);
+    }
+    let (node_outputs, task_outcome) = match &window.late_output {
+        Some(late_output) => {
+            let output = NodeOutputBuilder::default()
+                .namespace(task.namespace.clone())
+                .compute_graph_name(task.compute_graph_name.clone())
+                .compute_fn_name(task.compute_fn_name.clone())
+                .invocation_id(task.invocation_id.clone())
+                .payload(OutputPayload::Router(RouterOutput {
+                    edges: vec![late_output.clone()],
+                }))
+                .build()?;
+            (vec![output], TaskOutcome::Success)
+        }
+        None => {
+            task.failure = Some(TaskFailure::LateInput {
+                event_time: item.event_time(),
+                watermark,
+            });
+            txn.put_cf(&cf, &item.task_key, BinaryEncoder::encode(&task)?)?;
+            (vec![], TaskOutcome::Failure)
+        }
+    };
+    let finalize_req = FinalizeTaskRequest {
+        namespace: task.namespace.clone(),
+        compute_graph: task.compute_graph_name.clone(),
+        compute_fn: task.compute_fn_name.clone(),
+        invocation_id: task.invocation_id.clone(),
+        task_id: task.id.clone(),
+        node_outputs,
+        task_outcome,
+        // Window tasks are not allocated to an executor
+        executor_id: ExecutorId::default(),
+        diagnostics: None,
+        inline_payloads: vec![],
+        resource_usage: None,
+    };
+    if mark_task_completed(db.clone(), txn, finalize_req.clone())?.is_none() {
+        return Ok(None);
+    }
+    Ok(Some(finalize_req))
+}
+
+/// Closes a window regardless of how many inputs wait in it, e.g. once its
+/// oldest input waited max_age_secs. Each event-time window of an event-time
+/// window node closes separately.
+pub(crate) fn close_window(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &CloseWindowRequest,
+) -> Result<Vec<FinalizeTaskRequest>> {
+    let graph = txn
+        .get_cf(
+            &IndexifyObjectsColumns::ComputeGraphs.cf_db(&db),
+            format!("{}|{}", req.namespace, req.compute_graph),
+        )?
+        .map(|graph| JsonEncoder::decode::<ComputeGraph>(&graph))
+        .transpose()?
+        .ok_or_else(|| {
+            StateStoreError::not_found(format!("compute graph {}", req.compute_graph))
+        })?;
+    let window = graph
+        .nodes
+        .get(&req.window)
+        .and_then(|node| node.window())
+        .ok_or_else(|| StateStoreError::not_found(format!("window {}", req.window)))?;
+    let items = window_items(
+        db.clone(),
+        txn,
+        &req.namespace,
+        &req.compute_graph,
+        &req.window,
+    )?;
+    if window.event_time_secs.is_some() {
+        return close_event_windows(db, txn, window, &items, |_| true);
+    }
+    let chunk_size = window
+        .max_items
+        .map_or(items.len(), |max_items| max_items as usize)
+        .max(1);
+    let mut finalized = vec![];
+    for chunk in items.chunks(chunk_size) {
+        finalized.extend(close_window_items(db.clone(), txn, chunk)?);
+    }
+    Ok(finalized)
+}
+
+pub(crate) fn delete_input_data_object(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &DeleteInvocationRequest,
 ) -> Result<()> {
+    check_invocation_not_held(
+        db.clone(),
+        txn,
+        &req.namespace,
+        &req.compute_graph,
+        &req.invocation_id,
+    )?;
+    let key = InvocationPayload::key_from(&req.namespace, &req.compute_graph, &req.invocation_id);
+    let mut deleted_bytes = 0;
+    if let Some(value) = txn.get_for_update_cf(
+        &IndexifyObjectsColumns::GraphInvocations.cf_db(&db),
+        &key,
+        true,
+    )? {
+        let payload: InvocationPayload = JsonEncoder::decode(&value)?;
+        deleted_bytes += payload.payload.size;
+        for (name, value) in &payload.attributes {
+            txn.delete_cf(
+                &IndexifyObjectsColumns::InvocationAttributes.cf_db(&db),
+                attributes::index_key(
+                    &payload.namespace,
+                    &payload.compute_graph_name,
+                    name,
+                    value,
+                    &payload.id,
+                )?,
+            )?;
+        }
+        txn.delete_cf(&IndexifyObjectsColumns::GraphInvocations.cf_db(&db), &key)?;
+    }
     txn.delete_cf(
-        &IndexifyObjectsColumns::ComputeGraphs.cf_db(&db),
-        format!("{}|{}", namespace, name),
+        &IndexifyObjectsColumns::InvocationResults.cf_db(&db),
+        GraphInvocationCtx::key_from(&req.namespace, &req.compute_graph, &req.invocation_id),
     )?;
-    let prefix = format!("{}|{}|", namespace, name);
     delete_cf_prefix(
         txn,
-        &IndexifyObjectsColumns::GraphInvocations.cf_db(&db),
-        prefix.as_bytes(),
+        &IndexifyObjectsColumns::InvocationState.cf_db(&db),
+        InvocationStateEntry::key_prefix(&req.namespace, &req.compute_graph, &req.invocation_id)
+            .as_bytes(),
     )?;
-
     delete_cf_prefix(
         txn,
-        &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
-        prefix.as_bytes(),
+        &IndexifyObjectsColumns::InvocationEvents.cf_db(&db),
+        InvocationEvent::key_prefix(&req.namespace, &req.compute_graph, &req.invocation_id)
+            .as_bytes(),
     )?;
-
-    for iter in make_prefix_iterator(
-        txn,
-        &IndexifyObjectsColumns::FnOutputs.cf_db(&db),
-        prefix.as_bytes(),
-        &None,
-    ) {
-        let (key, value) = iter?;
-        let value = JsonEncoder::decode::<NodeOutput>(&value)?;
-        match &value.payload {
-            OutputPayload::Router(_) => {}
-            OutputPayload::Fn(payload) => {
-                println!("delete_compute_graph: {:?}", value.clone());
-                txn.put_cf(
-                    &IndexifyObjectsColumns::GcUrls.cf_db(&db),
-                    payload.path.as_bytes(),
-                    &[],
-                )?;
-            }
+    // Deleting an unfinished invocation cancels it, its tasks are never
+    // scheduled again
+    let ctx_cf = IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db);
+    let ctx_key =
+        GraphInvocationCtx::key_from(&req.namespace, &req.compute_graph, &req.invocation_id);
+    if let Some(value) = txn.get_for_update_cf(&ctx_cf, &ctx_key, true)? {
+        let mut ctx: GraphInvocationCtx = JsonEncoder::decode(&value)?;
+        if !ctx.completed {
+            release_pending_invocation(db.clone(), txn, &ctx)?;
+            ctx.completed = true;
+            txn.put_cf(&ctx_cf, &ctx_key, JsonEncoder::encode(&ctx)?)?;
         }
-        txn.delete_cf(&IndexifyObjectsColumns::FnOutputs.cf_db(&db), &key)?;
     }
+    update_storage_usage(db, txn, &req.namespace, &req.compute_graph, |usage| {
+        usage.ingestion_bytes = usage.ingestion_bytes.saturating_sub(deleted_bytes)
+    })?;
 
+    // FIXME - Delete the data objects which are outputs of the compute functions of
+    // the invocation
     Ok(())
 }
 
-pub fn remove_gc_urls(
+/// Rejects deleting the data of an invocation held by its own hold or by
+/// its graph's.
+fn check_invocation_not_held(
     db: Arc<TransactionDB>,
     txn: &Transaction<TransactionDB>,
-    urls: Vec<String>,
+    namespace: &str,
+    compute_graph: &str,
+    invocation_id: &str,
 ) -> Result<()> {
-    for url in urls {
-        txn.delete_cf(&IndexifyObjectsColumns::GcUrls.cf_db(&db), &url)?;
+    let holds_cf = IndexifyObjectsColumns::LegalHolds.cf_db(&db);
+    for hold_invocation_id in [None, Some(invocation_id)] {
+        let hold_key = LegalHold::key_from(namespace, compute_graph, hold_invocation_id);
+        if let Some(value) = txn.get_for_update_cf(&holds_cf, &hold_key, true)? {
+            let hold: LegalHold = JsonEncoder::decode(&value)?;
+            return Err(StateStoreError::Conflict(format!(
+                "invocation {} is under a legal hold set by {}: {}",
+                invocation_id, hold.set_by, hold.reason
+            ))
+            .into());
+        }
     }
     Ok(())
 }
 
-pub fn make_prefix_iterator<'a>(
-    txn: &'a Transaction<TransactionDB>,
-    cf_handle: &impl AsColumnFamilyRef,
-    prefix: &'a [u8],
-    restart_key: &'a Option<Vec<u8>>,
-) -> impl Iterator<Item = Result<(Box<[u8]>, Box<[u8]>)>> + 'a {
-    let mut read_options = ReadOptions::default();
-    read_options.set_readahead_size(4_194_304);
-    let iter = txn.iterator_cf_opt(
-        cf_handle,
-        read_options,
-        match restart_key {
-            Some(restart_key) => IteratorMode::From(&restart_key, Direction::Forward),
-            None => IteratorMode::From(prefix, Direction::Forward),
-        },
-    );
-    iter.map(|item| item.map_err(|e| anyhow!(e.to_string())))
-        .take_while(move |item| match item {
-            Ok((key, _)) => key.starts_with(prefix),
-            Err(_) => true,
-        })
+/// Deletes the data of an ingested invocation and of the invocations of the
+/// sub graphs it ran, see `data_model::data_deletion`. The blobs are handed
+/// to the garbage collector and listed in the report of the deletion, which
+/// replaces the report of an earlier deletion of the invocation.
+pub(crate) fn delete_ingested_data(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &DeleteIngestedDataRequest,
+) -> Result<DataDeletion> {
+    let mut deletion = DataDeletion {
+        namespace: req.namespace.clone(),
+        compute_graph: req.compute_graph.clone(),
+        invocation_id: req.invocation_id.clone(),
+        mode: req.mode,
+        requested_by: req.identity.clone(),
+        requested_at: get_epoch_time_in_ms(),
+        deadline: req.deadline,
+        invocations: vec![],
+        outputs: 0,
+        bytes: 0,
+        blobs: vec![],
+    };
+    // The ingested invocation is deleted first, then the invocations of its
+    // sub graphs
+    let mut invocations = vec![(req.compute_graph.clone(), req.invocation_id.clone())];
+    while let Some((compute_graph, invocation_id)) = invocations.pop() {
+        let ctx_key = GraphInvocationCtx::key_from(&req.namespace, &compute_graph, &invocation_id);
+        let Some(ctx) = txn
+            .get_for_update_cf(
+                &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
+                &ctx_key,
+                true,
+            )?
+            .map(|value| JsonEncoder::decode::<GraphInvocationCtx>(&value))
+            .transpose()?
+        else {
+            return Err(StateStoreError::not_found(format!("invocation {}", invocation_id)).into());
+        };
+        // Outputs of running tasks would be written after the deletion
+        if !ctx.completed {
+            return Err(StateStoreError::Conflict(format!(
+                "invocation {} is still running, its data can be deleted once it finished",
+                invocation_id
+            ))
+            .into());
+        }
+        check_invocation_not_held(db.clone(), txn, &req.namespace, &compute_graph, &invocation_id)?;
+        invocations.extend(sub_graph_invocations(db.clone(), txn, &ctx)?);
+        delete_invocation_data(db.clone(), txn, &ctx, &mut deletion)?;
+        deletion.invocations.push(DeletedInvocation {
+            compute_graph,
+            invocation_id,
+        });
+    }
+    txn.put_cf(
+        &IndexifyObjectsColumns::DataDeletions.cf_db(&db),
+        deletion.key(),
+        JsonEncoder::encode(&deletion)?,
+    )?;
+    Ok(deletion)
 }
 
-pub(crate) fn processed_reduction_tasks(
+// Child invocations the sub graph tasks of an invocation ran, by graph and
+// id. A child invocation is identified by the id of the task which ran it.
+fn sub_graph_invocations(
     db: Arc<TransactionDB>,
     txn: &Transaction<TransactionDB>,
-    task: &ReductionTasks,
-) -> Result<()> {
-    let cf = &IndexifyObjectsColumns::ReductionTasks.cf_db(&db);
-    for task in &task.new_reduction_tasks {
-        let serialized_task = JsonEncoder::encode(&task)?;
-        txn.put_cf(cf, task.key(), &serialized_task)?;
+    ctx: &GraphInvocationCtx,
+) -> Result<Vec<(String, String)>> {
+    let graph_key = format!("{}|{}", ctx.namespace, ctx.compute_graph_name);
+    // The invocations of deleted graphs are kept until the graphs are purged
+    let graph = match txn.get_cf(&IndexifyObjectsColumns::ComputeGraphs.cf_db(&db), &graph_key)? {
+        Some(graph) => Some(graph),
+        None => txn.get_cf(
+            &IndexifyObjectsColumns::DeletedComputeGraphs.cf_db(&db),
+            &graph_key,
+        )?,
+    };
+    let Some(graph) = graph else {
+        return Ok(vec![]);
+    };
+    let graph: ComputeGraph = JsonEncoder::decode(&graph)?;
+    let sub_graphs = graph.sub_graphs();
+    if sub_graphs.is_empty() {
+        return Ok(vec![]);
     }
-    for key in &task.processed_reduction_tasks {
-        txn.delete_cf(cf, key)?;
+    let mut children = vec![];
+    let prefix = KeyPrefix::encoded(&[&ctx.namespace, &ctx.compute_graph_name, &ctx.invocation_id]);
+    for kv in make_prefix_iterator(
+        txn,
+        &IndexifyObjectsColumns::Tasks.cf_db(&db),
+        prefix.as_bytes(),
+        &None,
+    ) {
+        let (_, value) = kv?;
+        let task: Task = JsonEncoder::decode(&value)?;
+        for sub_graph in &sub_graphs {
+            let child_key = GraphInvocationCtx::key_from(&ctx.namespace, sub_graph, task.id.get());
+            let Some(child) =
+                txn.get_cf(&IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db), &child_key)?
+            else {
+                continue;
+            };
+            let child: GraphInvocationCtx = JsonEncoder::decode(&child)?;
+            let is_child = child.parent.as_ref().is_some_and(|parent| {
+                parent.compute_graph == ctx.compute_graph_name &&
+                    parent.invocation_id == ctx.invocation_id
+            });
+            if is_child {
+                children.push((sub_graph.to_string(), child.invocation_id));
+            }
+        }
     }
-    Ok(())
-}
-
-#[derive(Debug, Eq, PartialEq)]
-pub(crate) enum InvocationCompletion {
-    User,
-    System,
+    Ok(children)
 }
 
-// returns true if system task has finished
-pub(crate) fn create_tasks(
+// Hands the blobs of a payload to the garbage collector, returns whether
+// they weren't already, e.g. the input of a sub graph invocation is an
+// output of its parent.
+fn collect_payload_blobs(
     db: Arc<TransactionDB>,
     txn: &Transaction<TransactionDB>,
-    req: &CreateTasksRequest,
-) -> Result<Option<InvocationCompletion>> {
-    let ctx_key = format!(
-        "{}|{}|{}",
-        req.namespace, req.compute_graph, req.invocation_id
-    );
-    let graph_ctx = txn.get_for_update_cf(
-        &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
-        &ctx_key,
-        true,
-    )?;
-    if graph_ctx.is_none() {
-        error!("Graph context not found for graph: {}", req.compute_graph);
+    payload: &DataPayload,
+    deletion: &mut DataDeletion,
+) -> Result<bool> {
+    // Referenced payloads are owned by the client, expired ones are deleted
+    // already
+    if matches!(payload.tier, StorageTier::Expired | StorageTier::Referenced) ||
+        deletion.blobs.iter().any(|blob| *blob == payload.path)
+    {
+        return Ok(false);
     }
-    let mut graph_ctx: GraphInvocationCtx = JsonEncoder::decode(&graph_ctx.unwrap())?;
-    for task in &req.tasks {
-        let serialized_task = JsonEncoder::encode(&task)?;
-        txn.put_cf(
-            &IndexifyObjectsColumns::Tasks.cf_db(&db),
-            task.key(),
-            &serialized_task,
-        )?;
+    for path in payload.paths() {
         txn.put_cf(
-            &IndexifyObjectsColumns::UnallocatedTasks.cf_db(&db),
-            task.key(),
+            &IndexifyObjectsColumns::GcUrls.cf_db(&db),
+            path.as_bytes(),
             &[],
         )?;
-
-        let analytics = graph_ctx
-            .fn_task_analytics
-            .entry(task.compute_fn_name.clone())
-            .or_insert_with(|| TaskAnalytics::default());
-        analytics.pending();
+        deletion.blobs.push(path.to_string());
     }
-    graph_ctx.outstanding_tasks += req.tasks.len() as u64;
-    // Subtract reference for completed state change event
-    graph_ctx.outstanding_tasks -= 1;
-    let serialized_analytics = JsonEncoder::encode(&graph_ctx)?;
-    txn.put_cf(
-        &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
-        ctx_key,
-        serialized_analytics,
-    )?;
-    if graph_ctx.outstanding_tasks == 0 {
-        Ok(Some(mark_invocation_finished(
-            db,
-            txn,
-            &req.namespace,
-            &req.compute_graph,
-            &req.invocation_id,
-        )?))
-    } else {
-        Ok(None)
+    deletion.bytes += payload.size;
+    Ok(true)
+}
+
+// Tombstone of a deleted payload, it keeps the size and hash of the payload
+fn tombstone(payload: &mut DataPayload) {
+    if payload.tier == StorageTier::Referenced {
+        return;
     }
+    payload.tier = StorageTier::Expired;
+    payload.mime_type = None;
+    payload.content_encoding = None;
+    payload.metadata = Default::default();
+    payload.replicas = Vec::new();
 }
 
-pub fn allocate_tasks(
+fn delete_invocation_data(
     db: Arc<TransactionDB>,
     txn: &Transaction<TransactionDB>,
-    task: &Task,
-    executor_id: &ExecutorId,
+    ctx: &GraphInvocationCtx,
+    deletion: &mut DataDeletion,
 ) -> Result<()> {
-    txn.put_cf(
-        &IndexifyObjectsColumns::TaskAllocations.cf_db(&db),
-        task.make_allocation_key(executor_id),
-        &[],
-    )?;
-    txn.delete_cf(
-        &IndexifyObjectsColumns::UnallocatedTasks.cf_db(&db),
-        task.key(),
-    )?;
-    Ok(())
+    let hard = deletion.mode == DataDeletionMode::Hard;
+    let (namespace, compute_graph, invocation_id) =
+        (&ctx.namespace, &ctx.compute_graph_name, &ctx.invocation_id);
+
+    // The parts of the payload are collected with the outputs they are stored
+    // as
+    let invocation_key = InvocationPayload::key_from(namespace, compute_graph, invocation_id);
+    let invocations_cf = IndexifyObjectsColumns::GraphInvocations.cf_db(&db);
+    let mut ingestion_bytes = 0;
+    if let Some(value) = txn.get_for_update_cf(&invocations_cf, &invocation_key, true)? {
+        let mut invocation: InvocationPayload = JsonEncoder::decode(&value)?;
+        if collect_payload_blobs(db.clone(), txn, &invocation.payload, deletion)? {
+            ingestion_bytes = invocation.payload.size;
+        }
+        if hard {
+            for (name, value) in &invocation.attributes {
+                txn.delete_cf(
+                    &IndexifyObjectsColumns::InvocationAttributes.cf_db(&db),
+                    attributes::index_key(namespace, compute_graph, name, value, invocation_id)?,
+                )?;
+            }
+            txn.delete_cf(&invocations_cf, &invocation_key)?;
+        } else {
+            tombstone(&mut invocation.payload);
+            invocation.parts.iter_mut().for_each(tombstone);
+            txn.put_cf(&invocations_cf, &invocation_key, JsonEncoder::encode(&invocation)?)?;
+        }
+    }
+
+    let outputs_cf = IndexifyObjectsColumns::FnOutputs.cf_db(&db);
+    let prefix = KeyPrefix::encoded(&[namespace, compute_graph, invocation_id]);
+    let mut outputs = vec![];
+    for kv in make_prefix_iterator(txn, &outputs_cf, prefix.as_bytes(), &None) {
+        let (key, value) = kv?;
+        outputs.push((key, JsonEncoder::decode::<NodeOutput>(&value)?));
+    }
+    let mut output_bytes = 0;
+    for (key, mut output) in outputs {
+        let OutputPayload::Fn(payload) = &mut output.payload else {
+            if hard {
+                txn.delete_cf(&outputs_cf, &key)?;
+            }
+            continue;
+        };
+        if collect_payload_blobs(db.clone(), txn, payload, deletion)? {
+            deletion.outputs += 1;
+            output_bytes += payload.size;
+        }
+        if hard {
+            txn.delete_cf(&outputs_cf, &key)?;
+        } else {
+            tombstone(payload);
+            txn.put_cf(&outputs_cf, &key, JsonEncoder::encode(&output)?)?;
+        }
+    }
+
+    // Diagnostics of the tasks, such as their stdout, can hold the data too
+    let tasks_cf = IndexifyObjectsColumns::Tasks.cf_db(&db);
+    let mut tasks = vec![];
+    for kv in make_prefix_iterator(txn, &tasks_cf, prefix.as_bytes(), &None) {
+        let (key, value) = kv?;
+        tasks.push((key, JsonEncoder::decode::<Task>(&value)?));
+    }
+    for (key, mut task) in tasks {
+        if let Some(diagnostics) = task.diagnostics.take() {
+            for payload in [
+                diagnostics.exception,
+                diagnostics.stdout,
+                diagnostics.stderr,
+                diagnostics.structured_logs,
+            ]
+            .iter()
+            .flatten()
+            {
+                collect_payload_blobs(db.clone(), txn, payload, deletion)?;
+            }
+        }
+        if hard {
+            delete_cf_prefix(
+                txn,
+                &IndexifyObjectsColumns::TaskOutputs.cf_db(&db),
+                Task::key_outputs_prefix(&task.namespace, task.id.get()).as_bytes(),
+            )?;
+            txn.delete_cf(&tasks_cf, &key)?;
+        } else {
+            txn.put_cf(&tasks_cf, &key, JsonEncoder::encode(&task)?)?;
+        }
+    }
+
+    let results_cf = IndexifyObjectsColumns::InvocationResults.cf_db(&db);
+    let ctx_key = GraphInvocationCtx::key_from(namespace, compute_graph, invocation_id);
+    if hard {
+        txn.delete_cf(&results_cf, &ctx_key)?;
+        txn.delete_cf(
+            &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
+            &ctx_key,
+        )?;
+        for column in [
+            IndexifyObjectsColumns::InvocationState,
+            IndexifyObjectsColumns::InvocationEvents,
+            IndexifyObjectsColumns::FanOutCursors,
+        ] {
+            delete_cf_prefix(txn, &column.cf_db(&db), prefix.as_bytes())?;
+        }
+    } else if let Some(value) = txn.get_for_update_cf(&results_cf, &ctx_key, true)? {
+        // Finished invocations keep copies of their terminal outputs
+        let mut result: InvocationResult = JsonEncoder::decode(&value)?;
+        for output in &mut result.outputs {
+            if let OutputPayload::Fn(payload) = &mut output.payload {
+                tombstone(payload);
+            }
+        }
+        txn.put_cf(&results_cf, &ctx_key, JsonEncoder::encode(&result)?)?;
+    }
+
+    // The payload of a sub graph invocation is an output of its parent
+    if ctx.parent.is_some() {
+        ingestion_bytes = 0;
+    }
+    update_storage_usage(db, txn, namespace, compute_graph, |usage| {
+        usage.ingestion_bytes = usage.ingestion_bytes.saturating_sub(ingestion_bytes);
+        usage.output_bytes = usage.output_bytes.saturating_sub(output_bytes);
+    })
 }
 
-/// Returns true if the task was marked as completed.
-/// If task was already completed, returns false.
-pub fn mark_task_completed(
+/// Writes a value of the scratch space of an invocation. Compare-and-swap
+/// writes fail with a conflict unless the value is at the expected version.
+pub(crate) fn set_invocation_state(
     db: Arc<TransactionDB>,
     txn: &Transaction<TransactionDB>,
-    req: FinalizeTaskRequest,
-) -> Result<bool> {
-    let task_key = format!(
-        "{}|{}|{}|{}|{}",
-        req.namespace, req.compute_graph, req.invocation_id, req.compute_fn, req.task_id
-    );
-    let task = txn
-        .get_for_update_cf(&IndexifyObjectsColumns::Tasks.cf_db(&db), &task_key, true)?
-        .ok_or(anyhow!("Task not found: {}", &req.task_id))?;
-    let mut task = JsonEncoder::decode::<Task>(&task)?;
-    if task.terminal_state() {
-        return Ok(false);
+    req: &SetInvocationStateRequest,
+) -> Result<()> {
+    let graph_ctx_key =
+        GraphInvocationCtx::key_from(&req.namespace, &req.compute_graph, &req.invocation_id);
+    if txn
+        .get_cf(
+            &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
+            &graph_ctx_key,
+        )?
+        .is_none()
+    {
+        return Err(StateStoreError::not_found(format!("invocation {}", req.invocation_id)).into());
     }
-    let graph_ctx_key = format!(
-        "{}|{}|{}",
-        req.namespace, req.compute_graph, req.invocation_id
+    let key = InvocationStateEntry::key_from(
+        &req.namespace,
+        &req.compute_graph,
+        &req.invocation_id,
+        &req.key,
     );
-    let graph_ctx = txn
+    let current_version = txn
         .get_for_update_cf(
-            &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
-            &graph_ctx_key,
+            &IndexifyObjectsColumns::InvocationState.cf_db(&db),
+            &key,
             true,
         )?
-        .ok_or(anyhow!(
-            "Graph context not found for task: {}",
-            &req.task_id
-        ))?;
-    let mut graph_ctx: GraphInvocationCtx = JsonEncoder::decode(&graph_ctx)?;
-    for mut output in req.node_outputs {
-        // Update with correct graph version
-        output.graph_version = graph_ctx.graph_version;
+        .map(|entry| JsonEncoder::decode::<InvocationStateEntry>(&entry))
+        .transpose()?
+        .map_or(0, |entry| entry.version);
+    if let Some(expected_version) = req.expected_version {
+        if expected_version != current_version {
+            return Err(StateStoreError::Conflict(format!(
+                "invocation state {} is at version {}, expected version {}",
+                req.key, current_version, expected_version
+            ))
+            .into());
+        }
+    }
+    let entry = InvocationStateEntry {
+        namespace: req.namespace.clone(),
+        compute_graph_name: req.compute_graph.clone(),
+        invocation_id: req.invocation_id.clone(),
+        key: req.key.clone(),
+        value: req.value.clone(),
+        version: current_version + 1,
+    };
+    txn.put_cf(
+        &IndexifyObjectsColumns::InvocationState.cf_db(&db),
+        key,
+        JsonEncoder::encode(&entry)?,
+    )?;
+    Ok(())
+}
 
-        let serialized_output = JsonEncoder::encode(&output)?;
-        // Create an output key
-        let output_key = output.key(&req.invocation_id);
-        txn.put_cf(
-            &IndexifyObjectsColumns::FnOutputs.cf_db(&db),
-            &output_key,
-            serialized_output,
+/// Creates or updates a compute graph. Updates must be based on the current
+/// revision of the graph unless forced, forcing also purges a deleted graph
+/// of the same name.
+/// Returns whether the compute graph was created rather than updated.
+pub(crate) fn create_compute_graph(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    range_deletes: &mut RangeDeletes,
+    req: &CreateComputeGraphRequest,
+) -> Result<bool> {
+    let mut compute_graph = req.compute_graph.clone();
+    let existing_compute_graph = txn.get_for_update_cf(
+        &IndexifyObjectsColumns::ComputeGraphs.cf_db(&db),
+        compute_graph.key(),
+        true,
+    )?;
+    let existing_compute_graph: Option<ComputeGraph> = existing_compute_graph
+        .map(|graph| JsonEncoder::decode(&graph))
+        .transpose()?;
+
+    let current_revision = existing_compute_graph
+        .as_ref()
+        .map_or(0, |graph| graph.revision);
+    let revision_matches = match req.expected_revision {
+        Some(revision) => revision == current_revision,
+        None => existing_compute_graph.is_none(),
+    };
+    if !req.force && !revision_matches {
+        return Err(StateStoreError::Conflict(format!(
+            "compute graph {} is at revision {}, expected revision {:?}",
+            compute_graph.name, current_revision, req.expected_revision
+        ))
+        .into());
+    }
+    compute_graph.revision = current_revision + 1;
+    let created = existing_compute_graph.is_none();
+
+    // The canary would share its version with the updated graph
+    let canary_key = compute_graph.key();
+    if txn
+        .get_for_update_cf(
+            &IndexifyObjectsColumns::GraphCanaries.cf_db(&db),
+            &canary_key,
+            true,
+        )?
+        .is_some()
+    {
+        if !req.force {
+            return Err(StateStoreError::Conflict(format!(
+                "compute graph {} has a canary, finalize or roll it back first",
+                compute_graph.name
+            ))
+            .into());
+        }
+        txn.delete_cf(
+            &IndexifyObjectsColumns::GraphCanaries.cf_db(&db),
+            &canary_key,
         )?;
+    }
 
-        // Create a key to store the pointer to the node output to the task
-        // NS_TASK_ID_<OutputID> -> Output Key
-        let task_output_key = task.key_output(&output.id);
-        let node_output_id = JsonEncoder::encode(&output_key)?;
-        txn.put_cf(
-            &IndexifyObjectsColumns::TaskOutputs.cf_db(&db),
-            task_output_key,
-            node_output_id,
+    let deleted_compute_graph = txn.get_for_update_cf(
+        &IndexifyObjectsColumns::DeletedComputeGraphs.cf_db(&db),
+        compute_graph.key(),
+        true,
+    )?;
+    if deleted_compute_graph.is_some() {
+        if !req.force {
+            return Err(StateStoreError::Conflict(format!(
+                "compute graph {} was deleted, restore it or force the update to purge it",
+                compute_graph.name
+            ))
+            .into());
+        }
+        purge_compute_graph(
+            db.clone(),
+            txn,
+            range_deletes,
+            &compute_graph.namespace,
+            &compute_graph.name,
         )?;
     }
-    let analytics = graph_ctx
-        .fn_task_analytics
-        .entry(req.compute_fn.to_string())
-        .or_insert_with(|| TaskAnalytics::default());
-    match req.task_outcome {
-        data_model::TaskOutcome::Success => analytics.success(),
-        data_model::TaskOutcome::Failure => analytics.fail(),
-        _ => {}
+
+    if let Some(existing_compute_graph) = existing_compute_graph {
+        // Updating the definition doesn't resume the graph
+        compute_graph.paused = existing_compute_graph.paused.clone();
+        if compute_graph.code.sha256_hash != existing_compute_graph.code.sha256_hash ||
+            compute_graph.edges != existing_compute_graph.edges ||
+            compute_graph.nodes != existing_compute_graph.nodes ||
+            compute_graph.start_fn != existing_compute_graph.start_fn
+        {
+            compute_graph.version = existing_compute_graph.version.next();
+        }
+    };
+
+    let serialized_compute_graph = JsonEncoder::encode(&compute_graph)?;
+    txn.put_cf(
+        &IndexifyObjectsColumns::ComputeGraphs.cf_db(&db),
+        compute_graph.key(),
+        &serialized_compute_graph,
+    )?;
+    txn.put_cf(
+        &IndexifyObjectsColumns::CodeArtifacts.cf_db(&db),
+        &compute_graph.code.sha256_hash,
+        JsonEncoder::encode(&compute_graph.code)?,
+    )?;
+    // Only the code of the latest version is accounted
+    update_storage_usage(
+        db,
+        txn,
+        &compute_graph.namespace,
+        &compute_graph.name,
+        |usage| usage.code_bytes = compute_graph.code.size,
+    )?;
+    Ok(created)
+}
+
+pub(crate) fn get_graph_canary(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    namespace: &str,
+    compute_graph: &str,
+) -> Result<Option<GraphCanary>> {
+    txn.get_cf(
+        &IndexifyObjectsColumns::GraphCanaries.cf_db(&db),
+        format!("{}|{}", namespace, compute_graph),
+    )?
+    .map(|canary| JsonEncoder::decode::<GraphCanary>(&canary))
+    .transpose()
+}
+
+/// The graph at the given version, the canary's if it is at that version and
+/// the current graph otherwise.
+pub(crate) fn get_compute_graph_version(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    namespace: &str,
+    compute_graph: &str,
+    version: &GraphVersion,
+) -> Result<Option<ComputeGraph>> {
+    if let Some(canary) = get_graph_canary(db.clone(), txn, namespace, compute_graph)? {
+        if canary.compute_graph.version == *version {
+            return Ok(Some(canary.compute_graph));
+        }
     }
-    let serialized_analytics = JsonEncoder::encode(&graph_ctx)?;
+    txn.get_cf(
+        &IndexifyObjectsColumns::ComputeGraphs.cf_db(&db),
+        format!("{}|{}", namespace, compute_graph),
+    )?
+    .map(|graph| JsonEncoder::decode::<ComputeGraph>(&graph))
+    .transpose()
+}
+
+/// Publishes a new version of a graph as a canary, the current version keeps
+/// running the invocations which aren't sampled.
+pub(crate) fn create_graph_canary(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &CreateGraphCanaryRequest,
+) -> Result<()> {
+    GraphCanary::validate_percent(req.percent)?;
+    let mut compute_graph = req.compute_graph.clone();
+    let key = compute_graph.key();
+    let current = txn
+        .get_for_update_cf(
+            &IndexifyObjectsColumns::ComputeGraphs.cf_db(&db),
+            &key,
+            true,
+        )?
+        .ok_or(StateStoreError::not_found(format!(
+            "compute graph {}",
+            compute_graph.name
+        )))?;
+    let current: ComputeGraph = JsonEncoder::decode(&current)?;
+    if let Some(revision) = req.expected_revision {
+        if revision != current.revision {
+            return Err(StateStoreError::Conflict(format!(
+                "compute graph {} is at revision {}, expected revision {}",
+                compute_graph.name, current.revision, revision
+            ))
+            .into());
+        }
+    }
+    let canaries_cf = IndexifyObjectsColumns::GraphCanaries.cf_db(&db);
+    if txn.get_for_update_cf(&canaries_cf, &key, true)?.is_some() {
+        return Err(StateStoreError::Conflict(format!(
+            "compute graph {} already has a canary, finalize or roll it back first",
+            compute_graph.name
+        ))
+        .into());
+    }
+    if compute_graph.code.sha256_hash == current.code.sha256_hash &&
+        compute_graph.edges == current.edges &&
+        compute_graph.nodes == current.nodes &&
+        compute_graph.start_fn == current.start_fn
+    {
+        return Err(DataModelError::InvalidGraph(
+            "the canary doesn't change the code or the nodes of the graph".to_string(),
+        )
+        .into());
+    }
+    compute_graph.version = current.version.next();
+    compute_graph.revision = current.revision;
+    let canary = GraphCanary {
+        compute_graph,
+        percent: req.percent,
+        created_at: get_epoch_time_in_ms(),
+    };
+    txn.put_cf(&canaries_cf, &key, JsonEncoder::encode(&canary)?)?;
     txn.put_cf(
-        &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
-        graph_ctx_key,
-        serialized_analytics,
+        &IndexifyObjectsColumns::CodeArtifacts.cf_db(&db),
+        &canary.compute_graph.code.sha256_hash,
+        JsonEncoder::encode(&canary.compute_graph.code)?,
+    )?;
+    Ok(())
+}
+
+fn get_graph_canary_for_update(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    namespace: &str,
+    compute_graph: &str,
+) -> Result<GraphCanary> {
+    let canary = txn
+        .get_for_update_cf(
+            &IndexifyObjectsColumns::GraphCanaries.cf_db(&db),
+            format!("{}|{}", namespace, compute_graph),
+            true,
+        )?
+        .ok_or(StateStoreError::not_found(format!(
+            "canary of compute graph {}",
+            compute_graph
+        )))?;
+    Ok(JsonEncoder::decode(&canary)?)
+}
+
+pub(crate) fn update_graph_canary(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &UpdateGraphCanaryRequest,
+) -> Result<()> {
+    GraphCanary::validate_percent(req.percent)?;
+    let mut canary =
+        get_graph_canary_for_update(db.clone(), txn, &req.namespace, &req.compute_graph)?;
+    canary.percent = req.percent;
+    txn.put_cf(
+        &IndexifyObjectsColumns::GraphCanaries.cf_db(&db),
+        canary.key(),
+        JsonEncoder::encode(&canary)?,
+    )?;
+    Ok(())
+}
+
+/// Makes the canary the current version of the graph. Returns the promoted
+/// graph.
+pub(crate) fn finalize_graph_canary(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &GraphCanaryRequest,
+) -> Result<ComputeGraph> {
+    let canary = get_graph_canary_for_update(db.clone(), txn, &req.namespace, &req.compute_graph)?;
+    let key = canary.key();
+    let current = txn
+        .get_for_update_cf(
+            &IndexifyObjectsColumns::ComputeGraphs.cf_db(&db),
+            &key,
+            true,
+        )?
+        .ok_or(StateStoreError::not_found(format!(
+            "compute graph {}",
+            req.compute_graph
+        )))?;
+    let current: ComputeGraph = JsonEncoder::decode(&current)?;
+    let mut compute_graph = canary.compute_graph;
+    compute_graph.revision = current.revision + 1;
+    compute_graph.paused = current.paused;
+    txn.put_cf(
+        &IndexifyObjectsColumns::ComputeGraphs.cf_db(&db),
+        &key,
+        JsonEncoder::encode(&compute_graph)?,
+    )?;
+    txn.delete_cf(&IndexifyObjectsColumns::GraphCanaries.cf_db(&db), &key)?;
+    update_storage_usage(
+        db,
+        txn,
+        &compute_graph.namespace,
+        &compute_graph.name,
+        |usage| usage.code_bytes = compute_graph.code.size,
+    )?;
+    Ok(compute_graph)
+}
+
+/// Removes the canary, new invocations all run on the current version. The
+/// running invocations of the canary continue on the current version.
+pub(crate) fn rollback_graph_canary(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &GraphCanaryRequest,
+) -> Result<()> {
+    let canary = get_graph_canary_for_update(db.clone(), txn, &req.namespace, &req.compute_graph)?;
+    txn.delete_cf(
+        &IndexifyObjectsColumns::GraphCanaries.cf_db(&db),
+        canary.key(),
+    )?;
+    Ok(())
+}
+
+/// Pauses or resumes a compute graph. Returns whether the graph was paused
+/// before.
+pub(crate) fn set_compute_graph_paused(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    namespace: &str,
+    name: &str,
+    paused: Option<GraphPause>,
+) -> Result<bool> {
+    let key = format!("{}|{}", namespace, name);
+    let graph = txn
+        .get_for_update_cf(
+            &IndexifyObjectsColumns::ComputeGraphs.cf_db(&db),
+            &key,
+            true,
+        )?
+        .ok_or(StateStoreError::not_found(format!(
+            "compute graph {}",
+            name
+        )))?;
+    let mut graph: ComputeGraph = JsonEncoder::decode(&graph)?;
+    let was_paused = graph.is_paused();
+    graph.paused = paused;
+    txn.put_cf(
+        &IndexifyObjectsColumns::ComputeGraphs.cf_db(&db),
+        &key,
+        JsonEncoder::encode(&graph)?,
+    )?;
+    Ok(was_paused)
+}
+
+/// Moves the compute graph to the deleted compute graphs. Its invocations are
+/// kept so it can be restored until it is purged.
+pub fn delete_compute_graph(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    namespace: &str,
+    name: &str,
+) -> Result<()> {
+    let key = format!("{}|{}", namespace, name);
+    let graph = txn
+        .get_for_update_cf(
+            &IndexifyObjectsColumns::ComputeGraphs.cf_db(&db),
+            &key,
+            true,
+        )?
+        .ok_or(StateStoreError::not_found(format!(
+            "compute graph {}",
+            name
+        )))?;
+    let mut graph: ComputeGraph = JsonEncoder::decode(&graph)?;
+    graph.deleted_at = Some(get_epoch_time_in_ms());
+    txn.delete_cf(&IndexifyObjectsColumns::ComputeGraphs.cf_db(&db), &key)?;
+    txn.delete_cf(&IndexifyObjectsColumns::GraphCanaries.cf_db(&db), &key)?;
+    txn.put_cf(
+        &IndexifyObjectsColumns::DeletedComputeGraphs.cf_db(&db),
+        &key,
+        JsonEncoder::encode(&graph)?,
+    )?;
+    Ok(())
+}
+
+pub fn undelete_compute_graph(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    namespace: &str,
+    name: &str,
+) -> Result<()> {
+    let key = format!("{}|{}", namespace, name);
+    let graph = txn
+        .get_for_update_cf(
+            &IndexifyObjectsColumns::DeletedComputeGraphs.cf_db(&db),
+            &key,
+            true,
+        )?
+        .ok_or(StateStoreError::not_found(format!(
+            "deleted compute graph {}",
+            name
+        )))?;
+    let mut graph: ComputeGraph = JsonEncoder::decode(&graph)?;
+    graph.deleted_at = None;
+    graph.revision += 1;
+    txn.delete_cf(
+        &IndexifyObjectsColumns::DeletedComputeGraphs.cf_db(&db),
+        &key,
     )?;
+    txn.put_cf(
+        &IndexifyObjectsColumns::ComputeGraphs.cf_db(&db),
+        &key,
+        JsonEncoder::encode(&graph)?,
+    )?;
+    Ok(())
+}
+
+/// Permanently removes a deleted compute graph and its invocations, the urls
+/// of their outputs are handed to the garbage collector.
+pub fn purge_compute_graph(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    range_deletes: &mut RangeDeletes,
+    namespace: &str,
+    name: &str,
+) -> Result<()> {
+    let key = format!("{}|{}", namespace, name);
+    if txn
+        .get_for_update_cf(
+            &IndexifyObjectsColumns::DeletedComputeGraphs.cf_db(&db),
+            &key,
+            true,
+        )?
+        .is_none()
+    {
+        // Restored or already purged
+        return Ok(());
+    }
+    let prefix = KeyPrefix::compute_graph(namespace, name);
+    check_not_held(
+        db.clone(),
+        txn,
+        prefix.as_bytes(),
+        &format!("compute graph {}/{}", namespace, name),
+    )?;
+    txn.delete_cf(
+        &IndexifyObjectsColumns::DeletedComputeGraphs.cf_db(&db),
+        &key,
+    )?;
+    let encoded_prefix = KeyPrefix::encoded(&[namespace, name]);
+    for iter in make_prefix_iterator(
+        txn,
+        &IndexifyObjectsColumns::GraphInvocations.cf_db(&db),
+        encoded_prefix.as_bytes(),
+        &None,
+    ) {
+        let (_, value) = iter?;
+        let invocation = JsonEncoder::decode::<InvocationPayload>(&value)?;
+        if invocation.payload.tier == StorageTier::Referenced {
+            continue;
+        }
+        for path in invocation.payload.paths() {
+            txn.put_cf(
+                &IndexifyObjectsColumns::GcUrls.cf_db(&db),
+                path.as_bytes(),
+                &[],
+            )?;
+        }
+    }
+    for iter in make_prefix_iterator(
+        txn,
+        &IndexifyObjectsColumns::FnOutputs.cf_db(&db),
+        encoded_prefix.as_bytes(),
+        &None,
+    ) {
+        let (_, value) = iter?;
+        let value = JsonEncoder::decode::<NodeOutput>(&value)?;
+        match &value.payload {
+            OutputPayload::Router(_) => {}
+            OutputPayload::Fn(payload) => {
+                for path in payload.paths() {
+                    txn.put_cf(
+                        &IndexifyObjectsColumns::GcUrls.cf_db(&db),
+                        path.as_bytes(),
+                        &[],
+                    )?;
+                }
+            }
+        }
+    }
+    for column in [
+        IndexifyObjectsColumns::GraphInvocations,
+        IndexifyObjectsColumns::GraphInvocationCtx,
+        IndexifyObjectsColumns::InvocationResults,
+        IndexifyObjectsColumns::FnOutputs,
+        IndexifyObjectsColumns::InvocationState,
+        IndexifyObjectsColumns::InvocationEvents,
+        IndexifyObjectsColumns::FanOutCursors,
+        IndexifyObjectsColumns::ReductionTasks,
+    ] {
+        range_deletes.delete_prefix(column, encoded_prefix.clone());
+    }
+    for column in [
+        IndexifyObjectsColumns::InvocationAttributes,
+        IndexifyObjectsColumns::WindowItems,
+        IndexifyObjectsColumns::WindowWatermarks,
+        IndexifyObjectsColumns::FunctionLatencies,
+    ] {
+        range_deletes.delete_prefix(column, prefix.clone());
+    }
+    txn.delete_cf(
+        &IndexifyObjectsColumns::Stats.cf_db(&db),
+        pending_invocations_key(namespace, name),
+    )?;
+    txn.delete_cf(&IndexifyObjectsColumns::StorageUsage.cf_db(&db), &key)?;
+    txn.delete_cf(&IndexifyObjectsColumns::TaskUsage.cf_db(&db), &key)?;
+    txn.delete_cf(&IndexifyObjectsColumns::SlaStatus.cf_db(&db), &key)?;
+
+    Ok(())
+}
+
+pub fn remove_gc_urls(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    urls: Vec<String>,
+) -> Result<()> {
+    for url in urls {
+        if is_inline_path(&url) {
+            txn.delete_cf(&IndexifyObjectsColumns::InlinePayloads.cf_db(&db), &url)?;
+        }
+        txn.delete_cf(&IndexifyObjectsColumns::GcUrls.cf_db(&db), &url)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn processed_reduction_tasks(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    task: &ReductionTasks,
+) -> Result<()> {
+    let cf = &IndexifyObjectsColumns::ReductionTasks.cf_db(&db);
+    for task in &task.new_reduction_tasks {
+        let serialized_task = BinaryEncoder::encode(&task)?;
+        txn.put_cf(cf, task.key(), &serialized_task)?;
+    }
+    for key in &task.processed_reduction_tasks {
+        txn.delete_cf(cf, key)?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum InvocationCompletion {
+    // Along with the invocation group of the invocation if it finished with it
+    User(InvocationResult, Option<InvocationGroup>),
+    System(InvocationResult),
+    // The invocation ran a sub graph task, which is now finalized
+    SubGraph(FinalizeTaskRequest),
+}
+
+// returns true if system task has finished
+pub(crate) fn create_tasks(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &CreateTasksRequest,
+) -> Result<Option<InvocationCompletion>> {
+    let ctx_key =
+        GraphInvocationCtx::key_from(&req.namespace, &req.compute_graph, &req.invocation_id);
+    let graph_ctx = txn.get_for_update_cf(
+        &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
+        &ctx_key,
+        true,
+    )?;
+    if graph_ctx.is_none() {
+        error!("Graph context not found for graph: {}", req.compute_graph);
+    }
+    let mut graph_ctx: GraphInvocationCtx = JsonEncoder::decode(&graph_ctx.unwrap())?;
+    let compute_graph = get_compute_graph_version(
+        db.clone(),
+        txn,
+        &req.namespace,
+        &req.compute_graph,
+        &graph_ctx.graph_version,
+    )?;
+    for task in &req.tasks {
+        let mut task = task.clone();
+        task.invocation_labels = graph_ctx.labels.clone();
+        task.priority = graph_ctx.priority;
+        task.lane = graph_ctx.lane;
+        task.parameters = graph_ctx.parameters.clone();
+        if let Some(compute_graph) = &compute_graph {
+            task.code_sha256 = Some(compute_graph.code.sha256_hash.clone());
+            let node = compute_graph.nodes.get(&task.compute_fn_name);
+            task.runtime = node.and_then(|node| node.runtime()).cloned();
+            let concurrency_key = node.and_then(|node| node.concurrency_key());
+            if let Some(template) = concurrency_key {
+                task.concurrency_key = Some(evaluate_concurrency_key(template, &task)?);
+            }
+            // Tasks of windows wait for the window to close
+            if node.and_then(|node| node.window()).is_some() {
+                let item = WindowItem::new(&task, get_epoch_time_in_ms(), graph_ctx.event_time);
+                txn.put_cf(
+                    &IndexifyObjectsColumns::WindowItems.cf_db(&db),
+                    item.key(),
+                    JsonEncoder::encode(&item)?,
+                )?;
+            }
+        }
+        let serialized_task = BinaryEncoder::encode(&task)?;
+        txn.put_cf(
+            &IndexifyObjectsColumns::Tasks.cf_db(&db),
+            task.key(),
+            &serialized_task,
+        )?;
+        txn.put_cf(
+            &IndexifyObjectsColumns::UnallocatedTasks.cf_db(&db),
+            task.key(),
+            &[],
+        )?;
+
+        let analytics = graph_ctx
+            .fn_task_analytics
+            .entry(task.compute_fn_name.clone())
+            .or_insert_with(|| TaskAnalytics::default());
+        analytics.pending();
+    }
+    put_invocation_events(
+        db.clone(),
+        txn,
+        &req.namespace,
+        &req.compute_graph,
+        &req.invocation_id,
+        req.tasks
+            .iter()
+            .map(|task| InvocationEventKind::TaskCreated {
+                task_id: task.id.to_string(),
+                compute_fn: task.compute_fn_name.clone(),
+            }),
+    )?;
+    graph_ctx.outstanding_tasks += req.tasks.len() as u64;
+    match &req.fan_out_cursor {
+        // The pending fan out holds the reference of the state change event
+        // until all its tasks are created
+        Some(cursor) if !cursor.is_done() => {
+            put_fan_out_cursor(db.clone(), txn, cursor)?;
+        }
+        Some(cursor) => {
+            txn.delete_cf(
+                &IndexifyObjectsColumns::FanOutCursors.cf_db(&db),
+                cursor.key(),
+            )?;
+            graph_ctx.outstanding_tasks -= 1;
+        }
+        // Subtract reference for completed state change event
+        None => graph_ctx.outstanding_tasks -= 1,
+    }
+    let serialized_analytics = JsonEncoder::encode(&graph_ctx)?;
+    txn.put_cf(
+        &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
+        ctx_key,
+        serialized_analytics,
+    )?;
+    if graph_ctx.outstanding_tasks == 0 {
+        Ok(Some(mark_invocation_finished(
+            db,
+            txn,
+            &req.namespace,
+            &req.compute_graph,
+            &req.invocation_id,
+        )?))
+    } else {
+        Ok(None)
+    }
+}
+
+fn put_fan_out_cursor(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    cursor: &FanOutCursor,
+) -> Result<()> {
+    txn.put_cf(
+        &IndexifyObjectsColumns::FanOutCursors.cf_db(&db),
+        cursor.key(),
+        JsonEncoder::encode(cursor)?,
+    )?;
+    Ok(())
+}
+
+pub fn allocate_tasks(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    task: &Task,
+    executor_id: &ExecutorId,
+) -> Result<()> {
+    txn.put_cf(
+        &IndexifyObjectsColumns::TaskAllocations.cf_db(&db),
+        task.make_allocation_key(executor_id),
+        &[],
+    )?;
+    // Persists the dispatch time the allocation key is derived from, along
+    // with the allocation time durations are measured from
+    let mut task = task.clone();
+    task.allocated_at = Some(get_epoch_time_in_ms());
+    // A previous attempt may have checkpointed since the task was read
+    if let Some(stored) =
+        txn.get_for_update_cf(&IndexifyObjectsColumns::Tasks.cf_db(&db), task.key(), true)?
+    {
+        task.checkpoint = JsonEncoder::decode::<Task>(&stored)?.checkpoint;
+    }
+    txn.put_cf(
+        &IndexifyObjectsColumns::Tasks.cf_db(&db),
+        task.key(),
+        BinaryEncoder::encode(&task)?,
+    )?;
+    txn.delete_cf(
+        &IndexifyObjectsColumns::UnallocatedTasks.cf_db(&db),
+        task.key(),
+    )?;
+    put_invocation_events(
+        db,
+        txn,
+        &task.namespace,
+        &task.compute_graph_name,
+        &task.invocation_id,
+        [InvocationEventKind::TaskAllocated {
+            task_id: task.id.to_string(),
+            compute_fn: task.compute_fn_name.clone(),
+            executor_id: executor_id.get().to_string(),
+        }],
+    )?;
+    Ok(())
+}
+
+/// Revokes the allocation of a task pre-empted by a higher priority task and
+/// moves it back to the unallocated tasks. Returns false if the task is not
+/// allocated to the executor anymore.
+pub(crate) fn preempt_task(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    task: &Task,
+    executor_id: &ExecutorId,
+) -> Result<bool> {
+    let allocation_key = task.make_allocation_key(executor_id);
+    if txn
+        .get_for_update_cf(
+            &IndexifyObjectsColumns::TaskAllocations.cf_db(&db),
+            &allocation_key,
+            true,
+        )?
+        .is_none()
+    {
+        return Ok(false);
+    }
+    txn.delete_cf(
+        &IndexifyObjectsColumns::TaskAllocations.cf_db(&db),
+        &allocation_key,
+    )?;
+    let mut task = task.clone();
+    task.gpu_ids.clear();
+    task.dispatch_time = None;
+    txn.put_cf(
+        &IndexifyObjectsColumns::Tasks.cf_db(&db),
+        task.key(),
+        BinaryEncoder::encode(&task)?,
+    )?;
+    txn.put_cf(
+        &IndexifyObjectsColumns::UnallocatedTasks.cf_db(&db),
+        task.key(),
+        &[],
+    )?;
+    put_invocation_events(
+        db,
+        txn,
+        &task.namespace,
+        &task.compute_graph_name,
+        &task.invocation_id,
+        [InvocationEventKind::TaskPreempted {
+            task_id: task.id.to_string(),
+            compute_fn: task.compute_fn_name.clone(),
+            executor_id: executor_id.get().to_string(),
+        }],
+    )?;
+    Ok(true)
+}
+
+/// Returns true if the task was marked as completed.
+/// If task was already completed, returns false.
+/// Output limits of the function of the task, in the graph version the task
+/// runs.
+fn task_output_limits(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    task: &Task,
+) -> Result<Option<OutputLimits>> {
+    let graph = get_compute_graph_version(
+        db,
+        txn,
+        &task.namespace,
+        &task.compute_graph_name,
+        &task.graph_version,
+    )?;
+    Ok(
+        match graph.and_then(|mut graph| graph.nodes.remove(&task.compute_fn_name)) {
+            Some(Node::Compute(compute_fn)) => compute_fn.output_limits,
+            _ => None,
+        },
+    )
+}
+
+/// Number and total size of the function outputs.
+fn fn_outputs_tally(outputs: &[NodeOutput]) -> (u64, u64) {
+    outputs
+        .iter()
+        .filter(|output| matches!(output.payload, OutputPayload::Fn(_)))
+        .fold((0, 0), |(count, bytes), output| {
+            (count + 1, bytes + output_size(output))
+        })
+}
+
+/// Finalizes the task with the outcome of the request, unless it exceeded
+/// the output limits of its function in which case it fails and its outputs
+/// are dropped. Returns the outcome, or None if the task already finished.
+pub fn mark_task_completed(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    mut req: FinalizeTaskRequest,
+) -> Result<Option<TaskOutcome>> {
+    let task_key = Task::key_from(
+        &req.namespace,
+        &req.compute_graph,
+        &req.invocation_id,
+        &req.compute_fn,
+        req.task_id.get(),
+    );
+    let task = txn
+        .get_for_update_cf(&IndexifyObjectsColumns::Tasks.cf_db(&db), &task_key, true)?
+        .ok_or(StateStoreError::not_found(format!("task {}", &req.task_id)))?;
+    let mut task = JsonEncoder::decode::<Task>(&task)?;
+    if task.terminal_state() {
+        return Ok(None);
+    }
+    let failure = match task.failure.clone() {
+        Some(failure) => Some(failure),
+        None => {
+            let (outputs, output_bytes) = fn_outputs_tally(&req.node_outputs);
+            task_output_limits(db.clone(), txn, &task)?.and_then(|limits| {
+                limits.check(
+                    task.streamed_outputs + outputs,
+                    task.streamed_output_bytes + output_bytes,
+                )
+            })
+        }
+    };
+    if let Some(failure) = failure {
+        // The uploads of the dropped outputs are never committed, they are
+        // swept by the garbage collector
+        req.node_outputs.clear();
+        req.inline_payloads.clear();
+        req.task_outcome = TaskOutcome::Failure;
+        task.failure = Some(failure);
+    }
+    let graph_ctx_key =
+        GraphInvocationCtx::key_from(&req.namespace, &req.compute_graph, &req.invocation_id);
+    let graph_ctx = txn
+        .get_for_update_cf(
+            &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
+            &graph_ctx_key,
+            true,
+        )?
+        .ok_or(StateStoreError::not_found(format!(
+            "graph context for task {}",
+            &req.task_id
+        )))?;
+    let mut graph_ctx: GraphInvocationCtx = JsonEncoder::decode(&graph_ctx)?;
+    let resource_usage = req.resource_usage.unwrap_or_default();
+    let usage = TaskUsage {
+        tasks: 1,
+        duration_ms: task
+            .allocated_at
+            .map(|allocated_at| get_epoch_time_in_ms().saturating_sub(allocated_at))
+            .unwrap_or_default(),
+        input_bytes: task_input_size(db.clone(), txn, &task)?,
+        output_bytes: req.node_outputs.iter().map(output_size).sum(),
+        cpu_seconds: resource_usage.cpu_seconds,
+        gpu_seconds: resource_usage.gpu_seconds,
+        cold_starts: (resource_usage.cold_start == Some(true)) as u64,
+        warm_starts: (resource_usage.cold_start == Some(false)) as u64,
+        failed_tasks: (req.task_outcome == TaskOutcome::Failure) as u64,
+    };
+    graph_ctx.usage.add(&usage);
+    add_task_usage(db.clone(), txn, &req.namespace, &req.compute_graph, &usage)?;
+    task.usage = Some(usage);
+    let mut events = vec![InvocationEventKind::TaskFinished {
+        task_id: task.id.to_string(),
+        compute_fn: task.compute_fn_name.clone(),
+        outcome: req.task_outcome.clone(),
+    }];
+    for output in &req.node_outputs {
+        if let OutputPayload::Router(router_output) = &output.payload {
+            events.push(InvocationEventKind::RouterChose {
+                task_id: task.id.to_string(),
+                compute_fn: task.compute_fn_name.clone(),
+                edges: router_output.edges.clone(),
+            });
+        }
+    }
+    put_invocation_events(
+        db.clone(),
+        txn,
+        &req.namespace,
+        &req.compute_graph,
+        &req.invocation_id,
+        events,
+    )?;
+    put_task_outputs(
+        db.clone(),
+        txn,
+        &task,
+        &req.invocation_id,
+        &graph_ctx,
+        req.node_outputs,
+    )?;
+    for payload in &req.inline_payloads {
+        txn.put_cf(
+            &IndexifyObjectsColumns::InlinePayloads.cf_db(&db),
+            payload.path.as_bytes(),
+            &payload.data,
+        )?;
+    }
+    let analytics = graph_ctx
+        .fn_task_analytics
+        .entry(req.compute_fn.to_string())
+        .or_insert_with(|| TaskAnalytics::default());
+    match req.task_outcome {
+        data_model::TaskOutcome::Success => analytics.success(),
+        data_model::TaskOutcome::Failure => analytics.fail(),
+        _ => {}
+    }
+    let serialized_analytics = JsonEncoder::encode(&graph_ctx)?;
+    txn.put_cf(
+        &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
+        graph_ctx_key,
+        serialized_analytics,
+    )?;
+
+    txn.delete_cf(
+        &IndexifyObjectsColumns::TaskAllocations.cf_db(&db),
+        &task.make_allocation_key(&req.executor_id),
+    )?;
+    // Tasks run by the server are finalized without being allocated
+    txn.delete_cf(
+        &IndexifyObjectsColumns::UnallocatedTasks.cf_db(&db),
+        task.key(),
+    )?;
+
+    if let Some(diagnostics) = &req.diagnostics {
+        for payload in diagnostics.payloads() {
+            commit_upload(db.clone(), txn, &payload.path)?;
+        }
+    }
+    task.diagnostics = req.diagnostics.clone();
+    // Finished tasks are never resumed
+    if let Some(checkpoint) = task.checkpoint.take() {
+        txn.put_cf(
+            &IndexifyObjectsColumns::GcUrls.cf_db(&db),
+            checkpoint.payload.path.as_bytes(),
+            &[],
+        )?;
+    }
+
+    task.outcome = req.task_outcome.clone();
+    let task_bytes = BinaryEncoder::encode(&task)?;
+    txn.put_cf(
+        &IndexifyObjectsColumns::Tasks.cf_db(&db),
+        task.key(),
+        task_bytes,
+    )?;
+    Ok(Some(req.task_outcome))
+}
+
+/// Registers blobs about to be uploaded for running tasks. Uploads for tasks
+/// which already finished are rejected, they would never be committed.
+pub(crate) fn register_pending_uploads(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &RegisterPendingUploadsRequest,
+) -> Result<()> {
+    for upload in &req.uploads {
+        let task = txn
+            .get_for_update_cf(
+                &IndexifyObjectsColumns::Tasks.cf_db(&db),
+                upload.task_key(),
+                false,
+            )?
+            .ok_or(StateStoreError::not_found(format!(
+                "task {}",
+                &upload.task_id
+            )))?;
+        if JsonEncoder::decode::<Task>(&task)?.terminal_state() {
+            return Err(StateStoreError::Conflict(format!(
+                "task {} already finished",
+                upload.task_id
+            ))
+            .into());
+        }
+        txn.put_cf(
+            &IndexifyObjectsColumns::PendingUploads.cf_db(&db),
+            &upload.url,
+            JsonEncoder::encode(upload)?,
+        )?;
+    }
+    Ok(())
+}
+
+/// The blob is referenced by the state store from now on.
+fn commit_upload(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    url: &str,
+) -> Result<()> {
+    txn.delete_cf(&IndexifyObjectsColumns::PendingUploads.cf_db(&db), url)?;
+    Ok(())
+}
+
+/// Hands the uploads which are still pending to the garbage collector.
+/// Returns the number of swept uploads.
+pub(crate) fn sweep_pending_uploads(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &SweepPendingUploadsRequest,
+) -> Result<usize> {
+    let mut swept = 0;
+    for url in &req.urls {
+        let pending = txn.get_for_update_cf(
+            &IndexifyObjectsColumns::PendingUploads.cf_db(&db),
+            url,
+            true,
+        )?;
+        if pending.is_none() {
+            continue;
+        }
+        txn.delete_cf(&IndexifyObjectsColumns::PendingUploads.cf_db(&db), url)?;
+        txn.put_cf(
+            &IndexifyObjectsColumns::GcUrls.cf_db(&db),
+            url.as_bytes(),
+            &[],
+        )?;
+        swept += 1;
+    }
+    Ok(swept)
+}
+
+fn put_task_outputs(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    task: &Task,
+    invocation_id: &str,
+    graph_ctx: &GraphInvocationCtx,
+    node_outputs: Vec<NodeOutput>,
+) -> Result<()> {
+    let mut added_bytes: i64 = 0;
+    for mut output in node_outputs {
+        // Update with correct graph version
+        output.graph_version = graph_ctx.graph_version;
+
+        if let OutputPayload::Fn(payload) = &output.payload {
+            commit_upload(db.clone(), txn, &payload.path)?;
+        }
+        let serialized_output = JsonEncoder::encode(&output)?;
+        // Create an output key
+        let output_key = output.key(invocation_id);
+        // Outputs of reducers replace the previous accumulated value
+        if let Some(existing) = txn.get_for_update_cf(
+            &IndexifyObjectsColumns::FnOutputs.cf_db(&db),
+            &output_key,
+            true,
+        )? {
+            added_bytes -= output_size(&JsonEncoder::decode::<NodeOutput>(&existing)?) as i64;
+        }
+        added_bytes += output_size(&output) as i64;
+        txn.put_cf(
+            &IndexifyObjectsColumns::FnOutputs.cf_db(&db),
+            &output_key,
+            serialized_output,
+        )?;
+
+        // Create a key to store the pointer to the node output to the task
+        // NS_TASK_ID_<OutputID> -> Output Key
+        let task_output_key = task.key_output(&output.id);
+        let node_output_id = JsonEncoder::encode(&output_key)?;
+        txn.put_cf(
+            &IndexifyObjectsColumns::TaskOutputs.cf_db(&db),
+            task_output_key,
+            node_output_id,
+        )?;
+    }
+    update_storage_usage(
+        db,
+        txn,
+        &task.namespace,
+        &task.compute_graph_name,
+        |usage| usage.output_bytes = usage.output_bytes.saturating_add_signed(added_bytes),
+    )?;
+    Ok(())
+}
+
+/// Saves the outputs a running task streamed. Returns the ids of the saved
+/// outputs, or None if the task already finished or exceeded the output
+/// limits of its function, it fails when it's finalized.
+pub fn stream_task_outputs(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &StreamTaskOutputsRequest,
+) -> Result<Option<Vec<String>>> {
+    let task_key = Task::key_from(
+        &req.namespace,
+        &req.compute_graph,
+        &req.invocation_id,
+        &req.compute_fn,
+        req.task_id.get(),
+    );
+    let task = txn
+        .get_for_update_cf(&IndexifyObjectsColumns::Tasks.cf_db(&db), &task_key, true)?
+        .ok_or(StateStoreError::not_found(format!("task {}", &req.task_id)))?;
+    let mut task = JsonEncoder::decode::<Task>(&task)?;
+    if task.terminal_state() || task.failure.is_some() {
+        return Ok(None);
+    }
+    let (outputs, output_bytes) = fn_outputs_tally(&req.node_outputs);
+    task.streamed_outputs += outputs;
+    task.streamed_output_bytes += output_bytes;
+    task.failure = task_output_limits(db.clone(), txn, &task)?
+        .and_then(|limits| limits.check(task.streamed_outputs, task.streamed_output_bytes));
+    txn.put_cf(
+        &IndexifyObjectsColumns::Tasks.cf_db(&db),
+        task.key(),
+        BinaryEncoder::encode(&task)?,
+    )?;
+    if task.failure.is_some() {
+        return Ok(None);
+    }
+    let graph_ctx_key =
+        GraphInvocationCtx::key_from(&req.namespace, &req.compute_graph, &req.invocation_id);
+    let graph_ctx = txn
+        .get_for_update_cf(
+            &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
+            &graph_ctx_key,
+            true,
+        )?
+        .ok_or(StateStoreError::not_found(format!(
+            "graph context for task {}",
+            &req.task_id
+        )))?;
+    let mut graph_ctx: GraphInvocationCtx = JsonEncoder::decode(&graph_ctx)?;
+    let mut node_outputs = req.node_outputs.clone();
+    for output in &mut node_outputs {
+        output.streamed = true;
+    }
+    let output_ids = node_outputs
+        .iter()
+        .map(|output| output.id.clone())
+        .collect();
+    put_task_outputs(
+        db.clone(),
+        txn,
+        &task,
+        &req.invocation_id,
+        &graph_ctx,
+        node_outputs,
+    )?;
+    // Reference for the state change creating the downstream tasks
+    graph_ctx.outstanding_tasks += 1;
+    txn.put_cf(
+        &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
+        graph_ctx_key,
+        JsonEncoder::encode(&graph_ctx)?,
+    )?;
+    Ok(Some(output_ids))
+}
+
+/// Replaces the checkpoint of a running task, the blob of the previous
+/// checkpoint is handed to the garbage collector. Returns whether there was a
+/// previous checkpoint.
+pub(crate) fn checkpoint_task(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &CheckpointTaskRequest,
+) -> Result<bool> {
+    let task_key = Task::key_from(
+        &req.namespace,
+        &req.compute_graph,
+        &req.invocation_id,
+        &req.compute_fn,
+        req.task_id.get(),
+    );
+    let task = txn
+        .get_for_update_cf(&IndexifyObjectsColumns::Tasks.cf_db(&db), &task_key, true)?
+        .ok_or(StateStoreError::not_found(format!("task {}", &req.task_id)))?;
+    let mut task = JsonEncoder::decode::<Task>(&task)?;
+    if task.terminal_state() {
+        return Err(
+            StateStoreError::Conflict(format!("task {} already finished", req.task_id)).into(),
+        );
+    }
+    commit_upload(db.clone(), txn, &req.checkpoint.payload.path)?;
+    let previous = task.checkpoint.replace(req.checkpoint.clone());
+    if let Some(previous) = &previous {
+        txn.put_cf(
+            &IndexifyObjectsColumns::GcUrls.cf_db(&db),
+            previous.payload.path.as_bytes(),
+            &[],
+        )?;
+    }
+    txn.put_cf(
+        &IndexifyObjectsColumns::Tasks.cf_db(&db),
+        task.key(),
+        BinaryEncoder::encode(&task)?,
+    )?;
+    Ok(previous.is_some())
+}
+
+pub(crate) fn save_state_changes(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    state_changes: &Vec<StateChange>,
+) -> Result<()> {
+    for state_change in state_changes {
+        let serialized_state_change = BinaryEncoder::encode(&state_change)?;
+        txn.put_cf(
+            &IndexifyObjectsColumns::StateChanges.cf_db(&db),
+            &state_change.id.to_key(),
+            serialized_state_change.clone(),
+        )?;
+
+        if state_change.processed_at.is_none() {
+            txn.put_cf(
+                &IndexifyObjectsColumns::UnprocessedStateChanges.cf_db(&db),
+                &state_change.id.to_key(),
+                serialized_state_change,
+            )?;
+        } else {
+            txn.delete_cf(
+                &IndexifyObjectsColumns::UnprocessedStateChanges.cf_db(&db),
+                &state_change.id.to_key(),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn mark_state_changes_processed(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    state_change_ids: &Vec<StateChangeId>,
+) -> Result<()> {
+    let mut state_changes = Vec::new();
+    for state_change_id in state_change_ids {
+        let state_change = txn.get_cf(
+            &IndexifyObjectsColumns::StateChanges.cf_db(&db),
+            state_change_id.to_key(),
+        )?;
+        if state_change.is_none() {
+            error!("State change not found: {}", state_change_id);
+            continue;
+        }
+        let state_change = state_change.unwrap();
+        let mut state_change: StateChange = JsonEncoder::decode(&state_change)?;
+        state_change.processed_at = Some(get_epoch_time_in_ms());
+        state_changes.push(state_change);
+    }
+    save_state_changes(db, txn, &state_changes)?;
+    Ok(())
+}
+
+// Returns true if the invocation was a system task
+fn mark_invocation_finished(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    namespace: &str,
+    compute_graph: &str,
+    invocation_id: &str,
+) -> Result<InvocationCompletion> {
+    tracing::info!(
+        "Marking invocation finished: {} {} {}",
+        namespace,
+        compute_graph,
+        invocation_id
+    );
+    let key = GraphInvocationCtx::key_from(&namespace, &compute_graph, &invocation_id);
+    let graph_ctx = txn
+        .get_cf(&IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db), &key)?
+        .ok_or(StateStoreError::not_found(format!(
+            "graph context for invocation {}",
+            &invocation_id
+        )))?;
+    let mut graph_ctx: GraphInvocationCtx = JsonEncoder::decode(&graph_ctx)?;
+    release_pending_invocation(db.clone(), txn, &graph_ctx)?;
+    graph_ctx.completed = true;
+    let serialized_graph_ctx = JsonEncoder::encode(&graph_ctx)?;
+    txn.put_cf(
+        &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
+        &key,
+        serialized_graph_ctx,
+    )?;
+    let result = invocation_result(db.clone(), txn, &graph_ctx)?;
+    txn.put_cf(
+        &IndexifyObjectsColumns::InvocationResults.cf_db(&db),
+        &key,
+        JsonEncoder::encode(&result)?,
+    )?;
+    put_invocation_events(
+        db.clone(),
+        txn,
+        namespace,
+        compute_graph,
+        invocation_id,
+        [InvocationEventKind::InvocationFinished {
+            status: result.status,
+        }],
+    )?;
+    if graph_ctx.is_system_task {
+        let cf = IndexifyObjectsColumns::Stats.cf_db(&db);
+        let key = b"pending_system_tasks";
+        let value = txn.get_cf(&cf, key)?;
+        let mut pending_system_tasks = match value {
+            Some(value) => {
+                let bytes: [u8; 8] = value
+                    .as_slice()
+                    .try_into()
+                    .map_err(|_| anyhow::anyhow!("Invalid length for usize conversion"))?;
+                usize::from_be_bytes(bytes)
+            }
+            None => 0,
+        };
+        pending_system_tasks -= 1;
+        txn.put_cf(&cf, key, &pending_system_tasks.to_be_bytes())?;
+        Ok(InvocationCompletion::System(result))
+    } else {
+        if let Some(parent) = &graph_ctx.parent {
+            let req = complete_sub_graph_task(db, txn, &graph_ctx, parent)?;
+            return Ok(InvocationCompletion::SubGraph(req));
+        }
+        let mut finished_group = None;
+        let group = match &graph_ctx.group {
+            Some(group_id) => {
+                get_invocation_group_for_update(db.clone(), txn, namespace, group_id)?
+            }
+            None => None,
+        };
+        if let Some(mut group) = group {
+            if group.invocation_finished(result.status, result.finished_at) {
+                finished_group = Some(group.clone());
+            }
+            put_invocation_group(db, txn, &group)?;
+        }
+        Ok(InvocationCompletion::User(result, finished_group))
+    }
+}
+
+// Outputs of the terminal nodes and failed tasks of a finished invocation
+fn invocation_result(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    graph_ctx: &GraphInvocationCtx,
+) -> Result<InvocationResult> {
+    let graph = txn
+        .get_cf(
+            &IndexifyObjectsColumns::ComputeGraphs.cf_db(&db),
+            format!("{}|{}", graph_ctx.namespace, graph_ctx.compute_graph_name),
+        )?
+        .map(|graph| JsonEncoder::decode::<ComputeGraph>(&graph))
+        .transpose()?;
+    let terminal_nodes: Vec<String> = graph
+        .as_ref()
+        .map(|graph| {
+            graph
+                .terminal_nodes()
+                .iter()
+                .map(|node| node.name().to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+    // Failures of routers handled by their on_error policy don't fail the
+    // invocation
+    let handles_failures = |compute_fn: &str| {
+        graph
+            .as_ref()
+            .and_then(|graph| graph.nodes.get(compute_fn))
+            .is_some_and(
+                |node| matches!(node, Node::Router(router) if router.targets_on_error().is_some()),
+            )
+    };
+    let prefix = KeyPrefix::encoded(&[
+        &graph_ctx.namespace,
+        &graph_ctx.compute_graph_name,
+        &graph_ctx.invocation_id,
+    ]);
+    let mut outputs = vec![];
+    for output in make_prefix_iterator(
+        txn,
+        &IndexifyObjectsColumns::FnOutputs.cf_db(&db),
+        prefix.as_bytes(),
+        &None,
+    ) {
+        let (_, value) = output?;
+        let output: NodeOutput = JsonEncoder::decode(&value)?;
+        if terminal_nodes.contains(&output.compute_fn_name) {
+            outputs.push(output);
+        }
+    }
+    let mut errors = vec![];
+    for task in make_prefix_iterator(
+        txn,
+        &IndexifyObjectsColumns::Tasks.cf_db(&db),
+        &graph_ctx.key(),
+        &None,
+    ) {
+        let (_, value) = task?;
+        let task: Task = JsonEncoder::decode(&value)?;
+        if task.outcome == TaskOutcome::Failure && !handles_failures(&task.compute_fn_name) {
+            errors.push(InvocationError {
+                compute_fn: task.compute_fn_name,
+                task_id: task.id,
+                exception: task.diagnostics.and_then(|d| d.exception),
+            });
+        }
+    }
+    let status = if errors.is_empty() {
+        InvocationStatus::Success
+    } else {
+        InvocationStatus::Failure
+    };
+    Ok(InvocationResult {
+        namespace: graph_ctx.namespace.clone(),
+        compute_graph_name: graph_ctx.compute_graph_name.clone(),
+        invocation_id: graph_ctx.invocation_id.clone(),
+        status,
+        outputs,
+        errors,
+        created_at: graph_ctx.created_at,
+        finished_at: get_epoch_time_in_ms(),
+    })
+}
+
+pub(crate) fn create_executor_pool(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &CreateExecutorPoolRequest,
+) -> Result<()> {
+    let serialized_pool = JsonEncoder::encode(&req.pool)?;
+    txn.put_cf(
+        &IndexifyObjectsColumns::ExecutorPools.cf_db(&db),
+        req.pool.key(),
+        serialized_pool,
+    )?;
+    Ok(())
+}
+
+pub(crate) fn delete_executor_pool(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &DeleteExecutorPoolRequest,
+) -> Result<()> {
+    txn.delete_cf(
+        &IndexifyObjectsColumns::ExecutorPools.cf_db(&db),
+        ExecutorPool::key_from(&req.namespace, &req.name),
+    )?;
+    Ok(())
+}
+
+/// Stores the entries, replacing the earlier entries of the same executors
+/// and functions. The expired entries of the functions are dropped.
+pub(crate) fn blacklist_executors(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &BlacklistExecutorsRequest,
+) -> Result<()> {
+    let cf = IndexifyObjectsColumns::ExecutorBlacklist.cf_db(&db);
+    for entry in &req.entries {
+        let prefix = ExecutorBlacklistEntry::fn_prefix(
+            &entry.namespace,
+            &entry.compute_graph,
+            &entry.compute_fn,
+        );
+        for kv in make_prefix_iterator(txn, &cf, prefix.as_bytes(), &None) {
+            let (key, value) = kv?;
+            let existing: ExecutorBlacklistEntry = JsonEncoder::decode(&value)?;
+            if !existing.is_active(entry.blacklisted_at) {
+                txn.delete_cf(&cf, key)?;
+            }
+        }
+        txn.put_cf(&cf, entry.key(), JsonEncoder::encode(entry)?)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn remove_executor_blacklist_entry(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &RemoveExecutorBlacklistEntryRequest,
+) -> Result<()> {
+    let key = ExecutorBlacklistEntry::key_from(
+        &req.namespace,
+        &req.compute_graph,
+        &req.compute_fn,
+        &req.executor_id,
+    );
+    let cf = IndexifyObjectsColumns::ExecutorBlacklist.cf_db(&db);
+    if txn.get_for_update_cf(&cf, &key, true)?.is_none() {
+        return Err(StateStoreError::not_found(format!(
+            "blacklist entry of executor {} for fn {}",
+            req.executor_id, req.compute_fn
+        ))
+        .into());
+    }
+    txn.delete_cf(&cf, &key)?;
+    Ok(())
+}
+
+// Unacknowledged abort commands kept per executor, the oldest are dropped
+// beyond it
+const MAX_PENDING_ABORTS: usize = 10_000;
+
+/// Persists the abort commands of an executor polling its control channel,
+/// they are delivered until it acknowledges them. Returns false for the
+/// other executors, which are told over their task stream instead.
+pub(crate) fn persist_abort_commands(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    executor_id: &ExecutorId,
+    commands: &[AbortCommand],
+) -> Result<bool> {
+    let Some(executor) = txn.get_cf(
+        &IndexifyObjectsColumns::Executors.cf_db(&db),
+        executor_id.get(),
+    )?
+    else {
+        return Ok(false);
+    };
+    let executor: ExecutorMetadata = JsonEncoder::decode(&executor)?;
+    if !executor.features.contains(&ExecutorFeature::ControlChannel) {
+        return Ok(false);
+    }
+    let cf = IndexifyObjectsColumns::PendingAborts.cf_db(&db);
+    for command in commands {
+        txn.put_cf(
+            &cf,
+            AbortCommand::key_from(executor_id, &command.task_id),
+            JsonEncoder::encode(command)?,
+        )?;
+    }
+    let prefix = AbortCommand::key_prefix(executor_id);
+    let mut pending = Vec::new();
+    for kv in make_prefix_iterator(txn, &cf, prefix.as_bytes(), &None) {
+        let (key, value) = kv?;
+        let command: AbortCommand = JsonEncoder::decode(&value)?;
+        pending.push((command.issued_at, key));
+    }
+    if pending.len() > MAX_PENDING_ABORTS {
+        pending.sort();
+        for (_, key) in &pending[..pending.len() - MAX_PENDING_ABORTS] {
+            txn.delete_cf(&cf, key)?;
+        }
+    }
+    Ok(true)
+}
+
+pub(crate) fn acknowledge_aborts(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &AcknowledgeAbortsRequest,
+) -> Result<()> {
+    let cf = IndexifyObjectsColumns::PendingAborts.cf_db(&db);
+    for task_id in &req.task_ids {
+        txn.delete_cf(&cf, AbortCommand::key_from(&req.executor_id, task_id))?;
+    }
+    Ok(())
+}
+
+pub(crate) fn record_executor_approval(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &RecordExecutorApprovalRequest,
+) -> Result<()> {
+    txn.put_cf(
+        &IndexifyObjectsColumns::ExecutorApprovals.cf_db(&db),
+        req.approval.executor_id.get(),
+        JsonEncoder::encode(&req.approval)?,
+    )?;
+    Ok(())
+}
+
+static LEGAL_HOLD_AUDIT_SEQ: AtomicU32 = AtomicU32::new(0);
+
+fn record_legal_hold_audit(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &LegalHoldRequest,
+    action: LegalHoldAction,
+) -> Result<()> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?;
+    let entry = LegalHoldAuditEntry {
+        namespace: req.namespace.clone(),
+        compute_graph: req.compute_graph.clone(),
+        invocation_id: req.invocation_id.clone(),
+        action,
+        identity: req.identity.clone(),
+        reason: req.reason.clone(),
+        at: now.as_millis() as u64,
+    };
+    txn.put_cf(
+        &IndexifyObjectsColumns::LegalHoldAudit.cf_db(&db),
+        LegalHoldAuditEntry::key(
+            &req.namespace,
+            now.as_nanos(),
+            LEGAL_HOLD_AUDIT_SEQ.fetch_add(1, Ordering::Relaxed),
+        ),
+        JsonEncoder::encode(&entry)?,
+    )?;
+    Ok(())
+}
+
+/// Sets a legal hold, replacing the reason of an existing one.
+pub(crate) fn set_legal_hold(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &LegalHoldRequest,
+) -> Result<()> {
+    // Deleted graphs can be held, so they aren't purged
+    let graph_key = format!("{}|{}", req.namespace, req.compute_graph);
+    let graph_exists = txn
+        .get_for_update_cf(
+            &IndexifyObjectsColumns::ComputeGraphs.cf_db(&db),
+            &graph_key,
+            true,
+        )?
+        .is_some() ||
+        txn.get_for_update_cf(
+            &IndexifyObjectsColumns::DeletedComputeGraphs.cf_db(&db),
+            &graph_key,
+            true,
+        )?
+        .is_some();
+    if !graph_exists {
+        return Err(StateStoreError::not_found(format!(
+            "compute graph {}/{}",
+            req.namespace, req.compute_graph
+        ))
+        .into());
+    }
+    if let Some(invocation_id) = &req.invocation_id {
+        let invocation_key =
+            InvocationPayload::key_from(&req.namespace, &req.compute_graph, invocation_id);
+        if txn
+            .get_for_update_cf(
+                &IndexifyObjectsColumns::GraphInvocations.cf_db(&db),
+                &invocation_key,
+                true,
+            )?
+            .is_none()
+        {
+            return Err(StateStoreError::not_found(format!("invocation {}", invocation_id)).into());
+        }
+    }
+    let hold = LegalHold {
+        namespace: req.namespace.clone(),
+        compute_graph: req.compute_graph.clone(),
+        invocation_id: req.invocation_id.clone(),
+        reason: req.reason.clone(),
+        set_by: req.identity.clone(),
+        set_at: get_epoch_time_in_ms(),
+    };
+    txn.put_cf(
+        &IndexifyObjectsColumns::LegalHolds.cf_db(&db),
+        hold.key(),
+        JsonEncoder::encode(&hold)?,
+    )?;
+    record_legal_hold_audit(db, txn, req, LegalHoldAction::Set)
+}
+
+pub(crate) fn clear_legal_hold(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &LegalHoldRequest,
+) -> Result<()> {
+    let key = LegalHold::key_from(
+        &req.namespace,
+        &req.compute_graph,
+        req.invocation_id.as_deref(),
+    );
+    let cf = IndexifyObjectsColumns::LegalHolds.cf_db(&db);
+    if txn.get_for_update_cf(&cf, &key, true)?.is_none() {
+        return Err(StateStoreError::not_found(format!(
+            "legal hold of {}/{}{}",
+            req.namespace,
+            req.compute_graph,
+            req.invocation_id
+                .as_ref()
+                .map(|id| format!("/{}", id))
+                .unwrap_or_default()
+        ))
+        .into());
+    }
+    txn.delete_cf(&cf, &key)?;
+    record_legal_hold_audit(db, txn, req, LegalHoldAction::Cleared)
+}
+
+/// Rejects deleting the objects under the prefix while some of them are
+/// held.
+fn check_not_held(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    prefix: &[u8],
+    what: &str,
+) -> Result<()> {
+    if let Some(kv) = make_prefix_iterator(
+        txn,
+        &IndexifyObjectsColumns::LegalHolds.cf_db(&db),
+        prefix,
+        &None,
+    )
+    .next()
+    {
+        let (_, value) = kv?;
+        let hold: LegalHold = JsonEncoder::decode(&value)?;
+        return Err(StateStoreError::Conflict(format!(
+            "{} is under a legal hold set by {}: {}",
+            what, hold.set_by, hold.reason
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+pub(crate) fn create_webhook(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &CreateWebhookRequest,
+) -> Result<()> {
+    txn.put_cf(
+        &IndexifyObjectsColumns::Webhooks.cf_db(&db),
+        req.webhook.key(),
+        JsonEncoder::encode(&req.webhook)?,
+    )?;
+    Ok(())
+}
+
+pub(crate) fn delete_webhook(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &DeleteWebhookRequest,
+) -> Result<()> {
+    let key = Webhook::key_from(&req.namespace, &req.id);
+    txn.delete_cf(&IndexifyObjectsColumns::Webhooks.cf_db(&db), &key)?;
+    let prefix = format!("{}|", key);
+    delete_cf_prefix(
+        txn,
+        &IndexifyObjectsColumns::WebhookDeliveries.cf_db(&db),
+        prefix.as_bytes(),
+    )?;
+    delete_cf_prefix(
+        txn,
+        &IndexifyObjectsColumns::WebhookDeadLetters.cf_db(&db),
+        prefix.as_bytes(),
+    )?;
+    Ok(())
+}
+
+/// Queues the event for the webhooks subscribed to it, of the namespace or of
+/// every namespace when it's None. Returns whether a delivery was queued.
+pub(crate) fn enqueue_webhook_event(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    namespace: Option<&str>,
+    event: WebhookEvent,
+) -> Result<bool> {
+    let prefix = namespace
+        .map(|namespace| format!("{}|", namespace))
+        .unwrap_or_default();
+    let mut deliveries = vec![];
+    for kv in make_prefix_iterator(
+        txn,
+        &IndexifyObjectsColumns::Webhooks.cf_db(&db),
+        prefix.as_bytes(),
+        &None,
+    ) {
+        let (_, value) = kv?;
+        let webhook: Webhook = JsonEncoder::decode(&value)?;
+        if webhook.receives(&event) {
+            deliveries.push(WebhookDelivery::new(&webhook, event.clone()));
+        }
+    }
+    for delivery in &deliveries {
+        txn.put_cf(
+            &IndexifyObjectsColumns::WebhookDeliveries.cf_db(&db),
+            delivery.key(),
+            JsonEncoder::encode(delivery)?,
+        )?;
+    }
+    Ok(!deliveries.is_empty())
+}
 
-    txn.delete_cf(
-        &IndexifyObjectsColumns::TaskAllocations.cf_db(&db),
-        &task.make_allocation_key(&req.executor_id),
-    )?;
+/// Records the SLA status of a compute graph. Returns whether a webhook
+/// delivery was queued, which happens when the SLA becomes breached or
+/// recovers.
+pub(crate) fn record_sla_status(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &RecordSlaStatusRequest,
+) -> Result<bool> {
+    let cf = IndexifyObjectsColumns::SlaStatus.cf_db(&db);
+    let key = req.status.key();
+    let previous = txn
+        .get_for_update_cf(&cf, &key, true)?
+        .map(|value| JsonEncoder::decode::<SlaStatus>(&value))
+        .transpose()?;
+    txn.put_cf(&cf, &key, JsonEncoder::encode(&req.status)?)?;
+    let was_breached = previous.is_some_and(|previous| previous.breached);
+    if was_breached == req.status.breached {
+        return Ok(false);
+    }
+    enqueue_webhook_event(
+        db,
+        txn,
+        Some(&req.status.namespace),
+        WebhookEvent::sla_changed(&req.status),
+    )
+}
 
-    task.diagnostics = req.diagnostics.clone();
+/// Records the statuses of the alerts evaluated by the leader and deletes
+/// those of the removed rules. Returns whether a webhook delivery was queued,
+/// which happens when an alert starts or stops firing. The alerts of removed
+/// rules which were firing are resolved.
+pub(crate) fn record_alert_statuses(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &RecordAlertStatusesRequest,
+) -> Result<bool> {
+    let cf = IndexifyObjectsColumns::AlertStatuses.cf_db(&db);
+    let mut changes = vec![];
+    for status in &req.statuses {
+        let previous = txn
+            .get_for_update_cf(&cf, status.key(), true)?
+            .map(|value| JsonEncoder::decode::<AlertStatus>(&value))
+            .transpose()?;
+        txn.put_cf(&cf, status.key(), JsonEncoder::encode(status)?)?;
+        let was_firing = previous.is_some_and(|previous| previous.firing());
+        if was_firing != status.firing() {
+            changes.push(status.clone());
+        }
+    }
+    for name in &req.removed {
+        let previous = txn
+            .get_for_update_cf(&cf, name, true)?
+            .map(|value| JsonEncoder::decode::<AlertStatus>(&value))
+            .transpose()?;
+        txn.delete_cf(&cf, name)?;
+        if let Some(previous) = previous.filter(|previous| previous.firing()) {
+            let now = get_epoch_time_in_ms();
+            changes.push(AlertStatus {
+                state: AlertState::Ok,
+                since: now,
+                evaluated_at: now,
+                ..previous
+            });
+        }
+    }
+    let mut queued = false;
+    for status in &changes {
+        queued |= enqueue_webhook_event(
+            db.clone(),
+            txn,
+            status.rule.namespace.as_deref(),
+            WebhookEvent::alert_changed(status),
+        )?;
+    }
+    Ok(queued)
+}
 
-    task.outcome = req.task_outcome.clone();
-    let task_bytes = JsonEncoder::encode(&task)?;
-    txn.put_cf(
-        &IndexifyObjectsColumns::Tasks.cf_db(&db),
-        task.key(),
-        task_bytes,
-    )?;
-    Ok(true)
+/// Queues the rejection for the webhooks of the namespace and of the graph.
+/// Returns whether a delivery was queued.
+pub(crate) fn record_data_rejection(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &RecordDataRejectionRequest,
+) -> Result<bool> {
+    enqueue_webhook_event(
+        db,
+        txn,
+        Some(&req.rejection.namespace),
+        WebhookEvent::data_rejected(&req.rejection),
+    )
 }
 
-pub(crate) fn save_state_changes(
+pub(crate) fn record_function_latencies(
     db: Arc<TransactionDB>,
     txn: &Transaction<TransactionDB>,
-    state_changes: &Vec<StateChange>,
+    req: &RecordFunctionLatenciesRequest,
 ) -> Result<()> {
-    for state_change in state_changes {
-        let serialized_state_change = JsonEncoder::encode(&state_change)?;
-        txn.put_cf(
-            &IndexifyObjectsColumns::StateChanges.cf_db(&db),
-            &state_change.id.to_key(),
-            serialized_state_change.clone(),
-        )?;
+    let graphs_cf = IndexifyObjectsColumns::ComputeGraphs.cf_db(&db);
+    let cf = IndexifyObjectsColumns::FunctionLatencies.cf_db(&db);
+    for latency in &req.latencies {
+        let graph_key = format!("{}|{}", latency.namespace, latency.compute_graph);
+        if txn
+            .get_for_update_cf(&graphs_cf, &graph_key, false)?
+            .is_none()
+        {
+            continue;
+        }
+        txn.put_cf(&cf, latency.key(), JsonEncoder::encode(latency)?)?;
+    }
+    Ok(())
+}
 
-        if state_change.processed_at.is_none() {
+/// Records delivery attempts. Deliveries of webhooks deleted while they were
+/// attempted are dropped.
+pub(crate) fn update_webhook_deliveries(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &UpdateWebhookDeliveriesRequest,
+) -> Result<()> {
+    let deliveries_cf = IndexifyObjectsColumns::WebhookDeliveries.cf_db(&db);
+    let dead_letters_cf = IndexifyObjectsColumns::WebhookDeadLetters.cf_db(&db);
+    for delivery in &req.delivered {
+        txn.delete_cf(&deliveries_cf, delivery.key())?;
+    }
+    for delivery in &req.retried {
+        if txn
+            .get_for_update_cf(&deliveries_cf, delivery.key(), true)?
+            .is_some()
+        {
             txn.put_cf(
-                &IndexifyObjectsColumns::UnprocessedStateChanges.cf_db(&db),
-                &state_change.id.to_key(),
-                serialized_state_change,
+                &deliveries_cf,
+                delivery.key(),
+                JsonEncoder::encode(delivery)?,
             )?;
-        } else {
-            txn.delete_cf(
-                &IndexifyObjectsColumns::UnprocessedStateChanges.cf_db(&db),
-                &state_change.id.to_key(),
+        }
+    }
+    for delivery in &req.dead_lettered {
+        if txn
+            .get_for_update_cf(&deliveries_cf, delivery.key(), true)?
+            .is_some()
+        {
+            txn.delete_cf(&deliveries_cf, delivery.key())?;
+            txn.put_cf(
+                &dead_letters_cf,
+                delivery.key(),
+                JsonEncoder::encode(delivery)?,
             )?;
         }
     }
     Ok(())
 }
 
-pub(crate) fn mark_state_changes_processed(
+/// Moves the dead letters of the namespace back to the deliveries, with their
+/// attempts reset. Returns the number of redriven deliveries.
+pub(crate) fn redrive_webhook_dead_letters(
     db: Arc<TransactionDB>,
     txn: &Transaction<TransactionDB>,
-    state_change_ids: &Vec<StateChangeId>,
+    req: &RedriveWebhookDeadLettersRequest,
+) -> Result<usize> {
+    let deliveries_cf = IndexifyObjectsColumns::WebhookDeliveries.cf_db(&db);
+    let dead_letters_cf = IndexifyObjectsColumns::WebhookDeadLetters.cf_db(&db);
+    let prefix = KeyPrefix::namespace(&req.namespace);
+    let now = get_epoch_time_in_ms();
+    let mut redriven = 0;
+    for kv in make_prefix_iterator(txn, &dead_letters_cf, prefix.as_bytes(), &None) {
+        let (key, value) = kv?;
+        let mut delivery: WebhookDelivery = JsonEncoder::decode(&value)?;
+        delivery.attempts = 0;
+        delivery.next_attempt_at = now;
+        delivery.last_error = None;
+        txn.delete_cf(&dead_letters_cf, &key)?;
+        txn.put_cf(&deliveries_cf, &key, JsonEncoder::encode(&delivery)?)?;
+        redriven += 1;
+    }
+    Ok(redriven)
+}
+
+/// Replaces payloads by their copies in another storage tier. The replaced
+/// payloads are garbage collected, as are the copies of payloads which were
+/// deleted or changed since they were copied. Expired payloads have no copy,
+/// they keep the path of the payload they replace.
+pub(crate) fn move_payloads(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &MovePayloadsRequest,
 ) -> Result<()> {
-    let mut state_changes = Vec::new();
-    for state_change_id in state_change_ids {
-        let state_change = txn.get_cf(
-            &IndexifyObjectsColumns::StateChanges.cf_db(&db),
-            state_change_id.to_key(),
-        )?;
-        if state_change.is_none() {
-            error!("State change not found: {}", state_change_id);
-            continue;
+    for payload_move in &req.moves {
+        let mut to = payload_move.to.clone();
+        // Expired payloads drop their replicas
+        if to.tier == StorageTier::Expired {
+            to.replicas.clear();
+        }
+        let moved = update_owned_payload(db.clone(), txn, &payload_move.owner, |payload| {
+            if *payload != payload_move.from {
+                return false;
+            }
+            *payload = to.clone();
+            true
+        })?;
+        let cf = IndexifyObjectsColumns::GcUrls.cf_db(&db);
+        if moved {
+            // A payload moved back to its previous tier reuses its old path
+            txn.delete_cf(&cf, to.path.as_bytes())?;
+            txn.put_cf(&cf, payload_move.from.path.as_bytes(), &[])?;
+            for replica in &payload_move.from.replicas {
+                if !to.replicas.contains(replica) {
+                    txn.put_cf(&cf, replica.path.as_bytes(), &[])?;
+                }
+            }
+        } else if to.tier != StorageTier::Expired {
+            txn.put_cf(&cf, to.path.as_bytes(), &[])?;
         }
-        let state_change = state_change.unwrap();
-        let mut state_change: StateChange = JsonEncoder::decode(&state_change)?;
-        state_change.processed_at = Some(get_epoch_time_in_ms());
-        state_changes.push(state_change);
     }
-    save_state_changes(db, txn, &state_changes)?;
     Ok(())
 }
 
-// Returns true if the invocation was a system task
-fn mark_invocation_finished(
+pub(crate) fn record_payload_replicas(
     db: Arc<TransactionDB>,
     txn: &Transaction<TransactionDB>,
-    namespace: &str,
-    compute_graph: &str,
-    invocation_id: &str,
-) -> Result<InvocationCompletion> {
-    tracing::info!(
-        "Marking invocation finished: {} {} {}",
-        namespace,
-        compute_graph,
-        invocation_id
-    );
-    let key = GraphInvocationCtx::key_from(&namespace, &compute_graph, &invocation_id);
-    let graph_ctx = txn
-        .get_cf(&IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db), &key)?
-        .ok_or(anyhow!(
-            "Graph context not found for invocation: {}",
-            &invocation_id
-        ))?;
-    let mut graph_ctx: GraphInvocationCtx = JsonEncoder::decode(&graph_ctx)?;
-    graph_ctx.completed = true;
-    let serialized_graph_ctx = JsonEncoder::encode(&graph_ctx)?;
-    txn.put_cf(
-        &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
-        key,
-        serialized_graph_ctx,
-    )?;
-    if graph_ctx.is_system_task {
-        let cf = IndexifyObjectsColumns::Stats.cf_db(&db);
-        let key = b"pending_system_tasks";
-        let value = txn.get_cf(&cf, key)?;
-        let mut pending_system_tasks = match value {
-            Some(value) => {
-                let bytes: [u8; 8] = value
-                    .as_slice()
-                    .try_into()
-                    .map_err(|_| anyhow::anyhow!("Invalid length for usize conversion"))?;
-                usize::from_be_bytes(bytes)
+    req: &RecordPayloadReplicasRequest,
+) -> Result<()> {
+    for replication in &req.replications {
+        let replica = &replication.replica;
+        let mut recorded = false;
+        update_owned_payload(db.clone(), txn, &replication.owner, |payload| {
+            // Compared without the replicas, which are recorded one region at
+            // a time
+            if payload.path != replication.payload.path ||
+                payload.sha256_hash != replication.payload.sha256_hash
+            {
+                return false;
             }
-            None => 0,
-        };
-        pending_system_tasks -= 1;
-        txn.put_cf(&cf, key, &pending_system_tasks.to_be_bytes())?;
-        Ok(InvocationCompletion::System)
-    } else {
-        Ok(InvocationCompletion::User)
+            if let Some(existing) = payload.replica(&replica.region) {
+                recorded = existing.path == replica.path;
+                return false;
+            }
+            payload.replicas.push(replica.clone());
+            recorded = true;
+            true
+        })?;
+        if !recorded {
+            txn.put_cf(
+                &IndexifyObjectsColumns::GcUrls.cf_db(&db),
+                replica.path.as_bytes(),
+                &[],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Applies an update to the payload of its owner, returns whether the update
+/// changed the payload.
+fn update_owned_payload(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    owner: &PayloadOwner,
+    update: impl FnOnce(&mut DataPayload) -> bool,
+) -> Result<bool> {
+    match owner {
+        PayloadOwner::Invocation {
+            namespace,
+            compute_graph,
+            invocation_id,
+        } => {
+            let cf = IndexifyObjectsColumns::GraphInvocations.cf_db(&db);
+            let key = InvocationPayload::key_from(namespace, compute_graph, invocation_id);
+            let Some(mut invocation) = txn
+                .get_for_update_cf(&cf, &key, true)?
+                .map(|value| JsonEncoder::decode::<InvocationPayload>(&value))
+                .transpose()?
+            else {
+                return Ok(false);
+            };
+            if !update(&mut invocation.payload) {
+                return Ok(false);
+            }
+            txn.put_cf(&cf, &key, JsonEncoder::encode(&invocation)?)?;
+        }
+        PayloadOwner::FnOutput { key } => {
+            let cf = IndexifyObjectsColumns::FnOutputs.cf_db(&db);
+            let Some(mut output) = txn
+                .get_for_update_cf(&cf, key, true)?
+                .map(|value| JsonEncoder::decode::<NodeOutput>(&value))
+                .transpose()?
+            else {
+                return Ok(false);
+            };
+            let OutputPayload::Fn(payload) = &mut output.payload else {
+                return Ok(false);
+            };
+            if !update(payload) {
+                return Ok(false);
+            }
+            txn.put_cf(&cf, key, JsonEncoder::encode(&output)?)?;
+        }
     }
+    Ok(true)
 }
 
 pub(crate) fn register_executor(
@@ -800,39 +4147,339 @@ pub(crate) fn register_executor(
     txn: &Transaction<TransactionDB>,
     req: &RegisterExecutorRequest,
 ) -> Result<()> {
-    let serialized_executor_metadata = JsonEncoder::encode(&req.executor)?;
+    let mut executor = req.executor.clone();
+    executor.last_heartbeat_at = Some(get_epoch_time_in_ms());
+    let serialized_executor_metadata = JsonEncoder::encode(&executor)?;
     txn.put_cf(
         &IndexifyObjectsColumns::Executors.cf_db(&db),
-        req.executor.key(),
+        executor.key(),
         serialized_executor_metadata,
     )?;
     Ok(())
 }
 
-pub(crate) fn deregister_executor(
+/// Records the code artifacts cached by a registered executor and its
+/// outstanding task window. Returns whether the window opened, so tasks
+/// waiting for room can be allocated to it.
+pub(crate) fn update_executor_artifacts(
     db: Arc<TransactionDB>,
     txn: &Transaction<TransactionDB>,
-    req: &DeregisterExecutorRequest,
-) -> Result<()> {
-    let mut read_options = ReadOptions::default();
-    read_options.set_readahead_size(4_194_304);
-    let prefix = format!("{}|", req.executor_id);
-    let iterator_mode = IteratorMode::From(prefix.as_bytes(), Direction::Forward);
-    let iter = txn.iterator_cf_opt(
-        &IndexifyObjectsColumns::TaskAllocations.cf_db(&db),
-        read_options,
-        iterator_mode,
-    );
-    for key in iter {
-        let (key, _) = key?;
-        txn.delete_cf(&IndexifyObjectsColumns::TaskAllocations.cf_db(&db), &key)?;
+    req: &UpdateExecutorArtifactsRequest,
+) -> Result<bool> {
+    let executor = txn
+        .get_for_update_cf(
+            &IndexifyObjectsColumns::Executors.cf_db(&db),
+            req.executor_id.get(),
+            true,
+        )?
+        .ok_or(StateStoreError::not_found(format!(
+            "executor {}",
+            req.executor_id
+        )))?;
+    let mut executor: ExecutorMetadata = JsonEncoder::decode(&executor)?;
+    let window_opened = executor.max_outstanding_tasks != req.max_outstanding_tasks ||
+        (req.max_outstanding_tasks.is_some() && req.queue_depth < executor.queue_depth);
+    executor.cached_artifacts = req.cached_artifacts.clone();
+    executor.queue_depth = req.queue_depth;
+    executor.max_outstanding_tasks = req.max_outstanding_tasks;
+    executor.last_heartbeat_at = Some(get_epoch_time_in_ms());
+    txn.put_cf(
+        &IndexifyObjectsColumns::Executors.cf_db(&db),
+        executor.key(),
+        JsonEncoder::encode(&executor)?,
+    )?;
+    Ok(window_opened)
+}
+
+/// Replaces the labels of a registered executor and moves its allocated tasks
+/// whose placement constraints or executor pool don't match the new labels
+/// back to the unallocated tasks. Returns the ids of the moved tasks.
+pub(crate) fn update_executor_labels(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &UpdateExecutorLabelsRequest,
+) -> Result<Vec<TaskId>> {
+    let executors_cf = IndexifyObjectsColumns::Executors.cf_db(&db);
+    let executor = txn
+        .get_for_update_cf(&executors_cf, req.executor_id.get(), true)?
+        .ok_or(StateStoreError::not_found(format!(
+            "executor {}",
+            req.executor_id
+        )))?;
+    let mut executor: ExecutorMetadata = JsonEncoder::decode(&executor)?;
+    executor.labels = req.labels.clone();
+    txn.put_cf(
+        &executors_cf,
+        executor.key(),
+        JsonEncoder::encode(&executor)?,
+    )?;
+
+    let allocations_cf = IndexifyObjectsColumns::TaskAllocations.cf_db(&db);
+    let prefix = Task::allocation_key_prefix(&req.executor_id);
+    let mut displaced = vec![];
+    for kv in make_prefix_iterator(txn, &allocations_cf, &prefix, &None) {
+        let (key, _) = kv?;
         let task_key = Task::key_from_allocation_key(&key)?;
+        let Some(task) = txn.get_cf(&IndexifyObjectsColumns::Tasks.cf_db(&db), &task_key)? else {
+            continue;
+        };
+        let task: Task = JsonEncoder::decode(&task)?;
+        let graph = txn.get_cf(
+            &IndexifyObjectsColumns::ComputeGraphs.cf_db(&db),
+            format!("{}|{}", task.namespace, task.compute_graph_name),
+        )?;
+        let Some(graph) = graph else {
+            continue;
+        };
+        let graph: ComputeGraph = JsonEncoder::decode(&graph)?;
+        let Some(node) = graph.nodes.get(&task.compute_fn_name) else {
+            continue;
+        };
+        let mut matches = node.matches_executor(&executor);
+        if let Some(pool_name) = node.executor_pool() {
+            let pool = txn.get_cf(
+                &IndexifyObjectsColumns::ExecutorPools.cf_db(&db),
+                ExecutorPool::key_from(&task.namespace, pool_name),
+            )?;
+            if let Some(pool) = pool {
+                let pool: ExecutorPool = JsonEncoder::decode(&pool)?;
+                matches &= pool.matches_executor(&executor);
+            }
+        }
+        if !matches {
+            displaced.push((key, task_key, task.id));
+        }
+    }
+    let mut task_ids = vec![];
+    for (key, task_key, task_id) in displaced {
+        txn.delete_cf(&allocations_cf, &key)?;
         txn.put_cf(
             &IndexifyObjectsColumns::UnallocatedTasks.cf_db(&db),
             &task_key,
             &[],
         )?;
+        task_ids.push(task_id);
+    }
+    Ok(task_ids)
+}
+
+/// Moves all the tasks allocated to an executor back to the unallocated
+/// tasks, reading the allocations batch_size keys at a time. Returns the
+/// number of moved tasks.
+pub(crate) fn requeue_executor_tasks(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    executor_id: &ExecutorId,
+    batch_size: usize,
+) -> Result<usize> {
+    let batch_size = batch_size.max(1);
+    let prefix = Task::allocation_key_prefix(executor_id);
+    let mut num_tasks = 0;
+    loop {
+        // The iterator doesn't return the allocations deleted by previous
+        // batches of the transaction
+        let iter = make_prefix_iterator(
+            txn,
+            &IndexifyObjectsColumns::TaskAllocations.cf_db(&db),
+            &prefix,
+            &None,
+        );
+        let mut keys = Vec::with_capacity(batch_size);
+        for kv in iter {
+            let (key, _) = kv?;
+            keys.push(key);
+            if keys.len() == batch_size {
+                break;
+            }
+        }
+        for key in &keys {
+            requeue_allocation(db.clone(), txn, executor_id, key)?;
+        }
+        num_tasks += keys.len();
+        if keys.len() < batch_size {
+            return Ok(num_tasks);
+        }
+    }
+}
+
+/// Moves an allocated task back to the unallocated tasks.
+fn requeue_allocation(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    executor_id: &ExecutorId,
+    allocation_key: &[u8],
+) -> Result<()> {
+    txn.delete_cf(
+        &IndexifyObjectsColumns::TaskAllocations.cf_db(&db),
+        allocation_key,
+    )?;
+    let task_key = Task::key_from_allocation_key(allocation_key)?;
+    txn.put_cf(
+        &IndexifyObjectsColumns::UnallocatedTasks.cf_db(&db),
+        &task_key,
+        &[],
+    )?;
+    // Task keys are Ns_CG_<Invocation_Id>_Fn_TaskId
+    if let [namespace, compute_graph, invocation_id, compute_fn, task_id] =
+        &keys::decode_key(&task_key)?[..]
+    {
+        put_invocation_events(
+            db.clone(),
+            txn,
+            namespace,
+            compute_graph,
+            invocation_id,
+            [InvocationEventKind::TaskRequeued {
+                task_id: task_id.to_string(),
+                compute_fn: compute_fn.to_string(),
+                executor_id: executor_id.get().to_string(),
+            }],
+        )?;
+    }
+    Ok(())
+}
+
+/// Checks the tasks an executor reports it was running when it went down
+/// against the tasks allocated to it, and stores the report of the
+/// reconciliation. Every task allocated to the executor is moved back to the
+/// unallocated tasks, keeping the outputs it streamed and its checkpoint. The
+/// partial outputs of the reported tasks which were never committed are
+/// handed to the garbage collector.
+pub(crate) fn reconcile_executor_tasks(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &ReconcileExecutorTasksRequest,
+) -> Result<ExecutorReconciliation> {
+    let prefix = Task::allocation_key_prefix(&req.executor_id);
+    let mut allocations = HashMap::new();
+    let mut allocation_order = vec![];
+    for kv in make_prefix_iterator(
+        txn,
+        &IndexifyObjectsColumns::TaskAllocations.cf_db(&db),
+        &prefix,
+        &None,
+    ) {
+        let (key, _) = kv?;
+        let task_key = Task::key_from_allocation_key(&key)?;
+        allocation_order.push(task_key.clone());
+        allocations.insert(task_key, key.to_vec());
+    }
+    let read_task = |task_key: &[u8]| -> Result<Option<Task>> {
+        txn.get_for_update_cf(&IndexifyObjectsColumns::Tasks.cf_db(&db), task_key, true)?
+            .map(|task| JsonEncoder::decode::<Task>(&task))
+            .transpose()
+    };
+
+    let mut tasks = vec![];
+    for reported in &req.reported_tasks {
+        let task_key = reported.task_key();
+        let task = read_task(&task_key)?;
+        let allocation = allocations.remove(&task_key);
+        let outcome = match (&task, &allocation) {
+            (None, _) => ReconciliationOutcome::NotFound,
+            (Some(task), _) if task.terminal_state() => ReconciliationOutcome::Finished,
+            (Some(_), Some(_)) => ReconciliationOutcome::Retried,
+            (Some(_), None) => ReconciliationOutcome::LeaseLost,
+        };
+        if let Some(allocation) = allocation {
+            if outcome == ReconciliationOutcome::Retried {
+                requeue_allocation(db.clone(), txn, &req.executor_id, &allocation)?;
+            } else {
+                txn.delete_cf(
+                    &IndexifyObjectsColumns::TaskAllocations.cf_db(&db),
+                    &allocation,
+                )?;
+            }
+        }
+        let mut discarded_outputs = vec![];
+        for url in &reported.partial_outputs {
+            let pending = txn.get_for_update_cf(
+                &IndexifyObjectsColumns::PendingUploads.cf_db(&db),
+                url,
+                true,
+            )?;
+            let Some(pending) = pending else {
+                continue;
+            };
+            // Uploads of other tasks are left to their own outcome
+            if JsonEncoder::decode::<PendingUpload>(&pending)?.task_id != reported.task_id {
+                continue;
+            }
+            txn.delete_cf(&IndexifyObjectsColumns::PendingUploads.cf_db(&db), url)?;
+            txn.put_cf(
+                &IndexifyObjectsColumns::GcUrls.cf_db(&db),
+                url.as_bytes(),
+                &[],
+            )?;
+            discarded_outputs.push(url.clone());
+        }
+        let salvaged = task.filter(|task| !task.terminal_state());
+        tasks.push(ReconciledTask {
+            namespace: reported.namespace.clone(),
+            compute_graph: reported.compute_graph.clone(),
+            compute_fn: reported.compute_fn.clone(),
+            invocation_id: reported.invocation_id.clone(),
+            task_id: reported.task_id.clone(),
+            reported: true,
+            outcome,
+            salvaged_outputs: salvaged.as_ref().map_or(0, |task| task.streamed_outputs),
+            salvaged_checkpoint: salvaged.is_some_and(|task| task.checkpoint.is_some()),
+            discarded_outputs,
+        });
+    }
+
+    // Tasks the executor lost track of are retried too
+    for task_key in allocation_order {
+        let Some(allocation) = allocations.remove(&task_key) else {
+            continue;
+        };
+        requeue_allocation(db.clone(), txn, &req.executor_id, &allocation)?;
+        let Some(task) = read_task(&task_key)?.filter(|task| !task.terminal_state()) else {
+            continue;
+        };
+        tasks.push(ReconciledTask {
+            namespace: task.namespace.clone(),
+            compute_graph: task.compute_graph_name.clone(),
+            compute_fn: task.compute_fn_name.clone(),
+            invocation_id: task.invocation_id.clone(),
+            task_id: task.id.clone(),
+            reported: false,
+            outcome: ReconciliationOutcome::Retried,
+            salvaged_outputs: task.streamed_outputs,
+            salvaged_checkpoint: task.checkpoint.is_some(),
+            discarded_outputs: vec![],
+        });
     }
+
+    let reconciliation = ExecutorReconciliation {
+        executor_id: req.executor_id.clone(),
+        restarted_at: req.restarted_at,
+        tasks,
+    };
+    txn.put_cf(
+        &IndexifyObjectsColumns::ExecutorReconciliations.cf_db(&db),
+        reconciliation.key(),
+        JsonEncoder::encode(&reconciliation)?,
+    )?;
+    Ok(reconciliation)
+}
+
+pub(crate) fn deregister_executor(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &DeregisterExecutorRequest,
+) -> Result<()> {
+    requeue_executor_tasks(
+        db.clone(),
+        txn,
+        &req.executor_id,
+        DEFAULT_REQUEUE_BATCH_SIZE,
+    )?;
+    delete_cf_prefix(
+        txn,
+        &IndexifyObjectsColumns::PendingAborts.cf_db(&db),
+        AbortCommand::key_prefix(&req.executor_id).as_bytes(),
+    )?;
     txn.delete_cf(
         &IndexifyObjectsColumns::Executors.cf_db(&db),
         req.executor_id.to_string(),
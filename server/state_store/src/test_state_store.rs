@@ -48,6 +48,8 @@ pub mod tests {
             let cg_request = CreateComputeGraphRequest {
                 namespace: TEST_NAMESPACE.to_string(),
                 compute_graph: tests::mock_graph_a(),
+                expected_revision: None,
+                force: false,
             };
             self.indexify_state
                 .write(StateMachineUpdateRequest {
@@ -76,6 +78,8 @@ pub mod tests {
             let cg_request = CreateComputeGraphRequest {
                 namespace: TEST_NAMESPACE.to_string(),
                 compute_graph: tests::mock_graph_b(),
+                expected_revision: None,
+                force: false,
             };
             self.indexify_state
                 .write(StateMachineUpdateRequest {
@@ -105,6 +109,8 @@ pub mod tests {
             let cg_request = CreateComputeGraphRequest {
                 namespace: TEST_NAMESPACE.to_string(),
                 compute_graph: tests::mock_graph_with_reducer(),
+                expected_revision: None,
+                force: false,
             };
             self.indexify_state
                 .write(StateMachineUpdateRequest {
@@ -162,6 +168,8 @@ pub mod tests {
                 node_outputs,
                 executor_id: ExecutorId::new(TEST_EXECUTOR_ID.to_string()),
                 diagnostics: None,
+                inline_payloads: vec![],
+                resource_usage: None,
             };
 
             self.indexify_state
@@ -187,6 +195,8 @@ pub mod tests {
                 task_outcome: TaskOutcome::Success,
                 executor_id: ExecutorId::new(TEST_EXECUTOR_ID.to_string()),
                 diagnostics: None,
+                inline_payloads: vec![],
+                resource_usage: None,
             };
             self.indexify_state
                 .write(StateMachineUpdateRequest {
@@ -207,6 +217,8 @@ pub mod tests {
                 task_outcome: TaskOutcome::Success,
                 executor_id: ExecutorId::new(TEST_EXECUTOR_ID.to_string()),
                 diagnostics: None,
+                inline_payloads: vec![],
+                resource_usage: None,
             };
             self.indexify_state
                 .write(StateMachineUpdateRequest {
@@ -0,0 +1,111 @@
+use std::time::SystemTime;
+
+use data_model::Task;
+use serde::{Deserialize, Serialize};
+
+/// Raises the priority of queued tasks with the time they have been waiting,
+/// so tasks of low priority invocations are eventually allocated under a
+/// constant load of higher priority tasks.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PriorityAgingConfig {
+    /// Waiting time after which the priority of a task is raised by a step
+    pub interval_secs: u64,
+    #[serde(default)]
+    pub curve: AgingCurve,
+    /// Boost of the first step, the curve scales it
+    #[serde(default = "default_step")]
+    pub step: i32,
+}
+
+fn default_step() -> i32 {
+    1
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum AgingCurve {
+    /// The boost grows by a step per interval
+    #[default]
+    Linear,
+    /// The boost doubles every interval
+    Exponential,
+}
+
+// Exponential boosts saturate at this many doublings
+const MAX_DOUBLINGS: u64 = 32;
+
+impl PriorityAgingConfig {
+    /// Boost of a task queued for `waited_secs`. The boost grows without
+    /// bound so any task eventually outranks newer tasks of any priority.
+    pub fn boost(&self, waited_secs: u64) -> i32 {
+        let intervals = waited_secs / self.interval_secs.max(1);
+        match self.curve {
+            AgingCurve::Linear => self
+                .step
+                .saturating_mul(i32::try_from(intervals).unwrap_or(i32::MAX)),
+            AgingCurve::Exponential => {
+                let boost = (1_i64 << intervals.min(MAX_DOUBLINGS)) - 1;
+                i32::try_from(i64::from(self.step).saturating_mul(boost)).unwrap_or(i32::MAX)
+            }
+        }
+    }
+
+    /// Priority the task is allocated with at `now`.
+    pub fn effective_priority(&self, task: &Task, now: SystemTime) -> i32 {
+        let waited_secs = now
+            .duration_since(task.creation_time)
+            .map(|waited| waited.as_secs())
+            .unwrap_or(0);
+        task.priority.saturating_add(self.boost(waited_secs))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use data_model::test_objects::tests::{create_mock_task, mock_graph_a};
+
+    use super::*;
+
+    #[test]
+    fn test_boost() {
+        let linear = PriorityAgingConfig {
+            interval_secs: 60,
+            curve: AgingCurve::Linear,
+            step: 2,
+        };
+        assert_eq!(linear.boost(59), 0);
+        assert_eq!(linear.boost(60), 2);
+        assert_eq!(linear.boost(600), 20);
+
+        let exponential = PriorityAgingConfig {
+            curve: AgingCurve::Exponential,
+            step: 1,
+            ..linear
+        };
+        assert_eq!(exponential.boost(0), 0);
+        assert_eq!(exponential.boost(60), 1);
+        assert_eq!(exponential.boost(180), 7);
+        assert_eq!(exponential.boost(u64::MAX), i32::MAX);
+    }
+
+    #[test]
+    fn test_old_tasks_outrank_new_ones() {
+        let config = PriorityAgingConfig {
+            interval_secs: 60,
+            curve: AgingCurve::Linear,
+            step: 1,
+        };
+        let cg = mock_graph_a();
+        let now = SystemTime::now();
+        let mut old = create_mock_task(&cg, "fn_a", "key", "old");
+        old.priority = 0;
+        old.creation_time = now - Duration::from_secs(600);
+        let mut new = create_mock_task(&cg, "fn_a", "key", "new");
+        new.priority = 5;
+        new.creation_time = now;
+        assert_eq!(config.effective_priority(&old, now), 10);
+        assert_eq!(config.effective_priority(&new, now), 5);
+    }
+}
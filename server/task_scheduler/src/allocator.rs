@@ -0,0 +1,316 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use data_model::{filter::LabelsFilter, ExecutorId, ExecutorMetadata, Task};
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+
+/// Number of tasks allocated to each executor.
+pub type ExecutorLoad = HashMap<ExecutorId, usize>;
+
+/// Placement policy picking the executor a task is allocated to.
+pub trait TaskAllocator: Send + Sync {
+    /// Picks one of the candidates, which can all run the task.
+    fn allocate(
+        &self,
+        task: &Task,
+        candidates: &[&ExecutorMetadata],
+        load: &ExecutorLoad,
+    ) -> Option<ExecutorId>;
+
+    /// Whether `allocate` reads the load, which is only computed when needed.
+    fn needs_load(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[serde(tag = "strategy", rename_all = "snake_case")]
+pub enum AllocationStrategy {
+    #[default]
+    Random,
+    /// Fills the busiest executors first, leaving others idle
+    BinPacking,
+    /// Allocates to the least busy executors
+    Spread,
+    /// Prefers executors matching the affinity, others are used when none
+    /// of them can run the task
+    LabelAffinity { affinity: LabelsFilter },
+    /// Cycles through the executors of each compute fn
+    RoundRobin,
+}
+
+impl AllocationStrategy {
+    pub fn allocator(&self) -> Arc<dyn TaskAllocator> {
+        match self {
+            AllocationStrategy::Random => Arc::new(RandomAllocator),
+            AllocationStrategy::BinPacking => Arc::new(BinPackingAllocator),
+            AllocationStrategy::Spread => Arc::new(SpreadAllocator),
+            AllocationStrategy::LabelAffinity { affinity } => Arc::new(LabelAffinityAllocator {
+                affinity: affinity.clone(),
+            }),
+            AllocationStrategy::RoundRobin => Arc::new(RoundRobinAllocator::default()),
+        }
+    }
+}
+
+/// Strategies of the namespaces and compute graphs, graphs take precedence
+/// over their namespace.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct AllocationConfig {
+    #[serde(default)]
+    pub default: AllocationStrategy,
+    #[serde(default)]
+    pub namespaces: HashMap<String, AllocationStrategy>,
+    // Keyed by `<namespace>/<compute graph>`
+    #[serde(default)]
+    pub graphs: HashMap<String, AllocationStrategy>,
+}
+
+pub struct Allocators {
+    default: Arc<dyn TaskAllocator>,
+    namespaces: HashMap<String, Arc<dyn TaskAllocator>>,
+    graphs: HashMap<String, Arc<dyn TaskAllocator>>,
+}
+
+impl Allocators {
+    pub fn new(config: &AllocationConfig) -> Self {
+        let allocators = |strategies: &HashMap<String, AllocationStrategy>| {
+            strategies
+                .iter()
+                .map(|(name, strategy)| (name.clone(), strategy.allocator()))
+                .collect()
+        };
+        Self {
+            default: config.default.allocator(),
+            namespaces: allocators(&config.namespaces),
+            graphs: allocators(&config.graphs),
+        }
+    }
+
+    pub fn get(&self, namespace: &str, compute_graph: &str) -> &dyn TaskAllocator {
+        self.graphs
+            .get(&format!("{}/{}", namespace, compute_graph))
+            .or_else(|| self.namespaces.get(namespace))
+            .unwrap_or(&self.default)
+            .as_ref()
+    }
+}
+
+impl Default for Allocators {
+    fn default() -> Self {
+        Self::new(&AllocationConfig::default())
+    }
+}
+
+pub struct RandomAllocator;
+
+impl TaskAllocator for RandomAllocator {
+    fn allocate(
+        &self,
+        _task: &Task,
+        candidates: &[&ExecutorMetadata],
+        _load: &ExecutorLoad,
+    ) -> Option<ExecutorId> {
+        candidates
+            .choose(&mut rand::thread_rng())
+            .map(|executor| executor.id.clone())
+    }
+}
+
+fn load_of(load: &ExecutorLoad, executor: &ExecutorMetadata) -> usize {
+    load.get(&executor.id).copied().unwrap_or_default()
+}
+
+pub struct BinPackingAllocator;
+
+impl TaskAllocator for BinPackingAllocator {
+    fn allocate(
+        &self,
+        _task: &Task,
+        candidates: &[&ExecutorMetadata],
+        load: &ExecutorLoad,
+    ) -> Option<ExecutorId> {
+        // Ties go to the lowest id so the same executors keep being filled
+        candidates
+            .iter()
+            .max_by(|a, b| {
+                load_of(load, a)
+                    .cmp(&load_of(load, b))
+                    .then_with(|| b.id.cmp(&a.id))
+            })
+            .map(|executor| executor.id.clone())
+    }
+
+    fn needs_load(&self) -> bool {
+        true
+    }
+}
+
+pub struct SpreadAllocator;
+
+impl TaskAllocator for SpreadAllocator {
+    fn allocate(
+        &self,
+        _task: &Task,
+        candidates: &[&ExecutorMetadata],
+        load: &ExecutorLoad,
+    ) -> Option<ExecutorId> {
+        candidates
+            .iter()
+            .min_by(|a, b| {
+                load_of(load, a)
+                    .cmp(&load_of(load, b))
+                    .then_with(|| a.id.cmp(&b.id))
+            })
+            .map(|executor| executor.id.clone())
+    }
+
+    fn needs_load(&self) -> bool {
+        true
+    }
+}
+
+pub struct LabelAffinityAllocator {
+    affinity: LabelsFilter,
+}
+
+impl TaskAllocator for LabelAffinityAllocator {
+    fn allocate(
+        &self,
+        task: &Task,
+        candidates: &[&ExecutorMetadata],
+        load: &ExecutorLoad,
+    ) -> Option<ExecutorId> {
+        let preferred: Vec<&ExecutorMetadata> = candidates
+            .iter()
+            .filter(|executor| self.affinity.matches(&executor.labels))
+            .copied()
+            .collect();
+        if preferred.is_empty() {
+            RandomAllocator.allocate(task, candidates, load)
+        } else {
+            RandomAllocator.allocate(task, &preferred, load)
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct RoundRobinAllocator {
+    // Namespace, compute graph and fn -> number of allocated tasks
+    next: Mutex<HashMap<String, usize>>,
+}
+
+impl TaskAllocator for RoundRobinAllocator {
+    fn allocate(
+        &self,
+        task: &Task,
+        candidates: &[&ExecutorMetadata],
+        _load: &ExecutorLoad,
+    ) -> Option<ExecutorId> {
+        if candidates.is_empty() {
+            return None;
+        }
+        let mut candidates = candidates.to_vec();
+        candidates.sort_by(|a, b| a.id.cmp(&b.id));
+        let key = format!(
+            "{}|{}|{}",
+            task.namespace, task.compute_graph_name, task.compute_fn_name
+        );
+        let mut next = self.next.lock().unwrap();
+        let next = next.entry(key).or_default();
+        let executor = candidates[*next % candidates.len()];
+        *next += 1;
+        Some(executor.id.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use data_model::test_objects::tests::{create_mock_task, mock_graph_a};
+
+    use super::*;
+
+    fn executor(id: &str, zone: &str) -> ExecutorMetadata {
+        ExecutorMetadata {
+            id: ExecutorId::new(id.to_string()),
+            image_name: "image".to_string(),
+            addr: "".to_string(),
+            labels: HashMap::from([("zone".to_string(), serde_json::json!(zone))]),
+            cached_artifacts: HashSet::new(),
+            gpus: vec![],
+            protocol_version: 0,
+            features: Default::default(),
+            runtimes: Default::default(),
+            queue_depth: 0,
+            max_outstanding_tasks: None,
+            version: None,
+            last_heartbeat_at: None,
+        }
+    }
+
+    fn allocate(strategy: &AllocationStrategy, load: &ExecutorLoad, times: usize) -> Vec<String> {
+        let executors = [
+            executor("a", "us"),
+            executor("b", "eu"),
+            executor("c", "eu"),
+        ];
+        let candidates: Vec<&ExecutorMetadata> = executors.iter().collect();
+        let task = create_mock_task(&mock_graph_a(), "fn_a", "inv", "inv");
+        let allocator = strategy.allocator();
+        (0..times)
+            .map(|_| {
+                allocator
+                    .allocate(&task, &candidates, load)
+                    .unwrap()
+                    .to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_strategies() {
+        let load = HashMap::from([
+            (ExecutorId::new("a".to_string()), 1),
+            (ExecutorId::new("b".to_string()), 3),
+        ]);
+        assert_eq!(
+            allocate(&AllocationStrategy::BinPacking, &load, 1),
+            vec!["b"]
+        );
+        assert_eq!(allocate(&AllocationStrategy::Spread, &load, 1), vec!["c"]);
+        assert_eq!(
+            allocate(&AllocationStrategy::RoundRobin, &load, 4),
+            vec!["a", "b", "c", "a"]
+        );
+        let affinity = AllocationStrategy::LabelAffinity {
+            affinity: LabelsFilter::from_str("zone=eu").unwrap(),
+        };
+        for executor in allocate(&affinity, &load, 10) {
+            assert_ne!(executor, "a");
+        }
+        // Falls back to any candidate when none match
+        let affinity = AllocationStrategy::LabelAffinity {
+            affinity: LabelsFilter::from_str("zone=ap").unwrap(),
+        };
+        assert_eq!(allocate(&affinity, &load, 1).len(), 1);
+    }
+
+    #[test]
+    fn test_graph_strategy_overrides_namespace() {
+        let config: AllocationConfig = serde_json::from_value(serde_json::json!({
+            "default": {"strategy": "spread"},
+            "namespaces": {"ns": {"strategy": "bin_packing"}},
+            "graphs": {"ns/graph": {"strategy": "label_affinity", "affinity": ["zone=eu"]}},
+        }))
+        .unwrap();
+        let allocators = Allocators::new(&config);
+        assert!(!allocators.get("ns", "graph").needs_load());
+        assert!(allocators.get("ns", "other").needs_load());
+        assert!(allocators.get("other", "graph").needs_load());
+    }
+}
@@ -0,0 +1,108 @@
+use std::collections::{HashMap, HashSet};
+
+use data_model::ExecutorId;
+use serde::{Deserialize, Serialize};
+
+/// Excludes an executor from the placement of the tasks of a function after
+/// it failed them repeatedly while other executors ran them successfully.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BlacklistConfig {
+    /// Consecutive failures of a function on an executor after which the
+    /// executor is blacklisted for the function
+    #[serde(default = "default_max_failures")]
+    pub max_failures: u32,
+    /// How long the executor stays blacklisted
+    #[serde(default = "default_duration_secs")]
+    pub duration_secs: u64,
+}
+
+fn default_max_failures() -> u32 {
+    3
+}
+
+fn default_duration_secs() -> u64 {
+    600
+}
+
+/// Consecutive failures of the functions per executor, keyed by
+/// namespace|compute_graph|compute_fn.
+#[derive(Debug, Default)]
+pub struct FailureTracker {
+    failures: HashMap<(String, ExecutorId), u32>,
+    // Executors which ran a task of the function successfully
+    succeeded: HashMap<String, HashSet<ExecutorId>>,
+}
+
+impl FailureTracker {
+    /// Records the outcome of a task of the function on the executor, returns
+    /// the number of consecutive failures when the executor must be
+    /// blacklisted. Failures are only held against an executor once another
+    /// executor succeeded, so a function failing everywhere doesn't empty the
+    /// cluster.
+    pub fn record(
+        &mut self,
+        fn_key: &str,
+        executor_id: &ExecutorId,
+        success: bool,
+        config: &BlacklistConfig,
+    ) -> Option<u32> {
+        let key = (fn_key.to_string(), executor_id.clone());
+        if success {
+            self.failures.remove(&key);
+            self.succeeded
+                .entry(fn_key.to_string())
+                .or_default()
+                .insert(executor_id.clone());
+            return None;
+        }
+        let failures = self.failures.entry(key.clone()).or_default();
+        *failures += 1;
+        let failures = *failures;
+        if let Some(succeeded) = self.succeeded.get_mut(fn_key) {
+            succeeded.remove(executor_id);
+        }
+        let others_succeeded = self
+            .succeeded
+            .get(fn_key)
+            .is_some_and(|succeeded| !succeeded.is_empty());
+        if failures < config.max_failures || !others_succeeded {
+            return None;
+        }
+        self.failures.remove(&key);
+        Some(failures)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failure_tracker() {
+        let config = BlacklistConfig {
+            max_failures: 2,
+            duration_secs: 60,
+        };
+        let mut tracker = FailureTracker::default();
+        let bad = ExecutorId::new("bad".to_string());
+        let good = ExecutorId::new("good".to_string());
+
+        // Nobody succeeded yet, the function itself may be broken
+        assert_eq!(tracker.record("ns|cg|fn", &bad, false, &config), None);
+        assert_eq!(tracker.record("ns|cg|fn", &bad, false, &config), None);
+
+        assert_eq!(tracker.record("ns|cg|fn", &good, true, &config), None);
+        assert_eq!(tracker.record("ns|cg|fn", &bad, false, &config), Some(3));
+        // The count restarts once the executor is blacklisted
+        assert_eq!(tracker.record("ns|cg|fn", &bad, false, &config), None);
+
+        // A success resets the consecutive failures
+        assert_eq!(tracker.record("ns|cg|fn", &bad, true, &config), None);
+        assert_eq!(tracker.record("ns|cg|fn", &bad, false, &config), None);
+        assert_eq!(tracker.record("ns|cg|fn", &bad, false, &config), Some(2));
+
+        // Failures of other functions are tracked separately
+        assert_eq!(tracker.record("ns|cg|other", &bad, false, &config), None);
+        assert_eq!(tracker.record("ns|cg|other", &bad, false, &config), None);
+    }
+}
@@ -0,0 +1,53 @@
+use data_model::ExecutorId;
+
+/// Why a pending task isn't allocated, in the order the scheduler checks
+/// them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PendingReason {
+    /// The task is allocated or finished
+    NotPending,
+    /// Tasks are created but not allocated in maintenance mode
+    MaintenanceMode,
+    GraphPaused,
+    /// Tasks of WASM routers, gates and windows aren't allocated to executors
+    NotAllocatable,
+    /// A task of the function with the same concurrency key is allocated
+    ConcurrencyKey(String),
+    /// No executor matches the placement constraints of the function
+    NoMatchingExecutor,
+    /// The matching executors are blacklisted for the function
+    Blacklisted,
+    /// The matching executors don't have enough free GPUs
+    GpusBusy,
+    /// The matching executors are reserved for namespaces with pending tasks
+    ReservedForOtherNamespaces,
+    /// The matching executors have as many outstanding tasks as they accept
+    CapacityExhausted,
+    /// The matching executors only have room in the slots reserved for the
+    /// latency lane, the task is in the batch lane
+    ReservedForLatencyLane,
+    /// The rate limit of the function is exhausted
+    RateLimited,
+    /// Nothing holds the task back, it's allocated by the next scheduling
+    /// pass unless higher priority tasks take the executors first
+    Allocatable,
+}
+
+#[derive(Debug, Clone)]
+pub struct PendingTaskExplanation {
+    pub reason: PendingReason,
+    /// Executors the task can be allocated to once the reason clears
+    pub candidates: Vec<ExecutorId>,
+    /// Why executors were ruled out, and other conditions worth knowing
+    pub details: Vec<String>,
+}
+
+impl PendingTaskExplanation {
+    pub fn new(reason: PendingReason) -> Self {
+        Self {
+            reason,
+            candidates: vec![],
+            details: vec![],
+        }
+    }
+}
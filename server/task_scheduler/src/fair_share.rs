@@ -0,0 +1,242 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    time::Duration,
+};
+
+use data_model::Task;
+
+// Virtual time a task of a namespace with weight 1 occupies on the dispatch
+// queue of its executor.
+pub const QUANTUM: Duration = Duration::from_millis(10);
+
+/// Weighted fair queuing of tasks across namespaces and their compute graphs.
+///
+/// Tasks are dispatched to executors in order of a virtual time instead of
+/// their creation time. Every compute graph has a clock which advances by
+/// `QUANTUM / weight` for each allocated task, so a graph flooding the system
+/// only delays its own tasks. The weight of a namespace is split between its
+/// graphs which have tasks queued, so namespaces share the executors
+/// according to their weights regardless of their number of graphs.
+#[derive(Debug, Default)]
+pub struct FairShare {
+    // Namespace -> compute graph -> virtual time of the next task
+    clocks: HashMap<String, HashMap<String, u64>>,
+}
+
+impl FairShare {
+    /// Returns the virtual time to dispatch the next task of the graph at,
+    /// `now` is the current time in nanoseconds.
+    pub fn dispatch_time(
+        &mut self,
+        namespace: &str,
+        compute_graph: &str,
+        weight: u32,
+        now: u64,
+    ) -> u64 {
+        let graphs = self.clocks.entry(namespace.to_string()).or_default();
+        // Graphs whose clock is behind the current time have no tasks queued
+        graphs.retain(|graph, clock| *clock > now || graph == compute_graph);
+        let clock = graphs.get(compute_graph).copied().unwrap_or(now);
+        let queued_graphs = if graphs.contains_key(compute_graph) {
+            graphs.len()
+        } else {
+            graphs.len() + 1
+        };
+        let dispatch_time = clock.max(now);
+        let increment = QUANTUM.as_nanos() as u64 * queued_graphs as u64 / weight.max(1) as u64;
+        graphs.insert(compute_graph.to_string(), dispatch_time + increment);
+        dispatch_time
+    }
+}
+
+/// Round robin of the unallocated tasks across their compute graphs.
+///
+/// Unallocated tasks are listed graph by graph, so executors whose capacity
+/// is limited by their outstanding task window or their GPUs would be filled
+/// by the first graph with queued tasks. Interleaving the tasks lets the
+/// graphs sharing an executor take turns on its free slots. Graphs which
+/// were served least recently go first, so the graph getting the only free
+/// slot of an executor changes from one scheduling run to the next.
+#[derive(Debug, Default)]
+pub struct GraphRoundRobin {
+    // (Namespace, compute graph) -> sequence number of its last allocation
+    last_served: HashMap<(String, String), u64>,
+    seq: u64,
+}
+
+impl GraphRoundRobin {
+    /// Interleaves tasks of the same priority across their graphs. Tasks must
+    /// be sorted by priority, the order of the tasks of a graph is kept.
+    pub fn interleave(&self, tasks: Vec<Task>) -> Vec<Task> {
+        self.interleave_by(tasks, |task| task.priority)
+    }
+
+    /// Same as `interleave`, with the key tasks are sorted by. Tasks with the
+    /// same key are interleaved together.
+    pub fn interleave_by<K: PartialEq>(
+        &self,
+        tasks: Vec<Task>,
+        key_of: impl Fn(&Task) -> K,
+    ) -> Vec<Task> {
+        let mut interleaved = Vec::with_capacity(tasks.len());
+        let mut tasks = tasks.into_iter().peekable();
+        while let Some(first) = tasks.next() {
+            let key = key_of(&first);
+            let mut band = vec![first];
+            while let Some(task) = tasks.next_if(|task| key_of(task) == key) {
+                band.push(task);
+            }
+            interleaved.extend(self.interleave_band(band));
+        }
+        interleaved
+    }
+
+    fn interleave_band(&self, band: Vec<Task>) -> Vec<Task> {
+        let len = band.len();
+        let mut queues: Vec<((String, String), VecDeque<Task>)> = Vec::new();
+        for task in band {
+            let graph = (task.namespace.clone(), task.compute_graph_name.clone());
+            match queues.iter_mut().find(|(key, _)| *key == graph) {
+                Some((_, queue)) => queue.push_back(task),
+                None => queues.push((graph, VecDeque::from([task]))),
+            }
+        }
+        if queues.len() == 1 {
+            return queues
+                .pop()
+                .map(|(_, queue)| queue.into())
+                .unwrap_or_default();
+        }
+        // Stable, graphs never served keep their order ahead of the others
+        queues.sort_by_key(|(graph, _)| self.last_served.get(graph).copied().unwrap_or(0));
+        let mut interleaved = Vec::with_capacity(len);
+        while interleaved.len() < len {
+            for (_, queue) in queues.iter_mut() {
+                interleaved.extend(queue.pop_front());
+            }
+        }
+        interleaved
+    }
+
+    /// Records the allocation of a task of the graph.
+    pub fn served(&mut self, namespace: &str, compute_graph: &str) {
+        self.seq += 1;
+        self.last_served
+            .insert((namespace.to_string(), compute_graph.to_string()), self.seq);
+    }
+
+    /// Forgets the graphs which have no unallocated tasks left.
+    pub fn retain(&mut self, tasks: &[Task]) {
+        let graphs: HashSet<(&str, &str)> = tasks
+            .iter()
+            .map(|task| (task.namespace.as_str(), task.compute_graph_name.as_str()))
+            .collect();
+        self.last_served.retain(|(namespace, compute_graph), _| {
+            graphs.contains(&(namespace.as_str(), compute_graph.as_str()))
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const Q: u64 = QUANTUM.as_nanos() as u64;
+
+    #[test]
+    fn test_flooding_namespace_does_not_starve_others() {
+        let mut fair_share = FairShare::default();
+        let now = 1_000 * Q;
+        let flood: Vec<u64> = (0..100)
+            .map(|_| fair_share.dispatch_time("ns_a", "graph", 1, now))
+            .collect();
+        // A task of another namespace is dispatched right away
+        let other = fair_share.dispatch_time("ns_b", "graph", 1, now + 1);
+        assert_eq!(other, now + 1);
+        assert_eq!(flood.iter().filter(|time| **time < other).count(), 1);
+    }
+
+    #[test]
+    fn test_weights() {
+        let mut fair_share = FairShare::default();
+        let now = 1_000 * Q;
+        let heavy: Vec<u64> = (0..10)
+            .map(|_| fair_share.dispatch_time("heavy", "graph", 2, now))
+            .collect();
+        let light: Vec<u64> = (0..10)
+            .map(|_| fair_share.dispatch_time("light", "graph", 1, now))
+            .collect();
+        let deadline = now + 4 * Q;
+        assert_eq!(heavy.iter().filter(|time| **time < deadline).count(), 8);
+        assert_eq!(light.iter().filter(|time| **time < deadline).count(), 4);
+    }
+
+    fn task(compute_graph: &str, priority: i32) -> Task {
+        let mut task = data_model::TaskBuilder::default()
+            .namespace("ns".to_string())
+            .compute_graph_name(compute_graph.to_string())
+            .compute_fn_name("fn".to_string())
+            .input_node_output_key("key".to_string())
+            .invocation_id("invocation".to_string())
+            .reducer_output_id(None)
+            .graph_version(Default::default())
+            .build()
+            .unwrap();
+        task.priority = priority;
+        task
+    }
+
+    fn graphs(tasks: &[Task]) -> Vec<&str> {
+        tasks
+            .iter()
+            .map(|task| task.compute_graph_name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_round_robin_across_graphs() {
+        let mut round_robin = GraphRoundRobin::default();
+        let tasks = vec![
+            task("noisy", 1),
+            task("noisy", 0),
+            task("noisy", 0),
+            task("noisy", 0),
+            task("quiet", 0),
+            task("other", 0),
+        ];
+        let interleaved = round_robin.interleave(tasks.clone());
+        assert_eq!(
+            graphs(&interleaved),
+            vec!["noisy", "noisy", "quiet", "other", "noisy", "noisy"]
+        );
+        // The order of the tasks of a graph is kept
+        assert_eq!(interleaved[1].id, tasks[1].id);
+        assert_eq!(interleaved[4].id, tasks[2].id);
+
+        // Graphs served least recently go first
+        round_robin.served("ns", "noisy");
+        round_robin.served("ns", "quiet");
+        let interleaved = round_robin.interleave(tasks[1..].to_vec());
+        assert_eq!(
+            graphs(&interleaved),
+            vec!["other", "noisy", "quiet", "noisy", "noisy"]
+        );
+
+        round_robin.retain(&tasks[4..5]);
+        assert_eq!(round_robin.last_served.len(), 1);
+    }
+
+    #[test]
+    fn test_graphs_share_the_namespace_weight() {
+        let mut fair_share = FairShare::default();
+        let now = 1_000 * Q;
+        for _ in 0..10 {
+            fair_share.dispatch_time("ns_a", "graph_1", 1, now);
+        }
+        // The second graph of the namespace queues behind its own clock
+        let first = fair_share.dispatch_time("ns_a", "graph_2", 1, now);
+        let second = fair_share.dispatch_time("ns_a", "graph_2", 1, now);
+        assert_eq!(first, now);
+        assert_eq!(second, now + 2 * Q);
+    }
+}
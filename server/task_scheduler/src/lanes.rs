@@ -0,0 +1,63 @@
+use data_model::{ExecutorMetadata, SchedulingLane, Task};
+use serde::{Deserialize, Serialize};
+
+/// Keeps slots of the outstanding task window of the executors for the tasks
+/// of latency lane invocations, so replays and backfills running in the batch
+/// lane never fill the executors ahead of fresh invocations.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LaneConfig {
+    /// Slots of each executor only latency lane tasks are allocated to.
+    /// Executors which accept any number of outstanding tasks have no slots
+    /// to reserve, those with a window no larger run no batch lane task.
+    pub reserved_latency_slots: u32,
+}
+
+impl LaneConfig {
+    /// Whether the task can be allocated to the executor, given the number of
+    /// tasks allocated to it.
+    pub fn admits(&self, task: &Task, executor: &ExecutorMetadata, allocated: usize) -> bool {
+        match task.lane {
+            SchedulingLane::Latency => executor.has_room(allocated),
+            SchedulingLane::Batch => executor
+                .free_capacity(allocated)
+                .map_or(true, |free| free > self.reserved_latency_slots as u64),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use data_model::test_objects::tests::{create_mock_task, mock_graph_a};
+
+    use super::*;
+
+    #[test]
+    fn test_reserved_latency_slots() {
+        let config = LaneConfig {
+            reserved_latency_slots: 2,
+        };
+        let executor = ExecutorMetadata {
+            max_outstanding_tasks: Some(4),
+            ..Default::default()
+        };
+        let latency = create_mock_task(&mock_graph_a(), "fn_a", "key", "inv");
+        let batch = Task {
+            lane: SchedulingLane::Batch,
+            ..latency.clone()
+        };
+        assert!(config.admits(&batch, &executor, 1));
+        // The remaining slots are left to latency lane tasks
+        assert!(!config.admits(&batch, &executor, 2));
+        assert!(config.admits(&latency, &executor, 3));
+        assert!(!config.admits(&latency, &executor, 4));
+        // The queue depth reported by the executor counts too
+        let busy = ExecutorMetadata {
+            queue_depth: 3,
+            ..executor
+        };
+        assert!(!config.admits(&batch, &busy, 0));
+
+        let unlimited = ExecutorMetadata::default();
+        assert!(config.admits(&batch, &unlimited, 100));
+    }
+}
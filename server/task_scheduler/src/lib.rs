@@ -1,11 +1,46 @@
-use std::sync::Arc;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
+use aging::PriorityAgingConfig;
+use allocator::{AllocationConfig, Allocators, ExecutorLoad};
 use anyhow::{anyhow, Result};
-use data_model::{ExecutorId, Node, ReduceTask, RuntimeInformation, Task};
-use rand::seq::SliceRandom;
-use state_store::{requests::TaskPlacement, IndexifyState};
+use blacklist::{BlacklistConfig, FailureTracker};
+use data_model::{
+    blacklist::ExecutorBlacklistEntry,
+    default_scheduling_weight,
+    ExecutorId,
+    ExecutorMetadata,
+    FanOutCursor,
+    GpuRequirement,
+    Node,
+    RateLimit,
+    ReduceTask,
+    RuntimeInformation,
+    SchedulingLane,
+    Task,
+    TaskOutcome,
+};
+use explain::{PendingReason, PendingTaskExplanation};
+use fair_share::{FairShare, GraphRoundRobin};
+use lanes::LaneConfig;
+use preemption::{plan_preemption, Preemption, PreemptionConfig};
+use rate_limiter::{RateLimiters, TokenBucket};
+use reservations::Reservations;
+use state_store::{requests::TaskPlacement, state_machine::IndexifyObjectsColumns, IndexifyState};
 use tracing::{error, info};
 
+pub mod aging;
+pub mod allocator;
+pub mod blacklist;
+pub mod explain;
+pub mod fair_share;
+pub mod lanes;
+pub mod preemption;
+pub mod rate_limiter;
+pub mod reservations;
 pub mod task_creator;
 
 #[derive(Debug)]
@@ -14,28 +49,164 @@ pub struct TaskCreationResult {
     pub compute_graph: String,
     pub tasks: Vec<Task>,
     pub new_reduction_tasks: Vec<ReduceTask>,
-    pub processed_reduction_tasks: Vec<String>,
+    pub processed_reduction_tasks: Vec<Vec<u8>>,
     pub invocation_finished: bool,
     pub invocation_id: String,
+    pub fan_out_cursor: Option<FanOutCursor>,
 }
 
 pub struct FilteredExecutors {
     pub executors: Vec<ExecutorId>,
+    // Subset of executors which already cached the code of the graph
+    pub code_cached: Vec<ExecutorId>,
+    // GPUs which would be reserved for the task on each executor
+    pub gpu_ids: HashMap<ExecutorId, Vec<String>>,
+    // Executors which could run the task if they had enough free GPUs
+    pub gpu_busy: Vec<ExecutorId>,
     pub diagnostic_msgs: Vec<String>,
 }
 
 pub struct TaskPlacementResult {
     pub task_placements: Vec<TaskPlacement>,
+    // Allocations revoked to make room for higher priority tasks
+    pub preemptions: Vec<TaskPlacement>,
     pub diagnostic_msgs: Vec<String>,
 }
 
 pub struct TaskScheduler {
     indexify_state: Arc<IndexifyState>,
+    rate_limiters: Mutex<RateLimiters>,
+    // Applied to compute functions which don't declare a rate limit
+    default_rate_limit: Mutex<Option<RateLimit>>,
+    fair_share: Mutex<FairShare>,
+    // Interleaves the tasks of the graphs sharing executors
+    graph_round_robin: Mutex<GraphRoundRobin>,
+    allocators: Mutex<Arc<Allocators>>,
+    // Disabled when None, the bucket enforces the pre-emption budget
+    preemption: Mutex<Option<(PreemptionConfig, TokenBucket)>>,
+    // Disabled when None
+    blacklist: Mutex<Option<(BlacklistConfig, FailureTracker)>>,
+    // Disabled when None
+    priority_aging: Mutex<Option<PriorityAgingConfig>>,
+    // No slot is reserved for the latency lane when None
+    lanes: Mutex<Option<LaneConfig>>,
 }
 
 impl TaskScheduler {
     pub fn new(indexify_state: Arc<IndexifyState>) -> Self {
-        Self { indexify_state }
+        Self {
+            indexify_state,
+            rate_limiters: Mutex::new(RateLimiters::default()),
+            default_rate_limit: Mutex::new(None),
+            fair_share: Mutex::new(FairShare::default()),
+            graph_round_robin: Mutex::new(GraphRoundRobin::default()),
+            allocators: Mutex::new(Arc::new(Allocators::default())),
+            preemption: Mutex::new(None),
+            blacklist: Mutex::new(None),
+            priority_aging: Mutex::new(None),
+            lanes: Mutex::new(None),
+        }
+    }
+
+    pub fn set_blacklist_config(&self, config: Option<&BlacklistConfig>) {
+        let mut blacklist = self.blacklist.lock().unwrap();
+        // Keeps the tracked failures when the config didn't change
+        if blacklist.as_ref().map(|(current, _)| current) == config {
+            return;
+        }
+        *blacklist = config.map(|config| (config.clone(), FailureTracker::default()));
+    }
+
+    /// Tracks the outcome of a finished task, returns the entry to persist
+    /// when the executor which ran it must be blacklisted for its function.
+    pub fn record_task_outcome(
+        &self,
+        task: &Task,
+        executor_id: &ExecutorId,
+    ) -> Result<Option<ExecutorBlacklistEntry>> {
+        let success = match task.outcome {
+            TaskOutcome::Success => true,
+            TaskOutcome::Failure => false,
+            TaskOutcome::Unknown => return Ok(None),
+        };
+        let mut blacklist = self.blacklist.lock().unwrap();
+        let Some((config, tracker)) = blacklist.as_mut() else {
+            return Ok(None);
+        };
+        let fn_key = ExecutorBlacklistEntry::fn_prefix(
+            &task.namespace,
+            &task.compute_graph_name,
+            &task.compute_fn_name,
+        );
+        let Some(failures) = tracker.record(&fn_key, executor_id, success, config) else {
+            return Ok(None);
+        };
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64;
+        info!(
+            "blacklisting executor {} for fn {} after {} failures",
+            executor_id, task.compute_fn_name, failures
+        );
+        Ok(Some(ExecutorBlacklistEntry {
+            namespace: task.namespace.clone(),
+            compute_graph: task.compute_graph_name.clone(),
+            compute_fn: task.compute_fn_name.clone(),
+            executor_id: executor_id.clone(),
+            failures,
+            blacklisted_at: now,
+            expires_at: now + config.duration_secs * 1000,
+        }))
+    }
+
+    pub fn set_preemption_config(&self, config: Option<&PreemptionConfig>) {
+        let mut preemption = self.preemption.lock().unwrap();
+        // Keeps the spent budget when the config didn't change
+        if preemption.as_ref().map(|(current, _)| current) == config {
+            return;
+        }
+        *preemption = config.map(|config| {
+            (
+                config.clone(),
+                TokenBucket::new(&config.budget, Instant::now()),
+            )
+        });
+    }
+
+    pub fn set_priority_aging_config(&self, config: Option<&PriorityAgingConfig>) {
+        *self.priority_aging.lock().unwrap() = config.cloned();
+    }
+
+    pub fn set_lane_config(&self, config: Option<&LaneConfig>) {
+        *self.lanes.lock().unwrap() = config.cloned();
+    }
+
+    /// Whether the task can be allocated to the executor, given the number of
+    /// tasks allocated to it.
+    fn has_room(
+        lanes: Option<&LaneConfig>,
+        task: &Task,
+        executor: &ExecutorMetadata,
+        allocated: usize,
+    ) -> bool {
+        match lanes {
+            Some(lanes) => lanes.admits(task, executor, allocated),
+            None => executor.has_room(allocated),
+        }
+    }
+
+    pub fn set_allocation_config(&self, config: &AllocationConfig) {
+        *self.allocators.lock().unwrap() = Arc::new(Allocators::new(config));
+    }
+
+    pub fn set_default_rate_limit(&self, rate_limit: Option<RateLimit>) {
+        *self.default_rate_limit.lock().unwrap() = rate_limit;
+    }
+
+    /// Time after which tasks held back by a rate limit can be allocated.
+    pub fn rate_limit_retry_after(&self) -> Option<Duration> {
+        self.rate_limiters
+            .lock()
+            .unwrap()
+            .next_available(Instant::now())
     }
 
     pub fn schedule_unplaced_tasks(&self) -> Result<TaskPlacementResult> {
@@ -43,49 +214,647 @@ impl TaskScheduler {
         self.schedule_tasks(tasks)
     }
 
-    fn schedule_tasks(&self, tasks: Vec<Task>) -> Result<TaskPlacementResult> {
+    fn schedule_tasks(&self, mut tasks: Vec<Task>) -> Result<TaskPlacementResult> {
+        // Higher priority tasks are allocated first, then latency lane tasks
+        // ahead of batch lane tasks. The sort is stable so tasks of the same
+        // priority and lane keep their order. Tasks which waited long enough
+        // are boosted ahead of newer higher priority tasks.
+        let priority_aging = self.priority_aging.lock().unwrap().clone();
+        let scheduled_at = SystemTime::now();
+        let priority_of = |task: &Task| match &priority_aging {
+            Some(priority_aging) => priority_aging.effective_priority(task, scheduled_at),
+            None => task.priority,
+        };
+        let order_of = |task: &Task| (std::cmp::Reverse(priority_of(task)), task.lane);
+        tasks.sort_by_key(order_of);
+        // Graphs take turns on the capacity of the executors they share
+        let mut graph_round_robin = self.graph_round_robin.lock().unwrap();
+        graph_round_robin.retain(&tasks);
+        let tasks = graph_round_robin.interleave_by(tasks, order_of);
         let mut task_allocations = Vec::new();
+        let mut preemptions = Vec::new();
         let mut diagnostic_msgs = Vec::new();
-        for task in tasks {
+        let mut scheduling_weights: HashMap<String, u32> = HashMap::new();
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos() as u64;
+        // Loaded when the first task which needs a GPU is scheduled
+        let mut gpus_in_use: Option<HashMap<ExecutorId, HashSet<String>>> = None;
+        // Loaded when the first task whose allocator needs it is scheduled,
+        // or upfront when executors limit their outstanding tasks
+        let mut load: Option<ExecutorLoad> = None;
+        let no_load = ExecutorLoad::new();
+        // Tasks allocated to the executors considered for pre-emption
+        let mut allocated: HashMap<ExecutorId, Vec<Task>> = HashMap::new();
+        // Loaded when the first task with a concurrency key is scheduled
+        let mut in_flight_keys: Option<HashSet<String>> = None;
+        // Whether the graphs of the tasks are paused, keyed by graph
+        let mut paused_graphs: HashMap<(String, String), bool> = HashMap::new();
+        // Executors blacklisted for the functions of the tasks, keyed by
+        // function
+        let mut blacklists: HashMap<String, HashSet<ExecutorId>> = HashMap::new();
+        let now_ms = now / 1_000_000;
+        let allocators = self.allocators.lock().unwrap().clone();
+        let lanes = self.lanes.lock().unwrap().clone();
+        let executors: HashMap<ExecutorId, ExecutorMetadata> = self
+            .indexify_state
+            .reader()
+            .get_all_executors()?
+            .into_iter()
+            .map(|executor| (executor.id.clone(), executor))
+            .collect();
+        if executors
+            .values()
+            .any(|executor| executor.max_outstanding_tasks.is_some())
+        {
+            load = Some(self.indexify_state.reader().allocated_task_counts()?);
+        }
+        let reservations = Reservations::new(
+            &self.indexify_state.reader().get_all_namespaces()?,
+            executors.values(),
+            tasks.iter().map(|task| task.namespace.as_str()),
+        );
+        let mut reported_shortfalls = HashSet::new();
+        for mut task in tasks {
+            let graph_key = (task.namespace.clone(), task.compute_graph_name.clone());
+            let paused = match paused_graphs.get(&graph_key) {
+                Some(paused) => *paused,
+                None => {
+                    let paused = self
+                        .indexify_state
+                        .reader()
+                        .is_compute_graph_paused(&task.namespace, &task.compute_graph_name)?;
+                    paused_graphs.insert(graph_key, paused);
+                    paused
+                }
+            };
+            if paused {
+                continue;
+            }
             let cg = self
                 .indexify_state
                 .reader()
-                .get_compute_graph(&task.namespace, &task.compute_graph_name)?
+                .get_compute_graph_version(
+                    &task.namespace,
+                    &task.compute_graph_name,
+                    &task.graph_version,
+                )?
                 .ok_or(anyhow!("compute graph not found"))?;
             let compute_fn = cg
                 .nodes
                 .get(&task.compute_fn_name)
                 .ok_or(anyhow!("compute fn not found"))?;
-            let filtered_executors = self.filter_executors(&compute_fn, &cg.runtime_information)?;
+            // Tasks of WASM routers are run by the server, tasks of gates wait
+            // for a signal and tasks of windows for the window to close
+            if compute_fn.wasm_module().is_some() ||
+                compute_fn.is_gate() ||
+                compute_fn.window().is_some()
+            {
+                continue;
+            }
+            let concurrency_key = task.scoped_concurrency_key();
+            if let Some(key) = &concurrency_key {
+                if in_flight_keys.is_none() {
+                    in_flight_keys = Some(self.in_flight_concurrency_keys(executors.keys())?);
+                }
+                if in_flight_keys
+                    .as_ref()
+                    .is_some_and(|keys| keys.contains(key))
+                {
+                    diagnostic_msgs.push(format!(
+                        "task {} of fn {} waits for a task with concurrency key {}",
+                        task.id,
+                        task.compute_fn_name,
+                        task.concurrency_key.as_deref().unwrap_or_default()
+                    ));
+                    continue;
+                }
+            }
+            if compute_fn.gpu().is_some() && gpus_in_use.is_none() {
+                gpus_in_use = Some(self.gpus_in_use()?);
+            }
+            let fn_key = ExecutorBlacklistEntry::fn_prefix(
+                &task.namespace,
+                &task.compute_graph_name,
+                &task.compute_fn_name,
+            );
+            if !blacklists.contains_key(&fn_key) {
+                let blacklisted = self.indexify_state.reader().blacklisted_executors(
+                    &task.namespace,
+                    &task.compute_graph_name,
+                    &task.compute_fn_name,
+                    now_ms,
+                )?;
+                blacklists.insert(fn_key.clone(), blacklisted);
+            }
+            let mut filtered_executors = self.filter_executors(
+                &task.namespace,
+                &compute_fn,
+                &cg.runtime_information,
+                &cg.code.sha256_hash,
+                gpus_in_use.as_ref(),
+                &blacklists[&fn_key],
+            )?;
             if !filtered_executors.diagnostic_msgs.is_empty() {
                 diagnostic_msgs.extend(filtered_executors.diagnostic_msgs);
             }
-            let executor_id = filtered_executors.executors.choose(&mut rand::thread_rng());
+            let allocator = allocators.get(&task.namespace, &task.compute_graph_name);
+            if allocator.needs_load() && load.is_none() {
+                load = Some(self.indexify_state.reader().allocated_task_counts()?);
+            }
+            let shortfall = reservations.shortfall(&task.namespace);
+            if shortfall > 0 && reported_shortfalls.insert(task.namespace.clone()) {
+                diagnostic_msgs.push(format!(
+                    "namespace {} is missing {} executors of its reserved capacity",
+                    task.namespace, shortfall
+                ));
+            }
+            // Executors reserved for other namespaces with pending tasks are
+            // left to them
+            let eligible = filtered_executors.executors.len();
+            filtered_executors
+                .executors
+                .retain(|id| reservations.allows(&task.namespace, id));
+            if eligible > 0 && filtered_executors.executors.is_empty() {
+                diagnostic_msgs.push(format!(
+                    "task {} of fn {} waits for executors reserved for other namespaces",
+                    task.id, task.compute_fn_name
+                ));
+            }
+            filtered_executors
+                .code_cached
+                .retain(|id| reservations.allows(&task.namespace, id));
+            filtered_executors
+                .gpu_busy
+                .retain(|id| reservations.allows(&task.namespace, id));
+            let executor_load = load.as_ref().unwrap_or(&no_load);
+            // Executors with room in their outstanding task window, outside
+            // of the slots reserved for the latency lane for batch lane tasks
+            let with_room = |ids: &[ExecutorId]| -> Vec<&ExecutorMetadata> {
+                ids.iter()
+                    .filter_map(|id| executors.get(id))
+                    .filter(|executor| {
+                        let allocated = executor_load.get(&executor.id).copied().unwrap_or(0);
+                        Self::has_room(lanes.as_ref(), &task, executor, allocated)
+                    })
+                    .collect()
+            };
+            // Prefer executors which don't have to fetch the code
+            let mut candidates = with_room(&filtered_executors.code_cached);
+            if candidates.is_empty() {
+                candidates = with_room(&filtered_executors.executors);
+            }
+            // Executors reserved for the namespace come first, its tasks
+            // spill over to the shared executors when they have no room
+            let reserved: Vec<&ExecutorMetadata> = with_room(&filtered_executors.executors)
+                .into_iter()
+                .filter(|executor| {
+                    reservations.owner(&executor.id) == Some(task.namespace.as_str())
+                })
+                .collect();
+            if !reserved.is_empty() {
+                candidates = reserved;
+            }
+            if candidates.is_empty() && !filtered_executors.executors.is_empty() {
+                diagnostic_msgs.push(match (&lanes, task.lane) {
+                    (Some(_), SchedulingLane::Batch) => format!(
+                        "task {} of fn {} waits for executor slots not reserved for the \
+                         latency lane",
+                        task.id, task.compute_fn_name
+                    ),
+                    _ => format!(
+                        "task {} of fn {} waits for executors to finish their outstanding tasks",
+                        task.id, task.compute_fn_name
+                    ),
+                });
+            }
+            let mut executor_id = allocator.allocate(&task, &candidates, executor_load);
+            let mut preemption = None;
+            match compute_fn.gpu() {
+                Some(gpu) if executor_id.is_none() => {
+                    preemption = self.preempt(
+                        &task,
+                        gpu,
+                        &filtered_executors.gpu_busy,
+                        &executors,
+                        gpus_in_use.as_ref(),
+                        &mut allocated,
+                        &mut diagnostic_msgs,
+                    )?;
+                    executor_id = preemption.as_ref().map(|p| p.executor.clone());
+                }
+                _ => {}
+            }
+            if executor_id.is_some() && !self.acquire_rate_limit(&task, compute_fn) {
+                info!("task {:?} held back by rate limit", task.id);
+                diagnostic_msgs.push(format!(
+                    "task {} of fn {} is rate limited",
+                    task.id, task.compute_fn_name
+                ));
+                continue;
+            }
             if let Some(executor_id) = executor_id {
+                let weight = match scheduling_weights.get(&task.namespace) {
+                    Some(weight) => *weight,
+                    None => {
+                        let weight = self
+                            .indexify_state
+                            .reader()
+                            .get_namespace(&task.namespace)?
+                            .map_or(default_scheduling_weight(), |ns| ns.scheduling_weight);
+                        scheduling_weights.insert(task.namespace.clone(), weight);
+                        weight
+                    }
+                };
+                if let Some(preemption) = preemption {
+                    if let Some(gpus_in_use) = gpus_in_use.as_mut() {
+                        let in_use = gpus_in_use.entry(executor_id.clone()).or_default();
+                        for victim in &preemption.victims {
+                            for gpu_id in &victim.gpu_ids {
+                                in_use.remove(gpu_id);
+                            }
+                        }
+                    }
+                    if let Some(executor_tasks) = allocated.get_mut(&executor_id) {
+                        executor_tasks.retain(|allocated_task| {
+                            !preemption
+                                .victims
+                                .iter()
+                                .any(|victim| victim.id == allocated_task.id)
+                        });
+                    }
+                    filtered_executors
+                        .gpu_ids
+                        .insert(executor_id.clone(), preemption.gpu_ids);
+                    info!(
+                        "task {:?} pre-empts {} tasks of executor {:?}",
+                        task.id,
+                        preemption.victims.len(),
+                        executor_id
+                    );
+                    preemptions.extend(preemption.victims.into_iter().map(|victim| {
+                        TaskPlacement {
+                            task: victim,
+                            executor: executor_id.clone(),
+                        }
+                    }));
+                }
+                task.dispatch_time = Some(self.fair_share.lock().unwrap().dispatch_time(
+                    &task.namespace,
+                    &task.compute_graph_name,
+                    weight,
+                    now,
+                ));
+                if let Some(gpu_ids) = filtered_executors.gpu_ids.get(&executor_id) {
+                    task.gpu_ids = gpu_ids.clone();
+                    if let Some(gpus_in_use) = gpus_in_use.as_mut() {
+                        gpus_in_use
+                            .entry(executor_id.clone())
+                            .or_default()
+                            .extend(gpu_ids.iter().cloned());
+                    }
+                }
+                if let Some(load) = load.as_mut() {
+                    *load.entry(executor_id.clone()).or_default() += 1;
+                }
+                if let (Some(key), Some(in_flight_keys)) =
+                    (concurrency_key, in_flight_keys.as_mut())
+                {
+                    in_flight_keys.insert(key);
+                }
+                graph_round_robin.served(&task.namespace, &task.compute_graph_name);
                 info!("assigning task {:?} to executor {:?}", task.id, executor_id);
                 task_allocations.push(TaskPlacement {
                     task,
-                    executor: executor_id.clone(),
+                    executor: executor_id,
                 });
             }
         }
         Ok(TaskPlacementResult {
             task_placements: task_allocations,
+            preemptions,
             diagnostic_msgs,
         })
     }
 
+    /// Explains why the task with the key isn't allocated, running the checks
+    /// of the scheduler without allocating it. None when the task doesn't
+    /// exist.
+    pub fn explain_pending_task(&self, task_key: &[u8]) -> Result<Option<PendingTaskExplanation>> {
+        let reader = self.indexify_state.reader();
+        let Some(task) = reader.get_from_cf::<Task, _>(&IndexifyObjectsColumns::Tasks, task_key)?
+        else {
+            return Ok(None);
+        };
+        let explanation = |reason| Ok(Some(PendingTaskExplanation::new(reason)));
+        if task.terminal_state() || !reader.is_task_unallocated(task_key)? {
+            return explanation(PendingReason::NotPending);
+        }
+        if self.indexify_state.in_maintenance_mode() {
+            return explanation(PendingReason::MaintenanceMode);
+        }
+        if reader.is_compute_graph_paused(&task.namespace, &task.compute_graph_name)? {
+            return explanation(PendingReason::GraphPaused);
+        }
+        let cg = reader
+            .get_compute_graph_version(
+                &task.namespace,
+                &task.compute_graph_name,
+                &task.graph_version,
+            )?
+            .ok_or(anyhow!("compute graph not found"))?;
+        let compute_fn = cg
+            .nodes
+            .get(&task.compute_fn_name)
+            .ok_or(anyhow!("compute fn not found"))?;
+        if compute_fn.wasm_module().is_some() ||
+            compute_fn.is_gate() ||
+            compute_fn.window().is_some() ||
+            compute_fn.sink().is_some()
+        {
+            return explanation(PendingReason::NotAllocatable);
+        }
+        let executors: HashMap<ExecutorId, ExecutorMetadata> = reader
+            .get_all_executors()?
+            .into_iter()
+            .map(|executor| (executor.id.clone(), executor))
+            .collect();
+        if let Some(key) = task.scoped_concurrency_key() {
+            if self
+                .in_flight_concurrency_keys(executors.keys())?
+                .contains(&key)
+            {
+                return explanation(PendingReason::ConcurrencyKey(
+                    task.concurrency_key.clone().unwrap_or_default(),
+                ));
+            }
+        }
+        let gpus_in_use = match compute_fn.gpu() {
+            Some(_) => Some(self.gpus_in_use()?),
+            None => None,
+        };
+        let now_ms = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64;
+        let blacklisted = reader.blacklisted_executors(
+            &task.namespace,
+            &task.compute_graph_name,
+            &task.compute_fn_name,
+            now_ms,
+        )?;
+        let filtered_executors = self.filter_executors(
+            &task.namespace,
+            compute_fn,
+            &cg.runtime_information,
+            &cg.code.sha256_hash,
+            gpus_in_use.as_ref(),
+            &blacklisted,
+        )?;
+        let mut explanation = PendingTaskExplanation {
+            details: filtered_executors.diagnostic_msgs,
+            ..PendingTaskExplanation::new(PendingReason::Allocatable)
+        };
+        if filtered_executors.executors.is_empty() {
+            explanation.reason = if !filtered_executors.gpu_busy.is_empty() {
+                explanation.candidates = filtered_executors.gpu_busy;
+                PendingReason::GpusBusy
+            } else if executors.values().any(|executor| {
+                blacklisted.contains(&executor.id) && compute_fn.matches_executor(executor)
+            }) {
+                PendingReason::Blacklisted
+            } else {
+                PendingReason::NoMatchingExecutor
+            };
+            return Ok(Some(explanation));
+        }
+
+        let pending_tasks = reader.unallocated_tasks()?;
+        let reservations = Reservations::new(
+            &reader.get_all_namespaces()?,
+            executors.values(),
+            pending_tasks.iter().map(|task| task.namespace.as_str()),
+        );
+        let shortfall = reservations.shortfall(&task.namespace);
+        if shortfall > 0 {
+            explanation.details.push(format!(
+                "namespace {} is missing {} executors of its reserved capacity",
+                task.namespace, shortfall
+            ));
+        }
+        let allowed: Vec<ExecutorId> = filtered_executors
+            .executors
+            .iter()
+            .filter(|id| reservations.allows(&task.namespace, id))
+            .cloned()
+            .collect();
+        if allowed.is_empty() {
+            explanation.reason = PendingReason::ReservedForOtherNamespaces;
+            explanation.candidates = filtered_executors.executors;
+            return Ok(Some(explanation));
+        }
+        let load = reader.allocated_task_counts()?;
+        let lanes = self.lanes.lock().unwrap().clone();
+        let room = |lanes: Option<&LaneConfig>| -> Vec<ExecutorId> {
+            allowed
+                .iter()
+                .filter(|id| {
+                    executors.get(*id).is_some_and(|executor| {
+                        let allocated = load.get(*id).copied().unwrap_or(0);
+                        Self::has_room(lanes, &task, executor, allocated)
+                    })
+                })
+                .cloned()
+                .collect()
+        };
+        let with_room = room(lanes.as_ref());
+        if with_room.is_empty() {
+            // Executors with room only in the slots reserved for the latency
+            // lane don't run batch lane tasks
+            explanation.reason = if lanes.is_some() && !room(None).is_empty() {
+                PendingReason::ReservedForLatencyLane
+            } else {
+                PendingReason::CapacityExhausted
+            };
+            explanation.candidates = allowed;
+            return Ok(Some(explanation));
+        }
+        explanation.candidates = with_room;
+        if let Some((key, rate_limit)) = self.rate_limit_of(&task, compute_fn) {
+            if self
+                .rate_limiters
+                .lock()
+                .unwrap()
+                .is_exhausted(&key, &rate_limit, Instant::now())
+            {
+                explanation.reason = PendingReason::RateLimited;
+            }
+        }
+        Ok(Some(explanation))
+    }
+
+    /// Plans the pre-emption of lower priority tasks on one of the executors
+    /// whose GPUs are busy, if pre-emption is enabled and its budget allows.
+    #[allow(clippy::too_many_arguments)]
+    fn preempt(
+        &self,
+        task: &Task,
+        gpu: &GpuRequirement,
+        gpu_busy: &[ExecutorId],
+        executors: &HashMap<ExecutorId, ExecutorMetadata>,
+        gpus_in_use: Option<&HashMap<ExecutorId, HashSet<String>>>,
+        allocated: &mut HashMap<ExecutorId, Vec<Task>>,
+        diagnostic_msgs: &mut Vec<String>,
+    ) -> Result<Option<Preemption>> {
+        let mut preemption = self.preemption.lock().unwrap();
+        let (Some((config, budget)), Some(gpus_in_use)) = (preemption.as_mut(), gpus_in_use) else {
+            return Ok(None);
+        };
+        if gpu_busy.is_empty() {
+            return Ok(None);
+        }
+        for executor_id in gpu_busy {
+            if !allocated.contains_key(executor_id) {
+                let tasks = self
+                    .indexify_state
+                    .reader()
+                    .get_tasks_by_executor(executor_id, usize::MAX)?;
+                allocated.insert(executor_id.clone(), tasks);
+            }
+        }
+        let candidates: Vec<&ExecutorMetadata> =
+            gpu_busy.iter().filter_map(|id| executors.get(id)).collect();
+        let Some(plan) = plan_preemption(
+            task,
+            gpu,
+            &candidates,
+            allocated,
+            gpus_in_use,
+            config.min_priority_gap,
+        ) else {
+            return Ok(None);
+        };
+        if !budget.try_acquire_n(plan.victims.len() as u32, Instant::now()) {
+            diagnostic_msgs.push(format!(
+                "task {} of fn {} can't pre-empt tasks, the pre-emption budget is spent",
+                task.id, task.compute_fn_name
+            ));
+            return Ok(None);
+        }
+        Ok(Some(plan))
+    }
+
+    /// Scoped concurrency keys of the tasks allocated to the executors.
+    fn in_flight_concurrency_keys<'a>(
+        &self,
+        executors: impl Iterator<Item = &'a ExecutorId>,
+    ) -> Result<HashSet<String>> {
+        let reader = self.indexify_state.reader();
+        let mut keys = HashSet::new();
+        for executor_id in executors {
+            keys.extend(
+                reader
+                    .get_tasks_by_executor(executor_id, usize::MAX)?
+                    .iter()
+                    .filter_map(|task| task.scoped_concurrency_key()),
+            );
+        }
+        Ok(keys)
+    }
+
+    /// GPUs reserved by the tasks allocated to each executor.
+    fn gpus_in_use(&self) -> Result<HashMap<ExecutorId, HashSet<String>>> {
+        let reader = self.indexify_state.reader();
+        let mut gpus_in_use = HashMap::new();
+        for executor in reader.get_all_executors()? {
+            if executor.gpus.is_empty() {
+                continue;
+            }
+            let gpu_ids = reader
+                .get_tasks_by_executor(&executor.id, usize::MAX)?
+                .into_iter()
+                .flat_map(|task| task.gpu_ids)
+                .collect();
+            gpus_in_use.insert(executor.id, gpu_ids);
+        }
+        Ok(gpus_in_use)
+    }
+
+    /// Key of the rate limiter of the function of the task and its limit,
+    /// None when the function isn't rate limited.
+    fn rate_limit_of(&self, task: &Task, node: &Node) -> Option<(String, RateLimit)> {
+        let rate_limit = match (node, node.rate_limit()) {
+            (_, Some(rate_limit)) => rate_limit.clone(),
+            (Node::Compute(_), None) => self.default_rate_limit.lock().unwrap().clone()?,
+            _ => return None,
+        };
+        let key = RateLimiters::key(
+            &task.namespace,
+            &task.compute_graph_name,
+            &task.compute_fn_name,
+        );
+        Some((key, rate_limit))
+    }
+
+    fn acquire_rate_limit(&self, task: &Task, node: &Node) -> bool {
+        let Some((key, rate_limit)) = self.rate_limit_of(task, node) else {
+            return true;
+        };
+        self.rate_limiters
+            .lock()
+            .unwrap()
+            .try_acquire(&key, &rate_limit, Instant::now())
+    }
+
     fn filter_executors(
         &self,
+        namespace: &str,
         node: &Node,
         graph_runtime: &RuntimeInformation,
+        code_sha256: &str,
+        gpus_in_use: Option<&HashMap<ExecutorId, HashSet<String>>>,
+        blacklisted: &HashSet<ExecutorId>,
     ) -> Result<FilteredExecutors> {
         let executors = self.indexify_state.reader().get_all_executors()?;
         let mut filtered_executors = Vec::new();
+        let mut code_cached = Vec::new();
+        let mut gpu_ids = HashMap::new();
+        let mut gpu_busy = Vec::new();
+        let no_gpus_in_use = HashSet::new();
 
         let mut diagnostic_msgs = vec![];
 
+        let executor_pool = match node.executor_pool() {
+            Some(pool_name) => {
+                match self
+                    .indexify_state
+                    .reader()
+                    .get_executor_pool(namespace, pool_name)?
+                {
+                    Some(pool) => Some(pool),
+                    None => {
+                        return Ok(FilteredExecutors {
+                            executors: vec![],
+                            code_cached: vec![],
+                            gpu_ids: HashMap::new(),
+                            gpu_busy: vec![],
+                            diagnostic_msgs: vec![format!(
+                                "executor pool {} of function {} does not exist",
+                                pool_name,
+                                node.name()
+                            )],
+                        });
+                    }
+                }
+            }
+            None => None,
+        };
+
         for executor in &executors {
+            if blacklisted.contains(&executor.id) {
+                diagnostic_msgs.push(format!(
+                    "executor {} is blacklisted for function {}",
+                    executor.id,
+                    node.name()
+                ));
+                continue;
+            }
+
             if let Some(minor_version) = executor.labels.get("python_minor_version") {
                 if let Ok(executor_python_minor_version) =
                     serde_json::from_value::<u8>(minor_version.clone())
@@ -107,6 +876,16 @@ impl TaskScheduler {
                 }
             }
 
+            if let Some(pool) = &executor_pool {
+                if !pool.matches_executor(executor) {
+                    diagnostic_msgs.push(format!(
+                        "executor {} is not a member of executor pool {}",
+                        executor.id, pool.name
+                    ));
+                    continue;
+                }
+            }
+
             if executor.image_name != node.image_name() {
                 diagnostic_msgs.push(format!(
                     "executor {}, image name: {} does not match function image name {}",
@@ -117,8 +896,46 @@ impl TaskScheduler {
                 continue;
             }
 
+            if let Some(runtime) = node.runtime() {
+                if !executor.supports_runtime(runtime) {
+                    diagnostic_msgs.push(format!(
+                        "executor {} does not advertise runtime {} of function {}",
+                        executor.id,
+                        runtime.image,
+                        node.name()
+                    ));
+                    continue;
+                }
+            }
+
+            if let Some(gpu) = node.gpu() {
+                let in_use = gpus_in_use
+                    .and_then(|gpus_in_use| gpus_in_use.get(&executor.id))
+                    .unwrap_or(&no_gpus_in_use);
+                match executor.free_gpus(gpu, in_use) {
+                    Some(free_gpus) => {
+                        gpu_ids.insert(executor.id.clone(), free_gpus);
+                    }
+                    None => {
+                        if node.matches_executor(executor) {
+                            gpu_busy.push(executor.id.clone());
+                        }
+                        diagnostic_msgs.push(format!(
+                            "executor {} does not have {} free gpus of model {}",
+                            executor.id,
+                            gpu.count,
+                            gpu.model.as_deref().unwrap_or("any")
+                        ));
+                        continue;
+                    }
+                }
+            }
+
             if node.matches_executor(executor) {
                 filtered_executors.push(executor.id.clone());
+                if executor.cached_artifacts.contains(code_sha256) {
+                    code_cached.push(executor.id.clone());
+                }
             }
         }
         if !filtered_executors.is_empty() {
@@ -126,6 +943,9 @@ impl TaskScheduler {
         }
         Ok(FilteredExecutors {
             executors: filtered_executors,
+            code_cached,
+            gpu_ids,
+            gpu_busy,
             diagnostic_msgs,
         })
     }
@@ -0,0 +1,194 @@
+use std::collections::{HashMap, HashSet};
+
+use data_model::{ExecutorId, ExecutorMetadata, GpuRequirement, RateLimit, Task};
+use serde::{Deserialize, Serialize};
+
+/// Lets tasks of high priority invocations which need GPUs revoke the
+/// allocations of running lower priority tasks when no executor has enough
+/// free GPUs.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PreemptionConfig {
+    /// At most budget.max_tasks tasks are pre-empted per budget.window_secs
+    pub budget: RateLimit,
+    /// Tasks are only pre-empted by tasks whose priority is higher by at
+    /// least this much
+    #[serde(default = "default_min_priority_gap")]
+    pub min_priority_gap: i32,
+}
+
+fn default_min_priority_gap() -> i32 {
+    1
+}
+
+/// Allocation of a task made possible by pre-empting tasks of an executor.
+#[derive(Debug)]
+pub struct Preemption {
+    pub executor: ExecutorId,
+    // GPUs reserved for the pre-empting task
+    pub gpu_ids: Vec<String>,
+    pub victims: Vec<Task>,
+}
+
+/// Picks the executor on which pre-empting the fewest tasks frees enough GPUs
+/// for the task, the lowest priority tasks of an executor are pre-empted
+/// first. `allocated` holds the tasks allocated to the executors.
+pub fn plan_preemption(
+    task: &Task,
+    gpu: &GpuRequirement,
+    executors: &[&ExecutorMetadata],
+    allocated: &HashMap<ExecutorId, Vec<Task>>,
+    gpus_in_use: &HashMap<ExecutorId, HashSet<String>>,
+    min_priority_gap: i32,
+) -> Option<Preemption> {
+    let mut best: Option<Preemption> = None;
+    for executor in executors {
+        let mut in_use = gpus_in_use.get(&executor.id).cloned().unwrap_or_default();
+        let mut candidates: Vec<&Task> = allocated
+            .get(&executor.id)
+            .into_iter()
+            .flatten()
+            .filter(|victim| {
+                !victim.gpu_ids.is_empty() &&
+                    victim.priority.saturating_add(min_priority_gap) <= task.priority
+            })
+            .collect();
+        candidates.sort_by_key(|victim| victim.priority);
+        let mut candidates = candidates.into_iter();
+        let mut victims = vec![];
+        let gpu_ids = loop {
+            if let Some(gpu_ids) = executor.free_gpus(gpu, &in_use) {
+                break Some(gpu_ids);
+            }
+            let Some(victim) = candidates.next() else {
+                break None;
+            };
+            for gpu_id in &victim.gpu_ids {
+                in_use.remove(gpu_id);
+            }
+            victims.push(victim.clone());
+        };
+        let Some(gpu_ids) = gpu_ids else {
+            continue;
+        };
+        if best
+            .as_ref()
+            .map_or(true, |best| victims.len() < best.victims.len())
+        {
+            best = Some(Preemption {
+                executor: executor.id.clone(),
+                gpu_ids,
+                victims,
+            });
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use data_model::{
+        test_objects::tests::{create_mock_task, mock_graph_a},
+        GpuDevice,
+        TaskId,
+    };
+
+    use super::*;
+
+    fn executor(id: &str, num_gpus: usize) -> ExecutorMetadata {
+        ExecutorMetadata {
+            id: ExecutorId::new(id.to_string()),
+            image_name: "image".to_string(),
+            addr: "".to_string(),
+            labels: HashMap::new(),
+            cached_artifacts: HashSet::new(),
+            gpus: (0..num_gpus)
+                .map(|i| GpuDevice {
+                    id: format!("gpu-{}", i),
+                    model: "A100".to_string(),
+                    free_memory_mb: 0,
+                })
+                .collect(),
+            protocol_version: 0,
+            features: Default::default(),
+            runtimes: Default::default(),
+            queue_depth: 0,
+            max_outstanding_tasks: None,
+            version: None,
+            last_heartbeat_at: None,
+        }
+    }
+
+    fn task(id: &str, priority: i32, gpu_ids: &[&str]) -> Task {
+        let mut task = create_mock_task(&mock_graph_a(), "fn_a", "inv", "inv");
+        task.id = TaskId::new(id.to_string());
+        task.priority = priority;
+        task.gpu_ids = gpu_ids.iter().map(|id| id.to_string()).collect();
+        task
+    }
+
+    #[test]
+    fn test_plan_preemption() {
+        let gpu = GpuRequirement {
+            count: 2,
+            model: None,
+        };
+        let executor_1 = executor("executor_1", 2);
+        let executor_2 = executor("executor_2", 2);
+        let allocated = HashMap::from([
+            (
+                executor_1.id.clone(),
+                vec![task("t1", 0, &["gpu-0"]), task("t2", 5, &["gpu-1"])],
+            ),
+            (
+                executor_2.id.clone(),
+                vec![task("t3", 1, &["gpu-0", "gpu-1"])],
+            ),
+        ]);
+        let gpus_in_use = HashMap::from([
+            (
+                executor_1.id.clone(),
+                HashSet::from(["gpu-0".to_string(), "gpu-1".to_string()]),
+            ),
+            (
+                executor_2.id.clone(),
+                HashSet::from(["gpu-0".to_string(), "gpu-1".to_string()]),
+            ),
+        ]);
+        let executors = vec![&executor_1, &executor_2];
+
+        // A single task of executor_2 frees both GPUs
+        let preemption = plan_preemption(
+            &task("new", 10, &[]),
+            &gpu,
+            &executors,
+            &allocated,
+            &gpus_in_use,
+            1,
+        )
+        .unwrap();
+        assert_eq!(preemption.executor, executor_2.id);
+        assert_eq!(preemption.victims.len(), 1);
+        assert_eq!(preemption.victims[0].id, TaskId::new("t3".to_string()));
+        assert_eq!(preemption.gpu_ids.len(), 2);
+
+        // Only tasks of lower priority are pre-empted
+        assert!(plan_preemption(
+            &task("new", 1, &[]),
+            &gpu,
+            &executors,
+            &allocated,
+            &gpus_in_use,
+            1
+        )
+        .is_none());
+        assert!(plan_preemption(
+            &task("new", 3, &[]),
+            &gpu,
+            &executors,
+            &allocated,
+            &gpus_in_use,
+            3
+        )
+        .is_none());
+    }
+}
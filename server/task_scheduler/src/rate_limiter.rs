@@ -0,0 +1,131 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use data_model::RateLimit;
+
+// Token bucket which refills continuously at max_tasks / window_secs and
+// holds at most max_tasks tokens.
+#[derive(Debug)]
+pub struct TokenBucket {
+    capacity: f64,
+    tokens: f64,
+    refill_per_sec: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(rate_limit: &RateLimit, now: Instant) -> Self {
+        let capacity = rate_limit.max_tasks as f64;
+        Self {
+            capacity,
+            tokens: capacity,
+            refill_per_sec: capacity / rate_limit.window_secs.max(1) as f64,
+            last_refill: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.tokens =
+            (self.tokens + elapsed.as_secs_f64() * self.refill_per_sec).min(self.capacity);
+        self.last_refill = now;
+    }
+
+    pub fn try_acquire(&mut self, now: Instant) -> bool {
+        self.try_acquire_n(1, now)
+    }
+
+    /// Takes n tokens at once, or none of them if fewer are available.
+    pub fn try_acquire_n(&mut self, n: u32, now: Instant) -> bool {
+        self.refill(now);
+        if self.tokens >= n as f64 {
+            self.tokens -= n as f64;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn time_until_available(&self, now: Instant) -> Duration {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        let tokens = self.tokens + elapsed.as_secs_f64() * self.refill_per_sec;
+        if tokens >= 1.0 || self.refill_per_sec <= 0.0 {
+            return Duration::ZERO;
+        }
+        Duration::from_secs_f64((1.0 - tokens) / self.refill_per_sec)
+    }
+}
+
+/// Token buckets keyed by namespace, compute graph and compute fn.
+#[derive(Debug, Default)]
+pub struct RateLimiters {
+    buckets: HashMap<String, (RateLimit, TokenBucket)>,
+}
+
+impl RateLimiters {
+    pub fn key(namespace: &str, compute_graph: &str, compute_fn: &str) -> String {
+        format!("{}|{}|{}", namespace, compute_graph, compute_fn)
+    }
+
+    /// Returns true if a task of the function can be allocated now.
+    pub fn try_acquire(&mut self, key: &str, rate_limit: &RateLimit, now: Instant) -> bool {
+        let entry = self
+            .buckets
+            .entry(key.to_string())
+            .or_insert_with(|| (rate_limit.clone(), TokenBucket::new(rate_limit, now)));
+        // The graph was updated with a new limit
+        if &entry.0 != rate_limit {
+            *entry = (rate_limit.clone(), TokenBucket::new(rate_limit, now));
+        }
+        entry.1.try_acquire(now)
+    }
+
+    /// Whether a task of the function would be held back now, without taking
+    /// a token.
+    pub fn is_exhausted(&self, key: &str, rate_limit: &RateLimit, now: Instant) -> bool {
+        match self.buckets.get(key) {
+            Some((limit, bucket)) if limit == rate_limit => {
+                !bucket.time_until_available(now).is_zero()
+            }
+            _ => false,
+        }
+    }
+
+    /// Shortest time until any of the exhausted buckets has a token again.
+    pub fn next_available(&self, now: Instant) -> Option<Duration> {
+        self.buckets
+            .values()
+            .map(|(_, bucket)| bucket.time_until_available(now))
+            .filter(|wait| !wait.is_zero())
+            .min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_bucket() {
+        let now = Instant::now();
+        let rate_limit = RateLimit {
+            max_tasks: 2,
+            window_secs: 10,
+        };
+        let mut limiters = RateLimiters::default();
+        let key = RateLimiters::key("ns", "graph", "fn");
+        assert!(limiters.try_acquire(&key, &rate_limit, now));
+        assert!(limiters.try_acquire(&key, &rate_limit, now));
+        assert!(!limiters.try_acquire(&key, &rate_limit, now));
+        assert!(limiters.is_exhausted(&key, &rate_limit, now));
+        let wait = limiters.next_available(now).unwrap();
+        assert_eq!(wait.as_secs_f64().round(), 5.0);
+
+        let later = now + Duration::from_secs(6);
+        assert!(!limiters.is_exhausted(&key, &rate_limit, later));
+        assert!(limiters.try_acquire(&key, &rate_limit, later));
+        assert!(!limiters.try_acquire(&key, &rate_limit, later));
+    }
+}
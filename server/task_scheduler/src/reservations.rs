@@ -0,0 +1,146 @@
+use std::collections::{HashMap, HashSet};
+
+use data_model::{ExecutorId, ExecutorMetadata, Namespace};
+
+/// Executors reserved for namespaces. Namespaces reserve executors in order
+/// of their name, each among the executors matching its selector in order of
+/// their id which aren't reserved by another namespace yet, so the same
+/// executors stay reserved while the cluster doesn't change.
+#[derive(Debug, Default)]
+pub struct Reservations {
+    owners: HashMap<ExecutorId, String>,
+    // Namespaces with pending tasks, the executors reserved for the other
+    // namespaces are idle and shared
+    busy: HashSet<String>,
+    // Executors missing to fulfill each reservation
+    shortfalls: HashMap<String, u32>,
+}
+
+impl Reservations {
+    pub fn new<'a>(
+        namespaces: &[Namespace],
+        executors: impl Iterator<Item = &'a ExecutorMetadata>,
+        pending_namespaces: impl Iterator<Item = &'a str>,
+    ) -> Self {
+        let mut executors: Vec<&ExecutorMetadata> = executors.collect();
+        executors.sort_by(|a, b| a.id.cmp(&b.id));
+        let mut namespaces: Vec<&Namespace> = namespaces
+            .iter()
+            .filter(|namespace| {
+                namespace.reserved_capacity.is_some() && namespace.deleted_at.is_none()
+            })
+            .collect();
+        namespaces.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut owners = HashMap::new();
+        let mut shortfalls = HashMap::new();
+        for namespace in namespaces {
+            let Some(reserved) = &namespace.reserved_capacity else {
+                continue;
+            };
+            let mut count = 0;
+            for executor in &executors {
+                if count == reserved.min_executors {
+                    break;
+                }
+                let available = !owners.contains_key(&executor.id);
+                if !available || !reserved.selector.matches(&executor.labels) {
+                    continue;
+                }
+                owners.insert(executor.id.clone(), namespace.name.clone());
+                count += 1;
+            }
+            if count < reserved.min_executors {
+                shortfalls.insert(namespace.name.clone(), reserved.min_executors - count);
+            }
+        }
+        Self {
+            owners,
+            busy: pending_namespaces
+                .map(|namespace| namespace.to_string())
+                .collect(),
+            shortfalls,
+        }
+    }
+
+    /// Namespace the executor is reserved for.
+    pub fn owner(&self, executor_id: &ExecutorId) -> Option<&str> {
+        self.owners.get(executor_id).map(|owner| owner.as_str())
+    }
+
+    /// Whether tasks of the namespace can be allocated to the executor, it
+    /// isn't reserved for a namespace with pending tasks other than this one.
+    pub fn allows(&self, namespace: &str, executor_id: &ExecutorId) -> bool {
+        match self.owner(executor_id) {
+            Some(owner) => owner == namespace || !self.busy.contains(owner),
+            None => true,
+        }
+    }
+
+    /// Number of executors missing to fulfill the reservation of the
+    /// namespace, 0 when fulfilled or when it doesn't reserve executors.
+    pub fn shortfall(&self, namespace: &str) -> u32 {
+        self.shortfalls.get(namespace).copied().unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use data_model::{
+        filter::{Condition, LabelsFilter},
+        ReservedCapacity,
+    };
+
+    use super::*;
+
+    fn executor(id: &str, tier: &str) -> ExecutorMetadata {
+        ExecutorMetadata {
+            id: ExecutorId::new(id.to_string()),
+            labels: HashMap::from([("tier".to_string(), serde_json::json!(tier))]),
+            ..Default::default()
+        }
+    }
+
+    fn namespace(name: &str, min_executors: u32) -> Namespace {
+        Namespace {
+            name: name.to_string(),
+            created_at: 0,
+            queue_limits: Default::default(),
+            scheduling_weight: 1,
+            reserved_capacity: Some(ReservedCapacity {
+                selector: LabelsFilter(vec![Condition::from_str("tier=dedicated").unwrap()]),
+                min_executors,
+            }),
+            replication: None,
+            deleted_at: None,
+        }
+    }
+
+    #[test]
+    fn test_reservations() {
+        let executors = [
+            executor("e3", "dedicated"),
+            executor("e1", "dedicated"),
+            executor("e2", "dedicated"),
+            executor("shared", "shared"),
+        ];
+        let namespaces = [namespace("b", 2), namespace("a", 2)];
+        let reservations =
+            Reservations::new(&namespaces, executors.iter(), ["a", "other"].into_iter());
+        let id = |id: &str| ExecutorId::new(id.to_string());
+        assert_eq!(reservations.owner(&id("e1")), Some("a"));
+        assert_eq!(reservations.owner(&id("e2")), Some("a"));
+        assert_eq!(reservations.owner(&id("e3")), Some("b"));
+        assert_eq!(reservations.owner(&id("shared")), None);
+        assert_eq!(reservations.shortfall("a"), 0);
+        assert_eq!(reservations.shortfall("b"), 1);
+
+        // a has pending tasks, its executors aren't shared
+        assert!(reservations.allows("a", &id("e1")));
+        assert!(!reservations.allows("other", &id("e1")));
+        assert!(!reservations.allows("b", &id("e1")));
+        // b is idle, its executor is shared
+        assert!(reservations.allows("other", &id("e3")));
+        assert!(reservations.allows("a", &id("shared")));
+    }
+}
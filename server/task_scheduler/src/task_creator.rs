@@ -1,8 +1,22 @@
 use std::sync::Arc;
 
 use anyhow::{anyhow, Result};
-use data_model::{ComputeGraph, InvokeComputeGraphEvent, Node, OutputPayload, Task, TaskOutcome};
-use state_store::IndexifyState;
+use data_model::{
+    ComputeGraph,
+    FanOutCursor,
+    GraphInvocationCtx,
+    GraphVersion,
+    InvokeComputeGraphEvent,
+    Node,
+    NodeOutput,
+    OutputPayload,
+    ParentInvocation,
+    ReduceTask,
+    Task,
+    TaskOutcome,
+    TaskOutputsStreamedEvent,
+};
+use state_store::{requests::SubGraphInvocationRequest, IndexifyState};
 use tracing::{error, info};
 
 use crate::TaskCreationResult;
@@ -11,9 +25,11 @@ pub async fn handle_invoke_compute_graph(
     indexify_state: Arc<IndexifyState>,
     event: InvokeComputeGraphEvent,
 ) -> Result<TaskCreationResult> {
-    let compute_graph = indexify_state
-        .reader()
-        .get_compute_graph(&event.namespace, &event.compute_graph)?;
+    let compute_graph = indexify_state.reader().get_invocation_compute_graph(
+        &event.namespace,
+        &event.compute_graph,
+        &event.invocation_id,
+    )?;
     if compute_graph.is_none() {
         error!(
             "compute graph not found: {:?} {:?}",
@@ -27,33 +43,57 @@ pub async fn handle_invoke_compute_graph(
             new_reduction_tasks: vec![],
             processed_reduction_tasks: vec![],
             invocation_finished: false,
+            fan_out_cursor: None,
         });
     }
     let compute_graph = compute_graph.unwrap();
-    // Crate a task for the compute graph
-    let task = compute_graph.start_fn.create_task(
+    let invocation_payload = indexify_state.reader().invocation_payload(
         &event.namespace,
         &event.compute_graph,
         &event.invocation_id,
-        &event.invocation_id,
-        None,
-        compute_graph.version,
     )?;
+    // The start node reads the invocation payload, or each of the parts an
+    // ingestion hook split it into
+    let input_keys = if invocation_payload.parts.is_empty() {
+        vec![event.invocation_id.clone()]
+    } else {
+        invocation_payload
+            .part_outputs(compute_graph.version)?
+            .iter()
+            .map(|output| output.input_key(&event.invocation_id))
+            .collect()
+    };
+    let mut tasks = vec![];
+    for input_key in &input_keys {
+        tasks.push(compute_graph.start_fn.create_task(
+            &event.namespace,
+            &event.compute_graph,
+            &event.invocation_id,
+            input_key,
+            None,
+            compute_graph.version,
+        )?);
+    }
     Ok(TaskCreationResult {
         namespace: event.namespace.clone(),
         compute_graph: event.compute_graph.clone(),
         invocation_id: event.invocation_id.clone(),
-        tasks: vec![task],
+        tasks,
         new_reduction_tasks: vec![],
         processed_reduction_tasks: vec![],
         invocation_finished: false,
+        fan_out_cursor: None,
     })
 }
 
+/// Creates the tasks following a finished task. At most `fan_out_batch_size`
+/// tasks of the downstream nodes are created, the rest are created by
+/// resuming the returned fan out cursor.
 pub async fn handle_task_finished(
     indexify_state: Arc<IndexifyState>,
     task: Task,
     compute_graph: ComputeGraph,
+    fan_out_batch_size: usize,
 ) -> Result<TaskCreationResult> {
     let invocation_ctx = indexify_state.reader().invocation_ctx(
         &task.namespace,
@@ -62,6 +102,16 @@ pub async fn handle_task_finished(
     )?;
 
     if task.outcome == TaskOutcome::Failure {
+        // Routers handle their failures with their on_error policy
+        if let Some(Node::Router(router)) = compute_graph.nodes.get(&task.compute_fn_name) {
+            if let Some(targets) = router.targets_on_error() {
+                info!(
+                    "router {} failed, routing its input to {:?}",
+                    router.name, targets
+                );
+                return route_input(&task, &compute_graph, &invocation_ctx, targets);
+            }
+        }
         let mut invocation_finished = false;
         if invocation_ctx.outstanding_tasks == 0 {
             invocation_finished = true;
@@ -78,6 +128,7 @@ pub async fn handle_task_finished(
             invocation_id: task.invocation_id.clone(),
             tasks: vec![],
             invocation_finished,
+            fan_out_cursor: None,
             new_reduction_tasks: vec![],
             processed_reduction_tasks: vec![],
         });
@@ -91,35 +142,19 @@ pub async fn handle_task_finished(
     for output in &outputs {
         if let OutputPayload::Router(router_output) = &output.payload {
             for edge in &router_output.edges {
-                router_edges.push(edge);
+                router_edges.push(edge.clone());
             }
         }
     }
-    if !router_edges.is_empty() {
-        for edge in router_edges {
-            let compute_fn = compute_graph
-                .nodes
-                .get(edge)
-                .ok_or(anyhow!("compute node not found: {:?}", edge))?;
-            let new_task = compute_fn.create_task(
-                &task.namespace,
-                &task.compute_graph_name,
-                &task.invocation_id,
-                &task.input_node_output_key,
-                None,
-                invocation_ctx.graph_version,
-            )?;
-            new_tasks.push(new_task);
+    // Routers which select no target route their input to their default
+    // targets
+    if router_edges.is_empty() {
+        if let Some(Node::Router(router)) = compute_graph.nodes.get(&task.compute_fn_name) {
+            router_edges.extend(router.default_targets.iter().cloned());
         }
-        return Ok(TaskCreationResult {
-            namespace: task.namespace.clone(),
-            compute_graph: task.compute_graph_name.clone(),
-            invocation_id: task.invocation_id.clone(),
-            tasks: new_tasks,
-            new_reduction_tasks: vec![],
-            processed_reduction_tasks: vec![],
-            invocation_finished: false,
-        });
+    }
+    if !router_edges.is_empty() {
+        return route_input(&task, &compute_graph, &invocation_ctx, &router_edges);
     }
 
     if let Some(compute_node) = compute_graph.nodes.get(&task.compute_fn_name) {
@@ -154,6 +189,7 @@ pub async fn handle_task_finished(
                         new_reduction_tasks: vec![],
                         processed_reduction_tasks: vec![reduction_task.key()],
                         invocation_finished: false,
+                        fan_out_cursor: None,
                     });
                 }
             }
@@ -180,11 +216,295 @@ pub async fn handle_task_finished(
             new_reduction_tasks: vec![],
             processed_reduction_tasks: vec![],
             invocation_finished,
+            fan_out_cursor: None,
+        });
+    }
+    // Downstream tasks of streamed outputs were created when they were ingested
+    let outputs: Vec<NodeOutput> = outputs.into_iter().filter(|o| !o.streamed).collect();
+    // The batch of a closed window is the single input of the downstream tasks
+    let is_window = compute_graph
+        .nodes
+        .get(&task.compute_fn_name)
+        .is_some_and(|node| node.window().is_some());
+    if is_window {
+        if !outputs.is_empty() {
+            let input_batch: Vec<String> = outputs
+                .iter()
+                .map(|output| output.input_key(&task.invocation_id))
+                .collect();
+            for edge in edges.unwrap() {
+                let compute_node = compute_graph
+                    .nodes
+                    .get(edge)
+                    .ok_or(anyhow!("compute node not found: {:?}", edge))?;
+                let mut new_task = compute_node.create_task(
+                    &task.namespace,
+                    &task.compute_graph_name,
+                    &task.invocation_id,
+                    &input_batch[0],
+                    None,
+                    invocation_ctx.graph_version,
+                )?;
+                new_task.input_batch = input_batch.clone();
+                new_tasks.push(new_task);
+            }
+        }
+        return Ok(TaskCreationResult {
+            namespace: task.namespace.clone(),
+            compute_graph: task.compute_graph_name.clone(),
+            invocation_id: task.invocation_id.clone(),
+            tasks: new_tasks,
+            new_reduction_tasks: vec![],
+            processed_reduction_tasks: vec![],
+            invocation_finished: false,
+            fan_out_cursor: None,
         });
     }
-    let edges = edges.unwrap();
+    // Reducers only get a single task, their other inputs are queued
+    let (reducer_edges, fan_out_edges): (Vec<String>, Vec<String>) =
+        edges.unwrap().iter().cloned().partition(|edge| {
+            compute_graph
+                .nodes
+                .get(edge)
+                .map(|node| node.reducer())
+                .unwrap_or(false)
+        });
+    create_downstream_tasks(
+        indexify_state,
+        &task,
+        &compute_graph,
+        &reducer_edges,
+        &outputs,
+        invocation_ctx.graph_version,
+        &mut new_tasks,
+        &mut new_reduction_tasks,
+    )?;
+    let cursor = FanOutCursor {
+        namespace: task.namespace.clone(),
+        compute_graph: task.compute_graph_name.clone(),
+        invocation_id: task.invocation_id.clone(),
+        task_id: task.id.clone(),
+        total: fan_out_edges.len() * outputs.len(),
+        edges: fan_out_edges,
+        next: 0,
+    };
+    let cursor = next_fan_out_batch(
+        &compute_graph,
+        &outputs,
+        cursor,
+        fan_out_batch_size,
+        invocation_ctx.graph_version,
+        &mut new_tasks,
+    )?;
+    Ok(TaskCreationResult {
+        namespace: task.namespace.clone(),
+        compute_graph: task.compute_graph_name.clone(),
+        invocation_id: task.invocation_id.clone(),
+        tasks: new_tasks,
+        new_reduction_tasks,
+        processed_reduction_tasks: vec![],
+        invocation_finished: false,
+        // Only persisted if the fan out didn't fit in a single batch
+        fan_out_cursor: Some(cursor).filter(|cursor| !cursor.is_done()),
+    })
+}
+
+/// Creates the next batch of tasks of a fan out which was interrupted
+/// after its previous batch.
+pub async fn resume_fan_out(
+    indexify_state: Arc<IndexifyState>,
+    cursor: FanOutCursor,
+    fan_out_batch_size: usize,
+) -> Result<TaskCreationResult> {
+    let mut result = TaskCreationResult {
+        namespace: cursor.namespace.clone(),
+        compute_graph: cursor.compute_graph.clone(),
+        invocation_id: cursor.invocation_id.clone(),
+        tasks: vec![],
+        new_reduction_tasks: vec![],
+        processed_reduction_tasks: vec![],
+        invocation_finished: false,
+        fan_out_cursor: None,
+    };
+    let compute_graph = indexify_state.reader().get_invocation_compute_graph(
+        &cursor.namespace,
+        &cursor.compute_graph,
+        &cursor.invocation_id,
+    )?;
+    let Some(compute_graph) = compute_graph else {
+        // The graph was deleted, abandon the fan out
+        error!(
+            "compute graph not found, abandoning fan out: {:?} {:?}",
+            cursor.namespace, cursor.compute_graph
+        );
+        result.fan_out_cursor = Some(FanOutCursor {
+            next: cursor.total,
+            ..cursor
+        });
+        return Ok(result);
+    };
+    let invocation_ctx = indexify_state.reader().invocation_ctx(
+        &cursor.namespace,
+        &cursor.compute_graph,
+        &cursor.invocation_id,
+    )?;
+    let outputs: Vec<NodeOutput> = indexify_state
+        .reader()
+        .get_task_outputs(&cursor.namespace, &cursor.task_id.to_string())?
+        .into_iter()
+        .filter(|o| !o.streamed)
+        .collect();
+    result.fan_out_cursor = Some(next_fan_out_batch(
+        &compute_graph,
+        &outputs,
+        cursor,
+        fan_out_batch_size,
+        invocation_ctx.graph_version,
+        &mut result.tasks,
+    )?);
+    Ok(result)
+}
+
+/// Creates up to `batch_size` tasks of the fan out, starting at the cursor.
+/// Returns the advanced cursor.
+fn next_fan_out_batch(
+    compute_graph: &ComputeGraph,
+    outputs: &[NodeOutput],
+    mut cursor: FanOutCursor,
+    batch_size: usize,
+    graph_version: GraphVersion,
+    new_tasks: &mut Vec<Task>,
+) -> Result<FanOutCursor> {
+    let end = cursor.total.min(cursor.next + batch_size.max(1));
+    for idx in cursor.next..end {
+        let edge = &cursor.edges[idx / outputs.len()];
+        let output = &outputs[idx % outputs.len()];
+        let compute_node = compute_graph
+            .nodes
+            .get(edge)
+            .ok_or(anyhow!("compute node not found: {:?}", edge))?;
+        let new_task = compute_node.create_task(
+            &cursor.namespace,
+            &cursor.compute_graph,
+            &cursor.invocation_id,
+            &output.input_key(&cursor.invocation_id),
+            None,
+            graph_version,
+        )?;
+        new_tasks.push(new_task);
+    }
+    cursor.next = end;
+    Ok(cursor)
+}
+
+/// Creates the tasks of the downstream nodes for outputs a running task
+/// streamed, so they can start before the task finishes.
+pub async fn handle_task_outputs_streamed(
+    indexify_state: Arc<IndexifyState>,
+    event: TaskOutputsStreamedEvent,
+    compute_graph: ComputeGraph,
+) -> Result<TaskCreationResult> {
+    let mut result = TaskCreationResult {
+        namespace: event.namespace.clone(),
+        compute_graph: event.compute_graph.clone(),
+        invocation_id: event.invocation_id.clone(),
+        tasks: vec![],
+        new_reduction_tasks: vec![],
+        processed_reduction_tasks: vec![],
+        invocation_finished: false,
+        fan_out_cursor: None,
+    };
+    let task = indexify_state.reader().get_task(
+        &event.namespace,
+        &event.compute_graph,
+        &event.invocation_id,
+        &event.compute_fn,
+        &event.task_id.to_string(),
+    )?;
+    let (Some(task), Some(edges)) = (task, compute_graph.edges.get(&event.compute_fn)) else {
+        return Ok(result);
+    };
+    let invocation_ctx = indexify_state.reader().invocation_ctx(
+        &event.namespace,
+        &event.compute_graph,
+        &event.invocation_id,
+    )?;
+    let mut outputs = vec![];
+    for output_id in &event.output_ids {
+        let output = indexify_state.reader().fn_output_payload(
+            &event.namespace,
+            &event.compute_graph,
+            &event.invocation_id,
+            &event.compute_fn,
+            output_id,
+        )?;
+        match output {
+            Some(output) => outputs.push(output),
+            None => error!("streamed output not found: {}", output_id),
+        }
+    }
+    create_downstream_tasks(
+        indexify_state,
+        &task,
+        &compute_graph,
+        edges,
+        &outputs,
+        invocation_ctx.graph_version,
+        &mut result.tasks,
+        &mut result.new_reduction_tasks,
+    )?;
+    Ok(result)
+}
+
+/// Creates the tasks of the targets a router task routed its input to. The
+/// invocation finishes if there are none and nothing else is outstanding.
+fn route_input(
+    task: &Task,
+    compute_graph: &ComputeGraph,
+    invocation_ctx: &GraphInvocationCtx,
+    targets: &[String],
+) -> Result<TaskCreationResult> {
+    let mut new_tasks = vec![];
+    for target in targets {
+        let compute_fn = compute_graph
+            .nodes
+            .get(target)
+            .ok_or(anyhow!("compute node not found: {:?}", target))?;
+        let new_task = compute_fn.create_task(
+            &task.namespace,
+            &task.compute_graph_name,
+            &task.invocation_id,
+            &task.input_node_output_key,
+            None,
+            invocation_ctx.graph_version,
+        )?;
+        new_tasks.push(new_task);
+    }
+    Ok(TaskCreationResult {
+        namespace: task.namespace.clone(),
+        compute_graph: task.compute_graph_name.clone(),
+        invocation_id: task.invocation_id.clone(),
+        invocation_finished: new_tasks.is_empty() && invocation_ctx.outstanding_tasks == 0,
+        tasks: new_tasks,
+        new_reduction_tasks: vec![],
+        processed_reduction_tasks: vec![],
+        fan_out_cursor: None,
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+fn create_downstream_tasks(
+    indexify_state: Arc<IndexifyState>,
+    task: &Task,
+    compute_graph: &ComputeGraph,
+    edges: &[String],
+    outputs: &[NodeOutput],
+    graph_version: GraphVersion,
+    new_tasks: &mut Vec<Task>,
+    new_reduction_tasks: &mut Vec<ReduceTask>,
+) -> Result<()> {
     for edge in edges {
-        for output in &outputs {
+        for output in outputs {
             let compute_node = compute_graph
                 .nodes
                 .get(edge)
@@ -208,7 +528,7 @@ pub async fn handle_task_finished(
                     &task.compute_graph_name,
                     &task.invocation_id,
                     &task.id.to_string(),
-                    &output.key(&task.invocation_id),
+                    &output.input_key(&task.invocation_id),
                 );
                 new_reduction_tasks.push(new_task);
                 continue;
@@ -217,20 +537,78 @@ pub async fn handle_task_finished(
                 &task.namespace,
                 &task.compute_graph_name,
                 &task.invocation_id,
-                &output.key(&task.invocation_id),
+                &output.input_key(&task.invocation_id),
                 None,
-                invocation_ctx.graph_version,
+                graph_version,
             )?;
             new_tasks.push(new_task);
         }
     }
-    Ok(TaskCreationResult {
-        namespace: task.namespace.clone(),
-        compute_graph: task.compute_graph_name.clone(),
-        invocation_id: task.invocation_id.clone(),
-        tasks: new_tasks,
-        new_reduction_tasks,
-        processed_reduction_tasks: vec![],
-        invocation_finished: false,
-    })
+    Ok(())
+}
+
+/// Creates the child invocations for the tasks of sub graph nodes. The input
+/// of the task becomes the input of the child invocation.
+pub fn sub_graph_invocations(
+    indexify_state: Arc<IndexifyState>,
+    tasks: &[Task],
+) -> Result<Vec<SubGraphInvocationRequest>> {
+    let mut invocations = vec![];
+    for task in tasks {
+        let compute_graph = indexify_state
+            .reader()
+            .get_compute_graph_version(
+                &task.namespace,
+                &task.compute_graph_name,
+                &task.graph_version,
+            )?
+            .ok_or(anyhow!("compute graph not found"))?;
+        let node = compute_graph
+            .nodes
+            .get(&task.compute_fn_name)
+            .ok_or(anyhow!(
+                "compute node not found: {:?}",
+                task.compute_fn_name
+            ))?;
+        let Some(sub_graph) = node.sub_graph() else {
+            continue;
+        };
+        // Tasks of the start node read the invocation payload
+        let payload = if task.input_node_output_key == task.invocation_id {
+            indexify_state
+                .reader()
+                .invocation_payload(
+                    &task.namespace,
+                    &task.compute_graph_name,
+                    &task.invocation_id,
+                )?
+                .payload
+        } else {
+            match indexify_state
+                .reader()
+                .fn_output_payload_by_key(&task.input_node_output_key)?
+                .payload
+            {
+                OutputPayload::Fn(payload) => payload,
+                OutputPayload::Router(_) => {
+                    return Err(anyhow!(
+                        "sub graph task {} has a router output as input",
+                        task.id
+                    ))
+                }
+            }
+        };
+        invocations.push(SubGraphInvocationRequest {
+            parent: ParentInvocation {
+                namespace: task.namespace.clone(),
+                compute_graph: task.compute_graph_name.clone(),
+                invocation_id: task.invocation_id.clone(),
+                compute_fn: task.compute_fn_name.clone(),
+                task_id: task.id.clone(),
+            },
+            compute_graph: sub_graph.to_string(),
+            payload,
+        });
+    }
+    Ok(invocations)
 }